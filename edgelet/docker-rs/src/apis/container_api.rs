@@ -63,7 +63,7 @@ pub trait ContainerApi: Send + Sync {
         stdin: bool,
         stdout: bool,
         stderr: bool,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = hyper::upgrade::Upgraded, Error = Error<serde_json::Value>> + Send>;
     fn container_changes(
         &self,
         id: &str,
@@ -121,7 +121,7 @@ pub trait ContainerApi: Send + Sync {
         &self,
         id: &str,
         name: &str,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>> + Send>;
     fn container_resize(
         &self,
         id: &str,
@@ -142,7 +142,7 @@ pub trait ContainerApi: Send + Sync {
         &self,
         id: &str,
         stream: bool,
-    ) -> Box<Future<Item = serde_json::Value, Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = serde_json::Value, Error = Error<serde_json::Value>> + Send>;
     fn container_stop(
         &self,
         id: &str,
@@ -161,7 +161,7 @@ pub trait ContainerApi: Send + Sync {
         &self,
         id: &str,
         update: ::models::ContainerUpdateUpdate,
-    ) -> Box<Future<Item = ::models::InlineResponse2003, Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = ::models::InlineResponse2003, Error = Error<serde_json::Value>> + Send>;
     fn container_wait(
         &self,
         id: &str,
@@ -289,7 +289,7 @@ where
         stdin: bool,
         stdout: bool,
         stderr: bool,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = hyper::upgrade::Upgraded, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::POST;
@@ -314,6 +314,10 @@ where
         if let Some(ref user_agent) = configuration.user_agent {
             req.header(http::header::USER_AGENT, &**user_agent);
         }
+        // The attached stdio is streamed over the connection once it's been
+        // upgraded from HTTP.
+        req.header(http::header::CONNECTION, "Upgrade")
+            .header(http::header::UPGRADE, "tcp");
         let req = req
             .body(hyper::Body::empty())
             .expect("could not build hyper::Request");
@@ -326,16 +330,16 @@ where
                 .map_err(|e| Error::from(e))
                 .and_then(|resp| {
                     let (http::response::Parts { status, .. }, body) = resp.into_parts();
-                    body.concat2()
-                        .and_then(move |body| Ok((status, body)))
-                        .map_err(|e| Error::from(e))
-                }).and_then(|(status, body)| {
-                    if status.is_success() {
-                        Ok(body)
+                    if status == hyper::StatusCode::SWITCHING_PROTOCOLS {
+                        futures::future::Either::A(body.on_upgrade().map_err(|e| Error::from(e)))
                     } else {
-                        Err(Error::from((status, &*body)))
+                        futures::future::Either::B(
+                            body.concat2()
+                                .map_err(|e| Error::from(e))
+                                .and_then(move |body| Err(Error::from((status, &*body)))),
+                        )
                     }
-                }).and_then(|_| futures::future::ok(())),
+                }),
         )
     }
 
@@ -926,7 +930,7 @@ where
         &self,
         id: &str,
         name: &str,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::POST;
@@ -1124,7 +1128,7 @@ where
         &self,
         id: &str,
         stream: bool,
-    ) -> Box<Future<Item = serde_json::Value, Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = serde_json::Value, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
@@ -1323,7 +1327,7 @@ where
         &self,
         id: &str,
         update: ::models::ContainerUpdateUpdate,
-    ) -> Box<Future<Item = ::models::InlineResponse2003, Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = ::models::InlineResponse2003, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::POST;
@@ -41,32 +41,15 @@ pub trait ImageApi: Send + Sync {
     ) -> Box<Future<Item = ::models::InlineResponse2006, Error = Error<serde_json::Value>>>;
     fn image_build(
         &self,
-        input_stream: Vec<u8>,
+        input_stream: hyper::Body,
         dockerfile: &str,
         t: &str,
-        extrahosts: &str,
-        remote: &str,
         q: bool,
         nocache: bool,
-        cachefrom: &str,
-        pull: &str,
         rm: bool,
         forcerm: bool,
-        memory: i32,
-        memswap: i32,
-        cpushares: i32,
-        cpusetcpus: &str,
-        cpuperiod: i32,
-        cpuquota: i32,
-        buildargs: i32,
-        shmsize: i32,
-        squash: bool,
-        labels: &str,
-        networkmode: &str,
-        content_type: &str,
-        x_registry_config: &str,
         platform: &str,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send>;
     fn image_commit(
         &self,
         container_config: ::models::ContainerConfig,
@@ -201,32 +184,15 @@ where
 
     fn image_build(
         &self,
-        input_stream: Vec<u8>,
+        input_stream: hyper::Body,
         dockerfile: &str,
         t: &str,
-        extrahosts: &str,
-        remote: &str,
         q: bool,
         nocache: bool,
-        cachefrom: &str,
-        pull: &str,
         rm: bool,
         forcerm: bool,
-        memory: i32,
-        memswap: i32,
-        cpushares: i32,
-        cpusetcpus: &str,
-        cpuperiod: i32,
-        cpuquota: i32,
-        buildargs: i32,
-        shmsize: i32,
-        squash: bool,
-        labels: &str,
-        networkmode: &str,
-        content_type: &str,
-        x_registry_config: &str,
         platform: &str,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::POST;
@@ -234,25 +200,10 @@ where
         let query = ::url::form_urlencoded::Serializer::new(String::new())
             .append_pair("dockerfile", &dockerfile.to_string())
             .append_pair("t", &t.to_string())
-            .append_pair("extrahosts", &extrahosts.to_string())
-            .append_pair("remote", &remote.to_string())
             .append_pair("q", &q.to_string())
             .append_pair("nocache", &nocache.to_string())
-            .append_pair("cachefrom", &cachefrom.to_string())
-            .append_pair("pull", &pull.to_string())
             .append_pair("rm", &rm.to_string())
             .append_pair("forcerm", &forcerm.to_string())
-            .append_pair("memory", &memory.to_string())
-            .append_pair("memswap", &memswap.to_string())
-            .append_pair("cpushares", &cpushares.to_string())
-            .append_pair("cpusetcpus", &cpusetcpus.to_string())
-            .append_pair("cpuperiod", &cpuperiod.to_string())
-            .append_pair("cpuquota", &cpuquota.to_string())
-            .append_pair("buildargs", &buildargs.to_string())
-            .append_pair("shmsize", &shmsize.to_string())
-            .append_pair("squash", &squash.to_string())
-            .append_pair("labels", &labels.to_string())
-            .append_pair("networkmode", &networkmode.to_string())
             .append_pair("platform", &platform.to_string())
             .finish();
         let uri_str = format!("/build?{}", query);
@@ -262,23 +213,15 @@ where
         // if let Err(e) = uri {
         //     return Box::new(futures::future::err(e));
         // }
-        let serialized = serde_json::to_string(&input_stream).unwrap();
-        let serialized_len = serialized.len();
-
         let mut req = hyper::Request::builder();
         req.method(method).uri(uri.unwrap());
         if let Some(ref user_agent) = configuration.user_agent {
             req.header(http::header::USER_AGENT, &**user_agent);
         }
-        let mut req = req
-            .header(http::header::CONTENT_TYPE, content_type)
-            .header("X-Registry-Config", x_registry_config)
-            .body(hyper::Body::from(serialized))
+        let req = req
+            .header(http::header::CONTENT_TYPE, "application/x-tar")
+            .body(input_stream)
             .expect("could not build hyper::Request");
-        req.headers_mut()
-            .typed_insert(&typed_headers::ContentType(mime::APPLICATION_JSON));
-        req.headers_mut()
-            .typed_insert(&typed_headers::ContentLength(serialized_len as u64));
 
         // send request
         Box::new(
@@ -288,16 +231,18 @@ where
                 .map_err(|e| Error::from(e))
                 .and_then(|resp| {
                     let (http::response::Parts { status, .. }, body) = resp.into_parts();
-                    body.concat2()
-                        .and_then(move |body| Ok((status, body)))
-                        .map_err(|e| Error::from(e))
-                }).and_then(|(status, body)| {
                     if status.is_success() {
-                        Ok(body)
+                        futures::future::Either::A(futures::future::ok(body))
                     } else {
-                        Err(Error::from((status, &*body)))
+                        futures::future::Either::B(
+                            body.concat2()
+                                .map_err(|e| Error::from(e))
+                                .and_then(move |body| {
+                                    Err::<hyper::Body, _>(Error::from((status, &*body)))
+                                }),
+                        )
                     }
-                }).and_then(|_| futures::future::ok(())),
+                }),
         )
     }
 
@@ -40,7 +40,7 @@ pub trait SystemApi: Send + Sync {
     ) -> Box<Future<Item = ::models::InlineResponse20010, Error = Error<serde_json::Value>>>;
     fn system_data_usage(
         &self,
-    ) -> Box<Future<Item = ::models::InlineResponse20013, Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = ::models::InlineResponse20013, Error = Error<serde_json::Value>> + Send>;
     fn system_events(
         &self,
         since: &str,
@@ -120,7 +120,8 @@ where
 
     fn system_data_usage(
         &self,
-    ) -> Box<Future<Item = ::models::InlineResponse20013, Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = ::models::InlineResponse20013, Error = Error<serde_json::Value>> + Send>
+    {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
@@ -17,47 +17,44 @@ pub struct ImageSummary {
     id: String,
     #[serde(rename = "ParentId")]
     parent_id: String,
-    #[serde(rename = "RepoTags")]
-    repo_tags: Vec<String>,
-    #[serde(rename = "RepoDigests")]
-    repo_digests: Vec<String>,
+    #[serde(rename = "RepoTags", skip_serializing_if = "Option::is_none")]
+    repo_tags: Option<Vec<String>>,
+    #[serde(rename = "RepoDigests", skip_serializing_if = "Option::is_none")]
+    repo_digests: Option<Vec<String>>,
     #[serde(rename = "Created")]
-    created: i32,
+    created: i64,
     #[serde(rename = "Size")]
-    size: i32,
+    size: i64,
     #[serde(rename = "SharedSize")]
-    shared_size: i32,
+    shared_size: i64,
     #[serde(rename = "VirtualSize")]
-    virtual_size: i32,
-    #[serde(rename = "Labels")]
-    labels: ::std::collections::HashMap<String, String>,
+    virtual_size: i64,
+    #[serde(rename = "Labels", skip_serializing_if = "Option::is_none")]
+    labels: Option<::std::collections::HashMap<String, String>>,
     #[serde(rename = "Containers")]
-    containers: i32,
+    containers: i64,
 }
 
 impl ImageSummary {
     pub fn new(
         id: String,
         parent_id: String,
-        repo_tags: Vec<String>,
-        repo_digests: Vec<String>,
-        created: i32,
-        size: i32,
-        shared_size: i32,
-        virtual_size: i32,
-        labels: ::std::collections::HashMap<String, String>,
-        containers: i32,
+        created: i64,
+        size: i64,
+        shared_size: i64,
+        virtual_size: i64,
+        containers: i64,
     ) -> Self {
         ImageSummary {
             id: id,
             parent_id: parent_id,
-            repo_tags: repo_tags,
-            repo_digests: repo_digests,
+            repo_tags: None,
+            repo_digests: None,
             created: created,
             size: size,
             shared_size: shared_size,
             virtual_size: virtual_size,
-            labels: labels,
+            labels: None,
             containers: containers,
         }
     }
@@ -89,106 +86,118 @@ impl ImageSummary {
     }
 
     pub fn set_repo_tags(&mut self, repo_tags: Vec<String>) {
-        self.repo_tags = repo_tags;
+        self.repo_tags = Some(repo_tags);
     }
 
     pub fn with_repo_tags(mut self, repo_tags: Vec<String>) -> Self {
-        self.repo_tags = repo_tags;
+        self.repo_tags = Some(repo_tags);
         self
     }
 
-    pub fn repo_tags(&self) -> &[String] {
-        &self.repo_tags
+    pub fn repo_tags(&self) -> Option<&[String]> {
+        self.repo_tags.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_repo_tags(&mut self) {
+        self.repo_tags = None;
     }
 
     pub fn set_repo_digests(&mut self, repo_digests: Vec<String>) {
-        self.repo_digests = repo_digests;
+        self.repo_digests = Some(repo_digests);
     }
 
     pub fn with_repo_digests(mut self, repo_digests: Vec<String>) -> Self {
-        self.repo_digests = repo_digests;
+        self.repo_digests = Some(repo_digests);
         self
     }
 
-    pub fn repo_digests(&self) -> &[String] {
-        &self.repo_digests
+    pub fn repo_digests(&self) -> Option<&[String]> {
+        self.repo_digests.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_repo_digests(&mut self) {
+        self.repo_digests = None;
     }
 
-    pub fn set_created(&mut self, created: i32) {
+    pub fn set_created(&mut self, created: i64) {
         self.created = created;
     }
 
-    pub fn with_created(mut self, created: i32) -> Self {
+    pub fn with_created(mut self, created: i64) -> Self {
         self.created = created;
         self
     }
 
-    pub fn created(&self) -> &i32 {
+    pub fn created(&self) -> &i64 {
         &self.created
     }
 
-    pub fn set_size(&mut self, size: i32) {
+    pub fn set_size(&mut self, size: i64) {
         self.size = size;
     }
 
-    pub fn with_size(mut self, size: i32) -> Self {
+    pub fn with_size(mut self, size: i64) -> Self {
         self.size = size;
         self
     }
 
-    pub fn size(&self) -> &i32 {
+    pub fn size(&self) -> &i64 {
         &self.size
     }
 
-    pub fn set_shared_size(&mut self, shared_size: i32) {
+    pub fn set_shared_size(&mut self, shared_size: i64) {
         self.shared_size = shared_size;
     }
 
-    pub fn with_shared_size(mut self, shared_size: i32) -> Self {
+    pub fn with_shared_size(mut self, shared_size: i64) -> Self {
         self.shared_size = shared_size;
         self
     }
 
-    pub fn shared_size(&self) -> &i32 {
+    pub fn shared_size(&self) -> &i64 {
         &self.shared_size
     }
 
-    pub fn set_virtual_size(&mut self, virtual_size: i32) {
+    pub fn set_virtual_size(&mut self, virtual_size: i64) {
         self.virtual_size = virtual_size;
     }
 
-    pub fn with_virtual_size(mut self, virtual_size: i32) -> Self {
+    pub fn with_virtual_size(mut self, virtual_size: i64) -> Self {
         self.virtual_size = virtual_size;
         self
     }
 
-    pub fn virtual_size(&self) -> &i32 {
+    pub fn virtual_size(&self) -> &i64 {
         &self.virtual_size
     }
 
     pub fn set_labels(&mut self, labels: ::std::collections::HashMap<String, String>) {
-        self.labels = labels;
+        self.labels = Some(labels);
     }
 
     pub fn with_labels(mut self, labels: ::std::collections::HashMap<String, String>) -> Self {
-        self.labels = labels;
+        self.labels = Some(labels);
         self
     }
 
-    pub fn labels(&self) -> &::std::collections::HashMap<String, String> {
-        &self.labels
+    pub fn labels(&self) -> Option<&::std::collections::HashMap<String, String>> {
+        self.labels.as_ref()
+    }
+
+    pub fn reset_labels(&mut self) {
+        self.labels = None;
     }
 
-    pub fn set_containers(&mut self, containers: i32) {
+    pub fn set_containers(&mut self, containers: i64) {
         self.containers = containers;
     }
 
-    pub fn with_containers(mut self, containers: i32) -> Self {
+    pub fn with_containers(mut self, containers: i64) -> Self {
         self.containers = containers;
         self
     }
 
-    pub fn containers(&self) -> &i32 {
+    pub fn containers(&self) -> &i64 {
         &self.containers
     }
 }
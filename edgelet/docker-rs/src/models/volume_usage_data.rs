@@ -17,44 +17,44 @@ use serde_json::Value;
 pub struct VolumeUsageData {
     /// Amount of disk space used by the volume (in bytes). This information is only available for volumes created with the `\"local\"` volume driver. For volumes created with other volume drivers, this field is set to `-1` (\"not available\")
     #[serde(rename = "Size")]
-    size: i32,
+    size: i64,
     /// The number of containers referencing this volume. This field is set to `-1` if the reference-count is not available.
     #[serde(rename = "RefCount")]
-    ref_count: i32,
+    ref_count: i64,
 }
 
 impl VolumeUsageData {
     /// Usage details about the volume. This information is used by the `GET /system/df` endpoint, and omitted in other endpoints.
-    pub fn new(size: i32, ref_count: i32) -> Self {
+    pub fn new(size: i64, ref_count: i64) -> Self {
         VolumeUsageData {
             size: size,
             ref_count: ref_count,
         }
     }
 
-    pub fn set_size(&mut self, size: i32) {
+    pub fn set_size(&mut self, size: i64) {
         self.size = size;
     }
 
-    pub fn with_size(mut self, size: i32) -> Self {
+    pub fn with_size(mut self, size: i64) -> Self {
         self.size = size;
         self
     }
 
-    pub fn size(&self) -> &i32 {
+    pub fn size(&self) -> &i64 {
         &self.size
     }
 
-    pub fn set_ref_count(&mut self, ref_count: i32) {
+    pub fn set_ref_count(&mut self, ref_count: i64) {
         self.ref_count = ref_count;
     }
 
-    pub fn with_ref_count(mut self, ref_count: i32) -> Self {
+    pub fn with_ref_count(mut self, ref_count: i64) -> Self {
         self.ref_count = ref_count;
         self
     }
 
-    pub fn ref_count(&self) -> &i32 {
+    pub fn ref_count(&self) -> &i64 {
         &self.ref_count
     }
 }
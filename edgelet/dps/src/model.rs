@@ -26,6 +26,9 @@ pub struct DeviceRegistration {
     registration_id: Option<String>,
     #[serde(rename = "tpm", skip_serializing_if = "Option::is_none")]
     tpm: Option<TpmAttestation>,
+    /// Custom allocation payload.
+    #[serde(rename = "payload", skip_serializing_if = "Option::is_none")]
+    payload: Option<Value>,
 }
 
 impl DeviceRegistration {
@@ -34,6 +37,7 @@ impl DeviceRegistration {
         DeviceRegistration {
             registration_id: None,
             tpm: None,
+            payload: None,
         }
     }
 
@@ -70,6 +74,23 @@ impl DeviceRegistration {
     pub fn reset_tpm(&mut self) {
         self.tpm = None;
     }
+
+    pub fn set_payload(&mut self, payload: Value) {
+        self.payload = Some(payload);
+    }
+
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    pub fn payload(&self) -> Option<&Value> {
+        self.payload.as_ref()
+    }
+
+    pub fn reset_payload(&mut self) {
+        self.payload = None;
+    }
 }
 
 impl Default for DeviceRegistration {
@@ -300,6 +321,9 @@ pub struct DeviceRegistrationResult {
     /// The entity tag associated with the resource.
     #[serde(rename = "etag", skip_serializing_if = "Option::is_none")]
     etag: Option<String>,
+    /// Custom allocation payload returned by the allocation policy.
+    #[serde(rename = "payload", skip_serializing_if = "Option::is_none")]
+    payload: Option<Value>,
 }
 
 impl DeviceRegistrationResult {
@@ -317,6 +341,7 @@ impl DeviceRegistrationResult {
             error_message: None,
             last_updated_date_time_utc: None,
             etag: None,
+            payload: None,
         }
     }
 
@@ -481,4 +506,21 @@ impl DeviceRegistrationResult {
     pub fn reset_etag(&mut self) {
         self.etag = None;
     }
+
+    pub fn set_payload(&mut self, payload: Value) {
+        self.payload = Some(payload);
+    }
+
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    pub fn payload(&self) -> Option<&Value> {
+        self.payload.as_ref()
+    }
+
+    pub fn reset_payload(&mut self) {
+        self.payload = None;
+    }
 }
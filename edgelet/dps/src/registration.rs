@@ -10,7 +10,7 @@ use futures::future::Either;
 use futures::{future, Future};
 use hyper::{Method, StatusCode};
 use percent_encoding::{percent_encode, PATH_SEGMENT_ENCODE_SET};
-use serde_json;
+use serde_json::{self, Value};
 use tokio::prelude::*;
 use tokio::timer::Interval;
 use url::form_urlencoded::Serializer as UrlSerializer;
@@ -98,6 +98,7 @@ where
     tpm_ek: Bytes,
     tpm_srk: Bytes,
     key_store: A,
+    payload: Option<Value>,
 }
 
 impl<C, K, A> DpsClient<C, K, A>
@@ -121,9 +122,17 @@ where
             tpm_ek,
             tpm_srk,
             key_store,
+            payload: None,
         })
     }
 
+    /// Sends `payload` with the registration, for custom allocation policies
+    /// to act on.
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
     fn get_tpm_challenge_key(body: &str, key_store: &mut A) -> Result<K, Error> {
         serde_json::from_str(body).map_err(Error::from).and_then(
             |tpm_challenge: TpmRegistrationResult| {
@@ -288,12 +297,17 @@ where
         tpm_ek: &Bytes,
         tpm_srk: &Bytes,
         key_store: &A,
+        payload: Option<Value>,
     ) -> Box<Future<Item = Option<RegistrationOperationStatus>, Error = Error> + Send> {
         let tpm_attestation = TpmAttestation::new(base64::encode(&tpm_ek))
             .with_storage_root_key(base64::encode(&tpm_srk));
         let registration = DeviceRegistration::new()
             .with_registration_id(registration_id.clone())
             .with_tpm(tpm_attestation);
+        let registration = match payload {
+            Some(payload) => registration.with_payload(payload),
+            None => registration,
+        };
         let client_inner = client.clone();
         let mut key_store_inner = key_store.clone();
         let r = client
@@ -350,7 +364,11 @@ where
         Box::new(r)
     }
 
-    pub fn register(&self) -> Box<Future<Item = (String, String), Error = Error> + Send> {
+    /// Registers the device, returning the ID it is assigned, the hub it is
+    /// assigned to and the payload the allocation policy returned, if any.
+    pub fn register(
+        &self,
+    ) -> Box<Future<Item = (String, String, Option<Value>), Error = Error> + Send> {
         let key_store = self.key_store.clone();
         let mut key_store_status = self.key_store.clone();
         let client_with_token_status = self.client.clone();
@@ -371,6 +389,7 @@ where
             &tpm_ek,
             &tpm_srk,
             &self.key_store,
+            self.payload.clone(),
         ).and_then(
             move |operation_status: Option<RegistrationOperationStatus>| match key_store
                 .get(&KeyIdentity::Device, "primary")
@@ -395,7 +414,7 @@ where
         ).and_then(move |operation_status: Option<DeviceRegistrationResult>| {
             operation_status
                 .ok_or_else(|| Error::from(ErrorKind::NotAssigned))
-                .and_then(|s| -> Result<(String, String), Error> {
+                .and_then(|s| -> Result<(String, String, Option<Value>), Error> {
                     let tpm_result_inner = s.clone();
                     let tpm_result = s.tpm();
                     tpm_result
@@ -412,7 +431,7 @@ where
                                             kb,
                                         ).map_err(Error::from)
                                 })
-                        }).and_then(|_| -> Result<(String, String), Error> {
+                        }).and_then(|_| -> Result<(String, String, Option<Value>), Error> {
                             get_device_info(&tpm_result_inner)
                         })
                 })
@@ -423,7 +442,7 @@ where
 
 fn get_device_info(
     registration_result: &DeviceRegistrationResult,
-) -> Result<(String, String), Error> {
+) -> Result<(String, String, Option<Value>), Error> {
     Ok((
         registration_result
             .device_id()
@@ -433,6 +452,7 @@ fn get_device_info(
             .assigned_hub()
             .map(ToOwned::to_owned)
             .ok_or_else(|| Error::from(ErrorKind::NotAssigned))?,
+        registration_result.payload().cloned(),
     ))
 }
 
@@ -502,6 +522,7 @@ mod tests {
             &Bytes::from("ek".to_string().into_bytes()),
             &Bytes::from("srk".to_string().into_bytes()),
             &MemoryKeyStore::new(),
+            None,
         ).map(|result| match result {
             Some(op) => {
                 assert_eq!(op.operation_id(), "something");
@@ -811,7 +832,21 @@ mod tests {
                     .with_device_id("device".to_string())
                     .with_assigned_hub("hub".to_string())
             ).unwrap(),
-            ("device".to_string(), "hub".to_string())
+            ("device".to_string(), "hub".to_string(), None)
+        )
+    }
+
+    #[test]
+    fn get_device_info_returns_payload() {
+        let payload: Value = serde_json::from_str(r#"{ "modules": ["sensor"] }"#).unwrap();
+        assert_eq!(
+            get_device_info(
+                &DeviceRegistrationResult::new("reg".to_string(), "assigned".to_string())
+                    .with_device_id("device".to_string())
+                    .with_assigned_hub("hub".to_string())
+                    .with_payload(payload.clone())
+            ).unwrap(),
+            ("device".to_string(), "hub".to_string(), Some(payload))
         )
     }
 }
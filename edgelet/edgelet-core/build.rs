@@ -0,0 +1,16 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::env;
+use std::process::Command;
+
+// Records the version of the compiler the daemon is built with, which is
+// reported along with the rest of the build information on GET /systeminfo.
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Ok(output) = Command::new(rustc).arg("--version").output() {
+        if output.status.success() {
+            let version = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=EDGELET_RUSTC_VERSION={}", version.trim());
+        }
+    }
+}
@@ -3,6 +3,7 @@
 use error::Error;
 use futures::future::Either;
 use futures::{future, Future, Stream};
+use host_process::HostProcesses;
 use module::{Module, ModuleRuntime};
 use pid::Pid;
 
@@ -10,6 +11,42 @@ pub enum Policy {
     Anonymous,
     Caller,
     Module(&'static str),
+    /// Callers that are modules in a namespace may only target modules in
+    /// the same namespace. Any other caller is authorized.
+    Namespace,
+}
+
+/// A caller that authenticated with a client certificate, such as a child
+/// device calling the workload API over TCP, and the modules it may call as.
+/// Its pid can't be told, so it is authorized by the names its certificate
+/// is bound to instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerIdentity {
+    common_name: String,
+    modules: Vec<String>,
+}
+
+impl PeerIdentity {
+    pub fn new(common_name: String, modules: Vec<String>) -> Self {
+        PeerIdentity {
+            common_name,
+            modules,
+        }
+    }
+
+    /// The common name of the certificate the caller presented.
+    pub fn common_name(&self) -> &str {
+        &self.common_name
+    }
+
+    /// The modules and devices the caller may call as.
+    pub fn modules(&self) -> &[String] {
+        &self.modules
+    }
+
+    pub fn may_act_as(&self, module: &str) -> bool {
+        self.modules.iter().any(|m| m == module)
+    }
 }
 
 pub struct Authorization<M>
@@ -18,6 +55,7 @@ where
 {
     runtime: M,
     policy: Policy,
+    host_processes: HostProcesses,
 }
 
 impl<M> Authorization<M>
@@ -27,7 +65,18 @@ where
     <M::Module as Module>::Error: Into<Error>,
 {
     pub fn new(runtime: M, policy: Policy) -> Self {
-        Authorization { runtime, policy }
+        Authorization {
+            runtime,
+            policy,
+            host_processes: HostProcesses::new(),
+        }
+    }
+
+    /// Lets the host processes in `host_processes` act as the modules they
+    /// are registered as, when no module container has that name.
+    pub fn with_host_processes(mut self, host_processes: HostProcesses) -> Self {
+        self.host_processes = host_processes;
+        self
     }
 
     pub fn authorize(
@@ -39,10 +88,39 @@ where
         match self.policy {
             Policy::Anonymous => Either::A(Either::A(self.auth_anonymous())),
             Policy::Caller => Either::A(Either::B(self.auth_caller(name, pid))),
-            Policy::Module(ref expected_name) => Either::B(self.auth_module(expected_name, pid)),
+            Policy::Module(ref expected_name) => {
+                Either::B(Either::A(self.auth_module(expected_name, pid)))
+            }
+            Policy::Namespace => Either::B(Either::B(self.auth_namespace(name, pid))),
         }
     }
 
+    /// Authorizes a caller that authenticated as `peer`. It may only call as
+    /// the modules its certificate is bound to, and may only target those
+    /// modules and the modules in their namespaces.
+    pub fn authorize_peer(
+        &self,
+        name: Option<String>,
+        peer: &PeerIdentity,
+    ) -> impl Future<Item = bool, Error = Error> {
+        let name = name.map(|n| n.trim_left_matches('$').to_string());
+        let authorized = match self.policy {
+            Policy::Anonymous => true,
+            Policy::Caller => name.as_ref().map_or(false, |name| peer.may_act_as(name)),
+            Policy::Module(ref expected_name) => peer.may_act_as(expected_name),
+            Policy::Namespace => {
+                return Either::B(self.auth_peer_namespace(name, peer.clone()));
+            }
+        };
+        if !authorized {
+            info!(
+                "Request not authorized - client certificate {} is not bound to the module",
+                peer.common_name()
+            );
+        }
+        Either::A(future::ok(authorized))
+    }
+
     fn auth_anonymous(&self) -> impl Future<Item = bool, Error = Error> {
         future::ok(true)
     }
@@ -52,24 +130,34 @@ where
         name: Option<String>,
         pid: Pid,
     ) -> impl Future<Item = bool, Error = Error> {
+        let host_processes = self.host_processes.clone();
         name.map_or_else(
             || Either::A(future::ok(false)),
             |name| Either::B(
                 self.runtime
                     .list_with_details()
                     .map_err(|e| e.into())
-                    .filter_map(move |(m, rs)| if m.name() == name { Some(rs) } else { None })
-                    .into_future()
-                    .then(move |result| match result {
-                        Ok((Some(rs), _)) => {
-                            let authorized = rs.pid() == pid;
-                            if !authorized {
-                                info!("Request not authorized - expected caller pid: {}, actual caller pid: {}", rs.pid(), pid);
-                            }
-                            Ok(authorized)
-                        },
-                        Ok((None, _)) => Ok(false),
-                        Err((err, _)) => Err(err),
+                    // a module's canary calls as the module while it soaks
+                    .filter_map({
+                        let name = name.clone();
+                        move |(m, rs)| if m.identity() == name { Some(rs.pid()) } else { None }
+                    })
+                    .collect()
+                    .map(move |pids| {
+                        if pids.is_empty() {
+                            return host_processes.is_caller(&name, pid);
+                        }
+                        let authorized = pids.iter().any(|p| *p == pid);
+                        if !authorized {
+                            let expected: Vec<String> =
+                                pids.iter().map(ToString::to_string).collect();
+                            info!(
+                                "Request not authorized - expected caller pid: {}, actual caller pid: {}",
+                                expected.join(" or "),
+                                pid
+                            );
+                        }
+                        authorized
                     })),
         )
     }
@@ -81,10 +169,97 @@ where
     ) -> impl Future<Item = bool, Error = Error> {
         self.auth_caller(Some(expected_name.to_string()), pid)
     }
+
+    // A peer belongs to the namespaces of the modules its certificate is
+    // bound to. One bound to modules outside of any namespace may only target
+    // those modules.
+    fn auth_peer_namespace(
+        &self,
+        name: Option<String>,
+        peer: PeerIdentity,
+    ) -> impl Future<Item = bool, Error = Error> {
+        self.runtime
+            .list_with_details()
+            .map_err(|e| e.into())
+            .map(|(m, _)| (m.name().to_string(), m.namespace().map(ToString::to_string)))
+            .collect()
+            .map(move |modules| {
+                let name = match name {
+                    Some(name) => name,
+                    None => return false,
+                };
+                if peer.may_act_as(&name) {
+                    return true;
+                }
+                let target_namespace = modules
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .and_then(|(_, namespace)| namespace.as_ref());
+                let authorized = target_namespace.map_or(false, |target_namespace| {
+                    modules.iter().any(|(n, namespace)| {
+                        peer.may_act_as(n) && namespace.as_ref() == Some(target_namespace)
+                    })
+                });
+                if !authorized {
+                    info!(
+                        "Request not authorized - client certificate {} is not bound to a module \
+                         in the namespace of module {}",
+                        peer.common_name(),
+                        name
+                    );
+                }
+                authorized
+            })
+    }
+
+    fn auth_namespace(
+        &self,
+        name: Option<String>,
+        pid: Pid,
+    ) -> impl Future<Item = bool, Error = Error> {
+        self.runtime
+            .list_with_details()
+            .map_err(|e| e.into())
+            .map(|(m, rs)| {
+                (
+                    m.name().to_string(),
+                    m.namespace().map(ToString::to_string),
+                    rs.pid(),
+                )
+            }).collect()
+            .map(move |modules| {
+                // Pid::Any stands for callers whose pid can't be told, which
+                // are let through like any caller that isn't a module.
+                let caller = match pid {
+                    Pid::Value(_) => modules.iter().find(|(_, _, p)| *p == pid),
+                    _ => None,
+                };
+                let caller_namespace = match caller {
+                    Some((_, Some(namespace), _)) => namespace,
+                    _ => return true,
+                };
+                let target_namespace = name.and_then(|name| {
+                    modules
+                        .iter()
+                        .find(|(n, _, _)| *n == name)
+                        .and_then(|(_, namespace, _)| namespace.clone())
+                });
+                let authorized = target_namespace.as_ref() == Some(caller_namespace);
+                if !authorized {
+                    info!(
+                        "Request not authorized - caller in namespace {} may not target modules \
+                         outside of it",
+                        caller_namespace
+                    );
+                }
+                authorized
+            })
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::path::{Path, PathBuf};
     use std::time::Duration;
 
     use super::*;
@@ -95,7 +270,7 @@ mod tests {
     use futures::{future, stream};
     use module::{
         LogOptions, Module, ModuleRegistry, ModuleRuntimeState, ModuleSpec,
-        SystemInfo as CoreSystemInfo,
+        SystemInfo as CoreSystemInfo, UpdateStrategy,
     };
 
     #[test]
@@ -135,6 +310,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_authorize_canary_as_its_module() {
+        let runtime = TestModuleList::new(vec![
+            TestModule::new("abc", 123),
+            TestModule::new("abc-canary", 456).with_identity("abc"),
+        ]);
+        let auth = Authorization::new(runtime, Policy::Caller);
+        for pid in &[123, 456] {
+            assert_eq!(
+                true,
+                auth.authorize(Some("abc".to_string()), Pid::Value(*pid))
+                    .wait()
+                    .unwrap()
+            );
+        }
+        assert_eq!(
+            false,
+            auth.authorize(Some("abc-canary".to_string()), Pid::Value(456))
+                .wait()
+                .unwrap()
+        );
+        assert_eq!(
+            false,
+            auth.authorize(Some("abc".to_string()), Pid::Value(789))
+                .wait()
+                .unwrap()
+        );
+    }
+
     #[test]
     fn should_reject_caller_without_name() {
         let runtime = TestModuleList::new(vec![TestModule::new("abc", 123)]);
@@ -166,6 +370,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_authorize_host_process_caller() {
+        let runtime = TestModuleList::new(vec![TestModule::new("abc", 123)]);
+        let host_processes = HostProcesses::new().with_inspector(|pid| {
+            if pid == 456 {
+                Some((PathBuf::from("/usr/bin/agent"), 1000))
+            } else {
+                None
+            }
+        });
+        host_processes
+            .register("agent", Path::new("/usr/bin/agent"), 1000)
+            .unwrap();
+        let auth = Authorization::new(runtime, Policy::Caller).with_host_processes(host_processes);
+        assert_eq!(
+            true,
+            auth.authorize(Some("agent".to_string()), Pid::Value(456))
+                .wait()
+                .unwrap()
+        );
+        assert_eq!(
+            false,
+            auth.authorize(Some("agent".to_string()), Pid::Value(123))
+                .wait()
+                .unwrap()
+        );
+    }
+
     #[test]
     fn should_authorize_module() {
         let runtime = TestModuleList::new(vec![
@@ -233,6 +465,123 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn should_authorize_namespace_caller_targeting_same_namespace() {
+        let runtime = TestModuleList::new(vec![
+            TestModule::new("abc", 123).with_namespace("contoso"),
+            TestModule::new("xyz", 987).with_namespace("contoso"),
+        ]);
+        let auth = Authorization::new(runtime, Policy::Namespace);
+        assert_eq!(
+            true,
+            auth.authorize(Some("xyz".to_string()), Pid::Value(123))
+                .wait()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn should_reject_namespace_caller_targeting_other_namespace() {
+        let runtime = TestModuleList::new(vec![
+            TestModule::new("abc", 123).with_namespace("contoso"),
+            TestModule::new("xyz", 987).with_namespace("fabrikam"),
+            TestModule::new("edgeHub", 456),
+        ]);
+        let auth = Authorization::new(runtime, Policy::Namespace);
+        for target in &["xyz", "edgeHub", "missing"] {
+            assert_eq!(
+                false,
+                auth.authorize(Some(target.to_string()), Pid::Value(123))
+                    .wait()
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn should_authorize_callers_outside_namespaces() {
+        let runtime = TestModuleList::new(vec![
+            TestModule::new("abc", 123),
+            TestModule::new("xyz", 987).with_namespace("fabrikam"),
+        ]);
+        let auth = Authorization::new(runtime, Policy::Namespace);
+        for pid in &[Pid::Value(123), Pid::Value(555), Pid::Any, Pid::None] {
+            assert_eq!(
+                true,
+                auth.authorize(Some("xyz".to_string()), *pid)
+                    .wait()
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn should_authorize_peers_as_the_modules_they_are_bound_to() {
+        let peer = PeerIdentity::new(
+            "childDevice".to_string(),
+            vec!["childDevice".to_string(), "sensor".to_string()],
+        );
+
+        let auth = Authorization::new(TestModuleList::new(vec![]), Policy::Caller);
+        let authorize = |name: Option<&str>| {
+            auth.authorize_peer(name.map(ToString::to_string), &peer)
+                .wait()
+                .unwrap()
+        };
+        assert!(authorize(Some("sensor")));
+        assert!(authorize(Some("$childDevice")));
+        assert!(!authorize(Some("edgeHub")));
+        assert!(!authorize(None));
+
+        let auth = Authorization::new(TestModuleList::new(vec![]), Policy::Module("edgeHub"));
+        assert!(!auth.authorize_peer(None, &peer).wait().unwrap());
+
+        let auth = Authorization::new(TestModuleList::new(vec![]), Policy::Anonymous);
+        assert!(auth.authorize_peer(None, &peer).wait().unwrap());
+    }
+
+    #[test]
+    fn should_authorize_peers_in_the_namespaces_of_their_modules() {
+        let runtime = TestModuleList::new(vec![
+            TestModule::new("sensor", 123).with_namespace("contoso"),
+            TestModule::new("filter", 456).with_namespace("contoso"),
+            TestModule::new("other", 789).with_namespace("fabrikam"),
+            TestModule::new("edgeHub", 321),
+        ]);
+        let auth = Authorization::new(runtime, Policy::Namespace);
+        let peer = PeerIdentity::new("childDevice".to_string(), vec!["sensor".to_string()]);
+        let authorize = |name: Option<&str>| {
+            auth.authorize_peer(name.map(ToString::to_string), &peer)
+                .wait()
+                .unwrap()
+        };
+        assert!(authorize(Some("sensor")));
+        assert!(authorize(Some("filter")));
+        assert!(!authorize(Some("other")));
+        assert!(!authorize(Some("edgeHub")));
+        assert!(!authorize(Some("missing")));
+        assert!(!authorize(None));
+    }
+
+    #[test]
+    fn should_reject_peers_outside_namespaces_targeting_other_modules() {
+        let runtime = TestModuleList::new(vec![
+            TestModule::new("sensor", 123),
+            TestModule::new("other", 789).with_namespace("fabrikam"),
+            TestModule::new("edgeHub", 321),
+        ]);
+        let auth = Authorization::new(runtime, Policy::Namespace);
+        let peer = PeerIdentity::new("childDevice".to_string(), vec!["sensor".to_string()]);
+        let authorize = |name: &str| {
+            auth.authorize_peer(Some(name.to_string()), &peer)
+                .wait()
+                .unwrap()
+        };
+        assert!(authorize("sensor"));
+        assert!(!authorize("other"));
+        assert!(!authorize("edgeHub"));
+    }
+
     struct TestConfig {}
 
     #[derive(Clone, Copy)]
@@ -247,6 +596,8 @@ mod tests {
         name: String,
         pid: i32,
         behavior: TestModuleBehavior,
+        namespace: Option<String>,
+        identity: Option<String>,
     }
 
     impl TestModule {
@@ -256,6 +607,8 @@ mod tests {
                 name,
                 pid,
                 behavior: TestModuleBehavior::Default,
+                namespace: None,
+                identity: None,
             }
         }
 
@@ -265,8 +618,20 @@ mod tests {
                 name,
                 pid,
                 behavior,
+                namespace: None,
+                identity: None,
             }
         }
+
+        pub fn with_namespace(mut self, namespace: &str) -> Self {
+            self.namespace = Some(namespace.to_string());
+            self
+        }
+
+        pub fn with_identity(mut self, identity: &str) -> Self {
+            self.identity = Some(identity.to_string());
+            self
+        }
     }
 
     macro_rules! notimpl_error {
@@ -295,6 +660,12 @@ mod tests {
         fn config(&self) -> &Self::Config {
             &TestConfig {}
         }
+        fn namespace(&self) -> Option<&str> {
+            self.namespace.as_ref().map(AsRef::as_ref)
+        }
+        fn identity(&self) -> &str {
+            self.identity.as_ref().map_or(self.name.as_str(), AsRef::as_ref)
+        }
         fn runtime_state(&self) -> Self::RuntimeStateFuture {
             match self.behavior {
                 TestModuleBehavior::Default => {
@@ -368,6 +739,7 @@ mod tests {
         type StopFuture = FutureResult<(), Self::Error>;
         type SystemInfoFuture = FutureResult<CoreSystemInfo, Self::Error>;
         type RemoveAllFuture = FutureResult<(), Self::Error>;
+        type UpdateFuture = FutureResult<(), Self::Error>;
 
         fn init(&self) -> Self::InitFuture {
             notimpl_error!()
@@ -397,6 +769,10 @@ mod tests {
             notimpl_error!()
         }
 
+        fn remove_with_volumes(&self, _id: &str) -> Self::RemoveFuture {
+            notimpl_error!()
+        }
+
         fn list(&self) -> Self::ListFuture {
             match self.behavior {
                 TestModuleListBehavior::Default => future::ok(self.modules.clone()),
@@ -427,5 +803,13 @@ mod tests {
         fn remove_all(&self) -> Self::RemoveAllFuture {
             notimpl_error!()
         }
+
+        fn update(
+            &self,
+            _module: ModuleSpec<Self::Config>,
+            _strategy: UpdateStrategy,
+        ) -> Self::UpdateFuture {
+            notimpl_error!()
+        }
     }
 }
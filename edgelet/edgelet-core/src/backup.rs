@@ -0,0 +1,329 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Backup and restore of the state the daemon keeps on the device.
+//!
+//! The state is made of entries, each a file or a directory that is backed up
+//! under a name that doesn't depend on where the device keeps it, so that a
+//! backup can be restored on a replacement device with another layout. Among
+//! them are the HSM's master encryption keys, so a backup is as sensitive as
+//! the device itself: it is a JSON archive of the entries' files encrypted
+//! with AES-256-GCM under a key derived from a passphrase.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use base64;
+use chrono::{DateTime, Utc};
+use failure::ResultExt;
+use openssl::hash::MessageDigest;
+use openssl::pkcs5;
+use openssl::rand::rand_bytes;
+use openssl::symm::{self, Cipher};
+use serde_json;
+
+use error::{Error, ErrorKind, Result};
+
+const MAGIC: &[u8] = b"IOTEDGE-BACKUP";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const PBKDF2_ITERATIONS: usize = 100_000;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Archive {
+    created: DateTime<Utc>,
+    files: Vec<ArchivedFile>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ArchivedFile {
+    name: String,
+    /// The base64 encoded contents of the file.
+    contents: String,
+}
+
+/// The entries of the daemon's state that are backed up and restored.
+#[derive(Clone, Debug, Default)]
+pub struct StateBackup {
+    entries: Vec<(String, PathBuf)>,
+}
+
+impl StateBackup {
+    pub fn new() -> Self {
+        StateBackup::default()
+    }
+
+    /// Backs up the file or directory at `path` under `name`. The files of a
+    /// directory are backed up under `name/<relative path>`.
+    pub fn with_entry(mut self, name: &str, path: PathBuf) -> Self {
+        self.entries.push((name.to_string(), path));
+        self
+    }
+
+    /// Archives the entries and encrypts the archive with `passphrase`.
+    /// Entries that don't exist on this device are left out.
+    pub fn backup(&self, passphrase: &str) -> Result<Vec<u8>> {
+        if passphrase.is_empty() {
+            return Err(Error::from(ErrorKind::BackupPassphrase));
+        }
+
+        let mut files = Vec::new();
+        for (name, path) in &self.entries {
+            collect(name, path, &mut files).context(ErrorKind::Backup)?;
+        }
+        let archive = Archive {
+            created: Utc::now(),
+            files,
+        };
+
+        let json = serde_json::to_vec(&archive).context(ErrorKind::Backup)?;
+        encrypt(&json, passphrase)
+    }
+
+    /// Decrypts `backup` with `passphrase` and restores its files, returning
+    /// their names. Nothing is restored unless every file in the backup
+    /// belongs to one of the entries.
+    pub fn restore(&self, backup: &[u8], passphrase: &str) -> Result<Vec<String>> {
+        let json = decrypt(backup, passphrase)?;
+        let archive: Archive = serde_json::from_slice(&json).context(ErrorKind::InvalidBackup)?;
+
+        let files = archive
+            .files
+            .into_iter()
+            .map(|file| {
+                let path = self
+                    .path_of(&file.name)
+                    .ok_or_else(|| Error::from(ErrorKind::InvalidBackup))?;
+                let contents = base64::decode(&file.contents).context(ErrorKind::InvalidBackup)?;
+                Ok((file.name, path, contents))
+            }).collect::<Result<Vec<_>>>()?;
+
+        for (name, path, contents) in &files {
+            info!("Restoring {} to {}", name, path.display());
+            write_file(path, contents).context(ErrorKind::Restore)?;
+        }
+        Ok(files.into_iter().map(|(name, _, _)| name).collect())
+    }
+
+    // Where the file backed up under `name` is restored to: the path of its
+    // entry, or a path under it if the entry is a directory. A name that would
+    // be restored outside of the entries has no path.
+    fn path_of(&self, name: &str) -> Option<PathBuf> {
+        for (entry, path) in &self.entries {
+            if name == entry {
+                return Some(path.clone());
+            }
+            if name.starts_with(entry.as_str()) && name[entry.len()..].starts_with('/') {
+                let relative = Path::new(&name[entry.len() + 1..]);
+                let is_normal = relative.components().all(|component| match component {
+                    Component::Normal(_) => true,
+                    _ => false,
+                });
+                if is_normal && relative.components().next().is_some() {
+                    return Some(path.join(relative));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn collect(name: &str, path: &Path, files: &mut Vec<ArchivedFile>) -> io::Result<()> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let name = format!("{}/{}", name, entry.file_name().to_string_lossy());
+            collect(&name, &entry.path(), files)?;
+        }
+    } else {
+        files.push(ArchivedFile {
+            name: name.to_string(),
+            contents: base64::encode(&fs::read(path)?),
+        });
+    }
+    Ok(())
+}
+
+// The restored files hold keys and credentials, so only the daemon's user can
+// read the ones that are created.
+fn write_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)
+}
+
+// A backup is the header, which is the magic, the format version, the salt of
+// the key and the IV, followed by the ciphertext and the tag. The header is
+// authenticated along with the ciphertext.
+fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut iv = [0; IV_LEN];
+    rand_bytes(&mut salt).context(ErrorKind::Backup)?;
+    rand_bytes(&mut iv).context(ErrorKind::Backup)?;
+    let key = derive_key(passphrase, &salt)?;
+
+    let mut backup = MAGIC.to_vec();
+    backup.push(FORMAT_VERSION);
+    backup.extend_from_slice(&salt);
+    backup.extend_from_slice(&iv);
+
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = symm::encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(&iv),
+        &backup,
+        plaintext,
+        &mut tag,
+    ).context(ErrorKind::Backup)?;
+    backup.extend_from_slice(&ciphertext);
+    backup.extend_from_slice(&tag);
+    Ok(backup)
+}
+
+fn decrypt(backup: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let header_len = MAGIC.len() + 1 + SALT_LEN + IV_LEN;
+    if backup.len() < header_len + TAG_LEN
+        || !backup.starts_with(MAGIC)
+        || backup[MAGIC.len()] != FORMAT_VERSION
+    {
+        return Err(Error::from(ErrorKind::InvalidBackup));
+    }
+
+    let (header, rest) = backup.split_at(header_len);
+    let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let iv = &header[header_len - IV_LEN..];
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    let key = derive_key(passphrase, salt)?;
+    let plaintext = symm::decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &key,
+        Some(iv),
+        header,
+        ciphertext,
+        tag,
+    ).context(ErrorKind::BackupPassphrase)?;
+    Ok(plaintext)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LEN]> {
+    let mut key = [0; KEY_LEN];
+    pkcs5::pbkdf2_hmac(
+        passphrase.as_bytes(),
+        salt,
+        PBKDF2_ITERATIONS,
+        MessageDigest::sha256(),
+        &mut key,
+    ).context(ErrorKind::Backup)?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn state(dir: &Path) -> StateBackup {
+        StateBackup::new()
+            .with_entry("config.yaml", dir.join("config.yaml"))
+            .with_entry("enc_keys", dir.join("hsm").join("enc_keys"))
+            .with_entry("settings_state", dir.join("cache").join("settings_state"))
+    }
+
+    #[test]
+    fn backups_restore_on_another_device() {
+        let device_dir = TempDir::new("backup").unwrap();
+        let device = device_dir.path();
+        fs::write(device.join("config.yaml"), "hostname: gateway\n").unwrap();
+        fs::create_dir_all(device.join("hsm").join("enc_keys")).unwrap();
+        fs::write(device.join("hsm").join("enc_keys").join("master"), [1, 2, 3]).unwrap();
+
+        let backup = state(device).backup("passphrase").unwrap();
+        assert!(backup.starts_with(MAGIC));
+
+        let replacement_dir = TempDir::new("backup").unwrap();
+        let replacement = replacement_dir.path();
+        let mut restored = state(replacement).restore(&backup, "passphrase").unwrap();
+        restored.sort();
+        assert_eq!(vec!["config.yaml", "enc_keys/master"], restored);
+        assert_eq!(
+            "hostname: gateway\n",
+            fs::read_to_string(replacement.join("config.yaml")).unwrap()
+        );
+        assert_eq!(
+            vec![1, 2, 3],
+            fs::read(replacement.join("hsm").join("enc_keys").join("master")).unwrap()
+        );
+        assert!(!replacement.join("cache").exists());
+    }
+
+    #[test]
+    fn backups_need_their_passphrase() {
+        let device_dir = TempDir::new("backup").unwrap();
+        let device = device_dir.path();
+        fs::write(device.join("config.yaml"), "hostname: gateway\n").unwrap();
+        let state = state(device);
+
+        match *state.backup("").unwrap_err().kind() {
+            ErrorKind::BackupPassphrase => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+        let backup = state.backup("passphrase").unwrap();
+        match *state.restore(&backup, "wrong").unwrap_err().kind() {
+            ErrorKind::BackupPassphrase => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+        let mut tampered = backup.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        match *state.restore(&tampered, "passphrase").unwrap_err().kind() {
+            ErrorKind::BackupPassphrase => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+        match *state.restore(b"not a backup", "passphrase").unwrap_err().kind() {
+            ErrorKind::InvalidBackup => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn files_are_only_restored_into_entries() {
+        let home = Path::new("iotedge");
+        let state = state(home);
+        assert_eq!(
+            Some(home.join("config.yaml")),
+            state.path_of("config.yaml")
+        );
+        assert_eq!(
+            Some(home.join("hsm").join("enc_keys").join("master")),
+            state.path_of("enc_keys/master")
+        );
+        assert_eq!(None, state.path_of("enc_keys/../../etc/passwd"));
+        assert_eq!(None, state.path_of("enc_keys/"));
+        assert_eq!(None, state.path_of("enc_keys_other/master"));
+        assert_eq!(None, state.path_of("/etc/passwd"));
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration as StdDuration;
+
+use base64;
+use chrono::{DateTime, Duration, Utc};
+use failure::ResultExt;
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use clock::{Clock, SystemClock};
+use error::{Error, ErrorKind, Result};
+
+/// How long the roots trusted before a rotation are still served when no
+/// other overlap is configured.
+pub const DEFAULT_ROTATION_OVERLAP_HOURS: u64 = 7 * 24;
+
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+type Reissue = Arc<Fn() -> Result<()> + Send + Sync>;
+
+/// Where a rotation of the Edge CA is at.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum RotationState {
+    /// The trust bundle only has the roots of the current Edge CA.
+    Stable,
+    /// The Edge CA has been rotated. Until the overlap ends the trust bundle
+    /// has the roots trusted before the rotation as well, so downstream
+    /// devices keep trusting certificates issued under the old root while
+    /// they pick up the new one.
+    Overlap {
+        previous_roots: String,
+        started_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        /// Whether the workload CA has been issued again under the new root.
+        workload_ca_reissued: bool,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Record {
+    /// The fingerprint of the Edge CA the daemon last started with.
+    edge_ca: Option<String>,
+    trust_bundle: String,
+    state: RotationState,
+}
+
+impl Default for Record {
+    fn default() -> Self {
+        Record {
+            edge_ca: None,
+            trust_bundle: String::new(),
+            state: RotationState::Stable,
+        }
+    }
+}
+
+/// Rotates the Edge CA without breaking downstream devices.
+///
+/// The Edge CA the daemon starts with is recorded. When it differs from the
+/// one recorded before, a rotation starts: the roots that were trusted before
+/// are served in the trust bundle along with the new ones until the overlap
+/// ends. The workload CA is still the one issued under the old root, so
+/// modules keep their certificates until it is issued again, which is done on
+/// demand or, at the latest, when the overlap ends. When created with `load`
+/// every change is persisted to disk.
+///
+/// The workload API issues certificates under the CAs the rotation is at,
+/// while the daemon advances it and the management API reissues it, each
+/// through a clone of the same rotation.
+#[derive(Clone)]
+pub struct CaRotation {
+    path: Option<PathBuf>,
+    overlap: Duration,
+    record: Arc<Mutex<Record>>,
+    clock: Arc<Clock>,
+    reissue: Option<Reissue>,
+}
+
+impl CaRotation {
+    pub fn new(overlap: StdDuration) -> Self {
+        CaRotation {
+            path: None,
+            overlap: overlap_duration(overlap),
+            record: Arc::new(Mutex::new(Record::default())),
+            clock: Arc::new(SystemClock),
+            reissue: None,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, overlap: StdDuration) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let record = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::CaRotation)?;
+            serde_json::from_reader(file).context(ErrorKind::CaRotation)?
+        } else {
+            Record::default()
+        };
+
+        Ok(CaRotation {
+            path: Some(path),
+            overlap: overlap_duration(overlap),
+            record: Arc::new(Mutex::new(record)),
+            clock: Arc::new(SystemClock),
+            reissue: None,
+        })
+    }
+
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets what is called to issue the workload CA again under the current
+    /// root.
+    pub fn with_reissue<F>(mut self, reissue: F) -> Self
+    where
+        F: Fn() -> Result<()> + Send + Sync + 'static,
+    {
+        self.reissue = Some(Arc::new(reissue));
+        self
+    }
+
+    /// Records the PEM encoded Edge CA certificate and trust bundle the
+    /// daemon started with, starting a rotation if the Edge CA isn't the one
+    /// recorded before. Returns whether a rotation started.
+    pub fn observe(&self, edge_ca: &str, trust_bundle: &str) -> Result<bool> {
+        let now = self.clock.now();
+        let fingerprint = fingerprint(edge_ca);
+        let mut record = self.lock();
+
+        let rotated = match record.edge_ca {
+            Some(ref recorded) => *recorded != fingerprint,
+            None => false,
+        };
+        if rotated {
+            // A rotation during the overlap of another one keeps serving the
+            // roots from before that one as well.
+            let previous_roots = match record.state {
+                RotationState::Overlap {
+                    ref previous_roots, ..
+                } => merge_bundles(previous_roots, &record.trust_bundle),
+                RotationState::Stable => record.trust_bundle.clone(),
+            };
+            let ends_at = now + self.overlap;
+            record.state = RotationState::Overlap {
+                previous_roots,
+                started_at: now,
+                ends_at,
+                workload_ca_reissued: false,
+            };
+            info!(
+                "The Edge CA has been rotated. The previous roots are trusted until {}.",
+                ends_at
+            );
+        }
+
+        record.edge_ca = Some(fingerprint);
+        record.trust_bundle = trust_bundle.to_string();
+        self.persist(&record)?;
+        Ok(rotated)
+    }
+
+    /// Where the rotation is at, ending the overlap if it's over.
+    pub fn state(&self) -> Result<RotationState> {
+        self.expire()?;
+        Ok(self.lock().state.clone())
+    }
+
+    /// When the overlap of the current rotation ends, if one is under way.
+    pub fn overlap_ends_at(&self) -> Option<DateTime<Utc>> {
+        match self.lock().state {
+            RotationState::Overlap { ends_at, .. } => Some(ends_at),
+            RotationState::Stable => None,
+        }
+    }
+
+    /// How long until the overlap of the current rotation ends, if one is
+    /// under way.
+    pub fn overlap_remaining(&self) -> Option<StdDuration> {
+        self.overlap_ends_at().map(|ends_at| {
+            (ends_at - self.clock.now())
+                .to_std()
+                .unwrap_or_else(|_| StdDuration::from_secs(0))
+        })
+    }
+
+    /// The trust bundle to serve for the PEM encoded trust bundle `current`,
+    /// which has the roots trusted before the rotation added to it during
+    /// the overlap.
+    pub fn trust_bundle(&self, current: &str) -> Result<String> {
+        match self.state()? {
+            RotationState::Overlap { previous_roots, .. } => {
+                Ok(merge_bundles(current, &previous_roots))
+            }
+            RotationState::Stable => Ok(current.to_string()),
+        }
+    }
+
+    /// Issues the workload CA again under the current root. Certificates the
+    /// old workload CA issued stay valid as long as their root is trusted.
+    pub fn reissue_workload_ca(&self) -> Result<()> {
+        let mut record = self.lock();
+        self.reissue()?;
+        if let RotationState::Overlap {
+            ref mut workload_ca_reissued,
+            ..
+        } = record.state
+        {
+            *workload_ca_reissued = true;
+        }
+        self.persist(&record)
+    }
+
+    /// Ends the overlap if it's over, issuing the workload CA again first if
+    /// that hasn't been done yet, since its root is no longer trusted.
+    pub fn expire(&self) -> Result<()> {
+        let now = self.clock.now();
+        let mut record = self.lock();
+        let reissued = match record.state {
+            RotationState::Overlap {
+                ends_at,
+                workload_ca_reissued,
+                ..
+            }
+                if ends_at <= now =>
+            {
+                workload_ca_reissued
+            }
+            _ => return Ok(()),
+        };
+
+        if !reissued {
+            self.reissue()?;
+        }
+        record.state = RotationState::Stable;
+        info!("The Edge CA rotation has ended. The previous roots are no longer trusted.");
+        self.persist(&record)
+    }
+
+    fn reissue(&self) -> Result<()> {
+        if let Some(ref reissue) = self.reissue {
+            info!("Issuing the workload CA under the current root...");
+            reissue().context(ErrorKind::CaRotation)?;
+            info!("Issued the workload CA under the current root.");
+        }
+        Ok(())
+    }
+
+    fn persist(&self, record: &Record) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::CaRotation)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::CaRotation)?;
+            serde_json::to_writer(file, record).context(ErrorKind::CaRotation)?;
+            fs::rename(&temp, path).context(ErrorKind::CaRotation)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<Record> {
+        self.record.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Overlaps are capped at a century, which keeps the end of one in the range
+// of dates that can be recorded.
+fn overlap_duration(overlap: StdDuration) -> Duration {
+    let max = Duration::days(100 * 365);
+    Duration::from_std(overlap).map_or(max, |overlap| overlap.min(max))
+}
+
+fn fingerprint(pem: &str) -> String {
+    let certs: String = certificates(pem).concat();
+    base64::encode(&Sha256::digest(certs.as_bytes()))
+}
+
+/// Splits a PEM bundle into its certificates, without the whitespace around
+/// them.
+pub fn certificates(bundle: &str) -> Vec<String> {
+    bundle
+        .split_terminator(PEM_END)
+        .map(str::trim)
+        .filter(|cert| !cert.is_empty())
+        .map(|cert| format!("{}\n{}\n", cert, PEM_END))
+        .collect()
+}
+
+/// The certificates of `first` followed by those of `second` that aren't in
+/// `first`.
+fn merge_bundles(first: &str, second: &str) -> String {
+    let mut merged = certificates(first);
+    for cert in certificates(second) {
+        if !merged.contains(&cert) {
+            merged.push(cert);
+        }
+    }
+    merged.concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tempdir::TempDir;
+
+    use super::*;
+    use clock::ManualClock;
+
+    const OLD_ROOT: &str = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+    const NEW_ROOT: &str = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+    const OLD_CA: &str = "-----BEGIN CERTIFICATE-----\nb2xkY2E=\n-----END CERTIFICATE-----\n";
+    const NEW_CA: &str = "-----BEGIN CERTIFICATE-----\nbmV3Y2E=\n-----END CERTIFICATE-----\n";
+
+    fn rotation() -> (CaRotation, ManualClock, Arc<Mutex<usize>>) {
+        let clock = ManualClock::new(Utc.ymd(2018, 10, 1).and_hms(0, 0, 0));
+        let reissued = Arc::new(Mutex::new(0));
+        let count = reissued.clone();
+        let rotation = CaRotation::new(StdDuration::from_secs(7 * 24 * 3600))
+            .with_clock(clock.clone())
+            .with_reissue(move || {
+                *count.lock().unwrap() += 1;
+                Ok(())
+            });
+        (rotation, clock, reissued)
+    }
+
+    #[test]
+    fn first_start_and_restarts_do_not_rotate() {
+        let (rotation, _, _) = rotation();
+        assert!(!rotation.observe(OLD_CA, OLD_ROOT).unwrap());
+        assert!(!rotation.observe(&format!("\n{}", OLD_CA), OLD_ROOT).unwrap());
+        assert_eq!(RotationState::Stable, rotation.state().unwrap());
+        assert_eq!(OLD_ROOT, rotation.trust_bundle(OLD_ROOT).unwrap());
+    }
+
+    #[test]
+    fn both_roots_are_trusted_during_the_overlap() {
+        let (rotation, clock, reissued) = rotation();
+        rotation.observe(OLD_CA, OLD_ROOT).unwrap();
+        assert!(rotation.observe(NEW_CA, NEW_ROOT).unwrap());
+
+        assert_eq!(
+            format!("{}{}", NEW_ROOT, OLD_ROOT),
+            rotation.trust_bundle(NEW_ROOT).unwrap()
+        );
+        assert_eq!(
+            Some(Utc.ymd(2018, 10, 8).and_hms(0, 0, 0)),
+            rotation.overlap_ends_at()
+        );
+        clock.advance(Duration::days(1));
+        assert_eq!(
+            Some(StdDuration::from_secs(6 * 24 * 3600)),
+            rotation.overlap_remaining()
+        );
+
+        rotation.reissue_workload_ca().unwrap();
+        assert_eq!(1, *reissued.lock().unwrap());
+        match rotation.state().unwrap() {
+            RotationState::Overlap {
+                workload_ca_reissued,
+                ..
+            } => assert!(workload_ca_reissued),
+            state => panic!("unexpected state {:?}", state),
+        }
+
+        clock.advance(Duration::days(6));
+        assert_eq!(NEW_ROOT, rotation.trust_bundle(NEW_ROOT).unwrap());
+        assert_eq!(RotationState::Stable, rotation.state().unwrap());
+        assert_eq!(1, *reissued.lock().unwrap());
+    }
+
+    #[test]
+    fn workload_ca_is_reissued_when_the_overlap_ends() {
+        let (rotation, clock, reissued) = rotation();
+        rotation.observe(OLD_CA, OLD_ROOT).unwrap();
+        rotation.observe(NEW_CA, NEW_ROOT).unwrap();
+
+        clock.advance(Duration::days(6));
+        rotation.expire().unwrap();
+        assert_eq!(0, *reissued.lock().unwrap());
+
+        clock.advance(Duration::days(1));
+        rotation.expire().unwrap();
+        assert_eq!(1, *reissued.lock().unwrap());
+        assert_eq!(None, rotation.overlap_ends_at());
+    }
+
+    #[test]
+    fn rotation_survives_restarts() {
+        let tmp_dir = TempDir::new("ca_rotation").unwrap();
+        let path = tmp_dir.path().join("ca_rotation.json");
+
+        let rotation = CaRotation::load(&path, StdDuration::from_secs(7 * 24 * 3600)).unwrap();
+        rotation.observe(OLD_CA, OLD_ROOT).unwrap();
+        let rotation = CaRotation::load(&path, StdDuration::from_secs(7 * 24 * 3600)).unwrap();
+        assert!(rotation.observe(NEW_CA, NEW_ROOT).unwrap());
+
+        let rotation = CaRotation::load(&path, StdDuration::from_secs(7 * 24 * 3600)).unwrap();
+        assert!(!rotation.observe(NEW_CA, NEW_ROOT).unwrap());
+        assert_eq!(
+            format!("{}{}", NEW_ROOT, OLD_ROOT),
+            rotation.trust_bundle(NEW_ROOT).unwrap()
+        );
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Cancellation of long-running operations.
+//!
+//! Dropping a future abandons it, but futures that are spawned, or that are
+//! waited on by a caller that is itself waiting on something else, are never
+//! dropped by whoever wants them to stop. A `CancellationToken` is handed to
+//! such operations instead, which give up as soon as it is cancelled and let
+//! go of the sockets they hold.
+//!
+//! ```ignore
+//! let token = CancellationToken::new();
+//! let pull = token.guard(registry.pull(&config));
+//! // ...on shutdown
+//! token.cancel();
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures::future::Shared;
+use futures::sync::oneshot::{self, Receiver, Sender};
+use futures::{Async, Future, Poll, Stream};
+
+use error::{Error, ErrorKind};
+
+struct Inner {
+    cancelled: AtomicBool,
+    sender: Mutex<Option<Sender<()>>>,
+    receiver: Shared<Receiver<()>>,
+}
+
+/// Cancels the operations it is handed to. Clones cancel the same
+/// operations.
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        CancellationToken {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                sender: Mutex::new(Some(sender)),
+                receiver: receiver.shared(),
+            }),
+        }
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Cancels the operations guarded by the token. Operations it guards
+    /// afterwards are cancelled before they start.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        if let Some(sender) = self
+            .inner
+            .sender
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            sender.send(()).unwrap_or(());
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// A future that resolves once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            _inner: self.inner.clone(),
+            receiver: self.inner.receiver.clone(),
+        }
+    }
+
+    /// Fails `future` with `ErrorKind::Cancelled` once the token is
+    /// cancelled, dropping it.
+    pub fn guard<F>(&self, future: F) -> Guarded<F>
+    where
+        F: Future,
+        F::Error: From<Error>,
+    {
+        Guarded {
+            inner: Some(future),
+            cancelled: self.cancelled(),
+        }
+    }
+
+    /// Ends `stream` once the token is cancelled, dropping it.
+    pub fn guard_stream<S: Stream>(&self, stream: S) -> GuardedStream<S> {
+        GuardedStream {
+            inner: Some(stream),
+            cancelled: self.cancelled(),
+        }
+    }
+}
+
+/// The future returned by `CancellationToken::cancelled`.
+pub struct Cancelled {
+    // Keeps the sender alive, so that the receiver only resolves on cancel.
+    _inner: Arc<Inner>,
+    receiver: Shared<Receiver<()>>,
+}
+
+impl Future for Cancelled {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.receiver.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(_)) | Err(_) => Ok(Async::Ready(())),
+        }
+    }
+}
+
+/// The future returned by `CancellationToken::guard`.
+pub struct Guarded<F> {
+    inner: Option<F>,
+    cancelled: Cancelled,
+}
+
+impl<F> Future for Guarded<F>
+where
+    F: Future,
+    F::Error: From<Error>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Ok(Async::Ready(())) = self.cancelled.poll() {
+            self.inner = None;
+        }
+        match self.inner {
+            Some(ref mut inner) => inner.poll(),
+            None => Err(F::Error::from(Error::from(ErrorKind::Cancelled))),
+        }
+    }
+}
+
+/// The stream returned by `CancellationToken::guard_stream`.
+pub struct GuardedStream<S> {
+    inner: Option<S>,
+    cancelled: Cancelled,
+}
+
+impl<S: Stream> Stream for GuardedStream<S> {
+    type Item = S::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Ok(Async::Ready(())) = self.cancelled.poll() {
+            self.inner = None;
+        }
+        match self.inner {
+            Some(ref mut inner) => inner.poll(),
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::future;
+    use futures::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn operations_run_until_cancelled() {
+        let token = CancellationToken::new();
+        assert!(!token.is_cancelled());
+        assert_eq!(
+            5,
+            token
+                .guard(future::ok::<_, Error>(5))
+                .wait()
+                .unwrap()
+        );
+
+        let pending = token.guard(future::empty::<(), Error>());
+        let cancel = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            cancel.cancel();
+        });
+        match *pending.wait().unwrap_err().kind() {
+            ErrorKind::Cancelled => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+        assert!(token.is_cancelled());
+
+        match *token
+            .guard(future::ok::<_, Error>(5))
+            .wait()
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::Cancelled => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn streams_end_when_cancelled() {
+        let token = CancellationToken::new();
+        let (sender, receiver) = mpsc::unbounded::<u32>();
+        sender.unbounded_send(1).unwrap();
+
+        let mut stream = token.guard_stream(receiver).wait();
+        assert_eq!(Some(Ok(1)), stream.next());
+        token.cancel();
+        assert_eq!(None, stream.next());
+        assert!(sender.unbounded_send(2).is_err());
+    }
+
+    #[test]
+    fn cancelled_resolves_for_every_clone() {
+        let token = CancellationToken::new();
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let cancelled = token.clone().cancelled();
+                thread::spawn(move || cancelled.wait())
+            }).collect();
+        token.cancel();
+        for waiter in waiters {
+            waiter.join().unwrap().unwrap();
+        }
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::cmp;
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use failure::ResultExt;
+use openssl::x509::X509;
+
+use error::{Error, ErrorKind, Result};
+
+/// How much of its lifetime must remain for a certificate to be handed out
+/// again, unless the policy says otherwise.
+const DEFAULT_REUSE_THRESHOLD_PERCENT: u8 = 50;
+
+/// The algorithm of the key of a certificate.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyAlgorithm {
+    Ec,
+    Rsa,
+}
+
+impl KeyAlgorithm {
+    /// The algorithm of the key of the first certificate in `pem`, or `None`
+    /// for keys of any other algorithm.
+    pub fn of_certificate(pem: &[u8]) -> Result<Option<Self>> {
+        let cert = X509::from_pem(pem).context(ErrorKind::InvalidCertificate)?;
+        let key = cert.public_key().context(ErrorKind::InvalidCertificate)?;
+        let algorithm = if key.ec_key().is_ok() {
+            Some(KeyAlgorithm::Ec)
+        } else if key.rsa().is_ok() {
+            Some(KeyAlgorithm::Rsa)
+        } else {
+            None
+        };
+        Ok(algorithm)
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyAlgorithm::Ec => write!(f, "ec"),
+            KeyAlgorithm::Rsa => write!(f, "rsa"),
+        }
+    }
+}
+
+/// The subject alternative names a certificate carries.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SanPolicy {
+    #[serde(default)]
+    module_uri: bool,
+    #[serde(default)]
+    dns_common_name: bool,
+}
+
+impl SanPolicy {
+    pub fn new() -> Self {
+        SanPolicy::default()
+    }
+
+    pub fn with_module_uri(mut self, module_uri: bool) -> Self {
+        self.module_uri = module_uri;
+        self
+    }
+
+    pub fn with_dns_common_name(mut self, dns_common_name: bool) -> Self {
+        self.dns_common_name = dns_common_name;
+        self
+    }
+
+    /// Whether the certificate carries the `azureiot://` URI of the module
+    /// it was issued to.
+    pub fn module_uri(&self) -> bool {
+        self.module_uri
+    }
+
+    /// Whether the certificate carries its common name as a DNS name, which
+    /// TLS clients that ignore the common name match the host name against.
+    pub fn dns_common_name(&self) -> bool {
+        self.dns_common_name
+    }
+}
+
+/// What the certificates of one type the workload API issues to modules
+/// look like: how long they are valid for at most, the algorithms their keys
+/// may have, when modules are told to renew them, when they are handed out
+/// again instead of issuing new ones and the subject alternative names they
+/// carry.
+///
+/// A certificate whose key has an algorithm the policy doesn't list is
+/// refused, after the HSM issued it when its properties leave the type of the
+/// key to the HSM. Any algorithm is allowed when none are listed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CertPolicy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_duration_secs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    key_algorithms: Vec<KeyAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    renewal_threshold_percent: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reuse_threshold_percent: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    san: Option<SanPolicy>,
+}
+
+impl CertPolicy {
+    pub fn new() -> Self {
+        CertPolicy::default()
+    }
+
+    pub fn with_max_duration(mut self, max_duration_secs: i64) -> Self {
+        self.max_duration_secs = Some(max_duration_secs);
+        self
+    }
+
+    pub fn with_key_algorithms(mut self, key_algorithms: Vec<KeyAlgorithm>) -> Self {
+        self.key_algorithms = key_algorithms;
+        self
+    }
+
+    pub fn with_renewal_threshold(mut self, percent: u8) -> Self {
+        self.renewal_threshold_percent = Some(percent);
+        self
+    }
+
+    pub fn with_reuse_threshold(mut self, percent: u8) -> Self {
+        self.reuse_threshold_percent = Some(percent);
+        self
+    }
+
+    pub fn with_san(mut self, san: SanPolicy) -> Self {
+        self.san = Some(san);
+        self
+    }
+
+    /// Caps the longest validity of the certificates at `max_duration_secs`,
+    /// or sets it to that if the policy has none.
+    pub fn capped(mut self, max_duration_secs: i64) -> Self {
+        let max_duration_secs = self
+            .max_duration_secs
+            .map_or(max_duration_secs, |max| cmp::min(max, max_duration_secs));
+        self.max_duration_secs = Some(max_duration_secs);
+        self
+    }
+
+    /// The longest validity, in seconds, of the certificates. No certificate
+    /// is valid for any time under a policy without one.
+    pub fn max_duration(&self) -> i64 {
+        self.max_duration_secs.unwrap_or(0)
+    }
+
+    pub fn key_algorithms(&self) -> &[KeyAlgorithm] {
+        &self.key_algorithms
+    }
+
+    /// The subject alternative names of the certificates, or `None` to leave
+    /// them to the type of the certificate.
+    pub fn san(&self) -> Option<SanPolicy> {
+        self.san
+    }
+
+    /// Fails if the key of the certificate `pem` has an algorithm the policy
+    /// doesn't allow.
+    pub fn check_key(&self, pem: &[u8]) -> Result<()> {
+        if self.key_algorithms.is_empty() {
+            return Ok(());
+        }
+        self.check_key_algorithm(KeyAlgorithm::of_certificate(pem)?)
+    }
+
+    /// Fails unless the policy allows keys of `algorithm`, where `None`
+    /// stands for keys of any algorithm other than those it knows.
+    pub fn check_key_algorithm(&self, algorithm: Option<KeyAlgorithm>) -> Result<()> {
+        match algorithm {
+            _ if self.key_algorithms.is_empty() => Ok(()),
+            Some(algorithm) if self.key_algorithms.contains(&algorithm) => Ok(()),
+            Some(algorithm) => Err(Error::from(ErrorKind::KeyAlgorithmNotAllowed(
+                algorithm.to_string(),
+            ))),
+            None => Err(Error::from(ErrorKind::KeyAlgorithmNotAllowed(
+                "unknown".to_string(),
+            ))),
+        }
+    }
+
+    /// When a certificate valid from `issued_at` until `expires_at` is due
+    /// to be renewed, once the renewal threshold of its lifetime has passed.
+    pub fn renew_at(
+        &self,
+        issued_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        self.renewal_threshold_percent.map(|percent| {
+            let lifetime = expires_at.signed_duration_since(issued_at).num_seconds();
+            let percent = i64::from(cmp::min(percent, 100));
+            issued_at + Duration::seconds(lifetime * percent / 100)
+        })
+    }
+
+    /// Whether a certificate valid from `issued_at` until `expires_at` can
+    /// still be handed out at `now` to a module asking for it again, which
+    /// it can while more than the reuse threshold of its lifetime remains
+    /// and it isn't due to be renewed yet. A threshold of 100 never reuses
+    /// certificates.
+    pub fn reusable(
+        &self,
+        issued_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let lifetime = expires_at.signed_duration_since(issued_at).num_seconds();
+        let remaining = expires_at.signed_duration_since(now).num_seconds();
+        let percent = self
+            .reuse_threshold_percent
+            .unwrap_or(DEFAULT_REUSE_THRESHOLD_PERCENT);
+        let percent = i64::from(cmp::min(percent, 100));
+        let due = self
+            .renew_at(issued_at, expires_at)
+            .map_or(false, |renew_at| now >= renew_at);
+        lifetime > 0 && remaining * 100 > lifetime * percent && !due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    use super::*;
+
+    fn ec_certificate() -> Vec<u8> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "sensor").unwrap();
+        let name = name.build();
+
+        let mut cert = X509Builder::new().unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        cert.build().to_pem().unwrap()
+    }
+
+    #[test]
+    fn keys_are_checked_against_the_allowed_algorithms() {
+        let pem = ec_certificate();
+        assert_eq!(
+            Some(KeyAlgorithm::Ec),
+            KeyAlgorithm::of_certificate(&pem).unwrap()
+        );
+        assert!(CertPolicy::new().check_key(&pem).is_ok());
+        assert!(CertPolicy::new()
+            .with_key_algorithms(vec![KeyAlgorithm::Rsa, KeyAlgorithm::Ec])
+            .check_key(&pem)
+            .is_ok());
+
+        let err = CertPolicy::new()
+            .with_key_algorithms(vec![KeyAlgorithm::Rsa])
+            .check_key(&pem)
+            .unwrap_err();
+        match *err.kind() {
+            ErrorKind::KeyAlgorithmNotAllowed(ref algorithm) => assert_eq!("ec", algorithm),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn durations_are_capped() {
+        assert_eq!(0, CertPolicy::new().max_duration());
+        assert_eq!(7200, CertPolicy::new().capped(7200).max_duration());
+        assert_eq!(
+            3600,
+            CertPolicy::new()
+                .with_max_duration(3600)
+                .capped(7200)
+                .max_duration()
+        );
+        assert_eq!(
+            7200,
+            CertPolicy::new()
+                .with_max_duration(9000)
+                .capped(7200)
+                .max_duration()
+        );
+    }
+
+    #[test]
+    fn renewal_is_due_after_the_threshold() {
+        let issued_at = Utc.ymd(2018, 10, 1).and_hms(0, 0, 0);
+        let expires_at = issued_at + Duration::hours(10);
+        assert_eq!(None, CertPolicy::new().renew_at(issued_at, expires_at));
+        assert_eq!(
+            Some(issued_at + Duration::hours(8)),
+            CertPolicy::new()
+                .with_renewal_threshold(80)
+                .renew_at(issued_at, expires_at)
+        );
+        assert_eq!(
+            Some(expires_at),
+            CertPolicy::new()
+                .with_renewal_threshold(150)
+                .renew_at(issued_at, expires_at)
+        );
+    }
+
+    #[test]
+    fn certificates_are_reused_above_the_threshold() {
+        let issued_at = Utc.ymd(2018, 10, 1).and_hms(0, 0, 0);
+        let expires_at = issued_at + Duration::hours(10);
+        let at = |hours| issued_at + Duration::hours(hours);
+
+        let policy = CertPolicy::new();
+        assert!(policy.reusable(issued_at, expires_at, at(4)));
+        assert!(!policy.reusable(issued_at, expires_at, at(5)));
+
+        let policy = CertPolicy::new().with_reuse_threshold(10);
+        assert!(policy.reusable(issued_at, expires_at, at(8)));
+        assert!(!policy.reusable(issued_at, expires_at, at(10)));
+
+        // a certificate due to be renewed is never handed out again
+        let policy = policy.with_renewal_threshold(70);
+        assert!(!policy.reusable(issued_at, expires_at, at(8)));
+
+        let policy = CertPolicy::new().with_reuse_threshold(100);
+        assert!(!policy.reusable(issued_at, expires_at, issued_at));
+    }
+}
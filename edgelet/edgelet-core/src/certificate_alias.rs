@@ -0,0 +1,198 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! The HSM aliases of the certificates modules ask for through the workload
+//! API.
+//!
+//! Aliases used to be the module ID with the generation ID and the kind of
+//! certificate appended, as in `<module><generation>server`, so module `ab`
+//! with generation `1` and module `a` with generation `b1` both got
+//! `ab1server`. They are now separated with a `/`, which neither module IDs
+//! nor generation IDs can contain. Aliases in the old format are still
+//! recognized, so that the certificates issued under them before the daemon
+//! was upgraded can be cleaned up.
+
+use std::fmt;
+
+const SEPARATOR: char = '/';
+const IDENTITY: &str = "identity";
+const SERVER: &str = "server";
+
+/// The alias of a certificate issued to a module.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum CertificateAlias {
+    /// The identity certificate of a module, which isn't tied to a
+    /// generation of its identity.
+    Identity { module_id: String },
+    /// The server certificate of a generation of a module.
+    Server {
+        module_id: String,
+        generation_id: String,
+    },
+}
+
+impl CertificateAlias {
+    pub fn identity(module_id: &str) -> Self {
+        CertificateAlias::Identity {
+            module_id: module_id.to_string(),
+        }
+    }
+
+    pub fn server(module_id: &str, generation_id: &str) -> Self {
+        CertificateAlias::Server {
+            module_id: module_id.to_string(),
+            generation_id: generation_id.to_string(),
+        }
+    }
+
+    /// Parses the alias of a certificate issued to `module_id`, in either
+    /// format. Returns `None` for aliases of other certificates.
+    pub fn parse(module_id: &str, alias: &str) -> Option<Self> {
+        CertificateAlias::parse_current(module_id, alias)
+            .or_else(|| CertificateAlias::parse_legacy(module_id, alias))
+    }
+
+    fn parse_current(module_id: &str, alias: &str) -> Option<Self> {
+        let mut parts = alias.split(SEPARATOR);
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(module), Some(IDENTITY), None, None) if module == module_id => {
+                Some(CertificateAlias::identity(module_id))
+            }
+            (Some(module), Some(generation_id), Some(SERVER), None)
+                if module == module_id && !generation_id.is_empty() =>
+            {
+                Some(CertificateAlias::server(module_id, generation_id))
+            }
+            _ => None,
+        }
+    }
+
+    // Without the module ID to go by, the old format can't be told apart.
+    fn parse_legacy(module_id: &str, alias: &str) -> Option<Self> {
+        if !alias.starts_with(module_id) {
+            return None;
+        }
+        let rest = &alias[module_id.len()..];
+        if rest == IDENTITY {
+            Some(CertificateAlias::identity(module_id))
+        } else if rest.len() > SERVER.len() && rest.ends_with(SERVER) {
+            let generation_id = &rest[..rest.len() - SERVER.len()];
+            Some(CertificateAlias::server(module_id, generation_id))
+        } else {
+            None
+        }
+    }
+
+    pub fn module_id(&self) -> &str {
+        match *self {
+            CertificateAlias::Identity { ref module_id }
+            | CertificateAlias::Server { ref module_id, .. } => module_id,
+        }
+    }
+
+    pub fn generation_id(&self) -> Option<&str> {
+        match *self {
+            CertificateAlias::Identity { .. } => None,
+            CertificateAlias::Server {
+                ref generation_id, ..
+            } => Some(generation_id),
+        }
+    }
+
+    /// The alias the certificate was kept under before the daemon separated
+    /// the parts of aliases.
+    pub fn legacy(&self) -> String {
+        match *self {
+            CertificateAlias::Identity { ref module_id } => format!("{}{}", module_id, IDENTITY),
+            CertificateAlias::Server {
+                ref module_id,
+                ref generation_id,
+            } => format!("{}{}{}", module_id, generation_id, SERVER),
+        }
+    }
+}
+
+impl fmt::Display for CertificateAlias {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CertificateAlias::Identity { ref module_id } => {
+                write!(f, "{}{}{}", module_id, SEPARATOR, IDENTITY)
+            }
+            CertificateAlias::Server {
+                ref module_id,
+                ref generation_id,
+            } => write!(
+                f,
+                "{}{}{}{}{}",
+                module_id, SEPARATOR, generation_id, SEPARATOR, SERVER
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_that_used_to_collide_are_distinct() {
+        let first = CertificateAlias::server("ab", "1");
+        let second = CertificateAlias::server("a", "b1");
+        assert_eq!(first.legacy(), second.legacy());
+        assert_ne!(first.to_string(), second.to_string());
+
+        let first = CertificateAlias::server("ab", "1s");
+        let second = CertificateAlias::server("a", "b1s");
+        assert_eq!("ab/1s/server", first.to_string());
+        assert_eq!("a/b1s/server", second.to_string());
+        assert_eq!(None, CertificateAlias::parse("a", &first.to_string()));
+        assert_eq!(
+            Some(second.clone()),
+            CertificateAlias::parse("a", &second.to_string())
+        );
+    }
+
+    #[test]
+    fn identity_alias_does_not_collide_with_server_alias() {
+        let identity = CertificateAlias::identity("m1").to_string();
+        let server = CertificateAlias::server("m1", "identity").to_string();
+        assert_eq!("m1/identity", identity);
+        assert_eq!("m1/identity/server", server);
+        assert_eq!(
+            Some(CertificateAlias::identity("m1")),
+            CertificateAlias::parse("m1", &identity)
+        );
+        assert_eq!(
+            Some(Some("identity")),
+            CertificateAlias::parse("m1", &server)
+                .as_ref()
+                .map(CertificateAlias::generation_id)
+        );
+    }
+
+    #[test]
+    fn legacy_aliases_are_recognized() {
+        assert_eq!(
+            Some(CertificateAlias::server("m1", "gen")),
+            CertificateAlias::parse("m1", "m1genserver")
+        );
+        assert_eq!(
+            Some(CertificateAlias::identity("m1")),
+            CertificateAlias::parse("m1", "m1identity")
+        );
+        assert_eq!(None, CertificateAlias::parse("m1", "m1server"));
+        assert_eq!(None, CertificateAlias::parse("m1", "m2genserver"));
+        assert_eq!(None, CertificateAlias::parse("m1", "iotedged-workload-ca"));
+    }
+
+    #[test]
+    fn legacy_alias_round_trips() {
+        let alias = CertificateAlias::server("m1", "gen");
+        assert_eq!("m1genserver", alias.legacy());
+        assert_eq!(
+            Some(alias.clone()),
+            CertificateAlias::parse("m1", &alias.legacy())
+        );
+        assert_eq!("m1", alias.module_id());
+        assert_eq!(Some("gen"), alias.generation_id());
+    }
+}
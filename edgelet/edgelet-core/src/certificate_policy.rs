@@ -0,0 +1,148 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use error::{Error, ErrorKind, Result};
+
+/// The module name in `allowed_names` whose patterns apply to the modules
+/// that have no patterns of their own.
+const ANY_MODULE: &str = "*";
+
+/// Limits the names modules may request in their server certificates.
+///
+/// A module may only request names that match one of its own patterns, or
+/// the patterns listed under `*` if it has none. A module with neither may
+/// request any name. Wildcard names (e.g. `*.contoso.com`) are refused unless
+/// `allow_wildcards` is set, whatever the patterns say.
+///
+/// A pattern is either a name, which only matches itself, or a name whose
+/// first label is `*`, which matches any name with one more label in its
+/// place. Names are compared without regard to case.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CertificatePolicy {
+    #[serde(default)]
+    allow_wildcards: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allowed_names: Vec<AllowedNames>,
+}
+
+/// The name patterns a module may request. These are a list rather than a map
+/// keyed by module name because the config file loader lower cases keys.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct AllowedNames {
+    module: String,
+    names: Vec<String>,
+}
+
+impl CertificatePolicy {
+    pub fn new() -> Self {
+        CertificatePolicy::default()
+    }
+
+    pub fn with_allow_wildcards(mut self, allow_wildcards: bool) -> Self {
+        self.allow_wildcards = allow_wildcards;
+        self
+    }
+
+    pub fn with_allowed_names(mut self, module: &str, patterns: Vec<String>) -> Self {
+        self.allowed_names.push(AllowedNames {
+            module: module.to_string(),
+            names: patterns,
+        });
+        self
+    }
+
+    pub fn allow_wildcards(&self) -> bool {
+        self.allow_wildcards
+    }
+
+    /// Fails with the first of `names` that `module` may not have in a
+    /// certificate. Every name a certificate carries, the common name as well
+    /// as any DNS names, has to be checked.
+    pub fn check(&self, module: &str, names: &[&str]) -> Result<()> {
+        let patterns = self
+            .patterns(module)
+            .or_else(|| self.patterns(ANY_MODULE));
+
+        for name in names {
+            let allowed = (self.allow_wildcards || !name.contains('*'))
+                && patterns.map_or(true, |patterns| {
+                    patterns.iter().any(|pattern| matches(pattern, name))
+                });
+            if !allowed {
+                return Err(Error::from(ErrorKind::CertificateNameNotAllowed(
+                    name.to_string(),
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn patterns(&self, module: &str) -> Option<&[String]> {
+        self.allowed_names
+            .iter()
+            .find(|allowed| allowed.module == module)
+            .map(|allowed| allowed.names.as_slice())
+    }
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    if pattern.starts_with("*.") {
+        let suffix = &pattern[1..];
+        let split = name.len().saturating_sub(suffix.len());
+        split > 0
+            && name.is_char_boundary(split)
+            && name[split..].eq_ignore_ascii_case(suffix)
+            && !name[..split].contains('.')
+    } else {
+        pattern.eq_ignore_ascii_case(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn default_policy_refuses_only_wildcards() {
+        let policy = CertificatePolicy::default();
+        assert!(policy.check("edgeHub", &["gateway.contoso.com"]).is_ok());
+        let err = policy.check("edgeHub", &["*.contoso.com"]).unwrap_err();
+        match *err.kind() {
+            ErrorKind::CertificateNameNotAllowed(ref name) => assert_eq!("*.contoso.com", name),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+        let policy = policy.with_allow_wildcards(true);
+        assert!(policy.check("edgeHub", &["*.contoso.com"]).is_ok());
+    }
+
+    #[test]
+    fn modules_are_limited_to_their_patterns() {
+        let policy = CertificatePolicy::new()
+            .with_allowed_names("edgeHub", patterns(&["gateway", "*.gateway.contoso.com"]))
+            .with_allowed_names("*", patterns(&["localhost"]));
+
+        assert!(policy.check("edgeHub", &["Gateway"]).is_ok());
+        assert!(
+            policy
+                .check("edgeHub", &["gateway", "mqtt.gateway.contoso.com"])
+                .is_ok()
+        );
+        assert!(policy.check("edgeHub", &["gateway.contoso.com"]).is_err());
+        assert!(policy.check("edgeHub", &["a.b.gateway.contoso.com"]).is_err());
+        assert!(policy.check("edgeHub", &["gateway", "localhost"]).is_err());
+
+        assert!(policy.check("sensor", &["localhost"]).is_ok());
+        assert!(policy.check("sensor", &["gateway"]).is_err());
+    }
+
+    #[test]
+    fn wildcard_patterns_do_not_allow_wildcard_names() {
+        let policy =
+            CertificatePolicy::new().with_allowed_names("edgeHub", patterns(&["*.contoso.com"]));
+        assert!(policy.check("edgeHub", &["*.contoso.com"]).is_err());
+    }
+}
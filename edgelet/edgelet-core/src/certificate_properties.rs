@@ -1,5 +1,12 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::net::IpAddr;
+
+use serde::de::{self, Deserialize, Deserializer, Unexpected};
+use serde::ser::{Serialize, Serializer};
+
+use cert_policy::KeyAlgorithm;
+
 /// Enumerator for `CERTIFICATE_TYPE`
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CertificateType {
@@ -16,6 +23,91 @@ pub enum CertificateIssuer {
     DeviceCa,
 }
 
+/// The elliptic curves the keys of certificates can be on.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum EcCurve {
+    #[serde(rename = "P-256")]
+    P256,
+    #[serde(rename = "P-384")]
+    P384,
+}
+
+/// The lengths in bits the RSA keys of certificates can have.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RsaKeyLength {
+    Bits2048,
+    Bits3072,
+    Bits4096,
+}
+
+impl RsaKeyLength {
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            2048 => Some(RsaKeyLength::Bits2048),
+            3072 => Some(RsaKeyLength::Bits3072),
+            4096 => Some(RsaKeyLength::Bits4096),
+            _ => None,
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        match *self {
+            RsaKeyLength::Bits2048 => 2048,
+            RsaKeyLength::Bits3072 => 3072,
+            RsaKeyLength::Bits4096 => 4096,
+        }
+    }
+}
+
+impl Serialize for RsaKeyLength {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for RsaKeyLength {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u32::deserialize(deserializer)?;
+        RsaKeyLength::from_bits(bits).ok_or_else(|| {
+            de::Error::invalid_value(Unexpected::Unsigned(bits.into()), &"2048, 3072 or 4096")
+        })
+    }
+}
+
+/// The type of the key a certificate is issued with. RSA keys without a
+/// length have the default length of the type of the certificate.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum KeyType {
+    Rsa {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        length: Option<RsaKeyLength>,
+    },
+    Ec {
+        curve: EcCurve,
+    },
+}
+
+impl KeyType {
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        match *self {
+            KeyType::Rsa { .. } => KeyAlgorithm::Rsa,
+            KeyType::Ec { .. } => KeyAlgorithm::Ec,
+        }
+    }
+
+    /// This key type, with the length of `default` if this is an RSA key
+    /// without a length and `default` is an RSA key with one.
+    pub fn with_default_length(self, default: Option<KeyType>) -> Self {
+        match (self, default) {
+            (KeyType::Rsa { length: None }, Some(KeyType::Rsa { length })) => {
+                KeyType::Rsa { length }
+            }
+            (key_type, _) => key_type,
+        }
+    }
+}
+
 /// Globally supported properties of certificates in the Edge.
 #[derive(Debug, Clone)]
 pub struct CertificateProperties {
@@ -25,6 +117,9 @@ pub struct CertificateProperties {
     alias: String,
     issuer: CertificateIssuer,
     san_entries: Option<Vec<String>>,
+    dns_names: Vec<String>,
+    ip_addresses: Vec<IpAddr>,
+    key_type: Option<KeyType>,
 }
 
 impl CertificateProperties {
@@ -41,6 +136,9 @@ impl CertificateProperties {
             alias,
             issuer: CertificateIssuer::DefaultCa,
             san_entries: None,
+            dns_names: vec![],
+            ip_addresses: vec![],
+            key_type: None,
         }
     }
 
@@ -97,10 +195,46 @@ impl CertificateProperties {
         self.san_entries = Some(entries);
         self
     }
+
+    /// The DNS names the certificate is valid for besides its common name,
+    /// which it carries as subject alternative names along with the entries
+    /// in `san_entries`.
+    pub fn dns_names(&self) -> &[String] {
+        &self.dns_names
+    }
+
+    pub fn with_dns_names(mut self, dns_names: Vec<String>) -> Self {
+        self.dns_names = dns_names;
+        self
+    }
+
+    /// The IP addresses the certificate is valid for, which it carries as
+    /// subject alternative names along with the entries in `san_entries`.
+    pub fn ip_addresses(&self) -> &[IpAddr] {
+        &self.ip_addresses
+    }
+
+    pub fn with_ip_addresses(mut self, ip_addresses: Vec<IpAddr>) -> Self {
+        self.ip_addresses = ip_addresses;
+        self
+    }
+
+    /// The type of the key of the certificate, or `None` for a key of the
+    /// type of the key of its issuer.
+    pub fn key_type(&self) -> Option<KeyType> {
+        self.key_type
+    }
+
+    pub fn with_key_type(mut self, key_type: KeyType) -> Self {
+        self.key_type = Some(key_type);
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use serde_json;
+
     use super::*;
 
     #[test]
@@ -118,6 +252,9 @@ mod tests {
         assert_eq!("alias", c.alias());
         assert_eq!(&CertificateIssuer::DefaultCa, c.issuer());
         assert_eq!(true, c.san_entries().is_none());
+        assert_eq!(true, c.dns_names().is_empty());
+        assert_eq!(true, c.ip_addresses().is_empty());
+        assert_eq!(None, c.key_type());
     }
 
     #[test]
@@ -133,12 +270,93 @@ mod tests {
         .with_validity_in_secs(240)
         .with_alias("Andrew Johnson".to_string())
         .with_issuer(CertificateIssuer::DeviceCa)
-        .with_san_entries(input_sans.clone());
+        .with_san_entries(input_sans.clone())
+        .with_dns_names(vec!["gateway".to_string()])
+        .with_ip_addresses(vec!["10.0.0.1".parse().unwrap()])
+        .with_key_type(KeyType::Ec {
+            curve: EcCurve::P384,
+        });
         assert_eq!(&240, c.validity_in_secs());
         assert_eq!("bafflegab", c.common_name());
         assert_eq!(&CertificateType::Ca, c.certificate_type());
         assert_eq!("Andrew Johnson", c.alias());
         assert_eq!(&CertificateIssuer::DeviceCa, c.issuer());
         assert_eq!(&*input_sans, c.san_entries().unwrap());
+        assert_eq!(&["gateway".to_string()], c.dns_names());
+        assert_eq!("10.0.0.1", c.ip_addresses()[0].to_string());
+        assert_eq!(
+            Some(KeyType::Ec {
+                curve: EcCurve::P384
+            }),
+            c.key_type()
+        );
+    }
+
+    #[test]
+    fn key_types_are_tagged_with_their_type() {
+        let ec: KeyType = serde_json::from_str(r#"{"type":"ec","curve":"P-256"}"#).unwrap();
+        assert_eq!(
+            KeyType::Ec {
+                curve: EcCurve::P256
+            },
+            ec
+        );
+        assert_eq!(KeyAlgorithm::Ec, ec.algorithm());
+
+        let rsa: KeyType = serde_json::from_str(r#"{"type":"rsa"}"#).unwrap();
+        assert_eq!(KeyType::Rsa { length: None }, rsa);
+        assert_eq!(KeyAlgorithm::Rsa, rsa.algorithm());
+        assert_eq!(r#"{"type":"rsa"}"#, serde_json::to_string(&rsa).unwrap());
+
+        assert!(serde_json::from_str::<KeyType>(r#"{"type":"ec","curve":"P-521"}"#).is_err());
+    }
+
+    #[test]
+    fn rsa_keys_are_2048_3072_or_4096_bits() {
+        let rsa: KeyType = serde_json::from_str(r#"{"type":"rsa","length":3072}"#).unwrap();
+        assert_eq!(
+            KeyType::Rsa {
+                length: Some(RsaKeyLength::Bits3072)
+            },
+            rsa
+        );
+        assert_eq!(
+            r#"{"type":"rsa","length":3072}"#,
+            serde_json::to_string(&rsa).unwrap()
+        );
+        assert_eq!(Some(RsaKeyLength::Bits4096), RsaKeyLength::from_bits(4096));
+        assert_eq!(None, RsaKeyLength::from_bits(1024));
+
+        assert!(serde_json::from_str::<KeyType>(r#"{"type":"rsa","length":1024}"#).is_err());
+        assert!(serde_json::from_str::<KeyType>(r#"{"type":"rsa","length":-1}"#).is_err());
+    }
+
+    #[test]
+    fn rsa_keys_without_a_length_take_the_default_length() {
+        let default = KeyType::Rsa {
+            length: Some(RsaKeyLength::Bits3072),
+        };
+        assert_eq!(
+            default,
+            KeyType::Rsa { length: None }.with_default_length(Some(default))
+        );
+
+        let rsa = KeyType::Rsa {
+            length: Some(RsaKeyLength::Bits2048),
+        };
+        assert_eq!(rsa, rsa.with_default_length(Some(default)));
+
+        let ec = KeyType::Ec {
+            curve: EcCurve::P256,
+        };
+        assert_eq!(ec, ec.with_default_length(Some(default)));
+        assert_eq!(
+            KeyType::Rsa { length: None },
+            KeyType::Rsa { length: None }.with_default_length(Some(ec))
+        );
+        assert_eq!(
+            KeyType::Rsa { length: None },
+            KeyType::Rsa { length: None }.with_default_length(None)
+        );
     }
 }
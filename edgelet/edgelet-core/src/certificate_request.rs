@@ -0,0 +1,122 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use failure::ResultExt;
+use openssl::nid::Nid;
+use openssl::x509::X509Req;
+
+use cert_policy::KeyAlgorithm;
+use error::{Error, ErrorKind, Result};
+
+/// A PEM certificate signing request of a module that keeps the private key
+/// of its certificate to itself, such as in a TPM or a TEE.
+///
+/// Only the public key and the common name are taken from the request. The
+/// rest of the certificate, including its subject alternative names, is up
+/// to the certificate properties it is issued with.
+#[derive(Clone, Debug)]
+pub struct CertificateRequest {
+    pem: Vec<u8>,
+    common_name: String,
+    key_algorithm: Option<KeyAlgorithm>,
+}
+
+impl CertificateRequest {
+    /// Parses the request in `pem`, which has to be signed with the private
+    /// key that goes with the public key in it and carry a common name.
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        let req = X509Req::from_pem(pem).context(ErrorKind::InvalidCertificateRequest)?;
+        let key = req
+            .public_key()
+            .context(ErrorKind::InvalidCertificateRequest)?;
+        if !req
+            .verify(&key)
+            .context(ErrorKind::InvalidCertificateRequest)?
+        {
+            return Err(Error::from(ErrorKind::InvalidCertificateRequest));
+        }
+
+        let common_name = req
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .and_then(|entry| entry.data().as_utf8().ok())
+            .map(|common_name| common_name.to_string())
+            .ok_or_else(|| Error::from(ErrorKind::InvalidCertificateRequest))?;
+        let key_algorithm = if key.ec_key().is_ok() {
+            Some(KeyAlgorithm::Ec)
+        } else if key.rsa().is_ok() {
+            Some(KeyAlgorithm::Rsa)
+        } else {
+            None
+        };
+
+        Ok(CertificateRequest {
+            pem: pem.to_vec(),
+            common_name,
+            key_algorithm,
+        })
+    }
+
+    pub fn pem(&self) -> &[u8] {
+        &self.pem
+    }
+
+    pub fn common_name(&self) -> &str {
+        &self.common_name
+    }
+
+    /// The algorithm of the public key, or `None` for keys of any other
+    /// algorithm.
+    pub fn key_algorithm(&self) -> Option<KeyAlgorithm> {
+        self.key_algorithm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, Private};
+    use openssl::x509::{X509NameBuilder, X509ReqBuilder};
+
+    use super::*;
+
+    fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    fn request(common_name: Option<&str>, key: &PKey<Private>, signer: &PKey<Private>) -> Vec<u8> {
+        let mut name = X509NameBuilder::new().unwrap();
+        if let Some(common_name) = common_name {
+            name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+                .unwrap();
+        }
+        let mut req = X509ReqBuilder::new().unwrap();
+        req.set_subject_name(&name.build()).unwrap();
+        req.set_pubkey(key).unwrap();
+        req.sign(signer, MessageDigest::sha256()).unwrap();
+        req.build().to_pem().unwrap()
+    }
+
+    #[test]
+    fn requests_must_be_signed_with_their_key() {
+        let key = key();
+        let pem = request(Some("sensor"), &key, &key);
+        let req = CertificateRequest::from_pem(&pem).unwrap();
+        assert_eq!("sensor", req.common_name());
+        assert_eq!(Some(KeyAlgorithm::Ec), req.key_algorithm());
+        assert_eq!(&pem[..], req.pem());
+
+        for pem in vec![
+            request(Some("sensor"), &key, &self::key()),
+            request(None, &key, &key),
+            b"-----BEGIN CERTIFICATE REQUEST-----".to_vec(),
+        ] {
+            match *CertificateRequest::from_pem(&pem).unwrap_err().kind() {
+                ErrorKind::InvalidCertificateRequest => (),
+                ref kind => panic!("unexpected error kind {:?}", kind),
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Verification of certificate chains against the trust bundle, for modules
+//! that have to check the certificates of downstream devices but are too
+//! small to carry a TLS library of their own.
+
+use chrono::{DateTime, TimeZone, Utc};
+use failure::ResultExt;
+use openssl::asn1::Asn1TimeRef;
+use openssl::nid::Nid;
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::{X509Ref, X509StoreContext, X509};
+
+use error::{ErrorKind, Result};
+
+// How OpenSSL prints ASN.1 times, e.g. `Jan  1 00:00:00 2030 GMT`.
+const ASN1_TIME_FORMAT: &str = "%b %e %H:%M:%S %Y GMT";
+
+/// A certificate of a verified chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedCertificate {
+    common_name: Option<String>,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+}
+
+impl VerifiedCertificate {
+    fn new(cert: &X509Ref) -> Result<Self> {
+        let common_name = cert
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .and_then(|entry| entry.data().as_utf8().ok())
+            .map(|name| name.to_string());
+        Ok(VerifiedCertificate {
+            common_name,
+            not_before: parse_time(cert.not_before())?,
+            not_after: parse_time(cert.not_after())?,
+        })
+    }
+
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn not_before(&self) -> DateTime<Utc> {
+        self.not_before
+    }
+
+    pub fn not_after(&self) -> DateTime<Utc> {
+        self.not_after
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now < self.not_before || self.not_after <= now
+    }
+}
+
+/// The outcome of verifying a chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainVerification {
+    error: Option<String>,
+    error_depth: Option<u32>,
+    certificates: Vec<VerifiedCertificate>,
+}
+
+impl ChainVerification {
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Why the chain isn't valid, as OpenSSL puts it.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(AsRef::as_ref)
+    }
+
+    /// The position in the chain of the certificate that failed
+    /// verification, where the leaf is 0.
+    pub fn error_depth(&self) -> Option<u32> {
+        self.error_depth
+    }
+
+    /// The certificates of the chain as it was given, leaf first.
+    pub fn certificates(&self) -> &[VerifiedCertificate] {
+        &self.certificates
+    }
+}
+
+/// Verifies the PEM chain `chain`, the leaf first and then the intermediates
+/// it was issued by, against the roots of the PEM `trust_bundle`.
+///
+/// OpenSSL builds the chain up to a root, checks that every certificate in it
+/// is valid at the current time and that every issuer is a CA whose key usage
+/// allows signing certificates. A chain that doesn't verify isn't an error,
+/// only one that can't be parsed is.
+pub fn verify_chain(chain: &[u8], trust_bundle: &[u8]) -> Result<ChainVerification> {
+    let mut certs = X509::stack_from_pem(chain).context(ErrorKind::InvalidCertificate)?;
+    if certs.is_empty() {
+        return Err(ErrorKind::InvalidCertificate.into());
+    }
+    let certificates = certs
+        .iter()
+        .map(|cert| VerifiedCertificate::new(cert))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut store = X509StoreBuilder::new().context(ErrorKind::InvalidCertificate)?;
+    for root in X509::stack_from_pem(trust_bundle).context(ErrorKind::InvalidCertificate)? {
+        store
+            .add_cert(root)
+            .context(ErrorKind::InvalidCertificate)?;
+    }
+    let store = store.build();
+
+    let leaf = certs.remove(0);
+    let mut intermediates = Stack::new().context(ErrorKind::InvalidCertificate)?;
+    for cert in certs {
+        intermediates
+            .push(cert)
+            .context(ErrorKind::InvalidCertificate)?;
+    }
+
+    let mut context = X509StoreContext::new().context(ErrorKind::InvalidCertificate)?;
+    let (valid, error, error_depth) = context
+        .init(&store, &leaf, &intermediates, |context| {
+            let valid = context.verify_cert()?;
+            Ok((valid, context.error(), context.error_depth()))
+        }).context(ErrorKind::InvalidCertificate)?;
+
+    Ok(ChainVerification {
+        error: if valid {
+            None
+        } else {
+            Some(error.error_string().to_string())
+        },
+        error_depth: if valid { None } else { Some(error_depth) },
+        certificates,
+    })
+}
+
+/// When the first certificate in the PEM `pem` expires.
+pub fn certificate_expiry(pem: &[u8]) -> Result<DateTime<Utc>> {
+    let cert = X509::from_pem(pem).context(ErrorKind::InvalidCertificate)?;
+    parse_time(cert.not_after())
+}
+
+fn parse_time(time: &Asn1TimeRef) -> Result<DateTime<Utc>> {
+    let time = Utc
+        .datetime_from_str(&time.to_string(), ASN1_TIME_FORMAT)
+        .context(ErrorKind::InvalidCertificate)?;
+    Ok(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::{BigNum, MsbOption};
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, Private};
+    use openssl::x509::extension::{BasicConstraints, KeyUsage};
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    use super::*;
+
+    fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    // Issues a certificate for `name` valid for `days` from now, signed by
+    // `issuer`, or self-signed without one.
+    fn issue(
+        name: &str,
+        key: &PKey<Private>,
+        issuer: Option<(&X509, &PKey<Private>)>,
+        ca: bool,
+        days: u32,
+    ) -> X509 {
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+        let subject = subject.build();
+        let mut serial = BigNum::new().unwrap();
+        serial.rand(64, MsbOption::MAYBE_ZERO, false).unwrap();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&serial.to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(days).unwrap())
+            .unwrap();
+        if ca {
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+            builder
+                .append_extension(KeyUsage::new().key_cert_sign().build().unwrap())
+                .unwrap();
+        } else {
+            builder
+                .append_extension(KeyUsage::new().digital_signature().build().unwrap())
+                .unwrap();
+        }
+        match issuer {
+            Some((issuer, issuer_key)) => {
+                builder.set_issuer_name(issuer.subject_name()).unwrap();
+                builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+            }
+            None => {
+                builder.set_issuer_name(&subject).unwrap();
+                builder.sign(key, MessageDigest::sha256()).unwrap();
+            }
+        }
+        builder.build()
+    }
+
+    fn pem(certs: &[&X509]) -> Vec<u8> {
+        certs
+            .iter()
+            .flat_map(|cert| cert.to_pem().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn chain_through_an_intermediate_verifies() {
+        let (root_key, ca_key, leaf_key) = (key(), key(), key());
+        let root = issue("root", &root_key, None, true, 30);
+        let ca = issue("ca", &ca_key, Some((&root, &root_key)), true, 30);
+        let leaf = issue("device", &leaf_key, Some((&ca, &ca_key)), false, 30);
+
+        let verification = verify_chain(&pem(&[&leaf, &ca]), &pem(&[&root])).unwrap();
+
+        assert!(verification.is_valid());
+        assert_eq!(None, verification.error_depth());
+        let names: Vec<_> = verification
+            .certificates()
+            .iter()
+            .map(|cert| cert.common_name().unwrap())
+            .collect();
+        assert_eq!(vec!["device", "ca"], names);
+        assert!(!verification.certificates()[0].is_expired(Utc::now()));
+    }
+
+    #[test]
+    fn chain_from_another_root_does_not_verify() {
+        let (root_key, other_key, leaf_key) = (key(), key(), key());
+        let root = issue("root", &root_key, None, true, 30);
+        let other = issue("other", &other_key, None, true, 30);
+        let leaf = issue("device", &leaf_key, Some((&other, &other_key)), false, 30);
+
+        let verification = verify_chain(&pem(&[&leaf]), &pem(&[&root])).unwrap();
+
+        assert!(!verification.is_valid());
+        assert!(verification.error().is_some());
+        assert_eq!(Some(0), verification.error_depth());
+    }
+
+    #[test]
+    fn issuer_that_is_not_a_ca_does_not_verify() {
+        let (root_key, issuer_key, leaf_key) = (key(), key(), key());
+        let root = issue("root", &root_key, None, true, 30);
+        let issuer = issue("issuer", &issuer_key, Some((&root, &root_key)), false, 30);
+        let leaf = issue("device", &leaf_key, Some((&issuer, &issuer_key)), false, 30);
+
+        let verification = verify_chain(&pem(&[&leaf, &issuer]), &pem(&[&root])).unwrap();
+
+        assert!(!verification.is_valid());
+        assert_eq!(Some(1), verification.error_depth());
+    }
+
+    #[test]
+    fn garbage_is_an_error() {
+        assert!(verify_chain(b"not a certificate", b"").is_err());
+        assert!(certificate_expiry(b"not a certificate").is_err());
+    }
+
+    #[test]
+    fn expiry_is_that_of_the_first_certificate() {
+        let (root_key, ca_key) = (key(), key());
+        let root = issue("root", &root_key, None, true, 30);
+        let ca = issue("ca", &ca_key, Some((&root, &root_key)), true, 10);
+
+        let expiry = certificate_expiry(&pem(&[&ca, &root])).unwrap();
+        let days = expiry.signed_duration_since(Utc::now()).num_days();
+        assert!(days >= 9 && days <= 10);
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Fault injection
+//!
+//! Resilience tests of the edge runtime modules need the daemon to misbehave
+//! on demand. With the `chaos` feature, calls into the HSM, docker operations
+//! and requests to IoT Hub are hooked so that they can be delayed or made to
+//! fail. What happens is scripted as a list of faults, each of which applies
+//! to the calls of one target, or only to one of its operations, with some
+//! probability. The faults that apply to a call are rolled in turn and the
+//! first one that fires decides what happens to it.
+//!
+//! The faults are process wide, so that the hooks don't have to be threaded
+//! through everything that makes the calls.
+
+use std::fmt;
+use std::sync::{PoisonError, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either};
+use futures::Future;
+use rand::{self, Rng};
+use tokio::timer::Delay;
+
+use error::{Error, ErrorKind};
+
+lazy_static! {
+    static ref FAULTS: RwLock<Vec<Fault>> = RwLock::new(Vec::new());
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FaultTarget {
+    Hsm,
+    Docker,
+    Hub,
+}
+
+impl fmt::Display for FaultTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            FaultTarget::Hsm => "hsm",
+            FaultTarget::Docker => "docker",
+            FaultTarget::Hub => "hub",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fault {
+    target: FaultTarget,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operation: Option<String>,
+    probability: f64,
+    #[serde(default)]
+    delay_ms: u64,
+    #[serde(default)]
+    fail: bool,
+}
+
+impl Fault {
+    pub fn new(target: FaultTarget, probability: f64) -> Self {
+        Fault {
+            target,
+            operation: None,
+            probability,
+            delay_ms: 0,
+            fail: false,
+        }
+    }
+
+    pub fn with_operation(mut self, operation: &str) -> Self {
+        self.operation = Some(operation.to_string());
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay_ms = delay.as_secs() * 1000 + u64::from(delay.subsec_millis());
+        self
+    }
+
+    pub fn with_failure(mut self, fail: bool) -> Self {
+        self.fail = fail;
+        self
+    }
+
+    pub fn target(&self) -> FaultTarget {
+        self.target
+    }
+
+    pub fn operation(&self) -> Option<&str> {
+        self.operation.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn probability(&self) -> f64 {
+        self.probability
+    }
+
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+
+    pub fn fails(&self) -> bool {
+        self.fail
+    }
+
+    fn applies_to(&self, target: FaultTarget, operation: &str) -> bool {
+        self.target == target && self.operation().map_or(true, |op| op == operation)
+    }
+
+    fn result(&self, operation: &str) -> Result<(), Error> {
+        if self.fail {
+            Err(Error::from(ErrorKind::FaultInjected(format!(
+                "{} {}",
+                self.target, operation
+            ))))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Replaces the faults being injected.
+pub fn set_faults(faults: Vec<Fault>) -> Result<(), Error> {
+    if let Some(fault) = faults.iter().find(|fault| !is_probability(fault.probability)) {
+        return Err(Error::from(ErrorKind::InvalidFault(format!(
+            "probability {} is not between 0 and 1",
+            fault.probability
+        ))));
+    }
+
+    *FAULTS.write().unwrap_or_else(PoisonError::into_inner) = faults;
+    Ok(())
+}
+
+pub fn faults() -> Vec<Fault> {
+    FAULTS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Injects the fault that fires, if any, into a blocking call. The call is
+/// held up for the fault's delay, and fails if the fault makes calls fail.
+pub fn inject(target: FaultTarget, operation: &str) -> Result<(), Error> {
+    match fire(target, operation) {
+        Some(fault) => {
+            thread::sleep(fault.delay());
+            fault.result(operation)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Injects the fault that fires, if any, into a call made from a future. The
+/// returned future must complete before the call is made.
+pub fn inject_async(
+    target: FaultTarget,
+    operation: &str,
+) -> impl Future<Item = (), Error = Error> + Send {
+    match fire(target, operation) {
+        Some(fault) => {
+            let result = fault.result(operation);
+            Either::A(
+                Delay::new(Instant::now() + fault.delay())
+                    .map_err(Error::from)
+                    .and_then(|()| result),
+            )
+        }
+        None => Either::B(future::ok(())),
+    }
+}
+
+fn fire(target: FaultTarget, operation: &str) -> Option<Fault> {
+    let faults = FAULTS.read().unwrap_or_else(PoisonError::into_inner);
+    let fault = faults
+        .iter()
+        .filter(|fault| fault.applies_to(target, operation))
+        .find(|fault| rand::thread_rng().gen::<f64>() < fault.probability)
+        .cloned();
+    if let Some(ref fault) = fault {
+        debug!(
+            "Injecting a fault into {} {} (delay {:?}, fail {})",
+            target,
+            operation,
+            fault.delay(),
+            fault.fail
+        );
+    }
+    fault
+}
+
+fn is_probability(value: f64) -> bool {
+    value >= 0.0 && value <= 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use tokio;
+
+    use super::*;
+
+    #[test]
+    fn faults_apply_to_their_target_and_operation() {
+        let fault = Fault::new(FaultTarget::Docker, 1.0);
+        assert!(fault.applies_to(FaultTarget::Docker, "start"));
+        assert!(!fault.applies_to(FaultTarget::Hub, "start"));
+
+        let fault = fault.with_operation("create");
+        assert!(fault.applies_to(FaultTarget::Docker, "create"));
+        assert!(!fault.applies_to(FaultTarget::Docker, "start"));
+    }
+
+    #[test]
+    fn faults_deserialize_with_defaults() {
+        let fault: Fault = serde_json::from_str(
+            r#"{ "target": "hsm", "operation": "sign", "probability": 0.5, "delayMs": 200 }"#,
+        ).unwrap();
+        assert_eq!(
+            Fault::new(FaultTarget::Hsm, 0.5)
+                .with_operation("sign")
+                .with_delay(Duration::from_millis(200)),
+            fault
+        );
+        assert!(!fault.fails());
+    }
+
+    #[test]
+    fn probabilities_out_of_range_are_refused() {
+        for probability in &[-0.1, 1.5, ::std::f64::NAN] {
+            let err = set_faults(vec![Fault::new(FaultTarget::Hub, *probability)]).unwrap_err();
+            match *err.kind() {
+                ErrorKind::InvalidFault(_) => (),
+                ref kind => panic!("unexpected error kind {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn certain_faults_always_fire() {
+        // The faults are process wide, so this is the only test that sets
+        // any.
+        set_faults(vec![
+            Fault::new(FaultTarget::Hsm, 0.0).with_failure(true),
+            Fault::new(FaultTarget::Hsm, 1.0)
+                .with_operation("encrypt")
+                .with_failure(true),
+            Fault::new(FaultTarget::Docker, 1.0).with_delay(Duration::from_millis(50)),
+        ]).unwrap();
+
+        assert!(inject(FaultTarget::Hsm, "decrypt").is_ok());
+        match *inject(FaultTarget::Hsm, "encrypt").unwrap_err().kind() {
+            ErrorKind::FaultInjected(ref call) => assert_eq!("hsm encrypt", call),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+        assert!(inject(FaultTarget::Hub, "get").is_ok());
+
+        let start = Instant::now();
+        let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+        runtime
+            .block_on(inject_async(FaultTarget::Docker, "start"))
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        set_faults(vec![]).unwrap();
+        assert!(faults().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// The source of the current time for logic that depends on it, such as
+/// certificate validity and expiry, token expiry and missed heartbeats. Tests
+/// use a `ManualClock` to put the time exactly where an edge case is rather
+/// than racing the system clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that stands still until it is set or advanced. A test keeps a
+/// clone to move the time of the clock it hands out.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now = *now + duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The time of the daemon corrected by the skew of its clock against a
+/// trusted source, such as the `Date` of the responses of IoT Hub. Devices
+/// with a broken RTC boot with a time that is far off, which this corrects
+/// for once the skew has been measured. Until then it is the time of the
+/// clock it wraps. The skew is recorded by the HTTP client from the responses
+/// of IoT Hub, and used by the workload API it was handed to.
+#[derive(Clone)]
+pub struct TrustedClock {
+    clock: Arc<Clock>,
+    skew: Arc<Mutex<Option<Duration>>>,
+}
+
+impl TrustedClock {
+    pub fn new() -> Self {
+        TrustedClock {
+            clock: Arc::new(SystemClock),
+            skew: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Measures the skew against the time of `clock` instead of the system
+    /// clock.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Records `trusted` as the time it is now according to the trusted
+    /// source.
+    pub fn record(&self, trusted: DateTime<Utc>) {
+        let skew = trusted.signed_duration_since(self.clock.now());
+        *self.skew.lock().unwrap_or_else(PoisonError::into_inner) = Some(skew);
+    }
+
+    /// How far the trusted time is ahead of the clock, once it was measured.
+    pub fn skew(&self) -> Option<Duration> {
+        *self.skew.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for TrustedClock {
+    fn default() -> Self {
+        TrustedClock::new()
+    }
+}
+
+impl Clock for TrustedClock {
+    fn now(&self) -> DateTime<Utc> {
+        let now = self.clock.now();
+        self.skew().map_or(now, |skew| now + skew)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn manual_clock_moves_only_when_told() {
+        let start = Utc.ymd(2018, 10, 1).and_hms(12, 0, 0);
+        let clock = ManualClock::new(start);
+        let shared = clock.clone();
+        assert_eq!(start, shared.now());
+
+        clock.advance(Duration::seconds(30));
+        assert_eq!(start + Duration::seconds(30), shared.now());
+
+        clock.set(start);
+        assert_eq!(start, shared.now());
+    }
+
+    #[test]
+    fn trusted_clock_corrects_for_skew() {
+        let start = Utc.ymd(1970, 1, 1).and_hms(0, 0, 10);
+        let clock = ManualClock::new(start);
+        let trusted = TrustedClock::new().with_clock(clock.clone());
+        assert_eq!(None, trusted.skew());
+        assert_eq!(start, trusted.now());
+
+        let now = Utc.ymd(2018, 10, 1).and_hms(12, 0, 0);
+        trusted.clone().record(now);
+        assert_eq!(Some(now - start), trusted.skew());
+        assert_eq!(now, trusted.now());
+
+        clock.advance(Duration::seconds(30));
+        assert_eq!(now + Duration::seconds(30), trusted.now());
+    }
+}
@@ -14,7 +14,9 @@ use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
 use certificate_properties::CertificateProperties;
+use certificate_request::CertificateRequest;
 use error::{Error, ErrorKind};
+use revocation::RevokedCertificate;
 
 /// This is the issuer alias used when `CertificateIssuer::DefaultCa` is provided by the caller
 pub const IOTEDGED_CA_ALIAS: &str = "iotedged-workload-ca";
@@ -122,6 +124,37 @@ pub trait CreateCertificate {
     ) -> Result<Self::Certificate, Error>;
 
     fn destroy_certificate(&self, alias: String) -> Result<(), Error>;
+
+    /// Issues a certificate with `properties` for the public key of
+    /// `request`, whose private key stays with the caller. HSMs that can only
+    /// issue certificates for keys they generate themselves fail with
+    /// `CertificateRequestNotSupported`.
+    fn sign_certificate_request(
+        &self,
+        _request: &CertificateRequest,
+        _properties: &CertificateProperties,
+    ) -> Result<Self::Certificate, Error> {
+        Err(Error::from(ErrorKind::CertificateRequestNotSupported))
+    }
+
+    /// Re-issues the certificate under the alias of `properties` with the
+    /// private key it was first issued with, so that whoever trusts the key
+    /// keeps trusting it. HSMs that can't reuse keys fail with
+    /// `CertificateRenewalNotSupported`.
+    fn renew_certificate(
+        &self,
+        _properties: &CertificateProperties,
+    ) -> Result<Self::Certificate, Error> {
+        Err(Error::from(ErrorKind::CertificateRenewalNotSupported))
+    }
+
+    /// The certificate under `alias`, as it was last issued or renewed. It
+    /// comes with its private key, if the HSM holds one for the alias. HSMs
+    /// that can't look up certificates fail with
+    /// `CertificateLookupNotSupported`.
+    fn get_certificate(&self, _alias: String) -> Result<Self::Certificate, Error> {
+        Err(Error::from(ErrorKind::CertificateLookupNotSupported))
+    }
 }
 
 pub trait Certificate {
@@ -139,6 +172,19 @@ pub trait GetTrustBundle {
     fn get_trust_bundle(&self) -> Result<Self::Certificate, Error>;
 }
 
+pub trait CreateCrl {
+    type Buffer: AsRef<[u8]>;
+
+    /// Creates a PEM encoded certificate revocation list for the certificates
+    /// issued by the workload CA. The list is due to be updated after
+    /// `validity_in_secs` seconds.
+    fn create_crl(
+        &self,
+        revoked: &[RevokedCertificate],
+        validity_in_secs: u64,
+    ) -> Result<Self::Buffer, Error>;
+}
+
 pub trait MakeRandom {
     fn get_random_bytes(&self, buffer: &mut [u8]) -> Result<(), Error>;
 }
@@ -0,0 +1,310 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::{DateTime, Duration, Utc};
+use failure::ResultExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use error::{ErrorKind, Result};
+
+/// Number of previous deployments kept when no other size is configured.
+pub const DEFAULT_HISTORY_SIZE: usize = 5;
+
+/// Module changes applied within this many seconds of the previous change are
+/// treated as part of the same deployment.
+const DEPLOYMENT_SETTLE_SECS: i64 = 60;
+
+/// A set of module configurations keyed by module name.
+pub type Deployment<T> = BTreeMap<String, T>;
+
+/// The version of a deployment manifest that doesn't name its own, which is
+/// the hex encoded SHA-256 of the manifest, as `sha256sum` prints it.
+pub fn manifest_version(manifest: &[u8]) -> String {
+    Sha256::digest(manifest)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct State<T> {
+    current: Deployment<T>,
+    previous: VecDeque<Deployment<T>>,
+    last_change: Option<DateTime<Utc>>,
+}
+
+impl<T> Default for State<T> {
+    fn default() -> Self {
+        State {
+            current: BTreeMap::new(),
+            previous: VecDeque::new(),
+            last_change: None,
+        }
+    }
+}
+
+/// Keeps track of the module configurations applied to the device and the
+/// last few deployments that preceded them so that they can be restored
+/// locally. When created with `load` every change is persisted to disk.
+pub struct DeploymentHistory<T> {
+    path: Option<PathBuf>,
+    max_size: usize,
+    settle_time: Duration,
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Clone for DeploymentHistory<T> {
+    fn clone(&self) -> Self {
+        DeploymentHistory {
+            path: self.path.clone(),
+            max_size: self.max_size,
+            settle_time: self.settle_time,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> DeploymentHistory<T>
+where
+    T: Clone + DeserializeOwned + Serialize,
+{
+    pub fn new(max_size: usize) -> Self {
+        DeploymentHistory {
+            path: None,
+            max_size,
+            settle_time: Duration::seconds(DEPLOYMENT_SETTLE_SECS),
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, max_size: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let state = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::DeploymentHistory)?;
+            serde_json::from_reader(file).context(ErrorKind::DeploymentHistory)?
+        } else {
+            State::default()
+        };
+
+        Ok(DeploymentHistory {
+            path: Some(path),
+            max_size,
+            settle_time: Duration::seconds(DEPLOYMENT_SETTLE_SECS),
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Sets how long after a module change further changes are still
+    /// considered part of the same deployment.
+    pub fn with_settle_time(mut self, settle_time: Duration) -> Self {
+        self.settle_time = settle_time;
+        self
+    }
+
+    /// Number of previous deployments that can be restored.
+    pub fn len(&self) -> usize {
+        self.lock().previous.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn current(&self) -> Deployment<T> {
+        self.lock().current.clone()
+    }
+
+    /// Returns the deployment `to` steps back, where `1` is the deployment
+    /// that immediately preceded the current one.
+    pub fn deployment(&self, to: usize) -> Option<Deployment<T>> {
+        if to == 0 {
+            return None;
+        }
+        self.lock().previous.get(to - 1).cloned()
+    }
+
+    pub fn module_applied(&self, name: &str, spec: T) -> Result<()> {
+        self.module_applied_at(name, spec, Utc::now())
+    }
+
+    pub fn module_removed(&self, name: &str) -> Result<()> {
+        self.module_removed_at(name, Utc::now())
+    }
+
+    /// Records that the deployment `to` steps back has been restored. The
+    /// deployment being replaced becomes the most recent previous one so
+    /// that the rollback itself can be undone.
+    pub fn restored(&self, to: usize) -> Result<()> {
+        let mut state = self.lock();
+        if to == 0 || to > state.previous.len() {
+            return Err(ErrorKind::DeploymentNotFound(to).into());
+        }
+        let target = state
+            .previous
+            .remove(to - 1)
+            .expect("deployment index was checked");
+        let replaced = ::std::mem::replace(&mut state.current, target);
+        state.previous.push_front(replaced);
+        state.previous.truncate(self.max_size);
+        state.last_change = None;
+        self.persist(&state)
+    }
+
+    fn module_applied_at(&self, name: &str, spec: T, now: DateTime<Utc>) -> Result<()> {
+        self.change(now, |current| {
+            current.insert(name.to_string(), spec);
+        })
+    }
+
+    fn module_removed_at(&self, name: &str, now: DateTime<Utc>) -> Result<()> {
+        self.change(now, |current| {
+            current.remove(name);
+        })
+    }
+
+    fn change<F>(&self, now: DateTime<Utc>, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Deployment<T>),
+    {
+        let mut state = self.lock();
+        let settle_time = self.settle_time;
+        let settled = state
+            .last_change
+            .map_or(true, |last| now.signed_duration_since(last) >= settle_time);
+        if settled && !state.current.is_empty() {
+            let snapshot = state.current.clone();
+            state.previous.push_front(snapshot);
+            state.previous.truncate(self.max_size);
+        }
+        f(&mut state.current);
+        state.last_change = Some(now);
+        self.persist(&state)
+    }
+
+    fn persist(&self, state: &State<T>) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::DeploymentHistory)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::DeploymentHistory)?;
+            serde_json::to_writer(file, state).context(ErrorKind::DeploymentHistory)?;
+            fs::rename(&temp, path).context(ErrorKind::DeploymentHistory)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(1_540_000_000 + secs, 0)
+    }
+
+    #[test]
+    fn manifest_version_is_its_sha256() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            manifest_version(b"")
+        );
+        assert_ne!(manifest_version(b"{}"), manifest_version(b"{ }"));
+    }
+
+    #[test]
+    fn changes_within_settle_time_form_one_deployment() {
+        let history = DeploymentHistory::new(DEFAULT_HISTORY_SIZE);
+        history.module_applied_at("a", 1, at(0)).unwrap();
+        history.module_applied_at("b", 1, at(10)).unwrap();
+        assert!(history.is_empty());
+
+        history.module_applied_at("a", 2, at(120)).unwrap();
+        history.module_removed_at("b", at(130)).unwrap();
+
+        assert_eq!(1, history.len());
+        let previous = history.deployment(1).unwrap();
+        assert_eq!(Some(&1), previous.get("a"));
+        assert_eq!(Some(&1), previous.get("b"));
+        let current = history.current();
+        assert_eq!(Some(&2), current.get("a"));
+        assert_eq!(None, current.get("b"));
+    }
+
+    #[test]
+    fn zero_settle_time_records_every_change() {
+        let history =
+            DeploymentHistory::new(DEFAULT_HISTORY_SIZE).with_settle_time(Duration::zero());
+        history.module_applied_at("a", 1, at(0)).unwrap();
+        history.module_applied_at("b", 1, at(0)).unwrap();
+
+        assert_eq!(1, history.len());
+        assert_eq!(1, history.deployment(1).unwrap().len());
+        assert_eq!(2, history.current().len());
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let history = DeploymentHistory::new(2);
+        for i in 0..5 {
+            history.module_applied_at("a", i, at(i64::from(i) * 100)).unwrap();
+        }
+
+        assert_eq!(2, history.len());
+        assert_eq!(Some(&3), history.deployment(1).unwrap().get("a"));
+        assert_eq!(Some(&2), history.deployment(2).unwrap().get("a"));
+        assert!(history.deployment(3).is_none());
+        assert!(history.deployment(0).is_none());
+    }
+
+    #[test]
+    fn restored_swaps_current_and_target() {
+        let history = DeploymentHistory::new(DEFAULT_HISTORY_SIZE);
+        history.module_applied_at("a", 1, at(0)).unwrap();
+        history.module_applied_at("a", 2, at(100)).unwrap();
+
+        history.restored(1).unwrap();
+
+        assert_eq!(Some(&1), history.current().get("a"));
+        assert_eq!(Some(&2), history.deployment(1).unwrap().get("a"));
+        assert_eq!(1, history.len());
+    }
+
+    #[test]
+    fn restored_fails_for_unknown_deployment() {
+        let history = DeploymentHistory::<i32>::new(DEFAULT_HISTORY_SIZE);
+        let err = history.restored(1).unwrap_err();
+        match *err.kind() {
+            ErrorKind::DeploymentNotFound(1) => (),
+            _ => panic!("unexpected error kind {}", err),
+        }
+    }
+
+    #[test]
+    fn load_reads_persisted_history() {
+        let tmp_dir = TempDir::new("deployment").unwrap();
+        let path = tmp_dir.path().join("deployment_history.json");
+
+        let history = DeploymentHistory::load(&path, DEFAULT_HISTORY_SIZE).unwrap();
+        history.module_applied_at("a", 1, at(0)).unwrap();
+        history.module_applied_at("a", 2, at(100)).unwrap();
+
+        let loaded = DeploymentHistory::<i32>::load(&path, DEFAULT_HISTORY_SIZE).unwrap();
+        assert_eq!(Some(&2), loaded.current().get("a"));
+        assert_eq!(Some(&1), loaded.deployment(1).unwrap().get("a"));
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Notices of features that still work but are going to be removed.
+//!
+//! API routes that are deprecated carry their notice in the responses they
+//! send, and deprecated config settings have theirs logged when the daemon
+//! starts and reported by `iotedge check`, so that module authors and
+//! operators hear about a removal before it happens.
+
+use std::fmt;
+
+/// A deprecated feature, what to use instead of it and the release it is
+/// removed in.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deprecation {
+    feature: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    replacement: Option<String>,
+    removal: String,
+}
+
+impl Deprecation {
+    /// A notice that `feature` is removed in the release `removal`.
+    pub fn new(feature: &str, removal: &str) -> Self {
+        Deprecation {
+            feature: feature.to_string(),
+            replacement: None,
+            removal: removal.to_string(),
+        }
+    }
+
+    pub fn with_replacement(mut self, replacement: &str) -> Self {
+        self.replacement = Some(replacement.to_string());
+        self
+    }
+
+    pub fn feature(&self) -> &str {
+        &self.feature
+    }
+
+    pub fn replacement(&self) -> Option<&str> {
+        self.replacement.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn removal(&self) -> &str {
+        &self.removal
+    }
+
+    /// The notice as an `x-ms-deprecation` header, e.g.
+    /// `GET /modules; removal="1.2.0"; replacement="GET /identities"`.
+    pub fn header_value(&self) -> String {
+        let mut value = format!("{}; removal=\"{}\"", self.feature, self.removal);
+        if let Some(ref replacement) = self.replacement {
+            value.push_str(&format!("; replacement=\"{}\"", replacement));
+        }
+        value
+    }
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is deprecated and will be removed in {}.",
+            self.feature, self.removal
+        )?;
+        if let Some(ref replacement) = self.replacement {
+            write!(f, " Use {} instead.", replacement)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+    #[test]
+    fn notice_names_the_removal_and_the_replacement() {
+        let deprecation = Deprecation::new("moby_runtime.docker_uri", "1.2.0")
+            .with_replacement("moby_runtime.uri");
+        assert_eq!(
+            "moby_runtime.docker_uri is deprecated and will be removed in 1.2.0. Use \
+             moby_runtime.uri instead.",
+            deprecation.to_string()
+        );
+        assert_eq!(
+            "moby_runtime.docker_uri; removal=\"1.2.0\"; replacement=\"moby_runtime.uri\"",
+            deprecation.header_value()
+        );
+
+        let deprecation = Deprecation::new("GET /modules", "1.2.0");
+        assert_eq!(
+            "GET /modules is deprecated and will be removed in 1.2.0.",
+            deprecation.to_string()
+        );
+        assert_eq!("GET /modules; removal=\"1.2.0\"", deprecation.header_value());
+    }
+
+    #[test]
+    fn notice_round_trips_through_json() {
+        let deprecation = Deprecation::new("moby_runtime.docker_uri", "1.2.0")
+            .with_replacement("moby_runtime.uri");
+        let json = serde_json::to_string(&deprecation).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"feature":"moby_runtime.docker_uri","replacement":"moby_runtime.uri","#,
+                r#""removal":"1.2.0"}"#
+            ),
+            json
+        );
+        assert_eq!(deprecation, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,680 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! DNS-SD records for advertising the gateway over multicast DNS.
+//!
+//! This only builds and answers DNS messages. Sending them on the network
+//! is left to the caller, which keeps this module free of any socket code.
+
+#[cfg(windows)]
+use std::env;
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::net::Ipv4Addr;
+
+use error::{Error, ErrorKind, Result};
+
+/// Maximum length of a host name (RFC 1123).
+const MAX_HOSTNAME_LENGTH: usize = 253;
+
+/// Maximum length of a single DNS label (RFC 1035).
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Maximum length of a single TXT record entry.
+const MAX_TXT_ENTRY_LENGTH: usize = 255;
+
+/// Maximum length of a DNS-SD service name, not counting the leading
+/// underscore (RFC 6335).
+const MAX_SERVICE_NAME_LENGTH: usize = 15;
+
+const SERVICES_NAME: &str = "_services._dns-sd._udp.local";
+const LOCAL_DOMAIN: &str = "local";
+
+/// Records are kept for two minutes by queriers, which is the TTL RFC 6762
+/// recommends for records containing host names.
+const RECORD_TTL: u32 = 120;
+
+const HEADER_LENGTH: usize = 12;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+
+const CLASS_IN: u16 = 1;
+const CLASS_ANY: u16 = 255;
+const CLASS_MASK: u16 = 0x7fff;
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// Bounds the number of compression pointers followed while reading a name
+/// so that a malicious message can't send us round in circles.
+const MAX_POINTER_JUMPS: usize = 16;
+
+/// A DNS host name: dot separated labels of ASCII letters, digits and
+/// dashes that don't start or end with a dash (RFC 1123).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Hostname(String);
+
+impl Hostname {
+    pub fn new(name: &str) -> Result<Self> {
+        let name = name.trim_right_matches('.');
+        let valid = !name.is_empty()
+            && name.len() <= MAX_HOSTNAME_LENGTH
+            && name.split('.').all(is_valid_label);
+
+        if valid {
+            Ok(Hostname(name.to_lowercase()))
+        } else {
+            Err(Error::from(ErrorKind::InvalidHostname(name.to_string())))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The first label of the host name, which is the name the host is
+    /// known by on the local link.
+    pub fn label(&self) -> &str {
+        self.0.split('.').next().unwrap_or(&self.0)
+    }
+
+    /// Whether both host names name the same host. A fully qualified name
+    /// names the same host as its first label on its own.
+    pub fn names_same_host(&self, other: &Hostname) -> bool {
+        let qualified = self.0.contains('.') && other.0.contains('.');
+        self == other || (!qualified && self.label() == other.label())
+    }
+
+    /// The host name of this device, as the operating system knows it. This
+    /// is `None` where it can't be found out.
+    pub fn of_device() -> Option<Self> {
+        device_hostname().and_then(|name| Hostname::new(name.trim()).ok())
+    }
+}
+
+#[cfg(unix)]
+fn device_hostname() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/hostname").ok()
+}
+
+#[cfg(windows)]
+fn device_hostname() -> Option<String> {
+    env::var("COMPUTERNAME").ok()
+}
+
+fn is_valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= MAX_LABEL_LENGTH
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+impl fmt::Display for Hostname {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A service to advertise, such as `_secure-mqtt._tcp` on port 8883.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ServiceSpec {
+    #[serde(rename = "type")]
+    type_: String,
+    port: u16,
+}
+
+impl ServiceSpec {
+    pub fn new(type_: String, port: u16) -> Self {
+        ServiceSpec { type_, port }
+    }
+
+    pub fn type_(&self) -> &str {
+        &self.type_
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+fn is_valid_service_type(type_: &str) -> bool {
+    let mut labels = type_.split('.');
+    let name = labels.next().unwrap_or("");
+    let protocol = labels.next().unwrap_or("");
+    name.starts_with('_')
+        && name.len() > 1
+        && name.len() <= MAX_SERVICE_NAME_LENGTH + 1
+        && is_valid_label(&name[1..])
+        && (protocol == "_tcp" || protocol == "_udp")
+        && labels.next().is_none()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum RecordData {
+    A(Ipv4Addr),
+    Ptr(String),
+    Srv(u16, String),
+    Txt(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Record {
+    name: String,
+    unique: bool,
+    data: RecordData,
+}
+
+impl Record {
+    fn type_(&self) -> u16 {
+        match self.data {
+            RecordData::A(_) => TYPE_A,
+            RecordData::Ptr(_) => TYPE_PTR,
+            RecordData::Srv(_, _) => TYPE_SRV,
+            RecordData::Txt(_) => TYPE_TXT,
+        }
+    }
+
+    fn matches(&self, name: &str, type_: u16) -> bool {
+        self.name.eq_ignore_ascii_case(name) && (type_ == TYPE_ANY || type_ == self.type_())
+    }
+
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn write(&self, ttl: u32, buf: &mut Vec<u8>) {
+        write_name(&self.name, buf);
+        write_u16(self.type_(), buf);
+        write_u16(if self.unique { CLASS_IN | CACHE_FLUSH } else { CLASS_IN }, buf);
+        write_u32(ttl, buf);
+
+        let mut data = vec![];
+        match self.data {
+            RecordData::A(ref address) => data.extend_from_slice(&address.octets()),
+            RecordData::Ptr(ref name) => write_name(name, &mut data),
+            RecordData::Srv(port, ref target) => {
+                // priority and weight, which only matter with several hosts
+                write_u16(0, &mut data);
+                write_u16(0, &mut data);
+                write_u16(port, &mut data);
+                write_name(target, &mut data);
+            }
+            RecordData::Txt(ref entries) => {
+                for entry in entries.iter().filter(|e| e.len() <= MAX_TXT_ENTRY_LENGTH) {
+                    data.push(entry.len() as u8);
+                    data.extend_from_slice(entry.as_bytes());
+                }
+                // a TXT record with no entries is a single empty string
+                if data.is_empty() {
+                    data.push(0);
+                }
+            }
+        }
+        write_u16(data.len() as u16, buf);
+        buf.extend_from_slice(&data);
+    }
+}
+
+/// Answers multicast DNS queries for the gateway's services.
+///
+/// Each service is advertised as an instance named after the host, with a
+/// TXT record carrying the full host name leaf devices should put in the
+/// `GatewayHostName` of their connection string. The host itself is
+/// advertised as `<label>.local`.
+#[derive(Clone, Debug)]
+pub struct Responder {
+    host: String,
+    records: Vec<Record>,
+}
+
+impl Responder {
+    pub fn new(hostname: &Hostname, address: Ipv4Addr, services: &[ServiceSpec]) -> Result<Self> {
+        let host = format!("{}.{}", hostname.label(), LOCAL_DOMAIN);
+        let mut records = vec![Record {
+            name: host.clone(),
+            unique: true,
+            data: RecordData::A(address),
+        }];
+
+        for service in services {
+            if !is_valid_service_type(service.type_()) {
+                return Err(Error::from(ErrorKind::InvalidServiceType(
+                    service.type_().to_string(),
+                )));
+            }
+
+            let type_name = format!("{}.{}", service.type_(), LOCAL_DOMAIN);
+            let instance_name = format!("{}.{}", hostname.label(), type_name);
+            records.push(Record {
+                name: SERVICES_NAME.to_string(),
+                unique: false,
+                data: RecordData::Ptr(type_name.clone()),
+            });
+            records.push(Record {
+                name: type_name,
+                unique: false,
+                data: RecordData::Ptr(instance_name.clone()),
+            });
+            records.push(Record {
+                name: instance_name.clone(),
+                unique: true,
+                data: RecordData::Srv(service.port(), host.clone()),
+            });
+            records.push(Record {
+                name: instance_name,
+                unique: true,
+                data: RecordData::Txt(vec![format!("hostname={}", hostname)]),
+            });
+        }
+
+        Ok(Responder { host, records })
+    }
+
+    /// Adds `entry`, such as `key=value`, to the TXT record of each service.
+    pub fn with_txt_entry(mut self, entry: String) -> Self {
+        for record in &mut self.records {
+            if let RecordData::Txt(ref mut entries) = record.data {
+                entries.push(entry.clone());
+            }
+        }
+        self
+    }
+
+    /// The name the host is advertised under, such as `gateway.local`.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// An unsolicited response announcing all records, sent when the
+    /// advertisement starts.
+    pub fn announcement(&self) -> Vec<u8> {
+        let records: Vec<&Record> = self.records.iter().collect();
+        write_response(&records, &[], RECORD_TTL)
+    }
+
+    /// An unsolicited response telling queriers to drop all records, sent
+    /// when the advertisement stops.
+    pub fn goodbye(&self) -> Vec<u8> {
+        let records: Vec<&Record> = self.records.iter().collect();
+        write_response(&records, &[], 0)
+    }
+
+    /// Builds the response to a multicast DNS message. Returns `None` if the
+    /// message isn't a well formed query or asks about none of our records.
+    ///
+    /// Responses are meant to be multicast. Legacy unicast queries, which
+    /// expect a reply directly to their source port, aren't supported.
+    pub fn respond(&self, message: &[u8]) -> Option<Vec<u8>> {
+        if message.len() < HEADER_LENGTH || read_u16(message, 2)? & FLAG_RESPONSE != 0 {
+            return None;
+        }
+
+        let mut answers: Vec<&Record> = vec![];
+        let mut pos = HEADER_LENGTH;
+        for _ in 0..read_u16(message, 4)? {
+            let (name, next) = read_name(message, pos)?;
+            let type_ = read_u16(message, next)?;
+            let class = read_u16(message, next + 2)? & CLASS_MASK;
+            pos = next + 4;
+
+            if class == CLASS_IN || class == CLASS_ANY {
+                for record in self.records.iter().filter(|r| r.matches(&name, type_)) {
+                    if !answers.contains(&record) {
+                        answers.push(record);
+                    }
+                }
+            }
+        }
+
+        if answers.is_empty() {
+            None
+        } else {
+            let additionals = self.additionals(&answers);
+            Some(write_response(&answers, &additionals, RECORD_TTL))
+        }
+    }
+
+    // Saves queriers a round trip by including the records they're going
+    // to ask about next: the SRV and TXT records of service instances and
+    // the address of the host.
+    fn additionals<'a>(&'a self, answers: &[&'a Record]) -> Vec<&'a Record> {
+        let mut names: Vec<&str> = answers
+            .iter()
+            .filter_map(|record| match record.data {
+                RecordData::Ptr(ref name) => Some(name.as_str()),
+                _ => None,
+            }).collect();
+        names.push(&self.host);
+
+        self.records
+            .iter()
+            .filter(|record| {
+                record.type_() != TYPE_PTR
+                    && names.iter().any(|name| record.name.eq_ignore_ascii_case(name))
+                    && !answers.contains(record)
+            }).collect()
+    }
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn write_response(answers: &[&Record], additionals: &[&Record], ttl: u32) -> Vec<u8> {
+    let mut buf = vec![];
+    write_u16(0, &mut buf);
+    write_u16(FLAG_RESPONSE | FLAG_AUTHORITATIVE, &mut buf);
+    write_u16(0, &mut buf);
+    write_u16(answers.len() as u16, &mut buf);
+    write_u16(0, &mut buf);
+    write_u16(additionals.len() as u16, &mut buf);
+    for record in answers.iter().chain(additionals) {
+        record.write(ttl, &mut buf);
+    }
+    buf
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn write_name(name: &str, buf: &mut Vec<u8>) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn write_u16(value: u16, buf: &mut Vec<u8>) {
+    buf.push((value >> 8) as u8);
+    buf.push(value as u8);
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+fn write_u32(value: u32, buf: &mut Vec<u8>) {
+    write_u16((value >> 16) as u16, buf);
+    write_u16(value as u16, buf);
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Option<u16> {
+    let bytes = message.get(pos..pos + 2)?;
+    Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+}
+
+/// Reads a possibly compressed name. Returns the name and the position
+/// right after it in the message.
+fn read_name(message: &[u8], pos: usize) -> Option<(String, usize)> {
+    let mut labels = vec![];
+    let mut pos = pos;
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *message.get(pos)? as usize;
+        if len == 0 {
+            break;
+        } else if len & 0xc0 == 0xc0 {
+            jumps += 1;
+            if jumps > MAX_POINTER_JUMPS {
+                return None;
+            }
+            let offset = read_u16(message, pos)? as usize & 0x3fff;
+            end = end.or(Some(pos + 2));
+            pos = offset;
+        } else if len <= MAX_LABEL_LENGTH {
+            let label = message.get(pos + 1..pos + 1 + len)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        } else {
+            return None;
+        }
+    }
+
+    Some((labels.join("."), end.unwrap_or(pos + 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responder() -> Responder {
+        Responder::new(
+            &Hostname::new("Gateway.contoso.com").unwrap(),
+            Ipv4Addr::new(192, 168, 1, 10),
+            &[
+                ServiceSpec::new("_secure-mqtt._tcp".to_string(), 8883),
+                ServiceSpec::new("_amqps._tcp".to_string(), 5671),
+            ],
+        ).unwrap()
+    }
+
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn query(questions: &[(&str, u16)]) -> Vec<u8> {
+        let mut buf = vec![];
+        write_u16(0, &mut buf);
+        write_u16(0, &mut buf);
+        write_u16(questions.len() as u16, &mut buf);
+        write_u16(0, &mut buf);
+        write_u16(0, &mut buf);
+        write_u16(0, &mut buf);
+        for &(name, type_) in questions {
+            write_name(name, &mut buf);
+            write_u16(type_, &mut buf);
+            write_u16(CLASS_IN, &mut buf);
+        }
+        buf
+    }
+
+    // Reads the names and types of the records in a response.
+    fn records(response: &[u8]) -> Vec<(String, u16)> {
+        let count = read_u16(response, 6).unwrap() + read_u16(response, 10).unwrap();
+        let mut pos = HEADER_LENGTH;
+        (0..count)
+            .map(|_| {
+                let (name, next) = read_name(response, pos).unwrap();
+                let type_ = read_u16(response, next).unwrap();
+                let len = read_u16(response, next + 8).unwrap() as usize;
+                pos = next + 10 + len;
+                (name, type_)
+            }).collect()
+    }
+
+    #[test]
+    fn hostname_accepts_dns_names() {
+        for name in &["localhost", "gateway-1", "Gateway.contoso.com", "gateway.local."] {
+            assert!(Hostname::new(name).is_ok(), "{}", name);
+        }
+        assert_eq!("gateway", Hostname::new("Gateway.contoso.com").unwrap().label());
+        assert_eq!(
+            "gateway.contoso.com",
+            Hostname::new("Gateway.contoso.com").unwrap().as_str()
+        );
+    }
+
+    #[test]
+    fn hostname_names_same_host() {
+        let name = |name| Hostname::new(name).unwrap();
+        assert!(name("Gateway").names_same_host(&name("gateway")));
+        assert!(name("gateway.contoso.com").names_same_host(&name("gateway")));
+        assert!(name("gateway").names_same_host(&name("gateway.contoso.com")));
+        assert!(!name("gateway.contoso.com").names_same_host(&name("gateway.fabrikam.com")));
+        assert!(!name("gateway-1").names_same_host(&name("gateway-2")));
+    }
+
+    #[test]
+    fn hostname_rejects_invalid_names() {
+        let long_label = "a".repeat(64);
+        let long_name = vec!["a"; 128].join(".");
+        for name in &[
+            "",
+            "<ADD HOSTNAME HERE>",
+            "my_gateway",
+            "-gateway",
+            "gateway-",
+            "gateway..local",
+            long_label.as_str(),
+            long_name.as_str(),
+        ] {
+            match Hostname::new(name) {
+                Err(err) => match err.kind() {
+                    ErrorKind::InvalidHostname(_) => (),
+                    _ => panic!("unexpected error kind for {}", name),
+                },
+                Ok(_) => panic!("expected {} to be rejected", name),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_service_type_fails() {
+        let hostname = Hostname::new("gateway").unwrap();
+        for type_ in &["mqtt._tcp", "_mqtt", "_mqtt._sctp", "_a-very-long-service._tcp", "_._tcp"] {
+            let services = [ServiceSpec::new(type_.to_string(), 8883)];
+            match Responder::new(&hostname, Ipv4Addr::new(127, 0, 0, 1), &services) {
+                Err(err) => match err.kind() {
+                    ErrorKind::InvalidServiceType(_) => (),
+                    _ => panic!("unexpected error kind for {}", type_),
+                },
+                Ok(_) => panic!("expected {} to be rejected", type_),
+            }
+        }
+    }
+
+    #[test]
+    fn announcement_contains_all_records() {
+        let responder = responder();
+        assert_eq!("gateway.local", responder.host());
+
+        let announcement = responder.announcement();
+        assert_eq!(FLAG_RESPONSE | FLAG_AUTHORITATIVE, read_u16(&announcement, 2).unwrap());
+        assert_eq!(
+            vec![
+                ("gateway.local".to_string(), TYPE_A),
+                (SERVICES_NAME.to_string(), TYPE_PTR),
+                ("_secure-mqtt._tcp.local".to_string(), TYPE_PTR),
+                ("gateway._secure-mqtt._tcp.local".to_string(), TYPE_SRV),
+                ("gateway._secure-mqtt._tcp.local".to_string(), TYPE_TXT),
+                (SERVICES_NAME.to_string(), TYPE_PTR),
+                ("_amqps._tcp.local".to_string(), TYPE_PTR),
+                ("gateway._amqps._tcp.local".to_string(), TYPE_SRV),
+                ("gateway._amqps._tcp.local".to_string(), TYPE_TXT),
+            ],
+            records(&announcement)
+        );
+    }
+
+    #[test]
+    fn txt_entries_are_added_to_every_service() {
+        let responder = responder().with_txt_entry("workload-signing=abc".to_string());
+        let txt: Vec<&RecordData> = responder
+            .records
+            .iter()
+            .filter(|record| record.type_() == TYPE_TXT)
+            .map(|record| &record.data)
+            .collect();
+        let expected = RecordData::Txt(vec![
+            "hostname=Gateway.contoso.com".to_string(),
+            "workload-signing=abc".to_string(),
+        ]);
+        assert_eq!(vec![&expected, &expected], txt);
+    }
+
+    #[test]
+    fn goodbye_has_zero_ttl() {
+        let goodbye = responder().goodbye();
+        let (_, next) = read_name(&goodbye, HEADER_LENGTH).unwrap();
+        assert_eq!(Some(&[0, 0, 0, 0][..]), goodbye.get(next + 4..next + 8));
+    }
+
+    #[test]
+    fn respond_to_service_browse_includes_instance_records() {
+        let response = responder()
+            .respond(&query(&[("_secure-mqtt._tcp.local", TYPE_PTR)]))
+            .unwrap();
+
+        assert_eq!(1, read_u16(&response, 6).unwrap());
+        assert_eq!(3, read_u16(&response, 10).unwrap());
+        assert_eq!(
+            vec![
+                ("_secure-mqtt._tcp.local".to_string(), TYPE_PTR),
+                ("gateway.local".to_string(), TYPE_A),
+                ("gateway._secure-mqtt._tcp.local".to_string(), TYPE_SRV),
+                ("gateway._secure-mqtt._tcp.local".to_string(), TYPE_TXT),
+            ],
+            records(&response)
+        );
+    }
+
+    #[test]
+    fn respond_to_address_query_ignores_case() {
+        let response = responder()
+            .respond(&query(&[("GATEWAY.local", TYPE_A)]))
+            .unwrap();
+
+        assert_eq!(vec![("gateway.local".to_string(), TYPE_A)], records(&response));
+        assert_eq!(
+            Some(&[192, 168, 1, 10][..]),
+            response.get(response.len() - 4..)
+        );
+    }
+
+    #[test]
+    fn respond_to_any_query() {
+        let response = responder()
+            .respond(&query(&[("gateway._amqps._tcp.local", TYPE_ANY)]))
+            .unwrap();
+
+        assert_eq!(2, read_u16(&response, 6).unwrap());
+        assert_eq!(1, read_u16(&response, 10).unwrap());
+    }
+
+    #[test]
+    fn respond_follows_compressed_names() {
+        let mut message = query(&[("_amqps._tcp.local", TYPE_PTR)]);
+        message[5] = 2;
+        // "gateway" followed by a pointer to "_amqps._tcp.local"
+        message.push(7);
+        message.extend_from_slice(b"gateway");
+        message.extend_from_slice(&[0xc0, 0x0c]);
+        write_u16(TYPE_SRV, &mut message);
+        write_u16(CLASS_IN, &mut message);
+
+        let response = responder().respond(&message).unwrap();
+        let names: Vec<(String, u16)> = records(&response).into_iter().take(2).collect();
+        assert_eq!(
+            vec![
+                ("_amqps._tcp.local".to_string(), TYPE_PTR),
+                ("gateway._amqps._tcp.local".to_string(), TYPE_SRV),
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn respond_ignores_other_names_and_responses() {
+        let responder = responder();
+        assert_eq!(None, responder.respond(&query(&[("printer.local", TYPE_A)])));
+
+        let mut response = query(&[("gateway.local", TYPE_A)]);
+        response[2] = 0x84;
+        assert_eq!(None, responder.respond(&response));
+    }
+
+    #[test]
+    fn respond_rejects_malformed_messages() {
+        let responder = responder();
+        assert_eq!(None, responder.respond(&[]));
+
+        // a question that runs past the end of the message
+        let mut truncated = query(&[("gateway.local", TYPE_A)]);
+        let len = truncated.len();
+        truncated.truncate(len - 3);
+        assert_eq!(None, responder.respond(&truncated));
+
+        // a name that points at itself
+        let mut looping = query(&[]);
+        looping[5] = 1;
+        looping.extend_from_slice(&[0xc0, 0x0c]);
+        write_u16(TYPE_A, &mut looping);
+        write_u16(CLASS_IN, &mut looping);
+        assert_eq!(None, responder.respond(&looping));
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use failure::{Fail, ResultExt};
+use futures::Future;
+use serde_json;
+
+use error::{ErrorKind, Result};
+use system_info::disk_space;
+
+/// How much disk a module takes up besides its image: the writable layer of
+/// its container, and the quota on it if its create options set one.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleDiskUsage {
+    name: String,
+    writable_layer: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quota: Option<u64>,
+}
+
+impl ModuleDiskUsage {
+    pub fn new(name: String, writable_layer: u64) -> Self {
+        ModuleDiskUsage {
+            name,
+            writable_layer,
+            quota: None,
+        }
+    }
+
+    pub fn with_quota(mut self, quota: u64) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The size of the writable layer of the container, in bytes.
+    pub fn writable_layer(&self) -> u64 {
+        self.writable_layer
+    }
+
+    /// The most the writable layer of the container may grow to, in bytes.
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+    }
+
+    /// How much of its quota the writable layer takes up, in percent.
+    pub fn quota_used_percent(&self) -> Option<u64> {
+        self.quota
+            .filter(|quota| *quota > 0)
+            .map(|quota| self.writable_layer.saturating_mul(100) / quota)
+    }
+}
+
+/// How much disk a volume managed by the container runtime takes up.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeDiskUsage {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+}
+
+impl VolumeDiskUsage {
+    pub fn new(name: String) -> Self {
+        VolumeDiskUsage { name, size: None }
+    }
+
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The size of the volume in bytes, if its driver reports it.
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+}
+
+/// What takes up the disk of the device: the images and volumes of the
+/// container runtime, the writable layers of the containers of the modules,
+/// and the state the daemon keeps in its home directory, along with the size
+/// and the free space of the volume holding the home directory.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsage {
+    images: u64,
+    #[serde(default)]
+    modules: Vec<ModuleDiskUsage>,
+    #[serde(default)]
+    volumes: Vec<VolumeDiskUsage>,
+    #[serde(default)]
+    daemon_state: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    available: Option<u64>,
+}
+
+impl DiskUsage {
+    pub fn new() -> Self {
+        DiskUsage::default()
+    }
+
+    pub fn with_images(mut self, images: u64) -> Self {
+        self.images = images;
+        self
+    }
+
+    pub fn with_modules(mut self, modules: Vec<ModuleDiskUsage>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub fn with_volumes(mut self, volumes: Vec<VolumeDiskUsage>) -> Self {
+        self.volumes = volumes;
+        self
+    }
+
+    pub fn with_daemon_state(mut self, daemon_state: u64) -> Self {
+        self.daemon_state = daemon_state;
+        self
+    }
+
+    pub fn with_disk_space(mut self, total: u64, available: u64) -> Self {
+        self.total = Some(total);
+        self.available = Some(available);
+        self
+    }
+
+    /// Adds the size of the state the daemon keeps in `homedir`, and the size
+    /// and free space of the volume holding it. Whatever can't be read is
+    /// left out.
+    pub fn with_host(self, homedir: &Path) -> Self {
+        let usage = match dir_size(homedir) {
+            Ok(daemon_state) => self.with_daemon_state(daemon_state),
+            Err(err) => {
+                warn!(
+                    "Could not measure the size of {}: {}",
+                    homedir.display(),
+                    err
+                );
+                self
+            }
+        };
+        match disk_space(homedir) {
+            Some((total, available)) => usage.with_disk_space(total, available),
+            None => usage,
+        }
+    }
+
+    /// The size of the layers of the images, in bytes. Layers that images
+    /// share are counted once.
+    pub fn images(&self) -> u64 {
+        self.images
+    }
+
+    pub fn modules(&self) -> &[ModuleDiskUsage] {
+        &self.modules
+    }
+
+    pub fn volumes(&self) -> &[VolumeDiskUsage] {
+        &self.volumes
+    }
+
+    /// The size of the home directory of the daemon, in bytes.
+    pub fn daemon_state(&self) -> u64 {
+        self.daemon_state
+    }
+
+    /// The size of the volume holding the home directory, in bytes.
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    /// The free space of the volume holding the home directory, in bytes.
+    pub fn available(&self) -> Option<u64> {
+        self.available
+    }
+
+    /// The bytes taken up by everything in the report.
+    pub fn used(&self) -> u64 {
+        let modules: u64 = self.modules.iter().map(|m| m.writable_layer).sum();
+        let volumes: u64 = self.volumes.iter().filter_map(|v| v.size).sum();
+        self.images + modules + volumes + self.daemon_state
+    }
+
+    /// Writes the report to `path`, for `iotedge check` to read.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(ErrorKind::DiskUsage)?;
+        }
+        let temp = path.with_extension("tmp");
+        fs::write(
+            &temp,
+            serde_json::to_string(self).context(ErrorKind::DiskUsage)?,
+        ).context(ErrorKind::DiskUsage)?;
+        fs::rename(&temp, path).context(ErrorKind::DiskUsage)?;
+        Ok(())
+    }
+}
+
+/// Reports how much disk the modules and the container runtime take up.
+pub trait DiskUsageProvider {
+    type Error: Fail;
+    type DiskUsageFuture: Future<Item = DiskUsage, Error = Self::Error> + Send;
+
+    fn disk_usage(&self) -> Self::DiskUsageFuture;
+}
+
+/// The size of the files under `path`, in bytes. Symbolic links aren't
+/// followed, and files removed while the directory is walked are skipped.
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        match entry.and_then(|entry| dir_size(&entry.path())) {
+            Ok(entry_size) => size += entry_size,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn directories_are_measured_recursively() {
+        let tmp_dir = TempDir::new("disk_usage").unwrap();
+        let dir = tmp_dir.path().join("homedir");
+        fs::create_dir_all(dir.join("cache/certs")).unwrap();
+        fs::write(dir.join("cache/hostname"), "gateway").unwrap();
+        fs::write(dir.join("cache/certs/device_ca.pem"), vec![0; 1000]).unwrap();
+
+        assert_eq!(1007, dir_size(&dir).unwrap());
+        assert_eq!(7, dir_size(&dir.join("cache/hostname")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(io::ErrorKind::NotFound, dir_size(&dir).unwrap_err().kind());
+    }
+
+    #[test]
+    fn usage_adds_up_and_round_trips() {
+        let usage = DiskUsage::new()
+            .with_images(1000)
+            .with_modules(vec![
+                ModuleDiskUsage::new("edgeHub".to_string(), 100).with_quota(400),
+                ModuleDiskUsage::new("sensor".to_string(), 50),
+            ]).with_volumes(vec![
+                VolumeDiskUsage::new("edgehub-store".to_string()).with_size(10),
+                VolumeDiskUsage::new("remote".to_string()),
+            ]).with_daemon_state(5);
+        assert_eq!(1165, usage.used());
+        assert_eq!(Some(25), usage.modules()[0].quota_used_percent());
+        assert_eq!(None, usage.modules()[1].quota_used_percent());
+
+        let json = serde_json::to_string(&usage).unwrap();
+        assert!(json.contains(r#""writableLayer":100"#));
+        assert!(!json.contains("available"));
+        assert_eq!(usage, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+
+use clock::TrustedClock;
+use generation::GenerationRegistry;
+
+pub const HUB_NAME_KEY: &str = "IOTEDGE_IOTHUBHOSTNAME";
+pub const GATEWAY_HOSTNAME_KEY: &str = "EDGEDEVICEHOSTNAME";
+pub const DEVICE_ID_KEY: &str = "IOTEDGE_DEVICEID";
+pub const MODULE_ID_KEY: &str = "IOTEDGE_MODULEID";
+pub const GENERATION_ID_KEY: &str = "IOTEDGE_MODULEGENERATIONID";
+pub const WORKLOAD_URI_KEY: &str = "IOTEDGE_WORKLOADURI";
+pub const API_VERSION_KEY: &str = "IOTEDGE_APIVERSION";
+pub const WORKLOAD_SIGNING_KEY_KEY: &str = "IOTEDGE_WORKLOADSIGNINGKEY";
+pub const CLOCK_SKEW_KEY: &str = "IOTEDGE_CLOCKSKEWSECONDS";
+pub const PARENT_HOSTNAME_KEY: &str = "IOTEDGE_PARENTHOSTNAME";
+
+/// An environment variable set in every module.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EnvVar {
+    name: String,
+    value: String,
+}
+
+impl EnvVar {
+    pub fn new(name: &str, value: &str) -> Self {
+        EnvVar {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Provides the environment module containers are created with.
+///
+/// Every module is told who it is and how to reach the workload API: the IoT
+/// Hub and device it belongs to, its module ID and, once it is known, its
+/// generation ID. On top of that, custom variables from the settings, such as
+/// proxy variables, are set in every module. A variable in a module's own
+/// spec takes precedence over both, except for the API version and the key
+/// workload API responses are signed with, which are always the daemon's.
+#[derive(Clone)]
+pub struct EnvProvider {
+    iot_hub_name: String,
+    gateway_hostname: String,
+    device_id: String,
+    workload_uri: String,
+    api_version: String,
+    custom: Vec<EnvVar>,
+    generations: GenerationRegistry,
+    signing_key: Option<String>,
+    trusted_clock: Option<TrustedClock>,
+    parent_hostname: Option<String>,
+}
+
+impl EnvProvider {
+    pub fn new(
+        iot_hub_name: &str,
+        gateway_hostname: &str,
+        device_id: &str,
+        workload_uri: &str,
+        api_version: &str,
+    ) -> Self {
+        EnvProvider {
+            iot_hub_name: iot_hub_name.to_string(),
+            gateway_hostname: gateway_hostname.to_lowercase(),
+            device_id: device_id.to_string(),
+            workload_uri: workload_uri.to_string(),
+            api_version: api_version.to_string(),
+            custom: Vec::new(),
+            generations: GenerationRegistry::new(),
+            signing_key: None,
+            trusted_clock: None,
+            parent_hostname: None,
+        }
+    }
+
+    pub fn with_custom_env(mut self, custom: Vec<EnvVar>) -> Self {
+        self.custom = custom;
+        self
+    }
+
+    /// Sets the registry the generation IDs of modules are looked up in.
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+
+    /// Sets the key modules verify the signatures of workload API responses
+    /// with.
+    pub fn with_signing_key(mut self, signing_key: String) -> Self {
+        self.signing_key = Some(signing_key);
+        self
+    }
+
+    /// Tells modules how many seconds the trusted time is ahead of the clock
+    /// of the device, as measured by `trusted_clock` when the module is
+    /// created, so that modules on devices with a broken RTC can correct
+    /// their timestamps.
+    pub fn with_trusted_clock(mut self, trusted_clock: TrustedClock) -> Self {
+        self.trusted_clock = Some(trusted_clock);
+        self
+    }
+
+    /// Tells modules the parent gateway of a nested device, which the edge
+    /// hub connects upstream to instead of IoT Hub.
+    pub fn with_parent_hostname(mut self, parent_hostname: String) -> Self {
+        self.parent_hostname = Some(parent_hostname);
+        self
+    }
+
+    /// The variables that identify the module `module_id`.
+    pub fn module_env(&self, module_id: &str) -> HashMap<String, String> {
+        let mut env = HashMap::new();
+        env.insert(HUB_NAME_KEY.to_string(), self.iot_hub_name.clone());
+        env.insert(
+            GATEWAY_HOSTNAME_KEY.to_string(),
+            self.gateway_hostname.clone(),
+        );
+        env.insert(DEVICE_ID_KEY.to_string(), self.device_id.clone());
+        env.insert(MODULE_ID_KEY.to_string(), module_id.to_string());
+        env.insert(WORKLOAD_URI_KEY.to_string(), self.workload_uri.clone());
+        if let Some(generation_id) = self.generations.current(module_id) {
+            env.insert(GENERATION_ID_KEY.to_string(), generation_id);
+        }
+        env.insert(API_VERSION_KEY.to_string(), self.api_version.clone());
+        if let Some(ref signing_key) = self.signing_key {
+            env.insert(WORKLOAD_SIGNING_KEY_KEY.to_string(), signing_key.clone());
+        }
+        if let Some(skew) = self.trusted_clock.as_ref().and_then(TrustedClock::skew) {
+            env.insert(CLOCK_SKEW_KEY.to_string(), skew.num_seconds().to_string());
+        }
+        if let Some(ref parent_hostname) = self.parent_hostname {
+            env.insert(PARENT_HOSTNAME_KEY.to_string(), parent_hostname.clone());
+        }
+        env
+    }
+
+    /// The environment of the module `module_id`, whose spec sets `spec_env`.
+    pub fn apply(
+        &self,
+        module_id: &str,
+        spec_env: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = self
+            .custom
+            .iter()
+            .map(|var| (var.name.clone(), var.value.clone()))
+            .collect();
+        env.extend(self.module_env(module_id));
+        env.extend(spec_env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        env.insert(API_VERSION_KEY.to_string(), self.api_version.clone());
+        match self.signing_key {
+            Some(ref signing_key) => {
+                env.insert(WORKLOAD_SIGNING_KEY_KEY.to_string(), signing_key.clone());
+            }
+            None => {
+                env.remove(WORKLOAD_SIGNING_KEY_KEY);
+            }
+        }
+        env
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    fn provider() -> EnvProvider {
+        EnvProvider::new(
+            "hub.azure-devices.net",
+            "Gateway.Local",
+            "device1",
+            "unix:///var/run/iotedge/workload.sock",
+            "2018-06-28",
+        )
+    }
+
+    #[test]
+    fn modules_are_told_who_they_are() {
+        let env = provider().module_env("sensor");
+        assert_eq!("hub.azure-devices.net", env[HUB_NAME_KEY]);
+        assert_eq!("gateway.local", env[GATEWAY_HOSTNAME_KEY]);
+        assert_eq!("device1", env[DEVICE_ID_KEY]);
+        assert_eq!("sensor", env[MODULE_ID_KEY]);
+        assert_eq!("unix:///var/run/iotedge/workload.sock", env[WORKLOAD_URI_KEY]);
+        assert_eq!("2018-06-28", env[API_VERSION_KEY]);
+        assert!(!env.contains_key(GENERATION_ID_KEY));
+        assert!(!env.contains_key(WORKLOAD_SIGNING_KEY_KEY));
+        assert!(!env.contains_key(PARENT_HOSTNAME_KEY));
+
+        let env = provider()
+            .with_parent_hostname("parent.contoso.com".to_string())
+            .module_env("edgeHub");
+        assert_eq!("parent.contoso.com", env[PARENT_HOSTNAME_KEY]);
+    }
+
+    #[test]
+    fn signing_key_is_always_the_daemons() {
+        let mut spec_env = HashMap::new();
+        spec_env.insert(WORKLOAD_SIGNING_KEY_KEY.to_string(), "forged".to_string());
+        assert!(!provider()
+            .apply("sensor", &spec_env)
+            .contains_key(WORKLOAD_SIGNING_KEY_KEY));
+
+        let provider = provider().with_signing_key("MFkwEwYHKoZIzj0CAQ".to_string());
+        assert_eq!(
+            "MFkwEwYHKoZIzj0CAQ",
+            provider.apply("sensor", &spec_env)[WORKLOAD_SIGNING_KEY_KEY]
+        );
+    }
+
+    #[test]
+    fn measured_clock_skew_is_injected() {
+        let trusted_clock = TrustedClock::new();
+        let provider = provider().with_trusted_clock(trusted_clock.clone());
+        assert!(!provider.module_env("sensor").contains_key(CLOCK_SKEW_KEY));
+
+        trusted_clock.record(Utc::now() + Duration::hours(1));
+        let skew: i64 = provider.module_env("sensor")[CLOCK_SKEW_KEY]
+            .parse()
+            .unwrap();
+        assert!(skew > 3590 && skew <= 3600);
+    }
+
+    #[test]
+    fn known_generations_are_injected() {
+        let generations = GenerationRegistry::new();
+        generations.advance("sensor", "g1");
+        let provider = provider().with_generations(generations);
+
+        assert_eq!("g1", provider.module_env("sensor")[GENERATION_ID_KEY]);
+        assert!(!provider.module_env("other").contains_key(GENERATION_ID_KEY));
+    }
+
+    #[test]
+    fn spec_env_takes_precedence_over_custom_env() {
+        let provider = provider().with_custom_env(vec![
+            EnvVar::new("https_proxy", "http://proxy:3128"),
+            EnvVar::new("no_proxy", "localhost"),
+            EnvVar::new(DEVICE_ID_KEY, "custom"),
+        ]);
+        let mut spec_env = HashMap::new();
+        spec_env.insert("no_proxy".to_string(), "edgeHub".to_string());
+        spec_env.insert(API_VERSION_KEY.to_string(), "1970-01-01".to_string());
+
+        let env = provider.apply("sensor", &spec_env);
+        assert_eq!("http://proxy:3128", env["https_proxy"]);
+        assert_eq!("edgeHub", env["no_proxy"]);
+        assert_eq!("device1", env[DEVICE_ID_KEY]);
+        assert_eq!("2018-06-28", env[API_VERSION_KEY]);
+    }
+}
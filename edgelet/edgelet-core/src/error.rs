@@ -2,7 +2,9 @@
 
 use std::fmt;
 use std::fmt::Display;
+use std::io::{self, ErrorKind as IoErrorKind};
 use std::num::ParseIntError;
+use std::str::FromStr;
 
 use edgelet_utils::Error as UtilsError;
 use failure::{Backtrace, Context, Fail};
@@ -50,6 +52,238 @@ pub enum ErrorKind {
     Parse,
     #[fail(display = "Http error")]
     Http,
+    #[fail(display = "Invalid module name \"{}\"", _0)]
+    InvalidModuleName(String),
+    #[fail(display = "Invalid generation ID \"{}\"", _0)]
+    InvalidGenerationId(String),
+    #[fail(display = "Invalid namespace \"{}\"", _0)]
+    InvalidNamespace(String),
+    #[fail(display = "Invalid label \"{}\"", _0)]
+    InvalidLabel(String),
+    #[fail(display = "Invalid label selector \"{}\"", _0)]
+    InvalidLabelSelector(String),
+    #[fail(display = "Could not access the deployment history")]
+    DeploymentHistory,
+    #[fail(display = "Deployment {} was not found in the deployment history", _0)]
+    DeploymentNotFound(usize),
+    #[fail(display = "Invalid schedule \"{}\"", _0)]
+    InvalidSchedule(String),
+    #[fail(display = "Could not access the certificate registry")]
+    CertificateRegistry,
+    #[fail(display = "Could not access the sequence numbers of encrypt operations")]
+    SequenceNumbers,
+    #[fail(display = "Could not access the responses to requests with idempotency keys")]
+    IdempotencyKeys,
+    #[fail(display = "Certificate {} was not found in the certificate registry", _0)]
+    CertificateNotFound(String),
+    #[fail(display = "Module {} has used all {} of its HSM key slots", _0, _1)]
+    KeySlotQuotaExceeded(String, usize),
+    #[fail(display = "Invalid certificate")]
+    InvalidCertificate,
+    #[fail(display = "Invalid hostname \"{}\"", _0)]
+    InvalidHostname(String),
+    #[fail(display = "Invalid service type \"{}\"", _0)]
+    InvalidServiceType(String),
+    #[fail(display = "Could not access the outbound message queue")]
+    Outbox,
+    #[fail(display = "The certificate policy does not allow \"{}\" for this module", _0)]
+    CertificateNameNotAllowed(String),
+    #[fail(display = "The certificate policy does not allow {} keys", _0)]
+    KeyAlgorithmNotAllowed(String),
+    #[fail(display = "Generation ID \"{}\" of module \"{}\" has been superseded", _1, _0)]
+    GenerationSuperseded(String, String),
+    #[fail(display = "Invalid fault: {}", _0)]
+    InvalidFault(String),
+    #[fail(display = "Injected a fault into {}", _0)]
+    FaultInjected(String),
+    #[fail(display = "Could not back up the daemon state")]
+    Backup,
+    #[fail(display = "Could not restore the daemon state")]
+    Restore,
+    #[fail(display = "Invalid backup")]
+    InvalidBackup,
+    #[fail(display = "The passphrase is empty or does not match the backup")]
+    BackupPassphrase,
+    #[fail(display = "Could not rotate the Edge CA")]
+    CaRotation,
+    #[fail(display = "Malformed SAS token")]
+    MalformedToken,
+    #[fail(display = "The operation was cancelled")]
+    Cancelled,
+    #[fail(display = "Could not access the daemon metrics")]
+    Metrics,
+    #[fail(display = "Could not open the OS tracing facility")]
+    OsTracing,
+    #[fail(display = "Could not sign the workload API response")]
+    ResponseSigning,
+    #[fail(display = "Could not write the trust bundle files of the modules")]
+    TrustBundleFiles,
+    #[fail(display = "Could not record the version of the trust bundle")]
+    TrustBundleVersion,
+    #[fail(display = "Could not access the host processes")]
+    HostProcesses,
+    #[fail(display = "Invalid host process: {}", _0)]
+    InvalidHostProcess(String),
+    #[fail(display = "Host process {} is not registered", _0)]
+    HostProcessNotFound(String),
+    #[fail(display = "Could not record the disk usage")]
+    DiskUsage,
+    #[fail(display = "Invalid certificate signing request")]
+    InvalidCertificateRequest,
+    #[fail(display = "The HSM cannot issue certificates for certificate signing requests")]
+    CertificateRequestNotSupported,
+    #[fail(display = "The HSM cannot renew certificates")]
+    CertificateRenewalNotSupported,
+    #[fail(display = "The HSM cannot look up certificates by alias")]
+    CertificateLookupNotSupported,
+    #[fail(display = "The HSM cannot sign certificate revocation lists")]
+    CrlNotSupported,
+}
+
+impl ErrorKind {
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// only say which operation failed and leave what went wrong to their
+    /// cause.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::InvalidModuleName(_)
+            | ErrorKind::InvalidGenerationId(_)
+            | ErrorKind::InvalidNamespace(_)
+            | ErrorKind::InvalidLabel(_)
+            | ErrorKind::InvalidLabelSelector(_)
+            | ErrorKind::InvalidSchedule(_)
+            | ErrorKind::InvalidCertificate
+            | ErrorKind::InvalidCertificateRequest
+            | ErrorKind::InvalidHostname(_)
+            | ErrorKind::InvalidServiceType(_)
+            | ErrorKind::InvalidFault(_)
+            | ErrorKind::InvalidBackup
+            | ErrorKind::InvalidHostProcess(_)
+            | ErrorKind::BackupPassphrase
+            | ErrorKind::MalformedToken
+            | ErrorKind::CertificateNameNotAllowed(_)
+            | ErrorKind::KeyAlgorithmNotAllowed(_)
+            | ErrorKind::EdgeRuntimeIdentityNotFound
+            | ErrorKind::Parse => Some(ErrorCategory::UserConfig),
+            ErrorKind::Http => Some(ErrorCategory::TransientNetwork),
+            ErrorKind::Sign(_)
+            | ErrorKind::KeyStore
+            | ErrorKind::Activate
+            | ErrorKind::KeySlotQuotaExceeded(_, _)
+            | ErrorKind::ResponseSigning => Some(ErrorCategory::Hsm),
+            ErrorKind::ModuleRuntime
+            | ErrorKind::NotFound
+            | ErrorKind::Watchdog
+            | ErrorKind::DeploymentNotFound(_)
+            | ErrorKind::CertificateNotFound(_)
+            | ErrorKind::HostProcessNotFound(_)
+            | ErrorKind::GenerationSuperseded(_, _)
+            | ErrorKind::Cancelled => Some(ErrorCategory::Runtime),
+            _ => None,
+        }
+    }
+}
+
+/// What went wrong, in the few categories that callers act on. The HTTP APIs
+/// map it to the status of their error responses and report it in them, and
+/// the CLI maps it to its exit code.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorCategory {
+    /// The request, the config file or the setup of the device is wrong, and
+    /// trying again won't help.
+    UserConfig,
+    /// A service over the network, such as IoT Hub, DPS or a registry,
+    /// couldn't be reached or failed. Trying again later may help.
+    TransientNetwork,
+    /// The HSM failed.
+    Hsm,
+    /// The container runtime or a module failed.
+    Runtime,
+    /// Anything else.
+    Internal,
+}
+
+impl ErrorCategory {
+    /// The code a command exits with when it fails with an error of this
+    /// category.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCategory::Internal => 1,
+            ErrorCategory::UserConfig => 2,
+            ErrorCategory::TransientNetwork => 3,
+            ErrorCategory::Hsm => 4,
+            ErrorCategory::Runtime => 5,
+        }
+    }
+}
+
+impl Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let category = match *self {
+            ErrorCategory::UserConfig => "user-config",
+            ErrorCategory::TransientNetwork => "transient-network",
+            ErrorCategory::Hsm => "hsm",
+            ErrorCategory::Runtime => "runtime",
+            ErrorCategory::Internal => "internal",
+        };
+        write!(f, "{}", category)
+    }
+}
+
+impl FromStr for ErrorCategory {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "user-config" => Ok(ErrorCategory::UserConfig),
+            "transient-network" => Ok(ErrorCategory::TransientNetwork),
+            "hsm" => Ok(ErrorCategory::Hsm),
+            "runtime" => Ok(ErrorCategory::Runtime),
+            "internal" => Ok(ErrorCategory::Internal),
+            _ => Err(Error::from(ErrorKind::Parse)),
+        }
+    }
+}
+
+/// The category of the outermost of `error` and its causes that `classify`
+/// has one for, or `Internal` if there is none.
+///
+/// Each crate has a `classify_error` for its own errors that falls back to
+/// the one of the crates underneath it, so the errors of a crate are
+/// categorized by the crate that knows them however they are wrapped.
+pub fn error_category<F>(error: &Fail, classify: F) -> ErrorCategory
+where
+    F: Fn(&Fail) -> Option<ErrorCategory>,
+{
+    let mut fail = Some(error);
+    while let Some(cause) = fail {
+        if let Some(category) = classify(cause) {
+            return category;
+        }
+        fail = cause.cause();
+    }
+    ErrorCategory::Internal
+}
+
+/// Categorizes the errors of this crate, and IO errors that come from the
+/// network.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    if let Some(error) = error.downcast_ref::<Error>() {
+        error.kind().category()
+    } else if let Some(error) = error.downcast_ref::<io::Error>() {
+        match error.kind() {
+            IoErrorKind::ConnectionRefused
+            | IoErrorKind::ConnectionReset
+            | IoErrorKind::ConnectionAborted
+            | IoErrorKind::NotConnected
+            | IoErrorKind::AddrNotAvailable
+            | IoErrorKind::BrokenPipe
+            | IoErrorKind::TimedOut => Some(ErrorCategory::TransientNetwork),
+            _ => None,
+        }
+    } else {
+        None
+    }
 }
 
 impl Fail for Error {
@@ -76,6 +310,10 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
 }
 
 impl From<ErrorKind> for Error {
@@ -115,3 +353,40 @@ impl From<ParseIntError> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_comes_from_the_outermost_error_that_has_one() {
+        let error = Error::from(
+            Error::from(ErrorKind::InvalidModuleName("../m1".to_string()))
+                .context(ErrorKind::Identity),
+        );
+        assert_eq!(ErrorCategory::UserConfig, error.category());
+
+        let error = Error::from(
+            io::Error::from(IoErrorKind::ConnectionRefused)
+                .context(ErrorKind::Provision("could not reach DPS".to_string())),
+        );
+        assert_eq!(ErrorCategory::TransientNetwork, error.category());
+
+        let error = Error::from(io::Error::from(IoErrorKind::Other).context(ErrorKind::Io));
+        assert_eq!(ErrorCategory::Internal, error.category());
+    }
+
+    #[test]
+    fn category_round_trips_through_its_name() {
+        for category in &[
+            ErrorCategory::UserConfig,
+            ErrorCategory::TransientNetwork,
+            ErrorCategory::Hsm,
+            ErrorCategory::Runtime,
+            ErrorCategory::Internal,
+        ] {
+            assert_eq!(*category, category.to_string().parse().unwrap());
+        }
+        assert!("unknown".parse::<ErrorCategory>().is_err());
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use error::{Error, ErrorKind, Result};
+
+type Cleanup = Arc<Fn(&str, &str) + Send + Sync>;
+
+/// Tracks the current generation of each module identity.
+///
+/// A module identity that is deleted and created again in IoT Hub gets a new
+/// generation ID, and with it new keys and certificates. Requests made under
+/// the old generation ID are refused from then on, and whatever was issued
+/// for it is cleaned up. Both happen under the same lock as the requests
+/// themselves, so a request can't create anything for a generation that is
+/// being cleaned up. Modules the registry hasn't seen yet, such as after a
+/// restart of the daemon, are taken to be at any generation.
+///
+/// The runtime moves modules to a new generation as the workload API checks
+/// the generation of their callers, so both are handed clones of the same
+/// registry.
+#[derive(Clone, Default)]
+pub struct GenerationRegistry {
+    generations: Arc<Mutex<HashMap<String, String>>>,
+    cleanup: Option<Cleanup>,
+}
+
+impl GenerationRegistry {
+    pub fn new() -> Self {
+        GenerationRegistry::default()
+    }
+
+    /// Sets what is called with the module name and generation ID of each
+    /// generation that is superseded or deleted.
+    pub fn with_cleanup<F>(mut self, cleanup: F) -> Self
+    where
+        F: Fn(&str, &str) + Send + Sync + 'static,
+    {
+        self.cleanup = Some(Arc::new(cleanup));
+        self
+    }
+
+    pub fn current(&self, module: &str) -> Option<String> {
+        self.lock().get(module).cloned()
+    }
+
+    /// Records `generation_id` as the current generation of `module`,
+    /// cleaning up after the generation it replaces.
+    pub fn advance(&self, module: &str, generation_id: &str) {
+        let mut generations = self.lock();
+        let previous = generations.insert(module.to_string(), generation_id.to_string());
+        if let Some(previous) = previous {
+            if previous != generation_id {
+                info!(
+                    "Generation {} of module {} has been superseded by generation {}",
+                    previous, module, generation_id
+                );
+                self.clean_up(module, &previous);
+            }
+        }
+    }
+
+    /// Forgets about a module whose identity has been deleted, cleaning up
+    /// after its last generation.
+    pub fn forget(&self, module: &str) {
+        let mut generations = self.lock();
+        if let Some(previous) = generations.remove(module) {
+            self.clean_up(module, &previous);
+        }
+    }
+
+    /// Runs `f` as long as `generation_id` is the current generation of
+    /// `module`. The generation can't change while `f` runs.
+    pub fn with_current<F, R>(&self, module: &str, generation_id: &str, f: F) -> Result<R>
+    where
+        F: FnOnce() -> R,
+    {
+        let generations = self.lock();
+        match generations.get(module) {
+            Some(current) if current != generation_id => Err(Error::from(
+                ErrorKind::GenerationSuperseded(module.to_string(), generation_id.to_string()),
+            )),
+            _ => Ok(f()),
+        }
+    }
+
+    fn clean_up(&self, module: &str, generation_id: &str) {
+        if let Some(ref cleanup) = self.cleanup {
+            cleanup(module, generation_id);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<HashMap<String, String>> {
+        self.generations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_modules_are_at_any_generation() {
+        let generations = GenerationRegistry::new();
+        assert_eq!(1, generations.with_current("sensor", "g1", || 1).unwrap());
+        assert_eq!(None, generations.current("sensor"));
+    }
+
+    #[test]
+    fn superseded_generations_are_refused() {
+        let generations = GenerationRegistry::new();
+        generations.advance("sensor", "g1");
+        assert!(generations.with_current("sensor", "g1", || ()).is_ok());
+
+        generations.advance("sensor", "g2");
+        assert_eq!(Some("g2".to_string()), generations.current("sensor"));
+        let err = generations
+            .with_current("sensor", "g1", || panic!("superseded generation ran"))
+            .unwrap_err();
+        match *err.kind() {
+            ErrorKind::GenerationSuperseded(ref module, ref generation_id) => {
+                assert_eq!("sensor", module);
+                assert_eq!("g1", generation_id);
+            }
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+        assert!(generations.with_current("sensor", "g2", || ()).is_ok());
+    }
+
+    #[test]
+    fn superseded_and_deleted_generations_are_cleaned_up() {
+        let cleaned = Arc::new(Mutex::new(Vec::new()));
+        let generations = {
+            let cleaned = cleaned.clone();
+            GenerationRegistry::new().with_cleanup(move |module, generation_id| {
+                cleaned
+                    .lock()
+                    .unwrap()
+                    .push(format!("{}/{}", module, generation_id));
+            })
+        };
+
+        generations.advance("sensor", "g1");
+        generations.advance("sensor", "g1");
+        generations.advance("sensor", "g2");
+        generations.clone().forget("sensor");
+        generations.forget("sensor");
+
+        assert_eq!(
+            vec!["sensor/g1".to_string(), "sensor/g2".to_string()],
+            *cleaned.lock().unwrap()
+        );
+        assert_eq!(None, generations.current("sensor"));
+    }
+}
@@ -0,0 +1,509 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{DateTime, Utc};
+use edgelet_utils::log_failure;
+use futures::{future, Future, Stream};
+use log::Level;
+use tokio::timer::Interval;
+
+use clock::{Clock, SystemClock};
+use error::{Error, Result};
+use module::{Module, ModuleRuntime, ModuleStatus, RestartPolicy};
+use outbox::{MessageKind, Outbox};
+
+/// This is the frequency with which the monitor looks for modules that
+/// missed their heartbeat.
+const HEARTBEAT_FREQUENCY_SECS: u64 = 10;
+
+/// This is how many times in a row a module is restarted for missing its
+/// heartbeat before the monitor gives up on it.
+const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/// The heartbeat state of a module that has opted in by sending a heartbeat.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeartbeatEntry {
+    last_seen: DateTime<Utc>,
+    healthy: bool,
+    restarts: u32,
+}
+
+impl HeartbeatEntry {
+    pub fn last_seen(&self) -> &DateTime<Utc> {
+        &self.last_seen
+    }
+
+    pub fn healthy(&self) -> bool {
+        self.healthy
+    }
+
+    /// How many times the module has been restarted since its last heartbeat.
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+}
+
+/// What the monitor does about a module that missed its heartbeat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HeartbeatAction {
+    Restart,
+    Leave,
+}
+
+impl HeartbeatAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            HeartbeatAction::Restart => "restart",
+            HeartbeatAction::Leave => "none",
+        }
+    }
+}
+
+/// A module the module runtime lists, as far as the monitor is concerned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ListedModule {
+    running: bool,
+    restart_policy: Option<RestartPolicy>,
+}
+
+/// The result of a heartbeat check that failed, as reported to IoT Hub.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HeartbeatCheck<'a> {
+    check: &'a str,
+    module: &'a str,
+    healthy: bool,
+    action: &'a str,
+}
+
+/// Restarts modules that stop sending heartbeats while the module runtime
+/// still reports them as running, which is how a hung process looks from
+/// the outside. Modules opt in by sending their first heartbeat. Those whose
+/// restart policy doesn't restart unhealthy modules, or that kept missing
+/// their heartbeat after `max_restarts` restarts, are only reported. The
+/// workload API records heartbeats on a clone of the monitor while the
+/// original is running.
+#[derive(Clone)]
+pub struct HeartbeatMonitor {
+    interval: StdDuration,
+    max_restarts: u32,
+    entries: Arc<Mutex<HashMap<String, HeartbeatEntry>>>,
+    outbox: Outbox,
+    clock: Arc<Clock>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new(interval: StdDuration) -> Self {
+        HeartbeatMonitor {
+            interval,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            outbox: Outbox::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Reports every missed heartbeat to IoT Hub through `outbox`.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// Gives up on a module once it has been restarted `max_restarts` times
+    /// in a row without sending a heartbeat.
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Times heartbeats, and decides when they have been missed, by `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn interval(&self) -> StdDuration {
+        self.interval
+    }
+
+    /// Records a heartbeat from `module`, marking it healthy again.
+    pub fn beat(&self, module: &str) -> Result<()> {
+        self.beat_at(ensure_not_empty!(module), self.clock.now());
+        Ok(())
+    }
+
+    fn beat_at(&self, module: &str, now: DateTime<Utc>) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                module.to_string(),
+                HeartbeatEntry {
+                    last_seen: now,
+                    healthy: true,
+                    restarts: 0,
+                },
+            );
+    }
+
+    pub fn entry(&self, module: &str) -> Option<HeartbeatEntry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(module)
+            .cloned()
+    }
+
+    // Returns the running modules whose last heartbeat is older than the
+    // interval, marks them unhealthy and decides whether to restart them by
+    // their restart policy and how often they were restarted already. Their
+    // clock starts again so that the restarted process gets a full interval
+    // to send its first heartbeat, as do modules that aren't running at the
+    // moment. Modules the runtime no longer lists are forgotten.
+    fn take_expired(
+        &self,
+        modules: &HashMap<String, ListedModule>,
+        now: DateTime<Utc>,
+    ) -> Vec<(String, HeartbeatAction)> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|name, _| modules.contains_key(name));
+
+        let mut expired = vec![];
+        for (name, entry) in entries.iter_mut() {
+            let module = &modules[name];
+            if !module.running {
+                entry.last_seen = now;
+            } else if (now - entry.last_seen)
+                .to_std()
+                .map_or(false, |elapsed| elapsed > self.interval)
+            {
+                entry.last_seen = now;
+                entry.healthy = false;
+                let restarts = module
+                    .restart_policy
+                    .map_or(true, RestartPolicy::restarts_unhealthy);
+                let action = if restarts && entry.restarts < self.max_restarts {
+                    entry.restarts += 1;
+                    HeartbeatAction::Restart
+                } else {
+                    HeartbeatAction::Leave
+                };
+                expired.push((name.clone(), action));
+            }
+        }
+        expired.sort_by(|a, b| a.0.cmp(&b.0));
+        expired
+    }
+
+    // A full or broken outbox mustn't stop the module from being restarted.
+    fn report_expired(&self, module: &str, action: HeartbeatAction) {
+        let check = HeartbeatCheck {
+            check: "heartbeat",
+            module,
+            healthy: false,
+            action: action.as_str(),
+        };
+        if let Err(err) = self.outbox.push(MessageKind::CheckResult, &check) {
+            warn!("Could not queue the heartbeat check result for IoT Hub:");
+            log_failure(Level::Warn, &err);
+        }
+    }
+
+    pub fn run_until<M, F>(
+        self,
+        runtime: M,
+        shutdown_signal: F,
+    ) -> impl Future<Item = (), Error = Error>
+    where
+        M: 'static + ModuleRuntime + Clone,
+        M::Error: Into<Error>,
+        F: Future<Item = (), Error = ()> + 'static,
+    {
+        info!(
+            "Starting heartbeat monitor with a {} second interval...",
+            self.interval.as_secs()
+        );
+        let monitor = Interval::new(
+            Instant::now(),
+            StdDuration::from_secs(HEARTBEAT_FREQUENCY_SECS),
+        ).map_err(Error::from)
+        .for_each(move |_| {
+            let monitor = self.clone();
+            let runtime = runtime.clone();
+            listed_modules(&runtime)
+                .map(move |modules| {
+                    monitor
+                        .take_expired(&modules, monitor.clock.now())
+                        .into_iter()
+                        .filter_map(|(name, action)| {
+                            monitor.report_expired(&name, action);
+                            match action {
+                                HeartbeatAction::Restart => Some(restart_module(&runtime, name)),
+                                HeartbeatAction::Leave => {
+                                    warn!(
+                                        "Module {} missed its heartbeat and is unhealthy, \
+                                         but is not restarted by its restart policy",
+                                        name
+                                    );
+                                    None
+                                }
+                            }
+                        }).collect::<Vec<_>>()
+                }).and_then(future::join_all)
+                .map(|_| ())
+                .or_else(|err| {
+                    warn!("Could not check module heartbeats: {}", err);
+                    Ok(())
+                })
+        });
+
+        // Swallow any errors from shutdown_signal
+        let shutdown_signal = shutdown_signal.then(|_| Ok(()));
+
+        shutdown_signal
+            .select(monitor)
+            .then(|result| match result {
+                Ok(((), _)) => Ok(()),
+                Err((e, _)) => Err(e),
+            })
+    }
+}
+
+fn listed_modules<M>(
+    runtime: &M,
+) -> impl Future<Item = HashMap<String, ListedModule>, Error = Error>
+where
+    M: 'static + ModuleRuntime,
+    M::Error: Into<Error>,
+{
+    runtime
+        .list_with_details()
+        .map_err(Into::into)
+        .map(|(module, state)| {
+            let listed = ListedModule {
+                running: *state.status() == ModuleStatus::Running,
+                restart_policy: module.restart_policy(),
+            };
+            (module.name().to_string(), listed)
+        }).collect()
+        .map(|modules| modules.into_iter().collect())
+}
+
+// Errors are logged rather than returned so that one failing module doesn't
+// stop the monitor.
+fn restart_module<M>(runtime: &M, name: String) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime,
+    M::Error: Into<Error>,
+{
+    warn!(
+        "Module {} missed its heartbeat and is unhealthy, restarting it",
+        name
+    );
+    runtime.restart(&name).then(move |result| {
+        if let Err(err) = result {
+            let err: Error = err.into();
+            warn!("Could not restart unhealthy module {}: {}", name, err);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use clock::ManualClock;
+
+    fn monitor() -> HeartbeatMonitor {
+        HeartbeatMonitor::new(StdDuration::from_secs(30))
+    }
+
+    fn listed(names: &[&str], running: bool) -> HashMap<String, ListedModule> {
+        names
+            .iter()
+            .map(|name| {
+                let module = ListedModule {
+                    running,
+                    restart_policy: None,
+                };
+                (name.to_string(), module)
+            }).collect()
+    }
+
+    fn running(names: &[&str]) -> HashMap<String, ListedModule> {
+        listed(names, true)
+    }
+
+    fn restart(name: &str) -> (String, HeartbeatAction) {
+        (name.to_string(), HeartbeatAction::Restart)
+    }
+
+    #[test]
+    fn beat_rejects_empty_module() {
+        assert!(monitor().beat("").is_err());
+        assert!(monitor().beat("   ").is_err());
+    }
+
+    #[test]
+    fn modules_without_heartbeats_are_not_monitored() {
+        let monitor = monitor();
+        let now = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        assert!(monitor.take_expired(&running(&["m1"]), now).is_empty());
+        assert_eq!(None, monitor.entry("m1"));
+    }
+
+    #[test]
+    fn missed_heartbeat_marks_module_unhealthy() {
+        let monitor = monitor();
+        let start = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        monitor.beat_at("m1", start);
+        monitor.beat_at("m2", start + Duration::seconds(20));
+
+        let now = start + Duration::seconds(31);
+        assert_eq!(
+            vec![restart("m1")],
+            monitor.take_expired(&running(&["m1", "m2"]), now)
+        );
+        let entry = monitor.entry("m1").unwrap();
+        assert!(!entry.healthy());
+        assert_eq!(&now, entry.last_seen());
+        assert!(monitor.entry("m2").unwrap().healthy());
+
+        // the restarted module gets a full interval before it is restarted again
+        assert!(
+            monitor
+                .take_expired(&running(&["m1", "m2"]), now + Duration::seconds(29))
+                .is_empty()
+        );
+
+        monitor.beat_at("m1", now + Duration::seconds(5));
+        assert!(monitor.entry("m1").unwrap().healthy());
+    }
+
+    #[test]
+    fn heartbeat_is_missed_only_after_the_full_interval() {
+        let start = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        let clock = ManualClock::new(start);
+        let monitor = monitor().with_clock(clock.clone());
+        monitor.beat("m1").unwrap();
+        assert_eq!(&start, monitor.entry("m1").unwrap().last_seen());
+
+        let deadline = start + Duration::seconds(30);
+        assert!(monitor.take_expired(&running(&["m1"]), deadline).is_empty());
+        assert_eq!(
+            vec![restart("m1")],
+            monitor.take_expired(&running(&["m1"]), deadline + Duration::seconds(1))
+        );
+    }
+
+    #[test]
+    fn missed_heartbeat_is_reported() {
+        let outbox = Outbox::new(10);
+        let monitor = monitor().with_outbox(outbox.clone());
+        monitor.report_expired("m1", HeartbeatAction::Restart);
+
+        let messages = outbox.peek(10);
+        assert_eq!(1, messages.len());
+        assert_eq!(MessageKind::CheckResult, messages[0].kind());
+        assert_eq!("m1", messages[0].body()["module"]);
+        assert_eq!(false, messages[0].body()["healthy"]);
+        assert_eq!("restart", messages[0].body()["action"]);
+    }
+
+    #[test]
+    fn stopped_modules_are_not_restarted() {
+        let monitor = monitor();
+        let start = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        monitor.beat_at("m1", start);
+
+        let stopped_at = start + Duration::minutes(5);
+        assert!(
+            monitor
+                .take_expired(&listed(&["m1"], false), stopped_at)
+                .is_empty()
+        );
+        assert!(monitor.entry("m1").unwrap().healthy());
+
+        // once started again it has a full interval to send a heartbeat
+        let now = stopped_at + Duration::seconds(10);
+        assert!(monitor.take_expired(&running(&["m1"]), now).is_empty());
+        assert_eq!(
+            vec![restart("m1")],
+            monitor.take_expired(&running(&["m1"]), stopped_at + Duration::seconds(31))
+        );
+    }
+
+    #[test]
+    fn restart_policy_is_applied() {
+        let monitor = monitor();
+        let start = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        for name in &["m1", "m2", "m3"] {
+            monitor.beat_at(name, start);
+        }
+
+        let mut modules = running(&["m1", "m2", "m3"]);
+        modules.get_mut("m2").unwrap().restart_policy = Some(RestartPolicy::OnUnhealthy);
+        modules.get_mut("m3").unwrap().restart_policy = Some(RestartPolicy::OnFailure);
+        assert_eq!(
+            vec![
+                restart("m1"),
+                restart("m2"),
+                ("m3".to_string(), HeartbeatAction::Leave),
+            ],
+            monitor.take_expired(&modules, start + Duration::seconds(31))
+        );
+        assert!(!monitor.entry("m3").unwrap().healthy());
+        assert_eq!(0, monitor.entry("m3").unwrap().restarts());
+    }
+
+    #[test]
+    fn module_is_given_up_on_after_max_restarts() {
+        let monitor = monitor().with_max_restarts(2);
+        let start = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        monitor.beat_at("m1", start);
+
+        let modules = running(&["m1"]);
+        let mut now = start;
+        for restarts in 1..3 {
+            now = now + Duration::seconds(31);
+            assert_eq!(vec![restart("m1")], monitor.take_expired(&modules, now));
+            assert_eq!(restarts, monitor.entry("m1").unwrap().restarts());
+        }
+
+        now = now + Duration::seconds(31);
+        assert_eq!(
+            vec![("m1".to_string(), HeartbeatAction::Leave)],
+            monitor.take_expired(&modules, now)
+        );
+        assert_eq!(2, monitor.entry("m1").unwrap().restarts());
+
+        // a heartbeat makes the module eligible for restarts again
+        monitor.beat_at("m1", now);
+        assert_eq!(0, monitor.entry("m1").unwrap().restarts());
+        assert_eq!(
+            vec![restart("m1")],
+            monitor.take_expired(&modules, now + Duration::seconds(31))
+        );
+    }
+
+    #[test]
+    fn removed_modules_are_forgotten() {
+        let monitor = monitor();
+        let start = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        monitor.beat_at("m1", start);
+        monitor.beat_at("m2", start);
+
+        assert!(
+            monitor
+                .take_expired(&running(&["m2"]), start + Duration::seconds(5))
+                .is_empty()
+        );
+        assert_eq!(None, monitor.entry("m1"));
+        assert!(monitor.entry("m2").is_some());
+    }
+}
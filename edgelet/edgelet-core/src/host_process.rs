@@ -0,0 +1,350 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use failure::ResultExt;
+use serde_json;
+
+use env::EnvProvider;
+use error::{Error, ErrorKind, Result};
+use identity::ModuleName;
+use pid::Pid;
+
+type Inspector = Arc<Fn(i32) -> Option<(PathBuf, u32)> + Send + Sync>;
+
+/// A process running on the host rather than in a container, which uses the
+/// workload API as a module of its own.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct HostProcess {
+    name: String,
+    executable: PathBuf,
+    uid: u32,
+}
+
+impl HostProcess {
+    /// The name of the module identity the process uses.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The path of the executable the process is expected to run.
+    pub fn executable(&self) -> &Path {
+        &self.executable
+    }
+
+    /// The user the process is expected to run as.
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+}
+
+/// Tracks the host processes registered as modules.
+///
+/// Native agents that predate containers can't be handed their environment
+/// when they are created, so each gets an env file instead, with the
+/// variables a module container would get, for whatever starts it to load.
+/// A caller of the workload API is taken to be a host process when it runs
+/// the registered executable as the registered user.
+///
+/// When created with `load` every change is persisted to disk. Processes
+/// registered through the management API are recognized by the workload API
+/// right away, without a restart.
+#[derive(Clone)]
+pub struct HostProcesses {
+    path: Option<PathBuf>,
+    env_files: Option<(EnvProvider, PathBuf)>,
+    processes: Arc<Mutex<BTreeMap<String, HostProcess>>>,
+    inspect: Inspector,
+}
+
+impl Default for HostProcesses {
+    fn default() -> Self {
+        HostProcesses {
+            path: None,
+            env_files: None,
+            processes: Arc::new(Mutex::new(BTreeMap::new())),
+            inspect: Arc::new(inspect_process),
+        }
+    }
+}
+
+impl HostProcesses {
+    pub fn new() -> Self {
+        HostProcesses::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let processes = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::HostProcesses)?;
+            serde_json::from_reader(file).context(ErrorKind::HostProcesses)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(HostProcesses {
+            path: Some(path),
+            processes: Arc::new(Mutex::new(processes)),
+            ..HostProcesses::default()
+        })
+    }
+
+    /// Checks that `name` is a valid module name and that `executable` is an
+    /// absolute path.
+    pub fn validate(name: &str, executable: &Path) -> Result<()> {
+        ModuleName::new(name)?;
+        if executable.is_absolute() {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::InvalidHostProcess(format!(
+                "executable path {} is not absolute",
+                executable.display()
+            ))))
+        }
+    }
+
+    /// Writes the env file of each host process to `dir`, with the
+    /// environment `env_provider` gives modules.
+    pub fn with_env_files(mut self, env_provider: EnvProvider, dir: PathBuf) -> Self {
+        self.env_files = Some((env_provider, dir));
+        self
+    }
+
+    /// Sets how the executable and user of a caller are looked up from its
+    /// pid.
+    pub fn with_inspector<F>(mut self, inspect: F) -> Self
+    where
+        F: Fn(i32) -> Option<(PathBuf, u32)> + Send + Sync + 'static,
+    {
+        self.inspect = Arc::new(inspect);
+        self
+    }
+
+    /// Registers the process running `executable` as `uid` as the module
+    /// `name`, replacing any process registered under that name before.
+    pub fn register(&self, name: &str, executable: &Path, uid: u32) -> Result<HostProcess> {
+        HostProcesses::validate(name, executable)?;
+
+        let process = HostProcess {
+            name: name.to_string(),
+            executable: executable.to_path_buf(),
+            uid,
+        };
+        let mut processes = self.lock();
+        processes.insert(process.name.clone(), process.clone());
+        self.persist(&processes)?;
+        self.write_env_file(&process.name)?;
+        Ok(process)
+    }
+
+    pub fn unregister(&self, name: &str) -> Result<HostProcess> {
+        let mut processes = self.lock();
+        let process = processes
+            .remove(name)
+            .ok_or_else(|| ErrorKind::HostProcessNotFound(name.to_string()))?;
+        self.persist(&processes)?;
+        if let Some(path) = self.env_file(name) {
+            if path.exists() {
+                fs::remove_file(path).context(ErrorKind::HostProcesses)?;
+            }
+        }
+        Ok(process)
+    }
+
+    pub fn get(&self, name: &str) -> Option<HostProcess> {
+        self.lock().get(name).cloned()
+    }
+
+    pub fn list(&self) -> Vec<HostProcess> {
+        self.lock().values().cloned().collect()
+    }
+
+    /// The path of the env file of the host process `name`.
+    pub fn env_file(&self, name: &str) -> Option<PathBuf> {
+        self.env_files
+            .as_ref()
+            .map(|(_, dir)| dir.join(format!("{}.env", name)))
+    }
+
+    /// Writes the env files of every host process again, since what modules
+    /// are told, such as the workload URI, may have changed since they were
+    /// written.
+    pub fn refresh_env_files(&self) -> Result<()> {
+        let processes = self.lock();
+        for name in processes.keys() {
+            self.write_env_file(name)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the caller with `pid` is the host process `name`.
+    pub fn is_caller(&self, name: &str, pid: Pid) -> bool {
+        let process = match self.get(name) {
+            Some(process) => process,
+            None => return false,
+        };
+        match pid {
+            Pid::Value(value) => match (self.inspect)(value) {
+                Some((executable, uid)) => {
+                    let authorized = executable == process.executable && uid == process.uid;
+                    if !authorized {
+                        info!(
+                            "Request not authorized - caller {} runs {} as uid {}, expected {} \
+                             as uid {}",
+                            pid,
+                            executable.display(),
+                            uid,
+                            process.executable.display(),
+                            process.uid
+                        );
+                    }
+                    authorized
+                }
+                None => false,
+            },
+            // a caller whose pid can't be told can't be shown to run the
+            // registered executable, so it doesn't act as the host process
+            Pid::Any | Pid::None => false,
+        }
+    }
+
+    fn write_env_file(&self, name: &str) -> Result<()> {
+        if let Some((ref env_provider, ref dir)) = self.env_files {
+            let mut env: Vec<(String, String)> = env_provider
+                .module_env(name)
+                .into_iter()
+                .collect();
+            env.sort();
+
+            fs::create_dir_all(dir).context(ErrorKind::HostProcesses)?;
+            let path = dir.join(format!("{}.env", name));
+            let temp = path.with_extension("tmp");
+            let mut file = File::create(&temp).context(ErrorKind::HostProcesses)?;
+            for (key, value) in env {
+                writeln!(file, "{}={}", key, value).context(ErrorKind::HostProcesses)?;
+            }
+            fs::rename(&temp, path).context(ErrorKind::HostProcesses)?;
+        }
+        Ok(())
+    }
+
+    fn persist(&self, processes: &BTreeMap<String, HostProcess>) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::HostProcesses)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::HostProcesses)?;
+            serde_json::to_writer(file, processes).context(ErrorKind::HostProcesses)?;
+            fs::rename(&temp, path).context(ErrorKind::HostProcesses)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<BTreeMap<String, HostProcess>> {
+        self.processes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn inspect_process(pid: i32) -> Option<(PathBuf, u32)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = PathBuf::from(format!("/proc/{}", pid));
+    let executable = fs::read_link(dir.join("exe")).ok()?;
+    let uid = fs::metadata(&dir).ok()?.uid();
+    Some((executable, uid))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn inspect_process(_pid: i32) -> Option<(PathBuf, u32)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn callers_must_run_the_registered_executable_as_the_registered_user() {
+        let processes = HostProcesses::new().with_inspector(|pid| match pid {
+            10 => Some((PathBuf::from("/usr/bin/agent"), 1000)),
+            11 => Some((PathBuf::from("/usr/bin/agent"), 0)),
+            12 => Some((PathBuf::from("/usr/bin/other"), 1000)),
+            _ => None,
+        });
+        processes
+            .register("agent", Path::new("/usr/bin/agent"), 1000)
+            .unwrap();
+
+        assert!(processes.is_caller("agent", Pid::Value(10)));
+        assert!(!processes.is_caller("agent", Pid::Value(11)));
+        assert!(!processes.is_caller("agent", Pid::Value(12)));
+        assert!(!processes.is_caller("agent", Pid::Value(13)));
+        assert!(!processes.is_caller("agent", Pid::None));
+        assert!(!processes.is_caller("agent", Pid::Any));
+        assert!(!processes.is_caller("other", Pid::Value(10)));
+    }
+
+    #[test]
+    fn executable_path_must_be_absolute() {
+        let processes = HostProcesses::new();
+        match *processes
+            .register("agent", Path::new("bin/agent"), 1000)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::InvalidHostProcess(_) => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+        assert!(processes.list().is_empty());
+    }
+
+    #[test]
+    fn registrations_survive_restarts_and_get_env_files() {
+        let tmp_dir = TempDir::new("host_process").unwrap();
+        let dir = tmp_dir.path();
+        let env_provider = EnvProvider::new(
+            "hub.azure-devices.net",
+            "gateway.local",
+            "device1",
+            "unix:///var/run/iotedge/workload.sock",
+            "2018-06-28",
+        );
+        let processes = HostProcesses::load(dir.join("host_processes.json"))
+            .unwrap()
+            .with_env_files(env_provider, dir.join("env"));
+        processes
+            .register("agent", Path::new("/usr/bin/agent"), 1000)
+            .unwrap();
+
+        let env_file = processes.env_file("agent").unwrap();
+        let env = fs::read_to_string(&env_file).unwrap();
+        assert!(env.contains("IOTEDGE_MODULEID=agent\n"));
+        assert!(env.contains("IOTEDGE_WORKLOADURI=unix:///var/run/iotedge/workload.sock\n"));
+
+        let processes = HostProcesses::load(dir.join("host_processes.json")).unwrap();
+        assert_eq!(
+            Path::new("/usr/bin/agent"),
+            processes.get("agent").unwrap().executable()
+        );
+
+        let processes = processes.with_env_files(
+            EnvProvider::new("hub", "gateway", "device1", "unix:///w.sock", "2018-06-28"),
+            dir.join("env"),
+        );
+        processes.unregister("agent").unwrap();
+        assert!(!env_file.exists());
+        assert!(processes.get("agent").is_none());
+        match *processes.unregister("agent").unwrap_err().kind() {
+            ErrorKind::HostProcessNotFound(ref name) => assert_eq!("agent", name),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration as StdDuration;
+
+use base64;
+use chrono::{DateTime, Duration, Utc};
+use failure::ResultExt;
+use serde_json;
+
+use clock::{Clock, SystemClock};
+use error::{ErrorKind, Result};
+
+/// How long the responses to requests with idempotency keys are kept by
+/// default. Retries come within minutes, so this leaves plenty of room.
+pub const DEFAULT_IDEMPOTENCY_WINDOW_SECS: u64 = 3600;
+
+/// The response to a request that was made with an idempotency key, kept to
+/// be returned again when the request is replayed.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdempotentResponse {
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    /// The body, base64 encoded.
+    body: String,
+}
+
+impl IdempotentResponse {
+    pub fn new(status: u16, content_type: Option<String>, body: &[u8]) -> Self {
+        IdempotentResponse {
+            status,
+            content_type,
+            body: base64::encode(body),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        base64::decode(&self.body).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    response: IdempotentResponse,
+    recorded_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct State {
+    records: BTreeMap<String, Record>,
+    // requests that are being handled, which aren't persisted since they
+    // are cut short by a restart
+    pending: BTreeSet<String>,
+}
+
+/// What to do with a request made with an idempotency key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Replay {
+    /// The key wasn't seen within the window. The request is handled, and
+    /// its response is `complete`d or the key `abandon`ed afterwards.
+    Handle,
+    /// A request with the key is still being handled.
+    InProgress,
+    /// A request with the key was handled already, and this was its
+    /// response.
+    Respond(IdempotentResponse),
+}
+
+/// Remembers the responses to requests made with an idempotency key for a
+/// window of time, so that a client that retries a request it didn't get the
+/// response to gets the response of the first attempt back instead of having
+/// the request carried out twice.
+///
+/// Keys are forgotten once the window has passed since the response was
+/// recorded. When created with `load`, the responses are persisted to disk,
+/// so that retries across a restart of the daemon are caught too. The routes
+/// that accept a key all record their responses here, under the method and
+/// path of the request along with the key.
+#[derive(Clone)]
+pub struct IdempotencyKeys {
+    path: Option<PathBuf>,
+    window: Duration,
+    state: Arc<Mutex<State>>,
+    clock: Arc<Clock>,
+}
+
+impl IdempotencyKeys {
+    pub fn new(window: StdDuration) -> Self {
+        IdempotencyKeys {
+            path: None,
+            window: window_duration(window),
+            state: Arc::new(Mutex::new(State::default())),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, window: StdDuration) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let records = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::IdempotencyKeys)?;
+            serde_json::from_reader(file).context(ErrorKind::IdempotencyKeys)?
+        } else {
+            BTreeMap::new()
+        };
+
+        let keys = IdempotencyKeys::new(window);
+        keys.lock().records = records;
+        Ok(IdempotencyKeys {
+            path: Some(path),
+            ..keys
+        })
+    }
+
+    /// Decides which keys have expired by the time of `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Tells whether the request with idempotency key `key` is handled or
+    /// replayed. When it is handled, the key is held until the request is
+    /// `complete`d or `abandon`ed.
+    pub fn begin(&self, key: &str) -> Replay {
+        let mut state = self.lock();
+        self.evict(&mut state);
+        if let Some(record) = state.records.get(key) {
+            return Replay::Respond(record.response.clone());
+        }
+        if !state.pending.insert(key.to_string()) {
+            return Replay::InProgress;
+        }
+        Replay::Handle
+    }
+
+    /// Records `response` as the response to the request with idempotency
+    /// key `key`, which replays of it get from now on.
+    pub fn complete(&self, key: &str, response: IdempotentResponse) -> Result<()> {
+        let mut state = self.lock();
+        state.pending.remove(key);
+        let record = Record {
+            response,
+            recorded_at: self.clock.now(),
+        };
+        state.records.insert(key.to_string(), record);
+        self.evict(&mut state);
+        self.persist(&state.records)
+    }
+
+    /// Lets go of the key of a request that failed in a way that is worth
+    /// retrying, so that the next attempt is handled again.
+    pub fn abandon(&self, key: &str) {
+        self.lock().pending.remove(key);
+    }
+
+    fn evict(&self, state: &mut State) {
+        let expired_before = self.clock.now() - self.window;
+        let expired: Vec<String> = state
+            .records
+            .iter()
+            .filter(|&(_, record)| record.recorded_at < expired_before)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            state.records.remove(&key);
+        }
+    }
+
+    fn persist(&self, records: &BTreeMap<String, Record>) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::IdempotencyKeys)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::IdempotencyKeys)?;
+            serde_json::to_writer(file, records).context(ErrorKind::IdempotencyKeys)?;
+            fs::rename(&temp, path).context(ErrorKind::IdempotencyKeys)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Windows too long for chrono are cut to a century.
+fn window_duration(window: StdDuration) -> Duration {
+    let max = Duration::days(100 * 365);
+    Duration::from_std(window).map_or(max, |window| window.min(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tempdir::TempDir;
+
+    use clock::ManualClock;
+
+    use super::*;
+
+    fn created() -> IdempotentResponse {
+        IdempotentResponse::new(
+            201,
+            Some("application/json".to_string()),
+            b"{\"name\":\"m1\"}",
+        )
+    }
+
+    #[test]
+    fn replays_get_the_first_response() {
+        let keys = IdempotencyKeys::new(StdDuration::from_secs(60));
+        assert_eq!(Replay::Handle, keys.begin("POST /modules k1"));
+        assert_eq!(Replay::InProgress, keys.begin("POST /modules k1"));
+        assert_eq!(Replay::Handle, keys.begin("POST /modules k2"));
+
+        keys.complete("POST /modules k1", created()).unwrap();
+        match keys.clone().begin("POST /modules k1") {
+            Replay::Respond(response) => {
+                assert_eq!(201, response.status());
+                assert_eq!(Some("application/json"), response.content_type());
+                assert_eq!(b"{\"name\":\"m1\"}".to_vec(), response.body());
+            }
+            replay => panic!("unexpected {:?}", replay),
+        }
+    }
+
+    #[test]
+    fn abandoned_keys_are_handled_again() {
+        let keys = IdempotencyKeys::new(StdDuration::from_secs(60));
+        assert_eq!(Replay::Handle, keys.begin("k1"));
+        keys.abandon("k1");
+        assert_eq!(Replay::Handle, keys.begin("k1"));
+    }
+
+    #[test]
+    fn keys_expire_after_the_window() {
+        let clock = ManualClock::new(Utc.ymd(2018, 6, 28).and_hms(0, 0, 0));
+        let keys = IdempotencyKeys::new(StdDuration::from_secs(60)).with_clock(clock.clone());
+        assert_eq!(Replay::Handle, keys.begin("k1"));
+        keys.complete("k1", created()).unwrap();
+
+        clock.advance(Duration::seconds(59));
+        assert_eq!(Replay::Respond(created()), keys.begin("k1"));
+
+        clock.advance(Duration::seconds(2));
+        assert_eq!(Replay::Handle, keys.begin("k1"));
+    }
+
+    #[test]
+    fn responses_survive_restarts() {
+        let tmp_dir = TempDir::new("idempotency").unwrap();
+        let path = tmp_dir.path().join("idempotency-keys.json");
+
+        let keys = IdempotencyKeys::load(&path, StdDuration::from_secs(60)).unwrap();
+        assert_eq!(Replay::Handle, keys.begin("k1"));
+        assert_eq!(Replay::Handle, keys.begin("k2"));
+        keys.complete("k1", created()).unwrap();
+
+        let keys = IdempotencyKeys::load(&path, StdDuration::from_secs(60)).unwrap();
+        assert_eq!(Replay::Respond(created()), keys.begin("k1"));
+        assert_eq!(Replay::Handle, keys.begin("k2"));
+    }
+}
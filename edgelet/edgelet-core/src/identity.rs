@@ -1,10 +1,16 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::fmt;
+use std::str::FromStr;
 
 use failure::Fail;
 use futures::Future;
 
+use error::{Error, ErrorKind};
+
+/// Maximum length IoT Hub allows for device and module identifiers.
+const MAX_ID_LENGTH: usize = 128;
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 pub enum AuthType {
     None,
@@ -23,11 +29,115 @@ impl fmt::Display for AuthType {
     }
 }
 
+/// A module identifier that satisfies IoT Hub's naming rules: up to 128
+/// ASCII alphanumeric characters plus `- : . + % _ # * ? ! ( ) , = @ ; $ '`.
+/// Names made up only of dots are rejected as well since they are
+/// meaningless as identities and dangerous as path or alias components.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ModuleName(String);
+
+impl ModuleName {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_ID_LENGTH
+            && name.chars().all(is_module_name_char)
+            && !name.chars().all(|c| c == '.');
+
+        if valid {
+            Ok(ModuleName(name.to_string()))
+        } else {
+            Err(Error::from(ErrorKind::InvalidModuleName(name.to_string())))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn is_module_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-:.+%_#*?!(),=@;$'".contains(c)
+}
+
+impl AsRef<str> for ModuleName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ModuleName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for ModuleName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ModuleName::new(s)
+    }
+}
+
+/// A module generation identifier as assigned by IoT Hub: up to 128 ASCII
+/// alphanumeric characters or dashes.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GenerationId(String);
+
+impl GenerationId {
+    pub fn new(id: &str) -> Result<Self, Error> {
+        let valid = !id.is_empty()
+            && id.len() <= MAX_ID_LENGTH
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+        if valid {
+            Ok(GenerationId(id.to_string()))
+        } else {
+            Err(Error::from(ErrorKind::InvalidGenerationId(id.to_string())))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for GenerationId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for GenerationId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for GenerationId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GenerationId::new(s)
+    }
+}
+
 pub trait Identity {
     fn module_id(&self) -> &str;
     fn managed_by(&self) -> &str;
     fn generation_id(&self) -> &str;
     fn auth_type(&self) -> AuthType;
+
+    /// The base64 encoded primary symmetric key of the identity, if it
+    /// authenticates with symmetric keys and the identity manager has it.
+    fn primary_key(&self) -> Option<&str> {
+        None
+    }
+
+    /// The base64 encoded secondary symmetric key of the identity.
+    fn secondary_key(&self) -> Option<&str> {
+        None
+    }
 }
 
 pub struct IdentitySpec {
@@ -83,3 +193,58 @@ pub trait IdentityManager {
     fn get(&self, id: IdentitySpec) -> Self::GetFuture;
     fn delete(&mut self, id: IdentitySpec) -> Self::DeleteFuture;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_name_accepts_hub_characters() {
+        let names = [
+            "edgeAgent",
+            "$edgeHub",
+            "m1",
+            "a-b:c.d+e%f_g#h*i?j!k(l)m,n=o@p;q$r's",
+        ];
+        for name in &names {
+            assert_eq!(*name, ModuleName::new(name).unwrap().as_str());
+        }
+        let longest = "a".repeat(MAX_ID_LENGTH);
+        assert!(ModuleName::new(&longest).is_ok());
+    }
+
+    #[test]
+    fn module_name_rejects_invalid() {
+        let too_long = "a".repeat(MAX_ID_LENGTH + 1);
+        let names = [
+            "",
+            ".",
+            "..",
+            "../server",
+            "a/b",
+            "a\\b",
+            "a b",
+            "m\u{e9}",
+            too_long.as_str(),
+        ];
+        for name in &names {
+            match ModuleName::new(name) {
+                Err(ref err) => match *err.kind() {
+                    ErrorKind::InvalidModuleName(ref n) => assert_eq!(*name, n.as_str()),
+                    _ => panic!("unexpected error kind {:?}", err.kind()),
+                },
+                Ok(_) => panic!("expected {:?} to be rejected", name),
+            }
+        }
+    }
+
+    #[test]
+    fn generation_id_validates() {
+        assert!(GenerationId::new("636704968692034950").is_ok());
+        assert!(GenerationId::new("I").is_ok());
+        assert!(GenerationId::new("").is_err());
+        assert!(GenerationId::new("1.2").is_err());
+        assert!(GenerationId::new("%2f").is_err());
+        assert!(GenerationId::new(&"1".repeat(MAX_ID_LENGTH + 1)).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use chrono::{DateTime, Utc};
+
+use certificate_alias::CertificateAlias;
+use clock::{Clock, SystemClock};
+use error::{ErrorKind, Result};
+use revocation::IssuedCertificate;
+
+type Destroy = Arc<Fn(&str) -> Result<()> + Send + Sync>;
+
+#[derive(Clone, Debug)]
+struct Slot {
+    module_id: String,
+    // unknown while the certificate is being issued
+    expires_at: Option<DateTime<Utc>>,
+    last_used: DateTime<Utc>,
+}
+
+/// An HSM alias a module holds a key slot for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyAlias {
+    alias: String,
+    module_id: String,
+    generation_id: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used: DateTime<Utc>,
+}
+
+impl KeyAlias {
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    pub fn module_id(&self) -> &str {
+        &self.module_id
+    }
+
+    /// The generation of the module identity the certificate under the
+    /// alias was issued for. Identity certificates aren't tied to a
+    /// generation.
+    pub fn generation_id(&self) -> Option<&str> {
+        self.generation_id.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn expires_at(&self) -> Option<&DateTime<Utc>> {
+        self.expires_at.as_ref()
+    }
+
+    /// When a certificate was last asked for under the alias.
+    pub fn last_used(&self) -> &DateTime<Utc> {
+        &self.last_used
+    }
+}
+
+/// The HSM key slots a module holds, one per certificate alias.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotUsage {
+    module_id: String,
+    aliases: Vec<String>,
+    quota: Option<usize>,
+}
+
+impl SlotUsage {
+    pub fn module_id(&self) -> &str {
+        &self.module_id
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    pub fn used(&self) -> usize {
+        self.aliases.len()
+    }
+
+    pub fn quota(&self) -> Option<usize> {
+        self.quota
+    }
+}
+
+/// Tracks the HSM key slots taken up by the certificates modules request
+/// through the workload API, and caps how many each module can hold.
+///
+/// A certificate keeps its slot until its alias is released, such as when
+/// the generation it was issued for is retired, or until it expires and the
+/// module asks for another one. Expired aliases are evicted then, so that a
+/// module that keeps rotating its certificates doesn't run out of slots.
+///
+/// Slots aren't persisted. The daemon restores them from the certificates in
+/// the certificate registry when it starts. Slots taken by the workload API
+/// are seen and released by the management API, which holds a clone.
+#[derive(Clone)]
+pub struct KeySlots {
+    quota: Option<usize>,
+    slots: Arc<Mutex<BTreeMap<String, Slot>>>,
+    clock: Arc<Clock>,
+    destroy: Option<Destroy>,
+}
+
+impl Default for KeySlots {
+    fn default() -> Self {
+        KeySlots {
+            quota: None,
+            slots: Arc::new(Mutex::new(BTreeMap::new())),
+            clock: Arc::new(SystemClock),
+            destroy: None,
+        }
+    }
+}
+
+impl KeySlots {
+    pub fn new() -> Self {
+        KeySlots::default()
+    }
+
+    /// Caps the number of slots each module can hold at `quota`.
+    pub fn with_quota(mut self, quota: usize) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Decides which slots have expired by the time of `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets what is called to destroy the certificate under an alias in the
+    /// HSM when a module's slots are released with `release_module`.
+    pub fn with_destroy<F>(mut self, destroy: F) -> Self
+    where
+        F: Fn(&str) -> Result<()> + Send + Sync + 'static,
+    {
+        self.destroy = Some(Arc::new(destroy));
+        self
+    }
+
+    pub fn quota(&self) -> Option<usize> {
+        self.quota
+    }
+
+    /// Takes up the slots of certificates issued before the daemon started.
+    pub fn restore(&self, certs: &[IssuedCertificate]) {
+        let mut slots = self.lock();
+        for cert in certs {
+            slots.insert(
+                cert.alias().to_string(),
+                Slot {
+                    module_id: cert.module_id().to_string(),
+                    expires_at: Some(*cert.expires_at()),
+                    last_used: *cert.issued_at(),
+                },
+            );
+        }
+    }
+
+    /// Reserves a slot for `alias` before a certificate is issued to
+    /// `module_id` under it. An alias that already has a slot keeps it.
+    ///
+    /// The module's other aliases whose certificates have expired are
+    /// evicted first and returned, for their certificates to be destroyed in
+    /// the HSM. Fails if the module already holds as many slots as its quota
+    /// allows.
+    pub fn reserve(&self, module_id: &str, alias: &str) -> Result<Vec<String>> {
+        let now = self.clock.now();
+        let mut slots = self.lock();
+
+        let evicted: Vec<String> = slots
+            .iter()
+            .filter(|&(held, slot)| {
+                held != alias
+                    && slot.module_id == module_id
+                    && slot.expires_at.map_or(false, |expires_at| expires_at <= now)
+            }).map(|(held, _)| held.clone())
+            .collect();
+        for held in &evicted {
+            slots.remove(held);
+        }
+
+        if let Some(slot) = slots.get_mut(alias) {
+            slot.last_used = now;
+        } else {
+            if let Some(quota) = self.quota {
+                let used = slots
+                    .values()
+                    .filter(|slot| slot.module_id == module_id)
+                    .count();
+                if used >= quota {
+                    let kind = ErrorKind::KeySlotQuotaExceeded(module_id.to_string(), quota);
+                    return Err(kind.into());
+                }
+            }
+            slots.insert(
+                alias.to_string(),
+                Slot {
+                    module_id: module_id.to_string(),
+                    expires_at: None,
+                    last_used: now,
+                },
+            );
+        }
+
+        Ok(evicted)
+    }
+
+    /// Records that the certificate under `alias` expires at `expires_at`.
+    pub fn occupy(&self, module_id: &str, alias: &str, expires_at: DateTime<Utc>) {
+        let now = self.clock.now();
+        self.lock().insert(
+            alias.to_string(),
+            Slot {
+                module_id: module_id.to_string(),
+                expires_at: Some(expires_at),
+                last_used: now,
+            },
+        );
+    }
+
+    /// Whether a slot is held for `alias`.
+    pub fn holds(&self, alias: &str) -> bool {
+        self.lock().contains_key(alias)
+    }
+
+    /// Frees the slot of `alias` once its certificate is gone from the HSM.
+    pub fn release(&self, alias: &str) {
+        self.lock().remove(alias);
+    }
+
+    /// Destroys the certificates under every alias `module_id` holds a slot
+    /// for and frees their slots, such as when the module is removed.
+    /// Returns the aliases that were freed. An alias whose certificate
+    /// couldn't be destroyed keeps its slot.
+    pub fn release_module(&self, module_id: &str) -> Vec<String> {
+        let aliases: Vec<String> = self
+            .lock()
+            .iter()
+            .filter(|&(_, slot)| slot.module_id == module_id)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+
+        self.destroy_all(aliases)
+    }
+
+    /// Destroys the certificates under the aliases whose module identity, or
+    /// the generation of it they were issued for, is gone, and frees their
+    /// slots. `generations` has the current generation of each module
+    /// identity there is. Returns the aliases that were freed.
+    pub fn release_orphans(&self, generations: &HashMap<String, String>) -> Vec<String> {
+        let orphans = self
+            .aliases()
+            .into_iter()
+            .filter(|alias| match generations.get(alias.module_id()) {
+                Some(current) => alias
+                    .generation_id()
+                    .map_or(false, |generation_id| generation_id != current),
+                None => true,
+            }).map(|alias| alias.alias)
+            .collect();
+        self.destroy_all(orphans)
+    }
+
+    // An alias whose certificate couldn't be destroyed keeps its slot.
+    fn destroy_all(&self, aliases: Vec<String>) -> Vec<String> {
+        let mut released = Vec::new();
+        for alias in aliases {
+            if let Some(ref destroy) = self.destroy {
+                if let Err(err) = destroy(&alias) {
+                    warn!("Could not destroy certificate {}: {}", alias, err);
+                    continue;
+                }
+            }
+            self.release(&alias);
+            released.push(alias);
+        }
+        released
+    }
+
+    /// Lists the aliases modules hold slots for, ordered by alias.
+    pub fn aliases(&self) -> Vec<KeyAlias> {
+        self.lock()
+            .iter()
+            .map(|(alias, slot)| KeyAlias {
+                alias: alias.clone(),
+                module_id: slot.module_id.clone(),
+                generation_id: CertificateAlias::parse(&slot.module_id, alias)
+                    .and_then(|alias| alias.generation_id().map(ToString::to_string)),
+                expires_at: slot.expires_at,
+                last_used: slot.last_used,
+            }).collect()
+    }
+
+    /// Lists the slots held by each module, ordered by module.
+    pub fn usage(&self) -> Vec<SlotUsage> {
+        let mut usage: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (alias, slot) in self.lock().iter() {
+            usage
+                .entry(slot.module_id.clone())
+                .or_insert_with(Vec::new)
+                .push(alias.clone());
+        }
+        usage
+            .into_iter()
+            .map(|(module_id, aliases)| SlotUsage {
+                module_id,
+                aliases,
+                quota: self.quota,
+            }).collect()
+    }
+
+    fn lock(&self) -> MutexGuard<BTreeMap<String, Slot>> {
+        self.slots.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use clock::ManualClock;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(1_540_000_000 + secs, 0)
+    }
+
+    #[test]
+    fn modules_are_held_to_their_quota() {
+        let slots = KeySlots::new()
+            .with_quota(2)
+            .with_clock(ManualClock::new(at(0)));
+
+        assert!(slots.reserve("sensor", "sensoridentity").unwrap().is_empty());
+        slots.occupy("sensor", "sensoridentity", at(100));
+        assert!(slots.reserve("sensor", "sensor1server").unwrap().is_empty());
+        // refreshing a certificate reuses its slot
+        assert!(slots.reserve("sensor", "sensoridentity").unwrap().is_empty());
+
+        match *slots.reserve("sensor", "sensor2server").unwrap_err().kind() {
+            ErrorKind::KeySlotQuotaExceeded(ref module_id, quota) => {
+                assert_eq!("sensor", module_id);
+                assert_eq!(2, quota);
+            }
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+        // other modules have quotas of their own
+        assert!(slots.reserve("display", "displayidentity").is_ok());
+
+        slots.release("sensor1server");
+        assert!(slots.reserve("sensor", "sensor2server").is_ok());
+    }
+
+    #[test]
+    fn expired_aliases_are_evicted() {
+        let clock = ManualClock::new(at(0));
+        let slots = KeySlots::new().with_quota(2).with_clock(clock.clone());
+        slots.occupy("sensor", "sensoridentity", at(100));
+        slots.occupy("sensor", "sensor1server", at(50));
+        slots.occupy("display", "display1server", at(50));
+
+        clock.advance(Duration::seconds(60));
+        let evicted = slots.reserve("sensor", "sensor2server").unwrap();
+
+        assert_eq!(vec!["sensor1server".to_string()], evicted);
+        let usage = slots.usage();
+        assert_eq!("display", usage[0].module_id());
+        assert_eq!(1, usage[0].used());
+        assert_eq!("sensor", usage[1].module_id());
+        assert_eq!(
+            &["sensor2server".to_string(), "sensoridentity".to_string()],
+            usage[1].aliases()
+        );
+        assert_eq!(Some(2), usage[1].quota());
+    }
+
+    #[test]
+    fn slots_of_a_removed_module_are_released() {
+        let slots = KeySlots::new().with_destroy(|alias| match alias {
+            "sensor1server" => Err(ErrorKind::KeyStore.into()),
+            _ => Ok(()),
+        });
+        slots.occupy("sensor", "sensoridentity", at(100));
+        slots.occupy("sensor", "sensor1server", at(100));
+        slots.occupy("display", "display1server", at(100));
+
+        let released = slots.release_module("sensor");
+
+        assert_eq!(vec!["sensoridentity".to_string()], released);
+        let usage = slots.usage();
+        assert_eq!(&["display1server".to_string()], usage[0].aliases());
+        // the certificate that couldn't be destroyed keeps its slot
+        assert_eq!(&["sensor1server".to_string()], usage[1].aliases());
+    }
+
+    #[test]
+    fn aliases_tell_their_generation_and_last_use() {
+        let clock = ManualClock::new(at(0));
+        let slots = KeySlots::new().with_clock(clock.clone());
+        slots.occupy("sensor", "sensoridentity", at(100));
+        clock.advance(Duration::seconds(10));
+        slots.occupy("sensor", "sensorgen1server", at(100));
+        clock.advance(Duration::seconds(10));
+        slots.reserve("sensor", "sensoridentity").unwrap();
+
+        let aliases = slots.aliases();
+        assert_eq!("sensorgen1server", aliases[0].alias());
+        assert_eq!(Some("gen1"), aliases[0].generation_id());
+        assert_eq!(&at(10), aliases[0].last_used());
+        assert_eq!("sensoridentity", aliases[1].alias());
+        assert_eq!(None, aliases[1].generation_id());
+        assert_eq!(&at(20), aliases[1].last_used());
+        assert_eq!(Some(&at(100)), aliases[1].expires_at());
+    }
+
+    #[test]
+    fn orphaned_aliases_are_released() {
+        let slots = KeySlots::new();
+        slots.occupy("sensor", "sensoridentity", at(100));
+        slots.occupy("sensor", "sensorgen1server", at(100));
+        slots.occupy("sensor", "sensorgen2server", at(100));
+        slots.occupy("display", "displayidentity", at(100));
+
+        let mut generations = HashMap::new();
+        generations.insert("sensor".to_string(), "gen2".to_string());
+        let released = slots.release_orphans(&generations);
+
+        assert_eq!(
+            vec!["displayidentity".to_string(), "sensorgen1server".to_string()],
+            released
+        );
+        let usage = slots.usage();
+        assert_eq!(1, usage.len());
+        assert_eq!(
+            &["sensorgen2server".to_string(), "sensoridentity".to_string()],
+            usage[0].aliases()
+        );
+    }
+
+    #[test]
+    fn orphans_are_told_apart_in_both_alias_formats() {
+        let slots = KeySlots::new();
+        // issued before aliases were separated, for generation `b1`
+        slots.occupy("a", "ab1server", at(100));
+        slots.occupy("a", "a/b1/server", at(100));
+        slots.occupy("ab", "ab/1/server", at(100));
+
+        let mut generations = HashMap::new();
+        generations.insert("a".to_string(), "b2".to_string());
+        generations.insert("ab".to_string(), "1".to_string());
+        let released = slots.release_orphans(&generations);
+
+        assert_eq!(
+            vec!["a/b1/server".to_string(), "ab1server".to_string()],
+            released
+        );
+        assert!(slots.holds("ab/1/server"));
+    }
+}
@@ -0,0 +1,197 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use error::{Error, ErrorKind, Result};
+
+const MAX_LABEL_KEY_LENGTH: usize = 253;
+const MAX_LABEL_VALUE_LENGTH: usize = 63;
+
+/// Checks that the label `key` of a module is made of letters, digits,
+/// dashes, underscores, dots and slashes, and starts with a letter or digit,
+/// and that `value` is empty or made of the same characters but slashes, so
+/// that every label can be picked by a selector.
+pub fn validate_label(key: &str, value: &str) -> Result<()> {
+    let valid = is_label_part(key, MAX_LABEL_KEY_LENGTH, true)
+        && (value.is_empty() || is_label_part(value, MAX_LABEL_VALUE_LENGTH, false));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::from(ErrorKind::InvalidLabel(format!(
+            "{}={}",
+            key, value
+        ))))
+    }
+}
+
+fn is_label_part(part: &str, max_length: usize, slashes: bool) -> bool {
+    !part.is_empty()
+        && part.len() <= max_length
+        && part.chars().all(|c| {
+            c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' || (slashes && c == '/')
+        }) && part.starts_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+    DoesNotExist(String),
+}
+
+impl Requirement {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match *self {
+            Requirement::Equals(ref key, ref value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(ref key, ref value) => labels.get(key) != Some(value),
+            Requirement::Exists(ref key) => labels.contains_key(key),
+            Requirement::DoesNotExist(ref key) => !labels.contains_key(key),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Requirement::Equals(ref key, ref value) => write!(f, "{}={}", key, value),
+            Requirement::NotEquals(ref key, ref value) => write!(f, "{}!={}", key, value),
+            Requirement::Exists(ref key) => write!(f, "{}", key),
+            Requirement::DoesNotExist(ref key) => write!(f, "!{}", key),
+        }
+    }
+}
+
+/// Picks modules by their labels. A selector is a comma separated list of
+/// requirements that all have to hold: `key=value` (or `key==value`),
+/// `key!=value`, `key` for modules that have the label and `!key` for those
+/// that don't. The empty selector picks every module.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+impl LabelSelector {
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements
+            .iter()
+            .all(|requirement| requirement.matches(labels))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::from(ErrorKind::InvalidLabelSelector(s.to_string()));
+
+        let mut requirements = vec![];
+        for requirement in s.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let requirement = if let Some(index) = requirement.find("!=") {
+                let (key, value) = (&requirement[..index], &requirement[index + 2..]);
+                Requirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some(index) = requirement.find('=') {
+                let value = requirement[index + 1..].trim_left_matches('=');
+                let key = &requirement[..index];
+                Requirement::Equals(key.trim().to_string(), value.trim().to_string())
+            } else if requirement.starts_with('!') {
+                Requirement::DoesNotExist(requirement[1..].trim().to_string())
+            } else {
+                Requirement::Exists(requirement.to_string())
+            };
+
+            let valid = match requirement {
+                Requirement::Equals(ref key, ref value)
+                | Requirement::NotEquals(ref key, ref value) => validate_label(key, value).is_ok(),
+                Requirement::Exists(ref key) | Requirement::DoesNotExist(ref key) => {
+                    validate_label(key, "").is_ok()
+                }
+            };
+            if !valid {
+                return Err(invalid());
+            }
+            requirements.push(requirement);
+        }
+
+        Ok(LabelSelector { requirements })
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let requirements: Vec<String> = self
+            .requirements
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        write!(f, "{}", requirements.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn labels_are_validated() {
+        assert!(validate_label("tier", "web").is_ok());
+        assert!(validate_label("contoso.com/solution", "line-1").is_ok());
+        assert!(validate_label("tier", "").is_ok());
+
+        assert!(validate_label("", "web").is_err());
+        assert!(validate_label("-tier", "web").is_err());
+        assert!(validate_label("tier", "a,b").is_err());
+        assert!(validate_label("tier", "a/b").is_err());
+        assert!(validate_label("tier=web", "").is_err());
+    }
+
+    #[test]
+    fn selectors_pick_modules_by_labels() {
+        let web = labels(&[("tier", "web"), ("solution", "line-1")]);
+        let db = labels(&[("tier", "db")]);
+
+        let selector: LabelSelector = "tier=web".parse().unwrap();
+        assert!(selector.matches(&web));
+        assert!(!selector.matches(&db));
+
+        let selector: LabelSelector = "tier!=web, !solution".parse().unwrap();
+        assert!(!selector.matches(&web));
+        assert!(selector.matches(&db));
+
+        let selector: LabelSelector = "solution,tier==web".parse().unwrap();
+        assert!(selector.matches(&web));
+        assert!(!selector.matches(&db));
+        assert_eq!("solution,tier=web", selector.to_string());
+
+        let selector: LabelSelector = "".parse().unwrap();
+        assert!(selector.is_empty());
+        assert!(selector.matches(&HashMap::new()));
+    }
+
+    #[test]
+    fn bad_selectors_are_rejected() {
+        for selector in &["=web", "tier=a/b", "!", "tier=web db", "tier=web=db"] {
+            match LabelSelector::from_str(selector) {
+                Err(ref err) => match *err.kind() {
+                    ErrorKind::InvalidLabelSelector(_) => (),
+                    ref kind => panic!("unexpected error {} for {}", kind, selector),
+                },
+                Ok(_) => panic!("{} was accepted", selector),
+            }
+        }
+    }
+}
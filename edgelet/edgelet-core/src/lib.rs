@@ -9,7 +9,6 @@
 #![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
 #![cfg_attr(feature = "cargo-clippy", allow(stutter, use_self))]
 
-#[cfg(test)]
 extern crate base64;
 extern crate bytes;
 extern crate chrono;
@@ -22,37 +21,141 @@ extern crate hmac;
 extern crate lazy_static;
 #[macro_use]
 extern crate log;
+extern crate openssl;
+extern crate rand;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
 extern crate sha2;
+#[cfg(test)]
+extern crate tempdir;
 extern crate tokio;
+extern crate url;
 
 #[macro_use]
 extern crate edgelet_utils;
 
 mod authorization;
+mod backup;
+mod ca_rotation;
+mod cancel;
+mod cert_policy;
+mod certificate_alias;
+mod certificate_policy;
 mod certificate_properties;
+mod certificate_request;
+mod certificate_verification;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+mod clock;
 pub mod crypto;
+mod deployment;
+mod deprecation;
+mod discovery;
+mod disk_usage;
+mod env;
 mod error;
+mod generation;
+mod heartbeat;
+mod host_process;
+mod idempotency;
 mod identity;
+mod key_slots;
+mod label;
+mod load_shedding;
+mod metrics;
 mod module;
+mod module_events;
+mod namespace;
+mod notification;
+mod operation;
+mod outbox;
 pub mod pid;
+pub mod reconcile;
+pub mod response_signing;
+mod retry;
+mod revocation;
+mod schedule;
+mod sequence;
+mod system_info;
+mod token;
+#[cfg(feature = "os-tracing")]
+pub mod trace;
+mod trust_bundle_files;
+mod trust_bundle_version;
+mod usage;
 pub mod watchdog;
 pub mod workload;
 
-pub use authorization::{Authorization, Policy};
-pub use certificate_properties::{CertificateIssuer, CertificateProperties, CertificateType};
+pub use authorization::{Authorization, PeerIdentity, Policy};
+pub use backup::StateBackup;
+pub use ca_rotation::{CaRotation, RotationState, DEFAULT_ROTATION_OVERLAP_HOURS};
+pub use cancel::{CancellationToken, Cancelled, Guarded, GuardedStream};
+pub use cert_policy::{CertPolicy, KeyAlgorithm, SanPolicy};
+pub use certificate_alias::CertificateAlias;
+pub use certificate_policy::CertificatePolicy;
+pub use certificate_properties::{
+    CertificateIssuer, CertificateProperties, CertificateType, EcCurve, KeyType, RsaKeyLength,
+};
+pub use certificate_request::CertificateRequest;
+pub use certificate_verification::{
+    certificate_expiry, verify_chain, ChainVerification, VerifiedCertificate,
+};
+pub use clock::{Clock, ManualClock, SystemClock, TrustedClock};
 pub use crypto::{
-    Certificate, CreateCertificate, Decrypt, Encrypt, GetTrustBundle, KeyBytes, KeyIdentity,
-    KeyStore, MasterEncryptionKey, PrivateKey, Signature, IOTEDGED_CA_ALIAS,
+    Certificate, CreateCertificate, CreateCrl, Decrypt, Encrypt, GetTrustBundle, KeyBytes,
+    KeyIdentity, KeyStore, MasterEncryptionKey, PrivateKey, Signature, IOTEDGED_CA_ALIAS,
+};
+pub use deployment::{manifest_version, Deployment, DeploymentHistory, DEFAULT_HISTORY_SIZE};
+pub use deprecation::Deprecation;
+pub use discovery::{Hostname, Responder, ServiceSpec};
+pub use disk_usage::{dir_size, DiskUsage, DiskUsageProvider, ModuleDiskUsage, VolumeDiskUsage};
+pub use env::{EnvProvider, EnvVar};
+pub use error::{classify_error, error_category, Error, ErrorCategory, ErrorKind};
+pub use generation::GenerationRegistry;
+pub use heartbeat::{HeartbeatEntry, HeartbeatMonitor};
+pub use host_process::{HostProcess, HostProcesses};
+pub use idempotency::{
+    IdempotencyKeys, IdempotentResponse, Replay, DEFAULT_IDEMPOTENCY_WINDOW_SECS,
+};
+pub use identity::{
+    AuthType, GenerationId, Identity, IdentityManager, IdentitySpec, ModuleName,
+};
+pub use key_slots::{KeyAlias, KeySlots, SlotUsage};
+pub use label::{validate_label, LabelSelector};
+pub use load_shedding::LoadShedder;
+pub use metrics::{
+    Metrics, CERTIFICATES_ISSUED, DAEMON_RESTARTS, EGRESS_VIOLATIONS, SOCKET_REBINDS,
+    WATCHDOG_RESTARTS,
 };
-pub use error::{Error, ErrorKind};
-pub use identity::{AuthType, Identity, IdentityManager, IdentitySpec};
 pub use module::{
-    LogOptions, LogTail, Module, ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleSpec,
-    ModuleStatus, SystemInfo,
+    ExitReason, ImageBuilder, LogOptions, LogTail, Module, ModuleConsole, ModulePriority,
+    ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleSpec, ModuleStatus, RestartPolicy,
+    SystemInfo, UpdateStrategy,
+};
+pub use module_events::{ModuleEvent, ModuleEventForwarder, ModuleEventKind};
+pub use namespace::Namespaces;
+pub use notification::{sign_notification, Notification, NotificationEvent, Notifier, Notify};
+pub use operation::{Operation, OperationState, OperationStatus, Operations};
+pub use outbox::{MessageKind, OutboundMessage, Outbox};
+pub use retry::{Backoff, Retry, RetryPolicy};
+pub use revocation::{CertificateRegistry, IssuedCertificate, RevokedCertificate};
+pub use schedule::{CronSchedule, ScheduleEntry, ScheduleSpec, ScheduledAction, Scheduler};
+pub use sequence::{
+    prepend_sequence_number, split_sequence_number, SequenceNumbers, SEQUENCE_NUMBER_LEN,
+};
+pub use system_info::{
+    build_commit, disk_space, memory_space, rustc_version, HostInfo, HostSystemInfo,
+    SystemInfoProvider,
+};
+pub use token::{
+    IdentityKeySource, SasToken, TokenIdentity, TokenStatus, TokenValidator,
+    DEFAULT_KEY_CACHE_SECS,
 };
+pub use trust_bundle_files::{TrustBundleFiles, TRUST_BUNDLE_FILENAME};
+pub use trust_bundle_version::{TrustBundleDelta, TrustBundleVersions};
+pub use usage::{ModuleUsage, WorkloadCall, WorkloadUsage};
 pub use workload::WorkloadConfig;
 
 lazy_static! {
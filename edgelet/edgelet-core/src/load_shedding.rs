@@ -0,0 +1,292 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use edgelet_utils::log_failure;
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use log::Level;
+use tokio::timer::Interval;
+
+use error::Error;
+use module::{Module, ModulePriority, ModuleRuntime, ModuleStatus};
+use outbox::{MessageKind, Outbox};
+use system_info::memory_space;
+
+/// This is the frequency with which the available memory is checked.
+const LOAD_SHEDDING_FREQUENCY_SECS: u64 = 30;
+
+/// This is how many percent of the memory have to be available on top of the
+/// threshold before the modules that were stopped are started again, so that
+/// a module isn't stopped and started over and over.
+const RESTORE_MARGIN_PERCENT: u64 = 10;
+
+/// What the load shedder does to a module.
+#[derive(Clone, Debug, PartialEq)]
+enum SheddingAction {
+    Stop(String),
+    Start(String),
+}
+
+/// What the load shedder did to a module, as reported to IoT Hub.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SheddingReport<'a> {
+    module: &'a str,
+    action: &'a str,
+    available_memory_percent: u64,
+}
+
+/// Stops the least important running module, one per check, while less than
+/// `min_available_percent` of the memory of the host is available, and
+/// starts the modules it stopped again, most important first, once memory is
+/// available again. Critical modules are never stopped.
+#[derive(Clone)]
+pub struct LoadShedder {
+    min_available_percent: u64,
+    shed: Arc<Mutex<Vec<String>>>,
+    outbox: Outbox,
+}
+
+impl LoadShedder {
+    pub fn new(min_available_percent: u64) -> Self {
+        LoadShedder {
+            min_available_percent,
+            shed: Arc::new(Mutex::new(vec![])),
+            outbox: Outbox::default(),
+        }
+    }
+
+    /// Reports every module stopped and started again to IoT Hub through
+    /// `outbox`.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    // Decides what to do about the modules given the available memory, and
+    // keeps track of the modules it stopped. Modules that are gone, or that
+    // someone else started, are forgotten.
+    fn next_action(
+        &self,
+        modules: &[(String, ModulePriority, ModuleStatus)],
+        available_percent: u64,
+    ) -> Option<SheddingAction> {
+        let mut shed = self.shed.lock().unwrap_or_else(PoisonError::into_inner);
+        shed.retain(|name| {
+            modules
+                .iter()
+                .any(|&(ref module, _, status)| module == name && status != ModuleStatus::Running)
+        });
+
+        if available_percent < self.min_available_percent {
+            let name = modules
+                .iter()
+                .filter(|&&(_, priority, status)| {
+                    status == ModuleStatus::Running && priority != ModulePriority::Critical
+                }).max_by_key(|&&(ref name, priority, _)| (priority, name.clone()))
+                .map(|&(ref name, _, _)| name.clone())?;
+            shed.push(name.clone());
+            Some(SheddingAction::Stop(name))
+        } else if available_percent >= self.min_available_percent + RESTORE_MARGIN_PERCENT {
+            let name = modules
+                .iter()
+                .filter(|&&(ref name, _, _)| shed.contains(name))
+                .min_by_key(|&&(ref name, priority, _)| (priority, name.clone()))
+                .map(|&(ref name, _, _)| name.clone())?;
+            shed.retain(|stopped| *stopped != name);
+            Some(SheddingAction::Start(name))
+        } else {
+            None
+        }
+    }
+
+    // A full or broken outbox mustn't stop the module from being stopped.
+    fn report(&self, module: &str, action: &str, available_percent: u64) {
+        let report = SheddingReport {
+            module,
+            action,
+            available_memory_percent: available_percent,
+        };
+        if let Err(err) = self.outbox.push(MessageKind::LoadShedding, &report) {
+            warn!("Could not queue the load shedding action for IoT Hub:");
+            log_failure(Level::Warn, &err);
+        }
+    }
+
+    pub fn run_until<M, F>(
+        self,
+        runtime: M,
+        shutdown_signal: F,
+    ) -> impl Future<Item = (), Error = Error>
+    where
+        M: 'static + ModuleRuntime + Clone,
+        M::Error: Into<Error>,
+        F: Future<Item = (), Error = ()> + 'static,
+    {
+        info!(
+            "Starting load shedding below {}% of available memory...",
+            self.min_available_percent
+        );
+        let shedder = Interval::new(
+            Instant::now(),
+            Duration::from_secs(LOAD_SHEDDING_FREQUENCY_SECS),
+        ).map_err(Error::from)
+        .for_each(move |_| {
+            let available_percent = match memory_space() {
+                Some((total, available)) if total > 0 => available.saturating_mul(100) / total,
+                _ => {
+                    debug!("Could not tell how much memory is available");
+                    return Either::A(future::ok(()));
+                }
+            };
+            let shedder = self.clone();
+            let runtime = runtime.clone();
+            let check = runtime
+                .list_with_details()
+                .map_err(Into::into)
+                .map(|(module, state)| {
+                    (module.name().to_string(), module.priority(), *state.status())
+                }).collect()
+                .and_then(move |modules| {
+                    match shedder.next_action(&modules, available_percent) {
+                        Some(SheddingAction::Stop(name)) => {
+                            warn!(
+                                "Only {}% of the memory is available, stopping module {}",
+                                available_percent, name
+                            );
+                            shedder.report(&name, "stop", available_percent);
+                            Either::A(Either::A(runtime.stop(&name, None).map_err(Into::into)))
+                        }
+                        Some(SheddingAction::Start(name)) => {
+                            info!(
+                                "{}% of the memory is available again, starting module {}",
+                                available_percent, name
+                            );
+                            shedder.report(&name, "start", available_percent);
+                            Either::A(Either::B(runtime.start(&name).map_err(Into::into)))
+                        }
+                        None => Either::B(future::ok(())),
+                    }
+                }).or_else(|err| {
+                    warn!("Could not shed load:");
+                    log_failure(Level::Warn, &err);
+                    Ok(())
+                });
+            Either::B(check)
+        });
+
+        // Swallow any errors from shutdown_signal
+        let shutdown_signal = shutdown_signal.then(|_| Ok(()));
+
+        shutdown_signal
+            .select(shedder)
+            .then(|result| match result {
+                Ok(((), _)) => Ok(()),
+                Err((e, _)) => Err(e),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules(
+        statuses: &[(&str, ModulePriority, ModuleStatus)],
+    ) -> Vec<(String, ModulePriority, ModuleStatus)> {
+        statuses
+            .iter()
+            .map(|&(name, priority, status)| (name.to_string(), priority, status))
+            .collect()
+    }
+
+    #[test]
+    fn least_important_module_is_stopped_first() {
+        let shedder = LoadShedder::new(10);
+        let mut running = modules(&[
+            ("edgeHub", ModulePriority::Critical, ModuleStatus::Running),
+            ("high", ModulePriority::High, ModuleStatus::Running),
+            ("low", ModulePriority::Low, ModuleStatus::Running),
+            ("normal", ModulePriority::Normal, ModuleStatus::Running),
+        ]);
+        assert_eq!(None, shedder.next_action(&running, 10));
+        assert_eq!(
+            Some(SheddingAction::Stop("low".to_string())),
+            shedder.next_action(&running, 9)
+        );
+
+        running[2].2 = ModuleStatus::Stopped;
+        assert_eq!(
+            Some(SheddingAction::Stop("normal".to_string())),
+            shedder.next_action(&running, 5)
+        );
+    }
+
+    #[test]
+    fn critical_modules_are_never_stopped() {
+        let shedder = LoadShedder::new(10);
+        let running = modules(&[
+            ("edgeAgent", ModulePriority::Critical, ModuleStatus::Running),
+            ("stopped", ModulePriority::Low, ModuleStatus::Stopped),
+        ]);
+        assert_eq!(None, shedder.next_action(&running, 1));
+    }
+
+    #[test]
+    fn stopped_modules_are_started_most_important_first() {
+        let shedder = LoadShedder::new(10);
+        let mut running = modules(&[
+            ("high", ModulePriority::High, ModuleStatus::Running),
+            ("low", ModulePriority::Low, ModuleStatus::Running),
+            ("other", ModulePriority::Normal, ModuleStatus::Stopped),
+        ]);
+        shedder.next_action(&running, 5);
+        running[1].2 = ModuleStatus::Stopped;
+        shedder.next_action(&running, 5);
+        running[0].2 = ModuleStatus::Stopped;
+
+        // not enough memory above the threshold yet
+        assert_eq!(None, shedder.next_action(&running, 19));
+        assert_eq!(
+            Some(SheddingAction::Start("high".to_string())),
+            shedder.next_action(&running, 20)
+        );
+        running[0].2 = ModuleStatus::Running;
+        assert_eq!(
+            Some(SheddingAction::Start("low".to_string())),
+            shedder.next_action(&running, 20)
+        );
+        running[1].2 = ModuleStatus::Running;
+
+        // modules it didn't stop are left alone
+        assert_eq!(None, shedder.next_action(&running, 20));
+    }
+
+    #[test]
+    fn modules_started_by_someone_else_are_forgotten() {
+        let shedder = LoadShedder::new(10);
+        let mut running = modules(&[("low", ModulePriority::Low, ModuleStatus::Running)]);
+        shedder.next_action(&running, 5);
+
+        // the module was started again behind the shedder's back
+        assert_eq!(None, shedder.next_action(&running, 15));
+        running[0].2 = ModuleStatus::Stopped;
+        assert_eq!(None, shedder.next_action(&running, 20));
+    }
+
+    #[test]
+    fn action_is_reported() {
+        let outbox = Outbox::new(10);
+        let shedder = LoadShedder::new(10).with_outbox(outbox.clone());
+        shedder.report("low", "stop", 5);
+
+        let messages = outbox.peek(10);
+        assert_eq!(1, messages.len());
+        assert_eq!(MessageKind::LoadShedding, messages[0].kind());
+        assert_eq!("low", messages[0].body()["module"]);
+        assert_eq!("stop", messages[0].body()["action"]);
+        assert_eq!(5, messages[0].body()["availableMemoryPercent"]);
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use edgelet_utils::log_failure;
+use failure::ResultExt;
+use futures::{Future, Stream};
+use log::Level;
+use serde_json;
+use tokio::timer::Interval;
+
+use error::{Error, ErrorKind, Result};
+
+/// The number of times the daemon started again with the counters of an
+/// earlier run, which is how crash loops show up.
+pub const DAEMON_RESTARTS: &str = "daemonRestarts";
+
+/// The number of times the watchdog restarted or re-created the edge runtime
+/// module, or restarted a failed module.
+pub const WATCHDOG_RESTARTS: &str = "watchdogRestarts";
+
+/// The number of certificates issued to modules.
+pub const CERTIFICATES_ISSUED: &str = "certificatesIssued";
+
+/// The number of times a socket inherited from systemd was unusable and the
+/// daemon bound a new one in its place.
+pub const SOCKET_REBINDS: &str = "socketRebinds";
+
+/// The number of packets dropped for going to destinations the egress
+/// policies of modules don't allow.
+pub const EGRESS_VIOLATIONS: &str = "egressViolations";
+
+/// Counters of what the daemon did, which only ever go up. When created with
+/// `load` the counters of the previous run are restored and carried on from,
+/// and `persist` writes them back to disk, so that they don't start over when
+/// the daemon restarts. Every part of the daemon counts on a clone of the
+/// same metrics, so they are persisted together.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    path: Option<PathBuf>,
+    counters: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Restores the counters persisted at `path` by the previous run and
+    /// counts a restart if there was one. The restart is persisted right
+    /// away, so that a daemon crashing before the next snapshot still counts
+    /// it. A snapshot that can't be read is an error rather than a reason to
+    /// start over from zero.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let restarted = path.exists();
+        let counters = if restarted {
+            let file = File::open(&path).context(ErrorKind::Metrics)?;
+            serde_json::from_reader(file).context(ErrorKind::Metrics)?
+        } else {
+            BTreeMap::new()
+        };
+
+        let metrics = Metrics {
+            path: Some(path),
+            counters: Arc::new(Mutex::new(counters)),
+        };
+        if restarted {
+            metrics.increment(DAEMON_RESTARTS);
+            metrics.persist()?;
+        }
+        Ok(metrics)
+    }
+
+    pub fn increment(&self, name: &str) {
+        self.add(name, 1);
+    }
+
+    pub fn add(&self, name: &str, count: u64) {
+        let mut counters = self.lock();
+        let counter = counters.entry(name.to_string()).or_insert(0);
+        *counter = counter.saturating_add(count);
+    }
+
+    pub fn get(&self, name: &str) -> u64 {
+        self.lock().get(name).cloned().unwrap_or(0)
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.lock().clone()
+    }
+
+    /// Writes the counters to disk. The snapshot is replaced in one go, so a
+    /// crash while it is written leaves the previous one in place.
+    pub fn persist(&self) -> Result<()> {
+        if let Some(ref path) = self.path {
+            let counters = self.snapshot();
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::Metrics)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::Metrics)?;
+            serde_json::to_writer(file, &counters).context(ErrorKind::Metrics)?;
+            fs::rename(&temp, path).context(ErrorKind::Metrics)?;
+        }
+        Ok(())
+    }
+
+    /// Persists the counters every `frequency` until `shutdown_signal`
+    /// fires, and once more then.
+    pub fn run_until<F>(
+        self,
+        frequency: Duration,
+        shutdown_signal: F,
+    ) -> impl Future<Item = (), Error = Error>
+    where
+        F: Future<Item = (), Error = ()> + 'static,
+    {
+        let metrics = self.clone();
+        let snapshots = Interval::new(Instant::now() + frequency, frequency)
+            .map_err(Error::from)
+            .for_each(move |_| {
+                metrics.persist_or_warn();
+                Ok(())
+            });
+
+        // Swallow any errors from shutdown_signal
+        let shutdown_signal = shutdown_signal.then(|_| Ok(()));
+
+        shutdown_signal
+            .select(snapshots)
+            .then(move |result| match result {
+                Ok(((), _)) => {
+                    self.persist_or_warn();
+                    Ok(())
+                }
+                Err((e, _)) => Err(e),
+            })
+    }
+
+    // Counters that can't be persisted are kept in memory until the next try.
+    fn persist_or_warn(&self) {
+        if let Err(err) = self.persist() {
+            warn!("Could not persist the daemon metrics:");
+            log_failure(Level::Warn, &err);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<BTreeMap<String, u64>> {
+        self.counters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn counters_only_go_up() {
+        let metrics = Metrics::new();
+        assert_eq!(0, metrics.get(WATCHDOG_RESTARTS));
+
+        metrics.increment(WATCHDOG_RESTARTS);
+        metrics.clone().add(WATCHDOG_RESTARTS, 2);
+        assert_eq!(3, metrics.get(WATCHDOG_RESTARTS));
+
+        metrics.add(WATCHDOG_RESTARTS, u64::max_value());
+        assert_eq!(u64::max_value(), metrics.get(WATCHDOG_RESTARTS));
+    }
+
+    #[test]
+    fn counters_are_restored_and_restarts_counted() {
+        let tmp_dir = TempDir::new("metrics").unwrap();
+        let path = tmp_dir.path().join("metrics.json");
+
+        let metrics = Metrics::load(&path).unwrap();
+        metrics.increment(CERTIFICATES_ISSUED);
+        metrics.persist().unwrap();
+        assert_eq!(0, metrics.get(DAEMON_RESTARTS));
+
+        let metrics = Metrics::load(&path).unwrap();
+        assert_eq!(1, metrics.get(CERTIFICATES_ISSUED));
+        assert_eq!(1, metrics.get(DAEMON_RESTARTS));
+        metrics.increment(CERTIFICATES_ISSUED);
+        metrics.persist().unwrap();
+
+        let metrics = Metrics::load(&path).unwrap();
+        assert_eq!(2, metrics.get(CERTIFICATES_ISSUED));
+        assert_eq!(2, metrics.get(DAEMON_RESTARTS));
+
+        // a restart is counted even if the daemon never persists again
+        drop(metrics);
+        assert_eq!(3, Metrics::load(&path).unwrap().get(DAEMON_RESTARTS));
+
+        fs::write(&path, "{").unwrap();
+        match *Metrics::load(&path).err().unwrap().kind() {
+            ErrorKind::Metrics => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
@@ -13,6 +13,7 @@ use failure::Fail;
 use futures::{Future, Stream};
 use pid::Pid;
 use serde_json;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 use error::{Error, Result};
 
@@ -45,15 +46,174 @@ impl fmt::Display for ModuleStatus {
     }
 }
 
+/// Why the process of a module last exited.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExitReason {
+    /// The process exited with code 0.
+    Normal,
+    /// The process exited with this non-zero code.
+    Error(i64),
+    /// The kernel killed the process for running out of memory.
+    OomKilled,
+    /// The runtime failed to start or run the process.
+    RuntimeFailure,
+}
+
+impl ExitReason {
+    /// Works out why a process exited from its exit code, whether the runtime
+    /// saw it killed for running out of memory and the error the runtime
+    /// reported for it, if any.
+    pub fn new(exit_code: i64, oom_killed: bool, runtime_error: Option<&str>) -> Self {
+        if oom_killed {
+            ExitReason::OomKilled
+        } else if runtime_error.map_or(false, |error| !error.is_empty()) {
+            ExitReason::RuntimeFailure
+        } else if exit_code == 0 {
+            ExitReason::Normal
+        } else {
+            ExitReason::Error(exit_code)
+        }
+    }
+
+    /// Reads back a reason from its name, which is how it is displayed, and
+    /// the exit code of the process.
+    pub fn from_name(name: &str, exit_code: i64) -> Option<Self> {
+        match name {
+            "normal" => Some(ExitReason::Normal),
+            "error" => Some(ExitReason::Error(exit_code)),
+            "oomKilled" => Some(ExitReason::OomKilled),
+            "runtimeFailure" => Some(ExitReason::RuntimeFailure),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ExitReason::Normal => "normal",
+            ExitReason::Error(_) => "error",
+            ExitReason::OomKilled => "oomKilled",
+            ExitReason::RuntimeFailure => "runtimeFailure",
+        };
+        write!(formatter, "{}", name)
+    }
+}
+
+/// The relative importance of a module when the device runs short of
+/// resources. Variants are declared from most to least important, so sorting
+/// puts the most important modules first.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModulePriority {
+    Critical,
+    High,
+    Normal,
+    Low,
+}
+
+impl ModulePriority {
+    /// The adjustment applied to the kernel's OOM score for the processes of
+    /// a module. Lower values make the kernel less likely to kill them.
+    pub fn oom_score_adj(self) -> i32 {
+        match self {
+            ModulePriority::Critical => -900,
+            ModulePriority::High => -500,
+            ModulePriority::Normal => 0,
+            ModulePriority::Low => 500,
+        }
+    }
+}
+
+impl Default for ModulePriority {
+    fn default() -> Self {
+        ModulePriority::Normal
+    }
+}
+
+impl FromStr for ModulePriority {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        serde_json::from_str(&format!("\"{}\"", s))
+    }
+}
+
+impl fmt::Display for ModulePriority {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            serde_json::to_string(self)
+                .map(|s| s.trim_matches('"').to_string())
+                .map_err(|_| fmt::Error)?
+        )
+    }
+}
+
+/// When a module is restarted. Variants are declared from the policy that
+/// restarts a module the most to the one that restarts it the least.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// The module is restarted whenever it exits or is unhealthy.
+    Always,
+    /// The module is restarted when it fails or is unhealthy.
+    OnUnhealthy,
+    /// The module is restarted when it fails.
+    OnFailure,
+    /// The module is never restarted.
+    Never,
+}
+
+impl RestartPolicy {
+    /// Whether a module that is running but unhealthy, such as one that
+    /// missed its heartbeat, is restarted.
+    pub fn restarts_unhealthy(self) -> bool {
+        match self {
+            RestartPolicy::Always | RestartPolicy::OnUnhealthy => true,
+            RestartPolicy::OnFailure | RestartPolicy::Never => false,
+        }
+    }
+
+    /// Whether a module whose process exited with an error is restarted.
+    pub fn restarts_failed(self) -> bool {
+        self != RestartPolicy::Never
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        serde_json::from_str(&format!("\"{}\"", s))
+    }
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            serde_json::to_string(self)
+                .map(|s| s.trim_matches('"').to_string())
+                .map_err(|_| fmt::Error)?
+        )
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ModuleRuntimeState {
     status: ModuleStatus,
     exit_code: Option<i64>,
+    exit_reason: Option<ExitReason>,
     status_description: Option<String>,
     started_at: Option<DateTime<Utc>>,
     finished_at: Option<DateTime<Utc>>,
     image_id: Option<String>,
     pid: Pid,
+    restart_policy_drift: Option<String>,
 }
 
 impl Default for ModuleRuntimeState {
@@ -61,11 +221,13 @@ impl Default for ModuleRuntimeState {
         ModuleRuntimeState {
             status: ModuleStatus::Unknown,
             exit_code: None,
+            exit_reason: None,
             status_description: None,
             started_at: None,
             finished_at: None,
             image_id: None,
             pid: Pid::None,
+            restart_policy_drift: None,
         }
     }
 }
@@ -89,6 +251,17 @@ impl ModuleRuntimeState {
         self
     }
 
+    /// Why the process of the module last exited. This is `None` while the
+    /// process has never exited.
+    pub fn exit_reason(&self) -> Option<ExitReason> {
+        self.exit_reason
+    }
+
+    pub fn with_exit_reason(mut self, exit_reason: Option<ExitReason>) -> Self {
+        self.exit_reason = exit_reason;
+        self
+    }
+
     pub fn status_description(&self) -> Option<&str> {
         self.status_description.as_ref().map(AsRef::as_ref)
     }
@@ -133,6 +306,18 @@ impl ModuleRuntimeState {
         self.pid = pid;
         self
     }
+
+    /// How the restart policy the runtime has for the module differs from
+    /// the one the module was created with, when someone changed it behind
+    /// the daemon's back.
+    pub fn restart_policy_drift(&self) -> Option<&str> {
+        self.restart_policy_drift.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_restart_policy_drift(mut self, restart_policy_drift: Option<String>) -> Self {
+        self.restart_policy_drift = restart_policy_drift;
+        self
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -143,6 +328,14 @@ pub struct ModuleSpec<T> {
     config: T,
     #[serde(default = "HashMap::new")]
     env: HashMap<String, String>,
+    #[serde(default)]
+    priority: ModulePriority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    restart_policy: Option<RestartPolicy>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    labels: HashMap<String, String>,
 }
 
 impl<T> Clone for ModuleSpec<T>
@@ -155,6 +348,10 @@ where
             type_: self.type_.clone(),
             config: self.config.clone(),
             env: self.env.clone(),
+            priority: self.priority,
+            namespace: self.namespace.clone(),
+            restart_policy: self.restart_policy,
+            labels: self.labels.clone(),
         }
     }
 }
@@ -166,6 +363,10 @@ impl<T> ModuleSpec<T> {
             type_: ensure_not_empty!(type_).to_string(),
             config,
             env,
+            priority: ModulePriority::default(),
+            namespace: None,
+            restart_policy: None,
+            labels: HashMap::new(),
         })
     }
 
@@ -212,6 +413,48 @@ impl<T> ModuleSpec<T> {
         self.env = env;
         self
     }
+
+    pub fn priority(&self) -> ModulePriority {
+        self.priority
+    }
+
+    pub fn with_priority(mut self, priority: ModulePriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// The namespace the module is deployed into, if any. Modules in a
+    /// namespace are isolated from the modules of other namespaces.
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_namespace(mut self, namespace: String) -> Self {
+        self.namespace = Some(namespace);
+        self
+    }
+
+    /// When the module is restarted. Modules without one are restarted
+    /// however their runtime configuration says.
+    pub fn restart_policy(&self) -> Option<RestartPolicy> {
+        self.restart_policy
+    }
+
+    pub fn with_restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = Some(restart_policy);
+        self
+    }
+
+    /// The labels the deployment attached to the module, which tooling can
+    /// group modules by, such as by solution or tier.
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.labels
+    }
+
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = labels;
+        self
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -253,6 +496,9 @@ impl ToString for LogTail {
 pub struct LogOptions {
     follow: bool,
     tail: LogTail,
+    since: i32,
+    limit_bytes: Option<u64>,
+    grep: Option<String>,
 }
 
 impl LogOptions {
@@ -260,6 +506,9 @@ impl LogOptions {
         LogOptions {
             follow: false,
             tail: LogTail::All,
+            since: 0,
+            limit_bytes: None,
+            grep: None,
         }
     }
 
@@ -273,6 +522,13 @@ impl LogOptions {
         self
     }
 
+    /// Only return the logs written after this UNIX timestamp. 0 returns all
+    /// of them.
+    pub fn with_since(mut self, since: i32) -> Self {
+        self.since = since;
+        self
+    }
+
     pub fn follow(&self) -> bool {
         self.follow
     }
@@ -280,6 +536,30 @@ impl LogOptions {
     pub fn tail(&self) -> &LogTail {
         &self.tail
     }
+
+    /// Stop returning logs once this many bytes of them were returned.
+    pub fn with_limit_bytes(mut self, limit_bytes: u64) -> Self {
+        self.limit_bytes = Some(limit_bytes);
+        self
+    }
+
+    /// Only return the lines of the logs that match this regular expression.
+    pub fn with_grep(mut self, grep: String) -> Self {
+        self.grep = Some(grep);
+        self
+    }
+
+    pub fn since(&self) -> i32 {
+        self.since
+    }
+
+    pub fn limit_bytes(&self) -> Option<u64> {
+        self.limit_bytes
+    }
+
+    pub fn grep(&self) -> Option<&str> {
+        self.grep.as_ref().map(AsRef::as_ref)
+    }
 }
 
 pub trait Module {
@@ -291,6 +571,33 @@ pub trait Module {
     fn type_(&self) -> &str;
     fn config(&self) -> &Self::Config;
     fn runtime_state(&self) -> Self::RuntimeStateFuture;
+
+    /// The namespace the module was deployed into, if any.
+    fn namespace(&self) -> Option<&str> {
+        None
+    }
+
+    /// The module that the module calls the daemon's APIs as. This is the
+    /// module itself, unless it stands in for another module, such as a
+    /// canary does for the module it updates.
+    fn identity(&self) -> &str {
+        self.name()
+    }
+
+    /// The restart policy the module was created with, if any.
+    fn restart_policy(&self) -> Option<RestartPolicy> {
+        None
+    }
+
+    /// The priority the module was created with.
+    fn priority(&self) -> ModulePriority {
+        ModulePriority::default()
+    }
+
+    /// The labels the module was created with.
+    fn labels(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
 }
 
 pub trait ModuleRegistry {
@@ -311,6 +618,8 @@ pub struct SystemInfo {
     architecture: String,
     /// iotedge version string
     version: &'static str,
+    /// Version of the container runtime, if it reports one.
+    server_version: Option<String>,
 }
 
 impl SystemInfo {
@@ -319,9 +628,15 @@ impl SystemInfo {
             os_type,
             architecture,
             version: super::version(),
+            server_version: None,
         }
     }
 
+    pub fn with_server_version(mut self, server_version: String) -> Self {
+        self.server_version = Some(server_version);
+        self
+    }
+
     pub fn os_type(&self) -> &str {
         &self.os_type
     }
@@ -333,6 +648,32 @@ impl SystemInfo {
     pub fn version(&self) -> &str {
         self.version
     }
+
+    pub fn server_version(&self) -> Option<&str> {
+        self.server_version.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// How `ModuleRuntime::update` replaces an existing module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpdateStrategy {
+    /// Remove the existing module and create the new one in its place. The
+    /// new module is not started.
+    Replace,
+    /// Start the new module alongside the existing one and only swap it in if
+    /// it is still running once the soak period has passed. Otherwise the new
+    /// module is removed and the existing one is left untouched. The swapped
+    /// in module is left running. The update completes once the new module
+    /// is running, and the runtime soaks and swaps it in in the background,
+    /// with its progress in the status description of the existing module.
+    /// The new module calls the daemon's APIs as the existing one meanwhile.
+    Canary(Duration),
+}
+
+impl Default for UpdateStrategy {
+    fn default() -> Self {
+        UpdateStrategy::Replace
+    }
 }
 
 pub trait ModuleRuntime {
@@ -358,6 +699,7 @@ pub trait ModuleRuntime {
     type StopFuture: Future<Item = (), Error = Self::Error> + Send;
     type SystemInfoFuture: Future<Item = SystemInfo, Error = Self::Error> + Send;
     type RemoveAllFuture: Future<Item = (), Error = Self::Error> + Send;
+    type UpdateFuture: Future<Item = (), Error = Self::Error> + Send;
 
     fn init(&self) -> Self::InitFuture;
     fn create(&self, module: ModuleSpec<Self::Config>) -> Self::CreateFuture;
@@ -365,12 +707,54 @@ pub trait ModuleRuntime {
     fn stop(&self, id: &str, wait_before_kill: Option<Duration>) -> Self::StopFuture;
     fn restart(&self, id: &str) -> Self::RestartFuture;
     fn remove(&self, id: &str) -> Self::RemoveFuture;
+    /// Removes the module along with the anonymous volumes it created.
+    fn remove_with_volumes(&self, id: &str) -> Self::RemoveFuture;
     fn system_info(&self) -> Self::SystemInfoFuture;
     fn list(&self) -> Self::ListFuture;
     fn list_with_details(&self) -> Self::ListWithDetailsStream;
     fn logs(&self, id: &str, options: &LogOptions) -> Self::LogsFuture;
     fn registry(&self) -> &Self::ModuleRegistry;
     fn remove_all(&self) -> Self::RemoveAllFuture;
+    fn update(
+        &self,
+        module: ModuleSpec<Self::Config>,
+        strategy: UpdateStrategy,
+    ) -> Self::UpdateFuture;
+}
+
+/// Builds images on the device, so that modules can be tried out while they
+/// are being developed without pushing their images to a registry first.
+pub trait ImageBuilder {
+    type Error: Fail;
+
+    /// The build context, a tar archive of the files the image is built from.
+    type Context;
+    type Chunk: AsRef<[u8]>;
+    type Output: Stream<Item = Self::Chunk, Error = Self::Error> + Send;
+    type BuildFuture: Future<Item = Self::Output, Error = Self::Error> + Send;
+
+    /// Builds the image `tag` from `context` with the Dockerfile at
+    /// `dockerfile` in it. The output of the build is streamed as the build
+    /// progresses.
+    fn build_image(
+        &self,
+        tag: &str,
+        dockerfile: &str,
+        context: Self::Context,
+    ) -> Self::BuildFuture;
+}
+
+/// Attaches to the console of a module, for interactive debugging.
+pub trait ModuleConsole {
+    type Error: Fail;
+
+    /// A connection to the stdin, stdout and stderr of a module. Whatever is
+    /// written to it is sent to the module's stdin, and whatever the module
+    /// writes to stdout and stderr can be read from it.
+    type Connection: AsyncRead + AsyncWrite + Send;
+    type AttachFuture: Future<Item = Self::Connection, Error = Self::Error> + Send;
+
+    fn attach(&self, id: &str) -> Self::AttachFuture;
 }
 
 #[cfg(test)]
@@ -381,7 +765,7 @@ mod tests {
     use std::string::ToString;
 
     use error::ErrorKind;
-    use module::ModuleStatus;
+    use module::{ModulePriority, ModuleStatus};
 
     fn get_inputs() -> Vec<(&'static str, ModuleStatus)> {
         vec![
@@ -408,6 +792,114 @@ mod tests {
         }
     }
 
+    #[test]
+    fn exit_reason_from_runtime_state() {
+        assert_eq!(ExitReason::Normal, ExitReason::new(0, false, None));
+        assert_eq!(ExitReason::Normal, ExitReason::new(0, false, Some("")));
+        assert_eq!(ExitReason::Error(2), ExitReason::new(2, false, None));
+        assert_eq!(ExitReason::OomKilled, ExitReason::new(137, true, None));
+        assert_eq!(
+            ExitReason::RuntimeFailure,
+            ExitReason::new(127, false, Some("executable file not found in $PATH"))
+        );
+    }
+
+    #[test]
+    fn exit_reason_names_round_trip() {
+        for reason in &[
+            ExitReason::Normal,
+            ExitReason::Error(3),
+            ExitReason::OomKilled,
+            ExitReason::RuntimeFailure,
+        ] {
+            assert_eq!(
+                Some(*reason),
+                ExitReason::from_name(&reason.to_string(), 3)
+            );
+        }
+        assert_eq!(None, ExitReason::from_name("crashed", 3));
+    }
+
+    #[test]
+    fn exit_reasons_serialize_by_name() {
+        for &(json, reason) in &[
+            (r#""normal""#, ExitReason::Normal),
+            (r#"{"error":3}"#, ExitReason::Error(3)),
+            (r#""oomKilled""#, ExitReason::OomKilled),
+            (r#""runtimeFailure""#, ExitReason::RuntimeFailure),
+        ] {
+            assert_eq!(json, serde_json::to_string(&reason).unwrap());
+            assert_eq!(reason, serde_json::from_str::<ExitReason>(json).unwrap());
+        }
+    }
+
+    #[test]
+    fn module_priority_ser_deser() {
+        let inputs = vec![
+            ("critical", ModulePriority::Critical),
+            ("high", ModulePriority::High),
+            ("normal", ModulePriority::Normal),
+            ("low", ModulePriority::Low),
+        ];
+        for &(priority, expected) in &inputs {
+            assert_eq!(priority, &expected.to_string());
+            assert_eq!(expected, ModulePriority::from_str(priority).unwrap());
+        }
+        assert!(ModulePriority::from_str("urgent").is_err());
+    }
+
+    #[test]
+    fn restart_policy_ser_deser() {
+        let inputs = vec![
+            ("always", RestartPolicy::Always),
+            ("on-unhealthy", RestartPolicy::OnUnhealthy),
+            ("on-failure", RestartPolicy::OnFailure),
+            ("never", RestartPolicy::Never),
+        ];
+        for &(policy, expected) in &inputs {
+            assert_eq!(policy, &expected.to_string());
+            assert_eq!(expected, RestartPolicy::from_str(policy).unwrap());
+        }
+        assert!(RestartPolicy::from_str("sometimes").is_err());
+        assert!(RestartPolicy::OnUnhealthy.restarts_unhealthy());
+        assert!(!RestartPolicy::OnFailure.restarts_unhealthy());
+    }
+
+    #[test]
+    fn module_priority_sorts_most_important_first() {
+        let mut priorities = vec![
+            ModulePriority::Low,
+            ModulePriority::Critical,
+            ModulePriority::Normal,
+            ModulePriority::High,
+        ];
+        priorities.sort();
+        assert_eq!(
+            vec![
+                ModulePriority::Critical,
+                ModulePriority::High,
+                ModulePriority::Normal,
+                ModulePriority::Low,
+            ],
+            priorities
+        );
+        for pair in priorities.windows(2) {
+            assert!(pair[0].oom_score_adj() < pair[1].oom_score_adj());
+        }
+    }
+
+    #[test]
+    fn module_spec_priority_defaults_to_normal() {
+        let spec: ModuleSpec<i32> =
+            serde_json::from_str(r#"{"name":"m1","type":"docker","config":10}"#).unwrap();
+        assert_eq!(ModulePriority::Normal, spec.priority());
+
+        let spec: ModuleSpec<i32> = serde_json::from_str(
+            r#"{"name":"m1","type":"docker","config":10,"priority":"critical"}"#,
+        ).unwrap();
+        assert_eq!(ModulePriority::Critical, spec.priority());
+    }
+
     #[test]
     fn module_config_empty_name_fails() {
         match ModuleSpec::new("", "docker", 10_i32, HashMap::new()) {
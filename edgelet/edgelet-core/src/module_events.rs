@@ -0,0 +1,579 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{DateTime, Duration, Utc};
+use edgelet_utils::log_failure;
+use futures::{Future, Stream};
+use log::Level;
+use tokio::timer::Interval;
+
+use clock::{Clock, SystemClock};
+use error::Error;
+use label::LabelSelector;
+use module::{Module, ModuleRuntime, ModuleRuntimeState, ModuleStatus};
+use notification::{Notification, NotificationEvent, Notifier};
+use outbox::{MessageKind, Outbox};
+
+/// This is how often the module runtime is polled for changes, unless the
+/// forwarder is created with another interval.
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 15;
+
+/// This is how many events are forwarded each minute, unless the forwarder
+/// is created with another rate limit.
+const DEFAULT_MAX_EVENTS_PER_MINUTE: usize = 60;
+
+/// This is how many events go in one message, unless the forwarder is
+/// created with another batch size.
+const DEFAULT_BATCH_SIZE: usize = 20;
+
+/// A module that restarts or fails this many times within the crash loop
+/// window is in a crash loop.
+const CRASH_LOOP_RESTARTS: usize = 3;
+
+/// This is the window, in minutes, crash loops are detected in. A module that
+/// keeps crashing is notified of again once a window has passed.
+const CRASH_LOOP_WINDOW_MINS: i64 = 10;
+
+/// What happened to a module.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleEventKind {
+    Created,
+    Started,
+    Restarted,
+    Stopped,
+    Failed,
+    Removed,
+}
+
+/// A change in the lifecycle or health of a module, as reported to IoT Hub.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleEvent {
+    module: String,
+    event: ModuleEventKind,
+    observed_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+impl ModuleEvent {
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn event(&self) -> ModuleEventKind {
+        self.event
+    }
+
+    pub fn observed_at(&self) -> &DateTime<Utc> {
+        &self.observed_at
+    }
+
+    pub fn exit_code(&self) -> Option<i64> {
+        self.exit_code
+    }
+}
+
+/// A batch of events, as queued for IoT Hub. `dropped` counts the events
+/// the rate limit kept from being forwarded since the previous batch.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModuleEventBatch<'a> {
+    events: &'a [ModuleEvent],
+    #[serde(skip_serializing_if = "is_zero")]
+    dropped: usize,
+}
+
+// serde's skip_serializing_if passes the field by reference
+#[cfg_attr(feature = "cargo-clippy", allow(trivially_copy_pass_by_ref))]
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Observed {
+    status: ModuleStatus,
+    started_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct CrashLoop {
+    restarts: Vec<DateTime<Utc>>,
+    notified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct State {
+    // None until the runtime has been polled once
+    modules: Option<HashMap<String, Observed>>,
+    window_start: Option<DateTime<Utc>>,
+    forwarded: usize,
+    dropped: usize,
+    crash_loops: HashMap<String, CrashLoop>,
+}
+
+/// Forwards the lifecycle and health events of modules to IoT Hub through
+/// the daemon's outbox, so that restarts show up in the cloud without a
+/// monitoring module of its own.
+///
+/// The module runtime is polled for the state of each module, and an event
+/// is raised for each change since the previous poll. A module running with
+/// another start time than before was restarted in between, such as by the
+/// restart policy of the container runtime. Events are forwarded in batches,
+/// and those beyond the rate limit are dropped and counted in the next batch.
+/// With a label selector, only the modules whose labels match it are
+/// watched. Modules that restart or fail over and over are reported to the
+/// notifier as crash loops. The state of the modules seen by the last poll
+/// is kept in one place, whichever clone of the forwarder polls next.
+#[derive(Clone)]
+pub struct ModuleEventForwarder {
+    interval: StdDuration,
+    max_events_per_minute: usize,
+    batch_size: usize,
+    outbox: Outbox,
+    notifier: Notifier,
+    clock: Arc<Clock>,
+    selector: LabelSelector,
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for ModuleEventForwarder {
+    fn default() -> Self {
+        ModuleEventForwarder {
+            interval: StdDuration::from_secs(DEFAULT_POLL_INTERVAL_SECS),
+            max_events_per_minute: DEFAULT_MAX_EVENTS_PER_MINUTE,
+            batch_size: DEFAULT_BATCH_SIZE,
+            outbox: Outbox::default(),
+            notifier: Notifier::default(),
+            clock: Arc::new(SystemClock),
+            selector: LabelSelector::default(),
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+}
+
+impl ModuleEventForwarder {
+    pub fn new() -> Self {
+        ModuleEventForwarder::default()
+    }
+
+    pub fn with_interval(mut self, interval: StdDuration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_max_events_per_minute(mut self, max_events_per_minute: usize) -> Self {
+        self.max_events_per_minute = max_events_per_minute;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Queues the batches of events in `outbox`.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// Notifies `notifier` of the modules in a crash loop.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Times events, and the rate limit, by `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Only raises events for the modules whose labels match `selector`.
+    pub fn with_label_selector(mut self, selector: LabelSelector) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    // Returns the events that take the modules from their state at the
+    // previous poll to `modules`. The first poll only records the state.
+    fn observe(&self, modules: &[(String, ModuleRuntimeState)]) -> Vec<ModuleEvent> {
+        let now = self.clock.now();
+        let current: HashMap<String, Observed> = modules
+            .iter()
+            .map(|&(ref name, ref state)| {
+                let observed = Observed {
+                    status: *state.status(),
+                    started_at: state.started_at().cloned(),
+                };
+                (name.clone(), observed)
+            }).collect();
+
+        let mut events = vec![];
+        let previous = mem::replace(&mut self.lock().modules, Some(current));
+        if let Some(previous) = previous {
+            for &(ref name, ref state) in modules {
+                let event = |event| event_of(name, event, now);
+                let stopped = |event| ModuleEvent {
+                    exit_code: state.exit_code(),
+                    description: state.status_description().map(ToString::to_string),
+                    ..event_of(name, event, now)
+                };
+                let status = *state.status();
+                match previous.get(name) {
+                    None => {
+                        events.push(event(ModuleEventKind::Created));
+                        if status == ModuleStatus::Running {
+                            events.push(event(ModuleEventKind::Started));
+                        }
+                    }
+                    Some(before) if before.status == status => {
+                        if status == ModuleStatus::Running
+                            && before.started_at.as_ref() != state.started_at()
+                        {
+                            events.push(event(ModuleEventKind::Restarted));
+                        }
+                    }
+                    Some(_) => match status {
+                        ModuleStatus::Running => events.push(event(ModuleEventKind::Started)),
+                        ModuleStatus::Stopped => events.push(stopped(ModuleEventKind::Stopped)),
+                        ModuleStatus::Failed => events.push(stopped(ModuleEventKind::Failed)),
+                        ModuleStatus::Unknown => (),
+                    },
+                }
+            }
+
+            let mut removed: Vec<&String> = previous
+                .keys()
+                .filter(|name| !modules.iter().any(|&(ref module, _)| module == *name))
+                .collect();
+            removed.sort();
+            events.extend(
+                removed
+                    .into_iter()
+                    .map(|name| event_of(name, ModuleEventKind::Removed, now)),
+            );
+        }
+        events
+    }
+
+    // Notifies of the modules `events` take into a crash loop. The events of
+    // a module count towards a crash loop for the length of the window.
+    fn detect_crash_loops(&self, events: &[ModuleEvent]) {
+        let window = Duration::minutes(CRASH_LOOP_WINDOW_MINS);
+        let mut notifications = vec![];
+        {
+            let mut state = self.lock();
+            for event in events {
+                match event.event {
+                    ModuleEventKind::Restarted | ModuleEventKind::Failed => (),
+                    ModuleEventKind::Removed => {
+                        state.crash_loops.remove(&event.module);
+                        continue;
+                    }
+                    _ => continue,
+                }
+
+                let now = event.observed_at;
+                let crash_loop = state
+                    .crash_loops
+                    .entry(event.module.clone())
+                    .or_insert_with(CrashLoop::default);
+                crash_loop.restarts.retain(|at| now - *at < window);
+                crash_loop.restarts.push(now);
+                let due = crash_loop
+                    .notified_at
+                    .map_or(true, |notified_at| now - notified_at >= window);
+                if crash_loop.restarts.len() >= CRASH_LOOP_RESTARTS && due {
+                    crash_loop.notified_at = Some(now);
+                    notifications.push(
+                        Notification::new(NotificationEvent::ModuleCrashLoop, &event.module, now)
+                            .with_details(format!(
+                                "restarted or failed {} times in {} minutes",
+                                crash_loop.restarts.len(),
+                                CRASH_LOOP_WINDOW_MINS
+                            )),
+                    );
+                }
+            }
+        }
+
+        for notification in notifications {
+            warn!("Module {} is in a crash loop", notification.subject());
+            self.notifier.notify(&notification);
+        }
+    }
+
+    // Queues the events the rate limit allows, in batches.
+    fn forward(&self, mut events: Vec<ModuleEvent>) {
+        let now = self.clock.now();
+        let (events, dropped) = {
+            let mut state = self.lock();
+            let window_over = state
+                .window_start
+                .map_or(true, |start| now - start >= Duration::minutes(1));
+            if window_over {
+                state.window_start = Some(now);
+                state.forwarded = 0;
+            }
+
+            let allowed = self.max_events_per_minute.saturating_sub(state.forwarded);
+            if events.len() > allowed {
+                state.dropped += events.len() - allowed;
+                events.truncate(allowed);
+            }
+            // the dropped events are only reported along with events that
+            // are forwarded, or once the rate limit allows another batch
+            if events.is_empty() && (state.dropped == 0 || allowed == 0) {
+                return;
+            }
+            state.forwarded += events.len();
+            let dropped = state.dropped;
+            state.dropped = 0;
+            (events, dropped)
+        };
+
+        if dropped > 0 {
+            warn!(
+                "Dropped {} module events over the limit of {} per minute",
+                dropped, self.max_events_per_minute
+            );
+        }
+        let mut batches: Vec<&[ModuleEvent]> = events.chunks(self.batch_size).collect();
+        if batches.is_empty() {
+            batches.push(&[]);
+        }
+        for (i, events) in batches.into_iter().enumerate() {
+            let batch = ModuleEventBatch {
+                events,
+                dropped: if i == 0 { dropped } else { 0 },
+            };
+            // a full or broken outbox mustn't stop the forwarder
+            if let Err(err) = self.outbox.push(MessageKind::ModuleEvents, &batch) {
+                warn!("Could not queue module events for IoT Hub:");
+                log_failure(Level::Warn, &err);
+            }
+        }
+    }
+
+    pub fn run_until<M, F>(
+        self,
+        runtime: M,
+        shutdown_signal: F,
+    ) -> impl Future<Item = (), Error = Error>
+    where
+        M: 'static + ModuleRuntime + Clone,
+        M::Error: Into<Error>,
+        F: Future<Item = (), Error = ()> + 'static,
+    {
+        info!(
+            "Forwarding module events to IoT Hub every {} seconds...",
+            self.interval.as_secs()
+        );
+        let forwarder = Interval::new(Instant::now(), self.interval)
+            .map_err(Error::from)
+            .for_each(move |_| {
+                let forwarder = self.clone();
+                let selector = self.selector.clone();
+                runtime
+                    .list_with_details()
+                    .map_err(Into::into)
+                    .filter(move |&(ref module, _)| selector.matches(&module.labels()))
+                    .map(|(module, state)| (module.name().to_string(), state))
+                    .collect()
+                    .map(move |modules| {
+                        let events = forwarder.observe(&modules);
+                        forwarder.detect_crash_loops(&events);
+                        forwarder.forward(events);
+                    }).or_else(|err| {
+                        warn!("Could not check for module events: {}", err);
+                        Ok(())
+                    })
+            });
+
+        // Swallow any errors from shutdown_signal
+        let shutdown_signal = shutdown_signal.then(|_| Ok(()));
+
+        shutdown_signal
+            .select(forwarder)
+            .then(|result| match result {
+                Ok(((), _)) => Ok(()),
+                Err((e, _)) => Err(e),
+            })
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn event_of(name: &str, event: ModuleEventKind, now: DateTime<Utc>) -> ModuleEvent {
+    ModuleEvent {
+        module: name.to_string(),
+        event,
+        observed_at: now,
+        exit_code: None,
+        description: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use clock::ManualClock;
+    use notification::Notify;
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        notifications: Arc<Mutex<Vec<Notification>>>,
+    }
+
+    impl Notify for Recorder {
+        fn notify(&self, notification: &Notification) {
+            self.notifications
+                .lock()
+                .unwrap()
+                .push(notification.clone());
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(1_540_000_000 + secs, 0)
+    }
+
+    fn running(name: &str, started_at: i64) -> (String, ModuleRuntimeState) {
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Running)
+            .with_started_at(Some(at(started_at)));
+        (name.to_string(), state)
+    }
+
+    fn failed(name: &str, exit_code: i64) -> (String, ModuleRuntimeState) {
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Failed)
+            .with_exit_code(Some(exit_code));
+        (name.to_string(), state)
+    }
+
+    fn kinds(events: &[ModuleEvent]) -> Vec<(&str, ModuleEventKind)> {
+        events
+            .iter()
+            .map(|event| (event.module(), event.event()))
+            .collect()
+    }
+
+    #[test]
+    fn changes_between_polls_raise_events() {
+        let forwarder = ModuleEventForwarder::new().with_clock(ManualClock::new(at(100)));
+        assert!(
+            forwarder
+                .observe(&[running("sensor", 0), running("display", 0), running("old", 0)])
+                .is_empty()
+        );
+
+        let events = forwarder.observe(&[
+            running("sensor", 50),
+            failed("display", 137),
+            running("kiosk", 90),
+        ]);
+
+        assert_eq!(
+            vec![
+                ("sensor", ModuleEventKind::Restarted),
+                ("display", ModuleEventKind::Failed),
+                ("kiosk", ModuleEventKind::Created),
+                ("kiosk", ModuleEventKind::Started),
+                ("old", ModuleEventKind::Removed),
+            ],
+            kinds(&events)
+        );
+        assert_eq!(Some(137), events[1].exit_code());
+        assert_eq!(&at(100), events[0].observed_at());
+
+        // nothing changed since
+        assert!(
+            forwarder
+                .observe(&[running("sensor", 50), failed("display", 137), running("kiosk", 90)])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn events_over_the_rate_limit_are_dropped_and_counted() {
+        let clock = ManualClock::new(at(0));
+        let outbox = Outbox::new(10);
+        let forwarder = ModuleEventForwarder::new()
+            .with_max_events_per_minute(3)
+            .with_batch_size(2)
+            .with_outbox(outbox.clone())
+            .with_clock(clock.clone());
+        let events = |count: usize| {
+            (0..count)
+                .map(|_| event_of("sensor", ModuleEventKind::Restarted, at(0)))
+                .collect::<Vec<_>>()
+        };
+
+        forwarder.forward(events(5));
+        let messages = outbox.peek(10);
+        assert_eq!(2, messages.len());
+        assert_eq!(MessageKind::ModuleEvents, messages[0].kind());
+        assert_eq!(2, messages[0].body()["events"].as_array().unwrap().len());
+        assert_eq!(1, messages[1].body()["events"].as_array().unwrap().len());
+        assert_eq!(2, messages[0].body()["dropped"]);
+        assert!(messages[1].body().get("dropped").is_none());
+        outbox.remove(&messages).unwrap();
+
+        // still over the limit for this minute
+        forwarder.forward(events(1));
+        assert!(outbox.is_empty());
+
+        clock.advance(Duration::minutes(1));
+        forwarder.forward(Vec::new());
+        let messages = outbox.peek(10);
+        assert_eq!(1, messages.len());
+        assert!(messages[0].body()["events"].as_array().unwrap().is_empty());
+        assert_eq!(1, messages[0].body()["dropped"]);
+    }
+
+    #[test]
+    fn modules_that_keep_crashing_are_notified_of_once_a_window() {
+        let recorder = Recorder::default();
+        let forwarder =
+            ModuleEventForwarder::new().with_notifier(Notifier::new().with_sink(recorder.clone()));
+        let crash = |name: &str, mins: i64| {
+            forwarder.detect_crash_loops(&[event_of(
+                name,
+                ModuleEventKind::Restarted,
+                at(mins * 60),
+            )]);
+            recorder.notifications.lock().unwrap().len()
+        };
+
+        // too far apart to be a crash loop
+        assert_eq!(0, crash("sensor", 0));
+        assert_eq!(0, crash("sensor", 8));
+        assert_eq!(0, crash("sensor", 16));
+
+        assert_eq!(0, crash("display", 0));
+        assert_eq!(0, crash("display", 1));
+        assert_eq!(1, crash("display", 2));
+        assert_eq!(1, crash("display", 3));
+        assert_eq!(1, crash("display", 9));
+        assert_eq!(2, crash("display", 12));
+
+        let notifications = recorder.notifications.lock().unwrap();
+        assert_eq!(NotificationEvent::ModuleCrashLoop, notifications[0].event());
+        assert_eq!("display", notifications[0].subject());
+        assert_eq!(&at(120), notifications[0].occurred_at());
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use error::{Error, ErrorKind, Result};
+
+/// Namespaces become part of the names of networks and directories, so they
+/// are kept short.
+const MAX_NAMESPACE_LENGTH: usize = 32;
+
+type Listener = Arc<Fn(&str, &Path) + Send + Sync>;
+
+/// Tracks the namespaces the modules of the device are grouped in.
+///
+/// Modules that are deployed into a namespace, such as the modules of one
+/// solution on a gateway shared by several of them, only see the modules in
+/// the same namespace. Each namespace gets a workload socket of its own, in a
+/// directory named after it next to the workload socket modules outside of
+/// any namespace use, which is listened on from the first time the namespace
+/// is seen.
+///
+/// The runtime registers the namespaces of the modules it creates, so that
+/// the workload socket of a new namespace is listened on by whoever holds a
+/// clone with a listener.
+#[derive(Clone, Default)]
+pub struct Namespaces {
+    workload_socket: Option<PathBuf>,
+    known: Arc<Mutex<BTreeSet<String>>>,
+    listener: Option<Listener>,
+}
+
+impl Namespaces {
+    pub fn new() -> Self {
+        Namespaces::default()
+    }
+
+    /// Checks that `namespace` is made of lowercase letters, digits and
+    /// dashes, and starts with a letter or digit.
+    pub fn validate(namespace: &str) -> Result<()> {
+        let valid = !namespace.is_empty()
+            && namespace.len() <= MAX_NAMESPACE_LENGTH
+            && namespace
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && !namespace.starts_with('-');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::InvalidNamespace(
+                namespace.to_string(),
+            )))
+        }
+    }
+
+    /// Sets the workload socket of the modules outside of any namespace.
+    /// Without it, namespaces don't get workload sockets of their own.
+    pub fn with_workload_socket(mut self, workload_socket: PathBuf) -> Self {
+        self.workload_socket = Some(workload_socket);
+        self
+    }
+
+    /// Sets what is called with each namespace seen for the first time and
+    /// the path of its workload socket, to listen on it.
+    pub fn with_listener<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(&str, &Path) + Send + Sync + 'static,
+    {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    pub fn shared_workload_socket(&self) -> Option<&Path> {
+        self.workload_socket.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn workload_socket(&self, namespace: &str) -> Option<PathBuf> {
+        self.workload_socket.as_ref().and_then(|socket| {
+            let file_name = socket.file_name()?;
+            let dir = socket.parent()?;
+            Some(dir.join(namespace).join(file_name))
+        })
+    }
+
+    pub fn contains(&self, namespace: &str) -> bool {
+        self.lock().contains(namespace)
+    }
+
+    /// Records a namespace modules are deployed into, and has its workload
+    /// socket listened on if it is new.
+    pub fn register(&self, namespace: &str) -> Result<()> {
+        Namespaces::validate(namespace)?;
+
+        let mut known = self.lock();
+        if known.insert(namespace.to_string()) {
+            info!("Modules are being deployed into namespace {}", namespace);
+            if let (Some(listener), Some(socket)) =
+                (self.listener.as_ref(), self.workload_socket(namespace))
+            {
+                listener(namespace, &socket);
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<BTreeSet<String>> {
+        self.known.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_are_validated() {
+        for namespace in &["contoso", "fabrikam-2", "0"] {
+            assert!(Namespaces::validate(namespace).is_ok());
+        }
+        let too_long = "a".repeat(MAX_NAMESPACE_LENGTH + 1);
+        let invalid = ["", "-contoso", "Contoso", "con.toso", too_long.as_str()];
+        for namespace in &invalid {
+            match *Namespaces::validate(namespace).unwrap_err().kind() {
+                ErrorKind::InvalidNamespace(ref name) => assert_eq!(*namespace, name.as_str()),
+                ref kind => panic!("unexpected error kind {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn namespaces_get_a_workload_socket_of_their_own() {
+        let namespaces = Namespaces::new();
+        assert_eq!(None, namespaces.workload_socket("contoso"));
+
+        let namespaces =
+            namespaces.with_workload_socket(PathBuf::from("/var/run/iotedge/workload.sock"));
+        assert_eq!(
+            Some(PathBuf::from("/var/run/iotedge/contoso/workload.sock")),
+            namespaces.workload_socket("contoso")
+        );
+    }
+
+    #[test]
+    fn new_namespaces_are_listened_on_once() {
+        let sockets = Arc::new(Mutex::new(vec![]));
+        let namespaces = {
+            let sockets = sockets.clone();
+            Namespaces::new()
+                .with_workload_socket(PathBuf::from("/run/iotedge/workload.sock"))
+                .with_listener(move |namespace, socket| {
+                    sockets
+                        .lock()
+                        .unwrap()
+                        .push((namespace.to_string(), socket.to_path_buf()));
+                })
+        };
+
+        namespaces.register("contoso").unwrap();
+        namespaces.clone().register("contoso").unwrap();
+        assert!(namespaces.register("Contoso").is_err());
+
+        assert!(namespaces.contains("contoso"));
+        assert!(!namespaces.contains("Contoso"));
+        assert_eq!(
+            vec![(
+                "contoso".to_string(),
+                PathBuf::from("/run/iotedge/contoso/workload.sock"),
+            )],
+            *sockets.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Notifications of events on the device that on-premises systems, such as
+//! alerting, want to hear about without going through the cloud.
+
+use std::fmt;
+use std::sync::Arc;
+
+use base64;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use error::{ErrorKind, Result};
+
+/// The events notifications are sent for.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationEvent {
+    /// A module restarted or failed over and over in a short time.
+    ModuleCrashLoop,
+    /// A certificate the device depends on is about to expire.
+    CertificateExpiring,
+    /// The device was provisioned again with another identity or hub.
+    ProvisioningChanged,
+}
+
+impl fmt::Display for NotificationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NotificationEvent::ModuleCrashLoop => write!(f, "moduleCrashLoop"),
+            NotificationEvent::CertificateExpiring => write!(f, "certificateExpiring"),
+            NotificationEvent::ProvisioningChanged => write!(f, "provisioningChanged"),
+        }
+    }
+}
+
+/// An event that fired, and what it fired for: the module, the certificate
+/// or the device.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    event: NotificationEvent,
+    subject: String,
+    occurred_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+}
+
+impl Notification {
+    pub fn new(event: NotificationEvent, subject: &str, occurred_at: DateTime<Utc>) -> Self {
+        Notification {
+            event,
+            subject: subject.to_string(),
+            occurred_at,
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: String) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn event(&self) -> NotificationEvent {
+        self.event
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn occurred_at(&self) -> &DateTime<Utc> {
+        &self.occurred_at
+    }
+
+    pub fn details(&self) -> Option<&str> {
+        self.details.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// Delivers notifications, such as to webhooks. Delivery happens in the
+/// background, so `notify` doesn't wait for it and failures are left to the
+/// implementation to report.
+pub trait Notify: Send + Sync {
+    fn notify(&self, notification: &Notification);
+}
+
+/// Hands notifications to whatever delivers them. The default notifier
+/// discards them. The sinks are added before the notifier is handed out, so
+/// every part of the daemon that raises notifications delivers them to the
+/// same sinks.
+#[derive(Clone, Default)]
+pub struct Notifier {
+    sinks: Vec<Arc<Notify>>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Notifier::default()
+    }
+
+    pub fn with_sink<N: 'static + Notify>(mut self, sink: N) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Whether notifications are discarded, so that the events they are for
+    /// needn't be watched for.
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn notify(&self, notification: &Notification) {
+        for sink in &self.sinks {
+            sink.notify(notification);
+        }
+    }
+}
+
+/// Signs the body of a notification with the shared `secret` of its
+/// receiver, as the base64 HMAC-SHA256 of the body, so that the receiver can
+/// tell it came from the device.
+pub fn sign_notification(secret: &[u8], body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new(secret).map_err(|_| ErrorKind::Sign(secret.len()))?;
+    mac.input(body);
+    Ok(base64::encode(mac.result().code().as_slice()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use chrono::TimeZone;
+    use serde_json;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        notifications: Arc<Mutex<Vec<Notification>>>,
+    }
+
+    impl Notify for Recorder {
+        fn notify(&self, notification: &Notification) {
+            self.notifications
+                .lock()
+                .unwrap()
+                .push(notification.clone());
+        }
+    }
+
+    #[test]
+    fn notifications_go_to_every_sink() {
+        let first = Recorder::default();
+        let second = Recorder::default();
+        let notifier = Notifier::new()
+            .with_sink(first.clone())
+            .with_sink(second.clone());
+        assert!(!notifier.is_empty());
+        assert!(Notifier::new().is_empty());
+
+        let notification = Notification::new(
+            NotificationEvent::ModuleCrashLoop,
+            "sensor",
+            Utc.ymd(2018, 10, 15).and_hms(12, 0, 0),
+        ).with_details("restarted 3 times".to_string());
+        notifier.notify(&notification);
+
+        assert_eq!(
+            vec![notification.clone()],
+            *first.notifications.lock().unwrap()
+        );
+        assert_eq!(vec![notification], *second.notifications.lock().unwrap());
+    }
+
+    #[test]
+    fn notifications_are_named_by_event() {
+        let notification = Notification::new(
+            NotificationEvent::ProvisioningChanged,
+            "gateway",
+            Utc.ymd(2018, 10, 15).and_hms(12, 0, 0),
+        );
+        let json = serde_json::to_value(&notification).unwrap();
+        assert_eq!("provisioningChanged", json["event"]);
+        assert_eq!("gateway", json["subject"]);
+        assert_eq!("2018-10-15T12:00:00Z", json["occurredAt"]);
+        assert!(json.get("details").is_none());
+        assert_eq!(
+            "provisioningChanged",
+            NotificationEvent::ProvisioningChanged.to_string()
+        );
+    }
+
+    #[test]
+    fn signatures_depend_on_the_secret() {
+        let signature = sign_notification(b"secret", b"{}").unwrap();
+        assert_eq!(signature, sign_notification(b"secret", b"{}").unwrap());
+        assert_ne!(signature, sign_notification(b"other", b"{}").unwrap());
+        assert_ne!(signature, sign_notification(b"secret", b"[]").unwrap());
+        assert_eq!(32, base64::decode(&signature).unwrap().len());
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use chrono::{DateTime, Utc};
+
+use clock::{Clock, SystemClock};
+
+/// Number of finished operations kept for callers to poll.
+const MAX_FINISHED_OPERATIONS: usize = 20;
+
+/// Where a long running operation is at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperationStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl OperationStatus {
+    pub fn is_finished(self) -> bool {
+        self != OperationStatus::Running
+    }
+}
+
+impl fmt::Display for OperationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match *self {
+            OperationStatus::Running => "running",
+            OperationStatus::Succeeded => "succeeded",
+            OperationStatus::Failed => "failed",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// What is known of an operation when it is polled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperationState {
+    id: String,
+    kind: String,
+    status: OperationStatus,
+    percent: u8,
+    step: Option<String>,
+    error: Option<String>,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl OperationState {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// What the operation does, such as `deployment` or `imagePull`.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn status(&self) -> OperationStatus {
+        self.status
+    }
+
+    /// How far along the operation is, from 0 to 100.
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    /// What the operation is doing, or did last when it failed.
+    pub fn step(&self) -> Option<&str> {
+        self.step.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn started_at(&self) -> &DateTime<Utc> {
+        &self.started_at
+    }
+
+    pub fn finished_at(&self) -> Option<&DateTime<Utc>> {
+        self.finished_at.as_ref()
+    }
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    operations: VecDeque<OperationState>,
+}
+
+/// The long running operations started through the management API since the
+/// daemon started, such as applying a deployment or pulling an image. The
+/// request that starts one is answered right away with the ID of the
+/// operation, whose progress is then polled.
+///
+/// Operations are kept in memory. Running ones are always kept, and so are
+/// the last few that finished. An operation started by one request can be
+/// polled by the next, which is handled by another clone of the service.
+#[derive(Clone)]
+pub struct Operations {
+    state: Arc<Mutex<State>>,
+    clock: Arc<Clock>,
+}
+
+impl Default for Operations {
+    fn default() -> Self {
+        Operations {
+            state: Arc::new(Mutex::new(State::default())),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl Operations {
+    pub fn new() -> Self {
+        Operations::default()
+    }
+
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Starts tracking an operation of `kind`. Its progress is reported
+    /// through the returned handle.
+    pub fn start(&self, kind: &str) -> Operation {
+        let now = self.clock.now();
+        let mut state = self.lock();
+        state.next_id += 1;
+        let id = state.next_id.to_string();
+        state.operations.push_front(OperationState {
+            id: id.clone(),
+            kind: kind.to_string(),
+            status: OperationStatus::Running,
+            percent: 0,
+            step: None,
+            error: None,
+            started_at: now,
+            finished_at: None,
+        });
+
+        // drop the oldest finished operations
+        let mut finished = 0;
+        state.operations.retain(|operation| {
+            if operation.status.is_finished() {
+                finished += 1;
+                finished <= MAX_FINISHED_OPERATIONS
+            } else {
+                true
+            }
+        });
+
+        Operation {
+            id,
+            operations: self.clone(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<OperationState> {
+        self.lock()
+            .operations
+            .iter()
+            .find(|operation| operation.id == id)
+            .cloned()
+    }
+
+    /// Lists the operations, the most recently started first.
+    pub fn list(&self) -> Vec<OperationState> {
+        self.lock().operations.iter().cloned().collect()
+    }
+
+    fn update<F: FnOnce(&mut OperationState)>(&self, id: &str, f: F) {
+        if let Some(operation) = self
+            .lock()
+            .operations
+            .iter_mut()
+            .find(|operation| operation.id == id && !operation.status.is_finished())
+        {
+            f(operation);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Reports the progress of one operation. Once the operation has succeeded
+/// or failed, it can't be changed anymore.
+#[derive(Clone)]
+pub struct Operation {
+    id: String,
+    operations: Operations,
+}
+
+impl Operation {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Records that the operation is `percent` done, and is now at `step`.
+    /// The operation doesn't go past 99 percent until it has succeeded.
+    pub fn progress(&self, percent: u8, step: &str) {
+        self.operations.update(&self.id, |operation| {
+            operation.percent = percent.min(99);
+            operation.step = Some(step.to_string());
+        });
+    }
+
+    pub fn succeed(&self) {
+        let now = self.operations.clock.now();
+        self.operations.update(&self.id, |operation| {
+            operation.status = OperationStatus::Succeeded;
+            operation.percent = 100;
+            operation.step = None;
+            operation.finished_at = Some(now);
+        });
+    }
+
+    /// Records that the operation failed with `error`. Its progress is left
+    /// at the step that failed.
+    pub fn fail(&self, error: String) {
+        let now = self.operations.clock.now();
+        self.operations.update(&self.id, |operation| {
+            operation.status = OperationStatus::Failed;
+            operation.error = Some(error);
+            operation.finished_at = Some(now);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_is_reported_until_the_operation_finishes() {
+        let operations = Operations::new();
+        let operation = operations.start("imagePull");
+        let state = operations.get(operation.id()).unwrap();
+        assert_eq!("imagePull", state.kind());
+        assert_eq!(OperationStatus::Running, state.status());
+        assert_eq!(0, state.percent());
+
+        operation.progress(100, "pulling");
+        let state = operations.get(operation.id()).unwrap();
+        assert_eq!(99, state.percent());
+        assert_eq!(Some("pulling"), state.step());
+
+        operation.fail("no such image".to_string());
+        operation.succeed();
+        let state = operations.get(operation.id()).unwrap();
+        assert_eq!(OperationStatus::Failed, state.status());
+        assert_eq!(Some("no such image"), state.error());
+        assert_eq!(Some("pulling"), state.step());
+        assert!(state.finished_at().is_some());
+
+        assert!(operations.get("42").is_none());
+    }
+
+    #[test]
+    fn only_the_last_finished_operations_are_kept() {
+        let operations = Operations::new();
+        let running = operations.start("deployment");
+        for _ in 0..MAX_FINISHED_OPERATIONS + 5 {
+            operations.start("imagePull").succeed();
+        }
+        operations.start("imagePull");
+
+        let listed = operations.list();
+        assert_eq!(MAX_FINISHED_OPERATIONS + 2, listed.len());
+        assert_eq!(OperationStatus::Running, listed[0].status());
+        assert_eq!(running.id(), listed[listed.len() - 1].id());
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use failure::ResultExt;
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use error::{ErrorKind, Result};
+
+/// This is how long messages are kept while IoT Hub can't be reached, unless
+/// the outbox is created with another retention.
+const DEFAULT_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// What a message sent by the daemon is about.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageKind {
+    CheckResult,
+    LoadShedding,
+    ModuleEvents,
+    ModuleImage,
+    SecurityEvent,
+    TrustBundleChanged,
+    WatchdogAction,
+}
+
+/// A device to cloud message produced by the daemon itself.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboundMessage {
+    kind: MessageKind,
+    created_at: DateTime<Utc>,
+    body: Value,
+}
+
+impl OutboundMessage {
+    pub fn kind(&self) -> MessageKind {
+        self.kind
+    }
+
+    pub fn created_at(&self) -> &DateTime<Utc> {
+        &self.created_at
+    }
+
+    pub fn body(&self) -> &Value {
+        &self.body
+    }
+}
+
+/// A bounded store and forward queue for the messages the daemon sends to
+/// IoT Hub. When the queue is full the oldest message is dropped, as are
+/// messages older than the retention. An outbox without capacity, which is
+/// the default, discards every message. When created with `load` every change
+/// is persisted to disk so that queued messages survive a restart. Messages
+/// are queued from wherever the daemon reports from and sent in the order
+/// they were queued, whichever clone they were queued on.
+#[derive(Clone)]
+pub struct Outbox {
+    path: Option<PathBuf>,
+    capacity: usize,
+    retention: Duration,
+    messages: Arc<Mutex<VecDeque<OutboundMessage>>>,
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        Outbox::new(0)
+    }
+}
+
+impl Outbox {
+    pub fn new(capacity: usize) -> Self {
+        Outbox {
+            path: None,
+            capacity,
+            retention: Duration::from_secs(DEFAULT_RETENTION_SECS),
+            messages: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, capacity: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut messages: VecDeque<OutboundMessage> = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::Outbox)?;
+            serde_json::from_reader(file).context(ErrorKind::Outbox)?
+        } else {
+            VecDeque::new()
+        };
+        while messages.len() > capacity {
+            messages.pop_front();
+        }
+
+        Ok(Outbox {
+            path: Some(path),
+            messages: Arc::new(Mutex::new(messages)),
+            ..Outbox::new(capacity)
+        })
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Queues a message to be sent once IoT Hub can be reached.
+    pub fn push<T: Serialize>(&self, kind: MessageKind, body: &T) -> Result<()> {
+        let body = serde_json::to_value(body).context(ErrorKind::Outbox)?;
+        self.push_at(kind, body, Utc::now())
+    }
+
+    /// Returns up to `count` of the oldest messages, leaving them queued
+    /// until they are acknowledged with `remove`.
+    pub fn peek(&self, count: usize) -> Vec<OutboundMessage> {
+        let mut messages = self.lock();
+        self.expire(&mut messages, Utc::now());
+        messages.iter().take(count).cloned().collect()
+    }
+
+    /// Removes `messages` once they've been sent. Messages that have already
+    /// been dropped to make room for newer ones are skipped.
+    pub fn remove(&self, sent: &[OutboundMessage]) -> Result<()> {
+        let mut messages = self.lock();
+        messages.retain(|message| !sent.contains(message));
+        self.persist(&messages)
+    }
+
+    fn push_at(&self, kind: MessageKind, body: Value, now: DateTime<Utc>) -> Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        let mut messages = self.lock();
+        self.expire(&mut messages, now);
+        while messages.len() >= self.capacity {
+            messages.pop_front();
+        }
+        messages.push_back(OutboundMessage {
+            kind,
+            created_at: now,
+            body,
+        });
+        self.persist(&messages)
+    }
+
+    fn expire(&self, messages: &mut VecDeque<OutboundMessage>, now: DateTime<Utc>) {
+        let retention = self.retention;
+        messages.retain(|message| {
+            (now - message.created_at)
+                .to_std()
+                .map_or(true, |age| age < retention)
+        });
+    }
+
+    fn persist(&self, messages: &VecDeque<OutboundMessage>) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::Outbox)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::Outbox)?;
+            serde_json::to_writer(file, messages).context(ErrorKind::Outbox)?;
+            fs::rename(&temp, path).context(ErrorKind::Outbox)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<VecDeque<OutboundMessage>> {
+        self.messages.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration as ChronoDuration, TimeZone};
+    use serde_json::Map;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn body(n: u64) -> Value {
+        let mut body = Map::new();
+        body.insert("n".to_string(), Value::from(n));
+        Value::Object(body)
+    }
+
+    #[test]
+    fn outbox_without_capacity_discards_messages() {
+        let outbox = Outbox::default();
+        outbox.push(MessageKind::CheckResult, &body(1)).unwrap();
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn full_outbox_drops_oldest_messages() {
+        let outbox = Outbox::new(2);
+        for n in 1..4 {
+            outbox.push(MessageKind::WatchdogAction, &body(n)).unwrap();
+        }
+        let messages = outbox.peek(10);
+        assert_eq!(2, messages.len());
+        assert_eq!(&body(2), messages[0].body());
+        assert_eq!(&body(3), messages[1].body());
+    }
+
+    #[test]
+    fn peeked_messages_stay_queued_until_removed() {
+        let outbox = Outbox::new(10);
+        for n in 1..4 {
+            outbox.push(MessageKind::SecurityEvent, &body(n)).unwrap();
+        }
+        let batch = outbox.peek(2);
+        assert_eq!(2, batch.len());
+        assert_eq!(3, outbox.len());
+
+        outbox.remove(&batch).unwrap();
+        let rest = outbox.peek(2);
+        assert_eq!(1, rest.len());
+        assert_eq!(&body(3), rest[0].body());
+    }
+
+    #[test]
+    fn expired_messages_are_dropped() {
+        let outbox = Outbox::new(10).with_retention(Duration::from_secs(3600));
+        let now = Utc::now();
+        outbox
+            .push_at(MessageKind::CheckResult, body(1), now - ChronoDuration::hours(2))
+            .unwrap();
+        outbox
+            .push_at(MessageKind::CheckResult, body(2), Utc.timestamp(0, 0))
+            .unwrap();
+        outbox.push_at(MessageKind::CheckResult, body(3), now).unwrap();
+
+        let messages = outbox.peek(10);
+        assert_eq!(1, messages.len());
+        assert_eq!(&body(3), messages[0].body());
+    }
+
+    #[test]
+    fn load_reads_persisted_messages() {
+        let tmp_dir = TempDir::new("outbox").unwrap();
+        let path = tmp_dir.path().join("outbox.json");
+
+        let outbox = Outbox::load(&path, 10).unwrap();
+        outbox.push(MessageKind::SecurityEvent, &body(1)).unwrap();
+        outbox.push(MessageKind::WatchdogAction, &body(2)).unwrap();
+
+        let loaded = Outbox::load(&path, 1).unwrap();
+        let messages = loaded.peek(10);
+        assert_eq!(1, messages.len());
+        assert_eq!(MessageKind::WatchdogAction, messages[0].kind());
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Reconciliation of the modules of the device with a fixed module set.
+//!
+//! Devices that always run the same modules, such as kiosks, don't need the
+//! edge agent to take deployments from IoT Hub. The daemon runs the modules
+//! of a manifest it is given instead: every so often it lists the modules the
+//! runtime has and works out what it takes to get from there to the
+//! manifest. Missing modules are created, modules that changed are created
+//! again, stopped ones are started, and the ones the manifest doesn't have
+//! are removed. A module that can't be brought in line is left for the next
+//! check, without holding up the others.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use edgelet_utils::log_failure;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use log::Level;
+use tokio::timer::Interval;
+
+use env::{GENERATION_ID_KEY, MODULE_ID_KEY};
+use error::Error;
+use identity::{Identity, IdentityManager, IdentitySpec};
+use module::{Module, ModuleRegistry, ModuleRuntime, ModuleSpec, ModuleStatus};
+
+/// This is the default frequency with which the modules are reconciled with
+/// the manifest.
+const RECONCILE_FREQUENCY_SECS: u64 = 30;
+
+/// Time to allow the modules to shut down gracefully when the daemon stops.
+const MODULE_STOP_TIME: Duration = Duration::from_secs(30);
+
+/// The modules of the edge runtime have module IDs that aren't their names.
+const SYSTEM_MODULES: &[(&str, &str)] = &[("edgeAgent", "$edgeAgent"), ("edgeHub", "$edgeHub")];
+
+/// The generation ID of the modules created while the device is offline,
+/// when IoT Hub can't be asked for the identities of the modules.
+const OFFLINE_GENERATION_ID: &str = "offline";
+
+/// What it takes to bring one module in line with the manifest.
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    Create(String),
+    Recreate(String),
+    Start(String),
+    Remove(String),
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Create(ref name) => write!(f, "create module {}", name),
+            Action::Recreate(ref name) => write!(f, "create module {} again", name),
+            Action::Start(ref name) => write!(f, "start module {}", name),
+            Action::Remove(ref name) => write!(f, "remove module {}", name),
+        }
+    }
+}
+
+/// What the runtime has of a module.
+#[derive(Debug)]
+struct Existing {
+    name: String,
+    status: ModuleStatus,
+    current: bool,
+}
+
+type Config<M> = <<M as ModuleRuntime>::Module as Module>::Config;
+
+type Check<M> =
+    Arc<Fn(&ModuleSpec<Config<M>>, &<M as ModuleRuntime>::Module) -> bool + Send + Sync>;
+
+pub struct Reconciler<M, I>
+where
+    M: ModuleRuntime,
+{
+    runtime: M,
+    id_mgr: I,
+    frequency: Duration,
+    check: Check<M>,
+    offline: bool,
+}
+
+impl<M, I> Reconciler<M, I>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: Clone,
+    M::Error: Into<Error>,
+    <M::Module as Module>::Error: Into<Error>,
+    I: 'static + IdentityManager + Clone,
+    I::Error: Into<Error>,
+{
+    pub fn new(runtime: M, id_mgr: I) -> Self {
+        Reconciler {
+            runtime,
+            id_mgr,
+            frequency: Duration::from_secs(RECONCILE_FREQUENCY_SECS),
+            check: Arc::new(|_, _| true),
+            offline: false,
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: Duration) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Sets how to tell whether a module the runtime has is still the one
+    /// its spec in the manifest describes. Modules are only created again
+    /// when `check` says they aren't. Without it, existing modules are kept
+    /// as they are.
+    pub fn with_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&ModuleSpec<<M::Module as Module>::Config>, &M::Module) -> bool
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.check = Arc::new(check);
+        self
+    }
+
+    /// Creates the modules without their identities in IoT Hub when
+    /// `offline`. They are given a generation ID of their own instead.
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Reconciles the modules with `manifest` until `shutdown_signal` fires,
+    /// and stops the modules of the manifest then.
+    pub fn run_until<F>(
+        self,
+        manifest: Vec<ModuleSpec<<M::Module as Module>::Config>>,
+        shutdown_signal: F,
+    ) -> impl Future<Item = (), Error = Error>
+    where
+        F: Future<Item = (), Error = ()> + 'static,
+    {
+        info!(
+            "Reconciling {} modules with the fixed module set every {} seconds...",
+            manifest.len(),
+            self.frequency.as_secs()
+        );
+        let names: Vec<String> = manifest
+            .iter()
+            .map(|spec| spec.name().to_string())
+            .collect();
+        let runtime = self.runtime.clone();
+        let Reconciler {
+            runtime: reconciled,
+            id_mgr,
+            frequency,
+            check,
+            offline,
+        } = self;
+
+        let reconciler = Interval::new(Instant::now(), frequency)
+            .map_err(Error::from)
+            .for_each(move |_| {
+                reconcile(&reconciled, &id_mgr, &manifest, &check, offline).or_else(|err| {
+                    warn!("Could not reconcile the modules with the fixed module set:");
+                    log_failure(Level::Warn, &err);
+                    Ok(())
+                })
+            });
+
+        // Swallow any errors from shutdown_signal
+        let shutdown_signal = shutdown_signal.then(|_| Ok(()));
+
+        shutdown_signal
+            .select(reconciler)
+            .then(move |result| match result {
+                Ok(((), _)) => Either::A(stop_all(&runtime, &names)),
+                Err((e, _)) => Either::B(future::err(e)),
+            })
+    }
+}
+
+fn reconcile<M, I>(
+    runtime: &M,
+    id_mgr: &I,
+    manifest: &[ModuleSpec<<M::Module as Module>::Config>],
+    check: &Check<M>,
+    offline: bool,
+) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: Clone,
+    M::Error: Into<Error>,
+    <M::Module as Module>::Error: Into<Error>,
+    I: 'static + IdentityManager + Clone,
+    I::Error: Into<Error>,
+{
+    let runtime = runtime.clone();
+    let id_mgr = id_mgr.clone();
+    let manifest = manifest.to_vec();
+    let check = check.clone();
+    runtime
+        .list()
+        .map_err(Into::into)
+        .and_then(|modules| {
+            future::join_all(modules.into_iter().map(|module| {
+                let state = module.runtime_state();
+                state.map_err(Into::into).map(move |state| (module, state))
+            }))
+        }).and_then(move |modules| {
+            let existing: Vec<Existing> = modules
+                .iter()
+                .map(|&(ref module, ref state)| Existing {
+                    name: module.name().to_string(),
+                    status: *state.status(),
+                    current: manifest
+                        .iter()
+                        .find(|spec| spec.name() == module.name())
+                        .map_or(true, |spec| check(spec, module)),
+                }).collect();
+            let actions = plan(&manifest, &existing);
+            stream::iter_ok::<_, Error>(actions).for_each(move |action| {
+                let applied = apply(&runtime, &id_mgr, &manifest, &action, offline);
+                applied.then(move |result| {
+                    if let Err(err) = result {
+                        warn!("Could not {} of the fixed module set:", action);
+                        log_failure(Level::Warn, &err);
+                    }
+                    Ok(())
+                })
+            })
+        })
+}
+
+/// Works out what it takes to get from the `existing` modules to the ones of
+/// `manifest`. Modules that go away are removed first, so that they let go
+/// of what the new ones may need, such as ports.
+fn plan<C>(manifest: &[ModuleSpec<C>], existing: &[Existing]) -> Vec<Action> {
+    let mut actions: Vec<Action> = existing
+        .iter()
+        .filter(|module| !manifest.iter().any(|spec| spec.name() == module.name))
+        .map(|module| Action::Remove(module.name.clone()))
+        .collect();
+
+    for spec in manifest {
+        let name = spec.name().to_string();
+        match existing.iter().find(|module| module.name == name) {
+            None => actions.push(Action::Create(name)),
+            Some(module) if !module.current => actions.push(Action::Recreate(name)),
+            Some(module) if module.status != ModuleStatus::Running => {
+                actions.push(Action::Start(name))
+            }
+            Some(_) => (),
+        }
+    }
+    actions
+}
+
+fn apply<M, I>(
+    runtime: &M,
+    id_mgr: &I,
+    manifest: &[ModuleSpec<<M::Module as Module>::Config>],
+    action: &Action,
+    offline: bool,
+) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: Clone,
+    M::Error: Into<Error>,
+    <M::Module as Module>::Error: Into<Error>,
+    I: 'static + IdentityManager + Clone,
+    I::Error: Into<Error>,
+{
+    let spec = |name: &str| {
+        manifest
+            .iter()
+            .find(|spec| spec.name() == name)
+            .cloned()
+            .expect("actions are planned from the manifest")
+    };
+    match *action {
+        Action::Create(ref name) => {
+            info!("Creating module {} of the fixed module set", name);
+            Either::A(create_and_start(
+                runtime.clone(),
+                id_mgr,
+                spec(name),
+                offline,
+            ))
+        }
+        Action::Recreate(ref name) => {
+            info!(
+                "Module {} changed in the fixed module set, creating it again",
+                name
+            );
+            let runtime = runtime.clone();
+            let id_mgr = id_mgr.clone();
+            let spec = spec(name);
+            Either::B(Either::A(remove(&runtime, name).and_then(move |()| {
+                create_and_start(runtime, &id_mgr, spec, offline)
+            })))
+        }
+        Action::Start(ref name) => {
+            info!("Starting module {} of the fixed module set", name);
+            Either::B(Either::B(Either::A(
+                runtime.start(name).map_err(Into::into),
+            )))
+        }
+        Action::Remove(ref name) => {
+            info!(
+                "Removing module {}, which is not in the fixed module set",
+                name
+            );
+            Either::B(Either::B(Either::B(remove(runtime, name))))
+        }
+    }
+}
+
+// A module that can't be stopped, because it isn't running, is removed all
+// the same.
+fn remove<M>(runtime: &M, name: &str) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    M::Error: Into<Error>,
+{
+    let remover = runtime.clone();
+    let name = name.to_string();
+    runtime
+        .stop(&name, Some(MODULE_STOP_TIME))
+        .then(move |_| remover.remove(&name))
+        .map_err(Into::into)
+}
+
+fn create_and_start<M, I>(
+    runtime: M,
+    id_mgr: &I,
+    spec: ModuleSpec<<M::Module as Module>::Config>,
+    offline: bool,
+) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: Clone,
+    M::Error: Into<Error>,
+    <M::Module as Module>::Error: Into<Error>,
+    I: 'static + IdentityManager + Clone,
+    I::Error: Into<Error>,
+{
+    let module_id = module_id(spec.name()).to_string();
+    let runtime_copy = runtime.clone();
+    let name = spec.name().to_string();
+
+    let generation_id = if offline {
+        Either::A(future::ok(OFFLINE_GENERATION_ID.to_string()))
+    } else {
+        Either::B(
+            module_identity(id_mgr, &module_id)
+                .map(|identity| identity.generation_id().to_string()),
+        )
+    };
+
+    generation_id.and_then(move |generation_id| {
+        let mut env = spec.env().clone();
+        env.insert(MODULE_ID_KEY.to_string(), module_id);
+        env.insert(GENERATION_ID_KEY.to_string(), generation_id);
+        let spec = spec.with_env(env);
+        runtime
+            .registry()
+            .pull(spec.clone().config())
+            .and_then(move |_| runtime.create(spec))
+            .and_then(move |_| runtime_copy.start(&name))
+            .map_err(Into::into)
+    })
+}
+
+// The identity of a module in IoT Hub, which is created if the module doesn't
+// have one yet.
+fn module_identity<I>(id_mgr: &I, module_id: &str) -> impl Future<Item = I::Identity, Error = Error>
+where
+    I: 'static + IdentityManager + Clone,
+    I::Error: Into<Error>,
+{
+    let mut id_mgr_copy = id_mgr.clone();
+    let module_id = module_id.to_string();
+    id_mgr
+        .get(IdentitySpec::new(&module_id))
+        .map_err(Into::into)
+        .and_then(move |identity| match identity {
+            Some(identity) => Either::A(
+                id_mgr_copy
+                    .update(
+                        IdentitySpec::new(identity.module_id())
+                            .with_generation_id(identity.generation_id().to_string()),
+                    ).map_err(Into::into),
+            ),
+            None => {
+                info!("Creating identity for module {}", module_id);
+                Either::B(
+                    id_mgr_copy
+                        .create(IdentitySpec::new(&module_id))
+                        .map_err(Into::into),
+                )
+            }
+        })
+}
+
+fn stop_all<M>(runtime: &M, names: &[String]) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+{
+    info!("Stopping the modules of the fixed module set");
+    future::join_all(names.iter().map(|name| {
+        let name = name.clone();
+        runtime
+            .stop(&name, Some(MODULE_STOP_TIME))
+            .then(move |result| {
+                if result.is_err() {
+                    debug!("Module {} was not stopped", name);
+                }
+                Ok::<_, Error>(())
+            })
+    })).map(|_| ())
+}
+
+/// The id of the identity of module `name` in IoT Hub, which is the name of
+/// the module but for the modules of the edge runtime.
+pub fn module_id(name: &str) -> &str {
+    SYSTEM_MODULES
+        .iter()
+        .find(|&&(module, _)| module == name)
+        .map_or(name, |&(_, id)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn spec(name: &str) -> ModuleSpec<()> {
+        ModuleSpec::new(name, "docker", (), HashMap::new()).unwrap()
+    }
+
+    fn existing(name: &str, status: ModuleStatus, current: bool) -> Existing {
+        Existing {
+            name: name.to_string(),
+            status,
+            current,
+        }
+    }
+
+    #[test]
+    fn runtime_is_brought_in_line_with_manifest() {
+        let manifest = vec![
+            spec("edgeHub"),
+            spec("sensor"),
+            spec("display"),
+            spec("kiosk"),
+        ];
+        let modules = vec![
+            existing("edgeAgent", ModuleStatus::Running, true),
+            existing("edgeHub", ModuleStatus::Running, true),
+            existing("sensor", ModuleStatus::Failed, true),
+            existing("display", ModuleStatus::Running, false),
+        ];
+
+        assert_eq!(
+            vec![
+                Action::Remove("edgeAgent".to_string()),
+                Action::Start("sensor".to_string()),
+                Action::Recreate("display".to_string()),
+                Action::Create("kiosk".to_string()),
+            ],
+            plan(&manifest, &modules)
+        );
+    }
+
+    #[test]
+    fn nothing_is_done_when_runtime_matches_manifest() {
+        let manifest = vec![spec("edgeHub")];
+        let modules = vec![existing("edgeHub", ModuleStatus::Running, true)];
+        assert!(plan(&manifest, &modules).is_empty());
+    }
+
+    #[test]
+    fn system_modules_have_their_own_module_ids() {
+        assert_eq!("$edgeHub", module_id("edgeHub"));
+        assert_eq!("$edgeAgent", module_id("edgeAgent"));
+        assert_eq!("sensor", module_id("sensor"));
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Signing of workload API responses.
+//!
+//! Modules trust what the workload API tells them about certificates, but a
+//! socket mounted from the wrong place can put another process between them
+//! and the daemon. With response signing on, the daemon signs the bodies of
+//! the responses that carry certificates with an ECDSA P-256 key of its own,
+//! and hands modules the public key when they are created, so that they can
+//! tell a response that didn't come from the daemon.
+//!
+//! The key is kept in the home directory, so that modules created before the
+//! daemon restarts can still verify its responses.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use base64;
+use failure::ResultExt;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::{Signer, Verifier};
+use sha2::{Digest, Sha256};
+
+use error::{ErrorKind, Result};
+
+/// The header the signature of a response is sent in.
+pub const SIGNATURE_HEADER: &str = "x-iotedge-signature";
+
+/// Signs response bodies with the key of the daemon. The key is only loaded
+/// once, however many workload APIs sign with it.
+#[derive(Clone)]
+pub struct ResponseSigner {
+    key: Arc<PKey<Private>>,
+    public_key: Vec<u8>,
+}
+
+impl ResponseSigner {
+    /// Loads the key at `path`, or generates one there if there is none yet.
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        let key = if path.exists() {
+            let pem = fs::read(path).context(ErrorKind::ResponseSigning)?;
+            PKey::private_key_from_pem(&pem).context(ErrorKind::ResponseSigning)?
+        } else {
+            let key = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)
+                .and_then(|group| EcKey::generate(&group))
+                .and_then(PKey::from_ec_key)
+                .context(ErrorKind::ResponseSigning)?;
+            let pem = key
+                .private_key_to_pem_pkcs8()
+                .context(ErrorKind::ResponseSigning)?;
+            write_key(path, &pem).context(ErrorKind::ResponseSigning)?;
+            key
+        };
+        ResponseSigner::new(key)
+    }
+
+    fn new(key: PKey<Private>) -> Result<Self> {
+        let public_key = key
+            .public_key_to_der()
+            .context(ErrorKind::ResponseSigning)?;
+        Ok(ResponseSigner {
+            key: Arc::new(key),
+            public_key,
+        })
+    }
+
+    /// The public key responses are verified with, as a base64 encoded DER
+    /// `SubjectPublicKeyInfo`.
+    pub fn verification_key(&self) -> String {
+        base64::encode(&self.public_key)
+    }
+
+    /// A short fingerprint of the verification key, the base64 encoded
+    /// SHA-256 of its DER encoding, for where the key itself doesn't fit.
+    pub fn fingerprint(&self) -> String {
+        base64::encode(&Sha256::digest(&self.public_key))
+    }
+
+    /// The base64 encoded DER ECDSA signature of the SHA-256 of `body`.
+    pub fn sign(&self, body: &[u8]) -> Result<String> {
+        let mut signer =
+            Signer::new(MessageDigest::sha256(), &self.key).context(ErrorKind::ResponseSigning)?;
+        signer.update(body).context(ErrorKind::ResponseSigning)?;
+        let signature = signer.sign_to_vec().context(ErrorKind::ResponseSigning)?;
+        Ok(base64::encode(&signature))
+    }
+}
+
+/// Checks `signature` over `body` with `verification_key`, both base64
+/// encoded the way `ResponseSigner` gives them out.
+pub fn verify(verification_key: &str, body: &[u8], signature: &str) -> Result<bool> {
+    let key = base64::decode(verification_key).context(ErrorKind::ResponseSigning)?;
+    let key = PKey::public_key_from_der(&key).context(ErrorKind::ResponseSigning)?;
+    let signature = base64::decode(signature).context(ErrorKind::ResponseSigning)?;
+    let mut verifier =
+        Verifier::new(MessageDigest::sha256(), &key).context(ErrorKind::ResponseSigning)?;
+    verifier.update(body).context(ErrorKind::ResponseSigning)?;
+    Ok(verifier
+        .verify(&signature)
+        .context(ErrorKind::ResponseSigning)?)
+}
+
+// Only the daemon's user may read the key.
+fn write_key(path: &Path, pem: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(pem)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn responses_verify_with_the_key_of_the_signer() {
+        let tmp_dir = TempDir::new("response_signing").unwrap();
+        let dir = tmp_dir.path();
+        let path = dir.join("response_signing_key.pem");
+        let signer = ResponseSigner::load_or_create(&path).unwrap();
+        let body = br#"{"certificate":"-----BEGIN CERTIFICATE-----"}"#;
+
+        let signature = signer.sign(body).unwrap();
+        assert!(verify(&signer.verification_key(), body, &signature).unwrap());
+        assert!(!verify(&signer.verification_key(), b"{}", &signature).unwrap());
+
+        let reloaded = ResponseSigner::load_or_create(&path).unwrap();
+        assert_eq!(signer.verification_key(), reloaded.verification_key());
+        assert_eq!(signer.fingerprint(), reloaded.fingerprint());
+
+        let other = ResponseSigner::load_or_create(&dir.join("other.pem")).unwrap();
+        assert!(!verify(&other.verification_key(), body, &signature).unwrap());
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! A future combinator that re-runs a fallible operation according to a
+//! `RetryPolicy`.
+//!
+//! ```ignore
+//! let policy = RetryPolicy::exponential(Duration::from_secs(1), Duration::from_secs(30))
+//!     .with_max_attempts(5);
+//! let pull = Retry::new(policy, move |_attempt| registry.pull(&config))
+//!     .with_hook(|attempt, _err, delay| warn!("pull attempt {} failed, retrying in {:?}", attempt, delay));
+//! ```
+
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+use rand::{self, Rng};
+use tokio::timer::Delay;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backoff {
+    /// Wait the same amount of time between every attempt.
+    Fixed(Duration),
+
+    /// Double the wait after every failed attempt, starting at `initial` and
+    /// never exceeding `max`. With `jitter` enabled the actual wait is picked
+    /// uniformly between zero and the computed delay.
+    Exponential {
+        initial: Duration,
+        max: Duration,
+        jitter: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    backoff: Backoff,
+    max_attempts: Option<u32>,
+    deadline: Option<Duration>,
+}
+
+impl RetryPolicy {
+    pub fn fixed(interval: Duration) -> Self {
+        RetryPolicy {
+            backoff: Backoff::Fixed(interval),
+            max_attempts: None,
+            deadline: None,
+        }
+    }
+
+    pub fn exponential(initial: Duration, max: Duration) -> Self {
+        RetryPolicy {
+            backoff: Backoff::Exponential {
+                initial,
+                max,
+                jitter: true,
+            },
+            max_attempts: None,
+            deadline: None,
+        }
+    }
+
+    pub fn backoff(&self) -> &Backoff {
+        &self.backoff
+    }
+
+    pub fn with_jitter(mut self, enabled: bool) -> Self {
+        if let Backoff::Exponential { ref mut jitter, .. } = self.backoff {
+            *jitter = enabled;
+        }
+        self
+    }
+
+    pub fn max_attempts(&self) -> Option<u32> {
+        self.max_attempts
+    }
+
+    /// Total number of attempts, including the first one.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Give up once this much time has passed since the first attempt was
+    /// started. A retry is never scheduled to fire past the deadline.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Returns the delay to wait before making attempt number `attempt + 1`
+    /// given that `attempt` attempts (1-based) have failed, or `None` if the
+    /// policy says to give up.
+    pub fn next_delay(&self, attempt: u32, elapsed: Duration) -> Option<Duration> {
+        if self.max_attempts.map_or(false, |max| attempt >= max) {
+            return None;
+        }
+
+        let delay = match self.backoff {
+            Backoff::Fixed(interval) => interval,
+            Backoff::Exponential {
+                initial,
+                max,
+                jitter,
+            } => {
+                let delay = exponential_delay(initial, max, attempt);
+                if jitter {
+                    full_jitter(delay)
+                } else {
+                    delay
+                }
+            }
+        };
+
+        match self.deadline {
+            Some(deadline) if elapsed + delay >= deadline => None,
+            _ => Some(delay),
+        }
+    }
+}
+
+fn exponential_delay(initial: Duration, max: Duration, attempt: u32) -> Duration {
+    // attempt is 1-based so the first retry waits `initial`
+    let shift = cmp::min(attempt.saturating_sub(1), 31);
+    initial
+        .checked_mul(1 << shift)
+        .map_or(max, |delay| cmp::min(delay, max))
+}
+
+fn full_jitter(delay: Duration) -> Duration {
+    let millis = delay.as_secs() * 1000 + u64::from(delay.subsec_millis());
+    if millis == 0 {
+        delay
+    } else {
+        Duration::from_millis(rand::thread_rng().gen_range(0, millis + 1))
+    }
+}
+
+type Condition<E> = Box<Fn(&E) -> bool + Send>;
+type Hook<E> = Box<Fn(u32, &E, Duration) + Send>;
+
+enum State<F> {
+    Running(F),
+    Waiting(Delay),
+}
+
+/// Runs the future returned by `action` until it succeeds, the policy gives
+/// up, or the error is rejected by the retry condition. The last error is
+/// returned on failure.
+///
+/// `action` receives the 1-based attempt number.
+pub struct Retry<A, F>
+where
+    F: Future,
+{
+    policy: RetryPolicy,
+    action: A,
+    condition: Option<Condition<F::Error>>,
+    hook: Option<Hook<F::Error>>,
+    attempt: u32,
+    started: Option<Instant>,
+    state: Option<State<F>>,
+}
+
+impl<A, F> Retry<A, F>
+where
+    A: FnMut(u32) -> F,
+    F: Future,
+{
+    pub fn new(policy: RetryPolicy, action: A) -> Self {
+        Retry {
+            policy,
+            action,
+            condition: None,
+            hook: None,
+            attempt: 0,
+            started: None,
+            state: None,
+        }
+    }
+
+    /// Only retry errors for which `condition` returns `true`.
+    pub fn with_condition<C>(mut self, condition: C) -> Self
+    where
+        C: 'static + Fn(&F::Error) -> bool + Send,
+    {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
+    /// Called with the attempt number, its error and the delay before the
+    /// next attempt every time a retry is scheduled.
+    pub fn with_hook<H>(mut self, hook: H) -> Self
+    where
+        H: 'static + Fn(u32, &F::Error, Duration) + Send,
+    {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    fn start_attempt(&mut self) -> State<F> {
+        self.attempt += 1;
+        State::Running((self.action)(self.attempt))
+    }
+}
+
+impl<A, F> Future for Retry<A, F>
+where
+    A: FnMut(u32) -> F,
+    F: Future,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+
+        loop {
+            let next = match self.state {
+                Some(State::Running(ref mut fut)) => match fut.poll() {
+                    Ok(Async::Ready(item)) => return Ok(Async::Ready(item)),
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(err) => {
+                        let retryable = self.condition.as_ref().map_or(true, |c| c(&err));
+                        let elapsed = self
+                            .started
+                            .map_or_else(Duration::default, |s| s.elapsed());
+                        let delay = if retryable {
+                            self.policy.next_delay(self.attempt, elapsed)
+                        } else {
+                            None
+                        };
+
+                        match delay {
+                            Some(delay) => {
+                                if let Some(ref hook) = self.hook {
+                                    hook(self.attempt, &err, delay);
+                                }
+                                Some(State::Waiting(Delay::new(Instant::now() + delay)))
+                            }
+                            None => return Err(err),
+                        }
+                    }
+                },
+                Some(State::Waiting(ref mut delay)) => match delay.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    // A broken timer should not turn a retryable failure
+                    // into a permanent one; just retry immediately.
+                    Ok(Async::Ready(_)) | Err(_) => None,
+                },
+                None => None,
+            };
+
+            let next = match next {
+                Some(state) => state,
+                None => self.start_attempt(),
+            };
+            self.state = Some(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use futures::future;
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn fixed_delay_is_constant() {
+        let policy = RetryPolicy::fixed(Duration::from_secs(5));
+        assert_eq!(
+            Some(Duration::from_secs(5)),
+            policy.next_delay(1, Duration::default())
+        );
+        assert_eq!(
+            Some(Duration::from_secs(5)),
+            policy.next_delay(10, Duration::default())
+        );
+    }
+
+    #[test]
+    fn exponential_delay_doubles_and_caps() {
+        let policy = RetryPolicy::exponential(Duration::from_secs(1), Duration::from_secs(10))
+            .with_jitter(false);
+        let delays: Vec<_> = (1..6)
+            .map(|a| policy.next_delay(a, Duration::default()).unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(4),
+                Duration::from_secs(8),
+                Duration::from_secs(10),
+            ],
+            delays
+        );
+        assert_eq!(
+            Some(Duration::from_secs(10)),
+            policy.next_delay(100, Duration::default())
+        );
+    }
+
+    #[test]
+    fn exponential_jitter_stays_below_delay() {
+        let policy = RetryPolicy::exponential(Duration::from_secs(1), Duration::from_secs(10));
+        for attempt in 1..10 {
+            let delay = policy.next_delay(attempt, Duration::default()).unwrap();
+            let max = exponential_delay(Duration::from_secs(1), Duration::from_secs(10), attempt);
+            assert!(delay <= max);
+        }
+    }
+
+    #[test]
+    fn max_attempts_stops_retrying() {
+        let policy = RetryPolicy::fixed(Duration::from_secs(1)).with_max_attempts(3);
+        assert!(policy.next_delay(2, Duration::default()).is_some());
+        assert!(policy.next_delay(3, Duration::default()).is_none());
+    }
+
+    #[test]
+    fn deadline_stops_retrying() {
+        let policy =
+            RetryPolicy::fixed(Duration::from_secs(5)).with_deadline(Duration::from_secs(10));
+        assert!(policy.next_delay(1, Duration::from_secs(4)).is_some());
+        assert!(policy.next_delay(1, Duration::from_secs(5)).is_none());
+    }
+
+    #[test]
+    fn retry_succeeds_after_failures() {
+        let calls = Rc::new(Cell::new(0));
+        let hooks = Arc::new(AtomicUsize::new(0));
+        let calls_copy = calls.clone();
+        let hooks_copy = hooks.clone();
+        let retry = Retry::new(
+            RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(5),
+            move |attempt| {
+                calls_copy.set(calls_copy.get() + 1);
+                if attempt < 3 {
+                    future::err::<u32, &str>("boom")
+                } else {
+                    future::ok(attempt)
+                }
+            },
+        ).with_hook(move |_, _, _| {
+            hooks_copy.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let result = Runtime::new().unwrap().block_on(retry);
+        assert_eq!(Ok(3), result);
+        assert_eq!(3, calls.get());
+        assert_eq!(2, hooks.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn retry_returns_last_error_when_exhausted() {
+        let retry = Retry::new(
+            RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(2),
+            |attempt| future::err::<(), u32>(attempt),
+        );
+
+        let result = Runtime::new().unwrap().block_on(retry);
+        assert_eq!(Err(2), result);
+    }
+
+    #[test]
+    fn retry_stops_when_condition_rejects() {
+        let retry = Retry::new(RetryPolicy::fixed(Duration::from_millis(1)), |attempt| {
+            future::err::<(), u32>(attempt)
+        }).with_condition(|err| *err < 2);
+
+        let result = Runtime::new().unwrap().block_on(retry);
+        assert_eq!(Err(2), result);
+    }
+}
@@ -0,0 +1,499 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use failure::ResultExt;
+use openssl::x509::X509;
+use serde_json;
+
+use clock::{Clock, SystemClock};
+use error::{ErrorKind, Result};
+use metrics::{Metrics, CERTIFICATES_ISSUED};
+
+/// A certificate issued to a module through the workload API.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IssuedCertificate {
+    serial_number: String,
+    module_id: String,
+    alias: String,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl IssuedCertificate {
+    /// Hex encoded serial number of the certificate.
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    pub fn module_id(&self) -> &str {
+        &self.module_id
+    }
+
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    pub fn issued_at(&self) -> &DateTime<Utc> {
+        &self.issued_at
+    }
+
+    pub fn expires_at(&self) -> &DateTime<Utc> {
+        &self.expires_at
+    }
+
+    pub fn revoked_at(&self) -> Option<&DateTime<Utc>> {
+        self.revoked_at.as_ref()
+    }
+}
+
+/// A certificate to be listed in a certificate revocation list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RevokedCertificate {
+    serial_number: String,
+    revoked_at: DateTime<Utc>,
+}
+
+impl RevokedCertificate {
+    pub fn new(serial_number: String, revoked_at: DateTime<Utc>) -> Self {
+        RevokedCertificate {
+            serial_number,
+            revoked_at,
+        }
+    }
+
+    /// Hex encoded serial number of the certificate.
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    pub fn revoked_at(&self) -> &DateTime<Utc> {
+        &self.revoked_at
+    }
+}
+
+/// Keeps track of the certificates issued to modules so that they can be
+/// revoked before they expire. Certificates are dropped once they expire
+/// since a CRL doesn't need to list them anymore. When created with `load`
+/// every change is persisted to disk.
+#[derive(Clone)]
+pub struct CertificateRegistry {
+    path: Option<PathBuf>,
+    certs: Arc<Mutex<BTreeMap<String, IssuedCertificate>>>,
+    clock: Arc<Clock>,
+    metrics: Metrics,
+}
+
+impl Default for CertificateRegistry {
+    fn default() -> Self {
+        CertificateRegistry {
+            path: None,
+            certs: Arc::new(Mutex::new(BTreeMap::new())),
+            clock: Arc::new(SystemClock),
+            metrics: Metrics::new(),
+        }
+    }
+}
+
+impl CertificateRegistry {
+    pub fn new() -> Self {
+        CertificateRegistry::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let certs = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::CertificateRegistry)?;
+            serde_json::from_reader(file).context(ErrorKind::CertificateRegistry)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(CertificateRegistry {
+            path: Some(path),
+            certs: Arc::new(Mutex::new(certs)),
+            clock: Arc::new(SystemClock),
+            metrics: Metrics::new(),
+        })
+    }
+
+    /// Decides which certificates have expired by the time of `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Counts the certificates recorded for the first time in `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Records a PEM encoded certificate issued to a module. Only the first
+    /// certificate of a chain is recorded. Returns the serial number of the
+    /// certificate.
+    pub fn record(
+        &self,
+        module_id: &str,
+        alias: &str,
+        pem: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<String> {
+        self.record_at(module_id, alias, pem, expires_at, self.clock.now())
+    }
+
+    /// Lists the certificates that haven't expired yet.
+    pub fn list(&self) -> Vec<IssuedCertificate> {
+        let now = self.clock.now();
+        self.lock()
+            .values()
+            .filter(|cert| cert.expires_at > now)
+            .cloned()
+            .collect()
+    }
+
+    /// The certificate issued under `alias` most recently that hasn't expired
+    /// yet.
+    pub fn latest(&self, alias: &str) -> Option<IssuedCertificate> {
+        let now = self.clock.now();
+        self.lock()
+            .values()
+            .filter(|cert| cert.alias == alias && cert.expires_at > now)
+            .max_by_key(|cert| cert.issued_at)
+            .cloned()
+    }
+
+    /// Revokes the certificate with the given hex encoded serial number.
+    /// Revoking a certificate that has already been revoked keeps the
+    /// original revocation time.
+    pub fn revoke(&self, serial_number: &str) -> Result<IssuedCertificate> {
+        self.revoke_at(serial_number, self.clock.now())
+    }
+
+    /// Lists the revoked certificates that haven't expired yet.
+    pub fn revoked(&self) -> Vec<RevokedCertificate> {
+        let now = self.clock.now();
+        self.lock()
+            .values()
+            .filter(|cert| cert.expires_at > now)
+            .filter_map(|cert| {
+                cert.revoked_at.map(|revoked_at| {
+                    RevokedCertificate::new(cert.serial_number.clone(), revoked_at)
+                })
+            }).collect()
+    }
+
+    fn record_at(
+        &self,
+        module_id: &str,
+        alias: &str,
+        pem: &[u8],
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<String> {
+        let serial_number = serial_number(pem)?;
+        let mut certs = self.lock();
+        prune(&mut certs, now);
+        // the HSM hands out an existing certificate again if it is still
+        // valid, so keep the original entry and any revocation it has
+        if let Entry::Vacant(entry) = certs.entry(serial_number.clone()) {
+            entry.insert(IssuedCertificate {
+                serial_number: serial_number.clone(),
+                module_id: module_id.to_string(),
+                alias: alias.to_string(),
+                issued_at: now,
+                expires_at,
+                revoked_at: None,
+            });
+            self.metrics.increment(CERTIFICATES_ISSUED);
+        }
+        self.persist(&certs)?;
+        Ok(serial_number)
+    }
+
+    fn revoke_at(&self, serial_number: &str, now: DateTime<Utc>) -> Result<IssuedCertificate> {
+        let serial_number = normalize_serial_number(serial_number);
+        let mut certs = self.lock();
+        prune(&mut certs, now);
+        let cert = {
+            let cert = certs
+                .get_mut(&serial_number)
+                .ok_or_else(|| ErrorKind::CertificateNotFound(serial_number.clone()))?;
+            if cert.revoked_at.is_none() {
+                cert.revoked_at = Some(now);
+            }
+            cert.clone()
+        };
+        self.persist(&certs)?;
+        Ok(cert)
+    }
+
+    fn persist(&self, certs: &BTreeMap<String, IssuedCertificate>) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::CertificateRegistry)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::CertificateRegistry)?;
+            serde_json::to_writer(file, certs).context(ErrorKind::CertificateRegistry)?;
+            fs::rename(&temp, path).context(ErrorKind::CertificateRegistry)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<BTreeMap<String, IssuedCertificate>> {
+        self.certs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn prune(certs: &mut BTreeMap<String, IssuedCertificate>, now: DateTime<Utc>) {
+    let expired: Vec<String> = certs
+        .values()
+        .filter(|cert| cert.expires_at <= now)
+        .map(|cert| cert.serial_number.clone())
+        .collect();
+    for serial_number in expired {
+        certs.remove(&serial_number);
+    }
+}
+
+/// Returns the hex encoded serial number of the first certificate in a PEM
+/// encoded chain.
+fn serial_number(pem: &[u8]) -> Result<String> {
+    let cert = X509::from_pem(pem).context(ErrorKind::InvalidCertificate)?;
+    let serial_number = cert
+        .serial_number()
+        .to_bn()
+        .and_then(|serial_number| serial_number.to_hex_str())
+        .context(ErrorKind::InvalidCertificate)?;
+    Ok(normalize_serial_number(&serial_number))
+}
+
+fn normalize_serial_number(serial_number: &str) -> String {
+    let serial_number = serial_number.trim().to_uppercase();
+    let serial_number = serial_number.trim_left_matches('0');
+    if serial_number.len() % 2 == 0 {
+        serial_number.to_string()
+    } else {
+        format!("0{}", serial_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use tempdir::TempDir;
+
+    use super::*;
+    use clock::ManualClock;
+
+    const TEST_CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBZDCCAQmgAwIBAgIEGis8TTAKBggqhkjOPQQDAjAPMQ0wCwYDVQQDDAR0ZXN0
+MB4XDTI2MTAxNTAzMjAzNloXDTM2MTAxMjAzMjAzNlowDzENMAsGA1UEAwwEdGVz
+dDBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABN2ftSd0gZVSaoFda34/lmVUR7oY
+JWM0DIrabpHJxKlQJhC3/FH9f1iv1KF92yy2RclBAVo6qOMa74WwavJKz5mjUzBR
+MB0GA1UdDgQWBBSUbs5JtYAp4Vpnrmzrx4CKc1L9ADAfBgNVHSMEGDAWgBSUbs5J
+tYAp4Vpnrmzrx4CKc1L9ADAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kA
+MEYCIQC2Lt9XrJku51lRg2uD5Lh92jKETvVUDVkJEPXZIL5YSAIhAMtCsa5CCBL3
+2h/t9vsE+nViaScjjRaLs6uhTt8HE9tg
+-----END CERTIFICATE-----
+";
+
+    // a version 1 certificate, which has no version field
+    const TEST_V1_CERT: &str = "-----BEGIN CERTIFICATE-----\n\
+                                MIICpDCCAYwCCQCgAJQdOd6dNzANBgkqhkiG9w0BAQsFADAUMRIwEAYDVQQDDAls\
+                                b2NhbGhvc3QwHhcNMTcwMTIwMTkyNTMzWhcNMjcwMTE4MTkyNTMzWjAUMRIwEAYD\
+                                VQQDDAlsb2NhbGhvc3QwggEiMA0GCSqGSIb3DQEBAQUAA4IBDwAwggEKAoIBAQDl\
+                                J3fRNWm05BRAhgUY7cpzaxHZIORomZaOp2Uua5yv+psdkpv35ExLhKGrUIK1AJLZ\
+                                ylnue0ohZfKPFTnoxMHOecnaaXZ9RA25M7XGQvw85ePlGOZKKf3zXw3Ds58GFY6S\
+                                r1SqtDopcDuMmDSg/afYVvGHDjb2Fc4hZFip350AADcmjH5SfWuxgptCY2Jl6ImJ\
+                                oOpxt+imWsJCJEmwZaXw+eZBb87e/9PH4DMXjIUFZebShowAfTh/sinfwRkaLVQ7\
+                                uJI82Ka/icm6Hmr56j7U81gDaF0DhC03ds5lhN7nMp5aqaKeEJiSGdiyyHAescfx\
+                                LO/SMunNc/eG7iAirY7BAgMBAAEwDQYJKoZIhvcNAQELBQADggEBACU7TRogb8sE\
+                                bv+SGzxKSgWKKbw+FNgC4Zi6Fz59t+4jORZkoZ8W87NM946wvkIpxbLKuc4F+7nT\
+                                GHHksyHIiGC3qPpi4vWpqVeNAP+kfQptFoWEOzxD7jQTWIcqYhvssKZGwDk06c/W\
+                                tvVnhZOZW+zzJKXA7mbwJrfp8VekOnN5zPwrOCumDiRX7BnEtMjqFDgdMgs9ohR5\
+                                aFsI7tsqp+dToLKaZqBLTvYwCgCJCxdg3QvMhVD8OxcEIFJtDEwm3h9WFFO3ocab\
+                                CmcMDyXUL354yaZ7RphCBLd06XXdaUU/eV6fOjY6T5ka4ZRJcYDJtjxSG04XPtxs\
+                                wQfrPGGoFhk=\
+                                \n-----END CERTIFICATE-----";
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(1_540_000_000 + secs, 0)
+    }
+
+    #[test]
+    fn serial_number_is_read_from_pem() {
+        assert_eq!("1A2B3C4D", serial_number(TEST_CERT.as_bytes()).unwrap());
+        assert_eq!(
+            "A000941D39DE9D37",
+            serial_number(TEST_V1_CERT.as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn serial_number_of_chain_is_first_cert() {
+        let chain = format!("{}\n{}", TEST_CERT, TEST_V1_CERT);
+        assert_eq!("1A2B3C4D", serial_number(chain.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn serial_number_fails_for_invalid_pem() {
+        for pem in &["", "boo", "-----BEGIN CERTIFICATE-----\nMAA=\n-----END CERTIFICATE-----"] {
+            let err = serial_number(pem.as_bytes()).unwrap_err();
+            match *err.kind() {
+                ErrorKind::InvalidCertificate => (),
+                _ => panic!("unexpected error kind {}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn normalize_serial_number_pads_and_uppercases() {
+        assert_eq!("1A2B3C4D", normalize_serial_number("1a2b3c4d"));
+        assert_eq!("0A2B", normalize_serial_number("00a2b"));
+    }
+
+    #[test]
+    fn revoke_records_revocation_time_once() {
+        let registry = CertificateRegistry::new();
+        let expires_at = Utc::now() + Duration::hours(1);
+        registry
+            .record_at("m1", "m1identity", TEST_CERT.as_bytes(), expires_at, at(0))
+            .unwrap();
+
+        let revoked = registry.revoke_at("1a2b3c4d", at(10)).unwrap();
+        assert_eq!(Some(&at(10)), revoked.revoked_at());
+        let revoked = registry.revoke_at("1A2B3C4D", at(20)).unwrap();
+        assert_eq!(Some(&at(10)), revoked.revoked_at());
+
+        assert_eq!(
+            vec![RevokedCertificate::new("1A2B3C4D".to_string(), at(10))],
+            registry.revoked()
+        );
+    }
+
+    #[test]
+    fn revoke_fails_for_unknown_serial_number() {
+        let registry = CertificateRegistry::new();
+        let err = registry.revoke("1A2B3C4D").unwrap_err();
+        match *err.kind() {
+            ErrorKind::CertificateNotFound(ref serial) => assert_eq!("1A2B3C4D", serial),
+            _ => panic!("unexpected error kind {}", err),
+        }
+    }
+
+    #[test]
+    fn record_keeps_existing_entry() {
+        let registry = CertificateRegistry::new();
+        let expires_at = Utc::now() + Duration::hours(1);
+        registry
+            .record_at("m1", "m1identity", TEST_CERT.as_bytes(), expires_at, at(0))
+            .unwrap();
+        registry.revoke_at("1A2B3C4D", at(10)).unwrap();
+        registry
+            .record_at("m1", "m1identity", TEST_CERT.as_bytes(), expires_at, at(20))
+            .unwrap();
+
+        let certs = registry.list();
+        assert_eq!(1, certs.len());
+        assert_eq!(&at(0), certs[0].issued_at());
+        assert_eq!(Some(&at(10)), certs[0].revoked_at());
+    }
+
+    #[test]
+    fn latest_certificate_of_alias_is_found() {
+        let registry = CertificateRegistry::new().with_clock(ManualClock::new(at(50)));
+        registry
+            .record_at("m1", "m1/I/server", TEST_CERT.as_bytes(), at(100), at(0))
+            .unwrap();
+        registry
+            .record_at("m1", "m1/I/server", TEST_V1_CERT.as_bytes(), at(200), at(10))
+            .unwrap();
+
+        let latest = registry.latest("m1/I/server").unwrap();
+        assert_eq!("A000941D39DE9D37", latest.serial_number());
+        assert!(registry.latest("m1identity").is_none());
+    }
+
+    #[test]
+    fn expired_certificates_are_dropped() {
+        let registry = CertificateRegistry::new();
+        registry
+            .record_at("m1", "m1identity", TEST_CERT.as_bytes(), at(100), at(0))
+            .unwrap();
+        registry.revoke_at("1A2B3C4D", at(10)).unwrap();
+
+        assert!(registry.list().is_empty());
+        assert!(registry.revoked().is_empty());
+        registry
+            .record_at("m2", "m2identity", TEST_V1_CERT.as_bytes(), at(300), at(200))
+            .unwrap();
+        assert_eq!(1, registry.lock().len());
+    }
+
+    #[test]
+    fn certificates_expire_exactly_at_their_expiry() {
+        let clock = ManualClock::new(at(0));
+        let registry = CertificateRegistry::new().with_clock(clock.clone());
+        registry
+            .record("m1", "m1identity", TEST_CERT.as_bytes(), at(100))
+            .unwrap();
+        registry.revoke("1A2B3C4D").unwrap();
+
+        clock.set(at(99));
+        assert_eq!(1, registry.list().len());
+        assert_eq!(1, registry.revoked().len());
+
+        clock.set(at(100));
+        assert!(registry.list().is_empty());
+        assert!(registry.revoked().is_empty());
+    }
+
+    #[test]
+    fn only_new_certificates_are_counted() {
+        let metrics = Metrics::new();
+        let registry = CertificateRegistry::new()
+            .with_clock(ManualClock::new(at(0)))
+            .with_metrics(metrics.clone());
+        registry
+            .record("m1", "m1identity", TEST_CERT.as_bytes(), at(100))
+            .unwrap();
+        registry
+            .record("m1", "m1identity", TEST_CERT.as_bytes(), at(100))
+            .unwrap();
+        assert_eq!(1, metrics.get(CERTIFICATES_ISSUED));
+
+        registry
+            .record("m2", "m2identity", TEST_V1_CERT.as_bytes(), at(100))
+            .unwrap();
+        assert_eq!(2, metrics.get(CERTIFICATES_ISSUED));
+    }
+
+    #[test]
+    fn load_reads_persisted_registry() {
+        let tmp_dir = TempDir::new("revocation").unwrap();
+        let path = tmp_dir.path().join("certificate_registry.json");
+
+        let registry = CertificateRegistry::load(&path).unwrap();
+        let expires_at = Utc::now() + Duration::hours(1);
+        registry
+            .record("m1", "m1server", TEST_CERT.as_bytes(), expires_at)
+            .unwrap();
+        registry.revoke("1A2B3C4D").unwrap();
+
+        let loaded = CertificateRegistry::load(&path).unwrap();
+        let certs = loaded.list();
+        assert_eq!(1, certs.len());
+        assert_eq!("m1", certs[0].module_id());
+        assert_eq!("m1server", certs[0].alias());
+        assert_eq!(1, loaded.revoked().len());
+    }
+}
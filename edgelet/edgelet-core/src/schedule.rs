@@ -0,0 +1,473 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use tokio::timer::Interval;
+
+use error::{Error, ErrorKind, Result};
+use module::ModuleRuntime;
+
+/// This is the frequency with which the scheduler looks for actions that are due.
+const SCHEDULER_FREQUENCY_SECS: u64 = 10;
+
+/// Schedules that cannot be satisfied within this many days (e.g. the 30th of
+/// February) never run.
+const SCHEDULE_HORIZON_DAYS: i64 = 366 * 5;
+
+/// A set of allowed values for one field of a cron expression, stored as a
+/// bit mask.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Field {
+    allowed: u64,
+    restricted: bool,
+}
+
+impl Field {
+    fn parse(field: &str, min: u32, max: u32) -> Option<Self> {
+        let mut allowed = 0_u64;
+        for part in field.split(',') {
+            let mut pieces = part.splitn(2, '/');
+            let range = pieces.next()?;
+            let step = match pieces.next() {
+                Some(step) => step.parse::<u32>().ok().filter(|step| *step > 0)?,
+                None => 1,
+            };
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some(dash) = range.find('-') {
+                (
+                    range[..dash].parse::<u32>().ok()?,
+                    range[dash + 1..].parse::<u32>().ok()?,
+                )
+            } else {
+                let start = range.parse::<u32>().ok()?;
+                // "5/15" means every 15 starting at 5
+                if step > 1 {
+                    (start, max)
+                } else {
+                    (start, start)
+                }
+            };
+            if start < min || end > max || start > end {
+                return None;
+            }
+            let mut value = start;
+            while value <= end {
+                allowed |= 1 << value;
+                value += step;
+            }
+        }
+
+        Some(Field {
+            allowed,
+            restricted: !field.starts_with('*'),
+        })
+    }
+
+    fn contains(self, value: u32) -> bool {
+        self.allowed & (1 << value) != 0
+    }
+}
+
+/// A standard five field cron expression (minute, hour, day of month, month
+/// and day of week) evaluated in UTC. The `@hourly`, `@daily`, `@weekly`,
+/// `@monthly` and `@yearly` shorthands are also accepted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+}
+
+impl CronSchedule {
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Returns the first time strictly after `after` at which the schedule
+    /// fires.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(SCHEDULE_HORIZON_DAYS);
+
+        let mut next = start;
+        while next < limit {
+            if !self.months.contains(next.month()) {
+                next = if next.month() == 12 {
+                    Utc.ymd(next.year() + 1, 1, 1).and_hms(0, 0, 0)
+                } else {
+                    Utc.ymd(next.year(), next.month() + 1, 1).and_hms(0, 0, 0)
+                };
+            } else if !self.day_matches(next) {
+                next = next.date().and_hms(0, 0, 0) + Duration::days(1);
+            } else if !self.hours.contains(next.hour()) {
+                next = next.with_minute(0)? + Duration::hours(1);
+            } else if !self.minutes.contains(next.minute()) {
+                next = next + Duration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+
+        None
+    }
+
+    // Like cron, when both the day of month and the day of week are
+    // restricted a day matching either of them is accepted.
+    fn day_matches(&self, time: DateTime<Utc>) -> bool {
+        let dom = self.days_of_month.contains(time.day());
+        let dow = self
+            .days_of_week
+            .contains(time.weekday().num_days_from_sunday());
+        match (self.days_of_month.restricted, self.days_of_week.restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let invalid = || Error::from(ErrorKind::InvalidSchedule(s.to_string()));
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid());
+        }
+
+        let mut days_of_week = Field::parse(fields[4], 0, 7).ok_or_else(invalid)?;
+        // both 0 and 7 are Sunday
+        if days_of_week.contains(7) {
+            days_of_week.allowed = (days_of_week.allowed & !(1 << 7)) | 1;
+        }
+
+        Ok(CronSchedule {
+            expression: s.trim().to_string(),
+            minutes: Field::parse(fields[0], 0, 59).ok_or_else(invalid)?,
+            hours: Field::parse(fields[1], 0, 23).ok_or_else(invalid)?,
+            days_of_month: Field::parse(fields[2], 1, 31).ok_or_else(invalid)?,
+            months: Field::parse(fields[3], 1, 12).ok_or_else(invalid)?,
+            days_of_week,
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduledAction {
+    Start,
+    Stop,
+    Restart,
+}
+
+impl fmt::Display for ScheduledAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match *self {
+            ScheduledAction::Start => "start",
+            ScheduledAction::Stop => "stop",
+            ScheduledAction::Restart => "restart",
+        };
+        write!(f, "{}", action)
+    }
+}
+
+/// An action to perform on a module whenever a cron expression fires.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ScheduleSpec {
+    module: String,
+    action: ScheduledAction,
+    schedule: String,
+}
+
+impl ScheduleSpec {
+    pub fn new(module: String, action: ScheduledAction, schedule: String) -> Self {
+        ScheduleSpec {
+            module,
+            action,
+            schedule,
+        }
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn action(&self) -> ScheduledAction {
+        self.action
+    }
+
+    pub fn schedule(&self) -> &str {
+        &self.schedule
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScheduleEntry {
+    spec: ScheduleSpec,
+    cron: CronSchedule,
+    next_run: Option<DateTime<Utc>>,
+}
+
+impl ScheduleEntry {
+    pub fn spec(&self) -> &ScheduleSpec {
+        &self.spec
+    }
+
+    pub fn next_run(&self) -> Option<&DateTime<Utc>> {
+        self.next_run.as_ref()
+    }
+}
+
+/// Starts, stops or restarts modules according to a set of cron schedules.
+/// The management API lists the next run times from a clone while the
+/// original is running.
+#[derive(Clone)]
+pub struct Scheduler {
+    entries: Arc<Mutex<Vec<ScheduleEntry>>>,
+}
+
+impl Scheduler {
+    pub fn new(specs: &[ScheduleSpec]) -> Result<Self> {
+        Self::new_at(specs, Utc::now())
+    }
+
+    fn new_at(specs: &[ScheduleSpec], now: DateTime<Utc>) -> Result<Self> {
+        let entries = specs
+            .iter()
+            .map(|spec| -> Result<ScheduleEntry> {
+                ensure_not_empty!(spec.module());
+                let cron = spec.schedule().parse::<CronSchedule>()?;
+                let next_run = cron.next_after(now);
+                Ok(ScheduleEntry {
+                    spec: spec.clone(),
+                    cron,
+                    next_run,
+                })
+            }).collect::<Result<Vec<_>>>()?;
+
+        Ok(Scheduler {
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    pub fn entries(&self) -> Vec<ScheduleEntry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    // Returns the actions that are due at `now` and moves their entries on
+    // to the next run.
+    fn take_due(&self, now: DateTime<Utc>) -> Vec<ScheduleSpec> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .iter_mut()
+            .filter(|entry| entry.next_run.map_or(false, |next_run| next_run <= now))
+            .map(|entry| {
+                entry.next_run = entry.cron.next_after(now);
+                entry.spec.clone()
+            }).collect()
+    }
+
+    pub fn run_until<M, F>(
+        self,
+        runtime: M,
+        shutdown_signal: F,
+    ) -> impl Future<Item = (), Error = Error>
+    where
+        M: 'static + ModuleRuntime + Clone,
+        M::Error: Into<Error>,
+        F: Future<Item = (), Error = ()> + 'static,
+    {
+        info!(
+            "Starting scheduler with {} schedule(s)...",
+            self.entries().len()
+        );
+        let scheduler = Interval::new(
+            Instant::now(),
+            StdDuration::from_secs(SCHEDULER_FREQUENCY_SECS),
+        ).map_err(Error::from)
+        .for_each(move |_| {
+            let runtime = runtime.clone();
+            let actions = self
+                .take_due(Utc::now())
+                .into_iter()
+                .map(move |spec| run_action(&runtime, &spec));
+            future::join_all(actions).map(|_| ())
+        });
+
+        // Swallow any errors from shutdown_signal
+        let shutdown_signal = shutdown_signal.then(|_| Ok(()));
+
+        shutdown_signal
+            .select(scheduler)
+            .then(|result| match result {
+                Ok(((), _)) => Ok(()),
+                Err((e, _)) => Err(e),
+            })
+    }
+}
+
+// Errors are logged rather than returned so that one failing module doesn't
+// stop the other schedules.
+fn run_action<M>(runtime: &M, spec: &ScheduleSpec) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime,
+    M::Error: Into<Error>,
+{
+    let name = spec.module().to_string();
+    let action = spec.action();
+    info!("Running scheduled {} of module {}", action, name);
+
+    let result = match action {
+        ScheduledAction::Start => Either::A(runtime.start(&name)),
+        ScheduledAction::Stop => Either::B(Either::A(runtime.stop(&name, None))),
+        ScheduledAction::Restart => Either::B(Either::B(runtime.restart(&name))),
+    };
+    result.then(move |result| {
+        if let Err(err) = result {
+            let err: Error = err.into();
+            warn!("Scheduled {} of module {} failed: {}", action, name, err);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    fn next(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        expression
+            .parse::<CronSchedule>()
+            .unwrap()
+            .next_after(after)
+    }
+
+    #[test]
+    fn next_run_of_simple_schedules() {
+        let now = Utc.ymd(2018, 10, 15).and_hms(13, 27, 42);
+        assert_eq!(Some(at(2018, 10, 15, 13, 28)), next("* * * * *", now));
+        assert_eq!(Some(at(2018, 10, 15, 13, 30)), next("*/15 * * * *", now));
+        assert_eq!(Some(at(2018, 10, 16, 2, 0)), next("0 2 * * *", now));
+        assert_eq!(Some(at(2018, 10, 16, 0, 0)), next("@daily", now));
+        assert_eq!(Some(at(2018, 11, 1, 0, 0)), next("@monthly", now));
+        assert_eq!(Some(at(2019, 1, 1, 0, 0)), next("@yearly", now));
+        assert_eq!(Some(at(2018, 10, 15, 14, 5)), next("5-10 14 * * *", now));
+    }
+
+    #[test]
+    fn next_run_is_strictly_after() {
+        let now = at(2018, 10, 15, 2, 0);
+        assert_eq!(Some(at(2018, 10, 16, 2, 0)), next("0 2 * * *", now));
+    }
+
+    #[test]
+    fn days_of_week() {
+        // 2018-10-15 is a Monday
+        let now = at(2018, 10, 15, 12, 0);
+        assert_eq!(Some(at(2018, 10, 21, 0, 0)), next("0 0 * * 0", now));
+        assert_eq!(Some(at(2018, 10, 21, 0, 0)), next("0 0 * * 7", now));
+        assert_eq!(Some(at(2018, 10, 19, 3, 0)), next("0 3 * * 5", now));
+        // either the 1st of the month or a Wednesday
+        assert_eq!(Some(at(2018, 10, 17, 0, 0)), next("0 0 1 * 3", now));
+    }
+
+    #[test]
+    fn leap_day() {
+        let now = at(2018, 10, 15, 12, 0);
+        assert_eq!(Some(at(2020, 2, 29, 0, 0)), next("0 0 29 2 *", now));
+        assert_eq!(None, next("0 0 30 2 *", now));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in &[
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+            "@often",
+        ] {
+            match expression.parse::<CronSchedule>() {
+                Ok(_) => panic!("expected {:?} to be rejected", expression),
+                Err(err) => match *err.kind() {
+                    ErrorKind::InvalidSchedule(ref s) => assert_eq!(*expression, s.as_str()),
+                    _ => panic!("unexpected error kind {}", err),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn take_due_advances_entries() {
+        let spec = ScheduleSpec::new(
+            "batch".to_string(),
+            ScheduledAction::Start,
+            "0 2 * * *".to_string(),
+        );
+        let scheduler = Scheduler::new_at(&[spec.clone()], at(2018, 10, 15, 12, 0)).unwrap();
+        assert_eq!(
+            Some(&at(2018, 10, 16, 2, 0)),
+            scheduler.entries()[0].next_run()
+        );
+
+        assert!(scheduler.take_due(at(2018, 10, 16, 1, 59)).is_empty());
+
+        let due = scheduler.take_due(Utc.ymd(2018, 10, 16).and_hms(2, 0, 5));
+        assert_eq!(vec![spec], due);
+        assert_eq!(
+            Some(&at(2018, 10, 17, 2, 0)),
+            scheduler.entries()[0].next_run()
+        );
+    }
+
+    #[test]
+    fn new_rejects_invalid_schedule() {
+        let spec = ScheduleSpec::new(
+            "batch".to_string(),
+            ScheduledAction::Stop,
+            "every night".to_string(),
+        );
+        assert!(Scheduler::new(&[spec]).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use failure::ResultExt;
+use serde_json;
+
+use error::{ErrorKind, Result};
+
+/// The number of bytes a sequence number takes up in front of the plaintext.
+pub const SEQUENCE_NUMBER_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Counter {
+    /// The last sequence number handed out to an encrypt operation.
+    issued: u64,
+    /// The highest sequence number a decrypt operation has accepted.
+    accepted: u64,
+}
+
+/// Hands out sequence numbers to the encrypt operations of modules, one
+/// counter per module and sequence key, and checks them on decrypt, so that
+/// modules can detect replayed messages without keeping a durable counter of
+/// their own.
+///
+/// A decrypt operation accepts a sequence number only if it is higher than
+/// any it has accepted before for the same counter, so messages have to be
+/// decrypted in the order they were encrypted. When created with `load`,
+/// every change is persisted to disk before the number is handed out or
+/// accepted, so that numbers are never handed out or accepted twice across
+/// restarts. The counters of a module are kept when it is removed, so that
+/// a module created again under the same ID carries on from them.
+#[derive(Clone, Default)]
+pub struct SequenceNumbers {
+    path: Option<PathBuf>,
+    counters: Arc<Mutex<BTreeMap<String, Counter>>>,
+}
+
+impl SequenceNumbers {
+    pub fn new() -> Self {
+        SequenceNumbers::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let counters = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::SequenceNumbers)?;
+            serde_json::from_reader(file).context(ErrorKind::SequenceNumbers)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(SequenceNumbers {
+            path: Some(path),
+            counters: Arc::new(Mutex::new(counters)),
+        })
+    }
+
+    /// Hands out the next sequence number of the counter `key` of module
+    /// `module_id`. The first number is 1.
+    pub fn next(&self, module_id: &str, key: &str) -> Result<u64> {
+        let mut counters = self.lock();
+        let mut counter = counters
+            .get(&counter_id(module_id, key))
+            .cloned()
+            .unwrap_or_default();
+        counter.issued += 1;
+        counters.insert(counter_id(module_id, key), counter);
+        self.persist(&counters)?;
+        Ok(counter.issued)
+    }
+
+    /// Accepts `sequence_number` for the counter `key` of module
+    /// `module_id`, unless it isn't higher than the last one accepted, in
+    /// which case the message it came with is a replay and `false` is
+    /// returned.
+    pub fn accept(&self, module_id: &str, key: &str, sequence_number: u64) -> Result<bool> {
+        let mut counters = self.lock();
+        let mut counter = counters
+            .get(&counter_id(module_id, key))
+            .cloned()
+            .unwrap_or_default();
+        if sequence_number <= counter.accepted {
+            return Ok(false);
+        }
+        counter.accepted = sequence_number;
+        counters.insert(counter_id(module_id, key), counter);
+        self.persist(&counters)?;
+        Ok(true)
+    }
+
+    fn persist(&self, counters: &BTreeMap<String, Counter>) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::SequenceNumbers)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::SequenceNumbers)?;
+            serde_json::to_writer(file, counters).context(ErrorKind::SequenceNumbers)?;
+            fs::rename(&temp, path).context(ErrorKind::SequenceNumbers)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<BTreeMap<String, Counter>> {
+        self.counters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Module IDs can't contain a `/`, so the counters of different modules
+// don't collide.
+fn counter_id(module_id: &str, key: &str) -> String {
+    format!("{}/{}", module_id, key)
+}
+
+/// Puts `sequence_number` in front of `plaintext`, big endian, so that it is
+/// encrypted along with it.
+pub fn prepend_sequence_number(sequence_number: u64, plaintext: &[u8]) -> Vec<u8> {
+    let mut sequenced = Vec::with_capacity(SEQUENCE_NUMBER_LEN + plaintext.len());
+    for i in (0..SEQUENCE_NUMBER_LEN).rev() {
+        sequenced.push((sequence_number >> (i * 8)) as u8);
+    }
+    sequenced.extend_from_slice(plaintext);
+    sequenced
+}
+
+/// Splits the sequence number `prepend_sequence_number` put in front of a
+/// plaintext off it. Returns `None` if the plaintext is too short to have
+/// one.
+pub fn split_sequence_number(sequenced: &[u8]) -> Option<(u64, &[u8])> {
+    if sequenced.len() < SEQUENCE_NUMBER_LEN {
+        return None;
+    }
+    let (number, plaintext) = sequenced.split_at(SEQUENCE_NUMBER_LEN);
+    let sequence_number = number
+        .iter()
+        .fold(0, |number, byte| (number << 8) | u64::from(*byte));
+    Some((sequence_number, plaintext))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn numbers_increase_per_module_and_key() {
+        let numbers = SequenceNumbers::new();
+        assert_eq!(1, numbers.next("m1", "telemetry").unwrap());
+        assert_eq!(2, numbers.next("m1", "telemetry").unwrap());
+        assert_eq!(1, numbers.next("m1", "commands").unwrap());
+        assert_eq!(1, numbers.next("m2", "telemetry").unwrap());
+        assert_eq!(3, numbers.clone().next("m1", "telemetry").unwrap());
+    }
+
+    #[test]
+    fn replayed_numbers_are_not_accepted() {
+        let numbers = SequenceNumbers::new();
+        assert!(numbers.accept("m1", "telemetry", 1).unwrap());
+        assert!(numbers.accept("m1", "telemetry", 3).unwrap());
+        assert!(!numbers.accept("m1", "telemetry", 3).unwrap());
+        assert!(!numbers.accept("m1", "telemetry", 2).unwrap());
+        assert!(numbers.accept("m2", "telemetry", 1).unwrap());
+        assert!(numbers.accept("m1", "commands", 1).unwrap());
+    }
+
+    #[test]
+    fn counters_survive_restarts() {
+        let tmp_dir = TempDir::new("sequence").unwrap();
+        let path = tmp_dir.path().join("sequence-numbers.json");
+
+        let numbers = SequenceNumbers::load(&path).unwrap();
+        assert_eq!(1, numbers.next("m1", "telemetry").unwrap());
+        assert!(numbers.accept("m1", "telemetry", 1).unwrap());
+
+        let numbers = SequenceNumbers::load(&path).unwrap();
+        assert_eq!(2, numbers.next("m1", "telemetry").unwrap());
+        assert!(!numbers.accept("m1", "telemetry", 1).unwrap());
+    }
+
+    #[test]
+    fn sequence_number_round_trips_with_the_plaintext() {
+        let sequenced = prepend_sequence_number(0x0102_0304_0506_0708, b"hello");
+        assert_eq!(&[1, 2, 3, 4, 5, 6, 7, 8], &sequenced[..8]);
+        assert_eq!(
+            Some((0x0102_0304_0506_0708, &b"hello"[..])),
+            split_sequence_number(&sequenced)
+        );
+        assert_eq!(None, split_sequence_number(b"short"));
+    }
+}
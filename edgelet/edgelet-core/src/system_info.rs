@@ -0,0 +1,396 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Details of the host the daemon runs on. Anything the host doesn't let on
+/// is left out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostInfo {
+    os_name: Option<String>,
+    kernel_version: Option<String>,
+    total_memory: Option<u64>,
+    total_disk: Option<u64>,
+    hsm_version: Option<String>,
+    offline: bool,
+}
+
+impl HostInfo {
+    pub fn new() -> Self {
+        HostInfo::default()
+    }
+
+    pub fn with_os_name(mut self, os_name: String) -> Self {
+        self.os_name = Some(os_name);
+        self
+    }
+
+    pub fn with_kernel_version(mut self, kernel_version: String) -> Self {
+        self.kernel_version = Some(kernel_version);
+        self
+    }
+
+    pub fn with_total_memory(mut self, total_memory: u64) -> Self {
+        self.total_memory = Some(total_memory);
+        self
+    }
+
+    pub fn with_total_disk(mut self, total_disk: u64) -> Self {
+        self.total_disk = Some(total_disk);
+        self
+    }
+
+    pub fn with_hsm_version(mut self, hsm_version: String) -> Self {
+        self.hsm_version = Some(hsm_version);
+        self
+    }
+
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// The name and version of the operating system, such as
+    /// "Ubuntu 18.04.1 LTS".
+    pub fn os_name(&self) -> Option<&str> {
+        self.os_name.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn kernel_version(&self) -> Option<&str> {
+        self.kernel_version.as_ref().map(AsRef::as_ref)
+    }
+
+    /// The physical memory of the host, in bytes.
+    pub fn total_memory(&self) -> Option<u64> {
+        self.total_memory
+    }
+
+    /// The size of the volume holding the home directory of the daemon, in
+    /// bytes.
+    pub fn total_disk(&self) -> Option<u64> {
+        self.total_disk
+    }
+
+    /// The version of libiothsm the daemon is linked against.
+    pub fn hsm_version(&self) -> Option<&str> {
+        self.hsm_version.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Whether the device is set up to run without IoT Hub.
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+}
+
+pub trait SystemInfoProvider {
+    fn host_info(&self) -> HostInfo;
+}
+
+/// Gathers the details of the host it runs on. They are gathered anew on
+/// each call, since they are only asked for on demand.
+#[derive(Clone, Debug)]
+pub struct HostSystemInfo {
+    homedir: PathBuf,
+    hsm_version: Option<String>,
+    offline: bool,
+}
+
+impl HostSystemInfo {
+    pub fn new<P: Into<PathBuf>>(homedir: P) -> Self {
+        HostSystemInfo {
+            homedir: homedir.into(),
+            hsm_version: None,
+            offline: false,
+        }
+    }
+
+    pub fn with_hsm_version(mut self, hsm_version: String) -> Self {
+        self.hsm_version = Some(hsm_version);
+        self
+    }
+
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+}
+
+impl SystemInfoProvider for HostSystemInfo {
+    fn host_info(&self) -> HostInfo {
+        let info = platform::host_info(&self.homedir).with_offline(self.offline);
+        match self.hsm_version {
+            Some(ref hsm_version) => info.with_hsm_version(hsm_version.clone()),
+            None => info,
+        }
+    }
+}
+
+/// The commit the daemon was built from, if the build recorded it.
+pub fn build_commit() -> Option<&'static str> {
+    option_env!("BUILD_SOURCEVERSION")
+}
+
+/// The version of the compiler the daemon was built with.
+pub fn rustc_version() -> Option<&'static str> {
+    option_env!("EDGELET_RUSTC_VERSION")
+}
+
+/// The size and the free space, in bytes, of the volume holding `path`.
+pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    platform::disk_space(path)
+}
+
+/// The size of the physical memory of the host and how much of it is
+/// available to new processes, in bytes.
+pub fn memory_space() -> Option<(u64, u64)> {
+    platform::memory_space()
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::fs;
+
+    use super::*;
+
+    pub fn host_info(homedir: &Path) -> HostInfo {
+        let os_name = fs::read_to_string("/etc/os-release")
+            .ok()
+            .and_then(|os_release| os_release_name(&os_release))
+            .or_else(|| run("uname", &["-s"]).map(|name| name.trim().to_string()));
+        let kernel_version = run("uname", &["-r"]).map(|version| version.trim().to_string());
+        let total_memory = fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| meminfo_total(&meminfo));
+        let total_disk = homedir
+            .to_str()
+            .and_then(|homedir| run("df", &["-Pk", homedir]))
+            .and_then(|df| df_total(&df));
+
+        let mut info = HostInfo::new();
+        if let Some(os_name) = os_name {
+            info = info.with_os_name(os_name);
+        }
+        if let Some(kernel_version) = kernel_version {
+            info = info.with_kernel_version(kernel_version);
+        }
+        if let Some(total_memory) = total_memory {
+            info = info.with_total_memory(total_memory);
+        }
+        if let Some(total_disk) = total_disk {
+            info = info.with_total_disk(total_disk);
+        }
+        info
+    }
+
+    pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+        let df = path.to_str().and_then(|path| run("df", &["-Pk", path]))?;
+        Some((df_total(&df)?, df_available(&df)?))
+    }
+
+    // PRETTY_NAME="Ubuntu 18.04.1 LTS"
+    pub fn os_release_name(os_release: &str) -> Option<String> {
+        os_release
+            .lines()
+            .find(|line| line.starts_with("PRETTY_NAME="))
+            .map(|line| {
+                line["PRETTY_NAME=".len()..]
+                    .trim()
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .to_string()
+            })
+    }
+
+    // MemTotal:        8167848 kB
+    pub fn memory_space() -> Option<(u64, u64)> {
+        let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+        Some((meminfo_total(&meminfo)?, meminfo_available(&meminfo)?))
+    }
+
+    pub fn meminfo_total(meminfo: &str) -> Option<u64> {
+        meminfo_value(meminfo, "MemTotal:")
+    }
+
+    pub fn meminfo_available(meminfo: &str) -> Option<u64> {
+        meminfo_value(meminfo, "MemAvailable:")
+    }
+
+    // MemTotal:        8167848 kB
+    fn meminfo_value(meminfo: &str, key: &str) -> Option<u64> {
+        meminfo
+            .lines()
+            .find(|line| line.starts_with(key))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kb| kb.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    }
+
+    // Filesystem     1024-blocks      Used Available Capacity Mounted on
+    // /dev/sda1        30308240  12584496  17707360      42% /
+    pub fn df_total(df: &str) -> Option<u64> {
+        df.lines()
+            .nth(1)
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kb| kb.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    }
+
+    pub fn df_available(df: &str) -> Option<u64> {
+        df.lines()
+            .nth(1)
+            .and_then(|line| line.split_whitespace().nth(3))
+            .and_then(|kb| kb.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+
+    pub fn host_info(homedir: &Path) -> HostInfo {
+        let os = run(
+            "wmic",
+            &["os", "get", "Caption,Version,TotalVisibleMemorySize", "/value"],
+        ).map(|os| wmic_values(&os))
+        .unwrap_or_default();
+        let disk = logical_disk(homedir, "Size").unwrap_or_default();
+
+        let mut info = HostInfo::new();
+        for (key, value) in os.into_iter().chain(disk) {
+            info = match key.as_ref() {
+                "Caption" => info.with_os_name(value),
+                "Version" => info.with_kernel_version(value),
+                // reported in kilobytes
+                "TotalVisibleMemorySize" => match value.parse::<u64>() {
+                    Ok(kb) => info.with_total_memory(kb * 1024),
+                    Err(_) => info,
+                },
+                "Size" => match value.parse::<u64>() {
+                    Ok(bytes) => info.with_total_disk(bytes),
+                    Err(_) => info,
+                },
+                _ => info,
+            };
+        }
+        info
+    }
+
+    // both reported in kilobytes
+    pub fn memory_space() -> Option<(u64, u64)> {
+        let os = run(
+            "wmic",
+            &["os", "get", "TotalVisibleMemorySize,FreePhysicalMemory", "/value"],
+        ).map(|os| wmic_values(&os))?;
+        let value = |name: &str| {
+            os.iter()
+                .find(|&&(ref key, _)| key == name)
+                .and_then(|&(_, ref value)| value.parse::<u64>().ok())
+                .map(|kb| kb * 1024)
+        };
+        Some((value("TotalVisibleMemorySize")?, value("FreePhysicalMemory")?))
+    }
+
+    pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+        let disk = logical_disk(path, "Size,FreeSpace")?;
+        let value = |name: &str| {
+            disk.iter()
+                .find(|&&(ref key, _)| key == name)
+                .and_then(|&(_, ref value)| value.parse::<u64>().ok())
+        };
+        Some((value("Size")?, value("FreeSpace")?))
+    }
+
+    // The properties of the drive `path` is on.
+    fn logical_disk(path: &Path, properties: &str) -> Option<Vec<(String, String)>> {
+        let drive = path
+            .to_str()
+            .and_then(|path| path.get(..2))
+            .filter(|drive| drive.ends_with(':'))
+            .unwrap_or("C:");
+        let filter = format!("DeviceID='{}'", drive);
+        run(
+            "wmic",
+            &["logicaldisk", "where", &filter, "get", properties, "/value"],
+        ).map(|disk| wmic_values(&disk))
+    }
+
+    // Caption=Microsoft Windows 10 Enterprise
+    pub fn wmic_values(output: &str) -> Vec<(String, String)> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.trim().splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if !value.is_empty() => {
+                        Some((key.to_string(), value.to_string()))
+                    }
+                    _ => None,
+                }
+            }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::platform::*;
+    use super::*;
+
+    #[test]
+    fn hsm_version_is_added_to_the_host_info() {
+        let info = HostSystemInfo::new(".")
+            .with_hsm_version("1.0.2".to_string())
+            .host_info();
+        assert_eq!(Some("1.0.2"), info.hsm_version());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn host_details_are_parsed() {
+        let os_release = "NAME=\"Ubuntu\"\nVERSION=\"18.04.1 LTS (Bionic Beaver)\"\n\
+                          PRETTY_NAME=\"Ubuntu 18.04.1 LTS\"\nVERSION_ID=\"18.04\"\n";
+        assert_eq!(
+            Some("Ubuntu 18.04.1 LTS".to_string()),
+            os_release_name(os_release)
+        );
+        assert_eq!(None, os_release_name("NAME=Raspbian\n"));
+
+        let meminfo = "MemTotal:        8167848 kB\nMemFree:          512000 kB\n\
+                       MemAvailable:    2041962 kB\n";
+        assert_eq!(Some(8_167_848 * 1024), meminfo_total(meminfo));
+        assert_eq!(Some(2_041_962 * 1024), meminfo_available(meminfo));
+        assert_eq!(None, meminfo_available("MemTotal:        8167848 kB\n"));
+
+        let df = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                  /dev/sda1        30308240  12584496  17707360      42% /\n";
+        assert_eq!(Some(30_308_240 * 1024), df_total(df));
+        assert_eq!(Some(17_707_360 * 1024), df_available(df));
+        assert_eq!(None, df_total("df: /nowhere: No such file or directory\n"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn host_details_are_parsed() {
+        let output = "\r\n\r\nCaption=Microsoft Windows 10 Enterprise\r\n\
+                      TotalVisibleMemorySize=8167848\r\nVersion=10.0.17763\r\nSize=\r\n";
+        assert_eq!(
+            vec![
+                (
+                    "Caption".to_string(),
+                    "Microsoft Windows 10 Enterprise".to_string()
+                ),
+                ("TotalVisibleMemorySize".to_string(), "8167848".to_string()),
+                ("Version".to_string(), "10.0.17763".to_string()),
+            ],
+            wmic_values(output)
+        );
+    }
+}
@@ -0,0 +1,519 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Validation of the SAS tokens leaf devices and their modules authenticate
+//! to the edge hub with.
+//!
+//! A token is signed with one of the symmetric keys of the identity it is
+//! issued for, so the edge hub can only check it with those keys, which live
+//! in IoT Hub. The keys are fetched once and cached, so tokens are validated
+//! on the device, and keys fetched before are still used when IoT Hub can't
+//! be reached.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration as StdDuration;
+
+use base64;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use consistenttime::ct_u8_slice_eq;
+use failure::Fail;
+use futures::{future, Future};
+use serde_json;
+use url::form_urlencoded;
+use url::percent_encoding::percent_decode;
+
+use clock::{Clock, SystemClock};
+use crypto::{MemoryKey, Sign, Signature, SignatureAlgorithm};
+use error::{Error, ErrorKind, Result};
+
+/// How long keys are used before they are fetched again when no other time
+/// is configured.
+pub const DEFAULT_KEY_CACHE_SECS: u64 = 3600;
+
+const SAS_PREFIX: &str = "SharedAccessSignature ";
+
+/// Gets the symmetric keys of a device, or of one of its modules, in the
+/// scope of the edge device.
+pub trait IdentityKeySource {
+    type Error: Fail;
+    type Future: Future<Item = Option<Vec<MemoryKey>>, Error = Self::Error> + Send;
+
+    fn get_keys(&self, device_id: &str, module_id: Option<&str>) -> Self::Future;
+}
+
+/// The outcome of validating a token.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TokenStatus {
+    Valid,
+    Expired,
+    /// The token isn't signed with any of the keys of its identity.
+    InvalidSignature,
+    /// The identity the token is issued for isn't in the scope of the edge
+    /// device, or doesn't authenticate with symmetric keys.
+    UnknownIdentity,
+    /// The keys of the identity couldn't be fetched and none were cached.
+    Unverifiable,
+    Malformed,
+}
+
+impl fmt::Display for TokenStatus {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            serde_json::to_string(self)
+                .map(|s| s.trim_matches('"').to_string())
+                .map_err(|_| fmt::Error)?
+        )
+    }
+}
+
+/// The device, and possibly module, a token is issued for.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TokenIdentity {
+    device_id: String,
+    module_id: Option<String>,
+}
+
+impl TokenIdentity {
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    pub fn module_id(&self) -> Option<&str> {
+        self.module_id.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// A parsed SAS token.
+#[derive(Clone, Debug)]
+pub struct SasToken {
+    /// The resource as it appears in the token, which is what is signed.
+    resource: String,
+    signature: Vec<u8>,
+    expiry: String,
+    expires_at: DateTime<Utc>,
+    identity: TokenIdentity,
+}
+
+impl SasToken {
+    pub fn parse(token: &str) -> Result<Self> {
+        let token = token.trim();
+        let token = if token.starts_with(SAS_PREFIX) {
+            &token[SAS_PREFIX.len()..]
+        } else {
+            token
+        };
+
+        let mut resource = None;
+        let mut signature = None;
+        let mut expiry = None;
+        for pair in token.split('&') {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("");
+            match key {
+                "sr" => resource = Some(value.to_string()),
+                "sig" => signature = Some(decode(value)),
+                "se" => expiry = Some(value.to_string()),
+                _ => (),
+            }
+        }
+
+        let resource = resource.ok_or_else(|| Error::from(ErrorKind::MalformedToken))?;
+        let signature = signature
+            .and_then(|signature| base64::decode(&signature).ok())
+            .ok_or_else(|| Error::from(ErrorKind::MalformedToken))?;
+        let expiry = expiry.ok_or_else(|| Error::from(ErrorKind::MalformedToken))?;
+        let expires_at = expiry
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+            .ok_or_else(|| Error::from(ErrorKind::MalformedToken))?;
+        let identity = identity(&decode(&resource))?;
+
+        Ok(SasToken {
+            resource,
+            signature,
+            expiry,
+            expires_at,
+            identity,
+        })
+    }
+
+    pub fn identity(&self) -> &TokenIdentity {
+        &self.identity
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    /// Whether the token is signed with one of `keys`.
+    pub fn is_signed_with(&self, keys: &[MemoryKey]) -> bool {
+        let data = format!("{}\n{}", self.resource, self.expiry);
+        keys.iter().any(|key| {
+            key.sign(SignatureAlgorithm::HMACSHA256, data.as_bytes())
+                .map(|signature| {
+                    let signature = signature.as_bytes();
+                    signature.len() == self.signature.len()
+                        && ct_u8_slice_eq(signature, &self.signature)
+                }).unwrap_or(false)
+        })
+    }
+}
+
+fn decode(value: &str) -> String {
+    form_urlencoded::parse(format!("v={}", value).as_bytes())
+        .next()
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default()
+}
+
+// The resource of a token is `<hub>/devices/<device>` or
+// `<hub>/devices/<device>/modules/<module>`, with the identifiers percent
+// encoded.
+fn identity(resource: &str) -> Result<TokenIdentity> {
+    let segments: Vec<String> = resource
+        .split('/')
+        .map(|segment| percent_decode(segment.as_bytes()).decode_utf8_lossy().into_owned())
+        .collect();
+    let is_named = |i: usize, name: &str| segments.get(i).map_or(false, |s| s == name);
+    let is_id = |i: usize| segments.get(i).map_or(false, |s| !s.is_empty());
+
+    if !is_named(1, "devices") || !is_id(2) {
+        return Err(Error::from(ErrorKind::MalformedToken));
+    }
+    let module_id = match segments.len() {
+        3 => None,
+        5 if is_named(3, "modules") && is_id(4) => Some(segments[4].clone()),
+        _ => return Err(Error::from(ErrorKind::MalformedToken)),
+    };
+    Ok(TokenIdentity {
+        device_id: segments[2].clone(),
+        module_id,
+    })
+}
+
+// What the keys of an identity are known to be.
+#[derive(Clone)]
+enum Keys {
+    Known(Vec<MemoryKey>),
+    /// The identity has no symmetric keys in the scope of the edge device.
+    Unknown,
+    /// The keys couldn't be fetched and none were cached.
+    Unavailable,
+}
+
+struct CachedKeys {
+    keys: Keys,
+    fetched_at: DateTime<Utc>,
+}
+
+/// Validates batches of tokens with the keys of their identities, which are
+/// fetched from `source` and cached.
+///
+/// Keys fetched while validating a batch are cached for the next batches,
+/// whichever workload API they are sent to.
+pub struct TokenValidator<S> {
+    source: S,
+    cache: Arc<Mutex<HashMap<TokenIdentity, CachedKeys>>>,
+    cache_time: StdDuration,
+    clock: Arc<Clock>,
+}
+
+impl<S: Clone> Clone for TokenValidator<S> {
+    fn clone(&self) -> Self {
+        TokenValidator {
+            source: self.source.clone(),
+            cache: self.cache.clone(),
+            cache_time: self.cache_time,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<S> TokenValidator<S>
+where
+    S: 'static + IdentityKeySource + Clone + Send + Sync,
+{
+    pub fn new(source: S) -> Self {
+        TokenValidator {
+            source,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            cache_time: StdDuration::from_secs(DEFAULT_KEY_CACHE_SECS),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets how long keys are used before they are fetched again.
+    pub fn with_cache_time(mut self, cache_time: StdDuration) -> Self {
+        self.cache_time = cache_time;
+        self
+    }
+
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Validates `tokens`, returning their statuses in the same order. The
+    /// keys of every identity are fetched at most once per batch.
+    pub fn validate(
+        &self,
+        tokens: &[String],
+    ) -> Box<Future<Item = Vec<TokenStatus>, Error = Error> + Send> {
+        let now = self.clock.now();
+        let tokens: Vec<Option<SasToken>> = tokens
+            .iter()
+            .map(|token| SasToken::parse(token).ok())
+            .collect();
+
+        let identities: HashSet<TokenIdentity> = tokens
+            .iter()
+            .filter_map(Option::as_ref)
+            .filter(|token| token.expires_at() > now)
+            .map(|token| token.identity().clone())
+            .collect();
+        let lookups = identities
+            .into_iter()
+            .map(|identity| self.keys(&identity).map(move |keys| (identity, keys)));
+
+        let statuses = future::join_all(lookups).map(move |keys| {
+            let keys: HashMap<TokenIdentity, Keys> = keys.into_iter().collect();
+            tokens
+                .iter()
+                .map(|token| match *token {
+                    None => TokenStatus::Malformed,
+                    Some(ref token) if token.expires_at() <= now => TokenStatus::Expired,
+                    Some(ref token) => match keys.get(token.identity()) {
+                        Some(Keys::Known(keys)) if token.is_signed_with(keys) => {
+                            TokenStatus::Valid
+                        }
+                        Some(Keys::Known(_)) => TokenStatus::InvalidSignature,
+                        Some(Keys::Unknown) => TokenStatus::UnknownIdentity,
+                        Some(Keys::Unavailable) | None => TokenStatus::Unverifiable,
+                    },
+                }).collect()
+        });
+        Box::new(statuses)
+    }
+
+    // The keys of `identity`, from the cache if they were fetched recently
+    // enough. Keys that can't be fetched again are used as long as they are
+    // cached.
+    fn keys(&self, identity: &TokenIdentity) -> Box<Future<Item = Keys, Error = Error> + Send> {
+        let now = self.clock.now();
+        if let Some(cached) = self.lock().get(identity) {
+            let fresh = Duration::from_std(self.cache_time)
+                .ok()
+                .and_then(|cache_time| cached.fetched_at.checked_add_signed(cache_time))
+                .map_or(true, |refresh_at| now < refresh_at);
+            if fresh {
+                return Box::new(future::ok(cached.keys.clone()));
+            }
+        }
+
+        let cache = self.cache.clone();
+        let identity = identity.clone();
+        let fetch = self
+            .source
+            .get_keys(identity.device_id(), identity.module_id())
+            .then(move |result| {
+                let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+                match result {
+                    Ok(keys) => {
+                        let keys = keys.map_or(Keys::Unknown, Keys::Known);
+                        cache.insert(
+                            identity,
+                            CachedKeys {
+                                keys: keys.clone(),
+                                fetched_at: now,
+                            },
+                        );
+                        Ok(keys)
+                    }
+                    Err(err) => {
+                        let cached = cache
+                            .get(&identity)
+                            .map_or(Keys::Unavailable, |cached| cached.keys.clone());
+                        warn!(
+                            "Could not fetch the keys of {}{}: {}",
+                            identity.device_id(),
+                            identity
+                                .module_id()
+                                .map(|module_id| format!("/{}", module_id))
+                                .unwrap_or_default(),
+                            err
+                        );
+                        Ok(cached)
+                    }
+                }
+            });
+        Box::new(fetch)
+    }
+
+    fn lock(&self) -> MutexGuard<HashMap<TokenIdentity, CachedKeys>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::TimeZone;
+    use futures::future::FutureResult;
+
+    use super::*;
+    use clock::ManualClock;
+
+    const KEY: &[u8] = b"leaf device key";
+
+    #[derive(Clone)]
+    struct TestSource {
+        fetches: Arc<AtomicUsize>,
+        available: Arc<Mutex<bool>>,
+    }
+
+    impl TestSource {
+        fn new() -> Self {
+            TestSource {
+                fetches: Arc::new(AtomicUsize::new(0)),
+                available: Arc::new(Mutex::new(true)),
+            }
+        }
+    }
+
+    impl IdentityKeySource for TestSource {
+        type Error = Error;
+        type Future = FutureResult<Option<Vec<MemoryKey>>, Error>;
+
+        fn get_keys(&self, device_id: &str, _module_id: Option<&str>) -> Self::Future {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            if !*self.available.lock().unwrap() {
+                return future::err(Error::from(ErrorKind::Http));
+            }
+            match device_id {
+                "leaf" => future::ok(Some(vec![
+                    MemoryKey::new(b"other key"),
+                    MemoryKey::new(KEY),
+                ])),
+                _ => future::ok(None),
+            }
+        }
+    }
+
+    fn token(resource: &str, expiry: i64, key: &[u8]) -> String {
+        let resource: String = form_urlencoded::byte_serialize(resource.as_bytes()).collect();
+        let signature = MemoryKey::new(key)
+            .sign(
+                SignatureAlgorithm::HMACSHA256,
+                format!("{}\n{}", resource, expiry).as_bytes(),
+            ).unwrap();
+        let signature: String =
+            form_urlencoded::byte_serialize(base64::encode(signature.as_bytes()).as_bytes())
+                .collect();
+        format!(
+            "SharedAccessSignature sr={}&sig={}&se={}",
+            resource, signature, expiry
+        )
+    }
+
+    fn validator() -> (TokenValidator<TestSource>, TestSource, ManualClock) {
+        let source = TestSource::new();
+        let clock = ManualClock::new(Utc.timestamp(1_000_000, 0));
+        let validator = TokenValidator::new(source.clone())
+            .with_cache_time(StdDuration::from_secs(60))
+            .with_clock(clock.clone());
+        (validator, source, clock)
+    }
+
+    #[test]
+    fn tokens_are_parsed() {
+        let token = SasToken::parse(&token("hub.azure-devices.net/devices/leaf", 2_000_000, KEY))
+            .unwrap();
+        assert_eq!("leaf", token.identity().device_id());
+        assert_eq!(None, token.identity().module_id());
+        assert_eq!(Utc.timestamp(2_000_000, 0), token.expires_at());
+
+        let token = SasToken::parse(&token(
+            "hub.azure-devices.net/devices/leaf/modules/sensor%231",
+            2_000_000,
+            KEY,
+        )).unwrap();
+        assert_eq!(Some("sensor#1"), token.identity().module_id());
+
+        for malformed in &[
+            "",
+            "sr=hub.azure-devices.net%2Fdevices%2Fleaf&se=2000000",
+            "sr=hub.azure-devices.net%2Fother%2Fleaf&sig=YWJj&se=2000000",
+            "sr=hub.azure-devices.net%2Fdevices%2Fleaf&sig=YWJj&se=soon",
+        ] {
+            match *SasToken::parse(malformed).unwrap_err().kind() {
+                ErrorKind::MalformedToken => (),
+                ref kind => panic!("unexpected error kind {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn batches_are_validated_with_the_keys_of_each_identity() {
+        let (validator, source, _) = validator();
+        let tokens = vec![
+            token("hub.azure-devices.net/devices/leaf", 2_000_000, KEY),
+            token("hub.azure-devices.net/devices/leaf", 2_000_000, b"wrong key"),
+            token("hub.azure-devices.net/devices/leaf", 999_999, KEY),
+            token("hub.azure-devices.net/devices/stranger", 2_000_000, KEY),
+            "not a token".to_string(),
+        ];
+
+        let statuses = validator.validate(&tokens).wait().unwrap();
+        assert_eq!(
+            vec![
+                TokenStatus::Valid,
+                TokenStatus::InvalidSignature,
+                TokenStatus::Expired,
+                TokenStatus::UnknownIdentity,
+                TokenStatus::Malformed,
+            ],
+            statuses
+        );
+        assert_eq!(2, source.fetches.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn keys_are_cached() {
+        let (validator, source, clock) = validator();
+        let tokens = vec![token("hub.azure-devices.net/devices/leaf", 2_000_000, KEY)];
+
+        validator.validate(&tokens).wait().unwrap();
+        validator.clone().validate(&tokens).wait().unwrap();
+        assert_eq!(1, source.fetches.load(Ordering::SeqCst));
+
+        clock.advance(Duration::seconds(60));
+        validator.validate(&tokens).wait().unwrap();
+        assert_eq!(2, source.fetches.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn cached_keys_are_used_while_the_hub_is_unreachable() {
+        let (validator, source, clock) = validator();
+        let tokens = vec![token("hub.azure-devices.net/devices/leaf", 2_000_000, KEY)];
+        let other = vec![token("hub.azure-devices.net/devices/other", 2_000_000, KEY)];
+
+        validator.validate(&tokens).wait().unwrap();
+        *source.available.lock().unwrap() = false;
+        clock.advance(Duration::seconds(600));
+        assert_eq!(
+            vec![TokenStatus::Valid],
+            validator.validate(&tokens).wait().unwrap()
+        );
+        assert_eq!(
+            vec![TokenStatus::Unverifiable],
+            validator.validate(&other).wait().unwrap()
+        );
+    }
+}
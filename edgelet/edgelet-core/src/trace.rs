@@ -0,0 +1,142 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Events for the tracing facility of the OS
+//!
+//! With the `os-tracing` feature, the daemon emits an event when it starts,
+//! is provisioned and stops, and one for each request it serves, to a sink
+//! that hands them to the OS: ETW on Windows and the ftrace trace marker on
+//! Linux. Those cost next to nothing while no trace session records them,
+//! unlike the logs, so they can be left on in production and be picked up by
+//! the tools operators already use to trace the rest of the system.
+//!
+//! Events are a name followed by `key=value` fields, on one line.
+//!
+//! The sink is process wide, so that it doesn't have to be threaded through
+//! everything that emits events.
+
+use std::fmt::{Display, Write as FmtWrite};
+use std::sync::{PoisonError, RwLock};
+
+#[cfg(unix)]
+use std::fs::{File, OpenOptions};
+#[cfg(unix)]
+use std::io::Write;
+
+#[cfg(unix)]
+use failure::ResultExt;
+
+#[cfg(unix)]
+use error::{Error, ErrorKind};
+
+lazy_static! {
+    static ref SINK: RwLock<Option<Box<TraceSink>>> = RwLock::new(None);
+}
+
+/// Where events go.
+pub trait TraceSink: Send + Sync {
+    /// Whether anything records the events. Events aren't even formatted
+    /// otherwise.
+    fn enabled(&self) -> bool;
+
+    fn write(&self, event: &str);
+}
+
+/// Sets the sink events are emitted to. Until it is set they are dropped.
+pub fn set_sink<S>(sink: S)
+where
+    S: TraceSink + 'static,
+{
+    *SINK.write().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(sink));
+}
+
+/// Whether an event would be recorded, for callers that have to do some work
+/// to come up with its fields.
+pub fn enabled() -> bool {
+    SINK.read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map_or(false, |sink| sink.enabled())
+}
+
+pub fn event(name: &str, fields: &[(&str, &Display)]) {
+    let sink = SINK.read().unwrap_or_else(PoisonError::into_inner);
+    if let Some(ref sink) = *sink {
+        if sink.enabled() {
+            sink.write(&format_event(name, fields));
+        }
+    }
+}
+
+// Values with spaces or quotes in them are quoted, so that the fields can be
+// told apart.
+fn format_event(name: &str, fields: &[(&str, &Display)]) -> String {
+    let mut event = name.to_string();
+    for &(key, value) in fields {
+        let value = value.to_string();
+        if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"') {
+            let _ = write!(event, " {}={:?}", key, value);
+        } else {
+            let _ = write!(event, " {}={}", key, value);
+        }
+    }
+    event
+}
+
+/// Writes events to the ftrace trace marker, where they show up among the
+/// kernel's own events as `ftrace:print`.
+#[cfg(unix)]
+pub struct TraceMarker {
+    file: File,
+}
+
+#[cfg(unix)]
+impl TraceMarker {
+    /// Opens the trace marker of tracefs, wherever it is mounted.
+    pub fn open() -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .write(true)
+            .open("/sys/kernel/tracing/trace_marker")
+            .or_else(|_| {
+                OpenOptions::new()
+                    .write(true)
+                    .open("/sys/kernel/debug/tracing/trace_marker")
+            }).context(ErrorKind::OsTracing)?;
+        Ok(TraceMarker { file })
+    }
+}
+
+#[cfg(unix)]
+impl TraceSink for TraceMarker {
+    // The kernel drops the writes itself while tracing is off.
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    // Each event is written in one go, so that events written by different
+    // threads don't interleave.
+    fn write(&self, event: &str) {
+        let _ = (&self.file).write_all(format!("iotedged: {}\n", event).as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_formatted_as_fields() {
+        assert_eq!("stopped", format_event("stopped", &[]));
+        assert_eq!(
+            r#"request label=management status=200 request="GET /modules HTTP/1.1" pid="""#,
+            format_event(
+                "request",
+                &[
+                    ("label", &"management"),
+                    ("status", &200),
+                    ("request", &"GET /modules HTTP/1.1"),
+                    ("pid", &""),
+                ]
+            )
+        );
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use failure::ResultExt;
+
+use error::{ErrorKind, Result};
+use identity::ModuleName;
+
+/// This is the name of the file the trust bundle is written to in the
+/// directory of each module.
+pub const TRUST_BUNDLE_FILENAME: &str = "trust-bundle.pem";
+
+/// Keeps the trust bundle in a file on the host for each module, for modules
+/// that can only read the CAs they trust from disk.
+///
+/// Each module has a directory of its own under `dir`, which is meant to be
+/// bind mounted read only into its container. The directory is mounted
+/// rather than the file, since a file that is bind mounted keeps pointing at
+/// the file it was when the container started. The file is replaced
+/// atomically, so modules never read a partly written bundle.
+///
+/// The runtime mounts the file into the containers it creates, while the
+/// daemon rewrites it as the CA rotates, so both hold the same files.
+#[derive(Clone)]
+pub struct TrustBundleFiles {
+    dir: PathBuf,
+    bundle: Arc<Mutex<Option<String>>>,
+}
+
+impl TrustBundleFiles {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        TrustBundleFiles {
+            dir: dir.into(),
+            bundle: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The directory the trust bundle of `module` is written to.
+    pub fn module_dir(&self, module: &str) -> PathBuf {
+        self.dir.join(module)
+    }
+
+    /// Writes the current trust bundle for `module`, before its container
+    /// is created. Returns the directory it was written to.
+    pub fn add(&self, module: &str) -> Result<PathBuf> {
+        // the module name becomes part of a path on the host
+        ModuleName::new(module)?;
+        let dir = self.module_dir(module);
+        fs::create_dir_all(&dir).context(ErrorKind::TrustBundleFiles)?;
+        if let Some(ref bundle) = *self.lock() {
+            write_bundle(&dir, bundle)?;
+        }
+        Ok(dir)
+    }
+
+    /// Removes the trust bundle of `module` once it's gone.
+    pub fn remove(&self, module: &str) -> Result<()> {
+        ModuleName::new(module)?;
+        let dir = self.module_dir(module);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).context(ErrorKind::TrustBundleFiles)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `bundle` for every module that has a directory, including the
+    /// ones added before the daemon restarted, unless it's the bundle that
+    /// was written last. Returns whether the files were written.
+    pub fn update(&self, bundle: &str) -> Result<bool> {
+        let mut current = self.lock();
+        if current.as_ref().map(String::as_str) == Some(bundle) {
+            return Ok(false);
+        }
+
+        fs::create_dir_all(&self.dir).context(ErrorKind::TrustBundleFiles)?;
+        for entry in fs::read_dir(&self.dir).context(ErrorKind::TrustBundleFiles)? {
+            let path = entry.context(ErrorKind::TrustBundleFiles)?.path();
+            if path.is_dir() {
+                write_bundle(&path, bundle)?;
+            }
+        }
+        *current = Some(bundle.to_string());
+        Ok(true)
+    }
+
+    fn lock(&self) -> MutexGuard<Option<String>> {
+        self.bundle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Writes the bundle next to where it goes first, so that the rename that
+// puts it in place doesn't cross file systems and is atomic.
+fn write_bundle(dir: &Path, bundle: &str) -> Result<()> {
+    let path = dir.join(TRUST_BUNDLE_FILENAME);
+    let temp = path.with_extension("tmp");
+    let mut file = File::create(&temp).context(ErrorKind::TrustBundleFiles)?;
+    file.write_all(bundle.as_bytes())
+        .and_then(|_| file.sync_all())
+        .context(ErrorKind::TrustBundleFiles)?;
+    fs::rename(&temp, &path).context(ErrorKind::TrustBundleFiles)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    const OLD_ROOT: &str = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+    const NEW_ROOT: &str = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+
+    #[test]
+    fn bundle_is_kept_in_a_file_for_each_module() {
+        let tmp_dir = TempDir::new("trust_bundle_files").unwrap();
+        let dir = tmp_dir.path();
+        let read = |module: &str| {
+            fs::read_to_string(dir.join(module).join(TRUST_BUNDLE_FILENAME)).unwrap()
+        };
+
+        let files = TrustBundleFiles::new(dir);
+        assert!(files.update(OLD_ROOT).unwrap());
+        assert_eq!(dir.join("sensor"), files.add("sensor").unwrap());
+        assert_eq!(OLD_ROOT, read("sensor"));
+        assert!(files.add("../sensor").is_err());
+
+        // a module added before a restart is updated too
+        let restarted = TrustBundleFiles::new(dir);
+        restarted.add("display").unwrap();
+        assert!(restarted.update(NEW_ROOT).unwrap());
+        assert!(!restarted.update(NEW_ROOT).unwrap());
+        assert_eq!(NEW_ROOT, read("sensor"));
+        assert_eq!(NEW_ROOT, read("display"));
+        assert!(!dir.join("sensor").join("trust-bundle.tmp").exists());
+
+        restarted.remove("sensor").unwrap();
+        assert!(!dir.join("sensor").exists());
+        restarted.remove("sensor").unwrap();
+    }
+}
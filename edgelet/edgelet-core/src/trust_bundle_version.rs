@@ -0,0 +1,332 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use failure::ResultExt;
+use serde_json::{self, Map, Value};
+
+use ca_rotation::certificates;
+use error::{ErrorKind, Result};
+use outbox::{MessageKind, Outbox};
+
+/// This is how many changes of the trust bundle are kept to compute deltas
+/// from. Modules that last saw an older version are served the whole bundle.
+const MAX_CHANGES: usize = 32;
+
+/// The roots added to and removed from the trust bundle since a version of
+/// it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct TrustBundleDelta {
+    version: u64,
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl TrustBundleDelta {
+    /// The version of the trust bundle the delta brings a module up to.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The PEM encoded roots added since the version the delta is from.
+    pub fn added(&self) -> &[String] {
+        &self.added
+    }
+
+    /// The PEM encoded roots removed since the version the delta is from.
+    pub fn removed(&self) -> &[String] {
+        &self.removed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    fn apply(&mut self, change: &TrustBundleDelta) {
+        for cert in &change.added {
+            match self.removed.iter().position(|removed| removed == cert) {
+                Some(index) => {
+                    self.removed.remove(index);
+                }
+                None => self.added.push(cert.clone()),
+            }
+        }
+        for cert in &change.removed {
+            match self.added.iter().position(|added| added == cert) {
+                Some(index) => {
+                    self.added.remove(index);
+                }
+                None => self.removed.push(cert.clone()),
+            }
+        }
+        self.version = change.version;
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Record {
+    version: u64,
+    roots: Vec<String>,
+    changes: VecDeque<TrustBundleDelta>,
+}
+
+/// Numbers the versions of the trust bundle served to modules, so that they
+/// can ask for what changed since the version they have instead of the whole
+/// bundle.
+///
+/// The version goes up by one each time the bundle that is served has other
+/// roots than before, and each change is reported to IoT Hub through the
+/// outbox. The most recent changes are kept to compute deltas from. When
+/// created with `load` every change is persisted to disk, so that versions
+/// keep going up across restarts. The daemon and the workload API of every
+/// namespace observe the trust bundle through the same versions, so a change
+/// is only numbered and reported once.
+#[derive(Clone, Default)]
+pub struct TrustBundleVersions {
+    path: Option<PathBuf>,
+    outbox: Outbox,
+    record: Arc<Mutex<Record>>,
+}
+
+impl TrustBundleVersions {
+    pub fn new() -> Self {
+        TrustBundleVersions::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let record = if path.exists() {
+            let file = File::open(&path).context(ErrorKind::TrustBundleVersion)?;
+            serde_json::from_reader(file).context(ErrorKind::TrustBundleVersion)?
+        } else {
+            Record::default()
+        };
+
+        Ok(TrustBundleVersions {
+            path: Some(path),
+            outbox: Outbox::default(),
+            record: Arc::new(Mutex::new(record)),
+        })
+    }
+
+    /// Reports every change of the trust bundle to IoT Hub through `outbox`.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// The current version of the trust bundle, or 0 if none has been
+    /// observed yet.
+    pub fn version(&self) -> u64 {
+        self.lock().version
+    }
+
+    /// Records the PEM encoded trust bundle that is served, and returns its
+    /// version.
+    pub fn observe(&self, trust_bundle: &str) -> Result<u64> {
+        let roots = certificates(trust_bundle);
+        let mut record = self.lock();
+
+        let change = TrustBundleDelta {
+            version: record.version + 1,
+            added: roots
+                .iter()
+                .filter(|root| !record.roots.contains(root))
+                .cloned()
+                .collect(),
+            removed: record
+                .roots
+                .iter()
+                .filter(|root| !roots.contains(root))
+                .cloned()
+                .collect(),
+        };
+        if record.version > 0 && change.is_empty() {
+            return Ok(record.version);
+        }
+
+        // The first version has nothing to be a change from.
+        if record.version > 0 {
+            info!(
+                "The trust bundle is now at version {}: {} roots added, {} removed.",
+                change.version,
+                change.added.len(),
+                change.removed.len()
+            );
+            self.report(&change);
+            record.changes.push_back(change.clone());
+            while record.changes.len() > MAX_CHANGES {
+                record.changes.pop_front();
+            }
+        }
+        record.version = change.version;
+        record.roots = roots;
+        self.persist(&record)?;
+        Ok(record.version)
+    }
+
+    /// The roots added and removed since version `since`, or `None` if the
+    /// changes since then are no longer kept, or `since` isn't a version
+    /// that has been served.
+    pub fn delta(&self, since: u64) -> Option<TrustBundleDelta> {
+        let record = self.lock();
+        if since == 0 || since > record.version {
+            return None;
+        }
+
+        let mut delta = TrustBundleDelta {
+            version: since,
+            ..TrustBundleDelta::default()
+        };
+        for change in record
+            .changes
+            .iter()
+            .filter(|change| change.version > since)
+        {
+            // the changes are kept in order, so a gap means the ones right
+            // after `since` have been dropped
+            if change.version != delta.version + 1 {
+                return None;
+            }
+            delta.apply(change);
+        }
+        if delta.version == record.version {
+            Some(delta)
+        } else {
+            None
+        }
+    }
+
+    fn report(&self, change: &TrustBundleDelta) {
+        let mut event = Map::new();
+        event.insert(
+            "event".to_string(),
+            Value::from("trustBundleChanged".to_string()),
+        );
+        event.insert("version".to_string(), Value::from(change.version));
+        event.insert("added".to_string(), Value::from(change.added.len()));
+        event.insert("removed".to_string(), Value::from(change.removed.len()));
+        if let Err(err) = self.outbox.push(MessageKind::TrustBundleChanged, &event) {
+            warn!(
+                "Could not queue the change of the trust bundle to version {} for IoT Hub: {}",
+                change.version, err
+            );
+        }
+    }
+
+    fn persist(&self, record: &Record) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(ErrorKind::TrustBundleVersion)?;
+            }
+            let temp = path.with_extension("tmp");
+            let file = File::create(&temp).context(ErrorKind::TrustBundleVersion)?;
+            serde_json::to_writer(file, record).context(ErrorKind::TrustBundleVersion)?;
+            fs::rename(&temp, path).context(ErrorKind::TrustBundleVersion)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<Record> {
+        self.record.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    const OLD_ROOT: &str = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+    const NEW_ROOT: &str = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+    const NEXT_ROOT: &str = "-----BEGIN CERTIFICATE-----\nbmV4dA==\n-----END CERTIFICATE-----\n";
+
+    #[test]
+    fn versions_go_up_when_the_roots_change() {
+        let outbox = Outbox::new(10);
+        let versions = TrustBundleVersions::new().with_outbox(outbox.clone());
+        assert_eq!(0, versions.version());
+
+        assert_eq!(1, versions.observe(OLD_ROOT).unwrap());
+        assert_eq!(1, versions.observe(OLD_ROOT).unwrap());
+        assert!(outbox.is_empty());
+
+        // the same roots in another order are the same bundle
+        let both = format!("{}{}", OLD_ROOT, NEW_ROOT);
+        assert_eq!(2, versions.observe(&both).unwrap());
+        assert_eq!(
+            2,
+            versions
+                .observe(&format!("{}\n{}", NEW_ROOT, OLD_ROOT))
+                .unwrap()
+        );
+
+        let messages = outbox.peek(10);
+        assert_eq!(1, messages.len());
+        assert_eq!(MessageKind::TrustBundleChanged, messages[0].kind());
+        assert_eq!(2, messages[0].body()["version"]);
+        assert_eq!(1, messages[0].body()["added"]);
+    }
+
+    #[test]
+    fn deltas_add_up_the_changes_since_a_version() {
+        let versions = TrustBundleVersions::new();
+        versions.observe(OLD_ROOT).unwrap();
+        versions
+            .observe(&format!("{}{}", OLD_ROOT, NEW_ROOT))
+            .unwrap();
+        versions.observe(OLD_ROOT).unwrap();
+        versions.observe(NEXT_ROOT).unwrap();
+
+        let current = versions.delta(4).unwrap();
+        assert_eq!(4, current.version());
+        assert!(current.is_empty());
+
+        let delta = versions.delta(3).unwrap();
+        assert_eq!(4, delta.version());
+        assert_eq!(&[NEXT_ROOT.to_string()], delta.added());
+        assert_eq!(&[OLD_ROOT.to_string()], delta.removed());
+
+        // the root added and removed again in between cancels out
+        let delta = versions.delta(1).unwrap();
+        assert_eq!(4, delta.version());
+        assert_eq!(&[NEXT_ROOT.to_string()], delta.added());
+        assert_eq!(&[OLD_ROOT.to_string()], delta.removed());
+
+        assert_eq!(None, versions.delta(0));
+        assert_eq!(None, versions.delta(5));
+    }
+
+    #[test]
+    fn old_versions_have_no_delta() {
+        let versions = TrustBundleVersions::new();
+        versions.observe(OLD_ROOT).unwrap();
+        for _ in 0..MAX_CHANGES {
+            versions.observe(NEW_ROOT).unwrap();
+            versions.observe(OLD_ROOT).unwrap();
+        }
+
+        let version = versions.version();
+        assert!(versions.delta(version - MAX_CHANGES as u64).is_some());
+        assert_eq!(None, versions.delta(version - MAX_CHANGES as u64 - 1));
+    }
+
+    #[test]
+    fn versions_survive_restarts() {
+        let tmp_dir = TempDir::new("trust_bundle_version").unwrap();
+        let path = tmp_dir.path().join("trust_bundle_versions.json");
+
+        let versions = TrustBundleVersions::load(&path).unwrap();
+        versions.observe(OLD_ROOT).unwrap();
+        versions.observe(NEW_ROOT).unwrap();
+
+        let versions = TrustBundleVersions::load(&path).unwrap();
+        assert_eq!(2, versions.version());
+        assert_eq!(2, versions.observe(NEW_ROOT).unwrap());
+        assert_eq!(&[NEW_ROOT.to_string()], versions.delta(1).unwrap().added());
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use metrics::Metrics;
+
+/// A workload API call that is accounted to the module making it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorkloadCall {
+    IssueCertificate,
+    Sign,
+    Encrypt,
+    Decrypt,
+}
+
+impl WorkloadCall {
+    // The name of the metric that counts the calls of every module, which
+    // the name of the module is appended to.
+    fn metric(self) -> &'static str {
+        match self {
+            WorkloadCall::IssueCertificate => "workloadCertificates",
+            WorkloadCall::Sign => "workloadSigns",
+            WorkloadCall::Encrypt => "workloadEncrypts",
+            WorkloadCall::Decrypt => "workloadDecrypts",
+        }
+    }
+
+    fn metric_of(self, module: &str) -> String {
+        format!("{}/{}", self.metric(), module)
+    }
+}
+
+/// How many workload API calls of each kind a module has made.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModuleUsage {
+    certificates: u64,
+    signs: u64,
+    encrypts: u64,
+    decrypts: u64,
+}
+
+impl ModuleUsage {
+    pub fn certificates(&self) -> u64 {
+        self.certificates
+    }
+
+    pub fn signs(&self) -> u64 {
+        self.signs
+    }
+
+    pub fn encrypts(&self) -> u64 {
+        self.encrypts
+    }
+
+    pub fn decrypts(&self) -> u64 {
+        self.decrypts
+    }
+}
+
+/// Counts the workload API calls that use the HSM per module, so that
+/// operators can spot modules that call it far more than they should. The
+/// calls are only accounted to a module once the caller was found to be the
+/// module itself.
+///
+/// The counts are kept in the daemon's metrics, as `workloadSigns/<module>`
+/// and the like, so they are listed and persisted along with the rest of
+/// them.
+#[derive(Clone, Default)]
+pub struct WorkloadUsage {
+    metrics: Metrics,
+}
+
+impl WorkloadUsage {
+    pub fn new(metrics: Metrics) -> Self {
+        WorkloadUsage { metrics }
+    }
+
+    pub fn record(&self, module: &str, call: WorkloadCall) {
+        self.metrics.increment(&call.metric_of(module));
+    }
+
+    pub fn usage(&self, module: &str) -> ModuleUsage {
+        let count = |call: WorkloadCall| self.metrics.get(&call.metric_of(module));
+        ModuleUsage {
+            certificates: count(WorkloadCall::IssueCertificate),
+            signs: count(WorkloadCall::Sign),
+            encrypts: count(WorkloadCall::Encrypt),
+            decrypts: count(WorkloadCall::Decrypt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_are_counted_per_module() {
+        let metrics = Metrics::new();
+        let usage = WorkloadUsage::new(metrics.clone());
+        usage.record("sensor", WorkloadCall::Sign);
+        usage.record("sensor", WorkloadCall::Sign);
+        usage.clone().record("sensor", WorkloadCall::Encrypt);
+        usage.record("display", WorkloadCall::IssueCertificate);
+
+        let sensor = usage.usage("sensor");
+        assert_eq!(2, sensor.signs());
+        assert_eq!(1, sensor.encrypts());
+        assert_eq!(0, sensor.decrypts());
+        assert_eq!(0, sensor.certificates());
+        assert_eq!(1, usage.usage("display").certificates());
+        assert_eq!(ModuleUsage::default(), usage.usage("unknown"));
+
+        assert_eq!(2, metrics.get("workloadSigns/sensor"));
+        assert_eq!(1, metrics.get("workloadCertificates/display"));
+    }
+}
@@ -4,14 +4,19 @@ use std::time::{Duration, Instant};
 
 use edgelet_utils::log_failure;
 use futures::future::{self, Either, FutureResult};
-use futures::Future;
+use futures::{stream, Future};
 use log::Level;
 use tokio::prelude::*;
 use tokio::timer::Interval;
 
 use error::{Error, ErrorKind};
 use identity::{Identity, IdentityManager, IdentitySpec};
-use module::{Module, ModuleRegistry, ModuleRuntime, ModuleSpec, ModuleStatus};
+use metrics::{Metrics, WATCHDOG_RESTARTS};
+use module::{
+    Module, ModulePriority, ModuleRegistry, ModuleRuntime, ModuleSpec, ModuleStatus, RestartPolicy,
+};
+use outbox::{MessageKind, Outbox};
+use retry::{Retry, RetryPolicy};
 
 // Time to allow EdgeAgent to gracefully shutdown (including stopping all modules, and updating reported properties)
 const EDGE_RUNTIME_STOP_TIME: Duration = Duration::from_secs(60);
@@ -19,12 +24,30 @@ const EDGE_RUNTIME_STOP_TIME: Duration = Duration::from_secs(60);
 /// This variable holds the generation ID associated with the Edge Agent module.
 const MODULE_GENERATIONID: &str = "IOTEDGE_MODULEGENERATIONID";
 
-/// This is the frequency with which the watchdog checks for the status of the edge runtime module.
+/// This is the default frequency with which the watchdog checks the edge runtime module's status.
 const WATCHDOG_FREQUENCY_SECS: u64 = 60;
 
+/// Number of times the watchdog tries to restart a stopped edge runtime module before waiting for the next check.
+const RESTART_MAX_ATTEMPTS: u32 = 3;
+const RESTART_INITIAL_BACKOFF_SECS: u64 = 1;
+const RESTART_MAX_BACKOFF_SECS: u64 = 10;
+
 pub struct Watchdog<M, I> {
     runtime: M,
     id_mgr: I,
+    frequency: Duration,
+    outbox: Outbox,
+    metrics: Metrics,
+}
+
+/// What the watchdog did to a module, as reported to IoT Hub.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WatchdogAction<'a> {
+    module: &'a str,
+    action: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_status: Option<String>,
 }
 
 impl<M, I> Watchdog<M, I>
@@ -37,7 +60,32 @@ where
     I::Error: Into<Error>,
 {
     pub fn new(runtime: M, id_mgr: I) -> Self {
-        Watchdog { runtime, id_mgr }
+        Watchdog {
+            runtime,
+            id_mgr,
+            frequency: Duration::from_secs(WATCHDOG_FREQUENCY_SECS),
+            outbox: Outbox::default(),
+            metrics: Metrics::new(),
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: Duration) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Reports every restart and re-creation of the edge runtime module, and
+    /// every restart of a failed module, to IoT Hub through `outbox`.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// Counts every restart and re-creation of the edge runtime module, and
+    /// every restart of a failed module, in `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
     }
 
     // Start the edge runtime module (EdgeAgent). This also updates the identity of the module (module_id)
@@ -58,7 +106,15 @@ where
         let id_mgr = self.id_mgr.clone();
         let module_id = module_id.to_string();
 
-        let watchdog = start_watchdog(runtime, id_mgr, spec, module_id);
+        let watchdog = start_watchdog(
+            runtime,
+            id_mgr,
+            spec,
+            module_id,
+            self.frequency,
+            self.outbox,
+            self.metrics,
+        );
 
         // Swallow any errors from shutdown_signal
         let shutdown_signal = shutdown_signal.then(|_| Ok(()));
@@ -89,12 +145,15 @@ where
         .map_err(|e| e.into())
 }
 
-// Start watchdog on a timer for 1 minute
+// Start watchdog on a timer, checking once every `frequency`
 pub fn start_watchdog<M, I>(
     runtime: M,
     id_mgr: I,
     spec: ModuleSpec<<M::Module as Module>::Config>,
     module_id: String,
+    frequency: Duration,
+    outbox: Outbox,
+    metrics: Metrics,
 ) -> impl Future<Item = (), Error = Error>
 where
     M: 'static + ModuleRuntime + Clone,
@@ -106,31 +165,111 @@ where
 {
     info!(
         "Starting watchdog with {} second frequency...",
-        WATCHDOG_FREQUENCY_SECS
+        frequency.as_secs()
     );
-    Interval::new(Instant::now(), Duration::from_secs(WATCHDOG_FREQUENCY_SECS))
+    Interval::new(Instant::now(), frequency)
         .map_err(Error::from)
         .for_each(move |_| {
             info!("Checking edge runtime status");
+            let runtime_copy = runtime.clone();
+            let name = spec.name().to_string();
+            let outbox_copy = outbox.clone();
+            let metrics_copy = metrics.clone();
             check_runtime(
                 runtime.clone(),
                 id_mgr.clone(),
                 spec.clone(),
                 module_id.clone(),
+                &outbox,
+                &metrics,
             ).or_else(|e| {
                 warn!("Error in watchdog when checking for edge runtime status:");
                 log_failure(Level::Warn, &e);
                 future::ok(())
+            }).and_then(move |_| {
+                restart_failed_modules(runtime_copy, name, &outbox_copy, &metrics_copy)
+            }).or_else(|e| {
+                warn!("Error in watchdog when restarting failed modules:");
+                log_failure(Level::Warn, &e);
+                future::ok(())
+            })
+        })
+}
+
+// Restart the modules that failed, other than the edge runtime module, one
+// at a time and most important first, so that the more important ones get the
+// device's resources first. Modules are only restarted when they were created
+// with a restart policy that restarts failed modules; the others are left to
+// the edge runtime module.
+fn restart_failed_modules<M>(
+    runtime: M,
+    edge_runtime: String,
+    outbox: &Outbox,
+    metrics: &Metrics,
+) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    M::Error: Into<Error>,
+{
+    let outbox = outbox.clone();
+    let metrics = metrics.clone();
+    runtime
+        .list_with_details()
+        .map_err(Into::into)
+        .filter(move |&(ref module, _)| module.name() != edge_runtime)
+        .map(|(module, state)| {
+            (
+                module.name().to_string(),
+                module.priority(),
+                module.restart_policy(),
+                *state.status(),
+            )
+        }).collect()
+        .and_then(move |modules| {
+            stream::iter_ok(restart_order(modules)).for_each(move |name| {
+                info!("Module {} failed, restarting it...", name);
+                report_action(
+                    &outbox,
+                    &name,
+                    "restart",
+                    Some(ModuleStatus::Failed.to_string()),
+                );
+                metrics.increment(WATCHDOG_RESTARTS);
+                runtime.start(&name).then(move |result| {
+                    if let Err(err) = result {
+                        let err: Error = err.into();
+                        warn!("Could not restart failed module {}:", name);
+                        log_failure(Level::Warn, &err);
+                    }
+                    Ok(())
+                })
             })
         })
 }
 
+// The names of the failed modules the watchdog restarts, in the order it
+// restarts them.
+fn restart_order(
+    modules: Vec<(String, ModulePriority, Option<RestartPolicy>, ModuleStatus)>,
+) -> Vec<String> {
+    let mut failed = modules
+        .into_iter()
+        .filter(|&(_, _, policy, status)| {
+            status == ModuleStatus::Failed && policy.map_or(false, RestartPolicy::restarts_failed)
+        }).map(|(name, priority, _, _)| (priority, name))
+        .collect::<Vec<_>>();
+    failed.sort();
+    failed.into_iter().map(|(_, name)| name).collect()
+}
+
 // Check if the edge runtime module is running, and if not, start it.
 fn check_runtime<M, I>(
     runtime: M,
     id_mgr: I,
     spec: ModuleSpec<<M::Module as Module>::Config>,
     module_id: String,
+    outbox: &Outbox,
+    metrics: &Metrics,
 ) -> impl Future<Item = (), Error = Error>
 where
     M: 'static + ModuleRuntime + Clone,
@@ -141,6 +280,8 @@ where
     I::Error: Into<Error>,
 {
     let module = spec.name().to_string();
+    let outbox = outbox.clone();
+    let metrics = metrics.clone();
     get_edge_runtime_mod(&runtime, module.clone())
         .and_then(|m| m.map(|m| m.runtime_state().map_err(|e| e.into())))
         .and_then(move |state| match state {
@@ -153,15 +294,62 @@ where
                         "Edge runtime status is {}, starting module now...",
                         *state.status(),
                     );
-                    future::Either::B(runtime.start(&module).map_err(|e| e.into()))
+                    report_action(
+                        &outbox,
+                        &module,
+                        "restart",
+                        Some(state.status().to_string()),
+                    );
+                    metrics.increment(WATCHDOG_RESTARTS);
+                    future::Either::B(restart_runtime(runtime, module))
                 };
                 Either::A(res)
             }
 
-            None => Either::B(create_and_start(runtime, &id_mgr, spec, &module_id)),
+            None => {
+                report_action(&outbox, &module, "create", None);
+                metrics.increment(WATCHDOG_RESTARTS);
+                Either::B(create_and_start(runtime, &id_mgr, spec, &module_id))
+            }
         }).map(|_| ())
 }
 
+// A full or broken outbox mustn't stop the watchdog from doing its job.
+fn report_action(outbox: &Outbox, module: &str, action: &str, previous_status: Option<String>) {
+    let action = WatchdogAction {
+        module,
+        action,
+        previous_status,
+    };
+    if let Err(err) = outbox.push(MessageKind::WatchdogAction, &action) {
+        warn!("Could not queue the watchdog action for IoT Hub:");
+        log_failure(Level::Warn, &err);
+    }
+}
+
+// Start a stopped edge runtime module, retrying a few times before giving up until the next check.
+fn restart_runtime<M>(runtime: M, module: String) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: Clone,
+    M::Error: Into<Error>,
+    <M::Module as Module>::Error: Into<Error>,
+{
+    let policy = RetryPolicy::exponential(
+        Duration::from_secs(RESTART_INITIAL_BACKOFF_SECS),
+        Duration::from_secs(RESTART_MAX_BACKOFF_SECS),
+    ).with_max_attempts(RESTART_MAX_ATTEMPTS);
+
+    Retry::new(policy, move |_| runtime.start(&module).map_err(|e| e.into()))
+        .with_hook(|attempt, err: &Error, delay| {
+            warn!(
+                "Attempt {} to start edge runtime failed, retrying in {:?}",
+                attempt, delay
+            );
+            log_failure(Level::Warn, err);
+        })
+}
+
 // Gets the edge runtime module, if it exists.
 fn get_edge_runtime_mod<M>(
     runtime: &M,
@@ -492,4 +680,84 @@ mod tests {
                 .auth_type
         );
     }
+
+    #[test]
+    fn failed_modules_are_restarted_most_important_first() {
+        let modules = vec![
+            (
+                "low".to_string(),
+                ModulePriority::Low,
+                Some(RestartPolicy::Always),
+                ModuleStatus::Failed,
+            ),
+            (
+                "critical".to_string(),
+                ModulePriority::Critical,
+                Some(RestartPolicy::OnFailure),
+                ModuleStatus::Failed,
+            ),
+            (
+                "normal".to_string(),
+                ModulePriority::Normal,
+                Some(RestartPolicy::OnUnhealthy),
+                ModuleStatus::Failed,
+            ),
+            (
+                "high".to_string(),
+                ModulePriority::High,
+                Some(RestartPolicy::Always),
+                ModuleStatus::Failed,
+            ),
+        ];
+        assert_eq!(
+            vec!["critical", "high", "normal", "low"],
+            restart_order(modules)
+        );
+    }
+
+    #[test]
+    fn only_failed_modules_with_a_restart_policy_are_restarted() {
+        let modules = vec![
+            (
+                "never".to_string(),
+                ModulePriority::Critical,
+                Some(RestartPolicy::Never),
+                ModuleStatus::Failed,
+            ),
+            (
+                "unmanaged".to_string(),
+                ModulePriority::Critical,
+                None,
+                ModuleStatus::Failed,
+            ),
+            (
+                "stopped".to_string(),
+                ModulePriority::Critical,
+                Some(RestartPolicy::Always),
+                ModuleStatus::Stopped,
+            ),
+            (
+                "running".to_string(),
+                ModulePriority::Critical,
+                Some(RestartPolicy::Always),
+                ModuleStatus::Running,
+            ),
+        ];
+        assert!(restart_order(modules).is_empty());
+    }
+
+    #[test]
+    fn report_action_queues_watchdog_action() {
+        let outbox = Outbox::new(10);
+        report_action(&outbox, "edgeAgent", "restart", Some("failed".to_string()));
+        report_action(&outbox, "edgeAgent", "create", None);
+
+        let messages = outbox.peek(10);
+        assert_eq!(2, messages.len());
+        assert_eq!(MessageKind::WatchdogAction, messages[0].kind());
+        assert_eq!("restart", messages[0].body()["action"]);
+        assert_eq!("failed", messages[0].body()["previousStatus"]);
+        assert_eq!("create", messages[1].body()["action"]);
+        assert!(messages[1].body().get("previousStatus").is_none());
+    }
 }
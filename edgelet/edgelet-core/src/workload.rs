@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use certificate_properties::CertificateType;
+use cert_policy::CertPolicy;
+use certificate_properties::{CertificateType, KeyType};
 
 /// Trait to obtain configuration data needed by any implementation of the workload interface
 /// for module identity and certificate management.
@@ -8,4 +9,18 @@ pub trait WorkloadConfig {
     fn iot_hub_name(&self) -> &str;
     fn device_id(&self) -> &str;
     fn get_cert_max_duration(&self, cert_type: CertificateType) -> i64;
+
+    /// The policy the certificates of `cert_type` are issued under, which
+    /// only caps their validity at `get_cert_max_duration` unless it is
+    /// overridden.
+    fn get_cert_policy(&self, cert_type: CertificateType) -> CertPolicy {
+        CertPolicy::new().with_max_duration(self.get_cert_max_duration(cert_type))
+    }
+
+    /// The type of the key of the certificates of `cert_type` modules don't
+    /// ask for one for, or `None` for a key of the type of the key of their
+    /// issuer.
+    fn get_default_key_type(&self, _cert_type: CertificateType) -> Option<KeyType> {
+        None
+    }
 }
@@ -0,0 +1,283 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Resource stats and limits of containers under either version of Linux
+//! control groups.
+//!
+//! Docker reports the stats of a container in the fields of the cgroup
+//! version of the host. Under cgroup v2 there is no per CPU usage and the
+//! memory stats have the names of the unified hierarchy, and some of the
+//! limits cgroup v1 has don't exist at all.
+
+use std::fmt;
+use std::path::Path;
+
+use docker::models::HostConfig;
+use serde_json::Value;
+
+/// The file that only exists at the root of the unified hierarchy of
+/// cgroup v2.
+const CGROUP_V2_CONTROLLERS: &str = "cgroup.controllers";
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The version of control groups a Linux host runs containers under.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CgroupVersion {
+    V1,
+    V2,
+}
+
+impl CgroupVersion {
+    /// Detects the version of the host's control groups from how they are
+    /// mounted. Returns `None` on hosts without control groups, such as
+    /// Windows.
+    pub fn detect() -> Option<Self> {
+        CgroupVersion::detect_at(Path::new(CGROUP_ROOT))
+    }
+
+    fn detect_at(root: &Path) -> Option<Self> {
+        if root.join(CGROUP_V2_CONTROLLERS).exists() {
+            Some(CgroupVersion::V2)
+        } else if root.exists() {
+            Some(CgroupVersion::V1)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for CgroupVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CgroupVersion::V1 => write!(f, "cgroup v1"),
+            CgroupVersion::V2 => write!(f, "cgroup v2"),
+        }
+    }
+}
+
+/// The resource usage of a module's container at one point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModuleStats {
+    cpu_percent: Option<f64>,
+    memory_usage: Option<u64>,
+    memory_limit: Option<u64>,
+}
+
+impl ModuleStats {
+    /// Reads the stats docker reported for a container on a host with
+    /// control groups of version `version`, or of either version when it
+    /// isn't known.
+    pub fn from_docker(stats: &Value, version: Option<CgroupVersion>) -> Self {
+        let memory = &stats["memory_stats"];
+        ModuleStats {
+            cpu_percent: cpu_percent(&stats["cpu_stats"], &stats["precpu_stats"]),
+            memory_usage: memory_usage(memory, version),
+            memory_limit: memory["limit"].as_u64(),
+        }
+    }
+
+    /// The share of the host's CPUs the container used since the previous
+    /// sample, where 100 is one CPU.
+    pub fn cpu_percent(&self) -> Option<f64> {
+        self.cpu_percent
+    }
+
+    /// The memory the container uses, in bytes, without the page cache the
+    /// kernel can reclaim.
+    pub fn memory_usage(&self) -> Option<u64> {
+        self.memory_usage
+    }
+
+    /// The memory limit of the container, in bytes.
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.memory_limit
+    }
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_precision_loss))]
+fn cpu_percent(cpu: &Value, precpu: &Value) -> Option<f64> {
+    let usage = cpu["cpu_usage"]["total_usage"].as_u64()?;
+    let system = cpu["system_cpu_usage"].as_u64()?;
+    let usage_delta = usage.checked_sub(precpu["cpu_usage"]["total_usage"].as_u64()?)?;
+    let system_delta = system.checked_sub(precpu["system_cpu_usage"].as_u64()?)?;
+    if system_delta == 0 {
+        return None;
+    }
+
+    // cgroup v2 has no per CPU usage to count the CPUs by
+    let cpus = cpu["online_cpus"]
+        .as_u64()
+        .or_else(|| {
+            cpu["cpu_usage"]["percpu_usage"]
+                .as_array()
+                .map(|percpu| percpu.len() as u64)
+        }).unwrap_or(1);
+    Some(usage_delta as f64 / system_delta as f64 * cpus as f64 * 100.0)
+}
+
+fn memory_usage(memory: &Value, version: Option<CgroupVersion>) -> Option<u64> {
+    let usage = memory["usage"].as_u64()?;
+    let stats = &memory["stats"];
+    let v1 = || {
+        stats["total_inactive_file"]
+            .as_u64()
+            .or_else(|| stats["cache"].as_u64())
+    };
+    let v2 = || stats["inactive_file"].as_u64();
+    let inactive = match version {
+        Some(CgroupVersion::V1) => v1(),
+        Some(CgroupVersion::V2) => v2(),
+        None => v1().or_else(v2),
+    };
+    match inactive {
+        Some(inactive) if inactive < usage => Some(usage - inactive),
+        _ => Some(usage),
+    }
+}
+
+/// Drops the limits of `host_config` that control groups of version
+/// `version` don't have, which docker would otherwise refuse to create the
+/// container with.
+pub fn supported_limits(host_config: HostConfig, version: Option<CgroupVersion>) -> HostConfig {
+    if version != Some(CgroupVersion::V2) {
+        return host_config;
+    }
+
+    let mut host_config = host_config;
+    let mut dropped = vec![];
+    if host_config.kernel_memory().is_some() {
+        host_config.reset_kernel_memory();
+        dropped.push("KernelMemory");
+    }
+    if host_config.memory_swappiness().is_some() {
+        host_config.reset_memory_swappiness();
+        dropped.push("MemorySwappiness");
+    }
+    if host_config.cpu_realtime_period().is_some() {
+        host_config.reset_cpu_realtime_period();
+        dropped.push("CpuRealtimePeriod");
+    }
+    if host_config.cpu_realtime_runtime().is_some() {
+        host_config.reset_cpu_realtime_runtime();
+        dropped.push("CpuRealtimeRuntime");
+    }
+    if !dropped.is_empty() {
+        warn!(
+            "Ignoring {} in the create options, which {} does not support.",
+            dropped.join(", "),
+            CgroupVersion::V2
+        );
+    }
+    host_config
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::fs;
+
+    #[cfg(unix)]
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn v1_stats() -> Value {
+        json!({
+            "cpu_stats": {
+                "cpu_usage": { "total_usage": 400, "percpu_usage": [200, 200] },
+                "system_cpu_usage": 2000
+            },
+            "precpu_stats": {
+                "cpu_usage": { "total_usage": 200, "percpu_usage": [100, 100] },
+                "system_cpu_usage": 1000
+            },
+            "memory_stats": {
+                "usage": 1000,
+                "limit": 4000,
+                "stats": { "total_inactive_file": 300, "cache": 400 }
+            }
+        })
+    }
+
+    fn v2_stats() -> Value {
+        json!({
+            "cpu_stats": {
+                "cpu_usage": { "total_usage": 400 },
+                "system_cpu_usage": 2000,
+                "online_cpus": 4
+            },
+            "precpu_stats": {
+                "cpu_usage": { "total_usage": 200 },
+                "system_cpu_usage": 1000,
+                "online_cpus": 4
+            },
+            "memory_stats": {
+                "usage": 1000,
+                "limit": 4000,
+                "stats": { "anon": 500, "file": 500, "inactive_file": 200 }
+            }
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cgroup_version_is_detected_from_the_mount() {
+        let root = tempdir().unwrap();
+        assert_eq!(
+            Some(CgroupVersion::V1),
+            CgroupVersion::detect_at(root.path())
+        );
+
+        fs::write(root.path().join(CGROUP_V2_CONTROLLERS), "cpu memory").unwrap();
+        assert_eq!(
+            Some(CgroupVersion::V2),
+            CgroupVersion::detect_at(root.path())
+        );
+
+        assert_eq!(
+            None,
+            CgroupVersion::detect_at(&root.path().join("nonexistent"))
+        );
+    }
+
+    #[test]
+    fn v1_stats_are_read() {
+        let stats = ModuleStats::from_docker(&v1_stats(), Some(CgroupVersion::V1));
+        assert!((stats.cpu_percent().unwrap() - 40.0).abs() < 1e-9);
+        assert_eq!(Some(700), stats.memory_usage());
+        assert_eq!(Some(4000), stats.memory_limit());
+    }
+
+    #[test]
+    fn v2_stats_are_read() {
+        let stats = ModuleStats::from_docker(&v2_stats(), Some(CgroupVersion::V2));
+        assert!((stats.cpu_percent().unwrap() - 80.0).abs() < 1e-9);
+        assert_eq!(Some(800), stats.memory_usage());
+        assert_eq!(Some(4000), stats.memory_limit());
+
+        let stats = ModuleStats::from_docker(&v2_stats(), None);
+        assert_eq!(Some(800), stats.memory_usage());
+    }
+
+    #[test]
+    fn missing_stats_are_empty() {
+        let stats = ModuleStats::from_docker(&json!({}), Some(CgroupVersion::V2));
+        assert_eq!(ModuleStats::default(), stats);
+    }
+
+    #[test]
+    fn limits_cgroup_v2_lacks_are_dropped() {
+        let host_config = HostConfig::new()
+            .with_memory(1024)
+            .with_kernel_memory(512)
+            .with_memory_swappiness(10);
+
+        let kept = supported_limits(host_config.clone(), Some(CgroupVersion::V1));
+        assert_eq!(Some(512), kept.kernel_memory());
+        assert_eq!(Some(10), kept.memory_swappiness());
+
+        let supported = supported_limits(host_config, Some(CgroupVersion::V2));
+        assert_eq!(Some(1024), supported.memory());
+        assert_eq!(None, supported.kernel_memory());
+        assert_eq!(None, supported.memory_swappiness());
+    }
+}
@@ -1,10 +1,34 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::fmt;
+
 use docker::models::{AuthConfig, ContainerCreateBody};
 use edgelet_utils::serde_clone;
 
+use egress::EgressPolicy;
 use error::Result;
 
+/// How a container is isolated from the host on Windows. Process isolated
+/// containers share the host's kernel, while Hyper-V isolated ones run in a
+/// utility VM of their own.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Isolation {
+    Process,
+    HyperV,
+}
+
+impl fmt::Display for Isolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the values docker takes for HostConfig.Isolation
+        let name = match *self {
+            Isolation::Process => "process",
+            Isolation::HyperV => "hyperv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DockerConfig {
@@ -16,6 +40,14 @@ pub struct DockerConfig {
     create_options: ContainerCreateBody,
     #[serde(skip_serializing_if = "Option::is_none")]
     auth: Option<AuthConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    isolation: Option<Isolation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image_digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    platform: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    egress: Option<EgressPolicy>,
 }
 
 impl DockerConfig {
@@ -29,6 +61,10 @@ impl DockerConfig {
             image_id: None,
             create_options,
             auth,
+            isolation: None,
+            image_digest: None,
+            platform: None,
+            egress: None,
         };
         Ok(config)
     }
@@ -76,6 +112,55 @@ impl DockerConfig {
         self.auth = Some(auth);
         self
     }
+
+    pub fn set_auth(&mut self, auth: AuthConfig) {
+        self.auth = Some(auth);
+    }
+
+    /// The isolation the module asks for. Isolation set in the create
+    /// options takes precedence.
+    pub fn isolation(&self) -> Option<Isolation> {
+        self.isolation
+    }
+
+    pub fn with_isolation(mut self, isolation: Isolation) -> Self {
+        self.isolation = Some(isolation);
+        self
+    }
+
+    /// The digest of the image the module was created from. For an image
+    /// pulled through a multi-arch manifest list, this is the list's digest
+    /// and the image ID tells the variant apart.
+    pub fn image_digest(&self) -> Option<&str> {
+        self.image_digest.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_image_digest(mut self, image_digest: String) -> Self {
+        self.image_digest = Some(image_digest);
+        self
+    }
+
+    /// The platform, as `os/architecture`, of the image the module was
+    /// created from.
+    pub fn platform(&self) -> Option<&str> {
+        self.platform.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_platform(mut self, platform: String) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+
+    /// The destinations the module may connect to, or `None` for a module
+    /// that may connect anywhere.
+    pub fn egress(&self) -> Option<&EgressPolicy> {
+        self.egress.as_ref()
+    }
+
+    pub fn with_egress(mut self, egress: EgressPolicy) -> Self {
+        self.egress = Some(egress);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -291,4 +376,41 @@ mod tests {
             "27017"
         );
     }
+
+    #[test]
+    fn docker_config_deser_isolation() {
+        let input_json = json!({
+            "image": "microsoft/nanoserver",
+            "isolation": "hyperv"
+        });
+        let config: DockerConfig = serde_json::from_str(&input_json.to_string()).unwrap();
+        assert_eq!(Some(Isolation::HyperV), config.isolation());
+        assert_eq!("hyperv", config.isolation().unwrap().to_string());
+
+        let config: DockerConfig = serde_json::from_str(r#"{"image": "ubuntu"}"#).unwrap();
+        assert_eq!(None, config.isolation());
+
+        let input_json = json!({
+            "image": "microsoft/nanoserver",
+            "isolation": "default"
+        });
+        assert!(serde_json::from_str::<DockerConfig>(&input_json.to_string()).is_err());
+    }
+
+    #[test]
+    fn docker_config_deser_egress() {
+        let input_json = json!({
+            "image": "opcua",
+            "egress": {
+                "allow": [{"destination": "10.1.0.0/16", "ports": [4840]}]
+            }
+        });
+        let config: DockerConfig = serde_json::from_str(&input_json.to_string()).unwrap();
+        assert_eq!("10.1.0.0/16", config.egress().unwrap().allow()[0].destination());
+        assert_eq!(&[4840], config.egress().unwrap().allow()[0].ports());
+
+        let config: DockerConfig = serde_json::from_str(r#"{"image": "ubuntu"}"#).unwrap();
+        assert!(config.egress().is_none());
+        assert!(serde_json::to_value(&config).unwrap().get("egress").is_none());
+    }
 }
@@ -0,0 +1,834 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Egress allowlists of modules.
+//!
+//! A module whose settings have an egress policy can only open connections
+//! to the destinations the policy allows, besides the networks its container
+//! is on. The policy is enforced with iptables on the docker host: every
+//! container with a policy gets a chain of its own, which the `DOCKER-USER`
+//! chain jumps to for the traffic from the container's addresses. The chain
+//! lets replies through, returns for the destinations that are allowed and
+//! drops everything else, and the packets it dropped are the violations of
+//! the policy.
+//!
+//! Containers with an IPv6 address get the same chain in ip6tables. Docker
+//! leaves no chain to users there, so the jumps are in an `IOTEDGE-EGRESS`
+//! chain of the daemon's own, which `FORWARD` jumps to.
+//!
+//! The chain and the jumps to it are replaced in one go with
+//! `iptables-restore`, so that the container's traffic is never let through
+//! by a chain that is half written. Host names are resolved every time the
+//! policy is enforced, and a host name that doesn't resolve is left out
+//! until it does.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use edgelet_core::Hostname;
+use edgelet_utils::log_failure;
+use failure::ResultExt;
+use log::Level;
+
+use error::{Error, ErrorKind, Result};
+
+/// The label the egress policy of a module is kept in on its container, as
+/// JSON.
+pub const EGRESS_LABEL_KEY: &str = "net.azure-devices.edge.egress";
+
+/// The chain docker leaves to users, which is gone through before docker's
+/// own rules for the traffic of containers.
+const DOCKER_USER_CHAIN: &str = "DOCKER-USER";
+
+/// The chain of the daemon's own in ip6tables, which `FORWARD` jumps to and
+/// which holds the jumps to the chains of containers.
+const IPV6_PARENT_CHAIN: &str = "IOTEDGE-EGRESS";
+
+/// The prefix of the chains of containers. Chain names can't be longer than
+/// 28 characters, which leaves 13 for the start of the container ID.
+const CHAIN_PREFIX: &str = "IOTEDGE-EGRESS-";
+const CHAIN_ID_LENGTH: usize = 13;
+
+/// The comment the jumps to the chain of a module's container are marked
+/// with, followed by the name of the module.
+const JUMP_COMMENT_PREFIX: &str = "iotedge-egress:";
+
+/// `multiport` matches no more than 15 ports at once.
+const MAX_MULTIPORT_PORTS: usize = 15;
+
+/// The version of IP a set of rules is for, each of which has its own
+/// iptables.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    fn of(address: &IpAddr) -> Self {
+        match *address {
+            IpAddr::V4(_) => IpFamily::V4,
+            IpAddr::V6(_) => IpFamily::V6,
+        }
+    }
+
+    fn iptables(self) -> &'static str {
+        match self {
+            IpFamily::V4 => "iptables",
+            IpFamily::V6 => "ip6tables",
+        }
+    }
+
+    fn iptables_restore(self) -> &'static str {
+        match self {
+            IpFamily::V4 => "iptables-restore",
+            IpFamily::V6 => "ip6tables-restore",
+        }
+    }
+
+    /// The chain that jumps to the chains of containers.
+    fn parent_chain(self) -> &'static str {
+        match self {
+            IpFamily::V4 => DOCKER_USER_CHAIN,
+            IpFamily::V6 => IPV6_PARENT_CHAIN,
+        }
+    }
+
+    /// The prefix length of a single address.
+    fn host_prefix(self) -> u8 {
+        match self {
+            IpFamily::V4 => 32,
+            IpFamily::V6 => 128,
+        }
+    }
+}
+
+/// The destinations a module may connect to.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct EgressPolicy {
+    #[serde(default)]
+    allow: Vec<EgressRule>,
+}
+
+impl EgressPolicy {
+    pub fn new() -> Self {
+        EgressPolicy::default()
+    }
+
+    pub fn with_rule(mut self, rule: EgressRule) -> Self {
+        self.allow.push(rule);
+        self
+    }
+
+    pub fn allow(&self) -> &[EgressRule] {
+        &self.allow
+    }
+
+    /// Fails on the first destination or port that can't be enforced, so
+    /// that a module with such a policy isn't created.
+    pub fn validate(&self) -> Result<()> {
+        for rule in &self.allow {
+            rule.parse_destination()?;
+            if rule.ports.contains(&0) {
+                return Err(Error::from(ErrorKind::InvalidEgressPolicy(format!(
+                    "port 0 of destination {} is not a port",
+                    rule.destination
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    // The networks, as address and prefix length, and ports the rules allow,
+    // with host names resolved to the addresses they have now.
+    fn resolve(&self) -> Result<Vec<(IpAddr, u8, &[u16])>> {
+        let mut resolved = vec![];
+        for rule in &self.allow {
+            match rule.parse_destination()? {
+                Destination::Network(address, prefix) => {
+                    resolved.push((address, prefix, &rule.ports[..]))
+                }
+                Destination::Host(host) => match resolve_host(host.as_str()) {
+                    Ok(addresses) => resolved.extend(addresses.into_iter().map(|address| {
+                        let prefix = IpFamily::of(&address).host_prefix();
+                        (address, prefix, &rule.ports[..])
+                    })),
+                    Err(err) => {
+                        warn!("Could not resolve {}, leaving it out for now.", host.as_str());
+                        log_failure(Level::Warn, &err);
+                    }
+                },
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+/// A destination a module may connect to: a host name, an IP address or an
+/// IP network in CIDR notation, on the ports of the rule or on any port when
+/// it has none.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EgressRule {
+    destination: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ports: Vec<u16>,
+}
+
+impl EgressRule {
+    pub fn new(destination: String) -> Self {
+        EgressRule {
+            destination,
+            ports: vec![],
+        }
+    }
+
+    pub fn with_ports(mut self, ports: Vec<u16>) -> Self {
+        self.ports = ports;
+        self
+    }
+
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    pub fn ports(&self) -> &[u16] {
+        &self.ports
+    }
+
+    fn parse_destination(&self) -> Result<Destination> {
+        let invalid = |reason: &str| {
+            Error::from(ErrorKind::InvalidEgressPolicy(format!(
+                "{} {}",
+                self.destination, reason
+            )))
+        };
+
+        let mut parts = self.destination.splitn(2, '/');
+        let address = parts.next().unwrap_or_default();
+        let prefix = parts.next();
+        match (address.parse::<IpAddr>(), prefix) {
+            (Ok(address), None) => {
+                let prefix = IpFamily::of(&address).host_prefix();
+                Ok(Destination::Network(address, prefix))
+            }
+            (Ok(address), Some(prefix)) => match prefix.parse::<u8>() {
+                Ok(prefix) if prefix <= IpFamily::of(&address).host_prefix() => {
+                    Ok(Destination::Network(address, prefix))
+                }
+                _ => Err(invalid("has an invalid prefix length")),
+            },
+            (Err(_), None) => Hostname::new(address)
+                .map(Destination::Host)
+                .map_err(|_| invalid("is not a host name, IP address or network")),
+            (Err(_), Some(_)) => Err(invalid("is not an IP network")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Destination {
+    Network(IpAddr, u8),
+    Host(Hostname),
+}
+
+fn resolve_host(host: &str) -> Result<Vec<IpAddr>> {
+    let addresses = (host, 0)
+        .to_socket_addrs()
+        .context(ErrorKind::EgressFirewall)?
+        .map(|address| address.ip())
+        .collect::<BTreeSet<_>>();
+    Ok(addresses.into_iter().collect())
+}
+
+/// What the policy of a module is enforced on: the container of the module
+/// and the networks it is on, as its address and the prefix length of the
+/// network.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EgressTarget {
+    module: String,
+    container_id: String,
+    networks: Vec<(IpAddr, u8)>,
+    policy: EgressPolicy,
+}
+
+impl EgressTarget {
+    pub fn new(
+        module: String,
+        container_id: String,
+        networks: Vec<(IpAddr, u8)>,
+        policy: EgressPolicy,
+    ) -> Self {
+        EgressTarget {
+            module,
+            container_id,
+            networks,
+            policy,
+        }
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    fn chain(&self) -> String {
+        let id = self
+            .container_id
+            .get(..CHAIN_ID_LENGTH)
+            .unwrap_or(&self.container_id);
+        format!("{}{}", CHAIN_PREFIX, id)
+    }
+
+    fn has_family(&self, family: IpFamily) -> bool {
+        self.networks
+            .iter()
+            .any(|&(ref address, _)| IpFamily::of(address) == family)
+    }
+}
+
+/// Runs the iptables commands of the firewall.
+pub trait Iptables {
+    /// Runs `program` with `args`, feeding it `input` if there is any, and
+    /// returns what it wrote out.
+    fn run(&self, program: &str, args: &[&str], input: Option<&str>) -> Result<String>;
+}
+
+/// Runs iptables on the host.
+pub struct IptablesCommand;
+
+impl Iptables for IptablesCommand {
+    fn run(&self, program: &str, args: &[&str], input: Option<&str>) -> Result<String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(ErrorKind::EgressFirewall)?;
+        if let Some(input) = input {
+            if let Some(stdin) = child.stdin.as_mut() {
+                stdin
+                    .write_all(input.as_bytes())
+                    .context(ErrorKind::EgressFirewall)?;
+            }
+        }
+        let output = child.wait_with_output().context(ErrorKind::EgressFirewall)?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            Err(Error::from(
+                format_err!("{} failed: {}", program, message).context(ErrorKind::EgressFirewall),
+            ))
+        }
+    }
+}
+
+/// Programs the iptables rules that enforce the egress policies of modules.
+/// Every clone programs the rules through the same `Iptables`, which is how
+/// the tests record the rules a runtime programs.
+#[derive(Clone)]
+pub struct EgressFirewall {
+    iptables: Arc<Iptables + Send + Sync>,
+}
+
+impl Default for EgressFirewall {
+    fn default() -> Self {
+        EgressFirewall::new(IptablesCommand)
+    }
+}
+
+impl EgressFirewall {
+    pub fn new<I: 'static + Iptables + Send + Sync>(iptables: I) -> Self {
+        EgressFirewall {
+            iptables: Arc::new(iptables),
+        }
+    }
+
+    /// Replaces the rules of the container of `target` with the rules of its
+    /// policy, and returns the number of packets the rules it replaced
+    /// dropped. The IPv6 rules are only programmed for containers with an
+    /// IPv6 address, which no IPv6 traffic can come from otherwise.
+    pub fn enforce(&self, target: &EgressTarget) -> Result<u64> {
+        let mut dropped = self.enforce_family(IpFamily::V4, target)?;
+        if target.has_family(IpFamily::V6) {
+            self.hook_ipv6()?;
+            dropped += self.enforce_family(IpFamily::V6, target)?;
+        } else {
+            self.remove_family(IpFamily::V6, &target.module)?;
+        }
+        Ok(dropped)
+    }
+
+    /// The modules there are rules for.
+    pub fn enforced(&self) -> Result<Vec<String>> {
+        let mut modules = BTreeSet::new();
+        for family in &[IpFamily::V4, IpFamily::V6] {
+            modules.extend(self.jumps(*family)?.into_iter().map(|jump| jump.module));
+        }
+        Ok(modules.into_iter().collect())
+    }
+
+    /// Removes the rules of the containers of `module`.
+    pub fn remove(&self, module: &str) -> Result<()> {
+        self.remove_family(IpFamily::V4, module)?;
+        self.remove_family(IpFamily::V6, module)
+    }
+
+    fn enforce_family(&self, family: IpFamily, target: &EgressTarget) -> Result<u64> {
+        let chain = target.chain();
+        // the chain doesn't exist before the policy is first enforced
+        let dropped = self
+            .iptables
+            .run(family.iptables(), &["-w", "-L", &chain, "-v", "-x", "-n"], None)
+            .map(|listing| dropped_packets(&listing))
+            .unwrap_or(0);
+
+        let jumps = self.jumps(family)?;
+        let mut input = format!("*filter\n:{} - [0:0]\n", chain);
+        for rule in chain_rules(family, &chain, target)? {
+            input.push_str(&rule);
+            input.push('\n');
+        }
+        for jump in jumps.iter().filter(|jump| jump.module == target.module) {
+            input.push_str(&format!("-D {}\n", jump.rule));
+        }
+        for &(address, _) in &target.networks {
+            if IpFamily::of(&address) != family {
+                continue;
+            }
+            input.push_str(&format!(
+                "-I {} -s {}/{} -m comment --comment \"{}{}\" -j {}\n",
+                family.parent_chain(),
+                address,
+                family.host_prefix(),
+                JUMP_COMMENT_PREFIX,
+                target.module,
+                chain
+            ));
+        }
+        input.push_str("COMMIT\n");
+
+        self.iptables
+            .run(family.iptables_restore(), &["-w", "--noflush"], Some(&input))?;
+        Ok(dropped)
+    }
+
+    fn remove_family(&self, family: IpFamily, module: &str) -> Result<()> {
+        let jumps: Vec<_> = self
+            .jumps(family)?
+            .into_iter()
+            .filter(|jump| jump.module == module)
+            .collect();
+        if jumps.is_empty() {
+            return Ok(());
+        }
+
+        let mut input = "*filter\n".to_string();
+        let mut chains = BTreeSet::new();
+        for jump in &jumps {
+            input.push_str(&format!("-D {}\n", jump.rule));
+            chains.insert(jump.chain.as_str());
+        }
+        for chain in chains {
+            input.push_str(&format!("-F {}\n-X {}\n", chain, chain));
+        }
+        input.push_str("COMMIT\n");
+
+        self.iptables
+            .run(family.iptables_restore(), &["-w", "--noflush"], Some(&input))?;
+        Ok(())
+    }
+
+    // Creates the chain the jumps of IPv6 are in, and the jump to it from
+    // `FORWARD`, unless they exist.
+    fn hook_ipv6(&self) -> Result<()> {
+        let ip6tables = IpFamily::V6.iptables();
+        if self
+            .iptables
+            .run(ip6tables, &["-w", "-S", IPV6_PARENT_CHAIN], None)
+            .is_err()
+        {
+            self.iptables
+                .run(ip6tables, &["-w", "-N", IPV6_PARENT_CHAIN], None)?;
+        }
+        let jump = ["-w", "-C", "FORWARD", "-j", IPV6_PARENT_CHAIN];
+        if self.iptables.run(ip6tables, &jump, None).is_err() {
+            self.iptables
+                .run(ip6tables, &["-w", "-I", "FORWARD", "-j", IPV6_PARENT_CHAIN], None)?;
+        }
+        Ok(())
+    }
+
+    // The IPv6 chain only exists once a container with an IPv6 address had
+    // its policy enforced, and there is nothing to list before then.
+    fn jumps(&self, family: IpFamily) -> Result<Vec<Jump>> {
+        let rules = self
+            .iptables
+            .run(family.iptables(), &["-w", "-S", family.parent_chain()], None);
+        let rules = match (family, rules) {
+            (_, Ok(rules)) => rules,
+            (IpFamily::V6, Err(_)) => String::new(),
+            (IpFamily::V4, Err(err)) => return Err(err),
+        };
+        Ok(rules.lines().filter_map(Jump::parse).collect())
+    }
+}
+
+/// A jump from `DOCKER-USER`, or `IOTEDGE-EGRESS` of ip6tables, to the chain
+/// of a module's container.
+#[derive(Debug, PartialEq)]
+struct Jump {
+    module: String,
+    chain: String,
+    /// The rule without the `-A`, as `iptables -S` lists it.
+    rule: String,
+}
+
+impl Jump {
+    fn parse(line: &str) -> Option<Self> {
+        let rule = line.trim();
+        if !rule.starts_with("-A ") {
+            return None;
+        }
+        let tokens: Vec<&str> = rule.split_whitespace().collect();
+        let after = |flag: &str| {
+            tokens
+                .iter()
+                .position(|token| *token == flag)
+                .and_then(|i| tokens.get(i + 1))
+                .map(|token| token.trim_matches('"'))
+        };
+        let module = after("--comment")
+            .filter(|comment| comment.starts_with(JUMP_COMMENT_PREFIX))?
+            .trim_left_matches(JUMP_COMMENT_PREFIX);
+        let chain = after("-j").filter(|chain| chain.starts_with(CHAIN_PREFIX))?;
+        Some(Jump {
+            module: module.to_string(),
+            chain: chain.to_string(),
+            rule: rule[3..].to_string(),
+        })
+    }
+}
+
+// Replies and traffic to the networks of the container go through, then the
+// destinations of the policy, and everything else is dropped. Only the
+// networks and destinations of `family` are in its chain.
+fn chain_rules(family: IpFamily, chain: &str, target: &EgressTarget) -> Result<Vec<String>> {
+    let mut rules = vec![format!(
+        "-A {} -m conntrack --ctstate RELATED,ESTABLISHED -j RETURN",
+        chain
+    )];
+    for &(address, prefix) in &target.networks {
+        if IpFamily::of(&address) != family {
+            continue;
+        }
+        rules.push(format!(
+            "-A {} -d {}/{} -j RETURN",
+            chain,
+            network_address(address, prefix),
+            prefix
+        ));
+    }
+    for (address, prefix, ports) in target.policy.resolve()? {
+        if IpFamily::of(&address) != family {
+            continue;
+        }
+        let destination = format!("{}/{}", network_address(address, prefix), prefix);
+        if ports.is_empty() {
+            rules.push(format!("-A {} -d {} -j RETURN", chain, destination));
+        }
+        for ports in ports.chunks(MAX_MULTIPORT_PORTS) {
+            let ports = ports
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            for protocol in &["tcp", "udp"] {
+                rules.push(format!(
+                    "-A {} -d {} -p {} -m multiport --dports {} -j RETURN",
+                    chain, destination, protocol, ports
+                ));
+            }
+        }
+    }
+    rules.push(format!("-A {} -j DROP", chain));
+    Ok(rules)
+}
+
+fn network_address(address: IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(address) => {
+            let mask = u32::max_value()
+                .checked_shl(32 - u32::from(prefix))
+                .unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(address) & mask))
+        }
+        IpAddr::V6(address) => {
+            let mask = u128::max_value()
+                .checked_shl(128 - u32::from(prefix))
+                .unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(address) & mask))
+        }
+    }
+}
+
+// The packets counted by the DROP rule at the end of a chain, as
+// `iptables -L -v -x -n` lists them.
+fn dropped_packets(listing: &str) -> u64 {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let packets = columns.next()?.parse::<u64>().ok()?;
+            let _bytes = columns.next()?;
+            match columns.next() {
+                Some("DROP") => Some(packets),
+                _ => None,
+            }
+        }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        rules: String,
+        rules6: String,
+        listing: String,
+        runs: Arc<Mutex<Vec<(String, Option<String>)>>>,
+    }
+
+    impl Iptables for Recorder {
+        fn run(&self, program: &str, args: &[&str], input: Option<&str>) -> Result<String> {
+            let command = format!("{} {}", program, args.join(" "));
+            self.runs
+                .lock()
+                .unwrap()
+                .push((command, input.map(ToString::to_string)));
+            match args.get(1) {
+                Some(&"-S") if program.starts_with("ip6tables") => Ok(self.rules6.clone()),
+                Some(&"-S") => Ok(self.rules.clone()),
+                Some(&"-L") => Ok(self.listing.clone()),
+                _ => Ok(String::new()),
+            }
+        }
+    }
+
+    // The input of every iptables-restore run by `program`.
+    fn restored(iptables: &Recorder, program: &str) -> Vec<String> {
+        iptables
+            .runs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|&&(ref command, _)| command.starts_with(program))
+            .filter_map(|&(_, ref input)| input.clone())
+            .collect()
+    }
+
+    fn target(policy: EgressPolicy) -> EgressTarget {
+        EgressTarget::new(
+            "opcua".to_string(),
+            "0123456789abcdef0123".to_string(),
+            vec![("172.18.0.5".parse().unwrap(), 16)],
+            policy,
+        )
+    }
+
+    #[test]
+    fn destinations_are_hosts_addresses_or_networks() {
+        let destination =
+            |destination: &str| EgressRule::new(destination.to_string()).parse_destination();
+        assert_eq!(
+            Destination::Network("10.1.0.0".parse().unwrap(), 16),
+            destination("10.1.0.0/16").unwrap()
+        );
+        assert_eq!(
+            Destination::Network("10.1.2.3".parse().unwrap(), 32),
+            destination("10.1.2.3").unwrap()
+        );
+        assert_eq!(
+            Destination::Host(Hostname::new("plc.plant.local").unwrap()),
+            destination("plc.plant.local").unwrap()
+        );
+        assert_eq!(
+            Destination::Network("fd00:1::".parse().unwrap(), 64),
+            destination("fd00:1::/64").unwrap()
+        );
+        assert_eq!(
+            Destination::Network("fe80::1".parse().unwrap(), 128),
+            destination("fe80::1").unwrap()
+        );
+
+        for invalid in &["10.1.0.0/33", "10.1.0.0/", "fd00::/129", "not a host", "plc/24", ""] {
+            match *destination(invalid).unwrap_err().kind() {
+                ErrorKind::InvalidEgressPolicy(_) => (),
+                ref kind => panic!("unexpected error kind {:?}", kind),
+            }
+        }
+
+        let policy = EgressPolicy::new()
+            .with_rule(EgressRule::new("10.1.0.0/16".to_string()).with_ports(vec![0]));
+        assert!(policy.validate().is_err());
+    }
+
+    #[test]
+    fn policies_are_read_from_module_settings() {
+        let policy: EgressPolicy = serde_json::from_str(
+            r#"{"allow": [{"destination": "10.1.0.0/16", "ports": [4840]},
+                          {"destination": "plc.plant.local"}]}"#,
+        ).unwrap();
+        assert_eq!(
+            EgressPolicy::new()
+                .with_rule(EgressRule::new("10.1.0.0/16".to_string()).with_ports(vec![4840]))
+                .with_rule(EgressRule::new("plc.plant.local".to_string())),
+            policy
+        );
+        assert!(policy.validate().is_ok());
+    }
+
+    #[test]
+    fn chains_allow_replies_networks_and_destinations_then_drop() {
+        let policy = EgressPolicy::new()
+            .with_rule(EgressRule::new("10.1.2.3/16".to_string()).with_ports(vec![4840, 502]))
+            .with_rule(EgressRule::new("192.168.1.10".to_string()));
+        let rules =
+            chain_rules(IpFamily::V4, "IOTEDGE-EGRESS-0123456789abc", &target(policy)).unwrap();
+        assert_eq!(
+            vec![
+                "-A IOTEDGE-EGRESS-0123456789abc -m conntrack --ctstate RELATED,ESTABLISHED \
+                 -j RETURN",
+                "-A IOTEDGE-EGRESS-0123456789abc -d 172.18.0.0/16 -j RETURN",
+                "-A IOTEDGE-EGRESS-0123456789abc -d 10.1.0.0/16 -p tcp -m multiport \
+                 --dports 4840,502 -j RETURN",
+                "-A IOTEDGE-EGRESS-0123456789abc -d 10.1.0.0/16 -p udp -m multiport \
+                 --dports 4840,502 -j RETURN",
+                "-A IOTEDGE-EGRESS-0123456789abc -d 192.168.1.10/32 -j RETURN",
+                "-A IOTEDGE-EGRESS-0123456789abc -j DROP",
+            ],
+            rules
+        );
+    }
+
+    #[test]
+    fn enforcing_replaces_the_chain_and_jumps_at_once() {
+        let iptables = Recorder {
+            rules: "-N DOCKER-USER\n\
+                    -A DOCKER-USER -s 172.18.0.4/32 -m comment --comment \"iotedge-egress:opcua\" \
+                    -j IOTEDGE-EGRESS-0123456789abc\n\
+                    -A DOCKER-USER -s 172.18.0.6/32 -m comment --comment iotedge-egress:other \
+                    -j IOTEDGE-EGRESS-fedcba9876543\n\
+                    -A DOCKER-USER -j RETURN\n"
+                .to_string(),
+            listing: "Chain IOTEDGE-EGRESS-0123456789abc (1 references)\n\
+                      \x20   pkts      bytes target     prot opt in out source    destination\n\
+                      \x20     10      600 RETURN     all  --  *      *       0.0.0.0/0 0.0.0.0/0\n\
+                      \x20      7      420 DROP       all  --  *      *       0.0.0.0/0 0.0.0.0/0\n"
+                .to_string(),
+            ..Recorder::default()
+        };
+        let firewall = EgressFirewall::new(iptables.clone());
+
+        let dropped = firewall.enforce(&target(EgressPolicy::new())).unwrap();
+        assert_eq!(7, dropped);
+
+        // there are no IPv6 rules for a container without an IPv6 address
+        assert!(restored(&iptables, "ip6tables-restore").is_empty());
+        assert_eq!(
+            vec![
+                "*filter\n\
+                 :IOTEDGE-EGRESS-0123456789abc - [0:0]\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -m conntrack --ctstate RELATED,ESTABLISHED \
+                 -j RETURN\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -d 172.18.0.0/16 -j RETURN\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -j DROP\n\
+                 -D DOCKER-USER -s 172.18.0.4/32 -m comment --comment \"iotedge-egress:opcua\" \
+                 -j IOTEDGE-EGRESS-0123456789abc\n\
+                 -I DOCKER-USER -s 172.18.0.5/32 -m comment --comment \"iotedge-egress:opcua\" \
+                 -j IOTEDGE-EGRESS-0123456789abc\n\
+                 COMMIT\n",
+            ],
+            restored(&iptables, "iptables-restore -w --noflush")
+        );
+    }
+
+    #[test]
+    fn ipv6_rules_are_enforced_in_their_own_chain() {
+        let iptables = Recorder::default();
+        let firewall = EgressFirewall::new(iptables.clone());
+        let policy = EgressPolicy::new()
+            .with_rule(EgressRule::new("fd00:1::5/64".to_string()).with_ports(vec![4840]))
+            .with_rule(EgressRule::new("10.1.2.3".to_string()));
+        let target = EgressTarget::new(
+            "opcua".to_string(),
+            "0123456789abcdef0123".to_string(),
+            vec![
+                ("172.18.0.5".parse().unwrap(), 16),
+                ("fd00:18::5".parse().unwrap(), 64),
+            ],
+            policy,
+        );
+        firewall.enforce(&target).unwrap();
+
+        assert_eq!(
+            vec![
+                "*filter\n\
+                 :IOTEDGE-EGRESS-0123456789abc - [0:0]\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -m conntrack --ctstate RELATED,ESTABLISHED \
+                 -j RETURN\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -d fd00:18::/64 -j RETURN\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -d fd00:1::/64 -p tcp -m multiport \
+                 --dports 4840 -j RETURN\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -d fd00:1::/64 -p udp -m multiport \
+                 --dports 4840 -j RETURN\n\
+                 -A IOTEDGE-EGRESS-0123456789abc -j DROP\n\
+                 -I IOTEDGE-EGRESS -s fd00:18::5/128 -m comment \
+                 --comment \"iotedge-egress:opcua\" -j IOTEDGE-EGRESS-0123456789abc\n\
+                 COMMIT\n",
+            ],
+            restored(&iptables, "ip6tables-restore")
+        );
+        // the IPv4 chain has none of the IPv6 destinations
+        assert!(!restored(&iptables, "iptables-restore")[0].contains("fd00"));
+    }
+
+    #[test]
+    fn rules_are_removed_by_module() {
+        let iptables = Recorder {
+            rules: "-A DOCKER-USER -s 172.18.0.4/32 -m comment --comment iotedge-egress:opcua \
+                    -j IOTEDGE-EGRESS-0123456789abc\n\
+                    -A DOCKER-USER -s 172.18.0.6/32 -m comment --comment iotedge-egress:other \
+                    -j IOTEDGE-EGRESS-fedcba9876543\n"
+                .to_string(),
+            ..Recorder::default()
+        };
+        let firewall = EgressFirewall::new(iptables.clone());
+        assert_eq!(vec!["opcua", "other"], firewall.enforced().unwrap());
+
+        firewall.remove("other").unwrap();
+        assert_eq!(
+            vec![
+                "*filter\n\
+                 -D DOCKER-USER -s 172.18.0.6/32 -m comment --comment iotedge-egress:other \
+                 -j IOTEDGE-EGRESS-fedcba9876543\n\
+                 -F IOTEDGE-EGRESS-fedcba9876543\n\
+                 -X IOTEDGE-EGRESS-fedcba9876543\n\
+                 COMMIT\n",
+            ],
+            restored(&iptables, "iptables-restore")
+        );
+
+        // only the rules are listed when there is nothing to remove
+        let runs = iptables.runs.lock().unwrap().len();
+        firewall.remove("sensor").unwrap();
+        assert_eq!(runs + 2, iptables.runs.lock().unwrap().len());
+    }
+}
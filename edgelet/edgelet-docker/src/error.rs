@@ -9,10 +9,12 @@ use serde_json;
 use url::ParseError;
 
 use docker::apis::{ApiError as DockerApiError, Error as DockerError};
-use edgelet_core::{Error as CoreError, ErrorKind as CoreErrorKind};
-use edgelet_http::Error as HttpError;
+use edgelet_core::{error_category, Error as CoreError, ErrorCategory, ErrorKind as CoreErrorKind};
+use edgelet_http::{classify_error as classify_http_error, Error as HttpError};
 use edgelet_utils::Error as UtilsError;
 
+use registry::RegistryAuthFailure;
+
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 #[derive(Debug)]
@@ -68,8 +70,67 @@ pub enum ErrorKind {
     DockerRuntime(DockerError<serde_json::Value>),
     #[fail(display = "Core error")]
     Core,
+    #[fail(
+        display = "Image architecture {} does not match host {}",
+        _0, _1
+    )]
+    ImageArchitectureMismatch(String, String),
+    #[fail(display = "{} isolation is not supported on {} hosts", _0, _1)]
+    IsolationNotSupported(String, String),
+    #[fail(
+        display = "Updated module {} did not keep running during its soak period",
+        _0
+    )]
+    CanaryUnhealthy(String),
+    #[fail(
+        display = "Could not authenticate to the registry of image {}: {}",
+        _0, _1
+    )]
+    RegistryAuthentication(String, RegistryAuthFailure),
     #[fail(display = "Http error")]
     Http,
+    #[fail(display = "Invalid egress policy: {}", _0)]
+    InvalidEgressPolicy(String),
+    #[fail(display = "Could not program the egress firewall")]
+    EgressFirewall,
+}
+
+impl ErrorKind {
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// leave it to their cause.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::InvalidDockerUri(_)
+            | ErrorKind::InvalidUdsUri(_)
+            | ErrorKind::ImageArchitectureMismatch(_, _)
+            | ErrorKind::IsolationNotSupported(_, _)
+            | ErrorKind::RegistryAuthentication(_, _)
+            | ErrorKind::InvalidEgressPolicy(_) => Some(ErrorCategory::UserConfig),
+            ErrorKind::Transport => Some(ErrorCategory::TransientNetwork),
+            ErrorKind::NotFound(_)
+            | ErrorKind::Conflict
+            | ErrorKind::NotModified
+            | ErrorKind::Docker
+            | ErrorKind::FormattedDockerRuntime(_)
+            | ErrorKind::DockerRuntime(_)
+            | ErrorKind::CanaryUnhealthy(_)
+            | ErrorKind::EgressFirewall => Some(ErrorCategory::Runtime),
+            ErrorKind::Utils
+            | ErrorKind::Serde
+            | ErrorKind::UrlParse
+            | ErrorKind::Core
+            | ErrorKind::Http => None,
+        }
+    }
+}
+
+/// Categorizes the errors of this crate, and falls back to
+/// `edgelet_http::classify_error`.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    match error.downcast_ref::<Error>() {
+        Some(error) => error.kind().category(),
+        None => classify_http_error(error),
+    }
 }
 
 impl Fail for Error {
@@ -92,6 +153,10 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
 }
 
 impl From<ErrorKind> for Error {
@@ -171,6 +236,14 @@ impl From<ParseError> for Error {
     }
 }
 
+impl From<CoreError> for Error {
+    fn from(error: CoreError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Core),
+        }
+    }
+}
+
 impl From<Error> for CoreError {
     fn from(err: Error) -> Self {
         CoreError::from(err.context(CoreErrorKind::ModuleRuntime))
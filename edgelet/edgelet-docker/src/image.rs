@@ -0,0 +1,157 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use docker::models::Image;
+
+/// The labels a module's container is created with to record the image it
+/// runs.
+pub const DIGEST_LABEL_KEY: &str = "net.azure-devices.edge.image-digest";
+pub const PLATFORM_LABEL_KEY: &str = "net.azure-devices.edge.platform";
+
+/// Stands for the registry of the parent gateway in the image names of a
+/// nested device, such as `$upstream:443/azureiotedge-agent:1.0`.
+pub const UPSTREAM_REGISTRY: &str = "$upstream";
+
+/// What an image name resolved to on this host.
+///
+/// An image pulled through a multi-arch manifest list is recorded under the
+/// list's digest, whichever of its variants was pulled, so the digest alone
+/// doesn't say which variant a device runs. The image ID, which is the digest
+/// of the variant's configuration, does, and the platform says which one it
+/// is.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedImage {
+    image: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<String>,
+    image_id: String,
+    platform: String,
+}
+
+impl ResolvedImage {
+    pub fn new(image: &str, inspected: &Image) -> Self {
+        ResolvedImage {
+            image: image.to_string(),
+            digest: inspected
+                .repo_digests()
+                .and_then(|digests| repo_digest(image, digests)),
+            image_id: inspected.id().clone(),
+            platform: format!("{}/{}", inspected.os(), inspected.architecture()),
+        }
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// The digest the image was pulled by, if it was pulled from a registry.
+    pub fn digest(&self) -> Option<&str> {
+        self.digest.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn image_id(&self) -> &str {
+        &self.image_id
+    }
+
+    /// The platform, as `os/architecture`, the image was built for.
+    pub fn platform(&self) -> &str {
+        &self.platform
+    }
+}
+
+// Picks the digest of the repository `image` names out of the repo digests of
+// an image, which has one for every repository it was pulled from.
+fn repo_digest(image: &str, repo_digests: &[String]) -> Option<String> {
+    let repository = repository(image);
+    let digest = |repo_digest: &String| {
+        let mut parts = repo_digest.splitn(2, '@');
+        match (parts.next(), parts.next()) {
+            (Some(repo), Some(digest)) => Some((repo.to_string(), digest.to_string())),
+            _ => None,
+        }
+    };
+
+    let digests: Vec<(String, String)> = repo_digests.iter().filter_map(digest).collect();
+    digests
+        .iter()
+        .find(|(repo, _)| repo == repository)
+        .or_else(|| digests.first())
+        .map(|(_, digest)| digest.clone())
+}
+
+// The repository of an image name, without its tag or digest. A colon is only
+// a tag separator after the last slash, since a registry can have a port.
+pub fn repository(image: &str) -> &str {
+    let image = image.split('@').next().unwrap_or(image);
+    let name_start = image.rfind('/').map_or(0, |i| i + 1);
+    match image[name_start..].find(':') {
+        Some(i) => &image[..name_start + i],
+        None => image,
+    }
+}
+
+// The image `image` names on a device whose parent gateway, if it is nested,
+// is `parent_hostname`. The registry proxy of the parent stands in for
+// `$upstream`.
+pub fn upstream_image(image: &str, parent_hostname: Option<&str>) -> String {
+    match parent_hostname {
+        Some(parent_hostname) if image.starts_with(UPSTREAM_REGISTRY) => {
+            format!("{}{}", parent_hostname, &image[UPSTREAM_REGISTRY.len()..])
+        }
+        _ => image.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repositories_drop_tags_and_digests() {
+        assert_eq!("ubuntu", repository("ubuntu"));
+        assert_eq!("ubuntu", repository("ubuntu:18.04"));
+        assert_eq!(
+            "mcr.microsoft.com/azureiotedge-agent",
+            repository("mcr.microsoft.com/azureiotedge-agent:1.0")
+        );
+        assert_eq!(
+            "localhost:5000/sensor",
+            repository("localhost:5000/sensor:1.0")
+        );
+        assert_eq!("localhost:5000/sensor", repository("localhost:5000/sensor"));
+        assert_eq!("sensor", repository("sensor@sha256:abc"));
+    }
+
+    #[test]
+    fn repo_digest_of_the_named_repository_is_picked() {
+        let digests = vec![
+            "other.azurecr.io/sensor@sha256:111".to_string(),
+            "mcr.microsoft.com/azureiotedge-agent@sha256:222".to_string(),
+        ];
+        assert_eq!(
+            Some("sha256:222".to_string()),
+            repo_digest("mcr.microsoft.com/azureiotedge-agent:1.0", &digests)
+        );
+        assert_eq!(
+            Some("sha256:111".to_string()),
+            repo_digest("retagged:latest", &digests)
+        );
+        assert_eq!(None, repo_digest("ubuntu", &[]));
+    }
+
+    #[test]
+    fn upstream_images_are_pulled_from_the_parent() {
+        assert_eq!(
+            "parent:443/azureiotedge-agent:1.0",
+            upstream_image("$upstream:443/azureiotedge-agent:1.0", Some("parent"))
+        );
+        assert_eq!(
+            "mcr.microsoft.com/azureiotedge-agent:1.0",
+            upstream_image("mcr.microsoft.com/azureiotedge-agent:1.0", Some("parent"))
+        );
+        assert_eq!(
+            "$upstream:443/azureiotedge-agent:1.0",
+            upstream_image("$upstream:443/azureiotedge-agent:1.0", None)
+        );
+    }
+}
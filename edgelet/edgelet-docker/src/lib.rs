@@ -27,15 +27,14 @@ extern crate serde;
 // Need stuff other than macros from serde_json for non-test code.
 #[cfg(not(test))]
 extern crate serde_json;
-#[cfg(test)]
 extern crate tokio;
+extern crate tokio_threadpool;
 extern crate url;
 
 // Need macros from serde_json for unit tests.
 #[cfg(test)]
 #[macro_use]
 extern crate serde_json;
-#[cfg(unix)]
 #[cfg(test)]
 extern crate tempfile;
 #[cfg(test)]
@@ -50,14 +49,24 @@ extern crate edgelet_utils;
 #[cfg(test)]
 extern crate edgelet_test_utils;
 
+mod cgroup;
 mod client;
 mod config;
+mod egress;
 mod error;
+mod image;
 mod module;
+mod registry;
 mod runtime;
 
-pub use config::DockerConfig;
-pub use error::{Error, ErrorKind};
-pub use module::{DockerModule, MODULE_TYPE};
+pub use cgroup::{CgroupVersion, ModuleStats};
+pub use config::{DockerConfig, Isolation};
+pub use egress::{
+    EgressFirewall, EgressPolicy, EgressRule, EgressTarget, Iptables, IptablesCommand,
+};
+pub use error::{classify_error, Error, ErrorKind};
+pub use image::ResolvedImage;
+pub use module::{DockerModule, MODULE_LABEL_PREFIX, MODULE_TYPE, NAMESPACE_LABEL_KEY};
+pub use registry::{AuthFailureRecord, RegistryAuthFailure};
 
-pub use runtime::DockerModuleRuntime;
+pub use runtime::{DockerModuleRuntime, TRUST_BUNDLE_PATH_KEY};
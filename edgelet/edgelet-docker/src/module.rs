@@ -1,5 +1,6 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use chrono::prelude::*;
@@ -8,13 +9,93 @@ use hyper::client::connect::Connect;
 
 use client::DockerClient;
 use config::DockerConfig;
+use docker::models::{InlineResponse200State, RestartPolicy as DockerRestartPolicy};
 use edgelet_core::pid::Pid;
-use edgelet_core::{Module, ModuleRuntimeState, ModuleStatus};
+use edgelet_core::{
+    ExitReason, Module, ModulePriority, ModuleRuntimeState, ModuleStatus, RestartPolicy,
+};
 use error::{Error, Result};
 
 pub const MODULE_TYPE: &str = "docker";
 pub const MIN_DATE: &str = "0001-01-01T00:00:00Z";
 
+/// The label of the containers, and networks, of modules deployed into a
+/// namespace, which holds the namespace.
+pub const NAMESPACE_LABEL_KEY: &str = "net.azure-devices.edge.namespace";
+
+/// The label of the containers of modules created with a restart policy,
+/// which holds the policy.
+pub const RESTART_POLICY_LABEL_KEY: &str = "net.azure-devices.edge.restart-policy";
+
+/// The label of the containers that stand in for another module, such as the
+/// canary of a module being updated, which holds the name of that module.
+pub const IDENTITY_LABEL_KEY: &str = "net.azure-devices.edge.identity";
+
+/// The label of the containers of modules, which holds the priority of the
+/// module.
+pub const PRIORITY_LABEL_KEY: &str = "net.azure-devices.edge.priority";
+
+/// The prefix of the labels of the containers of modules that hold the
+/// labels the deployment attached to the module, such that module label
+/// `tier=web` is container label `net.azure-devices.edge.label.tier=web`.
+pub const MODULE_LABEL_PREFIX: &str = "net.azure-devices.edge.label.";
+
+/// The docker restart policy that carries out a restart policy. Docker can't
+/// tell whether a module is healthy, so the heartbeat monitor restarts the
+/// unhealthy modules whose policy asks for it.
+pub fn docker_restart_policy(policy: RestartPolicy) -> &'static str {
+    match policy {
+        RestartPolicy::Always => "always",
+        RestartPolicy::OnUnhealthy | RestartPolicy::OnFailure => "on-failure",
+        RestartPolicy::Never => "no",
+    }
+}
+
+/// The restart policy the labels of a container say its module was created
+/// with, if any.
+pub fn labeled_restart_policy(labels: Option<&HashMap<String, String>>) -> Option<RestartPolicy> {
+    labels
+        .and_then(|labels| labels.get(RESTART_POLICY_LABEL_KEY))
+        .and_then(|policy| RestartPolicy::from_str(policy).ok())
+}
+
+/// The labels of a module, from the labels of its container.
+pub fn module_labels(labels: Option<&HashMap<String, String>>) -> HashMap<String, String> {
+    labels
+        .map(|labels| {
+            labels
+                .iter()
+                .filter(|&(key, _)| key.starts_with(MODULE_LABEL_PREFIX))
+                .map(|(key, value)| (key[MODULE_LABEL_PREFIX.len()..].to_string(), value.clone()))
+                .collect()
+        }).unwrap_or_else(HashMap::new)
+}
+
+/// Describes how the restart policy docker has for a container differs from
+/// the one the container's labels say its module was created with. There is
+/// no drift for containers created without a restart policy.
+pub fn restart_policy_drift(
+    labels: Option<&HashMap<String, String>>,
+    actual: Option<&DockerRestartPolicy>,
+) -> Option<String> {
+    let policy = labeled_restart_policy(labels)?;
+    let expected = docker_restart_policy(policy);
+    // docker reports containers created without a restart policy as having
+    // an empty one
+    let actual = actual
+        .and_then(DockerRestartPolicy::name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or("no");
+    if actual == expected {
+        None
+    } else {
+        Some(format!(
+            "restart policy {} needs docker restart policy {}, but the container has {}",
+            policy, expected, actual
+        ))
+    }
+}
+
 pub struct DockerModule<C: Connect> {
     client: DockerClient<C>,
     name: String,
@@ -41,6 +122,21 @@ fn status_from_exit_code(exit_code: Option<i64>) -> Option<ModuleStatus> {
     })
 }
 
+// Only containers that have exited have a reason for it. Containers that are
+// restarting have exited too, and are about to run again.
+fn exit_reason(state: &InlineResponse200State) -> Option<ExitReason> {
+    match state.status() {
+        Some("exited") | Some("dead") | Some("restarting") => state.exit_code().map(|code| {
+            ExitReason::new(
+                code,
+                state.oom_killed().map_or(false, |oom_killed| *oom_killed),
+                state.error(),
+            )
+        }),
+        _ => None,
+    }
+}
+
 impl<C: 'static + Connect> Module for DockerModule<C> {
     type Config = DockerConfig;
     type Error = Error;
@@ -58,12 +154,51 @@ impl<C: 'static + Connect> Module for DockerModule<C> {
         &self.config
     }
 
+    fn namespace(&self) -> Option<&str> {
+        self.config
+            .create_options()
+            .labels()
+            .and_then(|labels| labels.get(NAMESPACE_LABEL_KEY))
+            .map(AsRef::as_ref)
+    }
+
+    fn restart_policy(&self) -> Option<RestartPolicy> {
+        labeled_restart_policy(self.config.create_options().labels())
+    }
+
+    // Containers created before modules had a priority have the default one.
+    fn priority(&self) -> ModulePriority {
+        self.config
+            .create_options()
+            .labels()
+            .and_then(|labels| labels.get(PRIORITY_LABEL_KEY))
+            .and_then(|priority| ModulePriority::from_str(priority).ok())
+            .unwrap_or_default()
+    }
+
+    fn identity(&self) -> &str {
+        self.config
+            .create_options()
+            .labels()
+            .and_then(|labels| labels.get(IDENTITY_LABEL_KEY))
+            .map_or(self.name.as_str(), AsRef::as_ref)
+    }
+
+    fn labels(&self) -> HashMap<String, String> {
+        module_labels(self.config.create_options().labels())
+    }
+
     fn runtime_state(&self) -> Self::RuntimeStateFuture {
         Box::new(
             self.client
                 .container_api()
                 .container_inspect(&self.name, false)
                 .map(|resp| {
+                    let drift = restart_policy_drift(
+                        resp.config().and_then(|config| config.labels()),
+                        resp.host_config()
+                            .and_then(|host_config| host_config.restart_policy()),
+                    );
                     resp.state()
                         .map_or_else(ModuleRuntimeState::default, |state| {
                             let status = state
@@ -81,6 +216,7 @@ impl<C: 'static + Connect> Module for DockerModule<C> {
                             ModuleRuntimeState::default()
                                 .with_status(status)
                                 .with_exit_code(state.exit_code())
+                                .with_exit_reason(exit_reason(state))
                                 .with_status_description(state.status().map(ToOwned::to_owned))
                                 .with_started_at(
                                     state
@@ -96,7 +232,7 @@ impl<C: 'static + Connect> Module for DockerModule<C> {
                                         }),
                                 ).with_image_id(resp.id().map(ToOwned::to_owned))
                                 .with_pid(state.pid().map_or(Pid::None, Pid::Value))
-                        })
+                        }).with_restart_policy_drift(drift)
                 }).map_err(Error::from),
         )
     }
@@ -106,6 +242,7 @@ impl<C: 'static + Connect> Module for DockerModule<C> {
 mod tests {
     use super::*;
 
+    use std::collections::HashMap;
     use std::string::ToString;
 
     use hyper::Client;
@@ -115,7 +252,9 @@ mod tests {
 
     use docker::apis::client::APIClient;
     use docker::apis::configuration::Configuration;
-    use docker::models::{ContainerCreateBody, InlineResponse200, InlineResponse200State};
+    use docker::models::{
+        ContainerConfig, ContainerCreateBody, HostConfig, InlineResponse200, InlineResponse200State,
+    };
     use edgelet_core::pid::Pid;
     use edgelet_core::{Module, ModuleStatus};
     use edgelet_test_utils::JsonConnector;
@@ -145,6 +284,146 @@ mod tests {
         assert_eq!("mod1", docker_module.name());
         assert_eq!("docker", docker_module.type_());
         assert_eq!("ubuntu", docker_module.config().image());
+        assert_eq!(None, docker_module.namespace());
+        assert_eq!("mod1", docker_module.identity());
+    }
+
+    #[test]
+    fn identity_is_read_from_labels() {
+        let mut labels = HashMap::new();
+        labels.insert(IDENTITY_LABEL_KEY.to_string(), "mod1".to_string());
+        let docker_module = DockerModule::new(
+            create_api_client("boo"),
+            "mod1-canary",
+            DockerConfig::new("ubuntu", ContainerCreateBody::new().with_labels(labels), None)
+                .unwrap(),
+        ).unwrap();
+        assert_eq!("mod1", docker_module.identity());
+    }
+
+    #[test]
+    fn namespace_is_read_from_labels() {
+        let mut labels = HashMap::new();
+        labels.insert(NAMESPACE_LABEL_KEY.to_string(), "contoso".to_string());
+        let docker_module = DockerModule::new(
+            create_api_client("boo"),
+            "mod1",
+            DockerConfig::new("ubuntu", ContainerCreateBody::new().with_labels(labels), None)
+                .unwrap(),
+        ).unwrap();
+        assert_eq!(Some("contoso"), docker_module.namespace());
+    }
+
+    #[test]
+    fn priority_is_read_from_labels() {
+        let mut labels = HashMap::new();
+        labels.insert(PRIORITY_LABEL_KEY.to_string(), "critical".to_string());
+        let docker_module = DockerModule::new(
+            create_api_client("boo"),
+            "mod1",
+            DockerConfig::new("ubuntu", ContainerCreateBody::new().with_labels(labels), None)
+                .unwrap(),
+        ).unwrap();
+        assert_eq!(ModulePriority::Critical, docker_module.priority());
+
+        let docker_module = DockerModule::new(
+            create_api_client("boo"),
+            "mod1",
+            DockerConfig::new("ubuntu", ContainerCreateBody::new(), None).unwrap(),
+        ).unwrap();
+        assert_eq!(ModulePriority::Normal, docker_module.priority());
+    }
+
+    #[test]
+    fn restart_policy_is_read_from_labels() {
+        let mut labels = HashMap::new();
+        labels.insert(RESTART_POLICY_LABEL_KEY.to_string(), "on-unhealthy".to_string());
+        let docker_module = DockerModule::new(
+            create_api_client("boo"),
+            "mod1",
+            DockerConfig::new("ubuntu", ContainerCreateBody::new().with_labels(labels), None)
+                .unwrap(),
+        ).unwrap();
+        assert_eq!(
+            Some(RestartPolicy::OnUnhealthy),
+            docker_module.restart_policy()
+        );
+    }
+
+    #[test]
+    fn module_labels_are_read_from_labels() {
+        let mut labels = HashMap::new();
+        labels.insert(format!("{}tier", MODULE_LABEL_PREFIX), "web".to_string());
+        labels.insert(NAMESPACE_LABEL_KEY.to_string(), "contoso".to_string());
+        let docker_module = DockerModule::new(
+            create_api_client("boo"),
+            "mod1",
+            DockerConfig::new("ubuntu", ContainerCreateBody::new().with_labels(labels), None)
+                .unwrap(),
+        ).unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert("tier".to_string(), "web".to_string());
+        assert_eq!(expected, docker_module.labels());
+    }
+
+    #[test]
+    fn restart_policy_drift_compares_docker_policy_with_label() {
+        let mut labels = HashMap::new();
+        labels.insert(RESTART_POLICY_LABEL_KEY.to_string(), "always".to_string());
+        let always = DockerRestartPolicy::new().with_name("always".to_string());
+        let no = DockerRestartPolicy::new().with_name("no".to_string());
+
+        assert_eq!(None, restart_policy_drift(Some(&labels), Some(&always)));
+        assert_eq!(None, restart_policy_drift(None, Some(&no)));
+        assert_eq!(
+            Some(
+                "restart policy always needs docker restart policy always, but the container \
+                 has no"
+                    .to_string()
+            ),
+            restart_policy_drift(Some(&labels), Some(&no))
+        );
+
+        labels.insert(RESTART_POLICY_LABEL_KEY.to_string(), "never".to_string());
+        assert_eq!(None, restart_policy_drift(Some(&labels), None));
+        assert_eq!(
+            None,
+            restart_policy_drift(
+                Some(&labels),
+                Some(&DockerRestartPolicy::new().with_name(String::new()))
+            )
+        );
+    }
+
+    #[test]
+    fn module_runtime_state_reports_restart_policy_drift() {
+        let mut labels = HashMap::new();
+        labels.insert(RESTART_POLICY_LABEL_KEY.to_string(), "on-failure".to_string());
+        let docker_module = DockerModule::new(
+            create_api_client(
+                InlineResponse200::new()
+                    .with_state(InlineResponse200State::new().with_status("running".to_string()))
+                    .with_config(ContainerConfig::new().with_labels(labels))
+                    .with_host_config(HostConfig::new().with_restart_policy(
+                        DockerRestartPolicy::new().with_name("always".to_string()),
+                    )),
+            ),
+            "mod1",
+            DockerConfig::new("ubuntu", ContainerCreateBody::new(), None).unwrap(),
+        ).unwrap();
+
+        let state = tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(docker_module.runtime_state())
+            .unwrap();
+        assert_eq!(
+            Some(
+                "restart policy on-failure needs docker restart policy on-failure, but the \
+                 container has always"
+            ),
+            state.restart_policy_drift()
+        );
     }
 
     #[test]
@@ -241,6 +520,7 @@ mod tests {
             runtime_state.finished_at().unwrap().to_rfc3339()
         );
         assert_eq!(Pid::Value(1234), runtime_state.pid());
+        assert_eq!(None, runtime_state.exit_reason());
     }
 
     #[test]
@@ -269,6 +549,7 @@ mod tests {
         assert_eq!(ModuleStatus::Failed, *runtime_state.status());
         assert_eq!(10, runtime_state.exit_code().unwrap());
         assert_eq!(&"dead", &runtime_state.status_description().unwrap());
+        assert_eq!(Some(ExitReason::Error(10)), runtime_state.exit_reason());
         assert_eq!(started_at, runtime_state.started_at().unwrap().to_rfc3339());
         assert_eq!(
             finished_at,
@@ -276,6 +557,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn module_runtime_state_exit_reason() {
+        let inputs = vec![
+            ("exited", 0, false, "", Some(ExitReason::Normal)),
+            ("exited", 137, true, "", Some(ExitReason::OomKilled)),
+            ("restarting", 1, false, "", Some(ExitReason::Error(1))),
+            (
+                "exited",
+                127,
+                false,
+                "executable file not found in $PATH",
+                Some(ExitReason::RuntimeFailure),
+            ),
+            ("running", 0, true, "", None),
+        ];
+
+        for (docker_status, exit_code, oom_killed, error, expected) in inputs {
+            let docker_module = DockerModule::new(
+                create_api_client(
+                    InlineResponse200::new().with_state(
+                        InlineResponse200State::new()
+                            .with_status(docker_status.to_string())
+                            .with_exit_code(exit_code)
+                            .with_oom_killed(oom_killed)
+                            .with_error(error.to_string()),
+                    ),
+                ),
+                "mod1",
+                DockerConfig::new("ubuntu", ContainerCreateBody::new(), None).unwrap(),
+            ).unwrap();
+
+            let state = tokio::runtime::current_thread::Runtime::new()
+                .unwrap()
+                .block_on(docker_module.runtime_state())
+                .unwrap();
+            assert_eq!(expected, state.exit_reason());
+        }
+    }
+
     #[test]
     fn module_runtime_state_with_bad_started_at() {
         let started_at = "not really a date".to_string();
@@ -0,0 +1,217 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Failures to authenticate to a container registry.
+//!
+//! Docker reports most failures to pull an image as a 500, with the error the
+//! registry returned in the message, so whether a pull failed because of its
+//! credentials can only be told from the message. Such a pull fails the same
+//! way every time until the credentials in the deployment are fixed, so it
+//! isn't retried. The last one is recorded for `iotedge check` to report.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use failure::Fail;
+use hyper::StatusCode;
+use serde_json::{self, Value};
+
+use docker::apis::Error as DockerError;
+
+use error::{Error, ErrorKind};
+use image::repository;
+
+const BAD_CREDENTIALS_MESSAGES: &[&str] = &[
+    "unauthorized",
+    "authentication required",
+    "incorrect username or password",
+];
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RegistryAuthFailure {
+    /// The registry doesn't accept the credentials, or needs some.
+    BadCredentials,
+    /// The credentials are a token that has expired.
+    ExpiredToken,
+    /// The credentials are accepted, but don't grant access to the image.
+    AccessDenied,
+}
+
+impl fmt::Display for RegistryAuthFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            RegistryAuthFailure::BadCredentials => "the credentials were not accepted",
+            RegistryAuthFailure::ExpiredToken => "the token has expired",
+            RegistryAuthFailure::AccessDenied => "access to the image was denied",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+impl RegistryAuthFailure {
+    /// Works out whether a pull that failed with `status` and `message` was
+    /// refused by the registry.
+    pub fn classify(status: StatusCode, message: &str) -> Option<Self> {
+        let message = message.to_lowercase();
+        if message.contains("token") && message.contains("expired") {
+            Some(RegistryAuthFailure::ExpiredToken)
+        } else if status == StatusCode::UNAUTHORIZED
+            || BAD_CREDENTIALS_MESSAGES.iter().any(|m| message.contains(m))
+        {
+            Some(RegistryAuthFailure::BadCredentials)
+        } else if status == StatusCode::FORBIDDEN || message.contains("denied") {
+            Some(RegistryAuthFailure::AccessDenied)
+        } else {
+            None
+        }
+    }
+}
+
+/// Converts the error a pull of `image` failed with, telling failures to
+/// authenticate to the registry apart from the rest. A missing image is
+/// reported as such, even though Docker can't tell whether the image doesn't
+/// exist or the credentials don't grant access to it.
+pub fn pull_error(image: &str, err: DockerError<Value>) -> Error {
+    let failure = match err {
+        DockerError::Api(ref e) if e.code != StatusCode::NOT_FOUND => {
+            let message = e
+                .content
+                .as_ref()
+                .and_then(|content| content.get("message"))
+                .and_then(Value::as_str)
+                .unwrap_or("");
+            RegistryAuthFailure::classify(e.code, message)
+        }
+        _ => None,
+    };
+
+    let error = Error::from(err);
+    match failure {
+        Some(failure) => Error::from(
+            error.context(ErrorKind::RegistryAuthentication(image.to_string(), failure)),
+        ),
+        None => error,
+    }
+}
+
+/// The last failure to authenticate to a registry, which is kept until an
+/// image from the same repository is pulled.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct AuthFailureRecord {
+    image: String,
+    failure: RegistryAuthFailure,
+    message: String,
+}
+
+impl AuthFailureRecord {
+    pub fn new(image: &str, failure: RegistryAuthFailure, message: &str) -> Self {
+        AuthFailureRecord {
+            image: image.to_string(),
+            failure,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    pub fn failure(&self) -> RegistryAuthFailure {
+        self.failure
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+pub fn write_record(path: &Path, record: &AuthFailureRecord) -> io::Result<()> {
+    let json = serde_json::to_string(record)?;
+    fs::write(path, json)
+}
+
+/// Removes the record at `path` if it is for the same repository as `image`.
+pub fn clear_record(path: &Path, image: &str) -> io::Result<()> {
+    let record: AuthFailureRecord = match fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json)?,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if repository(record.image()) == repository(image) {
+        fs::remove_file(path)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn registry_refusals_are_classified() {
+        assert_eq!(
+            Some(RegistryAuthFailure::BadCredentials),
+            RegistryAuthFailure::classify(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Get https://r.azurecr.io/v2/sensor/manifests/1.0: unauthorized: \
+                 authentication required"
+            )
+        );
+        assert_eq!(
+            Some(RegistryAuthFailure::BadCredentials),
+            RegistryAuthFailure::classify(StatusCode::UNAUTHORIZED, "")
+        );
+        assert_eq!(
+            Some(RegistryAuthFailure::ExpiredToken),
+            RegistryAuthFailure::classify(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "unauthorized: Token has expired"
+            )
+        );
+        assert_eq!(
+            Some(RegistryAuthFailure::AccessDenied),
+            RegistryAuthFailure::classify(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "denied: requested access to the resource is denied"
+            )
+        );
+    }
+
+    #[test]
+    fn other_failures_are_not_auth_failures() {
+        assert_eq!(
+            None,
+            RegistryAuthFailure::classify(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Get https://r.azurecr.io/v2/: net/http: TLS handshake timeout"
+            )
+        );
+        assert_eq!(
+            None,
+            RegistryAuthFailure::classify(StatusCode::SERVICE_UNAVAILABLE, "")
+        );
+    }
+
+    #[test]
+    fn records_are_cleared_by_pulls_from_the_same_repository() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("registry_auth");
+        let record = AuthFailureRecord::new(
+            "r.azurecr.io/sensor:1.0",
+            RegistryAuthFailure::BadCredentials,
+            "unauthorized",
+        );
+        write_record(&path, &record).unwrap();
+
+        clear_record(&path, "r.azurecr.io/other:1.0").unwrap();
+        assert!(path.exists());
+        clear_record(&path, "r.azurecr.io/sensor:1.1").unwrap();
+        assert!(!path.exists());
+        clear_record(&path, "r.azurecr.io/sensor:1.1").unwrap();
+    }
+}
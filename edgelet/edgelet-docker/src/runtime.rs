@@ -2,36 +2,76 @@
 
 use std::collections::HashMap;
 use std::convert::From;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::ops::Deref;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use base64;
+use failure::Fail;
 use futures::prelude::*;
 use futures::{future, stream, Async, Stream};
-use hyper::{Body, Chunk as HyperChunk, Client};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Chunk as HyperChunk};
 use log::Level;
 use serde_json;
+use tokio;
+use tokio::timer::{Delay, Interval};
+use tokio_threadpool;
 use url::Url;
 
+use cgroup::{self, CgroupVersion, ModuleStats};
 use client::DockerClient;
-use config::DockerConfig;
+use config::{DockerConfig, Isolation};
 use docker::apis::client::APIClient;
 use docker::apis::configuration::Configuration;
-use docker::models::{ContainerCreateBody, NetworkConfig};
+use docker::models::{
+    Container, ContainerCreateBody, ContainerCreateBodyNetworkingConfig, ContainerSummary,
+    ContainerUpdateUpdate, EndpointSettings, HostConfig, InlineResponse200, NetworkConfig,
+    RestartPolicy as DockerRestartPolicy, Volume,
+};
+#[cfg(feature = "chaos")]
+use edgelet_core::chaos::{self, FaultTarget};
 use edgelet_core::{
-    LogOptions, Module, ModuleRegistry, ModuleRuntime, ModuleRuntimeState, ModuleSpec,
-    SystemInfo as CoreSystemInfo,
+    CancellationToken, DiskUsage, DiskUsageProvider, EnvProvider, ImageBuilder, LogOptions,
+    MessageKind, Metrics, Module, ModuleConsole, ModuleDiskUsage, ModuleName, ModuleRegistry,
+    ModuleRuntime, ModuleRuntimeState, ModuleSpec, Namespaces, Outbox, Retry, RetryPolicy,
+    SystemInfo as CoreSystemInfo, TrustBundleFiles, UpdateStrategy, VolumeDiskUsage,
+    EGRESS_VIOLATIONS, TRUST_BUNDLE_FILENAME,
 };
-use edgelet_http::UrlConnector;
+use edgelet_http::log_filter::LogFilter;
+use edgelet_http::{RetryConnector, UnixClientBuilder};
 use edgelet_utils::log_failure;
 
+use egress::{EgressFirewall, EgressPolicy, EgressTarget, EGRESS_LABEL_KEY};
 use error::{Error, ErrorKind, Result};
-use module::{DockerModule, MODULE_TYPE as DOCKER_MODULE_TYPE};
+use image::{upstream_image, ResolvedImage, DIGEST_LABEL_KEY, PLATFORM_LABEL_KEY};
+use module::{
+    docker_restart_policy, labeled_restart_policy, restart_policy_drift, DockerModule,
+    IDENTITY_LABEL_KEY, MODULE_LABEL_PREFIX, MODULE_TYPE as DOCKER_MODULE_TYPE,
+    NAMESPACE_LABEL_KEY, PRIORITY_LABEL_KEY, RESTART_POLICY_LABEL_KEY,
+};
+use registry::{self, AuthFailureRecord};
 
 const WAIT_BEFORE_KILL_SECONDS: i32 = 10;
 
+const PULL_MAX_ATTEMPTS: u32 = 3;
+const PULL_INITIAL_BACKOFF_SECS: u64 = 2;
+const PULL_MAX_BACKOFF_SECS: u64 = 30;
+
 static LABEL_KEY: &str = "net.azure-devices.edge.owner";
 static LABEL_VALUE: &str = "Microsoft.Azure.Devices.Edge.Agent";
+static CANARY_SUFFIX: &str = "-canary";
+
+/// The environment variable that has the path of the trust bundle file in
+/// the containers the trust bundle is mounted into.
+pub const TRUST_BUNDLE_PATH_KEY: &str = "IOTEDGE_TRUSTBUNDLEPATH";
+static WINDOWS_OS_TYPE: &str = "windows";
+
+/// The files the time zone of the host is kept in, which are mounted into
+/// the containers of modules that exist on the host.
+const LOCALTIME_FILES: &[&str] = &["/etc/localtime", "/etc/timezone"];
 
 lazy_static! {
     static ref LABELS: Vec<&'static str> = {
@@ -41,30 +81,87 @@ lazy_static! {
     };
 }
 
+/// How far the canary updates of modules got, by the name of the module
+/// being updated. Modules report it as their status description until the
+/// update has swapped in the canary or the module is removed.
+#[derive(Clone, Default)]
+struct CanaryUpdates(Arc<Mutex<HashMap<String, String>>>);
+
+impl CanaryUpdates {
+    fn report(&self, name: &str, progress: String) {
+        self.0
+            .lock()
+            .expect("Lock on canary updates failed")
+            .insert(name.to_string(), progress);
+    }
+
+    fn clear(&self, name: &str) {
+        self.0
+            .lock()
+            .expect("Lock on canary updates failed")
+            .remove(name);
+    }
+
+    fn progress(&self, name: &str) -> Option<String> {
+        self.0
+            .lock()
+            .expect("Lock on canary updates failed")
+            .get(name)
+            .cloned()
+    }
+}
+
 #[derive(Clone)]
 pub struct DockerModuleRuntime {
-    client: DockerClient<UrlConnector>,
+    client: DockerClient<RetryConnector>,
     network_id: Option<String>,
+    default_memory_limit: Option<i64>,
+    env_provider: Option<EnvProvider>,
+    default_isolation: Option<Isolation>,
+    outbox: Outbox,
+    auth_failure_record: Option<PathBuf>,
+    cancellation: CancellationToken,
+    namespaces: Namespaces,
+    trust_bundle_files: Option<(TrustBundleFiles, PathBuf)>,
+    cgroup_version: Option<CgroupVersion>,
+    offline: bool,
+    localtime_mount: bool,
+    parent_hostname: Option<String>,
+    egress_firewall: EgressFirewall,
+    metrics: Metrics,
+    canaries: CanaryUpdates,
 }
 
 impl DockerModuleRuntime {
     pub fn new(docker_url: &Url) -> Result<Self> {
         // build the hyper client
-        let client = Client::builder().build(UrlConnector::new(docker_url)?);
+        let builder = UnixClientBuilder::new();
 
         // extract base path - the bit that comes after the scheme
         let base_path = get_base_path(docker_url);
-        let mut configuration = Configuration::new(client);
+        let mut configuration = Configuration::new(builder.hyper_client(docker_url)?);
         configuration.base_path = base_path.to_string();
-
-        let scheme = docker_url.scheme().to_string();
-        configuration.uri_composer = Box::new(move |base_path, path| {
-            Ok(UrlConnector::build_hyper_uri(&scheme, base_path, path)?)
-        });
+        configuration.user_agent = Some(builder.user_agent().to_string());
+        configuration.uri_composer = builder.uri_composer(docker_url);
 
         Ok(DockerModuleRuntime {
             client: DockerClient::new(APIClient::new(configuration)),
             network_id: None,
+            default_memory_limit: None,
+            env_provider: None,
+            default_isolation: None,
+            outbox: Outbox::default(),
+            auth_failure_record: None,
+            cancellation: CancellationToken::new(),
+            namespaces: Namespaces::new(),
+            trust_bundle_files: None,
+            cgroup_version: CgroupVersion::detect(),
+            offline: false,
+            localtime_mount: false,
+            parent_hostname: None,
+            egress_firewall: EgressFirewall::default(),
+            metrics: Metrics::new(),
+            canaries: CanaryUpdates::default(),
         })
     }
 
@@ -73,6 +170,560 @@ impl DockerModuleRuntime {
         self
     }
 
+    /// Sets the memory limit, in bytes, of modules whose create options
+    /// don't set one.
+    pub fn with_default_memory_limit(mut self, memory_limit: i64) -> Self {
+        self.default_memory_limit = Some(memory_limit);
+        self
+    }
+
+    /// Sets what provides the environment of the modules created, on top of
+    /// the variables their specs set.
+    pub fn with_env_provider(mut self, env_provider: EnvProvider) -> Self {
+        self.env_provider = Some(env_provider);
+        self
+    }
+
+    /// Sets the isolation of modules that neither ask for one nor set one in
+    /// their create options.
+    pub fn with_default_isolation(mut self, isolation: Isolation) -> Self {
+        self.default_isolation = Some(isolation);
+        self
+    }
+
+    /// Reports the image each module is created from to IoT Hub through
+    /// `outbox`.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// Records the last failure to authenticate to a registry at `path`, for
+    /// `iotedge check` to report.
+    pub fn with_auth_failure_record(mut self, path: PathBuf) -> Self {
+        self.auth_failure_record = Some(path);
+        self
+    }
+
+    /// Aborts pulls, builds and attaches that are in flight, and ends the
+    /// logs being followed, once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Registers the namespaces of the modules created with `namespaces`.
+    /// Modules in a namespace are put on a network of their own, and are
+    /// given the workload socket of their namespace instead of the shared
+    /// one.
+    pub fn with_namespaces(mut self, namespaces: Namespaces) -> Self {
+        self.namespaces = namespaces;
+        self
+    }
+
+    /// Mounts the directory `files` keeps the trust bundle of each module in
+    /// read only at `container_dir` in the containers created.
+    pub fn with_trust_bundle_files(
+        mut self,
+        files: TrustBundleFiles,
+        container_dir: PathBuf,
+    ) -> Self {
+        self.trust_bundle_files = Some((files, container_dir));
+        self
+    }
+
+    /// Mounts the time zone of the host read only into the containers
+    /// created when `localtime_mount`, so that modules keep their local time
+    /// in step with the device.
+    pub fn with_localtime_mount(mut self, localtime_mount: bool) -> Self {
+        self.localtime_mount = localtime_mount;
+        self
+    }
+
+    /// Overrides the version of control groups detected on the host, which
+    /// decides what stats are read and which limits modules can be created
+    /// with.
+    pub fn with_cgroup_version(mut self, version: Option<CgroupVersion>) -> Self {
+        self.cgroup_version = version;
+        self
+    }
+
+    /// Pulls images once without retrying when `offline`, and goes on with
+    /// the image on the host when the pull fails, for devices that have
+    /// their images loaded instead of pulled.
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Pulls the images whose names start with `$upstream` from the registry
+    /// proxy of the parent gateway `parent_hostname`, for nested devices
+    /// that can't reach the registries themselves.
+    pub fn with_parent_hostname(mut self, parent_hostname: String) -> Self {
+        self.parent_hostname = Some(parent_hostname);
+        self
+    }
+
+    /// Programs the egress allowlists of modules with `firewall` instead of
+    /// the iptables of the host.
+    pub fn with_egress_firewall(mut self, firewall: EgressFirewall) -> Self {
+        self.egress_firewall = firewall;
+        self
+    }
+
+    /// Counts the connections modules attempted outside of their egress
+    /// allowlists in `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn cgroup_version(&self) -> Option<CgroupVersion> {
+        self.cgroup_version
+    }
+
+    /// Reads the CPU and memory usage of the container of module `id` once.
+    pub fn stats(&self, id: &str) -> impl Future<Item = ModuleStats, Error = Error> + Send {
+        let version = self.cgroup_version;
+        self.client
+            .container_api()
+            .container_stats(id, false)
+            .map_err(Error::from)
+            .map(move |stats| ModuleStats::from_docker(&stats, version))
+    }
+
+    /// Enforces the egress allowlists of the modules that run once, and
+    /// removes the rules of those that no longer do, such as the ones docker
+    /// stopped on its own.
+    pub fn enforce_egress(&self) -> impl Future<Item = (), Error = Error> + Send {
+        let firewall = self.egress_firewall.clone();
+        let metrics = self.metrics.clone();
+        let targets = self.egress_targets(None);
+        // the modules there were rules for are read first, so that the rules
+        // of a module that starts in the meantime are kept
+        let enforced = {
+            let firewall = firewall.clone();
+            blocking(move || {
+                firewall.enforced().unwrap_or_else(|err| {
+                    debug!("Could not read the egress rules: {}", err);
+                    vec![]
+                })
+            })
+        };
+        enforced
+            .and_then(move |enforced| targets.map(move |targets| (enforced, targets)))
+            .and_then(move |(enforced, targets)| {
+                blocking(move || {
+                    for target in &targets {
+                        if let Err(err) = enforce_egress_target(&firewall, &metrics, target) {
+                            warn!(
+                                "Could not enforce the egress allowlist of module {}.",
+                                target.module()
+                            );
+                            log_failure(Level::Warn, &err);
+                        }
+                    }
+                    for module in enforced {
+                        if !targets.iter().any(|target| target.module() == module) {
+                            remove_egress_rules(&firewall, &module);
+                        }
+                    }
+                })
+            })
+    }
+
+    /// Enforces the egress allowlists of modules every `frequency`, so that
+    /// the addresses of host names and of containers docker restarted are
+    /// kept up to date, until the runtime is cancelled.
+    pub fn watch_egress(&self, frequency: Duration) -> impl Future<Item = (), Error = ()> + Send {
+        let runtime = self.clone();
+        let enforcement = Interval::new(Instant::now() + frequency, frequency)
+            .map_err(|err| warn!("Could not schedule the enforcement of egress: {}", err))
+            .for_each(move |_| {
+                runtime.enforce_egress().then(|result| {
+                    if let Err(err) = result {
+                        warn!("Could not enforce the egress allowlists of modules.");
+                        log_failure(Level::Warn, &err);
+                    }
+                    Ok(())
+                })
+            });
+
+        enforcement
+            .select(self.cancellation.cancelled())
+            .then(|_| Ok(()))
+    }
+
+    // Lists the running containers with an egress allowlist, or only the one
+    // of module `name`.
+    fn egress_targets(
+        &self,
+        name: Option<String>,
+    ) -> impl Future<Item = Vec<EgressTarget>, Error = Error> + Send {
+        let mut filters = HashMap::new();
+        filters.insert("label", vec![EGRESS_LABEL_KEY]);
+        let client = self.client.clone();
+        serde_json::to_string(&filters)
+            .map_err(Error::from)
+            .into_future()
+            .and_then(move |filters| {
+                client
+                    .container_api()
+                    .container_list(false, 0, false, &filters)
+                    .map_err(Error::from)
+            }).map(move |containers| {
+                containers
+                    .iter()
+                    .filter_map(egress_target)
+                    .filter(|target| name.as_ref().map_or(true, |name| target.module() == name))
+                    .collect()
+            })
+    }
+
+    // Enforces the egress allowlist of module `name` once its container is
+    // started. A container whose allowlist can't be enforced is stopped
+    // again instead of being left to run without it.
+    fn enforce_module_egress(&self, name: String) -> impl Future<Item = (), Error = Error> + Send {
+        let firewall = self.egress_firewall.clone();
+        let metrics = self.metrics.clone();
+        let client = self.client.clone();
+        self.egress_targets(Some(name.clone()))
+            .and_then(move |targets| {
+                blocking(move || {
+                    targets
+                        .iter()
+                        .map(|target| enforce_egress_target(&firewall, &metrics, target))
+                        .collect::<Result<Vec<_>>>()
+                })
+            }).and_then(move |result| match result {
+                Ok(_) => future::Either::A(future::ok(())),
+                Err(err) => {
+                    warn!(
+                        "Could not enforce the egress allowlist of module {}, stopping it.",
+                        name
+                    );
+                    future::Either::B(
+                        client
+                            .container_api()
+                            .container_stop(&name, 0)
+                            .then(move |_| Err(err)),
+                    )
+                }
+            })
+    }
+
+    // Creates the network of `namespace` if it doesn't exist yet, and
+    // connects the modules outside of any namespace to it, so that modules in
+    // the namespace can reach the edge hub.
+    fn ensure_namespace_network(
+        &self,
+        network: String,
+        namespace: &str,
+    ) -> Box<Future<Item = (), Error = Error> + Send> {
+        let filter = format!(r#"{{"name":{{"{}":true}}}}"#, network);
+        let mut labels = HashMap::new();
+        labels.insert(LABEL_KEY.to_string(), LABEL_VALUE.to_string());
+        labels.insert(NAMESPACE_LABEL_KEY.to_string(), namespace.to_string());
+        let create_client = self.client.clone();
+        let connect_client = self.client.clone();
+        let runtime = self.clone();
+
+        Box::new(
+            self.client
+                .network_api()
+                .network_list(&filter)
+                .map_err(Error::from)
+                .and_then(move |existing_networks| {
+                    if existing_networks
+                        .iter()
+                        .any(|existing| existing.name() == Some(network.as_str()))
+                    {
+                        return future::Either::A(future::ok(()));
+                    }
+
+                    info!("Creating network {}", network);
+                    let config = NetworkConfig::new(network.clone())
+                        .with_check_duplicate(true)
+                        .with_labels(labels);
+                    let created = create_client
+                        .network_api()
+                        .network_create(config)
+                        .map_err(Error::from)
+                        .and_then(move |_| runtime.list())
+                        .and_then(move |modules| {
+                            let connected = modules
+                                .iter()
+                                .filter(|module| module.namespace().is_none())
+                                .map(|module| connect(&connect_client, &network, module.name()))
+                                .collect::<Vec<_>>();
+                            future::join_all(connected).map(|_| ())
+                        }).or_else(|err| match *err.kind() {
+                            // created by another module of the namespace
+                            ErrorKind::Conflict => Ok(()),
+                            _ => Err(err),
+                        });
+                    future::Either::B(created)
+                }),
+        )
+    }
+
+    // Resolves `image` to the variant of it that is on this host. Fails if the
+    // image was built for a different CPU architecture than the host's. If
+    // the image can't be inspected nothing is resolved, and container creation
+    // is left to report whatever is wrong. If the host's architecture can't
+    // be determined the check is skipped.
+    fn resolve_image(
+        &self,
+        image: &str,
+    ) -> Box<Future<Item = Option<ResolvedImage>, Error = Error> + Send> {
+        let image_name = image.to_string();
+        Box::new(
+            self.client
+                .image_api()
+                .image_inspect(image)
+                .join(self.client.system_api().system_info())
+                .then(move |result| match result {
+                    Ok((image, system_info)) => match system_info.architecture() {
+                        Some(host) if !architectures_match(image.architecture(), host) => {
+                            Err(Error::from(ErrorKind::ImageArchitectureMismatch(
+                                image.architecture().to_string(),
+                                host.to_string(),
+                            )))
+                        }
+                        _ => Ok(Some(ResolvedImage::new(&image_name, &image))),
+                    },
+                    Err(err) => {
+                        warn!(
+                            "Could not inspect image {}, skipping architecture check.",
+                            image_name
+                        );
+                        log_failure(Level::Warn, &Error::from(err));
+                        Ok(None)
+                    }
+                }),
+        )
+    }
+
+    // Fails if the host can't isolate containers the way `isolation` asks.
+    fn check_isolation(
+        &self,
+        isolation: Option<Isolation>,
+    ) -> Box<Future<Item = (), Error = Error> + Send> {
+        match isolation {
+            Some(isolation) => Box::new(
+                self.client
+                    .system_api()
+                    .system_info()
+                    .map_err(Error::from)
+                    .and_then(move |system_info| {
+                        check_isolation_support(isolation, system_info.os_type())
+                    }),
+            ),
+            None => Box::new(future::ok(())),
+        }
+    }
+
+    // Starts `module` under a temporary name next to the module it replaces
+    // and resolves once it is running. The canary is only swapped in if it is
+    // still running once `soak` has passed, which is waited for in the
+    // background. If it isn't, or anything goes wrong before the swap, the
+    // canary is removed and the existing module is left as it was. How far
+    // the update got is the status description of the existing module.
+    fn update_canary(
+        &self,
+        module: ModuleSpec<DockerConfig>,
+        soak: Duration,
+    ) -> Box<Future<Item = (), Error = Error> + Send> {
+        let name = module.name().to_string();
+        let canary_name = format!("{}{}", name, CANARY_SUFFIX);
+        let config = canary_config(module.config(), &name);
+        let canary = module.with_name(canary_name.clone()).with_config(config);
+        info!("Starting canary {} for module {}", canary_name, name);
+
+        let create_runtime = self.clone();
+        let start_runtime = self.clone();
+        let rollback_runtime = self.clone();
+        let soak_runtime = self.clone();
+
+        // clear out a canary left behind by an earlier update
+        let started = <DockerModuleRuntime as ModuleRuntime>::remove(self, &canary_name)
+            .then(move |_| create_runtime.create(canary))
+            .and_then({
+                let canary_name = canary_name.clone();
+                move |_| start_runtime.start(&canary_name)
+            }).or_else({
+                let canary_name = canary_name.clone();
+                move |err| {
+                    warn!("Removing canary {}, keeping the existing module", canary_name);
+                    log_failure(Level::Warn, &err);
+                    <DockerModuleRuntime as ModuleRuntime>::remove(&rollback_runtime, &canary_name)
+                        .then(move |_| Err(err))
+                }
+            });
+
+        Box::new(started.map(move |_| {
+            soak_runtime.canaries.report(
+                &name,
+                format!("Soaking canary {} for {}s", canary_name, soak.as_secs()),
+            );
+            tokio::spawn(soak_runtime.soak_canary(name, canary_name, soak));
+        }))
+    }
+
+    // Swaps in the canary `canary_name` of module `name` if it is still
+    // running once `soak` has passed, and removes it otherwise.
+    fn soak_canary(
+        &self,
+        name: String,
+        canary_name: String,
+        soak: Duration,
+    ) -> impl Future<Item = (), Error = ()> + Send {
+        let client = self.client.clone();
+        let soaked = {
+            let client = client.clone();
+            let canary_name = canary_name.clone();
+            let name = name.clone();
+
+            debug!("Soaking canary {} for {}s", canary_name, soak.as_secs());
+            Delay::new(Instant::now() + soak)
+                .map_err(|err| Error::from(err.context(ErrorKind::Docker)))
+                .and_then(move |_| {
+                    client
+                        .container_api()
+                        .container_inspect(&canary_name, false)
+                        .map_err(Error::from)
+                }).and_then(move |details| {
+                    if is_running_without_restarts(&details) {
+                        Ok(())
+                    } else {
+                        Err(Error::from(ErrorKind::CanaryUnhealthy(name)))
+                    }
+                })
+        };
+
+        let rollback_runtime = self.clone();
+        let rollback_name = canary_name.clone();
+        let swap_runtime = self.clone();
+        let canaries = self.canaries.clone();
+        soaked
+            .or_else(move |err| {
+                warn!("Removing canary {}, keeping the existing module", rollback_name);
+                log_failure(Level::Warn, &err);
+                <DockerModuleRuntime as ModuleRuntime>::remove(&rollback_runtime, &rollback_name)
+                    .then(move |_| Err(err))
+            }).and_then({
+                let name = name.clone();
+                move |_| {
+                    info!("Canary {} is healthy, replacing module {}", canary_name, name);
+                    <DockerModuleRuntime as ModuleRuntime>::remove(&swap_runtime, &name)
+                        .or_else(|err| match *err.kind() {
+                            ErrorKind::NotFound(_) => Ok(()),
+                            _ => Err(err),
+                        }).and_then(move |_| {
+                            client
+                                .container_api()
+                                .container_rename(&canary_name, &name)
+                                .map_err(Error::from)
+                        })
+                }
+            }).then(move |result| {
+                match result {
+                    Ok(_) => {
+                        info!("Swapped in updated module {}", name);
+                        canaries.clear(&name);
+                    }
+                    Err(err) => {
+                        warn!("Could not update module {}: {}", name, err);
+                        canaries.report(&name, format!("Canary update failed: {}", err));
+                    }
+                }
+                Ok::<_, ()>(())
+            })
+    }
+
+    // Removes a module's container, along with the anonymous volumes it
+    // created when `remove_volumes` is set. Named volumes and bind mounts
+    // are left alone, since other modules may use them.
+    fn remove_container(
+        &self,
+        id: &str,
+        remove_volumes: bool,
+    ) -> Box<Future<Item = (), Error = Error> + Send> {
+        debug!("Removing container {}", id);
+        let trust_bundle_files = self.trust_bundle_files.clone();
+        let firewall = self.egress_firewall.clone();
+        let canaries = self.canaries.clone();
+        let name = id.to_string();
+        Box::new(with_fault(
+            "remove",
+            self.client
+                .container_api()
+                .container_delete(
+                    fensure_not_empty!(id),
+                    remove_volumes,
+                    /* force */ true,
+                    /* remove link */ false,
+                ).map_err(|err| {
+                    let e = Error::from(err);
+                    warn!("Attempt to remove a container failed.");
+                    log_failure(Level::Warn, &e);
+                    e
+                }).and_then(move |_| {
+                    canaries.clear(&name);
+                    if let Some((files, _)) = trust_bundle_files {
+                        if let Err(err) = files.remove(&name) {
+                            warn!("Could not remove the trust bundle of module {}.", name);
+                            log_failure(Level::Warn, &err);
+                        }
+                    }
+                    blocking(move || remove_egress_rules(&firewall, &name))
+                }),
+        ))
+    }
+
+    // Puts back the restart policy the container of a module was created
+    // with when someone changed it since, so that docker goes by the module's
+    // policy once the container runs. Failing to do so doesn't keep the
+    // container from starting, and the drift stays in the module's details.
+    fn restore_restart_policy(&self, id: &str) -> impl Future<Item = (), Error = Error> + Send {
+        let client = self.client.clone();
+        let name = id.to_string();
+        self.client
+            .container_api()
+            .container_inspect(id, false)
+            .map_err(Error::from)
+            .and_then(move |details| {
+                let labels = details.config().and_then(|config| config.labels());
+                let drift = restart_policy_drift(
+                    labels,
+                    details
+                        .host_config()
+                        .and_then(|host_config| host_config.restart_policy()),
+                );
+                match (drift, labeled_restart_policy(labels)) {
+                    (Some(drift), Some(policy)) => {
+                        warn!("Restoring the restart policy of container {}: {}", name, drift);
+                        let update = ContainerUpdateUpdate::new().with_restart_policy(
+                            DockerRestartPolicy::new()
+                                .with_name(docker_restart_policy(policy).to_string()),
+                        );
+                        future::Either::A(
+                            client
+                                .container_api()
+                                .container_update(&name, update)
+                                .map_err(Error::from)
+                                .map(|_| ()),
+                        )
+                    }
+                    _ => future::Either::B(future::ok(())),
+                }
+            }).or_else(|err| {
+                warn!("Could not check the restart policy of a container.");
+                log_failure(Level::Warn, &err);
+                Ok(())
+            })
+    }
+
     fn merge_env(cur_env: Option<&[String]>, new_env: &HashMap<String, String>) -> Vec<String> {
         // build a new merged hashmap containing string slices for keys and values
         // pointing into String instances in new_env
@@ -96,6 +747,415 @@ impl DockerModuleRuntime {
     }
 }
 
+/// Makes the docker operation `operation` subject to the faults being
+/// injected into docker operations.
+#[cfg(feature = "chaos")]
+fn with_fault<F>(
+    operation: &'static str,
+    future: F,
+) -> impl Future<Item = F::Item, Error = Error> + Send
+where
+    F: Future<Error = Error> + Send,
+{
+    chaos::inject_async(FaultTarget::Docker, operation)
+        .map_err(Error::from)
+        .and_then(|()| future)
+}
+
+#[cfg(not(feature = "chaos"))]
+fn with_fault<F>(_operation: &'static str, future: F) -> F {
+    future
+}
+
+// An offline device has nothing to gain from pulling again.
+fn pull_retry_policy(offline: bool) -> RetryPolicy {
+    RetryPolicy::exponential(
+        Duration::from_secs(PULL_INITIAL_BACKOFF_SECS),
+        Duration::from_secs(PULL_MAX_BACKOFF_SECS),
+    ).with_max_attempts(if offline { 1 } else { PULL_MAX_ATTEMPTS })
+}
+
+// An image that doesn't exist, or that the registry won't let us have, will
+// fail the same way every time, so don't bother pulling it again.
+fn is_transient_pull_error(err: &Error) -> bool {
+    match *err.kind() {
+        ErrorKind::NotFound(_)
+        | ErrorKind::Conflict
+        | ErrorKind::NotModified
+        | ErrorKind::RegistryAuthentication(_, _) => false,
+        _ => true,
+    }
+}
+
+// Keeps the record of the last failure to authenticate to a registry up to
+// date. Failing to is logged rather than failing the pull.
+fn record_pull(path: &Path, image: &str, result: &Result<()>) {
+    let recorded = match result {
+        Ok(()) => registry::clear_record(path, image),
+        Err(err) => match *err.kind() {
+            ErrorKind::RegistryAuthentication(_, failure) => {
+                let message = err.cause().map_or_else(String::new, ToString::to_string);
+                registry::write_record(path, &AuthFailureRecord::new(image, failure, &message))
+            }
+            _ => Ok(()),
+        },
+    };
+    if let Err(err) = recorded {
+        warn!(
+            "Could not update the registry authentication record {}: {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+// Docker reports the host architecture as the kernel sees it (e.g. x86_64,
+// armv7l) while images carry GOARCH names (e.g. amd64, arm), so map both to
+// the latter before comparing.
+fn normalize_architecture(arch: &str) -> &str {
+    match arch {
+        "x86_64" | "amd64" => "amd64",
+        "aarch64" | "arm64" | "armv8" | "armv8l" => "arm64",
+        "armv7l" | "armv7" | "armv6l" | "armv6" | "armhf" | "armel" | "arm" => "arm",
+        "i386" | "i686" | "386" => "386",
+        arch => arch,
+    }
+}
+
+fn namespace_network(network_id: &str, namespace: &str) -> String {
+    format!("{}-{}", network_id, namespace)
+}
+
+// Binds the directory the trust bundle of a module is kept in read only, so
+// that the module can't change the CAs it trusts.
+fn mount_trust_bundle(host_config: HostConfig, dir: &Path, container_dir: &Path) -> HostConfig {
+    let mut binds = host_config.binds().map_or_else(Vec::new, ToOwned::to_owned);
+    binds.push(format!("{}:{}:ro", dir.display(), container_dir.display()));
+    host_config.with_binds(binds)
+}
+
+// Binds the time zone files of the host read only at the same paths, unless
+// the create options bind something there already.
+fn mount_localtime(host_config: HostConfig, files: &[&Path]) -> HostConfig {
+    let mut binds = host_config.binds().map_or_else(Vec::new, ToOwned::to_owned);
+    for file in files {
+        let bound = binds
+            .iter()
+            .any(|bind| bind.split(':').nth(1) == Some(&*file.to_string_lossy()));
+        if !bound {
+            binds.push(format!("{0}:{0}:ro", file.display()));
+        }
+    }
+    host_config.with_binds(binds)
+}
+
+// Points the binds of the shared workload socket, or of the directory it is
+// in, at the workload socket of `namespace` instead.
+fn namespace_binds(binds: &[String], namespaces: &Namespaces, namespace: &str) -> Vec<String> {
+    let shared_socket = namespaces.shared_workload_socket();
+    let socket = namespaces.workload_socket(namespace);
+    let (shared_socket, socket) = match (shared_socket, socket) {
+        (Some(shared_socket), Some(socket)) => (shared_socket, socket),
+        _ => return binds.to_vec(),
+    };
+
+    binds
+        .iter()
+        .map(|bind| {
+            let mut parts = bind.splitn(2, ':');
+            let host = Path::new(parts.next().unwrap_or(""));
+            let rest = parts.next();
+            let host = if host == shared_socket {
+                Some(socket.as_path())
+            } else if Some(host) == shared_socket.parent() {
+                socket.parent()
+            } else {
+                None
+            };
+            match (host, rest) {
+                (Some(host), Some(rest)) => format!("{}:{}", host.display(), rest),
+                _ => bind.clone(),
+            }
+        }).collect()
+}
+
+// Has the container join `network` only, with the endpoint settings it asks
+// for on any network.
+fn join_only(create_options: ContainerCreateBody, network: &str) -> ContainerCreateBody {
+    let settings = create_options
+        .networking_config()
+        .and_then(|config| config.endpoints_config())
+        .and_then(|endpoints| endpoints.values().next().cloned())
+        .unwrap_or_else(EndpointSettings::new);
+    let mut endpoints = HashMap::new();
+    endpoints.insert(network.to_string(), settings);
+    create_options.with_networking_config(
+        ContainerCreateBodyNetworkingConfig::new().with_endpoints_config(endpoints),
+    )
+}
+
+// Failing to connect a module to a network is logged rather than failing the
+// creation of the module.
+fn connect(
+    client: &DockerClient<RetryConnector>,
+    network: &str,
+    container: &str,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let network = network.to_string();
+    let container = container.to_string();
+    client
+        .network_api()
+        .network_connect(&network, Container::new().with_container(container.clone()))
+        .then(move |result| {
+            if let Err(err) = result {
+                warn!(
+                    "Could not connect module {} to network {}",
+                    container, network
+                );
+                log_failure(Level::Warn, &Error::from(err));
+            }
+            Ok::<_, Error>(())
+        })
+}
+
+// Connects a module outside of any namespace to the networks of all of them.
+fn join_namespace_networks(
+    client: &DockerClient<RetryConnector>,
+    container: &str,
+) -> impl Future<Item = (), Error = Error> + Send {
+    let filter = format!(r#"{{"label":{{"{}":true}}}}"#, NAMESPACE_LABEL_KEY);
+    let client_copy = client.clone();
+    let container = container.to_string();
+    client
+        .network_api()
+        .network_list(&filter)
+        .map_err(Error::from)
+        .and_then(move |networks| {
+            let connected = networks
+                .iter()
+                .filter_map(|network| network.name())
+                .map(|network| connect(&client_copy, network, &container))
+                .collect::<Vec<_>>();
+            future::join_all(connected).map(|_| ())
+        })
+}
+
+// Labels the container of the canary of module `name` with the module, so
+// that it can call the workload API as the module while it soaks.
+fn canary_config(config: &DockerConfig, name: &str) -> DockerConfig {
+    let create_options = config.create_options().clone();
+    let mut labels = create_options
+        .labels()
+        .cloned()
+        .unwrap_or_else(HashMap::new);
+    labels.insert(IDENTITY_LABEL_KEY.to_string(), name.to_string());
+    config
+        .clone()
+        .with_create_options(create_options.with_labels(labels))
+}
+
+// Records what the module's image resolved to in the labels of its container,
+// which is where modules' details are read back from.
+fn label_image(
+    create_options: ContainerCreateBody,
+    resolved: &ResolvedImage,
+) -> ContainerCreateBody {
+    let mut labels = create_options
+        .labels()
+        .cloned()
+        .unwrap_or_else(HashMap::new);
+    if let Some(digest) = resolved.digest() {
+        labels.insert(DIGEST_LABEL_KEY.to_string(), digest.to_string());
+    }
+    labels.insert(
+        PLATFORM_LABEL_KEY.to_string(),
+        resolved.platform().to_string(),
+    );
+    create_options.with_labels(labels)
+}
+
+// Reads back what the image of a module's container resolved to.
+fn with_resolved_image(config: DockerConfig, labels: &HashMap<String, String>) -> DockerConfig {
+    let config = match labels.get(DIGEST_LABEL_KEY) {
+        Some(digest) => config.with_image_digest(digest.clone()),
+        None => config,
+    };
+    match labels.get(PLATFORM_LABEL_KEY) {
+        Some(platform) => config.with_platform(platform.clone()),
+        None => config,
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageReport<'a> {
+    module: &'a str,
+    image: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<&'a str>,
+    image_id: &'a str,
+    platform: &'a str,
+}
+
+// A full or broken outbox mustn't fail the creation of the module.
+fn report_image(outbox: &Outbox, module: &str, resolved: &ResolvedImage) {
+    info!(
+        "Created module {} from image {} ({}, {})",
+        module,
+        resolved.image(),
+        resolved.digest().unwrap_or_else(|| resolved.image_id()),
+        resolved.platform()
+    );
+    let report = ImageReport {
+        module,
+        image: resolved.image(),
+        digest: resolved.digest(),
+        image_id: resolved.image_id(),
+        platform: resolved.platform(),
+    };
+    if let Err(err) = outbox.push(MessageKind::ModuleImage, &report) {
+        warn!("Could not queue the image of module {} for IoT Hub:", module);
+        log_failure(Level::Warn, &err);
+    }
+}
+
+// Isolation only applies to Windows containers; docker on other hosts would
+// otherwise refuse it with a less helpful message.
+fn check_isolation_support(isolation: Isolation, os_type: Option<&str>) -> Result<()> {
+    match os_type {
+        Some(os_type) if !os_type.eq_ignore_ascii_case(WINDOWS_OS_TYPE) => Err(Error::from(
+            ErrorKind::IsolationNotSupported(isolation.to_string(), os_type.to_string()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn architectures_match(image: &str, host: &str) -> bool {
+    normalize_architecture(image).eq_ignore_ascii_case(normalize_architecture(host))
+}
+
+// Docker restarts a container that exits according to its restart policy, so
+// a canary that crashed during the soak period may be running again by the
+// time it is checked. The restart count gives it away.
+fn is_running_without_restarts(details: &InlineResponse200) -> bool {
+    let running = details
+        .state()
+        .and_then(|state| state.running())
+        .map_or(false, |running| *running);
+    running && details.restart_count().unwrap_or(0) == 0
+}
+
+// The module of a running container with an egress allowlist, along with
+// its addresses and the allowlist. An allowlist that can't be read blocks
+// all of the container's egress rather than none of it.
+fn egress_target(container: &ContainerSummary) -> Option<EgressTarget> {
+    container.labels().get(EGRESS_LABEL_KEY).map(|label| {
+        let module = container
+            .names()
+            .iter()
+            .next()
+            .map_or("Unknown", |s| &s[1..]);
+        let policy = serde_json::from_str(label).unwrap_or_else(|err| {
+            warn!(
+                "Could not read the egress allowlist of module {}, blocking its egress.",
+                module
+            );
+            log_failure(Level::Warn, &Error::from(err));
+            EgressPolicy::new()
+        });
+        EgressTarget::new(
+            module.to_string(),
+            container.id().clone(),
+            egress_networks(container.network_settings().networks()),
+            policy,
+        )
+    })
+}
+
+// The IPv4 addresses of a container on its networks, with the prefix
+// lengths of the networks.
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(cast_possible_truncation, cast_sign_loss)
+)]
+fn egress_networks(networks: &HashMap<String, EndpointSettings>) -> Vec<(IpAddr, u8)> {
+    let mut addresses: Vec<_> = networks
+        .values()
+        .flat_map(|settings| {
+            let ipv4 = settings
+                .ip_address()
+                .and_then(|address| address.parse::<Ipv4Addr>().ok())
+                .map(IpAddr::V4);
+            let ipv4_prefix = settings
+                .ip_prefix_len()
+                .filter(|prefix| *prefix >= 0 && *prefix <= 32);
+            let ipv6 = settings
+                .global_i_pv6_address()
+                .and_then(|address| address.parse::<Ipv6Addr>().ok())
+                .map(IpAddr::V6);
+            let ipv6_prefix = settings
+                .global_i_pv6_prefix_len()
+                .filter(|prefix| *prefix >= 0 && *prefix <= 128);
+            vec![(ipv4, ipv4_prefix), (ipv6, ipv6_prefix)]
+        }).filter_map(|network| match network {
+            (Some(address), Some(prefix)) => Some((address, prefix as u8)),
+            _ => None,
+        }).collect();
+    addresses.sort();
+    addresses
+}
+
+fn enforce_egress_target(
+    firewall: &EgressFirewall,
+    metrics: &Metrics,
+    target: &EgressTarget,
+) -> Result<()> {
+    let dropped = firewall.enforce(target)?;
+    if dropped > 0 {
+        warn!(
+            "Dropped {} packets of module {} to destinations its egress allowlist doesn't allow.",
+            dropped,
+            target.module()
+        );
+        metrics.add(EGRESS_VIOLATIONS, dropped);
+    }
+    Ok(())
+}
+
+// Removes the egress rules of `module` when its container stops. Without
+// iptables there are no rules to remove, which isn't worth a warning on
+// every stop.
+fn remove_egress_rules(firewall: &EgressFirewall, module: &str) {
+    match firewall.enforced() {
+        Ok(ref modules) if modules.iter().any(|enforced| enforced == module) => {
+            if let Err(err) = firewall.remove(module) {
+                warn!("Could not remove the egress rules of module {}.", module);
+                log_failure(Level::Warn, &err);
+            }
+        }
+        Ok(_) => (),
+        Err(err) => debug!("Could not read the egress rules: {}", err),
+    }
+}
+
+// Runs `f`, which waits on iptables, on the blocking pool of the executor so
+// that it doesn't hold up the other futures on the thread. Off a thread pool,
+// such as in tests, `f` just runs on the current thread.
+fn blocking<F, R>(f: F) -> impl Future<Item = R, Error = Error> + Send
+where
+    F: FnOnce() -> R + Send,
+    R: Send,
+{
+    let mut f = Some(f);
+    future::poll_fn(move || {
+        let polled = tokio_threadpool::blocking(|| f.take().expect("blocking task ran twice")());
+        match polled {
+            Ok(polled) => Ok(polled),
+            Err(_) => Ok(Async::Ready(f.take().expect("blocking task ran twice")())),
+        }
+    })
+}
+
 fn get_base_path(url: &Url) -> &str {
     match url.scheme() {
         "unix" => url.path(),
@@ -118,22 +1178,52 @@ impl ModuleRegistry for DockerModuleRuntime {
             },
         );
 
+        let client = self.client.clone();
+        let image = upstream_image(
+            config.image(),
+            self.parent_hostname.as_ref().map(AsRef::as_ref),
+        );
+        let record = self.auth_failure_record.clone();
+        let pulled = image.clone();
+        let offline = self.offline;
         let response = creds
-            .map(|creds| {
-                debug!("Pulling {}", config.image());
-                self.client
-                    .image_api()
-                    .image_create(config.image(), "", "", "", "", &creds, "")
-                    .map_err(|err| {
-                        let e = Error::from(err);
-                        warn!("Attempt to pull image failed.");
-                        log_failure(Level::Warn, &e);
-                        e
-                    })
+            .map(move |creds| {
+                Retry::new(pull_retry_policy(offline), move |attempt| {
+                    debug!("Pulling {} (attempt {})", image, attempt);
+                    let image = image.clone();
+                    client
+                        .image_api()
+                        .image_create(&image, "", "", "", "", &creds, "")
+                        .map_err(move |err| registry::pull_error(&image, err))
+                }).with_condition(is_transient_pull_error)
+                .with_hook(|attempt, err, delay| {
+                    warn!(
+                        "Attempt {} to pull image failed, retrying in {:?}.",
+                        attempt, delay
+                    );
+                    log_failure(Level::Warn, err);
+                }).map_err(move |e| {
+                    let level = if offline { Level::Debug } else { Level::Warn };
+                    log!(level, "Attempt to pull image failed.");
+                    log_failure(level, &e);
+                    e
+                })
             }).into_future()
-            .flatten();
+            .flatten()
+            .then(move |result| {
+                if let Some(path) = record {
+                    record_pull(&path, &pulled, &result);
+                }
+                match result {
+                    Err(_) if offline => {
+                        info!("Using image {} on the host, the device is offline", pulled);
+                        Ok(())
+                    }
+                    result => result,
+                }
+            });
 
-        Box::new(response)
+        Box::new(self.cancellation.guard(with_fault("pull", response)))
     }
 
     fn remove(&self, name: &str) -> Self::RemoveFuture {
@@ -156,7 +1246,7 @@ impl ModuleRegistry for DockerModuleRuntime {
 impl ModuleRuntime for DockerModuleRuntime {
     type Error = Error;
     type Config = DockerConfig;
-    type Module = DockerModule<UrlConnector>;
+    type Module = DockerModule<RetryConnector>;
     type ModuleRegistry = Self;
     type Chunk = Chunk;
     type Logs = Logs;
@@ -173,6 +1263,7 @@ impl ModuleRuntime for DockerModuleRuntime {
     type StopFuture = Box<Future<Item = (), Error = Self::Error> + Send>;
     type SystemInfoFuture = Box<Future<Item = CoreSystemInfo, Error = Self::Error> + Send>;
     type RemoveAllFuture = Box<Future<Item = (), Error = Self::Error> + Send>;
+    type UpdateFuture = Box<Future<Item = (), Error = Self::Error> + Send>;
 
     fn init(&self) -> Self::InitFuture {
         let created = self.network_id.clone().map_or_else(
@@ -211,43 +1302,210 @@ impl ModuleRuntime for DockerModuleRuntime {
         // we only want "docker" modules
         fensure!(module.type_(), module.type_() == DOCKER_MODULE_TYPE);
 
-        let result = module
-            .config()
-            .clone_create_options()
+        // the module name becomes the container name, so reject anything that
+        // isn't a valid module identity before handing it to docker
+        let result = ModuleName::new(module.name())
+            .map_err(Error::from)
+            .and_then(|_| module.config().clone_create_options())
             .and_then(|create_options| {
                 // merge environment variables
-                let merged_env = DockerModuleRuntime::merge_env(create_options.env(), module.env());
+                let env = match self.env_provider {
+                    Some(ref provider) => provider.apply(module.name(), module.env()),
+                    None => module.env().clone(),
+                };
+                let env = match self.trust_bundle_files {
+                    Some((_, ref container_dir)) => {
+                        let path = container_dir.join(TRUST_BUNDLE_FILENAME);
+                        let mut env = env;
+                        env.insert(TRUST_BUNDLE_PATH_KEY.to_string(), path.display().to_string());
+                        env
+                    }
+                    None => env,
+                };
+                let merged_env = DockerModuleRuntime::merge_env(create_options.env(), &env);
 
                 let mut labels = create_options
                     .labels()
                     .cloned()
                     .unwrap_or_else(HashMap::new);
                 labels.insert(LABEL_KEY.to_string(), LABEL_VALUE.to_string());
+                labels.insert(
+                    PRIORITY_LABEL_KEY.to_string(),
+                    module.priority().to_string(),
+                );
+                if let Some(namespace) = module.namespace() {
+                    self.namespaces.register(namespace)?;
+                    labels.insert(NAMESPACE_LABEL_KEY.to_string(), namespace.to_string());
+                }
+                if let Some(restart_policy) = module.restart_policy() {
+                    labels.insert(
+                        RESTART_POLICY_LABEL_KEY.to_string(),
+                        restart_policy.to_string(),
+                    );
+                }
+                for (key, value) in module.labels() {
+                    labels.insert(format!("{}{}", MODULE_LABEL_PREFIX, key), value.clone());
+                }
+                // the allowlist is kept with the container, so that it is
+                // enforced again whenever the container gets a new address
+                if let Some(egress) = module.config().egress() {
+                    if cfg!(not(unix)) {
+                        return Err(Error::from(ErrorKind::InvalidEgressPolicy(
+                            "egress allowlists are not supported on Windows hosts".to_string(),
+                        )));
+                    }
+                    egress.validate()?;
+                    labels.insert(EGRESS_LABEL_KEY.to_string(), serde_json::to_string(egress)?);
+                }
+
+                // an OOM score adjustment set explicitly in the create options
+                // takes precedence over the one derived from the priority
+                let host_config = create_options
+                    .host_config()
+                    .cloned()
+                    .unwrap_or_else(HostConfig::new);
+                let host_config = if host_config.oom_score_adj().is_some() {
+                    host_config
+                } else {
+                    host_config.with_oom_score_adj(module.priority().oom_score_adj())
+                };
+                // the module's restart policy takes precedence over the one
+                // in the create options, so that what docker does can be
+                // checked against it later
+                let host_config = match module.restart_policy() {
+                    Some(restart_policy) => host_config.with_restart_policy(
+                        DockerRestartPolicy::new()
+                            .with_name(docker_restart_policy(restart_policy).to_string()),
+                    ),
+                    None => host_config,
+                };
+                let host_config = match self.default_memory_limit {
+                    Some(memory_limit) if host_config.memory().is_none() => {
+                        host_config.with_memory(memory_limit)
+                    }
+                    _ => host_config,
+                };
+                let host_config = cgroup::supported_limits(host_config, self.cgroup_version);
+                // isolation set explicitly in the create options takes
+                // precedence over the module's, which takes precedence over
+                // the default
+                let isolation = if host_config.isolation().is_some() {
+                    None
+                } else {
+                    module.config().isolation().or(self.default_isolation)
+                };
+                let host_config = match isolation {
+                    Some(isolation) => host_config.with_isolation(isolation.to_string()),
+                    None => host_config,
+                };
+                let host_config = match self.trust_bundle_files {
+                    Some((ref files, ref container_dir)) => {
+                        let dir = files.add(module.name())?;
+                        mount_trust_bundle(host_config, &dir, container_dir)
+                    }
+                    None => host_config,
+                };
+                let host_config = if self.localtime_mount {
+                    let files: Vec<&Path> = LOCALTIME_FILES
+                        .iter()
+                        .map(Path::new)
+                        .filter(|file| file.exists())
+                        .collect();
+                    mount_localtime(host_config, &files)
+                } else {
+                    host_config
+                };
+
+                // a module in a namespace only joins the network of its
+                // namespace, and binds the workload socket of its namespace
+                let own_network = module.namespace().and_then(|namespace| {
+                    self.network_id
+                        .as_ref()
+                        .map(|id| namespace_network(id, namespace))
+                });
+                let (host_config, create_options) = match module.namespace() {
+                    Some(namespace) => {
+                        let binds = host_config
+                            .binds()
+                            .map(|binds| namespace_binds(binds, &self.namespaces, namespace));
+                        let host_config = match binds {
+                            Some(binds) => host_config.with_binds(binds),
+                            None => host_config,
+                        };
+                        match own_network {
+                            Some(ref network) => (
+                                host_config.with_network_mode(network.clone()),
+                                join_only(create_options, network),
+                            ),
+                            None => (host_config, create_options),
+                        }
+                    }
+                    None => (host_config, create_options),
+                };
 
+                let image = upstream_image(
+                    module.config().image(),
+                    self.parent_hostname.as_ref().map(AsRef::as_ref),
+                );
                 debug!(
-                    "Creating container {} with image {}",
+                    "Creating container {} with image {} and priority {}",
                     module.name(),
-                    module.config().image()
+                    image,
+                    module.priority()
                 );
 
                 let create_options = create_options
-                    .with_image(module.config().image().to_string())
+                    .with_image(image.clone())
                     .with_env(merged_env)
-                    .with_labels(labels);
+                    .with_labels(labels)
+                    .with_host_config(host_config);
 
                 // Here we don't add the container to the iot edge docker network as the edge-agent is expected to do that.
                 // It contains the logic to add a container to the iot edge network only if a network is not already specified.
 
+                let networked = match (module.namespace(), own_network) {
+                    (Some(namespace), Some(network)) => {
+                        future::Either::A(self.ensure_namespace_network(network, namespace))
+                    }
+                    _ => future::Either::B(future::ok(())),
+                };
+                // modules outside of any namespace are shared by all of them
+                let shared_client = match (module.namespace(), &self.network_id) {
+                    (None, Some(_)) => Some(self.client.clone()),
+                    _ => None,
+                };
+
+                let client = self.client.clone();
+                let outbox = self.outbox.clone();
+                let name = module.name().to_string();
                 Ok(self
-                    .client
-                    .container_api()
-                    .container_create(create_options, module.name())
-                    .map_err(Error::from)
-                    .map(|_| ()))
+                    .resolve_image(&image)
+                    .join3(self.check_isolation(isolation), networked)
+                    .and_then(move |(resolved, _, _)| {
+                        let create_options = match resolved {
+                            Some(ref resolved) => label_image(create_options, resolved),
+                            None => create_options,
+                        };
+                        client
+                            .container_api()
+                            .container_create(create_options, &name)
+                            .map_err(Error::from)
+                            .and_then(move |_| {
+                                if let Some(resolved) = resolved {
+                                    report_image(&outbox, &name, &resolved);
+                                }
+                                match shared_client {
+                                    Some(client) => {
+                                        future::Either::A(join_namespace_networks(&client, &name))
+                                    }
+                                    None => future::Either::B(future::ok(())),
+                                }
+                            })
+                    }))
             });
 
         match result {
-            Ok(f) => Box::new(f),
+            Ok(f) => Box::new(with_fault("create", f)),
             Err(err) => {
                 warn!("Attempt to create a container failed.");
                 log_failure(Level::Warn, &err);
@@ -258,27 +1516,38 @@ impl ModuleRuntime for DockerModuleRuntime {
 
     fn start(&self, id: &str) -> Self::StartFuture {
         debug!("Starting container {}", id);
-        Box::new(
-            self.client
-                .container_api()
-                .container_start(fensure_not_empty!(id), "")
+        let start_client = self.client.clone();
+        let start_id = id.to_string();
+        let runtime = self.clone();
+        let name = id.to_string();
+        Box::new(with_fault(
+            "start",
+            self.restore_restart_policy(fensure_not_empty!(id))
+                .and_then(move |_| {
+                    start_client
+                        .container_api()
+                        .container_start(&start_id, "")
+                        .map_err(Error::from)
+                }).and_then(move |_| runtime.enforce_module_egress(name))
                 .map_err(|err| {
-                    let e = Error::from(err);
                     warn!("Attempt to start a container failed.");
-                    log_failure(Level::Warn, &e);
-                    e
+                    log_failure(Level::Warn, &err);
+                    err
                 }).map(|_| ()),
-        )
+        ))
     }
 
     fn stop(&self, id: &str, wait_before_kill: Option<Duration>) -> Self::StopFuture {
         debug!("Stopping container {}", id);
+        let firewall = self.egress_firewall.clone();
+        let name = id.to_string();
 
         #[cfg_attr(
             feature = "cargo-clippy",
             allow(cast_possible_truncation, cast_sign_loss)
         )]
-        Box::new(
+        Box::new(with_fault(
+            "stop",
             self.client
                 .container_api()
                 .container_stop(
@@ -292,17 +1561,18 @@ impl ModuleRuntime for DockerModuleRuntime {
                     warn!("Attempt to stop a container failed.");
                     log_failure(Level::Warn, &e);
                     e
-                }).map(|_| ()),
-        )
+                }).and_then(move |_| blocking(move || remove_egress_rules(&firewall, &name))),
+        ))
     }
 
     fn system_info(&self) -> Self::SystemInfoFuture {
-        Box::new(
+        Box::new(with_fault(
+            "system_info",
             self.client
                 .system_api()
                 .system_info()
                 .map(|system_info| {
-                    CoreSystemInfo::new(
+                    let info = CoreSystemInfo::new(
                         system_info
                             .os_type()
                             .unwrap_or(&String::from("Unknown"))
@@ -311,19 +1581,24 @@ impl ModuleRuntime for DockerModuleRuntime {
                             .architecture()
                             .unwrap_or(&String::from("Unknown"))
                             .to_string(),
-                    )
+                    );
+                    match system_info.server_version() {
+                        Some(version) => info.with_server_version(version.to_string()),
+                        None => info,
+                    }
                 }).map_err(|err| {
                     let e = Error::from(err);
                     warn!("Attempt to get system information failed.");
                     log_failure(Level::Warn, &e);
                     e
                 }),
-        )
+        ))
     }
 
     fn restart(&self, id: &str) -> Self::RestartFuture {
         debug!("Restarting container {}", id);
-        Box::new(
+        Box::new(with_fault(
+            "restart",
             self.client
                 .container_api()
                 .container_restart(fensure_not_empty!(id), WAIT_BEFORE_KILL_SECONDS)
@@ -333,26 +1608,15 @@ impl ModuleRuntime for DockerModuleRuntime {
                     log_failure(Level::Warn, &e);
                     e
                 }).map(|_| ()),
-        )
+        ))
     }
 
     fn remove(&self, id: &str) -> Self::RemoveFuture {
-        debug!("Removing container {}", id);
-        Box::new(
-            self.client
-                .container_api()
-                .container_delete(
-                    fensure_not_empty!(id),
-                    /* remove volumes */ false,
-                    /* force */ true,
-                    /* remove link */ false,
-                ).map_err(|err| {
-                    let e = Error::from(err);
-                    warn!("Attempt to remove a container failed.");
-                    log_failure(Level::Warn, &e);
-                    e
-                }).map(|_| ()),
-        )
+        self.remove_container(id, false)
+    }
+
+    fn remove_with_volumes(&self, id: &str) -> Self::RemoveFuture {
+        self.remove_container(id, true)
     }
 
     fn list(&self) -> Self::ListFuture {
@@ -378,7 +1642,10 @@ impl ModuleRuntime for DockerModuleRuntime {
                                 ).map(|config| {
                                     (
                                         container,
-                                        config.with_image_id(container.image_id().clone()),
+                                        with_resolved_image(
+                                            config.with_image_id(container.image_id().clone()),
+                                            container.labels(),
+                                        ),
                                     )
                                 })
                             }).flat_map(|(container, config)| {
@@ -400,20 +1667,49 @@ impl ModuleRuntime for DockerModuleRuntime {
                 log_failure(Level::Warn, &err);
                 err
             });
-        Box::new(result)
+        Box::new(with_fault("list", result))
     }
 
     fn list_with_details(&self) -> Self::ListWithDetailsStream {
-        list_with_details(self)
+        let canaries = self.canaries.clone();
+        Box::new(list_with_details(self).map(move |(module, state)| {
+            let state = match canaries.progress(module.name()) {
+                Some(progress) => state.with_status_description(Some(progress)),
+                None => state,
+            };
+            (module, state)
+        }))
     }
 
     fn logs(&self, id: &str, options: &LogOptions) -> Self::LogsFuture {
+        let filter = match LogFilter::from_options(options) {
+            Ok(filter) => filter,
+            Err(err) => return Box::new(future::err(Error::from(err))),
+        };
         let tail = &options.tail().to_string();
+        let cancellation = self.cancellation.clone();
         let result = self
             .client
             .container_api()
-            .container_logs(id, options.follow(), true, true, 0, false, tail)
-            .map(Logs)
+            .container_logs(
+                id,
+                options.follow(),
+                true,
+                true,
+                options.since(),
+                false,
+                tail,
+            )
+            .map(move |body| {
+                // The lines that aren't asked for are dropped as they are
+                // read, rather than sent to the client.
+                let body = cancellation.guard_stream(body);
+                if filter.keeps_everything() {
+                    Logs(Body::wrap_stream(body))
+                } else {
+                    Logs(Body::wrap_stream(filter.filter(body)))
+                }
+            })
             .map_err(|err| {
                 let e = Error::from(err);
                 warn!("Attempt to get container logs failed.");
@@ -436,6 +1732,190 @@ impl ModuleRuntime for DockerModuleRuntime {
             future::join_all(n).map(|_| ())
         }))
     }
+
+    fn update(
+        &self,
+        module: ModuleSpec<Self::Config>,
+        strategy: UpdateStrategy,
+    ) -> Self::UpdateFuture {
+        match strategy {
+            UpdateStrategy::Replace => {
+                let self_for_create = self.clone();
+                Box::new(
+                    <DockerModuleRuntime as ModuleRuntime>::remove(self, module.name())
+                        .and_then(move |_| self_for_create.create(module)),
+                )
+            }
+            UpdateStrategy::Canary(soak) => self.update_canary(module, soak),
+        }
+    }
+}
+
+impl ImageBuilder for DockerModuleRuntime {
+    type Error = Error;
+    type Context = Body;
+    type Chunk = Chunk;
+    type Output = Logs;
+    type BuildFuture = Box<Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn build_image(&self, tag: &str, dockerfile: &str, context: Body) -> Self::BuildFuture {
+        info!("Building image {}", tag);
+        let cancellation = self.cancellation.clone();
+        let result = self
+            .client
+            .image_api()
+            .image_build(
+                context,
+                fensure_not_empty!(dockerfile),
+                fensure_not_empty!(tag),
+                false,
+                false,
+                true,
+                true,
+                "",
+            ).map(move |body| Logs(Body::wrap_stream(cancellation.guard_stream(body))))
+            .map_err(|err| {
+                let e = Error::from(err);
+                warn!("Attempt to build an image failed.");
+                log_failure(Level::Warn, &e);
+                e
+            });
+        Box::new(self.cancellation.guard(result))
+    }
+}
+
+impl ModuleConsole for DockerModuleRuntime {
+    type Error = Error;
+    type Connection = Upgraded;
+    type AttachFuture = Box<Future<Item = Self::Connection, Error = Self::Error> + Send>;
+
+    // Docker multiplexes stdout and stderr on the connection unless the
+    // container has a TTY. Only containers created with `OpenStdin` read what
+    // is written to it.
+    fn attach(&self, id: &str) -> Self::AttachFuture {
+        debug!("Attaching to module {}", id);
+        let result = self
+            .client
+            .container_api()
+            .container_attach(fensure_not_empty!(id), "", false, true, true, true, true)
+            .map_err(|err| {
+                let e = Error::from(err);
+                warn!("Attempt to attach to a module failed.");
+                log_failure(Level::Warn, &e);
+                e
+            });
+        Box::new(self.cancellation.guard(result))
+    }
+}
+
+impl DiskUsageProvider for DockerModuleRuntime {
+    type Error = Error;
+    type DiskUsageFuture = Box<Future<Item = DiskUsage, Error = Self::Error> + Send>;
+
+    // `system df` reports the writable layer of every container, but not the
+    // quota on it, which is only in the host config of each container.
+    fn disk_usage(&self) -> Self::DiskUsageFuture {
+        let client = self.client.clone();
+        let result = self
+            .client
+            .system_api()
+            .system_data_usage()
+            .map_err(Error::from)
+            .and_then(move |df| {
+                let usage = DiskUsage::new()
+                    .with_images(bytes(df.layers_size().unwrap_or(0)))
+                    .with_volumes(
+                        df.volumes()
+                            .unwrap_or(&[])
+                            .iter()
+                            .map(volume_disk_usage)
+                            .collect(),
+                    );
+                let modules: Vec<_> = df
+                    .containers()
+                    .unwrap_or(&[])
+                    .iter()
+                    .filter(|container| {
+                        container.labels().get(LABEL_KEY).map(String::as_str) == Some(LABEL_VALUE)
+                    }).map(|container| module_disk_usage(&client, container))
+                    .collect();
+                future::join_all(modules).map(move |modules| usage.with_modules(modules))
+            }).map_err(|err| {
+                warn!("Attempt to get the disk usage failed.");
+                log_failure(Level::Warn, &err);
+                err
+            });
+        Box::new(self.cancellation.guard(result))
+    }
+}
+
+// The writable layer of the container of a module, along with the quota on
+// it. The quota is left out if the container can't be inspected.
+fn module_disk_usage(
+    client: &DockerClient<RetryConnector>,
+    container: &ContainerSummary,
+) -> impl Future<Item = ModuleDiskUsage, Error = Error> + Send {
+    let name = container
+        .names()
+        .iter()
+        .next()
+        .map_or("Unknown", |s| &s[1..])
+        .to_string();
+    let module = ModuleDiskUsage::new(name.clone(), bytes(*container.size_rw()));
+    client
+        .container_api()
+        .container_inspect(&name, false)
+        .then(move |details| {
+            let quota = details.ok().and_then(|details| {
+                details
+                    .host_config()
+                    .and_then(HostConfig::storage_opt)
+                    .and_then(storage_quota)
+            });
+            Ok::<_, Error>(match quota {
+                Some(quota) => module.with_quota(quota),
+                None => module,
+            })
+        })
+}
+
+fn volume_disk_usage(volume: &Volume) -> VolumeDiskUsage {
+    let usage = VolumeDiskUsage::new(volume.name().clone());
+    // drivers other than "local" report -1
+    match volume.usage_data().map(|data| *data.size()) {
+        Some(size) if size >= 0 => usage.with_size(bytes(size)),
+        _ => usage,
+    }
+}
+
+// Docker reports sizes as signed integers.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_sign_loss))]
+fn bytes(size: i64) -> u64 {
+    if size < 0 {
+        0
+    } else {
+        size as u64
+    }
+}
+
+// The `size` storage option of a container caps its writable layer. It is in
+// bytes, or in binary units such as "512m" or "10G".
+fn storage_quota(storage_opt: &HashMap<String, String>) -> Option<u64> {
+    let size = storage_opt.get("size")?.trim().to_lowercase();
+    let size = size.trim_right_matches('b').trim_right_matches('i');
+    let (number, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => size.split_at(index),
+        None => (size, ""),
+    };
+    let multiplier: u64 = match unit.trim() {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return None,
+    };
+    number.parse::<u64>().ok().map(|number| number * multiplier)
 }
 
 #[derive(Debug)]
@@ -534,7 +2014,7 @@ mod tests {
 
     use docker::models::ContainerCreateBody;
     use edgelet_core::pid::Pid;
-    use edgelet_core::ModuleRegistry;
+    use edgelet_core::{ModuleRegistry, UpdateStrategy};
 
     use error::{Error, ErrorKind};
 
@@ -563,6 +2043,22 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn storage_quotas_are_read_in_binary_units() {
+        let quota = |size: &str| {
+            let mut storage_opt = HashMap::new();
+            storage_opt.insert("size".to_string(), size.to_string());
+            storage_quota(&storage_opt)
+        };
+        assert_eq!(Some(1024), quota("1024"));
+        assert_eq!(Some(512 * 1024 * 1024), quota("512m"));
+        assert_eq!(Some(10 * 1024 * 1024 * 1024), quota("10G"));
+        assert_eq!(Some(2 * 1024 * 1024 * 1024), quota("2GiB"));
+        assert_eq!(None, quota("1.5g"));
+        assert_eq!(None, quota("lots"));
+        assert_eq!(None, storage_quota(&HashMap::new()));
+    }
+
     fn empty_test<F, R>(tester: F)
     where
         F: Fn(&mut DockerModuleRuntime) -> R,
@@ -600,6 +2096,29 @@ mod tests {
         empty_test(|ref mut mri| <DockerModuleRuntime as ModuleRegistry>::remove(mri, "     "));
     }
 
+    #[test]
+    fn pull_is_aborted_once_cancelled() {
+        let cancellation = CancellationToken::new();
+        let mri = DockerModuleRuntime::new(&Url::parse("http://localhost/").unwrap())
+            .unwrap()
+            .with_cancellation(cancellation.clone());
+        let config = DockerConfig::new("nginx:latest", ContainerCreateBody::new(), None).unwrap();
+        cancellation.cancel();
+
+        let err = tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(mri.pull(&config))
+            .unwrap_err();
+        assert_eq!(
+            mem::discriminant(&ErrorKind::Core),
+            mem::discriminant(err.kind())
+        );
+        assert_eq!(
+            "The operation was cancelled",
+            err.cause().unwrap().to_string()
+        );
+    }
+
     #[test]
     fn merge_env_empty() {
         let cur_env = Some(&[][..]);
@@ -665,6 +2184,168 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn architectures_match_across_naming_schemes() {
+        assert!(architectures_match("amd64", "x86_64"));
+        assert!(architectures_match("arm", "armv7l"));
+        assert!(architectures_match("arm64", "aarch64"));
+        assert!(architectures_match("386", "i686"));
+        assert!(architectures_match("s390x", "s390x"));
+        assert!(!architectures_match("amd64", "armv7l"));
+        assert!(!architectures_match("arm64", "armv7l"));
+        assert!(!architectures_match("arm", "x86_64"));
+    }
+
+    #[test]
+    fn namespaces_bind_their_own_workload_socket() {
+        let namespaces =
+            Namespaces::new().with_workload_socket(PathBuf::from("/var/run/iotedge/workload.sock"));
+        let binds = vec![
+            "/var/run/iotedge/workload.sock:/var/run/iotedge/workload.sock".to_string(),
+            "/var/run/iotedge:/var/run/iotedge:ro".to_string(),
+            "/data:/data".to_string(),
+        ];
+
+        assert_eq!(
+            vec![
+                "/var/run/iotedge/contoso/workload.sock:/var/run/iotedge/workload.sock",
+                "/var/run/iotedge/contoso:/var/run/iotedge:ro",
+                "/data:/data",
+            ],
+            namespace_binds(&binds, &namespaces, "contoso")
+        );
+        assert_eq!(binds, namespace_binds(&binds, &Namespaces::new(), "contoso"));
+    }
+
+    #[test]
+    fn trust_bundle_is_mounted_read_only() {
+        let host_config = HostConfig::new().with_binds(vec!["/data:/data".to_string()]);
+
+        let host_config = mount_trust_bundle(
+            host_config,
+            Path::new("/var/lib/iotedge/trust-bundles/sensor"),
+            Path::new("/etc/iotedge"),
+        );
+
+        assert_eq!(
+            Some(
+                &[
+                    "/data:/data".to_string(),
+                    "/var/lib/iotedge/trust-bundles/sensor:/etc/iotedge:ro".to_string(),
+                ][..]
+            ),
+            host_config.binds()
+        );
+        let host_config = mount_trust_bundle(HostConfig::new(), Path::new("/b"), Path::new("/c"));
+        assert_eq!(Some(&["/b:/c:ro".to_string()][..]), host_config.binds());
+    }
+
+    #[test]
+    fn localtime_is_mounted_read_only() {
+        let host_config =
+            HostConfig::new().with_binds(vec!["/zoneinfo/UTC:/etc/localtime".to_string()]);
+
+        let host_config = mount_localtime(
+            host_config,
+            &[Path::new("/etc/localtime"), Path::new("/etc/timezone")],
+        );
+
+        assert_eq!(
+            Some(
+                &[
+                    "/zoneinfo/UTC:/etc/localtime".to_string(),
+                    "/etc/timezone:/etc/timezone:ro".to_string(),
+                ][..]
+            ),
+            host_config.binds()
+        );
+    }
+
+    #[test]
+    fn namespaced_modules_join_only_their_network() {
+        let mut endpoints = HashMap::new();
+        endpoints.insert(
+            "azure-iot-edge".to_string(),
+            EndpointSettings::new().with_aliases(vec!["sensor".to_string()]),
+        );
+        let create_options = ContainerCreateBody::new().with_networking_config(
+            ContainerCreateBodyNetworkingConfig::new().with_endpoints_config(endpoints),
+        );
+
+        let network = namespace_network("azure-iot-edge", "contoso");
+        let create_options = join_only(create_options, &network);
+        let endpoints = create_options
+            .networking_config()
+            .and_then(|config| config.endpoints_config())
+            .unwrap();
+        assert_eq!(
+            vec!["azure-iot-edge-contoso"],
+            endpoints.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(&["sensor".to_string()][..]),
+            endpoints["azure-iot-edge-contoso"].aliases()
+        );
+    }
+
+    #[test]
+    fn resolved_images_are_read_back_from_labels() {
+        let mut labels = HashMap::new();
+        labels.insert(DIGEST_LABEL_KEY.to_string(), "sha256:222".to_string());
+        labels.insert(PLATFORM_LABEL_KEY.to_string(), "linux/arm".to_string());
+        let config = DockerConfig::new("sensor:1.0", ContainerCreateBody::new(), None).unwrap();
+
+        let config = with_resolved_image(config, &labels);
+        assert_eq!(Some("sha256:222"), config.image_digest());
+        assert_eq!(Some("linux/arm"), config.platform());
+
+        let config = DockerConfig::new("sensor:1.0", ContainerCreateBody::new(), None).unwrap();
+        let config = with_resolved_image(config, &HashMap::new());
+        assert_eq!(None, config.image_digest());
+        assert_eq!(None, config.platform());
+    }
+
+    #[test]
+    fn isolation_is_only_supported_on_windows() {
+        assert!(check_isolation_support(Isolation::HyperV, Some("windows")).is_ok());
+        assert!(check_isolation_support(Isolation::Process, Some("windows")).is_ok());
+        assert!(check_isolation_support(Isolation::Process, None).is_ok());
+
+        let err = check_isolation_support(Isolation::HyperV, Some("linux")).unwrap_err();
+        match *err.kind() {
+            ErrorKind::IsolationNotSupported(ref isolation, ref os_type) => {
+                assert_eq!("hyperv", isolation);
+                assert_eq!("linux", os_type);
+            }
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn create_fails_for_invalid_name() {
+        let mri = DockerModuleRuntime::new(&Url::parse("http://localhost/").unwrap()).unwrap();
+
+        let module_config = ModuleSpec::new(
+            "../m1",
+            DOCKER_MODULE_TYPE,
+            DockerConfig::new("nginx:latest", ContainerCreateBody::new(), None).unwrap(),
+            HashMap::new(),
+        ).unwrap();
+
+        let task = mri.create(module_config).then(|result| match result {
+            Ok(_) => panic!("Expected test to fail but it didn't!"),
+            Err(err) => match *err.kind() {
+                ErrorKind::Core => Ok::<_, Error>(()),
+                _ => panic!("Expected core error. Got some other error."),
+            },
+        });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
     #[test]
     fn start_fails_for_empty_id() {
         let mri = DockerModuleRuntime::new(&Url::parse("http://localhost/").unwrap()).unwrap();
@@ -853,6 +2534,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn canary_config_labels_the_canary_with_its_module() {
+        let mut labels = HashMap::new();
+        labels.insert("tier".to_string(), "web".to_string());
+        let config = DockerConfig::new(
+            "ubuntu",
+            ContainerCreateBody::new().with_labels(labels),
+            None,
+        ).unwrap();
+
+        let config = canary_config(&config, "m1");
+        let labels = config.create_options().labels().unwrap();
+        assert_eq!(Some(&"m1".to_string()), labels.get(IDENTITY_LABEL_KEY));
+        assert_eq!(Some(&"web".to_string()), labels.get("tier"));
+    }
+
+    #[test]
+    fn egress_networks_are_the_addresses_of_containers() {
+        let mut networks = HashMap::new();
+        networks.insert(
+            "azure-iot-edge".to_string(),
+            EndpointSettings::new()
+                .with_ip_address("172.18.0.4".to_string())
+                .with_ip_prefix_len(16),
+        );
+        networks.insert(
+            "plant".to_string(),
+            EndpointSettings::new()
+                .with_ip_address("10.2.0.7".to_string())
+                .with_ip_prefix_len(24)
+                .with_global_i_pv6_address("fd00:2::7".to_string())
+                .with_global_i_pv6_prefix_len(64),
+        );
+        networks.insert(
+            "disconnected".to_string(),
+            EndpointSettings::new().with_ip_address("".to_string()),
+        );
+
+        assert_eq!(
+            vec![
+                ("10.2.0.7".parse().unwrap(), 24),
+                ("172.18.0.4".parse().unwrap(), 16),
+                ("fd00:2::7".parse().unwrap(), 64),
+            ],
+            egress_networks(&networks)
+        );
+    }
+
     struct TestConfig;
 
     #[derive(Clone, Copy, Debug, PartialEq)]
@@ -936,6 +2665,7 @@ mod tests {
         type StopFuture = FutureResult<(), Self::Error>;
         type SystemInfoFuture = FutureResult<CoreSystemInfo, Self::Error>;
         type RemoveAllFuture = FutureResult<(), Self::Error>;
+        type UpdateFuture = FutureResult<(), Self::Error>;
 
         fn init(&self) -> Self::InitFuture {
             unimplemented!()
@@ -965,6 +2695,10 @@ mod tests {
             unimplemented!()
         }
 
+        fn remove_with_volumes(&self, _id: &str) -> Self::RemoveFuture {
+            unimplemented!()
+        }
+
         fn list(&self) -> Self::ListFuture {
             future::ok(self.modules.clone())
         }
@@ -984,5 +2718,13 @@ mod tests {
         fn remove_all(&self) -> Self::RemoveAllFuture {
             unimplemented!()
         }
+
+        fn update(
+            &self,
+            _module: ModuleSpec<Self::Config>,
+            _strategy: UpdateStrategy,
+        ) -> Self::UpdateFuture {
+            unimplemented!()
+        }
     }
 }
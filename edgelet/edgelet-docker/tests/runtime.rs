@@ -26,11 +26,13 @@ extern crate edgelet_test_utils;
 use std::collections::HashMap;
 use std::str;
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::prelude::*;
 use futures::{future, Stream};
 use hyper::{Body, Error as HyperError, Method, Request, Response};
+use tokio::runtime::current_thread::Runtime;
+use tokio::timer::Delay;
 use typed_headers::{mime, ContentLength, ContentType, HeaderMapExt};
 use url::form_urlencoded::parse as parse_query;
 use url::Url;
@@ -39,9 +41,13 @@ use url::Url;
 use docker::models::AuthConfig;
 use docker::models::{
     ContainerCreateBody, ContainerHostConfig, ContainerNetworkSettings, ContainerSummary,
-    HostConfig, HostConfigPortBindings, ImageDeleteResponseItem,
+    GraphDriverData, HostConfig, HostConfigPortBindings, Image, ImageDeleteResponseItem,
+    ImageRootFs,
+};
+use edgelet_core::{
+    LogOptions, LogTail, Module, ModulePriority, ModuleRegistry, ModuleRuntime, ModuleSpec,
+    RestartPolicy, UpdateStrategy,
 };
-use edgelet_core::{LogOptions, LogTail, Module, ModuleRegistry, ModuleRuntime, ModuleSpec};
 use edgelet_docker::{DockerConfig, DockerModuleRuntime};
 use edgelet_test_utils::{get_unused_tcp_port, run_tcp_server};
 
@@ -489,9 +495,64 @@ fn image_remove_succeeds() {
     runtime.block_on(task).unwrap();
 }
 
+fn json_response(response: String) -> Response<Body> {
+    let response_len = response.len();
+    let mut response = Response::new(response.into());
+    response
+        .headers_mut()
+        .typed_insert(&ContentLength(response_len as u64));
+    response
+        .headers_mut()
+        .typed_insert(&ContentType(mime::APPLICATION_JSON));
+    response
+}
+
+// Answers the image inspect and system info queries container creation makes
+// to validate the image architecture, or returns `None` for any other request.
+fn platform_handler(
+    req: &Request<Body>,
+    image_arch: &str,
+    host_arch: &str,
+) -> Option<Box<Future<Item = Response<Body>, Error = HyperError> + Send>> {
+    if *req.method() != Method::GET {
+        return None;
+    }
+
+    let response = if req.uri().path() == format!("/images/{}/json", IMAGE_NAME) {
+        serde_json::to_string(&Image::new(
+            "sha256:12345".to_string(),
+            "".to_string(),
+            "".to_string(),
+            "2018-10-01T00:00:00Z".to_string(),
+            "".to_string(),
+            "18.06.1".to_string(),
+            "".to_string(),
+            image_arch.to_string(),
+            "linux".to_string(),
+            0,
+            0,
+            GraphDriverData::new("overlay2".to_string()),
+            ImageRootFs::new("layers".to_string()),
+        )).unwrap()
+    } else if req.uri().path() == "/info" {
+        json!({
+            "OSType": "linux",
+            "Architecture": host_arch,
+        }).to_string()
+    } else {
+        return None;
+    };
+
+    Some(Box::new(future::ok(json_response(response))))
+}
+
 fn container_create_handler(
     req: Request<Body>,
 ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+    if let Some(response) = platform_handler(&req, "amd64", "x86_64") {
+        return response;
+    }
+
     assert_eq!(req.method(), &Method::POST);
     assert_eq!(req.uri().path(), "/containers/create");
 
@@ -560,6 +621,31 @@ fn container_create_handler(
                 expected.insert("test1".to_string(), json!({}));
                 assert_eq!(*volumes, expected);
 
+                assert_eq!(
+                    "high",
+                    create_options.labels().unwrap()["net.azure-devices.edge.priority"]
+                );
+                assert_eq!(
+                    Some(-500),
+                    create_options.host_config().unwrap().oom_score_adj()
+                );
+                assert_eq!(
+                    "on-unhealthy",
+                    create_options.labels().unwrap()["net.azure-devices.edge.restart-policy"]
+                );
+                assert_eq!(
+                    Some("on-failure"),
+                    create_options
+                        .host_config()
+                        .unwrap()
+                        .restart_policy()
+                        .and_then(|policy| policy.name())
+                );
+                assert_eq!(
+                    "web",
+                    create_options.labels().unwrap()["net.azure-devices.edge.label.tier"]
+                );
+
                 Ok(())
             }).map(move |_| {
                 let mut response = Response::new(response.into());
@@ -611,13 +697,18 @@ fn container_create_succeeds() {
             "and this".to_string(),
         ]).with_env(vec!["k4=v4".to_string(), "k5=v5".to_string()])
         .with_volumes(volumes);
+    let mut labels = HashMap::new();
+    labels.insert("tier".to_string(), "web".to_string());
 
     let module_config = ModuleSpec::new(
         "m1",
         "docker",
         DockerConfig::new("nginx:latest", create_options, None).unwrap(),
         env,
-    ).unwrap();
+    ).unwrap()
+    .with_priority(ModulePriority::High)
+    .with_restart_policy(RestartPolicy::OnUnhealthy)
+    .with_labels(labels);
 
     let mri =
         DockerModuleRuntime::new(&Url::parse(&format!("http://localhost:{}/", port)).unwrap())
@@ -631,10 +722,114 @@ fn container_create_succeeds() {
     runtime.block_on(task).unwrap();
 }
 
+fn container_create_default_memory_limit_handler(
+    req: Request<Body>,
+) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+    if let Some(response) = platform_handler(&req, "amd64", "x86_64") {
+        return response;
+    }
+
+    assert_eq!(req.uri().path(), "/containers/create");
+    let query_map: HashMap<String, String> = parse_query(req.uri().query().unwrap().as_bytes())
+        .into_owned()
+        .collect();
+    let module = query_map["name"].clone();
+
+    Box::new(req.into_body().concat2().map(move |body| {
+        let create_options: ContainerCreateBody = serde_json::from_slice(body.as_ref()).unwrap();
+        let expected = if module == "m1" {
+            134_217_728
+        } else {
+            3_221_225_472
+        };
+        assert_eq!(
+            Some(expected),
+            create_options.host_config().unwrap().memory()
+        );
+
+        json_response(
+            json!({
+                "Id": "12345",
+                "Warnings": []
+            }).to_string(),
+        )
+    }))
+}
+
+#[test]
+fn container_create_applies_default_memory_limit() {
+    let port = get_unused_tcp_port();
+    let server = run_tcp_server(
+        "127.0.0.1",
+        port,
+        container_create_default_memory_limit_handler,
+    ).map_err(|err| eprintln!("{}", err));
+
+    let module_config = |name, create_options| {
+        ModuleSpec::new(
+            name,
+            "docker",
+            DockerConfig::new("nginx:latest", create_options, None).unwrap(),
+            HashMap::new(),
+        ).unwrap()
+    };
+    let limited = ContainerCreateBody::new()
+        .with_host_config(HostConfig::new().with_memory(3_221_225_472));
+
+    let mri =
+        DockerModuleRuntime::new(&Url::parse(&format!("http://localhost:{}/", port)).unwrap())
+            .unwrap()
+            .with_default_memory_limit(134_217_728);
+
+    let task = mri
+        .create(module_config("m1", ContainerCreateBody::new()))
+        .and_then(move |_| mri.create(module_config("m2", limited)));
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    runtime.spawn(server);
+    runtime.block_on(task).unwrap();
+}
+
+#[test]
+fn container_create_fails_for_mismatched_architecture() {
+    let port = get_unused_tcp_port();
+    let server = run_tcp_server("127.0.0.1", port, |req: Request<Body>| {
+        platform_handler(&req, "arm64", "armv7l")
+            .unwrap_or_else(|| panic!("unexpected request {} {}", req.method(), req.uri()))
+    }).map_err(|err| eprintln!("{}", err));
+
+    let module_config = ModuleSpec::new(
+        "m1",
+        "docker",
+        DockerConfig::new(IMAGE_NAME, ContainerCreateBody::new(), None).unwrap(),
+        HashMap::new(),
+    ).unwrap();
+
+    let mri =
+        DockerModuleRuntime::new(&Url::parse(&format!("http://localhost:{}/", port)).unwrap())
+            .unwrap();
+
+    let task = mri.create(module_config);
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    runtime.spawn(server);
+    let err = runtime.block_on(task).unwrap_err();
+    assert_eq!(
+        "Image architecture arm64 does not match host armv7l",
+        err.to_string()
+    );
+}
+
 #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
 fn container_start_handler(
     req: Request<Body>,
 ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+    // the restart policy of the container is checked before it is started
+    if req.method() == Method::GET {
+        assert_eq!(req.uri().path(), "/containers/m1/json");
+        return Box::new(future::ok(json_response(json!({ "Id": "m1" }).to_string())));
+    }
+
     assert_eq!(req.method(), &Method::POST);
     assert_eq!(req.uri().path(), "/containers/m1/start");
 
@@ -658,6 +853,57 @@ fn container_start_succeeds() {
     runtime.block_on(task).unwrap();
 }
 
+#[test]
+fn container_start_restores_drifted_restart_policy() {
+    let port = get_unused_tcp_port();
+    let requests = Arc::new(RwLock::new(vec![]));
+    let server_requests = requests.clone();
+    let server = run_tcp_server("127.0.0.1", port, move |req: Request<Body>| {
+        let request = format!("{} {}", req.method(), req.uri().path());
+        let response = match (req.method(), req.uri().path()) {
+            (&Method::GET, "/containers/m1/json") => json_response(
+                json!({
+                    "Id": "m1",
+                    "Config": {
+                        "Labels": { "net.azure-devices.edge.restart-policy": "never" }
+                    },
+                    "HostConfig": { "RestartPolicy": { "Name": "always" } }
+                }).to_string(),
+            ),
+            (&Method::POST, "/containers/m1/update") => {
+                json_response(json!({ "Warnings": [] }).to_string())
+            }
+            _ => Response::new(Body::empty()),
+        };
+        let server_requests = server_requests.clone();
+        Box::new(req.into_body().concat2().map(move |body| {
+            server_requests.write().unwrap().push(format!(
+                "{} {}",
+                request,
+                String::from_utf8_lossy(&body)
+            ));
+            response
+        })) as Box<Future<Item = Response<Body>, Error = HyperError> + Send>
+    }).map_err(|err| eprintln!("{}", err));
+
+    let mri =
+        DockerModuleRuntime::new(&Url::parse(&format!("http://localhost:{}/", port)).unwrap())
+            .unwrap();
+
+    let task = mri.start("m1");
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    runtime.spawn(server);
+    runtime.block_on(task).unwrap();
+
+    let requests = requests.read().unwrap();
+    assert_eq!(3, requests.len());
+    assert_eq!("GET /containers/m1/json ", requests[0]);
+    assert!(requests[1].starts_with("POST /containers/m1/update "));
+    assert!(requests[1].contains(r#""RestartPolicy":{"Name":"no"}"#));
+    assert_eq!("POST /containers/m1/start ", requests[2]);
+}
+
 #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
 fn container_stop_handler(
     req: Request<Body>,
@@ -740,6 +986,191 @@ fn container_remove_succeeds() {
     runtime.block_on(task).unwrap();
 }
 
+#[test]
+fn container_remove_with_volumes_removes_anonymous_volumes() {
+    let port = get_unused_tcp_port();
+    let server = run_tcp_server("127.0.0.1", port, |req: Request<Body>| {
+        assert_eq!(req.uri().path(), "/containers/m1");
+        assert_eq!(req.uri().query(), Some("v=true&force=true&link=false"));
+        future::ok(Response::new(Body::empty()))
+    }).map_err(|err| eprintln!("{}", err));
+
+    let mri =
+        DockerModuleRuntime::new(&Url::parse(&format!("http://localhost:{}/", port)).unwrap())
+            .unwrap();
+
+    let task = ModuleRuntime::remove_with_volumes(&mri, "m1");
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    runtime.spawn(server);
+    runtime.block_on(task).unwrap();
+}
+
+// Serves the requests a canary update makes and records them so that tests
+// can check how the containers were replaced. The canary reports as running
+// only if `canary_running` is set. The existing module m1 is listed and
+// reports as running.
+fn canary_update_server(
+    port: u16,
+    requests: Arc<RwLock<Vec<String>>>,
+    canary_running: bool,
+) -> impl Future<Item = (), Error = ()> {
+    run_tcp_server("127.0.0.1", port, move |req: Request<Body>| {
+        if let Some(response) = platform_handler(&req, "amd64", "x86_64") {
+            return response;
+        }
+
+        let request = match req.uri().query() {
+            Some(query) => format!("{} {}?{}", req.method(), req.uri().path(), query),
+            None => format!("{} {}", req.method(), req.uri().path()),
+        };
+        requests.write().unwrap().push(request);
+
+        let response = match (req.method(), req.uri().path()) {
+            (&Method::POST, "/containers/create") => json_response(
+                json!({
+                    "Id": "12345",
+                    "Warnings": []
+                }).to_string(),
+            ),
+            (&Method::GET, "/containers/m1-canary/json") => json_response(
+                json!({
+                    "Id": "12345",
+                    "State": { "Running": canary_running },
+                    "RestartCount": 0
+                }).to_string(),
+            ),
+            (&Method::GET, "/containers/json") => json_response(
+                serde_json::to_string(&vec![ContainerSummary::new(
+                    "m1".to_string(),
+                    vec!["/m1".to_string()],
+                    IMAGE_NAME.to_string(),
+                    "img1".to_string(),
+                    "".to_string(),
+                    10,
+                    vec![],
+                    10,
+                    10,
+                    HashMap::new(),
+                    "".to_string(),
+                    "".to_string(),
+                    ContainerHostConfig::new(""),
+                    ContainerNetworkSettings::new(HashMap::new()),
+                    vec![],
+                )]).unwrap(),
+            ),
+            (&Method::GET, "/containers/m1/json") => json_response(
+                json!({
+                    "Id": "m1",
+                    "State": { "Running": true, "Status": "running" },
+                    "RestartCount": 0
+                }).to_string(),
+            ),
+            _ => Response::new(Body::empty()),
+        };
+        Box::new(future::ok(response))
+            as Box<Future<Item = Response<Body>, Error = HyperError> + Send>
+    }).map_err(|err| eprintln!("{}", err))
+}
+
+// Runs `runtime` until `requests` holds `count` requests, since canaries
+// soak in the background of the update.
+fn wait_for_requests(runtime: &mut Runtime, requests: &Arc<RwLock<Vec<String>>>, count: usize) {
+    for _ in 0..100 {
+        if requests.read().unwrap().len() >= count {
+            return;
+        }
+        runtime
+            .block_on(Delay::new(Instant::now() + Duration::from_millis(50)))
+            .unwrap();
+    }
+}
+
+fn canary_module_spec() -> ModuleSpec<DockerConfig> {
+    ModuleSpec::new(
+        "m1",
+        "docker",
+        DockerConfig::new(IMAGE_NAME, ContainerCreateBody::new(), None).unwrap(),
+        HashMap::new(),
+    ).unwrap()
+}
+
+#[test]
+fn container_update_canary_swaps_in_healthy_module() {
+    let port = get_unused_tcp_port();
+    let requests = Arc::new(RwLock::new(vec![]));
+    let server = canary_update_server(port, requests.clone(), true);
+
+    let mri =
+        DockerModuleRuntime::new(&Url::parse(&format!("http://localhost:{}/", port)).unwrap())
+            .unwrap();
+
+    let task = mri.update(
+        canary_module_spec(),
+        UpdateStrategy::Canary(Duration::from_secs(0)),
+    );
+
+    let mut runtime = Runtime::new().unwrap();
+    runtime.spawn(server);
+    runtime.block_on(task).unwrap();
+    wait_for_requests(&mut runtime, &requests, 7);
+
+    assert_eq!(
+        vec![
+            "DELETE /containers/m1-canary?v=false&force=true&link=false",
+            "POST /containers/create?name=m1-canary",
+            "GET /containers/m1-canary/json?size=false",
+            "POST /containers/m1-canary/start?detachKeys=",
+            "GET /containers/m1-canary/json?size=false",
+            "DELETE /containers/m1?v=false&force=true&link=false",
+            "POST /containers/m1-canary/rename?name=m1",
+        ],
+        *requests.read().unwrap()
+    );
+}
+
+#[test]
+fn container_update_canary_keeps_existing_module_when_unhealthy() {
+    let port = get_unused_tcp_port();
+    let requests = Arc::new(RwLock::new(vec![]));
+    let server = canary_update_server(port, requests.clone(), false);
+
+    let mri =
+        DockerModuleRuntime::new(&Url::parse(&format!("http://localhost:{}/", port)).unwrap())
+            .unwrap();
+
+    let task = mri.update(
+        canary_module_spec(),
+        UpdateStrategy::Canary(Duration::from_secs(0)),
+    );
+
+    let mut runtime = Runtime::new().unwrap();
+    runtime.spawn(server);
+    runtime.block_on(task).unwrap();
+    wait_for_requests(&mut runtime, &requests, 6);
+
+    {
+        let requests = requests.read().unwrap();
+        assert_eq!(
+            "DELETE /containers/m1-canary?v=false&force=true&link=false",
+            requests[requests.len() - 1]
+        );
+        assert!(!requests.iter().any(|request| request.contains("rename")));
+        assert!(!requests.iter().any(|request| request.starts_with("DELETE /containers/m1?")));
+    }
+
+    let modules = runtime
+        .block_on(mri.list_with_details().collect())
+        .unwrap();
+    assert_eq!(1, modules.len());
+    assert_eq!(
+        Some(
+            "Canary update failed: Updated module m1 did not keep running during its soak period"
+        ),
+        modules[0].1.status_description()
+    );
+}
+
 #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
 fn container_list_handler(
     req: Request<Body>,
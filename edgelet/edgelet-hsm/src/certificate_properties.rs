@@ -2,10 +2,11 @@
 
 use edgelet_core::{
     CertificateIssuer as CoreCertificateIssuer, CertificateProperties as CoreCertificateProperties,
-    CertificateType as CoreCertificateType, IOTEDGED_CA_ALIAS,
+    CertificateType as CoreCertificateType, EcCurve, KeyType, IOTEDGED_CA_ALIAS,
 };
 use hsm::{
-    CertificateProperties as HsmCertificateProperties, CertificateType as HsmCertificateType,
+    CertificateKeyType as HsmCertificateKeyType, CertificateProperties as HsmCertificateProperties,
+    CertificateType as HsmCertificateType,
 };
 
 fn convert_certificate_type(core: CoreCertificateType) -> HsmCertificateType {
@@ -17,6 +18,21 @@ fn convert_certificate_type(core: CoreCertificateType) -> HsmCertificateType {
     }
 }
 
+// The HSM takes the OpenSSL short names of curves.
+fn convert_key_type(core: KeyType) -> HsmCertificateKeyType {
+    match core {
+        KeyType::Rsa { length } => {
+            HsmCertificateKeyType::Rsa(length.map(|length| length.bits() as usize))
+        }
+        KeyType::Ec {
+            curve: EcCurve::P256,
+        } => HsmCertificateKeyType::Ec("prime256v1"),
+        KeyType::Ec {
+            curve: EcCurve::P384,
+        } => HsmCertificateKeyType::Ec("secp384r1"),
+    }
+}
+
 /// Convert Certificate properties defined in edgelet-core to HSM specific Certificate properties
 pub fn convert_properties(
     core: &CoreCertificateProperties,
@@ -26,15 +42,39 @@ pub fn convert_properties(
         CoreCertificateIssuer::DeviceCa => device_ca_alias.to_string(),
         CoreCertificateIssuer::DefaultCa => IOTEDGED_CA_ALIAS.to_string(),
     };
-    let no_sans: Vec<String> = vec![];
-    HsmCertificateProperties::new(
+    let props = HsmCertificateProperties::new(
         *core.validity_in_secs(),
         core.common_name().to_string(),
         convert_certificate_type(*core.certificate_type()),
         issuer_ca,
         core.alias().to_string(),
-        core.san_entries().unwrap_or(&no_sans).to_vec(),
-    )
+        san_entries(core),
+    );
+    match core.key_type() {
+        Some(key_type) => props.with_key_type(convert_key_type(key_type)),
+        None => props,
+    }
+}
+
+// The HSM takes subject alternative names in the form of the OpenSSL config
+// files, so DNS names and IP addresses are added to the entries in that form,
+// leaving out those already there.
+fn san_entries(core: &CoreCertificateProperties) -> Vec<String> {
+    let mut entries = core
+        .san_entries()
+        .map(|entries| entries.to_vec())
+        .unwrap_or_default();
+    let names = core
+        .dns_names()
+        .iter()
+        .map(|name| format!("DNS: {}", name))
+        .chain(core.ip_addresses().iter().map(|ip| format!("IP: {}", ip)));
+    for entry in names {
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    entries
 }
 
 #[cfg(test)]
@@ -42,9 +82,10 @@ mod tests {
     use edgelet_core::{
         CertificateIssuer as CoreCertificateIssuer,
         CertificateProperties as CoreCertificateProperties, CertificateType as CoreCertificateType,
-        IOTEDGED_CA_ALIAS,
+        EcCurve, KeyType, RsaKeyLength, IOTEDGED_CA_ALIAS,
     };
     use hsm::{
+        CertificateKeyType as HsmCertificateKeyType,
         CertificateProperties as HsmCertificateProperties, CertificateType as HsmCertificateType,
     };
 
@@ -82,6 +123,7 @@ mod tests {
         assert_eq!(None, hsm.locality());
         assert_eq!(None, hsm.organization());
         assert_eq!(None, hsm.organization_unit());
+        assert_eq!(None, hsm.key_type());
     }
 
     #[test]
@@ -133,4 +175,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn dns_names_and_ip_addresses_are_added_to_the_san_entries() {
+        let core_props = CoreCertificateProperties::new(
+            3600,
+            "gateway".to_string(),
+            CoreCertificateType::Server,
+            "alias".to_string(),
+        ).with_san_entries(vec!["DNS: gateway".to_string()])
+        .with_dns_names(vec!["gateway".to_string(), "mqtt.gateway".to_string()])
+        .with_ip_addresses(vec!["10.0.0.1".parse().unwrap(), "fe80::1".parse().unwrap()]);
+
+        let hsm_props = super::convert_properties(&core_props, "device_ca_test");
+        assert_eq!(
+            &[
+                "DNS: gateway".to_string(),
+                "DNS: mqtt.gateway".to_string(),
+                "IP: 10.0.0.1".to_string(),
+                "IP: fe80::1".to_string(),
+            ],
+            hsm_props.san_entries()
+        );
+    }
+
+    #[test]
+    fn key_types_are_converted_to_openssl_curve_names() {
+        let core_props = CoreCertificateProperties::new(
+            3600,
+            "gateway".to_string(),
+            CoreCertificateType::Server,
+            "alias".to_string(),
+        );
+        for (key_type, expected) in vec![
+            (
+                KeyType::Rsa { length: None },
+                HsmCertificateKeyType::Rsa(None),
+            ),
+            (
+                KeyType::Rsa {
+                    length: Some(RsaKeyLength::Bits3072),
+                },
+                HsmCertificateKeyType::Rsa(Some(3072)),
+            ),
+            (
+                KeyType::Ec {
+                    curve: EcCurve::P256,
+                },
+                HsmCertificateKeyType::Ec("prime256v1"),
+            ),
+            (
+                KeyType::Ec {
+                    curve: EcCurve::P384,
+                },
+                HsmCertificateKeyType::Ec("secp384r1"),
+            ),
+        ] {
+            let hsm_props =
+                super::convert_properties(&core_props.clone().with_key_type(key_type), "ca");
+            assert_eq!(Some(expected), hsm_props.key_type());
+        }
+    }
 }
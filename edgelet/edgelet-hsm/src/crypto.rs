@@ -1,14 +1,19 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use chrono::{DateTime, Utc};
+use std::str;
 use std::sync::{Arc, Mutex};
 
 use certificate_properties::convert_properties;
+#[cfg(feature = "chaos")]
+use edgelet_core::chaos::{self, FaultTarget};
 use edgelet_core::{
     Certificate as CoreCertificate, CertificateProperties as CoreCertificateProperties,
-    CreateCertificate as CoreCreateCertificate, Decrypt as CoreDecrypt, Encrypt as CoreEncrypt,
-    Error as CoreError, GetTrustBundle as CoreGetTrustBundle, KeyBytes as CoreKeyBytes,
+    CertificateRequest as CoreCertificateRequest, CreateCertificate as CoreCreateCertificate,
+    CreateCrl as CoreCreateCrl, Decrypt as CoreDecrypt, Encrypt as CoreEncrypt, Error as CoreError,
+    ErrorKind as CoreErrorKind, GetTrustBundle as CoreGetTrustBundle, KeyBytes as CoreKeyBytes,
     MasterEncryptionKey as CoreMasterEncryptionKey, PrivateKey as CorePrivateKey,
+    RevokedCertificate as CoreRevokedCertificate, IOTEDGED_CA_ALIAS,
 };
 
 pub use error::{Error, ErrorKind};
@@ -17,9 +22,10 @@ pub use hsm::{
     PrivateKey as HsmPrivateKey,
 };
 use hsm::{
-    CreateCertificate as HsmCreateCertificate,
+    CreateCertificate as HsmCreateCertificate, CreateCrl as HsmCreateCrl,
     CreateMasterEncryptionKey as HsmCreateMasterEncryptionKey, Crypto as HsmCrypto,
-    DestroyMasterEncryptionKey as HsmDestroyMasterEncryptionKey,
+    DestroyMasterEncryptionKey as HsmDestroyMasterEncryptionKey, ErrorKind as HsmErrorKind,
+    RevokedCertificate as HsmRevokedCertificate,
 };
 
 /// The TPM Key Store.
@@ -42,8 +48,21 @@ impl Crypto {
     }
 }
 
+/// Subjects the HSM call `operation` to the faults being injected into HSM
+/// calls.
+#[cfg(feature = "chaos")]
+pub(crate) fn inject_fault(operation: &str) -> Result<(), CoreError> {
+    chaos::inject(FaultTarget::Hsm, operation)
+}
+
+#[cfg(not(feature = "chaos"))]
+pub(crate) fn inject_fault(_operation: &str) -> Result<(), CoreError> {
+    Ok(())
+}
+
 impl CoreMasterEncryptionKey for Crypto {
     fn create_key(&self) -> Result<(), CoreError> {
+        inject_fault("create_key")?;
         self.crypto
             .lock()
             .expect("Lock on crypto structure failed")
@@ -53,6 +72,7 @@ impl CoreMasterEncryptionKey for Crypto {
     }
 
     fn destroy_key(&self) -> Result<(), CoreError> {
+        inject_fault("destroy_key")?;
         self.crypto
             .lock()
             .expect("Lock on crypto structure failed")
@@ -69,6 +89,7 @@ impl CoreCreateCertificate for Crypto {
         &self,
         properties: &CoreCertificateProperties,
     ) -> Result<Self::Certificate, CoreError> {
+        inject_fault("create_certificate")?;
         let crypto = self.crypto.lock().expect("Lock on crypto structure failed");
         let device_ca_alias = crypto.get_device_ca_alias();
         let cert = crypto
@@ -79,6 +100,7 @@ impl CoreCreateCertificate for Crypto {
     }
 
     fn destroy_certificate(&self, alias: String) -> Result<(), CoreError> {
+        inject_fault("destroy_certificate")?;
         self.crypto
             .lock()
             .expect("Lock on crypto structure failed")
@@ -87,6 +109,64 @@ impl CoreCreateCertificate for Crypto {
             .map_err(CoreError::from)?;
         Ok(())
     }
+
+    fn sign_certificate_request(
+        &self,
+        request: &CoreCertificateRequest,
+        properties: &CoreCertificateProperties,
+    ) -> Result<Self::Certificate, CoreError> {
+        inject_fault("sign_certificate_request")?;
+        let csr = str::from_utf8(request.pem())
+            .map_err(|_| CoreError::from(CoreErrorKind::InvalidCertificateRequest))?;
+        let crypto = self.crypto.lock().expect("Lock on crypto structure failed");
+        let device_ca_alias = crypto.get_device_ca_alias();
+        let cert = crypto
+            .sign_certificate_request(csr, &convert_properties(properties, &device_ca_alias))
+            .map_err(|err| match err.kind() {
+                // the HSM library leaves out the optional signing function
+                HsmErrorKind::NoneFn => {
+                    CoreError::from(CoreErrorKind::CertificateRequestNotSupported)
+                }
+                _ => CoreError::from(Error::from(err)),
+            })?;
+        Ok(Certificate(cert))
+    }
+
+    fn renew_certificate(
+        &self,
+        properties: &CoreCertificateProperties,
+    ) -> Result<Self::Certificate, CoreError> {
+        inject_fault("renew_certificate")?;
+        let crypto = self.crypto.lock().expect("Lock on crypto structure failed");
+        let device_ca_alias = crypto.get_device_ca_alias();
+        let cert = crypto
+            .renew_certificate(&convert_properties(properties, &device_ca_alias))
+            .map_err(|err| match err.kind() {
+                // the HSM library leaves out the optional renewal function
+                HsmErrorKind::NoneFn => {
+                    CoreError::from(CoreErrorKind::CertificateRenewalNotSupported)
+                }
+                _ => CoreError::from(Error::from(err)),
+            })?;
+        Ok(Certificate(cert))
+    }
+
+    fn get_certificate(&self, alias: String) -> Result<Self::Certificate, CoreError> {
+        inject_fault("get_certificate")?;
+        let cert = self
+            .crypto
+            .lock()
+            .expect("Lock on crypto structure failed")
+            .get_certificate(&alias)
+            .map_err(|err| match err.kind() {
+                // the HSM library leaves out the optional lookup function
+                HsmErrorKind::NoneFn => {
+                    CoreError::from(CoreErrorKind::CertificateLookupNotSupported)
+                }
+                _ => CoreError::from(Error::from(err)),
+            })?;
+        Ok(Certificate(cert))
+    }
 }
 
 impl CoreEncrypt for Crypto {
@@ -98,6 +178,7 @@ impl CoreEncrypt for Crypto {
         plaintext: &[u8],
         initialization_vector: &[u8],
     ) -> Result<Self::Buffer, CoreError> {
+        inject_fault("encrypt")?;
         self.crypto
             .lock()
             .expect("Lock on crypto structure failed")
@@ -116,6 +197,7 @@ impl CoreDecrypt for Crypto {
         ciphertext: &[u8],
         initialization_vector: &[u8],
     ) -> Result<Self::Buffer, CoreError> {
+        inject_fault("decrypt")?;
         self.crypto
             .lock()
             .expect("Lock on crypto structure failed")
@@ -129,6 +211,7 @@ impl CoreGetTrustBundle for Crypto {
     type Certificate = Certificate;
 
     fn get_trust_bundle(&self) -> Result<Self::Certificate, CoreError> {
+        inject_fault("get_trust_bundle")?;
         let cert = self
             .crypto
             .lock()
@@ -140,6 +223,32 @@ impl CoreGetTrustBundle for Crypto {
     }
 }
 
+impl CoreCreateCrl for Crypto {
+    type Buffer = Buffer;
+
+    fn create_crl(
+        &self,
+        revoked: &[CoreRevokedCertificate],
+        validity_in_secs: u64,
+    ) -> Result<Self::Buffer, CoreError> {
+        inject_fault("create_crl")?;
+        let revoked: Vec<HsmRevokedCertificate> = revoked
+            .iter()
+            .map(|cert| {
+                HsmRevokedCertificate::new(cert.serial_number().to_string(), *cert.revoked_at())
+            }).collect();
+        self.crypto
+            .lock()
+            .expect("Lock on crypto structure failed")
+            .create_crl(IOTEDGED_CA_ALIAS, &revoked, validity_in_secs)
+            .map_err(|err| match err.kind() {
+                // the HSM library leaves out the optional CRL function
+                HsmErrorKind::NoneFn => CoreError::from(CoreErrorKind::CrlNotSupported),
+                _ => CoreError::from(Error::from(err)),
+            })
+    }
+}
+
 #[derive(Debug)]
 pub struct Certificate(HsmCertificate);
 
@@ -10,6 +10,8 @@ use edgelet_core::crypto::{
 use edgelet_core::Error as CoreError;
 use hsm::{ManageTpmKeys, SignWithTpm, Tpm, TpmDigest};
 
+use crypto::inject_fault;
+
 pub use error::{Error, ErrorKind};
 
 const ROOT_KEY_NAME: &str = "primary";
@@ -114,6 +116,7 @@ impl Sign for TpmKey {
         _signature_algorithm: SignatureAlgorithm,
         data: &[u8],
     ) -> Result<Self::Signature, CoreError> {
+        inject_fault("sign")?;
         match self.identity {
             KeyIdentity::Device => self
                 .tpm
@@ -1,10 +1,16 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use std::fmt::{self, Display};
+use std::num::ParseIntError;
 use std::str::ParseBoolError;
 
-use edgelet_core::Error as CoreError;
-use edgelet_http::Error as EdgeletHttpError;
+use edgelet_core::{error_category, Error as CoreError, ErrorCategory, ErrorKind as CoreErrorKind};
+use edgelet_docker::{
+    classify_error as classify_docker_error, Error as DockerError, ErrorKind as DockerErrorKind,
+};
+use edgelet_http::{
+    category_status_code, Error as EdgeletHttpError, ErrorKind as EdgeletHttpErrorKind,
+};
 use edgelet_iothub::Error as IoTHubError;
 use failure::{Backtrace, Context, Fail};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
@@ -12,7 +18,6 @@ use http::{Error as HttpError, Response, StatusCode};
 use hyper::{Body, Error as HyperError, StatusCode as HyperStatusCode};
 use serde_json;
 
-use management::apis::Error as MgmtError;
 use management::models::ErrorResponse;
 
 use IntoResponse;
@@ -40,16 +45,91 @@ pub enum ErrorKind {
     BadParam,
     #[fail(display = "Bad body")]
     BadBody,
+    #[fail(display = "Not found")]
+    NotFound,
+    #[fail(display = "Conflict with current state")]
+    Conflict,
     #[fail(display = "IoT Hub error")]
     IoTHub,
     #[fail(display = "Invalid or missing API version")]
     InvalidApiVersion,
-    #[fail(display = "Client error")]
-    Client(MgmtError<serde_json::Value>),
     #[fail(display = "State not modified")]
     NotModified,
     #[fail(display = "Parse error")]
     Parse,
+    #[fail(display = "Could not upload the module logs")]
+    LogUpload,
+    #[fail(display = "The management API is read-only on this device")]
+    ReadOnly,
+}
+
+impl ErrorKind {
+    /// The HTTP status reported to the caller for an error of this kind.
+    ///
+    /// Request validation failures are client errors, failures of the
+    /// identity manager, IoT Hub or blob storage are reported as a bad
+    /// gateway and everything else is a failure on our side.
+    pub fn status_code(&self) -> StatusCode {
+        match *self {
+            ErrorKind::BadParam
+            | ErrorKind::BadBody
+            | ErrorKind::InvalidApiVersion
+            | ErrorKind::Parse => StatusCode::BAD_REQUEST,
+            ErrorKind::ReadOnly => StatusCode::FORBIDDEN,
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::Conflict => StatusCode::CONFLICT,
+            ErrorKind::NotModified => StatusCode::NOT_MODIFIED,
+            ErrorKind::IdentityManager | ErrorKind::IoTHub | ErrorKind::LogUpload => {
+                StatusCode::BAD_GATEWAY
+            }
+            ErrorKind::Core
+            | ErrorKind::ModuleRuntime
+            | ErrorKind::Serde
+            | ErrorKind::Hyper
+            | ErrorKind::Http => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// leave it to their cause.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::BadParam
+            | ErrorKind::BadBody
+            | ErrorKind::NotFound
+            | ErrorKind::InvalidApiVersion
+            | ErrorKind::Parse
+            | ErrorKind::ReadOnly => Some(ErrorCategory::UserConfig),
+            ErrorKind::IdentityManager | ErrorKind::IoTHub | ErrorKind::LogUpload => {
+                Some(ErrorCategory::TransientNetwork)
+            }
+            ErrorKind::Conflict => Some(ErrorCategory::Runtime),
+            ErrorKind::Core
+            | ErrorKind::ModuleRuntime
+            | ErrorKind::Serde
+            | ErrorKind::Hyper
+            | ErrorKind::Http
+            | ErrorKind::NotModified => None,
+        }
+    }
+
+    /// The code errors of this kind are reported with, for the kinds clients
+    /// have to tell apart from the other errors with the same status.
+    pub fn code(&self) -> Option<&'static str> {
+        match *self {
+            ErrorKind::ReadOnly => Some("ReadOnlyManagementApi"),
+            _ => None,
+        }
+    }
+}
+
+/// Categorizes the errors of this crate, and falls back to
+/// `edgelet_docker::classify_error`.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    match error.downcast_ref::<Error>() {
+        Some(error) => error.kind().category(),
+        None => classify_docker_error(error),
+    }
 }
 
 impl Fail for Error {
@@ -72,6 +152,36 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
+
+    /// The HTTP status reported to the caller for this error. Errors of the
+    /// kinds that are a failure on our side are reported by the category of
+    /// what caused them instead, so that a core error caused by a bad module
+    /// name is still a client error, and one caused by a module that doesn't
+    /// exist is a 404.
+    pub fn status_code(&self) -> StatusCode {
+        match self.kind().status_code() {
+            StatusCode::INTERNAL_SERVER_ERROR if module_not_found(self) => StatusCode::NOT_FOUND,
+            StatusCode::INTERNAL_SERVER_ERROR => category_status_code(self.category()),
+            status_code => status_code,
+        }
+    }
+}
+
+// Whether the module runtime failed because the module wasn't there, however
+// many errors it is wrapped in on the way up.
+fn module_not_found(error: &Error) -> bool {
+    Fail::causes(error).any(|cause| {
+        cause
+            .downcast_ref::<DockerError>()
+            .map_or(false, |err| match *err.kind() {
+                DockerErrorKind::NotFound(_) => true,
+                _ => false,
+            })
+    })
 }
 
 impl From<ErrorKind> for Error {
@@ -128,25 +238,38 @@ impl From<EdgeletHttpError> for Error {
     }
 }
 
-impl From<MgmtError<serde_json::Value>> for Error {
-    fn from(error: MgmtError<serde_json::Value>) -> Self {
-        match error {
-            MgmtError::Hyper(h) => From::from(h),
-            MgmtError::Serde(s) => From::from(s),
-            MgmtError::Api(ref e) if e.code == HyperStatusCode::NOT_MODIFIED => {
-                From::from(ErrorKind::NotModified)
-            }
-            MgmtError::Api(_) => From::from(ErrorKind::Client(error)),
+impl From<IoTHubError> for Error {
+    fn from(error: IoTHubError) -> Self {
+        let kind = iothub_error_kind(&error);
+        Error {
+            inner: error.context(kind),
         }
     }
 }
 
-impl From<IoTHubError> for Error {
-    fn from(error: IoTHubError) -> Self {
-        Error {
-            inner: error.context(ErrorKind::IoTHub),
+// Work out from the causes of an identity manager error whether it was the
+// request that was at fault or IoT Hub that failed to service it.
+fn iothub_error_kind(error: &IoTHubError) -> ErrorKind {
+    for cause in Fail::causes(error) {
+        if let Some(err) = cause.downcast_ref::<EdgeletHttpError>() {
+            if let EdgeletHttpErrorKind::ServiceError(status, _) = *err.kind() {
+                return match status {
+                    HyperStatusCode::BAD_REQUEST => ErrorKind::BadParam,
+                    HyperStatusCode::NOT_FOUND => ErrorKind::NotFound,
+                    HyperStatusCode::CONFLICT => ErrorKind::Conflict,
+                    _ => ErrorKind::IoTHub,
+                };
+            }
+        } else if let Some(err) = cause.downcast_ref::<CoreError>() {
+            if let CoreErrorKind::InvalidModuleName(_) | CoreErrorKind::InvalidGenerationId(_) =
+                *err.kind()
+            {
+                return ErrorKind::BadParam;
+            }
         }
     }
+
+    ErrorKind::IoTHub
 }
 
 impl From<ParseBoolError> for Error {
@@ -157,6 +280,14 @@ impl From<ParseBoolError> for Error {
     }
 }
 
+impl From<ParseIntError> for Error {
+    fn from(error: ParseIntError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Parse),
+        }
+    }
+}
+
 impl IntoResponse for Error {
     fn into_response(self) -> Response<Body> {
         let mut fail: &Fail = &self;
@@ -166,18 +297,25 @@ impl IntoResponse for Error {
             fail = cause;
         }
 
-        let status_code = match *self.kind() {
-            ErrorKind::BadParam | ErrorKind::BadBody | ErrorKind::InvalidApiVersion => {
-                StatusCode::BAD_REQUEST
-            }
-            _ => {
-                error!("Internal server error: {}", message);
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        };
+        let status_code = self.status_code();
+        if status_code.is_server_error() {
+            error!("Internal server error: {}", message);
+        }
+
+        // Per the RFC, status code NotModified should not have a body
+        if status_code == StatusCode::NOT_MODIFIED {
+            return Response::builder()
+                .status(status_code)
+                .body(Body::default())
+                .expect("response builder failure");
+        }
 
-        let body = serde_json::to_string(&ErrorResponse::new(message))
-            .expect("serialization of ErrorResponse failed.");
+        let mut response = ErrorResponse::new(message).with_category(self.category().to_string());
+        if let Some(code) = self.kind().code() {
+            response.set_code(code.to_string());
+        }
+        let body =
+            serde_json::to_string(&response).expect("serialization of ErrorResponse failed.");
 
         Response::builder()
             .status(status_code)
@@ -212,3 +350,79 @@ impl IntoResponse for IoTHubError {
         Error::from(self).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::{Future, Stream};
+
+    use super::*;
+
+    #[test]
+    fn invalid_module_name_is_bad_request() {
+        let error = IoTHubError::from(CoreError::from(CoreErrorKind::InvalidModuleName(
+            "../m1".to_string(),
+        )));
+        let response = error.into_response();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn other_iothub_error_is_bad_gateway() {
+        let error = IoTHubError::from(CoreError::from(CoreErrorKind::Identity));
+        let response = error.into_response();
+        assert_eq!(StatusCode::BAD_GATEWAY, response.status());
+    }
+
+    #[test]
+    fn core_error_is_reported_by_its_category() {
+        let error = Error::from(CoreError::from(CoreErrorKind::InvalidNamespace(
+            "-ns".to_string(),
+        )));
+        assert_eq!(ErrorCategory::UserConfig, error.category());
+        assert_eq!(StatusCode::BAD_REQUEST, error.status_code());
+
+        let error = Error::from(CoreError::from(CoreErrorKind::KeyStore));
+        assert_eq!(ErrorCategory::Hsm, error.category());
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, error.status_code());
+    }
+
+    #[test]
+    fn missing_module_is_not_found() {
+        let missing = DockerError::from(DockerErrorKind::NotFound("No such container: m1".into()));
+        let error = Error::from(CoreError::from(missing));
+        assert_eq!(StatusCode::NOT_FOUND, error.status_code());
+        assert_eq!(StatusCode::NOT_FOUND, error.into_response().status());
+
+        let error = Error::from(CoreError::from(DockerError::from(DockerErrorKind::Docker)));
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, error.status_code());
+    }
+
+    #[test]
+    fn error_response_has_the_category() {
+        let response = Error::from(ErrorKind::BadBody).into_response();
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some("user-config"), error.category());
+    }
+
+    #[test]
+    fn read_only_errors_have_a_code() {
+        let response = Error::from(ErrorKind::ReadOnly).into_response();
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some("ReadOnlyManagementApi"), error.code());
+
+        let response = Error::from(ErrorKind::BadBody).into_response();
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(None, error.code());
+    }
+
+    #[test]
+    fn not_modified_has_no_body() {
+        let response = Error::from(ErrorKind::NotModified).into_response();
+        assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+        assert!(response.headers().get(CONTENT_TYPE).is_none());
+    }
+}
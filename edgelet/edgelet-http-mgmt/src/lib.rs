@@ -9,6 +9,7 @@
 #![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
 #![cfg_attr(feature = "cargo-clippy", allow(stutter, use_self))]
 
+extern crate base64;
 #[cfg(test)]
 extern crate chrono;
 extern crate edgelet_core;
@@ -21,7 +22,7 @@ extern crate edgelet_test_utils;
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
-#[macro_use]
+extern crate flate2;
 extern crate futures;
 extern crate http;
 extern crate hyper;
@@ -36,19 +37,23 @@ extern crate serde;
 extern crate serde_json;
 #[cfg(not(test))]
 extern crate serde_json;
+#[cfg(test)]
+extern crate tempdir;
+extern crate tokio;
 extern crate url;
 
 use http::Response;
 use hyper::Body;
 
-mod client;
 mod error;
 mod server;
+mod state;
 
-pub use client::ModuleClient;
-pub use error::{Error, ErrorKind};
+pub use error::{classify_error, Error, ErrorKind};
+pub use management::models::Operation;
 pub use server::ListModules;
 pub use server::ManagementService;
+pub use state::ManagementState;
 
 pub trait IntoResponse {
     fn into_response(self) -> Response<Body>;
@@ -0,0 +1,107 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{CaRotation, RotationState};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::CaRotationStatus;
+use serde_json;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct GetCaRotation {
+    rotation: CaRotation,
+}
+
+impl GetCaRotation {
+    pub fn new(rotation: CaRotation) -> Self {
+        GetCaRotation { rotation }
+    }
+}
+
+impl Handler<Parameters> for GetCaRotation {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let response = self
+            .rotation
+            .state()
+            .map_err(Error::from)
+            .and_then(|state| {
+                let body = match state {
+                    RotationState::Stable => CaRotationStatus::new("stable".to_string()),
+                    RotationState::Overlap {
+                        started_at,
+                        ends_at,
+                        workload_ca_reissued,
+                        ..
+                    } => CaRotationStatus::new("overlap".to_string())
+                        .with_started_at(started_at.to_rfc3339())
+                        .with_ends_at(ends_at.to_rfc3339())
+                        .with_workload_ca_reissued(workload_ca_reissued),
+                };
+                let b = serde_json::to_string(&body).context(ErrorKind::Serde)?;
+                Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                    .body(b.into())
+                    .unwrap_or_else(|e| e.into_response()))
+            }).unwrap_or_else(|e| e.into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::Stream;
+
+    use super::*;
+
+    const OLD_CA: &str = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+    const NEW_CA: &str = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+
+    fn status(rotation: CaRotation) -> CaRotationStatus {
+        let handler = GetCaRotation::new(rotation);
+        let request = Request::get("http://localhost/ca/rotation")
+            .body(Body::default())
+            .unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let b = response.into_body().concat2().wait().unwrap();
+        serde_json::from_slice(&b).unwrap()
+    }
+
+    #[test]
+    fn stable() {
+        let rotation = CaRotation::new(Duration::from_secs(3600));
+        rotation.observe(OLD_CA, OLD_CA).unwrap();
+
+        let status = status(rotation);
+        assert_eq!("stable", status.state());
+        assert_eq!(None, status.ends_at());
+    }
+
+    #[test]
+    fn overlap() {
+        let rotation = CaRotation::new(Duration::from_secs(3600));
+        rotation.observe(OLD_CA, OLD_CA).unwrap();
+        rotation.observe(NEW_CA, NEW_CA).unwrap();
+
+        let status = status(rotation);
+        assert_eq!("overlap", status.state());
+        assert!(status.started_at().is_some());
+        assert!(status.ends_at().is_some());
+        assert_eq!(Some(&false), status.workload_ca_reissued());
+    }
+}
@@ -0,0 +1,6 @@
+// Copyright (c) Microsoft. All rights reserved.
+mod get;
+mod reissue;
+
+pub use self::get::GetCaRotation;
+pub use self::reissue::ReissueWorkloadCa;
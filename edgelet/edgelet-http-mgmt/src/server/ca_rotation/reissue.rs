@@ -0,0 +1,98 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::CaRotation;
+use edgelet_http::route::{Handler, Parameters};
+use futures::{future, Future};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+
+use error::Error;
+use IntoResponse;
+
+pub struct ReissueWorkloadCa {
+    rotation: CaRotation,
+}
+
+impl ReissueWorkloadCa {
+    pub fn new(rotation: CaRotation) -> Self {
+        ReissueWorkloadCa { rotation }
+    }
+}
+
+impl Handler<Parameters> for ReissueWorkloadCa {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let response = self
+            .rotation
+            .reissue_workload_ca()
+            .map(|_| {
+                info!("Issued the workload CA again under the current root");
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::default())
+                    .unwrap_or_else(|e| e.into_response())
+            }).unwrap_or_else(|e| Error::from(e).into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use edgelet_core::{CaRotation, Error as CoreError, ErrorKind as CoreErrorKind, RotationState};
+
+    use super::*;
+
+    const OLD_CA: &str = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+    const NEW_CA: &str = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+
+    fn reissue(handler: &ReissueWorkloadCa) -> StatusCode {
+        let request = Request::post("http://localhost/ca/rotation/reissue")
+            .body(Body::default())
+            .unwrap();
+        handler
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn workload_ca_is_reissued() {
+        let reissued = Arc::new(AtomicUsize::new(0));
+        let count = reissued.clone();
+        let rotation = CaRotation::new(Duration::from_secs(3600)).with_reissue(move || {
+            count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        rotation.observe(OLD_CA, OLD_CA).unwrap();
+        rotation.observe(NEW_CA, NEW_CA).unwrap();
+
+        let handler = ReissueWorkloadCa::new(rotation.clone());
+        assert_eq!(StatusCode::NO_CONTENT, reissue(&handler));
+        assert_eq!(1, reissued.load(Ordering::SeqCst));
+        match rotation.state().unwrap() {
+            RotationState::Overlap {
+                workload_ca_reissued,
+                ..
+            } => assert!(workload_ca_reissued),
+            state => panic!("unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    fn failures_are_reported() {
+        let rotation = CaRotation::new(Duration::from_secs(3600))
+            .with_reissue(|| Err(CoreError::from(CoreErrorKind::CaRotation)));
+
+        let handler = ReissueWorkloadCa::new(rotation);
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, reissue(&handler));
+    }
+}
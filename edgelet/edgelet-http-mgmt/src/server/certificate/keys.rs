@@ -0,0 +1,257 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+
+use edgelet_core::{
+    CertificateRegistry, Identity, IdentityManager, KeyAlias as CoreKeyAlias, KeySlots,
+};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::*;
+use serde::Serialize;
+use serde_json;
+
+use error::ErrorKind;
+use IntoResponse;
+
+/// Lists the HSM aliases modules hold certificates under through the
+/// workload API.
+pub struct ListKeys {
+    slots: KeySlots,
+}
+
+impl ListKeys {
+    pub fn new(slots: KeySlots) -> Self {
+        ListKeys { slots }
+    }
+}
+
+fn core_to_key(alias: &CoreKeyAlias) -> KeyAlias {
+    let key = KeyAlias::new(
+        alias.alias().to_string(),
+        alias.module_id().to_string(),
+        alias.last_used().to_rfc3339(),
+    );
+    let key = match alias.generation_id() {
+        Some(generation_id) => key.with_generation_id(generation_id.to_string()),
+        None => key,
+    };
+    match alias.expires_at() {
+        Some(expires_at) => key.with_expires_at(expires_at.to_rfc3339()),
+        None => key,
+    }
+}
+
+fn json_response<T: Serialize>(body: &T) -> Response<Body> {
+    match serde_json::to_string(body).context(ErrorKind::Serde) {
+        Ok(b) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_LENGTH, b.len().to_string().as_str())
+            .body(b.into())
+            .unwrap_or_else(|e| e.into_response()),
+        Err(e) => e.into_response(),
+    }
+}
+
+impl Handler<Parameters> for ListKeys {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let keys = self.slots.aliases().iter().map(core_to_key).collect();
+        Box::new(future::ok(json_response(&KeyAliasList::new(keys))))
+    }
+}
+
+/// Serves `POST /keys/cleanup`, which destroys the certificates under the
+/// HSM aliases whose module identity, or the generation of it they were
+/// issued for, is gone from IoT Hub, and revokes them. Aliases are normally
+/// cleaned up when a generation is superseded or a module is removed, but
+/// not when that happens while the daemon isn't running.
+pub struct CleanupKeys<I>
+where
+    I: 'static + IdentityManager,
+{
+    id_manager: I,
+    slots: KeySlots,
+    registry: CertificateRegistry,
+}
+
+impl<I> CleanupKeys<I>
+where
+    I: 'static + IdentityManager,
+{
+    pub fn new(id_manager: I, slots: KeySlots) -> Self {
+        CleanupKeys {
+            id_manager,
+            slots,
+            registry: CertificateRegistry::new(),
+        }
+    }
+
+    /// Revokes the certificates of the aliases cleaned up in `registry`.
+    pub fn with_registry(mut self, registry: CertificateRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+}
+
+impl<I> Handler<Parameters> for CleanupKeys<I>
+where
+    I: 'static + IdentityManager + Send,
+{
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let slots = self.slots.clone();
+        let registry = self.registry.clone();
+        let response = self.id_manager.list().then(move |result| {
+            let response = match result.context(ErrorKind::IdentityManager) {
+                Ok(identities) => {
+                    // the identities of the edge runtime modules are named
+                    // after the modules with a leading `$`
+                    let generations: HashMap<String, String> = identities
+                        .iter()
+                        .map(|identity| {
+                            (
+                                identity.module_id().trim_left_matches('$').to_string(),
+                                identity.generation_id().to_string(),
+                            )
+                        }).collect();
+                    cleanup(&slots, &registry, &generations)
+                }
+                Err(err) => err.into_response(),
+            };
+            Ok(response)
+        });
+
+        Box::new(response)
+    }
+}
+
+fn cleanup(
+    slots: &KeySlots,
+    registry: &CertificateRegistry,
+    generations: &HashMap<String, String>,
+) -> Response<Body> {
+    let released = slots.release_orphans(generations);
+    for alias in &released {
+        info!("Cleaned up orphaned certificate {} from the HSM", alias);
+    }
+    let revoked = revoke_certificates(registry, &released);
+    json_response(&KeyCleanup::new(released, revoked))
+}
+
+// Revokes the certificates issued under `aliases` that haven't been revoked
+// yet. Returns their serial numbers.
+fn revoke_certificates(registry: &CertificateRegistry, aliases: &[String]) -> Vec<String> {
+    registry
+        .list()
+        .into_iter()
+        .filter(|cert| {
+            cert.revoked_at().is_none() && aliases.iter().any(|alias| alias == cert.alias())
+        }).filter_map(|cert| match registry.revoke(cert.serial_number()) {
+            Ok(_) => Some(cert.serial_number().to_string()),
+            Err(err) => {
+                warn!(
+                    "Could not revoke certificate {} of module {}: {}",
+                    cert.serial_number(),
+                    cert.module_id(),
+                    err
+                );
+                None
+            }
+        }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use edgelet_core::AuthType;
+    use edgelet_test_utils::identity::{TestIdentity, TestIdentityManager};
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn keys_are_listed_with_their_generation() {
+        let slots = KeySlots::new();
+        slots.occupy("m1", "m1identity", Utc::now() + Duration::hours(1));
+        slots.occupy("m1", "m1genserver", Utc::now() + Duration::hours(1));
+        let handler = ListKeys::new(slots);
+        let request = Request::get("http://localhost/keys")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let list: KeyAliasList = serde_json::from_slice(&body).unwrap();
+        let keys = list.keys();
+        assert_eq!(2, keys.len());
+        assert_eq!("m1genserver", keys[0].alias());
+        assert_eq!("m1", keys[0].module_id());
+        assert_eq!(Some("gen"), keys[0].generation_id());
+        assert!(keys[0].expires_at().is_some());
+        assert_eq!("m1identity", keys[1].alias());
+        assert_eq!(None, keys[1].generation_id());
+    }
+
+    #[test]
+    fn keys_of_gone_identities_and_generations_are_cleaned_up() {
+        let slots = KeySlots::new();
+        slots.occupy("edgeHub", "edgeHubidentity", Utc::now() + Duration::hours(1));
+        slots.occupy("m1", "m1oldserver", Utc::now() + Duration::hours(1));
+        slots.occupy("m1", "m1newserver", Utc::now() + Duration::hours(1));
+        slots.occupy("m2", "m2identity", Utc::now() + Duration::hours(1));
+        let manager = TestIdentityManager::new(vec![
+            TestIdentity::new("$edgeHub", "iotedge", "1", AuthType::Sas),
+            TestIdentity::new("m1", "iotedge", "new", AuthType::Sas),
+        ]);
+        let handler = CleanupKeys::new(manager, slots.clone());
+        let request = Request::post("http://localhost/keys/cleanup")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let cleanup: KeyCleanup = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            &["m1oldserver".to_string(), "m2identity".to_string()],
+            cleanup.released()
+        );
+        let left: Vec<String> = slots
+            .aliases()
+            .iter()
+            .map(|alias| alias.alias().to_string())
+            .collect();
+        assert_eq!(vec!["edgeHubidentity", "m1newserver"], left);
+    }
+
+    #[test]
+    fn nothing_is_cleaned_up_when_identities_cannot_be_listed() {
+        let slots = KeySlots::new();
+        slots.occupy("m1", "m1identity", Utc::now() + Duration::hours(1));
+        let manager = TestIdentityManager::new(vec![]).with_fail_list(true);
+        let handler = CleanupKeys::new(manager, slots.clone());
+        let request = Request::post("http://localhost/keys/cleanup")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::BAD_GATEWAY, response.status());
+        assert_eq!(1, slots.aliases().len());
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::CertificateRegistry;
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::*;
+use serde_json;
+
+use super::core_to_issued;
+use error::ErrorKind;
+use IntoResponse;
+
+pub struct ListCertificates {
+    registry: CertificateRegistry,
+}
+
+impl ListCertificates {
+    pub fn new(registry: CertificateRegistry) -> Self {
+        ListCertificates { registry }
+    }
+}
+
+impl Handler<Parameters> for ListCertificates {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let certificates = self.registry.list().iter().map(core_to_issued).collect();
+        let body = IssuedCertificateList::new(certificates);
+
+        let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
+            Ok(b) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                .body(b.into())
+                .unwrap_or_else(|e| e.into_response()),
+            Err(e) => e.into_response(),
+        };
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use edgelet_test_utils::cert::TEST_CERT_PEM;
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn success() {
+        // arrange
+        let registry = CertificateRegistry::new();
+        registry
+            .record(
+                "m1",
+                "m1identity",
+                TEST_CERT_PEM.as_bytes(),
+                Utc::now() + Duration::hours(1),
+            ).unwrap();
+        let handler = ListCertificates::new(registry);
+        let request = Request::get("http://localhost/certificates")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let list: IssuedCertificateList = serde_json::from_slice(&b).unwrap();
+                let certificates = list.certificates();
+                assert_eq!(1, certificates.len());
+                assert_eq!("1A2B3C4D", certificates[0].serial_number());
+                assert_eq!("m1", certificates[0].module_id());
+                assert_eq!("m1identity", certificates[0].alias());
+                assert_eq!(None, certificates[0].revoked_at());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn empty() {
+        // arrange
+        let handler = ListCertificates::new(CertificateRegistry::new());
+        let request = Request::get("http://localhost/certificates")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let list: IssuedCertificateList = serde_json::from_slice(&b).unwrap();
+                assert!(list.certificates().is_empty());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+}
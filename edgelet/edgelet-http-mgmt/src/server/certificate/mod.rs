@@ -0,0 +1,27 @@
+// Copyright (c) Microsoft. All rights reserved.
+mod keys;
+mod list;
+mod revoke;
+mod slots;
+
+use edgelet_core::IssuedCertificate as CoreIssuedCertificate;
+use management::models::IssuedCertificate;
+
+pub use self::keys::{CleanupKeys, ListKeys};
+pub use self::list::ListCertificates;
+pub use self::revoke::RevokeCertificate;
+pub use self::slots::ListKeySlots;
+
+fn core_to_issued(cert: &CoreIssuedCertificate) -> IssuedCertificate {
+    let issued = IssuedCertificate::new(
+        cert.serial_number().to_string(),
+        cert.module_id().to_string(),
+        cert.alias().to_string(),
+        cert.issued_at().to_rfc3339(),
+        cert.expires_at().to_rfc3339(),
+    );
+    match cert.revoked_at() {
+        Some(revoked_at) => issued.with_revoked_at(revoked_at.to_rfc3339()),
+        None => issued,
+    }
+}
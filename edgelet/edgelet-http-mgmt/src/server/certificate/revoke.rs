@@ -0,0 +1,211 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{
+    CertificateRegistry, ErrorKind as CoreErrorKind, IssuedCertificate, MessageKind, Outbox,
+};
+use edgelet_http::route::{Handler, Parameters};
+use failure::{Fail, ResultExt};
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json::{self, Map, Value};
+
+use super::core_to_issued;
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct RevokeCertificate {
+    registry: CertificateRegistry,
+    outbox: Outbox,
+}
+
+impl RevokeCertificate {
+    pub fn new(registry: CertificateRegistry) -> Self {
+        RevokeCertificate {
+            registry,
+            outbox: Outbox::default(),
+        }
+    }
+
+    /// Reports every revocation to IoT Hub as a security event through
+    /// `outbox`.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+}
+
+fn report_revoked(outbox: &Outbox, cert: &IssuedCertificate) {
+    let mut event = Map::new();
+    event.insert(
+        "event".to_string(),
+        Value::from("certificateRevoked".to_string()),
+    );
+    event.insert(
+        "serialNumber".to_string(),
+        Value::from(cert.serial_number().to_string()),
+    );
+    event.insert(
+        "moduleId".to_string(),
+        Value::from(cert.module_id().to_string()),
+    );
+    if let Err(err) = outbox.push(MessageKind::SecurityEvent, &event) {
+        warn!(
+            "Could not queue the revocation of certificate {} for IoT Hub: {}",
+            cert.serial_number(),
+            err
+        );
+    }
+}
+
+impl Handler<Parameters> for RevokeCertificate {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let response = match params.name("serial") {
+            Some(serial_number) => self
+                .registry
+                .revoke(serial_number)
+                .map_err(|err| {
+                    let kind = match *err.kind() {
+                        CoreErrorKind::CertificateNotFound(_) => ErrorKind::NotFound,
+                        _ => ErrorKind::Core,
+                    };
+                    Error::from(err.context(kind))
+                }).and_then(|cert| {
+                    report_revoked(&self.outbox, &cert);
+                    serde_json::to_string(&core_to_issued(&cert))
+                        .context(ErrorKind::Serde)
+                        .map_err(Error::from)
+                }).and_then(|b| {
+                    Response::builder()
+                        .status(StatusCode::OK)
+                        .header(CONTENT_TYPE, "application/json")
+                        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                        .body(b.into())
+                        .map_err(Error::from)
+                }).unwrap_or_else(|e| e.into_response()),
+
+            None => Error::from(ErrorKind::BadParam).into_response(),
+        };
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use edgelet_test_utils::cert::TEST_CERT_PEM;
+    use futures::Stream;
+    use management::models::{ErrorResponse, IssuedCertificate};
+
+    use super::*;
+
+    fn registry() -> CertificateRegistry {
+        let registry = CertificateRegistry::new();
+        registry
+            .record(
+                "m1",
+                "m1identity",
+                TEST_CERT_PEM.as_bytes(),
+                Utc::now() + Duration::hours(1),
+            ).unwrap();
+        registry
+    }
+
+    #[test]
+    fn success() {
+        // arrange
+        let registry = registry();
+        let handler = RevokeCertificate::new(registry.clone());
+        let request = Request::post("http://localhost/certificates/1a2b3c4d/revoke")
+            .body(Body::default())
+            .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("serial".to_string()), "1a2b3c4d".to_string())]);
+
+        // act
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let cert: IssuedCertificate = serde_json::from_slice(&b).unwrap();
+                assert_eq!("1A2B3C4D", cert.serial_number());
+                assert_eq!("m1", cert.module_id());
+                assert!(cert.revoked_at().is_some());
+                Ok(())
+            }).wait()
+            .unwrap();
+        assert_eq!(1, registry.revoked().len());
+    }
+
+    #[test]
+    fn revocation_is_reported() {
+        let outbox = Outbox::new(10);
+        let handler = RevokeCertificate::new(registry()).with_outbox(outbox.clone());
+        let request = Request::post("http://localhost/certificates/1a2b3c4d/revoke")
+            .body(Body::default())
+            .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("serial".to_string()), "1a2b3c4d".to_string())]);
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let messages = outbox.peek(10);
+        assert_eq!(1, messages.len());
+        assert_eq!(MessageKind::SecurityEvent, messages[0].kind());
+        assert_eq!("certificateRevoked", messages[0].body()["event"]);
+        assert_eq!("1A2B3C4D", messages[0].body()["serialNumber"]);
+        assert_eq!("m1", messages[0].body()["moduleId"]);
+    }
+
+    #[test]
+    fn not_found() {
+        // arrange
+        let handler = RevokeCertificate::new(registry());
+        let request = Request::post("http://localhost/certificates/00ff/revoke")
+            .body(Body::default())
+            .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("serial".to_string()), "00ff".to_string())]);
+
+        // act
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert!(error.message().contains("was not found in the certificate registry"));
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn bad_params() {
+        // arrange
+        let handler = RevokeCertificate::new(registry());
+        let request = Request::post("http://localhost/certificates/revoke")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+}
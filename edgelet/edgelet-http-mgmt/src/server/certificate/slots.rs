@@ -0,0 +1,98 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{KeySlots, SlotUsage};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::*;
+use serde_json;
+
+use error::ErrorKind;
+use IntoResponse;
+
+/// Lists the HSM key slots each module holds through the workload API.
+pub struct ListKeySlots {
+    slots: KeySlots,
+}
+
+impl ListKeySlots {
+    pub fn new(slots: KeySlots) -> Self {
+        ListKeySlots { slots }
+    }
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+fn core_to_usage(usage: &SlotUsage) -> KeySlotUsage {
+    let model = KeySlotUsage::new(
+        usage.module_id().to_string(),
+        usage.used() as i64,
+        usage.aliases().to_vec(),
+    );
+    match usage.quota() {
+        Some(quota) => model.with_quota(quota as i64),
+        None => model,
+    }
+}
+
+impl Handler<Parameters> for ListKeySlots {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let modules = self.slots.usage().iter().map(core_to_usage).collect();
+        let body = KeySlotUsageList::new(modules);
+
+        let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
+            Ok(b) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                .body(b.into())
+                .unwrap_or_else(|e| e.into_response()),
+            Err(e) => e.into_response(),
+        };
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn slots_are_listed_by_module() {
+        let slots = KeySlots::new().with_quota(4);
+        slots.occupy("m1", "m1identity", Utc::now() + Duration::hours(1));
+        slots.occupy("m1", "m1genserver", Utc::now() + Duration::hours(1));
+        slots.occupy("m2", "m2identity", Utc::now() + Duration::hours(1));
+        let handler = ListKeySlots::new(slots);
+        let request = Request::get("http://localhost/certificates/slots")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let list: KeySlotUsageList = serde_json::from_slice(&body).unwrap();
+        let modules = list.modules();
+        assert_eq!(2, modules.len());
+        assert_eq!("m1", modules[0].module_id());
+        assert_eq!(2, modules[0].used());
+        assert_eq!(Some(4), modules[0].quota());
+        assert_eq!(
+            &["m1genserver".to_string(), "m1identity".to_string()],
+            modules[0].aliases()
+        );
+        assert_eq!("m2", modules[1].module_id());
+        assert_eq!(1, modules[1].used());
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+use edgelet_http::route::{Handler, Parameters};
+use futures::{future, Future};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+
+/// The handler of a route that is set once the router is built, by the
+/// `with_*` methods of `ManagementService`. The route is in the route table
+/// either way, but until a handler is set requests to it are turned down
+/// with a 404, the same as requests to a path that isn't routed.
+///
+/// The handler is kept behind a lock, so the one set on a clone is the one
+/// the router calls.
+#[derive(Clone, Default)]
+pub struct Deferred {
+    handler: Arc<RwLock<Option<Arc<Handler<Parameters> + Sync>>>>,
+}
+
+impl Deferred {
+    pub fn new() -> Self {
+        Deferred::default()
+    }
+
+    pub fn with_handler<H: Handler<Parameters> + Sync>(self, handler: H) -> Self {
+        self.set(handler);
+        self
+    }
+
+    pub fn set<H: Handler<Parameters> + Sync>(&self, handler: H) {
+        *self
+            .handler
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(handler));
+    }
+}
+
+impl Handler<Parameters> for Deferred {
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let handler = self
+            .handler
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        match handler {
+            Some(handler) => handler.handle(req, params),
+            None => Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::empty())
+                    .expect("hyper::Response with empty body should not fail to build"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        Box::new(future::ok(Response::new(Body::empty())))
+    }
+
+    fn status(deferred: &Deferred) -> StatusCode {
+        let request = Request::get("http://localhost/").body(Body::empty()).unwrap();
+        deferred
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn requests_are_not_found_until_a_handler_is_set() {
+        let deferred = Deferred::new();
+        assert_eq!(StatusCode::NOT_FOUND, status(&deferred));
+
+        deferred.clone().set(ok);
+        assert_eq!(StatusCode::OK, status(&deferred));
+    }
+}
@@ -0,0 +1,610 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use edgelet_core::{
+    manifest_version, Deployment, DeploymentHistory, Module, ModuleRegistry, ModuleRuntime,
+    ModuleSpec as CoreModuleSpec, Operation, Operations,
+};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::{DeploymentRequest, ModuleSpec};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use tokio;
+
+use super::plan::{plan, Action, PlanStatus, Plans};
+use super::rollback::{remove_module, restore_module};
+use error::{Error, ErrorKind};
+use server::module::{record_applied, record_removed, spec_to_core};
+use IntoResponse;
+
+/// One step of a plan, with what it takes to undo it.
+struct Step<C> {
+    action: Action,
+    name: String,
+    spec: Option<(ModuleSpec, CoreModuleSpec<C>)>,
+    previous: Option<CoreModuleSpec<C>>,
+}
+
+/// Applies a deployment as a whole. The images of all the modules are pulled
+/// before any module is changed, and the changes that were made are undone
+/// when one of them fails, so that a deployment is either applied or not at
+/// all. The plan is applied in the background, and where it is at is polled
+/// at `GET /deployments/apply/{id}`. How far along it is is also reported
+/// through the operation the plan refers to, at `GET /operations/{id}`.
+pub struct ApplyDeployment<M>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: DeserializeOwned + Serialize,
+{
+    runtime: M,
+    history: DeploymentHistory<ModuleSpec>,
+    plans: Plans,
+    operations: Operations,
+}
+
+impl<M> ApplyDeployment<M>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: DeserializeOwned + Serialize,
+{
+    pub fn new(runtime: M, history: DeploymentHistory<ModuleSpec>, plans: Plans) -> Self {
+        ApplyDeployment {
+            runtime,
+            history,
+            plans,
+            operations: Operations::new(),
+        }
+    }
+
+    pub fn with_operations(mut self, operations: Operations) -> Self {
+        self.operations = operations;
+        self
+    }
+}
+
+impl<M> Handler<Parameters> for ApplyDeployment<M>
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    <M::Module as Module>::Config: Clone + DeserializeOwned + Serialize,
+    M::Error: IntoResponse,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let runtime = self.runtime.clone();
+        let history = self.history.clone();
+        let plans = self.plans.clone();
+        let operations = self.operations.clone();
+        let response = req
+            .into_body()
+            .concat2()
+            .and_then(move |b| {
+                let specs = match read_deployment::<M>(&b) {
+                    Ok(specs) => specs,
+                    Err(err) => return Either::A(future::ok(err.into_response())),
+                };
+                Either::B(runtime.list().then(move |modules| {
+                    let response = match modules {
+                        Ok(modules) => {
+                            start_plan(runtime, history, &plans, &operations, specs, &modules)
+                                .unwrap_or_else(|e| e.into_response())
+                        }
+                        Err(err) => err.into_response(),
+                    };
+                    Ok::<_, HyperError>(response)
+                }))
+            }).or_else(|e| future::ok(e.into_response()));
+        Box::new(response)
+    }
+}
+
+fn read_deployment<M>(
+    body: &[u8],
+) -> Result<Vec<(ModuleSpec, CoreModuleSpec<<M::Module as Module>::Config>)>, Error>
+where
+    M: 'static + ModuleRuntime,
+    <M::Module as Module>::Config: DeserializeOwned + Serialize,
+{
+    let deployment: DeploymentRequest = serde_json::from_slice(body).context(ErrorKind::BadBody)?;
+    let version = deployment
+        .version()
+        .map(str::to_string)
+        .unwrap_or_else(|| manifest_version(body));
+
+    let mut names = HashSet::new();
+    deployment
+        .modules()
+        .iter()
+        .map(|spec| {
+            if !names.insert(spec.name().clone()) {
+                return Err(Error::from(ErrorKind::BadBody));
+            }
+            let core_spec = spec_to_core::<M>(spec).context(ErrorKind::BadBody)?;
+            let spec = match spec.deployment() {
+                Some(_) => spec.clone(),
+                None => spec.clone().with_deployment(version.clone()),
+            };
+            Ok((spec, core_spec))
+        }).collect()
+}
+
+fn start_plan<M>(
+    runtime: M,
+    history: DeploymentHistory<ModuleSpec>,
+    plans: &Plans,
+    operations: &Operations,
+    specs: Vec<(ModuleSpec, CoreModuleSpec<<M::Module as Module>::Config>)>,
+    modules: &[M::Module],
+) -> Result<Response<Body>, Error>
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    <M::Module as Module>::Config: Clone + DeserializeOwned + Serialize,
+{
+    let applied = history.current();
+    let existing: Vec<String> = modules.iter().map(|m| m.name().to_string()).collect();
+    let desired: Vec<ModuleSpec> = specs.iter().map(|&(ref spec, _)| spec.clone()).collect();
+    let planned = plan(&desired, &applied, &existing);
+    let started = plans
+        .start(planned.clone())
+        .ok_or_else(|| Error::from(ErrorKind::Conflict))?;
+    let id = started.id().to_string();
+    let operation = operations.start("deployment");
+    plans.set_operation(&id, operation.id());
+    let started = started.with_operation_id(operation.id().to_string());
+
+    let mut specs: HashMap<String, _> = specs
+        .into_iter()
+        .map(|(spec, core_spec)| (spec.name().clone(), (spec, core_spec)))
+        .collect();
+    let steps = planned
+        .into_iter()
+        .map(|(action, name)| Step {
+            action,
+            spec: specs.remove(&name),
+            previous: match action {
+                Action::Create => None,
+                Action::Update | Action::Remove => previous_spec::<M>(&name, &applied, modules),
+            },
+            name,
+        }).collect();
+    // what is left are the modules the plan leaves alone
+    let unchanged = specs.into_iter().map(|(_, (spec, _))| spec).collect();
+
+    info!("Applying deployment plan {}", id);
+    tokio::spawn(apply(
+        runtime,
+        history,
+        plans.clone(),
+        operation,
+        id,
+        steps,
+        unchanged,
+    ));
+
+    let b = serde_json::to_string(&started).context(ErrorKind::Serde)?;
+    Ok(Response::builder()
+        .status(StatusCode::ACCEPTED)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+        .body(b.into())?)
+}
+
+// Modules that were applied through the management API are restored from
+// their spec in the deployment history, others from what the runtime has of
+// them.
+fn previous_spec<M>(
+    name: &str,
+    applied: &Deployment<ModuleSpec>,
+    modules: &[M::Module],
+) -> Option<CoreModuleSpec<<M::Module as Module>::Config>>
+where
+    M: 'static + ModuleRuntime,
+    <M::Module as Module>::Config: Clone + DeserializeOwned + Serialize,
+{
+    let previous = match applied.get(name) {
+        Some(spec) => spec_to_core::<M>(spec).ok(),
+        None => modules
+            .iter()
+            .find(|module| module.name() == name)
+            .and_then(|module| {
+                CoreModuleSpec::new(
+                    module.name(),
+                    module.type_(),
+                    module.config().clone(),
+                    HashMap::new(),
+                ).ok()
+                .map(|spec| match module.namespace() {
+                    Some(namespace) => spec.with_namespace(namespace.to_string()),
+                    None => spec,
+                })
+            }),
+    };
+    if previous.is_none() {
+        warn!("Module {} can't be restored if the deployment is rolled back", name);
+    }
+    previous
+}
+
+// How far along a plan is once `done` of its `total` pulls and steps are done.
+fn percent(done: usize, total: usize) -> u8 {
+    if total == 0 {
+        0
+    } else {
+        (done * 100 / total) as u8
+    }
+}
+
+fn apply<M>(
+    runtime: M,
+    history: DeploymentHistory<ModuleSpec>,
+    plans: Plans,
+    operation: Operation,
+    id: String,
+    steps: Vec<Step<M::Config>>,
+    unchanged: Vec<ModuleSpec>,
+) -> impl Future<Item = (), Error = ()> + Send
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    M::Config: Clone,
+{
+    let pulls = steps.iter().filter(|step| step.spec.is_some()).count();
+    let total = pulls + steps.len();
+    let pulled = Arc::new(AtomicUsize::new(0));
+    operation.progress(0, "Pulling images");
+    let pulling: Vec<_> = steps
+        .iter()
+        .filter_map(|step| step.spec.as_ref())
+        .map(|&(ref spec, ref core_spec)| {
+            let operation = operation.clone();
+            let pulled = pulled.clone();
+            let name = spec.name().clone();
+            runtime.registry().pull(core_spec.config()).map(move |_| {
+                let done = pulled.fetch_add(1, Ordering::SeqCst) + 1;
+                let step = format!("Pulled the image of module {}", name);
+                operation.progress(percent(done, total), &step);
+            })
+        }).collect();
+
+    future::join_all(pulling).then(move |pulled| match pulled {
+        Ok(_) => {
+            plans.set_status(&id, PlanStatus::Applying);
+            let undo_runtime = runtime.clone();
+            let applying = apply_steps(runtime, steps, operation.clone(), pulls, total);
+            let applied = applying.and_then(move |(done, failed)| {
+                match failed {
+                    None => {
+                        for step in &done {
+                            match step.spec {
+                                Some((ref spec, _)) => record_applied(&history, spec),
+                                None => record_removed(&history, &step.name),
+                            }
+                        }
+                        for spec in &unchanged {
+                            record_applied(&history, spec);
+                        }
+                        info!("Applied deployment plan {}", id);
+                        plans.set_status(&id, PlanStatus::Succeeded);
+                        operation.succeed();
+                        Either::A(future::ok(()))
+                    }
+                    Some(failed) => {
+                        warn!("Rolling back deployment plan {}: {}", id, failed);
+                        plans.set_status(&id, PlanStatus::RollingBack);
+                        // the step that failed is the last one done
+                        let failed_at = pulls + done.len() - 1;
+                        operation.progress(percent(failed_at, total), "Rolling back");
+                        Either::B(roll_back(undo_runtime, done).map(move |errors| {
+                            if errors.is_empty() {
+                                info!("Rolled back deployment plan {}", id);
+                                plans.set_error(&id, PlanStatus::RolledBack, failed.clone());
+                                operation.fail(failed);
+                            } else {
+                                let error = format!("{}; {}", failed, errors.join("; "));
+                                warn!("Could not roll back deployment plan {}: {}", id, error);
+                                plans.set_error(&id, PlanStatus::Failed, error.clone());
+                                operation.fail(error);
+                            }
+                        }))
+                    }
+                }
+            });
+            Either::A(applied)
+        }
+        Err(err) => {
+            let error = format!("Could not pull an image: {}", err);
+            warn!("Deployment plan {} failed: {}", id, error);
+            plans.set_error(&id, PlanStatus::Failed, error.clone());
+            operation.fail(error);
+            Either::B(future::ok(()))
+        }
+    })
+}
+
+// Applies the steps in order until one of them fails, and returns the steps
+// that were applied and why one failed. The step that failed is returned with
+// the applied ones, since it may have been applied in part. The progress of
+// the plan is reported to the operation as each step starts, counting the
+// pulls and steps that were done before.
+fn apply_steps<M>(
+    runtime: M,
+    steps: Vec<Step<M::Config>>,
+    operation: Operation,
+    before: usize,
+    total: usize,
+) -> impl Future<Item = (Vec<Step<M::Config>>, Option<String>), Error = ()> + Send
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    M::Config: Clone,
+{
+    stream::iter_ok::<_, ()>(steps).fold((Vec::new(), None), move |(mut done, failed), step| {
+        if failed.is_some() {
+            return Either::A(future::ok((done, failed)));
+        }
+        let current = format!("{} module {}", step.action, step.name);
+        operation.progress(percent(before + done.len(), total), &current);
+        Either::B(apply_step(&runtime, &step).then(move |result| {
+            let failed = result.err().map(|err| {
+                format!("Could not {} module {}: {}", step.action, step.name, err)
+            });
+            done.push(step);
+            Ok::<_, ()>((done, failed))
+        }))
+    })
+}
+
+fn apply_step<M>(
+    runtime: &M,
+    step: &Step<M::Config>,
+) -> Box<Future<Item = (), Error = M::Error> + Send>
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    M::Config: Clone,
+{
+    debug!("Applying step {} module {}", step.action, step.name);
+    let name = step.name.clone();
+    match step.spec {
+        Some((_, ref spec)) => {
+            let removed = match step.action {
+                Action::Update => Either::A(runtime.remove(&name)),
+                Action::Create | Action::Remove => Either::B(future::ok(())),
+            };
+            let spec = spec.clone();
+            let create_runtime = runtime.clone();
+            let start_runtime = runtime.clone();
+            Box::new(
+                removed
+                    .and_then(move |_| create_runtime.create(spec))
+                    .and_then(move |_| start_runtime.start(&name)),
+            )
+        }
+        None => Box::new(runtime.remove(&name)),
+    }
+}
+
+// Undoes the steps in the reverse order they were applied, and returns why
+// the modules that couldn't be restored couldn't be.
+fn roll_back<M>(
+    runtime: M,
+    done: Vec<Step<M::Config>>,
+) -> impl Future<Item = Vec<String>, Error = ()> + Send
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    M::Config: Clone,
+{
+    stream::iter_ok::<_, ()>(done.into_iter().rev()).fold(Vec::new(), move |mut errors, step| {
+        let name = step.name;
+        let restored = match step.previous {
+            Some(previous) => Either::A(restore_module(runtime.clone(), previous)),
+            None => Either::B(remove_module(&runtime, name.clone())),
+        };
+        restored.then(move |result| {
+            if let Err(err) = result {
+                errors.push(format!("could not restore module {}: {}", name, err));
+            }
+            Ok::<_, ()>(errors)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{ModuleRuntimeState, OperationStatus, DEFAULT_HISTORY_SIZE};
+    use edgelet_http::route::Parameters;
+    use edgelet_test_utils::module::*;
+    use management::models::{Config, DeploymentPlan};
+    use serde_json;
+    use server::module::tests::Error;
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    fn runtime() -> TestRuntime<Error> {
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module = TestModule::new(
+            "test-module".to_string(),
+            config,
+            Ok(ModuleRuntimeState::default()),
+        );
+        TestRuntime::new(Ok(module))
+    }
+
+    fn spec(name: &str, image: &str) -> ModuleSpec {
+        let config = Config::new(json!({ "image": image }));
+        ModuleSpec::new(name.to_string(), "docker".to_string(), config)
+    }
+
+    fn history() -> DeploymentHistory<ModuleSpec> {
+        let history = DeploymentHistory::new(DEFAULT_HISTORY_SIZE);
+        history
+            .module_applied("test-module", spec("test-module", "microsoft/test-image:1"))
+            .unwrap();
+        history
+    }
+
+    fn request(modules: Vec<ModuleSpec>) -> Request<Body> {
+        let body = serde_json::to_string(&DeploymentRequest::new(modules)).unwrap();
+        Request::post("http://localhost/deployments/apply")
+            .body(body.into())
+            .unwrap()
+    }
+
+    // Applies the deployment, and waits for the plan to be applied in the
+    // background.
+    fn apply_deployment<H: Handler<Parameters>>(
+        handler: &H,
+        req: Request<Body>,
+    ) -> (StatusCode, Option<DeploymentPlan>) {
+        let mut runtime = Runtime::new().unwrap();
+        let response = runtime
+            .block_on(handler.handle(req, Parameters::new()))
+            .unwrap();
+        runtime.run().unwrap();
+
+        let status = response.status();
+        let body = response.into_body().concat2().wait().unwrap();
+        (status, serde_json::from_slice(&body).ok())
+    }
+
+    #[test]
+    fn deployment_is_applied() {
+        let history = history();
+        let plans = Plans::new();
+        let operations = Operations::new();
+        let handler = ApplyDeployment::new(runtime(), history.clone(), plans.clone())
+            .with_operations(operations.clone());
+
+        let (status, plan) = apply_deployment(
+            &handler,
+            request(vec![
+                spec("test-module", "microsoft/test-image:2"),
+                spec("sensor", "sensor:1"),
+            ]),
+        );
+
+        assert_eq!(StatusCode::ACCEPTED, status);
+        let plan = plans.get(plan.unwrap().id()).unwrap();
+        assert_eq!("succeeded", plan.status());
+        let steps: Vec<_> = plan
+            .steps()
+            .iter()
+            .map(|step| (step.action(), step.module()))
+            .collect();
+        assert_eq!(vec![("update", "test-module"), ("create", "sensor")], steps);
+
+        let current = history.current();
+        assert_eq!(
+            "microsoft/test-image:2",
+            current["test-module"].config().settings()["image"]
+        );
+        assert!(current.contains_key("sensor"));
+
+        let operation = operations.get(plan.operation_id().unwrap()).unwrap();
+        assert_eq!("deployment", operation.kind());
+        assert_eq!(OperationStatus::Succeeded, operation.status());
+        assert_eq!(100, operation.percent());
+    }
+
+    #[test]
+    fn failed_deployment_is_rolled_back() {
+        let history = history();
+        let plans = Plans::new();
+        let operations = Operations::new();
+        let runtime = runtime().with_create_error("sensor", Error::General);
+        let handler = ApplyDeployment::new(runtime, history.clone(), plans.clone())
+            .with_operations(operations.clone());
+
+        let (status, plan) = apply_deployment(
+            &handler,
+            request(vec![
+                spec("test-module", "microsoft/test-image:2"),
+                spec("sensor", "sensor:1"),
+            ]),
+        );
+
+        assert_eq!(StatusCode::ACCEPTED, status);
+        let plan = plans.get(plan.unwrap().id()).unwrap();
+        assert_eq!("rolledBack", plan.status());
+        assert_eq!(
+            Some("Could not create module sensor: General error"),
+            plan.error()
+        );
+
+        let current = history.current();
+        assert_eq!(
+            "microsoft/test-image:1",
+            current["test-module"].config().settings()["image"]
+        );
+        assert!(!current.contains_key("sensor"));
+
+        // the step that failed was the last of two pulls and two steps
+        let operation = operations.get(plan.operation_id().unwrap()).unwrap();
+        assert_eq!(OperationStatus::Failed, operation.status());
+        assert_eq!(75, operation.percent());
+        assert_eq!(Some("Rolling back"), operation.step());
+        assert_eq!(plan.error(), operation.error());
+    }
+
+    #[test]
+    fn deployment_version_is_recorded() {
+        let history = history();
+        let plans = Plans::new();
+        let handler = ApplyDeployment::new(runtime(), history.clone(), plans.clone());
+
+        // the module is left alone, but is recorded as coming from the new version
+        let deployment =
+            DeploymentRequest::new(vec![spec("test-module", "microsoft/test-image:1")])
+                .with_version("v2".to_string());
+        let req = Request::post("http://localhost/deployments/apply")
+            .body(serde_json::to_string(&deployment).unwrap().into())
+            .unwrap();
+        let (_, plan) = apply_deployment(&handler, req);
+
+        let plan = plans.get(plan.unwrap().id()).unwrap();
+        assert_eq!("succeeded", plan.status());
+        assert!(plan.steps().is_empty());
+        assert_eq!(Some("v2"), history.current()["test-module"].deployment());
+
+        // without a version, the hash of the request is recorded
+        let req = request(vec![spec("sensor", "sensor:1")]);
+        let body =
+            serde_json::to_vec(&DeploymentRequest::new(vec![spec("sensor", "sensor:1")])).unwrap();
+        apply_deployment(&handler, req);
+
+        let version = manifest_version(&body);
+        assert_eq!(
+            Some(version.as_str()),
+            history.current()["sensor"].deployment()
+        );
+    }
+
+    #[test]
+    fn duplicate_modules_are_bad_request() {
+        let handler = ApplyDeployment::new(runtime(), history(), Plans::new());
+        let (status, _) = apply_deployment(
+            &handler,
+            request(vec![spec("sensor", "sensor:1"), spec("sensor", "sensor:2")]),
+        );
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+
+    #[test]
+    fn deployments_are_not_applied_concurrently() {
+        let plans = Plans::new();
+        plans.start(Vec::new()).unwrap();
+        let handler = ApplyDeployment::new(runtime(), history(), plans);
+        let (status, _) = apply_deployment(&handler, request(vec![spec("sensor", "sensor:1")]));
+        assert_eq!(StatusCode::CONFLICT, status);
+    }
+}
@@ -0,0 +1,10 @@
+// Copyright (c) Microsoft. All rights reserved.
+mod apply;
+mod plan;
+mod rollback;
+mod status;
+
+pub use self::apply::ApplyDeployment;
+pub use self::plan::Plans;
+pub use self::rollback::RollbackDeployment;
+pub use self::status::GetDeploymentPlan;
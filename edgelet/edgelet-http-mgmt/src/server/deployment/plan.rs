@@ -0,0 +1,295 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use edgelet_core::Deployment;
+use management::models::{DeploymentPlan, ModuleSpec, PlanStep};
+use serde_json;
+
+/// Number of finished plans kept for callers to poll.
+const MAX_FINISHED_PLANS: usize = 10;
+
+/// Where a plan applied through `POST /deployments/apply` is at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlanStatus {
+    Pulling,
+    Applying,
+    RollingBack,
+    Succeeded,
+    Failed,
+    RolledBack,
+}
+
+impl PlanStatus {
+    pub fn is_finished(self) -> bool {
+        match self {
+            PlanStatus::Pulling | PlanStatus::Applying | PlanStatus::RollingBack => false,
+            PlanStatus::Succeeded | PlanStatus::Failed | PlanStatus::RolledBack => true,
+        }
+    }
+}
+
+impl fmt::Display for PlanStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match *self {
+            PlanStatus::Pulling => "pulling",
+            PlanStatus::Applying => "applying",
+            PlanStatus::RollingBack => "rollingBack",
+            PlanStatus::Succeeded => "succeeded",
+            PlanStatus::Failed => "failed",
+            PlanStatus::RolledBack => "rolledBack",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// What a plan does to one module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Create,
+    Update,
+    Remove,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match *self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Remove => "remove",
+        };
+        write!(f, "{}", action)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Plan {
+    id: String,
+    status: PlanStatus,
+    steps: Vec<(Action, String)>,
+    error: Option<String>,
+    operation_id: Option<String>,
+}
+
+impl Plan {
+    fn to_model(&self) -> DeploymentPlan {
+        let steps = self
+            .steps
+            .iter()
+            .map(|&(action, ref name)| PlanStep::new(action.to_string(), name.clone()))
+            .collect();
+        let plan = DeploymentPlan::new(self.id.clone(), self.status.to_string(), steps);
+        let plan = match self.error {
+            Some(ref error) => plan.with_error(error.clone()),
+            None => plan,
+        };
+        match self.operation_id {
+            Some(ref operation_id) => plan.with_operation_id(operation_id.clone()),
+            None => plan,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    plans: VecDeque<Plan>,
+}
+
+/// The plans applied through the management API since the daemon started.
+/// Only one plan is applied at a time, so that two deployments can't
+/// interleave their changes to the modules.
+#[derive(Clone, Default)]
+pub struct Plans {
+    state: Arc<Mutex<State>>,
+}
+
+impl Plans {
+    pub fn new() -> Self {
+        Plans::default()
+    }
+
+    /// Starts tracking a plan with `steps`, unless another one is still
+    /// being applied.
+    pub fn start(&self, steps: Vec<(Action, String)>) -> Option<DeploymentPlan> {
+        let mut state = self.lock();
+        if state.plans.iter().any(|plan| !plan.status.is_finished()) {
+            return None;
+        }
+
+        state.next_id += 1;
+        let id = state.next_id.to_string();
+        let plan = Plan {
+            id,
+            status: PlanStatus::Pulling,
+            steps,
+            error: None,
+            operation_id: None,
+        };
+        let started = plan.to_model();
+        state.plans.push_front(plan);
+        state.plans.truncate(MAX_FINISHED_PLANS + 1);
+        Some(started)
+    }
+
+    pub fn set_status(&self, id: &str, status: PlanStatus) {
+        self.update(id, |plan| plan.status = status);
+    }
+
+    pub fn set_error(&self, id: &str, status: PlanStatus, error: String) {
+        self.update(id, |plan| {
+            plan.status = status;
+            plan.error = Some(error);
+        });
+    }
+
+    /// Records the ID of the operation that reports the progress of the plan.
+    pub fn set_operation(&self, id: &str, operation_id: &str) {
+        self.update(id, |plan| {
+            plan.operation_id = Some(operation_id.to_string())
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<DeploymentPlan> {
+        self.lock()
+            .plans
+            .iter()
+            .find(|plan| plan.id == id)
+            .map(Plan::to_model)
+    }
+
+    fn update<F: FnOnce(&mut Plan)>(&self, id: &str, f: F) {
+        if let Some(plan) = self.lock().plans.iter_mut().find(|plan| plan.id == id) {
+            f(plan);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Works out the steps that take the modules from the ones `applied` through
+/// the management API, of which the runtime has `existing`, to `desired`.
+///
+/// Modules the runtime has that are in `desired` are updated unless they were
+/// applied with the same spec. Applied modules that aren't in `desired` are
+/// removed, while modules that were never deployed through the management
+/// API, such as the initial edge agent, are left alone.
+pub fn plan(
+    desired: &[ModuleSpec],
+    applied: &Deployment<ModuleSpec>,
+    existing: &[String],
+) -> Vec<(Action, String)> {
+    let exists = |name: &String| existing.contains(name);
+    // A module whose config only comes from another version of the
+    // deployment is left alone; its recorded version is updated once the
+    // plan has been applied.
+    let without_version = |spec: &ModuleSpec| {
+        let mut spec = spec.clone();
+        spec.reset_deployment();
+        serde_json::to_value(spec).ok()
+    };
+    let unchanged = |spec: &ModuleSpec| {
+        applied.get(spec.name()).map_or(false, |applied| {
+            without_version(applied) == without_version(spec)
+        })
+    };
+
+    let removed = applied
+        .keys()
+        .filter(|&name| exists(name) && !desired.iter().any(|spec| spec.name() == name))
+        .map(|name| (Action::Remove, name.clone()));
+    let changed = desired.iter().filter_map(|spec| {
+        if !exists(spec.name()) {
+            Some((Action::Create, spec.name().clone()))
+        } else if !unchanged(spec) {
+            Some((Action::Update, spec.name().clone()))
+        } else {
+            None
+        }
+    });
+    removed.chain(changed).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::DeploymentHistory;
+    use management::models::Config;
+
+    use super::*;
+
+    fn spec(name: &str, image: &str) -> ModuleSpec {
+        let config = Config::new(json!({ "image": image }));
+        ModuleSpec::new(name.to_string(), "docker".to_string(), config)
+    }
+
+    #[test]
+    fn modules_are_brought_in_line_with_the_deployment() {
+        let history = DeploymentHistory::new(1);
+        history
+            .module_applied("sensor", spec("sensor", "sensor:1"))
+            .unwrap();
+        history
+            .module_applied("display", spec("display", "display:1"))
+            .unwrap();
+        history
+            .module_applied("legacy", spec("legacy", "legacy:1"))
+            .unwrap();
+        let existing = vec![
+            "edgeAgent".to_string(),
+            "sensor".to_string(),
+            "display".to_string(),
+            "legacy".to_string(),
+        ];
+        let desired = vec![
+            spec("sensor", "sensor:1"),
+            spec("display", "display:2"),
+            spec("kiosk", "kiosk:1"),
+        ];
+
+        assert_eq!(
+            vec![
+                (Action::Remove, "legacy".to_string()),
+                (Action::Update, "display".to_string()),
+                (Action::Create, "kiosk".to_string()),
+            ],
+            plan(&desired, &history.current(), &existing)
+        );
+    }
+
+    #[test]
+    fn modules_from_another_version_are_left_alone() {
+        let history = DeploymentHistory::new(1);
+        history
+            .module_applied(
+                "sensor",
+                spec("sensor", "sensor:1").with_deployment("v1".to_string()),
+            )
+            .unwrap();
+        let existing = vec!["sensor".to_string()];
+        let desired = vec![spec("sensor", "sensor:1").with_deployment("v2".to_string())];
+
+        assert!(plan(&desired, &history.current(), &existing).is_empty());
+    }
+
+    #[test]
+    fn one_plan_is_applied_at_a_time() {
+        let plans = Plans::new();
+        let started = plans.start(vec![(Action::Create, "sensor".to_string())]);
+        let id = started.unwrap().id().to_string();
+        assert!(plans.start(Vec::new()).is_none());
+
+        plans.set_error(&id, PlanStatus::RolledBack, "pull failed".to_string());
+        let plan = plans.get(&id).unwrap();
+        assert_eq!("rolledBack", plan.status());
+        assert_eq!(Some("pull failed"), plan.error());
+        assert_eq!("create", plan.steps()[0].action());
+
+        let next = plans.start(Vec::new()).unwrap();
+        assert_ne!(id, next.id());
+        assert!(plans.get("unknown").is_none());
+    }
+}
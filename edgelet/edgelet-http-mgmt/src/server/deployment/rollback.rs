@@ -0,0 +1,313 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{
+    DeploymentHistory, Error as CoreError, ErrorKind as CoreErrorKind, Module, ModuleRegistry,
+    ModuleRuntime, ModuleSpec as CoreModuleSpec,
+};
+use edgelet_http::route::{Handler, Parameters};
+use failure::{Fail, ResultExt};
+use futures::{future, stream, Future, Stream};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::ModuleSpec;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use url::form_urlencoded::parse as parse_query;
+
+use error::{Error, ErrorKind};
+use server::module::spec_to_core;
+use IntoResponse;
+
+pub struct RollbackDeployment<M>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: DeserializeOwned + Serialize,
+{
+    runtime: M,
+    history: DeploymentHistory<ModuleSpec>,
+}
+
+impl<M> RollbackDeployment<M>
+where
+    M: 'static + ModuleRuntime + Clone,
+    <M::Module as Module>::Config: DeserializeOwned + Serialize,
+{
+    pub fn new(runtime: M, history: DeploymentHistory<ModuleSpec>) -> Self {
+        RollbackDeployment { runtime, history }
+    }
+}
+
+impl<M> Handler<Parameters> for RollbackDeployment<M>
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    <M::Module as Module>::Config: DeserializeOwned + Serialize,
+    M::Error: IntoResponse,
+    <M::ModuleRegistry as ModuleRegistry>::Error: IntoResponse,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let to = match rollback_target(&req) {
+            Ok(to) => to,
+            Err(err) => return Box::new(future::ok(err.into_response())),
+        };
+
+        let target = match self.history.deployment(to) {
+            Some(target) => target,
+            None => {
+                let err = CoreError::from(CoreErrorKind::DeploymentNotFound(to))
+                    .context(ErrorKind::NotFound);
+                return Box::new(future::ok(Error::from(err).into_response()));
+            }
+        };
+
+        let specs = match target
+            .values()
+            .map(spec_to_core::<M>)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(specs) => specs,
+            Err(err) => return Box::new(future::ok(err.into_response())),
+        };
+
+        // Modules that were deployed after the target deployment are removed.
+        // Modules that were never deployed through the management API, such
+        // as the initial edge agent, are left alone.
+        let stale: Vec<String> = self
+            .history
+            .current()
+            .keys()
+            .filter(|name| !target.contains_key(*name))
+            .cloned()
+            .collect();
+
+        info!("Rolling back to deployment {}", to);
+
+        let runtime = self.runtime.clone();
+        let history = self.history.clone();
+        let remove_runtime = runtime.clone();
+        let response = stream::iter_ok(stale)
+            .for_each(move |name| remove_module(&remove_runtime, name))
+            .and_then(move |_| {
+                stream::iter_ok(specs).for_each(move |spec| restore_module(runtime.clone(), spec))
+            }).map(move |_| match history.restored(to) {
+                Ok(()) => {
+                    info!("Rolled back to deployment {}", to);
+                    Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .body(Body::default())
+                        .unwrap_or_else(|e| e.into_response())
+                }
+                Err(err) => Error::from(err).into_response(),
+            }).or_else(|e| future::ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+fn rollback_target(req: &Request<Body>) -> Result<usize, Error> {
+    req.uri()
+        .query()
+        .and_then(|query| {
+            parse_query(query.as_bytes())
+                .find(|&(ref key, _)| key == "to")
+                .map(|(_, value)| value.into_owned())
+        }).map_or(Ok(1), |to| {
+            to.parse::<usize>()
+                .context(ErrorKind::BadParam)
+                .map_err(Error::from)
+        }).and_then(|to| {
+            if to == 0 {
+                Err(Error::from(ErrorKind::BadParam))
+            } else {
+                Ok(to)
+            }
+        })
+}
+
+pub(super) fn remove_module<M>(
+    runtime: &M,
+    name: String,
+) -> impl Future<Item = (), Error = M::Error>
+where
+    M: ModuleRuntime,
+{
+    debug!("Removing module {}", name);
+    runtime.remove(&name).then(move |result| {
+        if let Err(err) = result {
+            warn!("Could not remove module {}: {}", name, err);
+        }
+        Ok(())
+    })
+}
+
+pub(super) fn restore_module<M>(
+    runtime: M,
+    spec: CoreModuleSpec<M::Config>,
+) -> impl Future<Item = (), Error = M::Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+{
+    let name = spec.name().to_string();
+    info!("Restoring module {}", name);
+
+    let registry_runtime = runtime.clone();
+    let create_runtime = runtime.clone();
+    remove_module(&runtime, name.clone())
+        .and_then(move |_| {
+            registry_runtime
+                .registry()
+                .pull(spec.config())
+                .map(move |_| spec)
+        }).and_then(move |spec| create_runtime.create(spec))
+        .and_then(move |_| runtime.start(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use chrono::Duration;
+    use edgelet_core::{ModuleRuntimeState, ModuleStatus, DEFAULT_HISTORY_SIZE};
+    use edgelet_http::route::Parameters;
+    use edgelet_test_utils::module::*;
+    use management::models::{Config, ErrorResponse};
+    use serde_json;
+    use server::module::tests::Error;
+
+    use super::*;
+
+    fn runtime(module: Result<(), Error>) -> TestRuntime<Error> {
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Running)
+            .with_exit_code(Some(0))
+            .with_status_description(Some("description".to_string()))
+            .with_started_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(14, 20, 0, 1)))
+            .with_finished_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(15, 20, 0, 1)))
+            .with_image_id(Some("image-id".to_string()));
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module =
+            module.map(|_| TestModule::new("test-module".to_string(), config, Ok(state)));
+        TestRuntime::new(module)
+    }
+
+    fn spec(name: &str, image: &str) -> ModuleSpec {
+        let config = Config::new(json!({ "image": image }));
+        ModuleSpec::new(name.to_string(), "docker".to_string(), config)
+    }
+
+    fn history() -> DeploymentHistory<ModuleSpec> {
+        let history =
+            DeploymentHistory::new(DEFAULT_HISTORY_SIZE).with_settle_time(Duration::zero());
+        history
+            .module_applied("test-module", spec("test-module", "microsoft/test-image:1"))
+            .unwrap();
+        history
+            .module_applied("test-module", spec("test-module", "microsoft/test-image:2"))
+            .unwrap();
+        history
+    }
+
+    #[test]
+    fn success() {
+        // arrange
+        let history = history();
+        let handler = RollbackDeployment::new(runtime(Ok(())), history.clone());
+        let request = Request::post("http://localhost/deployments/rollback?to=1")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        let current = history.current();
+        assert_eq!(
+            "microsoft/test-image:1",
+            current["test-module"].config().settings()["image"]
+        );
+        assert_eq!(1, history.len());
+    }
+
+    #[test]
+    fn defaults_to_previous_deployment() {
+        // arrange
+        let history = history();
+        let handler = RollbackDeployment::new(runtime(Ok(())), history.clone());
+        let request = Request::post("http://localhost/deployments/rollback")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        assert_eq!(
+            "microsoft/test-image:1",
+            history.current()["test-module"].config().settings()["image"]
+        );
+    }
+
+    #[test]
+    fn not_found() {
+        // arrange
+        let handler = RollbackDeployment::new(runtime(Ok(())), history());
+        let request = Request::post("http://localhost/deployments/rollback?to=2")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+
+    #[test]
+    fn bad_param() {
+        for query in &["to=0", "to=-1", "to=latest"] {
+            // arrange
+            let handler = RollbackDeployment::new(runtime(Ok(())), history());
+            let uri = format!("http://localhost/deployments/rollback?{}", query);
+            let request = Request::post(uri.as_str()).body(Body::default()).unwrap();
+
+            // act
+            let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+            // assert
+            assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        }
+    }
+
+    #[test]
+    fn runtime_error() {
+        // arrange
+        let history = history();
+        let handler = RollbackDeployment::new(runtime(Err(Error::General)), history.clone());
+        let request = Request::post("http://localhost/deployments/rollback?to=1")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!("General error", error.message());
+                Ok(())
+            }).wait()
+            .unwrap();
+        assert_eq!(
+            "microsoft/test-image:2",
+            history.current()["test-module"].config().settings()["image"]
+        );
+    }
+}
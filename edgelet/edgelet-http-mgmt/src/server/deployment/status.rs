@@ -0,0 +1,95 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+
+use super::plan::Plans;
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// Serves where a plan applied through `POST /deployments/apply` is at.
+pub struct GetDeploymentPlan {
+    plans: Plans,
+}
+
+impl GetDeploymentPlan {
+    pub fn new(plans: Plans) -> Self {
+        GetDeploymentPlan { plans }
+    }
+}
+
+impl Handler<Parameters> for GetDeploymentPlan {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let plan = params
+            .name("id")
+            .and_then(|id| self.plans.get(id))
+            .ok_or_else(|| Error::from(ErrorKind::NotFound));
+
+        let response = plan
+            .and_then(|plan| Ok(serde_json::to_string(&plan).context(ErrorKind::Serde)?))
+            .and_then(|b| {
+                Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                    .body(b.into())?)
+            }).unwrap_or_else(|e: Error| e.into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+    use management::models::DeploymentPlan;
+
+    use super::super::plan::Action;
+    use super::*;
+
+    fn request() -> Request<Body> {
+        Request::get("http://localhost/deployments/apply/1")
+            .body(Body::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn plan_is_found_by_id() {
+        let plans = Plans::new();
+        let id = plans
+            .start(vec![(Action::Remove, "sensor".to_string())])
+            .unwrap()
+            .id()
+            .to_string();
+        let handler = GetDeploymentPlan::new(plans);
+        let params = Parameters::with_captures(vec![(Some("id".to_string()), id.clone())]);
+
+        let response = handler.handle(request(), params).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let plan: DeploymentPlan = serde_json::from_slice(&body).unwrap();
+        assert_eq!(id, plan.id());
+        assert_eq!("pulling", plan.status());
+        assert_eq!("sensor", plan.steps()[0].module());
+    }
+
+    #[test]
+    fn unknown_plan_is_not_found() {
+        let handler = GetDeploymentPlan::new(Plans::new());
+        let params = Parameters::with_captures(vec![(Some("id".to_string()), "1".to_string())]);
+
+        let response = handler.handle(request(), params).wait().unwrap();
+
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+}
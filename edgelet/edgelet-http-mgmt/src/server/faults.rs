@@ -0,0 +1,93 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Debug endpoint to script the faults being injected. `GET /debug/faults`
+//! returns the faults, and `PUT /debug/faults` replaces them with the list
+//! in the body. Putting an empty list stops injecting faults.
+
+use edgelet_core::chaos::{self, Fault};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Method, Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct Faults;
+
+impl Handler<Parameters> for Faults {
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        match *req.method() {
+            Method::GET => Box::new(future::ok(list_faults())),
+            Method::PUT => {
+                let response = req
+                    .into_body()
+                    .concat2()
+                    .map_err(Error::from)
+                    .and_then(|b| {
+                        serde_json::from_slice::<Vec<Fault>>(&b)
+                            .context(ErrorKind::BadBody)
+                            .map_err(Error::from)
+                    }).and_then(|faults| {
+                        chaos::set_faults(faults)
+                            .context(ErrorKind::BadBody)
+                            .map_err(Error::from)
+                    }).map(|()| list_faults())
+                    .or_else(|e| future::ok(e.into_response()));
+                Box::new(response)
+            }
+            _ => Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(Body::default())
+                    .expect("response builder failure"),
+            )),
+        }
+    }
+}
+
+fn list_faults() -> Response<Body> {
+    match serde_json::to_string(&chaos::faults()).context(ErrorKind::Serde) {
+        Ok(b) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_LENGTH, b.len().to_string().as_str())
+            .body(b.into())
+            .unwrap_or_else(|e| e.into_response()),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_faults_are_bad_request() {
+        let request = Request::put("http://localhost/debug/faults")
+            .body(r#"[{ "target": "docker", "probability": 2.0 }]"#.into())
+            .unwrap();
+
+        let response = Faults.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn other_methods_are_not_allowed() {
+        let request = Request::delete("http://localhost/debug/faults")
+            .body(Body::default())
+            .unwrap();
+
+        let response = Faults.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, response.status());
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::HostProcesses;
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::HostProcessList;
+use serde_json;
+
+use super::core_to_host_process;
+use error::ErrorKind;
+use IntoResponse;
+
+pub struct ListHostProcesses {
+    host_processes: HostProcesses,
+}
+
+impl ListHostProcesses {
+    pub fn new(host_processes: HostProcesses) -> Self {
+        ListHostProcesses { host_processes }
+    }
+}
+
+impl Handler<Parameters> for ListHostProcesses {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let host_processes = self
+            .host_processes
+            .list()
+            .iter()
+            .map(|process| core_to_host_process(process, &self.host_processes))
+            .collect();
+        let body = HostProcessList::new(host_processes);
+
+        let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
+            Ok(b) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                .body(b.into())
+                .unwrap_or_else(|e| e.into_response()),
+            Err(e) => e.into_response(),
+        };
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn success() {
+        let host_processes = HostProcesses::new();
+        host_processes
+            .register("agent", Path::new("/usr/bin/agent"), 1000)
+            .unwrap();
+        let handler = ListHostProcesses::new(host_processes);
+        let request = Request::get("http://localhost/hostprocesses")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let list: HostProcessList = serde_json::from_slice(&b).unwrap();
+                let host_processes = list.host_processes();
+                assert_eq!(1, host_processes.len());
+                assert_eq!("agent", host_processes[0].name());
+                assert_eq!("/usr/bin/agent", host_processes[0].executable_path());
+                assert_eq!(1000, host_processes[0].uid());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+mod list;
+mod register;
+mod unregister;
+
+use edgelet_core::{
+    Error as CoreError, ErrorKind as CoreErrorKind, HostProcess as CoreHostProcess, HostProcesses,
+};
+use failure::Fail;
+use management::models::HostProcess;
+
+use error::{Error, ErrorKind};
+
+pub use self::list::ListHostProcesses;
+pub use self::register::RegisterHostProcess;
+pub use self::unregister::UnregisterHostProcess;
+
+/// What the module identities of host processes are managed by, so that the
+/// edge agent leaves them alone when it reconciles the identities of the
+/// modules it deploys.
+const HOST_PROCESS_MANAGED_BY: &str = "hostProcess";
+
+fn core_to_host_process(process: &CoreHostProcess, host_processes: &HostProcesses) -> HostProcess {
+    let model = HostProcess::new(
+        process.name().to_string(),
+        process.executable().to_string_lossy().into_owned(),
+        i64::from(process.uid()),
+    );
+    match host_processes.env_file(process.name()) {
+        Some(env_file) => model.with_env_file(env_file.to_string_lossy().into_owned()),
+        None => model,
+    }
+}
+
+fn core_to_error(err: CoreError) -> Error {
+    let kind = match *err.kind() {
+        CoreErrorKind::HostProcessNotFound(_) => ErrorKind::NotFound,
+        CoreErrorKind::InvalidHostProcess(_) | CoreErrorKind::InvalidModuleName(_) => {
+            ErrorKind::BadParam
+        }
+        _ => ErrorKind::Core,
+    };
+    Error::from(err.context(kind))
+}
@@ -0,0 +1,229 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use edgelet_core::{HostProcesses, Identity, IdentityManager, IdentitySpec};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::HostProcessSpec;
+use serde_json;
+
+use super::{core_to_error, core_to_host_process, HOST_PROCESS_MANAGED_BY};
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct RegisterHostProcess<I>
+where
+    I: 'static + IdentityManager,
+    <I as IdentityManager>::Error: IntoResponse,
+{
+    id_manager: Arc<Mutex<I>>,
+    host_processes: HostProcesses,
+}
+
+impl<I> RegisterHostProcess<I>
+where
+    I: 'static + IdentityManager,
+    <I as IdentityManager>::Error: IntoResponse,
+{
+    pub fn new(id_manager: I, host_processes: HostProcesses) -> Self {
+        RegisterHostProcess {
+            id_manager: Arc::new(Mutex::new(id_manager)),
+            host_processes,
+        }
+    }
+}
+
+impl<I> Handler<Parameters> for RegisterHostProcess<I>
+where
+    I: 'static + IdentityManager + Send,
+    <I as IdentityManager>::Error: IntoResponse,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let id_manager = self.id_manager.clone();
+        let host_processes = self.host_processes.clone();
+
+        let response = future::result(
+            params
+                .name("name")
+                .map(ToString::to_string)
+                .ok_or_else(|| Error::from(ErrorKind::BadParam)),
+        ).and_then(|name| {
+            read_request(&name, req).map(move |(executable, uid)| (name, executable, uid))
+        }).and_then(move |(name, executable, uid)| {
+            // the identity is created first, so that the env file written
+            // when the process is registered carries its generation ID
+            let spec =
+                IdentitySpec::new(&name).with_managed_by(HOST_PROCESS_MANAGED_BY.to_string());
+            let create = id_manager.lock().unwrap().create(spec);
+            create.then(move |identity| -> Result<_, Error> {
+                let identity = match identity {
+                    Ok(identity) => identity,
+                    Err(err) => return Ok(err.into_response()),
+                };
+                let process = host_processes
+                    .register(&name, &executable, uid)
+                    .map_err(core_to_error)?;
+                info!(
+                    "Registered host process {} running {} as uid {}",
+                    name,
+                    executable.display(),
+                    uid
+                );
+
+                let body = core_to_host_process(&process, &host_processes)
+                    .with_generation_id(identity.generation_id().to_string());
+                let b = serde_json::to_string(&body).context(ErrorKind::Serde)?;
+                Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                    .body(b.into())
+                    .unwrap_or_else(|e| e.into_response()))
+            })
+        }).or_else(|e| future::ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+fn read_request(
+    name: &str,
+    req: Request<Body>,
+) -> impl Future<Item = (PathBuf, u32), Error = Error> {
+    let name = name.to_string();
+    req.into_body()
+        .concat2()
+        .map_err(Error::from)
+        .and_then(move |b| {
+            let spec = serde_json::from_slice::<HostProcessSpec>(&b).context(ErrorKind::BadBody)?;
+            let executable = PathBuf::from(spec.executable_path());
+            HostProcesses::validate(&name, &executable).map_err(core_to_error)?;
+            let uid = to_uid(spec.uid()).ok_or_else(|| Error::from(ErrorKind::BadBody))?;
+            Ok((executable, uid))
+        })
+}
+
+#[cfg_attr(
+    feature = "cargo-clippy",
+    allow(cast_possible_truncation, cast_sign_loss)
+)]
+fn to_uid(uid: i64) -> Option<u32> {
+    if uid >= 0 && uid <= i64::from(u32::max_value()) {
+        Some(uid as u32)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use edgelet_test_utils::identity::TestIdentityManager;
+    use management::models::{ErrorResponse, HostProcess};
+
+    use super::*;
+
+    fn request(body: &serde_json::Value) -> Request<Body> {
+        Request::put("http://localhost/hostprocesses/agent")
+            .body(serde_json::to_string(body).unwrap().into())
+            .unwrap()
+    }
+
+    fn params() -> Parameters {
+        Parameters::with_captures(vec![(Some("name".to_string()), "agent".to_string())])
+    }
+
+    #[test]
+    fn register_creates_identity() {
+        let host_processes = HostProcesses::new();
+        let handler = RegisterHostProcess::new(
+            TestIdentityManager::new(vec![]),
+            host_processes.clone(),
+        );
+        let body = json!({ "executablePath": "/usr/bin/agent", "uid": 1000 });
+
+        let response = handler.handle(request(&body), params()).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let process: HostProcess = serde_json::from_slice(&b).unwrap();
+                assert_eq!("agent", process.name());
+                assert_eq!(Some("1"), process.generation_id());
+                Ok(())
+            }).wait()
+            .unwrap();
+        let identities = handler.id_manager.lock().unwrap().list().wait().unwrap();
+        assert_eq!(1, identities.len());
+        assert_eq!(HOST_PROCESS_MANAGED_BY, identities[0].managed_by());
+        let process = host_processes.get("agent").unwrap();
+        assert_eq!(Path::new("/usr/bin/agent"), process.executable());
+        assert_eq!(1000, process.uid());
+    }
+
+    #[test]
+    fn register_rejects_relative_executable_and_bad_uid() {
+        let host_processes = HostProcesses::new();
+        let handler = RegisterHostProcess::new(
+            TestIdentityManager::new(vec![]),
+            host_processes.clone(),
+        );
+        let bodies = [
+            json!({ "executablePath": "bin/agent", "uid": 1000 }),
+            json!({ "executablePath": "/usr/bin/agent", "uid": -1 }),
+        ];
+
+        for body in &bodies {
+            let response = handler.handle(request(body), params()).wait().unwrap();
+            assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        }
+        assert!(host_processes.list().is_empty());
+        assert!(
+            handler
+                .id_manager
+                .lock()
+                .unwrap()
+                .list()
+                .wait()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn register_fails_when_identity_cannot_be_created() {
+        let host_processes = HostProcesses::new();
+        let handler = RegisterHostProcess::new(
+            TestIdentityManager::new(vec![]).with_fail_create(true),
+            host_processes.clone(),
+        );
+        let body = json!({ "executablePath": "/usr/bin/agent", "uid": 1000 });
+
+        let response = handler.handle(request(&body), params()).wait().unwrap();
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!("General error", error.message());
+                Ok(())
+            }).wait()
+            .unwrap();
+        assert!(host_processes.get("agent").is_none());
+    }
+}
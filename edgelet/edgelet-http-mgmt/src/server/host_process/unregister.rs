@@ -0,0 +1,133 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Mutex;
+
+use edgelet_core::{HostProcesses, IdentityManager, IdentitySpec};
+use edgelet_http::route::{Handler, Parameters};
+use futures::{future, Future};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+
+use super::core_to_error;
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct UnregisterHostProcess<I>
+where
+    I: 'static + IdentityManager,
+    <I as IdentityManager>::Error: IntoResponse,
+{
+    id_manager: Mutex<I>,
+    host_processes: HostProcesses,
+}
+
+impl<I> UnregisterHostProcess<I>
+where
+    I: 'static + IdentityManager,
+    <I as IdentityManager>::Error: IntoResponse,
+{
+    pub fn new(id_manager: I, host_processes: HostProcesses) -> Self {
+        UnregisterHostProcess {
+            id_manager: Mutex::new(id_manager),
+            host_processes,
+        }
+    }
+}
+
+impl<I> Handler<Parameters> for UnregisterHostProcess<I>
+where
+    I: 'static + IdentityManager + Send,
+    <I as IdentityManager>::Error: IntoResponse,
+{
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let process = params
+            .name("name")
+            .ok_or_else(|| Error::from(ErrorKind::BadParam))
+            .and_then(|name| self.host_processes.unregister(name).map_err(core_to_error));
+
+        let response = match process {
+            Ok(process) => {
+                info!("Unregistered host process {}", process.name());
+                let result = self
+                    .id_manager
+                    .lock()
+                    .unwrap()
+                    .delete(IdentitySpec::new(process.name()))
+                    .map(|_| {
+                        Response::builder()
+                            .status(StatusCode::NO_CONTENT)
+                            .body(Body::default())
+                            .unwrap_or_else(|e| e.into_response())
+                    }).or_else(|e| future::ok(e.into_response()));
+                future::Either::A(result)
+            }
+            Err(err) => future::Either::B(future::ok(err.into_response())),
+        };
+
+        Box::new(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use edgelet_core::AuthType;
+    use edgelet_test_utils::identity::{TestIdentity, TestIdentityManager};
+
+    use super::*;
+
+    fn params() -> Parameters {
+        Parameters::with_captures(vec![(Some("name".to_string()), "agent".to_string())])
+    }
+
+    #[test]
+    fn unregister_deletes_identity() {
+        let host_processes = HostProcesses::new();
+        host_processes
+            .register("agent", Path::new("/usr/bin/agent"), 1000)
+            .unwrap();
+        let manager = TestIdentityManager::new(vec![TestIdentity::new(
+            "agent",
+            "hostProcess",
+            "1",
+            AuthType::Sas,
+        )]);
+        let handler = UnregisterHostProcess::new(manager, host_processes.clone());
+        let request = Request::delete("http://localhost/hostprocesses/agent")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, params()).wait().unwrap();
+
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        assert!(host_processes.get("agent").is_none());
+        assert!(
+            handler
+                .id_manager
+                .lock()
+                .unwrap()
+                .list()
+                .wait()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn unregister_unknown_process_is_not_found() {
+        let handler =
+            UnregisterHostProcess::new(TestIdentityManager::new(vec![]), HostProcesses::new());
+        let request = Request::delete("http://localhost/hostprocesses/agent")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, params()).wait().unwrap();
+
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+}
@@ -13,8 +13,9 @@ use serde_json;
 
 use edgelet_core::{Identity as CoreIdentity, IdentityManager, IdentitySpec};
 use edgelet_http::route::{Handler, Parameters};
-use management::models::{Identity, IdentitySpec as CreateIdentitySpec};
+use management::models::IdentitySpec as CreateIdentitySpec;
 
+use super::core_to_identity;
 use error::{Error, ErrorKind};
 use IntoResponse;
 
@@ -25,6 +26,7 @@ where
     <I as IdentityManager>::Error: IntoResponse,
 {
     id_manager: Arc<Mutex<I>>,
+    export_keys: bool,
 }
 
 impl<I> CreateIdentity<I>
@@ -36,8 +38,15 @@ where
     pub fn new(id_manager: I) -> Self {
         CreateIdentity {
             id_manager: Arc::new(Mutex::new(id_manager)),
+            export_keys: false,
         }
     }
+
+    /// Returns the symmetric keys of the created identity.
+    pub fn with_export_keys(mut self, export_keys: bool) -> Self {
+        self.export_keys = export_keys;
+        self
+    }
 }
 
 impl<I> Handler<Parameters> for CreateIdentity<I>
@@ -52,18 +61,14 @@ where
         _params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let id_mgr = self.id_manager.clone();
+        let export_keys = self.export_keys;
         let response =
             read_request(req)
                 .and_then(move |spec| {
                     let mut rid = id_mgr.lock().unwrap();
                     rid.create(spec)
-                        .map(|identity| {
-                            let identity = Identity::new(
-                                identity.module_id().to_string(),
-                                identity.managed_by().to_string(),
-                                identity.generation_id().to_string(),
-                                identity.auth_type().to_string(),
-                            );
+                        .map(move |identity| {
+                            let identity = core_to_identity(&identity, export_keys);
 
                             match serde_json::to_string(&identity).context(ErrorKind::Serde) {
                                 Ok(b) => Response::builder()
@@ -186,6 +191,26 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn create_returns_keys_only_when_exported() {
+        let manager = TestIdentityManager::new(vec![]);
+        let handler = CreateIdentity::new(manager).with_export_keys(true);
+        let val = json!({ "moduleId": "m1" });
+        let request = Request::post("http://localhost/identities")
+            .body(serde_json::to_string(&val).unwrap().into())
+            .unwrap();
+
+        let response = handler
+            .handle(request, Parameters::default())
+            .wait()
+            .unwrap();
+
+        let body = response.into_body().concat2().wait().unwrap();
+        let identity: TestIdentity = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some("m1-primary"), identity.primary_key());
+        assert_eq!(Some("m1-secondary"), identity.secondary_key());
+    }
+
     #[test]
     fn create_no_body() {
         let manager = TestIdentityManager::new(vec![]);
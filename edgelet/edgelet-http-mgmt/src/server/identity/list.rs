@@ -1,16 +1,17 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::{Identity as CoreIdentity, IdentityManager};
+use edgelet_core::IdentityManager;
 use edgelet_http::route::{Handler, Parameters};
 use failure::ResultExt;
 use futures::{future, Future};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{Request, Response, StatusCode};
 use hyper::{Body, Error as HyperError};
-use management::models::{Identity, IdentityList};
+use management::models::IdentityList;
 use serde::Serialize;
 use serde_json;
 
+use super::core_to_identity;
 use error::ErrorKind;
 use IntoResponse;
 
@@ -20,6 +21,7 @@ where
     I::Identity: Serialize,
 {
     id_manager: I,
+    export_keys: bool,
 }
 
 impl<I> ListIdentities<I>
@@ -28,7 +30,16 @@ where
     I::Identity: Serialize,
 {
     pub fn new(id_manager: I) -> Self {
-        ListIdentities { id_manager }
+        ListIdentities {
+            id_manager,
+            export_keys: false,
+        }
+    }
+
+    /// Returns the symmetric keys of the identities.
+    pub fn with_export_keys(mut self, export_keys: bool) -> Self {
+        self.export_keys = export_keys;
+        self
     }
 }
 
@@ -42,20 +53,15 @@ where
         _req: Request<Body>,
         _params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
-        let response = self.id_manager.list().then(|result| {
+        let export_keys = self.export_keys;
+        let response = self.id_manager.list().then(move |result| {
             match result.context(ErrorKind::IdentityManager) {
                 Ok(identities) => {
                     let body = IdentityList::new(
                         identities
                             .iter()
-                            .map(|identity| {
-                                Identity::new(
-                                    identity.module_id().to_string(),
-                                    identity.managed_by().to_string(),
-                                    identity.generation_id().to_string(),
-                                    identity.auth_type().to_string(),
-                                )
-                            }).collect(),
+                            .map(|identity| core_to_identity(identity, export_keys))
+                            .collect(),
                     );
                     let result = match serde_json::to_string(&body).context(ErrorKind::Serde) {
                         Ok(b) => Response::builder()
@@ -126,6 +132,7 @@ mod tests {
             .body(Body::default())
             .unwrap();
         let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::BAD_GATEWAY, response.status());
 
         response
             .into_body()
@@ -10,6 +10,31 @@ pub use self::delete::DeleteIdentity;
 pub use self::list::ListIdentities;
 pub use self::update::UpdateIdentity;
 
+use edgelet_core::Identity as CoreIdentity;
+use management::models::Identity;
+
+// The symmetric keys of an identity only leave the daemon when the daemon is
+// set up to export them. Otherwise modules use them through the workload API.
+fn core_to_identity<I: CoreIdentity>(identity: &I, export_keys: bool) -> Identity {
+    let result = Identity::new(
+        identity.module_id().to_string(),
+        identity.managed_by().to_string(),
+        identity.generation_id().to_string(),
+        identity.auth_type().to_string(),
+    );
+    if !export_keys {
+        return result;
+    }
+    let result = match identity.primary_key() {
+        Some(key) => result.with_primary_key(key.to_string()),
+        None => result,
+    };
+    match identity.secondary_key() {
+        Some(key) => result.with_secondary_key(key.to_string()),
+        None => result,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use http::{Response, StatusCode};
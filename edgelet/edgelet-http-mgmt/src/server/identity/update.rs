@@ -13,8 +13,9 @@ use serde_json;
 
 use edgelet_core::{Identity as CoreIdentity, IdentityManager, IdentitySpec};
 use edgelet_http::route::{Handler, Parameters};
-use management::models::{Identity, UpdateIdentity as UpdateIdentityRequest};
+use management::models::UpdateIdentity as UpdateIdentityRequest;
 
+use super::core_to_identity;
 use error::{Error, ErrorKind};
 use IntoResponse;
 
@@ -25,6 +26,7 @@ where
     <I as IdentityManager>::Error: IntoResponse,
 {
     id_manager: Arc<Mutex<I>>,
+    export_keys: bool,
 }
 
 impl<I> UpdateIdentity<I>
@@ -36,8 +38,15 @@ where
     pub fn new(id_manager: I) -> Self {
         UpdateIdentity {
             id_manager: Arc::new(Mutex::new(id_manager)),
+            export_keys: false,
         }
     }
+
+    /// Returns the symmetric keys of the updated identity.
+    pub fn with_export_keys(mut self, export_keys: bool) -> Self {
+        self.export_keys = export_keys;
+        self
+    }
 }
 
 impl<I> Handler<Parameters> for UpdateIdentity<I>
@@ -52,13 +61,14 @@ where
         params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let id_manager = self.id_manager.clone();
+        let export_keys = self.export_keys;
         let response = match params.name("name") {
             Some(name) => {
                 let result = read_request(name, req)
                     .and_then(move |spec| {
                         let mut rid = id_manager.lock().unwrap();
                         rid.update(spec)
-                            .map(|id| write_response(&id))
+                            .map(move |id| write_response(&id, export_keys))
                             .or_else(|e| future::ok(e.into_response()))
                     }).or_else(|e| {
                         future::ok(e.into_response()) as FutureResult<Response<Body>, HyperError>
@@ -74,16 +84,11 @@ where
     }
 }
 
-fn write_response<I>(identity: &I) -> Response<Body>
+fn write_response<I>(identity: &I, export_keys: bool) -> Response<Body>
 where
     I: 'static + CoreIdentity + Serialize,
 {
-    let identity = Identity::new(
-        identity.module_id().to_string(),
-        identity.managed_by().to_string(),
-        identity.generation_id().to_string(),
-        identity.auth_type().to_string(),
-    );
+    let identity = core_to_identity(identity, export_keys);
 
     match serde_json::to_string(&identity).context(ErrorKind::Serde) {
         Ok(b) => Response::builder()
@@ -0,0 +1,112 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Development endpoint to build images on the device.
+//! `POST /images/build?t=<tag>` builds the image `tag` from the tar archive
+//! of the build context in the body, and streams the output of the build
+//! back. The Dockerfile is `Dockerfile` at the root of the context unless
+//! the `dockerfile` parameter says otherwise.
+
+use edgelet_core::ImageBuilder;
+use edgelet_http::route::{Handler, Parameters};
+use futures::{future, Future};
+use http::header::CONTENT_TYPE;
+use http::{Method, Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use url::form_urlencoded;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+const DEFAULT_DOCKERFILE: &str = "Dockerfile";
+
+pub struct BuildImage<B> {
+    builder: B,
+}
+
+impl<B> BuildImage<B> {
+    pub fn new(builder: B) -> Self {
+        BuildImage { builder }
+    }
+}
+
+impl<B> Handler<Parameters> for BuildImage<B>
+where
+    B: 'static + ImageBuilder<Context = Body> + Send,
+    B::Error: IntoResponse,
+    B::Output: Into<Body>,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        if *req.method() != Method::POST {
+            return Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(Body::default())
+                    .expect("response builder failure"),
+            ));
+        }
+
+        let response = match parse_query(req.uri().query()) {
+            Ok((tag, dockerfile)) => {
+                let result = self
+                    .builder
+                    .build_image(&tag, &dockerfile, req.into_body())
+                    .map(|output| {
+                        Response::builder()
+                            .status(StatusCode::OK)
+                            .header(CONTENT_TYPE, "application/json")
+                            .body(output.into())
+                            .unwrap_or_else(|e| e.into_response())
+                    }).or_else(|e| future::ok(e.into_response()));
+                future::Either::A(result)
+            }
+            Err(e) => future::Either::B(future::ok(e.into_response())),
+        };
+        Box::new(response)
+    }
+}
+
+// Returns the tag and the Dockerfile of the image to build.
+fn parse_query(query: Option<&str>) -> Result<(String, String), Error> {
+    let params: Vec<(String, String)> = form_urlencoded::parse(query.unwrap_or("").as_bytes())
+        .into_owned()
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .filter(|value| !value.is_empty())
+    };
+
+    let tag = param("t").ok_or_else(|| Error::from(ErrorKind::BadParam))?;
+    let dockerfile = param("dockerfile").unwrap_or_else(|| DEFAULT_DOCKERFILE.to_string());
+    Ok((tag, dockerfile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_is_required() {
+        assert!(parse_query(None).is_err());
+        assert!(parse_query(Some("t=")).is_err());
+        assert!(parse_query(Some("dockerfile=Dockerfile.arm32v7")).is_err());
+    }
+
+    #[test]
+    fn dockerfile_defaults_to_the_root_of_the_context() {
+        assert_eq!(
+            ("sensor:dev".to_string(), "Dockerfile".to_string()),
+            parse_query(Some("t=sensor%3Adev")).unwrap()
+        );
+        assert_eq!(
+            ("sensor:dev".to_string(), "arm32v7/Dockerfile".to_string()),
+            parse_query(Some("t=sensor:dev&dockerfile=arm32v7%2FDockerfile")).unwrap()
+        );
+    }
+}
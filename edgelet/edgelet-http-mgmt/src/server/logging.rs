@@ -0,0 +1,99 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_http::logging::LogSampling;
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::*;
+use serde_json;
+
+use error::ErrorKind;
+use IntoResponse;
+
+/// Lists the counters of the access log sampling rules of each API.
+pub struct ListLogSampling {
+    samplings: Vec<(String, LogSampling)>,
+}
+
+impl ListLogSampling {
+    pub fn new(samplings: Vec<(String, LogSampling)>) -> Self {
+        ListLogSampling { samplings }
+    }
+}
+
+impl Handler<Parameters> for ListLogSampling {
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let rules = self
+            .samplings
+            .iter()
+            .flat_map(|(api, sampling)| {
+                sampling.stats().into_iter().map(move |stats| {
+                    LogSamplingRule::new(
+                        api.clone(),
+                        stats.pattern().to_string(),
+                        stats.logged() as i64,
+                        stats.dropped() as i64,
+                    )
+                })
+            }).collect();
+        let body = LogSamplingList::new(rules);
+
+        let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
+            Ok(b) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                .body(b.into())
+                .unwrap_or_else(|e| e.into_response()),
+            Err(e) => e.into_response(),
+        };
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn rules_of_every_api_are_listed() {
+        let workload = LogSampling::new().with_rule("/sign$", 1, 100).unwrap();
+        let management = LogSampling::new().with_rule("^/modules$", 10, 100).unwrap();
+        let handler = ListLogSampling::new(vec![
+            ("management".to_string(), management),
+            ("workload".to_string(), workload),
+        ]);
+        let request = Request::get("http://localhost/logging/sampling")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let list: LogSamplingList = serde_json::from_slice(&body).unwrap();
+        let rules: Vec<_> = list
+            .rules()
+            .iter()
+            .map(|rule| (rule.api(), rule.pattern(), rule.logged(), rule.dropped()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("management", "^/modules$", 0, 0),
+                ("workload", "/sign$", 0, 0),
+            ],
+            rules
+        );
+    }
+}
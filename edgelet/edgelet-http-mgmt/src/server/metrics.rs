@@ -0,0 +1,146 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+
+use edgelet_core::{DeploymentHistory, Metrics, DEFAULT_HISTORY_SIZE};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::*;
+use serde_json;
+
+use error::ErrorKind;
+use IntoResponse;
+
+/// The metric that reports the version of the deployment a module came
+/// from, with a value of 1 for each module.
+pub const MODULE_DEPLOYMENT: &str = "moduleDeployment";
+
+/// Lists the counters of the daemon, including those carried over from the
+/// runs before a restart, and the versions of the deployments the modules
+/// came from.
+pub struct ListMetrics {
+    metrics: Metrics,
+    history: DeploymentHistory<ModuleSpec>,
+}
+
+impl ListMetrics {
+    pub fn new(metrics: Metrics) -> Self {
+        ListMetrics {
+            metrics,
+            history: DeploymentHistory::new(DEFAULT_HISTORY_SIZE),
+        }
+    }
+
+    pub fn with_history(mut self, history: DeploymentHistory<ModuleSpec>) -> Self {
+        self.history = history;
+        self
+    }
+}
+
+impl Handler<Parameters> for ListMetrics {
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let counters = self
+            .metrics
+            .snapshot()
+            .into_iter()
+            .map(|(name, value)| Metric::new(name, value as i64));
+        let deployments = self
+            .history
+            .current()
+            .into_iter()
+            .filter_map(|(name, spec)| {
+                spec.deployment().map(|version| {
+                    let mut labels = HashMap::new();
+                    labels.insert("module".to_string(), name);
+                    labels.insert("deployment".to_string(), version.to_string());
+                    Metric::new(MODULE_DEPLOYMENT.to_string(), 1).with_labels(labels)
+                })
+            });
+        let metrics = counters.chain(deployments).collect();
+        let body = MetricList::new(metrics);
+
+        let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
+            Ok(b) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                .body(b.into())
+                .unwrap_or_else(|e| e.into_response()),
+            Err(e) => e.into_response(),
+        };
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{DAEMON_RESTARTS, WATCHDOG_RESTARTS};
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn counters_are_listed() {
+        let metrics = Metrics::new();
+        metrics.add(WATCHDOG_RESTARTS, 3);
+        metrics.increment(DAEMON_RESTARTS);
+        let request = Request::get("http://localhost/metrics")
+            .body(Body::default())
+            .unwrap();
+
+        let response = ListMetrics::new(metrics)
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let list: MetricList = serde_json::from_slice(&body).unwrap();
+        let metrics: Vec<_> = list
+            .metrics()
+            .iter()
+            .map(|metric| (metric.name(), metric.value()))
+            .collect();
+        assert_eq!(
+            vec![("daemonRestarts", 1), ("watchdogRestarts", 3)],
+            metrics
+        );
+    }
+
+    #[test]
+    fn deployments_are_listed() {
+        let history = DeploymentHistory::new(DEFAULT_HISTORY_SIZE);
+        let config = Config::new(json!({"image":"sensor:1"}));
+        let spec = ModuleSpec::new("sensor".to_string(), "docker".to_string(), config)
+            .with_deployment("v2".to_string());
+        history.module_applied("sensor", spec).unwrap();
+        let request = Request::get("http://localhost/metrics")
+            .body(Body::default())
+            .unwrap();
+
+        let response = ListMetrics::new(Metrics::new())
+            .with_history(history)
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap();
+
+        let body = response.into_body().concat2().wait().unwrap();
+        let list: MetricList = serde_json::from_slice(&body).unwrap();
+        let metric = &list.metrics()[0];
+        assert_eq!(MODULE_DEPLOYMENT, metric.name());
+        assert_eq!(1, metric.value());
+        let labels = metric.labels().unwrap();
+        assert_eq!("sensor", labels["module"]);
+        assert_eq!("v2", labels["deployment"]);
+    }
+}
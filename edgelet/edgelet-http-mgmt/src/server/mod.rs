@@ -1,29 +1,78 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+mod ca_rotation;
+mod certificate;
+mod deferred;
+mod deployment;
+#[cfg(feature = "chaos")]
+mod faults;
+mod host_process;
 mod identity;
+mod image;
+mod logging;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod module;
+mod operation;
+mod schedule;
+mod state;
 mod system_info;
 
 use std::error::Error as StdError;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use edgelet_core::{
-    Error as CoreError, IdentityManager, Module, ModuleRegistry, ModuleRuntime, Policy,
+    DiskUsageProvider, Error as CoreError, IdentityManager, ImageBuilder, Module, ModuleConsole,
+    ModuleRegistry, ModuleRuntime, Policy, SystemInfoProvider,
 };
 use edgelet_http::authorization::Authorization;
+use edgelet_http::cache::Cached;
+use edgelet_http::client::ClientImpl;
+use edgelet_http::idempotency::Idempotent;
+use edgelet_http::logging::LogSampling;
 use edgelet_http::route::*;
 use failure;
 use futures::{future, Future};
 use hyper::service::{NewService, Service};
-use hyper::{Body, Request};
+use hyper::{Body, Method, Request};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use self::ca_rotation::*;
+use self::deferred::Deferred;
+use self::certificate::*;
+use self::deployment::*;
+#[cfg(feature = "chaos")]
+use self::faults::*;
+use self::host_process::*;
 use self::identity::*;
+use self::image::*;
+use self::logging::*;
+#[cfg(feature = "metrics")]
+use self::metrics::*;
 pub use self::module::*;
+use self::operation::*;
+use self::schedule::*;
+use self::state::*;
 use self::system_info::*;
 
+use error::{Error, ErrorKind};
+use state::ManagementState;
 use IntoResponse;
 
+// only needed for the `metrics` and `history` fields of `ManagementService`,
+// which serve `GET /metrics`
+#[cfg(feature = "metrics")]
+use edgelet_core::{DeploymentHistory, Metrics};
+#[cfg(feature = "metrics")]
+use management::models::ModuleSpec;
+
+/// How long the system information is served from memory, since monitoring
+/// polls it far more often than it changes.
+const SYSTEM_INFO_CACHE_TTL: Duration = Duration::from_secs(5);
+
 lazy_static! {
     static ref AGENT_NAME: String = "edgeAgent".to_string();
 }
@@ -31,15 +80,32 @@ lazy_static! {
 #[derive(Clone)]
 pub struct ManagementService {
     inner: RouterService<RegexRecognizer>,
+    build_image: Deferred,
+    attach: Deferred,
+    disk_usage: Deferred,
+    log_sampling: Deferred,
+    log_samplings: Vec<(String, LogSampling)>,
+    read_only: bool,
+    #[cfg(feature = "metrics")]
+    metrics: Deferred,
+    #[cfg(feature = "metrics")]
+    history: DeploymentHistory<ModuleSpec>,
 }
 
 impl ManagementService {
     // clippy bug: https://github.com/rust-lang-nursery/rust-clippy/issues/3220
     #[cfg_attr(feature = "cargo-clippy", allow(new_ret_no_self))]
-    pub fn new<M, I>(runtime: &M, identity: &I) -> impl Future<Item = Self, Error = failure::Error>
+    pub fn new<M, I, S, C>(
+        runtime: &M,
+        identity: &I,
+        export_keys: bool,
+        state: &ManagementState,
+        host: &S,
+        client: C,
+    ) -> impl Future<Item = Self, Error = failure::Error>
     where
         M: 'static + ModuleRuntime + Clone + Send + Sync,
-        <M::Module as Module>::Config: DeserializeOwned + Serialize,
+        <M::Module as Module>::Config: Clone + DeserializeOwned + Serialize,
         M::Error: IntoResponse,
         M::Error: Into<CoreError>,
         <M::Module as Module>::Error: Into<CoreError>,
@@ -48,30 +114,193 @@ impl ManagementService {
         I: 'static + IdentityManager + Clone + Send + Sync,
         I::Identity: Serialize,
         I::Error: IntoResponse,
+        S: 'static + SystemInfoProvider + Clone + Send + Sync,
+        C: 'static + ClientImpl,
     {
+        // used to upload module logs to blob storage
+        let client = Arc::new(client);
+        let plans = Plans::new();
+        let history = state.history();
+        let idempotency = state.idempotency();
+        let registry = state.registry();
+        let slots = state.slots();
+        let operations = state.operations();
+        let host_processes = state.host_processes();
+        let usage = state.usage();
+        let outbox = state.outbox();
+        let rotation = state.rotation();
+        let scheduler = state.scheduler();
+        let backup = state.backup();
+        // the handlers of these routes are set, or replaced, by `with_*`
+        let build_image = Deferred::new();
+        let attach = Deferred::new();
+        let disk_usage = Deferred::new();
+        let log_sampling = Deferred::new().with_handler(ListLogSampling::new(Vec::new()));
+        #[cfg(feature = "metrics")]
+        let metrics = Deferred::new()
+            .with_handler(ListMetrics::new(Metrics::new()).with_history(history.clone()));
         let router = router!(
-            get    "/modules"                         => Authorization::new(ListModules::new(runtime.clone()), Policy::Anonymous, runtime.clone()),
-            post   "/modules"                         => Authorization::new(CreateModule::new(runtime.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
-            get    "/modules/(?P<name>[^/]+)"         => Authorization::new(GetModule, Policy::Anonymous, runtime.clone()),
-            put    "/modules/(?P<name>[^/]+)"         => Authorization::new(UpdateModule::new(runtime.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
-            delete "/modules/(?P<name>[^/]+)"         => Authorization::new(DeleteModule::new(runtime.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/start"   => Authorization::new(StartModule::new(runtime.clone()), Policy::Anonymous, runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/stop"    => Authorization::new(StopModule::new(runtime.clone()), Policy::Anonymous, runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/restart" => Authorization::new(RestartModule::new(runtime.clone()), Policy::Anonymous, runtime.clone()),
-            get    "/modules/(?P<name>[^/]+)/logs"    => Authorization::new(ModuleLogs::new(runtime.clone()), Policy::Anonymous, runtime.clone()),
-
-            get    "/identities"                      => Authorization::new(ListIdentities::new(identity.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
-            post   "/identities"                      => Authorization::new(CreateIdentity::new(identity.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
-            put    "/identities/(?P<name>[^/]+)"      => Authorization::new(UpdateIdentity::new(identity.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
-            delete "/identities/(?P<name>[^/]+)"      => Authorization::new(DeleteIdentity::new(identity.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
-
-            get    "/systeminfo"                      => Authorization::new(GetSystemInfo::new(runtime.clone()), Policy::Anonymous, runtime.clone()),
+            get    "/modules"                         => Authorization::new(ListModules::new(runtime.clone()).with_history(history.clone()), Policy::Anonymous, runtime.clone()),
+            post   "/modules"                         => Authorization::new(Idempotent::new(CreateModule::new(runtime.clone()).with_history(history.clone()), idempotency.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            get    "/modules/(?P<name>[^/]+)"         => Authorization::new(GetModule, Policy::Namespace, runtime.clone()),
+            put    "/modules/(?P<name>[^/]+)"         => Authorization::new(Idempotent::new(UpdateModule::new(runtime.clone()).with_history(history.clone()), idempotency.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            delete "/modules/(?P<name>[^/]+)"         => Authorization::new(Idempotent::new(DeleteModule::new(runtime.clone(), identity.clone()).with_history(history.clone()).with_certificates(registry.clone(), slots.clone()), idempotency.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            post   "/modules/(?P<name>[^/]+)/start"   => Authorization::new(StartModule::new(runtime.clone()), Policy::Namespace, runtime.clone()),
+            post   "/modules/(?P<name>[^/]+)/stop"    => Authorization::new(StopModule::new(runtime.clone()), Policy::Namespace, runtime.clone()),
+            post   "/modules/(?P<name>[^/]+)/restart" => Authorization::new(RestartModule::new(runtime.clone()), Policy::Namespace, runtime.clone()),
+            get    "/modules/(?P<name>[^/]+)/logs"    => Authorization::new(ModuleLogs::new(runtime.clone()), Policy::Namespace, runtime.clone()),
+            get    "/modules/(?P<name>[^/]+)/usage"   => Authorization::new(GetModuleUsage::new(usage.clone()), Policy::Namespace, runtime.clone()),
+            post   "/modules/(?P<name>[^/]+)/logs/upload" => Authorization::new(UploadModuleLogs::new(runtime.clone(), client.clone()), Policy::Namespace, runtime.clone()),
+            get    "/modules/(?P<name>[^/]+)/attach"  => attach.clone(),
+
+            get    "/identities"                      => Authorization::new(ListIdentities::new(identity.clone()).with_export_keys(export_keys), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            post   "/identities"                      => Authorization::new(Idempotent::new(CreateIdentity::new(identity.clone()).with_export_keys(export_keys), idempotency.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            put    "/identities/(?P<name>[^/]+)"      => Authorization::new(Idempotent::new(UpdateIdentity::new(identity.clone()).with_export_keys(export_keys), idempotency.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            delete "/identities/(?P<name>[^/]+)"      => Authorization::new(Idempotent::new(DeleteIdentity::new(identity.clone()), idempotency.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+
+            get    "/hostprocesses"                   => Authorization::new(ListHostProcesses::new(host_processes.clone()), Policy::Anonymous, runtime.clone()),
+            put    "/hostprocesses/(?P<name>[^/]+)"   => Authorization::new(RegisterHostProcess::new(identity.clone(), host_processes.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            delete "/hostprocesses/(?P<name>[^/]+)"   => Authorization::new(UnregisterHostProcess::new(identity.clone(), host_processes.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+
+            post   "/deployments/rollback"            => Authorization::new(RollbackDeployment::new(runtime.clone(), history.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            post   "/deployments/apply"               => Authorization::new(ApplyDeployment::new(runtime.clone(), history.clone(), plans.clone()).with_operations(operations.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            get    "/deployments/apply/(?P<id>[^/]+)" => Authorization::new(GetDeploymentPlan::new(plans), Policy::Module(&*AGENT_NAME), runtime.clone()),
+
+            post   "/images/pull"                     => Authorization::new(PullImage::new(runtime.clone(), operations.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            post   "/images/build"                    => build_image.clone(),
+
+            get    "/operations/(?P<id>[^/]+)"        => Authorization::new(GetOperation::new(operations.clone()), Policy::Anonymous, runtime.clone()),
+
+            get    "/certificates"                    => Authorization::new(ListCertificates::new(registry.clone()), Policy::Anonymous, runtime.clone()),
+            get    "/certificates/slots"              => Authorization::new(ListKeySlots::new(slots.clone()), Policy::Anonymous, runtime.clone()),
+            post   "/certificates/(?P<serial>[^/]+)/revoke" => Authorization::new(RevokeCertificate::new(registry.clone()).with_outbox(outbox.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            get    "/keys"                            => Authorization::new(ListKeys::new(slots.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            post   "/keys/cleanup"                    => Authorization::new(CleanupKeys::new(identity.clone(), slots.clone()).with_registry(registry.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+
+            get    "/ca/rotation"                     => Authorization::new(GetCaRotation::new(rotation.clone()), Policy::Anonymous, runtime.clone()),
+            post   "/ca/rotation/reissue"             => Authorization::new(ReissueWorkloadCa::new(rotation.clone()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+
+            get    "/schedules"                       => Authorization::new(ListSchedules::new(scheduler.clone()), Policy::Anonymous, runtime.clone()),
+
+            post   "/state/backup"                    => Authorization::new(BackupState::new(backup.cloned()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+            post   "/state/restore"                   => Authorization::new(RestoreState::new(backup.cloned()), Policy::Module(&*AGENT_NAME), runtime.clone()),
+
+            get    "/systeminfo"                      => Authorization::new(Cached::new(GetSystemInfo::new(runtime.clone(), host.clone()).with_history(history.clone()), SYSTEM_INFO_CACHE_TTL), Policy::Anonymous, runtime.clone()),
+            get    "/systeminfo/disk"                 => disk_usage.clone(),
+
+            get    "/logging/sampling"                => log_sampling.clone(),
+            #[cfg(feature = "metrics")]
+            get    "/metrics"                         => metrics.clone(),
+
+            #[cfg(feature = "chaos")]
+            get    "/debug/faults"                    => Faults,
+            #[cfg(feature = "chaos")]
+            put    "/debug/faults"                    => Faults,
         );
 
+        #[cfg(feature = "metrics")]
+        let history = history.clone();
         router
             .new_service()
-            .map(|inner| ManagementService { inner })
-            .map_err(failure::Error::from_boxed_compat)
+            .map(|inner| ManagementService {
+                inner,
+                build_image,
+                attach,
+                disk_usage,
+                log_sampling,
+                log_samplings: Vec::new(),
+                read_only: false,
+                #[cfg(feature = "metrics")]
+                metrics,
+                #[cfg(feature = "metrics")]
+                history,
+            }).map_err(failure::Error::from_boxed_compat)
+    }
+
+    /// Lists the routes of the management API, including those that aren't
+    /// served until they are turned on with `with_*`.
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        self.inner.recognizer().routes()
+    }
+
+    /// Serves `POST /images/build`, which builds images with `builder` for
+    /// the edge agent. It is meant for development, so it isn't served unless
+    /// this is called.
+    pub fn with_image_builder<B, M>(self, builder: B, runtime: M) -> Self
+    where
+        B: 'static + ImageBuilder<Context = Body> + Send + Sync,
+        B::Error: IntoResponse,
+        B::Output: Into<Body>,
+        M: 'static + ModuleRuntime + Clone + Send + Sync,
+        M::Error: Into<CoreError>,
+        <M::Module as Module>::Error: Into<CoreError>,
+    {
+        self.build_image.set(Authorization::new(
+            BuildImage::new(builder),
+            Policy::Module(&*AGENT_NAME),
+            runtime,
+        ));
+        self
+    }
+
+    /// Serves `GET /modules/<name>/attach`, which attaches to the console of
+    /// a module. It is meant for development, so it isn't served unless this
+    /// is called.
+    pub fn with_attach<M>(self, runtime: M) -> Self
+    where
+        M: 'static + ModuleRuntime + ModuleConsole + Clone + Send + Sync,
+        <M as ModuleRuntime>::Error: Into<CoreError>,
+        <M::Module as Module>::Error: Into<CoreError>,
+        <M as ModuleConsole>::Error: IntoResponse,
+    {
+        self.attach.set(Authorization::new(
+            AttachModule::new(runtime.clone()),
+            Policy::Namespace,
+            runtime,
+        ));
+        self
+    }
+
+    /// Serves `GET /systeminfo/disk`, which reports what takes up the disk of
+    /// the device, with the state of the daemon measured in `homedir`.
+    pub fn with_disk_usage<D>(self, runtime: D, homedir: PathBuf) -> Self
+    where
+        D: 'static + ModuleRuntime + DiskUsageProvider + Clone + Send + Sync,
+        <D as ModuleRuntime>::Error: Into<CoreError>,
+        <D::Module as Module>::Error: Into<CoreError>,
+        <D as DiskUsageProvider>::Error: IntoResponse,
+    {
+        self.disk_usage.set(Authorization::new(
+            GetDiskUsage::new(runtime.clone(), homedir),
+            Policy::Anonymous,
+            runtime,
+        ));
+        self
+    }
+
+    /// Lists how many requests the access log of `api` left out through
+    /// `sampling` at `GET /logging/sampling`.
+    pub fn with_log_sampling(mut self, api: &str, sampling: LogSampling) -> Self {
+        self.log_samplings.push((api.to_string(), sampling));
+        self.log_sampling.set(ListLogSampling::new(self.log_samplings.clone()));
+        self
+    }
+
+    /// Refuses the requests that would change the device with a 403, except
+    /// those of the routes the edge agent applies deployments through, for
+    /// devices whose changes must only come from deployments. Requests that
+    /// read, such as listing modules or getting their logs, are served.
+    pub fn with_read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Lists the counters of `metrics` at `GET /metrics`, along with the
+    /// version of the deployment each module came from.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(self, metrics: Metrics) -> Self {
+        self.metrics.set(ListMetrics::new(metrics).with_history(self.history.clone()));
+        self
     }
 }
 
@@ -82,10 +311,49 @@ impl Service for ManagementService {
     type Future = <RouterService<RegexRecognizer> as Service>::Future;
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
+        // Paths that can't be normalized are turned down by the router.
+        let refused = self.read_only
+            && normalize_path(req.uri().path())
+                .map(|path| changes_device(req.method(), &path))
+                .unwrap_or(false);
+        if refused {
+            return Box::new(future::ok(Error::from(ErrorKind::ReadOnly).into_response()));
+        }
         self.inner.call(req)
     }
 }
 
+/// Whether a request to the normalized `path` would change the device in a
+/// way a read-only management API refuses. The routes the edge agent applies
+/// deployments through only serve it anyway, so they are left to their
+/// authorization, and requests that only read aren't refused whatever their
+/// method. Attaching to a module is a `GET`, but it writes to its console.
+fn changes_device(method: &Method, path: &str) -> bool {
+    if attach_module_name(path).is_some() {
+        return true;
+    }
+    if *method == Method::GET || *method == Method::HEAD {
+        return false;
+    }
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let agent = if *method == Method::POST {
+        path == "/modules"
+            || path == "/identities"
+            || path == "/deployments/apply"
+            || path == "/images/pull"
+    } else if *method == Method::PUT || *method == Method::DELETE {
+        segments.len() == 2 && (segments[0] == "modules" || segments[0] == "identities")
+    } else {
+        false
+    };
+    let uploads_logs =
+        segments.len() == 4 && segments[0] == "modules" && segments[2..] == ["logs", "upload"];
+    let reads = *method == Method::POST && (path == BACKUP_STATE_PATH || uploads_logs);
+
+    !agent && !reads
+}
+
 impl NewService for ManagementService {
     type ReqBody = <Self::Service as Service>::ReqBody;
     type ResBody = <Self::Service as Service>::ResBody;
@@ -98,3 +366,135 @@ impl NewService for ManagementService {
         future::ok(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use edgelet_core::{CaRotation, DeploymentHistory, HostSystemInfo, IdempotencyKeys, Scheduler};
+    use edgelet_test_utils::golden::assert_golden;
+    use edgelet_test_utils::identity::{Error, TestIdentityManager};
+    use edgelet_test_utils::module::TestRuntime;
+    use futures::Stream;
+    use http::{Response, StatusCode};
+    use hyper::Error as HyperError;
+    use management::models::ErrorResponse;
+    use serde_json;
+
+    use super::*;
+
+    fn service() -> ManagementService {
+        let client = |_: Request<Body>| -> Result<Response<Body>, HyperError> {
+            Ok(Response::new(Body::empty()))
+        };
+        ManagementService::new(
+            &TestRuntime::new(Err(Error::General)),
+            &TestIdentityManager::new(vec![]),
+            false,
+            &ManagementState::new(
+                DeploymentHistory::new(1),
+                Scheduler::new(&[]).unwrap(),
+                CaRotation::new(Duration::from_secs(3600)),
+                IdempotencyKeys::new(Duration::from_secs(60)),
+            ),
+            &HostSystemInfo::new("."),
+            client,
+        ).wait()
+        .unwrap()
+    }
+
+    // the golden file lists the routes of the default features
+    #[cfg(all(feature = "metrics", not(feature = "chaos")))]
+    #[test]
+    fn routes_match_the_golden_file() {
+        let service = service();
+        let routes: Vec<String> = service.routes().iter().map(ToString::to_string).collect();
+        let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/server/routes.txt");
+        assert_golden(golden, &(routes.join("\n") + "\n"));
+    }
+
+    #[test]
+    fn deferred_routes_are_not_found_until_they_are_set() {
+        let mut service = service();
+        let request = Request::get("http://localhost/systeminfo/disk")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.call(request).wait().unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        // served whatever the path was spelled like, like the other routes
+        let request = Request::get("http://localhost//logging/./sampling")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.call(request).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    #[test]
+    fn read_only_service_refuses_changes() {
+        let mut service = service().with_read_only();
+        let request = Request::post("http://localhost/modules/m1/restart")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.call(request).wait().unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(Some("ReadOnlyManagementApi"), error.code());
+    }
+
+    #[test]
+    fn read_only_service_refuses_changes_to_unnormalized_paths() {
+        let mut service = service().with_read_only();
+        for &(ref method, path) in &[
+            (Method::POST, "//modules//m1/restart"),
+            (Method::POST, "/modules/./m1/restart"),
+            (Method::GET, "/modules/m1/attach/"),
+        ] {
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(format!("http://localhost{}", path))
+                .body(Body::empty())
+                .unwrap();
+            let response = service.call(request).wait().unwrap();
+            assert_eq!(StatusCode::FORBIDDEN, response.status(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn only_requests_that_change_the_device_are_refused() {
+        for &(ref method, path) in &[
+            (Method::POST, "/modules/m1/start"),
+            (Method::POST, "/modules/m1/restart"),
+            (Method::PUT, "/hostprocesses/p1"),
+            (Method::DELETE, "/hostprocesses/p1"),
+            (Method::POST, "/deployments/rollback"),
+            (Method::POST, "/certificates/01/revoke"),
+            (Method::POST, "/keys/cleanup"),
+            (Method::POST, "/ca/rotation/reissue"),
+            (Method::POST, "/state/restore"),
+            (Method::POST, "/images/build"),
+            (Method::GET, "/modules/m1/attach"),
+        ] {
+            assert!(changes_device(method, path), "{} {}", method, path);
+        }
+
+        for &(ref method, path) in &[
+            (Method::GET, "/modules"),
+            (Method::GET, "/modules/m1/logs"),
+            (Method::GET, "/systeminfo"),
+            (Method::POST, "/modules/m1/logs/upload"),
+            (Method::POST, "/state/backup"),
+            (Method::POST, "/modules"),
+            (Method::PUT, "/modules/m1"),
+            (Method::DELETE, "/modules/m1"),
+            (Method::POST, "/identities"),
+            (Method::DELETE, "/identities/m1"),
+            (Method::POST, "/deployments/apply"),
+            (Method::POST, "/images/pull"),
+        ] {
+            assert!(!changes_device(method, path), "{} {}", method, path);
+        }
+    }
+}
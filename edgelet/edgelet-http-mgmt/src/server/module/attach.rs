@@ -0,0 +1,158 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Development endpoint to attach to the console of a module.
+//! `GET /modules/<name>/attach` with `Connection: Upgrade` and `Upgrade: tcp`
+//! is answered with `101 Switching Protocols`, after which the connection
+//! carries the module's stdio: what the client writes goes to the module's
+//! stdin, and what the module writes to stdout and stderr is sent back.
+
+use edgelet_core::ModuleConsole;
+use edgelet_http::route::{Handler, Parameters};
+use futures::{future, Future};
+use http::header::{HeaderMap, CONNECTION, UPGRADE};
+use http::{Method, Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use tokio;
+use tokio::io::{self, AsyncRead, AsyncWrite};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+const ATTACH_PATH_PREFIX: &str = "/modules/";
+const ATTACH_PATH_SUFFIX: &str = "/attach";
+const ATTACH_PROTOCOL: &str = "tcp";
+
+/// The name of the module `path` attaches to, if it is an attach path.
+pub fn attach_module_name(path: &str) -> Option<&str> {
+    if path.len() > ATTACH_PATH_PREFIX.len() + ATTACH_PATH_SUFFIX.len()
+        && path.starts_with(ATTACH_PATH_PREFIX)
+        && path.ends_with(ATTACH_PATH_SUFFIX)
+    {
+        let name = &path[ATTACH_PATH_PREFIX.len()..path.len() - ATTACH_PATH_SUFFIX.len()];
+        if !name.contains('/') {
+            return Some(name);
+        }
+    }
+    None
+}
+
+pub struct AttachModule<M> {
+    runtime: M,
+}
+
+impl<M> AttachModule<M> {
+    pub fn new(runtime: M) -> Self {
+        AttachModule { runtime }
+    }
+}
+
+impl<M> Handler<Parameters> for AttachModule<M>
+where
+    M: 'static + ModuleConsole + Send,
+    M::Error: IntoResponse,
+    M::Connection: 'static,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        if *req.method() != Method::GET {
+            return Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(Body::default())
+                    .expect("response builder failure"),
+            ));
+        }
+
+        let name = match params.name("name") {
+            Some(name) if is_upgrade(req.headers()) => name.to_string(),
+            _ => return Box::new(future::ok(Error::from(ErrorKind::BadParam).into_response())),
+        };
+
+        let response = self
+            .runtime
+            .attach(&name)
+            .map(move |console| {
+                // The client's end of the connection is only available once
+                // the upgrade has been sent back to it.
+                let session = req
+                    .into_body()
+                    .on_upgrade()
+                    .map_err(move |err| {
+                        warn!("Could not attach to module {}: {}", name, err);
+                    }).and_then(|client| pipe(client, console));
+                tokio::spawn(session);
+
+                Response::builder()
+                    .status(StatusCode::SWITCHING_PROTOCOLS)
+                    .header(CONNECTION, "Upgrade")
+                    .header(UPGRADE, ATTACH_PROTOCOL)
+                    .body(Body::empty())
+                    .unwrap_or_else(|e| e.into_response())
+            }).or_else(|e| future::ok(e.into_response()));
+        Box::new(response)
+    }
+}
+
+fn is_upgrade(headers: &HeaderMap) -> bool {
+    let connection = headers
+        .get(CONNECTION)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| {
+            value
+                .split(',')
+                .any(|option| option.trim().eq_ignore_ascii_case("upgrade"))
+        });
+    let upgrade = headers
+        .get(UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.trim().eq_ignore_ascii_case(ATTACH_PROTOCOL));
+    connection && upgrade
+}
+
+// Copies between the client and the module until either end closes.
+fn pipe<C, M>(client: C, console: M) -> impl Future<Item = (), Error = ()> + Send
+where
+    C: 'static + AsyncRead + AsyncWrite + Send,
+    M: 'static + AsyncRead + AsyncWrite + Send,
+{
+    let (client_reader, client_writer) = client.split();
+    let (console_reader, console_writer) = console.split();
+    let input = io::copy(client_reader, console_writer).map(|_| ());
+    let output = io::copy(console_reader, client_writer).map(|_| ());
+    input
+        .select(output)
+        .map(|_| debug!("Detached from module"))
+        .map_err(|(err, _)| debug!("Detached from module: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn attach_paths_name_a_module() {
+        assert_eq!(Some("sensor"), attach_module_name("/modules/sensor/attach"));
+        assert_eq!(None, attach_module_name("/modules//attach"));
+        assert_eq!(None, attach_module_name("/modules/sensor/logs"));
+        assert_eq!(None, attach_module_name("/modules/a/b/attach"));
+        assert_eq!(None, attach_module_name("/modules/attach"));
+    }
+
+    #[test]
+    fn only_tcp_upgrades_attach() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_upgrade(&headers));
+
+        headers.insert(CONNECTION, HeaderValue::from_static("keep-alive, Upgrade"));
+        headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+        assert!(!is_upgrade(&headers));
+
+        headers.insert(UPGRADE, HeaderValue::from_static("TCP"));
+        assert!(is_upgrade(&headers));
+    }
+}
@@ -1,6 +1,8 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::{Module, ModuleRegistry, ModuleRuntime, ModuleStatus};
+use edgelet_core::{
+    DeploymentHistory, Module, ModuleRegistry, ModuleRuntime, ModuleStatus, DEFAULT_HISTORY_SIZE,
+};
 use edgelet_http::route::{Handler, Parameters};
 use failure::ResultExt;
 use futures::{future, Future, Stream};
@@ -12,7 +14,7 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json;
 
-use super::{spec_to_core, spec_to_details};
+use super::{record_applied, spec_to_core, spec_to_details};
 use error::{Error, ErrorKind};
 use IntoResponse;
 
@@ -22,6 +24,7 @@ where
     <M::Module as Module>::Config: DeserializeOwned + Serialize,
 {
     runtime: M,
+    history: DeploymentHistory<ModuleSpec>,
 }
 
 impl<M> CreateModule<M>
@@ -30,7 +33,15 @@ where
     <M::Module as Module>::Config: DeserializeOwned + Serialize,
 {
     pub fn new(runtime: M) -> Self {
-        CreateModule { runtime }
+        CreateModule {
+            runtime,
+            history: DeploymentHistory::new(DEFAULT_HISTORY_SIZE),
+        }
+    }
+
+    pub fn with_history(mut self, history: DeploymentHistory<ModuleSpec>) -> Self {
+        self.history = history;
+        self
     }
 }
 
@@ -47,6 +58,7 @@ where
         _params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let runtime = self.runtime.clone();
+        let history = self.history.clone();
         let response = req
             .into_body()
             .concat2()
@@ -68,6 +80,7 @@ where
                                 runtime
                                     .create(core_spec)
                                     .map(move |_| {
+                                        record_applied(&history, &spec);
                                         let details = spec_to_details(&spec, ModuleStatus::Stopped);
                                         match serde_json::to_string(&details)
                                             .context(ErrorKind::Serde)
@@ -95,6 +108,8 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use chrono::prelude::*;
     use edgelet_core::{ModuleRuntimeState, ModuleStatus};
     use edgelet_http::route::Parameters;
@@ -237,4 +252,57 @@ mod tests {
             }).wait()
             .unwrap();
     }
+
+    #[test]
+    fn bad_priority() {
+        let handler = CreateModule::new(RUNTIME.clone());
+        let config = Config::new(json!({"image":"microsoft/test-image"}));
+        let spec = ModuleSpec::new("test-module".to_string(), "docker".to_string(), config)
+            .with_priority("urgent".to_string());
+        let request = Request::post("http://localhost/modules")
+            .body(serde_json::to_string(&spec).unwrap().into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn bad_label() {
+        let handler = CreateModule::new(RUNTIME.clone());
+        let config = Config::new(json!({"image":"microsoft/test-image"}));
+        let mut labels = HashMap::new();
+        labels.insert("tier".to_string(), "web,db".to_string());
+        let spec = ModuleSpec::new("test-module".to_string(), "docker".to_string(), config)
+            .with_labels(labels);
+        let request = Request::post("http://localhost/modules")
+            .body(serde_json::to_string(&spec).unwrap().into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn bad_restart_policy() {
+        let handler = CreateModule::new(RUNTIME.clone());
+        let config = Config::new(json!({"image":"microsoft/test-image"}));
+        let spec = ModuleSpec::new("test-module".to_string(), "docker".to_string(), config)
+            .with_restart_policy("sometimes".to_string());
+        let request = Request::post("http://localhost/modules")
+            .body(serde_json::to_string(&spec).unwrap().into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
 }
@@ -1,76 +1,230 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::ModuleRuntime;
+use edgelet_core::{
+    CertificateRegistry, DeploymentHistory, IdentityManager, IdentitySpec, KeySlots, ModuleRuntime,
+    DEFAULT_HISTORY_SIZE,
+};
 use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
 use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{Request, Response, StatusCode};
 use hyper::{Body, Error as HyperError};
+use management::models::{ModuleRemoval, ModuleSpec};
+use serde_json;
+use url::form_urlencoded::parse as parse_query;
 
+use super::record_removed;
 use error::{Error, ErrorKind};
 use IntoResponse;
 
-pub struct DeleteModule<M>
+/// Serves `DELETE /modules/{name}`, which removes a module along with what
+/// the query asks for. The identity and the volumes of the module are kept
+/// unless `identity=remove` or `volumes=remove` is asked for, since they are
+/// what a module that is deployed again picks up from. Its certificates are
+/// destroyed in the HSM and revoked unless `keys=retain` or `certs=retain` is
+/// asked for.
+///
+/// Cleanup is best effort once the module is gone. What couldn't be cleaned
+/// up is logged and left out of the summary in the response.
+pub struct DeleteModule<M, I>
 where
     M: 'static + ModuleRuntime,
     <M as ModuleRuntime>::Error: IntoResponse,
+    I: 'static + IdentityManager,
 {
     runtime: M,
+    identity: I,
+    history: DeploymentHistory<ModuleSpec>,
+    registry: CertificateRegistry,
+    slots: KeySlots,
 }
 
-impl<M> DeleteModule<M>
+impl<M, I> DeleteModule<M, I>
 where
     M: 'static + ModuleRuntime,
     <M as ModuleRuntime>::Error: IntoResponse,
+    I: 'static + IdentityManager,
 {
-    pub fn new(runtime: M) -> Self {
-        DeleteModule { runtime }
+    pub fn new(runtime: M, identity: I) -> Self {
+        DeleteModule {
+            runtime,
+            identity,
+            history: DeploymentHistory::new(DEFAULT_HISTORY_SIZE),
+            registry: CertificateRegistry::new(),
+            slots: KeySlots::new(),
+        }
+    }
+
+    pub fn with_history(mut self, history: DeploymentHistory<ModuleSpec>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Revokes the certificates of removed modules in `registry` and frees
+    /// their HSM key slots in `slots`.
+    pub fn with_certificates(mut self, registry: CertificateRegistry, slots: KeySlots) -> Self {
+        self.registry = registry;
+        self.slots = slots;
+        self
     }
 }
 
-impl<M> Handler<Parameters> for DeleteModule<M>
+impl<M, I> Handler<Parameters> for DeleteModule<M, I>
 where
     M: 'static + ModuleRuntime + Send,
     <M as ModuleRuntime>::Error: IntoResponse,
+    I: 'static + IdentityManager + Clone + Send,
 {
     fn handle(
         &self,
-        _req: Request<Body>,
+        req: Request<Body>,
         params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
-        let response = match params.name("name") {
-            Some(name) => {
-                let result = self
-                    .runtime
-                    .remove(name)
-                    .map(|_| {
-                        Response::builder()
-                            .status(StatusCode::NO_CONTENT)
-                            .body(Body::default())
-                            .unwrap_or_else(|e| e.into_response())
-                    }).or_else(|e| future::ok(e.into_response()));
-                future::Either::A(result)
-            }
+        let (name, cleanup) = match params
+            .name("name")
+            .ok_or_else(|| Error::from(ErrorKind::BadParam))
+            .and_then(|name| Ok((name.to_string(), Cleanup::from_query(req.uri().query())?)))
+        {
+            Ok(removal) => removal,
+            Err(err) => return Box::new(future::ok(err.into_response())),
+        };
 
-            None => future::Either::B(future::ok(Error::from(ErrorKind::BadParam).into_response())),
+        let removed = if cleanup.volumes {
+            self.runtime.remove_with_volumes(&name)
+        } else {
+            self.runtime.remove(&name)
         };
 
+        let history = self.history.clone();
+        let registry = self.registry.clone();
+        let slots = self.slots.clone();
+        let mut identity = self.identity.clone();
+        let response = removed
+            .and_then(move |_| {
+                record_removed(&history, &name);
+                let keys = if cleanup.keys {
+                    slots.release_module(&name)
+                } else {
+                    Vec::new()
+                };
+                let certificates = if cleanup.certs {
+                    revoke_certificates(&registry, &name)
+                } else {
+                    Vec::new()
+                };
+
+                // The certificates are cleaned up first, since deleting the
+                // identity retires the certificates of its generation too.
+                let deleted = if cleanup.identity {
+                    let deleted = identity.delete(IdentitySpec::new(&name)).then(
+                        move |result| -> Result<bool, M::Error> {
+                            if let Err(err) = result {
+                                warn!("Could not delete the identity of module {}: {}", name, err);
+                                Ok(false)
+                            } else {
+                                Ok(true)
+                            }
+                        },
+                    );
+                    future::Either::A(deleted)
+                } else {
+                    future::Either::B(future::ok(false))
+                };
+                deleted.map(move |identity_deleted| {
+                    ModuleRemoval::new(cleanup.volumes, identity_deleted, keys, certificates)
+                })
+            }).then(|result| -> Result<Response<Body>, HyperError> {
+                let response = result
+                    .map_err(IntoResponse::into_response)
+                    .and_then(|removal| {
+                        serde_json::to_string(&removal)
+                            .context(ErrorKind::Serde)
+                            .map_err(|err| Error::from(err).into_response())
+                    }).and_then(|b| {
+                        Response::builder()
+                            .status(StatusCode::OK)
+                            .header(CONTENT_TYPE, "application/json")
+                            .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                            .body(b.into())
+                            .map_err(IntoResponse::into_response)
+                    });
+                Ok(response.unwrap_or_else(|response| response))
+            });
+
         Box::new(response)
     }
 }
 
+/// What is removed along with a module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cleanup {
+    volumes: bool,
+    identity: bool,
+    keys: bool,
+    certs: bool,
+}
+
+impl Cleanup {
+    fn from_query(query: Option<&str>) -> Result<Self, Error> {
+        let query = query.unwrap_or("");
+        let param = |name: &str, default: bool| {
+            let value = parse_query(query.as_bytes())
+                .find(|&(ref key, _)| key == name)
+                .map(|(_, value)| value.into_owned());
+            match value.as_ref().map(String::as_str) {
+                None => Ok(default),
+                Some("remove") => Ok(true),
+                Some("retain") => Ok(false),
+                Some(_) => Err(Error::from(ErrorKind::BadParam)),
+            }
+        };
+
+        Ok(Cleanup {
+            volumes: param("volumes", false)?,
+            identity: param("identity", false)?,
+            keys: param("keys", true)?,
+            certs: param("certs", true)?,
+        })
+    }
+}
+
+// Revokes the certificates issued to `module` that haven't been revoked yet.
+// Returns their serial numbers.
+fn revoke_certificates(registry: &CertificateRegistry, module: &str) -> Vec<String> {
+    registry
+        .list()
+        .into_iter()
+        .filter(|cert| cert.module_id() == module && cert.revoked_at().is_none())
+        .filter_map(|cert| match registry.revoke(cert.serial_number()) {
+            Ok(_) => Some(cert.serial_number().to_string()),
+            Err(err) => {
+                warn!(
+                    "Could not revoke certificate {} of module {}: {}",
+                    cert.serial_number(),
+                    module,
+                    err
+                );
+                None
+            }
+        }).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::prelude::*;
-    use edgelet_core::{ModuleRuntimeState, ModuleStatus};
+    use chrono::Duration;
+    use edgelet_core::{AuthType, ModuleRuntimeState, ModuleStatus};
     use edgelet_http::route::Parameters;
+    use edgelet_test_utils::cert::TEST_CERT_PEM;
+    use edgelet_test_utils::identity::{TestIdentity, TestIdentityManager};
     use edgelet_test_utils::module::*;
+    use futures::Stream;
     use server::module::tests::Error;
 
     use super::*;
 
-    #[test]
-    fn success() {
-        // arrange
+    fn runtime() -> TestRuntime<Error> {
         let state = ModuleRuntimeState::default()
             .with_status(ModuleStatus::Running)
             .with_exit_code(Some(0))
@@ -81,36 +235,134 @@ mod tests {
         let config = TestConfig::new("microsoft/test-image".to_string());
         let module: TestModule<Error> =
             TestModule::new("test-module".to_string(), config, Ok(state));
-        let runtime = TestRuntime::new(Ok(module));
-        let handler = DeleteModule::new(runtime);
+        TestRuntime::new(Ok(module))
+    }
+
+    fn identity() -> TestIdentityManager {
+        TestIdentityManager::new(vec![TestIdentity::new(
+            "test",
+            "iotedge",
+            "1",
+            AuthType::Sas,
+        )])
+    }
+
+    fn delete(
+        handler: &DeleteModule<TestRuntime<Error>, TestIdentityManager>,
+        query: &str,
+    ) -> Response<Body> {
         let parameters =
             Parameters::with_captures(vec![(Some("name".to_string()), "test".to_string())]);
-        let request = Request::delete("http://localhost/modules/test")
+        let request = Request::delete(format!("http://localhost/modules/test{}", query))
             .body(Body::default())
             .unwrap();
+        handler.handle(request, parameters).wait().unwrap()
+    }
+
+    fn removal(response: Response<Body>) -> ModuleRemoval {
+        let body = response.into_body().concat2().wait().unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn success() {
+        // arrange
+        let handler = DeleteModule::new(runtime(), identity());
 
         // act
-        let response = handler.handle(request, parameters).wait().unwrap();
+        let response = delete(&handler, "");
 
         // assert
-        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        assert_eq!(StatusCode::OK, response.status());
+        let removal = removal(response);
+        assert!(!removal.volumes());
+        assert!(!removal.identity());
+        assert!(removal.keys().is_empty());
+        assert!(removal.certificates().is_empty());
+    }
+
+    #[test]
+    fn certificates_are_cleaned_up_by_default() {
+        // arrange
+        let registry = CertificateRegistry::new();
+        registry
+            .record(
+                "test",
+                "testidentity",
+                TEST_CERT_PEM.as_bytes(),
+                Utc::now() + Duration::hours(1),
+            ).unwrap();
+        let slots = KeySlots::new();
+        slots.occupy("test", "testidentity", Utc::now() + Duration::hours(1));
+        slots.occupy("other", "otheridentity", Utc::now() + Duration::hours(1));
+        let handler = DeleteModule::new(runtime(), identity())
+            .with_certificates(registry.clone(), slots.clone());
+
+        // act
+        let removal = removal(delete(&handler, ""));
+
+        // assert
+        assert_eq!(&vec!["testidentity".to_string()], removal.keys());
+        assert_eq!(1, removal.certificates().len());
+        assert!(registry.list()[0].revoked_at().is_some());
+        assert_eq!(1, slots.usage().len());
+        assert_eq!("other", slots.usage()[0].module_id());
+    }
+
+    #[test]
+    fn query_chooses_what_is_removed() {
+        // arrange
+        let registry = CertificateRegistry::new();
+        registry
+            .record(
+                "test",
+                "testidentity",
+                TEST_CERT_PEM.as_bytes(),
+                Utc::now() + Duration::hours(1),
+            ).unwrap();
+        let slots = KeySlots::new();
+        slots.occupy("test", "testidentity", Utc::now() + Duration::hours(1));
+        let handler = DeleteModule::new(runtime(), identity())
+            .with_certificates(registry.clone(), slots.clone());
+
+        // act
+        let response = delete(
+            &handler,
+            "?volumes=remove&identity=remove&keys=retain&certs=retain",
+        );
+
+        // assert
+        let removal = removal(response);
+        assert!(removal.volumes());
+        assert!(removal.identity());
+        assert!(removal.keys().is_empty());
+        assert!(removal.certificates().is_empty());
+        assert!(registry.list()[0].revoked_at().is_none());
+        assert_eq!(1, slots.usage().len());
+    }
+
+    #[test]
+    fn missing_identity_is_left_out_of_the_summary() {
+        let handler = DeleteModule::new(runtime(), TestIdentityManager::new(vec![]));
+
+        let removal = removal(delete(&handler, "?identity=remove"));
+
+        assert!(!removal.identity());
+    }
+
+    #[test]
+    fn bad_cleanup_option() {
+        let handler = DeleteModule::new(runtime(), identity());
+
+        let response = delete(&handler, "?volumes=keep");
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
     }
 
     #[test]
     fn delete_bad_params() {
         // arrange
-        let state = ModuleRuntimeState::default()
-            .with_status(ModuleStatus::Running)
-            .with_exit_code(Some(0))
-            .with_status_description(Some("description".to_string()))
-            .with_started_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(14, 20, 0, 1)))
-            .with_finished_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(15, 20, 0, 1)))
-            .with_image_id(Some("image-id".to_string()));
-        let config = TestConfig::new("microsoft/test-image".to_string());
-        let module: TestModule<Error> =
-            TestModule::new("test-module".to_string(), config, Ok(state));
-        let runtime = TestRuntime::new(Ok(module));
-        let handler = DeleteModule::new(runtime);
+        let handler = DeleteModule::new(runtime(), identity());
         let request = Request::delete("http://localhost/modules/test")
             .body(Body::default())
             .unwrap();
@@ -1,17 +1,18 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::{Module, ModuleRuntime};
+use edgelet_core::{DeploymentHistory, LabelSelector, Module, ModuleRuntime, DEFAULT_HISTORY_SIZE};
 use edgelet_http::route::{Handler, Parameters};
 use failure::ResultExt;
-use futures::{Future, Stream};
+use futures::{future, Future, Stream};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{Request, Response, StatusCode};
 use hyper::{Body, Error as HyperError};
 use management::models::*;
 use serde::Serialize;
 use serde_json;
+use url::form_urlencoded;
 
-use super::core_to_details;
+use super::{applied_version, core_to_details};
 use error::{Error, ErrorKind};
 use IntoResponse;
 
@@ -21,6 +22,7 @@ where
     <M::Module as Module>::Config: Serialize,
 {
     runtime: M,
+    history: DeploymentHistory<ModuleSpec>,
 }
 
 impl<M> ListModules<M>
@@ -29,7 +31,15 @@ where
     <M::Module as Module>::Config: Serialize,
 {
     pub fn new(runtime: M) -> Self {
-        ListModules { runtime }
+        ListModules {
+            runtime,
+            history: DeploymentHistory::new(DEFAULT_HISTORY_SIZE),
+        }
+    }
+
+    pub fn with_history(mut self, history: DeploymentHistory<ModuleSpec>) -> Self {
+        self.history = history;
+        self
     }
 }
 
@@ -40,19 +50,34 @@ where
 {
     fn handle(
         &self,
-        _req: Request<Body>,
+        req: Request<Body>,
         _params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         debug!("List modules");
+        let selector = match req.uri().query().map_or_else(
+            || Ok(LabelSelector::default()),
+            parse_label_selector,
+        ) {
+            Ok(selector) => selector,
+            Err(err) => return Box::new(future::ok(err.into_response())),
+        };
+        let history = self.history.clone();
         let response = self
             .runtime
             .list_with_details()
             .collect()
-            .then(|result| {
+            .then(move |result| {
                 let details: Result<_, Error> = result
                     .context(ErrorKind::ModuleRuntime)?
                     .into_iter()
-                    .map(|(module, state)| core_to_details(&module, &state))
+                    .filter(|&(ref module, _)| selector.matches(&module.labels()))
+                    .map(|(module, state)| {
+                        let details = core_to_details(&module, &state)?;
+                        Ok(match applied_version(&history, module.name()) {
+                            Some(version) => details.with_deployment(version),
+                            None => details,
+                        })
+                    })
                     .collect();
                 let body = ModuleList::new(details?);
                 let b = serde_json::to_string(&body).context(ErrorKind::Serde)?;
@@ -66,14 +91,28 @@ where
     }
 }
 
+// An empty selector, which generated clients send when they are not asked
+// for one, lists every module.
+fn parse_label_selector(query: &str) -> Result<LabelSelector, Error> {
+    let selector = form_urlencoded::parse(query.as_bytes())
+        .find(|&(ref key, _)| key == "labelSelector")
+        .map_or_else(
+            || Ok(LabelSelector::default()),
+            |(_, selector)| selector.parse(),
+        ).context(ErrorKind::BadParam)?;
+    Ok(selector)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use chrono::prelude::*;
-    use edgelet_core::{ModuleRuntimeState, ModuleStatus};
+    use edgelet_core::{ExitReason, ModuleRuntimeState, ModuleStatus};
     use edgelet_http::route::Parameters;
     use edgelet_test_utils::module::*;
     use futures::Stream;
-    use management::models::ModuleList;
+    use management::models::{Config, ModuleDetails, ModuleList, ModuleSpec};
     use server::module::tests::Error;
 
     use super::*;
@@ -135,6 +174,140 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn exit_reason_is_listed() {
+        // arrange
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Failed)
+            .with_exit_code(Some(137))
+            .with_exit_reason(Some(ExitReason::OomKilled))
+            .with_finished_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(15, 20, 0, 1)));
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module: TestModule<Error> =
+            TestModule::new("test-module".to_string(), config, Ok(state));
+        let runtime = TestRuntime::new(Ok(module));
+        let handler = ListModules::new(runtime);
+        let request = Request::get("http://localhost/modules")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let list: ModuleList = serde_json::from_slice(&b).unwrap();
+                let exit_status = list.modules()[0].status().exit_status().unwrap();
+                assert_eq!("137", exit_status.status_code());
+                assert_eq!(Some("oomKilled"), exit_status.reason());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn restart_policy_drift_is_listed() {
+        // arrange
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Running)
+            .with_restart_policy_drift(Some("the container has always".to_string()));
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module: TestModule<Error> =
+            TestModule::new("test-module".to_string(), config, Ok(state));
+        let runtime = TestRuntime::new(Ok(module));
+        let handler = ListModules::new(runtime);
+        let request = Request::get("http://localhost/modules")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let list: ModuleList = serde_json::from_slice(&b).unwrap();
+                assert_eq!(
+                    Some("the container has always"),
+                    list.modules()[0].status().restart_policy_drift()
+                );
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+
+    fn labeled_runtime() -> TestRuntime<Error> {
+        let mut labels = HashMap::new();
+        labels.insert("tier".to_string(), "web".to_string());
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module: TestModule<Error> = TestModule::new(
+            "test-module".to_string(),
+            config,
+            Ok(ModuleRuntimeState::default()),
+        ).with_labels(labels);
+        TestRuntime::new(Ok(module))
+    }
+
+    fn list(uri: &str) -> Response<Body> {
+        let handler = ListModules::new(labeled_runtime());
+        let request = Request::get(uri).body(Body::default()).unwrap();
+        handler.handle(request, Parameters::new()).wait().unwrap()
+    }
+
+    fn listed(response: Response<Body>) -> Vec<ModuleDetails> {
+        let body = response.into_body().concat2().wait().unwrap();
+        let list: ModuleList = serde_json::from_slice(&body).unwrap();
+        list.modules().to_vec()
+    }
+
+    #[test]
+    fn labels_are_listed() {
+        let modules = listed(list("http://localhost/modules"));
+        assert_eq!(1, modules.len());
+        assert_eq!(Some("web"), modules[0].labels().unwrap().get("tier").map(AsRef::as_ref));
+    }
+
+    #[test]
+    fn modules_are_selected_by_labels() {
+        let modules = listed(list("http://localhost/modules?labelSelector=tier%3Dweb"));
+        assert_eq!(1, modules.len());
+
+        let modules = listed(list("http://localhost/modules?labelSelector=tier%21%3Dweb"));
+        assert!(modules.is_empty());
+
+        let modules = listed(list("http://localhost/modules?labelSelector="));
+        assert_eq!(1, modules.len());
+    }
+
+    #[test]
+    fn deployment_is_listed() {
+        let history = DeploymentHistory::new(DEFAULT_HISTORY_SIZE);
+        let config = Config::new(json!({"image":"microsoft/test-image"}));
+        let spec = ModuleSpec::new("test-module".to_string(), "docker".to_string(), config)
+            .with_deployment("abc123".to_string());
+        history.module_applied("test-module", spec).unwrap();
+        let handler = ListModules::new(labeled_runtime()).with_history(history);
+        let request = Request::get("http://localhost/modules")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        let modules = listed(response);
+        assert_eq!(Some("abc123"), modules[0].deployment());
+    }
+
+    #[test]
+    fn bad_label_selector() {
+        let response = list("http://localhost/modules?labelSelector=tier%3Da%2Fb");
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
     #[test]
     fn list_failed() {
         // arrange
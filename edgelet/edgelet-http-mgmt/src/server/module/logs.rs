@@ -1,6 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use edgelet_core::{LogOptions, LogTail, ModuleRuntime};
+use edgelet_http::log_filter::LogFilter;
 use edgelet_http::route::{Handler, Parameters};
 use failure::ResultExt;
 use futures::{future, Future};
@@ -77,7 +78,32 @@ fn parse_options(query: &str) -> Result<LogOptions, Error> {
         .iter()
         .find(|&(ref key, _)| key == "follow")
         .map_or_else(|| Ok(false), |(_, val)| val.parse::<bool>())?;
-    let options = LogOptions::new().with_follow(follow).with_tail(tail);
+    let since = parse
+        .iter()
+        .find(|&(ref key, _)| key == "since")
+        .map_or_else(|| Ok(0), |(_, val)| val.parse::<i32>())?;
+    // A limit of 0 and an empty pattern, which generated clients send when
+    // they are not asked for, filter nothing.
+    let limit_bytes = parse
+        .iter()
+        .find(|&(ref key, _)| key == "limit_bytes")
+        .map_or_else(|| Ok(0), |(_, val)| val.parse::<u64>())?;
+    let grep = parse
+        .iter()
+        .find(|&(ref key, _)| key == "grep")
+        .map(|(_, val)| val.to_string())
+        .filter(|grep| !grep.is_empty());
+    let mut options = LogOptions::new()
+        .with_follow(follow)
+        .with_tail(tail)
+        .with_since(since);
+    if limit_bytes > 0 {
+        options = options.with_limit_bytes(limit_bytes);
+    }
+    if let Some(grep) = grep {
+        options = options.with_grep(grep);
+    }
+    LogFilter::from_options(&options).context(ErrorKind::BadParam)?;
     Ok(options)
 }
 
@@ -95,10 +121,11 @@ mod tests {
 
     #[test]
     fn correct_logoptions() {
-        let query = "follow=true&tail=6";
+        let query = "follow=true&tail=6&since=1539600000";
         let options = parse_options(&query).unwrap();
         assert_eq!(LogTail::Num(6), *options.tail());
         assert_eq!(true, options.follow());
+        assert_eq!(1_539_600_000, options.since());
     }
 
     #[test]
@@ -107,6 +134,35 @@ mod tests {
         let options = parse_options(&query).unwrap();
         assert_eq!(LogTail::default(), *options.tail());
         assert_eq!(false, options.follow());
+        assert_eq!(0, options.since());
+    }
+
+    #[test]
+    fn logoption_filter() {
+        let query = "limit_bytes=1024&grep=%5Berror%5D";
+        let options = parse_options(&query).unwrap();
+        assert_eq!(Some(1024), options.limit_bytes());
+        assert_eq!(Some("[error]"), options.grep());
+
+        let options = parse_options("limit_bytes=0&grep=").unwrap();
+        assert_eq!(None, options.limit_bytes());
+        assert_eq!(None, options.grep());
+    }
+
+    #[test]
+    fn logoption_grep_error() {
+        let query = "grep=error(";
+        let options = parse_options(&query);
+        assert!(options.is_err());
+        assert_eq!("Bad parameter", options.err().unwrap().to_string());
+    }
+
+    #[test]
+    fn logoption_since_error() {
+        let query = "since=yesterday";
+        let options = parse_options(&query);
+        assert!(options.is_err());
+        assert_eq!("Parse error", options.err().unwrap().to_string());
     }
 
     #[test]
@@ -1,11 +1,14 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
 
 use edgelet_core::{
-    Module, ModuleRuntime, ModuleRuntimeState, ModuleSpec as CoreModuleSpec, ModuleStatus,
+    validate_label, DeploymentHistory, Module, ModulePriority, ModuleRuntime, ModuleRuntimeState,
+    ModuleSpec as CoreModuleSpec, ModuleStatus, Namespaces, RestartPolicy,
 };
 use edgelet_docker::{Error as DockerError, ErrorKind as DockerErrorKind};
+use edgelet_http::category_status_code;
 use failure::{Fail, ResultExt};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{Response, StatusCode};
@@ -18,25 +21,33 @@ use serde_json;
 use error::{Error, ErrorKind};
 use IntoResponse;
 
+mod attach;
 mod create;
 mod delete;
 mod get;
 mod list;
 mod logs;
+mod pull;
 mod restart;
 mod start;
 mod stop;
 mod update;
+mod upload;
+mod usage;
 
+pub use self::attach::{attach_module_name, AttachModule};
 pub use self::create::CreateModule;
 pub use self::delete::DeleteModule;
 pub use self::get::GetModule;
 pub use self::list::ListModules;
 pub use self::logs::ModuleLogs;
+pub use self::pull::PullImage;
 pub use self::restart::RestartModule;
 pub use self::start::StartModule;
 pub use self::stop::StopModule;
 pub use self::update::UpdateModule;
+pub use self::upload::UploadModuleLogs;
+pub use self::usage::GetModuleUsage;
 
 impl IntoResponse for DockerError {
     fn into_response(self) -> Response<Body> {
@@ -51,15 +62,21 @@ impl IntoResponse for DockerError {
             DockerErrorKind::NotFound(_) => StatusCode::NOT_FOUND,
             DockerErrorKind::Conflict => StatusCode::CONFLICT,
             DockerErrorKind::NotModified => StatusCode::NOT_MODIFIED,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+            DockerErrorKind::ImageArchitectureMismatch(_, _) => StatusCode::BAD_REQUEST,
+            DockerErrorKind::IsolationNotSupported(_, _) => StatusCode::BAD_REQUEST,
+            // The credentials come with the deployment, so it's the deployment
+            // that has to change.
+            DockerErrorKind::RegistryAuthentication(_, _) => StatusCode::BAD_REQUEST,
+            _ => category_status_code(self.category()),
         };
 
         // Per the RFC, status code NotModified should not have a body
         let body = if status_code == StatusCode::NOT_MODIFIED {
             None
         } else {
-            let b = serde_json::to_string(&ErrorResponse::new(message))
-                .expect("serialization of ErrorResponse failed.");
+            let response = ErrorResponse::new(message).with_category(self.category().to_string());
+            let b =
+                serde_json::to_string(&response).expect("serialization of ErrorResponse failed.");
             Some(b)
         };
 
@@ -99,20 +116,33 @@ where
     }
     if let Some(code) = state.exit_code() {
         if let Some(finished_at) = state.finished_at() {
-            status.set_exit_status(ExitStatus::new(finished_at.to_rfc3339(), code.to_string()));
+            let mut exit_status = ExitStatus::new(finished_at.to_rfc3339(), code.to_string());
+            if let Some(reason) = state.exit_reason() {
+                exit_status.set_reason(reason.to_string());
+            }
+            status.set_exit_status(exit_status);
         }
     }
+    if let Some(drift) = state.restart_policy_drift() {
+        status.set_restart_policy_drift(drift.to_string());
+    }
 
-    Ok(ModuleDetails::new(
+    let details = ModuleDetails::new(
         "id".to_string(),
         module.name().to_string(),
         module.type_().to_string(),
         config,
         status,
-    ))
+    );
+    let labels = module.labels();
+    if labels.is_empty() {
+        Ok(details)
+    } else {
+        Ok(details.with_labels(labels))
+    }
 }
 
-fn spec_to_core<M>(
+pub(super) fn spec_to_core<M>(
     spec: &ModuleSpec,
 ) -> Result<CoreModuleSpec<<M::Module as Module>::Config>, Error>
 where
@@ -127,10 +157,69 @@ where
             .collect()
     });
     let config = serde_json::from_value(spec.config().settings().clone())?;
-    let module_spec = CoreModuleSpec::new(name, type_, config, env)?;
+    let priority = spec
+        .priority()
+        .map_or_else(|| Ok(ModulePriority::default()), ModulePriority::from_str)?;
+    let module_spec = CoreModuleSpec::new(name, type_, config, env)?.with_priority(priority);
+    let module_spec = match spec.namespace() {
+        Some(namespace) => {
+            Namespaces::validate(namespace)?;
+            module_spec.with_namespace(namespace.to_string())
+        }
+        None => module_spec,
+    };
+    let module_spec = match spec.restart_policy() {
+        Some(restart_policy) => {
+            module_spec.with_restart_policy(RestartPolicy::from_str(restart_policy)?)
+        }
+        None => module_spec,
+    };
+    let module_spec = match spec.labels() {
+        Some(labels) => {
+            for (key, value) in labels {
+                validate_label(key, value)?;
+            }
+            module_spec.with_labels(labels.clone())
+        }
+        None => module_spec,
+    };
     Ok(module_spec)
 }
 
+pub(super) fn record_applied(history: &DeploymentHistory<ModuleSpec>, spec: &ModuleSpec) {
+    if let Err(err) = history.module_applied(spec.name(), spec.clone()) {
+        warn!("Could not record module {} in deployment history: {}", spec.name(), err);
+    }
+}
+
+pub(super) fn record_removed(history: &DeploymentHistory<ModuleSpec>, name: &str) {
+    if let Err(err) = history.module_removed(name) {
+        warn!("Could not record removal of module {} in deployment history: {}", name, err);
+    }
+}
+
+/// The version of the deployment the config of module `name` came from, as
+/// recorded in the deployment history when it was applied.
+fn applied_version(history: &DeploymentHistory<ModuleSpec>, name: &str) -> Option<String> {
+    history
+        .current()
+        .get(name)
+        .and_then(|spec| spec.deployment().map(str::to_string))
+}
+
+/// The versions of the deployments the configs of the modules in the
+/// deployment history came from, in order and without duplicates. A device
+/// has converged to a deployment when it is the only one.
+pub(super) fn applied_versions(history: &DeploymentHistory<ModuleSpec>) -> Vec<String> {
+    history
+        .current()
+        .values()
+        .filter_map(|spec| spec.deployment().map(str::to_string))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
 fn spec_to_details(spec: &ModuleSpec, module_status: ModuleStatus) -> ModuleDetails {
     let id = spec.name().clone();
     let name = spec.name().clone();
@@ -148,12 +237,20 @@ fn spec_to_details(spec: &ModuleSpec, module_status: ModuleStatus) -> ModuleDeta
 
     let runtime_status = RuntimeStatus::new(module_status.to_string());
     let status = Status::new(runtime_status);
-    ModuleDetails::new(id, name, type_, config, status)
+    let details = ModuleDetails::new(id, name, type_, config, status);
+    let details = match spec.labels() {
+        Some(labels) => details.with_labels(labels.clone()),
+        None => details,
+    };
+    match spec.deployment() {
+        Some(deployment) => details.with_deployment(deployment.to_string()),
+        None => details,
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use edgelet_docker::{Error as DockerError, ErrorKind as DockerErrorKind};
+    use edgelet_docker::{Error as DockerError, ErrorKind as DockerErrorKind, RegistryAuthFailure};
     use futures::{Future, Stream};
     use http::{Response, StatusCode};
     use hyper::Body;
@@ -239,6 +336,7 @@ pub mod tests {
             .and_then(|b| {
                 let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
                 assert_eq!("Invalid URL", error.message());
+                assert_eq!(Some("internal"), error.category());
                 Ok(())
             }).wait()
             .unwrap();
@@ -266,4 +364,32 @@ pub mod tests {
             }).wait()
             .unwrap();
     }
+
+    #[test]
+    fn registry_authentication() {
+        // arrange
+        let error = DockerError::from(DockerErrorKind::RegistryAuthentication(
+            "r.azurecr.io/sensor:1.0".to_string(),
+            RegistryAuthFailure::ExpiredToken,
+        ));
+
+        // act
+        let response = error.into_response();
+
+        // assert
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!(
+                    "Could not authenticate to the registry of image r.azurecr.io/sensor:1.0: \
+                     the token has expired",
+                    error.message()
+                );
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
 }
@@ -0,0 +1,171 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{Module, ModuleRegistry, ModuleRuntime, Operations};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::Config;
+use serde::de::DeserializeOwned;
+use serde_json;
+use tokio;
+
+use error::{Error, ErrorKind};
+use server::operation::operation_response;
+use IntoResponse;
+
+/// Serves `POST /images/pull`, which pulls the image of a module in the
+/// background. The pull is answered with the operation that reports its
+/// progress, which is polled at `GET /operations/{id}`.
+///
+/// The registry doesn't report how much of an image has been pulled, so the
+/// operation only goes from started to finished.
+pub struct PullImage<M>
+where
+    M: 'static + ModuleRuntime,
+{
+    runtime: M,
+    operations: Operations,
+}
+
+impl<M> PullImage<M>
+where
+    M: 'static + ModuleRuntime,
+{
+    pub fn new(runtime: M, operations: Operations) -> Self {
+        PullImage {
+            runtime,
+            operations,
+        }
+    }
+}
+
+impl<M> Handler<Parameters> for PullImage<M>
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    <M::Module as Module>::Config: DeserializeOwned,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let runtime = self.runtime.clone();
+        let operations = self.operations.clone();
+        let response = req.into_body().concat2().map(move |b| {
+            let (image, config) = match read_config::<M>(&b) {
+                Ok(config) => config,
+                Err(err) => return err.into_response(),
+            };
+
+            let operation = operations.start("imagePull");
+            let step = format!("Pulling image {}", image);
+            info!("{}", step);
+            operation.progress(0, &step);
+
+            let pulling = operation.clone();
+            tokio::spawn(runtime.registry().pull(&config).then(move |result| {
+                match result {
+                    Ok(_) => {
+                        info!("Pulled image {}", image);
+                        pulling.succeed();
+                    }
+                    Err(err) => {
+                        warn!("Could not pull image {}: {}", image, err);
+                        pulling.fail(err.to_string());
+                    }
+                }
+                Ok::<_, ()>(())
+            }));
+
+            operations
+                .get(operation.id())
+                .ok_or_else(|| Error::from(ErrorKind::NotFound))
+                .and_then(|started| operation_response(&started, StatusCode::ACCEPTED))
+                .unwrap_or_else(|e| e.into_response())
+        });
+        Box::new(response)
+    }
+}
+
+// Returns the name of the image the config refers to, and the config.
+fn read_config<M>(body: &[u8]) -> Result<(String, M::Config), Error>
+where
+    M: 'static + ModuleRuntime,
+    <M::Module as Module>::Config: DeserializeOwned,
+{
+    let config: Config = serde_json::from_slice(body).context(ErrorKind::BadBody)?;
+    let image = config
+        .settings()
+        .get("image")
+        .and_then(|image| image.as_str())
+        .ok_or_else(|| Error::from(ErrorKind::BadBody))?
+        .to_string();
+    let config = serde_json::from_value(config.settings().clone()).context(ErrorKind::BadBody)?;
+    Ok((image, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{ModuleRuntimeState, OperationStatus};
+    use edgelet_test_utils::module::*;
+    use management::models::Operation;
+    use server::module::tests::Error;
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    fn runtime() -> TestRuntime<Error> {
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module = TestModule::new(
+            "test-module".to_string(),
+            config,
+            Ok(ModuleRuntimeState::default()),
+        );
+        TestRuntime::new(Ok(module))
+    }
+
+    // Pulls the image, and waits for the pull to finish in the background.
+    fn pull(handler: &PullImage<TestRuntime<Error>>, body: &str) -> Response<Body> {
+        let request = Request::post("http://localhost/images/pull")
+            .body(body.to_string().into())
+            .unwrap();
+        let mut runtime = Runtime::new().unwrap();
+        let response = runtime
+            .block_on(handler.handle(request, Parameters::new()))
+            .unwrap();
+        runtime.run().unwrap();
+        response
+    }
+
+    #[test]
+    fn image_is_pulled_in_the_background() {
+        let operations = Operations::new();
+        let handler = PullImage::new(runtime(), operations.clone());
+
+        let response = pull(&handler, r#"{"settings":{"image":"microsoft/test-image"}}"#);
+
+        assert_eq!(StatusCode::ACCEPTED, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let started: Operation = serde_json::from_slice(&body).unwrap();
+        assert_eq!("imagePull", started.kind());
+        assert_eq!("running", started.status());
+        assert_eq!(Some("Pulling image microsoft/test-image"), started.step());
+
+        let finished = operations.get(started.id()).unwrap();
+        assert_eq!(OperationStatus::Succeeded, finished.status());
+        assert_eq!(100, finished.percent());
+    }
+
+    #[test]
+    fn config_without_an_image_is_bad_request() {
+        let operations = Operations::new();
+        let handler = PullImage::new(runtime(), operations.clone());
+
+        let response = pull(&handler, r#"{"settings":{}}"#);
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert!(operations.list().is_empty());
+    }
+}
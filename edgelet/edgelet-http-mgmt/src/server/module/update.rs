@@ -1,6 +1,11 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::{Module, ModuleRegistry, ModuleRuntime, ModuleStatus};
+use std::time::Duration;
+
+use edgelet_core::{
+    DeploymentHistory, Module, ModuleRegistry, ModuleRuntime, ModuleSpec as CoreModuleSpec,
+    ModuleStatus, UpdateStrategy, DEFAULT_HISTORY_SIZE,
+};
 use edgelet_http::route::{Handler, Parameters};
 use failure::ResultExt;
 use futures::{future, Future, Stream};
@@ -13,16 +18,21 @@ use serde::Serialize;
 use serde_json;
 use url::form_urlencoded::parse as parse_query;
 
-use super::{spec_to_core, spec_to_details};
+use super::{record_applied, spec_to_core, spec_to_details};
 use error::{Error, ErrorKind};
 use IntoResponse;
 
+/// How long a canary has to keep running before it replaces the existing
+/// module, unless the request asks for a different soak period.
+const DEFAULT_CANARY_SOAK_SECS: u64 = 60;
+
 pub struct UpdateModule<M>
 where
     M: 'static + ModuleRuntime + Clone,
     <M::Module as Module>::Config: DeserializeOwned + Serialize,
 {
     runtime: M,
+    history: DeploymentHistory<ModuleSpec>,
 }
 
 impl<M> UpdateModule<M>
@@ -31,7 +41,15 @@ where
     <M::Module as Module>::Config: DeserializeOwned + Serialize,
 {
     pub fn new(runtime: M) -> Self {
-        UpdateModule { runtime }
+        UpdateModule {
+            runtime,
+            history: DeploymentHistory::new(DEFAULT_HISTORY_SIZE),
+        }
+    }
+
+    pub fn with_history(mut self, history: DeploymentHistory<ModuleSpec>) -> Self {
+        self.history = history;
+        self
     }
 }
 
@@ -48,6 +66,7 @@ where
         _params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let runtime = self.runtime.clone();
+        let history = self.history.clone();
         let start: bool = req
             .uri()
             .query()
@@ -57,6 +76,10 @@ where
                     .and_then(|(_, v)| if v == "false" { None } else { Some(()) })
                     .map(|_| true)
             }).unwrap_or_else(|| false);
+        let strategy = match update_strategy(&req, start) {
+            Ok(strategy) => strategy,
+            Err(err) => return Box::new(future::ok(err.into_response())),
+        };
 
         let response = req
             .into_body()
@@ -80,48 +103,25 @@ where
                             info!("Updating module {}", name);
                         }
 
-                        let created = runtime
-                            .remove(&name)
-                            .and_then(move |_| {
-                                debug!("Removed existing module {}", name);
-                                runtime
-                                    .registry()
-                                    .pull(core_spec.config())
-                                    .and_then(move |_| {
-                                        debug!("Successfully pulled new image for module {}", name);
-                                        runtime.create(core_spec).and_then(move |_| {
-                                            debug!("Created module {}", name);
-                                            if start {
-                                                info!("Starting module {}", name);
-                                                future::Either::A(
-                                                    runtime
-                                                        .start(&name)
-                                                        .map(|_| ModuleStatus::Running),
-                                                )
-                                            } else {
-                                                future::Either::B(future::ok(ModuleStatus::Stopped))
-                                            }.map(
-                                                move |status| {
-                                                    let details = spec_to_details(&spec, status);
-                                                    match serde_json::to_string(&details)
-                                                        .context(ErrorKind::Serde)
-                                                    {
-                                                        Ok(b) => Response::builder()
-                                                            .status(StatusCode::OK)
-                                                            .header(
-                                                                CONTENT_TYPE,
-                                                                "application/json",
-                                                            ).header(
-                                                                CONTENT_LENGTH,
-                                                                b.len().to_string().as_str(),
-                                                            ).body(b.into())
-                                                            .unwrap_or_else(|e| e.into_response()),
-                                                        Err(e) => e.into_response(),
-                                                    }
-                                                },
-                                            )
-                                        })
-                                    })
+                        let updated = match strategy {
+                            UpdateStrategy::Replace => {
+                                future::Either::A(replace_module(runtime, core_spec, start))
+                            }
+                            canary => future::Either::B(canary_module(runtime, core_spec, canary)),
+                        };
+                        let created = updated
+                            .map(move |status| {
+                                record_applied(&history, &spec);
+                                let details = spec_to_details(&spec, status);
+                                match serde_json::to_string(&details).context(ErrorKind::Serde) {
+                                    Ok(b) => Response::builder()
+                                        .status(StatusCode::OK)
+                                        .header(CONTENT_TYPE, "application/json")
+                                        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                                        .body(b.into())
+                                        .unwrap_or_else(|e| e.into_response()),
+                                    Err(e) => e.into_response(),
+                                }
                             }).or_else(|e| future::ok(e.into_response()));
                         future::Either::A(created)
                     }
@@ -132,6 +132,76 @@ where
     }
 }
 
+fn update_strategy(req: &Request<Body>, start: bool) -> Result<UpdateStrategy, Error> {
+    let query = req.uri().query().unwrap_or("");
+    let param = |name: &str| {
+        parse_query(query.as_bytes())
+            .find(|&(ref key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    match param("strategy").as_ref().map(String::as_str) {
+        None | Some("replace") => Ok(UpdateStrategy::Replace),
+        // a canary is judged by whether it keeps running, so it has to be
+        // started
+        Some("canary") if start => {
+            let soak = param("soak").map_or(Ok(DEFAULT_CANARY_SOAK_SECS), |soak| {
+                soak.parse::<u64>().context(ErrorKind::BadParam)
+            })?;
+            Ok(UpdateStrategy::Canary(Duration::from_secs(soak)))
+        }
+        Some(_) => Err(Error::from(ErrorKind::BadParam)),
+    }
+}
+
+fn replace_module<M>(
+    runtime: M,
+    spec: CoreModuleSpec<M::Config>,
+    start: bool,
+) -> impl Future<Item = ModuleStatus, Error = M::Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+{
+    let name = spec.name().to_string();
+    runtime.remove(&name).and_then(move |_| {
+        debug!("Removed existing module {}", name);
+        runtime.registry().pull(spec.config()).and_then(move |_| {
+            debug!("Successfully pulled new image for module {}", name);
+            runtime.create(spec).and_then(move |_| {
+                debug!("Created module {}", name);
+                if start {
+                    info!("Starting module {}", name);
+                    future::Either::A(runtime.start(&name).map(|_| ModuleStatus::Running))
+                } else {
+                    future::Either::B(future::ok(ModuleStatus::Stopped))
+                }
+            })
+        })
+    })
+}
+
+// The existing module keeps running while the image is pulled and the new
+// module soaks, and is only removed once the new module has proven itself.
+// This resolves as soon as the new module is running, and the runtime swaps
+// it in in the background.
+fn canary_module<M>(
+    runtime: M,
+    spec: CoreModuleSpec<M::Config>,
+    strategy: UpdateStrategy,
+) -> impl Future<Item = ModuleStatus, Error = M::Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+{
+    let name = spec.name().to_string();
+    runtime.registry().pull(spec.config()).and_then(move |_| {
+        debug!("Successfully pulled new image for module {}", name);
+        runtime.update(spec, strategy).map(move |_| {
+            info!("Started the updated module {} alongside the existing one", name);
+            ModuleStatus::Running
+        })
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::prelude::*;
@@ -234,6 +304,56 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn success_canary() {
+        let handler = UpdateModule::new(RUNTIME.clone());
+        let config = Config::new(json!({"image":"microsoft/test-image"}));
+        let spec = ModuleSpec::new("test-module".to_string(), "docker".to_string(), config);
+        let request =
+            Request::put("http://localhost/modules/test-module?start&strategy=canary&soak=0")
+                .body(serde_json::to_string(&spec).unwrap().into())
+                .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let details: ModuleDetails = serde_json::from_slice(&b).unwrap();
+                assert_eq!("test-module", details.name());
+                assert_eq!("running", details.status().runtime_status().status());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn bad_strategy() {
+        for query in &[
+            "start&strategy=rolling",
+            "start&strategy=canary&soak=soon",
+            "strategy=canary",
+        ] {
+            let handler = UpdateModule::new(RUNTIME.clone());
+            let config = Config::new(json!({"image":"microsoft/test-image"}));
+            let spec = ModuleSpec::new("test-module".to_string(), "docker".to_string(), config);
+            let uri = format!("http://localhost/modules/test-module?{}", query);
+            let request = Request::put(uri.as_str())
+                .body(serde_json::to_string(&spec).unwrap().into())
+                .unwrap();
+
+            // act
+            let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+            // assert
+            assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        }
+    }
+
     #[test]
     fn bad_body() {
         let handler = UpdateModule::new(RUNTIME.clone());
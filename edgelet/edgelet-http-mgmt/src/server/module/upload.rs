@@ -0,0 +1,500 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::{self, Write};
+use std::mem;
+use std::sync::Arc;
+
+use base64;
+use edgelet_core::{LogOptions, LogTail, ModuleRuntime};
+use edgelet_http::client::ClientImpl;
+use edgelet_http::route::{Handler, Parameters};
+use failure::{Fail, ResultExt};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use http::header::CONTENT_LENGTH;
+use http::{Method, Request, Response, StatusCode};
+use hyper::{Body, Chunk, Error as HyperError};
+use management::models::LogUpload;
+use serde_json;
+use url::Url;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// The logs are uploaded in blocks of about this size, so that no more than a
+/// block of them is held in memory at a time.
+const BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// The version of the blob storage REST API used for the uploads.
+const STORAGE_API_VERSION: &str = "2018-03-28";
+
+pub struct UploadModuleLogs<M, C>
+where
+    M: 'static + ModuleRuntime + Clone,
+    C: 'static + ClientImpl,
+{
+    runtime: M,
+    client: Arc<C>,
+}
+
+impl<M, C> UploadModuleLogs<M, C>
+where
+    M: 'static + ModuleRuntime + Clone,
+    C: 'static + ClientImpl,
+{
+    pub fn new(runtime: M, client: Arc<C>) -> Self {
+        UploadModuleLogs { runtime, client }
+    }
+}
+
+impl<M, C> Handler<Parameters> for UploadModuleLogs<M, C>
+where
+    M: 'static + ModuleRuntime + Clone + Send,
+    M::Error: IntoResponse,
+    M::Logs: Into<Body>,
+    C: 'static + ClientImpl,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let name = match params.name("name") {
+            Some(name) => name.to_string(),
+            None => return Box::new(future::ok(Error::from(ErrorKind::BadParam).into_response())),
+        };
+        let runtime = self.runtime.clone();
+        let client = self.client.clone();
+
+        let response = req
+            .into_body()
+            .concat2()
+            .map_err(Error::from)
+            .and_then(|b| {
+                let upload =
+                    serde_json::from_slice::<LogUpload>(&b).context(ErrorKind::BadBody)?;
+                let (options, url) = parse_upload(&upload)?;
+                Ok((options, url, upload.gzip().unwrap_or(false)))
+            }).then(move |parsed| match parsed {
+                Ok((options, url, gzip)) => {
+                    let uploaded = runtime.logs(&name, &options).then(move |logs| match logs {
+                        Ok(logs) => Either::A(
+                            upload(&client, url, logs.into(), gzip, BLOCK_SIZE)
+                                .map(|_| {
+                                    Response::builder()
+                                        .status(StatusCode::NO_CONTENT)
+                                        .body(Body::default())
+                                        .unwrap_or_else(|e| e.into_response())
+                                }).or_else(|e| future::ok(e.into_response())),
+                        ),
+                        Err(e) => Either::B(future::ok(e.into_response())),
+                    });
+                    Either::A(uploaded)
+                }
+                Err(e) => Either::B(future::ok(e.into_response())),
+            });
+
+        Box::new(response)
+    }
+}
+
+// The logs are read without following them, so that the upload ends.
+fn parse_upload(upload: &LogUpload) -> Result<(LogOptions, Url), Error> {
+    let tail = upload
+        .tail()
+        .map_or_else(|| Ok(LogTail::default()), str::parse::<LogTail>)
+        .context(ErrorKind::BadBody)?;
+    let options = LogOptions::new()
+        .with_tail(tail)
+        .with_since(upload.since().unwrap_or(0));
+
+    // The SAS token is what grants access to the blob, so there has to be one
+    // and it mustn't be sent in the clear.
+    let url = Url::parse(upload.sas_url()).context(ErrorKind::BadBody)?;
+    if url.scheme() != "https" || url.query().map_or(true, str::is_empty) {
+        return Err(Error::from(ErrorKind::BadBody));
+    }
+
+    Ok((options, url))
+}
+
+// Where the logs are collected until there are enough of them for a block.
+enum Buffer {
+    Plain(Vec<u8>),
+    Gzip(GzEncoder<Vec<u8>>),
+}
+
+impl Buffer {
+    fn new(gzip: bool) -> Self {
+        if gzip {
+            Buffer::Gzip(GzEncoder::new(Vec::new(), Compression::default()))
+        } else {
+            Buffer::Plain(Vec::new())
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Buffer::Plain(buffer) => {
+                buffer.extend_from_slice(data);
+                Ok(())
+            }
+            Buffer::Gzip(encoder) => encoder.write_all(data),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Buffer::Plain(buffer) => buffer.len(),
+            Buffer::Gzip(encoder) => encoder.get_ref().len(),
+        }
+    }
+
+    // Takes the data that is ready to be uploaded. The encoder only ever
+    // appends to its output, so compression carries on where it left off.
+    fn take(&mut self) -> Vec<u8> {
+        match self {
+            Buffer::Plain(buffer) => mem::replace(buffer, Vec::new()),
+            Buffer::Gzip(encoder) => mem::replace(encoder.get_mut(), Vec::new()),
+        }
+    }
+
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Buffer::Plain(buffer) => Ok(buffer),
+            Buffer::Gzip(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// Uploads `logs` to the blob at `url` as a block blob. A block is uploaded
+/// whenever `block_size` bytes have been collected, and the blob is committed
+/// once the logs end. An upload that fails part way leaves the blob as it was.
+fn upload<C, S>(
+    client: &Arc<C>,
+    url: Url,
+    logs: S,
+    gzip: bool,
+    block_size: usize,
+) -> impl Future<Item = (), Error = Error> + Send
+where
+    C: 'static + ClientImpl,
+    S: 'static + Stream<Item = Chunk, Error = HyperError> + Send,
+{
+    let block_client = client.clone();
+    let block_url = url.clone();
+    let client = client.clone();
+
+    logs.map_err(|err| Error::from(err.context(ErrorKind::LogUpload)))
+        .fold(
+            (Buffer::new(gzip), Vec::new()),
+            move |(mut buffer, mut blocks), chunk| {
+                if let Err(err) = buffer.write(&chunk) {
+                    return Either::B(future::err(Error::from(err.context(ErrorKind::LogUpload))));
+                }
+                if buffer.len() < block_size {
+                    return Either::B(future::ok((buffer, blocks)));
+                }
+
+                let block = put_block(&*block_client, &block_url, blocks.len(), buffer.take());
+                Either::A(block.map(move |id| {
+                    blocks.push(id);
+                    (buffer, blocks)
+                }))
+            },
+        ).and_then(move |(buffer, mut blocks)| {
+            let last = match buffer.finish() {
+                Ok(last) => last,
+                Err(err) => {
+                    return Either::B(future::err(Error::from(err.context(ErrorKind::LogUpload))))
+                }
+            };
+            let last_block = if last.is_empty() {
+                Either::B(future::ok(blocks))
+            } else {
+                Either::A(put_block(&*client, &url, blocks.len(), last).map(move |id| {
+                    blocks.push(id);
+                    blocks
+                }))
+            };
+            Either::A(
+                last_block.and_then(move |blocks| put_block_list(&*client, &url, &blocks, gzip)),
+            )
+        })
+}
+
+fn put_block<C>(
+    client: &C,
+    url: &Url,
+    index: usize,
+    block: Vec<u8>,
+) -> impl Future<Item = String, Error = Error>
+where
+    C: ClientImpl,
+{
+    // The ids of the blocks of a blob all have to be the same length
+    let id = base64::encode(&format!("{:08}", index));
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .append_pair("comp", "block")
+        .append_pair("blockid", &id);
+    put(client, &url, block, None).map(move |_| id)
+}
+
+fn put_block_list<C>(
+    client: &C,
+    url: &Url,
+    blocks: &[String],
+    gzip: bool,
+) -> impl Future<Item = (), Error = Error>
+where
+    C: ClientImpl,
+{
+    let mut list = blocks.iter().fold(
+        r#"<?xml version="1.0" encoding="utf-8"?><BlockList>"#.to_string(),
+        |mut list, id| {
+            list.push_str(&format!("<Latest>{}</Latest>", id));
+            list
+        },
+    );
+    list.push_str("</BlockList>");
+
+    let content_type = if gzip {
+        "application/gzip"
+    } else {
+        "application/octet-stream"
+    };
+    let mut url = url.clone();
+    url.query_pairs_mut().append_pair("comp", "blocklist");
+    put(client, &url, list.into_bytes(), Some(content_type))
+}
+
+fn put<C>(
+    client: &C,
+    url: &Url,
+    body: Vec<u8>,
+    content_type: Option<&str>,
+) -> impl Future<Item = (), Error = Error>
+where
+    C: ClientImpl,
+{
+    let mut req = Request::builder();
+    req.method(Method::PUT)
+        .uri(url.as_str())
+        .header("x-ms-version", STORAGE_API_VERSION)
+        .header(CONTENT_LENGTH, body.len().to_string().as_str());
+    if let Some(content_type) = content_type {
+        req.header("x-ms-blob-content-type", content_type);
+    }
+
+    match req.body(Body::from(body)) {
+        Ok(req) => Either::A(client.call(req).then(|res| match res {
+            Ok(ref res) if res.status().is_success() => Ok(()),
+            // the url isn't logged since it holds the SAS token
+            Ok(res) => {
+                warn!("Blob storage responded to a log upload with {}", res.status());
+                Err(Error::from(ErrorKind::LogUpload))
+            }
+            Err(err) => Err(Error::from(err.context(ErrorKind::LogUpload))),
+        })),
+        Err(err) => Either::B(future::err(Error::from(err.context(ErrorKind::LogUpload)))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::sync::Mutex;
+
+    use chrono::prelude::*;
+    use edgelet_core::{ModuleRuntimeState, ModuleStatus};
+    use edgelet_test_utils::module::*;
+    use flate2::read::GzDecoder;
+    use futures::stream;
+    use management::models::ErrorResponse;
+    use server::module::tests::Error as TestError;
+    use url::percent_encoding::percent_decode;
+
+    use super::*;
+
+    const SAS_URL: &str =
+        "https://account.blob.core.windows.net/logs/mod1.log?sv=2018-03-28&sig=abc";
+
+    type Requests = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+    // A blob storage stand in that records the query and body of every
+    // request and responds with `status`.
+    fn storage(
+        status: StatusCode,
+    ) -> (
+        Requests,
+        impl Fn(Request<Body>) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send>,
+    ) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let client = move |req: Request<Body>| {
+            assert_eq!(&Method::PUT, req.method());
+            assert_eq!("/logs/mod1.log", req.uri().path());
+            let query = req.uri().query().unwrap_or_default().to_string();
+            let recorded = recorded.clone();
+            let response = req.into_body().concat2().map(move |body| {
+                recorded.lock().unwrap().push((query, body.to_vec()));
+                Response::builder()
+                    .status(status)
+                    .body(Body::default())
+                    .unwrap()
+            });
+            Box::new(response) as Box<Future<Item = _, Error = _> + Send>
+        };
+        (requests, client)
+    }
+
+    fn logs() -> impl Stream<Item = Chunk, Error = HyperError> + Send {
+        stream::iter_ok(vec![
+            Chunk::from("first line\n"),
+            Chunk::from("second line\n"),
+            Chunk::from("third line\n"),
+        ])
+    }
+
+    fn uploaded_blocks(requests: &Requests) -> Vec<u8> {
+        let requests = requests.lock().unwrap();
+        let (list, blocks) = requests.split_last().unwrap();
+        assert!(list.0.ends_with("comp=blocklist"));
+        let list = String::from_utf8(list.1.clone()).unwrap();
+        for (query, _) in blocks {
+            let id = query.rsplit("blockid=").next().unwrap();
+            let id = percent_decode(id.as_bytes()).decode_utf8().unwrap();
+            assert!(list.contains(&format!("<Latest>{}</Latest>", id)));
+        }
+        blocks.iter().flat_map(|(_, body)| body.clone()).collect()
+    }
+
+    #[test]
+    fn upload_is_split_into_blocks() {
+        let (requests, client) = storage(StatusCode::CREATED);
+        let url = Url::parse(SAS_URL).unwrap();
+
+        upload(&Arc::new(client), url, logs(), false, 16)
+            .wait()
+            .unwrap();
+
+        assert_eq!(3, requests.lock().unwrap().len());
+        assert_eq!(
+            b"first line\nsecond line\nthird line\n".to_vec(),
+            uploaded_blocks(&requests)
+        );
+    }
+
+    #[test]
+    fn gzip_upload_can_be_decompressed() {
+        let (requests, client) = storage(StatusCode::CREATED);
+        let url = Url::parse(SAS_URL).unwrap();
+
+        upload(&Arc::new(client), url, logs(), true, 16)
+            .wait()
+            .unwrap();
+
+        let mut decoded = String::new();
+        GzDecoder::new(&uploaded_blocks(&requests)[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!("first line\nsecond line\nthird line\n", decoded);
+    }
+
+    #[test]
+    fn storage_failure_fails_upload() {
+        let (requests, client) = storage(StatusCode::FORBIDDEN);
+        let url = Url::parse(SAS_URL).unwrap();
+
+        let err = upload(&Arc::new(client), url, logs(), false, 16)
+            .wait()
+            .unwrap_err();
+
+        assert_eq!(StatusCode::BAD_GATEWAY, err.kind().status_code());
+        // nothing is uploaded after the first failed block
+        assert_eq!(1, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    fn upload_needs_a_sas_url() {
+        for url in &[
+            "not a url",
+            "http://account.blob.core.windows.net/logs/mod1.log?sv=2018-03-28&sig=abc",
+            "https://account.blob.core.windows.net/logs/mod1.log",
+        ] {
+            let upload = LogUpload::new(url.to_string());
+            assert!(parse_upload(&upload).is_err());
+        }
+
+        let upload = LogUpload::new(SAS_URL.to_string())
+            .with_tail("10".to_string())
+            .with_since(1_539_600_000);
+        let (options, _) = parse_upload(&upload).unwrap();
+        assert_eq!(LogTail::Num(10), *options.tail());
+        assert_eq!(1_539_600_000, options.since());
+        assert!(!options.follow());
+    }
+
+    fn runtime() -> TestRuntime<TestError> {
+        let state = ModuleRuntimeState::default()
+            .with_status(ModuleStatus::Running)
+            .with_exit_code(Some(0))
+            .with_started_at(Some(Utc.ymd(2018, 4, 13).and_hms_milli(14, 20, 0, 1)))
+            .with_image_id(Some("image-id".to_string()));
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module: TestModule<TestError> =
+            TestModule::new("test-module".to_string(), config, Ok(state));
+        TestRuntime::new(Ok(module))
+    }
+
+    #[test]
+    fn success() {
+        let (requests, client) = storage(StatusCode::CREATED);
+        let handler = UploadModuleLogs::new(runtime(), Arc::new(client));
+        let body = serde_json::to_string(&LogUpload::new(SAS_URL.to_string())).unwrap();
+        let request =
+            Request::post("http://localhost/modules/mod1/logs/upload?api-version=2018-06-28")
+                .body(body.into())
+                .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "mod1".to_string())]);
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        // the module has no logs, so only an empty block list is committed
+        let requests = requests.lock().unwrap();
+        assert_eq!(1, requests.len());
+        assert!(requests[0].0.ends_with("comp=blocklist"));
+    }
+
+    #[test]
+    fn bad_body_fails() {
+        let (requests, client) = storage(StatusCode::CREATED);
+        let handler = UploadModuleLogs::new(runtime(), Arc::new(client));
+        let body = r#"{"sasUrl": "https://account.blob.core.windows.net/logs/mod1.log"}"#;
+        let request =
+            Request::post("http://localhost/modules/mod1/logs/upload?api-version=2018-06-28")
+                .body(body.into())
+                .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "mod1".to_string())]);
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let error: ErrorResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!("Bad body", error.message());
+                Ok(())
+            }).wait()
+            .unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+    }
+}
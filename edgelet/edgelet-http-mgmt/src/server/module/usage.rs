@@ -0,0 +1,93 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{ModuleUsage as CoreModuleUsage, WorkloadUsage};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::ModuleUsage;
+use serde_json;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct GetModuleUsage {
+    usage: WorkloadUsage,
+}
+
+impl GetModuleUsage {
+    pub fn new(usage: WorkloadUsage) -> Self {
+        GetModuleUsage { usage }
+    }
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+fn core_to_usage(name: &str, usage: &CoreModuleUsage) -> ModuleUsage {
+    ModuleUsage::new(
+        name.to_string(),
+        usage.certificates() as i64,
+        usage.signs() as i64,
+        usage.encrypts() as i64,
+        usage.decrypts() as i64,
+    )
+}
+
+impl Handler<Parameters> for GetModuleUsage {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let response = params
+            .name("name")
+            .ok_or_else(|| Error::from(ErrorKind::BadParam))
+            .and_then(|name| {
+                let body = core_to_usage(name, &self.usage.usage(name));
+                let b = serde_json::to_string(&body).context(ErrorKind::Serde)?;
+                Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                    .body(b.into())
+                    .unwrap_or_else(|e| e.into_response()))
+            }).unwrap_or_else(|e| e.into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{Metrics, WorkloadCall};
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn usage_of_the_module_is_returned() {
+        let usage = WorkloadUsage::new(Metrics::new());
+        usage.record("m1", WorkloadCall::Sign);
+        usage.record("m1", WorkloadCall::Sign);
+        usage.record("m1", WorkloadCall::IssueCertificate);
+        usage.record("m2", WorkloadCall::Encrypt);
+
+        let handler = GetModuleUsage::new(usage);
+        let request = Request::get("http://localhost/modules/m1/usage")
+            .body(Body::default())
+            .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "m1".to_string())]);
+        let response = handler.handle(request, parameters).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let b = response.into_body().concat2().wait().unwrap();
+        let usage: ModuleUsage = serde_json::from_slice(&b).unwrap();
+        assert_eq!("m1", usage.module_id());
+        assert_eq!(1, usage.certificates());
+        assert_eq!(2, usage.signs());
+        assert_eq!(0, usage.encrypts());
+        assert_eq!(0, usage.decrypts());
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{OperationState, Operations};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::Operation;
+use serde_json;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// Serves `GET /operations/{id}`, which reports the progress of a long
+/// running operation, such as a deployment being applied or an image being
+/// pulled.
+pub struct GetOperation {
+    operations: Operations,
+}
+
+impl GetOperation {
+    pub fn new(operations: Operations) -> Self {
+        GetOperation { operations }
+    }
+}
+
+impl Handler<Parameters> for GetOperation {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let operation = params
+            .name("id")
+            .and_then(|id| self.operations.get(id))
+            .ok_or_else(|| Error::from(ErrorKind::NotFound));
+
+        let response = operation
+            .and_then(|operation| operation_response(&operation, StatusCode::OK))
+            .unwrap_or_else(|e| e.into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+pub fn operation_to_model(operation: &OperationState) -> Operation {
+    let model = Operation::new(
+        operation.id().to_string(),
+        operation.kind().to_string(),
+        operation.status().to_string(),
+        i64::from(operation.percent()),
+        operation.started_at().to_rfc3339(),
+    );
+    let model = match operation.step() {
+        Some(step) => model.with_step(step.to_string()),
+        None => model,
+    };
+    let model = match operation.error() {
+        Some(error) => model.with_error(error.to_string()),
+        None => model,
+    };
+    match operation.finished_at() {
+        Some(finished_at) => model.with_finished_at(finished_at.to_rfc3339()),
+        None => model,
+    }
+}
+
+/// Responds with where `operation` is at, such as with `202 Accepted` once
+/// it has been started.
+pub fn operation_response(
+    operation: &OperationState,
+    status: StatusCode,
+) -> Result<Response<Body>, Error> {
+    let b = serde_json::to_string(&operation_to_model(operation)).context(ErrorKind::Serde)?;
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+        .body(b.into())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+
+    use super::*;
+
+    fn get(operations: Operations, id: &str) -> Response<Body> {
+        let request = Request::get(format!("http://localhost/operations/{}", id))
+            .body(Body::default())
+            .unwrap();
+        let params = Parameters::with_captures(vec![(Some("id".to_string()), id.to_string())]);
+        GetOperation::new(operations)
+            .handle(request, params)
+            .wait()
+            .unwrap()
+    }
+
+    #[test]
+    fn operation_is_found_by_id() {
+        let operations = Operations::new();
+        let operation = operations.start("imagePull");
+        operation.progress(40, "Pulling image sensor:1");
+
+        let response = get(operations, operation.id());
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let model: Operation = serde_json::from_slice(&body).unwrap();
+        assert_eq!(operation.id(), model.id());
+        assert_eq!("imagePull", model.kind());
+        assert_eq!("running", model.status());
+        assert_eq!(40, model.percent());
+        assert_eq!(Some("Pulling image sensor:1"), model.step());
+        assert!(model.finished_at().is_none());
+    }
+
+    #[test]
+    fn unknown_operation_is_not_found() {
+        let response = get(Operations::new(), "1");
+
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::Scheduler;
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::*;
+use serde_json;
+
+use error::ErrorKind;
+use IntoResponse;
+
+pub struct ListSchedules {
+    scheduler: Scheduler,
+}
+
+impl ListSchedules {
+    pub fn new(scheduler: Scheduler) -> Self {
+        ListSchedules { scheduler }
+    }
+}
+
+impl Handler<Parameters> for ListSchedules {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let schedules = self
+            .scheduler
+            .entries()
+            .iter()
+            .map(|entry| {
+                let spec = entry.spec();
+                let schedule = Schedule::new(
+                    spec.module().to_string(),
+                    spec.action().to_string(),
+                    spec.schedule().to_string(),
+                );
+                match entry.next_run() {
+                    Some(next_run) => schedule.with_next_run(next_run.to_rfc3339()),
+                    None => schedule,
+                }
+            }).collect();
+        let body = ScheduleList::new(schedules);
+
+        let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
+            Ok(b) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                .body(b.into())
+                .unwrap_or_else(|e| e.into_response()),
+            Err(e) => e.into_response(),
+        };
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{ScheduleSpec, ScheduledAction};
+    use futures::Stream;
+
+    use super::*;
+
+    #[test]
+    fn success() {
+        // arrange
+        let specs = vec![
+            ScheduleSpec::new(
+                "batchUpload".to_string(),
+                ScheduledAction::Start,
+                "0 2 * * *".to_string(),
+            ),
+            ScheduleSpec::new(
+                "batchUpload".to_string(),
+                ScheduledAction::Stop,
+                "0 0 30 2 *".to_string(),
+            ),
+        ];
+        let handler = ListSchedules::new(Scheduler::new(&specs).unwrap());
+        let request = Request::get("http://localhost/schedules")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let list: ScheduleList = serde_json::from_slice(&b).unwrap();
+                let schedules = list.schedules();
+                assert_eq!(2, schedules.len());
+
+                assert_eq!("batchUpload", schedules[0].module());
+                assert_eq!("start", schedules[0].action());
+                assert_eq!("0 2 * * *", schedules[0].schedule());
+                assert!(schedules[0].next_run().unwrap().contains("T02:00:00"));
+
+                assert_eq!("stop", schedules[1].action());
+                assert_eq!(None, schedules[1].next_run());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+
+    #[test]
+    fn empty() {
+        // arrange
+        let handler = ListSchedules::new(Scheduler::new(&[]).unwrap());
+        let request = Request::get("http://localhost/schedules")
+            .body(Body::default())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::OK, response.status());
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let list: ScheduleList = serde_json::from_slice(&b).unwrap();
+                assert!(list.schedules().is_empty());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+}
@@ -1,5 +1,4 @@
 // Copyright (c) Microsoft. All rights reserved.
+mod list;
 
-mod module;
-
-pub use self::module::ModuleClient;
+pub use self::list::ListSchedules;
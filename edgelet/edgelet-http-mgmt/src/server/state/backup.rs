@@ -0,0 +1,125 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::StateBackup;
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Method, Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::BackupRequest;
+use serde_json;
+
+use super::{not_served, state_error};
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub const BACKUP_STATE_PATH: &str = "/state/backup";
+
+pub struct BackupState {
+    state: Option<StateBackup>,
+}
+
+impl BackupState {
+    pub fn new(state: Option<StateBackup>) -> Self {
+        BackupState { state }
+    }
+}
+
+impl Handler<Parameters> for BackupState {
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        if *req.method() != Method::POST {
+            return Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(Body::default())
+                    .expect("response builder failure"),
+            ));
+        }
+
+        let state = match self.state {
+            Some(ref state) => state.clone(),
+            None => return Box::new(future::ok(not_served())),
+        };
+        let response = req
+            .into_body()
+            .concat2()
+            .map(move |b| {
+                serde_json::from_slice::<BackupRequest>(&b)
+                    .context(ErrorKind::BadBody)
+                    .map_err(Error::from)
+                    .and_then(|request| state.backup(request.passphrase()).map_err(state_error))
+                    .map(|backup| {
+                        info!("Backed up the daemon state");
+                        Response::builder()
+                            .status(StatusCode::OK)
+                            .header(CONTENT_TYPE, "application/octet-stream")
+                            .header(CONTENT_LENGTH, backup.len().to_string().as_str())
+                            .body(backup.into())
+                            .unwrap_or_else(|e| e.into_response())
+                    }).unwrap_or_else(|e| e.into_response())
+            }).or_else(|e| future::ok(e.into_response()));
+        Box::new(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use management::models::ErrorResponse;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn backups_are_encrypted_with_the_passphrase() {
+        let tmp_dir = TempDir::new("backup").unwrap();
+        let path = tmp_dir.path().join("config.yaml");
+        fs::write(&path, "hostname: gateway\n").unwrap();
+        let state = StateBackup::new().with_entry("config.yaml", path.clone());
+        let handler = BackupState::new(Some(state.clone()));
+
+        let request = Request::post("http://localhost/state/backup")
+            .body(r#"{"passphrase":"passphrase"}"#.into())
+            .unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let backup = response.into_body().concat2().wait().unwrap();
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            vec!["config.yaml".to_string()],
+            state.restore(&backup, "passphrase").unwrap()
+        );
+        assert_eq!("hostname: gateway\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn empty_passphrases_are_refused() {
+        let handler = BackupState::new(Some(StateBackup::new()));
+        let request = Request::post("http://localhost/state/backup")
+            .body(r#"{"passphrase":""}"#.into())
+            .unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(error.message().contains("passphrase"));
+    }
+
+    #[test]
+    fn backups_are_not_served_without_a_state() {
+        let handler = BackupState::new(None);
+        let request = Request::post("http://localhost/state/backup")
+            .body(r#"{"passphrase":"passphrase"}"#.into())
+            .unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+}
@@ -0,0 +1,30 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+mod backup;
+mod restore;
+
+use edgelet_core::{Error as CoreError, ErrorKind as CoreErrorKind};
+use failure::Fail;
+use hyper::{Body, Response};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub use self::backup::{BackupState, BACKUP_STATE_PATH};
+pub use self::restore::RestoreState;
+
+// Backups aren't served unless the daemon is configured to, in which case
+// the state isn't handed to the handlers.
+fn not_served() -> Response<Body> {
+    Error::from(ErrorKind::NotFound).into_response()
+}
+
+// A backup that can't be decrypted or isn't one is the caller's mistake.
+fn state_error(err: CoreError) -> Error {
+    match *err.kind() {
+        CoreErrorKind::InvalidBackup | CoreErrorKind::BackupPassphrase => {
+            Error::from(err.context(ErrorKind::BadBody))
+        }
+        _ => Error::from(err),
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use base64;
+use edgelet_core::StateBackup;
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Method, Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::{RestoreRequest, RestoreResult};
+use serde_json;
+
+use super::{not_served, state_error};
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// Restores the daemon state from a backup. What is restored is only read
+/// when the daemon starts, so it has to be restarted afterwards.
+pub struct RestoreState {
+    state: Option<StateBackup>,
+}
+
+impl RestoreState {
+    pub fn new(state: Option<StateBackup>) -> Self {
+        RestoreState { state }
+    }
+}
+
+impl Handler<Parameters> for RestoreState {
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        if *req.method() != Method::POST {
+            return Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(Body::default())
+                    .expect("response builder failure"),
+            ));
+        }
+
+        let state = match self.state {
+            Some(ref state) => state.clone(),
+            None => return Box::new(future::ok(not_served())),
+        };
+        let response = req
+            .into_body()
+            .concat2()
+            .map(move |b| {
+                restore(&state, &b)
+                    .and_then(|result| {
+                        let b = serde_json::to_string(&result).context(ErrorKind::Serde)?;
+                        Ok(Response::builder()
+                            .status(StatusCode::OK)
+                            .header(CONTENT_TYPE, "application/json")
+                            .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                            .body(b.into())
+                            .unwrap_or_else(|e| e.into_response()))
+                    }).unwrap_or_else(|e: Error| e.into_response())
+            }).or_else(|e| future::ok(e.into_response()));
+        Box::new(response)
+    }
+}
+
+fn restore(state: &StateBackup, body: &[u8]) -> Result<RestoreResult, Error> {
+    let request: RestoreRequest = serde_json::from_slice(body).context(ErrorKind::BadBody)?;
+    let backup = base64::decode(request.backup()).context(ErrorKind::BadBody)?;
+    let restored = state
+        .restore(&backup, request.passphrase())
+        .map_err(state_error)?;
+    info!("Restored the daemon state. The daemon must be restarted to use it.");
+    Ok(RestoreResult::new(restored))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use management::models::ErrorResponse;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn restore_request(backup: &[u8], passphrase: &str) -> Request<Body> {
+        let request = RestoreRequest::new(passphrase.to_string(), base64::encode(backup));
+        Request::post("http://localhost/state/restore")
+            .body(serde_json::to_string(&request).unwrap().into())
+            .unwrap()
+    }
+
+    #[test]
+    fn backups_are_restored() {
+        let tmp_dir = TempDir::new("restore").unwrap();
+        let path = tmp_dir.path().join("config.yaml");
+        fs::write(&path, "hostname: gateway\n").unwrap();
+        let state = StateBackup::new().with_entry("config.yaml", path.clone());
+        let backup = state.backup("passphrase").unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let handler = RestoreState::new(Some(state));
+        let response = handler
+            .handle(restore_request(&backup, "passphrase"), Parameters::new())
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let body = response.into_body().concat2().wait().unwrap();
+        let result: RestoreResult = serde_json::from_slice(&body).unwrap();
+        assert_eq!(&vec!["config.yaml".to_string()], result.restored());
+        assert_eq!("hostname: gateway\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn backups_with_another_passphrase_are_bad_requests() {
+        let tmp_dir = TempDir::new("restore").unwrap();
+        let path = tmp_dir.path().join("config.yaml");
+        fs::write(&path, "hostname: gateway\n").unwrap();
+        let state = StateBackup::new().with_entry("config.yaml", path.clone());
+        let backup = state.backup("passphrase").unwrap();
+
+        let handler = RestoreState::new(Some(state));
+        let response = handler
+            .handle(restore_request(&backup, "wrong"), Parameters::new())
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(error.message().contains("passphrase"));
+    }
+}
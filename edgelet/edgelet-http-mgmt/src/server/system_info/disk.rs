@@ -0,0 +1,168 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::path::PathBuf;
+
+use edgelet_core::{DiskUsage as CoreDiskUsage, DiskUsageProvider};
+use edgelet_http::route::{Handler, Parameters};
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use management::models::*;
+use serde_json;
+
+use error::ErrorKind;
+use IntoResponse;
+
+/// Reports what takes up the disk of the device: images, the writable layers
+/// of the modules, volumes and the state of the daemon in `homedir`.
+pub struct GetDiskUsage<D> {
+    provider: D,
+    homedir: PathBuf,
+}
+
+impl<D> GetDiskUsage<D> {
+    pub fn new(provider: D, homedir: PathBuf) -> Self {
+        GetDiskUsage { provider, homedir }
+    }
+}
+
+impl<D> Handler<Parameters> for GetDiskUsage<D>
+where
+    D: 'static + DiskUsageProvider + Send,
+    D::Error: IntoResponse,
+{
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        debug!("Get Disk Usage");
+        let homedir = self.homedir.clone();
+        let response = self
+            .provider
+            .disk_usage()
+            .and_then(move |usage| {
+                let body = disk_usage(&usage.with_host(&homedir));
+                let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
+                    Ok(b) => Response::builder()
+                        .status(StatusCode::OK)
+                        .header(CONTENT_TYPE, "application/json")
+                        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                        .body(b.into())
+                        .unwrap_or_else(|e| e.into_response()),
+                    Err(e) => e.into_response(),
+                };
+                future::ok(response)
+            }).or_else(|e| future::ok(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+fn disk_usage(usage: &CoreDiskUsage) -> DiskUsage {
+    let modules = usage
+        .modules()
+        .iter()
+        .map(|module| {
+            let model =
+                ModuleDiskUsage::new(module.name().to_string(), module.writable_layer() as i64);
+            match module.quota() {
+                Some(quota) => model.with_quota(quota as i64),
+                None => model,
+            }
+        }).collect();
+    let volumes = usage
+        .volumes()
+        .iter()
+        .map(|volume| {
+            let model = VolumeDiskUsage::new(volume.name().to_string());
+            match volume.size() {
+                Some(size) => model.with_size(size as i64),
+                None => model,
+            }
+        }).collect();
+
+    let mut model = DiskUsage::new(
+        usage.images() as i64,
+        modules,
+        volumes,
+        usage.daemon_state() as i64,
+    );
+    if let Some(total) = usage.total() {
+        model.set_total(total as i64);
+    }
+    if let Some(available) = usage.available() {
+        model.set_available(available as i64);
+    }
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use edgelet_core::{
+        ModuleDiskUsage as CoreModuleDiskUsage, VolumeDiskUsage as CoreVolumeDiskUsage,
+    };
+    use futures::Stream;
+    use server::module::tests::Error;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct TestProvider(Result<CoreDiskUsage, Error>);
+
+    impl DiskUsageProvider for TestProvider {
+        type Error = Error;
+        type DiskUsageFuture = future::FutureResult<CoreDiskUsage, Error>;
+
+        fn disk_usage(&self) -> Self::DiskUsageFuture {
+            future::result(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn usage_is_reported_with_the_daemon_state() {
+        let usage = CoreDiskUsage::new()
+            .with_images(4_000_000_000)
+            .with_modules(vec![
+                CoreModuleDiskUsage::new("edgeHub".to_string(), 2048).with_quota(1 << 30),
+            ]).with_volumes(vec![CoreVolumeDiskUsage::new("edgehub-store".to_string())]);
+        let homedir = TempDir::new("disk").unwrap();
+        fs::write(homedir.path().join("hostname"), "gateway").unwrap();
+        let handler = GetDiskUsage::new(TestProvider(Ok(usage)), homedir.path().to_path_buf());
+        let request = Request::get("http://localhost/systeminfo/disk")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let usage: DiskUsage = serde_json::from_slice(&body).unwrap();
+        assert_eq!(4_000_000_000, usage.images());
+        assert_eq!(7, usage.daemon_state());
+        assert_eq!("edgeHub", usage.modules()[0].name());
+        assert_eq!(2048, usage.modules()[0].writable_layer());
+        assert_eq!(Some(1 << 30), usage.modules()[0].quota());
+        assert_eq!(None, usage.volumes()[0].size());
+    }
+
+    #[test]
+    fn runtime_failures_are_reported() {
+        let homedir = TempDir::new("disk").unwrap();
+        let handler =
+            GetDiskUsage::new(TestProvider(Err(Error::General)), homedir.path().to_path_buf());
+        let request = Request::get("http://localhost/systeminfo/disk")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!("General error", error.message());
+    }
+}
@@ -1,6 +1,9 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::{Module, ModuleRuntime};
+use edgelet_core::{
+    build_commit, rustc_version, DeploymentHistory, HostInfo, Module, ModuleRuntime,
+    SystemInfo as CoreSystemInfo, SystemInfoProvider, DEFAULT_HISTORY_SIZE,
+};
 use edgelet_http::route::{Handler, Parameters};
 use failure::ResultExt;
 use futures::{future, Future};
@@ -12,33 +15,46 @@ use serde::Serialize;
 use serde_json;
 
 use error::ErrorKind;
+use server::module::applied_versions;
 use IntoResponse;
 
-pub struct GetSystemInfo<M>
+pub struct GetSystemInfo<M, S>
 where
     M: 'static + ModuleRuntime,
     M::Error: IntoResponse,
     <M::Module as Module>::Config: Serialize,
 {
     runtime: M,
+    host: S,
+    history: DeploymentHistory<ModuleSpec>,
 }
 
-impl<M> GetSystemInfo<M>
+impl<M, S> GetSystemInfo<M, S>
 where
     M: 'static + ModuleRuntime,
     M::Error: IntoResponse,
     <M::Module as Module>::Config: Serialize,
 {
-    pub fn new(runtime: M) -> Self {
-        GetSystemInfo { runtime }
+    pub fn new(runtime: M, host: S) -> Self {
+        GetSystemInfo {
+            runtime,
+            host,
+            history: DeploymentHistory::new(DEFAULT_HISTORY_SIZE),
+        }
+    }
+
+    pub fn with_history(mut self, history: DeploymentHistory<ModuleSpec>) -> Self {
+        self.history = history;
+        self
     }
 }
 
-impl<M> Handler<Parameters> for GetSystemInfo<M>
+impl<M, S> Handler<Parameters> for GetSystemInfo<M, S>
 where
     M: 'static + ModuleRuntime + Send,
     M::Error: IntoResponse,
     <M::Module as Module>::Config: Serialize,
+    S: 'static + SystemInfoProvider + Send,
 {
     fn handle(
         &self,
@@ -46,15 +62,13 @@ where
         _params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         debug!("Get System Information");
+        let host = self.host.host_info();
+        let deployments = applied_versions(&self.history);
         let response = self
             .runtime
             .system_info()
-            .and_then(|systeminfo| {
-                let body = SystemInfo::new(
-                    systeminfo.os_type().to_string(),
-                    systeminfo.architecture().to_string(),
-                    systeminfo.version().to_string(),
-                );
+            .and_then(move |systeminfo| {
+                let body = system_info(&systeminfo, &host, deployments);
                 let response = match serde_json::to_string(&body).context(ErrorKind::Serde) {
                     Ok(b) => Response::builder()
                         .status(StatusCode::OK)
@@ -71,6 +85,46 @@ where
     }
 }
 
+/// Combines what the runtime and the host report with the build information
+/// of the daemon and the versions of the deployments the modules came from.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+fn system_info(runtime: &CoreSystemInfo, host: &HostInfo, deployments: Vec<String>) -> SystemInfo {
+    let mut info = SystemInfo::new(
+        runtime.os_type().to_string(),
+        runtime.architecture().to_string(),
+        runtime.version().to_string(),
+    );
+    if let Some(os_name) = host.os_name() {
+        info.set_operating_system(os_name.to_string());
+    }
+    if let Some(kernel_version) = host.kernel_version() {
+        info.set_kernel_version(kernel_version.to_string());
+    }
+    if let Some(total_memory) = host.total_memory() {
+        info.set_total_memory(total_memory as i64);
+    }
+    if let Some(total_disk) = host.total_disk() {
+        info.set_total_disk(total_disk as i64);
+    }
+    if let Some(server_version) = runtime.server_version() {
+        info.set_runtime_version(server_version.to_string());
+    }
+    if let Some(hsm_version) = host.hsm_version() {
+        info.set_hsm_version(hsm_version.to_string());
+    }
+    if let Some(commit) = build_commit() {
+        info.set_build_commit(commit.to_string());
+    }
+    if let Some(rustc) = rustc_version() {
+        info.set_rustc_version(rustc.to_string());
+    }
+    info.set_offline(host.offline());
+    if !deployments.is_empty() {
+        info.set_deployments(deployments);
+    }
+    info
+}
+
 #[cfg(test)]
 mod tests {
     use edgelet_core::{self, ModuleRuntimeState};
@@ -82,6 +136,18 @@ mod tests {
 
     use super::*;
 
+    struct TestHost;
+
+    impl SystemInfoProvider for TestHost {
+        fn host_info(&self) -> HostInfo {
+            HostInfo::new()
+                .with_os_name("Ubuntu 18.04.1 LTS".to_string())
+                .with_total_memory(8_363_876_352)
+                .with_hsm_version("1.0.2".to_string())
+                .with_offline(true)
+        }
+    }
+
     #[test]
     fn system_info_success() {
         // arrange
@@ -90,7 +156,7 @@ mod tests {
         let module: TestModule<Error> =
             TestModule::new("test-module".to_string(), config, Ok(state));
         let runtime = TestRuntime::new(Ok(module));
-        let handler = GetSystemInfo::new(runtime);
+        let handler = GetSystemInfo::new(runtime, TestHost);
         let request = Request::get("http://localhost/info")
             .body(Body::default())
             .unwrap();
@@ -110,17 +176,53 @@ mod tests {
                 assert_eq!("os_type_sample", os_type);
                 assert_eq!("architecture_sample", architecture);
                 assert_eq!(edgelet_core::version(), system_info.version());
+                assert_eq!(Some("Ubuntu 18.04.1 LTS"), system_info.operating_system());
+                assert_eq!(Some(&8_363_876_352), system_info.total_memory());
+                assert_eq!(Some("1.0.2"), system_info.hsm_version());
+                assert_eq!(None, system_info.kernel_version());
+                assert_eq!(None, system_info.runtime_version());
+                assert_eq!(Some(true), system_info.offline());
+                assert_eq!(None, system_info.deployments());
 
                 Ok(())
             }).wait()
             .unwrap();
     }
 
+    #[test]
+    fn deployments_are_reported() {
+        let history = DeploymentHistory::new(DEFAULT_HISTORY_SIZE);
+        for &(name, version) in &[("sensor", "v2"), ("display", "v1"), ("kiosk", "v2")] {
+            let config = Config::new(json!({"image": name}));
+            let spec = ModuleSpec::new(name.to_string(), "docker".to_string(), config)
+                .with_deployment(version.to_string());
+            history.module_applied(name, spec).unwrap();
+        }
+        let config = TestConfig::new("microsoft/test-image".to_string());
+        let module: TestModule<Error> = TestModule::new(
+            "test-module".to_string(),
+            config,
+            Ok(ModuleRuntimeState::default()),
+        );
+        let runtime = TestRuntime::new(Ok(module));
+        let handler = GetSystemInfo::new(runtime, TestHost).with_history(history);
+        let request = Request::get("http://localhost/info")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        let body = response.into_body().concat2().wait().unwrap();
+        let system_info: SystemInfo = serde_json::from_slice(&body).unwrap();
+        let expected = vec!["v1".to_string(), "v2".to_string()];
+        assert_eq!(Some(&expected[..]), system_info.deployments());
+    }
+
     #[test]
     fn system_info_failed() {
         // arrange
         let runtime = TestRuntime::new(Err(Error::General));
-        let handler = GetSystemInfo::new(runtime);
+        let handler = GetSystemInfo::new(runtime, TestHost);
         let request = Request::get("http://localhost/modules")
             .body(Body::default())
             .unwrap();
@@ -1,4 +1,6 @@
 // Copyright (c) Microsoft. All rights reserved.
+mod disk;
 mod get;
 
+pub use self::disk::GetDiskUsage;
 pub use self::get::GetSystemInfo;
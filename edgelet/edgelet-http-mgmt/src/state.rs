@@ -0,0 +1,137 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_core::{
+    CaRotation, CertificateRegistry, DeploymentHistory, HostProcesses, IdempotencyKeys, KeySlots,
+    Operations, Outbox, Scheduler, StateBackup, WorkloadUsage,
+};
+use management::models::ModuleSpec;
+
+/// The state of the daemon that the management API serves and changes.
+///
+/// Most of it is shared with the rest of the daemon, such as the certificates
+/// the workload API issued or the schedules it runs, so the daemon creates it
+/// and hands a clone to the management API. Whatever isn't set with `with_*`
+/// starts out empty, and is only seen by the management API.
+#[derive(Clone)]
+pub struct ManagementState {
+    history: DeploymentHistory<ModuleSpec>,
+    scheduler: Scheduler,
+    rotation: CaRotation,
+    idempotency: IdempotencyKeys,
+    registry: CertificateRegistry,
+    slots: KeySlots,
+    outbox: Outbox,
+    operations: Operations,
+    host_processes: HostProcesses,
+    usage: WorkloadUsage,
+    backup: Option<StateBackup>,
+}
+
+impl ManagementState {
+    pub fn new(
+        history: DeploymentHistory<ModuleSpec>,
+        scheduler: Scheduler,
+        rotation: CaRotation,
+        idempotency: IdempotencyKeys,
+    ) -> Self {
+        ManagementState {
+            history,
+            scheduler,
+            rotation,
+            idempotency,
+            registry: CertificateRegistry::default(),
+            slots: KeySlots::default(),
+            outbox: Outbox::default(),
+            operations: Operations::default(),
+            host_processes: HostProcesses::default(),
+            usage: WorkloadUsage::default(),
+            backup: None,
+        }
+    }
+
+    /// Lists and revokes the certificates of `registry`, and the keys of
+    /// `slots` they were issued for.
+    pub fn with_certificates(mut self, registry: CertificateRegistry, slots: KeySlots) -> Self {
+        self.registry = registry;
+        self.slots = slots;
+        self
+    }
+
+    /// Queues the revocations of certificates in `outbox`, to be sent to IoT
+    /// Hub along with the telemetry of the daemon.
+    pub fn with_outbox(mut self, outbox: Outbox) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// Tracks the image pulls and deployments that are started in the
+    /// background in `operations`.
+    pub fn with_operations(mut self, operations: Operations) -> Self {
+        self.operations = operations;
+        self
+    }
+
+    /// Registers the processes of the host that are allowed to call the
+    /// workload API in `host_processes`.
+    pub fn with_host_processes(mut self, host_processes: HostProcesses) -> Self {
+        self.host_processes = host_processes;
+        self
+    }
+
+    /// Reports how much each module used the workload API from `usage`.
+    pub fn with_usage(mut self, usage: WorkloadUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Backs up and restores the state of the daemon through `backup`.
+    /// Without it, backups and restores are refused.
+    pub fn with_backup(mut self, backup: StateBackup) -> Self {
+        self.backup = Some(backup);
+        self
+    }
+
+    pub fn history(&self) -> &DeploymentHistory<ModuleSpec> {
+        &self.history
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    pub fn rotation(&self) -> &CaRotation {
+        &self.rotation
+    }
+
+    pub fn idempotency(&self) -> &IdempotencyKeys {
+        &self.idempotency
+    }
+
+    pub fn registry(&self) -> &CertificateRegistry {
+        &self.registry
+    }
+
+    pub fn slots(&self) -> &KeySlots {
+        &self.slots
+    }
+
+    pub fn outbox(&self) -> &Outbox {
+        &self.outbox
+    }
+
+    pub fn operations(&self) -> &Operations {
+        &self.operations
+    }
+
+    pub fn host_processes(&self) -> &HostProcesses {
+        &self.host_processes
+    }
+
+    pub fn usage(&self) -> &WorkloadUsage {
+        &self.usage
+    }
+
+    pub fn backup(&self) -> Option<&StateBackup> {
+        self.backup.as_ref()
+    }
+}
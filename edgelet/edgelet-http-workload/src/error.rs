@@ -5,7 +5,8 @@ use std::str::Utf8Error;
 
 use base64::DecodeError;
 use chrono::format::ParseError;
-use edgelet_core::Error as CoreError;
+use edgelet_core::{error_category, Error as CoreError, ErrorCategory};
+use edgelet_http::{category_status_code, classify_error as classify_http_error};
 use edgelet_utils::Error as UtilsError;
 use failure::{Backtrace, Context, Fail};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
@@ -51,6 +52,109 @@ pub enum ErrorKind {
     Utils,
     #[fail(display = "UTF-8 encode/decode")]
     Utf8,
+    #[fail(display = "Certificate request violates the certificate policy")]
+    PolicyViolation,
+    #[fail(display = "The generation ID is no longer current")]
+    StaleGeneration,
+    #[fail(display = "Could not validate the tokens")]
+    TokenValidation,
+    #[fail(display = "The key must be between 1 and 64 bytes long")]
+    BadKeyLength,
+    #[fail(display = "The module has no HSM key slots left")]
+    KeySlotQuota,
+    #[fail(display = "Invalid certificate chain")]
+    InvalidCertificate,
+    #[fail(display = "The sequence number has been accepted before")]
+    SequenceReplayed,
+    #[fail(display = "Invalid subject alternative name")]
+    InvalidSubjectAltName,
+    #[fail(display = "Certificate signing requests are not supported")]
+    CertificateRequestNotSupported,
+    #[fail(display = "Invalid key type")]
+    InvalidKeyType,
+    #[fail(display = "No certificate has been issued to renew")]
+    NoCertificateToRenew,
+    #[fail(display = "Certificate renewal is not supported")]
+    CertificateRenewalNotSupported,
+    #[fail(display = "Certificate revocation lists are not supported")]
+    CrlNotSupported,
+}
+
+impl ErrorKind {
+    /// The HTTP status reported to the caller for an error of this kind.
+    ///
+    /// Failures caused by the request itself (bad path parameters or body,
+    /// arguments that fail validation or dates that don't parse) are client
+    /// errors. Everything else is a failure on our side.
+    pub fn status_code(&self) -> StatusCode {
+        match *self {
+            ErrorKind::BadParam
+            | ErrorKind::BadBody
+            | ErrorKind::Utils
+            | ErrorKind::DateParse
+            | ErrorKind::InvalidCertificate
+            | ErrorKind::InvalidSubjectAltName
+            | ErrorKind::InvalidKeyType => StatusCode::BAD_REQUEST,
+            ErrorKind::NotFound | ErrorKind::NoCertificateToRenew => StatusCode::NOT_FOUND,
+            ErrorKind::PolicyViolation => StatusCode::FORBIDDEN,
+            ErrorKind::StaleGeneration => StatusCode::GONE,
+            ErrorKind::SequenceReplayed => StatusCode::CONFLICT,
+            ErrorKind::Base64 | ErrorKind::BadKeyLength => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorKind::KeySlotQuota => StatusCode::INSUFFICIENT_STORAGE,
+            ErrorKind::CertificateRequestNotSupported
+            | ErrorKind::CertificateRenewalNotSupported
+            | ErrorKind::CrlNotSupported => StatusCode::NOT_IMPLEMENTED,
+            ErrorKind::KeyStore
+            | ErrorKind::Serde
+            | ErrorKind::Hyper
+            | ErrorKind::Http
+            | ErrorKind::BadPrivateKey
+            | ErrorKind::Sign
+            | ErrorKind::TokenValidation
+            | ErrorKind::Utf8 => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// leave it to their cause.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::BadParam
+            | ErrorKind::BadBody
+            | ErrorKind::BadPrivateKey
+            | ErrorKind::NotFound
+            | ErrorKind::Base64
+            | ErrorKind::DateParse
+            | ErrorKind::Utils
+            | ErrorKind::PolicyViolation
+            | ErrorKind::StaleGeneration
+            | ErrorKind::BadKeyLength
+            | ErrorKind::InvalidCertificate
+            | ErrorKind::SequenceReplayed
+            | ErrorKind::InvalidSubjectAltName
+            | ErrorKind::InvalidKeyType
+            | ErrorKind::NoCertificateToRenew
+            | ErrorKind::CertificateRequestNotSupported
+            | ErrorKind::CertificateRenewalNotSupported
+            | ErrorKind::CrlNotSupported => Some(ErrorCategory::UserConfig),
+            ErrorKind::KeyStore | ErrorKind::KeySlotQuota => Some(ErrorCategory::Hsm),
+            ErrorKind::Sign
+            | ErrorKind::Serde
+            | ErrorKind::Hyper
+            | ErrorKind::Http
+            | ErrorKind::Utf8
+            | ErrorKind::TokenValidation => None,
+        }
+    }
+}
+
+/// Categorizes the errors of this crate, and falls back to
+/// `edgelet_http::classify_error`.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    match error.downcast_ref::<Error>() {
+        Some(error) => error.kind().category(),
+        None => classify_http_error(error),
+    }
 }
 
 impl Fail for Error {
@@ -73,6 +177,20 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
+
+    /// The HTTP status reported to the caller for this error. Errors of the
+    /// kinds that are a failure on our side are reported by the category of
+    /// what caused them instead.
+    pub fn status_code(&self) -> StatusCode {
+        match self.kind().status_code() {
+            StatusCode::INTERNAL_SERVER_ERROR => category_status_code(self.category()),
+            status_code => status_code,
+        }
+    }
 }
 
 impl From<ErrorKind> for Error {
@@ -162,22 +280,18 @@ impl IntoResponse for Error {
             fail = cause;
         }
 
-        let status_code = match *self.kind() {
-            ErrorKind::NotFound => StatusCode::NOT_FOUND,
-            ErrorKind::BadParam | ErrorKind::BadBody => StatusCode::BAD_REQUEST,
-            ErrorKind::Base64 => StatusCode::UNPROCESSABLE_ENTITY,
-            _ => {
-                error!("Internal server error: {}", message);
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        };
+        let status_code = self.status_code();
+        if status_code.is_server_error() {
+            error!("Internal server error: {}", message);
+        }
 
         // Per the RFC, status code NotModified should not have a body
         let body = if status_code == StatusCode::NOT_MODIFIED {
             None
         } else {
-            let b = serde_json::to_string(&ErrorResponse::new(message))
-                .expect("serialization of ErrorResponse failed.");
+            let response = ErrorResponse::new(message).with_category(self.category().to_string());
+            let b =
+                serde_json::to_string(&response).expect("serialization of ErrorResponse failed.");
             Some(b)
         };
 
@@ -17,6 +17,7 @@ extern crate chrono;
 extern crate edgelet_core;
 #[macro_use]
 extern crate edgelet_http;
+#[cfg(feature = "mgmt")]
 extern crate edgelet_http_mgmt;
 #[cfg(test)]
 extern crate edgelet_test_utils;
@@ -30,8 +31,15 @@ extern crate http;
 extern crate hyper;
 #[macro_use]
 extern crate log;
+#[cfg(test)]
+extern crate openssl;
 extern crate serde;
 extern crate serde_json;
+#[cfg(test)]
+extern crate tempdir;
+extern crate tokio;
+extern crate tokio_threadpool;
+extern crate url;
 extern crate workload;
 
 use http::Response;
@@ -40,7 +48,7 @@ use hyper::Body;
 mod error;
 mod server;
 
-pub use server::WorkloadService;
+pub use server::{WorkloadLimits, WorkloadService};
 
 pub trait IntoResponse {
     fn into_response(self) -> Response<Body>;
@@ -0,0 +1,392 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Error as HttpError, Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde::de::DeserializeOwned;
+use serde_json;
+use url::form_urlencoded;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+
+use edgelet_core::{CertificateType, GenerationId, ModuleName, WorkloadConfig};
+use edgelet_http::route::{Handler, Parameters};
+use workload::models::{
+    BootstrapResponse, CertificateResponse, IdentityCertificateRequest, ServerCertificateRequest,
+    TrustBundleResponse,
+};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+type Call<B> = Box<Future<Item = Result<B, Response<Body>>, Error = HyperError> + Send>;
+
+/// Serves a module in one response what it fetches from the workload API
+/// when it starts: the trust bundle, its identity certificate and, if it
+/// asks for one with `serverCommonName`, a server certificate, along with
+/// the IDs of the hub, the device and the module. Every module fetching
+/// these one after the other when the device reboots keeps the daemon busy
+/// for a while.
+///
+/// The parts are fetched from the handlers of their own routes, so they are
+/// held to the same policies and counted the same way. A request that one
+/// of them fails gets its response.
+pub struct BootstrapHandler<W> {
+    config: W,
+    trust_bundle: Arc<Handler<Parameters> + Sync>,
+    identity: Arc<Handler<Parameters> + Sync>,
+    server: Arc<Handler<Parameters> + Sync>,
+}
+
+impl<W: WorkloadConfig> BootstrapHandler<W> {
+    pub fn new<T, I, S>(config: W, trust_bundle: T, identity: I, server: S) -> Self
+    where
+        T: Handler<Parameters> + Sync,
+        I: Handler<Parameters> + Sync,
+        S: Handler<Parameters> + Sync,
+    {
+        BootstrapHandler {
+            config,
+            trust_bundle: Arc::new(trust_bundle),
+            identity: Arc::new(identity),
+            server: Arc::new(server),
+        }
+    }
+}
+
+impl<W> Handler<Parameters> for BootstrapHandler<W>
+where
+    W: WorkloadConfig + Send + Sync + 'static,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let (module_id, generation_id) = match module(&params) {
+            Ok(ids) => ids,
+            Err(err) => return Box::new(future::ok(err.into_response())),
+        };
+        let (common_name, expiration) = query(&req);
+
+        let trust_bundle = call::<TrustBundleResponse>(
+            &*self.trust_bundle,
+            Request::get("/trust-bundle").body(Body::empty()),
+            Parameters::new(),
+            StatusCode::OK,
+        );
+
+        let identity_req = match expiration {
+            Some(ref expiration) => {
+                IdentityCertificateRequest::new().with_expiration(expiration.clone())
+            }
+            None => IdentityCertificateRequest::new(),
+        };
+        // module names may have characters that aren't allowed in a path
+        let module_path = format!(
+            "/modules/{}",
+            utf8_percent_encode(&module_id, PATH_SEGMENT_ENCODE_SET)
+        );
+        let identity = call::<CertificateResponse>(
+            &*self.identity,
+            json_request(
+                &format!("{}/certificate/identity", module_path),
+                &identity_req,
+            ),
+            Parameters::with_captures(vec![(Some("name".to_string()), module_id.clone())]),
+            StatusCode::CREATED,
+        );
+
+        let server = match common_name {
+            Some(common_name) => {
+                // the server certificate has to expire, so it is valid for as
+                // long as the policy allows unless the module says otherwise
+                let expiration = expiration.unwrap_or_else(|| {
+                    let max_duration = self
+                        .config
+                        .get_cert_policy(CertificateType::Server)
+                        .max_duration();
+                    (Utc::now() + Duration::seconds(max_duration)).to_rfc3339()
+                });
+                let server_req = ServerCertificateRequest::new(common_name, expiration);
+                let server = call::<CertificateResponse>(
+                    &*self.server,
+                    json_request(
+                        &format!("{}/genid/{}/certificate/server", module_path, generation_id),
+                        &server_req,
+                    ),
+                    Parameters::with_captures(vec![
+                        (Some("name".to_string()), module_id.clone()),
+                        (Some("genid".to_string()), generation_id.clone()),
+                    ]),
+                    StatusCode::CREATED,
+                );
+                future::Either::A(server.map(|result| result.map(Some)))
+            }
+            None => future::Either::B(future::ok(Ok(None))),
+        };
+
+        let iot_hub_name = self.config.iot_hub_name().to_string();
+        let device_id = self.config.device_id().to_string();
+        let response = trust_bundle
+            .join3(identity, server)
+            .map(move |results| match results {
+                (Ok(trust_bundle), Ok(identity), Ok(server)) => {
+                    let body = BootstrapResponse::new(
+                        iot_hub_name,
+                        device_id,
+                        module_id,
+                        generation_id,
+                        trust_bundle,
+                        identity,
+                    );
+                    let body = match server {
+                        Some(server) => body.with_server_certificate(server),
+                        None => body,
+                    };
+                    json_response(&body).unwrap_or_else(|e| e.into_response())
+                }
+                (Err(response), _, _) | (_, Err(response), _) | (_, _, Err(response)) => response,
+            });
+
+        Box::new(response)
+    }
+}
+
+fn module(params: &Parameters) -> Result<(String, String), Error> {
+    let module_id = params
+        .name("name")
+        .ok_or_else(|| Error::from(ErrorKind::BadParam))?;
+    let generation_id = params
+        .name("genid")
+        .ok_or_else(|| Error::from(ErrorKind::BadParam))?;
+    let module_id = ModuleName::new(module_id).context(ErrorKind::BadParam)?;
+    let generation_id = GenerationId::new(generation_id).context(ErrorKind::BadParam)?;
+    Ok((module_id.to_string(), generation_id.to_string()))
+}
+
+/// The common name of the server certificate and the expiration of the
+/// certificates the module asked for.
+fn query(req: &Request<Body>) -> (Option<String>, Option<String>) {
+    let mut common_name = None;
+    let mut expiration = None;
+    for (key, value) in form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes()) {
+        match &*key {
+            "serverCommonName" => common_name = Some(value.into_owned()),
+            "expiration" => expiration = Some(value.into_owned()),
+            _ => (),
+        }
+    }
+    (common_name, expiration)
+}
+
+fn json_request<B: ::serde::Serialize>(path: &str, body: &B) -> Result<Request<Body>, HttpError> {
+    // the body of a model always serializes
+    let body = serde_json::to_string(body).unwrap_or_default();
+    Request::post(path)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.into())
+}
+
+// Resolves to the body of the response of `handler`, or to the response
+// itself if it doesn't have the status `expected`.
+fn call<B>(
+    handler: &Handler<Parameters>,
+    req: Result<Request<Body>, HttpError>,
+    params: Parameters,
+    expected: StatusCode,
+) -> Call<B>
+where
+    B: DeserializeOwned + Send + 'static,
+{
+    let req = match req {
+        Ok(req) => req,
+        Err(err) => return Box::new(future::ok(Err(Error::from(err).into_response()))),
+    };
+    let response = handler.handle(req, params).and_then(move |response| {
+        if response.status() != expected {
+            return future::Either::A(future::ok(Err(response)));
+        }
+        let body = response.into_body().concat2().map(|body| {
+            serde_json::from_slice::<B>(&body)
+                .context(ErrorKind::Serde)
+                .map_err(|err| Error::from(err).into_response())
+        });
+        future::Either::B(body)
+    });
+    Box::new(response)
+}
+
+fn json_response(body: &BootstrapResponse) -> Result<Response<Body>, Error> {
+    let b = serde_json::to_string(body).context(ErrorKind::Serde)?;
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+        .body(b.into())
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use edgelet_core::CertPolicy;
+    use workload::models::{ErrorResponse, PrivateKey};
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct TestConfig;
+
+    impl WorkloadConfig for TestConfig {
+        fn iot_hub_name(&self) -> &str {
+            "zaphods_hub"
+        }
+
+        fn device_id(&self) -> &str {
+            "marvins_device"
+        }
+
+        fn get_cert_max_duration(&self, _cert_type: CertificateType) -> i64 {
+            3600
+        }
+
+        fn get_cert_policy(&self, _cert_type: CertificateType) -> CertPolicy {
+            CertPolicy::new().with_max_duration(3600)
+        }
+    }
+
+    // Responds with `status` and `body`, and keeps the requests it handled.
+    #[derive(Clone)]
+    struct TestHandler {
+        status: StatusCode,
+        body: String,
+        requests: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl TestHandler {
+        fn new<B: ::serde::Serialize>(status: StatusCode, body: &B) -> Self {
+            TestHandler {
+                status,
+                body: serde_json::to_string(body).unwrap(),
+                requests: Arc::new(Mutex::new(vec![])),
+            }
+        }
+
+        fn requests(&self) -> Vec<(String, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Handler<Parameters> for TestHandler {
+        fn handle(
+            &self,
+            req: Request<Body>,
+            _params: Parameters,
+        ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+            let requests = self.requests.clone();
+            let path = req.uri().path().to_string();
+            let response = Response::builder()
+                .status(self.status)
+                .body(self.body.clone().into())
+                .unwrap();
+            Box::new(req.into_body().concat2().map(move |body| {
+                let body = String::from_utf8(body.to_vec()).unwrap();
+                requests.lock().unwrap().push((path, body));
+                response
+            }))
+        }
+    }
+
+    fn certificate(pem: &str) -> CertificateResponse {
+        CertificateResponse::new(
+            PrivateKey::from_ref(pem.to_string()),
+            pem.to_string(),
+            "2018-10-15T12:00:00Z".to_string(),
+        )
+    }
+
+    fn bootstrap(handler: &BootstrapHandler<TestConfig>, query: &str) -> Response<Body> {
+        let request = Request::get(format!(
+            "http://localhost/modules/sensor/genid/I/bootstrap{}",
+            query
+        )).body(Body::empty())
+        .unwrap();
+        let params = Parameters::with_captures(vec![
+            (Some("name".to_string()), "sensor".to_string()),
+            (Some("genid".to_string()), "I".to_string()),
+        ]);
+        handler.handle(request, params).wait().unwrap()
+    }
+
+    #[test]
+    fn trust_material_is_served_in_one_response() {
+        let trust_bundle = TestHandler::new(
+            StatusCode::OK,
+            &TrustBundleResponse::new("roots".to_string()).with_version(3),
+        );
+        let identity = TestHandler::new(StatusCode::CREATED, &certificate("identity"));
+        let server = TestHandler::new(StatusCode::CREATED, &certificate("server"));
+        let handler =
+            BootstrapHandler::new(TestConfig, trust_bundle, identity.clone(), server.clone());
+
+        let response = bootstrap(&handler, "");
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let body: BootstrapResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!("zaphods_hub", body.iot_hub_name());
+        assert_eq!("marvins_device", body.device_id());
+        assert_eq!("sensor", body.module_id());
+        assert_eq!("I", body.generation_id());
+        assert_eq!("roots", body.trust_bundle().certificate());
+        assert_eq!(Some(&3), body.trust_bundle().version());
+        assert_eq!("identity", body.identity_certificate().certificate());
+        assert!(body.server_certificate().is_none());
+        assert_eq!(
+            vec![(
+                "/modules/sensor/certificate/identity".to_string(),
+                "{}".to_string()
+            )],
+            identity.requests()
+        );
+        assert!(server.requests().is_empty());
+
+        let response = bootstrap(
+            &handler,
+            "?serverCommonName=sensor.local&expiration=2018-10-15T12%3A00%3A00Z",
+        );
+        let body = response.into_body().concat2().wait().unwrap();
+        let body: BootstrapResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!("server", body.server_certificate().unwrap().certificate());
+        let (path, request) = server.requests().pop().unwrap();
+        assert_eq!("/modules/sensor/genid/I/certificate/server", path);
+        let request: ServerCertificateRequest = serde_json::from_str(&request).unwrap();
+        assert_eq!("sensor.local", request.common_name());
+        assert_eq!("2018-10-15T12:00:00Z", request.expiration());
+    }
+
+    #[test]
+    fn failures_of_the_parts_are_passed_on() {
+        let trust_bundle = TestHandler::new(
+            StatusCode::OK,
+            &TrustBundleResponse::new("roots".to_string()),
+        );
+        let identity = TestHandler::new(StatusCode::CREATED, &certificate("identity"));
+        let server = TestHandler::new(
+            StatusCode::FORBIDDEN,
+            &ErrorResponse::new("policy violation".to_string()),
+        );
+        let handler = BootstrapHandler::new(TestConfig, trust_bundle, identity, server);
+
+        let response = bootstrap(&handler, "?serverCommonName=trillian");
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!("policy violation", error.message());
+    }
+}
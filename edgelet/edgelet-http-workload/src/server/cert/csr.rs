@@ -0,0 +1,371 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Arc;
+
+use super::{compute_validity, san_entries};
+use chrono::{DateTime, Utc};
+use failure::{Fail, ResultExt};
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+
+use edgelet_core::{
+    CertPolicy, Certificate, CertificateAlias, CertificatePolicy, CertificateProperties,
+    CertificateRegistry, CertificateRequest, CertificateType, Clock, CreateCertificate,
+    ErrorKind as CoreErrorKind, GenerationId, GenerationRegistry, ModuleName, SystemClock,
+    WorkloadConfig,
+};
+use edgelet_http::route::{Handler, Parameters};
+use edgelet_utils::prepare_cert_uri_module;
+use workload::models::{SignCertificateRequest, SignCertificateResponse};
+
+use error::{Error, ErrorKind, Result};
+use IntoResponse;
+
+/// Issues server certificates for the public keys of certificate signing
+/// requests, for modules that generate their keys themselves, such as in a
+/// TPM or a TEE, and so never receive a private key from the daemon.
+///
+/// The certificate is issued under the same certificate policy and
+/// certificate type policy as those of `ServerCertHandler`. The HSM doesn't
+/// hold its key, so it doesn't take up a key slot of the module.
+pub struct CsrCertHandler<T: CreateCertificate, W: WorkloadConfig> {
+    hsm: T,
+    config: W,
+    registry: CertificateRegistry,
+    clock: Arc<Clock>,
+    policy: CertificatePolicy,
+    generations: GenerationRegistry,
+}
+
+impl<T: CreateCertificate, W: WorkloadConfig> CsrCertHandler<T, W> {
+    pub fn new(hsm: T, config: W) -> Self {
+        CsrCertHandler {
+            hsm,
+            config,
+            registry: CertificateRegistry::new(),
+            clock: Arc::new(SystemClock),
+            policy: CertificatePolicy::default(),
+            generations: GenerationRegistry::new(),
+        }
+    }
+
+    pub fn with_registry(mut self, registry: CertificateRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Computes how long requested certificates are valid for from the time
+    /// of `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: CertificatePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+}
+
+impl<T, W> Handler<Parameters> for CsrCertHandler<T, W>
+where
+    T: CreateCertificate + Clone + Send + Sync + 'static,
+    <T as CreateCertificate>::Certificate: Certificate,
+    W: WorkloadConfig + Clone + Send + Sync + 'static,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let hsm = self.hsm.clone();
+        let cfg = self.config.clone();
+        let registry = self.registry.clone();
+        let clock = self.clock.clone();
+        let policy = self.policy.clone();
+        let generations = self.generations.clone();
+        let cert_policy = cfg.get_cert_policy(CertificateType::Server);
+        let max_duration = cert_policy.max_duration();
+
+        let response = match (params.name("name"), params.name("genid")) {
+            (Some(module_id), Some(genid)) => {
+                let (module_name, genid, alias) = match ModuleName::new(module_id)
+                    .and_then(|name| GenerationId::new(genid).map(|genid| (name, genid)))
+                    .context(ErrorKind::BadParam)
+                {
+                    Ok((name, genid)) => (
+                        name.to_string(),
+                        genid.to_string(),
+                        CertificateAlias::server(name.as_str(), genid.as_str()),
+                    ),
+                    Err(err) => return Box::new(future::ok(Error::from(err).into_response())),
+                };
+                let module_uri =
+                    prepare_cert_uri_module(cfg.iot_hub_name(), cfg.device_id(), &module_name);
+                let result = req
+                    .into_body()
+                    .concat2()
+                    .map(move |body| {
+                        serde_json::from_slice::<SignCertificateRequest>(&body)
+                            .context(ErrorKind::BadBody)
+                            .map_err(Error::from)
+                            .and_then(|sign_req| {
+                                compute_validity(
+                                    ensure_not_empty!(sign_req.expiration()).as_str(),
+                                    max_duration,
+                                    clock.now(),
+                                ).map(|expiration| (sign_req, expiration))
+                            }).and_then(move |(sign_req, expiration)| {
+                                let request = CertificateRequest::from_pem(
+                                    ensure_not_empty!(sign_req.csr()).as_bytes(),
+                                ).context(ErrorKind::BadBody)?;
+                                policy
+                                    .check(&module_name, &[request.common_name()])
+                                    .context(ErrorKind::PolicyViolation)?;
+                                cert_policy
+                                    .check_key_algorithm(request.key_algorithm())
+                                    .context(ErrorKind::PolicyViolation)?;
+                                #[cfg_attr(feature = "cargo-clippy", allow(cast_sign_loss))]
+                                let props = CertificateProperties::new(
+                                    ensure_range!(expiration, 0, max_duration) as u64,
+                                    request.common_name().to_string(),
+                                    CertificateType::Server,
+                                    alias.to_string(),
+                                );
+                                let sans = san_entries(
+                                    cert_policy.san().unwrap_or_default(),
+                                    module_uri,
+                                    request.common_name(),
+                                );
+                                let props = if sans.is_empty() {
+                                    props
+                                } else {
+                                    props.with_san_entries(sans)
+                                };
+                                generations
+                                    .with_current(&module_name, &genid, || {
+                                        sign_cert(
+                                            &hsm,
+                                            &registry,
+                                            &module_name,
+                                            &request,
+                                            &props,
+                                            &cert_policy,
+                                            clock.now(),
+                                        )
+                                    }).context(ErrorKind::StaleGeneration)?
+                            }).unwrap_or_else(|e| e.into_response())
+                    }).map_err(Error::from)
+                    .or_else(|e| future::ok(e.into_response()));
+
+                future::Either::A(result)
+            }
+
+            (None, _) | (_, None) => {
+                future::Either::B(future::ok(Error::from(ErrorKind::BadParam).into_response()))
+            }
+        };
+
+        Box::new(response)
+    }
+}
+
+fn sign_cert<T: CreateCertificate>(
+    hsm: &T,
+    registry: &CertificateRegistry,
+    module_id: &str,
+    request: &CertificateRequest,
+    props: &CertificateProperties,
+    policy: &CertPolicy,
+    issued_at: DateTime<Utc>,
+) -> Result<Response<Body>> {
+    let cert = hsm
+        .sign_certificate_request(request, props)
+        .map_err(|err| match *err.kind() {
+            CoreErrorKind::CertificateRequestNotSupported => {
+                Error::from(err.context(ErrorKind::CertificateRequestNotSupported))
+            }
+            _ => Error::from(err),
+        })?;
+    let pem = cert.pem()?;
+    let expiration = cert.get_valid_to()?;
+    if let Err(err) = registry.record(module_id, props.alias(), pem.as_ref(), expiration) {
+        warn!(
+            "Could not record certificate {} issued to module {}: {}",
+            props.alias(),
+            module_id,
+            err
+        );
+    }
+
+    let response = SignCertificateResponse::new(
+        String::from_utf8_lossy(pem.as_ref()).to_string(),
+        expiration.to_rfc3339(),
+    );
+    let response = match policy.renew_at(issued_at, expiration) {
+        Some(renew_at) => response.with_renew_at(renew_at.to_rfc3339()),
+        None => response,
+    };
+    let body = serde_json::to_string(&response)?;
+    Response::builder()
+        .status(StatusCode::CREATED)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, body.len().to_string().as_str())
+        .body(body.into())
+        .map_err(From::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::result::Result as StdResult;
+
+    use chrono::Duration;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::x509::{X509NameBuilder, X509ReqBuilder};
+
+    use super::*;
+    use edgelet_core::{Error as CoreError, KeyAlgorithm};
+    use edgelet_test_utils::cert::{TestCert, TEST_CERT_PEM};
+    use workload::models::ErrorResponse;
+
+    #[derive(Clone)]
+    struct TestHsm {
+        supported: bool,
+    }
+
+    impl CreateCertificate for TestHsm {
+        type Certificate = TestCert;
+
+        fn create_certificate(
+            &self,
+            _properties: &CertificateProperties,
+        ) -> StdResult<Self::Certificate, CoreError> {
+            panic!("the HSM should not generate a key")
+        }
+
+        fn destroy_certificate(&self, _alias: String) -> StdResult<(), CoreError> {
+            Ok(())
+        }
+
+        fn sign_certificate_request(
+            &self,
+            request: &CertificateRequest,
+            properties: &CertificateProperties,
+        ) -> StdResult<Self::Certificate, CoreError> {
+            if !self.supported {
+                return Err(CoreError::from(
+                    CoreErrorKind::CertificateRequestNotSupported,
+                ));
+            }
+            assert_eq!("sensor", request.common_name());
+            assert_eq!("sensor", properties.common_name());
+            assert_eq!("sensor/I/server", properties.alias());
+            Ok(TestCert::default().with_cert(TEST_CERT_PEM.as_bytes().to_vec()))
+        }
+    }
+
+    #[derive(Clone)]
+    struct TestWorkloadConfig {
+        cert_policy: CertPolicy,
+    }
+
+    impl WorkloadConfig for TestWorkloadConfig {
+        fn iot_hub_name(&self) -> &str {
+            "zaphods_hub"
+        }
+
+        fn device_id(&self) -> &str {
+            "marvins_device"
+        }
+
+        fn get_cert_max_duration(&self, _cert_type: CertificateType) -> i64 {
+            7200
+        }
+
+        fn get_cert_policy(&self, _cert_type: CertificateType) -> CertPolicy {
+            self.cert_policy.clone().capped(7200)
+        }
+    }
+
+    fn csr() -> String {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "sensor").unwrap();
+        let mut req = X509ReqBuilder::new().unwrap();
+        req.set_subject_name(&name.build()).unwrap();
+        req.set_pubkey(&key).unwrap();
+        req.sign(&key, MessageDigest::sha256()).unwrap();
+        String::from_utf8(req.build().to_pem().unwrap()).unwrap()
+    }
+
+    fn sign(handler: &CsrCertHandler<TestHsm, TestWorkloadConfig>, csr: String) -> Response<Body> {
+        let sign_req =
+            SignCertificateRequest::new(csr, (Utc::now() + Duration::hours(1)).to_rfc3339());
+        let request = Request::post("http://localhost/modules/sensor/genid/I/certificate/csr")
+            .body(serde_json::to_string(&sign_req).unwrap().into())
+            .unwrap();
+        let params = Parameters::with_captures(vec![
+            (Some("name".to_string()), "sensor".to_string()),
+            (Some("genid".to_string()), "I".to_string()),
+        ]);
+        handler.handle(request, params).wait().unwrap()
+    }
+
+    fn handler(
+        supported: bool,
+        cert_policy: CertPolicy,
+    ) -> CsrCertHandler<TestHsm, TestWorkloadConfig> {
+        CsrCertHandler::new(TestHsm { supported }, TestWorkloadConfig { cert_policy })
+    }
+
+    #[test]
+    fn certificates_are_issued_for_the_key_of_the_request() {
+        let registry = CertificateRegistry::new();
+        let handler = handler(true, CertPolicy::new()).with_registry(registry.clone());
+
+        let response = sign(&handler, csr());
+        assert_eq!(StatusCode::CREATED, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let cert: SignCertificateResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(TEST_CERT_PEM, cert.certificate());
+        assert!(registry.revoke("1A2B3C4D").is_ok());
+    }
+
+    #[test]
+    fn requests_are_held_to_the_policies() {
+        let rsa_only = handler(
+            true,
+            CertPolicy::new().with_key_algorithms(vec![KeyAlgorithm::Rsa]),
+        );
+        assert_eq!(StatusCode::FORBIDDEN, sign(&rsa_only, csr()).status());
+
+        let gateway_only = handler(true, CertPolicy::new()).with_policy(
+            CertificatePolicy::new().with_allowed_names("sensor", vec!["gateway".to_string()]),
+        );
+        assert_eq!(StatusCode::FORBIDDEN, sign(&gateway_only, csr()).status());
+
+        let csr = "-----BEGIN CERTIFICATE REQUEST-----".to_string();
+        assert_eq!(StatusCode::BAD_REQUEST, sign(&gateway_only, csr).status());
+    }
+
+    #[test]
+    fn hsms_that_cannot_sign_requests_are_reported() {
+        let response = sign(&handler(false, CertPolicy::new()), csr());
+        assert_eq!(StatusCode::NOT_IMPLEMENTED, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(error.message().contains("not supported"));
+    }
+}
@@ -1,6 +1,8 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use super::{compute_validity, refresh_cert};
+use std::sync::Arc;
+
+use super::{blocking, compute_validity, refresh_cert, san_entries, AliasLocks};
 use failure::ResultExt;
 use futures::{future, Future, Stream};
 use http::{Request, Response};
@@ -8,7 +10,8 @@ use hyper::{Body, Error as HyperError};
 use serde_json;
 
 use edgelet_core::{
-    Certificate, CertificateProperties, CertificateType, CreateCertificate, WorkloadConfig,
+    Certificate, CertificateAlias, CertificateProperties, CertificateRegistry, CertificateType,
+    Clock, CreateCertificate, KeySlots, ModuleName, SanPolicy, SystemClock, WorkloadConfig,
 };
 use edgelet_http::route::{Handler, Parameters};
 use edgelet_utils::prepare_cert_uri_module;
@@ -20,11 +23,47 @@ use IntoResponse;
 pub struct IdentityCertHandler<T: CreateCertificate, W: WorkloadConfig> {
     hsm: T,
     config: W,
+    locks: AliasLocks,
+    registry: CertificateRegistry,
+    slots: KeySlots,
+    clock: Arc<Clock>,
 }
 
 impl<T: CreateCertificate, W: WorkloadConfig> IdentityCertHandler<T, W> {
     pub fn new(hsm: T, config: W) -> Self {
-        IdentityCertHandler { hsm, config }
+        IdentityCertHandler {
+            hsm,
+            config,
+            locks: AliasLocks::new(),
+            registry: CertificateRegistry::new(),
+            slots: KeySlots::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Serializes the certificate operations on each alias with those of the
+    /// other handlers that share `locks`.
+    pub fn with_locks(mut self, locks: AliasLocks) -> Self {
+        self.locks = locks;
+        self
+    }
+
+    pub fn with_registry(mut self, registry: CertificateRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Holds modules to the HSM key slots they are allowed in `slots`.
+    pub fn with_slots(mut self, slots: KeySlots) -> Self {
+        self.slots = slots;
+        self
+    }
+
+    /// Computes how long requested certificates are valid for from the time
+    /// of `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
     }
 }
 
@@ -41,37 +80,72 @@ where
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let hsm = self.hsm.clone();
         let cfg = self.config.clone();
-        let max_duration = cfg.get_cert_max_duration(CertificateType::Client);
+        let locks = self.locks.clone();
+        let registry = self.registry.clone();
+        let slots = self.slots.clone();
+        let clock = self.clock.clone();
+        let policy = cfg.get_cert_policy(CertificateType::Client);
+        let max_duration = policy.max_duration();
+        // identity certificates carry the URI of their module unless the
+        // policy says otherwise
+        let san = policy
+            .san()
+            .unwrap_or_else(|| SanPolicy::new().with_module_uri(true));
 
         let response = match params.name("name") {
             Some(module_id) => {
+                let module_id = match ModuleName::new(module_id).context(ErrorKind::BadParam) {
+                    Ok(module_id) => module_id,
+                    Err(err) => return Box::new(future::ok(Error::from(err).into_response())),
+                };
                 let cn = module_id.to_string();
-                let alias = format!("{}identity", module_id);
-                let module_uri =
-                    prepare_cert_uri_module(cfg.iot_hub_name(), cfg.device_id(), module_id);
+                let module_name = module_id.to_string();
+                let alias = CertificateAlias::identity(module_id.as_str());
+                let module_uri = prepare_cert_uri_module(
+                    cfg.iot_hub_name(),
+                    cfg.device_id(),
+                    module_id.as_str(),
+                );
                 let result = req
                     .into_body()
                     .concat2()
-                    .map(move |body| {
-                        serde_json::from_slice::<IdentityCertificateRequest>(&body)
-                            .context(ErrorKind::BadBody)
-                            .map_err(Error::from)
-                            .and_then(|cert_req| {
-                                cert_req.expiration().map_or_else(
-                                    || Ok(max_duration),
-                                    |exp| compute_validity(exp, max_duration).map_err(Error::from),
-                                )
-                            }).and_then(move |expiration| {
-                                let sans = vec![module_uri];
-                                #[cfg_attr(feature = "cargo-clippy", allow(cast_sign_loss))]
-                                let props = CertificateProperties::new(
-                                    ensure_range!(expiration, 0, max_duration) as u64,
-                                    ensure_not_empty!(cn),
-                                    CertificateType::Client,
-                                    alias.clone(),
-                                ).with_san_entries(sans);
-                                refresh_cert(&hsm, alias, &props)
-                            }).unwrap_or_else(|e| e.into_response())
+                    .and_then(move |body| {
+                        blocking(move || {
+                            serde_json::from_slice::<IdentityCertificateRequest>(&body)
+                                .context(ErrorKind::BadBody)
+                                .map_err(Error::from)
+                                .and_then(|cert_req| {
+                                    cert_req.expiration().map_or_else(
+                                        || Ok(max_duration),
+                                        |exp| {
+                                            compute_validity(exp, max_duration, clock.now())
+                                                .map_err(Error::from)
+                                        },
+                                    )
+                                }).and_then(move |expiration| {
+                                    let sans = san_entries(san, module_uri, &cn);
+                                    #[cfg_attr(feature = "cargo-clippy", allow(cast_sign_loss))]
+                                    let props = CertificateProperties::new(
+                                        ensure_range!(expiration, 0, max_duration) as u64,
+                                        ensure_not_empty!(cn),
+                                        CertificateType::Client,
+                                        alias.to_string(),
+                                    ).with_san_entries(sans);
+                                    refresh_cert(
+                                        &hsm,
+                                        &locks,
+                                        &registry,
+                                        &slots,
+                                        &module_name,
+                                        &alias,
+                                        &props,
+                                        &policy,
+                                        clock.now(),
+                                        false,
+                                        None,
+                                    )
+                                }).unwrap_or_else(|e| e.into_response())
+                        })
                     }).map_err(Error::from)
                     .or_else(|e| future::ok(e.into_response()));
 
@@ -97,7 +171,9 @@ mod tests {
         ErrorKind as CoreErrorKind, KeyBytes, PrivateKey, WorkloadConfig,
     };
     use edgelet_test_utils::cert::TestCert;
-    use workload::models::{CertificateResponse, ErrorResponse, IdentityCertificateRequest};
+    use workload::models::{
+        CertificateResponse, ErrorResponse, IdentityCertificateRequest, PrivateKeyType,
+    };
 
     use super::*;
     use http::StatusCode;
@@ -210,12 +286,26 @@ mod tests {
         assert_eq!("Bad parameter", parse_error_response(response).message());
     }
 
+    #[test]
+    fn invalid_name_in_path() {
+        let handler = IdentityCertHandler::new(TestHsm::default(), TestWorkloadData::default());
+        let request = Request::get("http://localhost/modules/..%2f/certificate/identity")
+            .body("{}".into())
+            .unwrap();
+        let params = Parameters::with_captures(vec![(
+            Some("name".to_string()),
+            "../".to_string(),
+        )]);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
     #[test]
     fn succeeds_with_private_key_bytes() {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -245,7 +335,7 @@ mod tests {
             .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
             .wait()
             .unwrap();
-        assert_eq!("key", cert_resp.private_key().type_());
+        assert_eq!(PrivateKeyType::Key, cert_resp.private_key().type_());
         assert_eq!(Some("Betelgeuse"), cert_resp.private_key().bytes());
     }
 
@@ -254,7 +344,7 @@ mod tests {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -283,7 +373,7 @@ mod tests {
             .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
             .wait()
             .unwrap();
-        assert_eq!("ref", cert_resp.private_key().type_());
+        assert_eq!(PrivateKeyType::Ref, cert_resp.private_key().type_());
         assert_eq!(Some("Betelgeuse"), cert_resp.private_key().ref_());
     }
 
@@ -292,7 +382,7 @@ mod tests {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert_eq!(MAX_DURATION_SEC, *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -321,7 +411,7 @@ mod tests {
             .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
             .wait()
             .unwrap();
-        assert_eq!("key", cert_resp.private_key().type_());
+        assert_eq!(PrivateKeyType::Key, cert_resp.private_key().type_());
         assert_eq!(Some("Betelgeuse"), cert_resp.private_key().bytes());
     }
 
@@ -330,7 +420,7 @@ mod tests {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert_eq!(MAX_DURATION_SEC, *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -360,7 +450,7 @@ mod tests {
             .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
             .wait()
             .unwrap();
-        assert_eq!("key", cert_resp.private_key().type_());
+        assert_eq!(PrivateKeyType::Key, cert_resp.private_key().type_());
         assert_eq!(Some("Betelgeuse"), cert_resp.private_key().bytes());
     }
 
@@ -378,7 +468,7 @@ mod tests {
             Parameters::with_captures(vec![(Some("name".to_string()), "beeblebrox".to_string())]);
 
         let response = handler.handle(request, params).wait().unwrap();
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -402,7 +492,7 @@ mod tests {
             Parameters::with_captures(vec![(Some("name".to_string()), "beeblebrox".to_string())]);
 
         let response = handler.handle(request, params).wait().unwrap();
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -426,7 +516,7 @@ mod tests {
             Parameters::with_captures(vec![(Some("name".to_string()), "beeblebrox".to_string())]);
 
         let response = handler.handle(request, params).wait().unwrap();
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -440,7 +530,7 @@ mod tests {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert_eq!(MAX_DURATION_SEC, *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -470,7 +560,7 @@ mod tests {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -500,12 +590,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn exceeding_key_slot_quota_fails() {
+        let slots = KeySlots::new().with_quota(1);
+        slots.reserve("beeblebrox", "beeblebrox/1/server").unwrap();
+        let handler = IdentityCertHandler::new(
+            TestHsm::default().with_on_create(|_| panic!("no certificate should be created")),
+            TestWorkloadData::default(),
+        ).with_slots(slots.clone());
+
+        let request = Request::get("http://localhost/modules/beeblebrox/certificate/identity")
+            .body("{}".into())
+            .unwrap();
+        let params =
+            Parameters::with_captures(vec![(Some("name".to_string()), "beeblebrox".to_string())]);
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::INSUFFICIENT_STORAGE, response.status());
+        assert_ne!(
+            parse_error_response(response)
+                .message()
+                .find("has used all 1 of its HSM key slots"),
+            None
+        );
+        assert_eq!(1, slots.usage()[0].used());
+    }
+
     #[test]
     fn pem_fails() {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -540,7 +656,7 @@ mod tests {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
@@ -575,7 +691,7 @@ mod tests {
         let handler = IdentityCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("beeblebrox", props.common_name());
-                assert_eq!("beeblebroxidentity", props.alias());
+                assert_eq!("beeblebrox/identity", props.alias());
                 assert_eq!(CertificateType::Client, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 let expected_uri = test_module_uri("beeblebrox");
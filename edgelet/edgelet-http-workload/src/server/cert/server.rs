@@ -1,16 +1,25 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use super::{compute_validity, refresh_cert};
+use std::sync::Arc;
+
+use super::{
+    blocking, compute_validity, refresh_cert, requested_key_type, requested_names,
+    reuse_or_refresh_cert, san_entries, AliasLocks, IssuedServerCerts,
+};
 use failure::ResultExt;
 use futures::{future, Future, Stream};
 use http::{Request, Response};
 use hyper::{Body, Error as HyperError};
 use serde_json;
+use url::form_urlencoded;
 
 use edgelet_core::{
-    Certificate, CertificateProperties, CertificateType, CreateCertificate, WorkloadConfig,
+    Certificate, CertificateAlias, CertificatePolicy, CertificateProperties, CertificateRegistry,
+    CertificateType, Clock, CreateCertificate, GenerationId, GenerationRegistry, KeySlots,
+    ModuleName, SystemClock, WorkloadConfig,
 };
 use edgelet_http::route::{Handler, Parameters};
+use edgelet_utils::prepare_cert_uri_module;
 use workload::models::ServerCertificateRequest;
 
 use error::{Error, ErrorKind};
@@ -19,11 +28,81 @@ use IntoResponse;
 pub struct ServerCertHandler<T: CreateCertificate, W: WorkloadConfig> {
     hsm: T,
     config: W,
+    locks: AliasLocks,
+    registry: CertificateRegistry,
+    slots: KeySlots,
+    clock: Arc<Clock>,
+    policy: CertificatePolicy,
+    generations: GenerationRegistry,
+    renew: bool,
+    issued: IssuedServerCerts,
 }
 
 impl<T: CreateCertificate, W: WorkloadConfig> ServerCertHandler<T, W> {
     pub fn new(hsm: T, config: W) -> Self {
-        ServerCertHandler { hsm, config }
+        ServerCertHandler {
+            hsm,
+            config,
+            locks: AliasLocks::new(),
+            registry: CertificateRegistry::new(),
+            slots: KeySlots::new(),
+            clock: Arc::new(SystemClock),
+            policy: CertificatePolicy::default(),
+            generations: GenerationRegistry::new(),
+            renew: false,
+            issued: IssuedServerCerts::new(),
+        }
+    }
+
+    /// Serializes the certificate operations on each alias with those of the
+    /// other handlers that share `locks`.
+    pub fn with_locks(mut self, locks: AliasLocks) -> Self {
+        self.locks = locks;
+        self
+    }
+
+    pub fn with_registry(mut self, registry: CertificateRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Holds modules to the HSM key slots they are allowed in `slots`.
+    pub fn with_slots(mut self, slots: KeySlots) -> Self {
+        self.slots = slots;
+        self
+    }
+
+    /// Computes how long requested certificates are valid for from the time
+    /// of `clock`.
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: CertificatePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+
+    /// Renews the certificate the module already holds under the alias with
+    /// its existing key instead of destroying it and issuing one with a new
+    /// key. The key type the request asks for is ignored.
+    pub fn with_renewal(mut self) -> Self {
+        self.renew = true;
+        self
+    }
+
+    /// Hands out the certificates recorded in `issued` again, and records
+    /// the ones it issues or renews there, so that handlers of other routes
+    /// see them too.
+    pub fn with_issued(mut self, issued: IssuedServerCerts) -> Self {
+        self.issued = issued;
+        self
     }
 }
 impl<T, W> Handler<Parameters> for ServerCertHandler<T, W>
@@ -39,33 +118,123 @@ where
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let hsm = self.hsm.clone();
         let cfg = self.config.clone();
-        let max_duration = cfg.get_cert_max_duration(CertificateType::Server);
+        let locks = self.locks.clone();
+        let registry = self.registry.clone();
+        let slots = self.slots.clone();
+        let clock = self.clock.clone();
+        let policy = self.policy.clone();
+        let generations = self.generations.clone();
+        let renew = self.renew;
+        let issued = self.issued.clone();
+        let force = forced(&req);
+        let cert_policy = cfg.get_cert_policy(CertificateType::Server);
+        let max_duration = cert_policy.max_duration();
+        let default_key_type = cfg.get_default_key_type(CertificateType::Server);
 
         let response = match (params.name("name"), params.name("genid")) {
             (Some(module_id), Some(genid)) => {
-                let alias = format!("{}{}server", module_id.to_string(), genid.to_string());
+                // the alias ends up as an HSM certificate name, so only let
+                // through names IoT Hub itself would accept
+                let (module_name, genid, alias) = match ModuleName::new(module_id)
+                    .and_then(|name| GenerationId::new(genid).map(|genid| (name, genid)))
+                    .context(ErrorKind::BadParam)
+                {
+                    Ok((name, genid)) => (
+                        name.to_string(),
+                        genid.to_string(),
+                        CertificateAlias::server(name.as_str(), genid.as_str()),
+                    ),
+                    Err(err) => return Box::new(future::ok(Error::from(err).into_response())),
+                };
+                let module_uri =
+                    prepare_cert_uri_module(cfg.iot_hub_name(), cfg.device_id(), &module_name);
                 let result = req
                     .into_body()
                     .concat2()
-                    .map(move |body| {
-                        serde_json::from_slice::<ServerCertificateRequest>(&body)
-                            .context(ErrorKind::BadBody)
-                            .map_err(Error::from)
-                            .and_then(|cert_req| {
-                                compute_validity(
-                                    ensure_not_empty!(cert_req.expiration()).as_str(),
-                                    max_duration,
-                                ).map(|expiration| (cert_req, expiration))
-                            }).and_then(move |(cert_req, expiration)| {
-                                #[cfg_attr(feature = "cargo-clippy", allow(cast_sign_loss))]
-                                let props = CertificateProperties::new(
-                                    ensure_range!(expiration, 0, max_duration) as u64,
-                                    ensure_not_empty!(cert_req.common_name().to_string()),
-                                    CertificateType::Server,
-                                    alias.clone(),
-                                );
-                                refresh_cert(&hsm, alias, &props)
-                            }).unwrap_or_else(|e| e.into_response())
+                    .and_then(move |body| {
+                        blocking(move || {
+                            serde_json::from_slice::<ServerCertificateRequest>(&body)
+                                .context(ErrorKind::BadBody)
+                                .map_err(Error::from)
+                                .and_then(|cert_req| {
+                                    compute_validity(
+                                        ensure_not_empty!(cert_req.expiration()).as_str(),
+                                        max_duration,
+                                        clock.now(),
+                                    ).map(|expiration| (cert_req, expiration))
+                                }).and_then(move |(cert_req, expiration)| {
+                                    let (dns_names, ip_addresses) = requested_names(&cert_req)?;
+                                    let names: Vec<&str> = Some(cert_req.common_name().as_str())
+                                        .into_iter()
+                                        .chain(dns_names.iter().map(String::as_str))
+                                        .collect();
+                                    policy
+                                        .check(&module_name, &names)
+                                        .context(ErrorKind::PolicyViolation)?;
+                                    let key_type = requested_key_type(&cert_req)?
+                                        .map(|key_type| {
+                                            key_type.with_default_length(default_key_type)
+                                        }).or(default_key_type);
+                                    if let Some(key_type) = key_type {
+                                        cert_policy
+                                            .check_key_algorithm(Some(key_type.algorithm()))
+                                            .context(ErrorKind::PolicyViolation)?;
+                                    }
+                                    #[cfg_attr(feature = "cargo-clippy", allow(cast_sign_loss))]
+                                    let props = CertificateProperties::new(
+                                        ensure_range!(expiration, 0, max_duration) as u64,
+                                        ensure_not_empty!(cert_req.common_name().to_string()),
+                                        CertificateType::Server,
+                                        alias.to_string(),
+                                    );
+                                    let sans = san_entries(
+                                        cert_policy.san().unwrap_or_default(),
+                                        module_uri,
+                                        cert_req.common_name(),
+                                    );
+                                    let props = if sans.is_empty() {
+                                        props
+                                    } else {
+                                        props.with_san_entries(sans)
+                                    }.with_dns_names(dns_names)
+                                    .with_ip_addresses(ip_addresses);
+                                    let props = match key_type {
+                                        Some(key_type) => props.with_key_type(key_type),
+                                        None => props,
+                                    };
+                                    generations
+                                        .with_current(&module_name, &genid, || {
+                                            if renew || force {
+                                                refresh_cert(
+                                                    &hsm,
+                                                    &locks,
+                                                    &registry,
+                                                    &slots,
+                                                    &module_name,
+                                                    &alias,
+                                                    &props,
+                                                    &cert_policy,
+                                                    clock.now(),
+                                                    renew,
+                                                    Some(&issued),
+                                                )
+                                            } else {
+                                                reuse_or_refresh_cert(
+                                                    &hsm,
+                                                    &locks,
+                                                    &registry,
+                                                    &slots,
+                                                    &module_name,
+                                                    &alias,
+                                                    &props,
+                                                    &cert_policy,
+                                                    clock.now(),
+                                                    &issued,
+                                                )
+                                            }
+                                        }).context(ErrorKind::StaleGeneration)?
+                                }).unwrap_or_else(|e| e.into_response())
+                        })
                     }).map_err(Error::from)
                     .or_else(|e| future::ok(e.into_response()));
 
@@ -81,22 +250,35 @@ where
     }
 }
 
+/// Whether the module asks for a new certificate even if it would get back
+/// the one it was issued before.
+fn forced(req: &Request<Body>) -> bool {
+    form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .any(|(key, value)| key == "force" && value == "true")
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::result::Result as StdResult;
-    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     use chrono::offset::Utc;
     use chrono::Duration;
 
     use super::*;
     use edgelet_core::{
-        CertificateProperties, CertificateType, CreateCertificate, Error as CoreError,
-        ErrorKind as CoreErrorKind, KeyBytes, PrivateKey, WorkloadConfig,
+        CertPolicy, CertificateProperties, CertificateType, CreateCertificate, EcCurve,
+        Error as CoreError, ErrorKind as CoreErrorKind, KeyAlgorithm, KeyBytes, KeyType,
+        PrivateKey, RsaKeyLength, SanPolicy, WorkloadConfig,
     };
-    use edgelet_test_utils::cert::TestCert;
+    use edgelet_test_utils::cert::{TestCert, TEST_CERT_PEM};
     use http::StatusCode;
-    use workload::models::{CertificateResponse, ErrorResponse, ServerCertificateRequest};
+    use workload::models::{
+        CertificateCurve, CertificateKeyType, CertificateResponse, ErrorResponse, PrivateKeyType,
+        ServerCertificateRequest,
+    };
 
     const MAX_DURATION_SEC: u64 = 7200;
 
@@ -105,6 +287,10 @@ mod tests {
         on_create: Option<
             Arc<Box<Fn(&CertificateProperties) -> StdResult<TestCert, CoreError> + Send + Sync>>,
         >,
+        on_renew: Option<
+            Arc<Box<Fn(&CertificateProperties) -> StdResult<TestCert, CoreError> + Send + Sync>>,
+        >,
+        certs: Arc<Mutex<HashMap<String, TestCert>>>,
     }
 
     impl TestHsm {
@@ -115,6 +301,24 @@ mod tests {
             self.on_create = Some(Arc::new(Box::new(on_create)));
             self
         }
+
+        fn with_on_renew<F>(mut self, on_renew: F) -> Self
+        where
+            F: Fn(&CertificateProperties) -> StdResult<TestCert, CoreError> + Send + Sync + 'static,
+        {
+            self.on_renew = Some(Arc::new(Box::new(on_renew)));
+            self
+        }
+
+        // the certificates issued under each alias are kept like the HSM
+        // keeps them, to be looked up again
+        fn keep(&self, properties: &CertificateProperties, cert: TestCert) -> TestCert {
+            self.certs
+                .lock()
+                .unwrap()
+                .insert(properties.alias().to_string(), cert.clone());
+            cert
+        }
     }
 
     impl CreateCertificate for TestHsm {
@@ -125,18 +329,42 @@ mod tests {
             properties: &CertificateProperties,
         ) -> StdResult<Self::Certificate, CoreError> {
             let callback = self.on_create.as_ref().unwrap();
-            callback(properties)
+            callback(properties).map(|cert| self.keep(properties, cert))
         }
 
-        fn destroy_certificate(&self, _alias: String) -> StdResult<(), CoreError> {
+        fn destroy_certificate(&self, alias: String) -> StdResult<(), CoreError> {
+            self.certs.lock().unwrap().remove(&alias);
             Ok(())
         }
+
+        fn renew_certificate(
+            &self,
+            properties: &CertificateProperties,
+        ) -> StdResult<Self::Certificate, CoreError> {
+            match self.on_renew {
+                Some(ref callback) => callback(properties).map(|cert| self.keep(properties, cert)),
+                None => Err(CoreError::from(
+                    CoreErrorKind::CertificateRenewalNotSupported,
+                )),
+            }
+        }
+
+        fn get_certificate(&self, alias: String) -> StdResult<Self::Certificate, CoreError> {
+            self.certs
+                .lock()
+                .unwrap()
+                .get(&alias)
+                .cloned()
+                .ok_or_else(|| CoreError::from(CoreErrorKind::NotFound))
+        }
     }
 
     struct TestWorkloadConfig {
         iot_hub_name: String,
         device_id: String,
         duration: i64,
+        cert_policy: Option<CertPolicy>,
+        default_key_type: Option<KeyType>,
     }
 
     impl Default for TestWorkloadConfig {
@@ -151,6 +379,8 @@ mod tests {
                 iot_hub_name: String::from("zaphods_hub"),
                 device_id: String::from("marvins_device"),
                 duration: MAX_DURATION_SEC as i64,
+                cert_policy: None,
+                default_key_type: None,
             }
         }
     }
@@ -168,6 +398,26 @@ mod tests {
         }
     }
 
+    impl TestWorkloadData {
+        fn with_cert_policy(cert_policy: CertPolicy) -> Self {
+            TestWorkloadData {
+                data: Arc::new(TestWorkloadConfig {
+                    cert_policy: Some(cert_policy),
+                    ..TestWorkloadConfig::default()
+                }),
+            }
+        }
+
+        fn with_default_key_type(key_type: KeyType) -> Self {
+            TestWorkloadData {
+                data: Arc::new(TestWorkloadConfig {
+                    default_key_type: Some(key_type),
+                    ..TestWorkloadConfig::default()
+                }),
+            }
+        }
+    }
+
     impl WorkloadConfig for TestWorkloadData {
         fn iot_hub_name(&self) -> &str {
             self.data.iot_hub_name.as_str()
@@ -180,6 +430,57 @@ mod tests {
         fn get_cert_max_duration(&self, _cert_type: CertificateType) -> i64 {
             self.data.duration
         }
+
+        fn get_cert_policy(&self, cert_type: CertificateType) -> CertPolicy {
+            self.data
+                .cert_policy
+                .clone()
+                .unwrap_or_default()
+                .capped(self.get_cert_max_duration(cert_type))
+        }
+
+        fn get_default_key_type(&self, _cert_type: CertificateType) -> Option<KeyType> {
+            self.data.default_key_type
+        }
+    }
+
+    fn server_cert_request() -> (Request<Body>, Parameters) {
+        key_type_request(None, None)
+    }
+
+    fn key_type_request(
+        key_type: Option<CertificateKeyType>,
+        curve: Option<CertificateCurve>,
+    ) -> (Request<Body>, Parameters) {
+        let cert_req = ServerCertificateRequest::new(
+            "marvin".to_string(),
+            (Utc::now() + Duration::hours(1)).to_rfc3339(),
+        );
+        let cert_req = match key_type {
+            Some(key_type) => cert_req.with_key_type(key_type),
+            None => cert_req,
+        };
+        let cert_req = match curve {
+            Some(curve) => cert_req.with_curve(curve),
+            None => cert_req,
+        };
+        certificate_request(&cert_req)
+    }
+
+    fn certificate_request(cert_req: &ServerCertificateRequest) -> (Request<Body>, Parameters) {
+        raw_certificate_request(serde_json::to_string(cert_req).unwrap())
+    }
+
+    fn raw_certificate_request(body: String) -> (Request<Body>, Parameters) {
+        let request =
+            Request::get("http://localhost/modules/beeblebrox/genid/I/certificate/server")
+                .body(body.into())
+                .unwrap();
+        let params = Parameters::with_captures(vec![
+            (Some("name".to_string()), "beeblebrox".to_string()),
+            (Some("genid".to_string()), "I".to_string()),
+        ]);
+        (request, params)
     }
 
     fn parse_error_response(response: Response<Body>) -> ErrorResponse {
@@ -213,6 +514,41 @@ mod tests {
         assert_eq!("Bad parameter", parse_error_response(response).message());
     }
 
+    #[test]
+    fn invalid_name() {
+        let handler = ServerCertHandler::new(TestHsm::default(), TestWorkloadData::default());
+        let request =
+            Request::get("http://localhost/modules/..%2fserver/genid/I/certificate/server")
+                .body("".into())
+                .unwrap();
+        let params = Parameters::with_captures(vec![
+            (Some("name".to_string()), "../server".to_string()),
+            (Some("genid".to_string()), "I".to_string()),
+        ]);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert!(
+            parse_error_response(response)
+                .message()
+                .starts_with("Bad parameter")
+        );
+    }
+
+    #[test]
+    fn invalid_genid() {
+        let handler = ServerCertHandler::new(TestHsm::default(), TestWorkloadData::default());
+        let request =
+            Request::get("http://localhost/modules/beeblebrox/genid/I.I/certificate/server")
+                .body("".into())
+                .unwrap();
+        let params = Parameters::with_captures(vec![
+            (Some("name".to_string()), "beeblebrox".to_string()),
+            (Some("genid".to_string()), "I.I".to_string()),
+        ]);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
     #[test]
     fn empty_body() {
         let handler = ServerCertHandler::new(TestHsm::default(), TestWorkloadData::default());
@@ -269,7 +605,7 @@ mod tests {
             (Some("genid".to_string()), "IV".to_string()),
         ]);
         let response = handler.handle(request, params).wait().unwrap();
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -294,7 +630,7 @@ mod tests {
             (Some("genid".to_string()), "I".to_string()),
         ]);
         let response = handler.handle(request, params).wait().unwrap();
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -320,7 +656,7 @@ mod tests {
             (Some("genid".to_string()), "I".to_string()),
         ]);
         let response = handler.handle(request, params).wait().unwrap();
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -346,7 +682,7 @@ mod tests {
             (Some("genid".to_string()), "I".to_string()),
         ]);
         let response = handler.handle(request, params).wait().unwrap();
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -375,7 +711,7 @@ mod tests {
         ]);
         let response = handler.handle(request, params).wait().unwrap();
 
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -404,7 +740,7 @@ mod tests {
         ]);
         let response = handler.handle(request, params).wait().unwrap();
 
-        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
         assert_ne!(
             parse_error_response(response)
                 .message()
@@ -418,7 +754,7 @@ mod tests {
         let handler = ServerCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("marvin", props.common_name());
-                assert_eq!("beeblebroxIserver", props.alias());
+                assert_eq!("beeblebrox/I/server", props.alias());
                 assert_eq!(CertificateType::Server, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 Err(CoreError::from(CoreErrorKind::Io))
@@ -456,7 +792,7 @@ mod tests {
         let handler = ServerCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("marvin", props.common_name());
-                assert_eq!("beeblebroxIserver", props.alias());
+                assert_eq!("beeblebrox/I/server", props.alias());
                 assert_eq!(CertificateType::Server, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 Ok(TestCert::default().with_fail_pem(true))
@@ -494,7 +830,7 @@ mod tests {
         let handler = ServerCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("marvin", props.common_name());
-                assert_eq!("beeblebroxIserver", props.alias());
+                assert_eq!("beeblebrox/I/server", props.alias());
                 assert_eq!(CertificateType::Server, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 Ok(TestCert::default().with_fail_private_key(true))
@@ -532,7 +868,7 @@ mod tests {
         let handler = ServerCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("marvin", props.common_name());
-                assert_eq!("beeblebroxIserver", props.alias());
+                assert_eq!("beeblebrox/I/server", props.alias());
                 assert_eq!(CertificateType::Server, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 Ok(TestCert::default()
@@ -565,7 +901,7 @@ mod tests {
             .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
             .wait()
             .unwrap();
-        assert_eq!("key", cert_resp.private_key().type_());
+        assert_eq!(PrivateKeyType::Key, cert_resp.private_key().type_());
         assert_eq!(Some("Betelgeuse"), cert_resp.private_key().bytes());
     }
 
@@ -574,7 +910,7 @@ mod tests {
         let handler = ServerCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("marvin", props.common_name());
-                assert_eq!("beeblebroxIserver", props.alias());
+                assert_eq!("beeblebrox/I/server", props.alias());
                 assert_eq!(CertificateType::Server, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 Ok(TestCert::default().with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
@@ -606,16 +942,425 @@ mod tests {
             .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
             .wait()
             .unwrap();
-        assert_eq!("ref", cert_resp.private_key().type_());
+        assert_eq!(PrivateKeyType::Ref, cert_resp.private_key().type_());
+        assert_eq!(Some("Betelgeuse"), cert_resp.private_key().ref_());
+    }
+
+    #[test]
+    fn records_issued_certificate() {
+        let registry = CertificateRegistry::new();
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(|_| {
+                Ok(TestCert::default()
+                    .with_cert(TEST_CERT_PEM.as_bytes().to_vec())
+                    .with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
+            }),
+            TestWorkloadData::default(),
+        ).with_registry(registry.clone());
+
+        let cert_req = ServerCertificateRequest::new(
+            "marvin".to_string(),
+            (Utc::now() + Duration::hours(1)).to_rfc3339(),
+        );
+
+        let request =
+            Request::get("http://localhost/modules/beeblebrox/genid/I/certificate/server")
+                .body(serde_json::to_string(&cert_req).unwrap().into())
+                .unwrap();
+
+        let params = Parameters::with_captures(vec![
+            (Some("name".to_string()), "beeblebrox".to_string()),
+            (Some("genid".to_string()), "I".to_string()),
+        ]);
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::CREATED, response.status());
+        let cert = registry.revoke("1A2B3C4D").unwrap();
+        assert_eq!("beeblebrox", cert.module_id());
+        assert_eq!("beeblebrox/I/server", cert.alias());
+    }
+
+    #[test]
+    fn policy_violation_is_forbidden() {
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(|_| panic!("certificate should not be created")),
+            TestWorkloadData::default(),
+        ).with_policy(
+            CertificatePolicy::new().with_allowed_names("beeblebrox", vec!["marvin".to_string()]),
+        );
+
+        for common_name in &["*.marvin", "trillian"] {
+            let cert_req = ServerCertificateRequest::new(
+                common_name.to_string(),
+                (Utc::now() + Duration::hours(1)).to_rfc3339(),
+            );
+
+            let request =
+                Request::get("http://localhost/modules/beeblebrox/genid/I/certificate/server")
+                    .body(serde_json::to_string(&cert_req).unwrap().into())
+                    .unwrap();
+
+            let params = Parameters::with_captures(vec![
+                (Some("name".to_string()), "beeblebrox".to_string()),
+                (Some("genid".to_string()), "I".to_string()),
+            ]);
+            let response = handler.handle(request, params).wait().unwrap();
+
+            assert_eq!(StatusCode::FORBIDDEN, response.status());
+            assert!(
+                parse_error_response(response)
+                    .message()
+                    .contains("violates the certificate policy")
+            );
+        }
+    }
+
+    #[test]
+    fn cert_policy_shapes_certificates() {
+        let policy = CertPolicy::new()
+            .with_max_duration(3600)
+            .with_renewal_threshold(50)
+            .with_san(SanPolicy::new().with_dns_common_name(true));
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(|props| {
+                assert_eq!(3600, *props.validity_in_secs());
+                assert_eq!(
+                    Some(&["DNS: marvin".to_string()][..]),
+                    props.san_entries()
+                );
+                Ok(TestCert::default()
+                    .with_cert(TEST_CERT_PEM.as_bytes().to_vec())
+                    .with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
+            }),
+            TestWorkloadData::with_cert_policy(policy),
+        );
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::CREATED, response.status());
+        let cert_resp = response
+            .into_body()
+            .concat2()
+            .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
+            .wait()
+            .unwrap();
+        assert!(cert_resp.renew_at().is_some());
+    }
+
+    #[test]
+    fn requested_names_are_issued_under_the_policy() {
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(|props| {
+                assert_eq!(&["mqtt.marvin".to_string()], props.dns_names());
+                assert_eq!("10.0.0.42", props.ip_addresses()[0].to_string());
+                Ok(TestCert::default()
+                    .with_cert(TEST_CERT_PEM.as_bytes().to_vec())
+                    .with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
+            }),
+            TestWorkloadData::default(),
+        ).with_policy(CertificatePolicy::new().with_allowed_names(
+            "beeblebrox",
+            vec!["marvin".to_string(), "*.marvin".to_string()],
+        ));
+
+        for (dns_name, status) in vec![
+            ("mqtt.marvin", StatusCode::CREATED),
+            ("trillian", StatusCode::FORBIDDEN),
+            ("mqtt.marvin,IP:10.0.0.1", StatusCode::BAD_REQUEST),
+        ] {
+            let cert_req = ServerCertificateRequest::new(
+                "marvin".to_string(),
+                (Utc::now() + Duration::hours(1)).to_rfc3339(),
+            ).with_dns_names(vec![dns_name.to_string()])
+            .with_ip_addresses(vec!["10.0.0.42".to_string()]);
+            let request =
+                Request::get("http://localhost/modules/beeblebrox/genid/I/certificate/server")
+                    .body(serde_json::to_string(&cert_req).unwrap().into())
+                    .unwrap();
+            let params = Parameters::with_captures(vec![
+                (Some("name".to_string()), "beeblebrox".to_string()),
+                (Some("genid".to_string()), "I".to_string()),
+            ]);
+
+            let response = handler.handle(request, params).wait().unwrap();
+            assert_eq!(status, response.status());
+        }
+    }
+
+    #[test]
+    fn keys_the_cert_policy_does_not_allow_are_refused() {
+        let registry = CertificateRegistry::new();
+        let policy = CertPolicy::new().with_key_algorithms(vec![KeyAlgorithm::Rsa]);
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(|_| {
+                Ok(TestCert::default()
+                    .with_cert(TEST_CERT_PEM.as_bytes().to_vec())
+                    .with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
+            }),
+            TestWorkloadData::with_cert_policy(policy),
+        ).with_registry(registry.clone());
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+        assert!(registry.revoke("1A2B3C4D").is_err());
+    }
+
+    #[test]
+    fn requested_key_types_are_issued() {
+        let p384 = KeyType::Ec {
+            curve: EcCurve::P384,
+        };
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(move |props| {
+                assert_eq!(Some(p384), props.key_type());
+                Ok(TestCert::default().with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
+            }),
+            TestWorkloadData::with_default_key_type(KeyType::Rsa { length: None }),
+        );
+
+        let (request, params) =
+            key_type_request(Some(CertificateKeyType::Ec), Some(CertificateCurve::P384));
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+    }
+
+    #[test]
+    fn key_type_defaults_to_the_workload_config() {
+        let rsa_3072 = KeyType::Rsa {
+            length: Some(RsaKeyLength::Bits3072),
+        };
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(move |props| {
+                assert_eq!(Some(rsa_3072), props.key_type());
+                Ok(TestCert::default().with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
+            }),
+            TestWorkloadData::with_default_key_type(rsa_3072),
+        );
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+
+        // an RSA key without a length gets the length of the default
+        let (request, params) = key_type_request(Some(CertificateKeyType::Rsa), None);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+    }
+
+    #[test]
+    fn key_types_are_checked_before_the_certificate_is_issued() {
+        let policy = CertPolicy::new().with_key_algorithms(vec![KeyAlgorithm::Rsa]);
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_create(|_| panic!("the certificate was issued")),
+            TestWorkloadData::with_cert_policy(policy),
+        );
+
+        let (request, params) = key_type_request(Some(CertificateKeyType::Ec), None);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+        // curves the API doesn't know are refused along with the body
+        let body = format!(
+            r#"{{"commonName": "marvin", "expiration": "{}", "keyType": "ec", "curve": "P-521"}}"#,
+            (Utc::now() + Duration::hours(1)).to_rfc3339()
+        );
+        let (request, params) = raw_certificate_request(body);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let cert_req = ServerCertificateRequest::new(
+            "marvin".to_string(),
+            (Utc::now() + Duration::hours(1)).to_rfc3339(),
+        ).with_key_type(CertificateKeyType::Rsa)
+        .with_key_length(1024);
+        let (request, params) = certificate_request(&cert_req);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn renewal_reuses_the_key_of_the_held_certificate() {
+        let slots = KeySlots::new();
+        slots.reserve("beeblebrox", "beeblebrox/I/server").unwrap();
+        let handler = ServerCertHandler::new(
+            TestHsm::default()
+                .with_on_create(|_| panic!("certificate should not be created"))
+                .with_on_renew(|props| {
+                    assert_eq!("marvin", props.common_name());
+                    assert_eq!("beeblebrox/I/server", props.alias());
+                    Ok(TestCert::default()
+                        .with_private_key(PrivateKey::Ref("Betelgeuse".to_string())))
+                }),
+            TestWorkloadData::default(),
+        ).with_slots(slots.clone())
+        .with_renewal();
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::CREATED, response.status());
+        assert!(slots.holds("beeblebrox/I/server"));
+        let cert_resp = response
+            .into_body()
+            .concat2()
+            .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
+            .wait()
+            .unwrap();
         assert_eq!(Some("Betelgeuse"), cert_resp.private_key().ref_());
     }
 
+    #[test]
+    fn renewal_without_a_certificate_is_not_found() {
+        let handler = ServerCertHandler::new(
+            TestHsm::default().with_on_renew(|_| panic!("certificate should not be renewed")),
+            TestWorkloadData::default(),
+        ).with_renewal();
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+
+    #[test]
+    fn renewal_the_hsm_does_not_support_is_not_implemented() {
+        let slots = KeySlots::new();
+        slots.reserve("beeblebrox", "beeblebrox/I/server").unwrap();
+        let handler = ServerCertHandler::new(TestHsm::default(), TestWorkloadData::default())
+            .with_slots(slots.clone())
+            .with_renewal();
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::NOT_IMPLEMENTED, response.status());
+        assert!(slots.holds("beeblebrox/I/server"));
+    }
+
+    fn counting_hsm(created: Arc<AtomicUsize>) -> TestHsm {
+        TestHsm::default().with_on_create(move |_| {
+            created.fetch_add(1, Ordering::SeqCst);
+            Ok(TestCert::default()
+                .with_cert(TEST_CERT_PEM.as_bytes().to_vec())
+                .with_private_key(PrivateKey::Ref("Betelgeuse".to_string()))
+                .with_valid_to(Utc::now() + Duration::hours(1)))
+        })
+    }
+
+    #[test]
+    fn issued_certificate_is_handed_out_again() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let handler =
+            ServerCertHandler::new(counting_hsm(created.clone()), TestWorkloadData::default());
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+        let issued = response.into_body().concat2().wait().unwrap();
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let reused = response.into_body().concat2().wait().unwrap();
+        assert_eq!(issued.as_ref(), reused.as_ref());
+        assert_eq!(1, created.load(Ordering::SeqCst));
+
+        // a certificate for other names is issued anew
+        let cert_req = ServerCertificateRequest::new(
+            "marvin".to_string(),
+            (Utc::now() + Duration::hours(1)).to_rfc3339(),
+        ).with_dns_names(vec!["mqtt.marvin".to_string()]);
+        let (request, params) = certificate_request(&cert_req);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+        assert_eq!(2, created.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn certificate_missing_from_the_hsm_is_issued_anew() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let hsm = counting_hsm(created.clone());
+        let handler = ServerCertHandler::new(hsm.clone(), TestWorkloadData::default());
+
+        let (request, params) = server_cert_request();
+        handler.handle(request, params).wait().unwrap();
+        hsm.destroy_certificate("beeblebrox/I/server".to_string()).unwrap();
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+        assert_eq!(2, created.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn forced_certificate_is_issued_anew() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let handler =
+            ServerCertHandler::new(counting_hsm(created.clone()), TestWorkloadData::default());
+
+        let (request, params) = server_cert_request();
+        handler.handle(request, params).wait().unwrap();
+
+        let cert_req = ServerCertificateRequest::new(
+            "marvin".to_string(),
+            (Utc::now() + Duration::hours(1)).to_rfc3339(),
+        );
+        let request = Request::post(
+            "http://localhost/modules/beeblebrox/genid/I/certificate/server?force=true",
+        ).body(serde_json::to_string(&cert_req).unwrap().into())
+        .unwrap();
+        let params = Parameters::with_captures(vec![
+            (Some("name".to_string()), "beeblebrox".to_string()),
+            (Some("genid".to_string()), "I".to_string()),
+        ]);
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+        assert_eq!(2, created.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn revoked_certificate_is_not_handed_out_again() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let registry = CertificateRegistry::new();
+        let handler =
+            ServerCertHandler::new(counting_hsm(created.clone()), TestWorkloadData::default())
+                .with_registry(registry.clone());
+
+        let (request, params) = server_cert_request();
+        handler.handle(request, params).wait().unwrap();
+        registry.revoke("1A2B3C4D").unwrap();
+
+        let (request, params) = server_cert_request();
+        let response = handler.handle(request, params).wait().unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+        assert_eq!(2, created.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn certificates_are_not_reused_under_a_full_threshold() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let handler = ServerCertHandler::new(
+            counting_hsm(created.clone()),
+            TestWorkloadData::with_cert_policy(CertPolicy::new().with_reuse_threshold(100)),
+        );
+
+        for _ in 0..2 {
+            let (request, params) = server_cert_request();
+            let response = handler.handle(request, params).wait().unwrap();
+            assert_eq!(StatusCode::CREATED, response.status());
+        }
+        assert_eq!(2, created.load(Ordering::SeqCst));
+    }
+
     #[test]
     fn long_expiration_capped_to_max_duration_ok() {
         let handler = ServerCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("marvin", props.common_name());
-                assert_eq!("beeblebroxIserver", props.alias());
+                assert_eq!("beeblebrox/I/server", props.alias());
                 assert_eq!(CertificateType::Server, *props.certificate_type());
                 assert_eq!(MAX_DURATION_SEC, *props.validity_in_secs());
                 Ok(TestCert::default()
@@ -648,7 +1393,7 @@ mod tests {
             .and_then(|b| Ok(serde_json::from_slice::<CertificateResponse>(&b).unwrap()))
             .wait()
             .unwrap();
-        assert_eq!("key", cert_resp.private_key().type_());
+        assert_eq!(PrivateKeyType::Key, cert_resp.private_key().type_());
         assert_eq!(Some("Betelgeuse"), cert_resp.private_key().bytes());
     }
 
@@ -657,7 +1402,7 @@ mod tests {
         let handler = ServerCertHandler::new(
             TestHsm::default().with_on_create(|props| {
                 assert_eq!("marvin", props.common_name());
-                assert_eq!("beeblebroxIserver", props.alias());
+                assert_eq!("beeblebrox/I/server", props.alias());
                 assert_eq!(CertificateType::Server, *props.certificate_type());
                 assert!(MAX_DURATION_SEC >= *props.validity_in_secs());
                 Ok(TestCert::default().with_fail_valid_to(true))
@@ -0,0 +1,199 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::str;
+
+use failure::{Fail, ResultExt};
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+
+use edgelet_core::{CertificateRegistry, CreateCrl, ErrorKind as CoreErrorKind};
+use edgelet_http::route::{Handler, Parameters};
+use workload::models::CrlResponse;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// How long a CRL stays valid. The list is signed again on every request,
+/// so this only bounds how long a cached copy may be trusted.
+const CRL_VALIDITY_SECS: u64 = 86_400;
+
+pub struct CrlHandler<T: CreateCrl> {
+    hsm: T,
+    registry: CertificateRegistry,
+}
+
+impl<T> CrlHandler<T>
+where
+    T: 'static + CreateCrl + Clone,
+{
+    pub fn new(hsm: T, registry: CertificateRegistry) -> Self {
+        CrlHandler { hsm, registry }
+    }
+}
+
+impl<T> Handler<Parameters> for CrlHandler<T>
+where
+    T: 'static + CreateCrl + Send,
+{
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let response = self
+            .hsm
+            .create_crl(&self.registry.revoked(), CRL_VALIDITY_SECS)
+            .map_err(|err| match *err.kind() {
+                CoreErrorKind::CrlNotSupported => {
+                    Error::from(err.context(ErrorKind::CrlNotSupported))
+                }
+                _ => Error::from(err),
+            })            .and_then(|crl| {
+                str::from_utf8(crl.as_ref())
+                    .context(ErrorKind::Utf8)
+                    .map_err(From::from)
+                    .map(|s| s.to_string())
+            }).and_then(|crl| {
+                serde_json::to_string(&CrlResponse::new(crl))
+                    .context(ErrorKind::Serde)
+                    .map_err(From::from)
+            }).and_then(|b| {
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                    .body(b.into())
+                    .map_err(Error::from)
+            }).unwrap_or_else(|e| e.into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::{TimeZone, Utc};
+    use futures::Future;
+    use futures::Stream;
+
+    use edgelet_core::{Error as CoreError, RevokedCertificate};
+    use edgelet_test_utils::cert::TEST_CERT_PEM;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct TestHsm {
+        fail_call: bool,
+        not_supported: bool,
+        crl: Vec<u8>,
+        revoked: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TestHsm {
+        fn with_fail_call(mut self, fail_call: bool) -> Self {
+            self.fail_call = fail_call;
+            self
+        }
+
+        fn with_not_supported(mut self, not_supported: bool) -> Self {
+            self.not_supported = not_supported;
+            self
+        }
+
+        fn with_crl(mut self, crl: Vec<u8>) -> Self {
+            self.crl = crl;
+            self
+        }
+    }
+
+    impl CreateCrl for TestHsm {
+        type Buffer = Vec<u8>;
+
+        fn create_crl(
+            &self,
+            revoked: &[RevokedCertificate],
+            validity_in_secs: u64,
+        ) -> Result<Self::Buffer, CoreError> {
+            assert_eq!(CRL_VALIDITY_SECS, validity_in_secs);
+            *self.revoked.lock().unwrap() = revoked
+                .iter()
+                .map(|cert| cert.serial_number().to_string())
+                .collect();
+            if self.not_supported {
+                Err(CoreError::from(CoreErrorKind::CrlNotSupported))
+            } else if self.fail_call {
+                Err(CoreError::from(CoreErrorKind::Io))
+            } else {
+                Ok(self.crl.clone())
+            }
+        }
+    }
+
+    #[test]
+    fn create_fail() {
+        let handler = CrlHandler::new(
+            TestHsm::default().with_fail_call(true),
+            CertificateRegistry::new(),
+        );
+        let request = Request::get("http://localhost/crl").body("".into()).unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+    }
+
+    #[test]
+    fn create_not_supported() {
+        let handler = CrlHandler::new(
+            TestHsm::default().with_not_supported(true),
+            CertificateRegistry::new(),
+        );
+        let request = Request::get("http://localhost/crl").body("".into()).unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::NOT_IMPLEMENTED, response.status());
+    }
+
+    #[test]
+    fn utf8_decode_fail() {
+        let handler = CrlHandler::new(
+            TestHsm::default().with_crl(vec![0, 159, 146, 150]),
+            CertificateRegistry::new(),
+        );
+        let request = Request::get("http://localhost/crl").body("".into()).unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+    }
+
+    #[test]
+    fn success() {
+        let registry = CertificateRegistry::new();
+        registry
+            .record(
+                "m1",
+                "m1identity",
+                TEST_CERT_PEM.as_bytes(),
+                Utc.ymd(2036, 10, 12).and_hms(3, 20, 36),
+            ).unwrap();
+        registry.revoke("1a2b3c4d").unwrap();
+
+        let hsm = TestHsm::default().with_crl(b"boo".to_vec());
+        let handler = CrlHandler::new(hsm.clone(), registry);
+        let request = Request::get("http://localhost/crl").body("".into()).unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(vec!["1A2B3C4D".to_string()], *hsm.revoked.lock().unwrap());
+
+        response
+            .into_body()
+            .concat2()
+            .and_then(|b| {
+                let crl: CrlResponse = serde_json::from_slice(&b).unwrap();
+                assert_eq!("boo", crl.crl().as_str());
+                Ok(())
+            }).wait()
+            .unwrap();
+    }
+}
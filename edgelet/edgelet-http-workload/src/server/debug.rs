@@ -0,0 +1,212 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Diagnostic endpoints that module SDKs test their workload clients
+//! against, with responses that don't depend on the HSM:
+//!
+//! - `/debug/echo` responds with the body of the request, with the same
+//!   content type.
+//! - `/debug/delay?ms=<ms>` responds after `ms` milliseconds, so that
+//!   clients can test their timeouts.
+//! - `/debug/fail?code=<code>` fails with the status `code`, which must be a
+//!   client or server error, and an error body like the other endpoints.
+
+use std::time::{Duration, Instant};
+
+use futures::{future, Future, Stream};
+use http::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+use tokio::timer::Delay;
+use url::form_urlencoded;
+use workload::models::ErrorResponse;
+
+use edgelet_http::route::{normalize_path, Handler, Parameters};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub const DEBUG_PATH_PREFIX: &str = "/debug/";
+const ECHO_PATH: &str = "/debug/echo";
+const DELAY_PATH: &str = "/debug/delay";
+const FAIL_PATH: &str = "/debug/fail";
+
+/// The longest `/debug/delay` waits, so that a client can't hold on to a
+/// connection for ever.
+const MAX_DELAY_MS: u64 = 60_000;
+
+pub struct DebugHandler;
+
+impl Handler<Parameters> for DebugHandler {
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let path = normalize_path(req.uri().path()).unwrap_or_default();
+        match path.as_str() {
+            ECHO_PATH => echo(req),
+            DELAY_PATH => match query_param(&req, "ms").and_then(|ms| delay_from_ms(&ms)) {
+                Ok(delay) => Box::new(
+                    Delay::new(Instant::now() + delay)
+                        .then(|_| Ok::<_, HyperError>(empty_response(StatusCode::OK))),
+                ),
+                Err(err) => Box::new(future::ok(err.into_response())),
+            },
+            FAIL_PATH => {
+                let response = query_param(&req, "code")
+                    .and_then(|code| fail_status(&code))
+                    .map(fail_response)
+                    .unwrap_or_else(|e| e.into_response());
+                Box::new(future::ok(response))
+            }
+            _ => Box::new(future::ok(Error::from(ErrorKind::NotFound).into_response())),
+        }
+    }
+}
+
+fn echo(req: Request<Body>) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .cloned()
+        .unwrap_or_else(|| HeaderValue::from_static("application/octet-stream"));
+    let response = req.into_body().concat2().map(move |b| {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, content_type)
+            .header(CONTENT_LENGTH, b.len().to_string().as_str())
+            .body(Body::from(b))
+            .unwrap_or_else(|e| Error::from(e).into_response())
+    });
+    Box::new(response)
+}
+
+fn query_param(req: &Request<Body>, name: &str) -> Result<String, Error> {
+    form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .find(|&(ref key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+        .ok_or_else(|| Error::from(ErrorKind::BadParam))
+}
+
+fn delay_from_ms(ms: &str) -> Result<Duration, Error> {
+    match ms.parse::<u64>() {
+        Ok(ms) if ms <= MAX_DELAY_MS => Ok(Duration::from_millis(ms)),
+        _ => Err(Error::from(ErrorKind::BadParam)),
+    }
+}
+
+fn fail_status(code: &str) -> Result<StatusCode, Error> {
+    code.parse::<u16>()
+        .ok()
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .filter(|status| status.is_client_error() || status.is_server_error())
+        .ok_or_else(|| Error::from(ErrorKind::BadParam))
+}
+
+fn fail_response(status: StatusCode) -> Response<Body> {
+    let message = format!("Failed with {} as requested", status);
+    let b = serde_json::to_string(&ErrorResponse::new(message))
+        .expect("serialization of ErrorResponse failed.");
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+        .body(b.into())
+        .expect("response builder failure")
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::default())
+        .expect("response builder failure")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    fn handle(req: Request<Body>) -> Response<Body> {
+        let mut runtime = Runtime::new().unwrap();
+        runtime
+            .block_on(DebugHandler.handle(req, Parameters::new()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Response<Body> {
+        handle(Request::get(uri).body(Body::default()).unwrap())
+    }
+
+    #[test]
+    fn echo_responds_with_the_body() {
+        let request = Request::post("http://localhost/debug/echo")
+            .header(CONTENT_TYPE, "text/plain")
+            .body("hello".into())
+            .unwrap();
+
+        let response = handle(request);
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("text/plain", response.headers()[CONTENT_TYPE]);
+        let body = response.into_body().concat2().wait().unwrap();
+        assert_eq!(b"hello", &*body);
+    }
+
+    #[test]
+    fn delay_waits_before_responding() {
+        let started = Instant::now();
+
+        let response = get("http://localhost/debug/delay?ms=50");
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let too_long = format!("http://localhost/debug/delay?ms={}", MAX_DELAY_MS + 1);
+        assert_eq!(StatusCode::BAD_REQUEST, get(&too_long).status());
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            get("http://localhost/debug/delay").status()
+        );
+    }
+
+    #[test]
+    fn fail_responds_with_the_code() {
+        let response = get("http://localhost/debug/fail?code=503");
+
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            "Failed with 503 Service Unavailable as requested",
+            error.message()
+        );
+
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            get("http://localhost/debug/fail?code=200").status()
+        );
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            get("http://localhost/debug/fail?code=teapot").status()
+        );
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            get("http://localhost//debug/../debug/fail?code=503").status()
+        );
+    }
+
+    #[test]
+    fn unknown_endpoint_is_not_found() {
+        assert_eq!(
+            StatusCode::NOT_FOUND,
+            get("http://localhost/debug/other").status()
+        );
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use base64;
-use edgelet_core::Decrypt;
+use edgelet_core::{split_sequence_number, Decrypt, GenerationRegistry, SequenceNumbers};
 use edgelet_http::route::{Handler, Parameters};
 use error::{Error, ErrorKind};
 use failure::ResultExt;
@@ -15,11 +15,29 @@ use IntoResponse;
 
 pub struct DecryptHandler<T: Decrypt> {
     hsm: T,
+    generations: GenerationRegistry,
+    sequences: SequenceNumbers,
 }
 
 impl<T: Decrypt> DecryptHandler<T> {
     pub fn new(hsm: T) -> Self {
-        DecryptHandler { hsm }
+        DecryptHandler {
+            hsm,
+            generations: GenerationRegistry::new(),
+            sequences: SequenceNumbers::new(),
+        }
+    }
+
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+
+    /// Sets the counters the sequence numbers of requests with a sequence
+    /// key are checked against.
+    pub fn with_sequence_numbers(mut self, sequences: SequenceNumbers) -> Self {
+        self.sequences = sequences;
+        self
     }
 }
 
@@ -33,6 +51,8 @@ where
         params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let hsm = self.hsm.clone();
+        let generations = self.generations.clone();
+        let sequences = self.sequences.clone();
         let response = match params
             .name("name")
             .ok_or_else(|| Error::from(ErrorKind::BadParam))
@@ -43,7 +63,9 @@ where
                     .map(|genid| (name, genid))
             }) {
             Ok((module_id, genid)) => {
-                let id = format!("{}{}", module_id.to_string(), genid.to_string());
+                let module_id = module_id.to_string();
+                let genid = genid.to_string();
+                let id = format!("{}{}", module_id, genid);
                 let ok = req.into_body().concat2().map(move |b| {
                     serde_json::from_slice::<DecryptRequest>(&b)
                         .context(ErrorKind::BadBody)
@@ -52,11 +74,28 @@ where
                             let ciphertext = base64::decode(request.ciphertext())?;
                             let initialization_vector =
                                 base64::decode(request.initialization_vector())?;
-                            hsm.decrypt(id.as_bytes(), &ciphertext, &initialization_vector)
-                                .map_err(Error::from)
-                        }).and_then(|plaintext| {
+                            let plaintext = generations
+                                .with_current(&module_id, &genid, || {
+                                    hsm.decrypt(id.as_bytes(), &ciphertext, &initialization_vector)
+                                }).context(ErrorKind::StaleGeneration)??;
+                            match request.sequence_key() {
+                                Some(key) => {
+                                    let (number, plaintext) =
+                                        split_sequence_number(plaintext.as_ref())
+                                            .ok_or(ErrorKind::BadBody)?;
+                                    if !sequences.accept(&module_id, key, number)? {
+                                        return Err(Error::from(ErrorKind::SequenceReplayed));
+                                    }
+                                    Ok((plaintext.to_vec(), Some(number)))
+                                }
+                                None => Ok((plaintext.as_ref().to_vec(), None)),
+                            }
+                        }).and_then(|(plaintext, sequence_number)| {
                             let encoded = base64::encode(&plaintext);
-                            let response = DecryptResponse::new(encoded);
+                            let mut response = DecryptResponse::new(encoded);
+                            if let Some(number) = sequence_number {
+                                response.set_sequence_number(number as i64);
+                            }
                             let body = serde_json::to_string(&response)
                                 .expect("Generated an invalid DecryptResponse object");
 
@@ -78,8 +117,8 @@ where
 
 #[cfg(test)]
 mod tests {
-    use edgelet_core::Decrypt;
     use edgelet_core::Error as CoreError;
+    use edgelet_core::{prepend_sequence_number, Decrypt};
     use edgelet_http::route::Parameters;
     use futures::Future;
     use http::{Request, StatusCode};
@@ -290,4 +329,30 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn sequenced_requests_are_rejected_when_replayed() {
+        let mut ciphertext = prepend_sequence_number(1, RAW_TEXT.as_bytes());
+        ciphertext.reverse();
+        let request = DecryptRequest::new(base64::encode(&ciphertext), b64_text!())
+            .with_sequence_key("telemetry".to_string());
+        let handler = DecryptHandler::new(TestHsm::default());
+
+        let (req, params) = create_args(Some(&request), params_ok!());
+        let response = handler.handle(req, params).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response
+            .into_body()
+            .concat2()
+            .map(move |b| serde_json::from_slice::<DecryptResponse>(&b).unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(b64_text!(), body.plaintext().to_string());
+        assert_eq!(Some(1), body.sequence_number());
+
+        let (req, params) = create_args(Some(&request), params_ok!());
+        let response = handler.handle(req, params).wait().unwrap();
+        assert_eq!(StatusCode::CONFLICT, response.status());
+        assert_response_message_eq("The sequence number has been accepted before", response);
+    }
 }
@@ -1,7 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use base64;
-use edgelet_core::Encrypt;
+use edgelet_core::{prepend_sequence_number, Encrypt, GenerationRegistry, SequenceNumbers};
 use edgelet_http::route::{Handler, Parameters};
 use error::{Error, ErrorKind};
 use failure::ResultExt;
@@ -15,11 +15,29 @@ use IntoResponse;
 
 pub struct EncryptHandler<T: Encrypt> {
     hsm: T,
+    generations: GenerationRegistry,
+    sequences: SequenceNumbers,
 }
 
 impl<T: Encrypt> EncryptHandler<T> {
     pub fn new(hsm: T) -> Self {
-        EncryptHandler { hsm }
+        EncryptHandler {
+            hsm,
+            generations: GenerationRegistry::new(),
+            sequences: SequenceNumbers::new(),
+        }
+    }
+
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+
+    /// Sets the counters requests with a sequence key take their sequence
+    /// numbers from.
+    pub fn with_sequence_numbers(mut self, sequences: SequenceNumbers) -> Self {
+        self.sequences = sequences;
+        self
     }
 }
 
@@ -33,6 +51,8 @@ where
         params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
         let hsm = self.hsm.clone();
+        let generations = self.generations.clone();
+        let sequences = self.sequences.clone();
         let response = match params
             .name("name")
             .ok_or_else(|| Error::from(ErrorKind::BadParam))
@@ -43,7 +63,9 @@ where
                     .map(|genid| (name, genid))
             }) {
             Ok((module_id, genid)) => {
-                let id = format!("{}{}", module_id.to_string(), genid.to_string());
+                let module_id = module_id.to_string();
+                let genid = genid.to_string();
+                let id = format!("{}{}", module_id, genid);
                 let ok = req.into_body().concat2().map(move |b| {
                     serde_json::from_slice::<EncryptRequest>(&b)
                         .context(ErrorKind::BadBody)
@@ -52,11 +74,31 @@ where
                             let plaintext = base64::decode(request.plaintext())?;
                             let initialization_vector =
                                 base64::decode(request.initialization_vector())?;
-                            hsm.encrypt(id.as_bytes(), &plaintext, &initialization_vector)
+                            if request.sequence_key() == Some("") {
+                                return Err(Error::from(ErrorKind::BadBody));
+                            }
+                            generations
+                                .with_current(&module_id, &genid, || {
+                                    // the sequence number is encrypted along with the
+                                    // plaintext, so that it can't be changed
+                                    let sequence_number = match request.sequence_key() {
+                                        Some(key) => Some(sequences.next(&module_id, key)?),
+                                        None => None,
+                                    };
+                                    let plaintext = match sequence_number {
+                                        Some(number) => prepend_sequence_number(number, &plaintext),
+                                        None => plaintext,
+                                    };
+                                    hsm.encrypt(id.as_bytes(), &plaintext, &initialization_vector)
+                                        .map(|ciphertext| (ciphertext, sequence_number))
+                                }).context(ErrorKind::StaleGeneration)?
                                 .map_err(Error::from)
-                        }).and_then(|ciphertext| {
+                        }).and_then(|(ciphertext, sequence_number)| {
                             let encoded = base64::encode(&ciphertext);
-                            let response = EncryptResponse::new(encoded);
+                            let mut response = EncryptResponse::new(encoded);
+                            if let Some(number) = sequence_number {
+                                response.set_sequence_number(number as i64);
+                            }
                             let body = serde_json::to_string(&response)
                                 .expect("Generated an invalid EncryptResponse object");
 
@@ -290,4 +332,42 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn sequenced_requests_encrypt_their_sequence_number() {
+        let handler = EncryptHandler::new(TestHsm::default());
+        for expected in 1..3 {
+            let request = request_ok().with_sequence_key("telemetry".to_string());
+            let (request, params) = create_args(Some(&request), params_ok!());
+
+            let response = handler.handle(request, params).wait().unwrap();
+            assert_eq!(StatusCode::OK, response.status());
+            let body = response
+                .into_body()
+                .concat2()
+                .map(move |b| serde_json::from_slice::<EncryptResponse>(&b).unwrap())
+                .wait()
+                .unwrap();
+
+            assert_eq!(Some(expected), body.sequence_number());
+            let mut plaintext = base64::decode(body.ciphertext()).unwrap();
+            plaintext.reverse();
+            assert_eq!(
+                prepend_sequence_number(expected as u64, RAW_TEXT.as_bytes()),
+                plaintext
+            );
+        }
+    }
+
+    #[test]
+    fn handler_responds_with_bad_request_when_sequence_key_is_empty() {
+        let request = request_ok().with_sequence_key(String::new());
+        let (request, params) = create_args(Some(&request), params_ok!());
+        let handler = EncryptHandler::new(TestHsm::default());
+
+        let response = handler.handle(request, params).wait().unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert_response_message_eq("Bad body", response);
+    }
 }
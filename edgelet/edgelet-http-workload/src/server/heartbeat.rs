@@ -0,0 +1,100 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use failure::ResultExt;
+use futures::{future, Future};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+
+use edgelet_core::HeartbeatMonitor;
+use edgelet_http::route::{Handler, Parameters};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct HeartbeatHandler {
+    monitor: HeartbeatMonitor,
+}
+
+impl HeartbeatHandler {
+    pub fn new(monitor: HeartbeatMonitor) -> Self {
+        HeartbeatHandler { monitor }
+    }
+}
+
+impl Handler<Parameters> for HeartbeatHandler {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let response = params
+            .name("name")
+            .ok_or_else(|| Error::from(ErrorKind::BadParam))
+            .and_then(|name| {
+                self.monitor
+                    .beat(name)
+                    .context(ErrorKind::BadParam)
+                    .map_err(Error::from)
+            }).and_then(|_| {
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::default())
+                    .map_err(Error::from)
+            }).unwrap_or_else(|e| e.into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn monitor() -> HeartbeatMonitor {
+        HeartbeatMonitor::new(Duration::from_secs(30))
+    }
+
+    #[test]
+    fn success() {
+        let monitor = monitor();
+        let handler = HeartbeatHandler::new(monitor.clone());
+        let request = Request::put("http://localhost/modules/m1/heartbeat")
+            .body(Body::default())
+            .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "m1".to_string())]);
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        assert!(monitor.entry("m1").unwrap().healthy());
+    }
+
+    #[test]
+    fn empty_name() {
+        let handler = HeartbeatHandler::new(monitor());
+        let request = Request::put("http://localhost/modules/%20/heartbeat")
+            .body(Body::default())
+            .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), " ".to_string())]);
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn bad_params() {
+        let handler = HeartbeatHandler::new(monitor());
+        let request = Request::put("http://localhost/modules/heartbeat")
+            .body(Body::default())
+            .unwrap();
+
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+}
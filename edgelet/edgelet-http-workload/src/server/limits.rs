@@ -0,0 +1,50 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use edgelet_http::concurrency::ConcurrencyLimit;
+
+/// How many requests of each class of workload operation are handled at
+/// once. Issuing certificates, signing and the encryption operations each
+/// have a limit of their own, so that a burst of one of them doesn't hold up
+/// the others. Operations aren't limited unless a limit is set.
+#[derive(Clone, Default)]
+pub struct WorkloadLimits {
+    cert: ConcurrencyLimit,
+    sign: ConcurrencyLimit,
+    encrypt: ConcurrencyLimit,
+}
+
+impl WorkloadLimits {
+    pub fn new() -> Self {
+        WorkloadLimits::default()
+    }
+
+    /// Limits the requests for identity and server certificates.
+    pub fn with_cert_limit(mut self, limit: usize) -> Self {
+        self.cert = ConcurrencyLimit::new(limit);
+        self
+    }
+
+    /// Limits the requests to sign with a module's key.
+    pub fn with_sign_limit(mut self, limit: usize) -> Self {
+        self.sign = ConcurrencyLimit::new(limit);
+        self
+    }
+
+    /// Limits the requests to encrypt, decrypt, wrap and unwrap keys.
+    pub fn with_encrypt_limit(mut self, limit: usize) -> Self {
+        self.encrypt = ConcurrencyLimit::new(limit);
+        self
+    }
+
+    pub fn cert(&self) -> ConcurrencyLimit {
+        self.cert.clone()
+    }
+
+    pub fn sign(&self) -> ConcurrencyLimit {
+        self.sign.clone()
+    }
+
+    pub fn encrypt(&self) -> ConcurrencyLimit {
+        self.encrypt.clone()
+    }
+}
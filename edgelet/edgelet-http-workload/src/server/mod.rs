@@ -1,19 +1,38 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+mod bootstrap;
 mod cert;
+mod crl;
+mod debug;
 mod decrypt;
 mod encrypt;
+mod heartbeat;
+mod limits;
 mod sign;
+mod signed;
+mod time;
+mod token;
 mod trust_bundle;
+mod usage;
+mod verify;
+mod wrap_key;
 
 use std::error::Error as StdError;
+use std::time::Duration;
 
+use edgelet_core::response_signing::ResponseSigner;
 use edgelet_core::{
-    CreateCertificate, Decrypt, Encrypt, Error as CoreError, GetTrustBundle, KeyStore, Module,
-    ModuleRuntime, Policy, WorkloadConfig,
+    CaRotation, CertificatePolicy, CertificateRegistry, CreateCertificate, CreateCrl, Decrypt,
+    Encrypt, Error as CoreError, GenerationRegistry, GetTrustBundle, HeartbeatMonitor,
+    HostProcesses, IdentityKeySource, KeySlots, KeyStore, Module, ModuleRuntime, Policy,
+    SequenceNumbers, TokenValidator, TrustBundleVersions, TrustedClock, WorkloadCall,
+    WorkloadConfig, WorkloadUsage,
 };
 use edgelet_http::authorization::Authorization;
+use edgelet_http::cache::Cached;
+use edgelet_http::concurrency::Limited;
 use edgelet_http::route::*;
+#[cfg(feature = "mgmt")]
 use edgelet_http_mgmt::ListModules;
 use failure;
 use futures::{future, Future};
@@ -21,51 +40,123 @@ use hyper::service::{NewService, Service};
 use hyper::{Body, Error as HyperError, Request, Response};
 use serde::Serialize;
 
-use self::cert::{IdentityCertHandler, ServerCertHandler};
+use self::bootstrap::BootstrapHandler;
+use self::cert::{
+    AliasLocks, CsrCertHandler, IdentityCertHandler, IssuedServerCerts, ServerCertHandler,
+};
+use self::crl::CrlHandler;
+use self::debug::{DebugHandler, DEBUG_PATH_PREFIX};
 use self::decrypt::DecryptHandler;
 use self::encrypt::EncryptHandler;
+use self::heartbeat::HeartbeatHandler;
+pub use self::limits::WorkloadLimits;
 use self::sign::SignHandler;
+use self::signed::SignedHandler;
+use self::time::TimeHandler;
+use self::token::ValidateTokensHandler;
 use self::trust_bundle::TrustBundleHandler;
+use self::usage::Counted;
+use self::verify::VerifyCertificateHandler;
+use self::wrap_key::{UnwrapKeyHandler, WrapKeyHandler};
+
+/// The edge hub validates the tokens of leaf devices on their behalf.
+const EDGE_HUB_NAME: &str = "edgeHub";
+
+/// How long the trust bundle is served from memory. Every module fetches it
+/// through its SDK, often every few seconds.
+const TRUST_BUNDLE_CACHE_TTL: Duration = Duration::from_secs(5);
 
 #[derive(Clone)]
 pub struct WorkloadService {
     inner: RouterService<RegexRecognizer>,
+    debug: bool,
 }
 
 impl WorkloadService {
     // clippy bug: https://github.com/rust-lang-nursery/rust-clippy/issues/3220
-    #[cfg_attr(feature = "cargo-clippy", allow(new_ret_no_self))]
-    pub fn new<K, H, M, W>(
+    #[cfg_attr(
+        feature = "cargo-clippy",
+        allow(new_ret_no_self, too_many_arguments)
+    )]
+    pub fn new<K, H, M, W, S>(
         key_store: &K,
         hsm: H,
         runtime: &M,
         config: W,
+        registry: &CertificateRegistry,
+        slots: &KeySlots,
+        policy: &CertificatePolicy,
+        generations: &GenerationRegistry,
+        sequences: &SequenceNumbers,
+        host_processes: &HostProcesses,
+        limits: &WorkloadLimits,
+        monitor: &HeartbeatMonitor,
+        rotation: &CaRotation,
+        versions: &TrustBundleVersions,
+        usage: &WorkloadUsage,
+        trusted_clock: &TrustedClock,
+        validator: &TokenValidator<S>,
+        signer: Option<&ResponseSigner>,
     ) -> impl Future<Item = Self, Error = failure::Error>
     where
         K: KeyStore + Clone + Send + Sync + 'static,
-        H: CreateCertificate + Decrypt + Encrypt + GetTrustBundle + Clone + Send + Sync + 'static,
+        H: CreateCertificate
+            + CreateCrl
+            + Decrypt
+            + Encrypt
+            + GetTrustBundle
+            + Clone
+            + Send
+            + Sync
+            + 'static,
         M: ModuleRuntime + Clone + Send + Sync + 'static,
         M::Error: Into<CoreError>,
         <M::Module as Module>::Config: Serialize,
         <M::Module as Module>::Error: Into<CoreError>,
         M::Logs: Into<Body>,
         W: WorkloadConfig + Clone + Send + Sync + 'static,
+        S: IdentityKeySource + Clone + Send + Sync + 'static,
     {
+        // the server certificates issued and renewed through any of the routes
+        let issued = IssuedServerCerts::new();
+        // every route that issues certificates works on the same aliases
+        let locks = AliasLocks::new();
         let router = router!(
+            #[cfg(feature = "mgmt")]
             get    "/modules" => Authorization::new(ListModules::new(runtime.clone()), Policy::Anonymous, runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/sign" => Authorization::new(SignHandler::new(key_store.clone()), Policy::Caller, runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/decrypt" => Authorization::new(DecryptHandler::new(hsm.clone()), Policy::Caller, runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/encrypt" => Authorization::new(EncryptHandler::new(hsm.clone()), Policy::Caller, runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/certificate/identity" => Authorization::new(IdentityCertHandler::new(hsm.clone(), config.clone()), Policy::Caller, runtime.clone()),
-            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/certificate/server" => Authorization::new(ServerCertHandler::new(hsm.clone(), config), Policy::Caller, runtime.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/sign" => Authorization::new(Counted::new(Limited::new(SignHandler::new(key_store.clone()).with_generations(generations.clone()), limits.sign()), usage.clone(), WorkloadCall::Sign), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/decrypt" => Authorization::new(Counted::new(Limited::new(DecryptHandler::new(hsm.clone()).with_generations(generations.clone()).with_sequence_numbers(sequences.clone()), limits.encrypt()), usage.clone(), WorkloadCall::Decrypt), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/encrypt" => Authorization::new(Counted::new(Limited::new(EncryptHandler::new(hsm.clone()).with_generations(generations.clone()).with_sequence_numbers(sequences.clone()), limits.encrypt()), usage.clone(), WorkloadCall::Encrypt), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/wrapkey" => Authorization::new(Limited::new(WrapKeyHandler::new(hsm.clone()).with_generations(generations.clone()), limits.encrypt()), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/unwrapkey" => Authorization::new(Limited::new(UnwrapKeyHandler::new(hsm.clone()).with_generations(generations.clone()), limits.encrypt()), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/certificate/identity" => Authorization::new(Counted::new(Limited::new(SignedHandler::new(IdentityCertHandler::new(hsm.clone(), config.clone()).with_locks(locks.clone()).with_registry(registry.clone()).with_slots(slots.clone()), signer.cloned()), limits.cert()), usage.clone(), WorkloadCall::IssueCertificate), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/certificate/server" => Authorization::new(Counted::new(Limited::new(SignedHandler::new(ServerCertHandler::new(hsm.clone(), config.clone()).with_locks(locks.clone()).with_registry(registry.clone()).with_slots(slots.clone()).with_policy(policy.clone()).with_generations(generations.clone()).with_issued(issued.clone()), signer.cloned()), limits.cert()), usage.clone(), WorkloadCall::IssueCertificate), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/certificate/server/renew" => Authorization::new(Counted::new(Limited::new(SignedHandler::new(ServerCertHandler::new(hsm.clone(), config.clone()).with_locks(locks.clone()).with_registry(registry.clone()).with_slots(slots.clone()).with_policy(policy.clone()).with_generations(generations.clone()).with_issued(issued.clone()).with_renewal(), signer.cloned()), limits.cert()), usage.clone(), WorkloadCall::IssueCertificate), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/certificate/csr" => Authorization::new(Counted::new(Limited::new(SignedHandler::new(CsrCertHandler::new(hsm.clone(), config.clone()).with_registry(registry.clone()).with_policy(policy.clone()).with_generations(generations.clone()), signer.cloned()), limits.cert()), usage.clone(), WorkloadCall::IssueCertificate), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            get    "/modules/(?P<name>[^/]+)/genid/(?P<genid>[^/]+)/bootstrap" => Authorization::new(Limited::new(SignedHandler::new(BootstrapHandler::new(config.clone(), TrustBundleHandler::new(hsm.clone()).with_rotation(rotation.clone()).with_versions(versions.clone()), Counted::new(IdentityCertHandler::new(hsm.clone(), config.clone()).with_locks(locks.clone()).with_registry(registry.clone()).with_slots(slots.clone()), usage.clone(), WorkloadCall::IssueCertificate), Counted::new(ServerCertHandler::new(hsm.clone(), config).with_locks(locks.clone()).with_issued(issued.clone()).with_registry(registry.clone()).with_slots(slots.clone()).with_policy(policy.clone()).with_generations(generations.clone()), usage.clone(), WorkloadCall::IssueCertificate)), signer.cloned()), limits.cert()), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            put    "/modules/(?P<name>[^/]+)/heartbeat" => Authorization::new(HeartbeatHandler::new(monitor.clone()), Policy::Caller, runtime.clone()).with_host_processes(host_processes.clone()),
+            post   "/certificate/verify" => Authorization::new(VerifyCertificateHandler::new(hsm.clone()).with_rotation(rotation.clone()), Policy::Anonymous, runtime.clone()),
+            post   "/tokens/validate" => Authorization::new(ValidateTokensHandler::new(validator.clone()), Policy::Module(EDGE_HUB_NAME), runtime.clone()),
 
-            get    "/trust-bundle" => Authorization::new(TrustBundleHandler::new(hsm), Policy::Anonymous, runtime.clone()),
+            get    "/trust-bundle" => Authorization::new(Cached::new(SignedHandler::new(TrustBundleHandler::new(hsm.clone()).with_rotation(rotation.clone()).with_versions(versions.clone()), signer.cloned()), TRUST_BUNDLE_CACHE_TTL), Policy::Anonymous, runtime.clone()),
+            get    "/crl" => Authorization::new(CrlHandler::new(hsm, registry.clone()), Policy::Anonymous, runtime.clone()),
+            get    "/time" => Authorization::new(TimeHandler::new(trusted_clock.clone()), Policy::Anonymous, runtime.clone()),
         );
 
         router
             .new_service()
-            .map(|inner| WorkloadService { inner })
-            .map_err(failure::Error::from_boxed_compat)
+            .map(|inner| WorkloadService {
+                inner,
+                debug: false,
+            }).map_err(failure::Error::from_boxed_compat)
+    }
+
+    /// Serves the diagnostic endpoints under `/debug/`, which module SDKs
+    /// test their workload clients against. They are meant for testing, so
+    /// they aren't served unless this is called.
+    pub fn with_debug_endpoints(mut self) -> Self {
+        self.debug = true;
+        self
     }
 }
 
@@ -76,6 +167,17 @@ impl Service for WorkloadService {
     type Future = Box<Future<Item = Response<Self::ResBody>, Error = Self::Error> + Send>;
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
+        // The diagnostic endpoints aren't part of the workload API, so they
+        // aren't routed with the rest of it. They are matched against the
+        // normalized path all the same, so that no spelling of their paths
+        // gets past the check.
+        let debug = self.debug
+            && normalize_path(req.uri().path())
+                .map(|path| path.starts_with(DEBUG_PATH_PREFIX))
+                .unwrap_or(false);
+        if debug {
+            return DebugHandler.handle(req, Parameters::new());
+        }
         self.inner.call(req)
     }
 }
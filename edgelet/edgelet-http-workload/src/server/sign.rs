@@ -2,6 +2,7 @@
 
 use base64;
 use edgelet_core::crypto::{KeyIdentity, KeyStore, Sign, Signature, SignatureAlgorithm};
+use edgelet_core::GenerationRegistry;
 use edgelet_http::route::{Handler, Parameters};
 use failure::ResultExt;
 use futures::{future, Future, Stream};
@@ -9,7 +10,7 @@ use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{Request, Response, StatusCode};
 use hyper::{Body, Error as HyperError};
 use serde_json;
-use workload::models::{SignRequest, SignResponse};
+use workload::models::{SignAlgorithm, SignRequest, SignResponse};
 
 use error::{Error, ErrorKind};
 use IntoResponse;
@@ -19,6 +20,7 @@ where
     K: 'static + KeyStore + Clone,
 {
     key_store: K,
+    generations: GenerationRegistry,
 }
 
 impl<K> SignHandler<K>
@@ -26,7 +28,15 @@ where
     K: 'static + KeyStore + Clone,
 {
     pub fn new(key_store: K) -> Self {
-        SignHandler { key_store }
+        SignHandler {
+            key_store,
+            generations: GenerationRegistry::new(),
+        }
+    }
+
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
     }
 }
 
@@ -36,13 +46,17 @@ pub fn sign<K: KeyStore>(
     id: String,
     request: SignRequest,
 ) -> Result<SignResponse, Error> {
+    let algorithm = match request.algo() {
+        SignAlgorithm::HMACSHA256 => SignatureAlgorithm::HMACSHA256,
+        SignAlgorithm::Unknown => Err(ErrorKind::BadParam)?,
+    };
     key_store
         .get(&KeyIdentity::Module(id), request.key_id())
         .context(ErrorKind::NotFound)
         .map_err(Error::from)
         .and_then(|k| {
             let data: Vec<u8> = base64::decode(request.data())?;
-            let signature = k.sign(SignatureAlgorithm::HMACSHA256, &data)?;
+            let signature = k.sign(algorithm, &data)?;
             let encoded = base64::encode(signature.as_bytes());
             Ok(SignResponse::new(encoded))
         })
@@ -70,13 +84,17 @@ where
                 let id = name.to_string();
                 let genid = genid.to_string();
                 let key_store = self.key_store.clone();
+                let generations = self.generations.clone();
                 let ok = req.into_body().concat2().map(move |b| {
                     serde_json::from_slice::<SignRequest>(&b)
                         .context(ErrorKind::BadBody)
                         .map_err(From::from)
                         .and_then(|request| {
                             let key_id = format!("{}{}", request.key_id(), genid);
-                            sign(key_store, id, request.with_key_id(key_id))
+                            generations
+                                .with_current(&id, &genid, || {
+                                    sign(key_store, id.clone(), request.with_key_id(key_id))
+                                }).context(ErrorKind::StaleGeneration)?
                         }).and_then(|r| {
                             serde_json::to_string(&r)
                                 .context(ErrorKind::Serde)
@@ -183,7 +201,7 @@ mod tests {
 
         let sign_request = SignRequest::new(
             "primary".to_string(),
-            "hmac".to_string(),
+            SignAlgorithm::HMACSHA256,
             base64::encode("The quick brown fox jumps over the lazy dog"),
         );
         let body = serde_json::to_string(&sign_request).unwrap();
@@ -225,7 +243,7 @@ mod tests {
 
         let sign_request = SignRequest::new(
             "primary".to_string(),
-            "hmac".to_string(),
+            SignAlgorithm::HMACSHA256,
             base64::encode("The quick brown fox jumps over the lazy dog"),
         );
         let body = serde_json::to_string(&sign_request).unwrap();
@@ -266,7 +284,7 @@ mod tests {
 
         let sign_request = SignRequest::new(
             "primary".to_string(),
-            "hmac".to_string(),
+            SignAlgorithm::HMACSHA256,
             base64::encode("The quick brown fox jumps over the lazy dog"),
         );
         let body = serde_json::to_string(&sign_request).unwrap();
@@ -291,7 +309,7 @@ mod tests {
 
         let sign_request = SignRequest::new(
             "primary".to_string(),
-            "hmac".to_string(),
+            SignAlgorithm::HMACSHA256,
             base64::encode("The quick brown fox jumps over the lazy dog"),
         );
         let body = serde_json::to_string(&sign_request).unwrap();
@@ -318,7 +336,7 @@ mod tests {
 
         let sign_request = SignRequest::new(
             "primary".to_string(),
-            "hmac".to_string(),
+            SignAlgorithm::HMACSHA256,
             "alsjdfasf".to_string(),
         );
         let body = serde_json::to_string(&sign_request).unwrap();
@@ -382,4 +400,60 @@ mod tests {
             }).wait()
             .unwrap();
     }
+
+    #[test]
+    fn unknown_algorithm() {
+        // arrange
+        let key = MemoryKey::new("key");
+        let store = TestKeyStore::new(key);
+        let handler = SignHandler::new(store);
+
+        let body = r#"{"keyId": "primary", "algo": "hmac", "data": "ZGF0YQ=="}"#;
+
+        let parameters = Parameters::with_captures(vec![
+            (Some("name".to_string()), "test".to_string()),
+            (Some("genid".to_string()), "g1".to_string()),
+        ]);
+        let request = Request::post("http://localhost/modules/name/sign")
+            .body(body.into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn superseded_generation_is_gone() {
+        // arrange
+        let key = MemoryKey::new("key");
+        let store = TestKeyStore::new(key);
+        let generations = GenerationRegistry::new();
+        generations.advance("test", "g2");
+        let handler = SignHandler::new(store.clone()).with_generations(generations);
+
+        let sign_request = SignRequest::new(
+            "primary".to_string(),
+            SignAlgorithm::HMACSHA256,
+            base64::encode("The quick brown fox jumps over the lazy dog"),
+        );
+        let body = serde_json::to_string(&sign_request).unwrap();
+
+        let parameters = Parameters::with_captures(vec![
+            (Some("name".to_string()), "test".to_string()),
+            (Some("genid".to_string()), "g1".to_string()),
+        ]);
+        let request = Request::post("http://localhost/modules/name/sign")
+            .body(body.into())
+            .unwrap();
+
+        // act
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        // assert
+        assert_eq!(StatusCode::GONE, response.status());
+        assert_eq!("", store.state.lock().unwrap().last_key_name);
+    }
 }
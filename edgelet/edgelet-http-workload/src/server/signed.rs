@@ -0,0 +1,119 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use failure::ResultExt;
+use futures::{Future, Stream};
+use http::header::HeaderValue;
+use http::{Request, Response};
+use hyper::{Body, Error as HyperError};
+
+use edgelet_core::response_signing::{ResponseSigner, SIGNATURE_HEADER};
+use edgelet_http::route::{Handler, Parameters};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// Signs the body of the responses of `inner`, when the daemon is set up to
+/// sign workload API responses.
+pub struct SignedHandler<H> {
+    inner: H,
+    signer: Option<ResponseSigner>,
+}
+
+impl<H> SignedHandler<H> {
+    pub fn new(inner: H, signer: Option<ResponseSigner>) -> Self {
+        SignedHandler { inner, signer }
+    }
+}
+
+impl<H> Handler<Parameters> for SignedHandler<H>
+where
+    H: Handler<Parameters>,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let response = self.inner.handle(req, params);
+        let signer = match self.signer {
+            Some(ref signer) => signer.clone(),
+            None => return response,
+        };
+
+        let response = response.and_then(move |response| {
+            let (parts, body) = response.into_parts();
+            body.concat2().map(move |body| {
+                signer
+                    .sign(&body)
+                    .context(ErrorKind::Sign)
+                    .map_err(Error::from)
+                    .map(|signature| {
+                        let mut response = Response::from_parts(parts, Body::from(body));
+                        if let Ok(signature) = HeaderValue::from_str(&signature) {
+                            response.headers_mut().insert(SIGNATURE_HEADER, signature);
+                        }
+                        response
+                    }).unwrap_or_else(|e| e.into_response())
+            })
+        });
+
+        Box::new(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::response_signing::verify;
+    use futures::future;
+    use http::StatusCode;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    struct TestHandler;
+
+    impl Handler<Parameters> for TestHandler {
+        fn handle(
+            &self,
+            _req: Request<Body>,
+            _params: Parameters,
+        ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+            let response = Response::builder()
+                .status(StatusCode::OK)
+                .body(r#"{"certificate":"pem"}"#.into())
+                .unwrap();
+            Box::new(future::ok(response))
+        }
+    }
+
+    fn request() -> Request<Body> {
+        Request::get("http://localhost/trust-bundle")
+            .body(Body::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn responses_are_signed() {
+        let tmp_dir = TempDir::new("signed").unwrap();
+        let path = tmp_dir.path().join("response_signing_key.pem");
+        let signer = ResponseSigner::load_or_create(&path).unwrap();
+        let handler = SignedHandler::new(TestHandler, Some(signer.clone()));
+
+        let response = handler.handle(request(), Parameters::new()).wait().unwrap();
+        let signature = response.headers()[SIGNATURE_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response.into_body().concat2().wait().unwrap();
+
+        assert_eq!(br#"{"certificate":"pem"}"#, &body[..]);
+        assert!(verify(&signer.verification_key(), &body, &signature).unwrap());
+    }
+
+    #[test]
+    fn responses_are_left_alone_without_a_signer() {
+        let handler = SignedHandler::new(TestHandler, None);
+        let response = handler.handle(request(), Parameters::new()).wait().unwrap();
+        assert!(response.headers().get(SIGNATURE_HEADER).is_none());
+    }
+}
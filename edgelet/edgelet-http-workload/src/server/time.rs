@@ -0,0 +1,91 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use failure::ResultExt;
+use futures::{future, Future};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+
+use edgelet_core::{Clock, TrustedClock};
+use edgelet_http::route::{Handler, Parameters};
+use workload::models::TimeResponse;
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// Serves the time of the daemon, corrected by the skew of its clock against
+/// IoT Hub, so that modules on devices with a broken RTC can correct their
+/// own timestamps.
+pub struct TimeHandler {
+    clock: TrustedClock,
+}
+
+impl TimeHandler {
+    pub fn new(clock: TrustedClock) -> Self {
+        TimeHandler { clock }
+    }
+}
+
+impl Handler<Parameters> for TimeHandler {
+    fn handle(
+        &self,
+        _req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let time = TimeResponse::new(self.clock.now().to_rfc3339());
+        let time = match self.clock.skew() {
+            Some(skew) => time.with_skew_seconds(skew.num_seconds()),
+            None => time,
+        };
+        let response = serde_json::to_string(&time)
+            .context(ErrorKind::Serde)
+            .map_err(Error::from)
+            .and_then(|b| {
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
+                    .body(b.into())
+                    .map_err(Error::from)
+            }).unwrap_or_else(|e| e.into_response());
+
+        Box::new(future::ok(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use futures::Stream;
+
+    use edgelet_core::ManualClock;
+
+    use super::*;
+
+    fn time(handler: &TimeHandler) -> TimeResponse {
+        let request = Request::get("http://localhost/time")
+            .body("".into())
+            .unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn time_is_corrected_by_the_skew() {
+        let now = Utc.ymd(1970, 1, 1).and_hms(0, 1, 0);
+        let clock = TrustedClock::new().with_clock(ManualClock::new(now));
+        let handler = TimeHandler::new(clock.clone());
+
+        let unmeasured = time(&handler);
+        assert_eq!(&now.to_rfc3339(), unmeasured.time());
+        assert_eq!(None, unmeasured.skew_seconds());
+
+        clock.record(now + Duration::seconds(90));
+        let measured = time(&handler);
+        assert_eq!(&(now + Duration::seconds(90)).to_rfc3339(), measured.time());
+        assert_eq!(Some(&90), measured.skew_seconds());
+    }
+}
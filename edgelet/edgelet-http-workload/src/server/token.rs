@@ -0,0 +1,138 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+
+use edgelet_core::{IdentityKeySource, TokenValidator};
+use edgelet_http::route::{Handler, Parameters};
+use workload::models::{ValidateTokensRequest, ValidateTokensResponse};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+pub struct ValidateTokensHandler<S> {
+    validator: TokenValidator<S>,
+}
+
+impl<S> ValidateTokensHandler<S> {
+    pub fn new(validator: TokenValidator<S>) -> Self {
+        ValidateTokensHandler { validator }
+    }
+}
+
+impl<S> Handler<Parameters> for ValidateTokensHandler<S>
+where
+    S: 'static + IdentityKeySource + Clone + Send + Sync,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let validator = self.validator.clone();
+        let response = req
+            .into_body()
+            .concat2()
+            .then(move |body| {
+                let request = body
+                    .context(ErrorKind::BadBody)
+                    .map_err(Error::from)
+                    .and_then(|body| {
+                        serde_json::from_slice::<ValidateTokensRequest>(&body)
+                            .context(ErrorKind::BadBody)
+                            .map_err(Error::from)
+                    });
+                match request {
+                    Ok(request) => future::Either::A(
+                        validator
+                            .validate(request.tokens())
+                            .map_err(|err| Error::from(err.context(ErrorKind::TokenValidation))),
+                    ),
+                    Err(err) => future::Either::B(future::err(err)),
+                }
+            }).and_then(|statuses| {
+                let statuses = statuses.iter().map(ToString::to_string).collect();
+                let body = serde_json::to_string(&ValidateTokensResponse::new(statuses))?;
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, body.len().to_string().as_str())
+                    .body(body.into())
+                    .map_err(Error::from)
+            }).or_else(|e| Ok::<_, HyperError>(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::crypto::MemoryKey;
+    use failure::Context;
+    use workload::models::ErrorResponse;
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct TestSource;
+
+    impl IdentityKeySource for TestSource {
+        type Error = Context<&'static str>;
+        type Future = future::FutureResult<Option<Vec<MemoryKey>>, Self::Error>;
+
+        fn get_keys(&self, _device_id: &str, _module_id: Option<&str>) -> Self::Future {
+            future::ok(None)
+        }
+    }
+
+    fn handler() -> ValidateTokensHandler<TestSource> {
+        ValidateTokensHandler::new(TokenValidator::new(TestSource))
+    }
+
+    #[test]
+    fn statuses_are_returned_in_the_order_of_the_tokens() {
+        let request = ValidateTokensRequest::new(vec![
+            "SharedAccessSignature sr=hub.azure-devices.net%2Fdevices%2Fleaf&sig=YWJj\
+             &se=4000000000"
+                .to_string(),
+            "not a token".to_string(),
+        ]);
+        let request = Request::post("http://localhost/tokens/validate")
+            .body(serde_json::to_string(&request).unwrap().into())
+            .unwrap();
+
+        let response = handler()
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let response: ValidateTokensResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            &vec!["unknownIdentity".to_string(), "malformed".to_string()],
+            response.statuses()
+        );
+    }
+
+    #[test]
+    fn bad_body() {
+        let request = Request::post("http://localhost/tokens/validate")
+            .body("tokens".into())
+            .unwrap();
+
+        let response = handler()
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(error.message().starts_with("Bad body"));
+    }
+}
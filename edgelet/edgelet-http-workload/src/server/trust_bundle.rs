@@ -7,17 +7,21 @@ use futures::{future, Future};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{Request, Response, StatusCode};
 use hyper::{Body, Error as HyperError};
+use serde::Serialize;
 use serde_json;
+use url::form_urlencoded;
 
-use edgelet_core::{Certificate, GetTrustBundle};
+use edgelet_core::{CaRotation, Certificate, GetTrustBundle, TrustBundleVersions};
 use edgelet_http::route::{Handler, Parameters};
-use workload::models::TrustBundleResponse;
+use workload::models::{TrustBundleDeltaResponse, TrustBundleResponse};
 
 use error::{Error, ErrorKind};
 use IntoResponse;
 
 pub struct TrustBundleHandler<T: GetTrustBundle> {
     hsm: T,
+    rotation: Option<CaRotation>,
+    versions: Option<TrustBundleVersions>,
 }
 
 impl<T> TrustBundleHandler<T>
@@ -25,7 +29,66 @@ where
     T: 'static + GetTrustBundle + Clone,
 {
     pub fn new(hsm: T) -> Self {
-        TrustBundleHandler { hsm }
+        TrustBundleHandler {
+            hsm,
+            rotation: None,
+            versions: None,
+        }
+    }
+
+    /// Serves the roots trusted before a rotation of the Edge CA as well
+    /// while `rotation` overlaps them with the new ones.
+    pub fn with_rotation(mut self, rotation: CaRotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// Numbers the trust bundles served with `versions`. A request with the
+    /// version the module has in `since` is served only the roots added and
+    /// removed since then, or a 304 if there are none.
+    pub fn with_versions(mut self, versions: TrustBundleVersions) -> Self {
+        self.versions = Some(versions);
+        self
+    }
+}
+
+impl<T> TrustBundleHandler<T>
+where
+    T: GetTrustBundle,
+    <T as GetTrustBundle>::Certificate: Certificate,
+{
+    fn trust_bundle(&self) -> Result<String, Error> {
+        let cert = self.hsm.get_trust_bundle().and_then(|cert| cert.pem())?;
+        let cert = str::from_utf8(cert.as_ref()).context(ErrorKind::Utf8)?;
+        match self.rotation {
+            Some(ref rotation) => rotation.trust_bundle(cert).map_err(Error::from),
+            None => Ok(cert.to_string()),
+        }
+    }
+
+    fn respond(&self, trust_bundle: String, since: Option<u64>) -> Result<Response<Body>, Error> {
+        let versions = match self.versions {
+            Some(ref versions) => versions,
+            None => return json_response(&TrustBundleResponse::new(trust_bundle)),
+        };
+
+        let version = versions.observe(&trust_bundle)?;
+        if since == Some(version) {
+            return Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())
+                .map_err(Error::from);
+        }
+        match since.and_then(|since| versions.delta(since)) {
+            Some(delta) => json_response(&TrustBundleDeltaResponse::new(
+                delta.version() as i64,
+                delta.added().to_vec(),
+                delta.removed().to_vec(),
+            )),
+            None => {
+                json_response(&TrustBundleResponse::new(trust_bundle).with_version(version as i64))
+            }
+        }
     }
 }
 
@@ -36,38 +99,45 @@ where
 {
     fn handle(
         &self,
-        _req: Request<Body>,
+        req: Request<Body>,
         _params: Parameters,
     ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
-        let response = self
-            .hsm
-            .get_trust_bundle()
-            .and_then(|cert| cert.pem())
-            .map_err(Error::from)
-            .and_then(|cert| {
-                str::from_utf8(cert.as_ref())
-                    .context(ErrorKind::Utf8)
-                    .map_err(From::from)
-                    .map(|s| s.to_string())
-            }).and_then(|cert| {
-                serde_json::to_string(&TrustBundleResponse::new(cert))
-                    .context(ErrorKind::Serde)
-                    .map_err(From::from)
-            }).and_then(|b| {
-                Response::builder()
-                    .status(StatusCode::OK)
-                    .header(CONTENT_TYPE, "application/json")
-                    .header(CONTENT_LENGTH, b.len().to_string().as_str())
-                    .body(b.into())
-                    .map_err(Error::from)
+        let response = since(&req)
+            .and_then(|since| {
+                self.trust_bundle()
+                    .and_then(|trust_bundle| self.respond(trust_bundle, since))
             }).unwrap_or_else(|e| e.into_response());
 
         Box::new(future::ok(response))
     }
 }
 
+/// The version of the trust bundle the module has, if it sent one.
+fn since(req: &Request<Body>) -> Result<Option<u64>, Error> {
+    form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .find(|&(ref key, _)| key == "since")
+        .map_or(Ok(None), |(_, since)| {
+            since
+                .parse::<u64>()
+                .map(Some)
+                .map_err(|_| Error::from(ErrorKind::BadParam))
+        })
+}
+
+fn json_response<B: Serialize>(body: &B) -> Result<Response<Body>, Error> {
+    let b = serde_json::to_string(body).context(ErrorKind::Serde)?;
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, b.len().to_string().as_str())
+        .body(b.into())
+        .map_err(Error::from)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use futures::Future;
     use futures::Stream;
 
@@ -174,4 +244,73 @@ mod tests {
             }).wait()
             .unwrap();
     }
+
+    #[test]
+    fn previous_roots_are_served_during_a_rotation() {
+        let old_root = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+        let new_root = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+        let rotation = CaRotation::new(Duration::from_secs(7 * 24 * 3600));
+        rotation.observe(old_root, old_root).unwrap();
+        rotation.observe(new_root, new_root).unwrap();
+
+        let cert = TestCert::default().with_cert(new_root.as_bytes().to_vec());
+        let handler =
+            TrustBundleHandler::new(TestHsm::default().with_cert(cert)).with_rotation(rotation);
+        let request = Request::get("http://localhost/trust-bundle")
+            .body("".into())
+            .unwrap();
+        let response = handler.handle(request, Parameters::new()).wait().unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let b = response.into_body().concat2().wait().unwrap();
+        let trust_bundle: TrustBundleResponse = serde_json::from_slice(&b).unwrap();
+        assert_eq!(
+            format!("{}{}", new_root, old_root),
+            trust_bundle.certificate().as_str()
+        );
+    }
+
+    #[test]
+    fn changes_since_a_version_are_served() {
+        let old_root = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+        let new_root = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+        let versions = TrustBundleVersions::new();
+        versions.observe(old_root).unwrap();
+
+        let bundle = format!("{}{}", old_root, new_root);
+        let cert = TestCert::default().with_cert(bundle.as_bytes().to_vec());
+        let handler =
+            TrustBundleHandler::new(TestHsm::default().with_cert(cert)).with_versions(versions);
+        let get = |uri: &str| {
+            let request = Request::get(uri).body("".into()).unwrap();
+            handler.handle(request, Parameters::new()).wait().unwrap()
+        };
+
+        let response = get("http://localhost/trust-bundle?api-version=2018-06-28");
+        assert_eq!(StatusCode::OK, response.status());
+        let b = response.into_body().concat2().wait().unwrap();
+        let trust_bundle: TrustBundleResponse = serde_json::from_slice(&b).unwrap();
+        assert_eq!(bundle, trust_bundle.certificate().as_str());
+        assert_eq!(Some(&2), trust_bundle.version());
+
+        let response = get("http://localhost/trust-bundle?api-version=2018-06-28&since=1");
+        assert_eq!(StatusCode::OK, response.status());
+        let b = response.into_body().concat2().wait().unwrap();
+        let delta: TrustBundleDeltaResponse = serde_json::from_slice(&b).unwrap();
+        assert_eq!(2, *delta.version());
+        assert_eq!(&vec![new_root.to_string()], delta.added());
+        assert!(delta.removed().is_empty());
+
+        let response = get("http://localhost/trust-bundle?api-version=2018-06-28&since=2");
+        assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+
+        // versions that were never served get the whole trust bundle
+        let response = get("http://localhost/trust-bundle?api-version=2018-06-28&since=7");
+        let b = response.into_body().concat2().wait().unwrap();
+        let trust_bundle: TrustBundleResponse = serde_json::from_slice(&b).unwrap();
+        assert_eq!(bundle, trust_bundle.certificate().as_str());
+
+        let response = get("http://localhost/trust-bundle?api-version=2018-06-28&since=latest");
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
 }
@@ -0,0 +1,87 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Arc;
+
+use futures::Future;
+use http::{Request, Response};
+use hyper::{Body, Error as HyperError};
+
+use edgelet_core::{WorkloadCall, WorkloadUsage};
+use edgelet_http::route::{Handler, Parameters};
+
+/// Handles requests with `inner` and accounts each of them to the module in
+/// the path. It goes inside the authorization of the route, so that a call
+/// is only accounted to a module the caller was found to be.
+pub struct Counted<H> {
+    inner: Arc<H>,
+    usage: WorkloadUsage,
+    call: WorkloadCall,
+}
+
+impl<H> Counted<H> {
+    pub fn new(inner: H, usage: WorkloadUsage, call: WorkloadCall) -> Self {
+        Counted {
+            inner: Arc::new(inner),
+            usage,
+            call,
+        }
+    }
+}
+
+impl<H> Handler<Parameters> for Counted<H>
+where
+    H: Handler<Parameters> + Sync,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        if let Some(name) = params.name("name") {
+            self.usage.record(name, self.call);
+        }
+        self.inner.handle(req, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::Metrics;
+    use futures::future;
+    use http::StatusCode;
+
+    use super::*;
+
+    struct TestHandler;
+
+    impl Handler<Parameters> for TestHandler {
+        fn handle(
+            &self,
+            _req: Request<Body>,
+            _params: Parameters,
+        ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+            let response = Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::default())
+                .unwrap();
+            Box::new(future::ok(response))
+        }
+    }
+
+    #[test]
+    fn calls_are_accounted_to_the_module() {
+        let usage = WorkloadUsage::new(Metrics::new());
+        let handler = Counted::new(TestHandler, usage.clone(), WorkloadCall::Sign);
+        let request = Request::post("http://localhost/modules/m1/genid/1/sign")
+            .body(Body::default())
+            .unwrap();
+        let parameters =
+            Parameters::with_captures(vec![(Some("name".to_string()), "m1".to_string())]);
+
+        let response = handler.handle(request, parameters).wait().unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(1, usage.usage("m1").signs());
+        assert_eq!(0, usage.usage("m1").encrypts());
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::str;
+
+use chrono::{DateTime, Utc};
+use failure::ResultExt;
+use futures::{Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde_json;
+
+use edgelet_core::{
+    verify_chain, CaRotation, Certificate, ChainVerification, GetTrustBundle,
+    VerifiedCertificate as CoreVerifiedCertificate,
+};
+use edgelet_http::route::{Handler, Parameters};
+use workload::models::{VerifiedCertificate, VerifyCertificateRequest, VerifyCertificateResponse};
+
+use error::{Error, ErrorKind};
+use IntoResponse;
+
+/// Serves `POST /certificate/verify`, which verifies a certificate chain a
+/// module was presented with, such as the one of a downstream device,
+/// against the trust bundle.
+pub struct VerifyCertificateHandler<T: GetTrustBundle> {
+    hsm: T,
+    rotation: Option<CaRotation>,
+}
+
+impl<T> VerifyCertificateHandler<T>
+where
+    T: 'static + GetTrustBundle + Clone,
+{
+    pub fn new(hsm: T) -> Self {
+        VerifyCertificateHandler {
+            hsm,
+            rotation: None,
+        }
+    }
+
+    /// Trusts the roots trusted before a rotation of the Edge CA as well
+    /// while `rotation` overlaps them with the new ones.
+    pub fn with_rotation(mut self, rotation: CaRotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+}
+
+impl<T> Handler<Parameters> for VerifyCertificateHandler<T>
+where
+    T: 'static + GetTrustBundle + Clone + Send,
+    <T as GetTrustBundle>::Certificate: Certificate,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        _params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let hsm = self.hsm.clone();
+        let rotation = self.rotation.clone();
+        let response = req
+            .into_body()
+            .concat2()
+            .then(move |body| {
+                let body = body.context(ErrorKind::BadBody)?;
+                let request = serde_json::from_slice::<VerifyCertificateRequest>(&body)
+                    .context(ErrorKind::BadBody)?;
+                let trust_bundle = trust_bundle(&hsm, rotation.as_ref())?;
+                let verification =
+                    verify_chain(request.certificate().as_bytes(), trust_bundle.as_bytes())
+                        .context(ErrorKind::InvalidCertificate)?;
+                let body = serde_json::to_string(&core_to_response(&verification))
+                    .context(ErrorKind::Serde)?;
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, body.len().to_string().as_str())
+                    .body(body.into())
+                    .map_err(Error::from)
+            }).or_else(|e| Ok::<_, HyperError>(e.into_response()));
+
+        Box::new(response)
+    }
+}
+
+fn trust_bundle<T>(hsm: &T, rotation: Option<&CaRotation>) -> Result<String, Error>
+where
+    T: GetTrustBundle,
+    <T as GetTrustBundle>::Certificate: Certificate,
+{
+    let cert = hsm.get_trust_bundle().and_then(|cert| cert.pem())?;
+    let cert = str::from_utf8(cert.as_ref())?.to_string();
+    match rotation {
+        Some(rotation) => rotation.trust_bundle(&cert).map_err(Error::from),
+        None => Ok(cert),
+    }
+}
+
+fn core_to_response(verification: &ChainVerification) -> VerifyCertificateResponse {
+    let now = Utc::now();
+    let certificates = verification
+        .certificates()
+        .iter()
+        .map(|cert| core_to_certificate(cert, now))
+        .collect();
+    let response = VerifyCertificateResponse::new(verification.is_valid(), certificates);
+    let response = match verification.error() {
+        Some(error) => response.with_error(error.to_string()),
+        None => response,
+    };
+    match verification.error_depth() {
+        Some(depth) => response.with_error_depth(depth as i32),
+        None => response,
+    }
+}
+
+fn core_to_certificate(cert: &CoreVerifiedCertificate, now: DateTime<Utc>) -> VerifiedCertificate {
+    let certificate = VerifiedCertificate::new(
+        cert.not_before().to_rfc3339(),
+        cert.not_after().to_rfc3339(),
+        cert.is_expired(now),
+    );
+    match cert.common_name() {
+        Some(common_name) => certificate.with_common_name(common_name.to_string()),
+        None => certificate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{Error as CoreError, ErrorKind as CoreErrorKind};
+    use edgelet_test_utils::cert::TestCert;
+    use workload::models::ErrorResponse;
+
+    use super::*;
+
+    #[derive(Clone, Default, Debug)]
+    struct TestHsm {
+        fail_call: bool,
+    }
+
+    impl GetTrustBundle for TestHsm {
+        type Certificate = TestCert;
+
+        fn get_trust_bundle(&self) -> Result<Self::Certificate, CoreError> {
+            if self.fail_call {
+                Err(CoreError::from(CoreErrorKind::Io))
+            } else {
+                Ok(TestCert::default().with_cert(b"".to_vec()))
+            }
+        }
+    }
+
+    fn verify(hsm: TestHsm, body: &str) -> Response<Body> {
+        let request = Request::post("http://localhost/certificate/verify")
+            .body(body.to_string().into())
+            .unwrap();
+        VerifyCertificateHandler::new(hsm)
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap()
+    }
+
+    fn error(response: Response<Body>) -> ErrorResponse {
+        let body = response.into_body().concat2().wait().unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn bad_body_is_a_bad_request() {
+        let response = verify(TestHsm::default(), "{}");
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn certificate_that_does_not_parse_is_a_bad_request() {
+        let request = VerifyCertificateRequest::new("not a certificate".to_string());
+        let response = verify(
+            TestHsm::default(),
+            &serde_json::to_string(&request).unwrap(),
+        );
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        let error = error(response);
+        assert!(error.message().starts_with("Invalid certificate chain"));
+        assert_eq!(Some("user-config"), error.category());
+    }
+
+    #[test]
+    fn trust_bundle_failure_is_an_error() {
+        let request = VerifyCertificateRequest::new("not a certificate".to_string());
+        let response = verify(
+            TestHsm { fail_call: true },
+            &serde_json::to_string(&request).unwrap(),
+        );
+        assert!(response.status().is_server_error());
+    }
+}
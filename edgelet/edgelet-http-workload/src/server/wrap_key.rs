@@ -0,0 +1,310 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Wrapping of the keys modules use for envelope encryption.
+//!
+//! A module that encrypts a local database with a key of its own only needs
+//! that key protected by the HSM, not the data. The key is encrypted with a
+//! key-encryption key (KEK) the HSM holds for the module, which is derived
+//! from an identity of its own so that keys wrapped here can't be recovered
+//! through the decrypt endpoint, nor data encrypted there through this one.
+
+use base64;
+use edgelet_core::{Decrypt, Encrypt, GenerationRegistry};
+use edgelet_http::route::{Handler, Parameters};
+use error::{Error, ErrorKind};
+use failure::ResultExt;
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use hyper::{Body, Error as HyperError};
+use serde::Serialize;
+use serde_json;
+use workload::models::{UnwrapKeyRequest, UnwrapKeyResponse, WrapKeyRequest, WrapKeyResponse};
+use IntoResponse;
+
+/// The longest key that can be wrapped, enough for any AES or HMAC key.
+const MAX_KEY_LEN: usize = 64;
+
+fn kek_id(module_id: &str, genid: &str) -> String {
+    format!("{}{}/kek", module_id, genid)
+}
+
+fn module_params(params: &Parameters) -> Result<(String, String), Error> {
+    let name = params
+        .name("name")
+        .ok_or_else(|| Error::from(ErrorKind::BadParam))?;
+    let genid = params
+        .name("genid")
+        .ok_or_else(|| Error::from(ErrorKind::BadParam))?;
+    Ok((name.to_string(), genid.to_string()))
+}
+
+fn json_response<T: Serialize>(response: &T) -> Response<Body> {
+    let body = serde_json::to_string(response).expect("Generated an invalid response object");
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_LENGTH, body.len().to_string().as_str())
+        .body(body.into())
+        .expect("Generated an invalid http::Response object")
+}
+
+pub struct WrapKeyHandler<T: Encrypt> {
+    hsm: T,
+    generations: GenerationRegistry,
+}
+
+impl<T: Encrypt> WrapKeyHandler<T> {
+    pub fn new(hsm: T) -> Self {
+        WrapKeyHandler {
+            hsm,
+            generations: GenerationRegistry::new(),
+        }
+    }
+
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+}
+
+impl<T> Handler<Parameters> for WrapKeyHandler<T>
+where
+    T: Encrypt + 'static + Clone + Send,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let hsm = self.hsm.clone();
+        let generations = self.generations.clone();
+        let response = match module_params(&params) {
+            Ok((module_id, genid)) => {
+                let id = kek_id(&module_id, &genid);
+                let ok = req.into_body().concat2().map(move |b| {
+                    serde_json::from_slice::<WrapKeyRequest>(&b)
+                        .context(ErrorKind::BadBody)
+                        .map_err(Error::from)
+                        .and_then(|request| {
+                            let key = base64::decode(request.key())?;
+                            if key.is_empty() || key.len() > MAX_KEY_LEN {
+                                return Err(Error::from(ErrorKind::BadKeyLength));
+                            }
+                            let initialization_vector =
+                                base64::decode(request.initialization_vector())?;
+                            generations
+                                .with_current(&module_id, &genid, || {
+                                    hsm.encrypt(id.as_bytes(), &key, &initialization_vector)
+                                }).context(ErrorKind::StaleGeneration)?
+                                .map_err(Error::from)
+                        }).map(|wrapped| {
+                            json_response(&WrapKeyResponse::new(base64::encode(&wrapped)))
+                        }).unwrap_or_else(|e| e.into_response())
+                });
+                future::Either::A(ok)
+            }
+            Err(e) => future::Either::B(future::ok(e.into_response())),
+        };
+        Box::new(response)
+    }
+}
+
+pub struct UnwrapKeyHandler<T: Decrypt> {
+    hsm: T,
+    generations: GenerationRegistry,
+}
+
+impl<T: Decrypt> UnwrapKeyHandler<T> {
+    pub fn new(hsm: T) -> Self {
+        UnwrapKeyHandler {
+            hsm,
+            generations: GenerationRegistry::new(),
+        }
+    }
+
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+}
+
+impl<T> Handler<Parameters> for UnwrapKeyHandler<T>
+where
+    T: Decrypt + 'static + Clone + Send,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        let hsm = self.hsm.clone();
+        let generations = self.generations.clone();
+        let response = match module_params(&params) {
+            Ok((module_id, genid)) => {
+                let id = kek_id(&module_id, &genid);
+                let ok = req.into_body().concat2().map(move |b| {
+                    serde_json::from_slice::<UnwrapKeyRequest>(&b)
+                        .context(ErrorKind::BadBody)
+                        .map_err(Error::from)
+                        .and_then(|request| {
+                            let wrapped = base64::decode(request.wrapped_key())?;
+                            let initialization_vector =
+                                base64::decode(request.initialization_vector())?;
+                            generations
+                                .with_current(&module_id, &genid, || {
+                                    hsm.decrypt(id.as_bytes(), &wrapped, &initialization_vector)
+                                }).context(ErrorKind::StaleGeneration)?
+                                .map_err(Error::from)
+                        }).map(|key| {
+                            json_response(&UnwrapKeyResponse::new(base64::encode(&key)))
+                        }).unwrap_or_else(|e| e.into_response())
+                });
+                future::Either::A(ok)
+            }
+            Err(e) => future::Either::B(future::ok(e.into_response())),
+        };
+        Box::new(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::Error as CoreError;
+    use workload::models::ErrorResponse;
+
+    use super::*;
+
+    // "Wraps" a key by prefixing it with the identity of the KEK, so that the
+    // tests can tell which key was used.
+    #[derive(Clone, Debug, Default)]
+    struct TestHsm;
+
+    impl Encrypt for TestHsm {
+        type Buffer = Vec<u8>;
+
+        fn encrypt(
+            &self,
+            client_id: &[u8],
+            plaintext: &[u8],
+            _initialization_vector: &[u8],
+        ) -> Result<Self::Buffer, CoreError> {
+            Ok([client_id, b":", plaintext].concat())
+        }
+    }
+
+    impl Decrypt for TestHsm {
+        type Buffer = Vec<u8>;
+
+        fn decrypt(
+            &self,
+            client_id: &[u8],
+            ciphertext: &[u8],
+            _initialization_vector: &[u8],
+        ) -> Result<Self::Buffer, CoreError> {
+            let prefix = [client_id, b":"].concat();
+            assert!(ciphertext.starts_with(&prefix));
+            Ok(ciphertext[prefix.len()..].to_vec())
+        }
+    }
+
+    fn params() -> Parameters {
+        Parameters::with_captures(vec![
+            (Some("name".to_string()), "test".to_string()),
+            (Some("genid".to_string()), "I".to_string()),
+        ])
+    }
+
+    fn wrap(key: &[u8]) -> Response<Body> {
+        let request = WrapKeyRequest::new(base64::encode(key), base64::encode("iv"));
+        let request = Request::post("http://localhost/modules/test/genid/I/wrapkey")
+            .body(serde_json::to_string(&request).unwrap().into())
+            .unwrap();
+        WrapKeyHandler::new(TestHsm)
+            .handle(request, params())
+            .wait()
+            .unwrap()
+    }
+
+    fn error_message(response: Response<Body>) -> String {
+        let body = response.into_body().concat2().wait().unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        error.message().to_string()
+    }
+
+    #[test]
+    fn keys_are_wrapped_with_the_kek_of_the_module_and_unwrapped() {
+        let response = wrap(b"0123456789abcdef");
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let wrapped: WrapKeyResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            b"testI/kek:0123456789abcdef".to_vec(),
+            base64::decode(wrapped.wrapped_key()).unwrap()
+        );
+
+        let request = UnwrapKeyRequest::new(wrapped.wrapped_key().clone(), base64::encode("iv"));
+        let request = Request::post("http://localhost/modules/test/genid/I/unwrapkey")
+            .body(serde_json::to_string(&request).unwrap().into())
+            .unwrap();
+        let response = UnwrapKeyHandler::new(TestHsm)
+            .handle(request, params())
+            .wait()
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().concat2().wait().unwrap();
+        let unwrapped: UnwrapKeyResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            b"0123456789abcdef".to_vec(),
+            base64::decode(unwrapped.key()).unwrap()
+        );
+    }
+
+    #[test]
+    fn keys_of_the_wrong_length_are_rejected() {
+        for key in &[vec![], vec![0; MAX_KEY_LEN + 1]] {
+            let response = wrap(key);
+
+            assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, response.status());
+            assert_eq!(
+                "The key must be between 1 and 64 bytes long",
+                error_message(response)
+            );
+        }
+        assert_eq!(StatusCode::OK, wrap(&[0; MAX_KEY_LEN]).status());
+    }
+
+    #[test]
+    fn stale_generations_are_rejected() {
+        let generations = GenerationRegistry::new();
+        generations.advance("test", "II");
+        let request = UnwrapKeyRequest::new(base64::encode("key"), base64::encode("iv"));
+        let request = Request::post("http://localhost/modules/test/genid/I/unwrapkey")
+            .body(serde_json::to_string(&request).unwrap().into())
+            .unwrap();
+
+        let response = UnwrapKeyHandler::new(TestHsm)
+            .with_generations(generations)
+            .handle(request, params())
+            .wait()
+            .unwrap();
+
+        assert_eq!(StatusCode::GONE, response.status());
+    }
+
+    #[test]
+    fn missing_params_are_rejected() {
+        let request = Request::post("http://localhost/modules/test/wrapkey")
+            .body(Body::default())
+            .unwrap();
+
+        let response = WrapKeyHandler::new(TestHsm)
+            .handle(request, Parameters::new())
+            .wait()
+            .unwrap();
+
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert_eq!("Bad parameter", error_message(response));
+    }
+}
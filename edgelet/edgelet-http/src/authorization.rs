@@ -1,7 +1,10 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use edgelet_core::pid::Pid;
-use edgelet_core::{Authorization as CoreAuth, Error as CoreError, Module, ModuleRuntime, Policy};
+use edgelet_core::{
+    Authorization as CoreAuth, Error as CoreError, HostProcesses, Module, ModuleRuntime,
+    PeerIdentity, Policy,
+};
 use error::{Error, ErrorKind};
 use futures::{future, Future};
 use hyper::{self, Body, Request, Response};
@@ -31,6 +34,13 @@ where
             inner: Arc::new(inner),
         }
     }
+
+    /// Lets the registered host processes call as the modules they are
+    /// registered as.
+    pub fn with_host_processes(mut self, host_processes: HostProcesses) -> Self {
+        self.auth = self.auth.with_host_processes(host_processes);
+        self
+    }
 }
 
 impl<H, M> Handler<Parameters> for Authorization<H, M>
@@ -54,9 +64,13 @@ where
         );
         let inner = self.inner.clone();
 
-        let response = self
-            .auth
-            .authorize(name, pid)
+        // callers that authenticated with a certificate are authorized by
+        // the modules it is bound to, since their pid can't be told
+        let authorized = match req.extensions().get::<PeerIdentity>() {
+            Some(peer) => future::Either::A(self.auth.authorize_peer(name, peer)),
+            None => future::Either::B(self.auth.authorize(name, pid)),
+        };
+        let response = authorized
             .map_err(Error::from)
             .and_then(move |authorized| {
                 if authorized {
@@ -75,7 +89,9 @@ mod tests {
     use std::time::Duration;
 
     use super::*;
-    use edgelet_core::{LogOptions, ModuleRegistry, ModuleRuntimeState, ModuleSpec, SystemInfo};
+    use edgelet_core::{
+        LogOptions, ModuleRegistry, ModuleRuntimeState, ModuleSpec, SystemInfo, UpdateStrategy,
+    };
     use futures::future::FutureResult;
     use futures::stream::Empty;
     use futures::{stream, Stream};
@@ -153,6 +169,23 @@ mod tests {
         assert_eq!(404, response.status());
     }
 
+    #[test]
+    fn handler_authorizes_peers_by_their_certificate() {
+        let runtime = TestModuleList::new(vec![TestModule::new("abc", 123)]);
+        let auth = Authorization::new(TestHandler::new(), Policy::Caller, runtime);
+        let peer = PeerIdentity::new("child".to_string(), vec!["sensor".to_string()]);
+
+        for (name, status) in vec![("sensor", 200), ("abc", 404)] {
+            let params =
+                Parameters::with_captures(vec![(Some("name".to_string()), name.to_string())]);
+            let mut request = Request::default();
+            request.extensions_mut().insert(Pid::Any);
+            request.extensions_mut().insert(peer.clone());
+            let response = auth.handle(request, params).wait().unwrap();
+            assert_eq!(status, response.status());
+        }
+    }
+
     #[derive(Clone)]
     struct TestHandler {}
 
@@ -285,6 +318,7 @@ mod tests {
         type StopFuture = FutureResult<(), Self::Error>;
         type SystemInfoFuture = FutureResult<SystemInfo, Self::Error>;
         type RemoveAllFuture = FutureResult<(), Self::Error>;
+        type UpdateFuture = FutureResult<(), Self::Error>;
 
         fn init(&self) -> Self::InitFuture {
             notimpl_error!()
@@ -314,6 +348,10 @@ mod tests {
             notimpl_error!()
         }
 
+        fn remove_with_volumes(&self, _id: &str) -> Self::RemoveFuture {
+            notimpl_error!()
+        }
+
         fn list(&self) -> Self::ListFuture {
             future::ok(self.modules.clone())
         }
@@ -338,5 +376,13 @@ mod tests {
         fn remove_all(&self) -> Self::RemoveAllFuture {
             notimpl_error!()
         }
+
+        fn update(
+            &self,
+            _module: ModuleSpec<Self::Config>,
+            _strategy: UpdateStrategy,
+        ) -> Self::UpdateFuture {
+            notimpl_error!()
+        }
     }
 }
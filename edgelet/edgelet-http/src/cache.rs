@@ -0,0 +1,265 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration as StdDuration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use edgelet_core::{Clock, SystemClock};
+use futures::{future, Future, Stream};
+use http::header::{HeaderValue, CONTENT_LENGTH, ETAG, IF_NONE_MATCH};
+use http::{HeaderMap, StatusCode};
+use hyper::{self, Body, Request, Response};
+
+use route::{Handler, Parameters};
+
+struct Entry {
+    headers: HeaderMap,
+    body: Bytes,
+    etag: HeaderValue,
+    expires_at: DateTime<Utc>,
+}
+
+impl Entry {
+    fn response(&self, req: &HeaderMap) -> Response<Body> {
+        if matches_etag(req, &self.etag) {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            response.headers_mut().insert(ETAG, self.etag.clone());
+            response
+        } else {
+            let mut response = Response::new(Body::from(self.body.clone()));
+            *response.headers_mut() = self.headers.clone();
+            response
+        }
+    }
+}
+
+/// Handles requests for a resource that is polled far more often than it
+/// changes with `inner`, and serves what `inner` responded for a while after.
+/// Successful responses carry an `ETag` of their body, and a request whose
+/// `If-None-Match` has it gets an empty `304 Not Modified`.
+///
+/// Every request with the same query string is served the same response, so
+/// this is only meant for routes without path parameters whose response
+/// doesn't depend on the caller.
+pub struct Cached<H> {
+    inner: Arc<H>,
+    ttl: Duration,
+    clock: Arc<Clock>,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl<H> Cached<H>
+where
+    H: Handler<Parameters> + Sync,
+{
+    pub fn new(inner: H, ttl: StdDuration) -> Self {
+        Cached {
+            inner: Arc::new(inner),
+            ttl: Duration::from_std(ttl).expect("the cache time to live is a few seconds"),
+            clock: Arc::new(SystemClock),
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_clock<C: 'static + Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+fn lock(entries: &Mutex<HashMap<String, Entry>>) -> MutexGuard<HashMap<String, Entry>> {
+    entries.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<H> Handler<Parameters> for Cached<H>
+where
+    H: Handler<Parameters> + Sync,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+        let now = self.clock.now();
+        let query = req.uri().query().unwrap_or("").to_string();
+        if let Some(entry) = lock(&self.entries).get(&query) {
+            if now < entry.expires_at {
+                return Box::new(future::ok(entry.response(req.headers())));
+            }
+        }
+
+        let req_headers = req.headers().clone();
+        let expires_at = now + self.ttl;
+        let cache = self.entries.clone();
+        let response = self
+            .inner
+            .handle(req, params)
+            .and_then(|response| {
+                let (parts, body) = response.into_parts();
+                body.concat2().map(|body| (parts, Bytes::from(body)))
+            }).map(move |(parts, body)| {
+                // errors aren't cached, so that the next request tries again
+                if parts.status != StatusCode::OK {
+                    return Response::from_parts(parts, Body::from(body));
+                }
+                let mut headers = parts.headers;
+                let etag = etag(&body);
+                headers.insert(ETAG, etag.clone());
+                headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+                let entry = Entry {
+                    headers,
+                    body,
+                    etag,
+                    expires_at,
+                };
+                let response = entry.response(&req_headers);
+                let mut entries = lock(&cache);
+                entries.retain(|_, entry| now < entry.expires_at);
+                entries.insert(query, entry);
+                response
+            });
+
+        Box::new(response)
+    }
+}
+
+fn etag(body: &[u8]) -> HeaderValue {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    HeaderValue::from_str(&format!("\"{:016x}\"", hasher.finish()))
+        .expect("a quoted hex string is a valid header value")
+}
+
+fn matches_etag(req: &HeaderMap, etag: &HeaderValue) -> bool {
+    let etag = match etag.to_str() {
+        Ok(etag) => etag,
+        Err(_) => return false,
+    };
+    req.get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_left_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use edgelet_core::ManualClock;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TestHandler {
+        calls: Arc<AtomicUsize>,
+        status: Option<StatusCode>,
+    }
+
+    impl Handler<Parameters> for TestHandler {
+        fn handle(
+            &self,
+            _req: Request<Body>,
+            _params: Parameters,
+        ) -> Box<Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst);
+            let mut response = Response::new(Body::from(format!("response {}", calls)));
+            *response.status_mut() = self.status.unwrap_or(StatusCode::OK);
+            Box::new(future::ok(response))
+        }
+    }
+
+    fn get(handler: &Cached<TestHandler>, etag: Option<&HeaderValue>) -> Response<Body> {
+        get_uri(handler, "http://localhost/trust-bundle", etag)
+    }
+
+    fn get_uri(
+        handler: &Cached<TestHandler>,
+        uri: &str,
+        etag: Option<&HeaderValue>,
+    ) -> Response<Body> {
+        let mut request = Request::get(uri);
+        if let Some(etag) = etag {
+            request.header(IF_NONE_MATCH, etag.clone());
+        }
+        handler
+            .handle(request.body(Body::empty()).unwrap(), Parameters::new())
+            .wait()
+            .unwrap()
+    }
+
+    fn body(response: Response<Body>) -> String {
+        let body = response.into_body().concat2().wait().unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn responses_are_served_from_the_cache_until_they_expire() {
+        let clock = ManualClock::new(Utc::now());
+        let inner = TestHandler::default();
+        let calls = inner.calls.clone();
+        let handler = Cached::new(inner, StdDuration::from_secs(10)).with_clock(clock.clone());
+
+        let first = get(&handler, None);
+        let etag = first.headers()[ETAG].clone();
+        assert_eq!("response 0", body(first));
+        assert_eq!("response 0", body(get(&handler, None)));
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        clock.advance(Duration::seconds(10));
+        let refreshed = get(&handler, None);
+        assert_ne!(etag, refreshed.headers()[ETAG]);
+        assert_eq!("response 1", body(refreshed));
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn matching_etag_is_not_modified() {
+        let handler = Cached::new(TestHandler::default(), StdDuration::from_secs(10));
+        let etag = get(&handler, None).headers()[ETAG].clone();
+
+        let response = get(&handler, Some(&etag));
+        assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+        assert_eq!(etag, response.headers()[ETAG]);
+        assert_eq!("", body(response));
+
+        let other = HeaderValue::from_static("\"other\"");
+        assert_eq!(StatusCode::OK, get(&handler, Some(&other)).status());
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let inner = TestHandler {
+            status: Some(StatusCode::INTERNAL_SERVER_ERROR),
+            ..TestHandler::default()
+        };
+        let calls = inner.calls.clone();
+        let handler = Cached::new(inner, StdDuration::from_secs(10));
+
+        let response = get(&handler, None);
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert!(response.headers().get(ETAG).is_none());
+        get(&handler, None);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn responses_are_cached_per_query_string() {
+        let inner = TestHandler::default();
+        let calls = inner.calls.clone();
+        let handler = Cached::new(inner, StdDuration::from_secs(10));
+
+        let since = "http://localhost/trust-bundle?since=1";
+        assert_eq!("response 0", body(get(&handler, None)));
+        assert_eq!("response 1", body(get_uri(&handler, since, None)));
+        assert_eq!("response 0", body(get(&handler, None)));
+        assert_eq!("response 1", body(get_uri(&handler, since, None)));
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+}
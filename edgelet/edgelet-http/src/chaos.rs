@@ -0,0 +1,67 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Arc;
+
+use edgelet_core::chaos::{self, FaultTarget};
+use futures::future::{self, Either};
+use futures::Future;
+use hyper::{Body, Error as HyperError, Request, Response, StatusCode};
+
+use client::ClientImpl;
+
+/// A client whose requests are subject to the faults being injected into
+/// requests to IoT Hub. A request that a fault fails never leaves the device,
+/// and gets a 503 response as if the hub were unavailable.
+#[derive(Clone)]
+pub struct FaultyClient<C> {
+    inner: Arc<C>,
+}
+
+impl<C> FaultyClient<C> {
+    pub fn new(inner: C) -> Self {
+        FaultyClient {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl<C> ClientImpl for FaultyClient<C>
+where
+    C: 'static + ClientImpl,
+{
+    type Response = Box<Future<Item = Response<Body>, Error = HyperError> + Send>;
+
+    fn call(&self, req: Request<Body>) -> Self::Response {
+        let operation = req.method().as_str().to_lowercase();
+        let inner = self.inner.clone();
+        Box::new(
+            chaos::inject_async(FaultTarget::Hub, &operation).then(move |result| match result {
+                Ok(()) => Either::A(inner.call(req)),
+                Err(err) => {
+                    let response = Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .body(err.to_string().into())
+                        .expect("response with a status and body is valid");
+                    Either::B(future::ok(response))
+                }
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_go_through_without_faults() {
+        let client = FaultyClient::new(|_req: Request<Body>| {
+            let response: Result<_, HyperError> = Ok(Response::new(Body::empty()));
+            response
+        });
+        let req = Request::get("http://localhost/devices")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(StatusCode::OK, client.call(req).wait().unwrap().status());
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use chrono::{DateTime, Duration, Utc};
+use edgelet_core::{Clock, SystemClock, TrustedClock};
 use futures::{Future, IntoFuture, Stream};
 use hyper::{self, Body, Error as HyperError, Method, Request, Response};
 use serde::de::DeserializeOwned;
@@ -58,6 +59,8 @@ pub struct Client<C, T> {
     api_version: String,
     host_name: Url,
     user_agent: Option<String>,
+    clock: Arc<Clock>,
+    trusted_clock: Option<TrustedClock>,
 }
 
 impl<C, T> Client<C, T>
@@ -78,6 +81,8 @@ where
             api_version: ensure_not_empty!(api_version).to_string(),
             host_name,
             user_agent: None,
+            clock: Arc::new(SystemClock),
+            trusted_clock: None,
         };
 
         Ok(client)
@@ -93,6 +98,21 @@ where
         self
     }
 
+    /// Computes the expiry of the SAS tokens added to requests from the time
+    /// of `clock`.
+    pub fn with_clock<K: 'static + Clock>(mut self, clock: K) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Records the `Date` of the responses as the trusted time of
+    /// `trusted_clock`, so that it measures the skew of the clock of the
+    /// device against the time of the server.
+    pub fn with_trusted_clock(mut self, trusted_clock: TrustedClock) -> Self {
+        self.trusted_clock = Some(trusted_clock);
+        self
+    }
+
     pub fn inner(&self) -> &C {
         &self.inner
     }
@@ -112,7 +132,7 @@ where
     fn add_sas_token(&self, req: &mut Request<Body>, path: &str) -> Result<(), Error> {
         if let Some(ref source) = self.token_source {
             let token_duration = Duration::hours(1);
-            let expiry = Utc::now() + token_duration;
+            let expiry = self.clock.now() + token_duration;
             let token = source.get(&expiry).map_err(|err| err.into())?;
             debug!(
                 "Success generating token for request {} {}",
@@ -188,12 +208,17 @@ where
 
                 Ok(req)
             }).map(|req| {
+                let trusted_clock = self.trusted_clock.clone();
                 self.inner
                     .call(req)
                     .map_err(|e| {
                         error!("{:?}", e);
                         Error::from(e)
-                    }).and_then(|resp| {
+                    }).and_then(move |resp| {
+                        // error responses carry the time of the server too
+                        if let Some(ref trusted_clock) = trusted_clock {
+                            record_date(trusted_clock, resp.headers());
+                        }
                         let (http::response::Parts { status, .. }, body) = resp.into_parts();
                         body.concat2()
                             .and_then(move |body| Ok((status, body)))
@@ -229,10 +254,22 @@ where
             api_version: self.api_version.clone(),
             host_name: self.host_name.clone(),
             user_agent: self.user_agent.clone(),
+            clock: self.clock.clone(),
+            trusted_clock: self.trusted_clock.clone(),
         }
     }
 }
 
+fn record_date(trusted_clock: &TrustedClock, headers: &http::HeaderMap) {
+    let date = headers
+        .get(http::header::DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+    if let Some(date) = date {
+        trusted_clock.record(date.with_timezone(&Utc));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -240,7 +277,8 @@ mod tests {
     use std::mem;
     use std::str;
 
-    use chrono::{DateTime, Utc};
+    use chrono::{DateTime, TimeZone, Utc};
+    use edgelet_core::ManualClock;
     use futures::future;
     use hyper::{Client as HyperClient, Request, Response};
     use tokio;
@@ -443,6 +481,40 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn sas_token_expires_an_hour_from_now() {
+        #[derive(Clone)]
+        struct ExpiryTokenSource;
+
+        impl TokenSource for ExpiryTokenSource {
+            type Error = Error;
+            fn get(&self, expiry: &DateTime<Utc>) -> Result<String, Error> {
+                Ok(expiry.timestamp().to_string())
+            }
+        }
+
+        let handler = |req: Request<Body>| {
+            let sas_header = req.headers().get(hyper::header::AUTHORIZATION).unwrap();
+            assert_eq!("SharedAccessSignature 1540003600", *sas_header);
+            Ok(Response::new(r#""response""#.into()))
+        };
+        let client = Client::new(
+            handler,
+            Some(ExpiryTokenSource),
+            "2018-04-10",
+            Url::parse("http://localhost").unwrap(),
+        ).unwrap()
+        .with_clock(ManualClock::new(Utc.timestamp(1_540_000_000, 0)));
+
+        let task = client.request::<String, String>(Method::GET, "/boo", None, None, false);
+
+        let _result: String = tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap()
+            .unwrap();
+    }
+
     #[test]
     fn request_adds_if_match_header() {
         let api_version = "2018-04-10";
@@ -569,4 +641,35 @@ mod tests {
             .unwrap();
         assert_eq!(result, "response");
     }
+
+    #[test]
+    fn request_records_the_date_of_responses() {
+        let api_version = "2018-04-10";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let token_source: Option<StaticTokenSource> = None;
+
+        let handler = |_req: Request<Body>| {
+            let mut response = Response::new(Body::empty());
+            response.headers_mut().insert(
+                hyper::header::DATE,
+                "Mon, 01 Oct 2018 12:00:00 GMT".parse().unwrap(),
+            );
+            Ok(response)
+        };
+        let now = Utc.timestamp(1_000_000, 0);
+        let trusted_clock = TrustedClock::new().with_clock(ManualClock::new(now));
+        let client = Client::new(handler, token_source, api_version, host_name)
+            .unwrap()
+            .with_trusted_clock(trusted_clock.clone());
+
+        let task = client.request::<String, String>(Method::GET, "/boo", None, None, false);
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+
+        let date = Utc.ymd(2018, 10, 1).and_hms(12, 0, 0);
+        assert_eq!(Some(date - now), trusted_clock.skew());
+        assert_eq!(date, trusted_clock.now());
+    }
 }
@@ -0,0 +1,170 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll};
+use hyper::{self, Body, Request, Response};
+
+use route::{Handler, Parameters};
+
+struct State {
+    available: usize,
+    waiting: Vec<Task>,
+}
+
+/// Caps how many requests a group of routes handles at once, so that a burst
+/// of one kind of request can't take up every thread of the daemon while
+/// requests of another kind wait. Requests over the limit wait for one of
+/// the requests being handled to finish.
+///
+/// A limit created with `default` lets every request through. The routes of
+/// a group are each handed a clone, and count against the same limit.
+#[derive(Clone, Default)]
+pub struct ConcurrencyLimit {
+    state: Option<Arc<Mutex<State>>>,
+}
+
+impl ConcurrencyLimit {
+    /// Lets at most `limit` requests, and at least one, be handled at once.
+    pub fn new(limit: usize) -> Self {
+        ConcurrencyLimit {
+            state: Some(Arc::new(Mutex::new(State {
+                available: limit.max(1),
+                waiting: Vec::new(),
+            }))),
+        }
+    }
+
+    /// Waits for a request to be allowed to be handled. It is handled until
+    /// the returned permit is dropped.
+    pub fn acquire(&self) -> Acquire {
+        Acquire {
+            limit: self.clone(),
+        }
+    }
+
+    fn lock(state: &Mutex<State>) -> MutexGuard<State> {
+        state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub struct Acquire {
+    limit: ConcurrencyLimit,
+}
+
+impl Future for Acquire {
+    type Item = Permit;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(ref state) = self.limit.state {
+            let mut state = ConcurrencyLimit::lock(state);
+            if state.available == 0 {
+                state.waiting.push(task::current());
+                return Ok(Async::NotReady);
+            }
+            state.available -= 1;
+        }
+        Ok(Async::Ready(Permit {
+            limit: self.limit.clone(),
+        }))
+    }
+}
+
+pub struct Permit {
+    limit: ConcurrencyLimit,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(ref state) = self.limit.state {
+            let mut state = ConcurrencyLimit::lock(state);
+            state.available += 1;
+            // every waiting request is woken, since some of them may have
+            // gone away while they waited
+            for task in state.waiting.drain(..) {
+                task.notify();
+            }
+        }
+    }
+}
+
+/// Handles requests with `inner` within a concurrency limit.
+pub struct Limited<H> {
+    inner: Arc<H>,
+    limit: ConcurrencyLimit,
+}
+
+impl<H> Limited<H>
+where
+    H: Handler<Parameters> + Sync,
+{
+    pub fn new(inner: H, limit: ConcurrencyLimit) -> Self {
+        Limited {
+            inner: Arc::new(inner),
+            limit,
+        }
+    }
+}
+
+impl<H> Handler<Parameters> for Limited<H>
+where
+    H: Handler<Parameters> + Sync,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: Parameters,
+    ) -> Box<Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+        let inner = self.inner.clone();
+        let response = self.limit.acquire().then(move |permit| {
+            let permit = permit.expect("acquiring a permit does not fail");
+            inner.handle(req, params).then(move |response| {
+                drop(permit);
+                response
+            })
+        });
+
+        Box::new(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use futures::executor::{self, Notify};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Woken(AtomicBool);
+
+    impl Notify for Woken {
+        fn notify(&self, _id: usize) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn requests_over_the_limit_wait_for_a_permit() {
+        let limit = ConcurrencyLimit::new(1);
+        let permit = limit.acquire().wait().unwrap();
+
+        let woken = Arc::new(Woken::default());
+        let mut waiting = executor::spawn(limit.acquire());
+        assert!(!waiting.poll_future_notify(&woken, 0).unwrap().is_ready());
+
+        drop(permit);
+        assert!(woken.0.load(Ordering::SeqCst));
+        assert!(waiting.poll_future_notify(&woken, 0).unwrap().is_ready());
+    }
+
+    #[test]
+    fn default_limit_lets_every_request_through() {
+        let limit = ConcurrencyLimit::default();
+        let permits: Vec<Permit> = (0..100).map(|_| limit.acquire().wait().unwrap()).collect();
+        assert_eq!(100, permits.len());
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Arc;
+
+use edgelet_core::Deprecation;
+use futures::Future;
+use http::header::HeaderValue;
+use hyper::{self, Body, Request, Response};
+
+use route::Handler;
+
+/// The header deprecated routes put their notice in.
+pub const DEPRECATION_HEADER: &str = "x-ms-deprecation";
+
+/// Handles requests to a deprecated route with `inner`, and adds the notice
+/// of the deprecation to every response, so that clients find out about the
+/// removal while the route still works.
+pub struct Deprecated<H> {
+    inner: Arc<H>,
+    deprecation: Deprecation,
+    header: HeaderValue,
+}
+
+impl<H> Deprecated<H> {
+    pub fn new(inner: H, deprecation: Deprecation) -> Self {
+        let header = HeaderValue::from_str(&deprecation.header_value())
+            .expect("deprecation notices are written in ASCII");
+        Deprecated {
+            inner: Arc::new(inner),
+            deprecation,
+            header,
+        }
+    }
+
+    pub fn deprecation(&self) -> &Deprecation {
+        &self.deprecation
+    }
+}
+
+impl<H, P> Handler<P> for Deprecated<H>
+where
+    H: Handler<P> + Sync,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: P,
+    ) -> Box<Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+        debug!(
+            "{} {} was called: {}",
+            req.method(),
+            req.uri().path(),
+            self.deprecation
+        );
+        let header = self.header.clone();
+        let response = self.inner.handle(req, params).map(move |mut response| {
+            response.headers_mut().insert(DEPRECATION_HEADER, header);
+            response
+        });
+        Box::new(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use hyper::StatusCode;
+
+    use route::Parameters;
+
+    use super::*;
+
+    struct TestHandler(StatusCode);
+
+    impl Handler<Parameters> for TestHandler {
+        fn handle(
+            &self,
+            _req: Request<Body>,
+            _params: Parameters,
+        ) -> Box<Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = self.0;
+            Box::new(future::ok(response))
+        }
+    }
+
+    fn get(handler: &Deprecated<TestHandler>) -> Response<Body> {
+        let request = Request::get("http://localhost/modules")
+            .body(Body::empty())
+            .unwrap();
+        handler.handle(request, Parameters::new()).wait().unwrap()
+    }
+
+    #[test]
+    fn responses_carry_the_notice() {
+        let deprecation =
+            Deprecation::new("GET /modules", "1.2.0").with_replacement("GET /identities");
+        let handler = Deprecated::new(TestHandler(StatusCode::OK), deprecation);
+
+        let response = get(&handler);
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(
+            "GET /modules; removal=\"1.2.0\"; replacement=\"GET /identities\"",
+            response.headers()[DEPRECATION_HEADER]
+        );
+    }
+
+    #[test]
+    fn error_responses_carry_the_notice() {
+        let deprecation = Deprecation::new("GET /modules", "1.2.0");
+        let handler = Deprecated::new(TestHandler(StatusCode::NOT_FOUND), deprecation);
+
+        let response = get(&handler);
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        assert_eq!(
+            "GET /modules; removal=\"1.2.0\"",
+            response.headers()[DEPRECATION_HEADER]
+        );
+    }
+}
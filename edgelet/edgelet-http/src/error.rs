@@ -6,7 +6,10 @@ use std::num::ParseIntError;
 use std::str;
 use std::str::Utf8Error;
 
-use edgelet_core::{Error as CoreError, ErrorKind as CoreErrorKind};
+use edgelet_core::{
+    classify_error as classify_core_error, error_category, Error as CoreError, ErrorCategory,
+    ErrorKind as CoreErrorKind,
+};
 use failure::{Backtrace, Context, Fail};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{self, Response, StatusCode};
@@ -52,6 +55,12 @@ pub enum ErrorKind {
     EmptyTokenSource,
     #[fail(display = "Invalid uri {}", _0)]
     InvalidUri(String),
+    #[fail(display = "Invalid named pipe access entry {}", _0)]
+    InvalidPipeAccess(String),
+    #[fail(display = "Invalid route pattern {}", _0)]
+    InvalidRoutePattern(String),
+    #[fail(display = "Invalid log filter {}", _0)]
+    InvalidLogFilter(String),
     #[fail(display = "Cannot parse uri")]
     UrlParse,
     #[fail(display = "Token source error")]
@@ -72,6 +81,70 @@ pub enum ErrorKind {
     Utf8,
     #[fail(display = "Error creating HTTP header")]
     TypedHeaders,
+    #[fail(display = "Unknown user or group \"{}\"", _0)]
+    UnknownOwner(String),
+    #[fail(display = "Socket {} is in use by another process", _0)]
+    SocketInUse(String),
+    #[fail(display = "Invalid TLS setup in {}", _0)]
+    InvalidTls(String),
+    #[fail(display = "Invalid idempotency key \"{}\"", _0)]
+    InvalidIdempotencyKey(String),
+    #[fail(
+        display = "A request with idempotency key \"{}\" is still being handled",
+        _0
+    )]
+    IdempotencyKeyInProgress(String),
+}
+
+impl ErrorKind {
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// leave it to their cause.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::ServiceError(status, _) => if status.is_client_error() {
+                Some(ErrorCategory::UserConfig)
+            } else {
+                Some(ErrorCategory::TransientNetwork)
+            },
+            ErrorKind::Hyper | ErrorKind::HyperTls => Some(ErrorCategory::TransientNetwork),
+            ErrorKind::InvalidApiVersion
+            | ErrorKind::InvalidUri(_)
+            | ErrorKind::InvalidPipeAccess(_)
+            | ErrorKind::InvalidRoutePattern(_)
+            | ErrorKind::InvalidLogFilter(_)
+            | ErrorKind::UnknownOwner(_)
+            | ErrorKind::InvalidTls(_)
+            | ErrorKind::InvalidIdempotencyKey(_)
+            | ErrorKind::IdempotencyKeyInProgress(_)
+            | ErrorKind::UrlParse => Some(ErrorCategory::UserConfig),
+            ErrorKind::SocketInUse(_) => Some(ErrorCategory::Runtime),
+            _ => None,
+        }
+    }
+}
+
+/// Categorizes the errors of this crate and the transport errors of hyper,
+/// and falls back to `edgelet_core::classify_error`.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    if let Some(error) = error.downcast_ref::<Error>() {
+        error.kind().category()
+    } else if error.downcast_ref::<HyperError>().is_some() {
+        Some(ErrorCategory::TransientNetwork)
+    } else {
+        classify_core_error(error)
+    }
+}
+
+/// The HTTP status of an error response for an error of `category`, for the
+/// errors that don't call for a more specific one.
+pub fn category_status_code(category: ErrorCategory) -> StatusCode {
+    match category {
+        ErrorCategory::UserConfig => StatusCode::BAD_REQUEST,
+        ErrorCategory::TransientNetwork => StatusCode::BAD_GATEWAY,
+        ErrorCategory::Hsm | ErrorCategory::Runtime | ErrorCategory::Internal => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
 }
 
 impl Fail for Error {
@@ -94,6 +167,10 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
 }
 
 impl From<ErrorKind> for Error {
@@ -166,14 +243,16 @@ impl IntoResponse for Error {
             fail = cause;
         }
 
+        let category = self.category();
         let status_code = match *self.kind() {
-            ErrorKind::InvalidApiVersion => StatusCode::BAD_REQUEST,
             ErrorKind::NotFound => StatusCode::NOT_FOUND,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorKind::IdempotencyKeyInProgress(_) => StatusCode::CONFLICT,
+            _ => category_status_code(category),
         };
 
         let body = json!({
             "message": message,
+            "category": category.to_string(),
         }).to_string();
 
         Response::builder()
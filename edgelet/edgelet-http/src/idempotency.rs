@@ -0,0 +1,272 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::sync::Arc;
+
+use edgelet_core::{IdempotencyKeys, IdempotentResponse, Replay};
+use futures::{future, Future, Stream};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{self, Body, Request, Response, StatusCode};
+
+use error::{Error, ErrorKind};
+use route::Handler;
+use IntoResponse;
+
+/// The header clients put the idempotency key of a request in.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "x-ms-idempotency-key";
+
+/// The header of a response that is the replay of the response to an
+/// earlier request with the same idempotency key.
+pub const IDEMPOTENT_REPLAY_HEADER: &str = "x-ms-idempotent-replay";
+
+const MAX_KEY_LENGTH: usize = 256;
+
+/// Handles requests to a route that changes state with `inner`, unless they
+/// carry an idempotency key a request to the same route was made with
+/// before, in which case the response to that request is returned again.
+/// Clients can then retry a request whose response they didn't get without
+/// it being carried out twice.
+///
+/// Responses with a server error status aren't kept, so that requests that
+/// failed that way are carried out again when they are retried. Requests
+/// without an idempotency key are always handled.
+pub struct Idempotent<H> {
+    inner: Arc<H>,
+    keys: IdempotencyKeys,
+}
+
+impl<H> Idempotent<H> {
+    pub fn new(inner: H, keys: IdempotencyKeys) -> Self {
+        Idempotent {
+            inner: Arc::new(inner),
+            keys,
+        }
+    }
+}
+
+impl<H, P> Handler<P> for Idempotent<H>
+where
+    H: Handler<P> + Sync,
+{
+    fn handle(
+        &self,
+        req: Request<Body>,
+        params: P,
+    ) -> Box<Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+        let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+            Some(key) => match key.to_str() {
+                Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+                _ => {
+                    let key = String::from_utf8_lossy(key.as_bytes()).to_string();
+                    let err = Error::from(ErrorKind::InvalidIdempotencyKey(key));
+                    return Box::new(future::ok(err.into_response()));
+                }
+            },
+            None => return self.inner.handle(req, params),
+        };
+
+        // the same key sent to different routes stands for different requests
+        let id = format!("{} {} {}", req.method(), req.uri().path(), key);
+        match self.keys.begin(&id) {
+            Replay::Respond(response) => Box::new(future::ok(replay(&response))),
+            Replay::InProgress => {
+                let err = Error::from(ErrorKind::IdempotencyKeyInProgress(key));
+                Box::new(future::ok(err.into_response()))
+            }
+            Replay::Handle => {
+                let keys = self.keys.clone();
+                let abandoned = keys.clone();
+                let abandoned_id = id.clone();
+                let response = self
+                    .inner
+                    .handle(req, params)
+                    .and_then(|response| {
+                        let (parts, body) = response.into_parts();
+                        body.concat2().map(|body| (parts, body))
+                    }).map(move |(parts, body)| {
+                        if parts.status.is_server_error() {
+                            keys.abandon(&id);
+                        } else {
+                            let content_type = parts
+                                .headers
+                                .get(CONTENT_TYPE)
+                                .and_then(|value| value.to_str().ok())
+                                .map(ToString::to_string);
+                            let response =
+                                IdempotentResponse::new(parts.status.as_u16(), content_type, &body);
+                            if let Err(err) = keys.complete(&id, response) {
+                                warn!("Could not keep the response to {}: {}", id, err);
+                            }
+                        }
+                        Response::from_parts(parts, body.into())
+                    }).map_err(move |err| {
+                        abandoned.abandon(&abandoned_id);
+                        err
+                    });
+                Box::new(response)
+            }
+        }
+    }
+}
+
+fn replay(response: &IdempotentResponse) -> Response<Body> {
+    let body = response.body();
+    let mut builder = Response::builder();
+    builder
+        .status(StatusCode::from_u16(response.status()).unwrap_or(StatusCode::OK))
+        .header(CONTENT_LENGTH, body.len().to_string().as_str())
+        .header(IDEMPOTENT_REPLAY_HEADER, "true");
+    if let Some(content_type) = response.content_type() {
+        builder.header(CONTENT_TYPE, content_type);
+    }
+    builder
+        .body(body.into())
+        .unwrap_or_else(|err| Error::from(err).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use route::Parameters;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TestHandler {
+        calls: Arc<AtomicUsize>,
+        status: Option<StatusCode>,
+    }
+
+    impl Handler<Parameters> for TestHandler {
+        fn handle(
+            &self,
+            _req: Request<Body>,
+            _params: Parameters,
+        ) -> Box<Future<Item = Response<Body>, Error = hyper::Error> + Send> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let response = Response::builder()
+                .status(self.status.unwrap_or(StatusCode::CREATED))
+                .header(CONTENT_TYPE, "application/json")
+                .body(format!("{{\"call\":{}}}", call).into())
+                .unwrap();
+            Box::new(future::ok(response))
+        }
+    }
+
+    fn request(key: Option<&str>) -> Request<Body> {
+        let mut request = Request::post("http://localhost/modules");
+        if let Some(key) = key {
+            request.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        request.body(Body::default()).unwrap()
+    }
+
+    fn body(response: Response<Body>) -> String {
+        let body = response.into_body().concat2().wait().unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn keys() -> IdempotencyKeys {
+        IdempotencyKeys::new(Duration::from_secs(60))
+    }
+
+    #[test]
+    fn replays_get_the_first_response() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler = Idempotent::new(
+            TestHandler {
+                calls: calls.clone(),
+                status: None,
+            },
+            keys(),
+        );
+
+        let first = handler
+            .handle(request(Some("k1")), Parameters::new())
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, first.status());
+        assert_eq!("{\"call\":1}", body(first));
+
+        let replayed = handler
+            .handle(request(Some("k1")), Parameters::new())
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, replayed.status());
+        assert_eq!(
+            "application/json",
+            replayed.headers().get(CONTENT_TYPE).unwrap()
+        );
+        assert!(replayed.headers().contains_key(IDEMPOTENT_REPLAY_HEADER));
+        assert_eq!("{\"call\":1}", body(replayed));
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        let other = handler
+            .handle(request(Some("k2")), Parameters::new())
+            .wait()
+            .unwrap();
+        assert_eq!("{\"call\":2}", body(other));
+    }
+
+    #[test]
+    fn requests_without_a_key_are_always_handled() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler = Idempotent::new(
+            TestHandler {
+                calls: calls.clone(),
+                status: None,
+            },
+            keys(),
+        );
+        for _ in 0..2 {
+            let response = handler.handle(request(None), Parameters::new()).wait();
+            assert!(response.is_ok());
+        }
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn server_errors_are_not_replayed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler = Idempotent::new(
+            TestHandler {
+                calls: calls.clone(),
+                status: Some(StatusCode::INTERNAL_SERVER_ERROR),
+            },
+            keys(),
+        );
+        for _ in 0..2 {
+            let response = handler
+                .handle(request(Some("k1")), Parameters::new())
+                .wait()
+                .unwrap();
+            assert!(!response.headers().contains_key(IDEMPOTENT_REPLAY_HEADER));
+        }
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn requests_with_a_key_in_progress_conflict() {
+        let keys = keys();
+        assert_eq!(Replay::Handle, keys.begin("POST /modules k1"));
+        let handler = Idempotent::new(TestHandler::default(), keys);
+
+        let response = handler
+            .handle(request(Some("k1")), Parameters::new())
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::CONFLICT, response.status());
+    }
+
+    #[test]
+    fn bad_keys_are_rejected() {
+        let handler = Idempotent::new(TestHandler::default(), keys());
+        let key = "k".repeat(MAX_KEY_LENGTH + 1);
+        let response = handler
+            .handle(request(Some(&key)), Parameters::new())
+            .wait()
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+}
@@ -28,13 +28,14 @@ extern crate hyper_proxy;
 extern crate hyper_tls;
 #[cfg(unix)]
 extern crate hyperlocal;
-#[cfg(target_os = "linux")]
 #[cfg(unix)]
 extern crate libc;
 #[macro_use]
 extern crate log;
+extern crate native_tls;
 #[cfg(unix)]
 extern crate nix;
+extern crate openssl;
 extern crate percent_encoding;
 extern crate regex;
 #[cfg(unix)]
@@ -59,39 +60,54 @@ extern crate url;
 extern crate edgelet_utils;
 
 use std::io;
-#[cfg(unix)]
-use std::net;
 use std::net::ToSocketAddrs;
-#[cfg(unix)]
-use std::os::unix::io::FromRawFd;
 use std::sync::Arc;
 
+use futures::future::Either;
 use futures::{future, Future, Poll, Stream};
 use hyper::server::conn::Http;
 use hyper::service::{NewService, Service};
 use hyper::{Body, Error as HyperError, Response};
-#[cfg(unix)]
-use systemd::Socket;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
-#[cfg(unix)]
-use tokio_uds::UnixListener;
+#[cfg(windows)]
+use tokio_named_pipe::PipeListener;
 use url::Url;
 
 pub mod authorization;
+pub mod cache;
+#[cfg(feature = "chaos")]
+mod chaos;
 pub mod client;
+pub mod concurrency;
+pub mod deprecation;
 pub mod error;
+pub mod idempotency;
+pub mod log_filter;
 pub mod logging;
+mod permissions;
 mod pid;
+pub mod pipe;
 pub mod route;
+pub mod tls;
 mod unix;
 mod util;
 mod version;
+pub mod webhook;
 
-pub use self::error::{Error, ErrorKind};
+#[cfg(feature = "chaos")]
+pub use self::chaos::FaultyClient;
+pub use self::error::{category_status_code, classify_error, Error, ErrorKind};
+pub use self::permissions::SocketPermissions;
+pub use self::pid::ClientSid;
+pub use self::tls::ServerTls;
 pub use self::util::proxy::MaybeProxyClient;
-pub use self::util::UrlConnector;
+pub use self::util::{RetryConnector, TlsVersion, UnixClient, UnixClientBuilder, UrlConnector};
 pub use self::version::{ApiVersionService, API_VERSION};
 
+#[cfg(unix)]
+pub use self::unix::repair as repair_socket;
+
 use self::pid::PidService;
 use self::util::incoming::Incoming;
 
@@ -101,6 +117,8 @@ const TCP_SCHEME: &str = "tcp";
 const UNIX_SCHEME: &str = "unix";
 #[cfg(unix)]
 const FD_SCHEME: &str = "fd";
+#[cfg(windows)]
+const PIPE_SCHEME: &str = "npipe";
 
 pub trait IntoResponse {
     fn into_response(self) -> Response<Body>;
@@ -127,6 +145,23 @@ pub struct Server<S> {
     protocol: Http,
     new_service: S,
     incoming: Incoming,
+    rebound: bool,
+    tls: Option<ServerTls>,
+}
+
+impl<S> Server<S> {
+    /// Whether the socket inherited from systemd was unusable and a new one
+    /// was bound in its place.
+    pub fn rebound(&self) -> bool {
+        self.rebound
+    }
+
+    /// Serves connections over TLS, which identifies callers by their client
+    /// certificates rather than their pids.
+    pub fn with_tls(mut self, tls: ServerTls) -> Self {
+        self.tls = Some(tls);
+        self
+    }
 }
 
 impl<S> Server<S>
@@ -149,6 +184,8 @@ where
             protocol,
             new_service,
             incoming,
+            tls,
+            ..
         } = self;
 
         let protocol = Arc::new(protocol);
@@ -158,6 +195,9 @@ where
 
             debug!("accepted new connection ({})", addr);
             let pid = socket.pid()?;
+            let client_sid = socket.client_sid()?;
+            let tls = tls.clone();
+            let addr = addr.to_string();
             let fut = new_service
                 .new_service()
                 .then(move |srv| match srv {
@@ -167,16 +207,24 @@ where
                         Err(())
                     }
                 }).and_then(move |(srv, addr)| {
-                    let service = PidService::new(pid, srv);
-                    protocol
-                        .serve_connection(socket, service)
-                        .then(move |result| match result {
-                            Ok(_) => Ok(()),
-                            Err(err) => {
-                                error!("server connection error: ({}) {}", addr, err);
-                                Err(())
-                            }
-                        })
+                    let service = PidService::new(pid, srv).with_client_sid(client_sid);
+                    match tls {
+                        Some(tls) => {
+                            Either::A(tls.accept(socket).then(move |result| match result {
+                                Ok((stream, peer)) => Either::A(serve_connection(
+                                    &protocol,
+                                    stream,
+                                    service.with_peer(Some(peer)),
+                                    addr,
+                                )),
+                                Err(err) => {
+                                    warn!("TLS handshake with ({}) failed: {}", addr, err);
+                                    Either::B(future::err(()))
+                                }
+                            }))
+                        }
+                        None => Either::B(serve_connection(&protocol, socket, service, addr)),
+                    }
                 });
             tokio::spawn(fut);
             Ok(())
@@ -199,17 +247,87 @@ where
     }
 }
 
+fn serve_connection<I, T>(
+    protocol: &Http,
+    io: I,
+    service: T,
+    addr: String,
+) -> impl Future<Item = (), Error = ()>
+where
+    I: AsyncRead + AsyncWrite + Send + 'static,
+    T: Service<ReqBody = Body, ResBody = Body, Error = HyperError> + Send + 'static,
+    <T as Service>::Future: Send + 'static,
+{
+    // Upgrades are served so that handlers can take over connections, as
+    // attaching to a module's console does.
+    protocol
+        .serve_connection(io, service)
+        .with_upgrades()
+        .then(move |result| match result {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("server connection error: ({}) {}", addr, err);
+                Err(())
+            }
+        })
+}
+
 pub trait HyperExt {
     fn bind_url<S>(&self, url: Url, new_service: S) -> Result<Server<S>, Error>
+    where
+        S: NewService<ReqBody = Body> + 'static,
+    {
+        self.bind_url_with_access(url, new_service, None)
+    }
+
+    /// Binds to `url`, allowing only `allowed` (see `pipe::security_descriptor`)
+    /// to connect if it is a named pipe. Without `allowed` the pipe gets the
+    /// default security descriptor of named pipes. Other kinds of URLs ignore
+    /// `allowed`.
+    fn bind_url_with_access<S>(
+        &self,
+        url: Url,
+        new_service: S,
+        allowed: Option<&[String]>,
+    ) -> Result<Server<S>, Error>
+    where
+        S: NewService<ReqBody = Body> + 'static,
+    {
+        self.bind_url_with_permissions(url, new_service, allowed, None)
+    }
+
+    /// Binds to `url` like `bind_url_with_access`, giving the socket
+    /// `permissions` if it is a Unix domain socket.
+    fn bind_url_with_permissions<S>(
+        &self,
+        url: Url,
+        new_service: S,
+        allowed: Option<&[String]>,
+        permissions: Option<&SocketPermissions>,
+    ) -> Result<Server<S>, Error>
     where
         S: NewService<ReqBody = Body> + 'static;
 }
 
 impl HyperExt for Http {
-    fn bind_url<S>(&self, url: Url, new_service: S) -> Result<Server<S>, Error>
+    fn bind_url_with_permissions<S>(
+        &self,
+        url: Url,
+        new_service: S,
+        allowed: Option<&[String]>,
+        permissions: Option<&SocketPermissions>,
+    ) -> Result<Server<S>, Error>
     where
         S: NewService<ReqBody = Body> + 'static,
     {
+        #[cfg(not(windows))]
+        let _ = allowed;
+        #[cfg(not(unix))]
+        let _ = permissions;
+
+        // only sockets inherited from systemd can be replaced
+        #[cfg_attr(not(unix), allow(unused_mut))]
+        let mut rebound = false;
         let incoming = match url.scheme() {
             HTTP_SCHEME | TCP_SCHEME => {
                 let addr = url.to_socket_addrs()?.next().ok_or_else(|| {
@@ -222,7 +340,7 @@ impl HyperExt for Http {
             #[cfg(unix)]
             UNIX_SCHEME => {
                 let path = url.path();
-                unix::listener(path)?
+                unix::listener(path, permissions)?
             }
             #[cfg(unix)]
             FD_SCHEME => {
@@ -235,17 +353,21 @@ impl HyperExt for Http {
                     .and_then(|num| systemd::listener(num).map_err(Error::from))
                     .or_else(|_| systemd::listener_name(host))?;
 
-                match socket {
-                    Socket::Inet(fd, _addr) => {
-                        let l = unsafe { net::TcpListener::from_raw_fd(fd) };
-                        Incoming::Tcp(TcpListener::from_std(l, &Default::default())?)
-                    }
-                    Socket::Unix(fd) => {
-                        let l = unsafe { ::std::os::unix::net::UnixListener::from_raw_fd(fd) };
-                        Incoming::Unix(UnixListener::from_std(l, &Default::default())?)
-                    }
-                    _ => Err(Error::from(ErrorKind::InvalidUri(url.to_string())))?,
-                }
+                let (incoming, replaced) = unix::inherited_listener(&url, socket, permissions)?;
+                rebound = replaced;
+                incoming
+            }
+            #[cfg(windows)]
+            PIPE_SCHEME => {
+                let path = pipe::path(&url)?;
+                let security_descriptor = match allowed {
+                    Some(allowed) => Some(pipe::security_descriptor(allowed)?),
+                    None => None,
+                };
+                Incoming::Pipe(PipeListener::bind(
+                    path,
+                    security_descriptor.as_ref().map(String::as_str),
+                )?)
             }
             _ => Err(Error::from(ErrorKind::InvalidUri(url.to_string())))?,
         };
@@ -254,6 +376,8 @@ impl HyperExt for Http {
             protocol: self.clone(),
             new_service,
             incoming,
+            rebound,
+            tls: None,
         })
     }
 }
@@ -0,0 +1,331 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Filtering of the logs of modules as they are read from the runtime.
+//!
+//! Logs are pulled over links that can be slow or metered, so the lines an
+//! operator asks for are picked out on the device rather than after all of
+//! them were sent. The runtime frames each write to stdout or stderr with an
+//! 8 byte header, which gives the stream and the length of what follows:
+//!
+//! ```text
+//! 01 00 00 00 00 00 00 1f 52 6f 73 65 73 20 61 72 65 ...
+//! │  ─────┬── ─────┬─────  R  o  s  e  s     a  r  e ...
+//! └stdout │        └ 0x0000001f = 31 bytes follow
+//!       unused
+//! ```
+//!
+//! The writes are split into lines, and the lines that are kept are framed
+//! again the same way. Logs of containers that run with a TTY aren't framed,
+//! and are passed on as lines without a header.
+
+use std::collections::BTreeMap;
+use std::mem;
+
+use edgelet_core::LogOptions;
+use failure::ResultExt;
+use futures::{Async, Poll, Stream};
+use regex::bytes::Regex;
+
+use error::{Error, ErrorKind};
+
+const HEADER_LEN: usize = 8;
+
+/// Keeps the lines of a module's logs that match a pattern, and stops
+/// reading them once a number of bytes has been kept.
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    grep: Option<Regex>,
+    limit_bytes: Option<u64>,
+}
+
+impl LogFilter {
+    pub fn new() -> Self {
+        LogFilter::default()
+    }
+
+    /// The filter asked for by the `grep` and `limit_bytes` of `options`.
+    pub fn from_options(options: &LogOptions) -> Result<Self, Error> {
+        let mut filter = LogFilter::new();
+        if let Some(grep) = options.grep() {
+            filter = filter.with_grep(grep)?;
+        }
+        if let Some(limit_bytes) = options.limit_bytes() {
+            filter = filter.with_limit_bytes(limit_bytes);
+        }
+        Ok(filter)
+    }
+
+    /// Only keeps the lines that match the regular expression `pattern`.
+    pub fn with_grep(mut self, pattern: &str) -> Result<Self, Error> {
+        let grep =
+            Regex::new(pattern).context(ErrorKind::InvalidLogFilter(pattern.to_string()))?;
+        self.grep = Some(grep);
+        Ok(self)
+    }
+
+    /// Stops once this many bytes of lines were kept, cutting the last one
+    /// short. Headers don't count.
+    pub fn with_limit_bytes(mut self, limit_bytes: u64) -> Self {
+        self.limit_bytes = Some(limit_bytes);
+        self
+    }
+
+    /// Whether every line is kept, in which case the logs don't have to go
+    /// through the filter at all.
+    pub fn keeps_everything(&self) -> bool {
+        self.grep.is_none() && self.limit_bytes.is_none()
+    }
+
+    pub fn filter<S>(self, logs: S) -> FilteredLogs<S> {
+        FilteredLogs {
+            inner: Some(logs),
+            filter: self,
+            framed: None,
+            input: Vec::new(),
+            lines: BTreeMap::new(),
+            output: Vec::new(),
+            kept: 0,
+        }
+    }
+}
+
+/// The stream returned by `LogFilter::filter`.
+pub struct FilteredLogs<S> {
+    // Dropped once the logs end or the limit is reached, which lets go of the
+    // connection they are read from.
+    inner: Option<S>,
+    filter: LogFilter,
+    framed: Option<bool>,
+    input: Vec<u8>,
+    lines: BTreeMap<u8, Vec<u8>>,
+    output: Vec<u8>,
+    kept: u64,
+}
+
+impl<S> FilteredLogs<S> {
+    fn read(&mut self, chunk: &[u8]) {
+        self.input.extend_from_slice(chunk);
+        if self.framed.is_none() && self.input.len() >= HEADER_LEN {
+            self.framed = Some(self.input[0] <= 2 && self.input[1..4] == [0, 0, 0]);
+        }
+
+        match self.framed {
+            Some(true) => {
+                while self.inner.is_some() && self.input.len() >= HEADER_LEN {
+                    let len = self.input[4..HEADER_LEN]
+                        .iter()
+                        .fold(0, |len, byte| (len << 8) | usize::from(*byte));
+                    if self.input.len() < HEADER_LEN + len {
+                        break;
+                    }
+                    let stream = self.input[0];
+                    let payload: Vec<u8> = self.input.drain(..HEADER_LEN + len).collect();
+                    self.split(stream, &payload[HEADER_LEN..]);
+                }
+            }
+            Some(false) => {
+                let input = mem::replace(&mut self.input, Vec::new());
+                self.split(1, &input);
+            }
+            None => (),
+        }
+    }
+
+    fn split(&mut self, stream: u8, data: &[u8]) {
+        self.lines
+            .entry(stream)
+            .or_insert_with(Vec::new)
+            .extend_from_slice(data);
+        while self.inner.is_some() {
+            let line: Vec<u8> = {
+                let partial = self.lines.entry(stream).or_insert_with(Vec::new);
+                match partial.iter().position(|byte| *byte == b'\n') {
+                    Some(end) => partial.drain(..=end).collect(),
+                    None => break,
+                }
+            };
+            self.keep(stream, &line);
+        }
+    }
+
+    // The last line of each stream may not end with a new line.
+    fn finish(&mut self) {
+        if self.framed.is_none() {
+            self.framed = Some(false);
+            let input = mem::replace(&mut self.input, Vec::new());
+            self.split(1, &input);
+        }
+        let lines = mem::replace(&mut self.lines, BTreeMap::new());
+        for (stream, line) in lines {
+            if !line.is_empty() && self.inner.is_some() {
+                self.keep(stream, &line);
+            }
+        }
+        self.inner = None;
+    }
+
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn keep(&mut self, stream: u8, line: &[u8]) {
+        if let Some(ref grep) = self.filter.grep {
+            if !grep.is_match(line) {
+                return;
+            }
+        }
+
+        let limit_bytes = self.filter.limit_bytes;
+        let line = match limit_bytes {
+            Some(limit) => {
+                let left = limit.saturating_sub(self.kept);
+                if (line.len() as u64) < left {
+                    line
+                } else {
+                    self.inner = None;
+                    &line[..left as usize]
+                }
+            }
+            None => line,
+        };
+        if line.is_empty() {
+            return;
+        }
+
+        self.kept += line.len() as u64;
+        if self.framed == Some(true) {
+            let len = line.len() as u32;
+            self.output.extend_from_slice(&[stream, 0, 0, 0]);
+            self.output.extend_from_slice(&[
+                (len >> 24) as u8,
+                (len >> 16) as u8,
+                (len >> 8) as u8,
+                len as u8,
+            ]);
+        }
+        self.output.extend_from_slice(line);
+    }
+}
+
+impl<S> Stream for FilteredLogs<S>
+where
+    S: Stream,
+    S::Item: AsRef<[u8]>,
+{
+    type Item = Vec<u8>;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if !self.output.is_empty() {
+                let output = mem::replace(&mut self.output, Vec::new());
+                return Ok(Async::Ready(Some(output)));
+            }
+
+            let chunk = match self.inner {
+                Some(ref mut inner) => match inner.poll()? {
+                    Async::Ready(chunk) => chunk,
+                    Async::NotReady => return Ok(Async::NotReady),
+                },
+                None => return Ok(Async::Ready(None)),
+            };
+            match chunk {
+                Some(chunk) => self.read(chunk.as_ref()),
+                None => self.finish(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{stream, Future};
+
+    use super::*;
+
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+    fn frame(stream: u8, data: &str) -> Vec<u8> {
+        let mut frame = vec![stream, 0, 0, 0, 0, 0, 0, data.len() as u8];
+        frame.extend_from_slice(data.as_bytes());
+        frame
+    }
+
+    fn run(filter: LogFilter, chunks: Vec<Vec<u8>>) -> Vec<u8> {
+        filter
+            .filter(stream::iter_ok::<_, ()>(chunks))
+            .concat2()
+            .wait()
+            .unwrap()
+    }
+
+    #[test]
+    fn lines_are_kept_if_they_match() {
+        let filter = LogFilter::new().with_grep("(?i)error").unwrap();
+        let logs = vec![
+            frame(1, "started\nError: disk full\n"),
+            frame(2, "warning: slow\nan error"),
+            frame(2, " occurred\n"),
+        ];
+
+        let expected = [
+            frame(1, "Error: disk full\n"),
+            frame(2, "an error occurred\n"),
+        ].concat();
+        assert_eq!(expected, run(filter, logs));
+    }
+
+    #[test]
+    fn frames_split_across_chunks_are_put_together() {
+        let filter = LogFilter::new().with_grep("b").unwrap();
+        let logs = [frame(1, "a\n"), frame(1, "b\n")].concat();
+        let chunks = logs.chunks(3).map(<[u8]>::to_vec).collect();
+
+        assert_eq!(frame(1, "b\n"), run(filter, chunks));
+    }
+
+    #[test]
+    fn logs_stop_at_the_limit() {
+        let filter = LogFilter::new().with_limit_bytes(10);
+        let logs = vec![frame(1, "first\n"), frame(1, "second\n"), frame(1, "third\n")];
+
+        let expected = [frame(1, "first\n"), frame(1, "seco")].concat();
+        assert_eq!(expected, run(filter, logs));
+    }
+
+    #[test]
+    fn limit_only_counts_kept_lines() {
+        let filter = LogFilter::new()
+            .with_grep("^e")
+            .unwrap()
+            .with_limit_bytes(4);
+        let logs = vec![frame(1, "info\nerror\nerror again\n")];
+
+        assert_eq!(frame(1, "erro"), run(filter, logs));
+    }
+
+    #[test]
+    fn unframed_logs_are_filtered_by_line() {
+        let filter = LogFilter::new().with_grep("b").unwrap();
+        let logs = vec![b"line a\nline b\nline".to_vec(), b" bb".to_vec()];
+
+        assert_eq!(b"line b\nline bb".to_vec(), run(filter, logs));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let err = LogFilter::new().with_grep("error(").unwrap_err();
+        assert_eq!(
+            &ErrorKind::InvalidLogFilter("error(".to_string()),
+            err.kind()
+        );
+    }
+
+    #[test]
+    fn filter_is_taken_from_options() {
+        let options = LogOptions::new()
+            .with_grep("error".to_string())
+            .with_limit_bytes(100);
+        let filter = LogFilter::from_options(&options).unwrap();
+        assert!(!filter.keeps_everything());
+        assert!(LogFilter::from_options(&LogOptions::new())
+            .unwrap()
+            .keeps_everything());
+    }
+}
@@ -2,24 +2,155 @@
 #![allow(deprecated)]
 
 use std::error::Error as StdError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "os-tracing")]
+use std::time::Instant;
 
 use chrono::prelude::*;
 use edgelet_core::pid::Pid;
+#[cfg(feature = "os-tracing")]
+use edgelet_core::trace;
+use failure::ResultExt;
 use futures::future;
 use futures::prelude::*;
 use http::header::{CONTENT_LENGTH, USER_AGENT};
+use http::StatusCode;
 use hyper::service::{NewService, Service};
 use hyper::{Body, Request, Response};
+use regex::Regex;
+
+use error::{Error, ErrorKind};
+
+/// Which of the requests to the routes matching a pattern are logged, so that
+/// frequent requests don't flood the log. The first rule whose pattern matches
+/// the path of a request applies to it. Requests no rule applies to are all
+/// logged.
+///
+/// The counts of the requests that were left out are kept by the sampling,
+/// so the management API can list them from a clone of the one the access
+/// log uses.
+#[derive(Clone, Default)]
+pub struct LogSampling {
+    rules: Vec<Arc<SamplingRule>>,
+}
+
+struct SamplingRule {
+    pattern: Regex,
+    success_percent: usize,
+    error_percent: usize,
+    successes: AtomicUsize,
+    errors: AtomicUsize,
+    logged: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl SamplingRule {
+    // Spreads the requests that are logged evenly, starting with the first.
+    fn sample(&self, status: StatusCode) -> bool {
+        let (count, percent) = if status.is_client_error() || status.is_server_error() {
+            (&self.errors, self.error_percent)
+        } else {
+            (&self.successes, self.success_percent)
+        };
+        let n = count.fetch_add(1, Ordering::SeqCst) % 100;
+        let log = n * percent % 100 < percent;
+        if log {
+            self.logged.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+        log
+    }
+}
+
+impl LogSampling {
+    pub fn new() -> Self {
+        LogSampling::default()
+    }
+
+    /// Logs `success_percent` percent of the successful requests to the
+    /// routes matching `pattern`, and `error_percent` percent of the ones that
+    /// fail. Percentages over 100 log every request.
+    pub fn with_rule(
+        mut self,
+        pattern: &str,
+        success_percent: u8,
+        error_percent: u8,
+    ) -> Result<Self, Error> {
+        let regex = Regex::new(pattern)
+            .context(ErrorKind::InvalidRoutePattern(pattern.to_string()))?;
+        self.rules.push(Arc::new(SamplingRule {
+            pattern: regex,
+            success_percent: usize::from(success_percent.min(100)),
+            error_percent: usize::from(error_percent.min(100)),
+            successes: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            logged: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }));
+        Ok(self)
+    }
+
+    /// How many requests each rule let be logged and how many it dropped.
+    pub fn stats(&self) -> Vec<SamplingStats> {
+        self.rules
+            .iter()
+            .map(|rule| SamplingStats {
+                pattern: rule.pattern.as_str().to_string(),
+                logged: rule.logged.load(Ordering::SeqCst),
+                dropped: rule.dropped.load(Ordering::SeqCst),
+            }).collect()
+    }
+
+    fn rule(&self, path: &str) -> Option<Arc<SamplingRule>> {
+        self.rules
+            .iter()
+            .find(|rule| rule.pattern.is_match(path))
+            .cloned()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SamplingStats {
+    pattern: String,
+    logged: usize,
+    dropped: usize,
+}
+
+impl SamplingStats {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn logged(&self) -> usize {
+        self.logged
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
 
 #[derive(Clone)]
 pub struct LoggingService<T> {
     label: String,
     inner: T,
+    sampling: LogSampling,
 }
 
 impl<T> LoggingService<T> {
     pub fn new(label: String, inner: T) -> Self {
-        LoggingService { label, inner }
+        LoggingService {
+            label,
+            inner,
+            sampling: LogSampling::default(),
+        }
+    }
+
+    pub fn with_sampling(mut self, sampling: LogSampling) -> Self {
+        self.sampling = sampling;
+        self
     }
 }
 
@@ -29,6 +160,35 @@ pub struct ResponseFuture<T> {
     request: String,
     user_agent: String,
     pid: Option<Pid>,
+    sampling: Option<Arc<SamplingRule>>,
+    #[cfg(feature = "os-tracing")]
+    started: Instant,
+}
+
+#[cfg(feature = "os-tracing")]
+impl<T> ResponseFuture<T> {
+    // Every request is traced, whether it is sampled for the log or not.
+    fn trace(&self, status: StatusCode) {
+        if !trace::enabled() {
+            return;
+        }
+        let elapsed = self.started.elapsed();
+        let duration_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        let pid = self
+            .pid
+            .as_ref()
+            .map_or_else(|| "-".to_string(), |p| p.to_string());
+        trace::event(
+            "request",
+            &[
+                ("label", &self.label),
+                ("request", &self.request),
+                ("status", &status.as_u16()),
+                ("duration_ms", &duration_ms),
+                ("pid", &pid),
+            ],
+        );
+    }
 }
 
 impl<T> Future for ResponseFuture<T>
@@ -40,6 +200,16 @@ where
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let response = try_ready!(self.inner.poll());
+        #[cfg(feature = "os-tracing")]
+        self.trace(response.status());
+
+        let log = self
+            .sampling
+            .as_ref()
+            .map_or(true, |rule| rule.sample(response.status()));
+        if !log {
+            return Ok(Async::Ready(response));
+        }
 
         let body_length = response
             .headers()
@@ -87,6 +257,7 @@ where
             .unwrap_or_else(|| "-")
             .to_string();
         let pid = req.extensions().get::<Pid>().cloned();
+        let sampling = self.sampling.rule(req.uri().path());
 
         let inner = self.inner.call(req);
         ResponseFuture {
@@ -95,6 +266,9 @@ where
             request,
             user_agent,
             pid,
+            sampling,
+            #[cfg(feature = "os-tracing")]
+            started: Instant::now(),
         }
     }
 }
@@ -114,3 +288,60 @@ where
         future::ok(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(sampling: &LogSampling, path: &str, status: StatusCode) -> bool {
+        sampling
+            .rule(path)
+            .map_or(true, |rule| rule.sample(status))
+    }
+
+    #[test]
+    fn requests_are_sampled_per_route() {
+        let sampling = LogSampling::new()
+            .with_rule("/sign$", 1, 100)
+            .unwrap()
+            .with_rule("/heartbeat$", 50, 50)
+            .unwrap();
+
+        let sign = "/modules/m1/genid/1/sign";
+        let logged = (0..200)
+            .filter(|_| sample(&sampling, sign, StatusCode::OK))
+            .count();
+        assert_eq!(2, logged);
+        assert!(sample(&sampling, sign, StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(sample(&sampling, sign, StatusCode::BAD_REQUEST));
+
+        let heartbeat = "/modules/m1/heartbeat";
+        let logged = (0..10)
+            .filter(|_| sample(&sampling, heartbeat, StatusCode::NO_CONTENT))
+            .count();
+        assert_eq!(5, logged);
+
+        assert!(sample(&sampling, "/modules", StatusCode::OK));
+        assert_eq!(
+            vec![("/sign$", 4, 198), ("/heartbeat$", 5, 5)],
+            sampling
+                .stats()
+                .iter()
+                .map(|stats| (stats.pattern(), stats.logged(), stats.dropped()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        match *LogSampling::new()
+            .with_rule("/sign(", 1, 100)
+            .err()
+            .unwrap()
+            .kind()
+        {
+            ErrorKind::InvalidRoutePattern(ref pattern) => assert_eq!("/sign(", pattern),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
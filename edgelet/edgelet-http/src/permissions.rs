@@ -0,0 +1,146 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#[cfg(unix)]
+use std::ffi::CString;
+#[cfg(unix)]
+use std::{mem, ptr};
+
+#[cfg(unix)]
+use failure::ResultExt;
+#[cfg(unix)]
+use libc;
+
+#[cfg(unix)]
+use error::{Error, ErrorKind};
+
+/// The mode, owner and group the Unix domain socket a server listens on is
+/// given when it is bound. Other kinds of URLs ignore them.
+///
+/// Without a mode the socket keeps the mode of the socket it replaces, and
+/// without a group it keeps its group where the daemon may give it, so that
+/// permissions granted by hand survive restarts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SocketPermissions {
+    mode: Option<u32>,
+    owner: Option<u32>,
+    group: Option<u32>,
+}
+
+impl SocketPermissions {
+    pub fn new() -> Self {
+        SocketPermissions::default()
+    }
+
+    /// Sets the permission bits of the socket, such as `0o660`.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode & 0o777);
+        self
+    }
+
+    /// Sets the user that owns the socket, by name or uid.
+    #[cfg(unix)]
+    pub fn with_owner(mut self, owner: &str) -> Result<Self, Error> {
+        self.owner = Some(user_id(owner)?);
+        Ok(self)
+    }
+
+    /// Sets the group of the socket, by name or gid.
+    #[cfg(unix)]
+    pub fn with_group(mut self, group: &str) -> Result<Self, Error> {
+        self.group = Some(group_id(group)?);
+        Ok(self)
+    }
+
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    pub fn owner(&self) -> Option<u32> {
+        self.owner
+    }
+
+    pub fn group(&self) -> Option<u32> {
+        self.group
+    }
+}
+
+// Names are looked up with the reentrant functions, since the runtime may
+// be looking up other names on other threads.
+#[cfg(unix)]
+fn user_id(user: &str) -> Result<u32, Error> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+
+    let name = CString::new(user).context(ErrorKind::UnknownOwner(user.to_string()))?;
+    let mut buf = vec![0; 16384];
+    let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let ret = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut passwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if ret == 0 && !result.is_null() {
+        Ok(passwd.pw_uid)
+    } else {
+        Err(Error::from(ErrorKind::UnknownOwner(user.to_string())))
+    }
+}
+
+#[cfg(unix)]
+fn group_id(group: &str) -> Result<u32, Error> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+
+    let name = CString::new(group).context(ErrorKind::UnknownOwner(group.to_string()))?;
+    let mut buf = vec![0; 16384];
+    let mut entry: libc::group = unsafe { mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let ret = unsafe {
+        libc::getgrnam_r(
+            name.as_ptr(),
+            &mut entry,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if ret == 0 && !result.is_null() {
+        Ok(entry.gr_gid)
+    } else {
+        Err(Error::from(ErrorKind::UnknownOwner(group.to_string())))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners_are_looked_up_by_name_or_id() {
+        let permissions = SocketPermissions::new()
+            .with_mode(0o100_660)
+            .with_owner("root")
+            .unwrap()
+            .with_group("1001")
+            .unwrap();
+        assert_eq!(Some(0o660), permissions.mode());
+        assert_eq!(Some(0), permissions.owner());
+        assert_eq!(Some(1001), permissions.group());
+
+        match *SocketPermissions::new()
+            .with_group("no-such-group")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::UnknownOwner(ref name) => assert_eq!("no-such-group", name),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
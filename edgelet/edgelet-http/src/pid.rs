@@ -1,19 +1,54 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use edgelet_core::pid::Pid;
+use edgelet_core::PeerIdentity;
 use futures::prelude::*;
 use hyper::service::Service;
 use hyper::{Body, Error as HyperError, Request};
 
+/// The SID of the user a client connected over a named pipe runs as. It is
+/// added to the extensions of the client's requests, next to its `Pid`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientSid(String);
+
+impl ClientSid {
+    pub fn new(sid: String) -> Self {
+        ClientSid(sid)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Clone)]
 pub struct PidService<T> {
     pid: Pid,
+    client_sid: Option<ClientSid>,
+    peer: Option<PeerIdentity>,
     inner: T,
 }
 
 impl<T> PidService<T> {
     pub fn new(pid: Pid, inner: T) -> Self {
-        PidService { pid, inner }
+        PidService {
+            pid,
+            client_sid: None,
+            peer: None,
+            inner,
+        }
+    }
+
+    pub fn with_client_sid(mut self, client_sid: Option<ClientSid>) -> Self {
+        self.client_sid = client_sid;
+        self
+    }
+
+    /// Adds the identity a client authenticated as with its certificate to
+    /// its requests, next to its `Pid`.
+    pub fn with_peer(mut self, peer: Option<PeerIdentity>) -> Self {
+        self.peer = peer;
+        self
     }
 }
 
@@ -31,6 +66,12 @@ where
     fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
         let mut req = req;
         req.extensions_mut().insert(self.pid);
+        if let Some(ref client_sid) = self.client_sid {
+            req.extensions_mut().insert(client_sid.clone());
+        }
+        if let Some(ref peer) = self.peer {
+            req.extensions_mut().insert(peer.clone());
+        }
         self.inner.call(req)
     }
 }
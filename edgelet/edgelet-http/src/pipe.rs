@@ -0,0 +1,87 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Named pipe access
+//!
+//! Who may connect to a named pipe is decided by the pipe's DACL. The
+//! daemon's pipes always give LocalSystem, which the daemon runs as, full
+//! access, and give read and write access to the groups or users they are
+//! configured with. Each of those is either a SID (`S-1-5-32-544`) or an
+//! SDDL alias for a well-known one (`BA` for the administrators group, `AU`
+//! for authenticated users).
+
+use url::Url;
+
+use error::{Error, ErrorKind};
+
+const PIPE_PREFIX: &str = "/pipe/";
+
+/// The pipe path (`\\.\pipe\name`) that a `npipe://./pipe/name` URL refers
+/// to.
+pub fn path(url: &Url) -> Result<String, Error> {
+    let path = url.path();
+    match url.host_str() {
+        Some(host) if path.len() > PIPE_PREFIX.len() && path.starts_with(PIPE_PREFIX) => {
+            Ok(format!(r"\\{}{}", host, path.replace("/", "\\")))
+        }
+        _ => Err(Error::from(ErrorKind::InvalidUri(url.to_string()))),
+    }
+}
+
+/// Builds the security descriptor, in SDDL form, that allows `allowed` to
+/// connect to a pipe.
+pub fn security_descriptor(allowed: &[String]) -> Result<String, Error> {
+    let mut sddl = "D:P(A;;GA;;;SY)".to_string();
+    for trustee in allowed {
+        if !is_trustee(trustee) {
+            return Err(Error::from(ErrorKind::InvalidPipeAccess(trustee.clone())));
+        }
+        sddl.push_str(&format!("(A;;GRGW;;;{})", trustee));
+    }
+    Ok(sddl)
+}
+
+fn is_trustee(trustee: &str) -> bool {
+    let is_alias = trustee.len() == 2 && trustee.chars().all(|c| c.is_ascii_uppercase());
+    let is_sid = trustee.starts_with("S-1-")
+        && trustee[2..]
+            .split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+    is_alias || is_sid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipe_urls_map_to_pipe_paths() {
+        let url = Url::parse("npipe://./pipe/iotedge_mgmt").unwrap();
+        assert_eq!(r"\\.\pipe\iotedge_mgmt", path(&url).unwrap());
+
+        let url = Url::parse("npipe://./iotedge_mgmt").unwrap();
+        assert!(path(&url).is_err());
+        let url = Url::parse("npipe://./pipe/").unwrap();
+        assert!(path(&url).is_err());
+    }
+
+    #[test]
+    fn allowed_trustees_get_read_write_access() {
+        let allowed = vec!["BA".to_string(), "S-1-5-32-545".to_string()];
+        assert_eq!(
+            "D:P(A;;GA;;;SY)(A;;GRGW;;;BA)(A;;GRGW;;;S-1-5-32-545)",
+            security_descriptor(&allowed).unwrap()
+        );
+        assert_eq!("D:P(A;;GA;;;SY)", security_descriptor(&[]).unwrap());
+    }
+
+    #[test]
+    fn malformed_trustees_are_refused() {
+        for trustee in &["ba", "Users", "S-1-5-", "S-1-5-x", "BA)(A;;GA;;;WD"] {
+            let err = security_descriptor(&[trustee.to_string()]).unwrap_err();
+            match *err.kind() {
+                ErrorKind::InvalidPipeAccess(ref t) => assert_eq!(*trustee, t.as_str()),
+                ref kind => panic!("unexpected error kind {:?}", kind),
+            }
+        }
+    }
+}
@@ -23,13 +23,20 @@
 /// The method names must be lowercase and must be one of:
 ///
 /// `get`, `post`, `put` and `delete`
+///
+/// The routes are recorded in the route table of the router as served under
+/// the current API version, with their handlers named as they are written.
+/// Routes can carry attributes, such as `#[cfg(feature = "...")]` for the
+/// routes that are only built into some builds.
 #[macro_export]
 macro_rules! router {
-    ($($method:ident $glob:expr => $handler:expr),+ $(,)*) => ({
-        Router::from(
-            $crate::route::RegexRoutesBuilder::default()
-            $(.$method($glob, $handler))*
-            .finish()
-        )
+    ($($(#[$attr:meta])* $method:ident $glob:expr => $handler:expr),+ $(,)*) => ({
+        let builder = $crate::route::RegexRoutesBuilder::default()
+            .with_api_version($crate::API_VERSION);
+        $(
+            $(#[$attr])*
+            let builder = builder.$method($glob, $handler).named(stringify!($handler));
+        )*
+        Router::from(builder.finish())
     });
 }
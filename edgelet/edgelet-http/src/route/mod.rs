@@ -5,13 +5,17 @@
 /// when using regex based routes.
 use std::clone::Clone;
 use std::error::Error as StdError;
+use std::fmt;
 use std::sync::Arc;
 
 use futures::{future, Future};
 use hyper::service::{NewService, Service};
 use hyper::{self, Body, Method, Request, Response, StatusCode};
 
+pub use self::normalize::normalize_path;
+
 pub mod macros;
+mod normalize;
 mod regex;
 
 pub type BoxFuture<T, E> = Box<Future<Item = T, Error = E>>;
@@ -51,6 +55,68 @@ pub trait Recognizer {
     ) -> Result<HandlerParamsPair<Self::Parameters>, StatusCode>;
 }
 
+/// An entry of the route table of a router, for tests to check that no
+/// route went missing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteInfo {
+    method: Method,
+    pattern: String,
+    handler: Option<String>,
+    api_version: Option<String>,
+}
+
+impl RouteInfo {
+    pub fn new(method: Method, pattern: String) -> Self {
+        RouteInfo {
+            method,
+            pattern,
+            handler: None,
+            api_version: None,
+        }
+    }
+
+    pub fn with_handler(mut self, handler: String) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    pub fn with_api_version(mut self, api_version: String) -> Self {
+        self.api_version = Some(api_version);
+        self
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The handler as it was written where the route was added.
+    pub fn handler(&self) -> Option<&str> {
+        self.handler.as_ref().map(String::as_str)
+    }
+
+    /// The `api-version` requests to the route must carry.
+    pub fn api_version(&self) -> Option<&str> {
+        self.api_version.as_ref().map(String::as_str)
+    }
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.pattern)?;
+        if let Some(ref api_version) = self.api_version {
+            write!(f, " api-version={}", api_version)?;
+        }
+        if let Some(ref handler) = self.handler {
+            write!(f, " => {}", handler)?;
+        }
+        Ok(())
+    }
+}
+
 pub trait Builder: Sized {
     type Recognizer: Recognizer;
 
@@ -128,6 +194,12 @@ pub struct RouterService<R: Recognizer> {
     inner: Arc<R>,
 }
 
+impl<R: Recognizer> RouterService<R> {
+    pub fn recognizer(&self) -> &R {
+        &self.inner
+    }
+}
+
 impl<R> Clone for RouterService<R>
 where
     R: Recognizer,
@@ -150,8 +222,11 @@ where
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
         let method = req.method().clone();
-        let path = req.uri().path().to_owned();
-        match self.inner.recognize(&method, &path) {
+        // Routes are matched against the normalized path, and paths that
+        // can't be normalized are turned down before any handler sees them.
+        let recognized = normalize_path(req.uri().path())
+            .and_then(|path| self.inner.recognize(&method, &path));
+        match recognized {
             Ok((handler, params)) => handler.handle(req, params),
 
             Err(code) => Box::new(future::ok(
@@ -0,0 +1,99 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Normalization of request paths before they are matched against routes.
+//!
+//! The workload socket can be reached by the containers of modules, which are
+//! not all trusted, so every path is reduced to one form before it is routed.
+//! Empty segments are dropped, `.` and `..` segments are resolved, and each
+//! segment is percent-decoded once to find out what it stands for. Segments
+//! are left encoded in the normalized path, since the parameters the router
+//! captures are decoded when they are matched, and must not be decoded twice.
+
+use http::StatusCode;
+use percent_encoding::percent_decode;
+
+/// Returns the normalized form of `path`, or `StatusCode::BAD_REQUEST` if it
+/// climbs above the root, isn't UTF-8 once decoded, or hides a separator or
+/// a NUL in a segment.
+pub fn normalize_path(path: &str) -> Result<String, StatusCode> {
+    if !path.starts_with('/') {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        let decoded = percent_decode(segment.as_bytes())
+            .decode_utf8()
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        if decoded.contains(|c: char| c == '/' || c == '\\' || c == '\0') {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        match decoded.as_ref() {
+            "" | "." => (),
+            ".." => {
+                segments.pop().ok_or(StatusCode::BAD_REQUEST)?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Ok(format!("/{}", segments.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_normalized() {
+        let paths = [
+            ("/", "/"),
+            ("//", "/"),
+            ("/modules", "/modules"),
+            ("/modules/", "/modules"),
+            ("//modules//edgeHub//", "/modules/edgeHub"),
+            ("/modules/./edgeHub", "/modules/edgeHub"),
+            ("/modules/edgeHub/..", "/modules"),
+            ("/modules/edgeHub/../edgeAgent/restart", "/modules/edgeAgent/restart"),
+            ("/modules/%2e/edgeHub", "/modules/edgeHub"),
+            ("/modules/x/%2E%2e/edgeHub", "/modules/edgeHub"),
+            ("/modules/%61%62c", "/modules/%61%62c"),
+            ("/modules/%252e%252e/logs", "/modules/%252e%252e/logs"),
+            ("/modules/...", "/modules/..."),
+            ("/modules/..a", "/modules/..a"),
+        ];
+        for &(path, expected) in &paths {
+            assert_eq!(Ok(expected.to_string()), normalize_path(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn hostile_paths_are_rejected() {
+        let paths = [
+            "",
+            "modules",
+            "*",
+            "/..",
+            "/../modules",
+            "/modules/../..",
+            "/modules/../../etc/passwd",
+            "//..//..//modules",
+            "/%2e%2e/modules",
+            "/modules/%2E%2E/%2e%2e/x",
+            "/modules/a%2fb",
+            "/modules/a%2F..%2F..%2Fb",
+            "/modules/..%5c..%5cb",
+            "/modules/a\\b",
+            "/modules/a%00b",
+            "/modules/%ff",
+            "/modules/%c0%ae%c0%ae",
+        ];
+        for path in &paths {
+            assert_eq!(
+                Err(StatusCode::BAD_REQUEST),
+                normalize_path(path),
+                "{}",
+                path
+            );
+        }
+    }
+}
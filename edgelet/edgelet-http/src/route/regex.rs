@@ -8,7 +8,7 @@ use http::{Method, StatusCode};
 use percent_encoding::percent_decode;
 use regex::Regex;
 
-use super::{Builder, Handler, HandlerParamsPair, Recognizer};
+use super::{Builder, Handler, HandlerParamsPair, Recognizer, RouteInfo};
 
 pub trait IntoCaptures {
     fn into_captures(self) -> Vec<(Option<String>, String)>;
@@ -66,11 +66,44 @@ impl Default for Parameters {
 struct RegexRoute {
     pattern: Regex,
     handler: Box<Handler<Parameters> + Sync>,
+    info: RouteInfo,
+    // the order the route was added in, as routes are kept by method
+    index: usize,
 }
 
 #[derive(Default)]
 pub struct RegexRoutesBuilder {
     routes: HashMap<Method, Vec<RegexRoute>>,
+    count: usize,
+    api_version: Option<String>,
+    last: Option<Method>,
+}
+
+impl RegexRoutesBuilder {
+    /// Records in the route table that the routes added from now on are
+    /// served under `api_version`.
+    pub fn with_api_version(mut self, api_version: &str) -> Self {
+        self.api_version = Some(api_version.to_string());
+        self
+    }
+
+    /// Names the handler of the route added last in the route table.
+    pub fn named(mut self, handler: &str) -> Self {
+        {
+            let routes = &mut self.routes;
+            let route = self
+                .last
+                .as_ref()
+                .and_then(|method| routes.get_mut(method))
+                .and_then(|routes| routes.last_mut());
+            if let Some(route) = route {
+                // spaced the same way however the handler was formatted
+                let name: String = handler.split_whitespace().collect();
+                route.info.handler = Some(name.replace(',', ", "));
+            }
+        }
+        self
+    }
 }
 
 impl Builder for RegexRoutesBuilder {
@@ -81,13 +114,26 @@ impl Builder for RegexRoutesBuilder {
         S: AsRef<str>,
         H: Handler<<Self::Recognizer as Recognizer>::Parameters> + Sync,
     {
+        let info = RouteInfo::new(method.clone(), pattern.as_ref().trim().to_string());
+        let info = match self.api_version {
+            Some(ref api_version) => info.with_api_version(api_version.clone()),
+            None => info,
+        };
         let pattern = normalize_pattern(pattern.as_ref());
         let pattern = Regex::new(&pattern).expect("failed to compile regex");
         let handler = Box::new(handler);
+        let index = self.count;
+        self.count += 1;
+        self.last = Some(method.clone());
         self.routes
             .entry(method)
             .or_insert_with(Vec::new)
-            .push(RegexRoute { pattern, handler });
+            .push(RegexRoute {
+                pattern,
+                handler,
+                info,
+                index,
+            });
         self
     }
 
@@ -102,6 +148,16 @@ pub struct RegexRecognizer {
     routes: HashMap<Method, Vec<RegexRoute>>,
 }
 
+impl RegexRecognizer {
+    /// Lists the routes in the order they were added. A request is handled
+    /// by the first route of its method whose pattern matches its path.
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        let mut routes: Vec<&RegexRoute> = self.routes.values().flatten().collect();
+        routes.sort_by_key(|route| route.index);
+        routes.into_iter().map(|route| route.info.clone()).collect()
+    }
+}
+
 impl Recognizer for RegexRecognizer {
     type Parameters = Parameters;
 
@@ -0,0 +1,263 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use edgelet_core::PeerIdentity;
+use failure::ResultExt;
+use futures::{Async, Future, Poll};
+use openssl::nid::Nid;
+use openssl::ssl::{
+    ErrorCode, HandshakeError, MidHandshakeSslStream, SslAcceptor, SslFiletype, SslMethod,
+    SslStream, SslVerifyMode,
+};
+use openssl::x509::X509;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use error::{Error, ErrorKind};
+
+/// Serves an API over TLS, such as the workload API when it is exposed to
+/// child devices over TCP, and authenticates its callers by their client
+/// certificates.
+///
+/// Clients must present a certificate issued under one of the client CAs,
+/// whose common name is bound to the modules and devices the client may call
+/// as. Connections of clients whose certificate isn't bound to any are
+/// closed after the handshake. The acceptor is set up once, when the
+/// certificates are loaded, and each connection is handshaked with a clone.
+#[derive(Clone)]
+pub struct ServerTls {
+    acceptor: Arc<SslAcceptor>,
+    peers: Arc<HashMap<String, PeerIdentity>>,
+}
+
+impl ServerTls {
+    /// Serves TLS with the PEM certificate chain in `cert` and the PEM
+    /// private key in `key`, and requires clients to present a certificate
+    /// issued under the roots in the PEM bundle `client_ca`.
+    pub fn new(cert: &Path, key: &Path, client_ca: &Path) -> Result<Self, Error> {
+        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls())
+            .context(ErrorKind::InvalidTls(cert.display().to_string()))?;
+        acceptor
+            .set_certificate_chain_file(cert)
+            .context(ErrorKind::InvalidTls(cert.display().to_string()))?;
+        acceptor
+            .set_private_key_file(key, SslFiletype::PEM)
+            .context(ErrorKind::InvalidTls(key.display().to_string()))?;
+        acceptor
+            .check_private_key()
+            .context(ErrorKind::InvalidTls(key.display().to_string()))?;
+        acceptor
+            .set_ca_file(client_ca)
+            .context(ErrorKind::InvalidTls(client_ca.display().to_string()))?;
+        acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+
+        Ok(ServerTls {
+            acceptor: Arc::new(acceptor.build()),
+            peers: Arc::new(HashMap::new()),
+        })
+    }
+
+    /// Lets clients whose certificate has the common name `common_name`
+    /// call as `modules`.
+    pub fn with_peer(mut self, common_name: &str, modules: Vec<String>) -> Self {
+        let peer = PeerIdentity::new(common_name.to_string(), modules);
+        Arc::make_mut(&mut self.peers).insert(common_name.to_string(), peer);
+        self
+    }
+
+    /// Completes the handshake with the client connected on `stream`, and
+    /// resolves to the stream along with the identity the client
+    /// authenticated as.
+    pub fn accept<S>(&self, stream: S) -> Accept<S>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        Accept {
+            tls: self.clone(),
+            state: Some(Handshake::Start(stream)),
+        }
+    }
+
+    fn peer(&self, cert: Option<X509>) -> io::Result<PeerIdentity> {
+        let common_name = cert.and_then(|cert| {
+            cert.subject_name()
+                .entries_by_nid(Nid::COMMONNAME)
+                .next()
+                .and_then(|entry| entry.data().as_utf8().ok())
+                .map(|common_name| common_name.to_string())
+        });
+        let common_name = common_name.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the client certificate has no common name",
+            )
+        })?;
+        self.peers.get(&common_name).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "client certificate {} is not bound to any module",
+                    common_name
+                ),
+            )
+        })
+    }
+}
+
+enum Handshake<S> {
+    Start(S),
+    Mid(MidHandshakeSslStream<S>),
+}
+
+/// The handshake of `ServerTls` with a client.
+pub struct Accept<S> {
+    tls: ServerTls,
+    state: Option<Handshake<S>>,
+}
+
+impl<S> Future for Accept<S>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    type Item = (TlsStream<S>, PeerIdentity);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let result = match self
+            .state
+            .take()
+            .expect("handshake polled after it completed")
+        {
+            Handshake::Start(stream) => self.tls.acceptor.accept(stream),
+            Handshake::Mid(stream) => stream.handshake(),
+        };
+        match result {
+            Ok(stream) => {
+                let peer = self.tls.peer(stream.ssl().peer_certificate())?;
+                Ok(Async::Ready((TlsStream(stream), peer)))
+            }
+            // the stream registered for the readiness it waits for
+            Err(HandshakeError::WouldBlock(stream)) => {
+                self.state = Some(Handshake::Mid(stream));
+                Ok(Async::NotReady)
+            }
+            Err(HandshakeError::SetupFailure(err)) => {
+                Err(io::Error::new(io::ErrorKind::Other, err))
+            }
+            Err(HandshakeError::Failure(stream)) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                stream.into_error().to_string(),
+            )),
+        }
+    }
+}
+
+/// A connection of a client that completed the TLS handshake. The handshake
+/// is done with openssl rather than native-tls, whose acceptor can't ask
+/// clients for a certificate, so tokio-tls can't wrap the stream either.
+pub struct TlsStream<S>(SslStream<S>);
+
+impl<S> Read for TlsStream<S>
+where
+    S: Read + Write,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<S> Write for TlsStream<S>
+where
+    S: Read + Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<S> AsyncRead for TlsStream<S> where S: AsyncRead + AsyncWrite {}
+
+impl<S> AsyncWrite for TlsStream<S>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self.0.shutdown() {
+            Ok(_) => (),
+            Err(ref err) if err.code() == ErrorCode::ZERO_RETURN => (),
+            Err(err) => {
+                return match err.into_io_error() {
+                    Ok(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+                    Ok(err) => Err(err),
+                    Err(err) => Err(io::Error::new(io::ErrorKind::Other, err.to_string())),
+                }
+            }
+        }
+        self.0.get_mut().shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    use super::*;
+
+    fn certificate(common_name: Option<&str>) -> X509 {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        if let Some(common_name) = common_name {
+            name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+                .unwrap();
+        }
+        let name = name.build();
+
+        let mut cert = X509Builder::new().unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        cert.build()
+    }
+
+    #[test]
+    fn clients_are_identified_by_the_common_name_of_their_certificate() {
+        let tls = ServerTls {
+            acceptor: Arc::new(
+                SslAcceptor::mozilla_intermediate(SslMethod::tls())
+                    .unwrap()
+                    .build(),
+            ),
+            peers: Arc::new(HashMap::new()),
+        }.with_peer("childDevice", vec!["sensor".to_string()]);
+
+        let peer = tls.peer(Some(certificate(Some("childDevice")))).unwrap();
+        assert_eq!("childDevice", peer.common_name());
+        assert!(peer.may_act_as("sensor"));
+
+        for cert in vec![
+            Some(certificate(Some("stranger"))),
+            Some(certificate(None)),
+            None,
+        ] {
+            let err = tls.peer(cert).unwrap_err();
+            assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+        }
+    }
+}
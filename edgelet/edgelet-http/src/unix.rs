@@ -3,52 +3,183 @@
 #![cfg(unix)]
 
 use std::fs;
-use std::os::unix::fs::MetadataExt;
+use std::io;
+use std::net;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::net::{UnixListener as StdUnixListener, UnixStream};
 use std::path::Path;
 
 use nix::sys::stat::{umask, Mode};
+use nix::unistd::{chown, close, Gid, Uid};
+use systemd::{self, Socket};
+use tokio::net::TcpListener;
 use tokio_uds::UnixListener;
+use url::Url;
 
-use error::Error;
+use error::{Error, ErrorKind};
+use permissions::SocketPermissions;
 use util::incoming::Incoming;
 
-pub fn listener<P: AsRef<Path>>(path: P) -> Result<Incoming, Error> {
-    let listener = if path.as_ref().exists() {
+pub fn listener<P: AsRef<Path>>(
+    path: P,
+    permissions: Option<&SocketPermissions>,
+) -> Result<Incoming, Error> {
+    let path = path.as_ref();
+    let mut mode = permissions.and_then(SocketPermissions::mode);
+    let owner = permissions.and_then(SocketPermissions::owner);
+    let group = permissions.and_then(SocketPermissions::group);
+    let mut prev_group = None;
+
+    if path.exists() {
         // get the previous file's metadata
         let metadata = fs::metadata(&path)?;
-        debug!(
-            "read metadata {:?} for {}",
-            metadata,
-            path.as_ref().display()
-        );
+        debug!("read metadata {:?} for {}", metadata, path.display());
 
-        debug!("unlinking {}...", path.as_ref().display());
+        debug!("unlinking {}...", path.display());
         fs::remove_file(&path)?;
-        debug!("unlinked {}", path.as_ref().display());
+        debug!("unlinked {}", path.display());
+
+        mode = mode.or_else(|| Some(metadata.mode()));
+        prev_group = Some(metadata.gid());
+    }
+
+    let listener = match mode {
+        Some(mode) => {
+            let mode = Mode::from_bits_truncate(mode);
+            let mut mask = Mode::all();
+            mask.toggle(mode);
 
-        let mode = Mode::from_bits_truncate(metadata.mode());
-        let mut mask = Mode::all();
-        mask.toggle(mode);
+            debug!("settings permissions {:#o} for {}...", mode, path.display());
+            let prev = umask(mask);
+            defer! {{ umask(prev); }}
+
+            debug!("binding {}...", path.display());
+            let listener = UnixListener::bind(&path)?;
+            debug!("bound {}", path.display());
+            listener
+        }
+        None => UnixListener::bind(&path)?,
+    };
 
+    if owner.is_some() || group.is_some() {
         debug!(
-            "settings permissions {:#o} for {}...",
-            mode,
-            path.as_ref().display()
+            "changing the owner of {} to {:?}:{:?}...",
+            path.display(),
+            owner,
+            group
         );
-        let prev = umask(mask);
-        defer! {{ umask(prev); }}
+        chown(path, owner.map(Uid::from_raw), group.map(Gid::from_raw))?;
+    } else if let Some(prev_group) = prev_group {
+        // the group the previous socket was given by hand is kept if the
+        // daemon may give it, and left to the one of the daemon otherwise
+        if let Err(err) = chown(path, None, Some(Gid::from_raw(prev_group))) {
+            debug!(
+                "could not keep group {} for {}: {}",
+                prev_group,
+                path.display(),
+                err
+            );
+        }
+    }
+
+    Ok(Incoming::Unix(listener))
+}
+
+/// Listens on `socket`, which was inherited from systemd for `url`, and
+/// returns whether it had to be replaced.
+///
+/// When systemd restarts a daemon that crashed, the sockets it hands over may
+/// no longer be usable. Such a socket is closed and a new one is bound to the
+/// same address in its place, with `permissions` if it is a Unix domain
+/// socket. The orphaned file of a Unix domain socket is removed first.
+pub fn inherited_listener(
+    url: &Url,
+    socket: Socket,
+    permissions: Option<&SocketPermissions>,
+) -> Result<(Incoming, bool), Error> {
+    match systemd::check(&socket) {
+        Ok(()) => Ok((from_fd(url, socket)?, false)),
+        Err(err) => {
+            warn!("{} Binding a new socket in place of {}...", err, url);
+            Ok((rebind(url, socket, permissions)?, true))
+        }
+    }
+}
 
-        debug!("binding {}...", path.as_ref().display());
-        let listener = UnixListener::bind(&path)?;
-        debug!("bound {}", path.as_ref().display());
+fn from_fd(url: &Url, socket: Socket) -> Result<Incoming, Error> {
+    match socket {
+        Socket::Inet(fd, _addr) => {
+            let l = unsafe { net::TcpListener::from_raw_fd(fd) };
+            let listener = TcpListener::from_std(l, &Default::default())?;
+            Ok(Incoming::Tcp(listener))
+        }
+        Socket::Unix(fd) => {
+            let l = unsafe { StdUnixListener::from_raw_fd(fd) };
+            let listener = UnixListener::from_std(l, &Default::default())?;
+            Ok(Incoming::Unix(listener))
+        }
+        Socket::Unknown => Err(Error::from(ErrorKind::InvalidUri(url.to_string()))),
+    }
+}
 
-        Incoming::Unix(listener)
-    } else {
-        let listener = UnixListener::bind(path)?;
-        Incoming::Unix(listener)
+fn rebind(
+    url: &Url,
+    socket: Socket,
+    permissions: Option<&SocketPermissions>,
+) -> Result<Incoming, Error> {
+    match socket {
+        Socket::Inet(fd, addr) => {
+            // the socket may already be closed, which is as good
+            let _ = close(fd);
+            Ok(Incoming::Tcp(TcpListener::bind(&addr)?))
+        }
+        Socket::Unix(fd) => {
+            let path = systemd::unix_path(fd)?;
+            let _ = close(fd);
+            match path {
+                Some(path) => listener(path, permissions),
+                None => Err(Error::from(ErrorKind::InvalidUri(url.to_string()))),
+            }
+        }
+        Socket::Unknown => Err(Error::from(ErrorKind::InvalidUri(url.to_string()))),
+    }
+}
+
+/// Clears what a daemon that didn't shut down cleanly, or a container runtime
+/// that got to the socket first, may have left where the socket at `path` is
+/// to be bound, so that binding it doesn't fail.
+///
+/// The directory of the socket is created if it is missing. An empty
+/// directory at the path of the socket, which the container runtime creates
+/// when it mounts a socket that doesn't exist yet into a module, is removed.
+/// A socket that another process still listens on is an error, since only one
+/// daemon can serve it. Stale sockets are left for `listener` to replace, so
+/// that the new socket keeps their mode and group.
+pub fn repair<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(Error::from(err)),
     };
 
-    Ok(listener)
+    if metadata.is_dir() {
+        info!(
+            "Removing the directory at {}, where the socket is to be bound...",
+            path.display()
+        );
+        fs::remove_dir(path)?;
+    } else if metadata.file_type().is_socket() && UnixStream::connect(path).is_ok() {
+        return Err(Error::from(ErrorKind::SocketInUse(
+            path.display().to_string(),
+        )));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -57,6 +188,7 @@ mod tests {
 
     use std::fs::OpenOptions;
     use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::IntoRawFd;
 
     use futures::Stream;
     use nix::sys::stat::stat;
@@ -77,7 +209,7 @@ mod tests {
         assert_eq!(0o600, file.metadata().unwrap().mode() & 0o7777);
         drop(file);
 
-        let listener = listener(&path).unwrap();
+        let listener = listener(&path, None).unwrap();
         let _srv = listener.for_each(move |(_socket, _addr)| Ok(()));
 
         let file_stat = stat(&path).unwrap();
@@ -85,4 +217,85 @@ mod tests {
 
         dir.close().unwrap();
     }
+
+    #[test]
+    fn mode_is_set_at_bind_time() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("mode.sock");
+        let permissions = SocketPermissions::new().with_mode(0o660);
+
+        let listener = listener(&path, Some(&permissions)).unwrap();
+        let _srv = listener.for_each(move |(_socket, _addr)| Ok(()));
+
+        let file_stat = stat(&path).unwrap();
+        assert_eq!(0o660, file_stat.st_mode & 0o777);
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn unusable_inherited_socket_is_replaced() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("inherited.sock");
+        let inherited = StdUnixListener::bind(&path).unwrap();
+        let fd = inherited.into_raw_fd();
+        fs::remove_file(&path).unwrap();
+
+        let url = Url::parse("fd://iotedge.socket").unwrap();
+        let (listener, rebound) = inherited_listener(&url, Socket::Unix(fd), None).unwrap();
+        let _srv = listener.for_each(move |(_socket, _addr)| Ok(()));
+
+        assert!(rebound);
+        UnixStream::connect(&path).unwrap();
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn usable_inherited_socket_is_kept() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("inherited.sock");
+        let inherited = StdUnixListener::bind(&path).unwrap();
+        let fd = inherited.into_raw_fd();
+
+        let url = Url::parse("fd://iotedge.socket").unwrap();
+        let (listener, rebound) = inherited_listener(&url, Socket::Unix(fd), None).unwrap();
+        let _srv = listener.for_each(move |(_socket, _addr)| Ok(()));
+
+        assert!(!rebound);
+        UnixStream::connect(&path).unwrap();
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn repair_clears_leftovers() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sockets").join("workload.sock");
+
+        // the directory of the socket is created
+        repair(&path).unwrap();
+        assert!(path.parent().unwrap().is_dir());
+
+        // a directory left by a container runtime is removed
+        fs::create_dir(&path).unwrap();
+        repair(&path).unwrap();
+        assert!(!path.exists());
+
+        // a socket still listened on is in use
+        let listening = listener(&path, None).unwrap();
+        match *repair(&path).unwrap_err().kind() {
+            ErrorKind::SocketInUse(_) => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+        // a stale socket is left to be replaced
+        drop(listening);
+        repair(&path).unwrap();
+        assert!(path.exists());
+
+        dir.close().unwrap();
+    }
 }
@@ -0,0 +1,287 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Client builder
+//!
+//! `UnixClientBuilder` builds the hyper clients used to talk to local
+//! services - the daemon's own management and workload endpoints as well as
+//! the container runtime - over a Unix socket, a named pipe or plain HTTP.
+//! Connections are given a timeout and connection attempts that are refused,
+//! as happens while the service on the other end is still starting up, are
+//! retried according to a bounded `RetryPolicy`. Requests are stamped with a
+//! user agent and, if one is set, an `api-version` query parameter.
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use edgelet_core::{Retry, RetryPolicy};
+use failure::{self, ResultExt};
+use futures::Future;
+use hyper::client::connect::{Connect, Connected, Destination};
+use hyper::client::ResponseFuture;
+use hyper::header::{HeaderValue, USER_AGENT};
+use hyper::{Body, Client, Request, Uri};
+use tokio::timer::Timeout;
+use url::form_urlencoded::Serializer as UrlSerializer;
+use url::Url;
+
+use client::ClientImpl;
+use error::{Error, ErrorKind};
+use util::{StreamSelector, UrlConnector};
+
+const API_VERSION_PARAM: &str = "api-version";
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_RETRY_INITIAL_DELAY_MILLIS: u64 = 250;
+const DEFAULT_RETRY_MAX_DELAY_SECS: u64 = 2;
+const DEFAULT_MAX_CONNECT_ATTEMPTS: u32 = 5;
+
+pub type UriComposer = Box<Fn(&str, &str) -> Result<Uri, failure::Error> + Send + Sync>;
+
+#[derive(Clone, Debug)]
+pub struct UnixClientBuilder {
+    connect_timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    api_version: Option<String>,
+    user_agent: String,
+}
+
+impl Default for UnixClientBuilder {
+    fn default() -> Self {
+        UnixClientBuilder {
+            connect_timeout: Some(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS)),
+            retry_policy: RetryPolicy::exponential(
+                Duration::from_millis(DEFAULT_RETRY_INITIAL_DELAY_MILLIS),
+                Duration::from_secs(DEFAULT_RETRY_MAX_DELAY_SECS),
+            ).with_max_attempts(DEFAULT_MAX_CONNECT_ATTEMPTS),
+            api_version: None,
+            user_agent: format!("edgelet/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+impl UnixClientBuilder {
+    pub fn new() -> Self {
+        UnixClientBuilder::default()
+    }
+
+    /// Gives up on a connection attempt that hasn't completed after
+    /// `timeout`. `None` waits for as long as the transport does.
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets how connection attempts that are refused are retried. The policy
+    /// should be bounded, with a maximum number of attempts or a deadline, so
+    /// that a service that is down for good is reported as such.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Adds an `api-version` query parameter to requests that don't have one.
+    pub fn with_api_version(mut self, api_version: &str) -> Self {
+        self.api_version = Some(api_version.to_string());
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    pub fn connector(&self, url: &Url) -> Result<RetryConnector, Error> {
+        Ok(RetryConnector {
+            inner: Arc::new(UrlConnector::new(url)?),
+            connect_timeout: self.connect_timeout,
+            retry_policy: self.retry_policy,
+        })
+    }
+
+    /// Builds the hyper client for the generated API clients, which set the
+    /// user agent themselves and compose their URIs with `uri_composer`.
+    pub fn hyper_client(&self, url: &Url) -> Result<Client<RetryConnector>, Error> {
+        Ok(Client::builder().build(self.connector(url)?))
+    }
+
+    pub fn uri_composer(&self, url: &Url) -> UriComposer {
+        let scheme = url.scheme().to_string();
+        let api_version = self.api_version.clone();
+        Box::new(move |base_path, path| {
+            let api_version = api_version.as_ref().map(String::as_str);
+            Ok(compose_uri(&scheme, api_version, base_path, path)?)
+        })
+    }
+
+    pub fn build(&self, url: &Url) -> Result<UnixClient, Error> {
+        let user_agent = HeaderValue::from_str(&self.user_agent).context(ErrorKind::Http)?;
+        Ok(UnixClient {
+            client: self.hyper_client(url)?,
+            scheme: url.scheme().to_string(),
+            base_path: base_path(url).to_string(),
+            api_version: self.api_version.clone(),
+            user_agent,
+        })
+    }
+}
+
+/// A client for a local service, built by `UnixClientBuilder`.
+#[derive(Clone)]
+pub struct UnixClient {
+    client: Client<RetryConnector>,
+    scheme: String,
+    base_path: String,
+    api_version: Option<String>,
+    user_agent: HeaderValue,
+}
+
+impl UnixClient {
+    /// Returns the URI of `path` on the service, with the `api-version` query
+    /// parameter added.
+    pub fn uri(&self, path: &str) -> Result<Uri, Error> {
+        let api_version = self.api_version.as_ref().map(String::as_str);
+        compose_uri(&self.scheme, api_version, &self.base_path, path)
+    }
+}
+
+impl ClientImpl for UnixClient {
+    type Response = ResponseFuture;
+
+    fn call(&self, mut req: Request<Body>) -> Self::Response {
+        if !req.headers().contains_key(USER_AGENT) {
+            req.headers_mut().insert(USER_AGENT, self.user_agent.clone());
+        }
+        self.client.request(req)
+    }
+}
+
+/// Wraps `UrlConnector` with the connect timeout and retries of the builder.
+#[derive(Clone)]
+pub struct RetryConnector {
+    inner: Arc<UrlConnector>,
+    connect_timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+}
+
+impl Connect for RetryConnector {
+    type Transport = StreamSelector;
+    type Error = io::Error;
+    type Future = Box<Future<Item = (Self::Transport, Connected), Error = Self::Error> + Send>;
+
+    fn connect(&self, dst: Destination) -> Self::Future {
+        let inner = self.inner.clone();
+        let connect_timeout = self.connect_timeout;
+
+        let attempts = Retry::new(self.retry_policy, move |_attempt| {
+            let connect = inner.connect(dst.clone());
+            match connect_timeout {
+                Some(timeout) => Box::new(Timeout::new(connect, timeout).map_err(|err| {
+                    if err.is_elapsed() {
+                        io::Error::new(io::ErrorKind::TimedOut, "connection timed out")
+                    } else {
+                        err.into_inner().unwrap_or_else(|| {
+                            io::Error::new(io::ErrorKind::Other, "connection timer failed")
+                        })
+                    }
+                })) as Self::Future,
+                None => connect,
+            }
+        }).with_condition(is_retryable)
+        .with_hook(|attempt, err, delay| {
+            debug!("Connection attempt {} failed ({}), retrying in {:?}", attempt, err, delay)
+        });
+
+        Box::new(attempts)
+    }
+}
+
+// A refused connection, or a socket that isn't there, usually means the
+// service is starting up or restarting.
+fn is_retryable(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound => true,
+        _ => false,
+    }
+}
+
+fn compose_uri(
+    scheme: &str,
+    api_version: Option<&str>,
+    base_path: &str,
+    path: &str,
+) -> Result<Uri, Error> {
+    match api_version {
+        Some(api_version) => {
+            UrlConnector::build_hyper_uri(scheme, base_path, &add_api_version(path, api_version))
+        }
+        None => UrlConnector::build_hyper_uri(scheme, base_path, path),
+    }
+}
+
+fn add_api_version(path: &str, api_version: &str) -> String {
+    let (path_only, query) = match path.find('?') {
+        Some(index) => (&path[..index], Some(&path[index + 1..])),
+        None => (path, None),
+    };
+    let has_api_version = query.map_or(false, |query| {
+        query
+            .split('&')
+            .any(|pair| pair.split('=').next() == Some(API_VERSION_PARAM))
+    });
+
+    if has_api_version {
+        path.to_string()
+    } else {
+        let param = UrlSerializer::new(String::new())
+            .append_pair(API_VERSION_PARAM, api_version)
+            .finish();
+        match query {
+            Some(query) if !query.is_empty() => format!("{}?{}&{}", path_only, query, param),
+            _ => format!("{}?{}", path_only, param),
+        }
+    }
+}
+
+// The part of `url` that `UrlConnector::build_hyper_uri` expects as the base
+// path.
+fn base_path(url: &Url) -> &str {
+    match url.scheme() {
+        "unix" => url.path(),
+        _ => url.as_str(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_version_is_added_to_path() {
+        assert_eq!("/modules?api-version=2018-06-28", add_api_version("/modules", "2018-06-28"));
+        assert_eq!(
+            "/modules?stream=true&api-version=2018-06-28",
+            add_api_version("/modules?stream=true", "2018-06-28")
+        );
+        assert_eq!("/modules?api-version=2018-06-28", add_api_version("/modules?", "2018-06-28"));
+    }
+
+    #[test]
+    fn api_version_is_not_added_twice() {
+        assert_eq!(
+            "/modules?api-version=2018-06-28&stream=true",
+            add_api_version("/modules?api-version=2018-06-28&stream=true", "2019-01-30")
+        );
+    }
+
+    #[test]
+    fn only_refused_connections_are_retried() {
+        assert!(is_retryable(&io::Error::from(io::ErrorKind::ConnectionRefused)));
+        assert!(is_retryable(&io::Error::from(io::ErrorKind::NotFound)));
+        assert!(!is_retryable(&io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert!(!is_retryable(&io::Error::from(io::ErrorKind::TimedOut)));
+    }
+}
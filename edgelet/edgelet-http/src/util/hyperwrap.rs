@@ -1,11 +1,16 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use error::Error;
+use std::fmt;
+
+use error::{Error, ErrorKind};
+use failure::ResultExt;
 use futures::future;
 use hyper::client::HttpConnector;
 use hyper::{Body, Client as HyperClient, Error as HyperError, Request, Response, StatusCode, Uri};
 use hyper_proxy::{Intercept, Proxy, ProxyConnector};
 use hyper_tls::HttpsConnector;
+use native_tls::{Certificate, Protocol, TlsConnector};
+use openssl::x509::X509;
 use typed_headers::Credentials;
 use url::percent_encoding::percent_decode;
 use url::Url;
@@ -14,9 +19,40 @@ use super::super::client::ClientImpl;
 
 const DNS_WORKER_THREADS: usize = 4;
 
+/// The oldest version of TLS outbound connections are allowed to use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TlsVersion {
+    Tls10,
+    Tls11,
+    Tls12,
+}
+
+impl TlsVersion {
+    fn protocol(self) -> Protocol {
+        match self {
+            TlsVersion::Tls10 => Protocol::Tlsv10,
+            TlsVersion::Tls11 => Protocol::Tlsv11,
+            TlsVersion::Tls12 => Protocol::Tlsv12,
+        }
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let version = match *self {
+            TlsVersion::Tls10 => "1.0",
+            TlsVersion::Tls11 => "1.1",
+            TlsVersion::Tls12 => "1.2",
+        };
+        write!(f, "TLS {}", version)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     proxy_uri: Option<Uri>,
+    min_tls_version: Option<TlsVersion>,
+    trust_bundle: Option<Vec<u8>>,
     null: bool,
 }
 
@@ -26,6 +62,18 @@ impl Config {
         self
     }
 
+    pub fn min_tls_version(&mut self, version: TlsVersion) -> &mut Config {
+        self.min_tls_version = Some(version);
+        self
+    }
+
+    /// Trusts the roots in the PEM bundle `trust_bundle` on top of the roots
+    /// of the host.
+    pub fn trust_bundle(&mut self, trust_bundle: &[u8]) -> &mut Config {
+        self.trust_bundle = Some(trust_bundle.to_vec());
+        self
+    }
+
     pub fn null(&mut self) -> &mut Config {
         self.null = true;
         self
@@ -36,7 +84,10 @@ impl Config {
             Ok(Client::Null)
         } else {
             let config = self.clone();
-            let https = HttpsConnector::new(DNS_WORKER_THREADS)?;
+            let https = https_connector(
+                config.min_tls_version,
+                config.trust_bundle.as_ref().map(Vec::as_slice),
+            )?;
             match config.proxy_uri {
                 None => Ok(Client::NoProxy(HyperClient::builder().build(https))),
                 Some(uri) => {
@@ -49,6 +100,32 @@ impl Config {
     }
 }
 
+// The same connector as `HttpsConnector::new` builds, with connections that
+// negotiate a version of TLS older than `min_tls_version` turned down, and
+// the roots in `trust_bundle` trusted as well.
+fn https_connector(
+    min_tls_version: Option<TlsVersion>,
+    trust_bundle: Option<&[u8]>,
+) -> Result<HttpsConnector<HttpConnector>, Error> {
+    let mut http = HttpConnector::new(DNS_WORKER_THREADS);
+    http.enforce_http(false);
+    let mut tls = TlsConnector::builder();
+    if let Some(version) = min_tls_version {
+        tls.min_protocol_version(Some(version.protocol()));
+    }
+    if let Some(trust_bundle) = trust_bundle {
+        let roots = X509::stack_from_pem(trust_bundle)
+            .context(ErrorKind::InvalidTls("the trust bundle".to_string()))?;
+        for root in roots {
+            let der = root
+                .to_der()
+                .context(ErrorKind::InvalidTls("the trust bundle".to_string()))?;
+            tls.add_root_certificate(Certificate::from_der(&der)?);
+        }
+    }
+    Ok(HttpsConnector::from((http, tls.build()?)))
+}
+
 fn uri_to_proxy(uri: Uri) -> Result<Proxy, Error> {
     let url = Url::parse(&uri.to_string())?;
     let mut proxy = Proxy::new(Intercept::All, uri);
@@ -78,6 +155,8 @@ impl Client {
     pub fn configure() -> Config {
         Config {
             proxy_uri: None,
+            min_tls_version: None,
+            trust_bundle: None,
             null: false,
         }
     }
@@ -151,6 +230,30 @@ mod tests {
         assert!(client.has_proxy());
     }
 
+    #[test]
+    fn can_create_client_with_min_tls_version() {
+        let uri = "http://example.com".parse::<Uri>().unwrap();
+        let client = Client::configure()
+            .proxy(uri)
+            .min_tls_version(TlsVersion::Tls12)
+            .build()
+            .unwrap();
+        assert!(client.has_proxy());
+        assert_eq!("TLS 1.2", TlsVersion::Tls12.to_string());
+    }
+
+    #[test]
+    fn invalid_trust_bundles_are_refused() {
+        let err = Client::configure()
+            .trust_bundle(b"-----BEGIN CERTIFICATE-----\nbm9wZQ==\n-----END CERTIFICATE-----\n")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            &ErrorKind::InvalidTls("the trust bundle".to_string()),
+            err.kind()
+        );
+    }
+
     #[test]
     fn proxy_no_username() {
         let uri = "http://example.com".parse().unwrap();
@@ -4,6 +4,8 @@ use std::io;
 
 use futures::{Poll, Stream};
 use tokio::net::TcpListener;
+#[cfg(windows)]
+use tokio_named_pipe::PipeListener;
 #[cfg(unix)]
 use tokio_uds::UnixListener;
 
@@ -13,6 +15,8 @@ pub enum Incoming {
     Tcp(TcpListener),
     #[cfg(unix)]
     Unix(UnixListener),
+    #[cfg(windows)]
+    Pipe(PipeListener),
 }
 
 impl Stream for Incoming {
@@ -46,6 +50,10 @@ impl Stream for Incoming {
                     Some((StreamSelector::Unix(stream), IncomingSocketAddr::Unix(addr)))
                 })
             }
+            #[cfg(windows)]
+            Incoming::Pipe(ref mut listener) => listener
+                .poll_accept()?
+                .map(|stream| Some((StreamSelector::Pipe(stream), IncomingSocketAddr::Pipe))),
         })
     }
 }
@@ -18,13 +18,17 @@ use tokio_uds::UnixStream;
 
 #[cfg(unix)]
 use pid::UnixStreamExt;
+use pid::ClientSid;
 
+pub mod builder;
 pub mod connector;
 mod hyperwrap;
 pub mod incoming;
 pub mod proxy;
 
+pub use self::builder::{RetryConnector, UnixClient, UnixClientBuilder};
 pub use self::connector::UrlConnector;
+pub use self::hyperwrap::TlsVersion;
 pub use self::incoming::Incoming;
 
 pub enum StreamSelector {
@@ -46,6 +50,18 @@ impl StreamSelector {
             StreamSelector::Unix(ref stream) => stream.pid(),
         }
     }
+
+    /// The SID of the user the client runs as, for connections over named
+    /// pipes.
+    pub fn client_sid(&self) -> io::Result<Option<ClientSid>> {
+        match *self {
+            #[cfg(windows)]
+            StreamSelector::Pipe(ref stream) => {
+                stream.client_sid().map(|sid| Some(ClientSid::new(sid)))
+            }
+            _ => Ok(None),
+        }
+    }
 }
 
 impl Read for StreamSelector {
@@ -133,6 +149,8 @@ pub enum IncomingSocketAddr {
     Tcp(SocketAddr),
     #[cfg(unix)]
     Unix(UnixSocketAddr),
+    #[cfg(windows)]
+    Pipe,
 }
 
 impl fmt::Display for IncomingSocketAddr {
@@ -147,6 +165,8 @@ impl fmt::Display for IncomingSocketAddr {
                     write!(f, "unknown")
                 }
             }
+            #[cfg(windows)]
+            IncomingSocketAddr::Pipe => write!(f, "named pipe"),
         }
     }
 }
@@ -1,7 +1,7 @@
 // Copyright (c) Microsoft. All rights reserved.
 
 use super::super::client::ClientImpl;
-use super::hyperwrap::Client;
+use super::hyperwrap::{Client, TlsVersion};
 use error::Error;
 use hyper::{Body, Request, Uri};
 
@@ -12,10 +12,36 @@ pub struct MaybeProxyClient {
 
 impl MaybeProxyClient {
     pub fn new(proxy_uri: Option<Uri>) -> Result<Self, Error> {
-        MaybeProxyClient::create(false, proxy_uri)
+        MaybeProxyClient::create(false, proxy_uri, None, None)
     }
 
-    fn create(null: bool, proxy_uri: Option<Uri>) -> Result<Self, Error> {
+    /// Creates a client that turns down connections that negotiate a version
+    /// of TLS older than `min_tls_version`.
+    pub fn with_min_tls_version(
+        proxy_uri: Option<Uri>,
+        min_tls_version: TlsVersion,
+    ) -> Result<Self, Error> {
+        MaybeProxyClient::create(false, proxy_uri, Some(min_tls_version), None)
+    }
+
+    /// Creates a client that trusts the roots in the PEM bundle
+    /// `trust_bundle` as well as those of the host, such as to reach a parent
+    /// gateway whose certificate is issued under a private root, and that
+    /// turns down versions of TLS older than `min_tls_version`, if any.
+    pub fn with_trust_bundle(
+        proxy_uri: Option<Uri>,
+        min_tls_version: Option<TlsVersion>,
+        trust_bundle: &[u8],
+    ) -> Result<Self, Error> {
+        MaybeProxyClient::create(false, proxy_uri, min_tls_version, Some(trust_bundle))
+    }
+
+    fn create(
+        null: bool,
+        proxy_uri: Option<Uri>,
+        min_tls_version: Option<TlsVersion>,
+        trust_bundle: Option<&[u8]>,
+    ) -> Result<Self, Error> {
         let mut config = Client::configure();
         if null {
             config.null();
@@ -23,6 +49,12 @@ impl MaybeProxyClient {
         if let Some(uri) = proxy_uri {
             config.proxy(uri);
         }
+        if let Some(version) = min_tls_version {
+            config.min_tls_version(version);
+        }
+        if let Some(trust_bundle) = trust_bundle {
+            config.trust_bundle(trust_bundle);
+        }
         Ok(MaybeProxyClient {
             client: config.build()?,
         })
@@ -30,7 +62,7 @@ impl MaybeProxyClient {
 
     #[cfg(test)]
     pub fn new_null() -> Result<Self, Error> {
-        MaybeProxyClient::create(true, None)
+        MaybeProxyClient::create(true, None, None, None)
     }
 
     #[cfg(test)]
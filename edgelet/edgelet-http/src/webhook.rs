@@ -0,0 +1,398 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Webhooks on-premises systems register to be told of events on the device.
+//!
+//! A notification is POSTed as JSON to every webhook that subscribes to its
+//! event, over HTTPS or a Unix socket. Plain HTTP isn't accepted, since the
+//! notifications describe the device. Requests carry the event in the
+//! `x-iotedge-event` header and, when the webhook has a secret, the
+//! HMAC-SHA256 of the body in the `x-iotedge-signature` header as
+//! `sha256=<base64>`. Failed requests are retried with an exponential
+//! backoff, except for those the receiver turned down.
+
+use std::io;
+use std::time::Duration;
+
+use edgelet_core::{
+    sign_notification, Notification, NotificationEvent, Notify, Retry, RetryPolicy,
+};
+use edgelet_utils::log_failure;
+use futures::{future, Future};
+use http::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Error as HyperError, Method, Request, Response, StatusCode, Uri};
+use log::Level;
+use serde_json;
+use tokio::runtime::TaskExecutor;
+use tokio::timer::Timeout;
+use url::Url;
+
+use client::ClientImpl;
+use error::{Error, ErrorKind};
+use util::proxy::MaybeProxyClient;
+#[cfg(unix)]
+use util::{UnixClient, UnixClientBuilder};
+
+pub const EVENT_HEADER: &str = "x-iotedge-event";
+pub const SIGNATURE_HEADER: &str = "x-iotedge-signature";
+
+const HTTPS_SCHEME: &str = "https";
+#[cfg(unix)]
+const UNIX_SCHEME: &str = "unix";
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_RETRY_INITIAL_DELAY_SECS: u64 = 1;
+const DEFAULT_RETRY_MAX_DELAY_SECS: u64 = 60;
+const DEFAULT_ATTEMPT_TIMEOUT_SECS: u64 = 30;
+
+/// An endpoint to POST notifications to.
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    url: Url,
+    events: Vec<NotificationEvent>,
+    secret: Option<Vec<u8>>,
+    trust_bundle: Option<Vec<u8>>,
+    retry_policy: RetryPolicy,
+    timeout: Duration,
+}
+
+impl Webhook {
+    /// Creates a webhook for `url`, which has to be an `https` URL or, on
+    /// Unix, a `unix` URL of a socket.
+    pub fn new(url: Url) -> Result<Self, Error> {
+        if !is_webhook_scheme(url.scheme()) {
+            return Err(Error::from(ErrorKind::InvalidUri(url.to_string())));
+        }
+
+        Ok(Webhook {
+            url,
+            events: vec![],
+            secret: None,
+            trust_bundle: None,
+            retry_policy: RetryPolicy::exponential(
+                Duration::from_secs(DEFAULT_RETRY_INITIAL_DELAY_SECS),
+                Duration::from_secs(DEFAULT_RETRY_MAX_DELAY_SECS),
+            ).with_max_attempts(DEFAULT_MAX_ATTEMPTS),
+            timeout: Duration::from_secs(DEFAULT_ATTEMPT_TIMEOUT_SECS),
+        })
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Only sends notifications of `events`. A webhook without events is
+    /// sent every notification.
+    pub fn with_events(mut self, events: Vec<NotificationEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Signs notifications with `secret`, which the receiver shares.
+    pub fn with_secret(mut self, secret: Vec<u8>) -> Self {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Trusts the roots in the PEM bundle `trust_bundle` as well as those of
+    /// the host, for receivers whose certificate is issued under a private
+    /// root.
+    pub fn with_trust_bundle(mut self, trust_bundle: Vec<u8>) -> Self {
+        self.trust_bundle = Some(trust_bundle);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Gives up on a notification after `max_attempts` attempts, including
+    /// the first, keeping the backoff of the retry policy.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.retry_policy = self.retry_policy.with_max_attempts(max_attempts);
+        self
+    }
+
+    /// Gives up on an attempt whose response hasn't arrived after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn subscribes(&self, event: NotificationEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    /// Sends `notification`, retrying until the receiver accepts it or the
+    /// retry policy gives up.
+    pub fn send(
+        &self,
+        notification: &Notification,
+    ) -> impl Future<Item = (), Error = Error> + Send {
+        let body = match serde_json::to_vec(notification) {
+            Ok(body) => body,
+            Err(err) => return future::Either::B(future::err(Error::from(err))),
+        };
+        let event = notification.event();
+        let webhook = self.clone();
+        let url = self.url.clone();
+
+        let attempts = Retry::new(self.retry_policy, move |_attempt| {
+            webhook.post(&body, event)
+        }).with_condition(is_retryable)
+        .with_hook(move |attempt, err, delay| {
+            warn!(
+                "Attempt {} to notify {} of {} failed ({}), retrying in {:?}",
+                attempt, url, event, err, delay
+            )
+        });
+        future::Either::A(attempts)
+    }
+
+    fn post(
+        &self,
+        body: &[u8],
+        event: NotificationEvent,
+    ) -> Box<Future<Item = (), Error = Error> + Send> {
+        let response = self
+            .client()
+            .and_then(|(client, uri)| Ok(client.call(self.request(uri, body, event)?)));
+
+        match response {
+            Ok(response) => Box::new(
+                Timeout::new(response, self.timeout)
+                    .map_err(|err| {
+                        if err.is_elapsed() {
+                            Error::from(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "webhook request timed out",
+                            ))
+                        } else {
+                            err.into_inner().map_or_else(
+                                || {
+                                    Error::from(io::Error::new(
+                                        io::ErrorKind::Other,
+                                        "webhook request timer failed",
+                                    ))
+                                },
+                                Error::from,
+                            )
+                        }
+                    }).and_then(|res| {
+                        let status = res.status();
+                        if status.is_success() {
+                            Ok(())
+                        } else {
+                            let reason = status.canonical_reason().unwrap_or_default();
+                            Err(Error::from(ErrorKind::ServiceError(
+                                status,
+                                reason.to_string(),
+                            )))
+                        }
+                    }),
+            ),
+            Err(err) => Box::new(future::err(err)),
+        }
+    }
+
+    fn client(&self) -> Result<(WebhookClient, Uri), Error> {
+        match self.url.scheme() {
+            #[cfg(unix)]
+            UNIX_SCHEME => {
+                let client = UnixClientBuilder::new().build(&self.url)?;
+                let uri = client.uri("/")?;
+                Ok((WebhookClient::Unix(client), uri))
+            }
+            _ => {
+                let client = match self.trust_bundle {
+                    Some(ref trust_bundle) => {
+                        MaybeProxyClient::with_trust_bundle(None, None, trust_bundle)?
+                    }
+                    None => MaybeProxyClient::new(None)?,
+                };
+                let uri = self
+                    .url
+                    .as_str()
+                    .parse()
+                    .map_err(|_| Error::from(ErrorKind::InvalidUri(self.url.to_string())))?;
+                Ok((WebhookClient::Https(client), uri))
+            }
+        }
+    }
+
+    fn request(
+        &self,
+        uri: Uri,
+        body: &[u8],
+        event: NotificationEvent,
+    ) -> Result<Request<Body>, Error> {
+        let mut builder = Request::builder();
+        builder
+            .method(Method::POST)
+            .uri(uri)
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .header(EVENT_HEADER, event.to_string().as_str());
+        if let Some(ref secret) = self.secret {
+            let signature = format!("sha256={}", sign_notification(secret, body)?);
+            builder.header(SIGNATURE_HEADER, signature.as_str());
+        }
+        Ok(builder.body(Body::from(body.to_vec()))?)
+    }
+}
+
+/// Sends notifications to the webhooks that subscribe to their event, in the
+/// background on `executor`. Failures are logged.
+#[derive(Clone)]
+pub struct Webhooks {
+    hooks: Vec<Webhook>,
+    executor: TaskExecutor,
+}
+
+impl Webhooks {
+    pub fn new(executor: TaskExecutor) -> Self {
+        Webhooks {
+            hooks: vec![],
+            executor,
+        }
+    }
+
+    pub fn with_webhook(mut self, webhook: Webhook) -> Self {
+        self.hooks.push(webhook);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+}
+
+impl Notify for Webhooks {
+    fn notify(&self, notification: &Notification) {
+        let event = notification.event();
+        for hook in self.hooks.iter().filter(|hook| hook.subscribes(event)) {
+            let url = hook.url().clone();
+            self.executor
+                .spawn(hook.send(notification).map_err(move |err| {
+                    warn!("Could not notify {} of {}", url, event);
+                    log_failure(Level::Warn, &err);
+                }));
+        }
+    }
+}
+
+enum WebhookClient {
+    Https(MaybeProxyClient),
+    #[cfg(unix)]
+    Unix(UnixClient),
+}
+
+impl WebhookClient {
+    fn call(
+        &self,
+        req: Request<Body>,
+    ) -> Box<Future<Item = Response<Body>, Error = HyperError> + Send> {
+        match *self {
+            WebhookClient::Https(ref client) => client.call(req),
+            #[cfg(unix)]
+            WebhookClient::Unix(ref client) => Box::new(client.call(req)),
+        }
+    }
+}
+
+fn is_webhook_scheme(scheme: &str) -> bool {
+    match scheme {
+        HTTPS_SCHEME => true,
+        #[cfg(unix)]
+        UNIX_SCHEME => true,
+        _ => false,
+    }
+}
+
+/// Requests the receiver turned down, other than for being sent too many,
+/// and TLS setups that can't work aren't retried.
+fn is_retryable(err: &Error) -> bool {
+    match *err.kind() {
+        ErrorKind::ServiceError(status, _) => {
+            !status.is_client_error() || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        ErrorKind::InvalidTls(_) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn notification() -> Notification {
+        Notification::new(
+            NotificationEvent::CertificateExpiring,
+            "edge-ca",
+            Utc.ymd(2018, 10, 15).and_hms(12, 0, 0),
+        )
+    }
+
+    #[test]
+    fn webhooks_are_https_or_unix_urls() {
+        assert!(Webhook::new(Url::parse("https://alerts.local/edge").unwrap()).is_ok());
+        #[cfg(unix)]
+        assert!(Webhook::new(Url::parse("unix:///var/run/alerts.sock").unwrap()).is_ok());
+
+        for url in &["http://alerts.local/edge", "ftp://alerts.local/edge"] {
+            match *Webhook::new(Url::parse(url).unwrap()).unwrap_err().kind() {
+                ErrorKind::InvalidUri(ref uri) => assert_eq!(*url, uri),
+                ref kind => panic!("unexpected error kind {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn webhooks_without_events_subscribe_to_all() {
+        let webhook = Webhook::new(Url::parse("https://alerts.local/edge").unwrap()).unwrap();
+        assert!(webhook.subscribes(NotificationEvent::ModuleCrashLoop));
+        assert!(webhook.subscribes(NotificationEvent::ProvisioningChanged));
+
+        let webhook = webhook.with_events(vec![NotificationEvent::ModuleCrashLoop]);
+        assert!(webhook.subscribes(NotificationEvent::ModuleCrashLoop));
+        assert!(!webhook.subscribes(NotificationEvent::ProvisioningChanged));
+    }
+
+    #[test]
+    fn requests_are_signed_with_the_secret() {
+        let uri: Uri = "https://alerts.local/edge".parse().unwrap();
+        let body = serde_json::to_vec(&notification()).unwrap();
+        let webhook = Webhook::new(Url::parse("https://alerts.local/edge").unwrap()).unwrap();
+
+        let req = webhook
+            .request(uri.clone(), &body, NotificationEvent::CertificateExpiring)
+            .unwrap();
+        assert_eq!(&Method::POST, req.method());
+        assert_eq!("certificateExpiring", req.headers()[EVENT_HEADER]);
+        assert_eq!("application/json", req.headers()[CONTENT_TYPE]);
+        assert!(!req.headers().contains_key(SIGNATURE_HEADER));
+
+        let req = webhook
+            .with_secret(b"secret".to_vec())
+            .request(uri, &body, NotificationEvent::CertificateExpiring)
+            .unwrap();
+        let signature = format!("sha256={}", sign_notification(b"secret", &body).unwrap());
+        assert_eq!(signature.as_str(), req.headers()[SIGNATURE_HEADER]);
+    }
+
+    #[test]
+    fn turned_down_requests_are_not_retried() {
+        let error = |status| Error::from(ErrorKind::ServiceError(status, String::new()));
+        assert!(is_retryable(&error(StatusCode::SERVICE_UNAVAILABLE)));
+        assert!(is_retryable(&error(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(!is_retryable(&error(StatusCode::BAD_REQUEST)));
+        assert!(!is_retryable(&error(StatusCode::UNAUTHORIZED)));
+        assert!(!is_retryable(&Error::from(ErrorKind::InvalidTls(
+            "trust bundle".to_string()
+        ))));
+        assert!(is_retryable(&Error::from(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "webhook request timed out"
+        ))));
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#![cfg(unix)]
+#![deny(unused_extern_crates, warnings)]
+// Remove this when clippy stops warning about old-style `allow()`,
+// which can only be silenced by enabling a feature and thus requires nightly
+//
+// Ref: https://github.com/rust-lang-nursery/rust-clippy/issues/3159#issuecomment-420530386
+#![allow(renamed_and_removed_lints)]
+#![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
+
+extern crate edgelet_core;
+extern crate edgelet_http;
+extern crate edgelet_test_utils;
+extern crate futures;
+extern crate hyper;
+#[macro_use(defer)]
+extern crate scopeguard;
+extern crate tokio;
+extern crate url;
+
+use std::io;
+use std::os::unix::net::UnixListener;
+use std::time::{Duration, Instant};
+
+use edgelet_core::RetryPolicy;
+use edgelet_http::client::ClientImpl;
+use edgelet_http::UnixClientBuilder;
+use edgelet_test_utils::run_uds_server;
+use futures::future;
+use futures::prelude::*;
+use hyper::header::USER_AGENT;
+use hyper::{Body, Request, Response, StatusCode};
+use tokio::timer::Delay;
+use url::Url;
+
+fn echo_handler(req: Request<Body>) -> impl Future<Item = Response<Body>, Error = io::Error> {
+    let user_agent = req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let query = req.uri().query().unwrap_or_default().to_string();
+    future::ok(Response::new(format!("{} {}", user_agent, query).into()))
+}
+
+// Leaves a socket file behind that nothing listens on, as a service that
+// hasn't started yet does.
+fn bind_stale_socket(path: &str) {
+    ::std::fs::remove_file(path).unwrap_or(());
+    drop(UnixListener::bind(path).unwrap());
+}
+
+fn get_body<C: ClientImpl>(
+    client: &C,
+    uri: hyper::Uri,
+) -> impl Future<Item = String, Error = hyper::Error> {
+    let req = Request::get(uri).body(Body::empty()).unwrap();
+    client.call(req).and_then(|res| {
+        assert_eq!(StatusCode::OK, res.status());
+        res.into_body()
+            .concat2()
+            .map(|body| String::from_utf8_lossy(body.as_ref()).to_string())
+    })
+}
+
+#[test]
+fn stamps_user_agent_and_api_version() {
+    let file_path = "/tmp/edgelet_test_client_builder_stamps.sock";
+    defer! {{
+        ::std::fs::remove_file(&file_path).unwrap_or(());
+    }}
+
+    let server = run_uds_server(file_path, echo_handler).map_err(|err| eprintln!("{}", err));
+
+    let client = UnixClientBuilder::new()
+        .with_api_version("2018-06-28")
+        .with_user_agent("iotedge/1.0")
+        .build(&Url::parse(&format!("unix://{}", file_path)).unwrap())
+        .unwrap();
+    let task = get_body(&client, client.uri("/modules?stream=true").unwrap());
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    runtime.spawn(server);
+    assert_eq!(
+        "iotedge/1.0 stream=true&api-version=2018-06-28",
+        runtime.block_on(task).unwrap()
+    );
+}
+
+#[test]
+fn retries_until_service_starts() {
+    let file_path = "/tmp/edgelet_test_client_builder_retries.sock";
+    defer! {{
+        ::std::fs::remove_file(&file_path).unwrap_or(());
+    }}
+    bind_stale_socket(file_path);
+
+    let server = Delay::new(Instant::now() + Duration::from_millis(200))
+        .map_err(|err| eprintln!("{}", err))
+        .and_then(move |_| {
+            run_uds_server(file_path, echo_handler).map_err(|err| eprintln!("{}", err))
+        });
+
+    let client = UnixClientBuilder::new()
+        .with_retry_policy(RetryPolicy::fixed(Duration::from_millis(50)).with_max_attempts(40))
+        .build(&Url::parse(&format!("unix://{}", file_path)).unwrap())
+        .unwrap();
+    let task = get_body(&client, client.uri("/").unwrap());
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    runtime.spawn(server);
+    assert!(runtime.block_on(task).unwrap().starts_with("edgelet/"));
+}
+
+#[test]
+fn gives_up_on_refused_connections() {
+    let file_path = "/tmp/edgelet_test_client_builder_refused.sock";
+    defer! {{
+        ::std::fs::remove_file(&file_path).unwrap_or(());
+    }}
+    bind_stale_socket(file_path);
+
+    let client = UnixClientBuilder::new()
+        .with_retry_policy(RetryPolicy::fixed(Duration::from_millis(10)).with_max_attempts(3))
+        .build(&Url::parse(&format!("unix://{}", file_path)).unwrap())
+        .unwrap();
+    let task = get_body(&client, client.uri("/").unwrap());
+
+    let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+    let err = runtime.block_on(task).unwrap_err();
+    assert!(err.is_connect());
+}
@@ -8,12 +8,14 @@
 #![allow(renamed_and_removed_lints)]
 #![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
 
+#[macro_use]
 extern crate edgelet_http;
 extern crate futures;
 extern crate http;
 extern crate hyper;
 
 use edgelet_http::route::{Builder, Parameters, RegexRoutesBuilder, Router};
+use edgelet_http::API_VERSION;
 use futures::{future, Future, Stream};
 use http::{Request, Response, StatusCode};
 use hyper::service::{NewService, Service};
@@ -114,3 +116,64 @@ fn not_found() {
 
     assert_eq!(StatusCode::NOT_FOUND, response1.status());
 }
+
+#[test]
+fn paths_are_normalized_before_routing() {
+    let recognizer = RegexRoutesBuilder::default()
+        .get("/route1/(?P<name>[^/]+)", route1)
+        .get("/route2/(?P<name>[^/]+)", route2)
+        .finish();
+    let router = Router::from(recognizer);
+    let mut service = router.new_service().wait().unwrap();
+
+    let uri = "http://example.com//route1/../route2/./the%6eame";
+    let request = Request::get(uri).body(Body::default()).unwrap();
+    let response = service.call(request).wait().unwrap();
+    assert_eq!(StatusCode::CREATED, response.status());
+    let body = response.into_body().concat2().wait().unwrap();
+    assert_eq!(b"route2 thename", body.as_ref());
+
+    for uri in &[
+        "http://example.com/route1/../../route2/thename",
+        "http://example.com/route1/%2e%2e/%2e%2e/route2/thename",
+        "http://example.com/route1/..%2f..%2froute2",
+    ] {
+        let request = Request::get(*uri).body(Body::default()).unwrap();
+        let response = service.call(request).wait().unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status(), "{}", uri);
+    }
+}
+
+#[test]
+fn route_table_lists_routes_in_order() {
+    let router = router!(
+        get    "/route1/(?P<name>[^/]+)" => route1,
+        post   "/route2/(?P<name>[^/]+)" => route2,
+        get    "/route2/(?P<name>[^/]+)" => route2,
+        #[cfg(any())]
+        get    "/route3/(?P<name>[^/]+)" => route1,
+    );
+    let service = router.new_service().wait().unwrap();
+
+    let routes: Vec<String> = service
+        .recognizer()
+        .routes()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let version = format!("api-version={}", API_VERSION);
+    assert_eq!(
+        vec![
+            format!("GET /route1/(?P<name>[^/]+) {} => route1", version),
+            format!("POST /route2/(?P<name>[^/]+) {} => route2", version),
+            format!("GET /route2/(?P<name>[^/]+) {} => route2", version),
+        ],
+        routes
+    );
+
+    let recognizer = RegexRoutesBuilder::default().get("/", route1).finish();
+    let route = &recognizer.routes()[0];
+    assert_eq!("/", route.pattern());
+    assert_eq!(None, route.handler());
+    assert_eq!(None, route.api_version());
+}
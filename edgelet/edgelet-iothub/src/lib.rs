@@ -19,6 +19,8 @@ extern crate futures;
 #[cfg(test)]
 extern crate hyper;
 #[macro_use]
+extern crate log;
+#[macro_use]
 extern crate percent_encoding;
 #[macro_use]
 extern crate serde_derive;
@@ -40,20 +42,28 @@ mod error;
 use std::convert::AsRef;
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use failure::ResultExt;
 use futures::future::{self, Either};
 use futures::Future;
+use log::Level;
 use percent_encoding::{percent_encode, PATH_SEGMENT_ENCODE_SET};
 use url::form_urlencoded::Serializer as UrlSerializer;
 
-use edgelet_core::crypto::{KeyIdentity, KeyStore, Sign, Signature, SignatureAlgorithm};
-use edgelet_core::{AuthType, Identity, IdentityManager, IdentitySpec};
+use edgelet_core::crypto::{
+    KeyIdentity, KeyStore, MemoryKey, Sign, Signature, SignatureAlgorithm,
+};
+use edgelet_core::{
+    AuthType, GenerationId, GenerationRegistry, Identity, IdentityKeySource, IdentityManager,
+    IdentitySpec, ModuleName, Retry, RetryPolicy,
+};
 use edgelet_http::client::{ClientImpl, TokenSource};
+use edgelet_utils::log_failure;
 use iothubservice::{
-    AuthMechanism, AuthType as HubAuthType, DeviceClient, ErrorKind as HubErrorKind, Module,
-    SymmetricKey,
+    AuthMechanism, AuthType as HubAuthType, DeviceClient, Error as HubError,
+    ErrorKind as HubErrorKind, Module, SymmetricKey,
 };
 
 pub use error::{Error, ErrorKind};
@@ -61,6 +71,35 @@ pub use error::{Error, ErrorKind};
 const KEY_PRIMARY: &str = "primary";
 const KEY_SECONDARY: &str = "secondary";
 
+const HUB_MAX_ATTEMPTS: u32 = 3;
+const HUB_INITIAL_BACKOFF_MILLIS: u64 = 500;
+const HUB_MAX_BACKOFF_SECS: u64 = 10;
+
+fn hub_retry_policy() -> RetryPolicy {
+    RetryPolicy::exponential(
+        Duration::from_millis(HUB_INITIAL_BACKOFF_MILLIS),
+        Duration::from_secs(HUB_MAX_BACKOFF_SECS),
+    ).with_max_attempts(HUB_MAX_ATTEMPTS)
+}
+
+// Read-only hub calls are safe to repeat when the failure was in transport
+// or on the service side.
+fn is_transient_hub_error(err: &HubError) -> bool {
+    match *err.kind() {
+        HubErrorKind::Hyper | HubErrorKind::Http => true,
+        HubErrorKind::HubServiceError(status, _) => status.is_server_error(),
+        _ => false,
+    }
+}
+
+fn log_hub_retry(attempt: u32, err: &HubError, delay: Duration) {
+    warn!(
+        "IoT Hub request attempt {} failed, retrying in {:?}",
+        attempt, delay
+    );
+    log_failure(Level::Warn, err);
+}
+
 define_encode_set! {
     pub IOTHUB_ENCODE_SET = [PATH_SEGMENT_ENCODE_SET] | { '=' }
 }
@@ -99,6 +138,20 @@ impl Identity for HubIdentity {
             .and_then(|auth_mechanism| auth_mechanism.type_())
             .map_or(AuthType::None, convert_auth_type)
     }
+
+    fn primary_key(&self) -> Option<&str> {
+        self.hub_module
+            .authentication()
+            .and_then(AuthMechanism::symmetric_key)
+            .and_then(SymmetricKey::primary_key)
+    }
+
+    fn secondary_key(&self) -> Option<&str> {
+        self.hub_module
+            .authentication()
+            .and_then(AuthMechanism::symmetric_key)
+            .and_then(SymmetricKey::secondary_key)
+    }
 }
 
 fn convert_auth_type(hub_auth_type: HubAuthType) -> AuthType {
@@ -192,6 +245,7 @@ where
     D: 'static + Sign + Clone,
 {
     state: Arc<State<K, C, D>>,
+    generations: GenerationRegistry,
     phantom: PhantomData<D>,
 }
 
@@ -205,11 +259,23 @@ where
     pub fn new(key_store: K, client: DeviceClient<C, SasTokenSource<D>>) -> Self {
         HubIdentityManager {
             state: Arc::new(State { key_store, client }),
+            generations: GenerationRegistry::new(),
             phantom: PhantomData,
         }
     }
 
-    fn get_key_pair(&self, id: &str, generation_id: &str) -> Result<(K::Key, K::Key), Error> {
+    /// Sets the registry that is told about every new generation of a module
+    /// identity, and about every deleted identity.
+    pub fn with_generations(mut self, generations: GenerationRegistry) -> Self {
+        self.generations = generations;
+        self
+    }
+
+    fn get_key_pair(
+        &self,
+        id: &ModuleName,
+        generation_id: &GenerationId,
+    ) -> Result<(K::Key, K::Key), Error> {
         self.state
             .key_store
             .get(
@@ -225,9 +291,18 @@ where
             }).context(ErrorKind::CannotGetKey(id.to_string()))
             .map_err(Error::from)
     }
+
+    fn record_generation(&self, module: Module) -> HubIdentity {
+        let identity = HubIdentity::new(module);
+        if !identity.generation_id().is_empty() {
+            self.generations
+                .advance(identity.module_id(), identity.generation_id());
+        }
+        identity
+    }
 }
 
-fn build_key_name(key_name: &str, generation_id: &str) -> String {
+fn build_key_name(key_name: &str, generation_id: &GenerationId) -> String {
     format!("{}{}", key_name, generation_id)
 }
 
@@ -241,6 +316,7 @@ where
     fn clone(&self) -> Self {
         HubIdentityManager {
             state: self.state.clone(),
+            generations: self.generations.clone(),
             phantom: PhantomData,
         }
     }
@@ -267,7 +343,11 @@ where
         // the module by the hub. Once we have a generation ID we use it to
         // derive the keys for the module which we then proceed to update in
         // the hub.
-        let (idman_copy1, idman_copy2) = (self.clone(), self.clone());
+        if let Err(err) = ModuleName::new(id.module_id()) {
+            return Box::new(future::err(Error::from(err)));
+        }
+
+        let (idman_copy1, idman_copy2, idman_copy3) = (self.clone(), self.clone(), self.clone());
         Box::new(
             self.state
                 .client
@@ -280,7 +360,11 @@ where
                     if let (Some(module_id), Some(generation_id)) =
                         (module.module_id(), module.generation_id())
                     {
-                        idman_copy1.get_key_pair(module_id, generation_id)
+                        let module_id = ModuleName::new(module_id)
+                            .context(ErrorKind::InvalidHubResponse)?;
+                        let generation_id = GenerationId::new(generation_id)
+                            .context(ErrorKind::InvalidHubResponse)?;
+                        idman_copy1.get_key_pair(&module_id, &generation_id)
                     } else {
                         Err(Error::from(ErrorKind::InvalidHubResponse))
                     }
@@ -298,14 +382,21 @@ where
                         .client
                         .update_module(id.module_id(), Some(auth), id.managed_by())
                         .map_err(Error::from)
-                        .map(HubIdentity::new)
+                        .map(move |module| idman_copy3.record_generation(module))
                 }),
         )
     }
 
     fn update(&mut self, id: IdentitySpec) -> Self::UpdateFuture {
         let result = if let Some(generation_id) = id.generation_id() {
-            match self.get_key_pair(id.module_id(), generation_id) {
+            let key_pair = ModuleName::new(id.module_id())
+                .and_then(|module_id| {
+                    GenerationId::new(generation_id).map(|generation_id| (module_id, generation_id))
+                }).map_err(Error::from)
+                .and_then(|(module_id, generation_id)| {
+                    self.get_key_pair(&module_id, &generation_id)
+                });
+            match key_pair {
                 Ok((primary_key, secondary_key)) => {
                     let auth = AuthMechanism::default()
                         .with_type(HubAuthType::Sas)
@@ -315,12 +406,13 @@ where
                                 .with_secondary_key(base64::encode(secondary_key.as_ref())),
                         );
 
+                    let idman = self.clone();
                     Either::A(
                         self.state
                             .client
                             .update_module(id.module_id(), Some(auth), id.managed_by())
                             .map_err(Error::from)
-                            .map(HubIdentity::new),
+                            .map(move |module| idman.record_generation(module)),
                     )
                 }
 
@@ -334,44 +426,111 @@ where
     }
 
     fn list(&self) -> Self::ListFuture {
+        let state = self.state.clone();
         Box::new(
-            self.state
-                .client
-                .list_modules()
+            Retry::new(hub_retry_policy(), move |_| state.client.list_modules())
+                .with_condition(is_transient_hub_error)
+                .with_hook(log_hub_retry)
                 .map_err(Error::from)
                 .map(|modules| modules.into_iter().map(HubIdentity::new).collect()),
         )
     }
 
     fn get(&self, id: IdentitySpec) -> Self::GetFuture {
+        let module_id = match ModuleName::new(id.module_id()) {
+            Ok(module_id) => module_id,
+            Err(err) => return Box::new(future::err(Error::from(err))),
+        };
+        let state = self.state.clone();
         Box::new(
-            self.state
-                .client
-                .get_module_by_id(id.module_id())
-                .map(Some)
-                .then(|result| {
-                    result.or_else(|err| {
-                        if *err.kind() == HubErrorKind::ModuleNotFound {
-                            Ok(None)
-                        } else {
-                            Err(err)
-                        }
-                    })
-                }).map_err(Error::from)
-                .map(|module| module.map(HubIdentity::new)),
+            Retry::new(hub_retry_policy(), move |_| {
+                state.client.get_module_by_id(module_id.as_str())
+            }).with_condition(is_transient_hub_error)
+            .with_hook(log_hub_retry)
+            .map(Some)
+            .then(|result| {
+                result.or_else(|err| {
+                    if *err.kind() == HubErrorKind::ModuleNotFound {
+                        Ok(None)
+                    } else {
+                        Err(err)
+                    }
+                })
+            }).map_err(Error::from)
+            .map(|module| module.map(HubIdentity::new)),
         )
     }
 
     fn delete(&mut self, id: IdentitySpec) -> Self::DeleteFuture {
+        if let Err(err) = ModuleName::new(id.module_id()) {
+            return Box::new(future::err(Error::from(err)));
+        }
+
+        let generations = self.generations.clone();
+        let module_id = id.module_id().to_string();
         Box::new(
             self.state
                 .client
                 .delete_module(id.module_id())
-                .map_err(Error::from),
+                .map_err(Error::from)
+                .map(move |()| generations.forget(&module_id)),
         )
     }
 }
 
+impl<K, C, D> IdentityKeySource for HubIdentityManager<K, C, D>
+where
+    K: 'static + KeyStore + Send + Sync,
+    K::Key: AsRef<[u8]> + Clone + Send,
+    C: 'static + ClientImpl,
+    D: 'static + Sign + Clone + Send + Sync,
+{
+    type Error = Error;
+    type Future = Box<Future<Item = Option<Vec<MemoryKey>>, Error = Self::Error> + Send>;
+
+    fn get_keys(&self, device_id: &str, module_id: Option<&str>) -> Self::Future {
+        let state = self.state.clone();
+        let device_id = device_id.to_string();
+        let module_id = module_id.map(ToString::to_string);
+        Box::new(
+            Retry::new(hub_retry_policy(), move |_| {
+                state
+                    .client
+                    .get_identity_in_scope(&device_id, module_id.as_ref().map(AsRef::as_ref))
+            }).with_condition(is_transient_hub_error)
+            .with_hook(log_hub_retry)
+            .map_err(Error::from)
+            .and_then(|identity| match identity {
+                Some(identity) => symmetric_keys(&identity),
+                None => Ok(None),
+            }),
+        )
+    }
+}
+
+// The symmetric keys of an identity in the scope of the edge device, which has
+// none if it authenticates with certificates.
+fn symmetric_keys(identity: &Module) -> Result<Option<Vec<MemoryKey>>, Error> {
+    let key = match identity
+        .authentication()
+        .and_then(AuthMechanism::symmetric_key)
+    {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+    let keys = key
+        .primary_key()
+        .into_iter()
+        .chain(key.secondary_key())
+        .map(|key| {
+            base64::decode(key)
+                .map(MemoryKey::new)
+                .context(ErrorKind::InvalidHubResponse)
+                .map_err(Error::from)
+        }).collect::<Result<Vec<_>, _>>()?;
+    Ok(if keys.is_empty() { None } else { Some(keys) })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -394,6 +553,51 @@ mod tests {
         assert_eq!(m1.generation_id(), "");
     }
 
+    #[test]
+    fn hub_identity_keys() {
+        let m1 = HubIdentity::new(
+            Module::default().with_authentication(
+                AuthMechanism::default()
+                    .with_type(HubAuthType::Sas)
+                    .with_symmetric_key(
+                        SymmetricKey::default()
+                            .with_primary_key("cHJpbWFyeQ==".to_string())
+                            .with_secondary_key("c2Vjb25kYXJ5".to_string()),
+                    ),
+            ),
+        );
+        assert_eq!(Some("cHJpbWFyeQ=="), m1.primary_key());
+        assert_eq!(Some("c2Vjb25kYXJ5"), m1.secondary_key());
+        assert_eq!(None, HubIdentity::new(Module::new()).primary_key());
+    }
+
+    #[test]
+    fn symmetric_keys_of_identities_in_scope() {
+        let leaf = Module::default()
+            .with_device_id("leaf".to_string())
+            .with_authentication(
+                AuthMechanism::default()
+                    .with_type(HubAuthType::Sas)
+                    .with_symmetric_key(
+                        SymmetricKey::default()
+                            .with_primary_key(base64::encode("pkey"))
+                            .with_secondary_key(base64::encode("skey")),
+                    ),
+            );
+        assert_eq!(2, symmetric_keys(&leaf).unwrap().unwrap().len());
+
+        let x509 = Module::default()
+            .with_device_id("camera".to_string())
+            .with_authentication(AuthMechanism::default().with_type(HubAuthType::X509));
+        assert!(symmetric_keys(&x509).unwrap().is_none());
+
+        let garbled = Module::default().with_authentication(
+            AuthMechanism::default()
+                .with_symmetric_key(SymmetricKey::default().with_primary_key("*".to_string())),
+        );
+        assert!(symmetric_keys(&garbled).is_err());
+    }
+
     #[test]
     fn get_key_pair_succeeds() {
         let mut key_store = MemoryKeyStore::new();
@@ -420,7 +624,11 @@ mod tests {
         let device_client = DeviceClient::new(client, "d1").unwrap();
 
         let identity_manager = HubIdentityManager::new(key_store, device_client);
-        let (pkey, skey) = identity_manager.get_key_pair("m1", "g1").unwrap();
+        let (pkey, skey) = identity_manager
+            .get_key_pair(
+                &ModuleName::new("m1").unwrap(),
+                &GenerationId::new("g1").unwrap(),
+            ).unwrap();
 
         assert_eq!(pkey.as_ref(), &Bytes::from("pkey"));
         assert_eq!(skey.as_ref(), &Bytes::from("skey"));
@@ -442,7 +650,11 @@ mod tests {
         let device_client = DeviceClient::new(client, "d1").unwrap();
 
         let identity_manager = HubIdentityManager::new(key_store, device_client);
-        identity_manager.get_key_pair("m1", "g1").unwrap();
+        identity_manager
+            .get_key_pair(
+                &ModuleName::new("m1").unwrap(),
+                &GenerationId::new("g1").unwrap(),
+            ).unwrap();
     }
 
     #[test]
@@ -467,7 +679,11 @@ mod tests {
         let device_client = DeviceClient::new(client, "d1").unwrap();
 
         let identity_manager = HubIdentityManager::new(key_store, device_client);
-        identity_manager.get_key_pair("m1", "g1").unwrap();
+        identity_manager
+            .get_key_pair(
+                &ModuleName::new("m1").unwrap(),
+                &GenerationId::new("g1").unwrap(),
+            ).unwrap();
     }
 
     #[test]
@@ -492,7 +708,11 @@ mod tests {
         let device_client = DeviceClient::new(client, "d1").unwrap();
 
         let identity_manager = HubIdentityManager::new(key_store, device_client);
-        identity_manager.get_key_pair("m1", "g1").unwrap();
+        identity_manager
+            .get_key_pair(
+                &ModuleName::new("m1").unwrap(),
+                &GenerationId::new("g1").unwrap(),
+            ).unwrap();
     }
 
     #[test]
@@ -575,7 +795,9 @@ mod tests {
         let client = Client::new(handler, Some(token_source), api_version, host_name).unwrap();
         let device_client = DeviceClient::new(client, "d1").unwrap();
 
-        let mut identity_manager = HubIdentityManager::new(key_store, device_client);
+        let generations = GenerationRegistry::new();
+        let mut identity_manager = HubIdentityManager::new(key_store, device_client)
+            .with_generations(generations.clone());
         let task = identity_manager.create(IdentitySpec::new("m1"));
 
         let hub_identity = tokio::runtime::current_thread::Runtime::new()
@@ -584,6 +806,7 @@ mod tests {
             .unwrap();
 
         assert_eq!(hub_identity.hub_module(), &expected_module_result);
+        assert_eq!(Some("g1".to_string()), generations.current("m1"));
     }
 
     #[test]
@@ -814,6 +1037,37 @@ mod tests {
         assert_eq!(None, hub_identity);
     }
 
+    #[test]
+    fn get_rejects_invalid_module_name() {
+        let key_store = MemoryKeyStore::new();
+
+        let api_version = "2018-04-10";
+        let host_name = Url::parse("http://localhost").unwrap();
+
+        let handler = |_req: Request<Body>| -> Result<Response<Body>, hyper::Error> {
+            panic!("request should not have been sent to the hub")
+        };
+        let token_source = SasTokenSource::new(
+            "hub".to_string(),
+            "device".to_string(),
+            MemoryKey::new("device"),
+        );
+        let client = Client::new(handler, Some(token_source), api_version, host_name).unwrap();
+        let device_client = DeviceClient::new(client, "d1").unwrap();
+
+        let identity_manager = HubIdentityManager::new(key_store, device_client);
+        let task = identity_manager.get(IdentitySpec::new("../m1"));
+
+        let err = tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap_err();
+        assert!(match *err.kind() {
+            ErrorKind::Core => true,
+            _ => false,
+        });
+    }
+
     #[test]
     fn delete_succeeds() {
         let key_store = MemoryKeyStore::new();
@@ -835,7 +1089,10 @@ mod tests {
         let client = Client::new(handler, Some(token_source), api_version, host_name).unwrap();
         let device_client = DeviceClient::new(client, "d1").unwrap();
 
-        let mut identity_manager = HubIdentityManager::new(key_store, device_client);
+        let generations = GenerationRegistry::new();
+        generations.advance("m1", "g1");
+        let mut identity_manager = HubIdentityManager::new(key_store, device_client)
+            .with_generations(generations.clone());
         let task = identity_manager
             .delete(IdentitySpec::new("m1"))
             .then(|result: Result<(), _>| result);
@@ -844,6 +1101,8 @@ mod tests {
             .unwrap()
             .block_on(task)
             .unwrap();
+
+        assert_eq!(None, generations.current("m1"));
     }
 
     #[test]
@@ -0,0 +1,182 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fmt::{self, Display};
+
+use edgelet_core::{error_category, Error as CoreError, ErrorCategory};
+use edgelet_http::{classify_error as classify_http_error, Error as EdgeletHttpError};
+use failure::{Backtrace, Context, Fail};
+use hyper::{Error as HyperError, StatusCode};
+use serde_json;
+
+use management::apis::Error as MgmtError;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[derive(Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "Core error")]
+    Core,
+    #[fail(display = "Serde error")]
+    Serde,
+    #[fail(display = "Hyper error")]
+    Hyper,
+    #[fail(display = "Http error")]
+    Http,
+    #[fail(display = "Client error")]
+    Client(MgmtError<serde_json::Value>),
+    #[fail(display = "State not modified")]
+    NotModified,
+}
+
+impl ErrorKind {
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// leave it to their cause. A client error has the category the daemon
+    /// reported in its error response.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::Client(MgmtError::Api(ref error)) => error
+                .content
+                .as_ref()
+                .and_then(|content| content["category"].as_str())
+                .and_then(|category| category.parse().ok()),
+            ErrorKind::Core
+            | ErrorKind::Serde
+            | ErrorKind::Hyper
+            | ErrorKind::Http
+            | ErrorKind::NotModified
+            | ErrorKind::Client(_) => None,
+        }
+    }
+}
+
+/// Categorizes the errors of this crate, and falls back to
+/// `edgelet_http::classify_error`.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    match error.downcast_ref::<Error>() {
+        Some(error) => error.kind().category(),
+        None => classify_http_error(error),
+    }
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&Fail> {
+        self.inner.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        self.inner.get_context()
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            inner: Context::new(kind),
+        }
+    }
+}
+
+impl From<Context<ErrorKind>> for Error {
+    fn from(inner: Context<ErrorKind>) -> Self {
+        Error { inner }
+    }
+}
+
+impl From<CoreError> for Error {
+    fn from(error: CoreError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Core),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Serde),
+        }
+    }
+}
+
+impl From<HyperError> for Error {
+    fn from(error: HyperError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Hyper),
+        }
+    }
+}
+
+impl From<EdgeletHttpError> for Error {
+    fn from(error: EdgeletHttpError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Http),
+        }
+    }
+}
+
+impl From<MgmtError<serde_json::Value>> for Error {
+    fn from(error: MgmtError<serde_json::Value>) -> Self {
+        match error {
+            MgmtError::Hyper(h) => From::from(h),
+            MgmtError::Serde(s) => From::from(s),
+            MgmtError::Api(ref e) if e.code == StatusCode::NOT_MODIFIED => {
+                From::from(ErrorKind::NotModified)
+            }
+            MgmtError::Api(_) => From::from(ErrorKind::Client(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::ErrorKind as CoreErrorKind;
+    use management::apis::ApiError;
+
+    use super::*;
+
+    #[test]
+    fn client_error_has_the_category_the_daemon_reported() {
+        let error = Error::from(MgmtError::Api(ApiError {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            content: Some(json!({ "message": "HSM failure", "category": "hsm" })),
+        }));
+        assert_eq!(ErrorCategory::Hsm, error.category());
+    }
+
+    #[test]
+    fn not_modified_is_its_own_kind() {
+        let error = Error::from(MgmtError::Api(ApiError {
+            code: StatusCode::NOT_MODIFIED,
+            content: None,
+        }));
+        match *error.kind() {
+            ErrorKind::NotModified => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn core_error_is_categorized_by_core() {
+        let error = Error::from(CoreError::from(CoreErrorKind::KeyStore));
+        assert_eq!(ErrorCategory::Hsm, error.category());
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! A client for the management API of the daemon.
+//!
+//! `ModuleClient` implements `ModuleRuntime` over the management API, so that
+//! the iotedge tool can list, restart and fetch the logs of modules the same
+//! way the daemon does with the container runtime, and has typed methods for
+//! the identities, deployments, operations and state of the daemon.
+
+#![deny(unused_extern_crates, warnings)]
+// Remove this when clippy stops warning about old-style `allow()`,
+// which can only be silenced by enabling a feature and thus requires nightly
+//
+// Ref: https://github.com/rust-lang-nursery/rust-clippy/issues/3159#issuecomment-420530386
+#![allow(renamed_and_removed_lints)]
+#![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
+#![cfg_attr(feature = "cargo-clippy", allow(stutter, use_self))]
+
+extern crate base64;
+extern crate edgelet_core;
+extern crate edgelet_docker;
+extern crate edgelet_http;
+extern crate failure;
+#[macro_use]
+extern crate failure_derive;
+#[macro_use]
+extern crate futures;
+extern crate hyper;
+extern crate management;
+#[cfg(test)]
+#[macro_use]
+extern crate serde_json;
+#[cfg(not(test))]
+extern crate serde_json;
+extern crate url;
+
+mod error;
+mod module;
+
+pub use error::{classify_error, Error, ErrorKind};
+pub use management::models::{Identity, Operation, SystemInfo};
+pub use module::{Chunk, Logs, ModuleClient, ModuleConfig, ModuleDetails};
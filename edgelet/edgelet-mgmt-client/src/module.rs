@@ -1,21 +1,26 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use base64;
 use edgelet_core::SystemInfo as CoreSystemInfo;
 use edgelet_core::*;
 use edgelet_docker::{self, DockerConfig};
-use edgelet_http::{UrlConnector, API_VERSION};
+use edgelet_http::{UnixClientBuilder, API_VERSION};
 use futures::future::{self, FutureResult};
 use futures::prelude::*;
 use futures::stream;
-use hyper::{Body, Chunk as HyperChunk, Client};
+use hyper::{Body, Chunk as HyperChunk};
 use management::apis::client::APIClient;
 use management::apis::configuration::Configuration;
-use management::models::{Config, ModuleDetails as HttpModuleDetails};
+use management::models::{
+    BackupRequest, Config, Identity, IdentitySpec, ModuleDetails as HttpModuleDetails, Operation,
+    RestoreRequest, SystemInfo,
+};
 use serde_json;
 use url::Url;
 
@@ -27,22 +32,125 @@ pub struct ModuleClient {
 
 impl ModuleClient {
     pub fn new(url: &Url) -> Result<Self, Error> {
-        let client = Client::builder().build(UrlConnector::new(url)?);
+        let builder = UnixClientBuilder::new()
+            .with_api_version(API_VERSION)
+            .with_user_agent(concat!("edgelet-mgmt-client/", env!("CARGO_PKG_VERSION")));
 
         let base_path = get_base_path(url);
-        let mut configuration = Configuration::new(client);
+        let mut configuration = Configuration::new(builder.hyper_client(url)?);
         configuration.base_path = base_path.to_string();
-
-        let scheme = url.scheme().to_string();
-        configuration.uri_composer = Box::new(move |base_path, path| {
-            Ok(UrlConnector::build_hyper_uri(&scheme, base_path, path)?)
-        });
+        configuration.user_agent = Some(builder.user_agent().to_string());
+        configuration.uri_composer = builder.uri_composer(url);
 
         let module_client = ModuleClient {
             client: Arc::new(APIClient::new(configuration)),
         };
         Ok(module_client)
     }
+
+    /// Restores the deployment `to` steps back in the daemon's deployment
+    /// history.
+    pub fn rollback(&self, to: i32) -> impl Future<Item = (), Error = Error> + Send {
+        self.client
+            .deployment_api()
+            .rollback_deployment(API_VERSION, to)
+            .map_err(Error::from)
+    }
+
+    /// Returns the progress of the long running operation `id`.
+    pub fn operation(&self, id: &str) -> impl Future<Item = Operation, Error = Error> + Send {
+        self.client
+            .operation_api()
+            .get_operation(API_VERSION, id)
+            .map_err(Error::from)
+    }
+
+    /// Backs up the daemon state in an archive encrypted with `passphrase`.
+    pub fn backup(&self, passphrase: &str) -> impl Future<Item = Vec<u8>, Error = Error> + Send {
+        self.client
+            .state_api()
+            .backup_state(API_VERSION, BackupRequest::new(passphrase.to_string()))
+            .map_err(Error::from)
+    }
+
+    /// Restores the daemon state from `backup`, which was encrypted with
+    /// `passphrase`, and returns the names of the files that were restored.
+    pub fn restore(
+        &self,
+        backup: &[u8],
+        passphrase: &str,
+    ) -> impl Future<Item = Vec<String>, Error = Error> + Send {
+        let request = RestoreRequest::new(passphrase.to_string(), base64::encode(backup));
+        self.client
+            .state_api()
+            .restore_state(API_VERSION, request)
+            .map(|result| result.restored().clone())
+            .map_err(Error::from)
+    }
+
+    /// Returns the module identities the daemon manages.
+    pub fn list_identities(&self) -> impl Future<Item = Vec<Identity>, Error = Error> + Send {
+        self.client
+            .identity_api()
+            .list_identities(API_VERSION)
+            .map(|list| list.identities().to_vec())
+            .map_err(Error::from)
+    }
+
+    /// Creates a module identity for `module_id`, or returns the existing
+    /// one. `managed_by` defaults to the daemon.
+    pub fn create_identity(
+        &self,
+        module_id: &str,
+        managed_by: Option<&str>,
+    ) -> impl Future<Item = Identity, Error = Error> + Send {
+        let spec = IdentitySpec::new(module_id.to_string());
+        let spec = match managed_by {
+            Some(managed_by) => spec.with_managed_by(managed_by.to_string()),
+            None => spec,
+        };
+        self.client
+            .identity_api()
+            .create_identity(API_VERSION, module_id, spec)
+            .map_err(Error::from)
+    }
+
+    pub fn delete_identity(&self, module_id: &str) -> impl Future<Item = (), Error = Error> + Send {
+        self.client
+            .identity_api()
+            .delete_identity(API_VERSION, module_id)
+            .map_err(Error::from)
+    }
+
+    /// Lists the modules whose labels match the label selector `selector`,
+    /// such as `tier=web,solution!=line-1`.
+    pub fn list_selected(
+        &self,
+        selector: &str,
+    ) -> impl Future<Item = Vec<ModuleDetails>, Error = Error> + Send {
+        self.client
+            .module_api()
+            .list_modules(API_VERSION, selector)
+            .map(|list| {
+                list.modules()
+                    .iter()
+                    .cloned()
+                    .map(|m| {
+                        let type_ = m.type_().clone();
+                        let config = m.config().clone();
+                        ModuleDetails(m, ModuleConfig(type_, config))
+                    }).collect()
+            }).map_err(Error::from)
+    }
+
+    /// Returns what the daemon knows of the host and of itself, such as its
+    /// version, which the `ModuleRuntime` implementation can't carry.
+    pub fn system_info_details(&self) -> impl Future<Item = SystemInfo, Error = Error> + Send {
+        self.client
+            .system_information_api()
+            .get_system_info(API_VERSION)
+            .map_err(Error::from)
+    }
 }
 
 fn get_base_path(url: &Url) -> &str {
@@ -97,6 +205,10 @@ impl Module for ModuleDetails {
     fn runtime_state(&self) -> Self::RuntimeStateFuture {
         future::result(runtime_status(&self.0))
     }
+
+    fn labels(&self) -> HashMap<String, String> {
+        self.0.labels().cloned().unwrap_or_else(HashMap::new)
+    }
 }
 
 fn runtime_status(details: &HttpModuleDetails) -> Result<ModuleRuntimeState, Error> {
@@ -110,6 +222,10 @@ fn runtime_status(details: &HttpModuleDetails) -> Result<ModuleRuntimeState, Err
         .status()
         .exit_status()
         .and_then(|e| e.status_code().parse::<i64>().ok());
+    let exit_reason = details.status().exit_status().and_then(|e| {
+        e.reason()
+            .and_then(|reason| ExitReason::from_name(reason, exit_code.unwrap_or_default()))
+    });
     let exit_time = details
         .status()
         .exit_status()
@@ -120,6 +236,7 @@ fn runtime_status(details: &HttpModuleDetails) -> Result<ModuleRuntimeState, Err
         .with_status(status)
         .with_status_description(description)
         .with_exit_code(exit_code)
+        .with_exit_reason(exit_reason)
         .with_started_at(start_time)
         .with_finished_at(exit_time);
     Ok(state)
@@ -160,9 +277,17 @@ impl ModuleRuntime for ModuleClient {
     type StopFuture = Box<Future<Item = (), Error = Self::Error> + Send>;
     type SystemInfoFuture = Box<Future<Item = CoreSystemInfo, Error = Self::Error> + Send>;
     type RemoveAllFuture = Box<Future<Item = (), Error = Self::Error> + Send>;
+    type UpdateFuture = Box<Future<Item = (), Error = Self::Error> + Send>;
 
     fn system_info(&self) -> Self::SystemInfoFuture {
-        unimplemented!()
+        let info = self.system_info_details().map(|info| {
+            let core = CoreSystemInfo::new(info.os_type().clone(), info.architecture().clone());
+            match info.runtime_version() {
+                Some(version) => core.with_server_version(version.to_string()),
+                None => core,
+            }
+        });
+        Box::new(info)
     }
 
     fn init(&self) -> Self::InitFuture {
@@ -225,29 +350,19 @@ impl ModuleRuntime for ModuleClient {
         unimplemented!()
     }
 
+    fn remove_with_volumes(&self, _id: &str) -> Self::RemoveFuture {
+        unimplemented!()
+    }
+
     fn list(&self) -> Self::ListFuture {
-        let modules = self
-            .client
-            .module_api()
-            .list_modules(API_VERSION)
-            .map(|list| {
-                list.modules()
-                    .into_iter()
-                    .cloned()
-                    .map(|m| {
-                        let type_ = m.type_().clone();
-                        let config = m.config().clone();
-                        ModuleDetails(m, ModuleConfig(type_, config))
-                    }).collect()
-            }).map_err(From::from);
-        Box::new(modules)
+        Box::new(self.list_selected(""))
     }
 
     fn list_with_details(&self) -> Self::ListWithDetailsStream {
         let modules = self
             .client
             .module_api()
-            .list_modules(API_VERSION)
+            .list_modules(API_VERSION, "")
             .map_err(Error::from)
             .map(|list| {
                 let iter = list.modules().to_owned().into_iter().map(|m| {
@@ -263,12 +378,22 @@ impl ModuleRuntime for ModuleClient {
         Box::new(modules)
     }
 
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
     fn logs(&self, id: &str, options: &LogOptions) -> Self::LogsFuture {
         let tail = &options.tail().to_string();
+        let limit_bytes = options.limit_bytes().unwrap_or(0) as i64;
         let result = self
             .client
             .module_api()
-            .module_logs(API_VERSION, id, options.follow(), tail)
+            .module_logs(
+                API_VERSION,
+                id,
+                options.follow(),
+                tail,
+                options.since(),
+                limit_bytes,
+                options.grep().unwrap_or(""),
+            )
             .map(Logs)
             .map_err(Error::from);
         Box::new(result)
@@ -287,6 +412,14 @@ impl ModuleRuntime for ModuleClient {
             future::join_all(n).map(|_| ())
         }))
     }
+
+    fn update(
+        &self,
+        _module: ModuleSpec<Self::Config>,
+        _strategy: UpdateStrategy,
+    ) -> Self::UpdateFuture {
+        unimplemented!()
+    }
 }
 
 pub struct Logs(Body);
@@ -3,6 +3,19 @@
 use chrono::{DateTime, Utc};
 use edgelet_core::{Certificate, Error as CoreError, ErrorKind as CoreErrorKind, PrivateKey};
 
+/// A self-signed certificate with serial number 1A2B3C4D.
+pub const TEST_CERT_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBZDCCAQmgAwIBAgIEGis8TTAKBggqhkjOPQQDAjAPMQ0wCwYDVQQDDAR0ZXN0
+MB4XDTI2MTAxNTAzMjAzNloXDTM2MTAxMjAzMjAzNlowDzENMAsGA1UEAwwEdGVz
+dDBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABN2ftSd0gZVSaoFda34/lmVUR7oY
+JWM0DIrabpHJxKlQJhC3/FH9f1iv1KF92yy2RclBAVo6qOMa74WwavJKz5mjUzBR
+MB0GA1UdDgQWBBSUbs5JtYAp4Vpnrmzrx4CKc1L9ADAfBgNVHSMEGDAWgBSUbs5J
+tYAp4Vpnrmzrx4CKc1L9ADAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kA
+MEYCIQC2Lt9XrJku51lRg2uD5Lh92jKETvVUDVkJEPXZIL5YSAIhAMtCsa5CCBL3
+2h/t9vsE+nViaScjjRaLs6uhTt8HE9tg
+-----END CERTIFICATE-----
+";
+
 #[derive(Clone, Debug, Default)]
 pub struct TestCert {
     cert: Vec<u8>,
@@ -10,6 +23,7 @@ pub struct TestCert {
     private_key: Option<PrivateKey<String>>,
     fail_private_key: bool,
     fail_valid_to: bool,
+    valid_to: Option<DateTime<Utc>>,
 }
 
 impl TestCert {
@@ -37,6 +51,12 @@ impl TestCert {
         self.fail_valid_to = fail_valid_to;
         self
     }
+
+    /// Expires at `valid_to` instead of whenever it is asked when it expires.
+    pub fn with_valid_to(mut self, valid_to: DateTime<Utc>) -> Self {
+        self.valid_to = Some(valid_to);
+        self
+    }
 }
 
 impl Certificate for TestCert {
@@ -63,7 +83,7 @@ impl Certificate for TestCert {
         if self.fail_valid_to {
             Err(CoreError::from(CoreErrorKind::Io))
         } else {
-            Ok(Utc::now())
+            Ok(self.valid_to.unwrap_or_else(Utc::now))
         }
     }
 }
@@ -0,0 +1,66 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Set to have `assert_golden` write what it is given to the golden file
+/// instead of comparing it with the file.
+pub const UPDATE_GOLDEN_VAR: &str = "UPDATE_GOLDEN";
+
+/// Fails with a line diff unless `actual` matches the committed golden file
+/// at `path`.
+pub fn assert_golden<P: AsRef<Path>>(path: P, actual: &str) {
+    let path = path.as_ref();
+    if env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        fs::write(path, actual)
+            .unwrap_or_else(|err| panic!("could not write {}: {}", path.display(), err));
+        return;
+    }
+
+    let expected = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("could not read {}: {}", path.display(), err));
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    if expected != actual {
+        panic!(
+            "{} is out of date (- expected, + actual); run with {}=1 to update it\n{}",
+            path.display(),
+            UPDATE_GOLDEN_VAR,
+            diff(&expected, &actual)
+        );
+    }
+}
+
+/// A line diff of the longest common subsequence of `old` and `new`.
+fn diff(old: &[&str], new: &[&str]) -> String {
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
@@ -35,6 +35,10 @@ pub struct TestIdentity {
     generation_id: String,
     #[serde(rename = "authType")]
     auth_type: AuthType,
+    #[serde(rename = "primaryKey", default, skip_serializing_if = "Option::is_none")]
+    primary_key: Option<String>,
+    #[serde(rename = "secondaryKey", default, skip_serializing_if = "Option::is_none")]
+    secondary_key: Option<String>,
 }
 
 impl TestIdentity {
@@ -49,8 +53,16 @@ impl TestIdentity {
             managed_by: managed_by.to_string(),
             generation_id: generation_id.to_string(),
             auth_type,
+            primary_key: None,
+            secondary_key: None,
         }
     }
+
+    pub fn with_keys(mut self, primary_key: &str, secondary_key: &str) -> Self {
+        self.primary_key = Some(primary_key.to_string());
+        self.secondary_key = Some(secondary_key.to_string());
+        self
+    }
 }
 
 impl Identity for TestIdentity {
@@ -69,6 +81,14 @@ impl Identity for TestIdentity {
     fn auth_type(&self) -> AuthType {
         self.auth_type
     }
+
+    fn primary_key(&self) -> Option<&str> {
+        self.primary_key.as_ref().map(AsRef::as_ref)
+    }
+
+    fn secondary_key(&self) -> Option<&str> {
+        self.secondary_key.as_ref().map(AsRef::as_ref)
+    }
 }
 
 #[derive(Clone)]
@@ -126,6 +146,9 @@ impl IdentityManager for TestIdentityManager {
                 id.managed_by().unwrap_or(&"".to_string()),
                 &format!("{}", self.gen_id_sentinel),
                 AuthType::Sas,
+            ).with_keys(
+                &format!("{}-primary", id.module_id()),
+                &format!("{}-secondary", id.module_id()),
             );
             self.identities.push(id.clone());
 
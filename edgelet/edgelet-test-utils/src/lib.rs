@@ -35,6 +35,7 @@ extern crate tokio;
 use std::net::TcpListener;
 
 pub mod cert;
+pub mod golden;
 pub mod identity;
 mod json_connector;
 pub mod module;
@@ -1,5 +1,6 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::time::Duration;
 
@@ -65,6 +66,7 @@ pub struct TestModule<E: Fail> {
     name: String,
     config: TestConfig,
     state: Result<ModuleRuntimeState, E>,
+    labels: HashMap<String, String>,
 }
 
 impl<E: Fail> TestModule<E> {
@@ -73,8 +75,14 @@ impl<E: Fail> TestModule<E> {
             name,
             config,
             state,
+            labels: HashMap::new(),
         }
     }
+
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = labels;
+        self
+    }
 }
 
 impl<E: Clone + Fail> Module for TestModule<E> {
@@ -97,12 +105,17 @@ impl<E: Clone + Fail> Module for TestModule<E> {
     fn runtime_state(&self) -> Self::RuntimeStateFuture {
         self.state.clone().into_future()
     }
+
+    fn labels(&self) -> HashMap<String, String> {
+        self.labels.clone()
+    }
 }
 
 #[derive(Clone)]
 pub struct TestRuntime<E: Fail> {
     module: Result<TestModule<E>, E>,
     registry: NullRegistry<E>,
+    create_error: Option<(String, E)>,
 }
 
 impl<E: Fail> TestRuntime<E> {
@@ -110,8 +123,15 @@ impl<E: Fail> TestRuntime<E> {
         TestRuntime {
             module,
             registry: NullRegistry::new(),
+            create_error: None,
         }
     }
+
+    /// Fails creating the module `name` with `error`, and only that module.
+    pub fn with_create_error(mut self, name: &str, error: E) -> Self {
+        self.create_error = Some((name.to_string(), error));
+        self
+    }
 }
 
 pub struct EmptyBody<E> {
@@ -167,6 +187,7 @@ impl<E: Clone + Fail> ModuleRuntime for TestRuntime<E> {
     type StopFuture = FutureResult<(), Self::Error>;
     type SystemInfoFuture = FutureResult<SystemInfo, Self::Error>;
     type RemoveAllFuture = FutureResult<(), Self::Error>;
+    type UpdateFuture = FutureResult<(), Self::Error>;
 
     fn system_info(&self) -> Self::SystemInfoFuture {
         match self.module {
@@ -185,9 +206,12 @@ impl<E: Clone + Fail> ModuleRuntime for TestRuntime<E> {
         }
     }
 
-    fn create(&self, _module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
+    fn create(&self, module: ModuleSpec<Self::Config>) -> Self::CreateFuture {
         match self.module {
-            Ok(_) => future::ok(()),
+            Ok(_) => match self.create_error {
+                Some((ref name, ref e)) if name == module.name() => future::err(e.clone()),
+                _ => future::ok(()),
+            },
             Err(ref e) => future::err(e.clone()),
         }
     }
@@ -220,6 +244,10 @@ impl<E: Clone + Fail> ModuleRuntime for TestRuntime<E> {
         }
     }
 
+    fn remove_with_volumes(&self, id: &str) -> Self::RemoveFuture {
+        self.remove(id)
+    }
+
     fn list(&self) -> Self::ListFuture {
         match self.module {
             Ok(ref m) => future::ok(vec![m.clone()]),
@@ -251,4 +279,15 @@ impl<E: Clone + Fail> ModuleRuntime for TestRuntime<E> {
     fn remove_all(&self) -> Self::RemoveAllFuture {
         future::ok(())
     }
+
+    fn update(
+        &self,
+        _module: ModuleSpec<Self::Config>,
+        _strategy: UpdateStrategy,
+    ) -> Self::UpdateFuture {
+        match self.module {
+            Ok(_) => future::ok(()),
+            Err(ref e) => future::err(e.clone()),
+        }
+    }
 }
@@ -0,0 +1,326 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::env;
+use std::rc::Rc;
+use std::time::Duration;
+
+use base64;
+use chrono::{DateTime, Utc};
+use edgelet_core::{Retry, RetryPolicy};
+use edgelet_http::{UnixClientBuilder, API_VERSION};
+use futures::Future;
+use hyper::StatusCode;
+use serde_json::Value;
+use url::Url;
+use workload::apis::client::APIClient;
+use workload::apis::configuration::Configuration;
+use workload::apis::Error as ApiError;
+use workload::models::{
+    CertificateResponse, DecryptRequest, EncryptRequest, IdentityCertificateRequest,
+    ServerCertificateRequest, SignAlgorithm, SignRequest,
+};
+
+use error::{Error, ErrorKind};
+
+const WORKLOAD_URI_KEY: &str = "IOTEDGE_WORKLOADURI";
+const MODULE_ID_KEY: &str = "IOTEDGE_MODULEID";
+const GENERATION_ID_KEY: &str = "IOTEDGE_MODULEGENERATIONID";
+const API_VERSION_KEY: &str = "IOTEDGE_APIVERSION";
+
+const TRANSIENT_NETWORK: &str = "transient-network";
+
+const DEFAULT_RETRY_INITIAL_DELAY_MILLIS: u64 = 250;
+const DEFAULT_RETRY_MAX_DELAY_SECS: u64 = 4;
+const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+
+/// A client for the workload API that makes its requests as one module.
+///
+/// The connection to the workload socket is kept open between requests, and
+/// clones share it.
+#[derive(Clone)]
+pub struct WorkloadClient {
+    client: Rc<APIClient>,
+    api_version: String,
+    module_id: String,
+    generation_id: String,
+    retry_policy: RetryPolicy,
+}
+
+impl WorkloadClient {
+    /// Creates a client for the workload API at `url` that makes its
+    /// requests as the generation `generation_id` of module `module_id`.
+    pub fn new(url: &Url, module_id: &str, generation_id: &str) -> Result<Self, Error> {
+        let builder = UnixClientBuilder::new().with_user_agent(concat!(
+            "edgelet-workload-client/",
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        let mut configuration = Configuration::new(builder.hyper_client(url)?);
+        configuration.base_path = match url.scheme() {
+            "unix" => url.path().to_string(),
+            _ => url.as_str().to_string(),
+        };
+        configuration.user_agent = Some(builder.user_agent().to_string());
+        configuration.uri_composer = builder.uri_composer(url);
+
+        Ok(WorkloadClient {
+            client: Rc::new(APIClient::new(configuration)),
+            api_version: API_VERSION.to_string(),
+            module_id: module_id.to_string(),
+            generation_id: generation_id.to_string(),
+            retry_policy: RetryPolicy::exponential(
+                Duration::from_millis(DEFAULT_RETRY_INITIAL_DELAY_MILLIS),
+                Duration::from_secs(DEFAULT_RETRY_MAX_DELAY_SECS),
+            ).with_max_attempts(DEFAULT_MAX_ATTEMPTS),
+        })
+    }
+
+    /// Creates a client for the module it runs in, from the environment
+    /// variables the daemon sets in every module.
+    pub fn from_env() -> Result<Self, Error> {
+        let url = Url::parse(&env_var(WORKLOAD_URI_KEY)?)?;
+        let client =
+            WorkloadClient::new(&url, &env_var(MODULE_ID_KEY)?, &env_var(GENERATION_ID_KEY)?)?;
+        Ok(match env::var(API_VERSION_KEY) {
+            Ok(api_version) => client.with_api_version(&api_version),
+            Err(_) => client,
+        })
+    }
+
+    pub fn with_api_version(mut self, api_version: &str) -> Self {
+        self.api_version = api_version.to_string();
+        self
+    }
+
+    /// Sets how requests that fail because the daemon, or a service it
+    /// depends on, is unavailable are retried. Other failures aren't.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn module_id(&self) -> &str {
+        &self.module_id
+    }
+
+    pub fn generation_id(&self) -> &str {
+        &self.generation_id
+    }
+
+    /// Asks for an identity certificate for the module, valid until
+    /// `expiration` or for as long as the daemon allows without one.
+    pub fn identity_certificate(
+        &self,
+        expiration: Option<DateTime<Utc>>,
+    ) -> impl Future<Item = CertificateResponse, Error = Error> {
+        let client = self.client.clone();
+        let (api_version, module_id) = (self.api_version.clone(), self.module_id.clone());
+        let expiration = expiration.map(|expiration| expiration.to_rfc3339());
+        self.retry(move || {
+            let request = match expiration {
+                Some(ref expiration) => {
+                    IdentityCertificateRequest::new().with_expiration(expiration.clone())
+                }
+                None => IdentityCertificateRequest::new(),
+            };
+            client
+                .workload_api()
+                .create_identity_certificate(&api_version, &module_id, request)
+        })
+    }
+
+    /// Asks for a server certificate for `common_name`, valid until
+    /// `expiration`.
+    pub fn server_certificate(
+        &self,
+        common_name: &str,
+        expiration: DateTime<Utc>,
+    ) -> impl Future<Item = CertificateResponse, Error = Error> {
+        let client = self.client.clone();
+        let (api_version, module_id, generation_id) = self.ids();
+        let common_name = common_name.to_string();
+        let expiration = expiration.to_rfc3339();
+        self.retry(move || {
+            client.workload_api().create_server_certificate(
+                &api_version,
+                &module_id,
+                &generation_id,
+                ServerCertificateRequest::new(common_name.clone(), expiration.clone()),
+            )
+        })
+    }
+
+    /// Signs `data` with HMAC-SHA256 with the key `key_id` of the module
+    /// identity, such as `primary`, and returns the digest.
+    pub fn sign(&self, key_id: &str, data: &[u8]) -> impl Future<Item = Vec<u8>, Error = Error> {
+        let client = self.client.clone();
+        let (api_version, module_id, generation_id) = self.ids();
+        let key_id = key_id.to_string();
+        let data = base64::encode(data);
+        self.retry(move || {
+            client.workload_api().sign(
+                &api_version,
+                &module_id,
+                &generation_id,
+                SignRequest::new(key_id.clone(), SignAlgorithm::HMACSHA256, data.clone()),
+            )
+        }).and_then(|response| base64::decode(response.digest()).map_err(Error::from))
+    }
+
+    /// Encrypts `plaintext` with the key of the module identity.
+    pub fn encrypt(
+        &self,
+        plaintext: &[u8],
+        initialization_vector: &[u8],
+    ) -> impl Future<Item = Vec<u8>, Error = Error> {
+        let client = self.client.clone();
+        let (api_version, module_id, generation_id) = self.ids();
+        let plaintext = base64::encode(plaintext);
+        let initialization_vector = base64::encode(initialization_vector);
+        self.retry(move || {
+            client.workload_api().encrypt(
+                &api_version,
+                &module_id,
+                &generation_id,
+                EncryptRequest::new(plaintext.clone(), initialization_vector.clone()),
+            )
+        }).and_then(|response| base64::decode(response.ciphertext()).map_err(Error::from))
+    }
+
+    /// Decrypts `ciphertext` that was encrypted with `encrypt`.
+    pub fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        initialization_vector: &[u8],
+    ) -> impl Future<Item = Vec<u8>, Error = Error> {
+        let client = self.client.clone();
+        let (api_version, module_id, generation_id) = self.ids();
+        let ciphertext = base64::encode(ciphertext);
+        let initialization_vector = base64::encode(initialization_vector);
+        self.retry(move || {
+            client.workload_api().decrypt(
+                &api_version,
+                &module_id,
+                &generation_id,
+                DecryptRequest::new(ciphertext.clone(), initialization_vector.clone()),
+            )
+        }).and_then(|response| base64::decode(response.plaintext()).map_err(Error::from))
+    }
+
+    /// Returns the PEM certificates modules should trust.
+    pub fn trust_bundle(&self) -> impl Future<Item = String, Error = Error> {
+        let client = self.client.clone();
+        let api_version = self.api_version.clone();
+        self.retry(move || client.workload_api().trust_bundle(&api_version))
+            .map(|response| response.certificate().clone())
+    }
+
+    fn ids(&self) -> (String, String, String) {
+        (
+            self.api_version.clone(),
+            self.module_id.clone(),
+            self.generation_id.clone(),
+        )
+    }
+
+    fn retry<A, F>(&self, mut action: A) -> impl Future<Item = F::Item, Error = Error>
+    where
+        A: FnMut() -> F,
+        F: Future<Error = ApiError<Value>>,
+    {
+        Retry::new(self.retry_policy, move |_attempt| action())
+            .with_condition(is_transient)
+            .map_err(Error::from)
+    }
+}
+
+fn env_var(key: &'static str) -> Result<String, Error> {
+    env::var(key).map_err(|_| Error::from(ErrorKind::MissingEnvVar(key)))
+}
+
+// Requests that didn't reach the daemon, or that failed because the daemon
+// is busy or couldn't reach a service it depends on, may succeed later.
+fn is_transient(error: &ApiError<Value>) -> bool {
+    match *error {
+        ApiError::Hyper(_) => true,
+        ApiError::Serde(_) => false,
+        ApiError::Api(ref error) => match error.code {
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS => true,
+            _ => error
+                .content
+                .as_ref()
+                .and_then(|content| content["category"].as_str())
+                .map_or(false, |category| category == TRANSIENT_NETWORK),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Map;
+    use workload::apis::ApiError as ApiResponseError;
+
+    use super::*;
+
+    fn api_error(code: StatusCode, content: Option<Value>) -> ApiError<Value> {
+        ApiError::Api(ApiResponseError { code, content })
+    }
+
+    #[test]
+    fn unavailable_daemon_is_transient() {
+        assert!(is_transient(&api_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            None
+        )));
+        assert!(is_transient(&api_error(StatusCode::BAD_GATEWAY, None)));
+        let content = json_error("IoT Hub is unreachable", TRANSIENT_NETWORK);
+        assert!(is_transient(&api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Some(content)
+        )));
+    }
+
+    #[test]
+    fn rejected_request_is_not_transient() {
+        let content = json_error("Bad parameter", "user-config");
+        assert!(!is_transient(&api_error(
+            StatusCode::BAD_REQUEST,
+            Some(content)
+        )));
+        assert!(!is_transient(&api_error(StatusCode::NOT_FOUND, None)));
+        assert!(!is_transient(&api_error(
+            StatusCode::INSUFFICIENT_STORAGE,
+            None
+        )));
+    }
+
+    #[test]
+    fn api_error_keeps_status_and_message() {
+        let content = json_error("Module not found", "user-config");
+        let error = Error::from(api_error(StatusCode::NOT_FOUND, Some(content)));
+        assert_eq!(Some(StatusCode::NOT_FOUND), error.status());
+        assert_eq!(
+            &ErrorKind::Api(StatusCode::NOT_FOUND, "Module not found".to_string()),
+            error.kind()
+        );
+    }
+
+    #[test]
+    fn client_is_created_for_unix_socket() {
+        let url = Url::parse("unix:///var/run/iotedge/workload.sock").unwrap();
+        let client = WorkloadClient::new(&url, "sensor", "1").unwrap();
+        assert_eq!("sensor", client.module_id());
+        assert_eq!("1", client.generation_id());
+        assert_eq!(API_VERSION, client.api_version);
+    }
+
+    fn json_error(message: &str, category: &str) -> Value {
+        let mut content = Map::new();
+        content.insert("message".to_string(), Value::from(message));
+        content.insert("category".to_string(), Value::from(category));
+        Value::Object(content)
+    }
+}
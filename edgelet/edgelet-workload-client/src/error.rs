@@ -0,0 +1,124 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fmt::{self, Display};
+
+use base64::DecodeError;
+use edgelet_http::Error as HttpError;
+use failure::{Backtrace, Context, Fail};
+use hyper::StatusCode;
+use serde_json::Value;
+use url::ParseError;
+use workload::apis::Error as ApiError;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[derive(Clone, Debug, Fail, PartialEq)]
+pub enum ErrorKind {
+    #[fail(display = "Could not create a client for the workload API")]
+    Client,
+    #[fail(display = "The environment variable {} is not set", _0)]
+    MissingEnvVar(&'static str),
+    #[fail(display = "Invalid workload API URI")]
+    UrlParse,
+    #[fail(display = "Could not reach the workload API")]
+    Connect,
+    #[fail(display = "The workload API failed with {}: {}", _0, _1)]
+    Api(StatusCode, String),
+    #[fail(display = "Could not parse the response of the workload API")]
+    Serde,
+    #[fail(display = "The workload API responded with invalid base64")]
+    Base64,
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&Fail> {
+        self.inner.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        self.inner.get_context()
+    }
+
+    /// The status the workload API responded with, if it did.
+    pub fn status(&self) -> Option<StatusCode> {
+        match *self.kind() {
+            ErrorKind::Api(status, _) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            inner: Context::new(kind),
+        }
+    }
+}
+
+impl From<Context<ErrorKind>> for Error {
+    fn from(inner: Context<ErrorKind>) -> Self {
+        Error { inner }
+    }
+}
+
+impl From<ApiError<Value>> for Error {
+    fn from(error: ApiError<Value>) -> Self {
+        match error {
+            ApiError::Hyper(error) => Error {
+                inner: error.context(ErrorKind::Connect),
+            },
+            ApiError::Serde(error) => Error {
+                inner: error.context(ErrorKind::Serde),
+            },
+            ApiError::Api(error) => {
+                let message = error
+                    .content
+                    .as_ref()
+                    .and_then(|content| content["message"].as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                Error::from(ErrorKind::Api(error.code, message))
+            }
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Base64),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::UrlParse),
+        }
+    }
+}
+
+impl From<HttpError> for Error {
+    fn from(error: HttpError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Client),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! A client for the workload API, for modules written in Rust.
+//!
+//! ```ignore
+//! let client = WorkloadClient::from_env()?;
+//! let digest = client.sign("primary", b"data");
+//! let certificate = client.server_certificate("sensor", Utc::now() + Duration::days(1));
+//! ```
+//!
+//! Requests are made as the module the client was created for, over a
+//! connection to the workload socket that is kept open between requests.
+//! Requests that fail because the daemon or a service it depends on is
+//! unavailable are retried. The futures use tokio timers for the delays
+//! between attempts, so they have to run on a tokio runtime.
+
+#![deny(unused_extern_crates, warnings)]
+// Remove this when clippy stops warning about old-style `allow()`,
+// which can only be silenced by enabling a feature and thus requires nightly
+//
+// Ref: https://github.com/rust-lang-nursery/rust-clippy/issues/3159#issuecomment-420530386
+#![allow(renamed_and_removed_lints)]
+#![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
+#![cfg_attr(feature = "cargo-clippy", allow(stutter, use_self))]
+
+extern crate base64;
+extern crate chrono;
+extern crate edgelet_core;
+extern crate edgelet_http;
+extern crate failure;
+#[macro_use]
+extern crate failure_derive;
+extern crate futures;
+extern crate hyper;
+extern crate serde_json;
+extern crate url;
+extern crate workload;
+
+mod client;
+mod error;
+
+pub use client::WorkloadClient;
+pub use error::{Error, ErrorKind};
+pub use workload::models::{CertificateResponse, PrivateKey, PrivateKeyType};
@@ -5,6 +5,7 @@ use std::convert::AsRef;
 use std::ffi::{CStr, CString, NulError};
 use std::ops::{Deref, Drop};
 use std::os::raw::{c_char, c_uchar, c_void};
+use std::ptr;
 use std::slice;
 use std::str;
 
@@ -20,6 +21,18 @@ pub enum CertificateType {
     Ca,
 }
 
+/// The key generated for a certificate, when it mustn't have the type of the
+/// key of its issuer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CertificateKeyType {
+    /// An RSA key of this length in bits, or of the default length of the
+    /// certificate type.
+    Rsa(Option<usize>),
+    /// An EC key on the curve with this OpenSSL short name, such as
+    /// `prime256v1`.
+    Ec(&'static str),
+}
+
 /// Common HSM functions for Edge
 /// create an instance of this to use the HSM common interfaces needed for Edge
 ///
@@ -30,11 +43,13 @@ pub enum CertificateType {
 /// - [`CreateCertificate`]
 /// - [`Encrypt`]
 /// - [`Decrypt`]
+/// - [`CreateCrl`]
 ///
 #[derive(Clone, Debug)]
 pub struct Crypto {
     handle: HSM_CLIENT_HANDLE,
     interface: HSM_CLIENT_CRYPTO_INTERFACE_TAG,
+    ext_interface: HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG,
 }
 
 // Handles don't have thread-affinity
@@ -64,12 +79,23 @@ impl Crypto {
             Err(ErrorKind::NullResponse)?
         }
         let interface = unsafe { *if_ptr };
+        // libraries don't have to implement the optional functions
+        let ext_if_ptr = unsafe { hsm_client_crypto_ext_interface() };
+        let ext_interface = if ext_if_ptr.is_null() {
+            HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG::default()
+        } else {
+            unsafe { *ext_if_ptr }
+        };
         if let Some(handle) = interface.hsm_client_crypto_create.map(|f| unsafe { f() }) {
             if handle.is_null() {
                 unsafe { hsm_client_crypto_deinit() };
                 Err(ErrorKind::NullResponse)?
             }
-            Ok(Crypto { handle, interface })
+            Ok(Crypto {
+                handle,
+                interface,
+                ext_interface,
+            })
         } else {
             unsafe { hsm_client_crypto_deinit() };
             Err(ErrorKind::NullResponse)?
@@ -217,6 +243,38 @@ fn make_certification_props(props: &CertificateProperties) -> Result<CERT_PROPS_
         }
     }
 
+    if let Some(key_type) = props.key_type {
+        let result = match key_type {
+            CertificateKeyType::Rsa(length) => unsafe {
+                match set_key_type(
+                    handle,
+                    CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_RSA,
+                    ptr::null(),
+                ) {
+                    0 => set_rsa_key_length(handle, length.unwrap_or(0)),
+                    result => result,
+                }
+            },
+            CertificateKeyType::Ec(curve) => {
+                let curve = CString::new(curve).map_err(|_| {
+                    unsafe { cert_properties_destroy(handle) };
+                    ErrorKind::CertProps
+                })?;
+                unsafe {
+                    set_key_type(
+                        handle,
+                        CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_EC,
+                        curve.as_ptr(),
+                    )
+                }
+            }
+        };
+        if result != 0 {
+            unsafe { cert_properties_destroy(handle) };
+            return Err(ErrorKind::CertProps)?;
+        }
+    }
+
     Ok(handle)
 }
 
@@ -254,6 +312,61 @@ impl CreateCertificate for Crypto {
             }).ok_or_else(|| ErrorKind::ToCStr)?;
         Ok(())
     }
+
+    fn renew_certificate(
+        &self,
+        properties: &CertificateProperties,
+    ) -> Result<HsmCertificate, Error> {
+        let if_fn = self
+            .ext_interface
+            .hsm_client_renew_certificate
+            .ok_or(ErrorKind::NoneFn)?;
+        let property_handle = make_certification_props(properties)?;
+        let cert_info_handle = unsafe { if_fn(self.handle, property_handle) };
+        unsafe { cert_properties_destroy(property_handle) };
+
+        if cert_info_handle.is_null() {
+            Err(ErrorKind::NullResponse)?
+        } else {
+            Ok(HsmCertificate { cert_info_handle })
+        }
+    }
+
+    fn sign_certificate_request(
+        &self,
+        csr: &str,
+        properties: &CertificateProperties,
+    ) -> Result<HsmCertificate, Error> {
+        let if_fn = self
+            .ext_interface
+            .hsm_client_sign_certificate_request
+            .ok_or(ErrorKind::NoneFn)?;
+        let c_csr = CString::new(csr).map_err(|_| ErrorKind::ToCStr)?;
+        let property_handle = make_certification_props(properties)?;
+        let cert_info_handle = unsafe { if_fn(self.handle, c_csr.as_ptr(), property_handle) };
+        unsafe { cert_properties_destroy(property_handle) };
+
+        if cert_info_handle.is_null() {
+            Err(ErrorKind::NullResponse)?
+        } else {
+            Ok(HsmCertificate { cert_info_handle })
+        }
+    }
+
+    fn get_certificate(&self, alias: &str) -> Result<HsmCertificate, Error> {
+        let if_fn = self
+            .ext_interface
+            .hsm_client_crypto_get_certificate
+            .ok_or(ErrorKind::NoneFn)?;
+        let c_alias = CString::new(alias).map_err(|_| ErrorKind::ToCStr)?;
+        let cert_info_handle = unsafe { if_fn(self.handle, c_alias.as_ptr()) };
+
+        if cert_info_handle.is_null() {
+            Err(ErrorKind::NullResponse)?
+        } else {
+            Ok(HsmCertificate { cert_info_handle })
+        }
+    }
 }
 
 impl GetTrustBundle for Crypto {
@@ -359,6 +472,77 @@ impl Decrypt for Crypto {
     }
 }
 
+impl CreateCrl for Crypto {
+    fn create_crl(
+        &self,
+        issuer_alias: &str,
+        revoked: &[RevokedCertificate],
+        validity_in_secs: u64,
+    ) -> Result<Buffer, Error> {
+        let if_fn = self
+            .ext_interface
+            .hsm_client_create_crl
+            .ok_or(ErrorKind::NoneFn)?;
+
+        let c_issuer_alias = CString::new(issuer_alias).map_err(|_| ErrorKind::ToCStr)?;
+        let c_serial_numbers: Vec<CString> = revoked
+            .iter()
+            .map(|cert| CString::new(cert.serial_number()))
+            .collect::<Result<Vec<CString>, NulError>>()
+            .map_err(|_| ErrorKind::ToCStr)?;
+        let serial_number_ptrs: Vec<*const c_char> =
+            c_serial_numbers.iter().map(|s| s.as_ptr()).collect();
+        let revocation_times: Vec<i64> = revoked
+            .iter()
+            .map(|cert| cert.revoked_at().timestamp())
+            .collect();
+        let mut crl = SIZED_BUFFER {
+            buffer: std::ptr::null_mut() as *mut c_uchar,
+            size: 0,
+        };
+        let result = unsafe {
+            if_fn(
+                self.handle,
+                c_issuer_alias.as_ptr(),
+                serial_number_ptrs.as_ptr(),
+                revocation_times.as_ptr(),
+                revoked.len(),
+                validity_in_secs,
+                &mut crl,
+            )
+        };
+        match result {
+            0 => Ok(Buffer::new(self.interface, crl)),
+            r => Err(r)?,
+        }
+    }
+}
+
+/// A certificate to be listed in a certificate revocation list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RevokedCertificate {
+    serial_number: String,
+    revoked_at: DateTime<Utc>,
+}
+
+impl RevokedCertificate {
+    /// `serial_number` is the hex encoded serial number of the certificate.
+    pub fn new(serial_number: String, revoked_at: DateTime<Utc>) -> Self {
+        RevokedCertificate {
+            serial_number,
+            revoked_at,
+        }
+    }
+
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    pub fn revoked_at(&self) -> &DateTime<Utc> {
+        &self.revoked_at
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CertificateProperties {
     validity_in_secs: u64,
@@ -372,6 +556,7 @@ pub struct CertificateProperties {
     organization: Option<String>,
     organization_unit: Option<String>,
     san_entries: Vec<String>,
+    key_type: Option<CertificateKeyType>,
 }
 
 impl CertificateProperties {
@@ -395,6 +580,7 @@ impl CertificateProperties {
             organization: None,
             organization_unit: None,
             san_entries,
+            key_type: None,
         }
     }
 
@@ -496,6 +682,15 @@ impl CertificateProperties {
         self.san_entries = entries;
         self
     }
+
+    pub fn key_type(&self) -> Option<CertificateKeyType> {
+        self.key_type
+    }
+
+    pub fn with_key_type(mut self, key_type: CertificateKeyType) -> Self {
+        self.key_type = Some(key_type);
+        self
+    }
 }
 
 impl Default for CertificateProperties {
@@ -512,6 +707,7 @@ impl Default for CertificateProperties {
             organization: None,
             organization_unit: None,
             san_entries: vec![],
+            key_type: None,
         }
     }
 }
@@ -620,11 +816,16 @@ mod tests {
     use std::ffi::{CStr, CString};
     use std::os::raw::{c_char, c_int, c_uchar, c_void};
 
+    use chrono::{TimeZone, Utc};
+
     use super::super::{
-        CreateCertificate, CreateMasterEncryptionKey, Decrypt, DestroyMasterEncryptionKey, Encrypt,
-        GetTrustBundle, MakeRandom,
+        CreateCertificate, CreateCrl, CreateMasterEncryptionKey, Decrypt,
+        DestroyMasterEncryptionKey, Encrypt, GetTrustBundle, MakeRandom,
+    };
+    use super::{
+        make_certification_props, Buffer, CertificateKeyType, CertificateProperties, Crypto,
+        RevokedCertificate,
     };
-    use super::{Buffer, CertificateProperties, Crypto};
     use hsm_sys::*;
 
     static TEST_RSA_CERT: &str = "-----BEGIN CERTIFICATE-----\nMIICpDCCAYwCCQCgAJQdOd6dNzANBgkqhkiG9w0BAQsFADAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwHhcNMTcwMTIwMTkyNTMzWhcNMjcwMTE4MTkyNTMzWjAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwggEiMA0GCSqGSIb3DQEBAQUAA4IBDwAwggEKAoIBAQDlJ3fRNWm05BRAhgUY7cpzaxHZIORomZaOp2Uua5yv+psdkpv35ExLhKGrUIK1AJLZylnue0ohZfKPFTnoxMHOecnaaXZ9RA25M7XGQvw85ePlGOZKKf3zXw3Ds58GFY6Sr1SqtDopcDuMmDSg/afYVvGHDjb2Fc4hZFip350AADcmjH5SfWuxgptCY2Jl6ImJoOpxt+imWsJCJEmwZaXw+eZBb87e/9PH4DMXjIUFZebShowAfTh/sinfwRkaLVQ7uJI82Ka/icm6Hmr56j7U81gDaF0DhC03ds5lhN7nMp5aqaKeEJiSGdiyyHAescfxLO/SMunNc/eG7iAirY7BAgMBAAEwDQYJKoZIhvcNAQELBQADggEBACU7TRogb8sEbv+SGzxKSgWKKbw+FNgC4Zi6Fz59t+4jORZkoZ8W87NM946wvkIpxbLKuc4F+7nTGHHksyHIiGC3qPpi4vWpqVeNAP+kfQptFoWEOzxD7jQTWIcqYhvssKZGwDk06c/WtvVnhZOZW+zzJKXA7mbwJrfp8VekOnN5zPwrOCumDiRX7BnEtMjqFDgdMgs9ohR5aFsI7tsqp+dToLKaZqBLTvYwCgCJCxdg3QvMhVD8OxcEIFJtDEwm3h9WFFO3ocabCmcMDyXUL354yaZ7RphCBLd06XXdaUU/eV6fOjY6T5ka4ZRJcYDJtjxSG04XPtxswQfrPGGoFhk=\n-----END CERTIFICATE-----";
@@ -813,6 +1014,55 @@ mod tests {
         let input_sans = vec![String::from("aa"), String::from("bb")];
         let props = CertificateProperties::default().with_san_entries(input_sans.clone());
         assert_eq!(&*input_sans, props.san_entries());
+        assert_eq!(None, props.key_type());
+
+        let props = props.with_key_type(CertificateKeyType::Ec("secp384r1"));
+        assert_eq!(Some(CertificateKeyType::Ec("secp384r1")), props.key_type());
+    }
+
+    #[test]
+    fn cert_props_key_type_get_set() {
+        let handle = make_certification_props(
+            &CertificateProperties::default().with_key_type(CertificateKeyType::Ec("prime256v1")),
+        ).unwrap();
+        unsafe {
+            assert_eq!(
+                CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_EC,
+                get_key_type(handle)
+            );
+            assert_eq!(
+                "prime256v1",
+                CStr::from_ptr(get_ec_curve_name(handle)).to_str().unwrap()
+            );
+            cert_properties_destroy(handle);
+        }
+
+        let handle = make_certification_props(&CertificateProperties::default()).unwrap();
+        unsafe {
+            assert_eq!(
+                CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_DEFAULT,
+                get_key_type(handle)
+            );
+            assert!(get_ec_curve_name(handle).is_null());
+            assert_eq!(0, get_rsa_key_length(handle));
+            cert_properties_destroy(handle);
+        }
+
+        let handle = make_certification_props(
+            &CertificateProperties::default().with_key_type(CertificateKeyType::Rsa(Some(3072))),
+        ).unwrap();
+        unsafe {
+            assert_eq!(
+                CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_RSA,
+                get_key_type(handle)
+            );
+            assert_eq!(3072, get_rsa_key_length(handle));
+            cert_properties_destroy(handle);
+        }
+
+        assert!(make_certification_props(
+            &CertificateProperties::default().with_key_type(CertificateKeyType::Rsa(Some(1024)))
+        ).is_err());
     }
 
     #[test]
@@ -949,6 +1199,40 @@ mod tests {
 
     unsafe extern "C" fn fake_destroy_cert(_handle: HSM_CLIENT_HANDLE, _alias: *const c_char) {}
 
+    unsafe extern "C" fn fake_sign_cert_request(
+        handle: HSM_CLIENT_HANDLE,
+        _csr: *const c_char,
+        certificate_props: CERT_PROPS_HANDLE,
+    ) -> CERT_INFO_HANDLE {
+        fake_create_cert(handle, certificate_props)
+    }
+
+    unsafe extern "C" fn fake_get_cert(
+        handle: HSM_CLIENT_HANDLE,
+        _alias: *const c_char,
+    ) -> CERT_INFO_HANDLE {
+        fake_create_cert(handle, ::std::ptr::null_mut())
+    }
+
+    unsafe extern "C" fn fake_create_crl(
+        handle: HSM_CLIENT_HANDLE,
+        _issuer_alias: *const c_char,
+        _serial_numbers: *const *const c_char,
+        _revocation_times: *const i64,
+        count: usize,
+        _validity: u64,
+        crl: *mut SIZED_BUFFER,
+    ) -> c_int {
+        let n = handle as isize;
+        if n == 0 {
+            (*crl).buffer = malloc(DEFAULT_BUF_LEN + count) as *mut c_uchar;
+            (*crl).size = DEFAULT_BUF_LEN + count;
+            0
+        } else {
+            1
+        }
+    }
+
     const DEFAULT_BUF_LEN: usize = 10;
 
     unsafe extern "C" fn fake_handle_destroy(_h: HSM_CLIENT_HANDLE) {}
@@ -961,6 +1245,7 @@ mod tests {
                 hsm_client_crypto_destroy: Some(fake_handle_destroy),
                 ..HSM_CLIENT_CRYPTO_INTERFACE::default()
             },
+            ext_interface: HSM_CLIENT_CRYPTO_EXT_INTERFACE::default(),
         }
     }
 
@@ -998,6 +1283,32 @@ mod tests {
         println!("You should never see this print {:?}", result);
     }
 
+    #[test]
+    #[should_panic(expected = "HSM API Not Implemented")]
+    fn no_renew_certificate_api_fail() {
+        let props = CertificateProperties::default();
+        let hsm_crypto = fake_no_if_hsm_crypto();
+        let result = hsm_crypto.renew_certificate(&props).unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
+    #[test]
+    #[should_panic(expected = "HSM API Not Implemented")]
+    fn no_sign_certificate_request_api_fail() {
+        let props = CertificateProperties::default();
+        let hsm_crypto = fake_no_if_hsm_crypto();
+        let result = hsm_crypto.sign_certificate_request("csr", &props).unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
+    #[test]
+    #[should_panic(expected = "HSM API Not Implemented")]
+    fn no_get_certificate_api_fail() {
+        let hsm_crypto = fake_no_if_hsm_crypto();
+        let result = hsm_crypto.get_certificate("alias").unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
     #[test]
     #[should_panic(expected = "HSM API Not Implemented")]
     fn no_trust_bundle_api_fail() {
@@ -1031,6 +1342,15 @@ mod tests {
             .unwrap();
         println!("You should never see this print {:?}", result);
     }
+
+    #[test]
+    #[should_panic(expected = "HSM API Not Implemented")]
+    fn no_create_crl_api_fail() {
+        let hsm_crypto = fake_no_if_hsm_crypto();
+        let result = hsm_crypto.create_crl("issuer", &[], 3600).unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
     fn fake_bad_hsm_crypto() -> Crypto {
         Crypto {
             handle: unsafe { fake_handle_create_bad() },
@@ -1047,6 +1367,12 @@ mod tests {
                 hsm_client_get_trust_bundle: Some(fake_trust_bundle),
                 hsm_client_free_buffer: Some(real_buffer_destroy),
             },
+            ext_interface: HSM_CLIENT_CRYPTO_EXT_INTERFACE {
+                hsm_client_create_crl: Some(fake_create_crl),
+                hsm_client_renew_certificate: Some(fake_create_cert),
+                hsm_client_sign_certificate_request: Some(fake_sign_cert_request),
+                hsm_client_crypto_get_certificate: Some(fake_get_cert),
+            },
         }
     }
 
@@ -1085,6 +1411,34 @@ mod tests {
         println!("You should never see this print {:?}", result);
     }
 
+    #[test]
+    #[should_panic(expected = "HSM API returned an invalid null response")]
+    fn hsm_renew_certificate_errors() {
+        let hsm_crypto = fake_bad_hsm_crypto();
+        let props = CertificateProperties::default();
+
+        let result = hsm_crypto.renew_certificate(&props).unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
+    #[test]
+    #[should_panic(expected = "HSM API returned an invalid null response")]
+    fn hsm_sign_certificate_request_errors() {
+        let hsm_crypto = fake_bad_hsm_crypto();
+        let props = CertificateProperties::default();
+
+        let result = hsm_crypto.sign_certificate_request("csr", &props).unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
+    #[test]
+    #[should_panic(expected = "HSM API returned an invalid null response")]
+    fn hsm_get_certificate_errors() {
+        let hsm_crypto = fake_bad_hsm_crypto();
+        let result = hsm_crypto.get_certificate("alias").unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
     #[test]
     #[should_panic(expected = "HSM API returned an invalid null response")]
     fn hsm_get_trust_bundle_errors() {
@@ -1112,6 +1466,14 @@ mod tests {
         println!("You should never see this print {:?}", result);
     }
 
+    #[test]
+    #[should_panic(expected = "HSM API failure occurred")]
+    fn hsm_create_crl_errors() {
+        let hsm_crypto = fake_bad_hsm_crypto();
+        let result = hsm_crypto.create_crl("issuer", &[], 3600).unwrap();
+        println!("You should never see this print {:?}", result);
+    }
+
     fn fake_good_hsm_crypto() -> Crypto {
         Crypto {
             handle: unsafe { fake_handle_create_good() },
@@ -1128,6 +1490,12 @@ mod tests {
                 hsm_client_get_trust_bundle: Some(fake_trust_bundle),
                 hsm_client_free_buffer: Some(real_buffer_destroy),
             },
+            ext_interface: HSM_CLIENT_CRYPTO_EXT_INTERFACE {
+                hsm_client_create_crl: Some(fake_create_crl),
+                hsm_client_renew_certificate: Some(fake_create_cert),
+                hsm_client_sign_certificate_request: Some(fake_sign_cert_request),
+                hsm_client_crypto_get_certificate: Some(fake_get_cert),
+            },
         }
     }
 
@@ -1145,6 +1513,9 @@ mod tests {
 
         let props = CertificateProperties::default();
         let _new_cert = hsm_crypto.create_certificate(&props).unwrap();
+        let _renewed_cert = hsm_crypto.renew_certificate(&props).unwrap();
+        let _signed_cert = hsm_crypto.sign_certificate_request("csr", &props).unwrap();
+        let _existing_cert = hsm_crypto.get_certificate("alias").unwrap();
 
         let crypt1 = hsm_crypto
             .encrypt(b"client_id", b"plaintext", b"init_vector")
@@ -1165,6 +1536,13 @@ mod tests {
 
         assert_eq!(plain1.len(), DEFAULT_BUF_LEN);
         assert_eq!(plain2.len(), DEFAULT_BUF_LEN);
+
+        let revoked = vec![
+            RevokedCertificate::new("1A2B".to_string(), Utc.timestamp(1_540_000_000, 0)),
+            RevokedCertificate::new("3C4D".to_string(), Utc.timestamp(1_540_000_100, 0)),
+        ];
+        let crl = hsm_crypto.create_crl("issuer", &revoked, 3600).unwrap();
+        assert_eq!(crl.len(), DEFAULT_BUF_LEN + 2);
     }
 
 }
@@ -23,6 +23,8 @@ extern crate chrono;
 extern crate failure;
 extern crate hsm_sys;
 
+use std::ffi::CStr;
+
 use hsm_sys::*;
 
 mod crypto;
@@ -31,7 +33,8 @@ pub mod tpm;
 mod x509;
 
 pub use crypto::{
-    Buffer, CertificateProperties, CertificateType, Crypto, HsmCertificate, KeyBytes, PrivateKey,
+    Buffer, CertificateKeyType, CertificateProperties, CertificateType, Crypto, HsmCertificate,
+    KeyBytes, PrivateKey, RevokedCertificate,
 };
 pub use error::{Error, ErrorKind};
 pub use tpm::{Tpm, TpmDigest, TpmKey};
@@ -79,6 +82,19 @@ pub trait CreateCertificate {
     ) -> Result<HsmCertificate, Error>;
 
     fn destroy_certificate(&self, alias: String) -> Result<(), Error>;
+
+    fn renew_certificate(
+        &self,
+        properties: &CertificateProperties,
+    ) -> Result<HsmCertificate, Error>;
+
+    fn sign_certificate_request(
+        &self,
+        csr: &str,
+        properties: &CertificateProperties,
+    ) -> Result<HsmCertificate, Error>;
+
+    fn get_certificate(&self, alias: &str) -> Result<HsmCertificate, Error>;
 }
 
 pub trait Encrypt {
@@ -102,3 +118,19 @@ pub trait Decrypt {
 pub trait GetTrustBundle {
     fn get_trust_bundle(&self) -> Result<HsmCertificate, Error>;
 }
+
+pub trait CreateCrl {
+    fn create_crl(
+        &self,
+        issuer_alias: &str,
+        revoked: &[RevokedCertificate],
+        validity_in_secs: u64,
+    ) -> Result<Buffer, Error>;
+}
+
+/// The version of libiothsm the daemon is linked against.
+pub fn version() -> String {
+    unsafe { CStr::from_ptr(hsm_get_version()) }
+        .to_string_lossy()
+        .into_owned()
+}
@@ -28,6 +28,13 @@ typedef enum CERTIFICATE_TYPE_TAG
     CERTIFICATE_TYPE_CA
 } CERTIFICATE_TYPE;
 
+typedef enum CERTIFICATE_KEY_TYPE_TAG
+{
+    CERTIFICATE_KEY_TYPE_DEFAULT = 0,
+    CERTIFICATE_KEY_TYPE_RSA,
+    CERTIFICATE_KEY_TYPE_EC
+} CERTIFICATE_KEY_TYPE;
+
 /**
 * @brief    Creates a certificate property handle to be used in set properties
 *           of a certificate
@@ -261,6 +268,58 @@ extern int set_san_entries
 */
 extern const char * const* get_san_entries(CERT_PROPS_HANDLE handle, size_t *num_entries);
 
+/**
+* @brief                  Sets the type of the key generated for the certificate. By default
+*                         the key has the type of the key of the issuer, or is an RSA key for
+*                         self signed certificates.
+*
+* @param handle           The CERT_PROPS_HANDLE that was created by the cert_properties_create call
+* @param key_type         The type of the key
+* @param ec_curve_name    The OpenSSL short name of the curve of an EC key, such as prime256v1.
+*                         It must be NULL for other types of keys.
+*
+* @return                 On success 0.  Non-zero on failure.
+*/
+extern int set_key_type(CERT_PROPS_HANDLE handle, CERTIFICATE_KEY_TYPE key_type, const char* ec_curve_name);
+
+/**
+* @brief                Gets the type of the key generated for the certificate
+*
+* @param handle         The CERT_PROPS_HANDLE that was created by the cert_properties_create call
+*
+* @return               The key type set on the certificate
+*/
+extern CERTIFICATE_KEY_TYPE get_key_type(CERT_PROPS_HANDLE handle);
+
+/**
+* @brief                Gets the curve of the EC key generated for the certificate
+*
+* @param handle         The CERT_PROPS_HANDLE that was created by the cert_properties_create call
+*
+* @return               The curve name set on the certificate, NULL if none was set
+*/
+extern const char* get_ec_curve_name(CERT_PROPS_HANDLE handle);
+
+/**
+* @brief                Sets the length in bits of the RSA key generated for the certificate
+*
+* @param handle         The CERT_PROPS_HANDLE that was created by the cert_properties_create call
+* @param key_length     The key length, 2048, 3072 or 4096, or 0 for the default length
+*                       of the certificate type
+*
+* @return                 On success 0.  Non-zero on failure.
+*/
+extern int set_rsa_key_length(CERT_PROPS_HANDLE handle, size_t key_length);
+
+/**
+* @brief                Gets the length in bits of the RSA key generated for the certificate
+*
+* @param handle         The CERT_PROPS_HANDLE that was created by the cert_properties_create call
+*
+* @return               The key length set on the certificate, 0 if none was set
+*/
+extern size_t get_rsa_key_length(CERT_PROPS_HANDLE handle);
+
 #ifdef __cplusplus
 }
 #endif /* __cplusplus */
@@ -6,10 +6,12 @@
 
 #ifdef __cplusplus
 #include <cstddef>
+#include <cstdint>
 #include <cstdlib>
 extern "C" {
 #else
 #include <stddef.h>
+#include <stdint.h>
 #include <stdlib.h>
 #endif /* __cplusplus */
 
@@ -259,6 +261,63 @@ typedef int (*HSM_CLIENT_DECRYPT_DATA)(HSM_CLIENT_HANDLE handle, const SIZED_BUF
 */
 typedef CERT_INFO_HANDLE (*HSM_CLIENT_GET_TRUST_BUNDLE)(HSM_CLIENT_HANDLE handle);
 
+/**
+* @brief    Generates a certificate revocation list signed by the certificate
+*           identified by the issuer alias.
+*
+* @param handle             A valid HSM client handle
+* @param issuer_alias       Alias of the CA certificate that issued the revoked certificates
+* @param serial_numbers     Hex encoded serial numbers of the revoked certificates
+* @param revocation_times   Revocation time of each certificate, in seconds since the epoch
+* @param count              Number of entries in serial_numbers and revocation_times
+* @param validity           Number of seconds until the next update of the list is due
+* @param[out] crl           The PEM encoded CRL. This function allocates memory for a buffer
+*                           which must be freed by a call to ::HSM_CLIENT_FREE_BUFFER.
+*
+* @return   Zero on success, nonzero otherwise
+*/
+typedef int (*HSM_CLIENT_CREATE_CRL)(HSM_CLIENT_HANDLE handle, const char* issuer_alias, const char* const* serial_numbers, const int64_t* revocation_times, size_t count, uint64_t validity, SIZED_BUFFER* crl);
+
+/**
+* @brief    Issues a new X.509 certificate for the alias of the supplied certificate
+*           properties with the private key the alias already has, instead of
+*           generating a new one. The certificate of the alias is replaced.
+*
+* @param handle       A valid HSM client handle
+* @param cert_props   Handle to certificate properties
+*
+* @return CERT_INFO_HANDLE -- Valid non NULL handle on success, NULL on error,
+*                             including when the alias has no certificate to renew
+*/
+typedef CERT_INFO_HANDLE (*HSM_CLIENT_RENEW_CERTIFICATE)(HSM_CLIENT_HANDLE handle, CERT_PROPS_HANDLE certificate_props);
+
+/**
+* @brief    Issues a new X.509 certificate for the public key of a PEM encoded
+*           certificate signing request. The issuer is the issuer alias of the
+*           supplied certificate properties. The HSM does not hold the private key
+*           of the certificate and does not store the certificate.
+*
+* @param handle       A valid HSM client handle
+* @param csr          PEM encoded certificate signing request, signed with its own key
+* @param cert_props   Handle to certificate properties
+*
+* @return CERT_INFO_HANDLE -- Valid non NULL handle on success, NULL on error.
+*                             The handle has no private key.
+*/
+typedef CERT_INFO_HANDLE (*HSM_CLIENT_SIGN_CERTIFICATE_REQUEST)(HSM_CLIENT_HANDLE handle, const char* csr, CERT_PROPS_HANDLE certificate_props);
+
+/**
+* @brief    Retrieves the X.509 certificate of an alias, as it was created or last
+*           renewed. Nothing is created if the alias has no certificate.
+*
+* @param handle   A valid HSM client handle
+* @param alias    Alias of the certificate
+*
+* @return CERT_INFO_HANDLE -- Valid non NULL handle on success, NULL on error,
+*                             including when the alias has no certificate
+*/
+typedef CERT_INFO_HANDLE (*HSM_CLIENT_CRYPTO_GET_CERTIFICATE)(HSM_CLIENT_HANDLE handle, const char* alias);
+
 typedef struct HSM_CLIENT_TPM_INTERFACE_TAG
 {
     HSM_CLIENT_CREATE hsm_client_tpm_create;
@@ -299,10 +358,31 @@ typedef struct HSM_CLIENT_CRYPTO_INTERFACE_TAG
     HSM_CLIENT_FREE_BUFFER hsm_client_free_buffer;
 } HSM_CLIENT_CRYPTO_INTERFACE;
 
+/**
+* Functions of the crypto interface that HSM libraries don't have to implement.
+* They are called with the handle created by ::hsm_client_crypto_interface and
+* any of them may be NULL.
+*/
+typedef struct HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG
+{
+    HSM_CLIENT_CREATE_CRL hsm_client_create_crl;
+    HSM_CLIENT_RENEW_CERTIFICATE hsm_client_renew_certificate;
+    HSM_CLIENT_SIGN_CERTIFICATE_REQUEST hsm_client_sign_certificate_request;
+    HSM_CLIENT_CRYPTO_GET_CERTIFICATE hsm_client_crypto_get_certificate;
+} HSM_CLIENT_CRYPTO_EXT_INTERFACE;
+
 extern const HSM_CLIENT_TPM_INTERFACE* hsm_client_tpm_interface();
 extern const HSM_CLIENT_X509_INTERFACE* hsm_client_x509_interface();
 extern const HSM_CLIENT_CRYPTO_INTERFACE* hsm_client_crypto_interface();
 
+/**
+* @brief    Retrieves the optional crypto functions. HSM libraries may leave this
+*           function out altogether, callers look it up when the library is loaded.
+*
+* @return   The interface, or NULL when the library implements none of its functions
+*/
+extern const HSM_CLIENT_CRYPTO_EXT_INTERFACE* hsm_client_crypto_ext_interface();
+
 extern int hsm_client_x509_init();
 extern void hsm_client_x509_deinit();
 extern int hsm_client_tpm_init();
@@ -7,6 +7,7 @@ const char* const ENV_DEVICE_CA_PATH = "IOTEDGE_DEVICE_CA_CERT";
 const char* const ENV_DEVICE_PK_PATH = "IOTEDGE_DEVICE_CA_PK";
 const char* const ENV_TRUSTED_CA_CERTS_PATH = "IOTEDGE_TRUSTED_CA_CERTS";
 const char* const ENV_TPM_SELECT = "IOTEDGE_USE_TPM_DEVICE";
+const char* const ENV_QUICKSTART_CA_VALIDITY = "IOTEDGE_QUICKSTART_CA_VALIDITY_SECS";
 
 /* HSM directory name under IOTEDGE_HOMEDIR */
 const char* const DEFAULT_EDGE_HOME_DIR_UNIX = "/var/lib/iotedge"; // note MacOS is included
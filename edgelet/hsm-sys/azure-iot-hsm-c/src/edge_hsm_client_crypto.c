@@ -284,6 +284,55 @@ static CERT_INFO_HANDLE edge_hsm_client_create_certificate(HSM_CLIENT_HANDLE han
     return result;
 }
 
+static CERT_INFO_HANDLE edge_hsm_client_renew_certificate(HSM_CLIENT_HANDLE handle, CERT_PROPS_HANDLE certificate_props)
+{
+    CERT_INFO_HANDLE result;
+    const char* alias;
+
+    if (!g_is_crypto_initialized)
+    {
+        LOG_ERROR("hsm_client_crypto_init not called");
+        result = NULL;
+    }
+    else if (handle == NULL)
+    {
+        LOG_ERROR("Invalid handle value specified");
+        result = NULL;
+    }
+    else if (certificate_props == NULL)
+    {
+        LOG_ERROR("Invalid certificate props value specified");
+        result = NULL;
+    }
+    else if ((alias = get_alias(certificate_props)) == NULL)
+    {
+        LOG_ERROR("Invalid certificate props alias value");
+        result = NULL;
+    }
+    else if (get_issuer_alias(certificate_props) == NULL)
+    {
+        LOG_ERROR("Invalid certificate props issuer alias value");
+        result = NULL;
+    }
+    else
+    {
+        EDGE_CRYPTO *edge_crypto = (EDGE_CRYPTO*)handle;
+        if (g_hsm_store_if->hsm_client_store_renew_pki_cert(edge_crypto->hsm_store_handle,
+                                                            certificate_props) != 0)
+        {
+            LOG_ERROR("Could not renew certificate in the store");
+            result = NULL;
+        }
+        else
+        {
+            result = g_hsm_store_if->hsm_client_store_get_pki_cert(edge_crypto->hsm_store_handle,
+                                                                   alias);
+        }
+    }
+
+    return result;
+}
+
 static CERT_INFO_HANDLE edge_hsm_client_get_trust_bundle(HSM_CLIENT_HANDLE handle)
 {
     CERT_INFO_HANDLE result;
@@ -522,6 +571,140 @@ static int edge_hsm_client_decrypt_data
     return result;
 }
 
+static int edge_hsm_client_create_crl
+(
+    HSM_CLIENT_HANDLE handle,
+    const char *issuer_alias,
+    const char * const *serial_numbers,
+    const int64_t *revocation_times,
+    size_t count,
+    uint64_t validity,
+    SIZED_BUFFER *crl
+)
+{
+    int result;
+
+    if (!g_is_crypto_initialized)
+    {
+        LOG_ERROR("hsm_client_crypto_init not called");
+        result = __FAILURE__;
+    }
+    else if (handle == NULL)
+    {
+        LOG_ERROR("Invalid handle value specified");
+        result = __FAILURE__;
+    }
+    else if (issuer_alias == NULL)
+    {
+        LOG_ERROR("Invalid issuer alias specified");
+        result = __FAILURE__;
+    }
+    else if (crl == NULL)
+    {
+        LOG_ERROR("Invalid output CRL buffer provided");
+        result = __FAILURE__;
+    }
+    else
+    {
+        EDGE_CRYPTO *edge_crypto = (EDGE_CRYPTO*)handle;
+        result = g_hsm_store_if->hsm_client_store_create_pki_crl(edge_crypto->hsm_store_handle,
+                                                                 issuer_alias,
+                                                                 serial_numbers,
+                                                                 revocation_times,
+                                                                 count,
+                                                                 validity,
+                                                                 &crl->buffer,
+                                                                 &crl->size);
+    }
+
+    return result;
+}
+
+static CERT_INFO_HANDLE edge_hsm_client_sign_certificate_request
+(
+    HSM_CLIENT_HANDLE handle,
+    const char* csr,
+    CERT_PROPS_HANDLE certificate_props
+)
+{
+    CERT_INFO_HANDLE result;
+
+    if (!g_is_crypto_initialized)
+    {
+        LOG_ERROR("hsm_client_crypto_init not called");
+        result = NULL;
+    }
+    else if (handle == NULL)
+    {
+        LOG_ERROR("Invalid handle value specified");
+        result = NULL;
+    }
+    else if ((csr == NULL) || (strlen(csr) == 0))
+    {
+        LOG_ERROR("Invalid certificate signing request value specified");
+        result = NULL;
+    }
+    else if (certificate_props == NULL)
+    {
+        LOG_ERROR("Invalid certificate props value specified");
+        result = NULL;
+    }
+    else if (get_issuer_alias(certificate_props) == NULL)
+    {
+        LOG_ERROR("Invalid certificate props issuer alias value");
+        result = NULL;
+    }
+    else
+    {
+        EDGE_CRYPTO *edge_crypto = (EDGE_CRYPTO*)handle;
+        result = g_hsm_store_if->hsm_client_store_sign_pki_cert_request(edge_crypto->hsm_store_handle,
+                                                                        certificate_props,
+                                                                        csr);
+        if (result == NULL)
+        {
+            LOG_ERROR("Could not sign certificate signing request in the store");
+        }
+    }
+
+    return result;
+}
+
+static CERT_INFO_HANDLE edge_hsm_client_crypto_get_certificate
+(
+    HSM_CLIENT_HANDLE handle,
+    const char* alias
+)
+{
+    CERT_INFO_HANDLE result;
+
+    if (!g_is_crypto_initialized)
+    {
+        LOG_ERROR("hsm_client_crypto_init not called");
+        result = NULL;
+    }
+    else if (handle == NULL)
+    {
+        LOG_ERROR("Invalid handle value specified");
+        result = NULL;
+    }
+    else if ((alias == NULL) || (strlen(alias) == 0))
+    {
+        LOG_ERROR("Invalid alias value specified");
+        result = NULL;
+    }
+    else
+    {
+        EDGE_CRYPTO *edge_crypto = (EDGE_CRYPTO*)handle;
+        result = g_hsm_store_if->hsm_client_store_get_pki_cert(edge_crypto->hsm_store_handle, alias);
+        if (result == NULL)
+        {
+            LOG_ERROR("Could not get certificate for alias %s from the store", alias);
+        }
+    }
+
+    return result;
+}
+
 static const HSM_CLIENT_CRYPTO_INTERFACE edge_hsm_crypto_interface =
 {
     edge_hsm_client_crypto_create,
@@ -537,7 +720,20 @@ static const HSM_CLIENT_CRYPTO_INTERFACE edge_hsm_crypto_interface =
     edge_hsm_crypto_free_buffer
 };
 
+static const HSM_CLIENT_CRYPTO_EXT_INTERFACE edge_hsm_crypto_ext_interface =
+{
+    edge_hsm_client_create_crl,
+    edge_hsm_client_renew_certificate,
+    edge_hsm_client_sign_certificate_request,
+    edge_hsm_client_crypto_get_certificate
+};
+
 const HSM_CLIENT_CRYPTO_INTERFACE* hsm_client_crypto_interface(void)
 {
     return &edge_hsm_crypto_interface;
 }
+
+const HSM_CLIENT_CRYPTO_EXT_INTERFACE* hsm_client_crypto_ext_interface(void)
+{
+    return &edge_hsm_crypto_ext_interface;
+}
@@ -1571,12 +1571,47 @@ static int load_if_cert_and_key_exist_by_alias
     return result;
 }
 
+/**
+ * The validity of the quick start owner and device CA certificates, which iotedged sets
+ * in seconds from its config file. CA_VALIDITY is used when it doesn't.
+ */
+static uint64_t get_quickstart_ca_validity(void)
+{
+    uint64_t result = CA_VALIDITY;
+    char *env_validity = NULL;
+
+    if (hsm_get_env(ENV_QUICKSTART_CA_VALIDITY, &env_validity) != 0)
+    {
+        LOG_ERROR("Could not lookup env variable %s", ENV_QUICKSTART_CA_VALIDITY);
+    }
+    else if ((env_validity != NULL) && (strlen(env_validity) != 0))
+    {
+        char *end = NULL;
+        unsigned long long validity = strtoull(env_validity, &end, 10);
+        if ((end == NULL) || (*end != 0) || (validity == 0) || (validity == ULLONG_MAX))
+        {
+            LOG_ERROR("Invalid validity %s in env variable %s. Using the default",
+                      env_validity, ENV_QUICKSTART_CA_VALIDITY);
+        }
+        else
+        {
+            result = (uint64_t)validity;
+        }
+    }
+    if (env_validity != NULL)
+    {
+        free(env_validity);
+    }
+
+    return result;
+}
+
 static int create_owner_ca_cert(void)
 {
     int result;
     CERT_PROPS_HANDLE ca_props;
     ca_props = create_ca_certificate_properties(OWNER_CA_COMMON_NAME,
-                                                CA_VALIDITY,
+                                                get_quickstart_ca_validity(),
                                                 OWNER_CA_ALIAS,
                                                 OWNER_CA_ALIAS,
                                                 CERTIFICATE_TYPE_CA);
@@ -1600,7 +1635,7 @@ static int create_device_ca_cert(void)
     int result;
     CERT_PROPS_HANDLE ca_props;
     ca_props = create_ca_certificate_properties(DEVICE_CA_COMMON_NAME,
-                                                CA_VALIDITY,
+                                                get_quickstart_ca_validity(),
                                                 hsm_get_device_ca_alias(),
                                                 OWNER_CA_ALIAS,
                                                 CERTIFICATE_TYPE_CA);
@@ -2648,6 +2683,258 @@ static int edge_hsm_client_store_remove_pki_trusted_cert
     return result;
 }
 
+static int edge_hsm_client_store_create_pki_crl
+(
+    HSM_CLIENT_STORE_HANDLE handle,
+    const char *issuer_alias,
+    const char * const *serial_numbers,
+    const int64_t *revocation_times,
+    size_t count,
+    uint64_t validity,
+    unsigned char **crl,
+    size_t *crl_size
+)
+{
+    int result;
+
+    if (handle == NULL)
+    {
+        LOG_ERROR("Invalid handle value");
+        result = __FAILURE__;
+    }
+    else if ((issuer_alias == NULL) || (strlen(issuer_alias) == 0))
+    {
+        LOG_ERROR("Invalid issuer alias value");
+        result = __FAILURE__;
+    }
+    else if ((count != 0) && ((serial_numbers == NULL) || (revocation_times == NULL)))
+    {
+        LOG_ERROR("Invalid revoked certificate values");
+        result = __FAILURE__;
+    }
+    else if ((crl == NULL) || (crl_size == NULL))
+    {
+        LOG_ERROR("Invalid output CRL buffer");
+        result = __FAILURE__;
+    }
+    else if (g_hsm_state != HSM_STATE_PROVISIONED)
+    {
+        LOG_ERROR("HSM store has not been provisioned");
+        result = __FAILURE__;
+    }
+    else
+    {
+        STORE_ENTRY_PKI_CERT *issuer_cert_entry;
+        const char *issuer_pk_path;
+        const char *issuer_cert_path;
+        if ((issuer_cert_entry = get_pki_cert((CRYPTO_STORE*)handle, issuer_alias)) == NULL)
+        {
+            LOG_ERROR("Could not get certificate entry for issuer %s", issuer_alias);
+            result = __FAILURE__;
+        }
+        else if (((issuer_cert_path = STRING_c_str(issuer_cert_entry->cert_file)) == NULL) ||
+                 ((issuer_pk_path = STRING_c_str(issuer_cert_entry->private_key_file)) == NULL))
+        {
+            LOG_ERROR("Unexpected NULL file paths found for issuer %s", issuer_alias);
+            result = __FAILURE__;
+        }
+        else if (generate_pki_crl(issuer_pk_path, issuer_cert_path, serial_numbers,
+                                  revocation_times, count, validity, crl, crl_size) != 0)
+        {
+            LOG_ERROR("Could not generate CRL for issuer %s", issuer_alias);
+            result = __FAILURE__;
+        }
+        else
+        {
+            result = 0;
+        }
+    }
+
+    return result;
+}
+
+static int edge_hsm_client_store_renew_pki_cert
+(
+    HSM_CLIENT_STORE_HANDLE handle,
+    CERT_PROPS_HANDLE cert_props_handle
+)
+{
+    int result;
+    const char* alias;
+    const char* issuer_alias;
+    STRING_HANDLE alias_cert_handle = NULL;
+    STRING_HANDLE alias_pk_handle = NULL;
+
+    if (handle == NULL)
+    {
+        LOG_ERROR("Invalid handle value");
+        result = __FAILURE__;
+    }
+    else if (cert_props_handle == NULL)
+    {
+        LOG_ERROR("Invalid certificate properties value");
+        result = __FAILURE__;
+    }
+    else if ((alias = get_alias(cert_props_handle)) == NULL)
+    {
+        LOG_ERROR("Invalid certificate alias value");
+        result = __FAILURE__;
+    }
+    else if ((issuer_alias = get_issuer_alias(cert_props_handle)) == NULL)
+    {
+        LOG_ERROR("Invalid certificate issuer alias value");
+        result = __FAILURE__;
+    }
+    else if (g_hsm_state != HSM_STATE_PROVISIONED)
+    {
+        LOG_ERROR("HSM store has not been provisioned");
+        result = __FAILURE__;
+    }
+    else if (((alias_cert_handle = STRING_new()) == NULL) ||
+             ((alias_pk_handle = STRING_new()) == NULL))
+    {
+        LOG_ERROR("Could not allocate string handles for storing certificate and key paths");
+        result = __FAILURE__;
+    }
+    else if (build_cert_file_paths(alias, alias_cert_handle, alias_pk_handle) != 0)
+    {
+        LOG_ERROR("Could not create file paths to the certificate and private key for alias %s", alias);
+        result = __FAILURE__;
+    }
+    else
+    {
+        CRYPTO_STORE *store = (CRYPTO_STORE*)handle;
+        const char *issuer_pk_path = NULL;
+        const char *issuer_cert_path = NULL;
+        const char *alias_pk_path = STRING_c_str(alias_pk_handle);
+        const char *alias_cert_path = STRING_c_str(alias_cert_handle);
+
+        result = 0;
+        if (!is_file_valid(alias_pk_path))
+        {
+            LOG_ERROR("There is no private key to renew the certificate of alias %s with", alias);
+            result = __FAILURE__;
+        }
+        else if (strcmp(alias, issuer_alias) != 0)
+        {
+            STORE_ENTRY_PKI_CERT *issuer_cert_entry;
+            if ((issuer_cert_entry = get_pki_cert(store, issuer_alias)) == NULL)
+            {
+                LOG_ERROR("Could not get certificate entry for issuer %s", issuer_alias);
+                result = __FAILURE__;
+            }
+            else if (((issuer_cert_path = STRING_c_str(issuer_cert_entry->cert_file)) == NULL) ||
+                     ((issuer_pk_path = STRING_c_str(issuer_cert_entry->private_key_file)) == NULL))
+            {
+                LOG_ERROR("Unexpected NULL file paths found for issuer %s", issuer_alias);
+                result = __FAILURE__;
+            }
+        }
+
+        if (result == 0)
+        {
+            // @note this overwrites the certificate file of the alias and keeps its
+            // private key file as it is
+            if (renew_pki_cert(cert_props_handle, rand(), alias_pk_path, alias_cert_path,
+                               issuer_pk_path, issuer_cert_path) != 0)
+            {
+                LOG_ERROR("Could not renew PKI certificate for %s", alias);
+                result = __FAILURE__;
+            }
+            else if (put_pki_cert(store, alias, issuer_alias, alias_cert_path, alias_pk_path) != 0)
+            {
+                LOG_ERROR("Could not put PKI certificate and key into the store for %s", alias);
+                result = __FAILURE__;
+            }
+        }
+    }
+
+    if (alias_cert_handle != NULL)
+    {
+        STRING_delete(alias_cert_handle);
+    }
+    if (alias_pk_handle != NULL)
+    {
+        STRING_delete(alias_pk_handle);
+    }
+
+    return result;
+}
+
+static CERT_INFO_HANDLE edge_hsm_client_store_sign_pki_cert_request
+(
+    HSM_CLIENT_STORE_HANDLE handle,
+    CERT_PROPS_HANDLE cert_props_handle,
+    const char* csr
+)
+{
+    CERT_INFO_HANDLE result;
+    const char* issuer_alias;
+
+    if (handle == NULL)
+    {
+        LOG_ERROR("Invalid handle value");
+        result = NULL;
+    }
+    else if (cert_props_handle == NULL)
+    {
+        LOG_ERROR("Invalid certificate properties value");
+        result = NULL;
+    }
+    else if ((csr == NULL) || (strlen(csr) == 0))
+    {
+        LOG_ERROR("Invalid certificate signing request value");
+        result = NULL;
+    }
+    else if ((issuer_alias = get_issuer_alias(cert_props_handle)) == NULL)
+    {
+        LOG_ERROR("Invalid certificate issuer alias value");
+        result = NULL;
+    }
+    else if (g_hsm_state != HSM_STATE_PROVISIONED)
+    {
+        LOG_ERROR("HSM store has not been provisioned");
+        result = NULL;
+    }
+    else
+    {
+        STORE_ENTRY_PKI_CERT *issuer_cert_entry;
+        const char *issuer_pk_path;
+        const char *issuer_cert_path;
+        char *cert = NULL;
+        if ((issuer_cert_entry = get_pki_cert((CRYPTO_STORE*)handle, issuer_alias)) == NULL)
+        {
+            LOG_ERROR("Could not get certificate entry for issuer %s", issuer_alias);
+            result = NULL;
+        }
+        else if (((issuer_cert_path = STRING_c_str(issuer_cert_entry->cert_file)) == NULL) ||
+                 ((issuer_pk_path = STRING_c_str(issuer_cert_entry->private_key_file)) == NULL))
+        {
+            LOG_ERROR("Unexpected NULL file paths found for issuer %s", issuer_alias);
+            result = NULL;
+        }
+        // @note the signed certificate is returned to the caller and never written to
+        // the store since the HSM doesn't hold its private key
+        else if (sign_pki_cert_request(cert_props_handle, rand(), csr,
+                                       issuer_pk_path, issuer_cert_path, &cert) != 0)
+        {
+            LOG_ERROR("Could not sign certificate signing request with issuer %s", issuer_alias);
+            result = NULL;
+        }
+        else if ((result = certificate_info_create(cert, NULL, 0, PRIVATE_KEY_UNKNOWN)) == NULL)
+        {
+            LOG_ERROR("Could not create certificate info handle for the signed certificate");
+        }
+
+        if (cert != NULL)
+        {
+            free(cert);
+        }
+    }
+
+    return result;
+}
+
 static int edge_hsm_client_store_insert_encryption_key
 (
     HSM_CLIENT_STORE_HANDLE handle,
@@ -2719,7 +3006,10 @@ static const HSM_CLIENT_STORE_INTERFACE edge_hsm_client_store_interface =
     edge_hsm_client_store_remove_pki_cert,
     edge_hsm_client_store_insert_pki_trusted_cert,
     edge_hsm_client_store_get_pki_trusted_certs,
-    edge_hsm_client_store_remove_pki_trusted_cert
+    edge_hsm_client_store_remove_pki_trusted_cert,
+    edge_hsm_client_store_create_pki_crl,
+    edge_hsm_client_store_renew_pki_cert,
+    edge_hsm_client_store_sign_pki_cert_request
 };
 
 const HSM_CLIENT_STORE_INTERFACE* hsm_client_store_interface(void)
@@ -16,6 +16,7 @@
 
 #include <openssl/asn1.h>
 #include <openssl/bio.h>
+#include <openssl/bn.h>
 #include <openssl/err.h>
 #include <openssl/ec.h>
 #include <openssl/pem.h>
@@ -211,14 +212,18 @@ static void cert_key_destroy(KEY_HANDLE key_handle)
 //#################################################################################################
 // PKI key generation
 //#################################################################################################
-static EVP_PKEY* generate_rsa_key(CERTIFICATE_TYPE cert_type)
+static EVP_PKEY* generate_rsa_key(CERTIFICATE_TYPE cert_type, size_t requested_key_len)
 {
     int status;
     BIGNUM *bne;
     EVP_PKEY *pkey;
     RSA *rsa;
 
-    size_t key_len = (cert_type == CERTIFICATE_TYPE_CA) ? RSA_KEY_LEN_CA : RSA_KEY_LEN_NON_CA;
+    size_t key_len = requested_key_len;
+    if (key_len == 0)
+    {
+        key_len = (cert_type == CERTIFICATE_TYPE_CA) ? RSA_KEY_LEN_CA : RSA_KEY_LEN_NON_CA;
+    }
     LOG_INFO("Generating RSA key of length %zu", key_len);
     if ((pkey = EVP_PKEY_new()) == NULL)
     {
@@ -314,7 +319,8 @@ static EVP_PKEY* generate_evp_key
 {
     EVP_PKEY *evp_key;
 
-    if (issuer_cert == NULL)
+    // a key type that was asked for takes precedence over that of the issuer
+    if ((issuer_cert == NULL) || (key_props != NULL))
     {
         if ((key_props != NULL) && (key_props->key_type == HSM_PKI_KEY_EC))
         {
@@ -325,7 +331,8 @@ static EVP_PKEY* generate_evp_key
         else
         {
             // by default use RSA keys if no issuer cert or key properties was provided
-            evp_key = generate_rsa_key(cert_type);
+            size_t key_len = (key_props != NULL) ? key_props->rsa_key_len : 0;
+            evp_key = generate_rsa_key(cert_type, key_len);
         }
     }
     else
@@ -345,7 +352,7 @@ static EVP_PKEY* generate_evp_key
             {
                 case EVP_PKEY_RSA:
                 {
-                    evp_key = generate_rsa_key(cert_type);
+                    evp_key = generate_rsa_key(cert_type, 0);
                 }
                 break;
 
@@ -1157,7 +1164,8 @@ static int generate_evp_certificate
                 LOG_ERROR("Failure signing x509");
                 result = __FAILURE__;
             }
-            else if (write_certificate_file(x509_cert, cert_file_name, issuer_certificate_file) != 0)
+            else if ((cert_file_name != NULL) &&
+                     (write_certificate_file(x509_cert, cert_file_name, issuer_certificate_file) != 0))
             {
                 LOG_ERROR("Failure saving x509 certificate");
                 result = __FAILURE__;
@@ -1176,6 +1184,43 @@ static int generate_evp_certificate
     return result;
 }
 
+// Gets the key type set on the certificate properties into key_props,
+// returning NULL if the key type of the issuer is to be used
+static const PKI_KEY_PROPS* get_requested_key_props
+(
+    CERT_PROPS_HANDLE cert_props_handle,
+    PKI_KEY_PROPS *key_props
+)
+{
+    const PKI_KEY_PROPS *result;
+
+    switch (get_key_type(cert_props_handle))
+    {
+        case CERTIFICATE_KEY_TYPE_RSA:
+        {
+            key_props->key_type = HSM_PKI_KEY_RSA;
+            key_props->ec_curve_name = NULL;
+            key_props->rsa_key_len = get_rsa_key_length(cert_props_handle);
+            result = key_props;
+        }
+        break;
+
+        case CERTIFICATE_KEY_TYPE_EC:
+        {
+            key_props->key_type = HSM_PKI_KEY_EC;
+            key_props->ec_curve_name = get_ec_curve_name(cert_props_handle);
+            key_props->rsa_key_len = 0;
+            result = key_props;
+        }
+        break;
+
+        default:
+            result = NULL;
+    }
+
+    return result;
+}
+
 static int generate_pki_cert_and_key_helper
 (
     CERT_PROPS_HANDLE cert_props_handle,
@@ -1185,7 +1230,8 @@ static int generate_pki_cert_and_key_helper
     const char* cert_file_name,
     const char* issuer_key_file,
     const char* issuer_certificate_file,
-    const PKI_KEY_PROPS *key_props
+    const PKI_KEY_PROPS *key_props,
+    bool reuse_key
 )
 {
     int result;
@@ -1259,6 +1305,11 @@ static int generate_pki_cert_and_key_helper
         else
         {
             bool perform_cert_gen;
+            PKI_KEY_PROPS requested_key_props;
+            if (key_props == NULL)
+            {
+                key_props = get_requested_key_props(cert_props_handle, &requested_key_props);
+            }
             if (issuer_certificate_file)
             {
                 if ((issuer_certificate = load_certificate_file(issuer_certificate_file)) == NULL)
@@ -1289,7 +1340,13 @@ static int generate_pki_cert_and_key_helper
             {
                 X509* x509_cert = NULL;
                 EVP_PKEY* evp_key = NULL;
-                if (generate_cert_key(cert_type, issuer_certificate, key_file_name, &evp_key, key_props) != 0)
+                if (reuse_key && ((evp_key = load_private_key_file(key_file_name)) == NULL))
+                {
+                    LOG_ERROR("Could not load private key for certificate renew request");
+                    result = __FAILURE__;
+                }
+                else if (!reuse_key &&
+                         (generate_cert_key(cert_type, issuer_certificate, key_file_name, &evp_key, key_props) != 0))
                 {
                     LOG_ERROR("Could not generate private key for certificate create request");
                     result = __FAILURE__;
@@ -1359,7 +1416,8 @@ int generate_pki_cert_and_key_with_props
                                                   cert_file_name,
                                                   NULL,
                                                   NULL,
-                                                  key_props);
+                                                  key_props,
+                                                  false);
     }
 
     return result;
@@ -1383,7 +1441,226 @@ int generate_pki_cert_and_key
                                             cert_file_name,
                                             issuer_key_file,
                                             issuer_certificate_file,
-                                            NULL);
+                                            NULL,
+                                            false);
+}
+
+int renew_pki_cert
+(
+    CERT_PROPS_HANDLE cert_props_handle,
+    int serial_number,
+    const char* key_file_name,
+    const char* cert_file_name,
+    const char* issuer_key_file,
+    const char* issuer_certificate_file
+)
+{
+    // only leaf certificates are renewed, which have no CA path length
+    return generate_pki_cert_and_key_helper(cert_props_handle,
+                                            serial_number,
+                                            0,
+                                            key_file_name,
+                                            cert_file_name,
+                                            issuer_key_file,
+                                            issuer_certificate_file,
+                                            NULL,
+                                            true);
+}
+
+static EVP_PKEY* load_cert_request_public_key(const char *csr)
+{
+    EVP_PKEY *result = NULL;
+    X509_REQ *request = NULL;
+    BIO *csr_bio;
+
+    if ((csr_bio = BIO_new_mem_buf((void*)csr, -1)) == NULL)
+    {
+        LOG_ERROR("Could not allocate BIO for the certificate signing request");
+    }
+    else if ((request = PEM_read_bio_X509_REQ(csr_bio, NULL, NULL, NULL)) == NULL)
+    {
+        LOG_ERROR("Could not parse the certificate signing request");
+    }
+    else if ((result = X509_REQ_get_pubkey(request)) == NULL)
+    {
+        LOG_ERROR("Could not get the public key of the certificate signing request");
+    }
+    else if (X509_REQ_verify(request, result) != 1)
+    {
+        LOG_ERROR("The certificate signing request is not signed with its own key");
+        destroy_evp_key(result);
+        result = NULL;
+    }
+
+    if (request != NULL)
+    {
+        X509_REQ_free(request);
+    }
+    if (csr_bio != NULL)
+    {
+        BIO_free_all(csr_bio);
+    }
+
+    return result;
+}
+
+static char* cert_chain_to_pem(X509 *x509_cert, const char *issuer_certificate_file)
+{
+    char *result = NULL;
+    BIO *cert_bio;
+
+    if ((cert_bio = BIO_new(BIO_s_mem())) == NULL)
+    {
+        LOG_ERROR("Could not allocate BIO for the certificate");
+    }
+    else
+    {
+        char *data;
+        long data_len;
+        if (!PEM_write_bio_X509(cert_bio, x509_cert))
+        {
+            LOG_ERROR("Unable to write certificate to memory");
+        }
+        else if (bio_chain_cert_helper(cert_bio, issuer_certificate_file) != 0)
+        {
+            LOG_ERROR("Could not append the issuer certificate chain");
+        }
+        else if ((data_len = BIO_get_mem_data(cert_bio, &data)) <= 0)
+        {
+            LOG_ERROR("Could not get the certificate from memory");
+        }
+        else if ((result = (char*)malloc((size_t)data_len + 1)) == NULL)
+        {
+            LOG_ERROR("Could not allocate memory for the certificate");
+        }
+        else
+        {
+            memcpy(result, data, (size_t)data_len);
+            result[data_len] = 0;
+        }
+        BIO_free_all(cert_bio);
+    }
+
+    return result;
+}
+
+int sign_pki_cert_request
+(
+    CERT_PROPS_HANDLE cert_props_handle,
+    int serial_number,
+    const char* csr,
+    const char* issuer_key_file,
+    const char* issuer_certificate_file,
+    char** cert
+)
+{
+    int result;
+    uint64_t requested_validity;
+    const char* common_name_prop_value;
+    CERTIFICATE_TYPE cert_type;
+
+    initialize_openssl();
+    if (cert != NULL)
+    {
+        *cert = NULL;
+    }
+
+    if ((cert_props_handle == NULL) || (cert == NULL))
+    {
+        LOG_ERROR("Invalid certificate properties or output certificate");
+        result = __FAILURE__;
+    }
+    else if (csr == NULL)
+    {
+        LOG_ERROR("Invalid certificate signing request");
+        result = __FAILURE__;
+    }
+    else if ((issuer_key_file == NULL) || (issuer_certificate_file == NULL))
+    {
+        LOG_ERROR("Invalid issuer certificate and key file provided");
+        result = __FAILURE__;
+    }
+    else if ((requested_validity = get_validity_seconds(cert_props_handle)) == 0)
+    {
+        LOG_ERROR("Validity in seconds cannot be 0");
+        result = __FAILURE__;
+    }
+    else if (requested_validity > LONG_MAX)
+    {
+        LOG_ERROR("Number of seconds too large %" PRIu64, requested_validity);
+        result = __FAILURE__;
+    }
+    else if (((common_name_prop_value = get_common_name(cert_props_handle)) == NULL) ||
+             (strlen(common_name_prop_value) == 0))
+    {
+        LOG_ERROR("Common name value cannot be NULL or empty");
+        result = __FAILURE__;
+    }
+    else if (((cert_type = get_certificate_type(cert_props_handle)) != CERTIFICATE_TYPE_CLIENT) &&
+             (cert_type != CERTIFICATE_TYPE_SERVER))
+    {
+        // the HSM doesn't issue CA certificates for keys it doesn't hold
+        LOG_ERROR("Error invalid certificate type %d for a certificate signing request", cert_type);
+        result = __FAILURE__;
+    }
+    else
+    {
+        X509* issuer_certificate = NULL;
+        EVP_PKEY* issuer_evp_key = NULL;
+        EVP_PKEY* evp_key = NULL;
+        X509* x509_cert = NULL;
+
+        if ((issuer_certificate = load_certificate_file(issuer_certificate_file)) == NULL)
+        {
+            LOG_ERROR("Could not load issuer certificate file");
+            result = __FAILURE__;
+        }
+        else if ((issuer_evp_key = load_private_key_file(issuer_key_file)) == NULL)
+        {
+            LOG_ERROR("Could not load issuer private key file");
+            result = __FAILURE__;
+        }
+        else if ((evp_key = load_cert_request_public_key(csr)) == NULL)
+        {
+            LOG_ERROR("Could not load the public key of the certificate signing request");
+            result = __FAILURE__;
+        }
+        else if (generate_evp_certificate(evp_key, cert_type, common_name_prop_value, requested_validity,
+                                          issuer_evp_key, issuer_certificate, issuer_certificate_file,
+                                          cert_props_handle, serial_number, 0, NULL, &x509_cert) != 0)
+        {
+            LOG_ERROR("Could not generate certificate for the certificate signing request");
+            result = __FAILURE__;
+        }
+        else if ((*cert = cert_chain_to_pem(x509_cert, issuer_certificate_file)) == NULL)
+        {
+            LOG_ERROR("Could not encode the certificate for the certificate signing request");
+            result = __FAILURE__;
+        }
+        else
+        {
+            result = 0;
+        }
+
+        if (x509_cert != NULL)
+        {
+            X509_free(x509_cert);
+        }
+        if (evp_key != NULL)
+        {
+            destroy_evp_key(evp_key);
+        }
+        if (issuer_evp_key != NULL)
+        {
+            destroy_evp_key(issuer_evp_key);
+        }
+        if (issuer_certificate != NULL)
+        {
+            X509_free(issuer_certificate);
+        }
+    }
+
+    return result;
 }
 
 KEY_HANDLE create_cert_key(const char* key_file_name)
@@ -1646,3 +1923,235 @@ int verify_certificate
 
     return result;
 }
+
+static int crl_add_revoked_cert(X509_CRL *crl, const char *serial_number, int64_t revocation_time)
+{
+    int result;
+    BIGNUM *serial_bn = NULL;
+    ASN1_INTEGER *serial = NULL;
+    ASN1_TIME *revoked_at = NULL;
+    X509_REVOKED *revoked;
+
+    if ((revoked = X509_REVOKED_new()) == NULL)
+    {
+        LOG_ERROR("Could not allocate revoked certificate entry");
+        result = __FAILURE__;
+    }
+    else if ((BN_hex2bn(&serial_bn, serial_number) == 0) ||
+             ((serial = BN_to_ASN1_INTEGER(serial_bn, NULL)) == NULL))
+    {
+        LOG_ERROR("Invalid serial number %s", serial_number);
+        result = __FAILURE__;
+    }
+    else if ((revoked_at = ASN1_TIME_set(NULL, (time_t)revocation_time)) == NULL)
+    {
+        LOG_ERROR("Could not set revocation time for serial number %s", serial_number);
+        result = __FAILURE__;
+    }
+    else if (!X509_REVOKED_set_serialNumber(revoked, serial) ||
+             !X509_REVOKED_set_revocationDate(revoked, revoked_at))
+    {
+        LOG_ERROR("Could not set revoked certificate entry for serial number %s", serial_number);
+        result = __FAILURE__;
+    }
+    else if (!X509_CRL_add0_revoked(crl, revoked))
+    {
+        LOG_ERROR("Could not add revoked certificate entry for serial number %s", serial_number);
+        result = __FAILURE__;
+    }
+    else
+    {
+        // the CRL now owns the revoked entry
+        revoked = NULL;
+        result = 0;
+    }
+
+    if (revoked != NULL)
+    {
+        X509_REVOKED_free(revoked);
+    }
+    if (revoked_at != NULL)
+    {
+        ASN1_TIME_free(revoked_at);
+    }
+    if (serial != NULL)
+    {
+        ASN1_INTEGER_free(serial);
+    }
+    if (serial_bn != NULL)
+    {
+        BN_free(serial_bn);
+    }
+
+    return result;
+}
+
+static int crl_to_pem(X509_CRL *crl, unsigned char **pem, size_t *pem_size)
+{
+    int result;
+    BIO *mem_bio;
+    BUF_MEM *mem_buf = NULL;
+
+    if ((mem_bio = BIO_new(BIO_s_mem())) == NULL)
+    {
+        LOG_ERROR("Could not allocate memory BIO for CRL");
+        result = __FAILURE__;
+    }
+    else
+    {
+        if (!PEM_write_bio_X509_CRL(mem_bio, crl))
+        {
+            LOG_ERROR("Could not PEM encode CRL");
+            result = __FAILURE__;
+        }
+        else if ((BIO_get_mem_ptr(mem_bio, &mem_buf) <= 0) || (mem_buf == NULL))
+        {
+            LOG_ERROR("Could not read PEM encoded CRL");
+            result = __FAILURE__;
+        }
+        else if ((*pem = (unsigned char*)malloc(mem_buf->length)) == NULL)
+        {
+            LOG_ERROR("Could not allocate memory for CRL");
+            result = __FAILURE__;
+        }
+        else
+        {
+            memcpy(*pem, mem_buf->data, mem_buf->length);
+            *pem_size = mem_buf->length;
+            result = 0;
+        }
+        BIO_free_all(mem_bio);
+    }
+
+    return result;
+}
+
+int generate_pki_crl
+(
+    const char *issuer_key_file,
+    const char *issuer_certificate_file,
+    const char * const *serial_numbers,
+    const int64_t *revocation_times,
+    size_t count,
+    uint64_t validity,
+    unsigned char **crl,
+    size_t *crl_size
+)
+{
+    int result;
+
+    if ((issuer_key_file == NULL) || (issuer_certificate_file == NULL))
+    {
+        LOG_ERROR("Invalid issuer certificate and key files");
+        result = __FAILURE__;
+    }
+    else if ((count != 0) && ((serial_numbers == NULL) || (revocation_times == NULL)))
+    {
+        LOG_ERROR("Invalid revoked certificate values");
+        result = __FAILURE__;
+    }
+    else if ((validity == 0) || (validity > LONG_MAX))
+    {
+        LOG_ERROR("Invalid CRL validity %" PRIu64, validity);
+        result = __FAILURE__;
+    }
+    else if ((crl == NULL) || (crl_size == NULL))
+    {
+        LOG_ERROR("Invalid output CRL buffer");
+        result = __FAILURE__;
+    }
+    else
+    {
+        X509 *issuer_certificate;
+        EVP_PKEY *issuer_evp_key = NULL;
+        X509_CRL *x509_crl = NULL;
+        ASN1_TIME *last_update = NULL;
+        ASN1_TIME *next_update = NULL;
+
+        *crl = NULL;
+        *crl_size = 0;
+        if ((issuer_certificate = load_certificate_file(issuer_certificate_file)) == NULL)
+        {
+            LOG_ERROR("Could not load issuer certificate file %s", issuer_certificate_file);
+            result = __FAILURE__;
+        }
+        else if ((issuer_evp_key = load_private_key_file(issuer_key_file)) == NULL)
+        {
+            LOG_ERROR("Could not load issuer private key file %s", issuer_key_file);
+            result = __FAILURE__;
+        }
+        else if ((x509_crl = X509_CRL_new()) == NULL)
+        {
+            LOG_ERROR("Could not allocate CRL");
+            result = __FAILURE__;
+        }
+        else if (((last_update = X509_gmtime_adj(NULL, 0)) == NULL) ||
+                 ((next_update = X509_gmtime_adj(NULL, (long)validity)) == NULL))
+        {
+            LOG_ERROR("Could not compute CRL update times");
+            result = __FAILURE__;
+        }
+        else if (!X509_CRL_set_version(x509_crl, 1) ||
+                 !X509_CRL_set_issuer_name(x509_crl, X509_get_subject_name(issuer_certificate)) ||
+                 !X509_CRL_set_lastUpdate(x509_crl, last_update) ||
+                 !X509_CRL_set_nextUpdate(x509_crl, next_update))
+        {
+            LOG_ERROR("Could not set CRL properties");
+            result = __FAILURE__;
+        }
+        else
+        {
+            size_t index;
+            result = 0;
+            for (index = 0; (index < count) && (result == 0); index++)
+            {
+                result = crl_add_revoked_cert(x509_crl,
+                                              serial_numbers[index],
+                                              revocation_times[index]);
+            }
+
+            if (result != 0)
+            {
+                LOG_ERROR("Could not add revoked certificates to CRL");
+            }
+            else if (!X509_CRL_sort(x509_crl))
+            {
+                LOG_ERROR("Could not sort CRL entries");
+                result = __FAILURE__;
+            }
+            else if (!X509_CRL_sign(x509_crl, issuer_evp_key, EVP_sha256()))
+            {
+                LOG_ERROR("Failure signing CRL");
+                result = __FAILURE__;
+            }
+            else if (crl_to_pem(x509_crl, crl, crl_size) != 0)
+            {
+                LOG_ERROR("Could not encode CRL");
+                result = __FAILURE__;
+            }
+        }
+
+        if (next_update != NULL)
+        {
+            ASN1_TIME_free(next_update);
+        }
+        if (last_update != NULL)
+        {
+            ASN1_TIME_free(last_update);
+        }
+        if (x509_crl != NULL)
+        {
+            X509_CRL_free(x509_crl);
+        }
+        if (issuer_evp_key != NULL)
+        {
+            destroy_evp_key(issuer_evp_key);
+        }
+        if (issuer_certificate != NULL)
+        {
+            X509_free(issuer_certificate);
+        }
+    }
+
+    return result;
+}
@@ -33,6 +33,9 @@ typedef struct HSM_CERT_PROPS_TAG
     char **san_list;
     char const** san_list_ro;
     size_t num_san_entries;
+    CERTIFICATE_KEY_TYPE key_type;
+    char* ec_curve_name;
+    size_t rsa_key_length;
 } HSM_CERT_PROPS;
 
 CERT_PROPS_HANDLE cert_properties_create(void)
@@ -85,6 +88,7 @@ void cert_properties_destroy(CERT_PROPS_HANDLE handle)
         free(handle->org_name);
         free(handle->org_unit);
         destroy_san_entries(handle);
+        free(handle->ec_curve_name);
         free(handle);
     }
 }
@@ -716,3 +720,114 @@ const char * const* get_san_entries(CERT_PROPS_HANDLE handle, size_t *num_entrie
 
     return result;
 }
+
+int set_key_type(CERT_PROPS_HANDLE handle, CERTIFICATE_KEY_TYPE key_type, const char* ec_curve_name)
+{
+    int result;
+    if (handle == NULL)
+    {
+        LogError("Invalid parameter encounterered");
+        result = __LINE__;
+    }
+    else if ((key_type != CERTIFICATE_KEY_TYPE_DEFAULT) &&
+             (key_type != CERTIFICATE_KEY_TYPE_RSA) &&
+             (key_type != CERTIFICATE_KEY_TYPE_EC))
+    {
+        LogError("Invalid key type %d", key_type);
+        result = __LINE__;
+    }
+    else if ((key_type == CERTIFICATE_KEY_TYPE_EC) == (ec_curve_name == NULL))
+    {
+        LogError("A curve name must be provided for EC keys only");
+        result = __LINE__;
+    }
+    else if ((ec_curve_name != NULL) && (strlen(ec_curve_name) == 0))
+    {
+        LogError("Curve name cannot be empty");
+        result = __LINE__;
+    }
+    else
+    {
+        char *curve = NULL;
+        if ((ec_curve_name != NULL) && (mallocAndStrcpy_s(&curve, ec_curve_name) != 0))
+        {
+            LogError("Failure allocating curve name");
+            result = __LINE__;
+        }
+        else
+        {
+            free(handle->ec_curve_name);
+            handle->ec_curve_name = curve;
+            handle->key_type = key_type;
+            result = 0;
+        }
+    }
+    return result;
+}
+
+CERTIFICATE_KEY_TYPE get_key_type(CERT_PROPS_HANDLE handle)
+{
+    CERTIFICATE_KEY_TYPE result;
+    if (handle == NULL)
+    {
+        LogError("Invalid parameter encounterered");
+        result = CERTIFICATE_KEY_TYPE_DEFAULT;
+    }
+    else
+    {
+        result = handle->key_type;
+    }
+    return result;
+}
+
+const char* get_ec_curve_name(CERT_PROPS_HANDLE handle)
+{
+    const char* result;
+    if (handle == NULL)
+    {
+        LogError("Invalid parameter encounterered");
+        result = NULL;
+    }
+    else
+    {
+        result = handle->ec_curve_name;
+    }
+    return result;
+}
+
+int set_rsa_key_length(CERT_PROPS_HANDLE handle, size_t key_length)
+{
+    int result;
+    if (handle == NULL)
+    {
+        LogError("Invalid parameter encounterered");
+        result = __LINE__;
+    }
+    else if ((key_length != 0) && (key_length != 2048) &&
+             (key_length != 3072) && (key_length != 4096))
+    {
+        LogError("Invalid RSA key length %zu", key_length);
+        result = __LINE__;
+    }
+    else
+    {
+        handle->rsa_key_length = key_length;
+        result = 0;
+    }
+    return result;
+}
+
+size_t get_rsa_key_length(CERT_PROPS_HANDLE handle)
+{
+    size_t result;
+    if (handle == NULL)
+    {
+        LogError("Invalid parameter encounterered");
+        result = 0;
+    }
+    else
+    {
+        result = handle->rsa_key_length;
+    }
+    return result;
+}
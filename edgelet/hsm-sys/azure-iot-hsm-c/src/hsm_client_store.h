@@ -65,6 +65,31 @@ typedef int (*HSM_CLIENT_STORE_REMOVE_PKI_TRUSTED_CERT)
     const char* alias
 );
 
+typedef int (*HSM_CLIENT_STORE_CREATE_PKI_CRL)
+(
+    HSM_CLIENT_STORE_HANDLE handle,
+    const char* issuer_alias,
+    const char* const* serial_numbers,
+    const int64_t* revocation_times,
+    size_t count,
+    uint64_t validity,
+    unsigned char** crl,
+    size_t* crl_size
+);
+
+typedef int (*HSM_CLIENT_STORE_RENEW_PKI_CERT)
+(
+    HSM_CLIENT_STORE_HANDLE handle,
+    CERT_PROPS_HANDLE cert_props_handle
+);
+
+typedef CERT_INFO_HANDLE (*HSM_CLIENT_STORE_SIGN_PKI_CERT_REQUEST)
+(
+    HSM_CLIENT_STORE_HANDLE handle,
+    CERT_PROPS_HANDLE cert_props_handle,
+    const char* csr
+);
+
 struct HSM_CLIENT_STORE_INTERFACE_TAG {
     HSM_CLIENT_STORE_CREATE hsm_client_store_create;
     HSM_CLIENT_STORE_DESTROY hsm_client_store_destroy;
@@ -81,6 +106,9 @@ struct HSM_CLIENT_STORE_INTERFACE_TAG {
     HSM_CLIENT_STORE_INSERT_PKI_TRUSTED_CERT hsm_client_store_insert_pki_trusted_cert;
     HSM_CLIENT_STORE_GET_PKI_TRUSTED_CERTS hsm_client_store_get_pki_trusted_certs;
     HSM_CLIENT_STORE_REMOVE_PKI_TRUSTED_CERT hsm_client_store_remove_pki_trusted_cert;
+    HSM_CLIENT_STORE_CREATE_PKI_CRL hsm_client_store_create_pki_crl;
+    HSM_CLIENT_STORE_RENEW_PKI_CERT hsm_client_store_renew_pki_cert;
+    HSM_CLIENT_STORE_SIGN_PKI_CERT_REQUEST hsm_client_store_sign_pki_cert_request;
 };
 typedef struct HSM_CLIENT_STORE_INTERFACE_TAG HSM_CLIENT_STORE_INTERFACE;
 const HSM_CLIENT_STORE_INTERFACE* hsm_client_store_interface(void);
@@ -8,6 +8,7 @@ extern const char* const ENV_EDGE_HOME_DIR;
 extern const char* const ENV_DEVICE_CA_PATH;
 extern const char* const ENV_DEVICE_PK_PATH;
 extern const char* const ENV_TRUSTED_CA_CERTS_PATH;
+extern const char* const ENV_QUICKSTART_CA_VALIDITY;
 
 /* HSM directory name under IOTEDGE_HOMEDIR */
 extern const char* const DEFAULT_EDGE_HOME_DIR_UNIX;
@@ -4,10 +4,12 @@
 #ifdef __cplusplus
 #include <cstdbool>
 #include <cstddef>
+#include <cstdint>
 extern "C" {
 #else
 #include <stdbool.h>
 #include <stddef.h>
+#include <stdint.h>
 #endif
 
 #include "azure_c_shared_utility/umock_c_prod.h"
@@ -24,6 +26,7 @@ struct PKI_KEY_PROPS_TAG
 {
     HSM_PKI_KEY_T key_type;
     const char *ec_curve_name;
+    size_t rsa_key_len;
 };
 typedef struct PKI_KEY_PROPS_TAG PKI_KEY_PROPS;
 
@@ -39,6 +42,15 @@ MOCKABLE_FUNCTION(, int, generate_pki_cert_and_key_with_props, CERT_PROPS_HANDLE
                     int, serial_number, int, ca_path_len,
                     const char*, key_file_name, const char*, cert_file_name,
                     const PKI_KEY_PROPS*, key_props);
+MOCKABLE_FUNCTION(, int, renew_pki_cert, CERT_PROPS_HANDLE, cert_props_handle, int, serial_number,
+                    const char*, key_file_name, const char*, cert_file_name,
+                    const char*, issuer_key_file, const char*, issuer_certificate_file);
+MOCKABLE_FUNCTION(, int, sign_pki_cert_request, CERT_PROPS_HANDLE, cert_props_handle, int, serial_number,
+                    const char*, csr, const char*, issuer_key_file, const char*, issuer_certificate_file,
+                    char**, cert);
+MOCKABLE_FUNCTION(, int, generate_pki_crl, const char*, issuer_key_file, const char*, issuer_certificate_file,
+                    const char* const*, serial_numbers, const int64_t*, revocation_times, size_t, count,
+                    uint64_t, validity, unsigned char**, crl, size_t*, crl_size);
 MOCKABLE_FUNCTION(, int, generate_encryption_key, unsigned char**, key, size_t*, key_size);
 MOCKABLE_FUNCTION(, int, verify_certificate, const char*, certificate, const char*, certificate_key, const char*, issuer_certificate, bool*, verify_status);
 
@@ -65,6 +65,15 @@ MOCKABLE_FUNCTION(, int, mocked_hsm_client_store_insert_pki_trusted_cert, HSM_CL
 MOCKABLE_FUNCTION(, CERT_INFO_HANDLE, mocked_hsm_client_store_get_pki_trusted_certs, HSM_CLIENT_STORE_HANDLE, handle);
 MOCKABLE_FUNCTION(, int, mocked_hsm_client_store_remove_pki_trusted_cert, HSM_CLIENT_STORE_HANDLE, handle, const char*, alias);
 
+// store crl mocks
+MOCKABLE_FUNCTION(, int, mocked_hsm_client_store_create_pki_crl, HSM_CLIENT_STORE_HANDLE, handle, const char*, issuer_alias, const char* const*, serial_numbers, const int64_t*, revocation_times, size_t, count, uint64_t, validity, unsigned char**, crl, size_t*, crl_size);
+
+// store renew mocks
+MOCKABLE_FUNCTION(, int, mocked_hsm_client_store_renew_pki_cert, HSM_CLIENT_STORE_HANDLE, handle, CERT_PROPS_HANDLE, cert_props_handle);
+
+// store certificate signing request mocks
+MOCKABLE_FUNCTION(, CERT_INFO_HANDLE, mocked_hsm_client_store_sign_pki_cert_request, HSM_CLIENT_STORE_HANDLE, handle, CERT_PROPS_HANDLE, cert_props_handle, const char*, csr);
+
 // key interface mocks
 MOCKABLE_FUNCTION(, int, mocked_hsm_client_key_sign, KEY_HANDLE, key_handle, const unsigned char*, data_to_be_signed, size_t, data_len, unsigned char**, digest, size_t*, digest_size);
 MOCKABLE_FUNCTION(, int, mocked_hsm_client_key_derive_and_sign, KEY_HANDLE, key_handle, const unsigned char*, data_to_be_signed, size_t, data_len, const unsigned char*, identity, size_t, identity_size, unsigned char**, digest, size_t*, digest_size);
@@ -107,6 +116,7 @@ static TEST_MUTEX_HANDLE g_dllByDll;
 
 const char* TEST_ALIAS_STRING = "test_alias";
 const char* TEST_ISSUER_ALIAS_STRING = "test_issuer_alias";
+const char* TEST_CSR_STRING = "test_csr";
 
 static const HSM_CLIENT_STORE_INTERFACE mocked_hsm_client_store_interface =
 {
@@ -124,7 +134,10 @@ static const HSM_CLIENT_STORE_INTERFACE mocked_hsm_client_store_interface =
     mocked_hsm_client_store_remove_pki_cert,
     mocked_hsm_client_store_insert_pki_trusted_cert,
     mocked_hsm_client_store_get_pki_trusted_certs,
-    mocked_hsm_client_store_remove_pki_trusted_cert
+    mocked_hsm_client_store_remove_pki_trusted_cert,
+    mocked_hsm_client_store_create_pki_crl,
+    mocked_hsm_client_store_renew_pki_cert,
+    mocked_hsm_client_store_sign_pki_cert_request
 };
 
 static const HSM_CLIENT_KEY_INTERFACE mocked_hsm_client_key_interface =
@@ -241,6 +254,24 @@ static int test_hook_hsm_client_store_create_pki_cert(HSM_CLIENT_STORE_HANDLE ha
     return 0;
 }
 
+static int test_hook_hsm_client_store_renew_pki_cert(HSM_CLIENT_STORE_HANDLE handle,
+                                                     CERT_PROPS_HANDLE cert_props_handle)
+{
+    (void)handle;
+    (void)cert_props_handle;
+    return 0;
+}
+
+static CERT_INFO_HANDLE test_hook_hsm_client_store_sign_pki_cert_request(HSM_CLIENT_STORE_HANDLE handle,
+                                                                          CERT_PROPS_HANDLE cert_props_handle,
+                                                                          const char* csr)
+{
+    (void)handle;
+    (void)cert_props_handle;
+    (void)csr;
+    return TEST_CERT_INFO_HANDLE;
+}
+
 static CERT_INFO_HANDLE test_hook_hsm_client_store_get_pki_cert(HSM_CLIENT_STORE_HANDLE handle,
                                                                 const char* alias)
 {
@@ -474,6 +505,12 @@ BEGIN_TEST_SUITE(edge_hsm_crypto_unittests)
             REGISTER_GLOBAL_MOCK_HOOK(mocked_hsm_client_store_remove_pki_trusted_cert, test_hook_hsm_client_store_remove_pki_trusted_cert);
             REGISTER_GLOBAL_MOCK_FAIL_RETURN(mocked_hsm_client_store_remove_pki_trusted_cert, 1);
 
+            REGISTER_GLOBAL_MOCK_HOOK(mocked_hsm_client_store_renew_pki_cert, test_hook_hsm_client_store_renew_pki_cert);
+            REGISTER_GLOBAL_MOCK_FAIL_RETURN(mocked_hsm_client_store_renew_pki_cert, 1);
+
+            REGISTER_GLOBAL_MOCK_HOOK(mocked_hsm_client_store_sign_pki_cert_request, test_hook_hsm_client_store_sign_pki_cert_request);
+            REGISTER_GLOBAL_MOCK_FAIL_RETURN(mocked_hsm_client_store_sign_pki_cert_request, NULL);
+
             REGISTER_GLOBAL_MOCK_HOOK(mocked_hsm_client_key_sign, test_hook_hsm_client_key_sign);
             REGISTER_GLOBAL_MOCK_FAIL_RETURN(mocked_hsm_client_key_sign, 1);
 
@@ -676,6 +713,28 @@ BEGIN_TEST_SUITE(edge_hsm_crypto_unittests)
             //cleanup
         }
 
+        /**
+         * Test function for API
+         *   hsm_client_crypto_ext_interface
+        */
+        TEST_FUNCTION(hsm_client_crypto_ext_interface_success)
+        {
+            //arrange
+
+            // act
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* result = hsm_client_crypto_ext_interface();
+
+            // assert
+            ASSERT_IS_NOT_NULL_WITH_MSG(result, "Line:" TOSTRING(__LINE__));
+            ASSERT_ARE_EQUAL_WITH_MSG(char_ptr, umock_c_get_expected_calls(), umock_c_get_actual_calls(), "Line:" TOSTRING(__LINE__));
+            ASSERT_IS_NOT_NULL_WITH_MSG(result->hsm_client_create_crl, "Line:" TOSTRING(__LINE__));
+            ASSERT_IS_NOT_NULL_WITH_MSG(result->hsm_client_renew_certificate, "Line:" TOSTRING(__LINE__));
+            ASSERT_IS_NOT_NULL_WITH_MSG(result->hsm_client_sign_certificate_request, "Line:" TOSTRING(__LINE__));
+            ASSERT_IS_NOT_NULL_WITH_MSG(result->hsm_client_crypto_get_certificate, "Line:" TOSTRING(__LINE__));
+
+            //cleanup
+        }
+
         /**
          * Test function for API
          *   hsm_client_crypto_create
@@ -1199,6 +1258,333 @@ BEGIN_TEST_SUITE(edge_hsm_crypto_unittests)
             umock_c_negative_tests_deinit();
         }
 
+        /**
+         * Test function for API
+         *   hsm_client_renew_certificate
+        */
+        TEST_FUNCTION(edge_hsm_client_renew_certificate_invalid_param_validation)
+        {
+            //arrange
+            int status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_RENEW_CERTIFICATE hsm_client_renew_certificate = ext_interface->hsm_client_renew_certificate;
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            // act, assert
+            cert_info_handle = hsm_client_renew_certificate(NULL, TEST_CERT_PROPS_HANDLE);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            // act, assert
+            cert_info_handle = hsm_client_renew_certificate(TEST_HSM_CLIENT_HANDLE, NULL);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            //cleanup
+            hsm_client_crypto_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_renew_certificate
+        */
+        TEST_FUNCTION(edge_hsm_client_renew_certificate_success)
+        {
+            //arrange
+            int status;
+            status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_INTERFACE* interface = hsm_client_crypto_interface();
+            HSM_CLIENT_CREATE hsm_client_crypto_create = interface->hsm_client_crypto_create;
+            HSM_CLIENT_DESTROY hsm_client_crypto_destroy = interface->hsm_client_crypto_destroy;
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_RENEW_CERTIFICATE hsm_client_renew_certificate = ext_interface->hsm_client_renew_certificate;
+            HSM_CLIENT_HANDLE hsm_handle = hsm_client_crypto_create();
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            STRICT_EXPECTED_CALL(get_alias(TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(get_issuer_alias(TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_renew_pki_cert(IGNORED_PTR_ARG, TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_get_pki_cert(IGNORED_PTR_ARG, TEST_ALIAS_STRING));
+
+            // act
+            cert_info_handle = hsm_client_renew_certificate(hsm_handle, TEST_CERT_PROPS_HANDLE);
+
+            // assert
+            ASSERT_ARE_EQUAL_WITH_MSG(void_ptr, TEST_CERT_INFO_HANDLE, cert_info_handle, "Line:" TOSTRING(__LINE__));
+            ASSERT_ARE_EQUAL_WITH_MSG(char_ptr, umock_c_get_expected_calls(), umock_c_get_actual_calls(), "Line:" TOSTRING(__LINE__));
+
+            //cleanup
+            hsm_client_crypto_destroy(hsm_handle);
+            hsm_client_crypto_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_renew_certificate
+        */
+        TEST_FUNCTION(edge_hsm_client_renew_certificate_negative)
+        {
+            //arrange
+            int test_result = umock_c_negative_tests_init();
+            ASSERT_ARE_EQUAL(int, 0, test_result);
+            int status;
+            status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_INTERFACE* interface = hsm_client_crypto_interface();
+            HSM_CLIENT_CREATE hsm_client_crypto_create = interface->hsm_client_crypto_create;
+            HSM_CLIENT_DESTROY hsm_client_crypto_destroy = interface->hsm_client_crypto_destroy;
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_RENEW_CERTIFICATE hsm_client_renew_certificate = ext_interface->hsm_client_renew_certificate;
+            HSM_CLIENT_HANDLE hsm_handle = hsm_client_crypto_create();
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            STRICT_EXPECTED_CALL(get_alias(TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(get_issuer_alias(TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_renew_pki_cert(IGNORED_PTR_ARG, TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_get_pki_cert(IGNORED_PTR_ARG, TEST_ALIAS_STRING));
+
+            umock_c_negative_tests_snapshot();
+
+            for (size_t i = 0; i < umock_c_negative_tests_call_count(); i++)
+            {
+                umock_c_negative_tests_reset();
+                umock_c_negative_tests_fail_call(i);
+
+                // act
+                cert_info_handle = hsm_client_renew_certificate(hsm_handle, TEST_CERT_PROPS_HANDLE);
+
+                // assert
+                ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+            }
+
+            //cleanup
+            hsm_client_crypto_destroy(hsm_handle);
+            hsm_client_crypto_deinit();
+            umock_c_negative_tests_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_sign_certificate_request
+        */
+        TEST_FUNCTION(edge_hsm_client_sign_certificate_request_invalid_param_validation)
+        {
+            //arrange
+            int status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_SIGN_CERTIFICATE_REQUEST hsm_client_sign_certificate_request = ext_interface->hsm_client_sign_certificate_request;
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            // act, assert
+            cert_info_handle = hsm_client_sign_certificate_request(NULL, TEST_CSR_STRING, TEST_CERT_PROPS_HANDLE);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            // act, assert
+            cert_info_handle = hsm_client_sign_certificate_request(TEST_HSM_CLIENT_HANDLE, NULL, TEST_CERT_PROPS_HANDLE);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            // act, assert
+            cert_info_handle = hsm_client_sign_certificate_request(TEST_HSM_CLIENT_HANDLE, "", TEST_CERT_PROPS_HANDLE);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            // act, assert
+            cert_info_handle = hsm_client_sign_certificate_request(TEST_HSM_CLIENT_HANDLE, TEST_CSR_STRING, NULL);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            //cleanup
+            hsm_client_crypto_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_sign_certificate_request
+        */
+        TEST_FUNCTION(edge_hsm_client_sign_certificate_request_success)
+        {
+            //arrange
+            int status;
+            status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_INTERFACE* interface = hsm_client_crypto_interface();
+            HSM_CLIENT_CREATE hsm_client_crypto_create = interface->hsm_client_crypto_create;
+            HSM_CLIENT_DESTROY hsm_client_crypto_destroy = interface->hsm_client_crypto_destroy;
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_SIGN_CERTIFICATE_REQUEST hsm_client_sign_certificate_request = ext_interface->hsm_client_sign_certificate_request;
+            HSM_CLIENT_HANDLE hsm_handle = hsm_client_crypto_create();
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            STRICT_EXPECTED_CALL(get_issuer_alias(TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_sign_pki_cert_request(IGNORED_PTR_ARG, TEST_CERT_PROPS_HANDLE, TEST_CSR_STRING));
+
+            // act
+            cert_info_handle = hsm_client_sign_certificate_request(hsm_handle, TEST_CSR_STRING, TEST_CERT_PROPS_HANDLE);
+
+            // assert
+            ASSERT_ARE_EQUAL_WITH_MSG(void_ptr, TEST_CERT_INFO_HANDLE, cert_info_handle, "Line:" TOSTRING(__LINE__));
+            ASSERT_ARE_EQUAL_WITH_MSG(char_ptr, umock_c_get_expected_calls(), umock_c_get_actual_calls(), "Line:" TOSTRING(__LINE__));
+
+            //cleanup
+            hsm_client_crypto_destroy(hsm_handle);
+            hsm_client_crypto_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_sign_certificate_request
+        */
+        TEST_FUNCTION(edge_hsm_client_sign_certificate_request_negative)
+        {
+            //arrange
+            int test_result = umock_c_negative_tests_init();
+            ASSERT_ARE_EQUAL(int, 0, test_result);
+            int status;
+            status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_INTERFACE* interface = hsm_client_crypto_interface();
+            HSM_CLIENT_CREATE hsm_client_crypto_create = interface->hsm_client_crypto_create;
+            HSM_CLIENT_DESTROY hsm_client_crypto_destroy = interface->hsm_client_crypto_destroy;
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_SIGN_CERTIFICATE_REQUEST hsm_client_sign_certificate_request = ext_interface->hsm_client_sign_certificate_request;
+            HSM_CLIENT_HANDLE hsm_handle = hsm_client_crypto_create();
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            STRICT_EXPECTED_CALL(get_issuer_alias(TEST_CERT_PROPS_HANDLE));
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_sign_pki_cert_request(IGNORED_PTR_ARG, TEST_CERT_PROPS_HANDLE, TEST_CSR_STRING));
+
+            umock_c_negative_tests_snapshot();
+
+            for (size_t i = 0; i < umock_c_negative_tests_call_count(); i++)
+            {
+                umock_c_negative_tests_reset();
+                umock_c_negative_tests_fail_call(i);
+
+                // act
+                cert_info_handle = hsm_client_sign_certificate_request(hsm_handle, TEST_CSR_STRING, TEST_CERT_PROPS_HANDLE);
+
+                // assert
+                ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+            }
+
+            //cleanup
+            hsm_client_crypto_destroy(hsm_handle);
+            hsm_client_crypto_deinit();
+            umock_c_negative_tests_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_crypto_get_certificate
+        */
+        TEST_FUNCTION(edge_hsm_client_crypto_get_certificate_invalid_param_validation)
+        {
+            //arrange
+            int status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_CRYPTO_GET_CERTIFICATE hsm_client_crypto_get_certificate = ext_interface->hsm_client_crypto_get_certificate;
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            // act, assert
+            cert_info_handle = hsm_client_crypto_get_certificate(NULL, TEST_ALIAS_STRING);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            // act, assert
+            cert_info_handle = hsm_client_crypto_get_certificate(TEST_HSM_CLIENT_HANDLE, NULL);
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+
+            // act, assert
+            cert_info_handle = hsm_client_crypto_get_certificate(TEST_HSM_CLIENT_HANDLE, "");
+            ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+            ASSERT_ARE_EQUAL_WITH_MSG(char_ptr, umock_c_get_expected_calls(), umock_c_get_actual_calls(), "Line:" TOSTRING(__LINE__));
+
+            //cleanup
+            hsm_client_crypto_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_crypto_get_certificate
+        */
+        TEST_FUNCTION(edge_hsm_client_crypto_get_certificate_success)
+        {
+            //arrange
+            int status;
+            status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_INTERFACE* interface = hsm_client_crypto_interface();
+            HSM_CLIENT_CREATE hsm_client_crypto_create = interface->hsm_client_crypto_create;
+            HSM_CLIENT_DESTROY hsm_client_crypto_destroy = interface->hsm_client_crypto_destroy;
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_CRYPTO_GET_CERTIFICATE hsm_client_crypto_get_certificate = ext_interface->hsm_client_crypto_get_certificate;
+            HSM_CLIENT_HANDLE hsm_handle = hsm_client_crypto_create();
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_get_pki_cert(IGNORED_PTR_ARG, TEST_ALIAS_STRING));
+
+            // act
+            cert_info_handle = hsm_client_crypto_get_certificate(hsm_handle, TEST_ALIAS_STRING);
+
+            // assert
+            ASSERT_ARE_EQUAL_WITH_MSG(void_ptr, TEST_CERT_INFO_HANDLE, cert_info_handle, "Line:" TOSTRING(__LINE__));
+            ASSERT_ARE_EQUAL_WITH_MSG(char_ptr, umock_c_get_expected_calls(), umock_c_get_actual_calls(), "Line:" TOSTRING(__LINE__));
+
+            //cleanup
+            hsm_client_crypto_destroy(hsm_handle);
+            hsm_client_crypto_deinit();
+        }
+
+        /**
+         * Test function for API
+         *   hsm_client_crypto_get_certificate
+        */
+        TEST_FUNCTION(edge_hsm_client_crypto_get_certificate_negative)
+        {
+            //arrange
+            int test_result = umock_c_negative_tests_init();
+            ASSERT_ARE_EQUAL(int, 0, test_result);
+            int status;
+            status = hsm_client_crypto_init();
+            ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+            const HSM_CLIENT_CRYPTO_INTERFACE* interface = hsm_client_crypto_interface();
+            HSM_CLIENT_CREATE hsm_client_crypto_create = interface->hsm_client_crypto_create;
+            HSM_CLIENT_DESTROY hsm_client_crypto_destroy = interface->hsm_client_crypto_destroy;
+            const HSM_CLIENT_CRYPTO_EXT_INTERFACE* ext_interface = hsm_client_crypto_ext_interface();
+            HSM_CLIENT_CRYPTO_GET_CERTIFICATE hsm_client_crypto_get_certificate = ext_interface->hsm_client_crypto_get_certificate;
+            HSM_CLIENT_HANDLE hsm_handle = hsm_client_crypto_create();
+            CERT_INFO_HANDLE cert_info_handle;
+            umock_c_reset_all_calls();
+
+            STRICT_EXPECTED_CALL(mocked_hsm_client_store_get_pki_cert(IGNORED_PTR_ARG, TEST_ALIAS_STRING));
+
+            umock_c_negative_tests_snapshot();
+
+            for (size_t i = 0; i < umock_c_negative_tests_call_count(); i++)
+            {
+                umock_c_negative_tests_reset();
+                umock_c_negative_tests_fail_call(i);
+
+                // act
+                cert_info_handle = hsm_client_crypto_get_certificate(hsm_handle, TEST_ALIAS_STRING);
+
+                // assert
+                ASSERT_IS_NULL_WITH_MSG(cert_info_handle, "Line:" TOSTRING(__LINE__));
+            }
+
+            //cleanup
+            hsm_client_crypto_destroy(hsm_handle);
+            hsm_client_crypto_deinit();
+            umock_c_negative_tests_deinit();
+        }
+
         /**
          * Test function for API
          *   hsm_client_get_trust_bundle
@@ -164,6 +164,9 @@ MOCKABLE_FUNCTION(, const char*, get_locality, CERT_PROPS_HANDLE, handle);
 MOCKABLE_FUNCTION(, const char*, get_organization_name, CERT_PROPS_HANDLE, handle);
 MOCKABLE_FUNCTION(, const char*, get_organization_unit, CERT_PROPS_HANDLE, handle);
 MOCKABLE_FUNCTION(, CERTIFICATE_TYPE, get_certificate_type, CERT_PROPS_HANDLE, handle);
+MOCKABLE_FUNCTION(, CERTIFICATE_KEY_TYPE, get_key_type, CERT_PROPS_HANDLE, handle);
+MOCKABLE_FUNCTION(, const char*, get_ec_curve_name, CERT_PROPS_HANDLE, handle);
+MOCKABLE_FUNCTION(, size_t, get_rsa_key_length, CERT_PROPS_HANDLE, handle);
 MOCKABLE_FUNCTION(, const char * const*, get_san_entries, CERT_PROPS_HANDLE, handle, size_t*, num_entries);
 
 MOCKABLE_FUNCTION(, X509_EXTENSION*, mocked_X509V3_EXT_conf_nid, struct lhash_st_CONF_VALUE*, conf, X509V3_CTX*, ctx, int, ext_nid, char*, value);
@@ -1052,6 +1055,27 @@ static CERTIFICATE_TYPE test_hook_get_certificate_type(CERT_PROPS_HANDLE handle)
     return TEST_PROPS_CERT_TYPE;
 }
 
+static CERTIFICATE_KEY_TYPE test_hook_get_key_type(CERT_PROPS_HANDLE handle)
+{
+    (void)handle;
+
+    return CERTIFICATE_KEY_TYPE_DEFAULT;
+}
+
+static const char* test_hook_get_ec_curve_name(CERT_PROPS_HANDLE handle)
+{
+    (void)handle;
+
+    return NULL;
+}
+
+static size_t test_hook_get_rsa_key_length(CERT_PROPS_HANDLE handle)
+{
+    (void)handle;
+
+    return 0;
+}
+
 static X509_EXTENSION* test_hook_mocked_X509V3_EXT_conf_nid
 (
     struct lhash_st_CONF_VALUE *conf,
@@ -1261,6 +1285,10 @@ static void test_helper_cert_create_with_subject
 
     if (!is_self_signed)
     {
+        STRICT_EXPECTED_CALL(get_key_type(TEST_CERT_PROPS_HANDLE));
+        ASSERT_IS_TRUE_WITH_MSG((i < failed_function_size), "Line:" TOSTRING(__LINE__));
+        i++;
+
         STRICT_EXPECTED_CALL(BIO_new_file(TEST_ISSUER_CERT_FILE, "r"));
         ASSERT_IS_TRUE_WITH_MSG((i < failed_function_size), "Line:" TOSTRING(__LINE__));
         failed_function_list[i++] = 1;
@@ -1896,6 +1924,7 @@ BEGIN_TEST_SUITE(edge_openssl_pki_unittests)
         REGISTER_UMOCK_ALIAS_TYPE(KEY_HANDLE, void*);
         REGISTER_UMOCK_ALIAS_TYPE(CERT_PROPS_HANDLE, void*);
         REGISTER_UMOCK_ALIAS_TYPE(CERTIFICATE_TYPE, int);
+        REGISTER_UMOCK_ALIAS_TYPE(CERTIFICATE_KEY_TYPE, int);
         REGISTER_UMOCK_ALIAS_TYPE(MODE_T, int);
 
         REGISTER_GLOBAL_MOCK_HOOK(gballoc_malloc, test_hook_gballoc_malloc);
@@ -2116,6 +2145,10 @@ BEGIN_TEST_SUITE(edge_openssl_pki_unittests)
         REGISTER_GLOBAL_MOCK_HOOK(get_certificate_type, test_hook_get_certificate_type);
         REGISTER_GLOBAL_MOCK_FAIL_RETURN(get_certificate_type, CERTIFICATE_TYPE_UNKNOWN);
 
+        REGISTER_GLOBAL_MOCK_HOOK(get_key_type, test_hook_get_key_type);
+        REGISTER_GLOBAL_MOCK_HOOK(get_ec_curve_name, test_hook_get_ec_curve_name);
+        REGISTER_GLOBAL_MOCK_HOOK(get_rsa_key_length, test_hook_get_rsa_key_length);
+
         REGISTER_GLOBAL_MOCK_HOOK(mocked_X509V3_EXT_conf_nid, test_hook_mocked_X509V3_EXT_conf_nid);
         REGISTER_GLOBAL_MOCK_FAIL_RETURN(mocked_X509V3_EXT_conf_nid, NULL);
 
@@ -1175,4 +1175,79 @@ BEGIN_TEST_SUITE(hsm_certificate_props_ut)
         umock_c_negative_tests_deinit();
     }
 
+    /**
+    * Test function for APIs
+    *   set_key_type
+    *   get_key_type
+    *   get_ec_curve_name
+    */
+    TEST_FUNCTION(certificate_props_get_set_key_type)
+    {
+        //arrange
+        int status;
+        CERT_PROPS_HANDLE props_handle = cert_properties_create();
+
+        // act, assert
+        ASSERT_ARE_EQUAL_WITH_MSG(int, CERTIFICATE_KEY_TYPE_DEFAULT, get_key_type(props_handle), "Line:" TOSTRING(__LINE__));
+        ASSERT_IS_NULL_WITH_MSG(get_ec_curve_name(props_handle), "Line:" TOSTRING(__LINE__));
+
+        status = set_key_type(props_handle, CERTIFICATE_KEY_TYPE_EC, "secp384r1");
+        ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(int, CERTIFICATE_KEY_TYPE_EC, get_key_type(props_handle), "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(char_ptr, "secp384r1", get_ec_curve_name(props_handle), "Line:" TOSTRING(__LINE__));
+
+        status = set_key_type(props_handle, CERTIFICATE_KEY_TYPE_RSA, NULL);
+        ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(int, CERTIFICATE_KEY_TYPE_RSA, get_key_type(props_handle), "Line:" TOSTRING(__LINE__));
+        ASSERT_IS_NULL_WITH_MSG(get_ec_curve_name(props_handle), "Line:" TOSTRING(__LINE__));
+
+        status = set_key_type(props_handle, CERTIFICATE_KEY_TYPE_EC, NULL);
+        ASSERT_ARE_NOT_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        status = set_key_type(props_handle, CERTIFICATE_KEY_TYPE_EC, "");
+        ASSERT_ARE_NOT_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        status = set_key_type(props_handle, CERTIFICATE_KEY_TYPE_RSA, "prime256v1");
+        ASSERT_ARE_NOT_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        status = set_key_type(NULL, CERTIFICATE_KEY_TYPE_RSA, NULL);
+        ASSERT_ARE_NOT_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(int, CERTIFICATE_KEY_TYPE_RSA, get_key_type(props_handle), "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(int, CERTIFICATE_KEY_TYPE_DEFAULT, get_key_type(NULL), "Line:" TOSTRING(__LINE__));
+        ASSERT_IS_NULL_WITH_MSG(get_ec_curve_name(NULL), "Line:" TOSTRING(__LINE__));
+
+        //cleanup
+        cert_properties_destroy(props_handle);
+    }
+
+    /**
+    * Test function for APIs
+    *   set_rsa_key_length
+    *   get_rsa_key_length
+    */
+    TEST_FUNCTION(certificate_props_get_set_rsa_key_length)
+    {
+        //arrange
+        int status;
+        CERT_PROPS_HANDLE props_handle = cert_properties_create();
+
+        // act, assert
+        ASSERT_ARE_EQUAL_WITH_MSG(size_t, 0, get_rsa_key_length(props_handle), "Line:" TOSTRING(__LINE__));
+
+        status = set_rsa_key_length(props_handle, 3072);
+        ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(size_t, 3072, get_rsa_key_length(props_handle), "Line:" TOSTRING(__LINE__));
+
+        status = set_rsa_key_length(props_handle, 1024);
+        ASSERT_ARE_NOT_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        status = set_rsa_key_length(NULL, 2048);
+        ASSERT_ARE_NOT_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(size_t, 3072, get_rsa_key_length(props_handle), "Line:" TOSTRING(__LINE__));
+
+        status = set_rsa_key_length(props_handle, 0);
+        ASSERT_ARE_EQUAL_WITH_MSG(int, 0, status, "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(size_t, 0, get_rsa_key_length(props_handle), "Line:" TOSTRING(__LINE__));
+        ASSERT_ARE_EQUAL_WITH_MSG(size_t, 0, get_rsa_key_length(NULL), "Line:" TOSTRING(__LINE__));
+
+        //cleanup
+        cert_properties_destroy(props_handle);
+    }
+
 END_TEST_SUITE(hsm_certificate_props_ut)
@@ -20,6 +20,9 @@
 ))]
 #![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
 
+#[cfg(all(not(debug_assertions), unix))]
+extern crate libc;
+
 use std::os::raw::{c_char, c_int, c_uchar, c_void};
 
 extern "C" {
@@ -266,6 +269,12 @@ pub const CERTIFICATE_TYPE_TAG_CERTIFICATE_TYPE_CA: CERTIFICATE_TYPE_TAG = 3;
 pub type CERTIFICATE_TYPE_TAG = u32;
 pub use self::CERTIFICATE_TYPE_TAG as CERTIFICATE_TYPE;
 
+pub const CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_DEFAULT: CERTIFICATE_KEY_TYPE_TAG = 0;
+pub const CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_RSA: CERTIFICATE_KEY_TYPE_TAG = 1;
+pub const CERTIFICATE_KEY_TYPE_TAG_CERTIFICATE_KEY_TYPE_EC: CERTIFICATE_KEY_TYPE_TAG = 2;
+pub type CERTIFICATE_KEY_TYPE_TAG = u32;
+pub use self::CERTIFICATE_KEY_TYPE_TAG as CERTIFICATE_KEY_TYPE;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct HSM_CERTIFICATE_PROPS_TAG {
@@ -355,6 +364,30 @@ extern "C" {
     ) -> *const *const c_char;
 }
 
+extern "C" {
+    pub fn set_key_type(
+        handle: CERT_PROPS_HANDLE,
+        key_type: CERTIFICATE_KEY_TYPE,
+        ec_curve_name: *const c_char,
+    ) -> c_int;
+}
+
+extern "C" {
+    pub fn get_key_type(handle: CERT_PROPS_HANDLE) -> CERTIFICATE_KEY_TYPE;
+}
+
+extern "C" {
+    pub fn get_ec_curve_name(handle: CERT_PROPS_HANDLE) -> *const c_char;
+}
+
+extern "C" {
+    pub fn set_rsa_key_length(handle: CERT_PROPS_HANDLE, key_length: usize) -> c_int;
+}
+
+extern "C" {
+    pub fn get_rsa_key_length(handle: CERT_PROPS_HANDLE) -> usize;
+}
+
 /// API generates a X.509 certificate and private key pair using the supplied
 /// certificate properties. Any CA certificates are expected to by issued by
 /// the Device CA. Other certificates may be issued by any intermediate CA
@@ -409,6 +442,74 @@ pub type HSM_CLIENT_DESTROY_CERTIFICATE =
 pub type HSM_CLIENT_GET_TRUST_BUNDLE =
     Option<unsafe extern "C" fn(handle: HSM_CLIENT_HANDLE) -> CERT_INFO_HANDLE>;
 
+/// API to generate a certificate revocation list signed by the certificate
+/// identified by the issuer alias.
+///
+/// handle[in]           -- A valid HSM client handle
+/// issuer_alias[in]     -- Alias of the CA certificate that issued the revoked certificates
+/// serial_numbers[in]   -- Hex encoded serial numbers of the revoked certificates
+/// revocation_times[in] -- Revocation time of each certificate, in seconds since the epoch
+/// count[in]            -- Number of entries in serial_numbers and revocation_times
+/// validity[in]         -- Number of seconds until the next update of the list is due
+/// crl[out]             -- PEM encoded CRL
+///
+/// Return
+/// 0 - Success
+/// Non 0 otherwise
+pub type HSM_CLIENT_CREATE_CRL = Option<
+    unsafe extern "C" fn(
+        handle: HSM_CLIENT_HANDLE,
+        issuer_alias: *const c_char,
+        serial_numbers: *const *const c_char,
+        revocation_times: *const i64,
+        count: usize,
+        validity: u64,
+        crl: *mut SIZED_BUFFER,
+    ) -> c_int,
+>;
+
+/// API to re-issue an existing certificate, reusing the private key that was
+/// generated when the certificate was first created.
+///
+/// handle[in]            -- A valid HSM client handle
+/// certificate_props[in] -- Handle to certificate properties of the certificate to renew
+///
+/// Return
+/// Valid CERT_INFO_HANDLE - Success
+/// NULL otherwise
+pub type HSM_CLIENT_RENEW_CERTIFICATE = Option<
+    unsafe extern "C" fn(handle: HSM_CLIENT_HANDLE, certificate_props: CERT_PROPS_HANDLE)
+        -> CERT_INFO_HANDLE,
+>;
+/// API to issue a certificate for the public key of a PEM encoded certificate
+/// signing request. The HSM neither holds the private key nor stores the certificate.
+///
+/// handle[in]            -- A valid HSM client handle
+/// csr[in]               -- PEM encoded certificate signing request
+/// certificate_props[in] -- Handle to certificate properties, including the issuer alias
+///
+/// Return
+/// Valid CERT_INFO_HANDLE without a private key - Success
+/// NULL otherwise
+pub type HSM_CLIENT_SIGN_CERTIFICATE_REQUEST = Option<
+    unsafe extern "C" fn(
+        handle: HSM_CLIENT_HANDLE,
+        csr: *const c_char,
+        certificate_props: CERT_PROPS_HANDLE,
+    ) -> CERT_INFO_HANDLE,
+>;
+/// API to retrieve the certificate of an alias, as it was created or last renewed.
+///
+/// handle[in]            -- A valid HSM client handle
+/// alias[in]             -- Alias of the certificate
+///
+/// Return
+/// Valid CERT_INFO_HANDLE - Success
+/// NULL otherwise, including when the alias has no certificate
+pub type HSM_CLIENT_CRYPTO_GET_CERTIFICATE = Option<
+    unsafe extern "C" fn(handle: HSM_CLIENT_HANDLE, alias: *const c_char) -> CERT_INFO_HANDLE,
+>;
+
 extern "C" {
     /// Creates the certificate information object and initializes the values
     ///
@@ -945,6 +1046,103 @@ fn bindgen_test_layout_HSM_CLIENT_CRYPTO_INTERFACE_TAG() {
     );
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG {
+    pub hsm_client_create_crl: HSM_CLIENT_CREATE_CRL,
+    pub hsm_client_renew_certificate: HSM_CLIENT_RENEW_CERTIFICATE,
+    pub hsm_client_sign_certificate_request: HSM_CLIENT_SIGN_CERTIFICATE_REQUEST,
+    pub hsm_client_crypto_get_certificate: HSM_CLIENT_CRYPTO_GET_CERTIFICATE,
+}
+pub type HSM_CLIENT_CRYPTO_EXT_INTERFACE = HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG;
+
+impl Default for HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG {
+    fn default() -> HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG {
+        HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG {
+            hsm_client_create_crl: None,
+            hsm_client_renew_certificate: None,
+            hsm_client_sign_certificate_request: None,
+            hsm_client_crypto_get_certificate: None,
+        }
+    }
+}
+
+#[test]
+fn bindgen_test_layout_HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG() {
+    assert_eq!(
+        ::std::mem::size_of::<HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG>(),
+        4_usize * ::std::mem::size_of::<usize>(),
+        concat!("Size of: ", stringify!(HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG>(),
+        ::std::mem::size_of::<usize>(),
+        concat!("Alignment of ", stringify!(HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG))
+    );
+    assert_eq!(
+        unsafe {
+            &(*(::std::ptr::null::<HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG>())).hsm_client_create_crl
+                as *const _ as usize
+        },
+        0_usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG),
+            "::",
+            stringify!(hsm_client_create_crl)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            &(*(::std::ptr::null::<HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG>()))
+                .hsm_client_renew_certificate as *const _ as usize
+        },
+        ::std::mem::size_of::<usize>(),
+        concat!(
+            "Offset of field: ",
+            stringify!(HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG),
+            "::",
+            stringify!(hsm_client_renew_certificate)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            &(*(::std::ptr::null::<HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG>()))
+                .hsm_client_sign_certificate_request as *const _ as usize
+        },
+        2_usize * ::std::mem::size_of::<usize>(),
+        concat!(
+            "Offset of field: ",
+            stringify!(HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG),
+            "::",
+            stringify!(hsm_client_sign_certificate_request)
+        )
+    );
+    assert_eq!(
+        unsafe {
+            &(*(::std::ptr::null::<HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG>()))
+                .hsm_client_crypto_get_certificate as *const _ as usize
+        },
+        3_usize * ::std::mem::size_of::<usize>(),
+        concat!(
+            "Offset of field: ",
+            stringify!(HSM_CLIENT_CRYPTO_EXT_INTERFACE_TAG),
+            "::",
+            stringify!(hsm_client_crypto_get_certificate)
+        )
+    );
+}
+
+#[test]
+fn bindgen_test_crypto_ext_interface() {
+    let interface = unsafe { hsm_client_crypto_ext_interface() };
+    assert!(!interface.is_null());
+    assert!(unsafe { (*interface).hsm_client_create_crl }.is_some());
+    assert!(unsafe { (*interface).hsm_client_renew_certificate }.is_some());
+    assert!(unsafe { (*interface).hsm_client_sign_certificate_request }.is_some());
+    assert!(unsafe { (*interface).hsm_client_crypto_get_certificate }.is_some());
+}
+
 extern "C" {
     pub fn hsm_client_tpm_interface() -> *const HSM_CLIENT_TPM_INTERFACE;
 }
@@ -954,6 +1152,58 @@ extern "C" {
 extern "C" {
     pub fn hsm_client_crypto_interface() -> *const HSM_CLIENT_CRYPTO_INTERFACE;
 }
+
+/// Looks up `hsm_client_crypto_ext_interface`, which HSM libraries don't have
+/// to export, and calls it.
+///
+/// Return
+/// The optional crypto functions of the library, or NULL if it has none
+#[cfg(debug_assertions)]
+pub unsafe fn hsm_client_crypto_ext_interface() -> *const HSM_CLIENT_CRYPTO_EXT_INTERFACE {
+    // debug builds link the library of this crate statically, which has it
+    extern "C" {
+        #[link_name = "hsm_client_crypto_ext_interface"]
+        fn ext_interface() -> *const HSM_CLIENT_CRYPTO_EXT_INTERFACE;
+    }
+    ext_interface()
+}
+
+/// Looks up `hsm_client_crypto_ext_interface`, which HSM libraries don't have
+/// to export, and calls it.
+///
+/// Return
+/// The optional crypto functions of the library, or NULL if it has none
+#[cfg(not(debug_assertions))]
+pub unsafe fn hsm_client_crypto_ext_interface() -> *const HSM_CLIENT_CRYPTO_EXT_INTERFACE {
+    let symbol = find_symbol(b"hsm_client_crypto_ext_interface\0");
+    if symbol.is_null() {
+        std::ptr::null()
+    } else {
+        let ext_interface: unsafe extern "C" fn() -> *const HSM_CLIENT_CRYPTO_EXT_INTERFACE =
+            std::mem::transmute(symbol);
+        ext_interface()
+    }
+}
+
+#[cfg(all(not(debug_assertions), unix))]
+unsafe fn find_symbol(name: &[u8]) -> *mut c_void {
+    libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr() as *const c_char)
+}
+
+#[cfg(all(not(debug_assertions), windows))]
+unsafe fn find_symbol(name: &[u8]) -> *mut c_void {
+    extern "system" {
+        fn GetModuleHandleA(module_name: *const c_char) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, proc_name: *const c_char) -> *mut c_void;
+    }
+
+    let module = GetModuleHandleA(b"iothsm.dll\0".as_ptr() as *const c_char);
+    if module.is_null() {
+        std::ptr::null_mut()
+    } else {
+        GetProcAddress(module, name.as_ptr() as *const c_char)
+    }
+}
 extern "C" {
     pub fn hsm_client_x509_init() -> c_int;
 }
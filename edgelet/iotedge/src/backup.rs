@@ -0,0 +1,66 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use edgelet_mgmt_client::ModuleClient;
+use futures::Future;
+
+use error::Error;
+use Command;
+
+pub struct Backup<W> {
+    file: PathBuf,
+    passphrase: String,
+    client: ModuleClient,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W> Backup<W> {
+    pub fn new(file: PathBuf, passphrase: String, client: ModuleClient, output: W) -> Self {
+        Backup {
+            file,
+            passphrase,
+            client,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<W> Command for Backup<W>
+where
+    W: 'static + Write + Send,
+{
+    type Future = Box<Future<Item = (), Error = Error> + Send>;
+
+    fn execute(&mut self) -> Self::Future {
+        let file = self.file.clone();
+        let write = self.output.clone();
+        let result = self
+            .client
+            .backup(&self.passphrase)
+            .map_err(Error::from)
+            .and_then(move |backup| {
+                write_backup(&file, &backup)?;
+                let mut w = write.lock().unwrap();
+                writeln!(w, "Backed up the IoT Edge daemon state to {}", file.display())?;
+                Ok(())
+            });
+        Box::new(result)
+    }
+}
+
+// The backup holds the device's keys, so only its owner can read it.
+fn write_backup(file: &Path, backup: &[u8]) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(file)?.write_all(backup)?;
+    Ok(())
+}
@@ -0,0 +1,818 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, FixedOffset};
+use edgelet_core::{Deprecation, DiskUsage, Hostname};
+use failure::ResultExt;
+use futures::future::{self, FutureResult};
+use serde_yaml;
+
+use error::{Error, ErrorKind};
+use Command;
+
+/// Certificates put the host name in their common name, which can't be
+/// longer than this.
+const MAX_COMMON_NAME_LENGTH: usize = 64;
+
+/// This is the file, under the daemon's home directory, that records the host
+/// name the daemon last issued certificates for.
+const HOSTNAME_RECORD: &str = "cache/hostname";
+
+/// This is the file, under the daemon's home directory, that records the last
+/// time a container registry refused to let the daemon pull an image.
+const REGISTRY_AUTH_RECORD: &str = "cache/registry-auth";
+
+/// This is the file, under the daemon's home directory, that records when
+/// the CA the daemon generates in quick start mode expires.
+const QUICKSTART_CA_RECORD: &str = "cache/quickstart_ca.json";
+
+/// This is the file, under the daemon's home directory, that records the
+/// deprecated settings the config file used when the daemon last started.
+const DEPRECATIONS_RECORD: &str = "cache/deprecations.json";
+
+/// This is the file, under the daemon's home directory, that the daemon backs
+/// up the device credentials it gets from DPS to.
+const PROVISIONING_BACKUP: &str = "cache/provisioning_backup.json";
+
+/// This is the file, under the daemon's home directory, that records what
+/// took up the disk of the device when the daemon last measured it.
+const DISK_USAGE_RECORD: &str = "cache/disk_usage.json";
+
+/// The disk is warned about once less than this much of it is free, in
+/// percent.
+const MIN_FREE_DISK_PERCENT: u64 = 10;
+
+/// Modules are warned about once their writable layer takes up this much of
+/// their quota, in percent.
+const MAX_QUOTA_USED_PERCENT: u64 = 90;
+
+/// The record the daemon keeps of when the quick start CA expires.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuickstartCaRecord {
+    expires_at: String,
+}
+
+/// The record the daemon keeps of its last failure to authenticate to a
+/// container registry. It is JSON, which is YAML as far as reading it goes.
+#[derive(Debug, Deserialize)]
+struct RegistryAuthRecord {
+    image: String,
+    failure: String,
+    message: String,
+}
+
+/// The parts of the provisioning section of the daemon's config file the
+/// checks look at.
+#[derive(Debug, Deserialize)]
+struct ProvisioningConfig {
+    source: String,
+}
+
+/// The parts of the daemon's config file the checks look at.
+#[derive(Debug, Deserialize)]
+struct Config {
+    hostname: String,
+    homedir: PathBuf,
+    #[serde(default)]
+    certificates: Option<serde_yaml::Value>,
+    #[serde(default)]
+    provisioning: Option<ProvisioningConfig>,
+    #[serde(default)]
+    offline: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum CheckResult {
+    Ok,
+    Warning(String),
+    Error(String),
+    Skipped(String),
+}
+
+type CheckFn = fn(&Config) -> CheckResult;
+
+const CHECKS: &[(&str, &str, CheckFn)] = &[
+    (
+        "config-hostname",
+        "config.yaml has a valid host name",
+        config_hostname,
+    ),
+    (
+        "device-hostname",
+        "config.yaml host name matches the device host name",
+        device_hostname,
+    ),
+    (
+        "certificates-hostname",
+        "certificates are issued for the config.yaml host name",
+        certificates_hostname,
+    ),
+    (
+        "registry-credentials",
+        "container registries accept the credentials in the deployment",
+        registry_credentials,
+    ),
+    (
+        "quickstart-certificates",
+        "production certificates are configured",
+        quickstart_certificates,
+    ),
+    (
+        "config-deprecations",
+        "config.yaml uses no deprecated settings",
+        config_deprecations,
+    ),
+    (
+        "offline-credentials",
+        "device credentials are cached for offline operation",
+        offline_credentials,
+    ),
+    (
+        "disk-usage",
+        "the device has enough free disk space",
+        disk_usage,
+    ),
+];
+
+/// The ids of the checks, in the order they run.
+pub fn check_ids() -> Vec<&'static str> {
+    CHECKS.iter().map(|(id, _, _)| *id).collect()
+}
+
+/// The warning that the daemon configured by `config_file` uses quick start
+/// certificates, if it does. There is none if the config file can't be read.
+pub fn quickstart_warning(config_file: &Path) -> Option<String> {
+    let file = File::open(config_file).ok()?;
+    let config: Config = serde_yaml::from_reader(file).ok()?;
+    match quickstart_certificates(&config) {
+        CheckResult::Warning(message) => Some(message),
+        _ => None,
+    }
+}
+
+/// Checks the device for problems that keep the IoT Edge runtime from working
+/// as it should. The command fails with `ErrorKind::ChecksFailed` if any check
+/// finds an error, and with `ErrorKind::ChecksWarned` if the checks only raise
+/// warnings.
+pub struct Check<W> {
+    config_file: PathBuf,
+    output: W,
+    list: bool,
+    only: Vec<String>,
+    skip: Vec<String>,
+}
+
+impl<W> Check<W> {
+    pub fn new(config_file: PathBuf, output: W) -> Self {
+        Check {
+            config_file,
+            output,
+            list: false,
+            only: Vec::new(),
+            skip: Vec::new(),
+        }
+    }
+
+    /// Lists the checks with their ids instead of running them.
+    pub fn with_list(mut self, list: bool) -> Self {
+        self.list = list;
+        self
+    }
+
+    /// Runs only the checks with the given ids. Every check runs if there
+    /// are none.
+    pub fn with_only(mut self, only: Vec<String>) -> Self {
+        self.only = only;
+        self
+    }
+
+    /// Leaves out the checks with the given ids.
+    pub fn with_skip(mut self, skip: Vec<String>) -> Self {
+        self.skip = skip;
+        self
+    }
+}
+
+impl<W> Check<W>
+where
+    W: Write,
+{
+    fn run(&mut self) -> Result<(), Error> {
+        let ids = check_ids();
+        let unknown = |selected: &[String]| selected.iter().any(|id| !ids.contains(&id.as_str()));
+        if unknown(&self.only) {
+            return Err(Error::from(ErrorKind::InvalidArgument("only")));
+        }
+        if unknown(&self.skip) {
+            return Err(Error::from(ErrorKind::InvalidArgument("skip")));
+        }
+
+        if self.list {
+            for (id, description, _) in CHECKS {
+                writeln!(self.output, "{:<24}{}", id, description)?;
+            }
+            return Ok(());
+        }
+
+        let file = File::open(&self.config_file).context(ErrorKind::Config)?;
+        let config: Config = serde_yaml::from_reader(file).context(ErrorKind::Config)?;
+
+        let (mut failed, mut warned) = (0, 0);
+        for (id, description, check) in CHECKS {
+            if !self.is_selected(id) {
+                continue;
+            }
+
+            match check(&config) {
+                CheckResult::Ok => writeln!(self.output, "[ok] {}", description)?,
+                CheckResult::Warning(message) => {
+                    warned += 1;
+                    writeln!(self.output, "[warning] {} ({})", description, id)?;
+                    writeln!(self.output, "    {}", message)?;
+                }
+                CheckResult::Error(message) => {
+                    failed += 1;
+                    writeln!(self.output, "[error] {} ({})", description, id)?;
+                    writeln!(self.output, "    {}", message)?;
+                }
+                CheckResult::Skipped(reason) => {
+                    writeln!(self.output, "[skipped] {} ({})", description, id)?;
+                    writeln!(self.output, "    {}", reason)?;
+                }
+            }
+        }
+
+        if failed > 0 {
+            Err(Error::from(ErrorKind::ChecksFailed(failed)))
+        } else if warned > 0 {
+            Err(Error::from(ErrorKind::ChecksWarned(warned)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<W> Check<W> {
+    fn is_selected(&self, id: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|only| only == id))
+            && !self.skip.iter().any(|skip| skip == id)
+    }
+}
+
+impl<W> Command for Check<W>
+where
+    W: Write + Send,
+{
+    type Future = FutureResult<(), Error>;
+
+    fn execute(&mut self) -> Self::Future {
+        future::result(self.run())
+    }
+}
+
+fn config_hostname(config: &Config) -> CheckResult {
+    if Hostname::new(&config.hostname).is_err() {
+        CheckResult::Error(format!(
+            "{} is not a valid DNS host name.",
+            config.hostname
+        ))
+    } else if config.hostname.len() > MAX_COMMON_NAME_LENGTH {
+        CheckResult::Error(format!(
+            "{} is longer than the {} characters a certificate common name can hold.",
+            config.hostname, MAX_COMMON_NAME_LENGTH
+        ))
+    } else {
+        CheckResult::Ok
+    }
+}
+
+fn device_hostname(config: &Config) -> CheckResult {
+    let hostname = match Hostname::new(&config.hostname) {
+        Ok(hostname) => hostname,
+        Err(_) => return CheckResult::Skipped("The host name is not valid.".to_string()),
+    };
+
+    match Hostname::of_device() {
+        Some(ref device) if hostname.names_same_host(device) => CheckResult::Ok,
+        Some(device) => CheckResult::Warning(format!(
+            "The device host name is {}. Clients that reach the device by that name \
+             will not be able to validate the certificates of the edge hub.",
+            device.as_str()
+        )),
+        None => CheckResult::Skipped("Could not find the device host name.".to_string()),
+    }
+}
+
+fn certificates_hostname(config: &Config) -> CheckResult {
+    match read_hostname_record(&config.homedir) {
+        Ok(Some(ref issued)) if issued.eq_ignore_ascii_case(&config.hostname) => CheckResult::Ok,
+        Ok(Some(issued)) => CheckResult::Warning(format!(
+            "The certificates were issued for the host name {}. Restart the IoT Edge daemon \
+             to issue them for {}.",
+            issued, config.hostname
+        )),
+        Ok(None) => CheckResult::Skipped(
+            "The IoT Edge daemon has not issued any certificates yet.".to_string(),
+        ),
+        Err(err) => CheckResult::Warning(format!(
+            "Could not read the host name the certificates were issued for: {}",
+            err
+        )),
+    }
+}
+
+fn registry_credentials(config: &Config) -> CheckResult {
+    let record = match fs::read_to_string(config.homedir.join(REGISTRY_AUTH_RECORD)) {
+        Ok(record) => record,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return CheckResult::Ok,
+        Err(err) => {
+            return CheckResult::Warning(format!(
+                "Could not read the record of registry authentication failures: {}",
+                err
+            ))
+        }
+    };
+    let record: RegistryAuthRecord = match serde_yaml::from_str(&record) {
+        Ok(record) => record,
+        Err(err) => {
+            return CheckResult::Warning(format!(
+                "Could not parse the record of registry authentication failures: {}",
+                err
+            ))
+        }
+    };
+
+    let remedy = match record.failure.as_str() {
+        "badCredentials" => "Check the registry username and password in the deployment.",
+        "expiredToken" => "Renew the registry token and update the deployment with it.",
+        "accessDenied" => "Check that the registry credentials in the deployment grant access to \
+                           the image.",
+        _ => "Check the registry credentials in the deployment.",
+    };
+    CheckResult::Error(format!(
+        "The registry refused to let {} be pulled ({}). {}",
+        record.image, record.message, remedy
+    ))
+}
+
+fn quickstart_certificates(config: &Config) -> CheckResult {
+    if config.certificates.is_some() {
+        return CheckResult::Ok;
+    }
+
+    let expiry = match read_quickstart_ca_record(&config.homedir) {
+        Some(expires_at) => format!(
+            " The quick start CA expires on {} and is generated again before then, after \
+             which downstream devices have to be given the new root.",
+            expires_at.format("%Y-%m-%d")
+        ),
+        None => String::new(),
+    };
+    CheckResult::Warning(format!(
+        "The device uses quick start certificates, which are not meant for production. \
+         Configure the certificates section of config.yaml with production certificates.{}",
+        expiry
+    ))
+}
+
+fn config_deprecations(config: &Config) -> CheckResult {
+    let record = match fs::read_to_string(config.homedir.join(DEPRECATIONS_RECORD)) {
+        Ok(record) => record,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return CheckResult::Ok,
+        Err(err) => {
+            return CheckResult::Warning(format!(
+                "Could not read the record of deprecated settings: {}",
+                err
+            ))
+        }
+    };
+    let deprecations: Vec<Deprecation> = match serde_yaml::from_str(&record) {
+        Ok(deprecations) => deprecations,
+        Err(err) => {
+            return CheckResult::Warning(format!(
+                "Could not parse the record of deprecated settings: {}",
+                err
+            ))
+        }
+    };
+    if deprecations.is_empty() {
+        return CheckResult::Ok;
+    }
+
+    let notices: Vec<String> = deprecations
+        .iter()
+        .map(|deprecation| deprecation.to_string())
+        .collect();
+    CheckResult::Warning(format!(
+        "config.yaml uses deprecated settings, as of the last start of the daemon. {} Update \
+         config.yaml and restart the daemon.",
+        notices.join(" ")
+    ))
+}
+
+// Devices provisioned by DPS can only start offline with the credentials it
+// gave them the last time they were online.
+fn offline_credentials(config: &Config) -> CheckResult {
+    if !config.offline {
+        return CheckResult::Skipped("The device is not set up to run offline.".to_string());
+    }
+    match config.provisioning {
+        Some(ref provisioning) if provisioning.source == "dps" => (),
+        _ => return CheckResult::Ok,
+    }
+
+    if config.homedir.join(PROVISIONING_BACKUP).exists() {
+        CheckResult::Ok
+    } else {
+        CheckResult::Error(
+            "The device is set up to run offline, but has no device credentials from DPS to \
+             run with. Start the IoT Edge daemon online once, so that DPS provisions the device."
+                .to_string(),
+        )
+    }
+}
+
+fn disk_usage(config: &Config) -> CheckResult {
+    let record = match fs::read_to_string(config.homedir.join(DISK_USAGE_RECORD)) {
+        Ok(record) => record,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return CheckResult::Skipped(
+                "The IoT Edge daemon has not recorded the disk usage yet.".to_string(),
+            )
+        }
+        Err(err) => {
+            return CheckResult::Warning(format!(
+                "Could not read the record of the disk usage: {}",
+                err
+            ))
+        }
+    };
+    let usage: DiskUsage = match serde_yaml::from_str(&record) {
+        Ok(usage) => usage,
+        Err(err) => {
+            return CheckResult::Warning(format!(
+                "Could not parse the record of the disk usage: {}",
+                err
+            ))
+        }
+    };
+
+    let mut warnings = Vec::new();
+    match (usage.total(), usage.available()) {
+        (Some(total), Some(available))
+            if total > 0 && available * 100 / total < MIN_FREE_DISK_PERCENT =>
+        {
+            warnings.push(format!(
+                "Only {} of {} is free on the volume holding {}. Images take up {}, the \
+                 writable layers of the modules {}, volumes {} and the state of the daemon {}. \
+                 Remove the images and volumes no module uses.",
+                megabytes(available),
+                megabytes(total),
+                config.homedir.display(),
+                megabytes(usage.images()),
+                megabytes(usage.modules().iter().map(|m| m.writable_layer()).sum()),
+                megabytes(usage.volumes().iter().filter_map(|v| v.size()).sum()),
+                megabytes(usage.daemon_state()),
+            ));
+        }
+        _ => (),
+    }
+    for module in usage.modules() {
+        match (module.quota(), module.quota_used_percent()) {
+            (Some(quota), Some(percent)) if percent >= MAX_QUOTA_USED_PERCENT => {
+                warnings.push(format!(
+                    "The writable layer of module {} takes up {} of its {} quota.",
+                    module.name(),
+                    megabytes(module.writable_layer()),
+                    megabytes(quota)
+                ));
+            }
+            _ => (),
+        }
+    }
+
+    if warnings.is_empty() {
+        CheckResult::Ok
+    } else {
+        CheckResult::Warning(format!(
+            "{} As of the last time the daemon measured the disk usage.",
+            warnings.join(" ")
+        ))
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{} MB", bytes / (1024 * 1024))
+}
+
+// The record is only there once the daemon has started in quick start mode.
+fn read_quickstart_ca_record(homedir: &Path) -> Option<DateTime<FixedOffset>> {
+    let record = fs::read_to_string(homedir.join(QUICKSTART_CA_RECORD)).ok()?;
+    let record: QuickstartCaRecord = serde_yaml::from_str(&record).ok()?;
+    DateTime::parse_from_rfc3339(&record.expires_at).ok()
+}
+
+fn read_hostname_record(homedir: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(homedir.join(HOSTNAME_RECORD)) {
+        Ok(hostname) => Ok(Some(hostname.trim().to_string())),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::ModuleDiskUsage;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn config(hostname: &str, homedir: PathBuf) -> Config {
+        Config {
+            hostname: hostname.to_string(),
+            homedir,
+            certificates: None,
+            provisioning: None,
+            offline: false,
+        }
+    }
+
+    #[test]
+    fn config_hostname_must_fit_in_common_name() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        assert_eq!(CheckResult::Ok, config_hostname(&config("gateway", homedir.clone())));
+
+        let long = format!("{}.{}", "a".repeat(40), "b".repeat(40));
+        match config_hostname(&config(&long, homedir.clone())) {
+            CheckResult::Error(_) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match config_hostname(&config("-gateway", homedir)) {
+            CheckResult::Error(_) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn certificates_hostname_compares_record() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        match certificates_hostname(&config("gateway", homedir.clone())) {
+            CheckResult::Skipped(_) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        fs::create_dir_all(homedir.join("cache")).unwrap();
+        fs::write(homedir.join(HOSTNAME_RECORD), "Gateway").unwrap();
+        assert_eq!(
+            CheckResult::Ok,
+            certificates_hostname(&config("gateway", homedir.clone()))
+        );
+        match certificates_hostname(&config("renamed", homedir.clone())) {
+            CheckResult::Warning(_) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn registry_credentials_reports_recorded_failure() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        assert_eq!(
+            CheckResult::Ok,
+            registry_credentials(&config("gateway", homedir.clone()))
+        );
+
+        fs::create_dir_all(homedir.join("cache")).unwrap();
+        fs::write(
+            homedir.join(REGISTRY_AUTH_RECORD),
+            r#"{"image":"sensor:1.0","failure":"expiredToken","message":"unauthorized"}"#,
+        ).unwrap();
+        match registry_credentials(&config("gateway", homedir.clone())) {
+            CheckResult::Error(message) => {
+                assert!(message.contains("sensor:1.0"));
+                assert!(message.contains("Renew the registry token"));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn quickstart_certificates_are_warned_about() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        match quickstart_certificates(&config("gateway", homedir.clone())) {
+            CheckResult::Warning(message) => assert!(!message.contains("expires")),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        fs::create_dir_all(homedir.join("cache")).unwrap();
+        fs::write(
+            homedir.join(QUICKSTART_CA_RECORD),
+            r#"{"expiresAt":"2019-06-30T12:00:00+00:00"}"#,
+        ).unwrap();
+        match quickstart_certificates(&config("gateway", homedir.clone())) {
+            CheckResult::Warning(message) => assert!(message.contains("expires on 2019-06-30")),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let mut configured = config("gateway", homedir.clone());
+        configured.certificates = Some(serde_yaml::Value::Null);
+        assert_eq!(CheckResult::Ok, quickstart_certificates(&configured));
+    }
+
+    #[test]
+    fn deprecated_settings_are_warned_about() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        assert_eq!(
+            CheckResult::Ok,
+            config_deprecations(&config("gateway", homedir.clone()))
+        );
+
+        fs::create_dir_all(homedir.join("cache")).unwrap();
+        fs::write(
+            homedir.join(DEPRECATIONS_RECORD),
+            concat!(
+                r#"[{"feature":"moby_runtime.docker_uri","replacement":"moby_runtime.uri","#,
+                r#""removal":"1.2.0"}]"#
+            ),
+        ).unwrap();
+        match config_deprecations(&config("gateway", homedir.clone())) {
+            CheckResult::Warning(message) => assert!(message.contains(
+                "moby_runtime.docker_uri is deprecated and will be removed in 1.2.0. Use \
+                 moby_runtime.uri instead."
+            )),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn offline_devices_need_cached_credentials() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        let mut offline = config("gateway", homedir.clone());
+        match offline_credentials(&offline) {
+            CheckResult::Skipped(_) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        offline.offline = true;
+        assert_eq!(CheckResult::Ok, offline_credentials(&offline));
+
+        offline.provisioning = Some(ProvisioningConfig {
+            source: "dps".to_string(),
+        });
+        match offline_credentials(&offline) {
+            CheckResult::Error(_) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        fs::create_dir_all(homedir.join("cache")).unwrap();
+        fs::write(
+            homedir.join(PROVISIONING_BACKUP),
+            r#"{"device_id":"gateway","hub_name":"hub.azure-devices.net"}"#,
+        ).unwrap();
+        assert_eq!(CheckResult::Ok, offline_credentials(&offline));
+    }
+
+    #[test]
+    fn low_disk_space_and_full_quotas_are_warned_about() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        match disk_usage(&config("gateway", homedir.clone())) {
+            CheckResult::Skipped(_) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        fs::create_dir_all(homedir.join("cache")).unwrap();
+        let gb = 1024 * 1024 * 1024;
+        let record = |module: ModuleDiskUsage, available: u64| {
+            let usage = DiskUsage::new()
+                .with_images(4 * gb)
+                .with_modules(vec![module])
+                .with_disk_space(32 * gb, available);
+            fs::write(
+                homedir.join(DISK_USAGE_RECORD),
+                serde_yaml::to_string(&usage).unwrap(),
+            ).unwrap();
+        };
+
+        record(ModuleDiskUsage::new("edgeHub".to_string(), gb), 8 * gb);
+        assert_eq!(
+            CheckResult::Ok,
+            disk_usage(&config("gateway", homedir.clone()))
+        );
+
+        record(
+            ModuleDiskUsage::new("edgeHub".to_string(), gb).with_quota(gb),
+            gb,
+        );
+        match disk_usage(&config("gateway", homedir.clone())) {
+            CheckResult::Warning(message) => {
+                assert!(message.contains("Only 1024 MB of 32768 MB is free"));
+                assert!(message.contains("Images take up 4096 MB"));
+                assert!(message.contains("module edgeHub takes up 1024 MB of its 1024 MB quota"));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn failed_checks_fail_the_command() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let path = tmp_dir.path().join("config.yaml");
+        fs::write(&path, "hostname: \"-gateway\"\nhomedir: \"/nonexistent\"\n").unwrap();
+
+        let mut output = Vec::new();
+        let err = Check::new(path.clone(), &mut output).run().unwrap_err();
+        match err.kind() {
+            ErrorKind::ChecksFailed(1) => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("[error] config.yaml has a valid host name"));
+        assert!(output.contains("[skipped] certificates are issued"));
+    }
+
+    #[test]
+    fn checks_are_listed_with_their_ids() {
+        let mut output = Vec::new();
+        Check::new(PathBuf::from("/nonexistent"), &mut output)
+            .with_list(true)
+            .run()
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let ids: Vec<_> = output
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(check_ids(), ids);
+        assert!(output.contains("config.yaml has a valid host name"));
+    }
+
+    #[test]
+    fn checks_can_be_selected_and_skipped() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let path = tmp_dir.path().join("config.yaml");
+        fs::write(&path, "hostname: \"-gateway\"\nhomedir: \"/nonexistent\"\n").unwrap();
+
+        let mut output = Vec::new();
+        Check::new(path.clone(), &mut output)
+            .with_only(vec![
+                "config-hostname".to_string(),
+                "registry-credentials".to_string(),
+            ]).with_skip(vec!["config-hostname".to_string()])
+            .run()
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            "[ok] container registries accept the credentials in the deployment\n",
+            output
+        );
+
+        for (only, skip, argument) in vec![
+            (vec!["nonexistent".to_string()], vec![], "only"),
+            (vec![], vec!["nonexistent".to_string()], "skip"),
+        ] {
+            let err = Check::new(path.clone(), Vec::new())
+                .with_only(only)
+                .with_skip(skip)
+                .run()
+                .unwrap_err();
+            match err.kind() {
+                ErrorKind::InvalidArgument(name) if *name == argument => (),
+                kind => panic!("unexpected error {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn warnings_fail_the_command_with_their_own_kind() {
+        let tmp_dir = TempDir::new("check").unwrap();
+        let homedir = tmp_dir.path().to_path_buf();
+        fs::create_dir_all(homedir.join("cache")).unwrap();
+        fs::write(homedir.join(HOSTNAME_RECORD), "renamed").unwrap();
+        let path = homedir.join("config.yaml");
+        fs::write(
+            &path,
+            format!("hostname: gateway\nhomedir: \"{}\"\n", homedir.display()),
+        ).unwrap();
+
+        let err = Check::new(path, Vec::new())
+            .with_only(vec!["certificates-hostname".to_string()])
+            .run()
+            .unwrap_err();
+        match err.kind() {
+            ErrorKind::ChecksWarned(1) => (),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert_eq!(1, err.exit_code());
+    }
+}
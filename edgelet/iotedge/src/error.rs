@@ -4,7 +4,8 @@ use std::fmt;
 use std::fmt::Display;
 use std::io;
 
-use edgelet_http_mgmt::Error as HttpMgmtError;
+use edgelet_core::{error_category, ErrorCategory};
+use edgelet_mgmt_client::{classify_error as classify_mgmt_error, Error as MgmtClientError};
 use failure::{Backtrace, Context, Fail};
 use url::ParseError;
 
@@ -25,6 +26,44 @@ pub enum ErrorKind {
     HttpMgmt,
     #[fail(display = "Missing host")]
     NoHost,
+    #[fail(display = "Invalid value for argument {}", _0)]
+    InvalidArgument(&'static str),
+    #[fail(display = "Could not read the IoT Edge daemon config file")]
+    Config,
+    #[fail(display = "{} check(s) failed", _0)]
+    ChecksFailed(usize),
+    #[fail(display = "{} check(s) raised warnings", _0)]
+    ChecksWarned(usize),
+    #[fail(display = "The operation failed")]
+    OperationFailed,
+}
+
+impl ErrorKind {
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// leave it to their cause, such as the error the daemon responded with.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::UrlParse
+            | ErrorKind::NoHost
+            | ErrorKind::InvalidArgument(_)
+            | ErrorKind::Config => Some(ErrorCategory::UserConfig),
+            ErrorKind::OperationFailed => Some(ErrorCategory::Runtime),
+            ErrorKind::ModuleRuntime
+            | ErrorKind::Io
+            | ErrorKind::HttpMgmt
+            | ErrorKind::ChecksFailed(_)
+            | ErrorKind::ChecksWarned(_) => None,
+        }
+    }
+}
+
+/// Categorizes the errors of this crate, and falls back to
+/// `edgelet_mgmt_client::classify_error`.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    match error.downcast_ref::<Error>() {
+        Some(error) => error.kind().category(),
+        None => classify_mgmt_error(error),
+    }
 }
 
 impl Fail for Error {
@@ -51,6 +90,23 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
+
+    /// The code the command exits with when it fails with this error.
+    /// `iotedge check` exits with 1 when the checks only raise warnings, and
+    /// with 2 when a check fails. Other errors exit with the code of their
+    /// category, so that a failure the daemon reports exits the same way
+    /// whichever command ran into it.
+    pub fn exit_code(&self) -> i32 {
+        match *self.kind() {
+            ErrorKind::ChecksWarned(_) => 1,
+            ErrorKind::ChecksFailed(_) => 2,
+            _ => self.category().exit_code(),
+        }
+    }
 }
 
 impl From<ErrorKind> for Error {
@@ -83,10 +139,32 @@ impl From<ParseError> for Error {
     }
 }
 
-impl From<HttpMgmtError> for Error {
-    fn from(error: HttpMgmtError) -> Self {
+impl From<MgmtClientError> for Error {
+    fn from(error: MgmtClientError) -> Self {
         Error {
             inner: error.context(ErrorKind::HttpMgmt),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::{Error as CoreError, ErrorKind as CoreErrorKind};
+
+    use super::*;
+
+    #[test]
+    fn exit_code_follows_the_category_of_the_cause() {
+        let error = Error::from(
+            MgmtClientError::from(CoreError::from(CoreErrorKind::KeyStore))
+                .context(ErrorKind::ModuleRuntime),
+        );
+        assert_eq!(ErrorCategory::Hsm.exit_code(), error.exit_code());
+
+        let error = Error::from(ErrorKind::InvalidArgument("since"));
+        assert_eq!(2, error.exit_code());
+
+        let error = Error::from(io::Error::from(io::ErrorKind::Other));
+        assert_eq!(1, error.exit_code());
+    }
+}
@@ -15,29 +15,45 @@ extern crate chrono_humanize;
 #[macro_use]
 extern crate clap;
 extern crate edgelet_core;
-extern crate edgelet_http_mgmt;
+extern crate edgelet_mgmt_client;
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
 #[macro_use]
 extern crate futures;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_yaml;
 extern crate tabwriter;
+#[cfg(test)]
+extern crate tempdir;
 extern crate tokio;
 extern crate url;
 
 use futures::Future;
 
+mod backup;
+mod check;
 mod error;
 mod list;
 mod logs;
+mod operation;
 mod restart;
+mod restore;
+mod rollback;
 mod unknown;
 mod version;
 
+pub use backup::Backup;
+pub use check::{check_ids, quickstart_warning, Check};
 pub use error::{Error, ErrorKind};
 pub use list::List;
 pub use logs::Logs;
+pub use operation::Operation;
 pub use restart::Restart;
+pub use restore::Restore;
+pub use rollback::Rollback;
 pub use unknown::Unknown;
 pub use version::Version;
 
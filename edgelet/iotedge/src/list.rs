@@ -6,7 +6,9 @@ use std::sync::{Arc, Mutex};
 
 use chrono::{Duration, Utc};
 use chrono_humanize::{Accuracy, HumanTime, Tense};
-use edgelet_core::{Module, ModuleRuntime, ModuleRuntimeState, ModuleStatus};
+use edgelet_core::{
+    ExitReason, LabelSelector, Module, ModuleRuntime, ModuleRuntimeState, ModuleStatus,
+};
 use futures::{Future, Stream};
 use tabwriter::TabWriter;
 
@@ -16,6 +18,7 @@ use Command;
 pub struct List<M, W> {
     runtime: M,
     output: Arc<Mutex<TabWriter<W>>>,
+    selector: LabelSelector,
 }
 
 impl<M, W> List<M, W>
@@ -27,8 +30,15 @@ where
         List {
             runtime,
             output: Arc::new(Mutex::new(tab)),
+            selector: LabelSelector::default(),
         }
     }
+
+    /// Lists only the modules whose labels match `selector`.
+    pub fn with_selector(mut self, selector: LabelSelector) -> Self {
+        self.selector = selector;
+        self
+    }
 }
 
 impl<M, W> Command for List<M, W>
@@ -44,10 +54,12 @@ where
 
     fn execute(&mut self) -> Self::Future {
         let write = self.output.clone();
+        let selector = self.selector.clone();
         let result = self
             .runtime
             .list_with_details()
             .map_err(|e| e.into())
+            .filter(move |&(ref module, _)| selector.matches(&module.labels()))
             .collect()
             .and_then(move |result| {
                 let mut w = write.lock().unwrap();
@@ -84,10 +96,10 @@ fn humanize_state(state: &ModuleRuntimeState) -> String {
         ModuleStatus::Failed => state
             .finished_at()
             .and_then(|time| {
-                state.exit_code().map(|code| {
+                humanize_exit(state).map(|exit| {
                     format!(
                         "Failed ({}) {}",
-                        code,
+                        exit,
                         time_string(&HumanTime::from(Utc::now() - *time), Tense::Past)
                     )
                 })
@@ -104,6 +116,17 @@ fn humanize_state(state: &ModuleRuntimeState) -> String {
     }
 }
 
+// The exit code is only shown when there's nothing more telling to show.
+fn humanize_exit(state: &ModuleRuntimeState) -> Option<String> {
+    match state.exit_reason() {
+        Some(ExitReason::OomKilled) => Some("out of memory".to_string()),
+        Some(ExitReason::RuntimeFailure) => Some("runtime failure".to_string()),
+        Some(ExitReason::Normal) | Some(ExitReason::Error(_)) | None => {
+            state.exit_code().map(|code| code.to_string())
+        }
+    }
+}
+
 fn time_string(ht: &HumanTime, tense: Tense) -> String {
     if *ht <= HumanTime::from(Duration::seconds(20)) {
         ht.to_text_en(Accuracy::Precise, tense)
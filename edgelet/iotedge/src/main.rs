@@ -11,7 +11,7 @@
 #[macro_use]
 extern crate clap;
 extern crate edgelet_core;
-extern crate edgelet_http_mgmt;
+extern crate edgelet_mgmt_client;
 extern crate failure;
 extern crate iotedge;
 extern crate tokio;
@@ -19,11 +19,12 @@ extern crate url;
 
 use std::io;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process;
 
 use clap::{App, AppSettings, Arg, SubCommand};
-use edgelet_core::{LogOptions, LogTail};
-use edgelet_http_mgmt::ModuleClient;
+use edgelet_core::{LabelSelector, LogOptions, LogTail};
+use edgelet_mgmt_client::ModuleClient;
 use failure::Fail;
 use iotedge::*;
 use url::Url;
@@ -33,6 +34,11 @@ const MGMT_URI: &str = "unix:///var/run/iotedge/mgmt.sock";
 #[cfg(windows)]
 const MGMT_URI: &str = "http://localhost:15580";
 
+#[cfg(unix)]
+const CONFIG_FILE: &str = "/etc/iotedge/config.yaml";
+#[cfg(windows)]
+const CONFIG_FILE: &str = "C:\\ProgramData\\iotedge\\config.yaml";
+
 fn main() {
     if let Err(ref error) = run() {
         let stderr = &mut io::stderr();
@@ -45,7 +51,7 @@ fn main() {
                 .unwrap_or_else(|_| panic!(errmsg));
             fail = cause;
         }
-        process::exit(1);
+        process::exit(error.exit_code());
     }
 }
 
@@ -66,7 +72,49 @@ fn run() -> Result<(), Error> {
                 .global(true)
                 .env("IOTEDGE_HOST")
                 .default_value(default_uri),
-        ).subcommand(SubCommand::with_name("list").about("List modules"))
+        ).subcommand(
+            SubCommand::with_name("check")
+                .about("Check the device for common configuration problems")
+                .arg(
+                    Arg::with_name("config-file")
+                        .help("Sets the IoT Edge daemon config file to check")
+                        .long("config-file")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .default_value(CONFIG_FILE),
+                ).arg(
+                    Arg::with_name("list")
+                        .help("Lists the checks with their ids instead of running them")
+                        .long("list"),
+                ).arg(
+                    Arg::with_name("only")
+                        .help("Runs only the checks with these ids")
+                        .long("only")
+                        .takes_value(true)
+                        .value_name("ID")
+                        .use_delimiter(true)
+                        .multiple(true)
+                        .possible_values(&check_ids()),
+                ).arg(
+                    Arg::with_name("skip")
+                        .help("Leaves out the checks with these ids")
+                        .long("skip")
+                        .takes_value(true)
+                        .value_name("ID")
+                        .use_delimiter(true)
+                        .multiple(true)
+                        .possible_values(&check_ids()),
+                ),
+        ).subcommand(
+            SubCommand::with_name("list").about("List modules").arg(
+                Arg::with_name("selector")
+                    .help("Lists only the modules with these labels, such as tier=web,solution!=a")
+                    .short("l")
+                    .long("selector")
+                    .takes_value(true)
+                    .value_name("SELECTOR"),
+            ),
+        )
         .subcommand(
             SubCommand::with_name("restart")
                 .about("Restart a module")
@@ -96,7 +144,60 @@ fn run() -> Result<(), Error> {
                         .help("Follow output log")
                         .short("f")
                         .long("follow"),
+                ).arg(
+                    Arg::with_name("limit-bytes")
+                        .help("Stop once this many bytes of the log were shown")
+                        .long("limit-bytes")
+                        .takes_value(true)
+                        .value_name("BYTES"),
+                ).arg(
+                    Arg::with_name("grep")
+                        .help("Only show the lines of the log that match this regular expression")
+                        .long("grep")
+                        .takes_value(true)
+                        .value_name("REGEX"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("rollback")
+                .about("Restore the modules of a previous deployment")
+                .arg(
+                    Arg::with_name("to")
+                        .help("Number of deployments to go back")
+                        .long("to")
+                        .takes_value(true)
+                        .value_name("NUM")
+                        .default_value("1"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("operation")
+                .about("Follow the progress of a long running operation")
+                .arg(
+                    Arg::with_name("ID")
+                        .help("ID of the operation")
+                        .required(true)
+                        .index(1),
                 ),
+        ).subcommand(
+            SubCommand::with_name("backup")
+                .about("Back up the state of the IoT Edge daemon in an encrypted archive")
+                .arg(
+                    Arg::with_name("output")
+                        .help("File to write the backup to")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .required(true),
+                ).arg(passphrase_arg()),
+        ).subcommand(
+            SubCommand::with_name("restore")
+                .about("Restore the state of the IoT Edge daemon from a backup")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("Backup to restore")
+                        .required(true)
+                        .index(1),
+                ).arg(passphrase_arg()),
         ).subcommand(SubCommand::with_name("version").about("Show the version information"))
         .get_matches();
 
@@ -109,7 +210,36 @@ fn run() -> Result<(), Error> {
     let mut tokio_runtime = tokio::runtime::Runtime::new()?;
 
     match matches.subcommand() {
-        ("list", Some(_args)) => tokio_runtime.block_on(List::new(runtime, io::stdout()).execute()),
+        ("check", Some(args)) => {
+            let config_file = PathBuf::from(args.value_of("config-file").unwrap());
+            let ids = |name| {
+                args.values_of(name)
+                    .map_or_else(Vec::new, |ids| ids.map(str::to_string).collect())
+            };
+            tokio_runtime.block_on(
+                Check::new(config_file, io::stdout())
+                    .with_list(args.is_present("list"))
+                    .with_only(ids("only"))
+                    .with_skip(ids("skip"))
+                    .execute(),
+            )
+        }
+        ("list", Some(args)) => {
+            let selector = args
+                .value_of("selector")
+                .map_or_else(|| Ok(LabelSelector::default()), str::parse)
+                .map_err(|_| Error::from(ErrorKind::InvalidArgument("selector")))?;
+            // The warning goes to stderr, so that it doesn't get in the way of
+            // scripts reading the list.
+            if let Some(warning) = quickstart_warning(Path::new(CONFIG_FILE)) {
+                writeln!(io::stderr(), "Warning: {}", warning)?;
+            }
+            tokio_runtime.block_on(
+                List::new(runtime, io::stdout())
+                    .with_selector(selector)
+                    .execute(),
+            )
+        }
         ("restart", Some(args)) => tokio_runtime.block_on(
             Restart::new(
                 args.value_of("MODULE").unwrap().to_string(),
@@ -124,10 +254,48 @@ fn run() -> Result<(), Error> {
                 .value_of("tail")
                 .and_then(|a| a.parse::<LogTail>().ok())
                 .unwrap_or_default();
-            let options = LogOptions::new().with_follow(follow).with_tail(tail);
+            let mut options = LogOptions::new().with_follow(follow).with_tail(tail);
+            if args.is_present("limit-bytes") {
+                let limit_bytes = value_t!(args, "limit-bytes", u64)
+                    .map_err(|_| Error::from(ErrorKind::InvalidArgument("limit-bytes")))?;
+                options = options.with_limit_bytes(limit_bytes);
+            }
+            if let Some(grep) = args.value_of("grep") {
+                options = options.with_grep(grep.to_string());
+            }
             tokio_runtime.block_on(Logs::new(id, options, runtime).execute())
         }
+        ("rollback", Some(args)) => {
+            let to = value_t!(args, "to", i32)
+                .map_err(|_| Error::from(ErrorKind::InvalidArgument("to")))?;
+            tokio_runtime.block_on(Rollback::new(to, runtime, io::stdout()).execute())
+        }
+        ("operation", Some(args)) => tokio_runtime.block_on(
+            Operation::new(args.value_of("ID").unwrap().to_string(), runtime, io::stdout())
+                .execute(),
+        ),
+        ("backup", Some(args)) => {
+            let file = PathBuf::from(args.value_of("output").unwrap());
+            let passphrase = args.value_of("passphrase").unwrap().to_string();
+            tokio_runtime.block_on(Backup::new(file, passphrase, runtime, io::stdout()).execute())
+        }
+        ("restore", Some(args)) => {
+            let file = PathBuf::from(args.value_of("FILE").unwrap());
+            let passphrase = args.value_of("passphrase").unwrap().to_string();
+            tokio_runtime.block_on(Restore::new(file, passphrase, runtime, io::stdout()).execute())
+        }
         ("version", Some(_args)) => tokio_runtime.block_on(Version::new().execute()),
         (command, _) => tokio_runtime.block_on(Unknown::new(command.to_string()).execute()),
     }
 }
+
+fn passphrase_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("passphrase")
+        .help("Passphrase the backup is encrypted with")
+        .long("passphrase")
+        .takes_value(true)
+        .value_name("PASSPHRASE")
+        .env("IOTEDGE_BACKUP_PASSPHRASE")
+        .hide_env_values(true)
+        .required(true)
+}
@@ -0,0 +1,129 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use edgelet_mgmt_client::{ModuleClient, Operation as OperationProgress};
+use failure::Fail;
+use futures::future::{self, Either, Loop};
+use futures::Future;
+use tokio::timer::Delay;
+
+use error::{Error, ErrorKind};
+use Command;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const BAR_WIDTH: i64 = 30;
+
+/// Follows a long running operation of the daemon, such as a deployment
+/// being applied, with a progress bar until it finishes.
+pub struct Operation<W> {
+    id: String,
+    client: ModuleClient,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W> Operation<W> {
+    pub fn new(id: String, client: ModuleClient, output: W) -> Self {
+        Operation {
+            id,
+            client,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<W> Command for Operation<W>
+where
+    W: 'static + Write + Send,
+{
+    type Future = Box<Future<Item = (), Error = Error> + Send>;
+
+    fn execute(&mut self) -> Self::Future {
+        let id = self.id.clone();
+        let client = self.client.clone();
+        let write = self.output.clone();
+        let result = future::loop_fn(None, move |last: Option<String>| {
+            let write = write.clone();
+            client
+                .operation(&id)
+                .map_err(Error::from)
+                .and_then(move |operation| {
+                    let line = render(&operation);
+                    let mut w = write.lock().unwrap();
+                    // the bar is drawn again over itself as it moves
+                    if last.as_ref() != Some(&line) {
+                        write!(w, "\r{}", line)?;
+                        w.flush()?;
+                    }
+                    if operation.status() == "running" {
+                        return Ok(Either::A(
+                            Delay::new(Instant::now() + POLL_INTERVAL)
+                                .map(move |_| Loop::Continue(Some(line)))
+                                .map_err(|err| Error::from(err.context(ErrorKind::Io))),
+                        ));
+                    }
+                    writeln!(w)?;
+                    match operation.error() {
+                        Some(error) => {
+                            writeln!(w, "{}", error)?;
+                            Err(Error::from(ErrorKind::OperationFailed))
+                        }
+                        None => Ok(Either::B(future::ok(Loop::Break(())))),
+                    }
+                }).and_then(|next| next)
+        });
+        Box::new(result)
+    }
+}
+
+// Renders the progress of an operation as a bar, followed by what the
+// operation is doing.
+fn render(operation: &OperationProgress) -> String {
+    let bar: String = (0..BAR_WIDTH)
+        .map(|i| {
+            if i * 100 < operation.percent() * BAR_WIDTH {
+                '#'
+            } else {
+                '-'
+            }
+        }).collect();
+    let step = match operation.status() {
+        "running" => operation.step().unwrap_or(""),
+        status => status,
+    };
+    format!("[{}] {:>3}% {}", bar, operation.percent(), step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(status: &str, percent: i64) -> OperationProgress {
+        OperationProgress::new(
+            "1".to_string(),
+            "deployment".to_string(),
+            status.to_string(),
+            percent,
+            "2018-10-01T00:00:00Z".to_string(),
+        )
+    }
+
+    #[test]
+    fn progress_is_rendered_as_a_bar() {
+        let running = operation("running", 50).with_step("create module sensor".to_string());
+        assert_eq!(
+            "[###############---------------]  50% create module sensor",
+            render(&running)
+        );
+        assert_eq!(
+            "[------------------------------]   0% ",
+            render(&operation("running", 0))
+        );
+        assert_eq!(
+            "[##############################] 100% succeeded",
+            render(&operation("succeeded", 100))
+        );
+    }
+}
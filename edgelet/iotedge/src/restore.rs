@@ -0,0 +1,59 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use edgelet_mgmt_client::ModuleClient;
+use futures::{future, Future};
+
+use error::Error;
+use Command;
+
+pub struct Restore<W> {
+    file: PathBuf,
+    passphrase: String,
+    client: ModuleClient,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W> Restore<W> {
+    pub fn new(file: PathBuf, passphrase: String, client: ModuleClient, output: W) -> Self {
+        Restore {
+            file,
+            passphrase,
+            client,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<W> Command for Restore<W>
+where
+    W: 'static + Write + Send,
+{
+    type Future = Box<Future<Item = (), Error = Error> + Send>;
+
+    fn execute(&mut self) -> Self::Future {
+        let backup = match fs::read(&self.file) {
+            Ok(backup) => backup,
+            Err(err) => return Box::new(future::err(Error::from(err))),
+        };
+
+        let write = self.output.clone();
+        let result = self
+            .client
+            .restore(&backup, &self.passphrase)
+            .map_err(Error::from)
+            .and_then(move |restored| {
+                let mut w = write.lock().unwrap();
+                for name in restored {
+                    writeln!(w, "Restored {}", name)?;
+                }
+                writeln!(w, "Restart the IoT Edge daemon for it to use the restored state.")?;
+                Ok(())
+            });
+        Box::new(result)
+    }
+}
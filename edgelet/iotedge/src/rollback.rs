@@ -0,0 +1,48 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use edgelet_mgmt_client::ModuleClient;
+use futures::Future;
+
+use error::Error;
+use Command;
+
+pub struct Rollback<W> {
+    to: i32,
+    client: ModuleClient,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W> Rollback<W> {
+    pub fn new(to: i32, client: ModuleClient, output: W) -> Self {
+        Rollback {
+            to,
+            client,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+}
+
+impl<W> Command for Rollback<W>
+where
+    W: 'static + Write + Send,
+{
+    type Future = Box<Future<Item = (), Error = Error> + Send>;
+
+    fn execute(&mut self) -> Self::Future {
+        let to = self.to;
+        let write = self.output.clone();
+        let result = self
+            .client
+            .rollback(to)
+            .map_err(Error::from)
+            .and_then(move |_| {
+                let mut w = write.lock().unwrap();
+                writeln!(w, "Rolled back to deployment {}", to)?;
+                Ok(())
+            });
+        Box::new(result)
+    }
+}
@@ -6,6 +6,7 @@ use edgelet_docker::DockerConfig;
 
 use error::Error;
 use logging;
+use secrets::SecretResolvers;
 use settings::Settings;
 
 pub fn create_base_app<'a, 'b>() -> App<'a, 'b> {
@@ -59,7 +60,9 @@ pub fn init_common<'a>() -> Result<(Settings<DockerConfig>, ArgMatches<'a>), Err
                 None
             });
 
-        Settings::<DockerConfig>::new(config_file)?
+        let mut settings = Settings::<DockerConfig>::new(config_file)?;
+        settings.resolve_secrets(&SecretResolvers::new())?;
+        settings
     };
 
     Ok((settings, matches))
@@ -74,6 +77,7 @@ pub fn init() -> Result<Settings<DockerConfig>, Error> {
     } else {
         logging::init();
     }
+    logging::set_max_level(settings.tuning().log_level());
 
     log_banner();
 
@@ -84,12 +88,16 @@ pub fn init() -> Result<Settings<DockerConfig>, Error> {
 pub fn init() -> Result<Settings<DockerConfig>, Error> {
     logging::init();
     log_banner();
-    init_common().map(|(settings, _)| settings)
+    let (settings, _) = init_common()?;
+    logging::set_max_level(settings.tuning().log_level());
+    Ok(settings)
 }
 
 #[cfg(target_os = "windows")]
 pub fn init_win_svc() -> Result<Settings<DockerConfig>, Error> {
     logging::init_win_log();
     log_banner();
-    init_common().map(|(settings, _)| settings)
+    let (settings, _) = init_common()?;
+    logging::set_max_level(settings.tuning().log_level());
+    Ok(settings)
 }
@@ -0,0 +1,167 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use edgelet_core::crypto::{Certificate, CreateCertificate, GetTrustBundle};
+use edgelet_core::{
+    CaRotation, Error as CoreError, ErrorKind as CoreErrorKind, Outbox, TrustBundleVersions,
+    DEFAULT_ROTATION_OVERLAP_HOURS,
+};
+use failure::Fail;
+use futures::future::Either;
+use futures::sync::oneshot::Receiver;
+use futures::{future, Future};
+use tokio::timer::Delay;
+
+use error::Error;
+use settings::Certificates;
+use {destroy_workload_ca, prepare_workload_ca};
+
+/// This is the name of the file in the cache directory that records the Edge
+/// CA and where a rotation of it is at.
+const EDGE_CA_ROTATION_FILENAME: &str = "ca_rotation.json";
+
+/// This is the name of the file in the settings directory that records the
+/// versions of the trust bundle served to modules.
+const EDGE_TRUST_BUNDLE_VERSIONS_FILENAME: &str = "trust_bundle_versions.json";
+
+/// Detects a rotation of the Edge CA since the last start. Downstream devices
+/// only trust the roots they were given before, so those are served in the
+/// trust bundle along with the new ones until the overlap configured in the
+/// certificates settings ends. The workload CA is issued again under the new
+/// root on demand, or when the overlap ends at the latest.
+pub fn check_ca_rotation<C>(
+    subdir_path: &Path,
+    certificates: Option<&Certificates>,
+    crypto: &C,
+) -> Result<CaRotation, Error>
+where
+    C: 'static + CreateCertificate + GetTrustBundle + Clone + Send + Sync,
+{
+    let overlap = certificates.map_or_else(
+        || Duration::from_secs(DEFAULT_ROTATION_OVERLAP_HOURS * 3600),
+        Certificates::rotation_overlap,
+    );
+
+    fs::create_dir_all(subdir_path)?;
+    let reissue_crypto = crypto.clone();
+    let rotation = CaRotation::load(subdir_path.join(EDGE_CA_ROTATION_FILENAME), overlap)?
+        .with_reissue(move || {
+            destroy_workload_ca(&reissue_crypto)
+                .and_then(|_| prepare_workload_ca(&reissue_crypto))
+                .map_err(|err| CoreError::from(err.context(CoreErrorKind::CaRotation)))
+        });
+
+    let trust_bundle = crypto.get_trust_bundle()?.pem()?;
+    let trust_bundle = String::from_utf8_lossy(trust_bundle.as_ref()).into_owned();
+    // Without a configured Edge CA, the daemon issues its own under the root
+    // it generates, so the root stands in for it.
+    let edge_ca = match certificates {
+        Some(certificates) => fs::read_to_string(certificates.device_ca_cert())?,
+        None => trust_bundle.clone(),
+    };
+
+    if rotation.observe(&edge_ca, &trust_bundle)? {
+        warn!(
+            "The Edge CA has changed since the last start. Downstream devices must be given \
+             the new root before the previous roots stop being trusted."
+        );
+    }
+    Ok(rotation)
+}
+
+/// Numbers the versions of the trust bundle served to modules, starting with
+/// the one served now, so that a change since the last start is reported
+/// through `outbox` without waiting for a module to fetch the trust bundle.
+pub fn check_trust_bundle_version<C>(
+    subdir_path: &Path,
+    crypto: &C,
+    rotation: &CaRotation,
+    outbox: &Outbox,
+) -> Result<TrustBundleVersions, Error>
+where
+    C: GetTrustBundle,
+{
+    fs::create_dir_all(subdir_path)?;
+    let versions_path = subdir_path.join(EDGE_TRUST_BUNDLE_VERSIONS_FILENAME);
+    let versions = TrustBundleVersions::load(versions_path)?.with_outbox(outbox.clone());
+
+    let trust_bundle = crypto.get_trust_bundle()?.pem()?;
+    let trust_bundle = String::from_utf8_lossy(trust_bundle.as_ref());
+    let version = versions.observe(&rotation.trust_bundle(&trust_bundle)?)?;
+    info!("Serving version {} of the trust bundle.", version);
+    Ok(versions)
+}
+
+/// Ends the overlap of a rotation that is under way once it is over, issuing
+/// the workload CA again if that hasn't been done on demand.
+pub fn watch_ca_rotation(
+    rotation: CaRotation,
+    shutdown: Receiver<()>,
+) -> impl Future<Item = (), Error = Error> {
+    let remaining = match rotation.overlap_remaining() {
+        Some(remaining) => remaining,
+        None => return Either::B(future::ok(())),
+    };
+
+    let expiry = Delay::new(Instant::now() + remaining).then(move |_| {
+        if let Err(err) = rotation.expire() {
+            warn!("Could not end the rotation of the Edge CA: {}", err);
+        }
+        Ok::<_, Error>(())
+    });
+
+    // Swallow any errors from the shutdown signal
+    let shutdown = shutdown.then(|_| Ok(()));
+
+    Either::A(shutdown.select(expiry).then(|result| match result {
+        Ok(((), _)) => Ok(()),
+        Err((e, _)) => Err(e),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use edgelet_core::RotationState;
+    use futures::sync::oneshot;
+    use tokio;
+
+    use super::*;
+
+    const OLD_CA: &str = "-----BEGIN CERTIFICATE-----\nb2xk\n-----END CERTIFICATE-----\n";
+    const NEW_CA: &str = "-----BEGIN CERTIFICATE-----\nbmV3\n-----END CERTIFICATE-----\n";
+
+    #[test]
+    fn nothing_is_watched_without_a_rotation() {
+        let rotation = CaRotation::new(Duration::from_secs(3600));
+        rotation.observe(OLD_CA, OLD_CA).unwrap();
+
+        let (_tx, rx) = oneshot::channel();
+        watch_ca_rotation(rotation, rx).wait().unwrap();
+    }
+
+    #[test]
+    fn workload_ca_is_reissued_when_the_overlap_ends() {
+        let reissued = Arc::new(AtomicUsize::new(0));
+        let count = reissued.clone();
+        let rotation = CaRotation::new(Duration::from_secs(0)).with_reissue(move || {
+            count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        rotation.observe(OLD_CA, OLD_CA).unwrap();
+        rotation.observe(NEW_CA, NEW_CA).unwrap();
+
+        let (_tx, rx) = oneshot::channel();
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(watch_ca_rotation(rotation.clone(), rx))
+            .unwrap();
+        assert_eq!(1, reissued.load(Ordering::SeqCst));
+        assert_eq!(RotationState::Stable, rotation.state().unwrap());
+    }
+}
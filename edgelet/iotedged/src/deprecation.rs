@@ -0,0 +1,60 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use edgelet_core::Deprecation;
+use serde_json;
+
+use error::Error;
+
+/// This is the name of the file in the cache directory that records the
+/// deprecated settings the config file uses, which `iotedge check` reads.
+const EDGE_DEPRECATIONS_FILENAME: &str = "deprecations.json";
+
+/// Warns about the deprecated settings in the config file, with the release
+/// they are removed in, and records them for `iotedge check`. The record is
+/// removed once the config file no longer uses any.
+pub fn record_deprecations(subdir_path: &Path, deprecations: &[Deprecation]) -> Result<(), Error> {
+    for deprecation in deprecations {
+        warn!("The config file uses a deprecated setting: {}", deprecation);
+    }
+
+    let path = subdir_path.join(EDGE_DEPRECATIONS_FILENAME);
+    if deprecations.is_empty() {
+        return match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Error::from(err)),
+        };
+    }
+
+    fs::create_dir_all(subdir_path)?;
+    fs::write(&path, serde_json::to_string(deprecations)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn record_is_written_and_removed() {
+        let tmp_dir = TempDir::new("deprecation").unwrap();
+        let path = tmp_dir.path().join(EDGE_DEPRECATIONS_FILENAME);
+        let deprecation = Deprecation::new("moby_runtime.docker_uri", "1.2.0")
+            .with_replacement("moby_runtime.uri");
+
+        record_deprecations(tmp_dir.path(), &[deprecation.clone()]).unwrap();
+        let recorded: Vec<Deprecation> =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(vec![deprecation], recorded);
+
+        record_deprecations(tmp_dir.path(), &[]).unwrap();
+        assert!(!path.exists());
+        record_deprecations(tmp_dir.path(), &[]).unwrap();
+    }
+}
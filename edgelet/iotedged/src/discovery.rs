@@ -0,0 +1,161 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket as StdUdpSocket};
+use std::sync::Arc;
+
+use edgelet_core::response_signing::ResponseSigner;
+use edgelet_core::{Hostname, Responder};
+use failure;
+use futures::future::{self, Either, Loop};
+use futures::sync::oneshot::Receiver;
+use futures::Future;
+#[cfg(unix)]
+use net2::unix::UnixUdpBuilderExt;
+use net2::UdpBuilder;
+use tokio::net::UdpSocket;
+use tokio::reactor::Handle;
+
+use error::Error;
+use settings::Discovery;
+
+const MDNS_PORT: u16 = 5353;
+
+/// The TXT entry carrying the fingerprint of the workload signing key.
+const SIGNING_TXT_KEY: &str = "workload-signing";
+
+/// Large enough for any message on a link with jumbo frames (RFC 6762).
+const MAX_MESSAGE_SIZE: usize = 9000;
+
+fn mdns_group() -> Ipv4Addr {
+    Ipv4Addr::new(224, 0, 0, 251)
+}
+
+/// Advertises the gateway on the local network over multicast DNS until
+/// `shutdown` fires.
+///
+/// When workload API responses are signed, the fingerprint of the key they
+/// are verified with is advertised along with the services.
+///
+/// An invalid hostname or service type in the settings is an error. Other
+/// failures only stop the advertisement, since leaf devices can still be
+/// pointed at the gateway by hand.
+pub fn start_discovery(
+    discovery: Option<&Discovery>,
+    hostname: &str,
+    signer: Option<&ResponseSigner>,
+    shutdown: Receiver<()>,
+) -> Result<Box<Future<Item = (), Error = failure::Error> + Send>, Error> {
+    let discovery = match discovery {
+        Some(discovery) if discovery.enabled() => discovery,
+        _ => return Ok(Box::new(future::ok(()))),
+    };
+
+    let hostname = Hostname::new(hostname)?;
+    let address = match discovery.address().map_or_else(local_address, Ok) {
+        Ok(address) => address,
+        Err(err) => {
+            warn!("Could not determine the address to advertise: {}", err);
+            return Ok(Box::new(future::ok(())));
+        }
+    };
+    let mut responder = Responder::new(&hostname, address, discovery.services())?;
+    if let Some(signer) = signer {
+        responder =
+            responder.with_txt_entry(format!("{}={}", SIGNING_TXT_KEY, signer.fingerprint()));
+    }
+
+    info!(
+        "Advertising {} on the local network as {} ({})...",
+        hostname,
+        responder.host(),
+        address
+    );
+    // The socket is registered with the reactor on first poll, once the
+    // future is running on the runtime.
+    let advertisement = future::lazy(move || {
+        bind()
+            .and_then(|socket| advertise(socket, responder, shutdown))
+            .map(|advertisement| {
+                Either::A(advertisement.or_else(|err| {
+                    warn!("Stopped advertising on the local network: {}", err);
+                    Ok(())
+                }))
+            }).unwrap_or_else(|err| {
+                warn!("Could not advertise on the local network: {}", err);
+                Either::B(future::ok(()))
+            })
+    });
+
+    Ok(Box::new(advertisement))
+}
+
+/// Picks the address of the interface multicast DNS traffic is routed
+/// through. Connecting a UDP socket doesn't send anything.
+fn local_address() -> io::Result<Ipv4Addr> {
+    let socket = StdUdpSocket::bind((Ipv4Addr::new(0, 0, 0, 0), 0))?;
+    socket.connect((mdns_group(), MDNS_PORT))?;
+    match socket.local_addr()? {
+        SocketAddr::V4(ref addr) if !addr.ip().is_unspecified() => Ok(*addr.ip()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            "no IPv4 interface available",
+        )),
+    }
+}
+
+// Other responders, such as avahi, are usually listening on the multicast DNS
+// port already, so the port has to be shared.
+fn bind() -> io::Result<StdUdpSocket> {
+    let builder = UdpBuilder::new_v4()?;
+    builder.reuse_address(true)?;
+    #[cfg(unix)]
+    builder.reuse_port(true)?;
+
+    let socket = builder.bind((Ipv4Addr::new(0, 0, 0, 0), MDNS_PORT))?;
+    socket.join_multicast_v4(&mdns_group(), &Ipv4Addr::new(0, 0, 0, 0))?;
+    socket.set_multicast_ttl_v4(255)?;
+    Ok(socket)
+}
+
+fn advertise(
+    socket: StdUdpSocket,
+    responder: Responder,
+    shutdown: Receiver<()>,
+) -> io::Result<impl Future<Item = (), Error = io::Error>> {
+    // kept for the goodbye, which has to come from the multicast DNS port too
+    let goodbye_socket = socket.try_clone()?;
+    let socket = UdpSocket::from_std(socket, &Handle::current())?;
+    let group = SocketAddr::new(IpAddr::V4(mdns_group()), MDNS_PORT);
+    let goodbye = responder.goodbye();
+    let responder = Arc::new(responder);
+
+    let answer = socket
+        .send_dgram(responder.announcement(), &group)
+        .and_then(move |(socket, _)| {
+            future::loop_fn(socket, move |socket| {
+                let responder = responder.clone();
+                socket
+                    .recv_dgram(vec![0; MAX_MESSAGE_SIZE])
+                    .and_then(move |(socket, buf, len, _)| {
+                        match responder.respond(&buf[..len]) {
+                            Some(response) => Either::A(
+                                socket
+                                    .send_dgram(response, &group)
+                                    .map(|(socket, _)| Loop::<(), _>::Continue(socket)),
+                            ),
+                            None => Either::B(future::ok(Loop::Continue(socket))),
+                        }
+                    })
+            })
+        });
+
+    let stop = shutdown.then(|_| Ok(()));
+    Ok(answer
+        .select(stop)
+        .map_err(|(err, _)| err)
+        .and_then(move |_| {
+            debug!("Withdrawing the local network advertisement");
+            goodbye_socket.send_to(&goodbye, &group).map(|_| ())
+        }))
+}
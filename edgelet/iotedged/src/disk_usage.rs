@@ -0,0 +1,53 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use edgelet_core::{CancellationToken, DiskUsageProvider};
+use edgelet_utils::log_failure;
+use futures::{Future, Stream};
+use log::Level;
+use tokio::timer::Interval;
+
+/// This is the file, under the cache directory, that the disk usage is
+/// recorded in for `iotedge check`.
+const EDGE_DISK_USAGE_FILENAME: &str = "disk_usage.json";
+
+/// This is how often the disk usage is recorded. Measuring it walks the
+/// writable layers of every container, so it isn't done more often.
+const DISK_USAGE_FREQUENCY_SECS: u64 = 60 * 60;
+
+/// Records what takes up the disk of the device, from when the daemon starts
+/// and then every hour, until `cancellation` fires.
+pub fn watch_disk_usage<D>(
+    runtime: D,
+    homedir: &Path,
+    subdir_path: &Path,
+    cancellation: CancellationToken,
+) -> impl Future<Item = (), Error = ()> + Send
+where
+    D: 'static + DiskUsageProvider + Send,
+{
+    let homedir = homedir.to_path_buf();
+    let path = subdir_path.join(EDGE_DISK_USAGE_FILENAME);
+    let record = Interval::new(
+        Instant::now(),
+        Duration::from_secs(DISK_USAGE_FREQUENCY_SECS),
+    ).map_err(|err| warn!("Could not schedule the disk usage record: {}", err))
+    .for_each(move |_| {
+        let homedir = homedir.clone();
+        let path = path.clone();
+        runtime.disk_usage().then(move |usage| {
+            // the runtime logs why it couldn't tell
+            if let Ok(usage) = usage {
+                if let Err(err) = usage.with_host(&homedir).save(&path) {
+                    warn!("Could not record the disk usage.");
+                    log_failure(Level::Warn, &err);
+                }
+            }
+            Ok(())
+        })
+    });
+
+    record.select(cancellation.cancelled()).then(|_| Ok(()))
+}
@@ -71,12 +71,44 @@ pub enum ErrorKind {
     Unconfigured,
     #[fail(display = "A provisioning error occurred.")]
     Provisioning,
+    #[fail(display = "Invalid DPS registration payload file")]
+    DpsPayload,
     #[fail(display = "A hardware hsm error occurred.")]
     HardHsm,
     #[fail(display = "An hsm error occurred.")]
     SoftHsm,
     #[fail(display = "Env var error")]
     Var,
+    #[fail(display = "Invalid settings overrides in the device twin")]
+    TwinOverrides,
+    #[fail(
+        display = "The quick start CA is about to expire. Restart the daemon to generate it again."
+    )]
+    QuickstartCaExpiring,
+    #[fail(display = "Could not pair with the standby device")]
+    Standby,
+    #[fail(
+        display = "A passive device can only share the identity of the primary device \
+                   when both of them are provisioned manually."
+    )]
+    StandbySharedIdentity,
+    #[fail(display = "Could not read the manifest of the fixed module set")]
+    FixedModules,
+    #[fail(display = "Invalid socket permissions")]
+    SocketPermissions,
+    #[fail(display = "Could not prepare the socket an API listens on")]
+    Socket,
+    #[fail(display = "Could not resolve a secret of the configuration file")]
+    Secret,
+    #[fail(display = "Could not check the certificate of the parent gateway")]
+    Parent,
+    #[fail(
+        display = "The parent gateway doesn't present a certificate issued under the trust bundle. \
+                   Add its root to the trusted CA certificates of this device."
+    )]
+    ParentNotTrusted,
+    #[fail(display = "Invalid notification webhook")]
+    Webhook,
     #[cfg(target_os = "windows")]
     #[fail(display = "Windows service error")]
     WindowsService,
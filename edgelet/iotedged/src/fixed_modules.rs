@@ -0,0 +1,194 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! A fixed module set the daemon runs itself, in place of the edge agent.
+//!
+//! The specs of the modules are read from a manifest when the daemon starts,
+//! and the modules are then reconciled with it by `edgelet_core::reconcile`.
+//! Each spec is hashed into a label of its container, so that a module whose
+//! spec has changed in the manifest since it was created is created again.
+//! The modules get the environment the edge agent would give them on top of
+//! the one in their spec.
+
+use std::fs;
+use std::path::Path;
+
+use base64;
+use edgelet_core::crypto::{DerivedKeyStore, Sign};
+use edgelet_core::reconcile::{self, Reconciler};
+use edgelet_core::{EnvProvider, Module, ModuleSpec};
+use edgelet_docker::{DockerConfig, DockerModuleRuntime};
+use edgelet_http::client::ClientImpl;
+use edgelet_iothub::HubIdentityManager;
+use failure::ResultExt;
+use futures::sync::oneshot::Receiver;
+use futures::Future;
+use serde_json;
+use sha2::{Digest, Sha256};
+
+use error::{Error, ErrorKind};
+use settings::FixedModules;
+use {AUTHSCHEME_KEY, AUTH_SCHEME};
+
+const MANIFEST_HASH_LABEL_KEY: &str = "net.azure-devices.edge.manifest-hash";
+
+/// Starts reconciling the modules with the manifest of `fixed` until
+/// `shutdown` fires. When `offline`, the modules are created without asking
+/// IoT Hub for their identities.
+pub fn start_fixed_modules<K, HC>(
+    fixed: &FixedModules,
+    runtime: &DockerModuleRuntime,
+    id_man: &HubIdentityManager<DerivedKeyStore<K>, HC, K>,
+    env_provider: &EnvProvider,
+    offline: bool,
+    shutdown: Receiver<()>,
+) -> Result<impl Future<Item = (), Error = Error>, Error>
+where
+    K: 'static + Sign + Clone + Send + Sync,
+    HC: 'static + ClientImpl,
+{
+    info!(
+        "Running the fixed module set of {} instead of the edge agent...",
+        fixed.manifest().display()
+    );
+    let manifest = load_manifest(fixed.manifest())?
+        .into_iter()
+        .map(|spec| label_with_hash(spec).map(|spec| with_module_env(spec, env_provider)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut reconciler = Reconciler::new(runtime.clone(), id_man.clone())
+        .with_check(is_current)
+        .with_offline(offline);
+    if let Some(frequency) = fixed.reconcile_interval() {
+        reconciler = reconciler.with_frequency(frequency);
+    }
+    Ok(reconciler
+        .run_until(manifest, shutdown.map_err(|_| ()))
+        .map_err(Error::from))
+}
+
+/// Reads the manifest, a JSON list of module specs.
+fn load_manifest(path: &Path) -> Result<Vec<ModuleSpec<DockerConfig>>, Error> {
+    let manifest = fs::read_to_string(path).context(ErrorKind::FixedModules)?;
+    let manifest = serde_json::from_str(&manifest).context(ErrorKind::FixedModules)?;
+    Ok(manifest)
+}
+
+fn label_with_hash(mut spec: ModuleSpec<DockerConfig>) -> Result<ModuleSpec<DockerConfig>, Error> {
+    let hash = spec_hash(&spec)?;
+    let create_options = spec.config().clone_create_options()?;
+    let mut labels = create_options.labels().cloned().unwrap_or_default();
+    labels.insert(MANIFEST_HASH_LABEL_KEY.to_string(), hash);
+    spec.config_mut()
+        .set_create_options(create_options.with_labels(labels));
+    Ok(spec)
+}
+
+fn with_module_env(
+    spec: ModuleSpec<DockerConfig>,
+    env_provider: &EnvProvider,
+) -> ModuleSpec<DockerConfig> {
+    let mut env = env_provider.apply(reconcile::module_id(spec.name()), spec.env());
+    env.entry(AUTHSCHEME_KEY.to_string())
+        .or_insert_with(|| AUTH_SCHEME.to_string());
+    spec.with_env(env)
+}
+
+// The spec goes through a `Value` first, whose maps are sorted, so that the
+// hash doesn't depend on the order of the environment variables.
+fn spec_hash(spec: &ModuleSpec<DockerConfig>) -> Result<String, Error> {
+    let spec = serde_json::to_string(&serde_json::to_value(spec)?)?;
+    Ok(base64::encode(&Sha256::digest_str(&spec)))
+}
+
+fn is_current<M>(spec: &ModuleSpec<DockerConfig>, module: &M) -> bool
+where
+    M: Module<Config = DockerConfig>,
+{
+    let hash = |config: &DockerConfig| {
+        config
+            .create_options()
+            .labels()
+            .and_then(|labels| labels.get(MANIFEST_HASH_LABEL_KEY))
+            .cloned()
+    };
+    hash(spec.config()).is_some() && hash(spec.config()) == hash(module.config())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::Write;
+
+    use docker::models::ContainerCreateBody;
+    use tempdir::TempDir;
+
+    fn spec(image: &str) -> ModuleSpec<DockerConfig> {
+        let config = DockerConfig::new(image, ContainerCreateBody::new(), None).unwrap();
+        ModuleSpec::new("edgeHub", "docker", config, HashMap::new()).unwrap()
+    }
+
+    fn label(spec: &ModuleSpec<DockerConfig>) -> String {
+        spec.config().create_options().labels().unwrap()[MANIFEST_HASH_LABEL_KEY].clone()
+    }
+
+    #[test]
+    fn specs_are_labeled_with_their_hash() {
+        let hub = label_with_hash(spec("mcr.microsoft.com/azureiotedge-hub:1.0")).unwrap();
+        let same = label_with_hash(spec("mcr.microsoft.com/azureiotedge-hub:1.0")).unwrap();
+        let newer = label_with_hash(spec("mcr.microsoft.com/azureiotedge-hub:1.1")).unwrap();
+
+        assert_eq!(label(&hub), label(&same));
+        assert_ne!(label(&hub), label(&newer));
+    }
+
+    #[test]
+    fn modules_get_the_environment_of_the_device() {
+        let env_provider = EnvProvider::new(
+            "hub.azure-devices.net",
+            "gateway.local",
+            "device1",
+            "unix:///var/run/iotedge/workload.sock",
+            "2018-06-28",
+        );
+        let hub = with_module_env(
+            spec("mcr.microsoft.com/azureiotedge-hub:1.0"),
+            &env_provider,
+        );
+
+        assert_eq!("$edgeHub", hub.env()["IOTEDGE_MODULEID"]);
+        assert_eq!("device1", hub.env()["IOTEDGE_DEVICEID"]);
+        assert_eq!(AUTH_SCHEME, hub.env()[AUTHSCHEME_KEY]);
+    }
+
+    #[test]
+    fn manifest_is_a_list_of_specs() {
+        let tmp_dir = TempDir::new("fixed_modules").unwrap();
+        let path = tmp_dir.path().join("modules.json");
+        File::create(&path)
+            .unwrap()
+            .write_all(
+                br#"[{
+                    "name": "edgeHub",
+                    "type": "docker",
+                    "config": { "image": "mcr.microsoft.com/azureiotedge-hub:1.0" },
+                    "env": { "OptimizeForPerformance": "false" }
+                }]"#,
+            ).unwrap();
+
+        let manifest = load_manifest(&path).unwrap();
+        assert_eq!(1, manifest.len());
+        assert_eq!("edgeHub", manifest[0].name());
+        assert_eq!("false", manifest[0].env()["OptimizeForPerformance"]);
+
+        match *load_manifest(&tmp_dir.path().join("missing.json"))
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::FixedModules => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
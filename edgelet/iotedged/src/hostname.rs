@@ -0,0 +1,112 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use edgelet_core::{CreateCertificate, Hostname, ModuleRuntime};
+use tokio;
+
+use error::Error;
+use {destroy_workload_ca, prepare_workload_ca, remove_edge_runtime_modules};
+
+/// This is the name of the file in the cache directory that records the host
+/// name the certificates were issued for.
+const EDGE_HOSTNAME_FILENAME: &str = "hostname";
+
+/// Detects a change of the device's host name since the last start. The
+/// server certificates issued before the change are no longer valid for it,
+/// so the workload CA they chain up to is regenerated. The edge runtime
+/// modules are removed, which makes the watchdog create the agent again with
+/// the new host name, and the agent in turn does the same for the hub.
+pub fn check_hostname<M, C>(
+    subdir_path: &Path,
+    hostname: &str,
+    runtime: &M,
+    crypto: &C,
+    tokio_runtime: &mut tokio::runtime::Runtime,
+) -> Result<(), Error>
+where
+    M: ModuleRuntime,
+    <M as ModuleRuntime>::Error: Into<Error>,
+    <M as ModuleRuntime>::RemoveFuture: 'static,
+    C: CreateCertificate,
+{
+    warn_unless_device_hostname(hostname);
+
+    let path = subdir_path.join(EDGE_HOSTNAME_FILENAME);
+    let recorded = read_hostname(&path)?;
+    match recorded {
+        Some(ref recorded) if !recorded.eq_ignore_ascii_case(hostname) => {
+            warn!(
+                "The host name has changed from {} to {}. Certificates issued for the old \
+                 host name are no longer valid and will be issued again.",
+                recorded, hostname
+            );
+            destroy_workload_ca(crypto)?;
+            prepare_workload_ca(crypto)?;
+
+            info!("Removing the edge runtime modules so they use the new host name...");
+            remove_edge_runtime_modules(runtime, tokio_runtime)?;
+            info!("Finished removing the edge runtime modules.");
+        }
+        Some(_) => debug!("Host name has not changed."),
+        None => debug!("Recording host name {}.", hostname),
+    }
+
+    fs::create_dir_all(subdir_path)?;
+    fs::write(&path, hostname)?;
+    Ok(())
+}
+
+fn read_hostname(path: &Path) -> Result<Option<String>, Error> {
+    match fs::read_to_string(path) {
+        Ok(hostname) => Ok(Some(hostname.trim().to_string())),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+// Certificates are only of use to clients that reach the device by the name
+// they are issued for, so a name that doesn't lead to the device is worth
+// a warning.
+fn warn_unless_device_hostname(hostname: &str) {
+    match Hostname::new(hostname) {
+        Ok(name) => {
+            if let Some(device) = Hostname::of_device() {
+                if !name.names_same_host(&device) {
+                    warn!(
+                        "The host name {} in the config file does not match the device host \
+                         name {}. Clients may not be able to validate the certificates of \
+                         the edge hub.",
+                        hostname,
+                        device.as_str()
+                    );
+                }
+            }
+        }
+        Err(_) => warn!("The host name {} in the config file is not a valid DNS name.", hostname),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn missing_record_has_no_hostname() {
+        let tmp_dir = TempDir::new("hostname").unwrap();
+        let path = tmp_dir.path().join(EDGE_HOSTNAME_FILENAME);
+        assert_eq!(None, read_hostname(&path).unwrap());
+    }
+
+    #[test]
+    fn record_is_read_without_whitespace() {
+        let tmp_dir = TempDir::new("hostname").unwrap();
+        let path = tmp_dir.path().join(EDGE_HOSTNAME_FILENAME);
+        fs::write(&path, "gateway\n").unwrap();
+        assert_eq!(Some("gateway".to_string()), read_hostname(&path).unwrap());
+    }
+}
@@ -15,6 +15,7 @@
 ))]
 
 extern crate base64;
+extern crate chrono;
 #[macro_use]
 extern crate clap;
 extern crate config;
@@ -23,6 +24,7 @@ extern crate edgelet_core;
 extern crate edgelet_docker;
 extern crate edgelet_hsm;
 extern crate edgelet_http;
+#[cfg(feature = "mgmt")]
 extern crate edgelet_http_mgmt;
 extern crate edgelet_http_workload;
 extern crate edgelet_iothub;
@@ -40,12 +42,15 @@ extern crate hyper_tls;
 extern crate iothubservice;
 #[macro_use]
 extern crate log;
+extern crate net2;
+extern crate openssl;
 extern crate provisioning;
 extern crate serde;
 extern crate sha2;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate serde_yaml;
 #[cfg(test)]
 extern crate tempdir;
 extern crate tokio;
@@ -59,10 +64,26 @@ extern crate windows_service;
 extern crate win_logger;
 
 pub mod app;
+mod ca_rotation;
+mod deprecation;
+mod discovery;
+mod disk_usage;
 mod error;
+mod fixed_modules;
+mod hostname;
 pub mod logging;
+mod migration;
+mod notifications;
+mod os_trace;
+mod overrides;
+mod parent;
+mod quickstart;
+pub mod secrets;
 pub mod settings;
 pub mod signal;
+mod standby;
+mod telemetry;
+mod trust_bundle_files;
 pub mod workload;
 
 #[cfg(not(target_os = "windows"))]
@@ -77,25 +98,45 @@ use std::fs;
 use std::fs::{DirBuilder, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
 
 use docker::models::HostConfig;
 use edgelet_core::crypto::{
-    CreateCertificate, Decrypt, DerivedKeyStore, Encrypt, GetTrustBundle, KeyIdentity, KeyStore,
-    MasterEncryptionKey, MemoryKey, MemoryKeyStore, Sign, IOTEDGED_CA_ALIAS,
+    CreateCertificate, CreateCrl, Decrypt, DerivedKeyStore, Encrypt, GetTrustBundle, KeyIdentity,
+    KeyStore, MasterEncryptionKey, MemoryKey, MemoryKeyStore, Sign, IOTEDGED_CA_ALIAS,
 };
+use edgelet_core::response_signing::ResponseSigner;
 use edgelet_core::watchdog::Watchdog;
-use edgelet_core::WorkloadConfig;
-use edgelet_core::{CertificateIssuer, CertificateProperties, CertificateType};
-use edgelet_core::{ModuleRuntime, ModuleSpec};
+use edgelet_core::{IdentityKeySource, WorkloadConfig};
+use edgelet_core::{CertificateAlias, CertificateIssuer, CertificateProperties, CertificateType};
+use edgelet_core::{
+    CaRotation, CancellationToken, CertificatePolicy, CertificateRegistry, EnvProvider,
+    GenerationRegistry, HeartbeatMonitor, HostProcesses, KeySlots, LoadShedder, Metrics, Module,
+    ModulePriority, ModuleRuntime, ModuleSpec, Namespaces, Notifier, Outbox, Scheduler,
+    SequenceNumbers, TokenValidator, TrustBundleVersions, TrustedClock, WorkloadUsage,
+    SOCKET_REBINDS,
+};
+#[cfg(feature = "mgmt")]
+use edgelet_core::{DeploymentHistory, HostSystemInfo, IdempotencyKeys, Operations, StateBackup};
 use edgelet_docker::{DockerConfig, DockerModuleRuntime};
 use edgelet_hsm::tpm::{TpmKey, TpmKeyStore};
 use edgelet_hsm::Crypto;
 use edgelet_http::client::{Client as HttpClient, ClientImpl};
-use edgelet_http::logging::LoggingService;
-use edgelet_http::{ApiVersionService, HyperExt, MaybeProxyClient, API_VERSION};
-use edgelet_http_mgmt::ManagementService;
-use edgelet_http_workload::WorkloadService;
+use edgelet_http::logging::{LogSampling, LoggingService};
+#[cfg(feature = "chaos")]
+use edgelet_http::FaultyClient;
+use edgelet_http::{
+    ApiVersionService, HyperExt, MaybeProxyClient, Server, ServerTls, SocketPermissions,
+    API_VERSION,
+};
+#[cfg(feature = "mgmt")]
+use edgelet_http_mgmt::{ManagementService, ManagementState};
+use edgelet_http_workload::{WorkloadLimits, WorkloadService};
 use edgelet_iothub::{HubIdentityManager, SasTokenSource};
+use edgelet_utils::log_failure;
+use failure::ResultExt;
 use futures::future::Either;
 use futures::sync::oneshot::{self, Receiver};
 use futures::{future, Future};
@@ -104,13 +145,30 @@ use hsm::ManageTpmKeys;
 use hyper::server::conn::Http;
 use hyper::Uri;
 use iothubservice::DeviceClient;
+use log::Level;
 use provisioning::provisioning::{
     BackupProvisioning, DpsProvisioning, ManualProvisioning, Provision, ProvisioningResult,
 };
-use sha2::{Digest, Sha256};
 use url::Url;
 
-use settings::{Dps, Manual, Provisioning, Settings, DEFAULT_CONNECTION_STRING};
+use ca_rotation::{check_ca_rotation, check_trust_bundle_version, watch_ca_rotation};
+use deprecation::record_deprecations;
+use discovery::start_discovery;
+use disk_usage::watch_disk_usage;
+use fixed_modules::start_fixed_modules;
+use hostname::check_hostname;
+use notifications::{edge_ca_expiry, load_notifier, provisioning_changed, watch_edge_ca_expiry};
+use overrides::{start_twin_overrides, Overridable};
+use parent::check_parent;
+use quickstart::{check_quickstart_ca, watch_quickstart_ca};
+use telemetry::{load_outbox, start_module_events, start_telemetry};
+use settings::{
+    Developer, Dps, Manual, Permissions, PipeAccess, Provisioning, SamplingRule, Settings,
+    SocketPermissions as SocketSettings, StandbyIdentity, StandbyRole, Tls, Tuning,
+    DEFAULT_CONNECTION_STRING,
+};
+use standby::{send_heartbeats, stand_by};
+use trust_bundle_files::{prepare_trust_bundle_files, watch_trust_bundle_files};
 
 use workload::WorkloadData;
 
@@ -121,29 +179,9 @@ const EDGE_RUNTIME_MODULE_NAME: &str = "edgeAgent";
 const AUTH_SCHEME: &str = "sasToken";
 
 /// The following constants are all environment variables names injected into
-/// the Edge Agent container.
+/// the Edge Agent container, on top of the ones every module gets from the
+/// `EnvProvider`.
 ///
-/// This variable holds the host name of the IoT Hub instance that edge agent
-/// is expected to work with.
-const HOSTNAME_KEY: &str = "IOTEDGE_IOTHUBHOSTNAME";
-
-/// This variable holds the host name for the edge device. This name is used
-/// by the edge agent to provide the edge hub container an alias name in the
-/// network so that TLS cert validation works.
-const GATEWAY_HOSTNAME_KEY: &str = "EDGEDEVICEHOSTNAME";
-
-/// This variable holds the IoT Hub device identifier.
-const DEVICEID_KEY: &str = "IOTEDGE_DEVICEID";
-
-/// This variable holds the IoT Hub module identifier.
-const MODULEID_KEY: &str = "IOTEDGE_MODULEID";
-
-/// This variable holds the URI to use for connecting to the workload endpoint
-/// in iotedged. This is used by the edge agent to connect to the workload API
-/// for its own needs and is also used for volume mounting into module
-/// containers when the URI refers to a Unix domain socket.
-const WORKLOAD_URI_KEY: &str = "IOTEDGE_WORKLOADURI";
-
 /// This variable holds the URI to use for connecting to the management
 /// endpoint in iotedged. This is used by the edge agent for managing module
 /// lifetimes and module identities.
@@ -168,13 +206,22 @@ const DEVICE_CA_CERT_KEY: &str = "IOTEDGE_DEVICE_CA_CERT";
 const DEVICE_CA_PK_KEY: &str = "IOTEDGE_DEVICE_CA_PK";
 const TRUSTED_CA_CERTS_KEY: &str = "IOTEDGE_TRUSTED_CA_CERTS";
 
+/// This is the variable the HSM takes the validity of the quick start CA
+/// from, in seconds.
+const QUICKSTART_CA_VALIDITY_KEY: &str = "IOTEDGE_QUICKSTART_CA_VALIDITY_SECS";
+
 /// This is the key for the docker network Id.
 const EDGE_NETWORKID_KEY: &str = "NetworkId";
 
-/// This is the key for the largest API version that this edgelet supports
-const API_VERSION_KEY: &str = "IOTEDGE_APIVERSION";
+/// This variable holds the JSON payload a custom allocation policy returned
+/// when the device was provisioned through DPS, for the edge agent to report.
+const PROVISIONING_PAYLOAD_KEY: &str = "IOTEDGE_PROVISIONING_PAYLOAD";
 
 const IOTHUB_API_VERSION: &str = "2017-11-08-preview";
+
+/// DPS only takes a registration payload from this API version on.
+const DPS_API_VERSION: &str = "2017-11-15";
+const DPS_PAYLOAD_API_VERSION: &str = "2019-03-31";
 const UNIX_SCHEME: &str = "unix";
 
 /// This is the name of the provisioning backup file
@@ -183,9 +230,66 @@ const EDGE_PROVISIONING_BACKUP_FILENAME: &str = "provisioning_backup.json";
 /// This is the name of the settings backup file
 const EDGE_SETTINGS_STATE_FILENAME: &str = "settings_state";
 
+/// This is the name of the deployment history file
+#[cfg(feature = "mgmt")]
+const EDGE_DEPLOYMENT_HISTORY_FILENAME: &str = "deployment_history.json";
+
+/// This is the name of the file tracking certificates issued to modules
+const EDGE_ISSUED_CERTIFICATES_FILENAME: &str = "issued_certificates.json";
+
+/// This is the name of the file tracking the sequence numbers of the encrypt
+/// operations of modules
+const EDGE_SEQUENCE_NUMBERS_FILENAME: &str = "sequence_numbers.json";
+
+/// This is the name of the file keeping the responses to management requests
+/// made with idempotency keys
+#[cfg(feature = "mgmt")]
+const EDGE_IDEMPOTENCY_KEYS_FILENAME: &str = "idempotency_keys.json";
+
+/// This is the name of the file recording the host processes registered as
+/// modules
+const EDGE_HOST_PROCESSES_FILENAME: &str = "host_processes.json";
+
+/// This is the name of the directory the env files of host processes are
+/// written to
+const EDGE_HOST_PROCESS_ENV_SUBDIR: &str = "hostprocesses";
+
+/// This is the name of the file queuing the daemon's messages for IoT Hub
+const EDGE_OUTBOX_FILENAME: &str = "outbox.json";
+
+/// This is the name of the file the daemon's metrics are persisted to, so
+/// that they carry on across restarts
+#[cfg(feature = "metrics")]
+const EDGE_METRICS_FILENAME: &str = "metrics.json";
+
+/// This is how often the daemon's metrics are persisted.
+#[cfg(feature = "metrics")]
+const METRICS_PERSIST_FREQUENCY_SECS: u64 = 60;
+
+/// This is how often the egress allowlists of modules are enforced again.
+const EGRESS_ENFORCE_FREQUENCY_SECS: u64 = 30;
+
+/// This is the name of the file recording the last failure to authenticate
+/// to a container registry, which `iotedge check` reads
+const EDGE_REGISTRY_AUTH_FILENAME: &str = "registry-auth";
+
+/// This is the name of the key workload API responses are signed with.
+const EDGE_WORKLOAD_SIGNING_KEY_FILENAME: &str = "workload_signing_key.pem";
+
 /// This is the name of the cache subdirectory for settings state
 const EDGE_SETTINGS_SUBDIR: &str = "cache";
 
+/// These are the names of the HSM subdirectory and of the directory in it
+/// holding the master encryption keys
+#[cfg(feature = "mgmt")]
+const EDGE_HSM_SUBDIR: &str = "hsm";
+#[cfg(feature = "mgmt")]
+const EDGE_HSM_ENC_KEYS_SUBDIR: &str = "enc_keys";
+
+/// This is the name the config file is backed up under
+#[cfg(feature = "mgmt")]
+const EDGE_CONFIG_BACKUP_NAME: &str = "config.yaml";
+
 /// These are the properties of the workload CA certificate
 const IOTEDGED_VALIDITY: u64 = 7_776_000; // 90 days
 const IOTEDGED_COMMONNAME: &str = "iotedged workload ca";
@@ -208,7 +312,32 @@ impl Main {
     {
         let Main { settings } = self;
 
-        let mut tokio_runtime = tokio::runtime::Runtime::new()?;
+        os_trace::init(settings.os_tracing());
+        os_trace::event("starting", &[("version", &crate_version!())]);
+
+        let tuning = settings.tuning();
+        let mut builder = tokio::runtime::Builder::new();
+        if let Some(threads) = tuning.worker_threads() {
+            builder.core_threads(threads);
+        }
+        if let Some(threads) = tuning.blocking_threads() {
+            builder.blocking_threads(threads);
+        }
+        let mut tokio_runtime = builder.build()?;
+        let notifier = load_notifier(settings.notifications(), tokio_runtime.executor())?;
+
+        // Pulls, followed logs and provisioning that are in flight when the
+        // daemon is asked to shut down are aborted, rather than holding up the
+        // shutdown with the sockets they keep open.
+        let cancellation = CancellationToken::new();
+        {
+            let cancellation = cancellation.clone();
+            tokio_runtime.spawn(shutdown_signal.map(move |_| {
+                os_trace::event("stopping", &[]);
+                cancellation.cancel()
+            }));
+        }
+        let shutdown_signal = cancellation.cancelled();
 
         if let Provisioning::Manual(ref manual) = settings.provisioning() {
             if manual.device_connection_string() == DEFAULT_CONNECTION_STRING {
@@ -216,14 +345,30 @@ impl Main {
             }
         }
 
-        let hyper_client = MaybeProxyClient::new(get_proxy_uri()?)?;
-
         info!(
             "Using runtime network id {}",
             settings.moby_runtime().network()
         );
-        let runtime = DockerModuleRuntime::new(settings.moby_runtime().uri())?
-            .with_network_id(settings.moby_runtime().network().to_string());
+        let mut runtime = DockerModuleRuntime::new(settings.moby_runtime().uri())?
+            .with_network_id(settings.moby_runtime().network().to_string())
+            .with_cancellation(cancellation.clone())
+            .with_offline(settings.offline());
+        if let Some(parent_hostname) = settings.parent_hostname() {
+            runtime = runtime.with_parent_hostname(parent_hostname.to_string());
+        }
+        if let Some(module_time) = settings.module_time() {
+            runtime = runtime.with_localtime_mount(module_time.mount_localtime());
+        }
+        if let Some(memory_limit) = tuning.module_memory_limit() {
+            runtime = runtime.with_default_memory_limit(memory_limit);
+        }
+        if let Some(isolation) = settings.moby_runtime().isolation() {
+            info!("Using {} isolation for modules by default", isolation);
+            runtime = runtime.with_default_isolation(isolation);
+        }
+        if let Some(cgroup_version) = runtime.cgroup_version() {
+            info!("Host uses {} for module stats and limits", cgroup_version);
+        }
 
         init_docker_runtime(&runtime, &mut tokio_runtime)?;
 
@@ -236,9 +381,13 @@ impl Main {
         info!("Configuring certificates...");
         let certificates = &settings.certificates();
         match certificates.as_ref() {
-            None => info!(
-                "Transparent gateway certificates not found, operating in quick start mode..."
-            ),
+            None => {
+                info!(
+                    "Transparent gateway certificates not found, operating in quick start mode..."
+                );
+                let lifetime = settings.quickstart_ca_lifetime().as_secs();
+                env::set_var(QUICKSTART_CA_VALIDITY_KEY, lifetime.to_string());
+            }
             Some(&c) => {
                 let path = c.device_ca_cert().as_os_str();
                 info!("Configuring the Device CA certificate using {:?}.", path);
@@ -259,8 +408,25 @@ impl Main {
         let crypto = Crypto::new()?;
         info!("Finished initializing hsm.");
 
-        // Detect if the settings were changed and if the device needs to be reconfigured
         let cache_subdir_path = Path::new(&settings.homedir()).join(EDGE_SETTINGS_SUBDIR);
+        let (crypto, until_renewal) = check_quickstart_ca(
+            &cache_subdir_path,
+            settings.certificates(),
+            settings.quickstart_ca_lifetime(),
+            crypto,
+            &runtime,
+            &mut tokio_runtime,
+        )?;
+        let quickstart_ca_expiring = Arc::new(AtomicBool::new(false));
+        if let Some(until_renewal) = until_renewal {
+            tokio_runtime.spawn(watch_quickstart_ca(
+                until_renewal,
+                cancellation.clone(),
+                quickstart_ca_expiring.clone(),
+            ));
+        }
+
+        // Detect if the settings were changed and if the device needs to be reconfigured
         check_settings_state(
             cache_subdir_path.clone(),
             EDGE_SETTINGS_STATE_FILENAME,
@@ -269,6 +435,115 @@ impl Main {
             &crypto,
             &mut tokio_runtime,
         )?;
+        check_hostname(
+            &cache_subdir_path,
+            settings.hostname(),
+            &runtime,
+            &crypto,
+            &mut tokio_runtime,
+        )?;
+        record_deprecations(&cache_subdir_path, settings.deprecations())?;
+        let rotation = check_ca_rotation(&cache_subdir_path, settings.certificates(), &crypto)?;
+
+        if let Some(notifications) = settings.notifications() {
+            if !notifier.is_empty() {
+                tokio_runtime.spawn(watch_edge_ca_expiry(
+                    edge_ca_expiry(settings.certificates(), crypto.clone()),
+                    notifications.certificate_expiry_warning(),
+                    notifier.clone(),
+                    cancellation.clone(),
+                ));
+            }
+        }
+
+        if let Some(mount) = settings.trust_bundle_files() {
+            if mount.enabled() {
+                let files = prepare_trust_bundle_files(settings.homedir(), &crypto, &rotation)?;
+                info!(
+                    "Mounting the trust bundle into modules at {}",
+                    mount.container_dir().display()
+                );
+                tokio_runtime.spawn(watch_trust_bundle_files(
+                    files.clone(),
+                    crypto.clone(),
+                    rotation.clone(),
+                    mount.refresh_interval(),
+                    cancellation.clone(),
+                ));
+                runtime = runtime.with_trust_bundle_files(files, mount.container_dir());
+            }
+        }
+
+        tokio_runtime.spawn(watch_disk_usage(
+            runtime.clone(),
+            settings.homedir(),
+            &cache_subdir_path,
+            cancellation.clone(),
+        ));
+
+        // A nested device reaches the cloud through its parent gateway, whose
+        // certificate is issued under a root of the trust bundle rather than
+        // one the host trusts.
+        let parent_trust_bundle = match settings.parent_hostname() {
+            Some(parent_hostname) => {
+                info!(
+                    "Connecting to the cloud through the parent gateway {}",
+                    parent_hostname
+                );
+                Some(check_parent(parent_hostname, &crypto, &rotation)?)
+            }
+            None => None,
+        };
+        let min_tls_version = settings.tls().and_then(Tls::min_version);
+        if let Some(version) = min_tls_version {
+            info!(
+                "Requiring {} or later for connections to the cloud",
+                version
+            );
+        }
+        let hyper_client = match (parent_trust_bundle, min_tls_version) {
+            (Some(trust_bundle), min_tls_version) => MaybeProxyClient::with_trust_bundle(
+                get_proxy_uri()?,
+                min_tls_version,
+                trust_bundle.as_bytes(),
+            )?,
+            (None, Some(version)) => {
+                MaybeProxyClient::with_min_tls_version(get_proxy_uri()?, version)?
+            }
+            (None, None) => MaybeProxyClient::new(get_proxy_uri()?)?,
+        };
+        // Requests to the cloud, which are mostly to IoT Hub, are subject to
+        // the faults injected into hub requests.
+        #[cfg(feature = "chaos")]
+        let hyper_client = {
+            warn!("Fault injection is enabled. This build must not be used in production.");
+            FaultyClient::new(hyper_client)
+        };
+
+        if let Some(standby) = settings.standby() {
+            if standby.identity() == StandbyIdentity::Shared {
+                if let Provisioning::Dps(_) = settings.provisioning() {
+                    Err(ErrorKind::StandbySharedIdentity)?;
+                }
+            }
+            if standby.role() == StandbyRole::Primary {
+                let heartbeats = send_heartbeats(standby, &runtime, cancellation.cancelled())?;
+                tokio_runtime.spawn(heartbeats);
+            }
+        }
+
+        // A passive device that shares the identity of the primary can't
+        // connect to IoT Hub with it before the primary failed.
+        if !stand_by(
+            settings.standby(),
+            StandbyIdentity::Shared,
+            settings.agent().config(),
+            &runtime,
+            &cancellation,
+            &mut tokio_runtime,
+        )? {
+            return shut_down(&quickstart_ca_expiring);
+        }
 
         info!("Provisioning edge device...");
         match settings.provisioning() {
@@ -276,21 +551,41 @@ impl Main {
                 let (key_store, provisioning_result, root_key) =
                     manual_provision(&manual, &mut tokio_runtime)?;
                 info!("Finished provisioning edge device.");
+                os_trace::event(
+                    "provisioned",
+                    &[
+                        ("hub", &provisioning_result.hub_name()),
+                        ("device", &provisioning_result.device_id()),
+                    ],
+                );
+                if !stand_by(
+                    settings.standby(),
+                    StandbyIdentity::Swap,
+                    settings.agent().config(),
+                    &runtime,
+                    &cancellation,
+                    &mut tokio_runtime,
+                )? {
+                    return shut_down(&quickstart_ca_expiring);
+                }
                 let cfg = WorkloadData::new(
                     provisioning_result.hub_name().to_string(),
                     provisioning_result.device_id().to_string(),
                     IOTEDGE_ID_CERT_MAX_DURATION_SECS,
                     IOTEDGE_SERVER_CERT_MAX_DURATION_SECS,
-                );
+                ).with_cert_policies(settings.cert_policies().clone());
                 start_api(
                     &settings,
                     hyper_client,
                     &runtime,
                     &key_store,
                     cfg,
+                    provisioning_result.payload(),
                     root_key,
                     shutdown_signal,
                     &crypto,
+                    &rotation,
+                    notifier,
                     tokio_runtime,
                 )?;
             }
@@ -300,33 +595,65 @@ impl Main {
                     &dps,
                     hyper_client.clone(),
                     dps_path,
+                    settings.parent_hostname(),
+                    settings.offline(),
                     runtime,
+                    &notifier,
+                    &cancellation,
                     &mut tokio_runtime,
                 )?;
                 info!("Finished provisioning edge device.");
+                os_trace::event(
+                    "provisioned",
+                    &[
+                        ("hub", &provisioning_result.hub_name()),
+                        ("device", &provisioning_result.device_id()),
+                    ],
+                );
+                if !stand_by(
+                    settings.standby(),
+                    StandbyIdentity::Swap,
+                    settings.agent().config(),
+                    &runtime,
+                    &cancellation,
+                    &mut tokio_runtime,
+                )? {
+                    return shut_down(&quickstart_ca_expiring);
+                }
                 let cfg = WorkloadData::new(
                     provisioning_result.hub_name().to_string(),
                     provisioning_result.device_id().to_string(),
                     IOTEDGE_ID_CERT_MAX_DURATION_SECS,
                     IOTEDGE_SERVER_CERT_MAX_DURATION_SECS,
-                );
+                ).with_cert_policies(settings.cert_policies().clone());
                 start_api(
                     &settings,
                     hyper_client,
                     &runtime,
                     &key_store,
                     cfg,
+                    provisioning_result.payload(),
                     root_key,
                     shutdown_signal,
                     &crypto,
+                    &rotation,
+                    notifier,
                     tokio_runtime,
                 )?;
             }
         };
 
-        info!("Shutdown complete.");
-        Ok(())
+        shut_down(&quickstart_ca_expiring)
+    }
+}
+
+fn shut_down(quickstart_ca_expiring: &AtomicBool) -> Result<(), Error> {
+    info!("Shutdown complete.");
+    os_trace::event("stopped", &[]);
+    if quickstart_ca_expiring.load(Ordering::SeqCst) {
+        Err(ErrorKind::QuickstartCaExpiring)?;
     }
+    Ok(())
 }
 
 pub fn get_proxy_uri() -> Result<Option<Uri>, Error> {
@@ -371,6 +698,37 @@ where
     Ok(())
 }
 
+/// The modules that are given the host name and the certificates issued
+/// under the workload CA when they are created, and so have to be created
+/// again once either changes.
+const EDGE_RUNTIME_MODULES: &[&str] = &[EDGE_RUNTIME_MODULE_NAME, "edgeHub"];
+
+// The watchdog creates the agent again once it is gone, and the agent in turn
+// does the same for the hub. Modules that can't be removed are left as they
+// are.
+fn remove_edge_runtime_modules<M>(
+    runtime: &M,
+    tokio_runtime: &mut tokio::runtime::Runtime,
+) -> Result<(), Error>
+where
+    M: ModuleRuntime,
+    <M as ModuleRuntime>::Error: Into<Error>,
+    <M as ModuleRuntime>::RemoveFuture: 'static,
+{
+    let removals = EDGE_RUNTIME_MODULES.iter().map(|name| {
+        runtime.remove(name).then(move |result| {
+            if let Err(err) = result {
+                let err: Error = err.into();
+                debug!("Could not remove module {}:", name);
+                log_failure(Level::Debug, &err);
+            }
+            Ok::<_, Error>(())
+        })
+    });
+    tokio_runtime.block_on(future::join_all(removals))?;
+    Ok(())
+}
+
 fn check_settings_state<M, C>(
     subdir_path: PathBuf,
     filename: &str,
@@ -388,12 +746,14 @@ where
     info!("Detecting if configuration file has changed...");
     let path = subdir_path.join(filename);
     let mut reconfig_reqd = false;
-    let diff = settings.diff_with_cached(path)?;
+    let diff = settings.diff_with_cached(path.clone())?;
     if diff {
         info!("Change to configuration file detected.");
         reconfig_reqd = true;
     } else {
         info!("No change to configuration file detected.");
+        // re-save the state in case it was written by an older version of the daemon
+        save_settings_state(&path, settings)?;
 
         #[cfg_attr(feature = "cargo-clippy", allow(single_match_else))]
         match prepare_workload_ca(crypto) {
@@ -449,31 +809,36 @@ where
     // regenerate the workload CA certificate
     destroy_workload_ca(crypto)?;
     prepare_workload_ca(crypto)?;
+    save_settings_state(&path, settings)
+}
+
+fn save_settings_state(path: &Path, settings: &Settings<DockerConfig>) -> Result<(), Error> {
     let mut file = File::create(path)?;
-    serde_json::to_string(settings)
-        .map_err(Error::from)
-        .map(|s| Sha256::digest_str(&s))
-        .map(|s| base64::encode(&s))
-        .and_then(|sb| file.write_all(sb.as_bytes()).map_err(Error::from))
+    file.write_all(settings.state_hash()?.as_bytes())?;
+    Ok(())
 }
 
 #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
-fn start_api<HC, K, F, C, W>(
+fn start_api<HC, K, F, C>(
     settings: &Settings<DockerConfig>,
     hyper_client: HC,
     runtime: &DockerModuleRuntime,
     key_store: &DerivedKeyStore<K>,
-    workload_config: W,
+    workload_config: WorkloadData,
+    provisioning_payload: Option<&serde_json::Value>,
     root_key: K,
     shutdown_signal: F,
     crypto: &C,
+    rotation: &CaRotation,
+    notifier: Notifier,
     mut tokio_runtime: tokio::runtime::Runtime,
 ) -> Result<(), Error>
 where
     F: Future<Item = (), Error = ()> + Send + 'static,
-    HC: ClientImpl + 'static,
+    HC: ClientImpl + Clone + 'static,
     K: Sign + Clone + Send + Sync + 'static,
     C: CreateCertificate
+        + CreateCrl
         + Decrypt
         + Encrypt
         + GetTrustBundle
@@ -482,57 +847,469 @@ where
         + Send
         + Sync
         + 'static,
-    W: WorkloadConfig + Clone + Send + Sync + 'static,
 {
     let hub_name = workload_config.iot_hub_name().to_string();
     let device_id = workload_config.device_id().to_string();
-    let hostname = format!("https://{}", hub_name);
+    // the requests of a nested device are relayed to IoT Hub by its parent
+    // gateway, while the tokens they carry are still for IoT Hub
+    let hostname = format!(
+        "https://{}",
+        settings.parent_hostname().unwrap_or(&hub_name)
+    );
     let token_source = SasTokenSource::new(hub_name.clone(), device_id.clone(), root_key);
+    // the time of IoT Hub corrects the clock of devices with a broken RTC,
+    // for the tokens sent to it as much as for modules
+    let trusted_clock = TrustedClock::new();
     let http_client = HttpClient::new(
-        hyper_client,
+        hyper_client.clone(),
         Some(token_source),
         IOTHUB_API_VERSION,
         Url::parse(&hostname)?,
-    )?;
+    )?.with_trusted_clock(trusted_clock.clone())
+    .with_clock(trusted_clock.clone());
     let device_client = DeviceClient::new(http_client, &device_id)?;
-    let id_man = HubIdentityManager::new(key_store.clone(), device_client);
 
     let (mgmt_tx, mgmt_rx) = oneshot::channel();
     let (work_tx, work_rx) = oneshot::channel();
 
-    let mgmt = start_management(&settings, &runtime, &id_man, mgmt_rx);
+    let registry_path = Path::new(&settings.homedir())
+        .join(EDGE_SETTINGS_SUBDIR)
+        .join(EDGE_ISSUED_CERTIFICATES_FILENAME);
+    let metrics = load_metrics(settings)?;
+
+    let registry = CertificateRegistry::load(registry_path)?.with_metrics(metrics.clone());
+    let slots = {
+        let crypto = crypto.clone();
+        let slots = KeySlots::new()
+            .with_destroy(move |alias| crypto.destroy_certificate(alias.to_string()));
+        match settings.tuning().key_slots_per_module() {
+            Some(quota) => slots.with_quota(quota),
+            None => slots,
+        }
+    };
+    slots.restore(&registry.list());
+
+    let generations = {
+        let crypto = crypto.clone();
+        let registry = registry.clone();
+        let slots = slots.clone();
+        GenerationRegistry::new().with_cleanup(move |module, generation_id| {
+            retire_generation(&crypto, &registry, &slots, module, generation_id)
+        })
+    };
+    let id_man = HubIdentityManager::new(key_store.clone(), device_client.clone())
+        .with_generations(generations.clone());
+    let sequences = SequenceNumbers::load(
+        Path::new(&settings.homedir())
+            .join(EDGE_SETTINGS_SUBDIR)
+            .join(EDGE_SEQUENCE_NUMBERS_FILENAME),
+    )?;
+
+    let signer = if settings.sign_workload_responses() {
+        let signing_key_path = Path::new(&settings.homedir())
+            .join(EDGE_SETTINGS_SUBDIR)
+            .join(EDGE_WORKLOAD_SIGNING_KEY_FILENAME);
+        Some(ResponseSigner::load_or_create(&signing_key_path)?)
+    } else {
+        None
+    };
+
+    let mut env_provider = EnvProvider::new(
+        &hub_name,
+        settings.hostname(),
+        &device_id,
+        &settings.connect().workload_uri().to_string(),
+        API_VERSION,
+    ).with_custom_env(settings.module_env().to_vec())
+    .with_generations(generations.clone());
+    if let Some(parent_hostname) = settings.parent_hostname() {
+        env_provider = env_provider.with_parent_hostname(parent_hostname.to_string());
+    }
+    if let Some(ref signer) = signer {
+        info!("Signing workload API responses.");
+        env_provider = env_provider.with_signing_key(signer.verification_key());
+    }
+    if settings
+        .module_time()
+        .map_or(false, |module_time| module_time.clock_skew_env())
+    {
+        env_provider = env_provider.with_trusted_clock(trusted_clock.clone());
+    }
+
+    // processes on the host registered as modules load the environment
+    // module containers are created with from env files
+    let host_processes = HostProcesses::load(
+        Path::new(&settings.homedir())
+            .join(EDGE_SETTINGS_SUBDIR)
+            .join(EDGE_HOST_PROCESSES_FILENAME),
+    )?.with_env_files(
+        env_provider.clone(),
+        Path::new(&settings.homedir()).join(EDGE_HOST_PROCESS_ENV_SUBDIR),
+    );
+    host_processes.refresh_env_files()?;
+
+    let outbox_path = Path::new(&settings.homedir())
+        .join(EDGE_SETTINGS_SUBDIR)
+        .join(EDGE_OUTBOX_FILENAME);
+    let outbox = load_outbox(settings.telemetry(), outbox_path)?;
+
+    // modules that have a version of the trust bundle are only served what
+    // changed since then, and every change is reported to IoT Hub
+    let versions = check_trust_bundle_version(
+        &Path::new(&settings.homedir()).join(EDGE_SETTINGS_SUBDIR),
+        crypto,
+        rotation,
+        &outbox,
+    )?;
+
+    let registry_auth_path = Path::new(&settings.homedir())
+        .join(EDGE_SETTINGS_SUBDIR)
+        .join(EDGE_REGISTRY_AUTH_FILENAME);
+
+    // modules get their environment from the provider, whoever creates them,
+    // the images they are created from are reported, registries refusing to
+    // let images be pulled are recorded and egress violations are counted
+    let runtime = runtime
+        .clone()
+        .with_env_provider(env_provider.clone())
+        .with_outbox(outbox.clone())
+        .with_auth_failure_record(registry_auth_path)
+        .with_metrics(metrics.clone());
+
+    let scheduler = Scheduler::new(settings.schedules())?;
+    let monitor =
+        HeartbeatMonitor::new(settings.heartbeat_interval()).with_outbox(outbox.clone());
+    let shedder = settings
+        .load_shedding()
+        .filter(|load_shedding| load_shedding.enabled())
+        .map(|load_shedding| {
+            LoadShedder::new(load_shedding.min_available_memory_percent())
+                .with_outbox(outbox.clone())
+        });
+
+    let (mgmt_sampling, work_sampling) = match settings.access_log() {
+        Some(access_log) => (
+            log_sampling(access_log.management())?,
+            log_sampling(access_log.workload())?,
+        ),
+        None => (LogSampling::new(), LogSampling::new()),
+    };
+
+    // sockets left behind by a daemon that didn't shut down cleanly are
+    // cleared before the APIs bind them
+    let workload_url = settings.listen().workload_uri().clone();
+    repair_socket(&workload_url)?;
+    let work_permissions =
+        socket_permissions(&workload_url, settings, SocketSettings::workload)?;
+    let work_tls = workload_tls(&workload_url, settings)?;
+    let work_limits = workload_limits(&settings.tuning());
+    let debug_endpoints = settings
+        .developer()
+        .map_or(false, Developer::debug_endpoints);
+    if debug_endpoints {
+        warn!("Workload debug endpoints are enabled. This must not be used in production.");
+    }
+
+    // modules deployed into a namespace are served a workload API of their
+    // own, on a socket in a directory of its own next to the shared one
+    let namespace_shutdown = CancellationToken::new();
+    let namespaces = match settings.listen().workload_uri() {
+        url if url.scheme() == UNIX_SCHEME => {
+            let serve = {
+                let key_store = key_store.clone();
+                let runtime = runtime.clone();
+                let crypto = crypto.clone();
+                let workload_config = workload_config.clone();
+                let registry = registry.clone();
+                let slots = slots.clone();
+                let generations = generations.clone();
+                let sequences = sequences.clone();
+                let host_processes = host_processes.clone();
+                let metrics = metrics.clone();
+                let limits = work_limits.clone();
+                let monitor = monitor.clone();
+                let rotation = rotation.clone();
+                let versions = versions.clone();
+                let validator = TokenValidator::new(id_man.clone());
+                let policy = settings.certificate_policy().cloned().unwrap_or_default();
+                let sampling = work_sampling.clone();
+                let signer = signer.clone();
+                let trusted_clock = trusted_clock.clone();
+                let permissions = work_permissions.clone();
+                let shutdown = namespace_shutdown.clone();
+                move |namespace: &str, socket: &Path| {
+                    info!("Starting workload API for namespace {}...", namespace);
+                    let url = socket
+                        .parent()
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .map_err(Error::from)
+                        .and_then(|()| {
+                            Url::parse(&format!("{}://{}", UNIX_SCHEME, socket.display()))
+                                .map_err(Error::from)
+                        }).and_then(|url| repair_socket(&url).map(|()| url));
+                    let namespace = namespace.to_string();
+                    match url {
+                        Ok(url) => {
+                            let workload = start_workload(
+                                url,
+                                None,
+                                permissions.clone(),
+                                None,
+                                &policy,
+                                &key_store,
+                                &runtime,
+                                shutdown.cancelled(),
+                                &crypto,
+                                workload_config.clone(),
+                                &registry,
+                                &slots,
+                                &generations,
+                                &sequences,
+                                &host_processes,
+                                &metrics,
+                                &limits,
+                                &monitor,
+                                &rotation,
+                                &versions,
+                                &trusted_clock,
+                                &validator,
+                                sampling.clone(),
+                                signer.as_ref(),
+                                debug_endpoints,
+                            );
+                            tokio::spawn(workload.map_err(move |err| {
+                                warn!(
+                                    "The workload API for namespace {} stopped: {}",
+                                    namespace, err
+                                )
+                            }));
+                        }
+                        Err(err) => {
+                            warn!(
+                                "Could not start the workload API for namespace {}:",
+                                namespace
+                            );
+                            log_failure(Level::Warn, &err);
+                        }
+                    }
+                }
+            };
+            let serve = Mutex::new(serve);
+            Namespaces::new()
+                .with_workload_socket(PathBuf::from(url.path()))
+                .with_listener(move |namespace, socket| {
+                    (*serve.lock().unwrap_or_else(PoisonError::into_inner))(namespace, socket)
+                })
+        }
+        _ => Namespaces::new(),
+    };
+    let runtime = runtime.with_namespaces(namespaces.clone());
+    register_namespaces(&runtime, &namespaces, &mut tokio_runtime)?;
+    // the rules of containers docker restarted on their own, with new
+    // addresses, are caught up with by enforcing the allowlists again
+    if cfg!(unix) {
+        tokio_runtime
+            .spawn(runtime.watch_egress(Duration::from_secs(EGRESS_ENFORCE_FREQUENCY_SECS)));
+    }
+
+    #[cfg(feature = "mgmt")]
+    let mgmt = {
+        let history_path = Path::new(&settings.homedir())
+            .join(EDGE_SETTINGS_SUBDIR)
+            .join(EDGE_DEPLOYMENT_HISTORY_FILENAME);
+        let history_size = settings.tuning().deployment_history_size();
+        let history = DeploymentHistory::load(history_path, history_size)?;
+        // the long running operations started through the management API
+        let operations = Operations::new();
+        // the responses to retried requests of the management API
+        let idempotency = IdempotencyKeys::load(
+            Path::new(&settings.homedir())
+                .join(EDGE_SETTINGS_SUBDIR)
+                .join(EDGE_IDEMPOTENCY_KEYS_FILENAME),
+            settings.tuning().idempotency_window(),
+        )?;
+        let management_url = settings.listen().management_uri();
+        repair_socket(management_url)?;
+        let permissions = socket_permissions(management_url, settings, SocketSettings::management)?;
+        let state = ManagementState::new(history, scheduler.clone(), rotation.clone(), idempotency)
+            .with_certificates(registry.clone(), slots.clone())
+            .with_outbox(outbox.clone())
+            .with_operations(operations)
+            .with_host_processes(host_processes.clone())
+            .with_usage(WorkloadUsage::new(metrics.clone()));
+        let state = if settings.management_state_backup() {
+            state.with_backup(state_backup(settings))
+        } else {
+            state
+        };
+        start_management(
+            &settings,
+            permissions,
+            &runtime,
+            &id_man,
+            &state,
+            &metrics,
+            mgmt_sampling,
+            work_sampling.clone(),
+            hyper_client,
+            mgmt_rx,
+        )
+    };
+    // without the management API the edge agent can't manage modules, and
+    // the modules that were deployed are left as they are
+    #[cfg(not(feature = "mgmt"))]
+    let mgmt = {
+        warn!("The management API is not built into this daemon.");
+        drop((mgmt_sampling, hyper_client));
+        mgmt_rx.then(|_| Ok::<_, failure::Error>(()))
+    };
+
+    // an offline device keeps its telemetry queued and its local settings,
+    // instead of failing to reach IoT Hub over and over
+    let online = !settings.offline();
+    if !online {
+        info!("The device is offline, IoT Hub won't be contacted.");
+    }
+
+    let (tele_tx, tele_rx) = oneshot::channel();
+    let telemetry = start_telemetry(
+        settings.telemetry().filter(|_| online),
+        outbox.clone(),
+        device_client.clone(),
+        tele_rx,
+    );
+
+    let (over_tx, over_rx) = oneshot::channel();
+    let overrides = start_twin_overrides(
+        settings.twin_overrides().filter(|_| online),
+        device_client,
+        workload_config.clone(),
+        Overridable::new(
+            settings.tuning().log_level(),
+            IOTEDGE_ID_CERT_MAX_DURATION_SECS,
+            IOTEDGE_SERVER_CERT_MAX_DURATION_SECS,
+        ),
+        over_rx,
+    );
 
     let workload = start_workload(
-        &settings,
+        workload_url.clone(),
+        pipe_access(&workload_url, settings, PipeAccess::workload),
+        work_permissions,
+        work_tls,
+        &settings.certificate_policy().cloned().unwrap_or_default(),
         key_store,
         &runtime,
-        work_rx,
+        work_rx.map_err(|_| ()),
         crypto,
         workload_config,
+        &registry,
+        &slots,
+        &generations,
+        &sequences,
+        &host_processes,
+        &metrics,
+        &work_limits,
+        &monitor,
+        rotation,
+        &versions,
+        &trusted_clock,
+        &TokenValidator::new(id_man.clone()),
+        work_sampling,
+        signer.as_ref(),
+        debug_endpoints,
     );
 
     let (runt_tx, runt_rx) = oneshot::channel();
-    let edge_rt = start_runtime(&runtime, &id_man, &hub_name, &device_id, &settings, runt_rx)?;
+    let edge_rt = match settings.fixed_modules() {
+        Some(fixed) => Either::A(start_fixed_modules(
+            fixed,
+            &runtime,
+            &id_man,
+            &env_provider,
+            settings.offline(),
+            runt_rx,
+        )?),
+        None => Either::B(start_runtime(
+            &runtime,
+            &id_man,
+            &env_provider,
+            &settings,
+            &outbox,
+            &metrics,
+            provisioning_payload,
+            runt_rx,
+        )?),
+    };
+
+    let (evt_tx, evt_rx) = oneshot::channel();
+    let events = start_module_events(
+        settings.telemetry(),
+        notifier,
+        runtime.clone(),
+        outbox,
+        evt_rx,
+    );
+
+    let (sched_tx, sched_rx) = oneshot::channel();
+    let (beat_tx, beat_rx) = oneshot::channel();
+    let (shed_tx, shed_rx) = oneshot::channel();
+    let (rot_tx, rot_rx) = oneshot::channel();
+    let (met_tx, met_rx) = oneshot::channel();
+    let scheduled = scheduler
+        .run_until(runtime.clone(), sched_rx.map_err(|_| ()))
+        .join(monitor.run_until(runtime.clone(), beat_rx.map_err(|_| ())))
+        .join(match shedder {
+            Some(shedder) => Either::A(shedder.run_until(runtime.clone(), shed_rx.map_err(|_| ()))),
+            None => Either::B(future::ok(())),
+        }).map_err(failure::Error::from)
+        .join(events.map_err(failure::Error::from))
+        .join5(
+            overrides.map_err(failure::Error::from),
+            telemetry.map_err(failure::Error::from),
+            watch_ca_rotation(rotation.clone(), rot_rx).map_err(failure::Error::from),
+            persist_metrics(metrics, met_rx),
+        ).map(|_| ());
+
+    let (disc_tx, disc_rx) = oneshot::channel();
+    let discovery = start_discovery(
+        settings.discovery(),
+        settings.hostname(),
+        signer.as_ref(),
+        disc_rx,
+    )?;
 
     // Wait for the watchdog to finish, and then send signal to the workload and management services.
     // This way the edgeAgent can finish shutting down all modules.
     let edge_rt_with_cleanup = edge_rt.map_err(Into::into).and_then(|_| {
         mgmt_tx.send(()).unwrap_or(());
         work_tx.send(()).unwrap_or(());
+        namespace_shutdown.cancel();
         future::ok(())
     });
 
     let shutdown = shutdown_signal.map(move |_| {
         debug!("shutdown signaled");
-        // Signal the watchdog to shutdown
+        // Stop running scheduled actions, heartbeat and twin checks, load shedding, watching the
+        // rotation of the Edge CA and sending telemetry and module events, persist the metrics one
+        // last time, and signal the watchdog to shutdown
+        sched_tx.send(()).unwrap_or(());
+        beat_tx.send(()).unwrap_or(());
+        shed_tx.send(()).unwrap_or(());
+        rot_tx.send(()).unwrap_or(());
+        over_tx.send(()).unwrap_or(());
+        tele_tx.send(()).unwrap_or(());
+        evt_tx.send(()).unwrap_or(());
+        met_tx.send(()).unwrap_or(());
+        disc_tx.send(()).unwrap_or(());
         runt_tx.send(()).unwrap_or(());
     });
     tokio_runtime.spawn(shutdown);
 
     let services = mgmt
-        .join3(workload, edge_rt_with_cleanup)
+        .join5(workload, edge_rt_with_cleanup, scheduled, discovery)
         .then(|result| match result {
-            Ok(((), (), ())) => Ok(()),
+            Ok(((), (), (), (), ())) => Ok(()),
             Err(err) => {
                 error!("{}", err);
                 Err(())
@@ -545,6 +1322,60 @@ where
     Ok(())
 }
 
+// Without the metrics feature the counters are only kept in memory, and go
+// nowhere.
+#[cfg(feature = "metrics")]
+fn load_metrics(settings: &Settings<DockerConfig>) -> Result<Metrics, Error> {
+    let metrics_path = Path::new(&settings.homedir())
+        .join(EDGE_SETTINGS_SUBDIR)
+        .join(EDGE_METRICS_FILENAME);
+    Ok(Metrics::load(metrics_path)?)
+}
+
+#[cfg(not(feature = "metrics"))]
+fn load_metrics(_settings: &Settings<DockerConfig>) -> Result<Metrics, Error> {
+    Ok(Metrics::new())
+}
+
+#[cfg(feature = "metrics")]
+fn persist_metrics(
+    metrics: Metrics,
+    shutdown: Receiver<()>,
+) -> impl Future<Item = (), Error = failure::Error> {
+    metrics
+        .run_until(
+            Duration::from_secs(METRICS_PERSIST_FREQUENCY_SECS),
+            shutdown.map_err(|_| ()),
+        ).map_err(failure::Error::from)
+}
+
+#[cfg(not(feature = "metrics"))]
+fn persist_metrics(
+    _metrics: Metrics,
+    shutdown: Receiver<()>,
+) -> impl Future<Item = (), Error = failure::Error> {
+    shutdown.then(|_| Ok(()))
+}
+
+// Namespaces of modules created before the daemon started are served their
+// workload API again.
+fn register_namespaces(
+    runtime: &DockerModuleRuntime,
+    namespaces: &Namespaces,
+    tokio_runtime: &mut tokio::runtime::Runtime,
+) -> Result<(), Error> {
+    let runtime = runtime.clone();
+    let namespaces = namespaces.clone();
+    tokio_runtime.block_on(future::lazy(move || {
+        runtime.list().map_err(Error::from).and_then(move |modules| {
+            for namespace in modules.iter().filter_map(Module::namespace) {
+                namespaces.register(namespace)?;
+            }
+            Ok::<_, Error>(())
+        })
+    }))
+}
+
 fn init_docker_runtime(
     runtime: &DockerModuleRuntime,
     tokio_runtime: &mut tokio::runtime::Runtime,
@@ -580,7 +1411,11 @@ fn dps_provision<HC, M>(
     provisioning: &Dps,
     hyper_client: HC,
     backup_path: PathBuf,
+    parent_hostname: Option<&str>,
+    offline: bool,
     runtime: M,
+    notifier: &Notifier,
+    cancellation: &CancellationToken,
     tokio_runtime: &mut tokio::runtime::Runtime,
 ) -> Result<(DerivedKeyStore<TpmKey>, ProvisioningResult, TpmKey, M), Error>
 where
@@ -588,26 +1423,52 @@ where
     M: ModuleRuntime + Send + 'static,
     M::Error: Into<Error>,
 {
+    let payload = provisioning
+        .payload_file()
+        .map(load_dps_payload)
+        .map_or(Ok(None), |payload| payload.map(Some))?;
+    let api_version = if payload.is_some() {
+        DPS_PAYLOAD_API_VERSION
+    } else {
+        DPS_API_VERSION
+    };
+
+    // the parent gateway of a nested device relays its registration to DPS
+    let mut endpoint = provisioning.global_endpoint().clone();
+    if let Some(parent_hostname) = parent_hostname {
+        endpoint.set_host(Some(parent_hostname))?;
+    }
+
     let tpm = Tpm::new().map_err(Error::from)?;
     let ek_result = tpm.get_ek().map_err(Error::from)?;
     let srk_result = tpm.get_srk().map_err(Error::from)?;
     let dps = DpsProvisioning::new(
         hyper_client,
-        provisioning.global_endpoint().clone(),
+        endpoint,
         provisioning.scope_id().to_string(),
         provisioning.registration_id().to_string(),
-        "2017-11-15",
+        api_version,
         ek_result,
         srk_result,
-    )?;
+    )?.with_cancellation(cancellation.clone());
+    let dps = match payload {
+        Some(payload) => dps.with_payload(payload),
+        None => dps,
+    };
     let tpm_hsm = TpmKeyStore::from_hsm(tpm)?;
-    let provision_with_file_backup = BackupProvisioning::new(dps, backup_path);
+    let provision_with_file_backup =
+        BackupProvisioning::new(dps, backup_path).with_offline(offline);
+    let notifier = notifier.clone();
     let provision = provision_with_file_backup
         .provision(tpm_hsm.clone())
         .map_err(Error::from)
-        .and_then(|prov_result| {
+        .and_then(move |prov_result| {
             if prov_result.reconfigure() {
                 info!("Successful DPS provisioning. This will trigger reconfiguration of modules.");
+                notifier.notify(&provisioning_changed(
+                    prov_result.device_id(),
+                    prov_result.hub_name(),
+                ));
                 // Each time DPS provisions, it gets back a new device key. This results in obsolete
                 // module keys in IoTHub from the previous provisioning. We delete all containers
                 // after each DPS provisioning run so that IoTHub can be updated with new module
@@ -633,12 +1494,22 @@ where
     tokio_runtime.block_on(provision)
 }
 
+/// Reads the JSON payload to send with the DPS registration.
+fn load_dps_payload(path: &Path) -> Result<serde_json::Value, Error> {
+    let payload = fs::read_to_string(path).context(ErrorKind::DpsPayload)?;
+    let payload = serde_json::from_str(&payload).context(ErrorKind::DpsPayload)?;
+    Ok(payload)
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
 fn start_runtime<K, HC>(
     runtime: &DockerModuleRuntime,
     id_man: &HubIdentityManager<DerivedKeyStore<K>, HC, K>,
-    hostname: &str,
-    device_id: &str,
+    env_provider: &EnvProvider,
     settings: &Settings<DockerConfig>,
+    outbox: &Outbox,
+    metrics: &Metrics,
+    provisioning_payload: Option<&serde_json::Value>,
     shutdown: Receiver<()>,
 ) -> Result<impl Future<Item = (), Error = Error>, Error>
 where
@@ -646,13 +1517,15 @@ where
     HC: 'static + ClientImpl,
 {
     let spec = settings.agent().clone();
-    let env = build_env(spec.env(), hostname, device_id, settings);
+    let env = build_env(spec.env(), env_provider, settings, provisioning_payload);
+    // the edge runtime module manages every other module, so it is the last
+    // thing that should be sacrificed when the device runs out of memory
     let mut spec = ModuleSpec::<DockerConfig>::new(
         EDGE_RUNTIME_MODULE_NAME,
         spec.type_(),
         spec.config().clone(),
         env,
-    )?;
+    )?.with_priority(ModulePriority::Critical);
 
     // volume mount management and workload URIs
     vol_mount_uri(
@@ -663,7 +1536,12 @@ where
         ],
     )?;
 
-    let watchdog = Watchdog::new(runtime.clone(), id_man.clone());
+    let mut watchdog = Watchdog::new(runtime.clone(), id_man.clone())
+        .with_outbox(outbox.clone())
+        .with_metrics(metrics.clone());
+    if let Some(frequency) = settings.tuning().watchdog_interval() {
+        watchdog = watchdog.with_frequency(frequency);
+    }
     let runtime_future = watchdog
         .run_until(spec, EDGE_RUNTIME_MODULEID, shutdown.map_err(|_| ()))
         .map_err(Error::from);
@@ -699,22 +1577,11 @@ fn vol_mount_uri(config: &mut DockerConfig, uris: &[&Url]) -> Result<(), Error>
 // Add the environment variables needed by the EdgeAgent.
 fn build_env(
     spec_env: &HashMap<String, String>,
-    hostname: &str,
-    device_id: &str,
+    env_provider: &EnvProvider,
     settings: &Settings<DockerConfig>,
+    provisioning_payload: Option<&serde_json::Value>,
 ) -> HashMap<String, String> {
     let mut env = HashMap::new();
-    env.insert(HOSTNAME_KEY.to_string(), hostname.to_string());
-    env.insert(
-        GATEWAY_HOSTNAME_KEY.to_string(),
-        settings.hostname().to_string().to_lowercase(),
-    );
-    env.insert(DEVICEID_KEY.to_string(), device_id.to_string());
-    env.insert(MODULEID_KEY.to_string(), EDGE_RUNTIME_MODULEID.to_string());
-    env.insert(
-        WORKLOAD_URI_KEY.to_string(),
-        settings.connect().workload_uri().to_string(),
-    );
     env.insert(
         MANAGEMENT_URI_KEY.to_string(),
         settings.connect().management_uri().to_string(),
@@ -728,17 +1595,27 @@ fn build_env(
         EDGE_NETWORKID_KEY.to_string(),
         settings.moby_runtime().network().to_string(),
     );
+    if let Some(payload) = provisioning_payload {
+        env.insert(PROVISIONING_PAYLOAD_KEY.to_string(), payload.to_string());
+    }
     for (key, val) in spec_env.iter() {
         env.insert(key.clone(), val.clone());
     }
-    env.insert(API_VERSION_KEY.to_string(), API_VERSION.to_string());
-    env
+    env_provider.apply(EDGE_RUNTIME_MODULEID, &env)
 }
 
+#[cfg(feature = "mgmt")]
+#[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
 fn start_management<K, HC>(
     settings: &Settings<DockerConfig>,
+    permissions: Option<SocketPermissions>,
     mgmt: &DockerModuleRuntime,
     id_man: &HubIdentityManager<DerivedKeyStore<K>, HC, K>,
+    state: &ManagementState,
+    metrics: &Metrics,
+    sampling: LogSampling,
+    work_sampling: LogSampling,
+    blob_client: HC,
     shutdown: Receiver<()>,
 ) -> impl Future<Item = (), Error = failure::Error>
 where
@@ -749,30 +1626,313 @@ where
 
     let label = "mgmt".to_string();
     let url = settings.listen().management_uri().clone();
+    let allowed = pipe_access(&url, settings, PipeAccess::management);
+
+    let image_builder = settings
+        .developer()
+        .filter(|developer| developer.image_build())
+        .map(|_| mgmt.clone());
+    let console = settings
+        .developer()
+        .filter(|developer| developer.attach())
+        .map(|_| mgmt.clone());
+    let disk_usage = mgmt.clone();
+    let homedir = settings.homedir().to_path_buf();
+    let listed = sampling.clone();
+    let metrics = metrics.clone();
+    let rebinds = metrics.clone();
+    let host = HostSystemInfo::new(settings.homedir())
+        .with_hsm_version(hsm::version())
+        .with_offline(settings.offline());
+    let read_only = settings.read_only_management();
+
+    if settings.export_identity_keys() {
+        warn!("The management API returns the symmetric keys of module identities.");
+    }
+
+    ManagementService::new(
+        mgmt,
+        id_man,
+        settings.export_identity_keys(),
+        state,
+        &host,
+        blob_client,
+    ).map(|service| match image_builder {
+        Some(image_builder) => {
+            warn!("Image builds are enabled. This must not be used in production.");
+            service.with_image_builder(image_builder.clone(), image_builder)
+        }
+        None => service,
+    }).map(|service| match console {
+        Some(console) => {
+            warn!("Attaching to modules is enabled. This must not be used in production.");
+            service.with_attach(console)
+        }
+        None => service,
+    }).map(move |service| {
+        service
+            .with_disk_usage(disk_usage, homedir)
+            .with_log_sampling("management", listed)
+            .with_log_sampling("workload", work_sampling)
+    }).map(move |service| {
+        if read_only {
+            info!("The management API is read-only, changes only come from deployments.");
+            service.with_read_only()
+        } else {
+            service
+        }
+    }).map(move |service| serve_metrics(service, metrics)).map(move |service| {
+        LoggingService::new(label, ApiVersionService::new(service)).with_sampling(sampling)
+    }).and_then(move |service| {
+        let server = Http::new()
+            .bind_url_with_permissions(
+                url.clone(),
+                service,
+                allowed.as_ref().map(Vec::as_slice),
+                permissions.as_ref(),
+            ).map_err(failure::Fail::compat)?;
+        count_rebind(&server, &rebinds);
+        let run = server.run_until(shutdown.map_err(|_| ()));
+        info!("Listening on {} with 1 thread for management API.", url);
+        Ok(run)
+    }).flatten()
+}
+
+/// Counts the sockets inherited from systemd that were unusable, which
+/// happens when systemd restarts a daemon that crashed.
+fn count_rebind<S>(server: &Server<S>, metrics: &Metrics) {
+    if server.rebound() {
+        metrics.increment(SOCKET_REBINDS);
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn serve_metrics(service: ManagementService, metrics: Metrics) -> ManagementService {
+    service.with_metrics(metrics)
+}
+
+#[cfg(all(feature = "mgmt", not(feature = "metrics")))]
+fn serve_metrics(service: ManagementService, _metrics: Metrics) -> ManagementService {
+    service
+}
 
-    ManagementService::new(mgmt, id_man)
-        .map(|service| LoggingService::new(label, ApiVersionService::new(service)))
-        .and_then(move |service| {
-            let run = Http::new()
-                .bind_url(url.clone(), service)
-                .map_err(failure::Fail::compat)?
-                .run_until(shutdown.map_err(|_| ()));
-            info!("Listening on {} with 1 thread for management API.", url);
-            Ok(run)
-        }).flatten()
+/// Returns the state of the daemon that the management API backs up and
+/// restores. Queued messages, metrics and the registry authentication record
+/// are left out, since they are only of use on the device they were written on.
+#[cfg(feature = "mgmt")]
+fn state_backup(settings: &Settings<DockerConfig>) -> StateBackup {
+    let cache = settings.homedir().join(EDGE_SETTINGS_SUBDIR);
+    let cached = &[
+        EDGE_PROVISIONING_BACKUP_FILENAME,
+        EDGE_SETTINGS_STATE_FILENAME,
+        EDGE_DEPLOYMENT_HISTORY_FILENAME,
+        EDGE_ISSUED_CERTIFICATES_FILENAME,
+    ];
+
+    let enc_keys = settings
+        .homedir()
+        .join(EDGE_HSM_SUBDIR)
+        .join(EDGE_HSM_ENC_KEYS_SUBDIR);
+    let state = cached
+        .iter()
+        .fold(StateBackup::new(), |state, name| {
+            state.with_entry(name, cache.join(name))
+        }).with_entry(EDGE_HSM_ENC_KEYS_SUBDIR, enc_keys);
+
+    match settings.config_file() {
+        Some(config_file) => state.with_entry(EDGE_CONFIG_BACKUP_NAME, config_file.to_path_buf()),
+        None => state,
+    }
+}
+
+/// Returns the sampling of the access log of an API that `rules` configure.
+fn log_sampling(rules: &[SamplingRule]) -> Result<LogSampling, Error> {
+    rules.iter().fold(Ok(LogSampling::new()), |sampling, rule| {
+        sampling?
+            .with_rule(rule.route(), rule.success_percent(), rule.error_percent())
+            .map_err(Error::from)
+    })
+}
+
+/// Returns who may connect to the API listening on `url`, if it is a named
+/// pipe and access to it is configured.
+fn pipe_access<F>(url: &Url, settings: &Settings<DockerConfig>, allowed: F) -> Option<Vec<String>>
+where
+    F: Fn(&PipeAccess) -> &[String],
+{
+    settings.listen().pipe_access().and_then(|access| {
+        if url.scheme() == "npipe" {
+            Some(allowed(access).to_vec())
+        } else {
+            warn!("Ignoring the pipe access settings for {}, which is not a named pipe.", url);
+            None
+        }
+    })
 }
 
-fn start_workload<K, C, W>(
+/// Returns the permissions the socket of the API listening on `url` is given,
+/// if it is a Unix domain socket and they are configured.
+fn socket_permissions<F>(
+    url: &Url,
     settings: &Settings<DockerConfig>,
+    configured: F,
+) -> Result<Option<SocketPermissions>, Error>
+where
+    F: Fn(&SocketSettings) -> Option<&Permissions>,
+{
+    let configured = match settings.listen().socket_permissions().and_then(configured) {
+        Some(configured) => configured,
+        None => return Ok(None),
+    };
+    if url.scheme() != UNIX_SCHEME {
+        warn!(
+            "Ignoring the socket permissions for {}, which is not a Unix domain socket.",
+            url
+        );
+        return Ok(None);
+    }
+
+    let mut permissions = SocketPermissions::new();
+    if let Some(mode) = configured.mode() {
+        let mode = u32::from_str_radix(mode, 8).context(ErrorKind::SocketPermissions)?;
+        permissions = permissions.with_mode(mode);
+    }
+    #[cfg(unix)]
+    {
+        if let Some(owner) = configured.owner() {
+            permissions = permissions
+                .with_owner(owner)
+                .context(ErrorKind::SocketPermissions)?;
+        }
+        if let Some(group) = configured.group() {
+            permissions = permissions
+                .with_group(group)
+                .context(ErrorKind::SocketPermissions)?;
+        }
+    }
+    Ok(Some(permissions))
+}
+
+/// Returns the TLS the API listening on `url` is served with, if it listens
+/// on TCP and the workload TLS is configured.
+fn workload_tls(url: &Url, settings: &Settings<DockerConfig>) -> Result<Option<ServerTls>, Error> {
+    let configured = match settings.listen().workload_tls() {
+        Some(configured) => configured,
+        None => return Ok(None),
+    };
+    if url.scheme() != "http" && url.scheme() != "tcp" {
+        warn!("Ignoring the workload TLS settings for {}, which is not a TCP socket.", url);
+        return Ok(None);
+    }
+
+    let tls = ServerTls::new(configured.cert(), configured.key(), configured.client_ca())?;
+    let tls = configured.peers().iter().fold(tls, |tls, peer| {
+        tls.with_peer(peer.common_name(), peer.modules().to_vec())
+    });
+    Ok(Some(tls))
+}
+
+/// Clears what a daemon that didn't shut down cleanly may have left where the
+/// API listening on `url` binds its socket, if it is a Unix domain socket.
+#[cfg(unix)]
+fn repair_socket(url: &Url) -> Result<(), Error> {
+    if url.scheme() == UNIX_SCHEME {
+        edgelet_http::repair_socket(url.path()).context(ErrorKind::Socket)?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn repair_socket(_url: &Url) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Destroys and revokes the server certificate issued for a superseded
+/// generation of a module identity, freeing its HSM key slot. Module keys
+/// are derived from the generation ID, so nothing is left of the old ones to
+/// clean up. A certificate may still be under the alias it was issued under
+/// before aliases were separated, so that one is retired as well.
+fn retire_generation<C>(
+    crypto: &C,
+    registry: &CertificateRegistry,
+    slots: &KeySlots,
+    module: &str,
+    generation_id: &str,
+) where
+    C: CreateCertificate,
+{
+    let alias = CertificateAlias::server(module, generation_id);
+    for alias in &[alias.to_string(), alias.legacy()] {
+        if let Err(err) = crypto.destroy_certificate(alias.clone()) {
+            warn!("Could not destroy certificate {}: {}", alias, err);
+        }
+        slots.release(alias);
+
+        let issued = registry
+            .list()
+            .into_iter()
+            .filter(|cert| cert.alias() == alias && cert.revoked_at().is_none());
+        for cert in issued {
+            if let Err(err) = registry.revoke(cert.serial_number()) {
+                warn!(
+                    "Could not revoke certificate {} of module {}: {}",
+                    cert.serial_number(),
+                    module,
+                    err
+                );
+            }
+        }
+    }
+}
+
+// Each class of workload operation is handled within a limit of its own, if
+// one is set.
+fn workload_limits(tuning: &Tuning) -> WorkloadLimits {
+    let mut limits = WorkloadLimits::new();
+    if let Some(limit) = tuning.workload_cert_concurrency() {
+        limits = limits.with_cert_limit(limit);
+    }
+    if let Some(limit) = tuning.workload_sign_concurrency() {
+        limits = limits.with_sign_limit(limit);
+    }
+    if let Some(limit) = tuning.workload_encrypt_concurrency() {
+        limits = limits.with_encrypt_limit(limit);
+    }
+    limits
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+fn start_workload<K, C, W, S, F>(
+    url: Url,
+    allowed: Option<Vec<String>>,
+    permissions: Option<SocketPermissions>,
+    tls: Option<ServerTls>,
+    policy: &CertificatePolicy,
     key_store: &K,
     runtime: &DockerModuleRuntime,
-    shutdown: Receiver<()>,
+    shutdown: F,
     crypto: &C,
     config: W,
+    registry: &CertificateRegistry,
+    slots: &KeySlots,
+    generations: &GenerationRegistry,
+    sequences: &SequenceNumbers,
+    host_processes: &HostProcesses,
+    metrics: &Metrics,
+    limits: &WorkloadLimits,
+    monitor: &HeartbeatMonitor,
+    rotation: &CaRotation,
+    versions: &TrustBundleVersions,
+    trusted_clock: &TrustedClock,
+    validator: &TokenValidator<S>,
+    sampling: LogSampling,
+    signer: Option<&ResponseSigner>,
+    debug_endpoints: bool,
 ) -> impl Future<Item = (), Error = failure::Error>
 where
     K: KeyStore + Clone + Send + Sync + 'static,
     C: CreateCertificate
+        + CreateCrl
         + Decrypt
         + Encrypt
         + GetTrustBundle
@@ -782,22 +1942,60 @@ where
         + Sync
         + 'static,
     W: WorkloadConfig + Clone + Send + Sync + 'static,
+    S: IdentityKeySource + Clone + Send + Sync + 'static,
+    F: Future<Item = (), Error = ()> + Send + 'static,
 {
     info!("Starting workload API...");
 
     let label = "work".to_string();
-    let url = settings.listen().workload_uri().clone();
+    // the calls of the modules are counted in the metrics, where the
+    // management API reads them from
+    let usage = WorkloadUsage::new(metrics.clone());
+    let metrics = metrics.clone();
 
-    WorkloadService::new(key_store, crypto.clone(), runtime, config)
-        .map(|service| LoggingService::new(label, ApiVersionService::new(service)))
-        .and_then(move |service| {
-            let run = Http::new()
-                .bind_url(url.clone(), service)
-                .map_err(failure::Fail::compat)?
-                .run_until(shutdown.map_err(|_| ()));
-            info!("Listening on {} with 1 thread for workload API.", url);
-            Ok(run)
-        }).flatten()
+    WorkloadService::new(
+        key_store,
+        crypto.clone(),
+        runtime,
+        config,
+        registry,
+        slots,
+        policy,
+        generations,
+        sequences,
+        host_processes,
+        limits,
+        monitor,
+        rotation,
+        versions,
+        &usage,
+        trusted_clock,
+        validator,
+        signer,
+    ).map(move |service| {
+        let service = if debug_endpoints {
+            service.with_debug_endpoints()
+        } else {
+            service
+        };
+        LoggingService::new(label, ApiVersionService::new(service)).with_sampling(sampling)
+    }).and_then(move |service| {
+        let server = Http::new()
+            .bind_url_with_permissions(
+                url.clone(),
+                service,
+                allowed.as_ref().map(Vec::as_slice),
+                permissions.as_ref(),
+            ).map_err(failure::Fail::compat)?;
+        let server = match tls {
+            Some(tls) => server.with_tls(tls),
+            None => server,
+        };
+        count_rebind(&server, &metrics);
+        let run = server.run_until(shutdown);
+        info!("Listening on {} with 1 thread for workload API.", url);
+        Ok(run)
+    }).flatten()
 }
 
 #[cfg(test)]
@@ -892,9 +2090,7 @@ mod tests {
             &crypto,
             &mut tokio_runtime,
         ).unwrap();
-        let expected = serde_json::to_string(&settings).unwrap();
-        let expected_sha = Sha256::digest_str(&expected);
-        let expected_base64 = base64::encode(&expected_sha);
+        let expected_base64 = settings.state_hash().unwrap();
         let mut written = String::new();
         File::open(tmp_dir.path().join("settings_state"))
             .unwrap()
@@ -939,9 +2135,7 @@ mod tests {
             &crypto,
             &mut tokio_runtime,
         ).unwrap();
-        let expected = serde_json::to_string(&settings1).unwrap();
-        let expected_sha = Sha256::digest_str(&expected);
-        let expected_base64 = base64::encode(&expected_sha);
+        let expected_base64 = settings1.state_hash().unwrap();
         let mut written1 = String::new();
         File::open(tmp_dir.path().join("settings_state"))
             .unwrap()
@@ -986,4 +2180,29 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn dps_payload_is_loaded_from_file() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("payload.json");
+        File::create(&path)
+            .unwrap()
+            .write_all(b"{ \"site\": \"plant-1\" }")
+            .unwrap();
+        let payload = load_dps_payload(&path).unwrap();
+        assert_eq!(Some("plant-1"), payload["site"].as_str());
+
+        File::create(&path).unwrap().write_all(b"site").unwrap();
+        match *load_dps_payload(&path).unwrap_err().kind() {
+            ErrorKind::DpsPayload => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+        match *load_dps_payload(&tmp_dir.path().join("missing.json"))
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::DpsPayload => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
 }
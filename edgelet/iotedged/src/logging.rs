@@ -5,7 +5,7 @@ use std::io::Write;
 
 use edgelet_utils::log_failure;
 use env_logger;
-use log::{Level, LevelFilter};
+use log::{self, Level, LevelFilter};
 #[cfg(target_os = "windows")]
 use win_logger::EventLogger;
 
@@ -52,6 +52,14 @@ pub fn init() {
         .init();
 }
 
+/// Lowers the log level to the one chosen in the settings, unless it was
+/// set with the IOTEDGE_LOG environment variable.
+pub fn set_max_level(level: LevelFilter) {
+    if env::var(ENV_LOG).is_err() {
+        log::set_max_level(level);
+    }
+}
+
 #[cfg(target_os = "windows")]
 pub fn init_win_log() {
     let mut min_log_level = "info".to_string();
@@ -0,0 +1,351 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Migration of config files written for older versions of the daemon.
+//!
+//! The layout of the config file is versioned by its `schema_version`, which
+//! is 1 when the file doesn't say. A file with an older layout has the
+//! migrations from its version on applied to it, in order, before it is
+//! loaded. The original file is left alone. The migrated file is written next
+//! to it, with a report of the migrations that were applied, so that it can
+//! replace the original once it has been looked over.
+//!
+//! Every setting a migration moves is deprecated, since older layouts are
+//! only read until `LEGACY_LAYOUT_REMOVAL`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use edgelet_core::Deprecation;
+use failure::ResultExt;
+use serde_yaml::{self, Mapping, Value};
+
+use error::{Error, ErrorKind};
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+pub const CURRENT_SCHEMA_VERSION: u64 = 2;
+
+const MIGRATED_SUFFIX: &str = "migrated";
+const REPORT_SUFFIX: &str = "migration.log";
+
+/// The release that stops migrating config files with an older layout.
+pub const LEGACY_LAYOUT_REMOVAL: &str = "1.2.0";
+
+struct Migration {
+    /// The version the migration upgrades from to the next one.
+    from: u64,
+    description: &'static str,
+    /// The setting of the older layout the migration moves.
+    deprecated: &'static str,
+    /// The setting of the newer layout it is moved to.
+    replacement: &'static str,
+    /// Moves the setting, and returns whether the file had it.
+    apply: fn(&mut Mapping) -> bool,
+}
+
+impl Migration {
+    fn deprecation(&self) -> Deprecation {
+        Deprecation::new(self.deprecated, LEGACY_LAYOUT_REMOVAL).with_replacement(self.replacement)
+    }
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    description: "moby_runtime.docker_uri was renamed to moby_runtime.uri",
+    deprecated: "moby_runtime.docker_uri",
+    replacement: "moby_runtime.uri",
+    apply: rename_docker_uri,
+}];
+
+fn rename_docker_uri(settings: &mut Mapping) -> bool {
+    move_value(
+        settings,
+        &["moby_runtime", "docker_uri"],
+        &["moby_runtime", "uri"],
+    );
+}
+
+/// A config file migrated to the current schema version.
+#[derive(Debug)]
+pub struct Migrated {
+    from: u64,
+    applied: Vec<&'static str>,
+    deprecations: Vec<Deprecation>,
+    yaml: String,
+}
+
+impl Migrated {
+    pub fn yaml(&self) -> &str {
+        &self.yaml
+    }
+
+    /// The settings of the older layout the file had.
+    pub fn deprecations(&self) -> &[Deprecation] {
+        &self.deprecations
+    }
+
+    fn report(&self, path: &Path) -> String {
+        let mut report = format!(
+            "Migrated {} from schema version {} to {}:\n",
+            path.display(),
+            self.from,
+            CURRENT_SCHEMA_VERSION
+        );
+        for description in &self.applied {
+            report.push_str(&format!("- {}\n", description));
+        }
+        report
+    }
+}
+
+/// Migrates the config file at `path` if it has an older layout, in which
+/// case the migrated file is returned. A file that can't be read or parsed is
+/// left to the settings to report on.
+pub fn migrate_file(path: &Path) -> Result<Option<Migrated>, Error> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(None),
+    };
+    let migrated = match migrate(&contents)? {
+        Some(migrated) => migrated,
+        None => return Ok(None),
+    };
+
+    for description in &migrated.applied {
+        info!("Migrated config file {}: {}", path.display(), description);
+    }
+    if let Err(err) = write_migrated(path, &migrated) {
+        warn!(
+            "Could not write the migrated config file next to {}: {}",
+            path.display(),
+            err
+        );
+    }
+    Ok(Some(migrated))
+}
+
+fn migrate(contents: &str) -> Result<Option<Migrated>, Error> {
+    let mut settings = match serde_yaml::from_str(contents) {
+        Ok(Value::Mapping(settings)) => settings,
+        _ => return Ok(None),
+    };
+
+    let version_key = Value::String(SCHEMA_VERSION_KEY.to_string());
+    let from = settings
+        .get(&version_key)
+        .and_then(Value::as_u64)
+        .unwrap_or(1);
+    if from > CURRENT_SCHEMA_VERSION {
+        warn!(
+            "The config file has schema version {}, which is newer than the {} this daemon \
+             understands.",
+            from, CURRENT_SCHEMA_VERSION
+        );
+        return Ok(None);
+    }
+
+    let migrations: Vec<&Migration> = MIGRATIONS
+        .iter()
+        .filter(|migration| migration.from >= from)
+        .collect();
+    if migrations.is_empty() {
+        return Ok(None);
+    }
+    let deprecations = migrations
+        .iter()
+        .filter(|migration| (migration.apply)(&mut settings))
+        .map(|migration| migration.deprecation())
+        .collect();
+
+    settings.insert(version_key, Value::Number(CURRENT_SCHEMA_VERSION.into()));
+    let yaml = serde_yaml::to_string(&Value::Mapping(settings)).context(ErrorKind::Settings)?;
+    Ok(Some(Migrated {
+        from,
+        applied: migrations
+            .iter()
+            .map(|migration| migration.description)
+            .collect(),
+        deprecations,
+        yaml,
+    }))
+}
+
+fn write_migrated(path: &Path, migrated: &Migrated) -> io::Result<()> {
+    fs::write(with_suffix(path, MIGRATED_SUFFIX), migrated.yaml())?;
+    fs::write(with_suffix(path, REPORT_SUFFIX), migrated.report(path))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Moves the value at the path `from` in the settings to the path `to`, which
+// can be in another section. A value already at `to` is kept, since it was
+// set for the newer layout. Returns whether there was a value at `from`.
+fn move_value(settings: &mut Mapping, from: &[&str], to: &[&str]) -> bool {
+    let value = match remove_value(settings, from) {
+        Some(value) => value,
+        None => return false,
+    };
+
+    let (last, sections) = to.split_last().expect("empty settings path");
+    let mut section = settings;
+    for name in sections {
+        let key = Value::String(name.to_string());
+        if !section.contains_key(&key) {
+            section.insert(key.clone(), Value::Mapping(Mapping::new()));
+        }
+        section = match { section }.get_mut(&key) {
+            Some(&mut Value::Mapping(ref mut mapping)) => mapping,
+            _ => return true,
+        };
+    }
+
+    let key = Value::String(last.to_string());
+    if !section.contains_key(&key) {
+        section.insert(key, value);
+    }
+    true
+}
+
+fn remove_value(settings: &mut Mapping, path: &[&str]) -> Option<Value> {
+    let (last, sections) = path.split_last()?;
+    let mut section = settings;
+    for name in sections {
+        section = match { section }.get_mut(&Value::String(name.to_string())) {
+            Some(&mut Value::Mapping(ref mut mapping)) => mapping,
+            _ => return None,
+        };
+    }
+    section.remove(&Value::String(last.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    const V1_SETTINGS: &str = "\
+hostname: \"gateway\"
+moby_runtime:
+  docker_uri: \"unix:///var/run/docker.sock\"
+  network: \"azure-iot-edge\"
+";
+
+    fn mapping(yaml: &str) -> Mapping {
+        match serde_yaml::from_str(yaml).unwrap() {
+            Value::Mapping(mapping) => mapping,
+            value => panic!("unexpected value {:?}", value),
+        }
+    }
+
+    fn lookup<'a>(settings: &'a Mapping, path: &[&str]) -> Option<&'a Value> {
+        let (last, sections) = path.split_last().unwrap();
+        let mut section = settings;
+        for name in sections {
+            section = match section.get(&Value::String(name.to_string())) {
+                Some(&Value::Mapping(ref mapping)) => mapping,
+                _ => return None,
+            };
+        }
+        section.get(&Value::String(last.to_string()))
+    }
+
+    #[test]
+    fn old_layouts_are_migrated() {
+        let migrated = migrate(V1_SETTINGS).unwrap().unwrap();
+        assert_eq!(1, migrated.from);
+        assert_eq!(
+            vec!["moby_runtime.docker_uri was renamed to moby_runtime.uri"],
+            migrated.applied
+        );
+        assert_eq!(
+            &[
+                Deprecation::new("moby_runtime.docker_uri", LEGACY_LAYOUT_REMOVAL)
+                    .with_replacement("moby_runtime.uri")
+            ],
+            migrated.deprecations()
+        );
+
+        let settings = mapping(migrated.yaml());
+        assert_eq!(
+            Some("unix:///var/run/docker.sock"),
+            lookup(&settings, &["moby_runtime", "uri"]).and_then(Value::as_str)
+        );
+        assert_eq!(
+            Some("azure-iot-edge"),
+            lookup(&settings, &["moby_runtime", "network"]).and_then(Value::as_str)
+        );
+        assert!(lookup(&settings, &["moby_runtime", "docker_uri"]).is_none());
+        assert_eq!(
+            Some(CURRENT_SCHEMA_VERSION),
+            lookup(&settings, &[SCHEMA_VERSION_KEY]).and_then(Value::as_u64)
+        );
+    }
+
+    #[test]
+    fn current_and_newer_layouts_are_left_alone() {
+        let current = "schema_version: 2\nmoby_runtime:\n  uri: \"http://localhost:2375\"\n";
+        assert!(migrate(current).unwrap().is_none());
+
+        let newer = "schema_version: 99\nmoby_runtime:\n  docker_uri: \"http://localhost:2375\"\n";
+        assert!(migrate(newer).unwrap().is_none());
+
+        assert!(migrate("not: [valid").unwrap().is_none());
+    }
+
+    #[test]
+    fn only_settings_the_file_had_are_deprecated() {
+        let migrated = migrate("hostname: \"gateway\"\n").unwrap().unwrap();
+        assert_eq!(1, migrated.applied.len());
+        assert!(migrated.deprecations().is_empty());
+    }
+
+    #[test]
+    fn values_set_for_the_newer_layout_are_kept() {
+        let mut settings =
+            mapping("moby_runtime:\n  docker_uri: \"http://old\"\n  uri: \"http://new\"\n");
+        assert!(rename_docker_uri(&mut settings));
+        assert_eq!(
+            Some("http://new"),
+            lookup(&settings, &["moby_runtime", "uri"]).and_then(Value::as_str)
+        );
+        assert!(lookup(&settings, &["moby_runtime", "docker_uri"]).is_none());
+    }
+
+    #[test]
+    fn values_move_across_sections() {
+        let mut settings = mapping("old:\n  value: 1\n");
+        assert!(move_value(
+            &mut settings,
+            &["old", "value"],
+            &["new", "nested", "value"]
+        ));
+        assert_eq!(
+            Some(1),
+            lookup(&settings, &["new", "nested", "value"]).and_then(Value::as_u64)
+        );
+        assert!(lookup(&settings, &["old", "value"]).is_none());
+    }
+
+    #[test]
+    fn migrated_file_and_report_are_written_next_to_the_original() {
+        let tmp_dir = TempDir::new("migration").unwrap();
+        let path = tmp_dir.path().join("config.yaml");
+        fs::write(&path, V1_SETTINGS).unwrap();
+
+        let migrated = migrate_file(&path).unwrap().unwrap();
+        assert_eq!(V1_SETTINGS, fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            migrated.yaml(),
+            fs::read_to_string(tmp_dir.path().join("config.yaml.migrated")).unwrap()
+        );
+        let report = fs::read_to_string(tmp_dir.path().join("config.yaml.migration.log")).unwrap();
+        assert!(report.contains("from schema version 1 to 2"));
+        assert!(report.contains("- moby_runtime.docker_uri was renamed to moby_runtime.uri"));
+    }
+}
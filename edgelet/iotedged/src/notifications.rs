@@ -0,0 +1,197 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Notifications of events on the device to the webhooks in the settings.
+//!
+//! Modules in a crash loop are reported by the module event forwarder and a
+//! new identity or hub by the DPS provisioning. The Edge CA is checked once a
+//! day, and reported as expiring every day it expires within the warning of
+//! the settings, so that a receiver that missed a notification hears of it
+//! again.
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use edgelet_core::crypto::{Certificate, GetTrustBundle};
+use edgelet_core::{
+    certificate_expiry, CancellationToken, Notification, NotificationEvent, Notifier,
+};
+use edgelet_http::webhook::{Webhook, Webhooks};
+use edgelet_utils::log_failure;
+use failure::ResultExt;
+use futures::{Future, Stream};
+use log::Level;
+use tokio::runtime::TaskExecutor;
+use tokio::timer::Interval;
+
+use error::{Error, ErrorKind};
+use settings::{Certificates, Notifications, Webhook as WebhookSettings};
+
+/// This is how often the expiry of the Edge CA is checked.
+const EDGE_CA_EXPIRY_CHECK_SECS: u64 = 24 * 60 * 60;
+
+/// This is the subject of the notifications of the Edge CA.
+const EDGE_CA_SUBJECT: &str = "edgeCa";
+
+pub type ExpiryCheck = Box<Fn() -> Result<DateTime<Utc>, Error> + Send>;
+
+/// Creates the notifier that sends notifications to the webhooks of
+/// `notifications`, on `executor`. Without webhooks it discards them.
+pub fn load_notifier(
+    notifications: Option<&Notifications>,
+    executor: TaskExecutor,
+) -> Result<Notifier, Error> {
+    let mut webhooks = Webhooks::new(executor);
+    for settings in notifications.map_or(&[][..], Notifications::webhooks) {
+        info!("Notifying {} of events on the device", settings.url());
+        webhooks = webhooks.with_webhook(webhook(settings)?);
+    }
+
+    if webhooks.is_empty() {
+        Ok(Notifier::new())
+    } else {
+        Ok(Notifier::new().with_sink(webhooks))
+    }
+}
+
+fn webhook(settings: &WebhookSettings) -> Result<Webhook, Error> {
+    let mut webhook = Webhook::new(settings.url().clone())
+        .context(ErrorKind::Webhook)?
+        .with_events(settings.events().to_vec());
+    if let Some(secret) = settings.secret() {
+        webhook = webhook.with_secret(secret.as_bytes().to_vec());
+    }
+    if let Some(path) = settings.trust_bundle() {
+        webhook = webhook.with_trust_bundle(fs::read(path).context(ErrorKind::Webhook)?);
+    }
+    if let Some(max_attempts) = settings.max_attempts() {
+        webhook = webhook.with_max_attempts(max_attempts);
+    }
+    Ok(webhook)
+}
+
+/// Looks up when the Edge CA expires: the device CA certificate when
+/// certificates are configured, which is read again every time so that a
+/// rotation is noticed, or else the quick start root.
+pub fn edge_ca_expiry<C>(certificates: Option<&Certificates>, crypto: C) -> ExpiryCheck
+where
+    C: 'static + GetTrustBundle + Send,
+{
+    match certificates {
+        Some(certificates) => {
+            let path = certificates.device_ca_cert().to_path_buf();
+            Box::new(move || -> Result<DateTime<Utc>, Error> {
+                Ok(certificate_expiry(&fs::read(&path)?)?)
+            })
+        }
+        None => Box::new(move || -> Result<DateTime<Utc>, Error> {
+            Ok(crypto.get_trust_bundle()?.get_valid_to()?)
+        }),
+    }
+}
+
+/// Checks when the Edge CA expires once a day, and notifies `notifier` when
+/// it expires within `warning`, until `cancellation` fires.
+pub fn watch_edge_ca_expiry(
+    expiry: ExpiryCheck,
+    warning: Duration,
+    notifier: Notifier,
+    cancellation: CancellationToken,
+) -> impl Future<Item = (), Error = ()> + Send {
+    let interval = Duration::from_secs(EDGE_CA_EXPIRY_CHECK_SECS);
+    let checks = Interval::new(Instant::now(), interval)
+        .map_err(|err| warn!("Could not schedule the Edge CA expiry check: {}", err))
+        .for_each(move |_| {
+            let notification =
+                expiry().map(|expires_at| expiry_notification(expires_at, Utc::now(), warning));
+            match notification {
+                Ok(Some(notification)) => {
+                    warn!("The Edge CA {}", notification.details().unwrap_or_default());
+                    notifier.notify(&notification);
+                }
+                Ok(None) => (),
+                Err(err) => {
+                    warn!("Could not check when the Edge CA expires.");
+                    log_failure(Level::Warn, &err);
+                }
+            }
+            Ok(())
+        });
+
+    checks.select(cancellation.cancelled()).then(|_| Ok(()))
+}
+
+fn expiry_notification(
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    warning: Duration,
+) -> Option<Notification> {
+    let warning = ChronoDuration::from_std(warning).unwrap_or_else(|_| ChronoDuration::max_value());
+    if expires_at - now > warning {
+        return None;
+    }
+
+    let details = if expires_at > now {
+        format!("expires at {}", expires_at.to_rfc3339())
+    } else {
+        format!("expired at {}", expires_at.to_rfc3339())
+    };
+    Some(
+        Notification::new(NotificationEvent::CertificateExpiring, EDGE_CA_SUBJECT, now)
+            .with_details(details),
+    )
+}
+
+/// The notification of a device that DPS provisioned with another identity
+/// or hub than before.
+pub fn provisioning_changed(device_id: &str, hub_name: &str) -> Notification {
+    Notification::new(
+        NotificationEvent::ProvisioningChanged,
+        device_id,
+        Utc::now(),
+    ).with_details(format!("provisioned to {}", hub_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json;
+
+    use super::*;
+
+    #[test]
+    fn edge_ca_is_reported_within_the_warning() {
+        let now = Utc.ymd(2018, 10, 15).and_hms(12, 0, 0);
+        let warning = Duration::from_secs(30 * 24 * 3600);
+
+        assert!(expiry_notification(now + ChronoDuration::days(31), now, warning).is_none());
+
+        let notification =
+            expiry_notification(now + ChronoDuration::days(29), now, warning).unwrap();
+        assert_eq!(NotificationEvent::CertificateExpiring, notification.event());
+        assert_eq!(EDGE_CA_SUBJECT, notification.subject());
+        assert_eq!(
+            Some("expires at 2018-11-13T12:00:00+00:00"),
+            notification.details()
+        );
+
+        let notification =
+            expiry_notification(now - ChronoDuration::days(1), now, warning).unwrap();
+        assert_eq!(
+            Some("expired at 2018-10-14T12:00:00+00:00"),
+            notification.details()
+        );
+    }
+
+    #[test]
+    fn only_https_and_unix_webhooks_are_accepted() {
+        let settings: WebhookSettings =
+            serde_json::from_str(r#"{"url": "http://alerts.local/iotedge"}"#).unwrap();
+        assert_eq!(ErrorKind::Webhook, *webhook(&settings).unwrap_err().kind());
+
+        let settings: WebhookSettings = serde_json::from_str(
+            r#"{"url": "https://alerts.local/iotedge", "trust_bundle": "/nonexistent/ca.pem"}"#,
+        ).unwrap();
+        assert_eq!(ErrorKind::Webhook, *webhook(&settings).unwrap_err().kind());
+    }
+}
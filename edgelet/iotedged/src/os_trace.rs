@@ -0,0 +1,94 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Lifecycle events of the daemon for the tracing facility of the OS.
+//!
+//! The events go to the ETW provider of the daemon on Windows and to the
+//! ftrace trace marker on Linux, and requests to the APIs are traced by the
+//! logging service of `edgelet-http`. Events are only emitted by a daemon
+//! built with the `os-tracing` feature, and then only when the `os_tracing`
+//! setting turns them on.
+
+use std::fmt::Display;
+
+#[cfg(feature = "os-tracing")]
+use edgelet_core::trace;
+#[cfg(all(feature = "os-tracing", unix))]
+use edgelet_utils::log_failure;
+#[cfg(all(feature = "os-tracing", unix))]
+use log::Level;
+
+#[cfg(all(feature = "os-tracing", windows))]
+use win_logger::{EtwProvider, GUID};
+
+/// The ETW provider of the daemon, which is enabled in trace sessions as
+/// `{7e1d4a36-5b1c-4b5e-9d4a-0b2f6c1e8a53}`.
+#[cfg(all(feature = "os-tracing", windows))]
+const ETW_PROVIDER_GUID: GUID = GUID {
+    Data1: 0x7e1d_4a36,
+    Data2: 0x5b1c,
+    Data3: 0x4b5e,
+    Data4: [0x9d, 0x4a, 0x0b, 0x2f, 0x6c, 0x1e, 0x8a, 0x53],
+};
+
+#[cfg(all(feature = "os-tracing", windows))]
+struct EtwSink(EtwProvider);
+
+#[cfg(all(feature = "os-tracing", windows))]
+impl trace::TraceSink for EtwSink {
+    fn enabled(&self) -> bool {
+        self.0.enabled()
+    }
+
+    fn write(&self, event: &str) {
+        self.0.write(event);
+    }
+}
+
+/// Starts emitting events if `enabled`. Failing to open the tracing facility
+/// isn't a reason not to start, so it is only warned about.
+#[cfg(all(feature = "os-tracing", unix))]
+pub fn init(enabled: bool) {
+    if enabled {
+        match trace::TraceMarker::open() {
+            Ok(marker) => {
+                info!("Emitting events to the ftrace trace marker.");
+                trace::set_sink(marker);
+            }
+            Err(err) => {
+                warn!("Could not open the ftrace trace marker. Events are not emitted:");
+                log_failure(Level::Warn, &err);
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "os-tracing", windows))]
+pub fn init(enabled: bool) {
+    if enabled {
+        match EtwProvider::new(ETW_PROVIDER_GUID) {
+            Ok(provider) => {
+                info!("Emitting events to the ETW provider of the daemon.");
+                trace::set_sink(EtwSink(provider));
+            }
+            Err(err) => warn!(
+                "Could not register the ETW provider. Events are not emitted: {}",
+                err
+            ),
+        }
+    }
+}
+
+#[cfg(not(feature = "os-tracing"))]
+pub fn init(enabled: bool) {
+    if enabled {
+        warn!("OS tracing is turned on, but the daemon was built without OS tracing.");
+    }
+}
+
+#[cfg(feature = "os-tracing")]
+pub fn event(name: &str, fields: &[(&str, &Display)]) {
+    trace::event(name, fields);
+}
+
+#[cfg(not(feature = "os-tracing"))]
+pub fn event(_name: &str, _fields: &[(&str, &Display)]) {}
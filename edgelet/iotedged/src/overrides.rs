@@ -0,0 +1,338 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::cmp;
+use std::time::Instant;
+
+use failure::{Fail, ResultExt};
+use futures::future::{self, Either};
+use futures::sync::oneshot::Receiver;
+use futures::{Future, Stream};
+use log::LevelFilter;
+use serde_json::{self, Value};
+use tokio::timer::Interval;
+
+use edgelet_http::client::{ClientImpl, TokenSource};
+use edgelet_http::error::Error as HttpError;
+use iothubservice::DeviceClient;
+
+use error::{Error, ErrorKind};
+use logging;
+use settings::{LogLevel, TwinOverrides};
+use workload::WorkloadData;
+
+/// This is the section of the device twin's desired properties, and of its
+/// reported properties, that belongs to the daemon.
+const OVERRIDES_SECTION: &str = "iotedged";
+
+/// The settings the device twin can override. The values from the config
+/// file apply to anything the twin leaves out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Overridable {
+    log_level: LevelFilter,
+    id_cert_max_duration: i64,
+    server_cert_max_duration: i64,
+}
+
+impl Overridable {
+    pub fn new(
+        log_level: LevelFilter,
+        id_cert_max_duration: i64,
+        server_cert_max_duration: i64,
+    ) -> Self {
+        Overridable {
+            log_level,
+            id_cert_max_duration,
+            server_cert_max_duration,
+        }
+    }
+}
+
+/// The desired section, as set by the operator.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct Overrides {
+    log_level: Option<LogLevel>,
+    id_cert_max_duration_secs: Option<i64>,
+    server_cert_max_duration_secs: Option<i64>,
+}
+
+impl Overrides {
+    /// Reads the daemon's section out of the desired properties. A missing
+    /// section overrides nothing.
+    fn from_desired(desired: &Value) -> Result<Self, Error> {
+        let overrides: Overrides = match desired.get(OVERRIDES_SECTION) {
+            None | Some(Value::Null) => Overrides::default(),
+            Some(section) => {
+                serde_json::from_value(section.clone()).context(ErrorKind::TwinOverrides)?
+            }
+        };
+
+        let durations = [
+            overrides.id_cert_max_duration_secs,
+            overrides.server_cert_max_duration_secs,
+        ];
+        if durations.iter().any(|d| d.map_or(false, |d| d <= 0)) {
+            Err(Error::from(ErrorKind::TwinOverrides))
+        } else {
+            Ok(overrides)
+        }
+    }
+
+    /// Certificate lifetimes can only be shortened. Anything longer than the
+    /// local maximum is capped, so that a compromised hub can't make the
+    /// daemon issue long lived certificates.
+    fn apply(&self, local: &Overridable) -> Overridable {
+        Overridable {
+            log_level: self
+                .log_level
+                .map_or(local.log_level, LogLevel::level_filter),
+            id_cert_max_duration: self
+                .id_cert_max_duration_secs
+                .map_or(local.id_cert_max_duration, |d| {
+                    cmp::min(d, local.id_cert_max_duration)
+                }),
+            server_cert_max_duration: self
+                .server_cert_max_duration_secs
+                .map_or(local.server_cert_max_duration, |d| {
+                    cmp::min(d, local.server_cert_max_duration)
+                }),
+        }
+    }
+}
+
+/// What is reported back in the daemon's section of the reported properties.
+/// `error` is reported as null once the desired section is valid again, which
+/// clears an earlier error from the twin.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Reported {
+    desired_version: Option<i64>,
+    log_level: String,
+    id_cert_max_duration_secs: i64,
+    server_cert_max_duration_secs: i64,
+    error: Option<String>,
+}
+
+impl Reported {
+    fn new(version: Option<i64>, applied: &Overridable, error: Option<String>) -> Self {
+        Reported {
+            desired_version: version,
+            log_level: applied.log_level.to_string().to_lowercase(),
+            id_cert_max_duration_secs: applied.id_cert_max_duration,
+            server_cert_max_duration_secs: applied.server_cert_max_duration,
+            error,
+        }
+    }
+
+    fn into_section(self) -> Result<Value, Error> {
+        let mut reported = serde_json::Map::new();
+        reported.insert(
+            OVERRIDES_SECTION.to_string(),
+            serde_json::to_value(self).context(ErrorKind::Json)?,
+        );
+        Ok(Value::Object(reported))
+    }
+}
+
+// Works out the settings to use for a version of the desired properties. An
+// invalid section leaves the settings applied before it in place.
+fn resolve(desired: &Value, local: &Overridable, current: &Overridable) -> (Overridable, Reported) {
+    let version = desired.get("$version").and_then(Value::as_i64);
+    match Overrides::from_desired(desired) {
+        Ok(overrides) => {
+            let applied = overrides.apply(local);
+            (applied, Reported::new(version, &applied, None))
+        }
+        Err(err) => {
+            let error = err
+                .cause()
+                .map_or_else(|| err.to_string(), |cause| format!("{}: {}", err, cause));
+            (*current, Reported::new(version, current, Some(error)))
+        }
+    }
+}
+
+/// Applies the settings overrides in the device twin, checking for changes
+/// every poll interval until `shutdown` fires. The values in effect are echoed
+/// in the twin's reported properties.
+///
+/// Failing to reach the hub only delays the next check, since the daemon runs
+/// fine on its local settings.
+pub fn start_twin_overrides<C, T>(
+    overrides: Option<&TwinOverrides>,
+    client: DeviceClient<C, T>,
+    workload: WorkloadData,
+    local: Overridable,
+    shutdown: Receiver<()>,
+) -> impl Future<Item = (), Error = Error>
+where
+    C: 'static + ClientImpl,
+    T: 'static + TokenSource + Clone,
+    T::Error: Into<HttpError>,
+{
+    let poll_interval = match overrides {
+        Some(overrides) if overrides.enabled() => overrides.poll_interval(),
+        _ => return Either::B(future::ok(())),
+    };
+
+    info!(
+        "Checking the device twin for settings overrides every {} seconds...",
+        poll_interval.as_secs()
+    );
+    let poll = Interval::new(Instant::now(), poll_interval)
+        .map_err(|err| Error::from(err.context(ErrorKind::Io)))
+        .fold((None, local), move |(version, current), _| {
+            let client = client.clone();
+            let workload = workload.clone();
+            client
+                .get_twin()
+                .map_err(Error::from)
+                .and_then(move |twin| {
+                    let (applied, reported) =
+                        resolve(twin.properties().desired(), &local, &current);
+                    let new_version = reported.desired_version;
+                    // nothing changed since the last check
+                    if new_version.is_some() && new_version == version {
+                        return Either::B(future::ok((version, current)));
+                    }
+
+                    apply(&workload, &applied, &reported);
+                    let report = future::result(reported.into_section())
+                        .and_then(move |section| {
+                            client
+                                .update_reported_properties(section)
+                                .map_err(Error::from)
+                        }).then(move |result| {
+                            if let Err(err) = result {
+                                warn!("Could not report the settings overrides: {}", err);
+                            }
+                            Ok((new_version, applied))
+                        });
+                    Either::A(report)
+                }).or_else(move |err| {
+                    warn!(
+                        "Could not check the device twin for settings overrides: {}",
+                        err
+                    );
+                    Ok::<_, Error>((version, current))
+                })
+        }).map(|_| ());
+
+    // Swallow any errors from the shutdown signal
+    let shutdown = shutdown.then(|_| Ok(()));
+
+    Either::A(shutdown.select(poll).then(|result| match result {
+        Ok(((), _)) => Ok(()),
+        Err((e, _)) => Err(e),
+    }))
+}
+
+fn apply(workload: &WorkloadData, applied: &Overridable, reported: &Reported) {
+    if let Some(ref error) = reported.error {
+        warn!(
+            "Ignoring invalid settings overrides in the device twin: {}",
+            error
+        );
+        return;
+    }
+
+    info!(
+        "Applying the settings overrides from version {} of the device twin",
+        reported
+            .desired_version
+            .map_or_else(|| "?".to_string(), |v| v.to_string())
+    );
+    logging::set_max_level(applied.log_level);
+    workload.set_cert_max_durations(
+        applied.id_cert_max_duration,
+        applied.server_cert_max_duration,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local() -> Overridable {
+        Overridable::new(LevelFilter::Info, 7200, 7_776_000)
+    }
+
+    fn desired(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn missing_section_keeps_local_settings() {
+        let (applied, reported) = resolve(
+            &desired(r#"{"$version": 3, "other": {"a": 1}}"#),
+            &local(),
+            &local(),
+        );
+        assert_eq!(local(), applied);
+        assert_eq!(Some(3), reported.desired_version);
+        assert_eq!("info", reported.log_level);
+        assert_eq!(None, reported.error);
+    }
+
+    #[test]
+    fn section_overrides_settings() {
+        let (applied, reported) = resolve(
+            &desired(
+                r#"{
+                    "$version": 4,
+                    "iotedged": {
+                        "logLevel": "warn",
+                        "idCertMaxDurationSecs": 3600
+                    }
+                }"#,
+            ),
+            &local(),
+            &local(),
+        );
+        assert_eq!(
+            Overridable::new(LevelFilter::Warn, 3600, 7_776_000),
+            applied
+        );
+        assert_eq!("warn", reported.log_level);
+        assert_eq!(3600, reported.id_cert_max_duration_secs);
+        assert_eq!(7_776_000, reported.server_cert_max_duration_secs);
+    }
+
+    #[test]
+    fn cert_durations_cannot_exceed_local_settings() {
+        let (applied, _) = resolve(
+            &desired(r#"{"iotedged": {"serverCertMaxDurationSecs": 99999999}}"#),
+            &local(),
+            &local(),
+        );
+        assert_eq!(7_776_000, applied.server_cert_max_duration);
+    }
+
+    #[test]
+    fn invalid_section_keeps_current_settings() {
+        let current = Overridable::new(LevelFilter::Error, 60, 60);
+        for json in &[
+            r#"{"$version": 5, "iotedged": {"logLevel": "trace"}}"#,
+            r#"{"$version": 5, "iotedged": {"idCertMaxDurationSecs": 0}}"#,
+            r#"{"$version": 5, "iotedged": {"homedir": "/tmp"}}"#,
+            r#"{"$version": 5, "iotedged": "warn"}"#,
+        ] {
+            let (applied, reported) = resolve(&desired(json), &local(), &current);
+            assert_eq!(current, applied);
+            assert_eq!(Some(5), reported.desired_version);
+            assert_eq!("error", reported.log_level);
+            assert!(reported.error.is_some());
+        }
+    }
+
+    #[test]
+    fn reported_section_clears_earlier_errors() {
+        let reported = Reported::new(Some(2), &local(), None)
+            .into_section()
+            .unwrap();
+        let section = &reported[OVERRIDES_SECTION];
+        assert_eq!(2, section["desiredVersion"]);
+        assert_eq!(7200, section["idCertMaxDurationSecs"]);
+        assert!(section.get("error").unwrap().is_null());
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use edgelet_core::crypto::{Certificate, GetTrustBundle};
+use edgelet_core::CaRotation;
+use edgelet_utils::log_failure;
+use failure::{err_msg, Fail, ResultExt};
+use log::Level;
+use openssl::ssl::{HandshakeError, Ssl, SslContextBuilder, SslMethod, SslVerifyMode};
+use openssl::x509::{X509VerifyResult, X509};
+
+use error::{Error, ErrorKind};
+
+/// This is the port the parent gateway of a nested device serves IoT Hub,
+/// DPS and its registry proxy on.
+pub const PARENT_PORT: u16 = 443;
+
+/// This is how long the parent gateway is given to accept the connection its
+/// certificate is checked on.
+const PARENT_TIMEOUT_SECS: u64 = 10;
+
+/// Checks that the parent gateway `parent_hostname` presents a certificate
+/// issued under the trust bundle, and returns the trust bundle for the
+/// connections to the parent to trust.
+///
+/// The root of the parent has to be in the trusted CA certificates of this
+/// device for it to be nested under the parent. A parent that can't be
+/// reached isn't checked, since it may come up later, and connections to it
+/// are verified against the trust bundle all the same.
+pub fn check_parent<C>(
+    parent_hostname: &str,
+    crypto: &C,
+    rotation: &CaRotation,
+) -> Result<String, Error>
+where
+    C: GetTrustBundle,
+{
+    let current = crypto.get_trust_bundle()?.pem()?;
+    let current = String::from_utf8_lossy(current.as_ref());
+    let trust_bundle = rotation.trust_bundle(&current)?;
+
+    match verify_parent(parent_hostname, PARENT_PORT, &trust_bundle) {
+        Ok(()) => info!(
+            "The parent gateway {} presents a certificate issued under the trust bundle.",
+            parent_hostname
+        ),
+        Err(ref err) if *err.kind() != ErrorKind::ParentNotTrusted => {
+            warn!(
+                "Could not check the certificate of the parent gateway {}.",
+                parent_hostname
+            );
+            log_failure(Level::Warn, err);
+        }
+        Err(err) => return Err(err),
+    }
+    Ok(trust_bundle)
+}
+
+// Opens a TLS connection to the parent that only trusts the roots in
+// `trust_bundle`, rather than those of the host as well, and fails with
+// `ParentNotTrusted` if the certificate of the parent doesn't verify.
+fn verify_parent(host: &str, port: u16, trust_bundle: &str) -> Result<(), Error> {
+    let mut context = SslContextBuilder::new(SslMethod::tls()).context(ErrorKind::Parent)?;
+    for root in X509::stack_from_pem(trust_bundle.as_bytes()).context(ErrorKind::Parent)? {
+        context
+            .cert_store_mut()
+            .add_cert(root)
+            .context(ErrorKind::Parent)?;
+    }
+    context.set_verify(SslVerifyMode::PEER);
+    let context = context.build();
+
+    let mut ssl = Ssl::new(&context).context(ErrorKind::Parent)?;
+    ssl.set_hostname(host).context(ErrorKind::Parent)?;
+    ssl.param_mut().set_host(host).context(ErrorKind::Parent)?;
+
+    let stream = connect(host, port).context(ErrorKind::Parent)?;
+    match ssl.connect(stream) {
+        Ok(_) => Ok(()),
+        Err(HandshakeError::Failure(stream)) => {
+            let result = stream.ssl().verify_result();
+            if result == X509VerifyResult::OK {
+                Err(Error::from(stream.into_error().context(ErrorKind::Parent)))
+            } else {
+                Err(Error::from(
+                    err_msg(result.error_string()).context(ErrorKind::ParentNotTrusted),
+                ))
+            }
+        }
+        Err(HandshakeError::SetupFailure(err)) => Err(Error::from(err.context(ErrorKind::Parent))),
+        Err(HandshakeError::WouldBlock(_)) => Err(Error::from(ErrorKind::Parent)),
+    }
+}
+
+fn connect(host: &str, port: u16) -> io::Result<TcpStream> {
+    let timeout = Duration::from_secs(PARENT_TIMEOUT_SECS);
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "the host has no address");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::ssl::SslAcceptor;
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use openssl::x509::{X509Builder, X509NameBuilder, X509Ref};
+
+    use super::*;
+
+    fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    // Issues a certificate for `common_name` under `issuer`, or a self-signed
+    // CA certificate without one.
+    fn certificate(
+        common_name: &str,
+        key: &PKey<Private>,
+        issuer: Option<(&X509Ref, &PKey<Private>)>,
+    ) -> X509 {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+            .unwrap();
+        let name = name.build();
+
+        let mut cert = X509Builder::new().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_pubkey(key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        match issuer {
+            Some((issuer, issuer_key)) => {
+                cert.set_issuer_name(issuer.subject_name()).unwrap();
+                let san = SubjectAlternativeName::new()
+                    .dns(common_name)
+                    .build(&cert.x509v3_context(Some(issuer), None))
+                    .unwrap();
+                cert.append_extension(san).unwrap();
+                cert.sign(issuer_key, MessageDigest::sha256()).unwrap();
+            }
+            None => {
+                cert.set_issuer_name(&name).unwrap();
+                let ca = BasicConstraints::new().critical().ca().build().unwrap();
+                cert.append_extension(ca).unwrap();
+                cert.sign(key, MessageDigest::sha256()).unwrap();
+            }
+        }
+        cert.build()
+    }
+
+    // Serves TLS with a certificate issued under `root` to one client.
+    fn parent(root: &X509, root_key: &PKey<Private>) -> u16 {
+        let key = key();
+        let cert = certificate("localhost", &key, Some((root, root_key)));
+        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            if let Ok((stream, _)) = listener.accept() {
+                let _ = acceptor.accept(stream);
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn parents_must_present_a_certificate_issued_under_the_trust_bundle() {
+        let root_key = key();
+        let root = certificate("parent root", &root_key, None);
+        let other_key = key();
+        let other = certificate("other root", &other_key, None);
+        let trust_bundle = String::from_utf8(root.to_pem().unwrap()).unwrap();
+
+        let port = parent(&root, &root_key);
+        verify_parent("localhost", port, &trust_bundle).unwrap();
+
+        let port = parent(&other, &other_key);
+        let err = verify_parent("localhost", port, &trust_bundle).unwrap_err();
+        assert_eq!(ErrorKind::ParentNotTrusted, *err.kind());
+    }
+}
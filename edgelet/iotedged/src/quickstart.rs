@@ -0,0 +1,230 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Expiry of the CA the daemon generates when no certificates are configured.
+//!
+//! In quick start mode the HSM generates a root CA and a device CA under it
+//! for the daemon, which only last as long as the quick start CA lifetime in
+//! the settings. Once less than a tenth of that is left, the root is removed
+//! on the next start, so that the HSM generates both again, and the workload
+//! CA and the edge runtime modules, which hold certificates issued under the
+//! old root, are refreshed along with them. A daemon that runs into that
+//! window shuts down with `ErrorKind::QuickstartCaExpiring`, so that it is
+//! restarted by the service manager and renews the CA then.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use edgelet_core::crypto::{Certificate, CreateCertificate, GetTrustBundle};
+use edgelet_core::{CancellationToken, ModuleRuntime};
+use edgelet_hsm::Crypto;
+use futures::Future;
+use serde_json;
+use tokio;
+use tokio::timer::Delay;
+
+use error::Error;
+use settings::Certificates;
+use {destroy_workload_ca, prepare_workload_ca, remove_edge_runtime_modules};
+
+/// This is the name of the file in the cache directory that records when the
+/// quick start CA expires, which `iotedge check` and `iotedge list` read.
+const EDGE_QUICKSTART_CA_FILENAME: &str = "quickstart_ca.json";
+
+/// This is the alias the HSM keeps the quick start root CA under. The HSM
+/// generates the root and the device CA under it again when it is initialized
+/// without it.
+const QUICKSTART_ROOT_CA_ALIAS: &str = "edge_owner_ca";
+
+/// The quick start CA is generated again once less than this part of its
+/// lifetime is left.
+const RENEWAL_WINDOW_DIVISOR: u32 = 10;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct QuickstartCaRecord {
+    expires_at: String,
+}
+
+/// Generates the quick start CA again if it expires within the renewal
+/// window, and records when it expires. The HSM only generates the CA when it
+/// is initialized, so `crypto` is taken over and dropped, and the one
+/// initialized anew is returned, along with how long is left until the CA is
+/// due to be generated again. With certificates configured there is no quick
+/// start CA, and the record is removed.
+pub fn check_quickstart_ca<M>(
+    subdir_path: &Path,
+    certificates: Option<&Certificates>,
+    lifetime: Duration,
+    crypto: Crypto,
+    runtime: &M,
+    tokio_runtime: &mut tokio::runtime::Runtime,
+) -> Result<(Crypto, Option<Duration>), Error>
+where
+    M: ModuleRuntime,
+    <M as ModuleRuntime>::Error: Into<Error>,
+    <M as ModuleRuntime>::RemoveFuture: 'static,
+{
+    let path = subdir_path.join(EDGE_QUICKSTART_CA_FILENAME);
+    if certificates.is_some() {
+        remove_record(&path)?;
+        return Ok((crypto, None));
+    }
+
+    let window = renewal_window(lifetime);
+    let (expires_at, remaining) = expiry(&crypto)?;
+    let (crypto, expires_at, remaining) = if remaining <= window {
+        warn!(
+            "The quick start CA expires at {}. Generating it again, along with the \
+             certificates issued under it...",
+            expires_at
+        );
+        crypto.destroy_certificate(QUICKSTART_ROOT_CA_ALIAS.to_string())?;
+        drop(crypto);
+        let crypto = Crypto::new()?;
+        destroy_workload_ca(&crypto)?;
+        prepare_workload_ca(&crypto)?;
+        remove_edge_runtime_modules(runtime, tokio_runtime)?;
+        let (expires_at, remaining) = expiry(&crypto)?;
+        info!("Finished generating the quick start CA.");
+        (crypto, expires_at, remaining)
+    } else {
+        (crypto, expires_at, remaining)
+    };
+
+    warn!(
+        "The device uses quick start certificates, which are not meant for production. \
+         The quick start CA expires at {} and is generated again before then, after \
+         which downstream devices have to be given the new root.",
+        expires_at
+    );
+    write_record(&path, &expires_at)?;
+    Ok((crypto, remaining.checked_sub(window)))
+}
+
+/// Shuts the daemon down through `cancellation` once the quick start CA is
+/// due to be generated again, setting `expiring` so that it exits with an
+/// error and is restarted. Stops waiting if the daemon shuts down first.
+pub fn watch_quickstart_ca(
+    until_renewal: Duration,
+    cancellation: CancellationToken,
+    expiring: Arc<AtomicBool>,
+) -> impl Future<Item = (), Error = ()> + Send {
+    let shutdown = cancellation.clone();
+    let renewal = Delay::new(Instant::now() + until_renewal).then(move |_| {
+        warn!("The quick start CA is about to expire. Shutting down to generate it again...");
+        expiring.store(true, Ordering::SeqCst);
+        shutdown.cancel();
+        Ok::<_, ()>(())
+    });
+
+    renewal.select(cancellation.cancelled()).then(|_| Ok(()))
+}
+
+fn renewal_window(lifetime: Duration) -> Duration {
+    lifetime / RENEWAL_WINDOW_DIVISOR
+}
+
+// The root is the only certificate in the trust bundle in quick start mode.
+#[cfg_attr(feature = "cargo-clippy", allow(cast_sign_loss))]
+fn expiry<C: GetTrustBundle>(crypto: &C) -> Result<(String, Duration), Error> {
+    let valid_to = crypto.get_trust_bundle()?.get_valid_to()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0);
+    let expires = valid_to.timestamp().max(0) as u64;
+    Ok((
+        valid_to.to_rfc3339(),
+        Duration::from_secs(expires.saturating_sub(now)),
+    ))
+}
+
+fn write_record(path: &Path, expires_at: &str) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let record = QuickstartCaRecord {
+        expires_at: expires_at.to_string(),
+    };
+    fs::write(path, serde_json::to_string(&record)?)?;
+    Ok(())
+}
+
+fn remove_record(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn renewal_window_is_a_tenth_of_the_lifetime() {
+        assert_eq!(
+            Duration::from_secs(9 * 24 * 3600),
+            renewal_window(Duration::from_secs(90 * 24 * 3600))
+        );
+    }
+
+    #[test]
+    fn record_is_written_and_removed() {
+        let tmp_dir = TempDir::new("quickstart").unwrap();
+        let path = tmp_dir
+            .path()
+            .join("cache")
+            .join(EDGE_QUICKSTART_CA_FILENAME);
+
+        write_record(&path, "2019-04-01T00:00:00+00:00").unwrap();
+        let record: QuickstartCaRecord =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!("2019-04-01T00:00:00+00:00", record.expires_at);
+
+        remove_record(&path).unwrap();
+        assert!(!path.exists());
+        remove_record(&path).unwrap();
+    }
+
+    #[test]
+    fn daemon_shuts_down_when_renewal_is_due() {
+        let cancellation = CancellationToken::new();
+        let expiring = Arc::new(AtomicBool::new(false));
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(watch_quickstart_ca(
+                Duration::from_millis(10),
+                cancellation.clone(),
+                expiring.clone(),
+            )).unwrap();
+
+        assert!(expiring.load(Ordering::SeqCst));
+        assert!(cancellation.is_cancelled());
+    }
+
+    #[test]
+    fn watch_stops_on_shutdown() {
+        let cancellation = CancellationToken::new();
+        let expiring = Arc::new(AtomicBool::new(false));
+        cancellation.cancel();
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(watch_quickstart_ca(
+                Duration::from_secs(3600),
+                cancellation,
+                expiring.clone(),
+            )).unwrap();
+
+        assert!(!expiring.load(Ordering::SeqCst));
+    }
+}
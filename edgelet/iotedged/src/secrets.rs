@@ -0,0 +1,235 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Secrets in the settings.
+//!
+//! Settings that hold secrets, such as the device connection string or the
+//! password of a container registry, can refer to where the secret is kept
+//! instead of holding it in plain text, such as `file:///etc/iotedge/key` or
+//! `env://IOTEDGE_REGISTRY_PASSWORD`. The references are resolved when the
+//! settings are loaded by the resolver of their scheme. Values that aren't
+//! references are taken as they are.
+
+use std::env;
+use std::fmt;
+use std::fs;
+
+use failure::ResultExt;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use error::{Error, ErrorKind};
+
+/// A setting that holds a secret. It is left out of debug output, which the
+/// settings may be logged with, but serializes as its value, so that the
+/// hash of the settings changes with it.
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+/// Looks up the secrets that references of one scheme refer to.
+pub trait SecretResolver {
+    /// The scheme of the references, such as "file" for
+    /// `file:///etc/iotedge/key`.
+    fn scheme(&self) -> &str;
+
+    /// Looks up the secret that `path`, the part of the reference after
+    /// `<scheme>://`, refers to.
+    fn resolve(&self, path: &str) -> Result<Secret, Error>;
+}
+
+/// Resolves `file://` references to the contents of the file, without the
+/// line break at the end.
+pub struct FileResolver;
+
+impl SecretResolver for FileResolver {
+    fn scheme(&self) -> &str {
+        "file"
+    }
+
+    fn resolve(&self, path: &str) -> Result<Secret, Error> {
+        let contents = fs::read_to_string(path).context(ErrorKind::Secret)?;
+        Ok(Secret::new(
+            contents.trim_right_matches(&['\r', '\n'][..]).to_string(),
+        ))
+    }
+}
+
+/// Resolves `env://` references to the value of the environment variable
+/// of the daemon.
+pub struct EnvResolver;
+
+impl SecretResolver for EnvResolver {
+    fn scheme(&self) -> &str {
+        "env"
+    }
+
+    fn resolve(&self, path: &str) -> Result<Secret, Error> {
+        let value = env::var(path).context(ErrorKind::Secret)?;
+        Ok(Secret::new(value))
+    }
+}
+
+/// The resolvers the references in the settings are resolved with. There
+/// are resolvers for `file://` and `env://` references to begin with, and
+/// others, such as for a key vault or secrets sealed by the TPM, can be
+/// added with `with_resolver`.
+pub struct SecretResolvers {
+    resolvers: Vec<Box<SecretResolver>>,
+}
+
+impl SecretResolvers {
+    pub fn new() -> Self {
+        SecretResolvers {
+            resolvers: vec![Box::new(FileResolver), Box::new(EnvResolver)],
+        }
+    }
+
+    /// Resolves the references of the scheme of `resolver` with it, instead
+    /// of a resolver of the same scheme added before.
+    pub fn with_resolver<R: 'static + SecretResolver>(mut self, resolver: R) -> Self {
+        self.resolvers
+            .retain(|resolved| resolved.scheme() != resolver.scheme());
+        self.resolvers.push(Box::new(resolver));
+        self
+    }
+
+    /// The secret `value` refers to, or `value` itself if it isn't a
+    /// reference. References of a scheme there is no resolver for fail, so
+    /// that they aren't taken for the secret.
+    pub fn resolve(&self, value: &Secret) -> Result<Secret, Error> {
+        let (scheme, path) = match reference(value.expose()) {
+            Some(reference) => reference,
+            None => return Ok(value.clone()),
+        };
+        match self
+            .resolvers
+            .iter()
+            .find(|resolver| resolver.scheme() == scheme)
+        {
+            Some(resolver) => resolver.resolve(path),
+            None => Err(Error::from(
+                format_err!("There is no secret provider for {}:// references", scheme)
+                    .context(ErrorKind::Secret),
+            )),
+        }
+    }
+}
+
+impl Default for SecretResolvers {
+    fn default() -> Self {
+        SecretResolvers::new()
+    }
+}
+
+// Splits a reference into its scheme and path. Connection strings and
+// passwords have no lowercase scheme followed by "://" at their start.
+fn reference(value: &str) -> Option<(&str, &str)> {
+    let index = value.find("://")?;
+    let scheme = &value[..index];
+    if !scheme.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        Some((scheme, &value[index + 3..]))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    struct VaultResolver;
+
+    impl SecretResolver for VaultResolver {
+        fn scheme(&self) -> &str {
+            "keyvault"
+        }
+
+        fn resolve(&self, path: &str) -> Result<Secret, Error> {
+            Ok(Secret::new(format!("secret of {}", path)))
+        }
+    }
+
+    fn secret(value: &str) -> Secret {
+        Secret::new(value.to_string())
+    }
+
+    #[test]
+    fn references_are_resolved_by_scheme() {
+        let tmp_dir = TempDir::new("secrets").unwrap();
+        let path = tmp_dir.path().join("key");
+        fs::write(&path, "c2VjcmV0\n").unwrap();
+        env::set_var("IOTEDGED_TEST_SECRET", "password");
+        let resolvers = SecretResolvers::new().with_resolver(VaultResolver);
+
+        let file = format!("file://{}", path.display());
+        assert_eq!(
+            secret("c2VjcmV0"),
+            resolvers.resolve(&secret(&file)).unwrap()
+        );
+        assert_eq!(
+            secret("password"),
+            resolvers
+                .resolve(&secret("env://IOTEDGED_TEST_SECRET"))
+                .unwrap()
+        );
+        assert_eq!(
+            secret("secret of edge/registry"),
+            resolvers
+                .resolve(&secret("keyvault://edge/registry"))
+                .unwrap()
+        );
+
+        let plain = "HostName=hub.azure-devices.net;DeviceId=d1;SharedAccessKey=a2V5";
+        assert_eq!(secret(plain), resolvers.resolve(&secret(plain)).unwrap());
+    }
+
+    #[test]
+    fn references_without_a_resolver_fail() {
+        let resolvers = SecretResolvers::new();
+        assert!(resolvers.resolve(&secret("tpm://registry")).is_err());
+        assert!(resolvers
+            .resolve(&secret("env://IOTEDGED_TEST_MISSING_SECRET"))
+            .is_err());
+    }
+
+    #[test]
+    fn secrets_are_redacted_from_debug_output() {
+        let debug = format!("{:?}", secret("SharedAccessKey=a2V5"));
+        assert!(!debug.contains("a2V5"));
+        assert_eq!("\"a2V5\"", serde_json::to_string(&secret("a2V5")).unwrap());
+    }
+}
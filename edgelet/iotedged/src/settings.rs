@@ -2,12 +2,14 @@
 
 use std::fs::{File as FsFile, OpenOptions};
 use std::io::Read;
+use std::net::{Ipv4Addr, SocketAddr};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use base64;
 use config::{Config, Environment, File, FileFormat};
 use edgelet_utils::log_failure;
-use log::Level;
+use log::{Level, LevelFilter};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json;
@@ -15,8 +17,16 @@ use sha2::{Digest, Sha256};
 use url::Url;
 use url_serde;
 
-use edgelet_core::ModuleSpec;
+use edgelet_core::{
+    CertPolicy, CertificatePolicy, Deprecation, EnvVar, KeyType, ModuleSpec, NotificationEvent,
+    ScheduleSpec, ServiceSpec, DEFAULT_HISTORY_SIZE, DEFAULT_IDEMPOTENCY_WINDOW_SECS,
+    DEFAULT_ROTATION_OVERLAP_HOURS,
+};
+use edgelet_docker::{DockerConfig, Isolation};
+use edgelet_http::TlsVersion;
 use error::Error;
+use migration;
+use secrets::{Secret, SecretResolvers};
 
 /// This is the name of the network created by the iotedged
 const DEFAULT_NETWORKID: &str = "azure-iot-edge";
@@ -24,6 +34,35 @@ const DEFAULT_NETWORKID: &str = "azure-iot-edge";
 /// This is the default connection string
 pub const DEFAULT_CONNECTION_STRING: &str = "<ADD DEVICE CONNECTION STRING HERE>";
 
+/// This is how long a module that sends heartbeats may go without one
+/// before it is restarted, unless the config file says otherwise.
+const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 60;
+
+/// These bound the queue of messages the daemon sends to IoT Hub, unless the
+/// config file says otherwise.
+const DEFAULT_TELEMETRY_MAX_MESSAGES: usize = 1000;
+const DEFAULT_TELEMETRY_RETENTION_HOURS: u64 = 7 * 24;
+const DEFAULT_TELEMETRY_BATCH_SIZE: usize = 10;
+
+/// This is how often the device twin is checked for settings overrides,
+/// unless the config file says otherwise.
+const DEFAULT_TWIN_POLL_INTERVAL_SECS: u64 = 300;
+
+/// This is how long the CA the daemon generates in quick start mode is valid
+/// for, unless the config file says otherwise.
+const DEFAULT_QUICKSTART_CA_LIFETIME_DAYS: u64 = 90;
+
+/// These are the port a passive device listens for the heartbeat of the
+/// primary on, how often the primary sends it and how long the passive
+/// device goes without it before failing over, unless the config file says
+/// otherwise.
+const DEFAULT_STANDBY_PORT: u16 = 15580;
+const DEFAULT_STANDBY_HEARTBEAT_INTERVAL_SECS: u64 = 5;
+const DEFAULT_STANDBY_FAILOVER_TIMEOUT_SECS: u64 = 30;
+
+/// A passive device doesn't fail over before it missed this many heartbeats.
+const MIN_MISSED_STANDBY_HEARTBEATS: u32 = 3;
+
 #[cfg(unix)]
 static DEFAULTS: &str = include_str!("config/unix/default.yaml");
 
@@ -33,12 +72,12 @@ static DEFAULTS: &str = include_str!("config/windows/default.yaml");
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub struct Manual {
-    device_connection_string: String,
+    device_connection_string: Secret,
 }
 
 impl Manual {
     pub fn device_connection_string(&self) -> &str {
-        &self.device_connection_string
+        self.device_connection_string.expose()
     }
 }
 
@@ -49,6 +88,8 @@ pub struct Dps {
     global_endpoint: Url,
     scope_id: String,
     registration_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payload_file: Option<PathBuf>,
 }
 
 impl Dps {
@@ -63,6 +104,12 @@ impl Dps {
     pub fn registration_id(&self) -> &str {
         &self.registration_id
     }
+
+    /// A JSON file whose contents are sent with the registration, for a
+    /// custom allocation policy to act on.
+    pub fn payload_file(&self) -> Option<&Path> {
+        self.payload_file.as_ref().map(AsRef::as_ref)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -97,6 +144,12 @@ pub struct Listen {
     workload_uri: Url,
     #[serde(with = "url_serde")]
     management_uri: Url,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pipe_access: Option<PipeAccess>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    socket_permissions: Option<SocketPermissions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workload_tls: Option<WorkloadTls>,
 }
 
 impl Listen {
@@ -107,6 +160,141 @@ impl Listen {
     pub fn management_uri(&self) -> &Url {
         &self.management_uri
     }
+
+    pub fn pipe_access(&self) -> Option<&PipeAccess> {
+        self.pipe_access.as_ref()
+    }
+
+    pub fn socket_permissions(&self) -> Option<&SocketPermissions> {
+        self.socket_permissions.as_ref()
+    }
+
+    pub fn workload_tls(&self) -> Option<&WorkloadTls> {
+        self.workload_tls.as_ref()
+    }
+}
+
+/// The TLS the workload API is served with when it listens on TCP, such as
+/// for child devices in nested topologies. Clients must present a
+/// certificate issued under `client_ca`, and may only call as the modules
+/// and devices the common name of their certificate is bound to in `peers`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WorkloadTls {
+    cert: PathBuf,
+    key: PathBuf,
+    client_ca: PathBuf,
+    #[serde(default)]
+    peers: Vec<TlsPeer>,
+}
+
+impl WorkloadTls {
+    /// The PEM certificate chain the workload API presents to clients.
+    pub fn cert(&self) -> &Path {
+        &self.cert
+    }
+
+    /// The PEM private key of `cert`.
+    pub fn key(&self) -> &Path {
+        &self.key
+    }
+
+    /// The PEM roots the certificates of clients must be issued under,
+    /// usually the Edge CA.
+    pub fn client_ca(&self) -> &Path {
+        &self.client_ca
+    }
+
+    pub fn peers(&self) -> &[TlsPeer] {
+        &self.peers
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TlsPeer {
+    common_name: String,
+    modules: Vec<String>,
+}
+
+impl TlsPeer {
+    pub fn common_name(&self) -> &str {
+        &self.common_name
+    }
+
+    /// The modules and devices a client presenting a certificate with the
+    /// common name may call as.
+    pub fn modules(&self) -> &[String] {
+        &self.modules
+    }
+}
+
+/// The mode, owner and group of the Unix domain sockets the management and
+/// workload APIs listen on, which the sockets are given each time they are
+/// bound. The sockets of namespaces get the ones of the workload API.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SocketPermissions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    management: Option<Permissions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workload: Option<Permissions>,
+}
+
+impl SocketPermissions {
+    pub fn management(&self) -> Option<&Permissions> {
+        self.management.as_ref()
+    }
+
+    pub fn workload(&self) -> Option<&Permissions> {
+        self.workload.as_ref()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Permissions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+}
+
+impl Permissions {
+    /// The permission bits, given in octal such as `"0660"`, since YAML
+    /// would read a bare `0660` as a decimal number.
+    pub fn mode(&self) -> Option<&str> {
+        self.mode.as_ref().map(AsRef::as_ref)
+    }
+
+    /// The user, by name or uid.
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_ref().map(AsRef::as_ref)
+    }
+
+    /// The group, by name or gid.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// The groups or users, as SIDs or SDDL aliases, that may connect to the
+/// management and workload APIs when they listen on named pipes. LocalSystem
+/// always may.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PipeAccess {
+    #[serde(default)]
+    management: Vec<String>,
+    #[serde(default)]
+    workload: Vec<String>,
+}
+
+impl PipeAccess {
+    pub fn management(&self) -> &[String] {
+        &self.management
+    }
+
+    pub fn workload(&self) -> &[String] {
+        &self.workload
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -114,6 +302,8 @@ pub struct MobyRuntime {
     #[serde(with = "url_serde")]
     uri: Url,
     network: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    isolation: Option<Isolation>,
 }
 
 impl MobyRuntime {
@@ -128,6 +318,12 @@ impl MobyRuntime {
             &self.network
         }
     }
+
+    /// The isolation of modules that don't choose one. Only Windows hosts
+    /// support choosing.
+    pub fn isolation(&self) -> Option<Isolation> {
+        self.isolation
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -135,6 +331,8 @@ pub struct Certificates {
     device_ca_cert: PathBuf,
     device_ca_pk: PathBuf,
     trusted_ca_certs: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rotation_overlap_hours: Option<u64>,
 }
 
 impl Certificates {
@@ -149,225 +347,1530 @@ impl Certificates {
     pub fn trusted_ca_certs(&self) -> &Path {
         &self.trusted_ca_certs
     }
+
+    /// How long, after the Edge CA is rotated, the roots trusted before are
+    /// still served in the trust bundle.
+    pub fn rotation_overlap(&self) -> Duration {
+        let hours = self
+            .rotation_overlap_hours
+            .unwrap_or(DEFAULT_ROTATION_OVERLAP_HOURS);
+        Duration::from_secs(hours.saturating_mul(3600))
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Settings<T> {
-    provisioning: Provisioning,
-    agent: ModuleSpec<T>,
-    hostname: String,
-    connect: Connect,
-    listen: Listen,
-    homedir: PathBuf,
-    moby_runtime: MobyRuntime,
-    certificates: Option<Certificates>,
+/// The CA the daemon generates for itself when no certificates are
+/// configured, which is only meant for development.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct QuickstartCa {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lifetime_days: Option<u64>,
 }
 
-impl<T> Settings<T>
-where
-    T: DeserializeOwned + Serialize,
-{
-    pub fn new(filename: Option<&str>) -> Result<Self, Error> {
-        let mut config = Config::default();
-        config.merge(File::from_str(DEFAULTS, FileFormat::Yaml))?;
-        if let Some(file) = filename {
-            config.merge(File::with_name(file).required(true))?;
-        }
+impl QuickstartCa {
+    /// How long the CA is valid for, which is never less than a day.
+    pub fn lifetime(&self) -> Duration {
+        let days = self
+            .lifetime_days
+            .unwrap_or(DEFAULT_QUICKSTART_CA_LIFETIME_DAYS)
+            .max(1);
+        Duration::from_secs(days.saturating_mul(24 * 3600))
+    }
+}
 
-        config.merge(Environment::with_prefix("iotedge"))?;
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Discovery {
+    #[serde(default)]
+    enabled: bool,
+    address: Option<Ipv4Addr>,
+    #[serde(default = "default_discovery_services")]
+    services: Vec<ServiceSpec>,
+}
 
-        let settings: Self = config.try_into()?;
+impl Discovery {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
 
-        Ok(settings)
+    /// The address to advertise. When this isn't set, the address of the
+    /// interface multicast traffic is routed through is used.
+    pub fn address(&self) -> Option<Ipv4Addr> {
+        self.address
     }
 
-    pub fn provisioning(&self) -> &Provisioning {
-        &self.provisioning
+    pub fn services(&self) -> &[ServiceSpec] {
+        &self.services
     }
+}
 
-    pub fn agent(&self) -> &ModuleSpec<T> {
-        &self.agent
+/// The protocols edgeHub accepts connections from leaf devices on.
+fn default_discovery_services() -> Vec<ServiceSpec> {
+    vec![
+        ServiceSpec::new("_secure-mqtt._tcp".to_string(), 8883),
+        ServiceSpec::new("_amqps._tcp".to_string(), 5671),
+        ServiceSpec::new("_https._tcp".to_string(), 443),
+    ]
+}
+
+/// Lets the `iotedged` section of the device twin's desired properties
+/// override some of these settings while the daemon runs.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwinOverrides {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    poll_interval_secs: Option<u64>,
+}
+
+impl TwinOverrides {
+    pub fn enabled(&self) -> bool {
+        self.enabled
     }
 
-    pub fn agent_mut(&mut self) -> &mut ModuleSpec<T> {
-        &mut self.agent
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.poll_interval_secs
+                .unwrap_or(DEFAULT_TWIN_POLL_INTERVAL_SECS)
+                .max(1),
+        )
     }
+}
 
-    pub fn hostname(&self) -> &str {
-        &self.hostname
+/// Features meant for developing modules against the device, which mustn't
+/// be turned on in production.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Developer {
+    #[serde(default)]
+    image_build: bool,
+    #[serde(default)]
+    attach: bool,
+    #[serde(default)]
+    debug_endpoints: bool,
+}
+
+impl Developer {
+    /// Whether the management API builds images, so that modules can be
+    /// built and run on the device without a registry.
+    pub fn image_build(&self) -> bool {
+        self.image_build
     }
 
-    pub fn connect(&self) -> &Connect {
-        &self.connect
+    /// Whether the management API attaches to the consoles of modules, so
+    /// that they can be debugged interactively.
+    pub fn attach(&self) -> bool {
+        self.attach
     }
 
-    pub fn listen(&self) -> &Listen {
-        &self.listen
+    /// Whether the workload API serves the diagnostic endpoints under
+    /// `/debug/`, which module SDKs test their workload clients against.
+    pub fn debug_endpoints(&self) -> bool {
+        self.debug_endpoints
     }
+}
 
-    pub fn homedir(&self) -> &Path {
-        &self.homedir
+/// Which of the requests to the management and workload APIs are written to
+/// their access logs. Requests to routes no rule matches are all logged.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct AccessLog {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    management: Vec<SamplingRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    workload: Vec<SamplingRule>,
+}
+
+impl AccessLog {
+    pub fn management(&self) -> &[SamplingRule] {
+        &self.management
     }
 
-    pub fn moby_runtime(&self) -> &MobyRuntime {
-        &self.moby_runtime
+    pub fn workload(&self) -> &[SamplingRule] {
+        &self.workload
     }
+}
 
-    pub fn certificates(&self) -> Option<&Certificates> {
-        self.certificates.as_ref()
+/// Logs a percentage of the requests to the routes matching a regular
+/// expression. Every request is logged unless a percentage says otherwise.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SamplingRule {
+    route: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    success_percent: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_percent: Option<u8>,
+}
+
+impl SamplingRule {
+    pub fn route(&self) -> &str {
+        &self.route
     }
 
-    pub fn diff_with_cached(&self, path: PathBuf) -> Result<bool, Error> {
-        OpenOptions::new()
-            .read(true)
-            .open(path)
-            .map_err(Error::from)
-            .and_then(|mut file: FsFile| {
-                let mut buffer = String::new();
-                file.read_to_string(&mut buffer)?;
-                let encoded = serde_json::to_string(self)
-                    .map_err(Error::from)
-                    .map(|s| Sha256::digest_str(&s))
-                    .map(|s| base64::encode(&s))?;
-                if encoded == buffer {
-                    debug!("Config state matches supplied config.");
-                    Ok(false)
-                } else {
-                    Ok(true)
-                }
-            }).or_else(|err| {
-                log_failure(Level::Debug, &err);
-                debug!("Error reading config backup.");
-                Ok(true)
-            })
+    pub fn success_percent(&self) -> u8 {
+        self.success_percent.unwrap_or(100)
+    }
+
+    pub fn error_percent(&self) -> u8 {
+        self.error_percent.unwrap_or(100)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use config::{Config, File, FileFormat};
-    use edgelet_docker::DockerConfig;
-    use std::io::Write;
-    use tempdir::TempDir;
+/// Whether the device is the active one of a pair of devices or stands by
+/// for it.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StandbyRole {
+    Primary,
+    Passive,
+}
 
-    #[cfg(unix)]
-    static GOOD_SETTINGS: &str = "test/linux/sample_settings.yaml";
-    #[cfg(unix)]
-    static GOOD_SETTINGS1: &str = "test/linux/sample_settings1.yaml";
-    #[cfg(unix)]
-    static BAD_SETTINGS: &str = "test/linux/bad_sample_settings.yaml";
-    #[cfg(unix)]
-    static GOOD_SETTINGS_TG: &str = "test/linux/sample_settings.tg.yaml";
+/// Whether the devices of a pair share the device identity of the primary,
+/// or each have an identity of their own.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StandbyIdentity {
+    Shared,
+    Swap,
+}
 
-    #[cfg(windows)]
-    static GOOD_SETTINGS: &str = "test/windows/sample_settings.yaml";
-    #[cfg(windows)]
-    static GOOD_SETTINGS1: &str = "test/windows/sample_settings1.yaml";
-    #[cfg(windows)]
-    static BAD_SETTINGS: &str = "test/windows/bad_sample_settings.yaml";
-    #[cfg(windows)]
-    static GOOD_SETTINGS_TG: &str = "test/windows/sample_settings.tg.yaml";
+/// Pairs the device with another one that takes over the module set when
+/// the primary fails. Experimental.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Standby {
+    role: StandbyRole,
+    identity: StandbyIdentity,
+    peer: SocketAddr,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    listen: Option<SocketAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    heartbeat_interval_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    failover_timeout_secs: Option<u64>,
+}
 
-    fn unwrap_manual_provisioning(p: &Provisioning) -> String {
-        match p {
-            Provisioning::Manual(manual) => manual.device_connection_string().to_string(),
-            _ => "not implemented".to_string(),
-        }
+impl Standby {
+    pub fn role(&self) -> StandbyRole {
+        self.role
     }
 
-    #[test]
-    fn default_in_yaml_matches_constant() {
-        let mut config = Config::default();
-        config
-            .merge(File::from_str(DEFAULTS, FileFormat::Yaml))
-            .unwrap();
-        let settings: Settings<DockerConfig> = config.try_into().unwrap();
+    pub fn identity(&self) -> StandbyIdentity {
+        self.identity
+    }
 
-        match settings.provisioning() {
-            Provisioning::Manual(ref manual) => {
-                assert_eq!(manual.device_connection_string(), DEFAULT_CONNECTION_STRING)
-            }
-            _ => assert!(false),
-        }
+    /// The address of the other device of the pair. The primary sends its
+    /// heartbeat there, and the passive device only takes heartbeats that
+    /// come from its IP address.
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
     }
 
-    #[test]
-    fn no_file_gets_error() {
-        let settings = Settings::<DockerConfig>::new(Some("garbage"));
-        assert!(settings.is_err());
+    /// The address a passive device listens for the heartbeat on.
+    pub fn listen(&self) -> SocketAddr {
+        self.listen
+            .unwrap_or_else(|| SocketAddr::from((Ipv4Addr::new(0, 0, 0, 0), DEFAULT_STANDBY_PORT)))
     }
 
-    #[test]
-    fn bad_file_gets_error() {
-        let settings = Settings::<DockerConfig>::new(Some(BAD_SETTINGS));
-        assert!(settings.is_err());
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.heartbeat_interval_secs
+                .unwrap_or(DEFAULT_STANDBY_HEARTBEAT_INTERVAL_SECS)
+                .max(1),
+        )
     }
 
-    #[test]
-    fn manual_file_gets_sample_connection_string() {
-        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS));
-        println!("{:?}", settings);
-        assert!(settings.is_ok());
-        let s = settings.unwrap();
-        let p = s.provisioning();
-        let connection_string = unwrap_manual_provisioning(p);
-        assert_eq!(
-            connection_string,
-            "HostName=something.something.com;DeviceId=something;SharedAccessKey=something"
+    /// How long a passive device goes without a heartbeat before it fails
+    /// over, which is never less than a few heartbeat intervals.
+    pub fn failover_timeout(&self) -> Duration {
+        let timeout = Duration::from_secs(
+            self.failover_timeout_secs
+                .unwrap_or(DEFAULT_STANDBY_FAILOVER_TIMEOUT_SECS),
         );
+        timeout.max(self.heartbeat_interval() * MIN_MISSED_STANDBY_HEARTBEATS)
     }
+}
 
-    #[test]
-    fn manual_file_gets_sample_tg_paths() {
-        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS_TG));
-        println!("{:?}", settings);
-        assert!(settings.is_ok());
-        let s = settings.unwrap();
-        let certificates = s.certificates();
-        certificates
-            .map(|c| {
-                assert_eq!(c.device_ca_cert().to_str().unwrap(), "device_ca_cert.pem");
-                assert_eq!(c.device_ca_pk().to_str().unwrap(), "device_ca_pk.pem");
-                assert_eq!(
-                    c.trusted_ca_certs().to_str().unwrap(),
-                    "trusted_ca_certs.pem"
-                );
-            }).expect("certificates not configured");
-    }
+/// Runs the modules of a manifest file instead of the edge agent, for devices
+/// that always run the same modules. The daemon creates the modules itself
+/// and keeps them running, and the device takes no deployments.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FixedModules {
+    manifest: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reconcile_interval_secs: Option<u64>,
+}
 
-    #[test]
-    fn diff_with_same_cached_returns_false() {
-        let tmp_dir = TempDir::new("blah").unwrap();
-        let path = tmp_dir.path().join("cache");
-        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
-        let settings_to_write = serde_json::to_string(&settings).unwrap();
-        let sha_to_write = Sha256::digest_str(&settings_to_write);
-        let base64_to_write = base64::encode(&sha_to_write);
-        FsFile::create(path.clone())
-            .unwrap()
-            .write_all(base64_to_write.as_bytes())
-            .unwrap();
-        assert_eq!(settings.diff_with_cached(path).unwrap(), false);
+impl FixedModules {
+    /// The JSON file listing the specs of the modules, which are given the
+    /// way the management API takes them. It is a file of its own since the
+    /// keys of maps in the settings are lowercased.
+    pub fn manifest(&self) -> &Path {
+        &self.manifest
     }
 
-    #[test]
-    fn diff_with_different_cached_returns_true() {
-        let tmp_dir = TempDir::new("blah").unwrap();
-        let path = tmp_dir.path().join("cache");
-        let settings1 = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
-        let settings_to_write = serde_json::to_string(&settings1).unwrap();
-        let sha_to_write = Sha256::digest_str(&settings_to_write);
-        let base64_to_write = base64::encode(&sha_to_write);
-        FsFile::create(path.clone())
-            .unwrap()
-            .write_all(base64_to_write.as_bytes())
-            .unwrap();
-        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
-        assert_eq!(settings.diff_with_cached(path).unwrap(), true);
+    /// How often the modules are checked against the manifest.
+    pub fn reconcile_interval(&self) -> Option<Duration> {
+        self.reconcile_interval_secs.map(Duration::from_secs)
     }
+}
 
-    #[test]
+/// The default directory the trust bundle is mounted at in containers.
+#[cfg(unix)]
+pub const DEFAULT_TRUST_BUNDLE_CONTAINER_DIR: &str = "/etc/iotedge/trust-bundle";
+
+#[cfg(windows)]
+pub const DEFAULT_TRUST_BUNDLE_CONTAINER_DIR: &str = "C:\\ProgramData\\iotedge\\trust-bundle";
+
+/// How often the trust bundle files are checked against the current trust
+/// bundle when no interval is configured.
+const DEFAULT_TRUST_BUNDLE_REFRESH_SECS: u64 = 60;
+
+/// Writes the trust bundle to a file on the host for each module, which is
+/// mounted read only into its container, for modules that can only read the
+/// CAs they trust from disk. The files are kept up to date when the trust
+/// bundle changes, such as when a rotation of the Edge CA ends.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TrustBundleMount {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    container_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    refresh_interval_secs: Option<u64>,
+}
+
+impl TrustBundleMount {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The directory the trust bundle file is in in the containers.
+    pub fn container_dir(&self) -> PathBuf {
+        self.container_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_TRUST_BUNDLE_CONTAINER_DIR))
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        let secs = self
+            .refresh_interval_secs
+            .unwrap_or(DEFAULT_TRUST_BUNDLE_REFRESH_SECS);
+        Duration::from_secs(secs.max(1))
+    }
+}
+
+/// The policies of the certificates modules get from the workload API, by
+/// type. The longest validity of each policy is capped at the one the daemon
+/// allows for the type.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CertPolicies {
+    #[serde(default)]
+    identity: CertPolicy,
+    #[serde(default)]
+    server: CertPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    server_key_type: Option<KeyType>,
+}
+
+impl CertPolicies {
+    fn is_empty(&self) -> bool {
+        *self == CertPolicies::default()
+    }
+
+    /// The policy of the identity certificates of modules.
+    pub fn identity(&self) -> &CertPolicy {
+        &self.identity
+    }
+
+    /// The policy of the server certificates of modules.
+    pub fn server(&self) -> &CertPolicy {
+        &self.server
+    }
+
+    /// The type of the key of the server certificates of modules that don't
+    /// ask for one, or `None` for a key of the type of the key of the CA. The
+    /// length of an RSA key type is also that of modules that ask for RSA
+    /// keys without a length.
+    pub fn server_key_type(&self) -> Option<KeyType> {
+        self.server_key_type
+    }
+}
+
+/// Helps modules on devices whose clock can't be trusted, such as devices
+/// with a broken RTC, keep their timestamps right.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ModuleTime {
+    #[serde(default)]
+    mount_localtime: bool,
+    #[serde(default)]
+    clock_skew_env: bool,
+}
+
+impl ModuleTime {
+    /// Whether the time zone of the host is mounted read only into the
+    /// containers of modules.
+    pub fn mount_localtime(&self) -> bool {
+        self.mount_localtime
+    }
+
+    /// Whether modules are told the skew of the clock of the device against
+    /// IoT Hub, as measured when they are created, in their environment.
+    pub fn clock_skew_env(&self) -> bool {
+        self.clock_skew_env
+    }
+}
+
+/// This is the share of the memory of the host, in percent, below which the
+/// least important modules are stopped, unless the config file sets another.
+const DEFAULT_MIN_AVAILABLE_MEMORY_PERCENT: u64 = 10;
+
+/// Stops the least important modules, by their priority, while the host runs
+/// short of memory.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LoadShedding {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_available_memory_percent: Option<u64>,
+}
+
+impl LoadShedding {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The share of the memory of the host, in percent, that has to be
+    /// available for no module to be stopped.
+    pub fn min_available_memory_percent(&self) -> u64 {
+        self.min_available_memory_percent
+            .unwrap_or(DEFAULT_MIN_AVAILABLE_MEMORY_PERCENT)
+    }
+}
+
+/// The oldest version of TLS the daemon negotiates with IoT Hub and DPS.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum MinTlsVersion {
+    #[serde(rename = "1.0")]
+    Tls10,
+    #[serde(rename = "1.1")]
+    Tls11,
+    #[serde(rename = "1.2")]
+    Tls12,
+}
+
+impl MinTlsVersion {
+    pub fn tls_version(self) -> TlsVersion {
+        match self {
+            MinTlsVersion::Tls10 => TlsVersion::Tls10,
+            MinTlsVersion::Tls11 => TlsVersion::Tls11,
+            MinTlsVersion::Tls12 => TlsVersion::Tls12,
+        }
+    }
+}
+
+/// The TLS the daemon negotiates on the connections it opens. The TLS
+/// library decides the cipher suites, so the minimum version is what keeps
+/// the legacy ones out.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Tls {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_version: Option<MinTlsVersion>,
+}
+
+impl Tls {
+    pub fn min_version(&self) -> Option<TlsVersion> {
+        self.min_version.map(MinTlsVersion::tls_version)
+    }
+}
+
+/// Sends check results, security events and watchdog actions to IoT Hub as
+/// device to cloud messages. Messages are queued on disk while IoT Hub can't
+/// be reached.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Telemetry {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_messages: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retention_hours: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    batch_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    module_events: Option<ModuleEvents>,
+}
+
+impl Telemetry {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The most messages queued. The oldest are dropped to make room.
+    pub fn max_messages(&self) -> usize {
+        self.max_messages.unwrap_or(DEFAULT_TELEMETRY_MAX_MESSAGES)
+    }
+
+    /// How long a message is kept while IoT Hub can't be reached.
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(
+            self.retention_hours
+                .unwrap_or(DEFAULT_TELEMETRY_RETENTION_HOURS)
+                .saturating_mul(60 * 60),
+        )
+    }
+
+    /// The most messages sent each time the queue is drained.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+            .unwrap_or(DEFAULT_TELEMETRY_BATCH_SIZE)
+            .max(1)
+    }
+
+    pub fn module_events(&self) -> Option<&ModuleEvents> {
+        self.module_events.as_ref()
+    }
+}
+
+/// Forwards the lifecycle and health events of modules, such as restarts,
+/// along with the rest of the daemon telemetry. Unset limits keep the
+/// forwarder's defaults.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ModuleEvents {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    poll_interval_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_events_per_minute: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    batch_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label_selector: Option<String>,
+}
+
+impl ModuleEvents {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// How often the module runtime is checked for changes.
+    pub fn poll_interval(&self) -> Option<Duration> {
+        self.poll_interval_secs
+            .map(|secs| Duration::from_secs(secs.max(1)))
+    }
+
+    /// The most events forwarded each minute. The rest are dropped.
+    pub fn max_events_per_minute(&self) -> Option<usize> {
+        self.max_events_per_minute
+    }
+
+    /// The most events in one message.
+    pub fn batch_size(&self) -> Option<usize> {
+        self.batch_size
+    }
+
+    /// The label selector of the modules whose events are forwarded, such
+    /// as `tier=web`. The events of every module are forwarded without one.
+    pub fn label_selector(&self) -> Option<&str> {
+        self.label_selector.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// How long before the Edge CA expires webhooks are told that it is about
+/// to, when no warning is configured.
+const DEFAULT_CERTIFICATE_EXPIRY_WARNING_DAYS: u64 = 30;
+
+/// Tells on-premises systems, such as local alerting, of events on the
+/// device without going through the cloud.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Notifications {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    webhooks: Vec<Webhook>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    certificate_expiry_warning_days: Option<u64>,
+}
+
+impl Notifications {
+    pub fn webhooks(&self) -> &[Webhook] {
+        &self.webhooks
+    }
+
+    /// How long before the Edge CA expires it is reported as expiring.
+    pub fn certificate_expiry_warning(&self) -> Duration {
+        Duration::from_secs(
+            self.certificate_expiry_warning_days
+                .unwrap_or(DEFAULT_CERTIFICATE_EXPIRY_WARNING_DAYS)
+                .saturating_mul(24 * 60 * 60),
+        )
+    }
+}
+
+/// An `https` or `unix` URL notifications are POSTed to.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Webhook {
+    #[serde(with = "url_serde")]
+    url: Url,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    events: Vec<NotificationEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<Secret>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trust_bundle: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_attempts: Option<u32>,
+}
+
+impl Webhook {
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The events the webhook is notified of. A webhook without events is
+    /// notified of all of them.
+    pub fn events(&self) -> &[NotificationEvent] {
+        &self.events
+    }
+
+    /// The secret notifications are signed with, which the receiver shares.
+    pub fn secret(&self) -> Option<&str> {
+        self.secret.as_ref().map(Secret::expose)
+    }
+
+    /// A PEM file of the roots the certificate of the receiver is issued
+    /// under, if they aren't trusted by the host.
+    pub fn trust_bundle(&self) -> Option<&Path> {
+        self.trust_bundle.as_ref().map(AsRef::as_ref)
+    }
+
+    /// The most attempts to send a notification, including the first.
+    pub fn max_attempts(&self) -> Option<u32> {
+        self.max_attempts.map(|attempts| attempts.max(1))
+    }
+}
+
+/// A named bundle of defaults for the tunables in `Tuning`, so that small
+/// devices don't need every one of them set by hand.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    Standard,
+    Constrained,
+}
+
+impl Profile {
+    // serde's skip_serializing_if passes the field by reference
+    #[cfg_attr(feature = "cargo-clippy", allow(trivially_copy_pass_by_ref))]
+    fn is_standard(&self) -> bool {
+        *self == Profile::Standard
+    }
+
+    /// The tunables set by this profile. Anything left unset keeps the
+    /// daemon's built-in default.
+    pub fn tuning(self) -> Tuning {
+        match self {
+            Profile::Standard => Tuning::default(),
+            Profile::Constrained => Tuning {
+                worker_threads: Some(1),
+                blocking_threads: Some(4),
+                deployment_history_size: Some(2),
+                watchdog_interval_secs: Some(120),
+                log_level: Some(LogLevel::Warn),
+                module_memory_limit_mb: Some(128),
+                key_slots_per_module: None,
+                workload_cert_concurrency: None,
+                workload_sign_concurrency: None,
+                workload_encrypt_concurrency: None,
+                idempotency_window_secs: None,
+            },
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::Standard
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+}
+
+impl LogLevel {
+    pub fn level_filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+        }
+    }
+}
+
+/// Resource related knobs. Each one that is set in the config file overrides
+/// the value chosen by the profile.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Tuning {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    worker_threads: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocking_threads: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deployment_history_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    watchdog_interval_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_level: Option<LogLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_memory_limit_mb: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_slots_per_module: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workload_cert_concurrency: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workload_sign_concurrency: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workload_encrypt_concurrency: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idempotency_window_secs: Option<u64>,
+}
+
+impl Tuning {
+    fn is_empty(&self) -> bool {
+        *self == Tuning::default()
+    }
+
+    // Fills in the tunables that aren't set from `defaults`.
+    fn or(self, defaults: Tuning) -> Self {
+        Tuning {
+            worker_threads: self.worker_threads.or(defaults.worker_threads),
+            blocking_threads: self.blocking_threads.or(defaults.blocking_threads),
+            deployment_history_size: self
+                .deployment_history_size
+                .or(defaults.deployment_history_size),
+            watchdog_interval_secs: self
+                .watchdog_interval_secs
+                .or(defaults.watchdog_interval_secs),
+            log_level: self.log_level.or(defaults.log_level),
+            module_memory_limit_mb: self
+                .module_memory_limit_mb
+                .or(defaults.module_memory_limit_mb),
+            key_slots_per_module: self
+                .key_slots_per_module
+                .or(defaults.key_slots_per_module),
+            workload_cert_concurrency: self
+                .workload_cert_concurrency
+                .or(defaults.workload_cert_concurrency),
+            workload_sign_concurrency: self
+                .workload_sign_concurrency
+                .or(defaults.workload_sign_concurrency),
+            workload_encrypt_concurrency: self
+                .workload_encrypt_concurrency
+                .or(defaults.workload_encrypt_concurrency),
+            idempotency_window_secs: self
+                .idempotency_window_secs
+                .or(defaults.idempotency_window_secs),
+        }
+    }
+
+    /// The number of threads running the daemon's services. Defaults to one
+    /// per CPU core.
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.worker_threads.map(|threads| threads.max(1))
+    }
+
+    /// The maximum number of threads for blocking operations such as file
+    /// access.
+    pub fn blocking_threads(&self) -> Option<usize> {
+        self.blocking_threads.map(|threads| threads.max(1))
+    }
+
+    /// The number of previous deployments kept for rollback.
+    pub fn deployment_history_size(&self) -> usize {
+        self.deployment_history_size.unwrap_or(DEFAULT_HISTORY_SIZE)
+    }
+
+    /// How often the watchdog checks that the edge runtime is running.
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval_secs.map(Duration::from_secs)
+    }
+
+    /// The most verbose log level. The IOTEDGE_LOG environment variable
+    /// takes precedence when it is set.
+    pub fn log_level(&self) -> LevelFilter {
+        self.log_level.map_or(LevelFilter::Info, LogLevel::level_filter)
+    }
+
+    /// The memory limit, in bytes, of modules whose create options don't set
+    /// one.
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+    pub fn module_memory_limit(&self) -> Option<i64> {
+        self.module_memory_limit_mb
+            .map(|limit| limit.saturating_mul(1024 * 1024) as i64)
+    }
+
+    /// The number of HSM key slots each module can hold certificates in
+    /// through the workload API. Defaults to no quota.
+    pub fn key_slots_per_module(&self) -> Option<usize> {
+        self.key_slots_per_module
+    }
+
+    /// The number of requests for certificates the workload API handles at
+    /// once. Defaults to no limit.
+    pub fn workload_cert_concurrency(&self) -> Option<usize> {
+        self.workload_cert_concurrency
+    }
+
+    /// The number of requests to sign the workload API handles at once.
+    /// Defaults to no limit.
+    pub fn workload_sign_concurrency(&self) -> Option<usize> {
+        self.workload_sign_concurrency
+    }
+
+    /// The number of requests to encrypt, decrypt, wrap or unwrap keys the
+    /// workload API handles at once. Defaults to no limit.
+    pub fn workload_encrypt_concurrency(&self) -> Option<usize> {
+        self.workload_encrypt_concurrency
+    }
+
+    /// How long the management API keeps the responses to requests made
+    /// with an idempotency key, to return them again when the requests are
+    /// retried.
+    pub fn idempotency_window(&self) -> Duration {
+        Duration::from_secs(
+            self.idempotency_window_secs
+                .unwrap_or(DEFAULT_IDEMPOTENCY_WINDOW_SECS),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Settings<T> {
+    provisioning: Provisioning,
+    agent: ModuleSpec<T>,
+    hostname: String,
+    connect: Connect,
+    listen: Listen,
+    homedir: PathBuf,
+    moby_runtime: MobyRuntime,
+    certificates: Option<Certificates>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quickstart_ca: Option<QuickstartCa>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    certificate_policy: Option<CertificatePolicy>,
+    #[serde(default, skip_serializing_if = "CertPolicies::is_empty")]
+    cert_policies: CertPolicies,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    schedules: Vec<ScheduleSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    discovery: Option<Discovery>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    heartbeat_interval_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Profile::is_standard")]
+    profile: Profile,
+    #[serde(default, skip_serializing_if = "Tuning::is_empty")]
+    tuning: Tuning,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    twin_overrides: Option<TwinOverrides>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    telemetry: Option<Telemetry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    module_env: Vec<EnvVar>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    developer: Option<Developer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    access_log: Option<AccessLog>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    standby: Option<Standby>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    os_tracing: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fixed_modules: Option<FixedModules>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sign_workload_responses: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    export_identity_keys: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    read_only_management: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    management_state_backup: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offline: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trust_bundle_files: Option<TrustBundleMount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    module_time: Option<ModuleTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    load_shedding: Option<LoadShedding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls: Option<Tls>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notifications: Option<Notifications>,
+    #[serde(skip)]
+    config_file: Option<PathBuf>,
+    #[serde(skip)]
+    deprecations: Vec<Deprecation>,
+}
+
+impl<T> Settings<T>
+where
+    T: DeserializeOwned + Serialize,
+{
+    pub fn new(filename: Option<&str>) -> Result<Self, Error> {
+        let mut config = Config::default();
+        config.merge(File::from_str(DEFAULTS, FileFormat::Yaml))?;
+        let mut deprecations = vec![];
+        if let Some(file) = filename {
+            match migration::migrate_file(Path::new(file))? {
+                Some(migrated) => {
+                    config.merge(File::from_str(migrated.yaml(), FileFormat::Yaml))?;
+                    deprecations.extend_from_slice(migrated.deprecations());
+                }
+                None => {
+                    config.merge(File::with_name(file).required(true))?;
+                }
+            };
+        }
+
+        config.merge(Environment::with_prefix("iotedge"))?;
+
+        let mut settings: Self = config.try_into()?;
+        settings.config_file = filename.map(PathBuf::from);
+        settings.deprecations = deprecations;
+
+        Ok(settings)
+    }
+
+    pub fn provisioning(&self) -> &Provisioning {
+        &self.provisioning
+    }
+
+    pub fn agent(&self) -> &ModuleSpec<T> {
+        &self.agent
+    }
+
+    pub fn agent_mut(&mut self) -> &mut ModuleSpec<T> {
+        &mut self.agent
+    }
+
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    pub fn connect(&self) -> &Connect {
+        &self.connect
+    }
+
+    pub fn listen(&self) -> &Listen {
+        &self.listen
+    }
+
+    pub fn homedir(&self) -> &Path {
+        &self.homedir
+    }
+
+    pub fn moby_runtime(&self) -> &MobyRuntime {
+        &self.moby_runtime
+    }
+
+    pub fn certificates(&self) -> Option<&Certificates> {
+        self.certificates.as_ref()
+    }
+
+    /// How long the CA generated when no certificates are configured is
+    /// valid for.
+    pub fn quickstart_ca_lifetime(&self) -> Duration {
+        self.quickstart_ca
+            .as_ref()
+            .map_or_else(|| QuickstartCa::default().lifetime(), QuickstartCa::lifetime)
+    }
+
+    pub fn certificate_policy(&self) -> Option<&CertificatePolicy> {
+        self.certificate_policy.as_ref()
+    }
+
+    pub fn cert_policies(&self) -> &CertPolicies {
+        &self.cert_policies
+    }
+
+    pub fn schedules(&self) -> &[ScheduleSpec] {
+        &self.schedules
+    }
+
+    pub fn discovery(&self) -> Option<&Discovery> {
+        self.discovery.as_ref()
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.heartbeat_interval_secs
+                .unwrap_or(DEFAULT_HEARTBEAT_INTERVAL_SECS),
+        )
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    /// The tunables from the config file, with the rest taken from the
+    /// profile.
+    pub fn tuning(&self) -> Tuning {
+        self.tuning.clone().or(self.profile.tuning())
+    }
+
+    pub fn twin_overrides(&self) -> Option<&TwinOverrides> {
+        self.twin_overrides.as_ref()
+    }
+
+    pub fn telemetry(&self) -> Option<&Telemetry> {
+        self.telemetry.as_ref()
+    }
+
+    /// The environment variables set in every module. They are a list rather
+    /// than a map since the keys of maps in the settings are lowercased.
+    pub fn module_env(&self) -> &[EnvVar] {
+        &self.module_env
+    }
+
+    pub fn developer(&self) -> Option<&Developer> {
+        self.developer.as_ref()
+    }
+
+    pub fn access_log(&self) -> Option<&AccessLog> {
+        self.access_log.as_ref()
+    }
+
+    pub fn standby(&self) -> Option<&Standby> {
+        self.standby.as_ref()
+    }
+
+    pub fn fixed_modules(&self) -> Option<&FixedModules> {
+        self.fixed_modules.as_ref()
+    }
+
+    /// Whether lifecycle and request events are emitted to the tracing
+    /// facility of the OS, which takes a daemon built with the `os-tracing`
+    /// feature.
+    pub fn os_tracing(&self) -> bool {
+        self.os_tracing.unwrap_or(false)
+    }
+
+    /// Whether the workload API signs the responses that carry certificates,
+    /// so that modules can tell that they came from the daemon.
+    pub fn sign_workload_responses(&self) -> bool {
+        self.sign_workload_responses.unwrap_or(false)
+    }
+
+    /// Whether the identity endpoints of the management API return the
+    /// symmetric keys of module identities. Otherwise the keys never leave
+    /// the daemon, and modules sign with them through the workload API.
+    pub fn export_identity_keys(&self) -> bool {
+        self.export_identity_keys.unwrap_or(false)
+    }
+
+    /// Whether the management API refuses the requests that would change the
+    /// modules, identities, certificates or keys of the device other than
+    /// those of the edge agent, so that changes only come from deployments.
+    pub fn read_only_management(&self) -> bool {
+        self.read_only_management.unwrap_or(false)
+    }
+
+    /// Whether the management API backs up and restores the state of the
+    /// daemon for the edge agent. Backups hold the keys of the device, so
+    /// they aren't served unless this is turned on.
+    pub fn management_state_backup(&self) -> bool {
+        self.management_state_backup.unwrap_or(false)
+    }
+
+    /// Whether the device is meant to run without a connection to IoT Hub
+    /// or DPS. The daemon then doesn't try to reach them, and runs the
+    /// modules it knows of with the credentials it has cached.
+    pub fn offline(&self) -> bool {
+        self.offline.unwrap_or(false)
+    }
+
+    /// The parent gateway of a nested device, which it reaches IoT Hub, DPS
+    /// and the registry proxy through instead of the public cloud.
+    pub fn parent_hostname(&self) -> Option<&str> {
+        self.parent_hostname.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn trust_bundle_files(&self) -> Option<&TrustBundleMount> {
+        self.trust_bundle_files.as_ref()
+    }
+
+    pub fn module_time(&self) -> Option<&ModuleTime> {
+        self.module_time.as_ref()
+    }
+
+    pub fn load_shedding(&self) -> Option<&LoadShedding> {
+        self.load_shedding.as_ref()
+    }
+
+    pub fn tls(&self) -> Option<&Tls> {
+        self.tls.as_ref()
+    }
+
+    pub fn notifications(&self) -> Option<&Notifications> {
+        self.notifications.as_ref()
+    }
+
+    /// The config file the settings were loaded from, if any.
+    pub fn config_file(&self) -> Option<&Path> {
+        self.config_file.as_ref().map(AsRef::as_ref)
+    }
+
+    /// The deprecated settings the config file uses, which are logged when
+    /// the daemon starts and reported by `iotedge check`.
+    pub fn deprecations(&self) -> &[Deprecation] {
+        &self.deprecations
+    }
+
+    /// A hash of the settings whose change calls for the device to be set up
+    /// from scratch. The host name is left out, since a change of host name
+    /// only calls for new certificates, which the daemon handles on its own.
+    /// So are the certificates, since a rotation of the Edge CA is handled
+    /// without removing the modules that trust it, as is the lifetime of the
+    /// quick start CA, and the access log, which only changes what the daemon
+    /// logs, and so is OS tracing. So is the pairing with a standby device,
+    /// since a passive device removes the modules on its own, and the fixed
+    /// module set, which the modules are brought in line with as it changes.
+    /// So are the notifications, which are only sent off the device, and load
+    /// shedding, which only stops and starts the modules.
+    pub fn state_hash(&self) -> Result<String, Error> {
+        self.hash_without(&[
+            "hostname",
+            "certificates",
+            "quickstart_ca",
+            "access_log",
+            "standby",
+            "os_tracing",
+            "fixed_modules",
+            "notifications",
+            "load_shedding",
+        ])
+    }
+
+    // The hashes older versions of the daemon cached: the one that only left
+    // out the host name, and the one of all the settings.
+    fn previous_state_hashes(&self) -> Result<Vec<String>, Error> {
+        let encoded = serde_json::to_string(self)?;
+        Ok(vec![
+            self.hash_without(&["hostname"])?,
+            base64::encode(&Sha256::digest_str(&encoded)),
+        ])
+    }
+
+    fn hash_without(&self, keys: &[&str]) -> Result<String, Error> {
+        let mut settings = serde_json::to_value(self)?;
+        if let Some(settings) = settings.as_object_mut() {
+            for key in keys {
+                settings.remove(*key);
+            }
+        }
+        let encoded = serde_json::to_string(&settings)?;
+        Ok(base64::encode(&Sha256::digest_str(&encoded)))
+    }
+
+    pub fn diff_with_cached(&self, path: PathBuf) -> Result<bool, Error> {
+        OpenOptions::new()
+            .read(true)
+            .open(path)
+            .map_err(Error::from)
+            .and_then(|mut file: FsFile| {
+                let mut buffer = String::new();
+                file.read_to_string(&mut buffer)?;
+                let previous = self.previous_state_hashes()?;
+                if buffer == self.state_hash()? || previous.contains(&buffer) {
+                    debug!("Config state matches supplied config.");
+                    Ok(false)
+                } else {
+                    Ok(true)
+                }
+            }).or_else(|err| {
+                log_failure(Level::Debug, &err);
+                debug!("Error reading config backup.");
+                Ok(true)
+            })
+    }
+}
+
+impl Settings<DockerConfig> {
+    /// Replaces the settings that refer to secrets, the device connection
+    /// string and the password of the registry of the edge agent, with the
+    /// secrets they refer to.
+    pub fn resolve_secrets(&mut self, resolvers: &SecretResolvers) -> Result<(), Error> {
+        if let Provisioning::Manual(ref mut manual) = self.provisioning {
+            manual.device_connection_string =
+                resolvers.resolve(&manual.device_connection_string)?;
+        }
+
+        let config = self.agent.config_mut();
+        let auth = config.auth().and_then(|auth| {
+            auth.password()
+                .map(|password| (auth.clone(), Secret::new(password.to_string())))
+        });
+        if let Some((auth, password)) = auth {
+            let password = resolvers.resolve(&password)?;
+            config.set_auth(auth.with_password(password.expose().to_string()));
+        }
+
+        if let Some(ref mut notifications) = self.notifications {
+            for webhook in &mut notifications.webhooks {
+                if let Some(secret) = webhook.secret.take() {
+                    webhook.secret = Some(resolvers.resolve(&secret)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{Config, File, FileFormat};
+    use docker::models::AuthConfig;
+    use edgelet_core::{EcCurve, KeyAlgorithm, RsaKeyLength, SanPolicy, ScheduledAction};
+    use std::env;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[cfg(unix)]
+    static GOOD_SETTINGS: &str = "test/linux/sample_settings.yaml";
+    #[cfg(unix)]
+    static GOOD_SETTINGS1: &str = "test/linux/sample_settings1.yaml";
+    #[cfg(unix)]
+    static BAD_SETTINGS: &str = "test/linux/bad_sample_settings.yaml";
+    #[cfg(unix)]
+    static GOOD_SETTINGS_TG: &str = "test/linux/sample_settings.tg.yaml";
+
+    #[cfg(windows)]
+    static GOOD_SETTINGS: &str = "test/windows/sample_settings.yaml";
+    #[cfg(windows)]
+    static GOOD_SETTINGS1: &str = "test/windows/sample_settings1.yaml";
+    #[cfg(windows)]
+    static BAD_SETTINGS: &str = "test/windows/bad_sample_settings.yaml";
+    #[cfg(windows)]
+    static GOOD_SETTINGS_TG: &str = "test/windows/sample_settings.tg.yaml";
+
+    fn unwrap_manual_provisioning(p: &Provisioning) -> String {
+        match p {
+            Provisioning::Manual(manual) => manual.device_connection_string().to_string(),
+            _ => "not implemented".to_string(),
+        }
+    }
+
+    #[test]
+    fn default_in_yaml_matches_constant() {
+        let mut config = Config::default();
+        config
+            .merge(File::from_str(DEFAULTS, FileFormat::Yaml))
+            .unwrap();
+        let settings: Settings<DockerConfig> = config.try_into().unwrap();
+
+        match settings.provisioning() {
+            Provisioning::Manual(ref manual) => {
+                assert_eq!(manual.device_connection_string(), DEFAULT_CONNECTION_STRING)
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn no_file_gets_error() {
+        let settings = Settings::<DockerConfig>::new(Some("garbage"));
+        assert!(settings.is_err());
+    }
+
+    #[test]
+    fn bad_file_gets_error() {
+        let settings = Settings::<DockerConfig>::new(Some(BAD_SETTINGS));
+        assert!(settings.is_err());
+    }
+
+    #[test]
+    fn manual_file_gets_sample_connection_string() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS));
+        println!("{:?}", settings);
+        assert!(settings.is_ok());
+        let s = settings.unwrap();
+        let p = s.provisioning();
+        let connection_string = unwrap_manual_provisioning(p);
+        assert_eq!(
+            connection_string,
+            "HostName=something.something.com;DeviceId=something;SharedAccessKey=something"
+        );
+        assert_eq!(Some(Path::new(GOOD_SETTINGS)), s.config_file());
+    }
+
+    #[test]
+    fn manual_file_gets_sample_tg_paths() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS_TG));
+        println!("{:?}", settings);
+        assert!(settings.is_ok());
+        let s = settings.unwrap();
+        let certificates = s.certificates();
+        certificates
+            .map(|c| {
+                assert_eq!(c.device_ca_cert().to_str().unwrap(), "device_ca_cert.pem");
+                assert_eq!(c.device_ca_pk().to_str().unwrap(), "device_ca_pk.pem");
+                assert_eq!(
+                    c.trusted_ca_certs().to_str().unwrap(),
+                    "trusted_ca_certs.pem"
+                );
+            }).expect("certificates not configured");
+    }
+
+    #[test]
+    fn diff_with_same_cached_returns_false() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        let settings_to_write = serde_json::to_string(&settings).unwrap();
+        let sha_to_write = Sha256::digest_str(&settings_to_write);
+        let base64_to_write = base64::encode(&sha_to_write);
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(base64_to_write.as_bytes())
+            .unwrap();
+        assert_eq!(settings.diff_with_cached(path).unwrap(), false);
+    }
+
+    #[test]
+    fn diff_with_different_cached_returns_true() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings1 = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let settings_to_write = serde_json::to_string(&settings1).unwrap();
+        let sha_to_write = Sha256::digest_str(&settings_to_write);
+        let base64_to_write = base64::encode(&sha_to_write);
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(base64_to_write.as_bytes())
+            .unwrap();
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert_eq!(settings.diff_with_cached(path).unwrap(), true);
+    }
+
+    #[test]
+    fn diff_ignores_hostname() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(settings.state_hash().unwrap().as_bytes())
+            .unwrap();
+
+        let mut renamed = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        renamed.hostname = "renamed".to_string();
+        assert_eq!(renamed.diff_with_cached(path).unwrap(), false);
+    }
+
+    #[test]
+    fn diff_ignores_certificates() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(settings.state_hash().unwrap().as_bytes())
+            .unwrap();
+
+        let mut rotated = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        rotated.certificates = Some(Certificates {
+            device_ca_cert: PathBuf::from("new_ca.pem"),
+            device_ca_pk: PathBuf::from("new_ca.key.pem"),
+            trusted_ca_certs: PathBuf::from("new_root.pem"),
+            rotation_overlap_hours: Some(24),
+        });
+        assert_eq!(rotated.diff_with_cached(path).unwrap(), false);
+        assert_eq!(
+            Duration::from_secs(24 * 3600),
+            rotated.certificates().unwrap().rotation_overlap()
+        );
+    }
+
+    #[test]
+    fn diff_ignores_access_log() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(settings.state_hash().unwrap().as_bytes())
+            .unwrap();
+
+        let mut sampled = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        sampled.access_log = Some(AccessLog {
+            management: vec![],
+            workload: vec![SamplingRule {
+                route: "/sign$".to_string(),
+                success_percent: Some(1),
+                error_percent: None,
+            }],
+        });
+        assert_eq!(sampled.diff_with_cached(path).unwrap(), false);
+        let rule = &sampled.access_log().unwrap().workload()[0];
+        assert_eq!((1, 100), (rule.success_percent(), rule.error_percent()));
+    }
+
+    #[test]
+    fn diff_ignores_os_tracing() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(!settings.os_tracing());
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(settings.state_hash().unwrap().as_bytes())
+            .unwrap();
+
+        let mut traced = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        traced.os_tracing = Some(true);
+        assert!(traced.os_tracing());
+        assert_eq!(traced.diff_with_cached(path).unwrap(), false);
+    }
+
+    #[test]
+    fn diff_ignores_fixed_modules() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.fixed_modules().is_none());
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(settings.state_hash().unwrap().as_bytes())
+            .unwrap();
+
+        let mut fixed = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        fixed.fixed_modules = Some(FixedModules {
+            manifest: PathBuf::from("/etc/iotedge/modules.json"),
+            reconcile_interval_secs: Some(10),
+        });
+        assert_eq!(
+            Some(Duration::from_secs(10)),
+            fixed.fixed_modules().unwrap().reconcile_interval()
+        );
+        assert_eq!(fixed.diff_with_cached(path).unwrap(), false);
+    }
+
+    // Modules are only given the key to verify the responses with when they
+    // are created.
+    #[test]
+    fn diff_includes_workload_response_signing() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(!settings.sign_workload_responses());
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(settings.state_hash().unwrap().as_bytes())
+            .unwrap();
+
+        let mut signed = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        signed.sign_workload_responses = Some(true);
+        assert!(signed.sign_workload_responses());
+        assert_eq!(signed.diff_with_cached(path).unwrap(), true);
+    }
+
+    #[test]
+    fn identity_keys_are_not_exported_by_default() {
+        let mut settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(!settings.export_identity_keys());
+        settings.export_identity_keys = Some(true);
+        assert!(settings.export_identity_keys());
+    }
+
+    #[test]
+    fn management_api_is_writable_by_default() {
+        let mut settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(!settings.read_only_management());
+        settings.read_only_management = Some(true);
+        assert!(settings.read_only_management());
+    }
+
+    #[test]
+    fn state_backups_are_not_served_by_default() {
+        let mut settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(!settings.management_state_backup());
+        settings.management_state_backup = Some(true);
+        assert!(settings.management_state_backup());
+    }
+
+    #[test]
+    fn secrets_are_resolved() {
+        let mut settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        let hash = settings.state_hash().unwrap();
+        settings.resolve_secrets(&SecretResolvers::new()).unwrap();
+        assert_eq!(hash, settings.state_hash().unwrap());
+
+        env::set_var(
+            "IOTEDGED_TEST_CONNECTION_STRING",
+            "HostName=hub.azure-devices.net;DeviceId=d1;SharedAccessKey=a2V5",
+        );
+        env::set_var("IOTEDGED_TEST_REGISTRY_PASSWORD", "password");
+        settings.provisioning = Provisioning::Manual(Manual {
+            device_connection_string: Secret::new(
+                "env://IOTEDGED_TEST_CONNECTION_STRING".to_string(),
+            ),
+        });
+        let config = settings.agent.config().clone().with_auth(
+            AuthConfig::new().with_password("env://IOTEDGED_TEST_REGISTRY_PASSWORD".to_string()),
+        );
+        *settings.agent.config_mut() = config;
+        settings.resolve_secrets(&SecretResolvers::new()).unwrap();
+
+        match settings.provisioning() {
+            Provisioning::Manual(manual) => assert_eq!(
+                "HostName=hub.azure-devices.net;DeviceId=d1;SharedAccessKey=a2V5",
+                manual.device_connection_string()
+            ),
+            _ => panic!("unexpected provisioning"),
+        }
+        assert_eq!(
+            Some("password"),
+            settings
+                .agent()
+                .config()
+                .auth()
+                .and_then(AuthConfig::password)
+        );
+        assert!(!format!("{:?}", settings.provisioning()).contains("a2V5"));
+    }
+
+    #[test]
+    fn devices_are_online_by_default() {
+        let mut settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(!settings.offline());
+        settings.offline = Some(true);
+        assert!(settings.offline());
+    }
+
+    #[test]
+    fn parent_hostname_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert_eq!(None, settings.parent_hostname());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        assert_eq!(Some("parent.contoso.com"), settings.parent_hostname());
+    }
+
+    #[test]
+    fn diff_accepts_hash_cached_before_certificates_were_left_out() {
+        let tmp_dir = TempDir::new("blah").unwrap();
+        let path = tmp_dir.path().join("cache");
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        FsFile::create(path.clone())
+            .unwrap()
+            .write_all(settings.hash_without(&["hostname"]).unwrap().as_bytes())
+            .unwrap();
+        assert_eq!(settings.diff_with_cached(path).unwrap(), false);
+    }
+
+    #[test]
     fn diff_with_no_file_returns_true() {
         let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
         assert_eq!(
@@ -378,17 +1881,443 @@ mod tests {
         );
     }
 
+    #[test]
+    fn schedules_default_to_empty() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.schedules().is_empty());
+    }
+
+    #[test]
+    fn schedules_are_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let schedules = settings.schedules();
+        assert_eq!(2, schedules.len());
+        assert_eq!("batchUpload", schedules[0].module());
+        assert_eq!(ScheduledAction::Start, schedules[0].action());
+        assert_eq!("0 2 * * *", schedules[0].schedule());
+        assert_eq!(ScheduledAction::Stop, schedules[1].action());
+    }
+
+    #[test]
+    fn quickstart_ca_lifetime_defaults_to_90_days() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert_eq!(
+            Duration::from_secs(90 * 24 * 3600),
+            settings.quickstart_ca_lifetime()
+        );
+    }
+
+    #[test]
+    fn quickstart_ca_lifetime_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        assert_eq!(
+            Duration::from_secs(30 * 24 * 3600),
+            settings.quickstart_ca_lifetime()
+        );
+    }
+
+    #[test]
+    fn certificate_policy_defaults_to_none() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.certificate_policy().is_none());
+    }
+
+    #[test]
+    fn certificate_policy_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let policy = settings.certificate_policy().unwrap();
+        assert!(!policy.allow_wildcards());
+        assert!(policy.check("edgeHub", &["mqtt.gateway.contoso.com"]).is_ok());
+        assert!(policy.check("edgeHub", &["gateway.contoso.com"]).is_err());
+    }
+
+    #[test]
+    fn pipe_access_defaults_to_none() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.listen().pipe_access().is_none());
+    }
+
+    #[test]
+    fn pipe_access_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let pipe_access = settings.listen().pipe_access().unwrap();
+        assert_eq!(&["BA".to_string()], pipe_access.management());
+        assert_eq!(
+            &["BA".to_string(), "S-1-5-32-545".to_string()],
+            pipe_access.workload()
+        );
+    }
+
+    #[test]
+    fn socket_permissions_are_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.listen().socket_permissions().is_none());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let permissions = settings.listen().socket_permissions().unwrap();
+        let management = permissions.management().unwrap();
+        assert_eq!(Some("0660"), management.mode());
+        assert_eq!(None, management.owner());
+        assert_eq!(Some("0"), management.group());
+        let workload = permissions.workload().unwrap();
+        assert_eq!(Some("0666"), workload.mode());
+        assert_eq!(None, workload.group());
+    }
+
+    #[test]
+    fn workload_tls_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.listen().workload_tls().is_none());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let tls = settings.listen().workload_tls().unwrap();
+        assert_eq!(Path::new("/etc/iotedge/workload.pem"), tls.cert());
+        assert_eq!(Path::new("/etc/iotedge/workload.key.pem"), tls.key());
+        assert_eq!(Path::new("/etc/iotedge/edge-ca.pem"), tls.client_ca());
+        assert_eq!(1, tls.peers().len());
+        assert_eq!("childDevice", tls.peers()[0].common_name());
+        assert_eq!(
+            &["childDevice".to_string(), "sensor".to_string()],
+            tls.peers()[0].modules()
+        );
+    }
+
+    #[test]
+    fn discovery_defaults_to_none() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.discovery().is_none());
+    }
+
+    #[test]
+    fn discovery_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let discovery = settings.discovery().unwrap();
+        assert!(discovery.enabled());
+        assert_eq!(Some(Ipv4Addr::new(192, 168, 1, 10)), discovery.address());
+        assert_eq!(default_discovery_services(), discovery.services());
+    }
+
+    #[test]
+    fn heartbeat_interval_defaults() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert_eq!(
+            Duration::from_secs(DEFAULT_HEARTBEAT_INTERVAL_SECS),
+            settings.heartbeat_interval()
+        );
+    }
+
+    #[test]
+    fn heartbeat_interval_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        assert_eq!(Duration::from_secs(30), settings.heartbeat_interval());
+    }
+
+    #[test]
+    fn standard_profile_keeps_built_in_defaults() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert_eq!(Profile::Standard, settings.profile());
+        let tuning = settings.tuning();
+        assert_eq!(None, tuning.worker_threads());
+        assert_eq!(None, tuning.blocking_threads());
+        assert_eq!(DEFAULT_HISTORY_SIZE, tuning.deployment_history_size());
+        assert_eq!(None, tuning.watchdog_interval());
+        assert_eq!(LevelFilter::Info, tuning.log_level());
+        assert_eq!(None, tuning.module_memory_limit());
+        assert_eq!(
+            Duration::from_secs(DEFAULT_IDEMPOTENCY_WINDOW_SECS),
+            tuning.idempotency_window()
+        );
+    }
+
+    #[test]
+    fn tuning_overrides_profile() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        assert_eq!(Profile::Constrained, settings.profile());
+        let tuning = settings.tuning();
+        assert_eq!(Some(2), tuning.worker_threads());
+        assert_eq!(Some(268_435_456), tuning.module_memory_limit());
+        assert_eq!(Some(8), tuning.key_slots_per_module());
+        assert_eq!(Some(2), tuning.workload_encrypt_concurrency());
+        assert_eq!(None, tuning.workload_cert_concurrency());
+        // the rest comes from the profile
+        assert_eq!(Some(4), tuning.blocking_threads());
+        assert_eq!(2, tuning.deployment_history_size());
+        assert_eq!(Some(Duration::from_secs(120)), tuning.watchdog_interval());
+        assert_eq!(LevelFilter::Warn, tuning.log_level());
+    }
+
+    #[test]
+    fn twin_overrides_default_to_none() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.twin_overrides().is_none());
+    }
+
+    #[test]
+    fn twin_overrides_are_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let overrides = settings.twin_overrides().unwrap();
+        assert!(overrides.enabled());
+        assert_eq!(Duration::from_secs(60), overrides.poll_interval());
+    }
+
+    #[test]
+    fn telemetry_defaults() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.telemetry().is_none());
+
+        let telemetry: Telemetry = serde_json::from_str(r#"{"enabled": true}"#).unwrap();
+        assert_eq!(DEFAULT_TELEMETRY_MAX_MESSAGES, telemetry.max_messages());
+        assert_eq!(Duration::from_secs(7 * 24 * 3600), telemetry.retention());
+        assert_eq!(DEFAULT_TELEMETRY_BATCH_SIZE, telemetry.batch_size());
+    }
+
+    #[test]
+    fn telemetry_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let telemetry = settings.telemetry().unwrap();
+        assert!(telemetry.enabled());
+        assert_eq!(200, telemetry.max_messages());
+        assert_eq!(Duration::from_secs(48 * 3600), telemetry.retention());
+        assert_eq!(5, telemetry.batch_size());
+        let events = telemetry.module_events().unwrap();
+        assert!(events.enabled());
+        assert_eq!(Some(Duration::from_secs(10)), events.poll_interval());
+        assert_eq!(Some(30), events.max_events_per_minute());
+        assert_eq!(None, events.batch_size());
+        assert_eq!(Some("tier=web"), events.label_selector());
+    }
+
+    #[test]
+    fn notifications_are_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.notifications().is_none());
+
+        env::set_var("IOTEDGED_TEST_WEBHOOK_SECRET", "webhook secret");
+        let mut settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let hash = settings.state_hash().unwrap();
+        settings.resolve_secrets(&SecretResolvers::new()).unwrap();
+        assert_eq!(hash, settings.state_hash().unwrap());
+
+        let notifications = settings.notifications().unwrap();
+        assert_eq!(
+            Duration::from_secs(14 * 24 * 3600),
+            notifications.certificate_expiry_warning()
+        );
+        let webhooks = notifications.webhooks();
+        assert_eq!(2, webhooks.len());
+        assert_eq!("https://alerts.local/iotedge", webhooks[0].url().as_str());
+        assert_eq!(
+            &[
+                NotificationEvent::ModuleCrashLoop,
+                NotificationEvent::CertificateExpiring
+            ],
+            webhooks[0].events()
+        );
+        assert_eq!(Some("webhook secret"), webhooks[0].secret());
+        assert_eq!(Some(Path::new("/etc/alerts/ca.pem")), webhooks[0].trust_bundle());
+        assert_eq!(Some(3), webhooks[0].max_attempts());
+        assert!(webhooks[1].events().is_empty());
+        assert_eq!(None, webhooks[1].secret());
+        assert!(!format!("{:?}", notifications).contains("webhook secret"));
+
+        let notifications: Notifications = serde_json::from_str("{}").unwrap();
+        assert_eq!(
+            Duration::from_secs(30 * 24 * 3600),
+            notifications.certificate_expiry_warning()
+        );
+    }
+
+    #[test]
+    fn trust_bundle_files_are_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let mount = settings.trust_bundle_files().unwrap();
+        assert!(mount.enabled());
+        assert_eq!(
+            PathBuf::from(DEFAULT_TRUST_BUNDLE_CONTAINER_DIR),
+            mount.container_dir()
+        );
+        assert_eq!(Duration::from_secs(30), mount.refresh_interval());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.trust_bundle_files().is_none());
+    }
+
+    #[test]
+    fn cert_policies_are_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let policies = settings.cert_policies();
+        assert_eq!(
+            &CertPolicy::new()
+                .with_key_algorithms(vec![KeyAlgorithm::Ec])
+                .with_renewal_threshold(80),
+            policies.identity()
+        );
+        assert_eq!(
+            &CertPolicy::new()
+                .with_max_duration(86_400)
+                .with_san(SanPolicy::new().with_dns_common_name(true)),
+            policies.server()
+        );
+        assert_eq!(
+            Some(KeyType::Ec {
+                curve: EcCurve::P256
+            }),
+            policies.server_key_type()
+        );
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert_eq!(&CertPolicies::default(), settings.cert_policies());
+    }
+
+    #[test]
+    fn server_key_type_may_be_rsa_of_a_length() {
+        let policies: CertPolicies =
+            serde_json::from_str(r#"{"server_key_type": {"type": "rsa", "length": 4096}}"#)
+                .unwrap();
+        assert_eq!(
+            Some(KeyType::Rsa {
+                length: Some(RsaKeyLength::Bits4096)
+            }),
+            policies.server_key_type()
+        );
+
+        assert!(serde_json::from_str::<CertPolicies>(
+            r#"{"server_key_type": {"type": "rsa", "length": 1024}}"#
+        ).is_err());
+    }
+
+    #[test]
+    fn module_time_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let module_time = settings.module_time().unwrap();
+        assert!(module_time.mount_localtime());
+        assert!(!module_time.clock_skew_env());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.module_time().is_none());
+    }
+
+    #[test]
+    fn load_shedding_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let load_shedding = settings.load_shedding().unwrap();
+        assert!(load_shedding.enabled());
+        assert_eq!(15, load_shedding.min_available_memory_percent());
+
+        let load_shedding: LoadShedding = serde_json::from_str(r#"{"enabled": true}"#).unwrap();
+        assert_eq!(10, load_shedding.min_available_memory_percent());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.load_shedding().is_none());
+    }
+
+    #[test]
+    fn min_tls_version_is_read_from_file() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        assert_eq!(
+            Some(TlsVersion::Tls12),
+            settings.tls().and_then(Tls::min_version)
+        );
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.tls().is_none());
+    }
+
+    #[test]
+    fn old_layouts_are_migrated_when_loaded() {
+        let tmp_dir = TempDir::new("migration").unwrap();
+        let path = tmp_dir.path().join("config.yaml");
+        let old = ::std::fs::read_to_string(GOOD_SETTINGS).unwrap().replace(
+            "\n  uri: \"http://localhost:2375\"",
+            "\n  docker_uri: \"http://localhost:2375\"",
+        );
+        assert!(old.contains("docker_uri"));
+        ::std::fs::write(&path, old).unwrap();
+
+        let settings = Settings::<DockerConfig>::new(path.to_str()).unwrap();
+        assert_eq!(
+            "http://localhost:2375/",
+            settings.moby_runtime().uri().as_str()
+        );
+        assert!(tmp_dir.path().join("config.yaml.migrated").exists());
+        let features: Vec<_> = settings
+            .deprecations()
+            .iter()
+            .map(Deprecation::feature)
+            .collect();
+        assert_eq!(vec!["moby_runtime.docker_uri"], features);
+
+        let current = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(current.deprecations().is_empty());
+    }
+
+    #[test]
+    fn developer_features_are_off_by_default() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.developer().is_none());
+
+        let developer: Developer = serde_json::from_str("{}").unwrap();
+        assert!(!developer.image_build());
+        assert!(!developer.attach());
+        assert!(!developer.debug_endpoints());
+    }
+
+    #[test]
+    fn module_env_keeps_case() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.module_env().is_empty());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        assert_eq!(
+            &[
+                EnvVar::new("HTTPS_PROXY", "http://proxy:3128"),
+                EnvVar::new("NO_PROXY", "localhost"),
+            ],
+            settings.module_env()
+        );
+    }
+
+    #[test]
+    fn standby_defaults() {
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS)).unwrap();
+        assert!(settings.standby().is_none());
+
+        let settings = Settings::<DockerConfig>::new(Some(GOOD_SETTINGS1)).unwrap();
+        let standby = settings.standby().unwrap();
+        assert_eq!(StandbyRole::Passive, standby.role());
+        assert_eq!(StandbyIdentity::Shared, standby.identity());
+        assert_eq!(
+            "192.168.1.10:15580".parse::<SocketAddr>().unwrap(),
+            standby.peer()
+        );
+        assert_eq!(
+            "0.0.0.0:15580".parse::<SocketAddr>().unwrap(),
+            standby.listen()
+        );
+        assert_eq!(Duration::from_secs(2), standby.heartbeat_interval());
+        assert_eq!(Duration::from_secs(30), standby.failover_timeout());
+
+        // a passive device doesn't fail over before it missed a few heartbeats
+        let standby: Standby = serde_json::from_str(
+            r#"{"role":"primary","identity":"swap","peer":"[fe80::1]:15580",
+                "heartbeat_interval_secs":20,"failover_timeout_secs":10}"#,
+        ).unwrap();
+        assert_eq!(Duration::from_secs(60), standby.failover_timeout());
+    }
+
     #[test]
     fn network_default() {
         let moby1 = MobyRuntime {
             uri: Url::parse("http://test").unwrap(),
             network: "".to_string(),
+            isolation: None,
         };
         assert_eq!(DEFAULT_NETWORKID, moby1.network());
 
         let moby2 = MobyRuntime {
             uri: Url::parse("http://test").unwrap(),
             network: "some-network".to_string(),
+            isolation: None,
         };
         assert_eq!("some-network", moby2.network());
     }
@@ -0,0 +1,330 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+//! Pairing of two devices as an active primary and a passive standby.
+//!
+//! The primary sends a heartbeat over UDP to the passive device for as long
+//! as its module runtime answers, listing the images of its modules. The
+//! passive device doesn't run any modules. It pulls the images it is told
+//! about, so that they are at hand, and once no heartbeat has come for the
+//! failover timeout it goes on to start the module set itself.
+//!
+//! With a `shared` identity both devices are provisioned with the same device
+//! connection string, and the passive device doesn't connect to IoT Hub
+//! until it fails over, since only one of them may use the identity at a
+//! time. With `swap` each device has an identity of its own, which the
+//! passive device provisions while it stands by, and the deployment has to
+//! target both of them.
+
+use std::collections::HashSet;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket as StdUdpSocket};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use docker::models::ContainerCreateBody;
+use edgelet_core::{CancellationToken, Module, ModuleRegistry, ModuleRuntime};
+use edgelet_docker::{DockerConfig, DockerModuleRuntime};
+use edgelet_utils::log_failure;
+use failure::{Fail, ResultExt};
+use futures::future::{self, Loop};
+use futures::{Future, Stream};
+use log::Level;
+use serde_json;
+use tokio;
+use tokio::net::UdpSocket;
+use tokio::timer::Interval;
+
+use error::{Error, ErrorKind};
+use settings::{Standby, StandbyIdentity, StandbyRole};
+
+/// Large enough for the heartbeat of a device running a few hundred modules.
+const MAX_HEARTBEAT_SIZE: usize = 9000;
+
+/// This is how often the passive device checks when the last heartbeat came.
+const FAILOVER_CHECK_FREQUENCY_SECS: u64 = 1;
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Heartbeat {
+    images: Vec<String>,
+}
+
+/// Holds a passive device back until the primary fails, removing the modules
+/// it may have started when it failed over before. Returns whether the
+/// device is to go on and start the module set, which it isn't if the daemon
+/// is shut down first.
+///
+/// The daemon stands by at the point that suits the identity it pairs with:
+/// before provisioning when it shares the identity of the primary, after it
+/// when it has an identity of its own. Anything other than a passive device
+/// standing by with `identity` goes on right away.
+pub fn stand_by(
+    standby: Option<&Standby>,
+    identity: StandbyIdentity,
+    agent: &DockerConfig,
+    runtime: &DockerModuleRuntime,
+    cancellation: &CancellationToken,
+    tokio_runtime: &mut tokio::runtime::Runtime,
+) -> Result<bool, Error> {
+    let standby = match standby {
+        Some(standby)
+            if standby.role() == StandbyRole::Passive && standby.identity() == identity =>
+        {
+            standby
+        }
+        _ => return Ok(true),
+    };
+
+    info!("Removing the modules of the device while it stands by...");
+    tokio_runtime.block_on(runtime.remove_all())?;
+
+    info!(
+        "Standing by for the primary device at {}, listening for its heartbeat on {}...",
+        standby.peer(),
+        standby.listen()
+    );
+    let failover = wait_for_failover(standby, agent.clone(), runtime.clone());
+    let shutdown = cancellation.cancelled().then(|_| Ok(false));
+    let failed_over = tokio_runtime.block_on(
+        failover
+            .map(|()| true)
+            .select(shutdown)
+            .map(|(failed_over, _)| failed_over)
+            .map_err(|(err, _)| err),
+    )?;
+
+    if failed_over {
+        warn!(
+            "No heartbeat came from the primary device at {} for {} seconds. \
+             Failing over to this device...",
+            standby.peer(),
+            standby.failover_timeout().as_secs()
+        );
+    }
+    Ok(failed_over)
+}
+
+/// Sends the heartbeat of a primary device to its passive peer until
+/// `shutdown` fires. A heartbeat is only sent when the module runtime lists
+/// the modules, so that a runtime that stopped answering fails the device
+/// over as well.
+pub fn send_heartbeats<F>(
+    standby: &Standby,
+    runtime: &DockerModuleRuntime,
+    shutdown: F,
+) -> Result<impl Future<Item = (), Error = ()> + Send, Error>
+where
+    F: Future<Item = (), Error = ()> + Send + 'static,
+{
+    let peer = standby.peer();
+    let socket = bind_sender(peer).context(ErrorKind::Standby)?;
+    info!(
+        "Sending the heartbeat of this device to its passive peer at {}...",
+        peer
+    );
+
+    let runtime = runtime.clone();
+    let heartbeats = Interval::new(Instant::now(), standby.heartbeat_interval())
+        .map_err(|err| {
+            warn!(
+                "Stopped sending the heartbeat to the passive device: {}",
+                err
+            )
+        })
+        .for_each(move |_| {
+            let socket = socket.try_clone();
+            runtime.list().then(move |modules| {
+                match modules {
+                    Ok(modules) => {
+                        let heartbeat = Heartbeat {
+                            images: modules
+                                .iter()
+                                .map(|module| module.config().image().to_string())
+                                .collect(),
+                        };
+                        let sent = socket
+                            .and_then(|socket| send_heartbeat(&socket, peer, &heartbeat));
+                        if let Err(err) = sent {
+                            debug!(
+                                "Could not send the heartbeat to the passive device: {}",
+                                err
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        debug!("Not sending a heartbeat, since the modules could not be listed:");
+                        log_failure(Level::Debug, &err);
+                    }
+                }
+                Ok(())
+            })
+        });
+
+    Ok(heartbeats.select(shutdown).then(|_| Ok(())))
+}
+
+fn wait_for_failover(
+    standby: &Standby,
+    agent: DockerConfig,
+    runtime: DockerModuleRuntime,
+) -> impl Future<Item = (), Error = Error> {
+    let listen = standby.listen();
+    let peer = standby.peer();
+    let timeout = standby.failover_timeout();
+    let last_heartbeat = Arc::new(Mutex::new(Instant::now()));
+    let pulled = Arc::new(Mutex::new(HashSet::new()));
+
+    let heartbeats = {
+        let last_heartbeat = last_heartbeat.clone();
+        future::lazy(move || {
+            // The image of the edge runtime module is at hand before the
+            // primary says anything, with the credentials it is pulled with.
+            pre_pull(&runtime, &pulled, agent);
+            UdpSocket::bind(&listen).map(|socket| (socket, runtime, pulled))
+        }).and_then(move |(socket, runtime, pulled)| {
+            future::loop_fn(socket, move |socket| {
+                let last_heartbeat = last_heartbeat.clone();
+                let runtime = runtime.clone();
+                let pulled = pulled.clone();
+                socket
+                    .recv_dgram(vec![0; MAX_HEARTBEAT_SIZE])
+                    .map(move |(socket, buf, len, from)| {
+                        if let Some(heartbeat) = read_heartbeat(&buf[..len], from, peer) {
+                            *last_heartbeat
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner) = Instant::now();
+                            for image in heartbeat.images {
+                                pre_pull_image(&runtime, &pulled, &image);
+                            }
+                        }
+                        Loop::<(), _>::Continue(socket)
+                    })
+            })
+        }).map_err(|err| Error::from(err.context(ErrorKind::Standby)))
+    };
+
+    let silence = Interval::new(
+        Instant::now(),
+        Duration::from_secs(FAILOVER_CHECK_FREQUENCY_SECS),
+    ).map_err(|err| Error::from(err.context(ErrorKind::Standby)))
+    .take_while(move |_| {
+        let last_heartbeat = *last_heartbeat
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(last_heartbeat.elapsed() < timeout)
+    }).for_each(|_| Ok(()));
+
+    heartbeats.select(silence).map(|_| ()).map_err(|(err, _)| err)
+}
+
+fn pre_pull_image(runtime: &DockerModuleRuntime, pulled: &Mutex<HashSet<String>>, image: &str) {
+    match DockerConfig::new(image, ContainerCreateBody::new(), None) {
+        Ok(config) => pre_pull(runtime, pulled, config),
+        Err(err) => {
+            debug!("Not pulling image {}:", image);
+            log_failure(Level::Debug, &err);
+        }
+    }
+}
+
+// Each image is pulled once while the device stands by. Pulls run on their
+// own, so that heartbeats keep being read while they are in flight.
+fn pre_pull(runtime: &DockerModuleRuntime, pulled: &Mutex<HashSet<String>>, config: DockerConfig) {
+    let image = config.image().to_string();
+    if !pulled
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(image.clone())
+    {
+        return;
+    }
+
+    info!("Pulling image {} while standing by...", image);
+    tokio::spawn(ModuleRegistry::pull(runtime, &config).then(move |result| {
+        match result {
+            Ok(()) => info!("Pulled image {}.", image),
+            Err(err) => {
+                warn!("Could not pull image {} while standing by:", image);
+                log_failure(Level::Warn, &err);
+            }
+        }
+        Ok(())
+    }));
+}
+
+fn bind_sender(peer: SocketAddr) -> io::Result<StdUdpSocket> {
+    if peer.is_ipv4() {
+        StdUdpSocket::bind((Ipv4Addr::new(0, 0, 0, 0), 0))
+    } else {
+        StdUdpSocket::bind((Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0))
+    }
+}
+
+fn send_heartbeat(
+    socket: &StdUdpSocket,
+    peer: SocketAddr,
+    heartbeat: &Heartbeat,
+) -> io::Result<()> {
+    let heartbeat = serde_json::to_vec(heartbeat)?;
+    socket.send_to(&heartbeat, peer).map(|_| ())
+}
+
+// Heartbeats only count when they come from the address of the primary. The
+// primary sends them from any port.
+fn read_heartbeat(buf: &[u8], from: SocketAddr, peer: SocketAddr) -> Option<Heartbeat> {
+    if from.ip() != peer.ip() {
+        debug!("Ignoring a heartbeat from {}, which is not the primary device", from);
+        return None;
+    }
+    serde_json::from_slice(buf)
+        .map_err(|err| debug!("Ignoring an invalid heartbeat from {}: {}", from, err))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn heartbeats_are_only_read_from_the_primary() {
+        let heartbeat = Heartbeat {
+            images: vec!["mcr.microsoft.com/azureiotedge-agent:1.0".to_string()],
+        };
+        let buf = serde_json::to_vec(&heartbeat).unwrap();
+        assert_eq!(
+            r#"{"images":["mcr.microsoft.com/azureiotedge-agent:1.0"]}"#,
+            String::from_utf8(buf.clone()).unwrap()
+        );
+
+        let peer = addr("192.168.1.10:15580");
+        assert_eq!(
+            Some(heartbeat),
+            read_heartbeat(&buf, addr("192.168.1.10:49152"), peer)
+        );
+        assert_eq!(None, read_heartbeat(&buf, addr("192.168.1.11:15580"), peer));
+        assert_eq!(
+            None,
+            read_heartbeat(b"{\"images\":", addr("192.168.1.10:49152"), peer)
+        );
+    }
+
+    #[test]
+    fn heartbeats_reach_the_peer() {
+        let receiver = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = receiver.local_addr().unwrap();
+        let heartbeat = Heartbeat {
+            images: vec!["edgehub:1.0".to_string()],
+        };
+
+        let sender = bind_sender(peer).unwrap();
+        send_heartbeat(&sender, peer, &heartbeat).unwrap();
+
+        let mut buf = vec![0; MAX_HEARTBEAT_SIZE];
+        let (len, from) = receiver.recv_from(&mut buf).unwrap();
+        assert_eq!(Some(heartbeat), read_heartbeat(&buf[..len], from, peer));
+    }
+}
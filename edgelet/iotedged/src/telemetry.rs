@@ -0,0 +1,202 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use edgelet_utils::log_failure;
+use failure::Fail;
+use futures::future::{self, Either, Loop};
+use futures::sync::oneshot::Receiver;
+use futures::{Future, Stream};
+use log::Level;
+use tokio::timer::Interval;
+
+use edgelet_core::Error as CoreError;
+use edgelet_core::{ModuleEventForwarder, ModuleRuntime, Notifier, OutboundMessage, Outbox};
+use edgelet_http::client::{ClientImpl, TokenSource};
+use edgelet_http::error::Error as HttpError;
+use iothubservice::DeviceClient;
+
+use error::{Error, ErrorKind};
+use settings::Telemetry;
+
+/// This is how often the queued messages are sent to IoT Hub.
+const TELEMETRY_FREQUENCY_SECS: u64 = 30;
+
+/// Creates the queue for the messages the daemon sends to IoT Hub. When
+/// telemetry is off the queue discards every message.
+pub fn load_outbox<P>(telemetry: Option<&Telemetry>, path: P) -> Result<Outbox, Error>
+where
+    P: AsRef<Path>,
+{
+    match telemetry {
+        Some(telemetry) if telemetry.enabled() => {
+            let outbox = Outbox::load(path, telemetry.max_messages())?;
+            Ok(outbox.with_retention(telemetry.retention()))
+        }
+        _ => Ok(Outbox::default()),
+    }
+}
+
+/// Sends the queued messages to IoT Hub in batches until `shutdown` fires.
+/// A batch stops at the first message that can't be sent, which is left
+/// queued for the next attempt.
+pub fn start_telemetry<C, T>(
+    telemetry: Option<&Telemetry>,
+    outbox: Outbox,
+    client: DeviceClient<C, T>,
+    shutdown: Receiver<()>,
+) -> impl Future<Item = (), Error = Error>
+where
+    C: 'static + ClientImpl,
+    T: 'static + TokenSource + Clone,
+    T::Error: Into<HttpError>,
+{
+    let batch_size = match telemetry {
+        Some(telemetry) if telemetry.enabled() => telemetry.batch_size(),
+        _ => return Either::B(future::ok(())),
+    };
+
+    info!(
+        "Sending daemon telemetry to IoT Hub in batches of up to {} messages...",
+        batch_size
+    );
+    let send = Interval::new(
+        Instant::now(),
+        Duration::from_secs(TELEMETRY_FREQUENCY_SECS),
+    ).map_err(|err| Error::from(err.context(ErrorKind::Io)))
+    .for_each(move |_| {
+        let outbox = outbox.clone();
+        send_batch(&client, outbox.peek(batch_size)).map(move |sent| {
+            if let Err(err) = outbox.remove(&sent) {
+                warn!("Could not remove sent messages from the telemetry queue:");
+                log_failure(Level::Warn, &err);
+            }
+        })
+    });
+
+    // Swallow any errors from the shutdown signal
+    let shutdown = shutdown.then(|_| Ok(()));
+
+    Either::A(shutdown.select(send).then(|result| match result {
+        Ok(((), _)) => Ok(()),
+        Err((e, _)) => Err(e),
+    }))
+}
+
+/// Forwards the lifecycle and health events of the modules in `runtime`
+/// through `outbox` until `shutdown` fires, when telemetry and module events
+/// are both enabled. Modules in a crash loop are also reported to `notifier`,
+/// so the events are watched for whenever it has somewhere to send them.
+pub fn start_module_events<M>(
+    telemetry: Option<&Telemetry>,
+    notifier: Notifier,
+    runtime: M,
+    outbox: Outbox,
+    shutdown: Receiver<()>,
+) -> impl Future<Item = (), Error = Error>
+where
+    M: 'static + ModuleRuntime + Clone,
+    M::Error: Into<CoreError>,
+{
+    let events = match telemetry {
+        Some(telemetry) if telemetry.enabled() => telemetry.module_events(),
+        Some(_) | None => None,
+    }.filter(|events| events.enabled());
+    if events.is_none() && notifier.is_empty() {
+        return Either::B(future::ok(()));
+    }
+
+    let mut forwarder = ModuleEventForwarder::new().with_notifier(notifier);
+    if let Some(events) = events {
+        forwarder = forwarder.with_outbox(outbox);
+        if let Some(interval) = events.poll_interval() {
+            forwarder = forwarder.with_interval(interval);
+        }
+        if let Some(max_events) = events.max_events_per_minute() {
+            forwarder = forwarder.with_max_events_per_minute(max_events);
+        }
+        if let Some(batch_size) = events.batch_size() {
+            forwarder = forwarder.with_batch_size(batch_size);
+        }
+        if let Some(selector) = events.label_selector() {
+            match selector.parse() {
+                Ok(selector) => forwarder = forwarder.with_label_selector(selector),
+                Err(err) => return Either::B(future::err(Error::from(err))),
+            }
+        }
+    }
+
+    Either::A(
+        forwarder
+            .run_until(runtime, shutdown.map_err(|_| ()))
+            .map_err(Error::from),
+    )
+}
+
+// Sends the messages in order and returns the ones that were sent.
+fn send_batch<C, T>(
+    client: &DeviceClient<C, T>,
+    batch: Vec<OutboundMessage>,
+) -> impl Future<Item = Vec<OutboundMessage>, Error = Error>
+where
+    C: 'static + ClientImpl,
+    T: 'static + TokenSource + Clone,
+    T::Error: Into<HttpError>,
+{
+    let client = client.clone();
+    future::loop_fn(
+        (batch.into_iter(), Vec::new()),
+        move |(mut batch, mut sent)| match batch.next() {
+            None => Either::A(future::ok(Loop::Break(sent))),
+            Some(message) => Either::B(client.send_event(message.clone()).then(move |result| {
+                match result {
+                    Ok(()) => {
+                        sent.push(message);
+                        Ok(Loop::Continue((batch, sent)))
+                    }
+                    Err(err) => {
+                        debug!("Could not send daemon telemetry to IoT Hub: {}", err);
+                        Ok(Loop::Break(sent))
+                    }
+                }
+            })),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use edgelet_core::MessageKind;
+    use serde_json::{self, Value};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn disabled_telemetry_discards_messages() {
+        let tmp_dir = TempDir::new("telemetry").unwrap();
+        let path = tmp_dir.path().join("outbox.json");
+        let telemetry: Telemetry = serde_json::from_str(r#"{"enabled": false}"#).unwrap();
+
+        for telemetry in &[None, Some(&telemetry)] {
+            let outbox = load_outbox(*telemetry, &path).unwrap();
+            outbox.push(MessageKind::CheckResult, &Value::Null).unwrap();
+            assert!(outbox.is_empty());
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn enabled_telemetry_queues_messages_on_disk() {
+        let tmp_dir = TempDir::new("telemetry").unwrap();
+        let path = tmp_dir.path().join("outbox.json");
+        let telemetry: Telemetry =
+            serde_json::from_str(r#"{"enabled": true, "max_messages": 2}"#).unwrap();
+
+        let outbox = load_outbox(Some(&telemetry), &path).unwrap();
+        assert_eq!(2, outbox.capacity());
+        outbox.push(MessageKind::SecurityEvent, &Value::Null).unwrap();
+        assert_eq!(1, load_outbox(Some(&telemetry), &path).unwrap().len());
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use edgelet_core::crypto::{Certificate, GetTrustBundle};
+use edgelet_core::{CaRotation, CancellationToken, TrustBundleFiles};
+use edgelet_utils::log_failure;
+use futures::{Future, Stream};
+use log::Level;
+use tokio::timer::Interval;
+
+use error::Error;
+
+/// This is the name of the directory under the home directory the trust
+/// bundle of each module is kept in.
+const TRUST_BUNDLE_FILES_DIR: &str = "trust_bundles";
+
+/// Writes the current trust bundle for the modules, including the ones
+/// created before the daemon started.
+pub fn prepare_trust_bundle_files<C>(
+    homedir: &Path,
+    crypto: &C,
+    rotation: &CaRotation,
+) -> Result<TrustBundleFiles, Error>
+where
+    C: GetTrustBundle,
+{
+    let files = TrustBundleFiles::new(homedir.join(TRUST_BUNDLE_FILES_DIR));
+    files.update(&trust_bundle(crypto, rotation)?)?;
+    Ok(files)
+}
+
+/// Writes the trust bundle for the modules again every `interval` if it
+/// changed, such as when the overlap of a rotation of the Edge CA ended.
+pub fn watch_trust_bundle_files<C>(
+    files: TrustBundleFiles,
+    crypto: C,
+    rotation: CaRotation,
+    interval: Duration,
+    cancellation: CancellationToken,
+) -> impl Future<Item = (), Error = ()> + Send
+where
+    C: 'static + GetTrustBundle + Send,
+{
+    let refresh = Interval::new(Instant::now() + interval, interval)
+        .map_err(|err| warn!("Could not schedule the trust bundle files update: {}", err))
+        .for_each(move |_| {
+            let updated = trust_bundle(&crypto, &rotation)
+                .and_then(|bundle| files.update(&bundle).map_err(Error::from));
+            match updated {
+                Ok(true) => info!("Updated the trust bundle files of the modules."),
+                Ok(false) => (),
+                Err(err) => {
+                    warn!("Could not update the trust bundle files of the modules.");
+                    log_failure(Level::Warn, &err);
+                }
+            }
+            Ok(())
+        });
+
+    refresh.select(cancellation.cancelled()).then(|_| Ok(()))
+}
+
+// The trust bundle the workload API serves, which has the roots trusted
+// before a rotation of the Edge CA as well until the overlap ends.
+fn trust_bundle<C: GetTrustBundle>(crypto: &C, rotation: &CaRotation) -> Result<String, Error> {
+    let current = crypto.get_trust_bundle()?.pem()?;
+    let current = String::from_utf8_lossy(current.as_ref());
+    Ok(rotation.trust_bundle(&current)?)
+}
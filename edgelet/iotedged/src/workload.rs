@@ -1,14 +1,21 @@
 // Copyright (c) Microsoft. All rights reserved.
 
-use edgelet_core::{CertificateType, WorkloadConfig};
-use std::sync::Arc;
+use edgelet_core::{CertPolicy, CertificateType, KeyType, WorkloadConfig};
+use std::sync::{Arc, Mutex, PoisonError};
 
-#[derive(Debug, Clone)]
+use settings::CertPolicies;
+
+#[derive(Clone, Copy, Debug)]
+struct CertMaxDurations {
+    id_cert: i64,
+    server_cert: i64,
+}
+
+#[derive(Debug)]
 struct WorkloadConfigData {
     iot_hub_name: String,
     device_id: String,
-    id_cert_max_duration: i64,
-    srv_cert_max_duration: i64,
+    max_durations: Mutex<CertMaxDurations>,
 }
 
 impl WorkloadConfigData {
@@ -21,8 +28,10 @@ impl WorkloadConfigData {
         WorkloadConfigData {
             iot_hub_name,
             device_id,
-            id_cert_max_duration,
-            srv_cert_max_duration,
+            max_durations: Mutex::new(CertMaxDurations {
+                id_cert: id_cert_max_duration,
+                server_cert: srv_cert_max_duration,
+            }),
         }
     }
 
@@ -34,18 +43,38 @@ impl WorkloadConfigData {
         &self.device_id
     }
 
+    fn max_durations(&self) -> CertMaxDurations {
+        *self
+            .max_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
     pub fn id_cert_max(&self) -> i64 {
-        self.id_cert_max_duration
+        self.max_durations().id_cert
     }
 
     pub fn server_cert_max(&self) -> i64 {
-        self.srv_cert_max_duration
+        self.max_durations().server_cert
+    }
+
+    pub fn set_cert_max(&self, id_cert_max_duration: i64, srv_cert_max_duration: i64) {
+        *self
+            .max_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = CertMaxDurations {
+            id_cert: id_cert_max_duration,
+            server_cert: srv_cert_max_duration,
+        };
     }
 }
 
+/// The certificate durations are kept behind a lock, so that `set_cert_max`
+/// changes them for the workload API that was handed the data too.
 #[derive(Debug, Clone)]
 pub struct WorkloadData {
     data: Arc<WorkloadConfigData>,
+    policies: CertPolicies,
 }
 
 impl WorkloadData {
@@ -61,7 +90,24 @@ impl WorkloadData {
             id_cert_max_duration,
             srv_cert_max_duration,
         );
-        WorkloadData { data: Arc::new(w) }
+        WorkloadData {
+            data: Arc::new(w),
+            policies: CertPolicies::default(),
+        }
+    }
+
+    /// Issues the certificates of modules under `policies`, whose longest
+    /// validity is capped at the durations of the daemon.
+    pub fn with_cert_policies(mut self, policies: CertPolicies) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    /// Changes the longest validity, in seconds, of the certificates issued
+    /// from now on.
+    pub fn set_cert_max_durations(&self, id_cert_max_duration: i64, srv_cert_max_duration: i64) {
+        self.data
+            .set_cert_max(id_cert_max_duration, srv_cert_max_duration);
     }
 }
 
@@ -81,4 +127,20 @@ impl WorkloadConfig for WorkloadData {
             _ => 0,
         }
     }
+
+    fn get_cert_policy(&self, cert_type: CertificateType) -> CertPolicy {
+        let policy = match cert_type {
+            CertificateType::Client => self.policies.identity().clone(),
+            CertificateType::Server => self.policies.server().clone(),
+            _ => CertPolicy::default(),
+        };
+        policy.capped(self.get_cert_max_duration(cert_type))
+    }
+
+    fn get_default_key_type(&self, cert_type: CertificateType) -> Option<KeyType> {
+        match cert_type {
+            CertificateType::Server => self.policies.server_key_type(),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,259 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+
+use futures::future::{self, Either};
+use futures::Future;
+use hyper::{Method, StatusCode};
+
+use edgelet_http::client::{Client, ClientImpl, TokenSource};
+use edgelet_http::error::{Error as HttpError, ErrorKind as HttpErrorKind};
+use error::{Error, ErrorKind};
+use model::Configuration;
+
+/// Reads the automatic device management configurations of an IoT hub. IoT
+/// Edge deployments are configurations too.
+pub struct ConfigurationClient<C, T>
+where
+    C: ClientImpl,
+    T: TokenSource + Clone,
+{
+    client: Client<C, T>,
+}
+
+impl<C, T> ConfigurationClient<C, T>
+where
+    C: ClientImpl,
+    T: 'static + TokenSource + Clone,
+    T::Error: Into<HttpError>,
+{
+    pub fn new(client: Client<C, T>) -> Self {
+        ConfigurationClient { client }
+    }
+
+    /// Lists the configurations of the hub, at most `top` of them if given.
+    pub fn list_configurations(
+        &self,
+        top: Option<u32>,
+    ) -> impl Future<Item = Vec<Configuration>, Error = Error> {
+        let top = top.map(|top| top.to_string());
+        let query = top.as_ref().map(|top| {
+            let mut query = HashMap::new();
+            query.insert("top", top.as_str());
+            query
+        });
+
+        self.client
+            .request::<(), Vec<Configuration>>(Method::GET, "/configurations", query, None, false)
+            .map_err(Error::from)
+            .and_then(|configs| configs.ok_or_else(|| Error::from(ErrorKind::EmptyResponse)))
+    }
+
+    pub fn get_configuration(&self, id: &str) -> impl Future<Item = Configuration, Error = Error> {
+        if id.trim().is_empty() {
+            Either::B(future::err(Error::from(ErrorKind::EmptyConfigurationId)))
+        } else {
+            let res = self
+                .client
+                .request::<(), Configuration>(
+                    Method::GET,
+                    &format!("/configurations/{}", id),
+                    None,
+                    None,
+                    false,
+                ).map_err(|err| {
+                    if let HttpErrorKind::ServiceError(code, _) = err.kind() {
+                        if *code == StatusCode::NOT_FOUND {
+                            return Error::from(ErrorKind::ConfigurationNotFound);
+                        }
+                    }
+
+                    Error::from(err)
+                }).and_then(|config| {
+                    config.ok_or_else(|| Error::from(ErrorKind::ConfigurationNotFound))
+                });
+
+            Either::A(res)
+        }
+    }
+}
+
+impl<C, T> Clone for ConfigurationClient<C, T>
+where
+    C: ClientImpl,
+    T: TokenSource + Clone,
+{
+    fn clone(&self) -> Self {
+        ConfigurationClient {
+            client: self.client.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{DateTime, Utc};
+    use hyper::{Body, Client as HyperClient, Request, Response};
+    use serde_json;
+    use tokio;
+    use typed_headers::{mime, ContentType, HeaderMapExt};
+    use url::Url;
+
+    use model::ConfigurationMetrics;
+
+    struct NullTokenSource;
+
+    impl TokenSource for NullTokenSource {
+        type Error = Error;
+        fn get(&self, _expiry: &DateTime<Utc>) -> Result<String, Error> {
+            Ok("token".to_string())
+        }
+    }
+
+    impl Clone for NullTokenSource {
+        fn clone(&self) -> Self {
+            NullTokenSource {}
+        }
+    }
+
+    fn json_response(body: String) -> Response<Body> {
+        let mut response = Response::new(body.into());
+        response
+            .headers_mut()
+            .typed_insert(&ContentType(mime::APPLICATION_JSON));
+        response
+    }
+
+    #[test]
+    fn configurations_list_request() {
+        let api_version = "2018-06-30";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let configs = vec![
+            Configuration::new("deployment1".to_string())
+                .with_target_condition("tags.environment='test'".to_string())
+                .with_priority(10)
+                .with_system_metrics(
+                    ConfigurationMetrics::new()
+                        .with_result("targetedCount".to_string(), 12)
+                        .with_result("appliedCount".to_string(), 10),
+                ),
+            Configuration::new("deployment2".to_string()),
+        ];
+        let expected_configs = configs.clone();
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::GET);
+            assert_eq!(req.uri().path(), "/configurations");
+            assert!(req.uri().query().unwrap().contains("top=5"));
+
+            Ok(json_response(serde_json::to_string(&configs).unwrap()))
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let config_client = ConfigurationClient::new(client);
+        let task = config_client
+            .list_configurations(Some(5))
+            .then(|configs| {
+                let configs = configs.unwrap();
+                assert_eq!(expected_configs, configs);
+                assert_eq!(
+                    Some(&10),
+                    configs[0]
+                        .system_metrics()
+                        .unwrap()
+                        .results()
+                        .get("appliedCount")
+                );
+                Ok::<_, Error>(())
+            });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn configuration_get_request() {
+        let api_version = "2018-06-30";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let config = Configuration::new("deployment1".to_string())
+            .with_label("team".to_string(), "edge".to_string());
+        let expected_config = config.clone();
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::GET);
+            assert_eq!(req.uri().path(), "/configurations/deployment1");
+
+            Ok(json_response(serde_json::to_string(&config).unwrap()))
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let config_client = ConfigurationClient::new(client);
+        let task = config_client
+            .get_configuration("deployment1")
+            .then(|config| {
+                assert_eq!(expected_config, config.unwrap());
+                Ok::<_, Error>(())
+            });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn configuration_get_not_found() {
+        let api_version = "2018-06-30";
+        let host_name = Url::parse("http://localhost").unwrap();
+
+        let handler = move |_req: Request<Body>| {
+            let response = Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .expect("could not build hyper::Response");
+            Ok(response)
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let config_client = ConfigurationClient::new(client);
+        let task = config_client
+            .get_configuration("deployment1")
+            .then(|config| {
+                assert_eq!(
+                    ErrorKind::ConfigurationNotFound,
+                    *config.unwrap_err().kind()
+                );
+                Ok::<_, Error>(())
+            });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn configuration_get_empty_id_fails() {
+        let client = Client::new(
+            HyperClient::new(),
+            Some(NullTokenSource),
+            "2018-06-30",
+            Url::parse("http://localhost").unwrap(),
+        ).unwrap();
+
+        let config_client = ConfigurationClient::new(client);
+        let task = config_client.get_configuration("   ").then(|config| {
+            assert_eq!(ErrorKind::EmptyConfigurationId, *config.unwrap_err().kind());
+            Ok::<_, Error>(())
+        });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+}
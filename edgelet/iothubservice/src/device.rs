@@ -3,11 +3,33 @@
 use futures::future::{self, Either};
 use futures::Future;
 use hyper::{Method, StatusCode};
+use serde::Serialize;
+use serde_json::{Map, Value};
 
 use edgelet_http::client::{Client, ClientImpl, TokenSource};
 use edgelet_http::error::{Error as HttpError, ErrorKind as HttpErrorKind};
 use error::{Error, ErrorKind};
-use model::{AuthMechanism, Module};
+use model::{AuthMechanism, Module, Twin};
+
+/// The module of an edge device that leaf devices and their modules are in
+/// the scope of.
+const EDGE_HUB_MODULE_ID: &str = "$edgeHub";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopeRequest {
+    target_device_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_module_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScopeResult {
+    #[serde(default)]
+    devices: Vec<Module>,
+    #[serde(default)]
+    modules: Vec<Module>,
+}
 
 pub struct DeviceClient<C, T>
 where
@@ -128,6 +150,97 @@ where
             .and_then(|modules| modules.ok_or_else(|| Error::from(ErrorKind::EmptyResponse)))
     }
 
+    /// Gets the twin of the device, which includes the automatic device
+    /// management configurations targeting it.
+    pub fn get_twin(&self) -> impl Future<Item = Twin, Error = Error> {
+        self.client
+            .request::<(), Twin>(
+                Method::GET,
+                &format!("/twins/{}", &self.device_id),
+                None,
+                None,
+                false,
+            ).map_err(Error::from)
+            .and_then(|twin| twin.ok_or_else(|| Error::from(ErrorKind::EmptyResponse)))
+    }
+
+    /// Merges `reported` into the reported properties of the device twin.
+    /// Properties set to null are removed.
+    pub fn update_reported_properties(
+        &self,
+        reported: Value,
+    ) -> impl Future<Item = Twin, Error = Error> {
+        let mut properties = Map::new();
+        properties.insert("reported".to_string(), reported);
+        let mut patch = Map::new();
+        patch.insert("properties".to_string(), Value::Object(properties));
+
+        self.client
+            .request::<Value, Twin>(
+                Method::PATCH,
+                &format!("/twins/{}", &self.device_id),
+                None,
+                Some(Value::Object(patch)),
+                false,
+            ).map_err(Error::from)
+            .and_then(|twin| twin.ok_or_else(|| Error::from(ErrorKind::EmptyResponse)))
+    }
+
+    /// Sends a device to cloud message with a JSON body.
+    pub fn send_event<B>(&self, body: B) -> impl Future<Item = (), Error = Error>
+    where
+        B: Serialize,
+    {
+        self.client
+            .request::<B, ()>(
+                Method::POST,
+                &format!("/devices/{}/messages/events", &self.device_id),
+                None,
+                Some(body),
+                false,
+            ).map_err(Error::from)
+            .and_then(|_| Ok(()))
+    }
+
+    /// Gets a device in the scope of this edge device, or one of its modules,
+    /// with its keys. A device is returned as a module without a module ID.
+    pub fn get_identity_in_scope(
+        &self,
+        device_id: &str,
+        module_id: Option<&str>,
+    ) -> impl Future<Item = Option<Module>, Error = Error> {
+        let request = ScopeRequest {
+            target_device_id: device_id.to_string(),
+            target_module_id: module_id.map(ToString::to_string),
+        };
+        let is_module = module_id.is_some();
+        self.client
+            .request::<ScopeRequest, ScopeResult>(
+                Method::POST,
+                &format!(
+                    "/devices/{}/modules/{}/deviceAndModuleInDeviceScope",
+                    self.device_id, EDGE_HUB_MODULE_ID
+                ),
+                None,
+                Some(request),
+                false,
+            ).then(move |result| match result {
+                Ok(scope) => Ok(scope.and_then(|mut scope| {
+                    if is_module {
+                        scope.modules.pop()
+                    } else {
+                        scope.devices.pop()
+                    }
+                })),
+                Err(err) => match *err.kind() {
+                    HttpErrorKind::ServiceError(code, _) if code == StatusCode::NOT_FOUND => {
+                        Ok(None)
+                    }
+                    _ => Err(Error::from(err)),
+                },
+            })
+    }
+
     pub fn delete_module(&self, module_id: &str) -> impl Future<Item = (), Error = Error> {
         if module_id.trim().is_empty() {
             Either::B(future::err(Error::from(ErrorKind::EmptyModuleId)))
@@ -169,13 +282,13 @@ mod tests {
     use chrono::{DateTime, Utc};
     use futures::Stream;
     use hyper::{self, Body, Client as HyperClient, Method, Request, Response};
-    use serde_json;
+    use serde_json::{self, Value};
     use tokio;
     use typed_headers::{mime, ContentType, HeaderMapExt};
     use url::Url;
 
     use error::ErrorKind;
-    use model::{AuthType, SymmetricKey};
+    use model::{AuthType, ConfigurationStatus, Properties, SymmetricKey, TwinConfiguration};
 
     struct NullTokenSource;
 
@@ -591,6 +704,179 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn identity_in_scope_request() {
+        let api_version = "2018-04-10";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let key = SymmetricKey::default().with_primary_key("pkey".to_string());
+        let leaf = Module::default()
+            .with_device_id("leaf".to_string())
+            .with_authentication(
+                AuthMechanism::default()
+                    .with_type(AuthType::Sas)
+                    .with_symmetric_key(key),
+            );
+        let expected_leaf = leaf.clone();
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::POST);
+            assert_eq!(
+                req.uri().path(),
+                "/devices/d1/modules/$edgeHub/deviceAndModuleInDeviceScope"
+            );
+
+            let result = format!(
+                r#"{{"devices":[{}],"modules":[]}}"#,
+                serde_json::to_string(&leaf).unwrap()
+            );
+            let mut response = Response::new(result.into());
+            response
+                .headers_mut()
+                .typed_insert(&ContentType(mime::APPLICATION_JSON));
+            Ok(response)
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let device_client = DeviceClient::new(client, "d1").unwrap();
+        let task = device_client
+            .get_identity_in_scope("leaf", None)
+            .then(|identity| {
+                assert_eq!(Some(expected_leaf), identity.unwrap());
+                Ok::<_, Error>(())
+            });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn twin_get_request() {
+        let api_version = "2018-04-10";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let twin = Twin::new("d1", 3, AuthType::Sas, Properties::new(Value::Null))
+            .with_configuration(
+                "deployment1".to_string(),
+                TwinConfiguration::new(ConfigurationStatus::Applied),
+            ).with_configuration(
+                "deployment2".to_string(),
+                TwinConfiguration::new(ConfigurationStatus::Targeted),
+            );
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::GET);
+            assert_eq!(req.uri().path(), "/twins/d1");
+
+            let mut response = Response::new(serde_json::to_string(&twin).unwrap().into());
+            response
+                .headers_mut()
+                .typed_insert(&ContentType(mime::APPLICATION_JSON));
+            Ok(response)
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let device_client = DeviceClient::new(client, "d1").unwrap();
+        let task = device_client.get_twin().then(|twin| {
+            let twin = twin.unwrap();
+            assert_eq!("d1", twin.device_id());
+            assert_eq!(2, twin.configurations().len());
+            assert_eq!(vec!["deployment1"], twin.applied_configurations());
+            Ok::<_, Error>(())
+        });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn twin_update_reported_properties_request() {
+        let api_version = "2018-04-10";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let reported: Value = serde_json::from_str(r#"{"iotedged":{"logLevel":"warn"}}"#).unwrap();
+        let twin = Twin::new(
+            "d1",
+            4,
+            AuthType::Sas,
+            Properties::new(Value::Null).with_reported(reported.clone()),
+        );
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::PATCH);
+            assert_eq!(req.uri().path(), "/twins/d1");
+
+            let twin = serde_json::to_string(&twin).unwrap();
+            req.into_body().concat2().map(move |body| {
+                let patch: Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(
+                    "warn",
+                    patch["properties"]["reported"]["iotedged"]["logLevel"]
+                );
+                assert!(patch["properties"].get("desired").is_none());
+
+                let mut response = Response::new(twin.into());
+                response
+                    .headers_mut()
+                    .typed_insert(&ContentType(mime::APPLICATION_JSON));
+                response
+            })
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let device_client = DeviceClient::new(client, "d1").unwrap();
+        let task = device_client
+            .update_reported_properties(reported)
+            .then(|twin| {
+                let twin = twin.unwrap();
+                assert_eq!(
+                    "warn",
+                    twin.properties().reported()["iotedged"]["logLevel"]
+                );
+                Ok::<_, Error>(())
+            });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn event_send_request() {
+        let api_version = "2018-04-10";
+        let host_name = Url::parse("http://localhost").unwrap();
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::POST);
+            assert_eq!(req.uri().path(), "/devices/d1/messages/events");
+
+            req.into_body().concat2().map(|body| {
+                let event: Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!("watchdogAction", event["kind"]);
+
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())
+                    .expect("could not build hyper::Response")
+            })
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let device_client = DeviceClient::new(client, "d1").unwrap();
+        let event: Value = serde_json::from_str(r#"{"kind":"watchdogAction"}"#).unwrap();
+        let task = device_client.send_event(event).then(|result| {
+            assert!(result.is_ok());
+            Ok::<_, Error>(())
+        });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
     #[test]
     fn modules_get_not_found() {
         let api_version = "2018-04-10";
@@ -37,6 +37,14 @@ pub enum ErrorKind {
     ModuleNotFound,
     #[fail(display = "Module ID is empty")]
     EmptyModuleId,
+    #[fail(display = "Configuration not found")]
+    ConfigurationNotFound,
+    #[fail(display = "Configuration ID is empty")]
+    EmptyConfigurationId,
+    #[fail(display = "Job not found")]
+    JobNotFound,
+    #[fail(display = "Job ID is empty")]
+    EmptyJobId,
     #[fail(display = "Failed to get sas token")]
     Token,
 }
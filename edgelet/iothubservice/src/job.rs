@@ -0,0 +1,290 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::collections::HashMap;
+
+use futures::future::{self, Either};
+use futures::Future;
+use hyper::{Method, StatusCode};
+
+use edgelet_http::client::{Client, ClientImpl, TokenSource};
+use edgelet_http::error::{Error as HttpError, ErrorKind as HttpErrorKind};
+use error::{Error, ErrorKind};
+use model::{DeviceJob, Job, QuerySpecification};
+
+/// Reads the jobs scheduled on an IoT hub and their progress on each device.
+pub struct JobClient<C, T>
+where
+    C: ClientImpl,
+    T: TokenSource + Clone,
+{
+    client: Client<C, T>,
+}
+
+impl<C, T> JobClient<C, T>
+where
+    C: ClientImpl,
+    T: 'static + TokenSource + Clone,
+    T::Error: Into<HttpError>,
+{
+    pub fn new(client: Client<C, T>) -> Self {
+        JobClient { client }
+    }
+
+    pub fn get_job(&self, job_id: &str) -> impl Future<Item = Job, Error = Error> {
+        if job_id.trim().is_empty() {
+            Either::B(future::err(Error::from(ErrorKind::EmptyJobId)))
+        } else {
+            let res = self
+                .client
+                .request::<(), Job>(
+                    Method::GET,
+                    &format!("/jobs/v2/{}", job_id),
+                    None,
+                    None,
+                    false,
+                ).map_err(|err| {
+                    if let HttpErrorKind::ServiceError(code, _) = err.kind() {
+                        if *code == StatusCode::NOT_FOUND {
+                            return Error::from(ErrorKind::JobNotFound);
+                        }
+                    }
+
+                    Error::from(err)
+                }).and_then(|job| job.ok_or_else(|| Error::from(ErrorKind::JobNotFound)));
+
+            Either::A(res)
+        }
+    }
+
+    /// Lists the jobs of the hub, optionally only those of the given type
+    /// and status (e.g. "scheduleUpdateTwin" and "running").
+    pub fn list_jobs(
+        &self,
+        job_type: Option<&str>,
+        job_status: Option<&str>,
+    ) -> impl Future<Item = Vec<Job>, Error = Error> {
+        let mut query = HashMap::new();
+        if let Some(job_type) = job_type {
+            query.insert("jobType", job_type);
+        }
+        if let Some(job_status) = job_status {
+            query.insert("jobStatus", job_status);
+        }
+
+        self.client
+            .request::<(), Vec<Job>>(Method::GET, "/jobs/v2/query", Some(query), None, false)
+            .map_err(Error::from)
+            .and_then(|jobs| jobs.ok_or_else(|| Error::from(ErrorKind::EmptyResponse)))
+    }
+
+    /// Lists the state of every job that has run, or is due to run, on a
+    /// device.
+    pub fn list_device_jobs(
+        &self,
+        device_id: &str,
+    ) -> impl Future<Item = Vec<DeviceJob>, Error = Error> {
+        let query = QuerySpecification::new(format!(
+            "SELECT * FROM devices.jobs WHERE devices.jobs.deviceId = '{}'",
+            device_id.replace('\'', "''")
+        ));
+
+        self.client
+            .request::<QuerySpecification, Vec<DeviceJob>>(
+                Method::POST,
+                "/devices/query",
+                None,
+                Some(query),
+                false,
+            ).map_err(Error::from)
+            .and_then(|jobs| jobs.ok_or_else(|| Error::from(ErrorKind::EmptyResponse)))
+    }
+}
+
+impl<C, T> Clone for JobClient<C, T>
+where
+    C: ClientImpl,
+    T: TokenSource + Clone,
+{
+    fn clone(&self) -> Self {
+        JobClient {
+            client: self.client.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{DateTime, Utc};
+    use futures::Stream;
+    use hyper::{Body, Client as HyperClient, Request, Response};
+    use serde_json;
+    use tokio;
+    use typed_headers::{mime, ContentType, HeaderMapExt};
+    use url::Url;
+
+    struct NullTokenSource;
+
+    impl TokenSource for NullTokenSource {
+        type Error = Error;
+        fn get(&self, _expiry: &DateTime<Utc>) -> Result<String, Error> {
+            Ok("token".to_string())
+        }
+    }
+
+    impl Clone for NullTokenSource {
+        fn clone(&self) -> Self {
+            NullTokenSource {}
+        }
+    }
+
+    fn json_response(body: String) -> Response<Body> {
+        let mut response = Response::new(body.into());
+        response
+            .headers_mut()
+            .typed_insert(&ContentType(mime::APPLICATION_JSON));
+        response
+    }
+
+    #[test]
+    fn job_get_request() {
+        let api_version = "2018-06-30";
+        let host_name = Url::parse("http://localhost").unwrap();
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::GET);
+            assert_eq!(req.uri().path(), "/jobs/v2/job1");
+
+            Ok(json_response(
+                r#"{
+                    "jobId": "job1",
+                    "type": "scheduleUpdateTwin",
+                    "status": "running",
+                    "startTime": "2018-10-15T12:00:00Z",
+                    "deviceJobStatistics": {
+                        "deviceCount": 3,
+                        "failedCount": 1,
+                        "succeededCount": 1,
+                        "runningCount": 1,
+                        "pendingCount": 0
+                    }
+                }"#.to_string(),
+            ))
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let job_client = JobClient::new(client);
+        let task = job_client.get_job("job1").then(|job| {
+            let job = job.unwrap();
+            assert_eq!("job1", job.job_id());
+            assert_eq!(Some("scheduleUpdateTwin"), job.type_());
+            assert_eq!(Some("running"), job.status());
+            let statistics = job.device_job_statistics().unwrap();
+            assert_eq!(3, statistics.device_count());
+            assert_eq!(1, statistics.failed_count());
+            assert_eq!(0, statistics.pending_count());
+            Ok::<_, Error>(())
+        });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn job_get_empty_id_fails() {
+        let client = Client::new(
+            HyperClient::new(),
+            Some(NullTokenSource),
+            "2018-06-30",
+            Url::parse("http://localhost").unwrap(),
+        ).unwrap();
+
+        let job_client = JobClient::new(client);
+        let task = job_client.get_job("").then(|job| {
+            assert_eq!(ErrorKind::EmptyJobId, *job.unwrap_err().kind());
+            Ok::<_, Error>(())
+        });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn jobs_list_request() {
+        let api_version = "2018-06-30";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let jobs = vec![
+            Job::new("job1".to_string())
+                .with_type("scheduleDeviceMethod".to_string())
+                .with_status("completed".to_string()),
+        ];
+        let expected_jobs = jobs.clone();
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::GET);
+            assert_eq!(req.uri().path(), "/jobs/v2/query");
+            let query = req.uri().query().unwrap();
+            assert!(query.contains("jobStatus=completed"));
+            assert!(!query.contains("jobType"));
+
+            Ok(json_response(serde_json::to_string(&jobs).unwrap()))
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let job_client = JobClient::new(client);
+        let task = job_client
+            .list_jobs(None, Some("completed"))
+            .then(|jobs| {
+                assert_eq!(expected_jobs, jobs.unwrap());
+                Ok::<_, Error>(())
+            });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+
+    #[test]
+    fn device_jobs_list_request() {
+        let api_version = "2018-06-30";
+        let host_name = Url::parse("http://localhost").unwrap();
+        let jobs = vec![
+            DeviceJob::new("d'1".to_string(), "job1".to_string())
+                .with_status("completed".to_string()),
+        ];
+        let expected_jobs = jobs.clone();
+
+        let handler = move |req: Request<Body>| {
+            assert_eq!(req.method(), &Method::POST);
+            assert_eq!(req.uri().path(), "/devices/query");
+
+            let jobs = jobs.clone();
+            req.into_body().concat2().map(move |body| {
+                let query: QuerySpecification = serde_json::from_slice(&body).unwrap();
+                assert_eq!(
+                    "SELECT * FROM devices.jobs WHERE devices.jobs.deviceId = 'd''1'",
+                    query.query()
+                );
+                json_response(serde_json::to_string(&jobs).unwrap())
+            })
+        };
+        let client = Client::new(handler, Some(NullTokenSource), api_version, host_name).unwrap();
+
+        let job_client = JobClient::new(client);
+        let task = job_client.list_device_jobs("d'1").then(|jobs| {
+            assert_eq!(expected_jobs, jobs.unwrap());
+            Ok::<_, Error>(())
+        });
+
+        tokio::runtime::current_thread::Runtime::new()
+            .unwrap()
+            .block_on(task)
+            .unwrap();
+    }
+}
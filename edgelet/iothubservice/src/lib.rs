@@ -15,6 +15,7 @@ extern crate chrono;
 extern crate failure;
 extern crate futures;
 extern crate hyper;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
@@ -28,10 +29,18 @@ extern crate edgelet_http;
 #[macro_use]
 extern crate edgelet_utils;
 
+mod configuration;
 mod device;
 pub mod error;
+mod job;
 mod model;
 
+pub use configuration::ConfigurationClient;
 pub use device::DeviceClient;
 pub use error::{Error, ErrorKind};
-pub use model::{AuthMechanism, AuthType, Module, Properties, SymmetricKey, Twin, X509Thumbprint};
+pub use job::JobClient;
+pub use model::{
+    AuthMechanism, AuthType, Configuration, ConfigurationMetrics, ConfigurationStatus, DeviceJob,
+    DeviceJobStatistics, Job, Module, Properties, QuerySpecification, SymmetricKey, Twin,
+    TwinConfiguration, X509Thumbprint,
+};
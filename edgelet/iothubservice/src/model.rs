@@ -1,5 +1,6 @@
 // Copyright (c) Microsoft. All rights reserved.
 
+use std::collections::HashMap;
 use std::default::Default;
 
 use serde_json::Value;
@@ -21,6 +22,8 @@ pub struct Twin {
     version: i32,
     authentication_type: AuthType,
     properties: Properties,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    configurations: HashMap<String, TwinConfiguration>,
 }
 
 impl Twin {
@@ -36,6 +39,7 @@ impl Twin {
             version,
             authentication_type,
             properties,
+            configurations: HashMap::new(),
         }
     }
 
@@ -64,6 +68,11 @@ impl Twin {
         self
     }
 
+    pub fn with_configuration(mut self, id: String, configuration: TwinConfiguration) -> Self {
+        self.configurations.insert(id, configuration);
+        self
+    }
+
     pub fn device_id(&self) -> &str {
         &self.device_id
     }
@@ -83,21 +92,76 @@ impl Twin {
     pub fn properties(&self) -> &Properties {
         &self.properties
     }
+
+    /// The automatic device management configurations targeting this device,
+    /// keyed by configuration ID.
+    pub fn configurations(&self) -> &HashMap<String, TwinConfiguration> {
+        &self.configurations
+    }
+
+    /// The IDs of the configurations that have been applied to this device,
+    /// in alphabetical order.
+    pub fn applied_configurations(&self) -> Vec<&str> {
+        let mut applied: Vec<&str> = self
+            .configurations
+            .iter()
+            .filter(|&(_, config)| config.status() == ConfigurationStatus::Applied)
+            .map(|(id, _)| id.as_str())
+            .collect();
+        applied.sort();
+        applied
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConfigurationStatus {
+    Targeted,
+    Applied,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TwinConfiguration {
+    status: ConfigurationStatus,
+}
+
+impl TwinConfiguration {
+    pub fn new(status: ConfigurationStatus) -> Self {
+        TwinConfiguration { status }
+    }
+
+    pub fn status(&self) -> ConfigurationStatus {
+        self.status
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Properties {
     desired: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    reported: Value,
 }
 
 impl Properties {
     pub fn new(desired: Value) -> Properties {
-        Properties { desired }
+        Properties {
+            desired,
+            reported: Value::Null,
+        }
+    }
+
+    pub fn with_reported(mut self, reported: Value) -> Properties {
+        self.reported = reported;
+        self
     }
 
     pub fn desired(&self) -> &Value {
         &self.desired
     }
+
+    pub fn reported(&self) -> &Value {
+        &self.reported
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -317,3 +381,349 @@ impl Default for Module {
         Module::new()
     }
 }
+
+/// The results of the queries a configuration runs against the devices it
+/// targets, such as how many devices it has been applied to.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ConfigurationMetrics {
+    #[serde(default)]
+    results: HashMap<String, i64>,
+    #[serde(default)]
+    queries: HashMap<String, String>,
+}
+
+impl ConfigurationMetrics {
+    pub fn new() -> Self {
+        ConfigurationMetrics::default()
+    }
+
+    pub fn with_result(mut self, name: String, count: i64) -> Self {
+        self.results.insert(name, count);
+        self
+    }
+
+    pub fn results(&self) -> &HashMap<String, i64> {
+        &self.results
+    }
+
+    pub fn with_query(mut self, name: String, query: String) -> Self {
+        self.queries.insert(name, query);
+        self
+    }
+
+    pub fn queries(&self) -> &HashMap<String, String> {
+        &self.queries
+    }
+}
+
+/// An automatic device management configuration, which is also how IoT Edge
+/// deployments are represented.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Configuration {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema_version: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    labels: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_condition: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_time_utc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_updated_time_utc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_metrics: Option<ConfigurationMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<ConfigurationMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+}
+
+impl Configuration {
+    pub fn new(id: String) -> Self {
+        Configuration {
+            id,
+            schema_version: None,
+            labels: HashMap::new(),
+            content: None,
+            target_condition: None,
+            created_time_utc: None,
+            last_updated_time_utc: None,
+            priority: None,
+            system_metrics: None,
+            metrics: None,
+            etag: None,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn schema_version(&self) -> Option<&str> {
+        self.schema_version.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_label(mut self, name: String, value: String) -> Self {
+        self.labels.insert(name, value);
+        self
+    }
+
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.labels
+    }
+
+    pub fn with_content(mut self, content: Value) -> Self {
+        self.content = Some(content);
+        self
+    }
+
+    pub fn content(&self) -> Option<&Value> {
+        self.content.as_ref()
+    }
+
+    pub fn with_target_condition(mut self, target_condition: String) -> Self {
+        self.target_condition = Some(target_condition);
+        self
+    }
+
+    pub fn target_condition(&self) -> Option<&str> {
+        self.target_condition.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn created_time_utc(&self) -> Option<&str> {
+        self.created_time_utc.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn last_updated_time_utc(&self) -> Option<&str> {
+        self.last_updated_time_utc.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn priority(&self) -> Option<i32> {
+        self.priority
+    }
+
+    pub fn with_system_metrics(mut self, system_metrics: ConfigurationMetrics) -> Self {
+        self.system_metrics = Some(system_metrics);
+        self
+    }
+
+    pub fn system_metrics(&self) -> Option<&ConfigurationMetrics> {
+        self.system_metrics.as_ref()
+    }
+
+    pub fn with_metrics(mut self, metrics: ConfigurationMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn metrics(&self) -> Option<&ConfigurationMetrics> {
+        self.metrics.as_ref()
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// Counts of the devices a job runs on, by the state of the job on each
+/// device.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceJobStatistics {
+    #[serde(default)]
+    device_count: i64,
+    #[serde(default)]
+    failed_count: i64,
+    #[serde(default)]
+    succeeded_count: i64,
+    #[serde(default)]
+    running_count: i64,
+    #[serde(default)]
+    pending_count: i64,
+}
+
+impl DeviceJobStatistics {
+    pub fn device_count(&self) -> i64 {
+        self.device_count
+    }
+
+    pub fn failed_count(&self) -> i64 {
+        self.failed_count
+    }
+
+    pub fn succeeded_count(&self) -> i64 {
+        self.succeeded_count
+    }
+
+    pub fn running_count(&self) -> i64 {
+        self.running_count
+    }
+
+    pub fn pending_count(&self) -> i64 {
+        self.pending_count
+    }
+}
+
+/// A job scheduled on IoT Hub. The type and status are kept as strings since
+/// IoT Hub adds new values from time to time.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    job_id: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    type_: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_job_statistics: Option<DeviceJobStatistics>,
+}
+
+impl Job {
+    pub fn new(job_id: String) -> Self {
+        Job {
+            job_id,
+            type_: None,
+            status: None,
+            start_time: None,
+            end_time: None,
+            failure_reason: None,
+            device_job_statistics: None,
+        }
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    pub fn with_type(mut self, type_: String) -> Self {
+        self.type_ = Some(type_);
+        self
+    }
+
+    pub fn type_(&self) -> Option<&str> {
+        self.type_.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_status(mut self, status: String) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn start_time(&self) -> Option<&str> {
+        self.start_time.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn end_time(&self) -> Option<&str> {
+        self.end_time.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn failure_reason(&self) -> Option<&str> {
+        self.failure_reason.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn device_job_statistics(&self) -> Option<&DeviceJobStatistics> {
+        self.device_job_statistics.as_ref()
+    }
+}
+
+/// The state of a job on a single device.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceJob {
+    device_id: String,
+    job_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_time_utc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_time_utc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_updated_date_time_utc: Option<String>,
+}
+
+impl DeviceJob {
+    pub fn new(device_id: String, job_id: String) -> Self {
+        DeviceJob {
+            device_id,
+            job_id,
+            job_type: None,
+            status: None,
+            start_time_utc: None,
+            end_time_utc: None,
+            last_updated_date_time_utc: None,
+        }
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    pub fn job_type(&self) -> Option<&str> {
+        self.job_type.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn with_status(mut self, status: String) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn start_time_utc(&self) -> Option<&str> {
+        self.start_time_utc.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn end_time_utc(&self) -> Option<&str> {
+        self.end_time_utc.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn last_updated_date_time_utc(&self) -> Option<&str> {
+        self.last_updated_date_time_utc.as_ref().map(AsRef::as_ref)
+    }
+}
+
+/// The body of a query against the device registry.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct QuerySpecification {
+    query: String,
+}
+
+impl QuerySpecification {
+    pub fn new(query: String) -> Self {
+        QuerySpecification { query }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+}
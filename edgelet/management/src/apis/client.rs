@@ -4,8 +4,11 @@ use super::configuration::Configuration;
 use hyper;
 
 pub struct APIClient {
+    deployment_api: Box<::apis::DeploymentApi>,
     identity_api: Box<::apis::IdentityApi>,
     module_api: Box<::apis::ModuleApi>,
+    operation_api: Box<::apis::OperationApi>,
+    state_api: Box<::apis::StateApi>,
     system_information_api: Box<::apis::SystemInformationApi>,
 }
 
@@ -17,14 +20,21 @@ impl APIClient {
         let configuration = Arc::new(configuration);
 
         APIClient {
+            deployment_api: Box::new(::apis::DeploymentApiClient::new(configuration.clone())),
             identity_api: Box::new(::apis::IdentityApiClient::new(configuration.clone())),
             module_api: Box::new(::apis::ModuleApiClient::new(configuration.clone())),
+            operation_api: Box::new(::apis::OperationApiClient::new(configuration.clone())),
+            state_api: Box::new(::apis::StateApiClient::new(configuration.clone())),
             system_information_api: Box::new(::apis::SystemInformationApiClient::new(
                 configuration.clone(),
             )),
         }
     }
 
+    pub fn deployment_api(&self) -> &::apis::DeploymentApi {
+        self.deployment_api.as_ref()
+    }
+
     pub fn identity_api(&self) -> &::apis::IdentityApi {
         self.identity_api.as_ref()
     }
@@ -33,6 +43,14 @@ impl APIClient {
         self.module_api.as_ref()
     }
 
+    pub fn operation_api(&self) -> &::apis::OperationApi {
+        self.operation_api.as_ref()
+    }
+
+    pub fn state_api(&self) -> &::apis::StateApi {
+        self.state_api.as_ref()
+    }
+
     pub fn system_information_api(&self) -> &::apis::SystemInformationApi {
         self.system_information_api.as_ref()
     }
@@ -35,16 +35,16 @@ pub trait IdentityApi: Send + Sync {
         api_version: &str,
         name: &str,
         identity: ::models::IdentitySpec,
-    ) -> Box<Future<Item = ::models::Identity, Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = ::models::Identity, Error = Error<serde_json::Value>> + Send>;
     fn delete_identity(
         &self,
         api_version: &str,
         name: &str,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>> + Send>;
     fn list_identities(
         &self,
         api_version: &str,
-    ) -> Box<Future<Item = ::models::IdentityList, Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = ::models::IdentityList, Error = Error<serde_json::Value>> + Send>;
 }
 
 impl<C> IdentityApi for IdentityApiClient<C>
@@ -58,7 +58,7 @@ where
         api_version: &str,
         name: &str,
         identity: ::models::IdentitySpec,
-    ) -> Box<Future<Item = ::models::Identity, Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = ::models::Identity, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::PUT;
@@ -117,7 +117,7 @@ where
         &self,
         api_version: &str,
         name: &str,
-    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = (), Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::DELETE;
@@ -165,7 +165,7 @@ where
     fn list_identities(
         &self,
         api_version: &str,
-    ) -> Box<Future<Item = ::models::IdentityList, Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = ::models::IdentityList, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
@@ -48,10 +48,16 @@ impl<T> From<serde_json::Error> for Error<T> {
     }
 }
 
+mod deployment_api;
+pub use self::deployment_api::{DeploymentApi, DeploymentApiClient};
 mod identity_api;
 pub use self::identity_api::{IdentityApi, IdentityApiClient};
 mod module_api;
 pub use self::module_api::{ModuleApi, ModuleApiClient};
+mod operation_api;
+pub use self::operation_api::{OperationApi, OperationApiClient};
+mod state_api;
+pub use self::state_api::{StateApi, StateApiClient};
 mod system_information_api;
 pub use self::system_information_api::{SystemInformationApi, SystemInformationApiClient};
 
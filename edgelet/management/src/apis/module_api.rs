@@ -48,6 +48,7 @@ pub trait ModuleApi: Send + Sync {
     fn list_modules(
         &self,
         api_version: &str,
+        label_selector: &str,
     ) -> Box<Future<Item = ::models::ModuleList, Error = Error<serde_json::Value>> + Send>;
     fn module_logs(
         &self,
@@ -55,7 +56,15 @@ pub trait ModuleApi: Send + Sync {
         name: &str,
         follow: bool,
         tail: &str,
+        since: i32,
+        limit_bytes: i64,
+        grep: &str,
     ) -> Box<Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send>;
+    fn module_usage(
+        &self,
+        api_version: &str,
+        name: &str,
+    ) -> Box<Future<Item = ::models::ModuleUsage, Error = Error<serde_json::Value>> + Send>;
     fn restart_module(
         &self,
         api_version: &str,
@@ -248,6 +257,7 @@ where
     fn list_modules(
         &self,
         api_version: &str,
+        label_selector: &str,
     ) -> Box<Future<Item = ::models::ModuleList, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
@@ -255,6 +265,7 @@ where
 
         let query = ::url::form_urlencoded::Serializer::new(String::new())
             .append_pair("api-version", &api_version.to_string())
+            .append_pair("labelSelector", &label_selector.to_string())
             .finish();
         let uri_str = format!("/modules?{}", query);
 
@@ -302,6 +313,9 @@ where
         name: &str,
         follow: bool,
         tail: &str,
+        since: i32,
+        limit_bytes: i64,
+        grep: &str,
     ) -> Box<Future<Item = hyper::Body, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
@@ -311,6 +325,9 @@ where
             .append_pair("api-version", &api_version.to_string())
             .append_pair("follow", &follow.to_string())
             .append_pair("tail", &tail.to_string())
+            .append_pair("since", &since.to_string())
+            .append_pair("limit_bytes", &limit_bytes.to_string())
+            .append_pair("grep", &grep.to_string())
             .finish();
         let uri_str = format!("/modules/{name}/logs?{}", query, name = name);
 
@@ -346,6 +363,58 @@ where
         )
     }
 
+    fn module_usage(
+        &self,
+        api_version: &str,
+        name: &str,
+    ) -> Box<Future<Item = ::models::ModuleUsage, Error = Error<serde_json::Value>> + Send> {
+        let configuration: &configuration::Configuration<C> = self.configuration.borrow();
+
+        let method = hyper::Method::GET;
+
+        let query = ::url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("api-version", &api_version.to_string())
+            .finish();
+        let uri_str = format!("/modules/{name}/usage?{}", query, name = name);
+
+        let uri = (configuration.uri_composer)(&configuration.base_path, &uri_str);
+        // TODO(farcaller): handle error
+        // if let Err(e) = uri {
+        //     return Box::new(futures::future::err(e));
+        // }
+        let mut req = hyper::Request::builder();
+        req.method(method).uri(uri.unwrap());
+        if let Some(ref user_agent) = configuration.user_agent {
+            req.header(http::header::USER_AGENT, &**user_agent);
+        }
+        let req = req
+            .body(hyper::Body::empty())
+            .expect("could not build hyper::Request");
+
+        // send request
+        Box::new(
+            configuration
+                .client
+                .request(req)
+                .map_err(Error::from)
+                .and_then(|resp| {
+                    let (http::response::Parts { status, .. }, body) = resp.into_parts();
+                    body.concat2()
+                        .and_then(move |body| Ok((status, body)))
+                        .map_err(Error::from)
+                }).and_then(|(status, body)| {
+                    if status.is_success() {
+                        Ok(body)
+                    } else {
+                        Err(Error::from((status, &*body)))
+                    }
+                }).and_then(|body| {
+                    let parsed: Result<::models::ModuleUsage, _> = serde_json::from_slice(&body);
+                    parsed.map_err(Error::from)
+                }),
+        )
+    }
+
     fn restart_module(
         &self,
         api_version: &str,
@@ -0,0 +1,142 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+use std::borrow::Borrow;
+use std::sync::Arc;
+
+use futures::{Future, Stream};
+use hyper;
+use serde_json;
+use typed_headers::{self, http, mime, HeaderMapExt};
+
+use super::{configuration, Error};
+
+pub struct StateApiClient<C: hyper::client::connect::Connect> {
+    configuration: Arc<configuration::Configuration<C>>,
+}
+
+impl<C: hyper::client::connect::Connect> StateApiClient<C> {
+    pub fn new(configuration: Arc<configuration::Configuration<C>>) -> Self {
+        StateApiClient { configuration }
+    }
+}
+
+pub trait StateApi: Send + Sync {
+    fn backup_state(
+        &self,
+        api_version: &str,
+        request: ::models::BackupRequest,
+    ) -> Box<Future<Item = Vec<u8>, Error = Error<serde_json::Value>> + Send>;
+    fn restore_state(
+        &self,
+        api_version: &str,
+        request: ::models::RestoreRequest,
+    ) -> Box<Future<Item = ::models::RestoreResult, Error = Error<serde_json::Value>> + Send>;
+}
+
+impl<C> StateApiClient<C>
+where
+    C: hyper::client::connect::Connect + 'static,
+    <C as hyper::client::connect::Connect>::Transport: 'static,
+    <C as hyper::client::connect::Connect>::Future: 'static,
+{
+    fn post<T>(
+        &self,
+        api_version: &str,
+        path: &str,
+        request: &T,
+    ) -> Box<Future<Item = hyper::Chunk, Error = Error<serde_json::Value>> + Send>
+    where
+        T: ::serde::Serialize,
+    {
+        let configuration: &configuration::Configuration<C> = self.configuration.borrow();
+
+        let method = hyper::Method::POST;
+
+        let query = ::url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("api-version", &api_version.to_string())
+            .finish();
+        let uri_str = format!("{}?{}", path, query);
+
+        let uri = (configuration.uri_composer)(&configuration.base_path, &uri_str);
+        // TODO(farcaller): handle error
+        // if let Err(e) = uri {
+        //     return Box::new(futures::future::err(e));
+        // }
+        let serialized = serde_json::to_string(request).unwrap();
+        let serialized_len = serialized.len();
+
+        let mut req = hyper::Request::builder();
+        req.method(method).uri(uri.unwrap());
+        if let Some(ref user_agent) = configuration.user_agent {
+            req.header(http::header::USER_AGENT, &**user_agent);
+        }
+        let mut req = req
+            .body(hyper::Body::from(serialized))
+            .expect("could not build hyper::Request");
+        req.headers_mut()
+            .typed_insert(&typed_headers::ContentType(mime::APPLICATION_JSON));
+        req.headers_mut()
+            .typed_insert(&typed_headers::ContentLength(serialized_len as u64));
+
+        // send request
+        Box::new(
+            configuration
+                .client
+                .request(req)
+                .map_err(Error::from)
+                .and_then(|resp| {
+                    let (http::response::Parts { status, .. }, body) = resp.into_parts();
+                    body.concat2()
+                        .and_then(move |body| Ok((status, body)))
+                        .map_err(Error::from)
+                }).and_then(|(status, body)| {
+                    if status.is_success() {
+                        Ok(body)
+                    } else {
+                        Err(Error::from((status, &*body)))
+                    }
+                }),
+        )
+    }
+}
+
+impl<C> StateApi for StateApiClient<C>
+where
+    C: hyper::client::connect::Connect + 'static,
+    <C as hyper::client::connect::Connect>::Transport: 'static,
+    <C as hyper::client::connect::Connect>::Future: 'static,
+{
+    fn backup_state(
+        &self,
+        api_version: &str,
+        request: ::models::BackupRequest,
+    ) -> Box<Future<Item = Vec<u8>, Error = Error<serde_json::Value>> + Send> {
+        Box::new(
+            self.post(api_version, "/state/backup", &request)
+                .map(|body| body.to_vec()),
+        )
+    }
+
+    fn restore_state(
+        &self,
+        api_version: &str,
+        request: ::models::RestoreRequest,
+    ) -> Box<Future<Item = ::models::RestoreResult, Error = Error<serde_json::Value>> + Send> {
+        Box::new(
+            self.post(api_version, "/state/restore", &request)
+                .and_then(|body| {
+                    let parsed: Result<::models::RestoreResult, _> =
+                        serde_json::from_slice(&body);
+                    parsed.map_err(Error::from)
+                }),
+        )
+    }
+}
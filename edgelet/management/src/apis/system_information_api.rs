@@ -32,7 +32,7 @@ pub trait SystemInformationApi: Send + Sync {
     fn get_system_info(
         &self,
         api_version: &str,
-    ) -> Box<Future<Item = ::models::SystemInfo, Error = Error<serde_json::Value>>>;
+    ) -> Box<Future<Item = ::models::SystemInfo, Error = Error<serde_json::Value>> + Send>;
 }
 
 impl<C> SystemInformationApi for SystemInformationApiClient<C>
@@ -44,7 +44,7 @@ where
     fn get_system_info(
         &self,
         api_version: &str,
-    ) -> Box<Future<Item = ::models::SystemInfo, Error = Error<serde_json::Value>>> {
+    ) -> Box<Future<Item = ::models::SystemInfo, Error = Error<serde_json::Value>> + Send> {
         let configuration: &configuration::Configuration<C> = self.configuration.borrow();
 
         let method = hyper::Method::GET;
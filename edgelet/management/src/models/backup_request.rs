@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupRequest {
+    /// The passphrase the backup is encrypted with.
+    #[serde(rename = "passphrase")]
+    passphrase: String,
+}
+
+impl BackupRequest {
+    pub fn new(passphrase: String) -> Self {
+        BackupRequest { passphrase }
+    }
+
+    pub fn set_passphrase(&mut self, passphrase: String) {
+        self.passphrase = passphrase;
+    }
+
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase = passphrase;
+        self
+    }
+
+    pub fn passphrase(&self) -> &String {
+        &self.passphrase
+    }
+}
@@ -0,0 +1,106 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CaRotationStatus {
+    /// Whether the roots trusted before a rotation of the Edge CA are still
+    /// served in the trust bundle.
+    #[serde(rename = "state")]
+    state: String,
+    #[serde(rename = "startedAt", skip_serializing_if = "Option::is_none")]
+    started_at: Option<String>,
+    /// When the roots trusted before the rotation stop being served.
+    #[serde(rename = "endsAt", skip_serializing_if = "Option::is_none")]
+    ends_at: Option<String>,
+    /// Whether the workload CA has been issued again under the new root.
+    #[serde(
+        rename = "workloadCaReissued",
+        skip_serializing_if = "Option::is_none"
+    )]
+    workload_ca_reissued: Option<bool>,
+}
+
+impl CaRotationStatus {
+    pub fn new(state: String) -> Self {
+        CaRotationStatus {
+            state,
+            started_at: None,
+            ends_at: None,
+            workload_ca_reissued: None,
+        }
+    }
+
+    pub fn set_state(&mut self, state: String) {
+        self.state = state;
+    }
+
+    pub fn with_state(mut self, state: String) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn state(&self) -> &String {
+        &self.state
+    }
+
+    pub fn set_started_at(&mut self, started_at: String) {
+        self.started_at = Some(started_at);
+    }
+
+    pub fn with_started_at(mut self, started_at: String) -> Self {
+        self.started_at = Some(started_at);
+        self
+    }
+
+    pub fn started_at(&self) -> Option<&str> {
+        self.started_at.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_started_at(&mut self) {
+        self.started_at = None;
+    }
+
+    pub fn set_ends_at(&mut self, ends_at: String) {
+        self.ends_at = Some(ends_at);
+    }
+
+    pub fn with_ends_at(mut self, ends_at: String) -> Self {
+        self.ends_at = Some(ends_at);
+        self
+    }
+
+    pub fn ends_at(&self) -> Option<&str> {
+        self.ends_at.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_ends_at(&mut self) {
+        self.ends_at = None;
+    }
+
+    pub fn set_workload_ca_reissued(&mut self, workload_ca_reissued: bool) {
+        self.workload_ca_reissued = Some(workload_ca_reissued);
+    }
+
+    pub fn with_workload_ca_reissued(mut self, workload_ca_reissued: bool) -> Self {
+        self.workload_ca_reissued = Some(workload_ca_reissued);
+        self
+    }
+
+    pub fn workload_ca_reissued(&self) -> Option<&bool> {
+        self.workload_ca_reissued.as_ref()
+    }
+
+    pub fn reset_workload_ca_reissued(&mut self) {
+        self.workload_ca_reissued = None;
+    }
+}
@@ -0,0 +1,117 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeploymentPlan {
+    /// The ID the plan is polled with.
+    #[serde(rename = "id")]
+    id: String,
+    /// Where the plan is at: pulling, applying, rollingBack, succeeded,
+    /// failed or rolledBack.
+    #[serde(rename = "status")]
+    status: String,
+    /// The changes the plan makes to the modules, in the order they are made.
+    #[serde(rename = "steps")]
+    steps: Vec<::models::PlanStep>,
+    /// Why the plan failed or was rolled back.
+    #[serde(rename = "error", skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// The ID of the operation that reports the progress of the plan.
+    #[serde(rename = "operationId", skip_serializing_if = "Option::is_none")]
+    operation_id: Option<String>,
+}
+
+impl DeploymentPlan {
+    pub fn new(id: String, status: String, steps: Vec<::models::PlanStep>) -> Self {
+        DeploymentPlan {
+            id,
+            status,
+            steps,
+            error: None,
+            operation_id: None,
+        }
+    }
+
+    pub fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    pub fn with_status(mut self, status: String) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn set_steps(&mut self, steps: Vec<::models::PlanStep>) {
+        self.steps = steps;
+    }
+
+    pub fn with_steps(mut self, steps: Vec<::models::PlanStep>) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn steps(&self) -> &[::models::PlanStep] {
+        &self.steps
+    }
+
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_error(&mut self) {
+        self.error = None;
+    }
+
+    pub fn set_operation_id(&mut self, operation_id: String) {
+        self.operation_id = Some(operation_id);
+    }
+
+    pub fn with_operation_id(mut self, operation_id: String) -> Self {
+        self.operation_id = Some(operation_id);
+        self
+    }
+
+    pub fn operation_id(&self) -> Option<&str> {
+        self.operation_id.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_operation_id(&mut self) {
+        self.operation_id = None;
+    }
+}
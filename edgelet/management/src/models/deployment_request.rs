@@ -0,0 +1,63 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeploymentRequest {
+    /// The modules of the deployment. Modules that were deployed before and
+    /// aren't in the list are removed.
+    #[serde(rename = "modules")]
+    modules: Vec<::models::ModuleSpec>,
+    /// The version of the deployment, which the modules are recorded as
+    /// coming from. Defaults to the hex encoded SHA-256 of the request body.
+    #[serde(rename = "version", skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+}
+
+impl DeploymentRequest {
+    pub fn new(modules: Vec<::models::ModuleSpec>) -> Self {
+        DeploymentRequest {
+            modules,
+            version: None,
+        }
+    }
+
+    pub fn set_modules(&mut self, modules: Vec<::models::ModuleSpec>) {
+        self.modules = modules;
+    }
+
+    pub fn with_modules(mut self, modules: Vec<::models::ModuleSpec>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub fn modules(&self) -> &[::models::ModuleSpec] {
+        &self.modules
+    }
+
+    pub fn set_version(&mut self, version: String) {
+        self.version = Some(version);
+    }
+
+    pub fn with_version(mut self, version: String) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_version(&mut self) {
+        self.version = None;
+    }
+}
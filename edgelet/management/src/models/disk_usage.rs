@@ -0,0 +1,138 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// The size of the layers of the images, in bytes. Layers that images share are counted once.
+    #[serde(rename = "images")]
+    images: i64,
+    /// The writable layers of the containers of the modules.
+    #[serde(rename = "modules")]
+    modules: Vec<::models::ModuleDiskUsage>,
+    /// The volumes managed by the container runtime.
+    #[serde(rename = "volumes")]
+    volumes: Vec<::models::VolumeDiskUsage>,
+    /// The size of the home directory of the daemon, in bytes.
+    #[serde(rename = "daemonState")]
+    daemon_state: i64,
+    /// The size of the volume holding the home directory of the daemon, in bytes.
+    #[serde(rename = "total", skip_serializing_if = "Option::is_none")]
+    total: Option<i64>,
+    /// The free space of the volume holding the home directory of the daemon, in bytes.
+    #[serde(rename = "available", skip_serializing_if = "Option::is_none")]
+    available: Option<i64>,
+}
+
+impl DiskUsage {
+    pub fn new(
+        images: i64,
+        modules: Vec<::models::ModuleDiskUsage>,
+        volumes: Vec<::models::VolumeDiskUsage>,
+        daemon_state: i64,
+    ) -> Self {
+        DiskUsage {
+            images,
+            modules,
+            volumes,
+            daemon_state,
+            total: None,
+            available: None,
+        }
+    }
+
+    pub fn set_images(&mut self, images: i64) {
+        self.images = images;
+    }
+
+    pub fn with_images(mut self, images: i64) -> Self {
+        self.images = images;
+        self
+    }
+
+    pub fn images(&self) -> i64 {
+        self.images
+    }
+
+    pub fn set_modules(&mut self, modules: Vec<::models::ModuleDiskUsage>) {
+        self.modules = modules;
+    }
+
+    pub fn with_modules(mut self, modules: Vec<::models::ModuleDiskUsage>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub fn modules(&self) -> &[::models::ModuleDiskUsage] {
+        &self.modules
+    }
+
+    pub fn set_volumes(&mut self, volumes: Vec<::models::VolumeDiskUsage>) {
+        self.volumes = volumes;
+    }
+
+    pub fn with_volumes(mut self, volumes: Vec<::models::VolumeDiskUsage>) -> Self {
+        self.volumes = volumes;
+        self
+    }
+
+    pub fn volumes(&self) -> &[::models::VolumeDiskUsage] {
+        &self.volumes
+    }
+
+    pub fn set_daemon_state(&mut self, daemon_state: i64) {
+        self.daemon_state = daemon_state;
+    }
+
+    pub fn with_daemon_state(mut self, daemon_state: i64) -> Self {
+        self.daemon_state = daemon_state;
+        self
+    }
+
+    pub fn daemon_state(&self) -> i64 {
+        self.daemon_state
+    }
+
+    pub fn set_total(&mut self, total: i64) {
+        self.total = Some(total);
+    }
+
+    pub fn with_total(mut self, total: i64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    pub fn total(&self) -> Option<i64> {
+        self.total
+    }
+
+    pub fn reset_total(&mut self) {
+        self.total = None;
+    }
+
+    pub fn set_available(&mut self, available: i64) {
+        self.available = Some(available);
+    }
+
+    pub fn with_available(mut self, available: i64) -> Self {
+        self.available = Some(available);
+        self
+    }
+
+    pub fn available(&self) -> Option<i64> {
+        self.available
+    }
+
+    pub fn reset_available(&mut self) {
+        self.available = None;
+    }
+}
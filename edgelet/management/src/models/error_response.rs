@@ -15,11 +15,21 @@ use serde_json::Value;
 pub struct ErrorResponse {
     #[serde(rename = "message")]
     message: String,
+    /// What went wrong: user-config, transient-network, hsm, runtime or internal.
+    #[serde(rename = "category", skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    /// Identifies errors the status doesn't tell apart, such as ReadOnlyManagementApi.
+    #[serde(rename = "code", skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
 }
 
 impl ErrorResponse {
     pub fn new(message: String) -> Self {
-        ErrorResponse { message }
+        ErrorResponse {
+            message,
+            category: None,
+            code: None,
+        }
     }
 
     pub fn set_message(&mut self, message: String) {
@@ -34,4 +44,38 @@ impl ErrorResponse {
     pub fn message(&self) -> &String {
         &self.message
     }
+
+    pub fn set_category(&mut self, category: String) {
+        self.category = Some(category);
+    }
+
+    pub fn with_category(mut self, category: String) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_category(&mut self) {
+        self.category = None;
+    }
+
+    pub fn set_code(&mut self, code: String) {
+        self.code = Some(code);
+    }
+
+    pub fn with_code(mut self, code: String) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_code(&mut self) {
+        self.code = None;
+    }
 }
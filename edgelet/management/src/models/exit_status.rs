@@ -17,6 +17,9 @@ pub struct ExitStatus {
     exit_time: String,
     #[serde(rename = "statusCode")]
     status_code: String,
+    /// Why the module exited.
+    #[serde(rename = "reason", skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
 }
 
 impl ExitStatus {
@@ -24,6 +27,7 @@ impl ExitStatus {
         ExitStatus {
             exit_time,
             status_code,
+            reason: None,
         }
     }
 
@@ -52,4 +56,21 @@ impl ExitStatus {
     pub fn status_code(&self) -> &String {
         &self.status_code
     }
+
+    pub fn set_reason(&mut self, reason: String) {
+        self.reason = Some(reason);
+    }
+
+    pub fn with_reason(mut self, reason: String) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_reason(&mut self) {
+        self.reason = None;
+    }
 }
@@ -0,0 +1,113 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HostProcess {
+    #[serde(rename = "name")]
+    name: String,
+    #[serde(rename = "executablePath")]
+    executable_path: String,
+    #[serde(rename = "uid")]
+    uid: i64,
+    /// Generation ID of the module identity the process uses.
+    #[serde(rename = "generationId", skip_serializing_if = "Option::is_none")]
+    generation_id: Option<String>,
+    /// Path of the file holding the environment of the process.
+    #[serde(rename = "envFile", skip_serializing_if = "Option::is_none")]
+    env_file: Option<String>,
+}
+
+impl HostProcess {
+    pub fn new(name: String, executable_path: String, uid: i64) -> Self {
+        HostProcess {
+            name,
+            executable_path,
+            uid,
+            generation_id: None,
+            env_file: None,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn set_executable_path(&mut self, executable_path: String) {
+        self.executable_path = executable_path;
+    }
+
+    pub fn with_executable_path(mut self, executable_path: String) -> Self {
+        self.executable_path = executable_path;
+        self
+    }
+
+    pub fn executable_path(&self) -> &String {
+        &self.executable_path
+    }
+
+    pub fn set_uid(&mut self, uid: i64) {
+        self.uid = uid;
+    }
+
+    pub fn with_uid(mut self, uid: i64) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    pub fn uid(&self) -> i64 {
+        self.uid
+    }
+
+    pub fn set_generation_id(&mut self, generation_id: String) {
+        self.generation_id = Some(generation_id);
+    }
+
+    pub fn with_generation_id(mut self, generation_id: String) -> Self {
+        self.generation_id = Some(generation_id);
+        self
+    }
+
+    pub fn generation_id(&self) -> Option<&str> {
+        self.generation_id.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_generation_id(&mut self) {
+        self.generation_id = None;
+    }
+
+    pub fn set_env_file(&mut self, env_file: String) {
+        self.env_file = Some(env_file);
+    }
+
+    pub fn with_env_file(mut self, env_file: String) -> Self {
+        self.env_file = Some(env_file);
+        self
+    }
+
+    pub fn env_file(&self) -> Option<&str> {
+        self.env_file.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_env_file(&mut self) {
+        self.env_file = None;
+    }
+}
@@ -0,0 +1,37 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HostProcessList {
+    #[serde(rename = "hostProcesses")]
+    host_processes: Vec<::models::HostProcess>,
+}
+
+impl HostProcessList {
+    pub fn new(host_processes: Vec<::models::HostProcess>) -> Self {
+        HostProcessList { host_processes }
+    }
+
+    pub fn set_host_processes(&mut self, host_processes: Vec<::models::HostProcess>) {
+        self.host_processes = host_processes;
+    }
+
+    pub fn with_host_processes(mut self, host_processes: Vec<::models::HostProcess>) -> Self {
+        self.host_processes = host_processes;
+        self
+    }
+
+    pub fn host_processes(&self) -> &[::models::HostProcess] {
+        &self.host_processes
+    }
+}
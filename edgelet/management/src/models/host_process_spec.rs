@@ -0,0 +1,57 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HostProcessSpec {
+    /// Absolute path of the executable the process runs.
+    #[serde(rename = "executablePath")]
+    executable_path: String,
+    /// User the process runs as.
+    #[serde(rename = "uid")]
+    uid: i64,
+}
+
+impl HostProcessSpec {
+    pub fn new(executable_path: String, uid: i64) -> Self {
+        HostProcessSpec {
+            executable_path,
+            uid,
+        }
+    }
+
+    pub fn set_executable_path(&mut self, executable_path: String) {
+        self.executable_path = executable_path;
+    }
+
+    pub fn with_executable_path(mut self, executable_path: String) -> Self {
+        self.executable_path = executable_path;
+        self
+    }
+
+    pub fn executable_path(&self) -> &String {
+        &self.executable_path
+    }
+
+    pub fn set_uid(&mut self, uid: i64) {
+        self.uid = uid;
+    }
+
+    pub fn with_uid(mut self, uid: i64) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    pub fn uid(&self) -> i64 {
+        self.uid
+    }
+}
@@ -11,7 +11,7 @@
 #[allow(unused_imports)]
 use serde_json::Value;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Identity {
     #[serde(rename = "moduleId")]
     module_id: String,
@@ -21,6 +21,12 @@ pub struct Identity {
     generation_id: String,
     #[serde(rename = "authType")]
     auth_type: String,
+    /// The base64 encoded primary symmetric key, if the daemon allows keys to be exported.
+    #[serde(rename = "primaryKey", skip_serializing_if = "Option::is_none")]
+    primary_key: Option<String>,
+    /// The base64 encoded secondary symmetric key, if the daemon allows keys to be exported.
+    #[serde(rename = "secondaryKey", skip_serializing_if = "Option::is_none")]
+    secondary_key: Option<String>,
 }
 
 impl Identity {
@@ -35,6 +41,8 @@ impl Identity {
             managed_by,
             generation_id,
             auth_type,
+            primary_key: None,
+            secondary_key: None,
         }
     }
 
@@ -89,4 +97,38 @@ impl Identity {
     pub fn auth_type(&self) -> &String {
         &self.auth_type
     }
+
+    pub fn set_primary_key(&mut self, primary_key: String) {
+        self.primary_key = Some(primary_key);
+    }
+
+    pub fn with_primary_key(mut self, primary_key: String) -> Self {
+        self.primary_key = Some(primary_key);
+        self
+    }
+
+    pub fn primary_key(&self) -> Option<&str> {
+        self.primary_key.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_primary_key(&mut self) {
+        self.primary_key = None;
+    }
+
+    pub fn set_secondary_key(&mut self, secondary_key: String) {
+        self.secondary_key = Some(secondary_key);
+    }
+
+    pub fn with_secondary_key(mut self, secondary_key: String) -> Self {
+        self.secondary_key = Some(secondary_key);
+        self
+    }
+
+    pub fn secondary_key(&self) -> Option<&str> {
+        self.secondary_key.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_secondary_key(&mut self) {
+        self.secondary_key = None;
+    }
 }
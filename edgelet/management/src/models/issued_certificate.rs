@@ -0,0 +1,132 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IssuedCertificate {
+    /// Hex encoded serial number of the certificate.
+    #[serde(rename = "serialNumber")]
+    serial_number: String,
+    /// The name of the module the certificate was issued to.
+    #[serde(rename = "moduleId")]
+    module_id: String,
+    /// The HSM alias the certificate is stored under.
+    #[serde(rename = "alias")]
+    alias: String,
+    #[serde(rename = "issuedAt")]
+    issued_at: String,
+    #[serde(rename = "expiresAt")]
+    expires_at: String,
+    #[serde(rename = "revokedAt", skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<String>,
+}
+
+impl IssuedCertificate {
+    pub fn new(
+        serial_number: String,
+        module_id: String,
+        alias: String,
+        issued_at: String,
+        expires_at: String,
+    ) -> Self {
+        IssuedCertificate {
+            serial_number,
+            module_id,
+            alias,
+            issued_at,
+            expires_at,
+            revoked_at: None,
+        }
+    }
+
+    pub fn set_serial_number(&mut self, serial_number: String) {
+        self.serial_number = serial_number;
+    }
+
+    pub fn with_serial_number(mut self, serial_number: String) -> Self {
+        self.serial_number = serial_number;
+        self
+    }
+
+    pub fn serial_number(&self) -> &String {
+        &self.serial_number
+    }
+
+    pub fn set_module_id(&mut self, module_id: String) {
+        self.module_id = module_id;
+    }
+
+    pub fn with_module_id(mut self, module_id: String) -> Self {
+        self.module_id = module_id;
+        self
+    }
+
+    pub fn module_id(&self) -> &String {
+        &self.module_id
+    }
+
+    pub fn set_alias(&mut self, alias: String) {
+        self.alias = alias;
+    }
+
+    pub fn with_alias(mut self, alias: String) -> Self {
+        self.alias = alias;
+        self
+    }
+
+    pub fn alias(&self) -> &String {
+        &self.alias
+    }
+
+    pub fn set_issued_at(&mut self, issued_at: String) {
+        self.issued_at = issued_at;
+    }
+
+    pub fn with_issued_at(mut self, issued_at: String) -> Self {
+        self.issued_at = issued_at;
+        self
+    }
+
+    pub fn issued_at(&self) -> &String {
+        &self.issued_at
+    }
+
+    pub fn set_expires_at(&mut self, expires_at: String) {
+        self.expires_at = expires_at;
+    }
+
+    pub fn with_expires_at(mut self, expires_at: String) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    pub fn expires_at(&self) -> &String {
+        &self.expires_at
+    }
+
+    pub fn set_revoked_at(&mut self, revoked_at: String) {
+        self.revoked_at = Some(revoked_at);
+    }
+
+    pub fn with_revoked_at(mut self, revoked_at: String) -> Self {
+        self.revoked_at = Some(revoked_at);
+        self
+    }
+
+    pub fn revoked_at(&self) -> Option<&str> {
+        self.revoked_at.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_revoked_at(&mut self) {
+        self.revoked_at = None;
+    }
+}
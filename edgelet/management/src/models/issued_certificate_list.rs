@@ -0,0 +1,37 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssuedCertificateList {
+    #[serde(rename = "certificates")]
+    certificates: Vec<::models::IssuedCertificate>,
+}
+
+impl IssuedCertificateList {
+    pub fn new(certificates: Vec<::models::IssuedCertificate>) -> Self {
+        IssuedCertificateList { certificates }
+    }
+
+    pub fn set_certificates(&mut self, certificates: Vec<::models::IssuedCertificate>) {
+        self.certificates = certificates;
+    }
+
+    pub fn with_certificates(mut self, certificates: Vec<::models::IssuedCertificate>) -> Self {
+        self.certificates = certificates;
+        self
+    }
+
+    pub fn certificates(&self) -> &[::models::IssuedCertificate] {
+        &self.certificates
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyAlias {
+    /// The HSM alias.
+    #[serde(rename = "alias")]
+    alias: String,
+    /// The name of the module holding the alias.
+    #[serde(rename = "moduleId")]
+    module_id: String,
+    /// The generation of the module identity the certificate under the alias was issued for. Absent for identity certificates.
+    #[serde(rename = "generationId", skip_serializing_if = "Option::is_none")]
+    generation_id: Option<String>,
+    /// When the certificate under the alias expires. Absent while it is being issued.
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    expires_at: Option<String>,
+    /// When a certificate was last asked for under the alias.
+    #[serde(rename = "lastUsed")]
+    last_used: String,
+}
+
+impl KeyAlias {
+    pub fn new(alias: String, module_id: String, last_used: String) -> Self {
+        KeyAlias {
+            alias,
+            module_id,
+            generation_id: None,
+            expires_at: None,
+            last_used,
+        }
+    }
+
+    pub fn set_alias(&mut self, alias: String) {
+        self.alias = alias;
+    }
+
+    pub fn with_alias(mut self, alias: String) -> Self {
+        self.alias = alias;
+        self
+    }
+
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    pub fn set_module_id(&mut self, module_id: String) {
+        self.module_id = module_id;
+    }
+
+    pub fn with_module_id(mut self, module_id: String) -> Self {
+        self.module_id = module_id;
+        self
+    }
+
+    pub fn module_id(&self) -> &str {
+        &self.module_id
+    }
+
+    pub fn set_generation_id(&mut self, generation_id: String) {
+        self.generation_id = Some(generation_id);
+    }
+
+    pub fn with_generation_id(mut self, generation_id: String) -> Self {
+        self.generation_id = Some(generation_id);
+        self
+    }
+
+    pub fn generation_id(&self) -> Option<&str> {
+        self.generation_id.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_generation_id(&mut self) {
+        self.generation_id = None;
+    }
+
+    pub fn set_expires_at(&mut self, expires_at: String) {
+        self.expires_at = Some(expires_at);
+    }
+
+    pub fn with_expires_at(mut self, expires_at: String) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn expires_at(&self) -> Option<&str> {
+        self.expires_at.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_expires_at(&mut self) {
+        self.expires_at = None;
+    }
+
+    pub fn set_last_used(&mut self, last_used: String) {
+        self.last_used = last_used;
+    }
+
+    pub fn with_last_used(mut self, last_used: String) -> Self {
+        self.last_used = last_used;
+        self
+    }
+
+    pub fn last_used(&self) -> &str {
+        &self.last_used
+    }
+}
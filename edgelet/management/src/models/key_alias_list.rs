@@ -0,0 +1,37 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyAliasList {
+    #[serde(rename = "keys")]
+    keys: Vec<::models::KeyAlias>,
+}
+
+impl KeyAliasList {
+    pub fn new(keys: Vec<::models::KeyAlias>) -> Self {
+        KeyAliasList { keys }
+    }
+
+    pub fn set_keys(&mut self, keys: Vec<::models::KeyAlias>) {
+        self.keys = keys;
+    }
+
+    pub fn with_keys(mut self, keys: Vec<::models::KeyAlias>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn keys(&self) -> &[::models::KeyAlias] {
+        &self.keys
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyCleanup {
+    /// The aliases whose certificates were destroyed in the HSM.
+    #[serde(rename = "released")]
+    released: Vec<String>,
+    /// The serial numbers of the certificates that were revoked.
+    #[serde(rename = "revoked")]
+    revoked: Vec<String>,
+}
+
+impl KeyCleanup {
+    pub fn new(released: Vec<String>, revoked: Vec<String>) -> Self {
+        KeyCleanup { released, revoked }
+    }
+
+    pub fn set_released(&mut self, released: Vec<String>) {
+        self.released = released;
+    }
+
+    pub fn with_released(mut self, released: Vec<String>) -> Self {
+        self.released = released;
+        self
+    }
+
+    pub fn released(&self) -> &[String] {
+        &self.released
+    }
+
+    pub fn set_revoked(&mut self, revoked: Vec<String>) {
+        self.revoked = revoked;
+    }
+
+    pub fn with_revoked(mut self, revoked: Vec<String>) -> Self {
+        self.revoked = revoked;
+        self
+    }
+
+    pub fn revoked(&self) -> &[String] {
+        &self.revoked
+    }
+}
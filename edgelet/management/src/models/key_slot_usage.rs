@@ -0,0 +1,95 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeySlotUsage {
+    /// The name of the module.
+    #[serde(rename = "moduleId")]
+    module_id: String,
+    /// The number of HSM key slots the module holds.
+    #[serde(rename = "used")]
+    used: i64,
+    /// The number of HSM key slots the module is allowed to hold.
+    #[serde(rename = "quota", skip_serializing_if = "Option::is_none")]
+    quota: Option<i64>,
+    /// The HSM aliases of the certificates holding the slots.
+    #[serde(rename = "aliases")]
+    aliases: Vec<String>,
+}
+
+impl KeySlotUsage {
+    pub fn new(module_id: String, used: i64, aliases: Vec<String>) -> Self {
+        KeySlotUsage {
+            module_id,
+            used,
+            quota: None,
+            aliases,
+        }
+    }
+
+    pub fn set_module_id(&mut self, module_id: String) {
+        self.module_id = module_id;
+    }
+
+    pub fn with_module_id(mut self, module_id: String) -> Self {
+        self.module_id = module_id;
+        self
+    }
+
+    pub fn module_id(&self) -> &str {
+        &self.module_id
+    }
+
+    pub fn set_used(&mut self, used: i64) {
+        self.used = used;
+    }
+
+    pub fn with_used(mut self, used: i64) -> Self {
+        self.used = used;
+        self
+    }
+
+    pub fn used(&self) -> i64 {
+        self.used
+    }
+
+    pub fn set_quota(&mut self, quota: i64) {
+        self.quota = Some(quota);
+    }
+
+    pub fn with_quota(mut self, quota: i64) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    pub fn quota(&self) -> Option<i64> {
+        self.quota
+    }
+
+    pub fn reset_quota(&mut self) {
+        self.quota = None;
+    }
+
+    pub fn set_aliases(&mut self, aliases: Vec<String>) {
+        self.aliases = aliases;
+    }
+
+    pub fn with_aliases(mut self, aliases: Vec<String>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+}
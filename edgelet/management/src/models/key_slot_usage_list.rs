@@ -0,0 +1,37 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeySlotUsageList {
+    #[serde(rename = "modules")]
+    modules: Vec<::models::KeySlotUsage>,
+}
+
+impl KeySlotUsageList {
+    pub fn new(modules: Vec<::models::KeySlotUsage>) -> Self {
+        KeySlotUsageList { modules }
+    }
+
+    pub fn set_modules(&mut self, modules: Vec<::models::KeySlotUsage>) {
+        self.modules = modules;
+    }
+
+    pub fn with_modules(mut self, modules: Vec<::models::KeySlotUsage>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub fn modules(&self) -> &[::models::KeySlotUsage] {
+        &self.modules
+    }
+}
@@ -0,0 +1,37 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogSamplingList {
+    #[serde(rename = "rules")]
+    rules: Vec<::models::LogSamplingRule>,
+}
+
+impl LogSamplingList {
+    pub fn new(rules: Vec<::models::LogSamplingRule>) -> Self {
+        LogSamplingList { rules }
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<::models::LogSamplingRule>) {
+        self.rules = rules;
+    }
+
+    pub fn with_rules(mut self, rules: Vec<::models::LogSamplingRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn rules(&self) -> &[::models::LogSamplingRule] {
+        &self.rules
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogSamplingRule {
+    /// The API whose access log the rule samples.
+    #[serde(rename = "api")]
+    api: String,
+    /// The pattern matched against the paths of the requests.
+    #[serde(rename = "pattern")]
+    pattern: String,
+    /// The number of requests that were logged.
+    #[serde(rename = "logged")]
+    logged: i64,
+    /// The number of requests that were left out of the log.
+    #[serde(rename = "dropped")]
+    dropped: i64,
+}
+
+impl LogSamplingRule {
+    pub fn new(api: String, pattern: String, logged: i64, dropped: i64) -> Self {
+        LogSamplingRule {
+            api,
+            pattern,
+            logged,
+            dropped,
+        }
+    }
+
+    pub fn set_api(&mut self, api: String) {
+        self.api = api;
+    }
+
+    pub fn with_api(mut self, api: String) -> Self {
+        self.api = api;
+        self
+    }
+
+    pub fn api(&self) -> &str {
+        &self.api
+    }
+
+    pub fn set_pattern(&mut self, pattern: String) {
+        self.pattern = pattern;
+    }
+
+    pub fn with_pattern(mut self, pattern: String) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn set_logged(&mut self, logged: i64) {
+        self.logged = logged;
+    }
+
+    pub fn with_logged(mut self, logged: i64) -> Self {
+        self.logged = logged;
+        self
+    }
+
+    pub fn logged(&self) -> i64 {
+        self.logged
+    }
+
+    pub fn set_dropped(&mut self, dropped: i64) {
+        self.dropped = dropped;
+    }
+
+    pub fn with_dropped(mut self, dropped: i64) -> Self {
+        self.dropped = dropped;
+        self
+    }
+
+    pub fn dropped(&self) -> i64 {
+        self.dropped
+    }
+}
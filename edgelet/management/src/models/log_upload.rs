@@ -0,0 +1,103 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogUpload {
+    /// The SAS URL of the blob the logs are uploaded to.
+    #[serde(rename = "sasUrl")]
+    sas_url: String,
+    /// Only upload the logs written after this UNIX timestamp.
+    #[serde(rename = "since", skip_serializing_if = "Option::is_none")]
+    since: Option<i32>,
+    /// Only upload this number of lines from the end of the logs.
+    #[serde(rename = "tail", skip_serializing_if = "Option::is_none")]
+    tail: Option<String>,
+    /// Compress the logs with gzip before uploading them.
+    #[serde(rename = "gzip", skip_serializing_if = "Option::is_none")]
+    gzip: Option<bool>,
+}
+
+impl LogUpload {
+    pub fn new(sas_url: String) -> Self {
+        LogUpload {
+            sas_url,
+            since: None,
+            tail: None,
+            gzip: None,
+        }
+    }
+
+    pub fn set_sas_url(&mut self, sas_url: String) {
+        self.sas_url = sas_url;
+    }
+
+    pub fn with_sas_url(mut self, sas_url: String) -> Self {
+        self.sas_url = sas_url;
+        self
+    }
+
+    pub fn sas_url(&self) -> &String {
+        &self.sas_url
+    }
+
+    pub fn set_since(&mut self, since: i32) {
+        self.since = Some(since);
+    }
+
+    pub fn with_since(mut self, since: i32) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn since(&self) -> Option<i32> {
+        self.since
+    }
+
+    pub fn reset_since(&mut self) {
+        self.since = None;
+    }
+
+    pub fn set_tail(&mut self, tail: String) {
+        self.tail = Some(tail);
+    }
+
+    pub fn with_tail(mut self, tail: String) -> Self {
+        self.tail = Some(tail);
+        self
+    }
+
+    pub fn tail(&self) -> Option<&str> {
+        self.tail.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_tail(&mut self) {
+        self.tail = None;
+    }
+
+    pub fn set_gzip(&mut self, gzip: bool) {
+        self.gzip = Some(gzip);
+    }
+
+    pub fn with_gzip(mut self, gzip: bool) -> Self {
+        self.gzip = Some(gzip);
+        self
+    }
+
+    pub fn gzip(&self) -> Option<bool> {
+        self.gzip
+    }
+
+    pub fn reset_gzip(&mut self) {
+        self.gzip = None;
+    }
+}
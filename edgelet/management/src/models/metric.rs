@@ -0,0 +1,78 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Metric {
+    /// The name of the metric.
+    #[serde(rename = "name")]
+    name: String,
+    /// The value of the metric. Counters never go down across restarts.
+    #[serde(rename = "value")]
+    value: i64,
+    /// What the metric is about, for metrics that report one value per module or version.
+    #[serde(rename = "labels", skip_serializing_if = "Option::is_none")]
+    labels: Option<::std::collections::HashMap<String, String>>,
+}
+
+impl Metric {
+    pub fn new(name: String, value: i64) -> Self {
+        Metric {
+            name,
+            value,
+            labels: None,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_value(&mut self, value: i64) {
+        self.value = value;
+    }
+
+    pub fn with_value(mut self, value: i64) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    pub fn set_labels(&mut self, labels: ::std::collections::HashMap<String, String>) {
+        self.labels = Some(labels);
+    }
+
+    pub fn with_labels(mut self, labels: ::std::collections::HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    pub fn labels(&self) -> Option<&::std::collections::HashMap<String, String>> {
+        self.labels.as_ref()
+    }
+
+    pub fn reset_labels(&mut self) {
+        self.labels = None;
+    }
+}
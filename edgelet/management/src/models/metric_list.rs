@@ -0,0 +1,37 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetricList {
+    #[serde(rename = "metrics")]
+    metrics: Vec<::models::Metric>,
+}
+
+impl MetricList {
+    pub fn new(metrics: Vec<::models::Metric>) -> Self {
+        MetricList { metrics }
+    }
+
+    pub fn set_metrics(&mut self, metrics: Vec<::models::Metric>) {
+        self.metrics = metrics;
+    }
+
+    pub fn with_metrics(mut self, metrics: Vec<::models::Metric>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn metrics(&self) -> &[::models::Metric] {
+        &self.metrics
+    }
+}
@@ -1,11 +1,27 @@
+mod backup_request;
+pub use self::backup_request::BackupRequest;
+mod ca_rotation_status;
+pub use self::ca_rotation_status::CaRotationStatus;
 mod config;
 pub use self::config::Config;
+mod deployment_plan;
+pub use self::deployment_plan::DeploymentPlan;
+mod deployment_request;
+pub use self::deployment_request::DeploymentRequest;
+mod disk_usage;
+pub use self::disk_usage::DiskUsage;
 mod env_var;
 pub use self::env_var::EnvVar;
 mod error_response;
 pub use self::error_response::ErrorResponse;
 mod exit_status;
 pub use self::exit_status::ExitStatus;
+mod host_process;
+pub use self::host_process::HostProcess;
+mod host_process_list;
+pub use self::host_process_list::HostProcessList;
+mod host_process_spec;
+pub use self::host_process_spec::HostProcessSpec;
 mod identity;
 pub use self::identity::Identity;
 mod identity_list;
@@ -14,18 +30,62 @@ mod identity_spec;
 pub use self::identity_spec::IdentitySpec;
 mod update_identity;
 pub use self::update_identity::UpdateIdentity;
+mod issued_certificate;
+pub use self::issued_certificate::IssuedCertificate;
+mod issued_certificate_list;
+pub use self::issued_certificate_list::IssuedCertificateList;
+mod key_alias;
+pub use self::key_alias::KeyAlias;
+mod key_alias_list;
+pub use self::key_alias_list::KeyAliasList;
+mod key_cleanup;
+pub use self::key_cleanup::KeyCleanup;
+mod key_slot_usage;
+pub use self::key_slot_usage::KeySlotUsage;
+mod key_slot_usage_list;
+pub use self::key_slot_usage_list::KeySlotUsageList;
+mod log_sampling_list;
+pub use self::log_sampling_list::LogSamplingList;
+mod log_sampling_rule;
+pub use self::log_sampling_rule::LogSamplingRule;
+mod log_upload;
+pub use self::log_upload::LogUpload;
+mod metric;
+pub use self::metric::Metric;
+mod metric_list;
+pub use self::metric_list::MetricList;
 mod module_details;
 pub use self::module_details::ModuleDetails;
+mod module_disk_usage;
+pub use self::module_disk_usage::ModuleDiskUsage;
 mod module_list;
 pub use self::module_list::ModuleList;
+mod module_removal;
+pub use self::module_removal::ModuleRemoval;
 mod module_spec;
 pub use self::module_spec::ModuleSpec;
+mod module_usage;
+pub use self::module_usage::ModuleUsage;
+mod operation;
+pub use self::operation::Operation;
+mod plan_step;
+pub use self::plan_step::PlanStep;
+mod restore_request;
+pub use self::restore_request::RestoreRequest;
+mod restore_result;
+pub use self::restore_result::RestoreResult;
 mod runtime_status;
 pub use self::runtime_status::RuntimeStatus;
+mod schedule;
+pub use self::schedule::Schedule;
+mod schedule_list;
+pub use self::schedule_list::ScheduleList;
 mod status;
 pub use self::status::Status;
 mod system_info;
 pub use self::system_info::SystemInfo;
+mod volume_disk_usage;
+pub use self::volume_disk_usage::VolumeDiskUsage;
 
 // TODO(farcaller): sort out files
 pub struct File;
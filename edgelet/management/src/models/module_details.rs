@@ -26,6 +26,12 @@ pub struct ModuleDetails {
     config: ::models::Config,
     #[serde(rename = "status")]
     status: ::models::Status,
+    /// The labels the module was created with.
+    #[serde(rename = "labels", skip_serializing_if = "Option::is_none")]
+    labels: Option<::std::collections::HashMap<String, String>>,
+    /// The version of the deployment the module config came from.
+    #[serde(rename = "deployment", skip_serializing_if = "Option::is_none")]
+    deployment: Option<String>,
 }
 
 impl ModuleDetails {
@@ -42,6 +48,8 @@ impl ModuleDetails {
             type_,
             config,
             status,
+            labels: None,
+            deployment: None,
         }
     }
 
@@ -109,4 +117,38 @@ impl ModuleDetails {
     pub fn status(&self) -> &::models::Status {
         &self.status
     }
+
+    pub fn set_labels(&mut self, labels: ::std::collections::HashMap<String, String>) {
+        self.labels = Some(labels);
+    }
+
+    pub fn with_labels(mut self, labels: ::std::collections::HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    pub fn labels(&self) -> Option<&::std::collections::HashMap<String, String>> {
+        self.labels.as_ref()
+    }
+
+    pub fn reset_labels(&mut self) {
+        self.labels = None;
+    }
+
+    pub fn set_deployment(&mut self, deployment: String) {
+        self.deployment = Some(deployment);
+    }
+
+    pub fn with_deployment(mut self, deployment: String) -> Self {
+        self.deployment = Some(deployment);
+        self
+    }
+
+    pub fn deployment(&self) -> Option<&str> {
+        self.deployment.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_deployment(&mut self) {
+        self.deployment = None;
+    }
 }
@@ -0,0 +1,78 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModuleDiskUsage {
+    /// The name of the module.
+    #[serde(rename = "name")]
+    name: String,
+    /// The size of the writable layer of the container of the module, in bytes.
+    #[serde(rename = "writableLayer")]
+    writable_layer: i64,
+    /// The most the writable layer may grow to, in bytes, if the create options of the module set a quota.
+    #[serde(rename = "quota", skip_serializing_if = "Option::is_none")]
+    quota: Option<i64>,
+}
+
+impl ModuleDiskUsage {
+    pub fn new(name: String, writable_layer: i64) -> Self {
+        ModuleDiskUsage {
+            name,
+            writable_layer,
+            quota: None,
+        }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_writable_layer(&mut self, writable_layer: i64) {
+        self.writable_layer = writable_layer;
+    }
+
+    pub fn with_writable_layer(mut self, writable_layer: i64) -> Self {
+        self.writable_layer = writable_layer;
+        self
+    }
+
+    pub fn writable_layer(&self) -> i64 {
+        self.writable_layer
+    }
+
+    pub fn set_quota(&mut self, quota: i64) {
+        self.quota = Some(quota);
+    }
+
+    pub fn with_quota(mut self, quota: i64) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    pub fn quota(&self) -> Option<i64> {
+        self.quota
+    }
+
+    pub fn reset_quota(&mut self) {
+        self.quota = None;
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModuleRemoval {
+    /// Whether the anonymous volumes of the module were removed.
+    #[serde(rename = "volumes")]
+    volumes: bool,
+    /// Whether the identity of the module was deleted.
+    #[serde(rename = "identity")]
+    identity: bool,
+    /// The aliases of the certificates destroyed in the HSM.
+    #[serde(rename = "keys")]
+    keys: Vec<String>,
+    /// The serial numbers of the certificates revoked.
+    #[serde(rename = "certificates")]
+    certificates: Vec<String>,
+}
+
+impl ModuleRemoval {
+    pub fn new(
+        volumes: bool,
+        identity: bool,
+        keys: Vec<String>,
+        certificates: Vec<String>,
+    ) -> Self {
+        ModuleRemoval {
+            volumes,
+            identity,
+            keys,
+            certificates,
+        }
+    }
+
+    pub fn set_volumes(&mut self, volumes: bool) {
+        self.volumes = volumes;
+    }
+
+    pub fn with_volumes(mut self, volumes: bool) -> Self {
+        self.volumes = volumes;
+        self
+    }
+
+    pub fn volumes(&self) -> bool {
+        self.volumes
+    }
+
+    pub fn set_identity(&mut self, identity: bool) {
+        self.identity = identity;
+    }
+
+    pub fn with_identity(mut self, identity: bool) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    pub fn identity(&self) -> bool {
+        self.identity
+    }
+
+    pub fn set_keys(&mut self, keys: Vec<String>) {
+        self.keys = keys;
+    }
+
+    pub fn with_keys(mut self, keys: Vec<String>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub fn set_certificates(&mut self, certificates: Vec<String>) {
+        self.certificates = certificates;
+    }
+
+    pub fn with_certificates(mut self, certificates: Vec<String>) -> Self {
+        self.certificates = certificates;
+        self
+    }
+
+    pub fn certificates(&self) -> &Vec<String> {
+        &self.certificates
+    }
+}
@@ -11,7 +11,7 @@
 #[allow(unused_imports)]
 use serde_json::Value;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ModuleSpec {
     /// The name of a the module.
     #[serde(rename = "name")]
@@ -20,6 +20,22 @@ pub struct ModuleSpec {
     type_: String,
     #[serde(rename = "config")]
     config: ::models::Config,
+    /// The priority of the module when the device runs short of resources.
+    #[serde(rename = "priority", skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    /// The namespace of the module, which isolates it from the modules of other namespaces.
+    #[serde(rename = "namespace", skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    /// When the module is restarted.
+    #[serde(rename = "restartPolicy", skip_serializing_if = "Option::is_none")]
+    restart_policy: Option<String>,
+    /// Labels that tooling can group modules by, such as by solution or tier.
+    #[serde(rename = "labels", skip_serializing_if = "Option::is_none")]
+    labels: Option<::std::collections::HashMap<String, String>>,
+    /// The version of the deployment the module config came from, such as the
+    /// hash of the deployment manifest.
+    #[serde(rename = "deployment", skip_serializing_if = "Option::is_none")]
+    deployment: Option<String>,
 }
 
 impl ModuleSpec {
@@ -28,6 +44,11 @@ impl ModuleSpec {
             name,
             type_,
             config,
+            priority: None,
+            namespace: None,
+            restart_policy: None,
+            labels: None,
+            deployment: None,
         }
     }
 
@@ -69,4 +90,89 @@ impl ModuleSpec {
     pub fn config(&self) -> &::models::Config {
         &self.config
     }
+
+    pub fn set_priority(&mut self, priority: String) {
+        self.priority = Some(priority);
+    }
+
+    pub fn with_priority(mut self, priority: String) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn priority(&self) -> Option<&str> {
+        self.priority.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_priority(&mut self) {
+        self.priority = None;
+    }
+
+    pub fn set_namespace(&mut self, namespace: String) {
+        self.namespace = Some(namespace);
+    }
+
+    pub fn with_namespace(mut self, namespace: String) -> Self {
+        self.namespace = Some(namespace);
+        self
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_namespace(&mut self) {
+        self.namespace = None;
+    }
+
+    pub fn set_restart_policy(&mut self, restart_policy: String) {
+        self.restart_policy = Some(restart_policy);
+    }
+
+    pub fn with_restart_policy(mut self, restart_policy: String) -> Self {
+        self.restart_policy = Some(restart_policy);
+        self
+    }
+
+    pub fn restart_policy(&self) -> Option<&str> {
+        self.restart_policy.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_restart_policy(&mut self) {
+        self.restart_policy = None;
+    }
+
+    pub fn set_labels(&mut self, labels: ::std::collections::HashMap<String, String>) {
+        self.labels = Some(labels);
+    }
+
+    pub fn with_labels(mut self, labels: ::std::collections::HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    pub fn labels(&self) -> Option<&::std::collections::HashMap<String, String>> {
+        self.labels.as_ref()
+    }
+
+    pub fn reset_labels(&mut self) {
+        self.labels = None;
+    }
+
+    pub fn set_deployment(&mut self, deployment: String) {
+        self.deployment = Some(deployment);
+    }
+
+    pub fn with_deployment(mut self, deployment: String) -> Self {
+        self.deployment = Some(deployment);
+        self
+    }
+
+    pub fn deployment(&self) -> Option<&str> {
+        self.deployment.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_deployment(&mut self) {
+        self.deployment = None;
+    }
 }
@@ -0,0 +1,114 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModuleUsage {
+    /// The name of the module.
+    #[serde(rename = "moduleId")]
+    module_id: String,
+    /// The number of certificates issued to the module.
+    #[serde(rename = "certificates")]
+    certificates: i64,
+    /// The number of sign calls the module made.
+    #[serde(rename = "signs")]
+    signs: i64,
+    /// The number of encrypt calls the module made.
+    #[serde(rename = "encrypts")]
+    encrypts: i64,
+    /// The number of decrypt calls the module made.
+    #[serde(rename = "decrypts")]
+    decrypts: i64,
+}
+
+impl ModuleUsage {
+    pub fn new(
+        module_id: String,
+        certificates: i64,
+        signs: i64,
+        encrypts: i64,
+        decrypts: i64,
+    ) -> Self {
+        ModuleUsage {
+            module_id,
+            certificates,
+            signs,
+            encrypts,
+            decrypts,
+        }
+    }
+
+    pub fn set_module_id(&mut self, module_id: String) {
+        self.module_id = module_id;
+    }
+
+    pub fn with_module_id(mut self, module_id: String) -> Self {
+        self.module_id = module_id;
+        self
+    }
+
+    pub fn module_id(&self) -> &str {
+        &self.module_id
+    }
+
+    pub fn set_certificates(&mut self, certificates: i64) {
+        self.certificates = certificates;
+    }
+
+    pub fn with_certificates(mut self, certificates: i64) -> Self {
+        self.certificates = certificates;
+        self
+    }
+
+    pub fn certificates(&self) -> i64 {
+        self.certificates
+    }
+
+    pub fn set_signs(&mut self, signs: i64) {
+        self.signs = signs;
+    }
+
+    pub fn with_signs(mut self, signs: i64) -> Self {
+        self.signs = signs;
+        self
+    }
+
+    pub fn signs(&self) -> i64 {
+        self.signs
+    }
+
+    pub fn set_encrypts(&mut self, encrypts: i64) {
+        self.encrypts = encrypts;
+    }
+
+    pub fn with_encrypts(mut self, encrypts: i64) -> Self {
+        self.encrypts = encrypts;
+        self
+    }
+
+    pub fn encrypts(&self) -> i64 {
+        self.encrypts
+    }
+
+    pub fn set_decrypts(&mut self, decrypts: i64) {
+        self.decrypts = decrypts;
+    }
+
+    pub fn with_decrypts(mut self, decrypts: i64) -> Self {
+        self.decrypts = decrypts;
+        self
+    }
+
+    pub fn decrypts(&self) -> i64 {
+        self.decrypts
+    }
+}
@@ -0,0 +1,169 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Operation {
+    /// The ID the operation is polled with.
+    #[serde(rename = "id")]
+    id: String,
+    /// What the operation does: deployment or imagePull.
+    #[serde(rename = "kind")]
+    kind: String,
+    /// Where the operation is at: running, succeeded or failed.
+    #[serde(rename = "status")]
+    status: String,
+    /// How far along the operation is, from 0 to 100.
+    #[serde(rename = "percent")]
+    percent: i64,
+    /// What the operation is doing, or did last when it failed.
+    #[serde(rename = "step", skip_serializing_if = "Option::is_none")]
+    step: Option<String>,
+    /// Why the operation failed.
+    #[serde(rename = "error", skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(rename = "startedAt")]
+    started_at: String,
+    #[serde(rename = "finishedAt", skip_serializing_if = "Option::is_none")]
+    finished_at: Option<String>,
+}
+
+impl Operation {
+    pub fn new(id: String, kind: String, status: String, percent: i64, started_at: String) -> Self {
+        Operation {
+            id,
+            kind,
+            status,
+            percent,
+            step: None,
+            error: None,
+            started_at,
+            finished_at: None,
+        }
+    }
+
+    pub fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn set_kind(&mut self, kind: String) {
+        self.kind = kind;
+    }
+
+    pub fn with_kind(mut self, kind: String) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    pub fn with_status(mut self, status: String) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn set_percent(&mut self, percent: i64) {
+        self.percent = percent;
+    }
+
+    pub fn with_percent(mut self, percent: i64) -> Self {
+        self.percent = percent;
+        self
+    }
+
+    pub fn percent(&self) -> i64 {
+        self.percent
+    }
+
+    pub fn set_step(&mut self, step: String) {
+        self.step = Some(step);
+    }
+
+    pub fn with_step(mut self, step: String) -> Self {
+        self.step = Some(step);
+        self
+    }
+
+    pub fn step(&self) -> Option<&str> {
+        self.step.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_step(&mut self) {
+        self.step = None;
+    }
+
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_error(&mut self) {
+        self.error = None;
+    }
+
+    pub fn set_started_at(&mut self, started_at: String) {
+        self.started_at = started_at;
+    }
+
+    pub fn with_started_at(mut self, started_at: String) -> Self {
+        self.started_at = started_at;
+        self
+    }
+
+    pub fn started_at(&self) -> &str {
+        &self.started_at
+    }
+
+    pub fn set_finished_at(&mut self, finished_at: String) {
+        self.finished_at = Some(finished_at);
+    }
+
+    pub fn with_finished_at(mut self, finished_at: String) -> Self {
+        self.finished_at = Some(finished_at);
+        self
+    }
+
+    pub fn finished_at(&self) -> Option<&str> {
+        self.finished_at.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_finished_at(&mut self) {
+        self.finished_at = None;
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// What is done to the module: create, update or remove.
+    #[serde(rename = "action")]
+    action: String,
+    /// The name of the module.
+    #[serde(rename = "module")]
+    module: String,
+}
+
+impl PlanStep {
+    pub fn new(action: String, module: String) -> Self {
+        PlanStep { action, module }
+    }
+
+    pub fn set_action(&mut self, action: String) {
+        self.action = action;
+    }
+
+    pub fn with_action(mut self, action: String) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    pub fn set_module(&mut self, module: String) {
+        self.module = module;
+    }
+
+    pub fn with_module(mut self, module: String) -> Self {
+        self.module = module;
+        self
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+}
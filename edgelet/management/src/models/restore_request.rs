@@ -0,0 +1,54 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestoreRequest {
+    /// The passphrase the backup was encrypted with.
+    #[serde(rename = "passphrase")]
+    passphrase: String,
+    /// The base64 encoded backup.
+    #[serde(rename = "backup")]
+    backup: String,
+}
+
+impl RestoreRequest {
+    pub fn new(passphrase: String, backup: String) -> Self {
+        RestoreRequest { passphrase, backup }
+    }
+
+    pub fn set_passphrase(&mut self, passphrase: String) {
+        self.passphrase = passphrase;
+    }
+
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase = passphrase;
+        self
+    }
+
+    pub fn passphrase(&self) -> &String {
+        &self.passphrase
+    }
+
+    pub fn set_backup(&mut self, backup: String) {
+        self.backup = backup;
+    }
+
+    pub fn with_backup(mut self, backup: String) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    pub fn backup(&self) -> &String {
+        &self.backup
+    }
+}
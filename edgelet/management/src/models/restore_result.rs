@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestoreResult {
+    /// The names of the files that were restored.
+    #[serde(rename = "restored")]
+    restored: Vec<String>,
+}
+
+impl RestoreResult {
+    pub fn new(restored: Vec<String>) -> Self {
+        RestoreResult { restored }
+    }
+
+    pub fn set_restored(&mut self, restored: Vec<String>) {
+        self.restored = restored;
+    }
+
+    pub fn with_restored(mut self, restored: Vec<String>) -> Self {
+        self.restored = restored;
+        self
+    }
+
+    pub fn restored(&self) -> &Vec<String> {
+        &self.restored
+    }
+}
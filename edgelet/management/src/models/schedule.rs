@@ -0,0 +1,94 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    /// The name of the module the action applies to.
+    #[serde(rename = "module")]
+    module: String,
+    /// The action to perform on the module.
+    #[serde(rename = "action")]
+    action: String,
+    /// The cron expression that determines when the action runs.
+    #[serde(rename = "schedule")]
+    schedule: String,
+    #[serde(rename = "nextRun", skip_serializing_if = "Option::is_none")]
+    next_run: Option<String>,
+}
+
+impl Schedule {
+    pub fn new(module: String, action: String, schedule: String) -> Self {
+        Schedule {
+            module,
+            action,
+            schedule,
+            next_run: None,
+        }
+    }
+
+    pub fn set_module(&mut self, module: String) {
+        self.module = module;
+    }
+
+    pub fn with_module(mut self, module: String) -> Self {
+        self.module = module;
+        self
+    }
+
+    pub fn module(&self) -> &String {
+        &self.module
+    }
+
+    pub fn set_action(&mut self, action: String) {
+        self.action = action;
+    }
+
+    pub fn with_action(mut self, action: String) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn action(&self) -> &String {
+        &self.action
+    }
+
+    pub fn set_schedule(&mut self, schedule: String) {
+        self.schedule = schedule;
+    }
+
+    pub fn with_schedule(mut self, schedule: String) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    pub fn schedule(&self) -> &String {
+        &self.schedule
+    }
+
+    pub fn set_next_run(&mut self, next_run: String) {
+        self.next_run = Some(next_run);
+    }
+
+    pub fn with_next_run(mut self, next_run: String) -> Self {
+        self.next_run = Some(next_run);
+        self
+    }
+
+    pub fn next_run(&self) -> Option<&str> {
+        self.next_run.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_next_run(&mut self) {
+        self.next_run = None;
+    }
+}
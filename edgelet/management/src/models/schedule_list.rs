@@ -0,0 +1,37 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleList {
+    #[serde(rename = "schedules")]
+    schedules: Vec<::models::Schedule>,
+}
+
+impl ScheduleList {
+    pub fn new(schedules: Vec<::models::Schedule>) -> Self {
+        ScheduleList { schedules }
+    }
+
+    pub fn set_schedules(&mut self, schedules: Vec<::models::Schedule>) {
+        self.schedules = schedules;
+    }
+
+    pub fn with_schedules(mut self, schedules: Vec<::models::Schedule>) -> Self {
+        self.schedules = schedules;
+        self
+    }
+
+    pub fn schedules(&self) -> &[::models::Schedule] {
+        &self.schedules
+    }
+}
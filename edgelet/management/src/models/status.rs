@@ -25,6 +25,12 @@ pub struct Status {
     exit_status: Option<::models::ExitStatus>,
     #[serde(rename = "runtimeStatus")]
     runtime_status: ::models::RuntimeStatus,
+    /// How the restart policy the runtime has for the module differs from the one the module was created with.
+    #[serde(
+        rename = "restartPolicyDrift",
+        skip_serializing_if = "Option::is_none"
+    )]
+    restart_policy_drift: Option<String>,
 }
 
 impl Status {
@@ -33,6 +39,7 @@ impl Status {
             start_time: None,
             exit_status: None,
             runtime_status,
+            restart_policy_drift: None,
         }
     }
 
@@ -82,4 +89,21 @@ impl Status {
     pub fn runtime_status(&self) -> &::models::RuntimeStatus {
         &self.runtime_status
     }
+
+    pub fn set_restart_policy_drift(&mut self, restart_policy_drift: String) {
+        self.restart_policy_drift = Some(restart_policy_drift);
+    }
+
+    pub fn with_restart_policy_drift(mut self, restart_policy_drift: String) -> Self {
+        self.restart_policy_drift = Some(restart_policy_drift);
+        self
+    }
+
+    pub fn restart_policy_drift(&self) -> Option<&str> {
+        self.restart_policy_drift.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_restart_policy_drift(&mut self) {
+        self.restart_policy_drift = None;
+    }
 }
@@ -19,6 +19,36 @@ pub struct SystemInfo {
     architecture: String,
     #[serde(rename = "version")]
     version: String,
+    /// Name and version of the host operating system.
+    #[serde(rename = "operatingSystem", skip_serializing_if = "Option::is_none")]
+    operating_system: Option<String>,
+    /// Version of the kernel of the host.
+    #[serde(rename = "kernelVersion", skip_serializing_if = "Option::is_none")]
+    kernel_version: Option<String>,
+    /// Physical memory of the host, in bytes.
+    #[serde(rename = "totalMemory", skip_serializing_if = "Option::is_none")]
+    total_memory: Option<i64>,
+    /// Size of the volume holding the home directory of the daemon, in bytes.
+    #[serde(rename = "totalDisk", skip_serializing_if = "Option::is_none")]
+    total_disk: Option<i64>,
+    /// Version of the container runtime.
+    #[serde(rename = "runtimeVersion", skip_serializing_if = "Option::is_none")]
+    runtime_version: Option<String>,
+    /// Version of libiothsm.
+    #[serde(rename = "hsmVersion", skip_serializing_if = "Option::is_none")]
+    hsm_version: Option<String>,
+    /// Commit the daemon was built from.
+    #[serde(rename = "buildCommit", skip_serializing_if = "Option::is_none")]
+    build_commit: Option<String>,
+    /// Version of the compiler the daemon was built with.
+    #[serde(rename = "rustcVersion", skip_serializing_if = "Option::is_none")]
+    rustc_version: Option<String>,
+    /// Whether the device is set up to run without IoT Hub.
+    #[serde(rename = "offline", skip_serializing_if = "Option::is_none")]
+    offline: Option<bool>,
+    /// The versions of the deployments the configs of the modules came from.
+    #[serde(rename = "deployments", skip_serializing_if = "Option::is_none")]
+    deployments: Option<Vec<String>>,
 }
 
 impl SystemInfo {
@@ -27,6 +57,16 @@ impl SystemInfo {
             os_type,
             architecture,
             version,
+            operating_system: None,
+            kernel_version: None,
+            total_memory: None,
+            total_disk: None,
+            runtime_version: None,
+            hsm_version: None,
+            build_commit: None,
+            rustc_version: None,
+            offline: None,
+            deployments: None,
         }
     }
 
@@ -68,4 +108,174 @@ impl SystemInfo {
     pub fn version(&self) -> &String {
         &self.version
     }
+
+    pub fn set_operating_system(&mut self, operating_system: String) {
+        self.operating_system = Some(operating_system);
+    }
+
+    pub fn with_operating_system(mut self, operating_system: String) -> Self {
+        self.operating_system = Some(operating_system);
+        self
+    }
+
+    pub fn operating_system(&self) -> Option<&str> {
+        self.operating_system.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_operating_system(&mut self) {
+        self.operating_system = None;
+    }
+
+    pub fn set_kernel_version(&mut self, kernel_version: String) {
+        self.kernel_version = Some(kernel_version);
+    }
+
+    pub fn with_kernel_version(mut self, kernel_version: String) -> Self {
+        self.kernel_version = Some(kernel_version);
+        self
+    }
+
+    pub fn kernel_version(&self) -> Option<&str> {
+        self.kernel_version.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_kernel_version(&mut self) {
+        self.kernel_version = None;
+    }
+
+    pub fn set_total_memory(&mut self, total_memory: i64) {
+        self.total_memory = Some(total_memory);
+    }
+
+    pub fn with_total_memory(mut self, total_memory: i64) -> Self {
+        self.total_memory = Some(total_memory);
+        self
+    }
+
+    pub fn total_memory(&self) -> Option<&i64> {
+        self.total_memory.as_ref()
+    }
+
+    pub fn reset_total_memory(&mut self) {
+        self.total_memory = None;
+    }
+
+    pub fn set_total_disk(&mut self, total_disk: i64) {
+        self.total_disk = Some(total_disk);
+    }
+
+    pub fn with_total_disk(mut self, total_disk: i64) -> Self {
+        self.total_disk = Some(total_disk);
+        self
+    }
+
+    pub fn total_disk(&self) -> Option<&i64> {
+        self.total_disk.as_ref()
+    }
+
+    pub fn reset_total_disk(&mut self) {
+        self.total_disk = None;
+    }
+
+    pub fn set_runtime_version(&mut self, runtime_version: String) {
+        self.runtime_version = Some(runtime_version);
+    }
+
+    pub fn with_runtime_version(mut self, runtime_version: String) -> Self {
+        self.runtime_version = Some(runtime_version);
+        self
+    }
+
+    pub fn runtime_version(&self) -> Option<&str> {
+        self.runtime_version.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_runtime_version(&mut self) {
+        self.runtime_version = None;
+    }
+
+    pub fn set_hsm_version(&mut self, hsm_version: String) {
+        self.hsm_version = Some(hsm_version);
+    }
+
+    pub fn with_hsm_version(mut self, hsm_version: String) -> Self {
+        self.hsm_version = Some(hsm_version);
+        self
+    }
+
+    pub fn hsm_version(&self) -> Option<&str> {
+        self.hsm_version.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_hsm_version(&mut self) {
+        self.hsm_version = None;
+    }
+
+    pub fn set_build_commit(&mut self, build_commit: String) {
+        self.build_commit = Some(build_commit);
+    }
+
+    pub fn with_build_commit(mut self, build_commit: String) -> Self {
+        self.build_commit = Some(build_commit);
+        self
+    }
+
+    pub fn build_commit(&self) -> Option<&str> {
+        self.build_commit.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_build_commit(&mut self) {
+        self.build_commit = None;
+    }
+
+    pub fn set_rustc_version(&mut self, rustc_version: String) {
+        self.rustc_version = Some(rustc_version);
+    }
+
+    pub fn with_rustc_version(mut self, rustc_version: String) -> Self {
+        self.rustc_version = Some(rustc_version);
+        self
+    }
+
+    pub fn rustc_version(&self) -> Option<&str> {
+        self.rustc_version.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_rustc_version(&mut self) {
+        self.rustc_version = None;
+    }
+
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = Some(offline);
+    }
+
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = Some(offline);
+        self
+    }
+
+    pub fn offline(&self) -> Option<bool> {
+        self.offline
+    }
+
+    pub fn reset_offline(&mut self) {
+        self.offline = None;
+    }
+
+    pub fn set_deployments(&mut self, deployments: Vec<String>) {
+        self.deployments = Some(deployments);
+    }
+
+    pub fn with_deployments(mut self, deployments: Vec<String>) -> Self {
+        self.deployments = Some(deployments);
+        self
+    }
+
+    pub fn deployments(&self) -> Option<&[String]> {
+        self.deployments.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_deployments(&mut self) {
+        self.deployments = None;
+    }
 }
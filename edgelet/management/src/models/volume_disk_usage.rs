@@ -0,0 +1,58 @@
+/*
+ * IoT Edge Management API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VolumeDiskUsage {
+    /// The name of the volume.
+    #[serde(rename = "name")]
+    name: String,
+    /// The size of the volume in bytes, if its driver reports it.
+    #[serde(rename = "size", skip_serializing_if = "Option::is_none")]
+    size: Option<i64>,
+}
+
+impl VolumeDiskUsage {
+    pub fn new(name: String) -> Self {
+        VolumeDiskUsage { name, size: None }
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_size(&mut self, size: i64) {
+        self.size = Some(size);
+    }
+
+    pub fn with_size(mut self, size: i64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    pub fn reset_size(&mut self) {
+        self.size = None;
+    }
+}
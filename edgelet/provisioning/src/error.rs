@@ -11,8 +11,9 @@ use regex::Error as RegexError;
 use serde_json::Error as SerdeError;
 
 use dps::{Error as DpsError, ErrorKind as DpsErrorKind};
-use edgelet_core::{Error as CoreError, ErrorKind as CoreErrorKind};
-use edgelet_http::Error as HttpError;
+use edgelet_core::{error_category, Error as CoreError, ErrorCategory, ErrorKind as CoreErrorKind};
+use edgelet_hsm::Error as HsmError;
+use edgelet_http::{classify_error as classify_http_error, Error as HttpError};
 use edgelet_utils::Error as UtilsError;
 
 #[derive(Debug)]
@@ -46,6 +47,37 @@ pub enum ErrorKind {
     Serde,
 }
 
+impl ErrorKind {
+    /// The category of errors of this kind, or `None` for the kinds that
+    /// leave it to their cause.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match *self {
+            ErrorKind::Hsm => Some(ErrorCategory::Hsm),
+            ErrorKind::Regex | ErrorKind::Base64 => Some(ErrorCategory::UserConfig),
+            ErrorKind::Dps
+            | ErrorKind::Utils
+            | ErrorKind::NotFound
+            | ErrorKind::Provision(_)
+            | ErrorKind::Core
+            | ErrorKind::Http
+            | ErrorKind::Io
+            | ErrorKind::Serde => None,
+        }
+    }
+}
+
+/// Categorizes the errors of this crate and of the HSM, and falls back to
+/// `edgelet_http::classify_error`.
+pub fn classify_error(error: &Fail) -> Option<ErrorCategory> {
+    if let Some(error) = error.downcast_ref::<Error>() {
+        error.kind().category()
+    } else if error.downcast_ref::<HsmError>().is_some() {
+        Some(ErrorCategory::Hsm)
+    } else {
+        classify_http_error(error)
+    }
+}
+
 impl Fail for Error {
     fn cause(&self) -> Option<&Fail> {
         self.inner.cause()
@@ -66,6 +98,10 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         self.inner.get_context()
     }
+
+    pub fn category(&self) -> ErrorCategory {
+        error_category(self, classify_error)
+    }
 }
 
 impl From<ErrorKind> for Error {
@@ -37,5 +37,5 @@ extern crate edgelet_utils;
 pub mod error;
 pub mod provisioning;
 
-pub use error::Error;
+pub use error::{classify_error, Error};
 pub use provisioning::{BackupProvisioning, DpsProvisioning, Provision, ProvisioningResult};
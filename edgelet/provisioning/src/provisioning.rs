@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::time::Duration;
 
 use base64;
 use bytes::Bytes;
@@ -11,11 +12,13 @@ use bytes::Bytes;
 use futures::future::Either;
 use futures::{future, Future};
 use regex::RegexSet;
-use serde_json;
+use serde_json::{self, Value};
 use url::Url;
 
 use dps::registration::{DpsClient, DpsTokenSource};
+use dps::{Error as DpsError, ErrorKind as DpsErrorKind};
 use edgelet_core::crypto::{Activate, KeyIdentity, KeyStore, MemoryKey, MemoryKeyStore};
+use edgelet_core::{CancellationToken, Retry, RetryPolicy};
 use edgelet_hsm::tpm::{TpmKey, TpmKeyStore};
 use edgelet_http::client::{Client as HttpClient, ClientImpl};
 use edgelet_utils::log_failure;
@@ -31,12 +34,19 @@ const DEVICEID_REGEX: &str = r"DeviceId=([A-Za-z0-9\-:.+%_#*?!(),=@;$']{1,128})"
 const HOSTNAME_REGEX: &str = r"HostName=([a-zA-Z0-9_\-\.]+)";
 const SHAREDACCESSKEY_REGEX: &str = r"SharedAccessKey=(.+)";
 
+/// Number of times DPS registration is attempted before provisioning gives up
+const DPS_REGISTRATION_MAX_ATTEMPTS: u32 = 5;
+const DPS_REGISTRATION_INITIAL_BACKOFF_SECS: u64 = 5;
+const DPS_REGISTRATION_MAX_BACKOFF_SECS: u64 = 60;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ProvisioningResult {
     device_id: String,
     hub_name: String,
     #[serde(skip)]
     reconfigure: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payload: Option<Value>,
 }
 
 impl ProvisioningResult {
@@ -48,6 +58,12 @@ impl ProvisioningResult {
         &self.hub_name
     }
 
+    /// The payload a custom allocation policy returned along with the
+    /// assignment, if any.
+    pub fn payload(&self) -> Option<&Value> {
+        self.payload.as_ref()
+    }
+
     pub fn reconfigure(&self) -> bool {
         self.reconfigure
     }
@@ -140,6 +156,7 @@ impl Provision for ManualProvisioning {
                 device_id,
                 hub_name: hub,
                 reconfigure: false,
+                payload: None,
             }).map_err(Error::from);
         Box::new(future::result(result))
     }
@@ -154,6 +171,8 @@ where
     registration_id: String,
     hsm_tpm_ek: HsmTpmKey,
     hsm_tpm_srk: HsmTpmKey,
+    cancellation: CancellationToken,
+    payload: Option<Value>,
 }
 
 impl<C> DpsProvisioning<C>
@@ -182,9 +201,25 @@ where
             registration_id,
             hsm_tpm_ek,
             hsm_tpm_srk,
+            cancellation: CancellationToken::new(),
+            payload: None,
         };
         Ok(result)
     }
+
+    /// Sends `payload` with the registration, for a custom allocation policy
+    /// to act on.
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    /// Gives up on registering, along with the retries, once `cancellation`
+    /// is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
 }
 
 impl<C> Provision for DpsProvisioning<C>
@@ -205,9 +240,22 @@ where
             Bytes::from(self.hsm_tpm_srk.as_ref()),
             key_activator,
         ) {
-            Ok(c) => Either::A(
-                c.register()
-                    .map(|(device_id, hub_name)| {
+            Ok(c) => {
+                let c = match self.payload {
+                    Some(payload) => c.with_payload(payload),
+                    None => c,
+                };
+                let register = Retry::new(dps_retry_policy(), move |_| c.register())
+                    .with_condition(|err: &DpsError| match *err.kind() {
+                        DpsErrorKind::Http | DpsErrorKind::TimerError => true,
+                        _ => false,
+                    }).with_hook(|attempt, err, delay| {
+                        warn!(
+                            "DPS registration attempt {} failed, retrying in {:?}",
+                            attempt, delay
+                        );
+                        log_failure(Level::Warn, err);
+                    }).map(|(device_id, hub_name, payload)| {
                         info!(
                             "DPS registration assigned device \"{}\" in hub \"{}\"",
                             device_id, hub_name
@@ -216,9 +264,11 @@ where
                             device_id,
                             hub_name,
                             reconfigure: false,
+                            payload,
                         }
-                    }).map_err(Error::from),
-            ),
+                    }).map_err(Error::from);
+                Either::A(self.cancellation.guard(register))
+            }
             Err(err) => Either::B(future::err(Error::from(err))),
         };
 
@@ -226,12 +276,20 @@ where
     }
 }
 
+fn dps_retry_policy() -> RetryPolicy {
+    RetryPolicy::exponential(
+        Duration::from_secs(DPS_REGISTRATION_INITIAL_BACKOFF_SECS),
+        Duration::from_secs(DPS_REGISTRATION_MAX_BACKOFF_SECS),
+    ).with_max_attempts(DPS_REGISTRATION_MAX_ATTEMPTS)
+}
+
 pub struct BackupProvisioning<P>
 where
     P: 'static + Provision,
 {
     underlying: P,
     path: PathBuf,
+    offline: bool,
 }
 
 impl<P> BackupProvisioning<P>
@@ -242,9 +300,18 @@ where
         BackupProvisioning {
             underlying: provisioner,
             path,
+            offline: false,
         }
     }
 
+    /// Restores the credentials from the backup without provisioning first
+    /// when `offline`, for devices that can't reach the provisioning service
+    /// by design.
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
     fn backup(prov_result: &ProvisioningResult, path: PathBuf) -> Result<(), Error> {
         // create a file if it doesn't exist, else open it for writing
         let mut file = File::create(path)?;
@@ -277,6 +344,11 @@ where
         self,
         key_activator: Self::Hsm,
     ) -> Box<Future<Item = ProvisioningResult, Error = Error> + Send> {
+        if self.offline {
+            info!("Device is offline, skipping provisioning");
+            return Box::new(future::result(Self::restore(self.path)));
+        }
+
         let path = self.path.clone();
         let path_on_err = self.path.clone();
         Box::new(
@@ -321,6 +393,7 @@ mod tests {
                 device_id: "TestDevice".to_string(),
                 hub_name: "TestHub".to_string(),
                 reconfigure: false,
+                payload: serde_json::from_str(r#"{ "site": "plant-1" }"#).ok(),
             }))
         }
     }
@@ -472,6 +545,12 @@ mod tests {
                 let prov_result = result.expect("Unexpected");
                 assert_eq!(prov_result.device_id(), "TestDevice");
                 assert_eq!(prov_result.hub_name(), "TestHub");
+                assert_eq!(
+                    Some("plant-1"),
+                    prov_result
+                        .payload()
+                        .and_then(|payload| payload["site"].as_str())
+                );
                 Ok::<_, Error>(())
             });
         tokio::runtime::current_thread::Runtime::new()
@@ -510,12 +589,39 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn offline_restores_without_provisioning() {
+        let tmp_dir = TempDir::new("backup").unwrap();
+        let file_path = tmp_dir.path().join("dps_backup.json");
+
+        // nothing was backed up, so there is nothing to restore even though
+        // provisioning would have worked
+        let offline = BackupProvisioning::new(TestProvisioning {}, file_path.clone())
+            .with_offline(true)
+            .provision(MemoryKeyStore::new());
+        let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+        assert!(runtime.block_on(offline).is_err());
+
+        let online = BackupProvisioning::new(TestProvisioning {}, file_path.clone())
+            .provision(MemoryKeyStore::new());
+        runtime.block_on(online).unwrap();
+
+        let offline = BackupProvisioning::new(TestProvisioningWithError {}, file_path)
+            .with_offline(true)
+            .provision(MemoryKeyStore::new());
+        let prov_result = runtime.block_on(offline).unwrap();
+        assert_eq!(prov_result.device_id(), "TestDevice");
+        assert_eq!(prov_result.hub_name(), "TestHub");
+        assert!(!prov_result.reconfigure());
+    }
+
     #[test]
     fn prov_result_serialize_skips_reconfigure_flag() {
         let json = serde_json::to_string(&ProvisioningResult {
             device_id: "something".to_string(),
             hub_name: "something".to_string(),
             reconfigure: true,
+            payload: None,
         }).unwrap();
         assert_eq!(
             "{\"device_id\":\"something\",\"hub_name\":\"something\"}",
@@ -30,6 +30,8 @@ pub enum ErrorKind {
     Nix,
     #[fail(display = "File descriptor not found.")]
     NotFound,
+    #[fail(display = "Inherited socket is unusable: {}.", _0)]
+    Unusable(&'static str),
 }
 
 impl Fail for Error {
@@ -21,8 +21,13 @@ extern crate lazy_static;
 extern crate log;
 #[cfg(target_os = "linux")]
 extern crate nix;
+#[cfg(target_os = "linux")]
+#[cfg(test)]
+extern crate tempdir;
 
 use std::net::SocketAddr;
+#[cfg(not(target_os = "linux"))]
+use std::path::PathBuf;
 
 mod error;
 #[cfg(target_os = "linux")]
@@ -40,10 +45,10 @@ pub enum Socket {
 }
 
 #[cfg(target_os = "linux")]
-pub use self::linux::{listener, listener_name, listeners_name};
+pub use self::linux::{check, listener, listener_name, listeners_name, unix_path};
 
 #[cfg(not(target_os = "linux"))]
-pub use self::other::{listener, listener_name, listeners_name};
+pub use self::other::{check, listener, listener_name, listeners_name, unix_path};
 
 #[cfg(not(target_os = "linux"))]
 mod other {
@@ -60,4 +65,12 @@ mod other {
     pub fn listeners_name(_name: &str) -> Result<Vec<Socket>, Error> {
         Err(Error::from(ErrorKind::NotFound))
     }
+
+    pub fn check(_socket: &Socket) -> Result<(), Error> {
+        Err(Error::from(ErrorKind::NotFound))
+    }
+
+    pub fn unix_path(_fd: Fd) -> Result<Option<PathBuf>, Error> {
+        Err(Error::from(ErrorKind::NotFound))
+    }
 }
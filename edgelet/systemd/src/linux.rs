@@ -7,9 +7,13 @@
 
 use std::collections::{hash_map, HashMap};
 use std::env;
+use std::fs;
 use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::PathBuf;
 
 use nix::fcntl;
+use nix::poll::{self, EventFlags, PollFd};
 use nix::sys::socket::{self, AddressFamily, SockType};
 use nix::sys::stat;
 use nix::unistd::{getpid, Pid};
@@ -52,6 +56,59 @@ pub fn listeners_name(name: &str) -> Result<Vec<Socket>, Error> {
     })
 }
 
+/// Checks that a socket inherited from systemd can still be listened on.
+///
+/// When systemd restarts a daemon that crashed, the sockets it hands over may
+/// have been closed, shut down or left in error by the previous run, or the
+/// file of a Unix domain socket may have been removed, so that nothing can
+/// connect to it anymore. The socket is polled without waiting, so that a
+/// hang up or an error shows without accepting a connection.
+pub fn check(socket: &Socket) -> Result<(), Error> {
+    let fd = match *socket {
+        Socket::Inet(fd, _) | Socket::Unix(fd) => fd,
+        Socket::Unknown => return Err(Error::from(ErrorKind::Unusable("not a socket"))),
+    };
+
+    if !is_socket_internal(fd, None, None)? {
+        return Err(Error::from(ErrorKind::Unusable("not a socket")));
+    }
+    if !socket::getsockopt(fd, socket::sockopt::AcceptConn)? {
+        return Err(Error::from(ErrorKind::Unusable("not listening")));
+    }
+    if socket::getsockopt(fd, socket::sockopt::SocketError)? != 0 {
+        return Err(Error::from(ErrorKind::Unusable("pending error")));
+    }
+
+    let mut fds = [PollFd::new(fd, EventFlags::POLLIN)];
+    poll::poll(&mut fds, 0)?;
+    let hung_up = EventFlags::POLLERR | EventFlags::POLLHUP | EventFlags::POLLNVAL;
+    let events = fds[0].revents().unwrap_or_else(EventFlags::empty);
+    if events.intersects(hung_up) {
+        return Err(Error::from(ErrorKind::Unusable("hung up")));
+    }
+
+    if let Socket::Unix(fd) = *socket {
+        if let Some(path) = unix_path(fd)? {
+            let is_socket = fs::symlink_metadata(&path)
+                .map(|metadata| metadata.file_type().is_socket())
+                .unwrap_or(false);
+            if !is_socket {
+                return Err(Error::from(ErrorKind::Unusable("socket file is gone")));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path a Unix domain socket is bound to, or `None` for an
+/// unnamed or abstract socket.
+pub fn unix_path(fd: Fd) -> Result<Option<PathBuf>, Error> {
+    match socket::getsockname(fd)? {
+        socket::SockAddr::Unix(addr) => Ok(addr.path().map(|path| path.to_path_buf())),
+        _ => Ok(None),
+    }
+}
+
 fn unsetenv_all() {
     env::remove_var(ENV_PID);
     env::remove_var(ENV_FDS);
@@ -215,10 +272,13 @@ fn is_socket_unix(
 mod tests {
     use super::*;
 
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixListener;
     use std::panic;
     use std::sync::{Mutex, MutexGuard};
 
     use nix::unistd;
+    use tempdir::TempDir;
 
     lazy_static! {
         static ref LOCK: Mutex<()> = Mutex::new(());
@@ -282,6 +342,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn listening_unix_socket_passes_check() {
+        let _l = lock_env();
+        let dir = TempDir::new("systemd").unwrap();
+        let path = dir.path().join("check.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let socket = Socket::Unix(listener.as_raw_fd());
+
+        check(&socket).unwrap();
+        assert_eq!(Some(path.clone()), unix_path(listener.as_raw_fd()).unwrap());
+
+        // nothing can connect to a socket whose file was removed
+        fs::remove_file(&path).unwrap();
+        match *check(&socket).unwrap_err().kind() {
+            ErrorKind::Unusable(_) => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+
+    }
+
+    #[test]
+    fn socket_that_is_not_listening_fails_check() {
+        let _l = lock_env();
+        let fd = socket::socket(
+            AddressFamily::Inet,
+            SockType::Stream,
+            socket::SockFlag::empty(),
+            None,
+        ).unwrap();
+        match *check(&Socket::Unix(fd)).unwrap_err().kind() {
+            ErrorKind::Unusable(_) => (),
+            ref kind => panic!("unexpected error kind {:?}", kind),
+        }
+        unistd::close(fd).unwrap();
+    }
+
     #[test]
     fn test_listen_fds_with_missing_env() {
         let r = {
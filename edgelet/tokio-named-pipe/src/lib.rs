@@ -10,6 +10,7 @@
 #![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
 #![cfg_attr(feature = "cargo-clippy", allow(use_self))]
 
+#[macro_use]
 extern crate futures;
 extern crate mio_named_pipes;
 extern crate tokio;
@@ -30,6 +31,11 @@ use tokio::reactor::PollEvented2;
 use winapi::um::namedpipeapi::WaitNamedPipeW;
 use winapi::um::winbase::*;
 
+mod listener;
+mod security;
+
+pub use listener::PipeListener;
+
 const ERROR_PIPE_BUSY: i32 = 0xE7;
 const PIPE_WAIT_TIMEOUT_MS: u32 = 10 * 1000;
 
@@ -90,6 +96,16 @@ impl PipeStream {
         }
     }
 
+    fn from_io(io: PollEvented2<NamedPipe>) -> Self {
+        PipeStream { io }
+    }
+
+    /// Returns the SID of the user that the process at the other end of the
+    /// pipe runs as, in string form.
+    pub fn client_sid(&self) -> io::Result<String> {
+        security::client_sid(self.io.get_ref().as_raw_handle())
+    }
+
     pub fn disconnect(&self) -> io::Result<()> {
         self.io.get_ref().disconnect()
     }
@@ -0,0 +1,118 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::io;
+use std::iter::once;
+use std::mem;
+use std::os::windows::prelude::*;
+use std::path::Path;
+use std::ptr;
+
+use futures::{Async, Poll};
+use mio_named_pipes::NamedPipe;
+use tokio::reactor::PollEvented2;
+use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+use winapi::um::namedpipeapi::CreateNamedPipeW;
+use winapi::um::winbase::{
+    FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX, PIPE_READMODE_BYTE,
+    PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+
+use security::SecurityDescriptor;
+use PipeStream;
+
+const PIPE_BUFFER_SIZE: u32 = 65536;
+
+/// The server end of a named pipe. Every client connects to an instance of
+/// the pipe of its own, so a new instance is created whenever one has been
+/// accepted.
+///
+/// Only local clients may connect. The first instance is created with
+/// `FILE_FLAG_FIRST_PIPE_INSTANCE`, so binding fails rather than sharing the
+/// pipe with another process that has created it first.
+pub struct PipeListener {
+    path: Vec<u16>,
+    security_descriptor: Option<SecurityDescriptor>,
+    pending: PollEvented2<NamedPipe>,
+    connecting: bool,
+}
+
+impl PipeListener {
+    /// Creates the pipe at `path`. Clients are allowed to connect according to
+    /// `security_descriptor`, given in SDDL form, or according to the default
+    /// security descriptor of named pipes if there is none.
+    pub fn bind<P: AsRef<Path>>(path: P, security_descriptor: Option<&str>) -> io::Result<Self> {
+        let path: Vec<u16> = path
+            .as_ref()
+            .as_os_str()
+            .encode_wide()
+            .chain(once(0))
+            .collect();
+        let security_descriptor = match security_descriptor {
+            Some(sddl) => Some(SecurityDescriptor::from_sddl(sddl)?),
+            None => None,
+        };
+        let pending = create_instance(&path, security_descriptor.as_ref(), true)?;
+
+        Ok(PipeListener {
+            path,
+            security_descriptor,
+            pending,
+            connecting: false,
+        })
+    }
+
+    pub fn poll_accept(&mut self) -> Poll<PipeStream, io::Error> {
+        if !self.connecting {
+            match self.pending.get_ref().connect() {
+                Ok(()) => (),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => self.connecting = true,
+                Err(err) => return Err(err),
+            }
+        }
+
+        // A pending connection completes when the pipe becomes writable.
+        if self.connecting {
+            try_ready!(self.pending.poll_write_ready());
+            self.connecting = false;
+        }
+
+        let next = create_instance(&self.path, self.security_descriptor.as_ref(), false)?;
+        let connected = mem::replace(&mut self.pending, next);
+        Ok(Async::Ready(PipeStream::from_io(connected)))
+    }
+}
+
+fn create_instance(
+    path: &[u16],
+    security_descriptor: Option<&SecurityDescriptor>,
+    first: bool,
+) -> io::Result<PollEvented2<NamedPipe>> {
+    let mut attributes = security_descriptor.map(SecurityDescriptor::attributes);
+    let attributes_ptr = attributes
+        .as_mut()
+        .map_or(ptr::null_mut(), |attributes| attributes as *mut _);
+
+    let mut open_mode = PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED;
+    if first {
+        open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+    }
+
+    let handle = unsafe {
+        CreateNamedPipeW(
+            path.as_ptr(),
+            open_mode,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            PIPE_BUFFER_SIZE,
+            PIPE_BUFFER_SIZE,
+            0,
+            attributes_ptr,
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+
+    let named_pipe = unsafe { NamedPipe::from_raw_handle(handle) };
+    Ok(PollEvented2::new(named_pipe))
+}
@@ -0,0 +1,136 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::iter::once;
+use std::mem;
+use std::os::windows::prelude::*;
+use std::ptr;
+use std::slice;
+
+use winapi::shared::minwindef::{DWORD, FALSE};
+use winapi::shared::sddl::{
+    ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+};
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::minwinbase::SECURITY_ATTRIBUTES;
+use winapi::um::processthreadsapi::{OpenProcess, OpenProcessToken};
+use winapi::um::securitybaseapi::GetTokenInformation;
+use winapi::um::winbase::{GetNamedPipeClientProcessId, LocalFree};
+use winapi::um::winnt::{
+    TokenUser, HANDLE, PROCESS_QUERY_LIMITED_INFORMATION, PSECURITY_DESCRIPTOR, TOKEN_QUERY,
+    TOKEN_USER,
+};
+
+/// A security descriptor converted from its SDDL form.
+pub struct SecurityDescriptor(PSECURITY_DESCRIPTOR);
+
+// The descriptor is only ever read after it has been created.
+unsafe impl Send for SecurityDescriptor {}
+unsafe impl Sync for SecurityDescriptor {}
+
+impl SecurityDescriptor {
+    pub fn from_sddl(sddl: &str) -> io::Result<Self> {
+        let sddl: Vec<u16> = OsStr::new(sddl).encode_wide().chain(once(0)).collect();
+        let mut descriptor = ptr::null_mut();
+        let converted = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                ptr::null_mut(),
+            )
+        };
+        if converted == FALSE {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(SecurityDescriptor(descriptor))
+        }
+    }
+
+    /// The attributes to create a pipe instance with. They point into the
+    /// descriptor, so must not outlive it.
+    pub fn attributes(&self) -> SECURITY_ATTRIBUTES {
+        #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation))]
+        SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as DWORD,
+            lpSecurityDescriptor: self.0,
+            bInheritHandle: FALSE,
+        }
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            LocalFree(self.0);
+        }
+    }
+}
+
+struct Handle(HANDLE);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Returns the SID, in string form, of the user that the process at the
+/// client end of the pipe `pipe` runs as.
+pub fn client_sid(pipe: HANDLE) -> io::Result<String> {
+    let mut pid = 0;
+    if unsafe { GetNamedPipeClientProcessId(pipe, &mut pid) } == FALSE {
+        return Err(io::Error::last_os_error());
+    }
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) };
+    if process.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let process = Handle(process);
+
+    let mut token = ptr::null_mut();
+    if unsafe { OpenProcessToken(process.0, TOKEN_QUERY, &mut token) } == FALSE {
+        return Err(io::Error::last_os_error());
+    }
+    let token = Handle(token);
+
+    // The first call fails, but says how big the buffer has to be. The
+    // buffer is made of u64s so it is aligned well enough for a TOKEN_USER.
+    let mut size = 0;
+    unsafe {
+        GetTokenInformation(token.0, TokenUser, ptr::null_mut(), 0, &mut size);
+    }
+    let mut buffer = vec![0_u64; (size as usize + 7) / 8];
+    let queried = unsafe {
+        GetTokenInformation(
+            token.0,
+            TokenUser,
+            buffer.as_mut_ptr() as *mut _,
+            size,
+            &mut size,
+        )
+    };
+    if queried == FALSE {
+        return Err(io::Error::last_os_error());
+    }
+    let user = unsafe { &*(buffer.as_ptr() as *const TOKEN_USER) };
+
+    let mut sid = ptr::null_mut();
+    if unsafe { ConvertSidToStringSidW(user.User.Sid, &mut sid) } == FALSE {
+        return Err(io::Error::last_os_error());
+    }
+    let sid_string = unsafe {
+        let len = (0..).take_while(|&i| *sid.offset(i) != 0).count();
+        let sid_string = OsString::from_wide(slice::from_raw_parts(sid, len));
+        LocalFree(sid as *mut _);
+        sid_string
+    };
+
+    sid_string
+        .into_string()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "SID is not valid Unicode"))
+}
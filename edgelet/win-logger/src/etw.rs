@@ -0,0 +1,56 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::ffi::OsStr;
+use std::io::Error as IoError;
+use std::iter::once;
+use std::os::windows::ffi::OsStrExt;
+use std::ptr;
+
+use winapi::shared::evntprov::{
+    EventProviderEnabled, EventRegister, EventUnregister, EventWriteString, REGHANDLE,
+};
+use winapi::shared::guiddef::GUID;
+
+use error::Error;
+
+/// Events are written at the informational level, with no keywords.
+const LEVEL: u8 = 4;
+const KEYWORD: u64 = 0;
+
+/// An ETW provider that writes events as strings. Events cost next to
+/// nothing until a trace session enables the provider.
+pub struct EtwProvider {
+    handle: REGHANDLE,
+}
+
+impl EtwProvider {
+    #[cfg_attr(feature = "cargo-clippy", allow(cast_possible_wrap))]
+    pub fn new(guid: GUID) -> Result<Self, Error> {
+        let mut handle: REGHANDLE = 0;
+        let status = unsafe { EventRegister(&guid, None, ptr::null_mut(), &mut handle) };
+        if status == 0 {
+            Ok(EtwProvider { handle })
+        } else {
+            Err(Error::from(IoError::from_raw_os_error(status as i32)))
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        unsafe { EventProviderEnabled(self.handle, LEVEL, KEYWORD) != 0 }
+    }
+
+    pub fn write(&self, event: &str) {
+        let event: Vec<u16> = OsStr::new(event).encode_wide().chain(once(0)).collect();
+        unsafe {
+            EventWriteString(self.handle, LEVEL, KEYWORD, event.as_ptr());
+        }
+    }
+}
+
+impl Drop for EtwProvider {
+    fn drop(&mut self) {
+        unsafe {
+            EventUnregister(self.handle);
+        }
+    }
+}
@@ -20,6 +20,8 @@ extern crate log;
 extern crate winapi;
 
 pub mod error;
+#[cfg(feature = "etw")]
+mod etw;
 mod handle;
 
 use std::ffi::{OsStr, OsString};
@@ -28,6 +30,11 @@ use std::iter::once;
 use std::os::windows::ffi::OsStrExt;
 use std::ptr;
 
+#[cfg(feature = "etw")]
+pub use etw::EtwProvider;
+#[cfg(feature = "etw")]
+pub use winapi::shared::guiddef::GUID;
+
 use error::Error;
 use handle::Handle;
 use log::{Level, LevelFilter, Log, Metadata, Record};
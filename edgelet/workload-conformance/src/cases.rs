@@ -0,0 +1,369 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use base64;
+use chrono::{DateTime, Duration, Utc};
+use futures::Future;
+use hyper::StatusCode;
+use serde_json::Value;
+use workload::apis::Error as ApiError;
+use workload::models::{
+    CertificateResponse, DecryptRequest, EncryptRequest, PrivateKeyType, ServerCertificateRequest,
+    SignAlgorithm, SignRequest,
+};
+
+use pem;
+use Target;
+
+type CaseFuture = Box<Future<Item = (), Error = String>>;
+type CaseFn = fn(&Target) -> CaseFuture;
+
+/// The key every module identity has, which `sign` uses.
+const PRIMARY_KEY: &str = "primary";
+const HMAC_SHA256_LEN: usize = 32;
+
+/// How much later than requested a certificate may expire, to allow for
+/// implementations that round validity periods.
+const EXPIRATION_TOLERANCE_MINS: i64 = 5;
+
+pub const CASES: &[(&str, &str, CaseFn)] = &[
+    (
+        "trust-bundle",
+        "the trust bundle is a list of PEM certificates",
+        trust_bundle,
+    ),
+    (
+        "api-version",
+        "requests with an unsupported API version are refused with 400",
+        unsupported_api_version,
+    ),
+    (
+        "server-cert",
+        "server certificates are issued with the requested expiration",
+        server_cert,
+    ),
+    (
+        "server-cert-max-duration",
+        "server certificate expirations are capped at the maximum duration",
+        server_cert_max_duration,
+    ),
+    (
+        "server-cert-expired",
+        "server certificates that expire in the past are refused with 400",
+        server_cert_expired,
+    ),
+    (
+        "server-cert-common-name",
+        "server certificates without a common name are refused with 400",
+        server_cert_without_common_name,
+    ),
+    (
+        "sign",
+        "data is signed with HMAC-SHA256 and the same data gets the same digest",
+        sign,
+    ),
+    (
+        "sign-unknown-key",
+        "signing with an unknown key is refused with 404",
+        sign_with_unknown_key,
+    ),
+    (
+        "sign-bad-data",
+        "signing data that isn't base64 is refused with 422",
+        sign_bad_data,
+    ),
+    (
+        "encrypt-decrypt",
+        "encrypted data decrypts to the original plaintext",
+        encrypt_decrypt,
+    ),
+];
+
+fn trust_bundle(target: &Target) -> CaseFuture {
+    Box::new(
+        target
+            .api()
+            .trust_bundle(&target.api_version)
+            .map_err(|err| describe(&err))
+            .and_then(|response| {
+                let labels = pem::labels(response.certificate())?;
+                if labels.is_empty() {
+                    Err("the trust bundle is empty".to_string())
+                } else if let Some(label) = labels.iter().find(|l| *l != "CERTIFICATE") {
+                    Err(format!("the trust bundle has a {} block", label))
+                } else {
+                    Ok(())
+                }
+            }),
+    )
+}
+
+fn unsupported_api_version(target: &Target) -> CaseFuture {
+    expect_status(
+        target.api().trust_bundle("1970-01-01"),
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+fn server_cert(target: &Target) -> CaseFuture {
+    let requested = Utc::now() + Duration::hours(1);
+    Box::new(
+        create_server_cert(target, &target.module_id, requested)
+            .map_err(|err| describe(&err))
+            .and_then(move |response| {
+                check_cert_response(&response)?;
+                let expiration = parse_expiration(&response)?;
+                if expiration > requested + Duration::minutes(EXPIRATION_TOLERANCE_MINS) {
+                    Err(format!(
+                        "the certificate expires at {}, later than the requested {}",
+                        expiration.to_rfc3339(),
+                        requested.to_rfc3339()
+                    ))
+                } else if expiration <= Utc::now() {
+                    Err(format!("the certificate expired at {}", expiration.to_rfc3339()))
+                } else {
+                    Ok(())
+                }
+            }),
+    )
+}
+
+fn server_cert_max_duration(target: &Target) -> CaseFuture {
+    let requested = Utc::now() + Duration::days(100 * 365);
+    Box::new(
+        create_server_cert(target, &target.module_id, requested)
+            .map_err(|err| describe(&err))
+            .and_then(move |response| {
+                check_cert_response(&response)?;
+                let expiration = parse_expiration(&response)?;
+                if expiration >= requested - Duration::days(1) {
+                    Err(format!(
+                        "the certificate expires at {}, a hundred years from now",
+                        expiration.to_rfc3339()
+                    ))
+                } else if expiration <= Utc::now() {
+                    Err(format!("the certificate expired at {}", expiration.to_rfc3339()))
+                } else {
+                    Ok(())
+                }
+            }),
+    )
+}
+
+fn server_cert_expired(target: &Target) -> CaseFuture {
+    let requested = Utc::now() - Duration::hours(1);
+    expect_status(
+        create_server_cert(target, &target.module_id, requested),
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+fn server_cert_without_common_name(target: &Target) -> CaseFuture {
+    let requested = Utc::now() + Duration::hours(1);
+    expect_status(
+        create_server_cert(target, "", requested),
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+fn sign(target: &Target) -> CaseFuture {
+    let data = base64::encode("workload conformance");
+    let first = sign_data(target, PRIMARY_KEY, &data);
+    let second = sign_data(target, PRIMARY_KEY, &data);
+
+    Box::new(
+        first
+            .join(second)
+            .map_err(|err| describe(&err))
+            .and_then(|(first, second)| {
+                let digest = base64::decode(&first)
+                    .map_err(|_| format!("the digest {} is not base64", first))?;
+                if digest.len() != HMAC_SHA256_LEN {
+                    Err(format!(
+                        "the digest is {} bytes long rather than {}",
+                        digest.len(),
+                        HMAC_SHA256_LEN
+                    ))
+                } else if first != second {
+                    Err(format!("the same data was signed as {} and {}", first, second))
+                } else {
+                    Ok(())
+                }
+            }),
+    )
+}
+
+fn sign_with_unknown_key(target: &Target) -> CaseFuture {
+    let data = base64::encode("workload conformance");
+    expect_status(
+        sign_data(target, "conformance-unknown", &data),
+        StatusCode::NOT_FOUND,
+    )
+}
+
+fn sign_bad_data(target: &Target) -> CaseFuture {
+    expect_status(
+        sign_data(target, PRIMARY_KEY, "not base64!"),
+        StatusCode::UNPROCESSABLE_ENTITY,
+    )
+}
+
+fn encrypt_decrypt(target: &Target) -> CaseFuture {
+    let plaintext = base64::encode("workload conformance");
+    let initialization_vector = base64::encode("0123456789abcdef");
+
+    let client = target.client.clone();
+    let api_version = target.api_version.clone();
+    let module_id = target.module_id.clone();
+    let generation_id = target.generation_id.clone();
+
+    let encrypt = target.api().encrypt(
+        &api_version,
+        &module_id,
+        &generation_id,
+        EncryptRequest::new(plaintext.clone(), initialization_vector.clone()),
+    );
+    let decrypt = encrypt.and_then(move |response| {
+        client.workload_api().decrypt(
+            &api_version,
+            &module_id,
+            &generation_id,
+            DecryptRequest::new(response.ciphertext().clone(), initialization_vector),
+        )
+    });
+
+    Box::new(
+        decrypt
+            .map_err(|err| describe(&err))
+            .and_then(move |response| {
+                if *response.plaintext() == plaintext {
+                    Ok(())
+                } else {
+                    Err(format!("{} decrypted to {}", plaintext, response.plaintext()))
+                }
+            }),
+    )
+}
+
+fn create_server_cert(
+    target: &Target,
+    common_name: &str,
+    expiration: DateTime<Utc>,
+) -> Box<Future<Item = CertificateResponse, Error = ApiError<Value>>> {
+    target.api().create_server_certificate(
+        &target.api_version,
+        &target.module_id,
+        &target.generation_id,
+        ServerCertificateRequest::new(common_name.to_string(), expiration.to_rfc3339()),
+    )
+}
+
+fn sign_data(
+    target: &Target,
+    key_id: &str,
+    data: &str,
+) -> Box<Future<Item = String, Error = ApiError<Value>>> {
+    Box::new(
+        target
+            .api()
+            .sign(
+                &target.api_version,
+                &target.module_id,
+                &target.generation_id,
+                SignRequest::new(key_id.to_string(), SignAlgorithm::HMACSHA256, data.to_string()),
+            ).map(|response| response.digest().clone()),
+    )
+}
+
+fn check_cert_response(response: &CertificateResponse) -> Result<(), String> {
+    let labels = pem::labels(response.certificate())?;
+    if labels.first().map(String::as_str) != Some("CERTIFICATE") {
+        return Err("the response does not start with a certificate".to_string());
+    }
+
+    let private_key = response.private_key();
+    match (private_key.type_(), private_key.bytes()) {
+        (PrivateKeyType::Key, Some(bytes)) => {
+            let labels = pem::labels(bytes)?;
+            if labels.len() == 1 && labels[0].ends_with("PRIVATE KEY") {
+                Ok(())
+            } else {
+                Err("the private key is not a single PEM private key".to_string())
+            }
+        }
+        (PrivateKeyType::Key, None) => Err("the private key has no bytes".to_string()),
+        // Keys that stay in an HSM are returned as references.
+        (PrivateKeyType::Ref, _) => Ok(()),
+    }
+}
+
+fn parse_expiration(response: &CertificateResponse) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(response.expiration())
+        .map(|expiration| expiration.with_timezone(&Utc))
+        .map_err(|_| format!("the expiration {} is not RFC 3339", response.expiration()))
+}
+
+fn expect_status<F>(request: F, expected: StatusCode) -> CaseFuture
+where
+    F: 'static + Future<Error = ApiError<Value>>,
+{
+    Box::new(request.then(move |result| match result {
+        Ok(_) => Err(format!("expected {} but the request succeeded", expected)),
+        Err(ApiError::Api(ref err)) if err.code == expected => Ok(()),
+        Err(err) => Err(format!("expected {} but {}", expected, describe(&err))),
+    }))
+}
+
+fn describe(err: &ApiError<Value>) -> String {
+    match *err {
+        ApiError::Api(ref err) => {
+            let message = err
+                .content
+                .as_ref()
+                .and_then(|content| content.get("message"))
+                .and_then(Value::as_str)
+                .unwrap_or("no message");
+            format!("the request failed with {} ({})", err.code, message)
+        }
+        ApiError::Hyper(ref err) => format!("the request failed: {}", err),
+        ApiError::Serde(ref err) => format!("the response could not be parsed: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future;
+    use serde_json;
+    use workload::apis::ApiError as ApiErrorContent;
+
+    use super::*;
+
+    fn api_error(code: StatusCode) -> ApiError<Value> {
+        ApiError::Api(ApiErrorContent {
+            code,
+            content: Some(serde_json::from_str(r#"{ "message": "nope" }"#).unwrap()),
+        })
+    }
+
+    #[test]
+    fn expected_status_passes() {
+        let request = future::err::<(), _>(api_error(StatusCode::BAD_REQUEST));
+        assert_eq!(
+            Ok(()),
+            expect_status(request, StatusCode::BAD_REQUEST).wait()
+        );
+    }
+
+    #[test]
+    fn other_outcomes_fail() {
+        let request = future::err::<(), _>(api_error(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(
+            Err("expected 400 Bad Request but the request failed with 500 Internal Server Error \
+                 (nope)"
+                .to_string()),
+            expect_status(request, StatusCode::BAD_REQUEST).wait()
+        );
+
+        let request = future::ok::<(), ApiError<Value>>(());
+        assert!(expect_status(request, StatusCode::BAD_REQUEST).wait().is_err());
+    }
+}
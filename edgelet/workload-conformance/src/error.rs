@@ -0,0 +1,88 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+use std::fmt;
+use std::fmt::Display;
+use std::io;
+
+use edgelet_http::Error as HttpError;
+use failure::{Backtrace, Context, Fail};
+use url::ParseError;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[derive(Clone, Copy, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "An IO error occurred.")]
+    Io,
+    #[fail(display = "Cannot parse uri")]
+    UrlParse,
+    #[fail(display = "Could not create a client for the workload endpoint")]
+    Client,
+    #[fail(display = "Missing value for argument {}", _0)]
+    MissingArgument(&'static str),
+    #[fail(display = "{} test(s) failed", _0)]
+    TestsFailed(usize),
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&Fail> {
+        self.inner.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        self.inner.get_context()
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            inner: Context::new(kind),
+        }
+    }
+}
+
+impl From<Context<ErrorKind>> for Error {
+    fn from(inner: Context<ErrorKind>) -> Self {
+        Error { inner }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Io),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::UrlParse),
+        }
+    }
+}
+
+impl From<HttpError> for Error {
+    fn from(error: HttpError) -> Self {
+        Error {
+            inner: error.context(ErrorKind::Client),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#![deny(unused_extern_crates, warnings)]
+// Remove this when clippy stops warning about old-style `allow()`,
+// which can only be silenced by enabling a feature and thus requires nightly
+//
+// Ref: https://github.com/rust-lang-nursery/rust-clippy/issues/3159#issuecomment-420530386
+#![allow(renamed_and_removed_lints)]
+#![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
+#![cfg_attr(feature = "cargo-clippy", allow(stutter, use_self))]
+
+extern crate base64;
+extern crate chrono;
+extern crate edgelet_http;
+extern crate failure;
+#[macro_use]
+extern crate failure_derive;
+extern crate futures;
+extern crate hyper;
+extern crate serde_json;
+extern crate tokio;
+extern crate url;
+extern crate workload;
+
+mod cases;
+mod error;
+mod pem;
+
+use std::io::Write;
+use std::rc::Rc;
+
+use edgelet_http::UnixClientBuilder;
+use tokio::runtime::current_thread::Runtime;
+use url::Url;
+use workload::apis::client::APIClient;
+use workload::apis::configuration::Configuration;
+use workload::apis::WorkloadApi;
+
+pub use error::{Error, ErrorKind};
+
+/// The workload endpoint under test, and the module identity the tests make
+/// their requests as.
+///
+/// Implementations only serve a module's requests if they come from the
+/// module itself, so the suite is meant to run inside a module, where the
+/// endpoint and identity are found in the `IOTEDGE_*` environment variables.
+pub struct Target {
+    client: Rc<APIClient>,
+    api_version: String,
+    module_id: String,
+    generation_id: String,
+}
+
+impl Target {
+    pub fn new(
+        url: &Url,
+        api_version: &str,
+        module_id: &str,
+        generation_id: &str,
+    ) -> Result<Self, Error> {
+        let builder = UnixClientBuilder::new()
+            .with_user_agent(concat!("workload-conformance/", env!("CARGO_PKG_VERSION")));
+
+        let mut configuration = Configuration::new(builder.hyper_client(url)?);
+        configuration.base_path = match url.scheme() {
+            "unix" => url.path().to_string(),
+            _ => url.as_str().to_string(),
+        };
+        configuration.user_agent = Some(builder.user_agent().to_string());
+        configuration.uri_composer = builder.uri_composer(url);
+
+        Ok(Target {
+            client: Rc::new(APIClient::new(configuration)),
+            api_version: api_version.to_string(),
+            module_id: module_id.to_string(),
+            generation_id: generation_id.to_string(),
+        })
+    }
+
+    fn api(&self) -> &WorkloadApi {
+        self.client.workload_api()
+    }
+}
+
+/// Runs every test of the suite against the target, writing a line with the
+/// outcome of each to `output`. Fails if any of the tests fail.
+pub fn run<W: Write>(target: &Target, output: &mut W) -> Result<(), Error> {
+    let mut runtime = Runtime::new()?;
+
+    let mut failed = 0;
+    for (id, description, case) in cases::CASES {
+        match runtime.block_on(case(target)) {
+            Ok(()) => writeln!(output, "[pass] {} ({})", description, id)?,
+            Err(message) => {
+                failed += 1;
+                writeln!(output, "[fail] {} ({})", description, id)?;
+                writeln!(output, "    {}", message)?;
+            }
+        }
+    }
+
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(Error::from(ErrorKind::TestsFailed(failed)))
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright (c) Microsoft. All rights reserved.
+
+#![deny(unused_extern_crates, warnings)]
+// Remove this when clippy stops warning about old-style `allow()`,
+// which can only be silenced by enabling a feature and thus requires nightly
+//
+// Ref: https://github.com/rust-lang-nursery/rust-clippy/issues/3159#issuecomment-420530386
+#![allow(renamed_and_removed_lints)]
+#![cfg_attr(feature = "cargo-clippy", deny(clippy, clippy_pedantic))]
+
+#[macro_use]
+extern crate clap;
+extern crate failure;
+extern crate url;
+extern crate workload_conformance;
+
+use std::io;
+use std::io::Write;
+use std::process;
+
+use clap::{App, Arg};
+use failure::Fail;
+use url::Url;
+use workload_conformance::{Error, ErrorKind, Target};
+
+/// The version of the workload API that the suite tests by default.
+const API_VERSION: &str = "2018-06-28";
+
+fn main() {
+    if let Err(ref error) = run() {
+        let stderr = &mut io::stderr();
+        let errmsg = "Error writing to stderr";
+
+        let mut fail: &Fail = error;
+        writeln!(stderr, "{}", error.to_string()).unwrap_or_else(|_| panic!(errmsg));
+        while let Some(cause) = fail.cause() {
+            writeln!(stderr, "\tcaused by: {}", cause.to_string())
+                .unwrap_or_else(|_| panic!(errmsg));
+            fail = cause;
+        }
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let matches = App::new(crate_name!())
+        .version(crate_version!())
+        .about(crate_description!())
+        .arg(
+            Arg::with_name("uri")
+                .help("Workload endpoint to test")
+                .long("uri")
+                .takes_value(true)
+                .value_name("URI")
+                .env("IOTEDGE_WORKLOADURI")
+                .required(true),
+        ).arg(
+            Arg::with_name("module-id")
+                .help("Module identity to make the requests as")
+                .long("module-id")
+                .takes_value(true)
+                .value_name("MODULE")
+                .env("IOTEDGE_MODULEID")
+                .required(true),
+        ).arg(
+            Arg::with_name("generation-id")
+                .help("Generation ID of the module identity")
+                .long("generation-id")
+                .takes_value(true)
+                .value_name("GENID")
+                .env("IOTEDGE_MODULEGENERATIONID")
+                .required(true),
+        ).arg(
+            Arg::with_name("api-version")
+                .help("Version of the workload API to test")
+                .long("api-version")
+                .takes_value(true)
+                .value_name("VERSION")
+                .env("IOTEDGE_APIVERSION")
+                .default_value(API_VERSION),
+        ).get_matches();
+
+    let value_of = |name: &'static str| {
+        matches
+            .value_of(name)
+            .ok_or_else(|| Error::from(ErrorKind::MissingArgument(name)))
+    };
+
+    let url = Url::parse(value_of("uri")?)?;
+    let target = Target::new(
+        &url,
+        value_of("api-version")?,
+        value_of("module-id")?,
+        value_of("generation-id")?,
+    )?;
+
+    workload_conformance::run(&target, &mut io::stdout())
+}
@@ -0,0 +1,159 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BootstrapResponse {
+    /// Name of the IoT hub the device is registered with
+    #[serde(rename = "iotHubName")]
+    iot_hub_name: String,
+    /// ID of the device
+    #[serde(rename = "deviceId")]
+    device_id: String,
+    /// ID of the module
+    #[serde(rename = "moduleId")]
+    module_id: String,
+    /// Generation ID of the module
+    #[serde(rename = "generationId")]
+    generation_id: String,
+    #[serde(rename = "trustBundle")]
+    trust_bundle: ::models::TrustBundleResponse,
+    #[serde(rename = "identityCertificate")]
+    identity_certificate: ::models::CertificateResponse,
+    #[serde(rename = "serverCertificate", skip_serializing_if = "Option::is_none")]
+    server_certificate: Option<::models::CertificateResponse>,
+}
+
+impl BootstrapResponse {
+    pub fn new(
+        iot_hub_name: String,
+        device_id: String,
+        module_id: String,
+        generation_id: String,
+        trust_bundle: ::models::TrustBundleResponse,
+        identity_certificate: ::models::CertificateResponse,
+    ) -> Self {
+        BootstrapResponse {
+            iot_hub_name,
+            device_id,
+            module_id,
+            generation_id,
+            trust_bundle,
+            identity_certificate,
+            server_certificate: None,
+        }
+    }
+
+    pub fn set_iot_hub_name(&mut self, iot_hub_name: String) {
+        self.iot_hub_name = iot_hub_name;
+    }
+
+    pub fn with_iot_hub_name(mut self, iot_hub_name: String) -> Self {
+        self.iot_hub_name = iot_hub_name;
+        self
+    }
+
+    pub fn iot_hub_name(&self) -> &String {
+        &self.iot_hub_name
+    }
+
+    pub fn set_device_id(&mut self, device_id: String) {
+        self.device_id = device_id;
+    }
+
+    pub fn with_device_id(mut self, device_id: String) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
+    pub fn device_id(&self) -> &String {
+        &self.device_id
+    }
+
+    pub fn set_module_id(&mut self, module_id: String) {
+        self.module_id = module_id;
+    }
+
+    pub fn with_module_id(mut self, module_id: String) -> Self {
+        self.module_id = module_id;
+        self
+    }
+
+    pub fn module_id(&self) -> &String {
+        &self.module_id
+    }
+
+    pub fn set_generation_id(&mut self, generation_id: String) {
+        self.generation_id = generation_id;
+    }
+
+    pub fn with_generation_id(mut self, generation_id: String) -> Self {
+        self.generation_id = generation_id;
+        self
+    }
+
+    pub fn generation_id(&self) -> &String {
+        &self.generation_id
+    }
+
+    pub fn set_trust_bundle(&mut self, trust_bundle: ::models::TrustBundleResponse) {
+        self.trust_bundle = trust_bundle;
+    }
+
+    pub fn with_trust_bundle(mut self, trust_bundle: ::models::TrustBundleResponse) -> Self {
+        self.trust_bundle = trust_bundle;
+        self
+    }
+
+    pub fn trust_bundle(&self) -> &::models::TrustBundleResponse {
+        &self.trust_bundle
+    }
+
+    pub fn set_identity_certificate(
+        &mut self,
+        identity_certificate: ::models::CertificateResponse,
+    ) {
+        self.identity_certificate = identity_certificate;
+    }
+
+    pub fn with_identity_certificate(
+        mut self,
+        identity_certificate: ::models::CertificateResponse,
+    ) -> Self {
+        self.identity_certificate = identity_certificate;
+        self
+    }
+
+    pub fn identity_certificate(&self) -> &::models::CertificateResponse {
+        &self.identity_certificate
+    }
+
+    pub fn set_server_certificate(&mut self, server_certificate: ::models::CertificateResponse) {
+        self.server_certificate = Some(server_certificate);
+    }
+
+    pub fn with_server_certificate(
+        mut self,
+        server_certificate: ::models::CertificateResponse,
+    ) -> Self {
+        self.server_certificate = Some(server_certificate);
+        self
+    }
+
+    pub fn server_certificate(&self) -> Option<&::models::CertificateResponse> {
+        self.server_certificate.as_ref()
+    }
+
+    pub fn reset_server_certificate(&mut self) {
+        self.server_certificate = None;
+    }
+}
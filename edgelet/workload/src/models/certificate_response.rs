@@ -21,6 +21,9 @@ pub struct CertificateResponse {
     /// Certificate expiration date-time (ISO 8601)
     #[serde(rename = "expiration")]
     expiration: String,
+    /// When the certificate is due to be renewed (ISO 8601)
+    #[serde(rename = "renewAt", skip_serializing_if = "Option::is_none")]
+    renew_at: Option<String>,
 }
 
 impl CertificateResponse {
@@ -29,6 +32,7 @@ impl CertificateResponse {
             private_key,
             certificate,
             expiration,
+            renew_at: None,
         }
     }
 
@@ -70,4 +74,21 @@ impl CertificateResponse {
     pub fn expiration(&self) -> &String {
         &self.expiration
     }
+
+    pub fn set_renew_at(&mut self, renew_at: String) {
+        self.renew_at = Some(renew_at);
+    }
+
+    pub fn with_renew_at(mut self, renew_at: String) -> Self {
+        self.renew_at = Some(renew_at);
+        self
+    }
+
+    pub fn renew_at(&self) -> Option<&String> {
+        self.renew_at.as_ref()
+    }
+
+    pub fn reset_renew_at(&mut self) {
+        self.renew_at = None;
+    }
 }
@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrlResponse {
+    /// PEM formatted certificate revocation list signed by the workload CA.
+    #[serde(rename = "crl")]
+    crl: String,
+}
+
+impl CrlResponse {
+    pub fn new(crl: String) -> Self {
+        CrlResponse { crl }
+    }
+
+    pub fn set_crl(&mut self, crl: String) {
+        self.crl = crl;
+    }
+
+    pub fn with_crl(mut self, crl: String) -> Self {
+        self.crl = crl;
+        self
+    }
+
+    pub fn crl(&self) -> &String {
+        &self.crl
+    }
+}
@@ -19,6 +19,9 @@ pub struct DecryptRequest {
     /// An initialization vector used to decrypt the data.
     #[serde(rename = "initializationVector")]
     initialization_vector: String,
+    /// Name of the counter the data was encrypted with, whose numbers must increase.
+    #[serde(rename = "sequenceKey", skip_serializing_if = "Option::is_none")]
+    sequence_key: Option<String>,
 }
 
 impl DecryptRequest {
@@ -26,6 +29,7 @@ impl DecryptRequest {
         DecryptRequest {
             ciphertext,
             initialization_vector,
+            sequence_key: None,
         }
     }
 
@@ -54,4 +58,21 @@ impl DecryptRequest {
     pub fn initialization_vector(&self) -> &String {
         &self.initialization_vector
     }
+
+    pub fn set_sequence_key(&mut self, sequence_key: String) {
+        self.sequence_key = Some(sequence_key);
+    }
+
+    pub fn with_sequence_key(mut self, sequence_key: String) -> Self {
+        self.sequence_key = Some(sequence_key);
+        self
+    }
+
+    pub fn sequence_key(&self) -> Option<&str> {
+        self.sequence_key.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_sequence_key(&mut self) {
+        self.sequence_key = None;
+    }
 }
@@ -16,11 +16,17 @@ pub struct DecryptResponse {
     /// The decrypted form of the data encoded in base 64.
     #[serde(rename = "plaintext")]
     plaintext: String,
+    /// The sequence number the data was encrypted with, when a sequence key was given.
+    #[serde(rename = "sequenceNumber", skip_serializing_if = "Option::is_none")]
+    sequence_number: Option<i64>,
 }
 
 impl DecryptResponse {
     pub fn new(plaintext: String) -> Self {
-        DecryptResponse { plaintext }
+        DecryptResponse {
+            plaintext,
+            sequence_number: None,
+        }
     }
 
     pub fn set_plaintext(&mut self, plaintext: String) {
@@ -35,4 +41,21 @@ impl DecryptResponse {
     pub fn plaintext(&self) -> &String {
         &self.plaintext
     }
+
+    pub fn set_sequence_number(&mut self, sequence_number: i64) {
+        self.sequence_number = Some(sequence_number);
+    }
+
+    pub fn with_sequence_number(mut self, sequence_number: i64) -> Self {
+        self.sequence_number = Some(sequence_number);
+        self
+    }
+
+    pub fn sequence_number(&self) -> Option<i64> {
+        self.sequence_number
+    }
+
+    pub fn reset_sequence_number(&mut self) {
+        self.sequence_number = None;
+    }
 }
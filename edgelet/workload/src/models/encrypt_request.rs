@@ -19,6 +19,9 @@ pub struct EncryptRequest {
     /// An initialization vector used to encrypt the data.
     #[serde(rename = "initializationVector")]
     initialization_vector: String,
+    /// Name of a counter of the module whose next sequence number is encrypted with the data.
+    #[serde(rename = "sequenceKey", skip_serializing_if = "Option::is_none")]
+    sequence_key: Option<String>,
 }
 
 impl EncryptRequest {
@@ -26,6 +29,7 @@ impl EncryptRequest {
         EncryptRequest {
             plaintext,
             initialization_vector,
+            sequence_key: None,
         }
     }
 
@@ -54,4 +58,21 @@ impl EncryptRequest {
     pub fn initialization_vector(&self) -> &String {
         &self.initialization_vector
     }
+
+    pub fn set_sequence_key(&mut self, sequence_key: String) {
+        self.sequence_key = Some(sequence_key);
+    }
+
+    pub fn with_sequence_key(mut self, sequence_key: String) -> Self {
+        self.sequence_key = Some(sequence_key);
+        self
+    }
+
+    pub fn sequence_key(&self) -> Option<&str> {
+        self.sequence_key.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_sequence_key(&mut self) {
+        self.sequence_key = None;
+    }
 }
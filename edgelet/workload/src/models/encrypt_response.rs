@@ -16,11 +16,17 @@ pub struct EncryptResponse {
     /// The encrypted form of the data encoded in base 64.
     #[serde(rename = "ciphertext")]
     ciphertext: String,
+    /// The sequence number encrypted along with the data, when a sequence key was given.
+    #[serde(rename = "sequenceNumber", skip_serializing_if = "Option::is_none")]
+    sequence_number: Option<i64>,
 }
 
 impl EncryptResponse {
     pub fn new(ciphertext: String) -> Self {
-        EncryptResponse { ciphertext }
+        EncryptResponse {
+            ciphertext,
+            sequence_number: None,
+        }
     }
 
     pub fn set_ciphertext(&mut self, ciphertext: String) {
@@ -35,4 +41,21 @@ impl EncryptResponse {
     pub fn ciphertext(&self) -> &String {
         &self.ciphertext
     }
+
+    pub fn set_sequence_number(&mut self, sequence_number: i64) {
+        self.sequence_number = Some(sequence_number);
+    }
+
+    pub fn with_sequence_number(mut self, sequence_number: i64) -> Self {
+        self.sequence_number = Some(sequence_number);
+        self
+    }
+
+    pub fn sequence_number(&self) -> Option<i64> {
+        self.sequence_number
+    }
+
+    pub fn reset_sequence_number(&mut self) {
+        self.sequence_number = None;
+    }
 }
@@ -15,11 +15,17 @@ use serde_json::Value;
 pub struct ErrorResponse {
     #[serde(rename = "message")]
     message: String,
+    /// What went wrong: user-config, transient-network, hsm, runtime or internal.
+    #[serde(rename = "category", skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
 }
 
 impl ErrorResponse {
     pub fn new(message: String) -> Self {
-        ErrorResponse { message }
+        ErrorResponse {
+            message,
+            category: None,
+        }
     }
 
     pub fn set_message(&mut self, message: String) {
@@ -34,4 +40,21 @@ impl ErrorResponse {
     pub fn message(&self) -> &String {
         &self.message
     }
+
+    pub fn set_category(&mut self, category: String) {
+        self.category = Some(category);
+    }
+
+    pub fn with_category(mut self, category: String) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_category(&mut self) {
+        self.category = None;
+    }
 }
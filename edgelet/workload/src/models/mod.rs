@@ -1,5 +1,9 @@
+mod bootstrap_response;
+pub use self::bootstrap_response::BootstrapResponse;
 mod certificate_response;
 pub use self::certificate_response::CertificateResponse;
+mod crl_response;
+pub use self::crl_response::CrlResponse;
 mod decrypt_request;
 pub use self::decrypt_request::DecryptRequest;
 mod decrypt_response;
@@ -13,15 +17,43 @@ pub use self::error_response::ErrorResponse;
 mod identity_certificate_request;
 pub use self::identity_certificate_request::IdentityCertificateRequest;
 mod private_key;
-pub use self::private_key::PrivateKey;
+pub use self::private_key::{PrivateKey, PrivateKeyType};
 mod server_certificate_request;
-pub use self::server_certificate_request::ServerCertificateRequest;
+pub use self::server_certificate_request::{
+    CertificateCurve, CertificateKeyType, ServerCertificateRequest,
+};
+mod sign_certificate_request;
+pub use self::sign_certificate_request::SignCertificateRequest;
+mod sign_certificate_response;
+pub use self::sign_certificate_response::SignCertificateResponse;
 mod sign_request;
-pub use self::sign_request::SignRequest;
+pub use self::sign_request::{SignAlgorithm, SignRequest};
 mod sign_response;
 pub use self::sign_response::SignResponse;
+mod time_response;
+pub use self::time_response::TimeResponse;
+mod trust_bundle_delta_response;
+pub use self::trust_bundle_delta_response::TrustBundleDeltaResponse;
 mod trust_bundle_response;
 pub use self::trust_bundle_response::TrustBundleResponse;
+mod unwrap_key_request;
+pub use self::unwrap_key_request::UnwrapKeyRequest;
+mod unwrap_key_response;
+pub use self::unwrap_key_response::UnwrapKeyResponse;
+mod validate_tokens_request;
+pub use self::validate_tokens_request::ValidateTokensRequest;
+mod validate_tokens_response;
+pub use self::validate_tokens_response::ValidateTokensResponse;
+mod verified_certificate;
+pub use self::verified_certificate::VerifiedCertificate;
+mod verify_certificate_request;
+pub use self::verify_certificate_request::VerifyCertificateRequest;
+mod verify_certificate_response;
+pub use self::verify_certificate_response::VerifyCertificateResponse;
+mod wrap_key_request;
+pub use self::wrap_key_request::WrapKeyRequest;
+mod wrap_key_response;
+pub use self::wrap_key_response::WrapKeyResponse;
 
 // TODO(farcaller): sort out files
 pub struct File;
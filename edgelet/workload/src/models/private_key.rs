@@ -11,11 +11,19 @@
 #[allow(unused_imports)]
 use serde_json::Value;
 
+/// Indicates format of the key (present in PEM formatted bytes or a reference)
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivateKeyType {
+    Ref,
+    Key,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PrivateKey {
     /// Indicates format of the key (present in PEM formatted bytes or a reference)
     #[serde(rename = "type")]
-    type_: String,
+    type_: PrivateKeyType,
     /// Reference to private key.
     #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
     ref_: Option<String>,
@@ -25,7 +33,7 @@ pub struct PrivateKey {
 }
 
 impl PrivateKey {
-    pub fn new(type_: String) -> Self {
+    pub fn new(type_: PrivateKeyType) -> Self {
         PrivateKey {
             type_,
             ref_: None,
@@ -33,17 +41,27 @@ impl PrivateKey {
         }
     }
 
-    pub fn set_type(&mut self, type_: String) {
+    /// Creates a private key that refers to a key held by the HSM.
+    pub fn from_ref(ref_: String) -> Self {
+        PrivateKey::new(PrivateKeyType::Ref).with_ref(ref_)
+    }
+
+    /// Creates a private key carrying PEM formatted bytes.
+    pub fn from_bytes(bytes: String) -> Self {
+        PrivateKey::new(PrivateKeyType::Key).with_bytes(bytes)
+    }
+
+    pub fn set_type(&mut self, type_: PrivateKeyType) {
         self.type_ = type_;
     }
 
-    pub fn with_type(mut self, type_: String) -> Self {
+    pub fn with_type(mut self, type_: PrivateKeyType) -> Self {
         self.type_ = type_;
         self
     }
 
-    pub fn type_(&self) -> &String {
-        &self.type_
+    pub fn type_(&self) -> PrivateKeyType {
+        self.type_
     }
 
     pub fn set_ref(&mut self, ref_: String) {
@@ -80,3 +98,31 @@ impl PrivateKey {
         self.bytes = None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn private_key_type_serializes_lowercase() {
+        let key = PrivateKey::from_ref("alias".to_string());
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(r#"{"type":"ref","ref":"alias"}"#, json);
+
+        let key = PrivateKey::from_bytes("pem".to_string());
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(r#"{"type":"key","bytes":"pem"}"#, json);
+    }
+
+    #[test]
+    fn private_key_type_deserializes() {
+        let key: PrivateKey = serde_json::from_str(r#"{"type":"key","bytes":"pem"}"#).unwrap();
+        assert_eq!(PrivateKeyType::Key, key.type_());
+        assert_eq!(Some("pem"), key.bytes());
+
+        let key: PrivateKey = serde_json::from_str(r#"{"type":"ref","ref":"alias"}"#).unwrap();
+        assert_eq!(PrivateKeyType::Ref, key.type_());
+        assert_eq!(Some("alias"), key.ref_());
+    }
+}
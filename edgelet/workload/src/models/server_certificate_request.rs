@@ -11,6 +11,23 @@
 #[allow(unused_imports)]
 use serde_json::Value;
 
+/// Type of the key of the certificate, rsa or ec
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CertificateKeyType {
+    Rsa,
+    Ec,
+}
+
+/// Curve of an ec key, P-256 or P-384
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum CertificateCurve {
+    #[serde(rename = "P-256")]
+    P256,
+    #[serde(rename = "P-384")]
+    P384,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerCertificateRequest {
     /// Subject common name
@@ -19,6 +36,21 @@ pub struct ServerCertificateRequest {
     /// Certificate expiration date-time (ISO 8601)
     #[serde(rename = "expiration")]
     expiration: String,
+    /// DNS names the certificate is valid for, besides the common name
+    #[serde(rename = "dnsNames", skip_serializing_if = "Option::is_none")]
+    dns_names: Option<Vec<String>>,
+    /// IP addresses the certificate is valid for
+    #[serde(rename = "ipAddresses", skip_serializing_if = "Option::is_none")]
+    ip_addresses: Option<Vec<String>>,
+    /// Type of the key of the certificate, rsa or ec
+    #[serde(rename = "keyType", skip_serializing_if = "Option::is_none")]
+    key_type: Option<CertificateKeyType>,
+    /// Curve of an ec key, P-256 or P-384
+    #[serde(rename = "curve", skip_serializing_if = "Option::is_none")]
+    curve: Option<CertificateCurve>,
+    /// Length in bits of an rsa key, 2048, 3072 or 4096
+    #[serde(rename = "keyLength", skip_serializing_if = "Option::is_none")]
+    key_length: Option<i32>,
 }
 
 impl ServerCertificateRequest {
@@ -26,6 +58,11 @@ impl ServerCertificateRequest {
         ServerCertificateRequest {
             common_name,
             expiration,
+            dns_names: None,
+            ip_addresses: None,
+            key_type: None,
+            curve: None,
+            key_length: None,
         }
     }
 
@@ -54,4 +91,119 @@ impl ServerCertificateRequest {
     pub fn expiration(&self) -> &String {
         &self.expiration
     }
+
+    pub fn set_dns_names(&mut self, dns_names: Vec<String>) {
+        self.dns_names = Some(dns_names);
+    }
+
+    pub fn with_dns_names(mut self, dns_names: Vec<String>) -> Self {
+        self.dns_names = Some(dns_names);
+        self
+    }
+
+    pub fn dns_names(&self) -> Option<&Vec<String>> {
+        self.dns_names.as_ref()
+    }
+
+    pub fn reset_dns_names(&mut self) {
+        self.dns_names = None;
+    }
+
+    pub fn set_ip_addresses(&mut self, ip_addresses: Vec<String>) {
+        self.ip_addresses = Some(ip_addresses);
+    }
+
+    pub fn with_ip_addresses(mut self, ip_addresses: Vec<String>) -> Self {
+        self.ip_addresses = Some(ip_addresses);
+        self
+    }
+
+    pub fn ip_addresses(&self) -> Option<&Vec<String>> {
+        self.ip_addresses.as_ref()
+    }
+
+    pub fn reset_ip_addresses(&mut self) {
+        self.ip_addresses = None;
+    }
+
+    pub fn set_key_type(&mut self, key_type: CertificateKeyType) {
+        self.key_type = Some(key_type);
+    }
+
+    pub fn with_key_type(mut self, key_type: CertificateKeyType) -> Self {
+        self.key_type = Some(key_type);
+        self
+    }
+
+    pub fn key_type(&self) -> Option<CertificateKeyType> {
+        self.key_type
+    }
+
+    pub fn reset_key_type(&mut self) {
+        self.key_type = None;
+    }
+
+    pub fn set_curve(&mut self, curve: CertificateCurve) {
+        self.curve = Some(curve);
+    }
+
+    pub fn with_curve(mut self, curve: CertificateCurve) -> Self {
+        self.curve = Some(curve);
+        self
+    }
+
+    pub fn curve(&self) -> Option<CertificateCurve> {
+        self.curve
+    }
+
+    pub fn reset_curve(&mut self) {
+        self.curve = None;
+    }
+
+    pub fn set_key_length(&mut self, key_length: i32) {
+        self.key_length = Some(key_length);
+    }
+
+    pub fn with_key_length(mut self, key_length: i32) -> Self {
+        self.key_length = Some(key_length);
+        self
+    }
+
+    pub fn key_length(&self) -> Option<i32> {
+        self.key_length
+    }
+
+    pub fn reset_key_length(&mut self) {
+        self.key_length = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn key_type_and_curve_serialize_as_in_the_api() {
+        let request = ServerCertificateRequest::new("marvin".to_string(), "2019".to_string())
+            .with_key_type(CertificateKeyType::Ec)
+            .with_curve(CertificateCurve::P384);
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            r#"{"commonName":"marvin","expiration":"2019","keyType":"ec","curve":"P-384"}"#,
+            json
+        );
+
+        let request: ServerCertificateRequest = serde_json::from_str(
+            r#"{"commonName":"marvin","expiration":"2019","keyType":"rsa","curve":"P-256"}"#,
+        ).unwrap();
+        assert_eq!(Some(CertificateKeyType::Rsa), request.key_type());
+        assert_eq!(Some(CertificateCurve::P256), request.curve());
+
+        assert!(
+            serde_json::from_str::<ServerCertificateRequest>(
+                r#"{"commonName":"marvin","expiration":"2019","curve":"P-521"}"#
+            ).is_err()
+        );
+    }
 }
@@ -0,0 +1,54 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignCertificateRequest {
+    /// PEM formatted certificate signing request
+    #[serde(rename = "csr")]
+    csr: String,
+    /// Certificate expiration date-time (ISO 8601)
+    #[serde(rename = "expiration")]
+    expiration: String,
+}
+
+impl SignCertificateRequest {
+    pub fn new(csr: String, expiration: String) -> Self {
+        SignCertificateRequest { csr, expiration }
+    }
+
+    pub fn set_csr(&mut self, csr: String) {
+        self.csr = csr;
+    }
+
+    pub fn with_csr(mut self, csr: String) -> Self {
+        self.csr = csr;
+        self
+    }
+
+    pub fn csr(&self) -> &String {
+        &self.csr
+    }
+
+    pub fn set_expiration(&mut self, expiration: String) {
+        self.expiration = expiration;
+    }
+
+    pub fn with_expiration(mut self, expiration: String) -> Self {
+        self.expiration = expiration;
+        self
+    }
+
+    pub fn expiration(&self) -> &String {
+        &self.expiration
+    }
+}
@@ -0,0 +1,78 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignCertificateResponse {
+    /// Base64 encoded PEM formatted byte array containing the certificate and its chain.
+    #[serde(rename = "certificate")]
+    certificate: String,
+    /// Certificate expiration date-time (ISO 8601)
+    #[serde(rename = "expiration")]
+    expiration: String,
+    /// When the certificate is due to be renewed (ISO 8601)
+    #[serde(rename = "renewAt", skip_serializing_if = "Option::is_none")]
+    renew_at: Option<String>,
+}
+
+impl SignCertificateResponse {
+    pub fn new(certificate: String, expiration: String) -> Self {
+        SignCertificateResponse {
+            certificate,
+            expiration,
+            renew_at: None,
+        }
+    }
+
+    pub fn set_certificate(&mut self, certificate: String) {
+        self.certificate = certificate;
+    }
+
+    pub fn with_certificate(mut self, certificate: String) -> Self {
+        self.certificate = certificate;
+        self
+    }
+
+    pub fn certificate(&self) -> &String {
+        &self.certificate
+    }
+
+    pub fn set_expiration(&mut self, expiration: String) {
+        self.expiration = expiration;
+    }
+
+    pub fn with_expiration(mut self, expiration: String) -> Self {
+        self.expiration = expiration;
+        self
+    }
+
+    pub fn expiration(&self) -> &String {
+        &self.expiration
+    }
+
+    pub fn set_renew_at(&mut self, renew_at: String) {
+        self.renew_at = Some(renew_at);
+    }
+
+    pub fn with_renew_at(mut self, renew_at: String) -> Self {
+        self.renew_at = Some(renew_at);
+        self
+    }
+
+    pub fn renew_at(&self) -> Option<&String> {
+        self.renew_at.as_ref()
+    }
+
+    pub fn reset_renew_at(&mut self) {
+        self.renew_at = None;
+    }
+}
@@ -8,9 +8,41 @@
  * Generated by: https://github.com/swagger-api/swagger-codegen.git
  */
 
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
 #[allow(unused_imports)]
 use serde_json::Value;
 
+/// Sign algorithm to be used.
+///
+/// Algorithms the API doesn't know are read as `Unknown` rather than failing
+/// to read the request, the same as when the algorithm was a string, and it is
+/// up to the server to refuse them. `Unknown` can't be sent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignAlgorithm {
+    HMACSHA256,
+    Unknown,
+}
+
+impl Serialize for SignAlgorithm {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            SignAlgorithm::HMACSHA256 => serializer.serialize_str("HMACSHA256"),
+            SignAlgorithm::Unknown => Err(ser::Error::custom("unknown sign algorithm")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SignAlgorithm {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let algo = String::deserialize(deserializer)?;
+        Ok(match algo.as_str() {
+            "HMACSHA256" => SignAlgorithm::HMACSHA256,
+            _ => SignAlgorithm::Unknown,
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SignRequest {
     /// Name of key to perform sign operation.
@@ -18,14 +50,14 @@ pub struct SignRequest {
     key_id: String,
     /// Sign algorithm to be used.
     #[serde(rename = "algo")]
-    algo: String,
+    algo: SignAlgorithm,
     /// Data to be signed.
     #[serde(rename = "data")]
     data: String,
 }
 
 impl SignRequest {
-    pub fn new(key_id: String, algo: String, data: String) -> Self {
+    pub fn new(key_id: String, algo: SignAlgorithm, data: String) -> Self {
         SignRequest { key_id, algo, data }
     }
 
@@ -42,17 +74,17 @@ impl SignRequest {
         &self.key_id
     }
 
-    pub fn set_algo(&mut self, algo: String) {
+    pub fn set_algo(&mut self, algo: SignAlgorithm) {
         self.algo = algo;
     }
 
-    pub fn with_algo(mut self, algo: String) -> Self {
+    pub fn with_algo(mut self, algo: SignAlgorithm) -> Self {
         self.algo = algo;
         self
     }
 
-    pub fn algo(&self) -> &String {
-        &self.algo
+    pub fn algo(&self) -> SignAlgorithm {
+        self.algo
     }
 
     pub fn set_data(&mut self, data: String) {
@@ -68,3 +100,26 @@ impl SignRequest {
         &self.data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn unknown_algorithms_are_read() {
+        let request: SignRequest =
+            serde_json::from_str(r#"{"keyId":"primary","algo":"HMACSHA256","data":"ZGF0YQ=="}"#)
+                .unwrap();
+        assert_eq!(SignAlgorithm::HMACSHA256, request.algo());
+        assert_eq!(
+            r#"{"keyId":"primary","algo":"HMACSHA256","data":"ZGF0YQ=="}"#,
+            serde_json::to_string(&request).unwrap()
+        );
+
+        let request: SignRequest =
+            serde_json::from_str(r#"{"keyId":"primary","algo":"hmac","data":"ZGF0YQ=="}"#).unwrap();
+        assert_eq!(SignAlgorithm::Unknown, request.algo());
+        assert!(serde_json::to_string(&request).is_err());
+    }
+}
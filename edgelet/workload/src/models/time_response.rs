@@ -0,0 +1,61 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeResponse {
+    /// The time of the daemon, corrected by the skew of its clock against IoT Hub.
+    #[serde(rename = "time")]
+    time: String,
+    /// How many seconds the time of IoT Hub is ahead of the clock of the device. Absent until the skew was measured.
+    #[serde(rename = "skewSeconds", skip_serializing_if = "Option::is_none")]
+    skew_seconds: Option<i64>,
+}
+
+impl TimeResponse {
+    pub fn new(time: String) -> Self {
+        TimeResponse {
+            time,
+            skew_seconds: None,
+        }
+    }
+
+    pub fn set_time(&mut self, time: String) {
+        self.time = time;
+    }
+
+    pub fn with_time(mut self, time: String) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn time(&self) -> &String {
+        &self.time
+    }
+
+    pub fn set_skew_seconds(&mut self, skew_seconds: i64) {
+        self.skew_seconds = Some(skew_seconds);
+    }
+
+    pub fn with_skew_seconds(mut self, skew_seconds: i64) -> Self {
+        self.skew_seconds = Some(skew_seconds);
+        self
+    }
+
+    pub fn skew_seconds(&self) -> Option<&i64> {
+        self.skew_seconds.as_ref()
+    }
+
+    pub fn reset_skew_seconds(&mut self) {
+        self.skew_seconds = None;
+    }
+}
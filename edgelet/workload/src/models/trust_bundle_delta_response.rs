@@ -0,0 +1,74 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustBundleDeltaResponse {
+    /// The version of the trust bundle the delta brings the module up to.
+    #[serde(rename = "version")]
+    version: i64,
+    /// The PEM formatted roots added to the trust bundle since the version the module has.
+    #[serde(rename = "added")]
+    added: Vec<String>,
+    /// The PEM formatted roots removed from the trust bundle since the version the module has.
+    #[serde(rename = "removed")]
+    removed: Vec<String>,
+}
+
+impl TrustBundleDeltaResponse {
+    pub fn new(version: i64, added: Vec<String>, removed: Vec<String>) -> Self {
+        TrustBundleDeltaResponse {
+            version,
+            added,
+            removed,
+        }
+    }
+
+    pub fn set_version(&mut self, version: i64) {
+        self.version = version;
+    }
+
+    pub fn with_version(mut self, version: i64) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn version(&self) -> &i64 {
+        &self.version
+    }
+
+    pub fn set_added(&mut self, added: Vec<String>) {
+        self.added = added;
+    }
+
+    pub fn with_added(mut self, added: Vec<String>) -> Self {
+        self.added = added;
+        self
+    }
+
+    pub fn added(&self) -> &Vec<String> {
+        &self.added
+    }
+
+    pub fn set_removed(&mut self, removed: Vec<String>) {
+        self.removed = removed;
+    }
+
+    pub fn with_removed(mut self, removed: Vec<String>) -> Self {
+        self.removed = removed;
+        self
+    }
+
+    pub fn removed(&self) -> &Vec<String> {
+        &self.removed
+    }
+}
@@ -16,11 +16,17 @@ pub struct TrustBundleResponse {
     /// Base64 encoded PEM formatted byte array containing the trusted certificates.
     #[serde(rename = "certificate")]
     certificate: String,
+    /// The version of the trust bundle, which goes up each time its roots change.
+    #[serde(rename = "version", skip_serializing_if = "Option::is_none")]
+    version: Option<i64>,
 }
 
 impl TrustBundleResponse {
     pub fn new(certificate: String) -> Self {
-        TrustBundleResponse { certificate }
+        TrustBundleResponse {
+            certificate,
+            version: None,
+        }
     }
 
     pub fn set_certificate(&mut self, certificate: String) {
@@ -35,4 +41,21 @@ impl TrustBundleResponse {
     pub fn certificate(&self) -> &String {
         &self.certificate
     }
+
+    pub fn set_version(&mut self, version: i64) {
+        self.version = Some(version);
+    }
+
+    pub fn with_version(mut self, version: i64) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn version(&self) -> Option<&i64> {
+        self.version.as_ref()
+    }
+
+    pub fn reset_version(&mut self) {
+        self.version = None;
+    }
 }
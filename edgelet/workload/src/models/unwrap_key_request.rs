@@ -0,0 +1,57 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnwrapKeyRequest {
+    /// The wrapped key to be unwrapped.
+    #[serde(rename = "wrappedKey")]
+    wrapped_key: String,
+    /// The initialization vector the key was wrapped with.
+    #[serde(rename = "initializationVector")]
+    initialization_vector: String,
+}
+
+impl UnwrapKeyRequest {
+    pub fn new(wrapped_key: String, initialization_vector: String) -> Self {
+        UnwrapKeyRequest {
+            wrapped_key,
+            initialization_vector,
+        }
+    }
+
+    pub fn set_wrapped_key(&mut self, wrapped_key: String) {
+        self.wrapped_key = wrapped_key;
+    }
+
+    pub fn with_wrapped_key(mut self, wrapped_key: String) -> Self {
+        self.wrapped_key = wrapped_key;
+        self
+    }
+
+    pub fn wrapped_key(&self) -> &String {
+        &self.wrapped_key
+    }
+
+    pub fn set_initialization_vector(&mut self, initialization_vector: String) {
+        self.initialization_vector = initialization_vector;
+    }
+
+    pub fn with_initialization_vector(mut self, initialization_vector: String) -> Self {
+        self.initialization_vector = initialization_vector;
+        self
+    }
+
+    pub fn initialization_vector(&self) -> &String {
+        &self.initialization_vector
+    }
+}
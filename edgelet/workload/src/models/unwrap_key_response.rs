@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnwrapKeyResponse {
+    /// The unwrapped key encoded in base 64.
+    #[serde(rename = "key")]
+    key: String,
+}
+
+impl UnwrapKeyResponse {
+    pub fn new(key: String) -> Self {
+        UnwrapKeyResponse { key }
+    }
+
+    pub fn set_key(&mut self, key: String) {
+        self.key = key;
+    }
+
+    pub fn with_key(mut self, key: String) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn key(&self) -> &String {
+        &self.key
+    }
+}
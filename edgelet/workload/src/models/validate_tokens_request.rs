@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidateTokensRequest {
+    /// SAS tokens presented by leaf devices and their modules.
+    #[serde(rename = "tokens")]
+    tokens: Vec<String>,
+}
+
+impl ValidateTokensRequest {
+    pub fn new(tokens: Vec<String>) -> Self {
+        ValidateTokensRequest { tokens }
+    }
+
+    pub fn set_tokens(&mut self, tokens: Vec<String>) {
+        self.tokens = tokens;
+    }
+
+    pub fn with_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    pub fn tokens(&self) -> &Vec<String> {
+        &self.tokens
+    }
+}
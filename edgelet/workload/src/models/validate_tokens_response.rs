@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidateTokensResponse {
+    /// The status of each token, in the order of the request.
+    #[serde(rename = "statuses")]
+    statuses: Vec<String>,
+}
+
+impl ValidateTokensResponse {
+    pub fn new(statuses: Vec<String>) -> Self {
+        ValidateTokensResponse { statuses }
+    }
+
+    pub fn set_statuses(&mut self, statuses: Vec<String>) {
+        self.statuses = statuses;
+    }
+
+    pub fn with_statuses(mut self, statuses: Vec<String>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+
+    pub fn statuses(&self) -> &Vec<String> {
+        &self.statuses
+    }
+}
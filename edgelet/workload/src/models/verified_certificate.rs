@@ -0,0 +1,95 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifiedCertificate {
+    /// Common name of the subject of the certificate.
+    #[serde(rename = "commonName", skip_serializing_if = "Option::is_none")]
+    common_name: Option<String>,
+    /// Start of the validity period of the certificate.
+    #[serde(rename = "notBefore")]
+    not_before: String,
+    /// End of the validity period of the certificate.
+    #[serde(rename = "notAfter")]
+    not_after: String,
+    /// Whether the certificate is outside of its validity period.
+    #[serde(rename = "expired")]
+    expired: bool,
+}
+
+impl VerifiedCertificate {
+    pub fn new(not_before: String, not_after: String, expired: bool) -> Self {
+        VerifiedCertificate {
+            common_name: None,
+            not_before,
+            not_after,
+            expired,
+        }
+    }
+
+    pub fn set_common_name(&mut self, common_name: String) {
+        self.common_name = Some(common_name);
+    }
+
+    pub fn with_common_name(mut self, common_name: String) -> Self {
+        self.common_name = Some(common_name);
+        self
+    }
+
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_common_name(&mut self) {
+        self.common_name = None;
+    }
+
+    pub fn set_not_before(&mut self, not_before: String) {
+        self.not_before = not_before;
+    }
+
+    pub fn with_not_before(mut self, not_before: String) -> Self {
+        self.not_before = not_before;
+        self
+    }
+
+    pub fn not_before(&self) -> &String {
+        &self.not_before
+    }
+
+    pub fn set_not_after(&mut self, not_after: String) {
+        self.not_after = not_after;
+    }
+
+    pub fn with_not_after(mut self, not_after: String) -> Self {
+        self.not_after = not_after;
+        self
+    }
+
+    pub fn not_after(&self) -> &String {
+        &self.not_after
+    }
+
+    pub fn set_expired(&mut self, expired: bool) {
+        self.expired = expired;
+    }
+
+    pub fn with_expired(mut self, expired: bool) -> Self {
+        self.expired = expired;
+        self
+    }
+
+    pub fn expired(&self) -> bool {
+        self.expired
+    }
+}
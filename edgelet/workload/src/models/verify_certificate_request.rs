@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyCertificateRequest {
+    /// PEM formatted certificate chain to verify, the leaf certificate first.
+    #[serde(rename = "certificate")]
+    certificate: String,
+}
+
+impl VerifyCertificateRequest {
+    pub fn new(certificate: String) -> Self {
+        VerifyCertificateRequest { certificate }
+    }
+
+    pub fn set_certificate(&mut self, certificate: String) {
+        self.certificate = certificate;
+    }
+
+    pub fn with_certificate(mut self, certificate: String) -> Self {
+        self.certificate = certificate;
+        self
+    }
+
+    pub fn certificate(&self) -> &String {
+        &self.certificate
+    }
+}
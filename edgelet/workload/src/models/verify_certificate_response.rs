@@ -0,0 +1,99 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyCertificateResponse {
+    /// Whether the chain verifies against the trust bundle.
+    #[serde(rename = "valid")]
+    valid: bool,
+    /// Why the chain does not verify.
+    #[serde(rename = "error", skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Position in the chain of the certificate that failed verification, where the leaf is 0.
+    #[serde(rename = "errorDepth", skip_serializing_if = "Option::is_none")]
+    error_depth: Option<i32>,
+    /// The certificates of the chain as they were given, the leaf certificate first.
+    #[serde(rename = "certificates")]
+    certificates: Vec<::models::VerifiedCertificate>,
+}
+
+impl VerifyCertificateResponse {
+    pub fn new(valid: bool, certificates: Vec<::models::VerifiedCertificate>) -> Self {
+        VerifyCertificateResponse {
+            valid,
+            error: None,
+            error_depth: None,
+            certificates,
+        }
+    }
+
+    pub fn set_valid(&mut self, valid: bool) {
+        self.valid = valid;
+    }
+
+    pub fn with_valid(mut self, valid: bool) -> Self {
+        self.valid = valid;
+        self
+    }
+
+    pub fn valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(AsRef::as_ref)
+    }
+
+    pub fn reset_error(&mut self) {
+        self.error = None;
+    }
+
+    pub fn set_error_depth(&mut self, error_depth: i32) {
+        self.error_depth = Some(error_depth);
+    }
+
+    pub fn with_error_depth(mut self, error_depth: i32) -> Self {
+        self.error_depth = Some(error_depth);
+        self
+    }
+
+    pub fn error_depth(&self) -> Option<i32> {
+        self.error_depth
+    }
+
+    pub fn reset_error_depth(&mut self) {
+        self.error_depth = None;
+    }
+
+    pub fn set_certificates(&mut self, certificates: Vec<::models::VerifiedCertificate>) {
+        self.certificates = certificates;
+    }
+
+    pub fn with_certificates(mut self, certificates: Vec<::models::VerifiedCertificate>) -> Self {
+        self.certificates = certificates;
+        self
+    }
+
+    pub fn certificates(&self) -> &Vec<::models::VerifiedCertificate> {
+        &self.certificates
+    }
+}
@@ -0,0 +1,57 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WrapKeyRequest {
+    /// The symmetric key to be wrapped.
+    #[serde(rename = "key")]
+    key: String,
+    /// An initialization vector used to wrap the key.
+    #[serde(rename = "initializationVector")]
+    initialization_vector: String,
+}
+
+impl WrapKeyRequest {
+    pub fn new(key: String, initialization_vector: String) -> Self {
+        WrapKeyRequest {
+            key,
+            initialization_vector,
+        }
+    }
+
+    pub fn set_key(&mut self, key: String) {
+        self.key = key;
+    }
+
+    pub fn with_key(mut self, key: String) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn key(&self) -> &String {
+        &self.key
+    }
+
+    pub fn set_initialization_vector(&mut self, initialization_vector: String) {
+        self.initialization_vector = initialization_vector;
+    }
+
+    pub fn with_initialization_vector(mut self, initialization_vector: String) -> Self {
+        self.initialization_vector = initialization_vector;
+        self
+    }
+
+    pub fn initialization_vector(&self) -> &String {
+        &self.initialization_vector
+    }
+}
@@ -0,0 +1,38 @@
+/*
+ * IoT Edge Module Workload API
+ *
+ * No description provided (generated by Swagger Codegen https://github.com/swagger-api/swagger-codegen)
+ *
+ * OpenAPI spec version: 2018-06-28
+ *
+ * Generated by: https://github.com/swagger-api/swagger-codegen.git
+ */
+
+#[allow(unused_imports)]
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WrapKeyResponse {
+    /// The wrapped form of the key encoded in base 64.
+    #[serde(rename = "wrappedKey")]
+    wrapped_key: String,
+}
+
+impl WrapKeyResponse {
+    pub fn new(wrapped_key: String) -> Self {
+        WrapKeyResponse { wrapped_key }
+    }
+
+    pub fn set_wrapped_key(&mut self, wrapped_key: String) {
+        self.wrapped_key = wrapped_key;
+    }
+
+    pub fn with_wrapped_key(mut self, wrapped_key: String) -> Self {
+        self.wrapped_key = wrapped_key;
+        self
+    }
+
+    pub fn wrapped_key(&self) -> &String {
+        &self.wrapped_key
+    }
+}